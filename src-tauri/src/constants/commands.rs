@@ -7,22 +7,82 @@ use ts_rs::TS;
 pub struct TauriCommands;
 
 impl TauriCommands {
+    // Event catalog
+    pub const GET_EVENT_SCHEMA: &'static str = "get_event_schema";
+
     // Workspace commands
     pub const CREATE_WORKSPACE: &'static str = "create_workspace";
     pub const GET_WORKSPACES: &'static str = "get_workspaces";
     pub const UPDATE_WORKSPACE: &'static str = "update_workspace";
     pub const DELETE_WORKSPACE: &'static str = "delete_workspace";
+    pub const CHECK_WORKSPACE_READINESS: &'static str = "check_workspace_readiness";
+    pub const GET_WORKSPACE_NOTES: &'static str = "get_workspace_notes";
+    pub const SAVE_WORKSPACE_NOTES: &'static str = "save_workspace_notes";
+    pub const EXPORT_WORKSPACE_ARCHIVE: &'static str = "export_workspace_archive";
+    pub const IMPORT_WORKSPACE_ARCHIVE: &'static str = "import_workspace_archive";
 
     // Chat commands
     pub const CREATE_CHAT: &'static str = "create_chat";
     pub const GET_CHATS: &'static str = "get_chats";
+    pub const MARK_CHAT_READ: &'static str = "mark_chat_read";
     pub const UPDATE_CHAT: &'static str = "update_chat";
     pub const DELETE_CHAT: &'static str = "delete_chat";
     pub const DELETE_ALL_CHATS_BY_WORKSPACE: &'static str = "delete_all_chats_by_workspace";
     pub const SEND_MESSAGE: &'static str = "send_message";
+    pub const SEND_MESSAGE_COMPARE: &'static str = "send_message_compare";
     pub const EDIT_AND_RESEND_MESSAGE: &'static str = "edit_and_resend_message";
     pub const RESPOND_TOOL_PERMISSION: &'static str = "respond_tool_permission";
+    pub const LIST_REMEMBERED_TOOL_PERMISSIONS: &'static str = "list_remembered_tool_permissions";
+    pub const REVOKE_REMEMBERED_TOOL_PERMISSION: &'static str = "revoke_remembered_tool_permission";
+    pub const RESPOND_COST_CONFIRMATION: &'static str = "respond_cost_confirmation";
     pub const GENERATE_CHAT_TITLE: &'static str = "generate_chat_title";
+    pub const SUMMARIZE_CHAT: &'static str = "summarize_chat";
+    pub const RUN_PROMPT_ONESHOT: &'static str = "run_prompt_oneshot";
+    pub const NOTIFY_WORKSPACE_ACTIVE: &'static str = "notify_workspace_active";
+    pub const GET_CHAT_SCRATCHPAD: &'static str = "get_chat_scratchpad";
+    pub const SET_CHAT_SCRATCHPAD: &'static str = "set_chat_scratchpad";
+    pub const GET_CHAT_INSTRUCTIONS: &'static str = "get_chat_instructions";
+    pub const SET_CHAT_INSTRUCTIONS: &'static str = "set_chat_instructions";
+    pub const GET_CHAT_BOOT_DATA: &'static str = "get_chat_boot_data";
+    pub const GET_TURN_TIMELINE: &'static str = "get_turn_timeline";
+    pub const GET_ACTIVE_CHAT_STATUS: &'static str = "get_active_chat_status";
+    pub const GET_MESSAGE_PERMALINK: &'static str = "get_message_permalink";
+    pub const OPEN_PERMALINK: &'static str = "open_permalink";
+    pub const TAKE_PENDING_NAVIGATION: &'static str = "take_pending_navigation";
+    pub const EXPORT_CHAT: &'static str = "export_chat";
+    pub const EXPORT_WORKSPACE: &'static str = "export_workspace";
+    pub const LIST_BRANCHES: &'static str = "list_branches";
+    pub const CREATE_BRANCH: &'static str = "create_branch";
+    pub const SWITCH_BRANCH: &'static str = "switch_branch";
+    pub const REGENERATE_MESSAGE: &'static str = "regenerate_message";
+    pub const SELECT_MESSAGE_VARIANT: &'static str = "select_message_variant";
+
+    // Activity commands
+    pub const GET_ACTIVITY: &'static str = "get_activity";
+    pub const MARK_ACTIVITY_READ: &'static str = "mark_activity_read";
+
+    // LLM Connection commands
+    pub const GET_CONNECTION_RATE_STATUS: &'static str = "get_connection_rate_status";
+    pub const EMBED_TEXTS: &'static str = "embed_texts";
+    pub const GET_OLLAMA_MODEL_INFO: &'static str = "get_ollama_model_info";
+    pub const PULL_OLLAMA_MODEL: &'static str = "pull_ollama_model";
+
+    // Semantic index commands
+    pub const SEMANTIC_SEARCH_MESSAGES: &'static str = "semantic_search_messages";
+    pub const BACKFILL_SEMANTIC_INDEX: &'static str = "backfill_semantic_index";
+
+    // Knowledge commands
+    pub const INDEX_DOCUMENT: &'static str = "index_document";
+    pub const LIST_KNOWLEDGE_DOCUMENTS: &'static str = "list_knowledge_documents";
+    pub const SEARCH_KNOWLEDGE: &'static str = "search_knowledge";
+    pub const DELETE_DOCUMENT: &'static str = "delete_document";
+
+    // Search commands
+    pub const SEARCH_MESSAGES: &'static str = "search_messages";
+    pub const SEARCH_CHATS: &'static str = "search_chats";
+
+    // Tool catalog commands
+    pub const GET_TOOL_CATALOG_HISTORY: &'static str = "get_tool_catalog_history";
 
     // Message commands
     pub const CREATE_MESSAGE: &'static str = "create_message";
@@ -31,6 +91,7 @@ impl TauriCommands {
     pub const DELETE_MESSAGE: &'static str = "delete_message";
     pub const DELETE_MESSAGES_AFTER: &'static str = "delete_messages_after";
     pub const CANCEL_MESSAGE: &'static str = "cancel_message";
+    pub const DIFF_MESSAGES: &'static str = "diff_messages";
 
     // MCP Server commands
     pub const CREATE_MCP_SERVER: &'static str = "create_mcp_server";
@@ -44,17 +105,44 @@ impl TauriCommands {
     pub const UPDATE_MCP_SERVER_CONNECTION: &'static str = "update_mcp_server_connection";
     pub const DELETE_MCP_SERVER_CONNECTION: &'static str = "delete_mcp_server_connection";
     pub const UPDATE_MCP_SERVER_STATUS: &'static str = "update_mcp_server_status";
+    pub const SET_MCP_SERVER_AUTO_CONNECT: &'static str = "set_mcp_server_auto_connect";
+    pub const VALIDATE_MCP_CONNECTION_CONFIG: &'static str = "validate_mcp_connection_config";
 
     // App Settings commands
     pub const SAVE_APP_SETTING: &'static str = "save_app_setting";
     pub const GET_APP_SETTING: &'static str = "get_app_setting";
     pub const GET_ALL_APP_SETTINGS: &'static str = "get_all_app_settings";
+    pub const GET_TIME_SETTINGS: &'static str = "get_time_settings";
+    pub const SAVE_TIME_SETTINGS: &'static str = "save_time_settings";
+    pub const GET_BUILTIN_TOOL_SETTINGS: &'static str = "get_builtin_tool_settings";
+    pub const SAVE_BUILTIN_TOOL_SETTINGS: &'static str = "save_builtin_tool_settings";
 
     // Prompt commands
     pub const CREATE_PROMPT: &'static str = "create_prompt";
     pub const GET_PROMPTS: &'static str = "get_prompts";
     pub const UPDATE_PROMPT: &'static str = "update_prompt";
     pub const DELETE_PROMPT: &'static str = "delete_prompt";
+    pub const EXPORT_PROMPTS: &'static str = "export_prompts";
+    pub const IMPORT_PROMPTS: &'static str = "import_prompts";
+    pub const CREATE_PROMPT_FROM_MESSAGES: &'static str = "create_prompt_from_messages";
+
+    // Quick action commands
+    pub const CREATE_QUICK_ACTION: &'static str = "create_quick_action";
+    pub const GET_QUICK_ACTIONS: &'static str = "get_quick_actions";
+    pub const UPDATE_QUICK_ACTION: &'static str = "update_quick_action";
+    pub const DELETE_QUICK_ACTION: &'static str = "delete_quick_action";
+    pub const RUN_QUICK_ACTION: &'static str = "run_quick_action";
+
+    // Webhook commands
+    pub const CREATE_WEBHOOK: &'static str = "create_webhook";
+    pub const GET_WEBHOOKS: &'static str = "get_webhooks";
+    pub const UPDATE_WEBHOOK: &'static str = "update_webhook";
+    pub const DELETE_WEBHOOK: &'static str = "delete_webhook";
+
+    // Outbox commands
+    pub const GET_OUTBOX: &'static str = "get_outbox";
+    pub const RETRY_OUTBOX: &'static str = "retry_outbox";
+    pub const DISCARD_OUTBOX_ITEM: &'static str = "discard_outbox_item";
 
     // Hub commands
     pub const FETCH_HUB_PROMPTS: &'static str = "fetch_hub_prompts";
@@ -63,6 +151,9 @@ impl TauriCommands {
     pub const FETCH_HUB_MCP_SERVERS: &'static str = "fetch_hub_mcp_servers";
     pub const INSTALL_MCP_SERVER_FROM_HUB: &'static str = "install_mcp_server_from_hub";
     pub const REFRESH_HUB_INDEX: &'static str = "refresh_hub_index";
+    pub const FETCH_HUB_PACKS: &'static str = "fetch_hub_packs";
+    pub const INSTALL_PACK_FROM_HUB: &'static str = "install_pack_from_hub";
+    pub const UNINSTALL_PACK_FROM_HUB: &'static str = "uninstall_pack_from_hub";
 
     // MCP Tools commands
     pub const TEST_MCP_CONNECTION_AND_FETCH_TOOLS: &'static str =
@@ -73,6 +164,16 @@ impl TauriCommands {
     pub const CALL_MCP_TOOL: &'static str = "call_mcp_tool";
     pub const DISCONNECT_MCP_CLIENT: &'static str = "disconnect_mcp_client";
     pub const GET_ACTIVE_TOOLS_FOR_WORKSPACE: &'static str = "get_active_tools_for_workspace";
+    pub const GET_WORKSPACE_TOOLS: &'static str = "get_workspace_tools";
+    pub const SET_WORKSPACE_TOOL_OVERRIDE: &'static str = "set_workspace_tool_override";
+    pub const LIST_MCP_RESOURCES: &'static str = "list_mcp_resources";
+    pub const LIST_MCP_RESOURCE_TEMPLATES: &'static str = "list_mcp_resource_templates";
+    pub const READ_MCP_RESOURCE: &'static str = "read_mcp_resource";
+    pub const ATTACH_MCP_RESOURCE: &'static str = "attach_mcp_resource";
+    pub const LIST_MCP_PROMPTS: &'static str = "list_mcp_prompts";
+    pub const GET_MCP_PROMPT: &'static str = "get_mcp_prompt";
+    pub const APPLY_MCP_PROMPT_TO_CHAT: &'static str = "apply_mcp_prompt_to_chat";
+    pub const RESPOND_MCP_SAMPLING: &'static str = "respond_mcp_sampling";
 
     // Python commands
     pub const GET_PYTHON_RUNTIMES_STATUS: &'static str = "get_python_runtimes_status";
@@ -95,6 +196,9 @@ impl TauriCommands {
     pub const DELETE_AGENT: &'static str = "delete_agent";
     pub const GET_AGENT_INFO: &'static str = "get_agent_info";
     pub const UPDATE_AGENT: &'static str = "update_agent";
+    pub const SET_AGENT_TOOL_PERMISSIONS: &'static str = "set_agent_tool_permissions";
+    pub const EXPORT_AGENT: &'static str = "export_agent";
+    pub const IMPORT_AGENT: &'static str = "import_agent";
     pub const GET_OR_CREATE_SPECIALIST_SESSION: &'static str = "get_or_create_specialist_session";
 
     // Note commands
@@ -102,4 +206,39 @@ impl TauriCommands {
     pub const GET_NOTES: &'static str = "get_notes";
     pub const UPDATE_NOTE: &'static str = "update_note";
     pub const DELETE_NOTE: &'static str = "delete_note";
+
+    // Attachment commands
+    pub const APPROVE_FILE_ACCESS: &'static str = "approve_file_access";
+    pub const VERIFY_ATTACHMENTS: &'static str = "verify_attachments";
+    pub const CANCEL_VERIFY_ATTACHMENTS: &'static str = "cancel_verify_attachments";
+    pub const RESOLVE_LUNEX_FILE: &'static str = "resolve_lunex_file";
+    pub const EMBED_LUNEX_FILES: &'static str = "embed_lunex_files";
+    pub const EXPORT_LUNEX_FILES: &'static str = "export_lunex_files";
+
+    // LLM response cache commands
+    pub const GET_LLM_CACHE_STATS: &'static str = "get_llm_cache_stats";
+    pub const CLEAR_LLM_CACHE: &'static str = "clear_llm_cache";
+
+    // Encryption and system commands
+    pub const ENABLE_ENCRYPTION: &'static str = "enable_encryption";
+    pub const DISABLE_ENCRYPTION: &'static str = "disable_encryption";
+    pub const GET_SYSTEM_STATUS: &'static str = "get_system_status";
+    pub const RUN_DB_MAINTENANCE: &'static str = "run_db_maintenance";
+    pub const GET_DB_INFO: &'static str = "get_db_info";
+    pub const PANIC_STOP_ALL: &'static str = "panic_stop_all";
+    pub const RESUME_ACTIVITY: &'static str = "resume_activity";
+
+    // Backup commands
+    pub const BACKUP_NOW: &'static str = "backup_now";
+    pub const LIST_BACKUPS: &'static str = "list_backups";
+    pub const RESTORE_BACKUP: &'static str = "restore_backup";
+
+    // Chat share commands
+    pub const START_CHAT_SHARE: &'static str = "start_chat_share";
+    pub const STOP_CHAT_SHARE: &'static str = "stop_chat_share";
+
+    // Chat import commands
+    pub const IMPORT_EXTERNAL_CHATS: &'static str = "import_external_chats";
+    pub const IMPORT_CHAT: &'static str = "import_chat";
+    pub const CANCEL_CHAT_IMPORT: &'static str = "cancel_chat_import";
 }
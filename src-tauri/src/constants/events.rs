@@ -15,6 +15,10 @@ impl TauriEvents {
     pub const MESSAGE_ERROR: &'static str = "message-error";
     pub const MESSAGE_CANCELLED: &'static str = "message-cancelled";
     pub const MESSAGE_METADATA_UPDATED: &'static str = "message-metadata-updated";
+    pub const OUTPUT_LIMIT_REACHED: &'static str = "output-limit-reached";
+    pub const MESSAGE_STREAM_STATS: &'static str = "message-stream-stats";
+    pub const MESSAGE_VARIANTS_UPDATED: &'static str = "message-variants-updated";
+    pub const CONTEXT_TRIMMED: &'static str = "context-trimmed";
 
     // Tool call events
     pub const TOOL_CALL_REQUEST: &'static str = "tool-call-request";
@@ -26,9 +30,15 @@ impl TauriEvents {
     pub const TOOL_EXECUTION_COMPLETED: &'static str = "tool-execution-completed";
     pub const TOOL_EXECUTION_ERROR: &'static str = "tool-execution-error";
     pub const TOOL_PERMISSION_REQUEST: &'static str = "tool-permission-request";
+    pub const COST_CONFIRMATION_REQUEST: &'static str = "cost-confirmation-request";
+    pub const SCRATCHPAD_UPDATED: &'static str = "scratchpad-updated";
+
+    // Workspace events
+    pub const WORKSPACE_NOTES_UPDATED: &'static str = "workspace-notes-updated";
 
     // Agent events
     pub const AGENT_LOOP_ITERATION: &'static str = "agent-loop-iteration";
+    pub const AGENT_LOOP_WARNING: &'static str = "agent-loop-warning";
 
     // Menu events
     pub const MENU_NEW_CHAT: &'static str = "menu-new-chat";
@@ -47,4 +57,36 @@ impl TauriEvents {
 
     // Chat events
     pub const CHAT_UPDATED: &'static str = "chat-updated";
+    pub const NAVIGATE_TO_MESSAGE: &'static str = "navigate-to-message";
+    pub const ACTIVE_CHAT_STATUS_CHANGED: &'static str = "active-chat-status-changed";
+    pub const OUTBOX_STATUS: &'static str = "outbox-status";
+
+    // Activity events
+    pub const ACTIVITY_ADDED: &'static str = "activity-added";
+
+    // Model events
+    pub const MODEL_CAPABILITY_WARNING: &'static str = "model-capability-warning";
+
+    // Quick action events
+    pub const QUICK_ACTION: &'static str = "quick-action";
+
+    // Encryption events
+    pub const ENCRYPTION_PROGRESS: &'static str = "encryption-progress";
+
+    // Chat import events
+    pub const CHAT_IMPORT_PROGRESS: &'static str = "chat-import-progress";
+
+    // Semantic index events
+    pub const SEMANTIC_INDEX_PROGRESS: &'static str = "semantic-index-progress";
+
+    // Kill switch events
+    pub const GLOBAL_HALT: &'static str = "global-halt";
+
+    // Local model events
+    pub const OLLAMA_PULL_PROGRESS: &'static str = "ollama-pull-progress";
+
+    // MCP events
+    pub const MCP_SAMPLING_REQUEST: &'static str = "mcp-sampling-request";
+    pub const MCP_SERVER_STATUS: &'static str = "mcp-server-status";
+    pub const MCP_AUTO_CONNECT_PROGRESS: &'static str = "mcp-auto-connect-progress";
 }
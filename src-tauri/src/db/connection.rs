@@ -1,7 +1,15 @@
 use rusqlite::{Connection, Result};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 use tauri::Manager;
 
+/// How long a statement waits for another connection's write lock (a turn
+/// saving messages, a maintenance step) before failing with `SQLITE_BUSY`.
+pub const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Name of the database file in the app data directory.
+pub const DATABASE_FILE: &str = "database.db";
+
 pub fn get_db_path(app: &tauri::AppHandle) -> Result<PathBuf> {
     let app_data_dir = match app.path().app_data_dir() {
         Ok(dir) => dir,
@@ -21,23 +29,74 @@ pub fn get_db_path(app: &tauri::AppHandle) -> Result<PathBuf> {
             ))
         }
     }
-    Ok(app_data_dir.join("database.db"))
+    Ok(app_data_dir.join(DATABASE_FILE))
 }
 
 pub fn get_connection(app: &tauri::AppHandle) -> Result<Connection> {
     let db_path = get_db_path(app)?;
-    Connection::open(db_path)
+    open_connection(&db_path)
+}
+
+pub fn open_connection(path: &Path) -> Result<Connection> {
+    let conn = Connection::open(path)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+/// Database-wide settings, applied before migrations. WAL lets reads go on
+/// while a turn or a maintenance step writes; incremental auto-vacuum only
+/// takes effect on a database without tables yet, older ones switch on
+/// their first full `VACUUM`.
+pub fn configure_database(conn: &Connection) -> Result<()> {
+    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    conn.execute("PRAGMA auto_vacuum = INCREMENTAL", [])?;
+    conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+        row.get::<_, String>(0)
+    })?;
+    Ok(())
 }
 
 pub fn init_db(app: &tauri::AppHandle) -> Result<Connection> {
     let db_path = get_db_path(app)?;
-    let conn = Connection::open(db_path)?;
+    let conn = open_connection(&db_path)?;
 
-    // Enable foreign keys
-    conn.execute("PRAGMA foreign_keys = ON", [])?;
+    configure_database(&conn)?;
 
+    backup_before_migrations(&conn, &db_path);
     // Run migrations
     crate::db::migrations::run_migrations(&conn)?;
 
     Ok(conn)
 }
+
+/// Snapshot a database that holds data before migrations change it. A
+/// failed snapshot is logged and does not hold the upgrade back.
+fn backup_before_migrations(conn: &Connection, db_path: &Path) {
+    use crate::features::backup::archive::{create_backup, BACKUPS_DIR, FILES_DIR};
+    use crate::features::backup::BackupReason;
+
+    let has_data = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'workspaces')",
+            [],
+            |row| row.get::<_, bool>(0),
+        )
+        .unwrap_or(false);
+    let migrating =
+        crate::db::migrations::pending_migrations(conn).is_ok_and(|pending| !pending.is_empty());
+    let Some(data_dir) = db_path.parent() else {
+        return;
+    };
+    if !has_data || !migrating {
+        return;
+    }
+    match create_backup(
+        db_path,
+        &data_dir.join(FILES_DIR),
+        &data_dir.join(BACKUPS_DIR),
+        BackupReason::PreMigration,
+    ) {
+        Ok(backup) => tracing::info!(id = %backup.id, "Backed up the database before migrating it"),
+        Err(e) => tracing::error!(error = %e, "Failed to back up the database before migrating it"),
+    }
+}
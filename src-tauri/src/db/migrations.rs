@@ -1,6 +1,199 @@
-use rusqlite::{Connection, Result};
+//! Versioned schema migrations.
+//!
+//! Each migration runs once, in version order and in a transaction of its
+//! own, and is recorded in `schema_version` with a checksum of its SQL.
+//! Migrations are forward-only: add a new one at the end of `MIGRATIONS`
+//! and never edit one that has shipped, or databases that ran it refuse to
+//! open. Migration 1 is the schema as it was built before versioning; it is
+//! idempotent so databases of any earlier release can run it.
 
+use crate::features::encryption::ENCRYPTED_PREFIX;
+use rusqlite::{params, Connection, Result};
+use sha2::{Digest, Sha256};
+
+pub enum MigrationStep {
+    Sql(&'static str),
+    /// For changes SQL alone cannot make, such as rewriting values
+    Rust(fn(&Connection) -> Result<()>),
+}
+
+pub struct Migration {
+    pub version: u32,
+    pub name: &'static str,
+    pub step: MigrationStep,
+}
+
+impl Migration {
+    /// What `schema_version` keeps to notice a migration changed after it
+    /// ran: the SQL for SQL steps, the name for Rust ones.
+    pub fn checksum(&self) -> String {
+        let source = match self.step {
+            MigrationStep::Sql(sql) => sql,
+            MigrationStep::Rust(_) => self.name,
+        };
+        hex::encode(Sha256::digest(source.as_bytes()))
+    }
+}
+
+/// Every migration, by version from 1 with no gaps.
+pub static MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "baseline",
+        step: MigrationStep::Rust(baseline),
+    },
+    Migration {
+        version: 2,
+        name: "mcp_connection_auth_token",
+        step: MigrationStep::Sql("ALTER TABLE mcp_server_connections ADD COLUMN auth_token TEXT;"),
+    },
+    Migration {
+        version: 3,
+        name: "mcp_connection_auto_connect",
+        step: MigrationStep::Sql(
+            "ALTER TABLE mcp_server_connections ADD COLUMN auto_connect INTEGER NOT NULL DEFAULT 0;",
+        ),
+    },
+    Migration {
+        version: 4,
+        name: "workspace_tool_overrides",
+        step: MigrationStep::Sql(
+            "CREATE TABLE workspace_tool_overrides (
+                workspace_id TEXT NOT NULL,
+                tool_name TEXT NOT NULL,
+                disabled INTEGER NOT NULL DEFAULT 0,
+                alias TEXT,
+                description TEXT,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (workspace_id, tool_name),
+                FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+            );",
+        ),
+    },
+];
+
+/// A migration recorded in `schema_version`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppliedMigration {
+    pub version: u32,
+    pub name: String,
+    pub checksum: String,
+    pub applied_at: i64,
+}
+
+pub fn latest_version() -> u32 {
+    MIGRATIONS.last().map_or(0, |m| m.version)
+}
+
+/// Bring the schema up to date, after checking the migrations that already
+/// ran against `MIGRATIONS`.
 pub fn run_migrations(conn: &Connection) -> Result<()> {
+    for migration in pending_migrations(conn)? {
+        let tx = conn.unchecked_transaction()?;
+        match migration.step {
+            MigrationStep::Sql(sql) => tx.execute_batch(sql)?,
+            MigrationStep::Rust(run) => run(&tx)?,
+        }
+        tx.execute(
+            "INSERT INTO schema_version (version, name, checksum, applied_at) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                migration.version,
+                migration.name,
+                migration.checksum(),
+                chrono::Utc::now().timestamp_millis()
+            ],
+        )?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+/// Migrations recorded in `schema_version`, by version. Creates the table
+/// on a database that has none.
+pub fn applied_migrations(conn: &Connection) -> Result<Vec<AppliedMigration>> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS schema_version (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            checksum TEXT NOT NULL,
+            applied_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    let mut stmt = conn.prepare(
+        "SELECT version, name, checksum, applied_at FROM schema_version ORDER BY version",
+    )?;
+    let applied = stmt
+        .query_map([], |row| {
+            Ok(AppliedMigration {
+                version: row.get(0)?,
+                name: row.get(1)?,
+                checksum: row.get(2)?,
+                applied_at: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>>>()?;
+    Ok(applied)
+}
+
+/// Migrations still to run, in order. Fails when a recorded migration is
+/// unknown to this build, was changed since it ran, or a newer release
+/// migrated the database.
+pub fn pending_migrations(conn: &Connection) -> Result<Vec<&'static Migration>> {
+    check_order(MIGRATIONS)?;
+    let applied = applied_migrations(conn)?;
+    check_applied(MIGRATIONS, &applied)?;
+    Ok(MIGRATIONS.iter().skip(applied.len()).collect())
+}
+
+/// Versions must run from 1 with no gaps.
+pub fn check_order(migrations: &[Migration]) -> Result<()> {
+    for (expected, migration) in (1..).zip(migrations) {
+        if migration.version != expected {
+            return Err(migration_error(format!(
+                "Migration '{}' has version {}, expected {expected}",
+                migration.name, migration.version
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// `applied` must be the first migrations of `migrations`, unchanged.
+pub fn check_applied(migrations: &[Migration], applied: &[AppliedMigration]) -> Result<()> {
+    for (expected, recorded) in (1..).zip(applied) {
+        let Some(migration) = migrations.iter().find(|m| m.version == recorded.version) else {
+            return Err(migration_error(format!(
+                "The database was migrated to version {} by a newer release; this one knows up to version {}",
+                recorded.version,
+                migrations.last().map_or(0, |m| m.version)
+            )));
+        };
+        if recorded.version != expected {
+            return Err(migration_error(format!(
+                "Migration {expected} is missing from the database although {} ran",
+                recorded.version
+            )));
+        }
+        if recorded.name != migration.name || recorded.checksum != migration.checksum() {
+            return Err(migration_error(format!(
+                "Migration {} ('{}') changed since it ran as '{}'",
+                recorded.version, migration.name, recorded.name
+            )));
+        }
+    }
+    Ok(())
+}
+
+fn migration_error(message: String) -> rusqlite::Error {
+    rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_ERROR),
+        Some(message),
+    )
+}
+
+/// The schema built before migrations were versioned.
+fn baseline(conn: &Connection) -> Result<()> {
     // Create workspaces table
     conn.execute(
         "CREATE TABLE IF NOT EXISTS workspaces (
@@ -346,5 +539,714 @@ pub fn run_migrations(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Create file_hashes table (content-addressed attachments)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS file_hashes (
+            hash TEXT PRIMARY KEY,
+            path TEXT NOT NULL,
+            size INTEGER NOT NULL,
+            ref_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_file_hashes_path ON file_hashes(path)",
+        [],
+    )?;
+
+    // Create remote_file_uploads table (provider-side copies of attachments)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS remote_file_uploads (
+            hash TEXT NOT NULL,
+            provider_key TEXT NOT NULL,
+            file_uri TEXT NOT NULL,
+            file_name TEXT NOT NULL,
+            expires_at INTEGER NOT NULL,
+            PRIMARY KEY (hash, provider_key)
+        )",
+        [],
+    )?;
+
+    // Create context_caches table (provider-side cached prompt prefixes)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS context_caches (
+            scope_id TEXT NOT NULL,
+            provider_key TEXT NOT NULL,
+            prefix_hash TEXT NOT NULL,
+            cache_name TEXT NOT NULL,
+            model TEXT NOT NULL,
+            expires_at INTEGER NOT NULL,
+            PRIMARY KEY (scope_id, provider_key, prefix_hash)
+        )",
+        [],
+    )?;
+
+    // Add context_cache_enabled column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN context_cache_enabled INTEGER",
+        [],
+    )
+    .ok();
+
+    // Add cached_tokens column to usage_stats if it doesn't exist
+    conn.execute(
+        "ALTER TABLE usage_stats ADD COLUMN cached_tokens INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Create tool_usage_stats table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tool_usage_stats (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            chat_id TEXT NOT NULL,
+            message_id TEXT NOT NULL,
+            tool_name TEXT NOT NULL,
+            connection_id TEXT,
+            status TEXT NOT NULL,
+            duration_ms INTEGER NOT NULL DEFAULT 0,
+            result_bytes INTEGER NOT NULL DEFAULT 0,
+            timestamp INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Create indexes for tool_usage_stats
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_usage_stats_workspace_timestamp ON tool_usage_stats(workspace_id, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_usage_stats_timestamp ON tool_usage_stats(timestamp)",
+        [],
+    )?;
+
+    // Create llm_response_cache table (reusable utility-call responses)
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS llm_response_cache (
+            cache_key TEXT PRIMARY KEY,
+            provider TEXT NOT NULL,
+            model TEXT NOT NULL,
+            response TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            last_accessed_at INTEGER NOT NULL,
+            expires_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_llm_response_cache_last_accessed ON llm_response_cache(last_accessed_at)",
+        [],
+    )?;
+
+    // Add is_cached column to usage_stats if it doesn't exist
+    conn.execute(
+        "ALTER TABLE usage_stats ADD COLUMN is_cached INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Add keep_warm column to llm_connections if it doesn't exist
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN keep_warm INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Add scratchpad column to chats if it doesn't exist
+    conn.execute("ALTER TABLE chats ADD COLUMN scratchpad TEXT", [])
+        .ok();
+
+    // Add folder and provenance columns to prompts if they don't exist
+    conn.execute("ALTER TABLE prompts ADD COLUMN folder TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE prompts ADD COLUMN source TEXT", [])
+        .ok();
+
+    // Add system_prompt_suffix column to llm_connections if it doesn't exist
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN system_prompt_suffix TEXT",
+        [],
+    )
+    .ok();
+
+    // Add PII redaction columns if they don't exist
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN skip_redaction INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN redaction_config TEXT",
+        [],
+    )
+    .ok();
+
+    // Create activity_log table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS activity_log (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            chat_id TEXT NOT NULL,
+            kind TEXT NOT NULL,
+            summary TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            read INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_activity_log_workspace_created ON activity_log(workspace_id, created_at)",
+        [],
+    )?;
+
+    // Add model preference columns to prompts if they don't exist
+    conn.execute("ALTER TABLE prompts ADD COLUMN preferred_provider TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE prompts ADD COLUMN preferred_model TEXT", [])
+        .ok();
+    conn.execute(
+        "ALTER TABLE prompts ADD COLUMN required_capabilities TEXT",
+        [],
+    )
+    .ok();
+
+    // Add secret_masking_enabled column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN secret_masking_enabled INTEGER",
+        [],
+    )
+    .ok();
+
+    // Add paste_dedup_enabled column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN paste_dedup_enabled INTEGER DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Hub entries listed by each installed pack
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS hub_pack_items (
+            pack_id TEXT NOT NULL,
+            item_kind TEXT NOT NULL,
+            item_id TEXT NOT NULL,
+            owned INTEGER NOT NULL DEFAULT 0,
+            installed_at INTEGER NOT NULL,
+            PRIMARY KEY (pack_id, item_kind, item_id)
+        )",
+        [],
+    )?;
+
+    // Add post_processing_config column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN post_processing_config TEXT",
+        [],
+    )
+    .ok();
+
+    // Add model allow/block list columns to llm_connections if they don't exist
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN model_allowlist TEXT",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN model_blocklist TEXT",
+        [],
+    )
+    .ok();
+
+    // Create quick_actions table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quick_actions (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            name TEXT NOT NULL,
+            prompt_template TEXT NOT NULL,
+            preferred_model TEXT,
+            output_mode TEXT NOT NULL,
+            shortcut TEXT,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Usage is purged per chat when a chat is deleted
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_usage_stats_chat_id ON usage_stats(chat_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_usage_stats_chat_id ON tool_usage_stats(chat_id)",
+        [],
+    )?;
+
+    conn.execute("ALTER TABLE llm_connections ADD COLUMN config TEXT", [])
+        .ok();
+
+    // Create webhooks table
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS webhooks (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            url TEXT NOT NULL,
+            secret TEXT NOT NULL,
+            enabled INTEGER NOT NULL DEFAULT 1,
+            event_kinds TEXT NOT NULL,
+            include_content INTEGER NOT NULL DEFAULT 0,
+            last_status INTEGER,
+            last_error TEXT,
+            last_delivery_at INTEGER,
+            failure_count INTEGER NOT NULL DEFAULT 0,
+            created_at INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_webhooks_workspace_id ON webhooks(workspace_id)",
+        [],
+    )?;
+
+    // Add cost confirmation columns to workspace_settings if they don't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN confirm_above_estimated_cost REAL",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN assumed_completion_tokens INTEGER",
+        [],
+    )
+    .ok();
+
+    // Add tool call cap columns to workspace_settings if they don't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN max_tool_calls_per_turn INTEGER",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN tool_call_caps TEXT",
+        [],
+    )
+    .ok();
+
+    // Add metadata column to chats if it doesn't exist
+    conn.execute("ALTER TABLE chats ADD COLUMN metadata TEXT", [])
+        .ok();
+
+    // Add last_read_at column to chats if it doesn't exist
+    conn.execute("ALTER TABLE chats ADD COLUMN last_read_at INTEGER", [])
+        .ok();
+
+    // Create tool_catalog_history table: what changed in an MCP server's tools
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tool_catalog_history (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            connection_id TEXT NOT NULL,
+            added TEXT NOT NULL,
+            removed TEXT NOT NULL,
+            changed TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_tool_catalog_history_workspace_created ON tool_catalog_history(workspace_id, created_at)",
+        [],
+    )?;
+
+    // Create tool_catalog_snapshots table: the tools last fetched per MCP server
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tool_catalog_snapshots (
+            connection_id TEXT PRIMARY KEY,
+            tools_json TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (connection_id) REFERENCES mcp_server_connections(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Create message_embeddings table: vectors of the semantic index
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_embeddings (
+            message_id TEXT PRIMARY KEY,
+            chat_id TEXT NOT NULL,
+            workspace_id TEXT NOT NULL,
+            vector BLOB NOT NULL,
+            model TEXT NOT NULL,
+            dims INTEGER NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_message_embeddings_workspace_model ON message_embeddings(workspace_id, model)",
+        [],
+    )?;
+
+    // Create workspace_notes table: the markdown brief of a workspace
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS workspace_notes (
+            workspace_id TEXT PRIMARY KEY,
+            notes TEXT NOT NULL,
+            include_in_context INTEGER NOT NULL DEFAULT 0,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Add auto_continue_interrupted column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN auto_continue_interrupted INTEGER DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Add max_output_bytes column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN max_output_bytes INTEGER",
+        [],
+    )
+    .ok();
+
+    // Add outbox_enabled column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN outbox_enabled INTEGER DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Create outbox table: messages waiting for their connection to come back
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS outbox (
+            id TEXT PRIMARY KEY,
+            chat_id TEXT NOT NULL,
+            user_message_id TEXT NOT NULL,
+            content TEXT NOT NULL,
+            files TEXT NOT NULL,
+            params TEXT NOT NULL,
+            base_url TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            attempts INTEGER NOT NULL DEFAULT 0,
+            last_error TEXT,
+            FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_outbox_chat_id ON outbox(chat_id, created_at)",
+        [],
+    )?;
+
+    // Add agent_tool_permission column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN agent_tool_permission TEXT",
+        [],
+    )
+    .ok();
+
+    // Add custom_instructions column to chats if it doesn't exist
+    conn.execute("ALTER TABLE chats ADD COLUMN custom_instructions TEXT", [])
+        .ok();
+
+    // Add Azure deployment columns to llm_connections if they don't exist
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN deployment_name TEXT",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN api_version TEXT",
+        [],
+    )
+    .ok();
+
+    // Add AWS columns to llm_connections if they don't exist
+    conn.execute("ALTER TABLE llm_connections ADD COLUMN aws_region TEXT", [])
+        .ok();
+    conn.execute(
+        "ALTER TABLE llm_connections ADD COLUMN aws_profile TEXT",
+        [],
+    )
+    .ok();
+
+    // Add cache_write_tokens column to usage_stats if it doesn't exist
+    conn.execute(
+        "ALTER TABLE usage_stats ADD COLUMN cache_write_tokens INTEGER NOT NULL DEFAULT 0",
+        [],
+    )
+    .ok();
+
+    // Add generation parameter columns to workspace_settings and
+    // chat_input_settings if they don't exist
+    for table in ["workspace_settings", "chat_input_settings"] {
+        for column in [
+            "temperature REAL",
+            "top_p REAL",
+            "frequency_penalty REAL",
+            "presence_penalty REAL",
+            "max_tokens INTEGER",
+        ] {
+            conn.execute(&format!("ALTER TABLE {table} ADD COLUMN {column}"), [])
+                .ok();
+        }
+    }
+
+    // Branches of a chat: messages record the branch they were written on,
+    // chats the branch they show
+    conn.execute("ALTER TABLE messages ADD COLUMN branch_id TEXT", [])
+        .ok(); // Ignore error if column already exists
+    conn.execute("ALTER TABLE chats ADD COLUMN active_branch_id TEXT", [])
+        .ok(); // Ignore error if column already exists
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chat_branches (
+            id TEXT PRIMARY KEY,
+            chat_id TEXT NOT NULL,
+            parent_branch_id TEXT,
+            fork_message_id TEXT,
+            fork_timestamp INTEGER,
+            name TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_chat_branches_chat_id ON chat_branches(chat_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_messages_branch_id ON messages(branch_id)",
+        [],
+    )?;
+
+    // Create the full-text search index over message content; the triggers
+    // keep message_search_fts in step with message_search
+    let search_index_exists: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE name = 'message_search')",
+        [],
+        |row| row.get(0),
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS message_search (
+            id INTEGER PRIMARY KEY,
+            message_id TEXT NOT NULL UNIQUE,
+            chat_id TEXT NOT NULL,
+            content TEXT NOT NULL,
+            FOREIGN KEY (message_id) REFERENCES messages(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE VIRTUAL TABLE IF NOT EXISTS message_search_fts USING fts5(
+            content,
+            content = 'message_search',
+            content_rowid = 'id',
+            tokenize = 'unicode61 remove_diacritics 2'
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS message_search_ai AFTER INSERT ON message_search BEGIN
+            INSERT INTO message_search_fts (rowid, content) VALUES (new.id, new.content);
+        END",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TRIGGER IF NOT EXISTS message_search_ad AFTER DELETE ON message_search BEGIN
+            INSERT INTO message_search_fts (message_search_fts, rowid, content)
+            VALUES ('delete', old.id, old.content);
+        END",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_message_search_chat_id ON message_search(chat_id)",
+        [],
+    )?;
+    // Index the messages stored before the index existed
+    if !search_index_exists {
+        conn.execute(
+            "INSERT INTO message_search (message_id, chat_id, content)
+             SELECT id, chat_id, content FROM messages
+             WHERE role IN ('user', 'assistant') AND content != ''
+               AND content NOT LIKE ?1",
+            [format!("{ENCRYPTED_PREFIX}%")],
+        )?;
+    }
+
+    // Add max_parallel_tools column to workspace_settings if it doesn't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN max_parallel_tools INTEGER",
+        [],
+    )
+    .ok();
+
+    // Create chat_tool_permissions table for "always" answers kept for one chat
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS chat_tool_permissions (
+            chat_id TEXT NOT NULL,
+            tool_name TEXT NOT NULL,
+            permission TEXT NOT NULL,
+            created_at INTEGER NOT NULL,
+            PRIMARY KEY (chat_id, tool_name),
+            FOREIGN KEY (chat_id) REFERENCES chats(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Add rolling summary columns to chats if they don't exist
+    conn.execute("ALTER TABLE chats ADD COLUMN summary TEXT", [])
+        .ok();
+    conn.execute("ALTER TABLE chats ADD COLUMN summary_through INTEGER", [])
+        .ok();
+
+    // Add summary settings columns to workspace_settings if they don't exist
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN summarize_after_messages INTEGER",
+        [],
+    )
+    .ok();
+    conn.execute(
+        "ALTER TABLE workspace_settings ADD COLUMN summary_model TEXT",
+        [],
+    )
+    .ok();
+
+    // Create knowledge tables: documents attached to a workspace and the
+    // embedded chunks retrieval reads
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS knowledge_documents (
+            id TEXT PRIMARY KEY,
+            workspace_id TEXT NOT NULL,
+            path TEXT NOT NULL,
+            title TEXT NOT NULL,
+            size_bytes INTEGER NOT NULL,
+            chunk_count INTEGER NOT NULL,
+            model TEXT NOT NULL,
+            indexed_at INTEGER NOT NULL,
+            UNIQUE (workspace_id, path),
+            FOREIGN KEY (workspace_id) REFERENCES workspaces(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS knowledge_chunks (
+            document_id TEXT NOT NULL,
+            chunk_index INTEGER NOT NULL,
+            workspace_id TEXT NOT NULL,
+            content TEXT NOT NULL,
+            vector BLOB NOT NULL,
+            model TEXT NOT NULL,
+            PRIMARY KEY (document_id, chunk_index),
+            FOREIGN KEY (document_id) REFERENCES knowledge_documents(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_knowledge_chunks_workspace_model ON knowledge_chunks(workspace_id, model)",
+        [],
+    )?;
+
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn schema_migrations_run_once_in_order_and_reject_changes() {
+        use crate::db::migrations::{
+            applied_migrations, check_applied, check_order, latest_version, run_migrations,
+            AppliedMigration, Migration, MigrationStep,
+        };
+        use crate::features::maintenance::service::db_info;
+
+        // A database from before versioning gets the baseline recorded
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE workspaces (id TEXT PRIMARY KEY, name TEXT NOT NULL, created_at INTEGER NOT NULL)",
+            [],
+        )
+        .unwrap();
+        run_migrations(&conn).unwrap();
+        let info = db_info(&conn).unwrap();
+        assert_eq!(info.schema_version, latest_version());
+        assert!(info.pending.is_empty());
+        assert_eq!(info.applied[0].version, 1);
+        assert_eq!(info.applied[0].name, "baseline");
+        assert!(info.applied[0].applied_at.is_some());
+
+        // Running again changes nothing
+        let applied = applied_migrations(&conn).unwrap();
+        run_migrations(&conn).unwrap();
+        assert_eq!(applied_migrations(&conn).unwrap(), applied);
+
+        let migrations = [
+            Migration {
+                version: 1,
+                name: "first",
+                step: MigrationStep::Sql("CREATE TABLE a (id INTEGER)"),
+            },
+            Migration {
+                version: 2,
+                name: "second",
+                step: MigrationStep::Sql("CREATE TABLE b (id INTEGER)"),
+            },
+        ];
+        check_order(&migrations).unwrap();
+        let recorded = |version: u32, name: &str, checksum: String| AppliedMigration {
+            version,
+            name: name.to_string(),
+            checksum,
+            applied_at: 0,
+        };
+        check_applied(
+            &migrations,
+            &[recorded(1, "first", migrations[0].checksum())],
+        )
+        .unwrap();
+
+        // A migration edited after it ran
+        let error = check_applied(&migrations, &[recorded(1, "first", "edited".into())])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("changed since it ran"), "{error}");
+        // A database migrated by a newer release
+        let error = check_applied(
+            &migrations[..1],
+            &[
+                recorded(1, "first", migrations[0].checksum()),
+                recorded(2, "second", migrations[1].checksum()),
+            ],
+        )
+        .unwrap_err()
+        .to_string();
+        assert!(error.contains("newer release"), "{error}");
+        // A gap in what ran
+        assert!(check_applied(
+            &migrations,
+            &[recorded(2, "second", migrations[1].checksum())]
+        )
+        .is_err());
+        // A gap in the versions themselves
+        let gap = [Migration {
+            version: 2,
+            name: "second",
+            step: MigrationStep::Sql(""),
+        }];
+        assert!(check_order(&gap).is_err());
+    }
+}
@@ -1,4 +1,4 @@
 pub mod connection;
 pub mod migrations;
 
-pub use connection::{get_connection, init_db};
+pub use connection::{configure_database, get_connection, init_db, open_connection};
@@ -1,3 +1,4 @@
+use crate::services::llm::rate_limit::RateLimitKind;
 use serde::Serializer;
 use thiserror::Error;
 
@@ -28,6 +29,19 @@ pub enum AppError {
     #[error("[LLM] {0}")]
     Llm(String),
 
+    /// A provider refused the request over a rate limit, with its hints on
+    /// when to try again
+    #[error("[RateLimited] {message}")]
+    RateLimited {
+        message: String,
+        retry_after_ms: Option<u64>,
+        limit_kind: Option<RateLimitKind>,
+    },
+
+    /// A provider stream broke off before any of the answer arrived
+    #[error("[StreamInterrupted] {0}")]
+    StreamInterrupted(String),
+
     #[error("[Python] {0}")]
     Python(String),
 
@@ -52,11 +66,27 @@ pub enum AppError {
     #[error("[Cancelled] Operation cancelled by user")]
     Cancelled,
 
+    /// A turn over the workspace's cost threshold was not approved
+    #[error("[CostNotConfirmed] {0}")]
+    CostNotConfirmed(String),
+
+    /// The model could not be reached and the message waits in the chat's
+    /// outbox, under this item id
+    #[error("[Queued] Message queued until the connection is back")]
+    Queued(String),
+
+    /// The kill switch stopped all activity until it is resumed
+    #[error("[Halted] All activity is stopped until it is resumed")]
+    Halted,
+
     #[error("[Error] {0}")]
     Generic(String),
 
     #[error("[Agent] {0}")]
     Agent(String),
+
+    #[error("[Instance] Another instance (process {0}) is using the data directory")]
+    InstanceLocked(u32),
 }
 
 impl AppError {
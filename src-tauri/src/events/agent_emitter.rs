@@ -1,7 +1,6 @@
-use crate::constants::TauriEvents;
 use crate::error::AppError;
-use crate::events::AgentLoopIterationEvent;
-use tauri::{AppHandle, Emitter};
+use crate::events::{emit_event, AgentLoopIterationEvent, AgentLoopWarningEvent};
+use tauri::AppHandle;
 
 pub struct AgentEmitter {
     app: AppHandle,
@@ -19,18 +18,34 @@ impl AgentEmitter {
         max_iterations: usize,
         has_tool_calls: bool,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::AGENT_LOOP_ITERATION,
-                AgentLoopIterationEvent {
-                    chat_id,
-                    iteration,
-                    max_iterations,
-                    has_tool_calls,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit agent-loop-iteration event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            AgentLoopIterationEvent {
+                chat_id,
+                iteration,
+                max_iterations,
+                has_tool_calls,
+            },
+        )
+    }
+
+    pub fn emit_agent_loop_warning(
+        &self,
+        chat_id: String,
+        message_id: String,
+        kind: String,
+        message: String,
+        tool_call_ids: Vec<String>,
+    ) -> Result<(), AppError> {
+        emit_event(
+            &self.app,
+            AgentLoopWarningEvent {
+                chat_id,
+                message_id,
+                kind,
+                message,
+                tool_call_ids,
+            },
+        )
     }
 }
@@ -0,0 +1,205 @@
+//! Every event the backend emits, with its payload type.
+//!
+//! Payload structs get their event name from this table, and emitters send
+//! them through `emit_event`, so an event that is not registered here does
+//! not compile. `get_event_schema` exposes the table with a JSON Schema per
+//! payload for frontend checks and external integrations.
+
+use super::{
+    ActiveChatStatusChangedEvent, ActivityAddedEvent, AgentLoopIterationEvent,
+    AgentLoopWarningEvent, ChatImportProgressEvent, ChatUpdatedEvent, ContextTrimmedEvent,
+    CostConfirmationRequestEvent, EncryptionProgressEvent, GlobalHaltEvent,
+    McpAutoConnectProgressEvent, McpSamplingRequestEvent, McpServerStatusEvent,
+    MessageCancelledEvent, MessageChunkEvent, MessageCompleteEvent, MessageErrorEvent,
+    MessageMetadataUpdatedEvent, MessageStartedEvent, MessageStreamStatsEvent,
+    MessageVariantsUpdatedEvent, ModelCapabilityWarningEvent, NavigateToMessageEvent,
+    OllamaPullProgressEvent, OutboxStatusEvent, OutputLimitReachedEvent, QuickActionEvent,
+    ScratchpadUpdatedEvent, SemanticIndexProgressEvent, ThinkingChunkEvent, ToolCallsDetectedEvent,
+    ToolExecutionCompletedEvent, ToolExecutionErrorEvent, ToolExecutionProgressEvent,
+    ToolExecutionStartedEvent, ToolPermissionRequestEvent, WorkspaceNotesUpdatedEvent,
+};
+use crate::constants::TauriEvents;
+use crate::error::AppError;
+use crate::state::AppState;
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Bump when a payload changes incompatibly (a field removed, renamed or
+/// retyped). Adding an optional field does not need a bump.
+pub const EVENT_SCHEMA_VERSION: u32 = 1;
+
+/// A payload type registered in the catalog under `NAME`.
+pub trait CatalogEvent: Serialize + Clone + JsonSchema {
+    const NAME: &'static str;
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventDescriptor {
+    pub name: &'static str,
+    pub payload_type: &'static str,
+    pub schema: RootSchema,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventSchema {
+    pub version: u32,
+    pub events: Vec<EventDescriptor>,
+}
+
+/// `payload` events get a `CatalogEvent` impl; `plain` events (menu actions)
+/// carry a unit or string payload and are only listed.
+macro_rules! event_catalog {
+    (
+        payload { $($name:expr => $payload:ty),* $(,)? }
+        plain { $($plain_name:expr => $plain_payload:ty),* $(,)? }
+    ) => {
+        $(
+            impl CatalogEvent for $payload {
+                const NAME: &'static str = $name;
+            }
+        )*
+
+        fn event_descriptors() -> Vec<EventDescriptor> {
+            vec![
+                $(
+                    EventDescriptor {
+                        name: $name,
+                        payload_type: stringify!($payload),
+                        schema: schema_for!($payload),
+                    },
+                )*
+                $(
+                    EventDescriptor {
+                        name: $plain_name,
+                        payload_type: stringify!($plain_payload),
+                        schema: schema_for!($plain_payload),
+                    },
+                )*
+            ]
+        }
+    };
+}
+
+event_catalog! {
+    payload {
+        TauriEvents::MESSAGE_STARTED => MessageStartedEvent,
+        TauriEvents::MESSAGE_CHUNK => MessageChunkEvent,
+        TauriEvents::THINKING_CHUNK => ThinkingChunkEvent,
+        TauriEvents::MESSAGE_COMPLETE => MessageCompleteEvent,
+        TauriEvents::MESSAGE_ERROR => MessageErrorEvent,
+        TauriEvents::MESSAGE_CANCELLED => MessageCancelledEvent,
+        TauriEvents::MESSAGE_METADATA_UPDATED => MessageMetadataUpdatedEvent,
+        TauriEvents::OUTPUT_LIMIT_REACHED => OutputLimitReachedEvent,
+        TauriEvents::MESSAGE_STREAM_STATS => MessageStreamStatsEvent,
+        TauriEvents::MESSAGE_VARIANTS_UPDATED => MessageVariantsUpdatedEvent,
+        TauriEvents::CONTEXT_TRIMMED => ContextTrimmedEvent,
+        TauriEvents::TOOL_CALLS_DETECTED => ToolCallsDetectedEvent,
+        TauriEvents::TOOL_EXECUTION_STARTED => ToolExecutionStartedEvent,
+        TauriEvents::TOOL_EXECUTION_PROGRESS => ToolExecutionProgressEvent,
+        TauriEvents::TOOL_EXECUTION_COMPLETED => ToolExecutionCompletedEvent,
+        TauriEvents::TOOL_EXECUTION_ERROR => ToolExecutionErrorEvent,
+        TauriEvents::TOOL_PERMISSION_REQUEST => ToolPermissionRequestEvent,
+        TauriEvents::COST_CONFIRMATION_REQUEST => CostConfirmationRequestEvent,
+        TauriEvents::SCRATCHPAD_UPDATED => ScratchpadUpdatedEvent,
+        TauriEvents::WORKSPACE_NOTES_UPDATED => WorkspaceNotesUpdatedEvent,
+        TauriEvents::AGENT_LOOP_ITERATION => AgentLoopIterationEvent,
+        TauriEvents::AGENT_LOOP_WARNING => AgentLoopWarningEvent,
+        TauriEvents::CHAT_UPDATED => ChatUpdatedEvent,
+        TauriEvents::NAVIGATE_TO_MESSAGE => NavigateToMessageEvent,
+        TauriEvents::ACTIVE_CHAT_STATUS_CHANGED => ActiveChatStatusChangedEvent,
+        TauriEvents::OUTBOX_STATUS => OutboxStatusEvent,
+        TauriEvents::ACTIVITY_ADDED => ActivityAddedEvent,
+        TauriEvents::MODEL_CAPABILITY_WARNING => ModelCapabilityWarningEvent,
+        TauriEvents::QUICK_ACTION => QuickActionEvent,
+        TauriEvents::ENCRYPTION_PROGRESS => EncryptionProgressEvent,
+        TauriEvents::CHAT_IMPORT_PROGRESS => ChatImportProgressEvent,
+        TauriEvents::SEMANTIC_INDEX_PROGRESS => SemanticIndexProgressEvent,
+        TauriEvents::GLOBAL_HALT => GlobalHaltEvent,
+        TauriEvents::OLLAMA_PULL_PROGRESS => OllamaPullProgressEvent,
+        TauriEvents::MCP_SAMPLING_REQUEST => McpSamplingRequestEvent,
+        TauriEvents::MCP_SERVER_STATUS => McpServerStatusEvent,
+        TauriEvents::MCP_AUTO_CONNECT_PROGRESS => McpAutoConnectProgressEvent,
+    }
+    plain {
+        TauriEvents::MENU_NEW_CHAT => (),
+        TauriEvents::MENU_TOGGLE_SIDEBAR => (),
+        TauriEvents::MENU_THEME => String,
+        TauriEvents::MENU_SETTINGS => (),
+        TauriEvents::MENU_DOCUMENTATION => (),
+        TauriEvents::MENU_ABOUT => (),
+        TauriEvents::MENU_CHECK_UPDATES => (),
+        TauriEvents::MENU_KEYBOARD_SHORTCUTS => (),
+    }
+}
+
+/// Emit a registered event under its catalog name. Once the app state is
+/// up, events go through its `EventDispatcher`, which keeps each chat's
+/// events in order and shows them to running chat shares as they go out.
+pub fn emit_event<E: CatalogEvent>(app: &AppHandle, payload: E) -> Result<(), AppError> {
+    match app.try_state::<AppState>() {
+        Some(state) => state
+            .event_dispatcher
+            .dispatch(E::NAME, serde_json::to_value(&payload)?),
+        None => app
+            .emit(E::NAME, payload)
+            .map_err(|e| AppError::Generic(format!("Failed to emit {} event: {e}", E::NAME))),
+    }
+}
+
+pub fn event_schema() -> EventSchema {
+    EventSchema {
+        version: EVENT_SCHEMA_VERSION,
+        events: event_descriptors(),
+    }
+}
+
+#[tauri::command]
+pub fn get_event_schema() -> EventSchema {
+    event_schema()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::TauriEvents;
+
+    #[test]
+    fn event_catalog_lists_each_event_once() {
+        use crate::events::catalog::{event_schema, EVENT_SCHEMA_VERSION};
+        use std::collections::HashSet;
+
+        let schema = event_schema();
+        assert_eq!(schema.version, EVENT_SCHEMA_VERSION);
+
+        let mut names = HashSet::new();
+        for event in &schema.events {
+            assert!(names.insert(event.name), "duplicate event {}", event.name);
+        }
+        for name in [
+            TauriEvents::MESSAGE_CHUNK,
+            TauriEvents::TOOL_EXECUTION_PROGRESS,
+            TauriEvents::SCRATCHPAD_UPDATED,
+            TauriEvents::CHAT_UPDATED,
+        ] {
+            assert!(
+                names.contains(name),
+                "{name} missing from the event catalog"
+            );
+        }
+    }
+
+    #[test]
+    fn emitted_payloads_are_registered() {
+        use crate::events::catalog::CatalogEvent;
+        use crate::events::{MessageChunkEvent, ToolExecutionProgressEvent};
+
+        // Emitters only accept `CatalogEvent` payloads, so registration is
+        // checked at compile time; this pins the names they map to.
+        assert_eq!(MessageChunkEvent::NAME, TauriEvents::MESSAGE_CHUNK);
+        assert_eq!(
+            ToolExecutionProgressEvent::NAME,
+            TauriEvents::TOOL_EXECUTION_PROGRESS
+        );
+    }
+}
@@ -0,0 +1,54 @@
+//! The single queue every backend event goes through.
+//!
+//! Emitters hand events to `EventDispatcher::dispatch` instead of emitting
+//! them, and one task emits them in queue order, applying the contract in
+//! `ordering`. Events dispatched from different tasks are therefore seen in
+//! the order they were dispatched, not in the order their emits happened to
+//! run.
+
+use super::ordering::{QueuedEvent, TurnOrdering};
+use crate::error::AppError;
+use serde_json::Value;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+pub struct EventDispatcher {
+    queue: mpsc::UnboundedSender<QueuedEvent>,
+}
+
+impl EventDispatcher {
+    /// Start the dispatch task; `sink` does the actual emitting.
+    pub fn spawn(sink: impl Fn(&'static str, Value) + Send + 'static) -> Self {
+        let (queue, events) = mpsc::unbounded_channel();
+        tauri::async_runtime::spawn(run(events, sink));
+        Self { queue }
+    }
+
+    pub fn dispatch(&self, name: &'static str, payload: Value) -> Result<(), AppError> {
+        self.queue.send(QueuedEvent { name, payload }).map_err(|_| {
+            AppError::Generic(format!("Failed to emit {name} event: dispatcher stopped"))
+        })
+    }
+}
+
+async fn run(mut events: mpsc::UnboundedReceiver<QueuedEvent>, sink: impl Fn(&'static str, Value)) {
+    let mut ordering = TurnOrdering::new();
+    loop {
+        let deadline = ordering.next_deadline();
+        let released = tokio::select! {
+            received = events.recv() => match received {
+                Some(event) => ordering.push(event, Instant::now()),
+                None => break,
+            },
+            () = tokio::time::sleep_until(deadline.unwrap_or_else(Instant::now)), if deadline.is_some() => {
+                ordering.expire(Instant::now())
+            }
+        };
+        for event in released {
+            sink(event.name, event.payload);
+        }
+    }
+    for event in ordering.drain() {
+        sink(event.name, event.payload);
+    }
+}
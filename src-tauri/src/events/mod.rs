@@ -1,59 +1,122 @@
+use crate::services::llm::rate_limit::RateLimitKind;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 pub mod agent_emitter;
-
+pub mod catalog;
+pub mod dispatcher;
+pub mod ordering;
 pub mod tool_emitter;
 
 pub use crate::features::message::MessageEmitter;
 pub use agent_emitter::AgentEmitter;
+pub use catalog::emit_event;
+pub use dispatcher::EventDispatcher;
 pub use tool_emitter::ToolEmitter;
 
 // Event types for Phase 1 (LLM streaming)
 // These will be used when we implement LLM service in Phase 1
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MessageStartedEvent {
     pub chat_id: String,
     pub user_message_id: String,
     pub assistant_message_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MessageChunkEvent {
     pub chat_id: String,
     pub message_id: String,
     pub chunk: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ThinkingChunkEvent {
     pub chat_id: String,
     pub message_id: String,
     pub chunk: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MessageCompleteEvent {
     pub chat_id: String,
     pub message_id: String,
     pub content: String,
     pub token_usage: Option<TokenUsage>,
+    /// The stream was cancelled and `content` is what arrived before it,
+    /// already stored on the message
+    #[serde(default)]
+    pub partial: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MessageErrorEvent {
     pub chat_id: String,
     pub message_id: String,
     pub error: String,
+    /// How long the provider asked to wait, when it refused over a rate limit
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retry_after_ms: Option<u64>,
+    /// Which rate limit the request ran into, when the provider said
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit_kind: Option<RateLimitKind>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MessageCancelledEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    pub content: String, // Partial content received before the cancel
+}
+
+/// A streamed answer was cut off at an output cap and kept up to it.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutputLimitReachedEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    pub kind: String, // "content", "reasoning" or "tool_arguments"
+    pub limit_bytes: usize,
+}
+
+/// The oldest turns of a chat were left out of a request so it fits the
+/// model's context window. Token counts are estimates.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ContextTrimmedEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    pub dropped_messages: usize,
+    pub estimated_tokens: u32,
+    pub context_window: u32,
+}
+
+/// Generation speed of an answer still streaming, about once a second.
+/// Estimates only: the completed answer's usage has the real numbers.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MessageStreamStatsEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    pub est_tokens_so_far: u64,
+    pub tokens_per_sec: f64,
+    pub elapsed_ms: u64,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct MessageMetadataUpdatedEvent {
     pub chat_id: String,
     pub message_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// An answer was regenerated or another of its generations was selected.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct MessageVariantsUpdatedEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    pub variant_index: usize,
+    pub variant_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct TokenUsage {
     pub prompt_tokens: Option<u32>,
     pub completion_tokens: Option<u32>,
@@ -61,21 +124,21 @@ pub struct TokenUsage {
 }
 
 // Tool call events for Phase 2
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolCallsDetectedEvent {
     pub chat_id: String,
     pub message_id: String,
     pub tool_calls: Vec<ToolCall>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolExecutionStartedEvent {
     pub chat_id: String,
     pub message_id: String,
     pub tool_calls_count: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolExecutionProgressEvent {
     pub chat_id: String,
     pub message_id: String,
@@ -86,7 +149,7 @@ pub struct ToolExecutionProgressEvent {
     pub error: Option<String>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolExecutionCompletedEvent {
     pub chat_id: String,
     pub message_id: String,
@@ -95,7 +158,21 @@ pub struct ToolExecutionCompletedEvent {
     pub failed_count: usize,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ScratchpadUpdatedEvent {
+    pub chat_id: String,
+    pub content: String,
+}
+
+/// The notes of a workspace were saved; `get_workspace_notes` has them.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct WorkspaceNotesUpdatedEvent {
+    pub workspace_id: String,
+    pub include_notes_in_context: bool,
+    pub updated_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolExecutionErrorEvent {
     pub chat_id: String,
     pub message_id: String,
@@ -104,7 +181,7 @@ pub struct ToolExecutionErrorEvent {
     pub error: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct AgentLoopIterationEvent {
     pub chat_id: String,
     pub iteration: usize,
@@ -112,14 +189,26 @@ pub struct AgentLoopIterationEvent {
     pub has_tool_calls: bool,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// The agent loop held back part of what the model asked for, such as tool
+/// calls over a cap, and the turn goes on without it.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct AgentLoopWarningEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    /// `tool_call_cap`
+    pub kind: String,
+    pub message: String,
+    pub tool_call_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolCallEvent {
     pub chat_id: String,
     pub message_id: String,
     pub tool_calls: Vec<ToolCall>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolResultEvent {
     pub chat_id: String,
     pub message_id: String,
@@ -127,7 +216,7 @@ pub struct ToolResultEvent {
     pub result: String,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolCall {
     pub id: String,
     pub name: String,
@@ -135,15 +224,261 @@ pub struct ToolCall {
 }
 
 // Tool permission request event
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ToolPermissionRequestEvent {
     pub chat_id: String,
     pub message_id: String,
     pub tool_calls: Vec<ToolCall>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// A turn's first model call is held until the user confirms its estimated
+/// cost with `respond_cost_confirmation`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct CostConfirmationRequestEvent {
+    pub chat_id: String,
+    pub message_id: String,
+    /// The workspace's `confirm_above_estimated_cost`
+    pub threshold: f64,
+    pub estimate: crate::features::chat::cost_gate::CostEstimate,
+    /// Seconds until the turn is dropped without an answer
+    pub timeout_secs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
 pub struct ChatUpdatedEvent {
     pub chat_id: String,
     pub title: String,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ActivityAddedEvent {
+    pub id: String,
+    pub workspace_id: String,
+    pub chat_id: String,
+    pub kind: String,
+    pub summary: String,
+    pub created_at: i64,
+    pub read: bool,
+}
+
+/// A message was sent from a prompt whose required model capabilities no
+/// available model has; the turn ran on its usual model.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ModelCapabilityWarningEvent {
+    pub chat_id: String,
+    pub prompt_id: String,
+    pub model: String,
+    pub missing_capabilities: Vec<String>,
+}
+
+/// The status of a chat changed phase, model or iteration.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ActiveChatStatusChangedEvent {
+    pub status: crate::features::chat::status::ActiveChatStatus,
+}
+
+/// A message of a chat's outbox was queued, sent, given up on or discarded.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct OutboxStatusEvent {
+    pub chat_id: String,
+    pub item_id: String,
+    pub user_message_id: String,
+    pub status: crate::features::outbox::OutboxStatus,
+    /// Sends tried so far
+    pub attempts: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// The answer, once the message was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assistant_message_id: Option<String>,
+}
+
+/// A permalink was opened. When `found` is false, `error` says why and the
+/// ids are only those that could still be resolved.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct NavigateToMessageEvent {
+    pub uri: String,
+    pub found: bool,
+    pub workspace_id: Option<String>,
+    pub chat_id: Option<String>,
+    pub message_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Progress of a quick action run, keyed by its transient run id rather than
+/// a message id so previews never touch a chat.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QuickActionEvent {
+    Started {
+        action_run_id: String,
+        action_id: String,
+        output_mode: String,
+        /// Set when the answer is added to a chat
+        chat_id: Option<String>,
+        message_id: Option<String>,
+    },
+    Chunk {
+        action_run_id: String,
+        chunk: String,
+    },
+    Complete {
+        action_run_id: String,
+        content: String,
+        token_usage: Option<TokenUsage>,
+    },
+    Error {
+        action_run_id: String,
+        error: String,
+    },
+}
+
+/// Progress of `enable_encryption` or `disable_encryption` rewriting stored
+/// messages. The last event of a run has `done` set.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct EncryptionProgressEvent {
+    /// `true` while encrypting, `false` while decrypting
+    pub enabling: bool,
+    pub processed: u64,
+    pub total: u64,
+    pub done: bool,
+}
+
+/// Progress of `backfill_semantic_index` embedding messages. Messages the
+/// model failed on are `skipped` and tried again by a later pass; the last
+/// event of a run has `done` set.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct SemanticIndexProgressEvent {
+    pub model: String,
+    pub indexed: u64,
+    pub skipped: u64,
+    /// Messages waiting when the run started
+    pub total: u64,
+    pub done: bool,
+}
+
+/// Progress of `import_external_chats`. The first event of a run carries the
+/// id `cancel_chat_import` takes; the last one has `done` set.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct ChatImportProgressEvent {
+    pub import_id: String,
+    /// `chatgpt` or `claude`
+    pub source: String,
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    /// Conversations read so far
+    pub conversations: usize,
+    pub done: bool,
+}
+
+/// The kill switch stopped all activity, with what it stopped, or activity
+/// was resumed (`halted` false, the counts 0).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct GlobalHaltEvent {
+    pub halted: bool,
+    /// Chats whose running turn was cancelled
+    pub chats_stopped: usize,
+    /// Of those, the ones running tools
+    pub tool_executions_stopped: usize,
+    /// Tool permission requests answered as cancelled
+    pub permission_requests_cancelled: usize,
+    /// Turns waiting for their cost to be confirmed, cancelled
+    pub cost_confirmations_cancelled: usize,
+    /// MCP sampling requests waiting for approval, declined
+    pub sampling_requests_cancelled: usize,
+    /// Chats whose queued messages wait for the resume
+    pub outbox_chats_paused: usize,
+}
+
+/// Progress of a model download to an Ollama connection's server. The last
+/// event of a download has `done` set, with `error` when it failed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct OllamaPullProgressEvent {
+    pub connection_id: String,
+    pub model: String,
+    /// The server's step, e.g. "pulling manifest", "verifying sha256 digest"
+    pub status: String,
+    /// Layer being downloaded
+    pub digest: Option<String>,
+    /// Bytes of the layer
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    pub done: bool,
+    pub error: Option<String>,
+}
+
+/// A connected MCP server asks for a completion. It is held until the user
+/// answers with `respond_mcp_sampling`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct McpSamplingRequestEvent {
+    pub request_id: String,
+    pub mcp_connection_id: String,
+    pub server_name: String,
+    /// The workspace whose LLM connection would answer
+    pub workspace_id: String,
+    pub llm_connection_id: String,
+    pub model: String,
+    pub system_prompt: Option<String>,
+    pub messages: Vec<crate::features::tool::mcp_sampling::SamplingMessagePreview>,
+    pub max_tokens: u32,
+    /// Seconds until the request is declined without an answer
+    pub timeout_secs: u64,
+}
+
+/// The health check found an MCP server down, is reconnecting it, got it
+/// back or gave up on it.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct McpServerStatusEvent {
+    pub connection_id: String,
+    pub server_name: String,
+    /// "connected", "connecting" or "disconnected", as stored on the connection
+    pub status: String,
+    pub error: Option<String>,
+    /// Failed checks in a row
+    pub failures: u32,
+    /// Seconds until the next reconnect attempt, while reconnecting
+    pub retry_in_secs: Option<u64>,
+}
+
+/// Progress of connecting the MCP servers flagged `auto_connect` at startup.
+/// Each server is reported "connecting", then "connected" or
+/// "disconnected"; the run is over once `completed` reaches `total`.
+#[derive(Debug, Serialize, Deserialize, Clone, JsonSchema)]
+pub struct McpAutoConnectProgressEvent {
+    pub connection_id: String,
+    pub server_name: String,
+    pub status: String,
+    pub error: Option<String>,
+    /// Tools the server offers, once connected
+    pub tool_count: Option<usize>,
+    /// Servers done so far
+    pub completed: usize,
+    pub total: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn cancelled_completion_is_marked_partial() {
+        use crate::events::MessageCompleteEvent;
+
+        // Payloads from before the flag read as complete answers
+        let event: MessageCompleteEvent = serde_json::from_value(serde_json::json!({
+            "chat_id": "c",
+            "message_id": "m",
+            "content": "Hello",
+            "token_usage": null,
+        }))
+        .unwrap();
+        assert!(!event.partial);
+
+        let event = MessageCompleteEvent {
+            partial: true,
+            ..event
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap()["partial"],
+            serde_json::json!(true)
+        );
+    }
+}
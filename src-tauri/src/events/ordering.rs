@@ -0,0 +1,362 @@
+//! The order chat events reach the frontend in.
+//!
+//! The contract, per chat:
+//! - events are emitted in the order they were dispatched (FIFO), except that
+//! - a tool event never goes out before the `message-started` of the message
+//!   it references. A tool event for a message not announced yet is held,
+//!   together with everything the chat dispatches after it, until that
+//!   message is announced, a completion, error or cancel of the chat flushes
+//!   the hold, or `REORDER_WINDOW` passes.
+//!
+//! Events without a top-level `chat_id` are never held. Chats that have not
+//! announced any message (quick action runs) are not held either, since
+//! nothing will be announced for them.
+
+use crate::constants::TauriEvents;
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Longest a tool event waits for its message to be announced.
+pub const REORDER_WINDOW: Duration = Duration::from_millis(250);
+/// Announced messages remembered per chat; tool events may still reference
+/// earlier iterations of the turn.
+const ANNOUNCED_PER_CHAT: usize = 64;
+
+/// Events that reference an assistant message through `message_id`.
+const TOOL_EVENTS: &[&str] = &[
+    TauriEvents::TOOL_CALLS_DETECTED,
+    TauriEvents::TOOL_EXECUTION_STARTED,
+    TauriEvents::TOOL_EXECUTION_PROGRESS,
+    TauriEvents::TOOL_EXECUTION_COMPLETED,
+    TauriEvents::TOOL_EXECUTION_ERROR,
+    TauriEvents::TOOL_PERMISSION_REQUEST,
+];
+
+/// Events that end a message; whatever the chat holds goes out before them.
+const FLUSHING_EVENTS: &[&str] = &[
+    TauriEvents::MESSAGE_COMPLETE,
+    TauriEvents::MESSAGE_ERROR,
+    TauriEvents::MESSAGE_CANCELLED,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueuedEvent {
+    pub name: &'static str,
+    pub payload: Value,
+}
+
+impl QueuedEvent {
+    fn chat_id(&self) -> Option<&str> {
+        self.payload.get("chat_id").and_then(Value::as_str)
+    }
+
+    fn field(&self, field: &str) -> Option<&str> {
+        self.payload.get(field).and_then(Value::as_str)
+    }
+
+    /// The message a tool event waits for.
+    fn awaited_message(&self) -> Option<&str> {
+        TOOL_EVENTS
+            .contains(&self.name)
+            .then(|| self.field("message_id"))
+            .flatten()
+    }
+}
+
+#[derive(Default)]
+struct ChatOrder {
+    announced: VecDeque<String>,
+    held: VecDeque<(Instant, QueuedEvent)>,
+}
+
+impl ChatOrder {
+    fn is_announced(&self, message_id: &str) -> bool {
+        self.announced.iter().any(|id| id == message_id)
+    }
+
+    fn announce(&mut self, message_id: &str) {
+        if self.is_announced(message_id) {
+            return;
+        }
+        if self.announced.len() == ANNOUNCED_PER_CHAT {
+            self.announced.pop_front();
+        }
+        self.announced.push_back(message_id.to_string());
+    }
+
+    /// Whether `event` must wait for its message to be announced.
+    fn blocks(&self, event: &QueuedEvent) -> bool {
+        !self.announced.is_empty()
+            && event
+                .awaited_message()
+                .is_some_and(|message_id| !self.is_announced(message_id))
+    }
+
+    /// Release held events from the front, stopping at the first one still
+    /// waiting for its message unless its window has passed.
+    fn release(&mut self, now: Option<Instant>, released: &mut Vec<QueuedEvent>) {
+        while let Some((deadline, event)) = self.held.front() {
+            let expired = now.is_some_and(|now| *deadline <= now);
+            if self.blocks(event) && !expired {
+                break;
+            }
+            if let Some((_, event)) = self.held.pop_front() {
+                released.push(event);
+            }
+        }
+    }
+}
+
+/// Applies the ordering contract to dispatched events.
+#[derive(Default)]
+pub struct TurnOrdering {
+    chats: HashMap<String, ChatOrder>,
+}
+
+impl TurnOrdering {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take in a dispatched event, returning the events to emit now, in order.
+    pub fn push(&mut self, event: QueuedEvent, now: Instant) -> Vec<QueuedEvent> {
+        let Some(chat_id) = event.chat_id().map(str::to_string) else {
+            return vec![event];
+        };
+        let chat = self.chats.entry(chat_id).or_default();
+        let mut released = Vec::new();
+
+        if event.name == TauriEvents::MESSAGE_STARTED {
+            if let Some(message_id) = event.field("assistant_message_id") {
+                chat.announce(message_id);
+            }
+            // Jumps ahead of the events waiting for it
+            released.push(event);
+            chat.release(None, &mut released);
+        } else if FLUSHING_EVENTS.contains(&event.name) {
+            released.extend(chat.held.drain(..).map(|(_, held)| held));
+            released.push(event);
+        } else if !chat.held.is_empty() || chat.blocks(&event) {
+            chat.held.push_back((now + REORDER_WINDOW, event));
+        } else {
+            released.push(event);
+        }
+        released
+    }
+
+    /// Release the events whose window has passed, and those queued behind
+    /// them that no longer wait.
+    pub fn expire(&mut self, now: Instant) -> Vec<QueuedEvent> {
+        let mut released = Vec::new();
+        for chat in self.chats.values_mut() {
+            chat.release(Some(now), &mut released);
+        }
+        released
+    }
+
+    /// When the next held event's window passes.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.chats
+            .values()
+            .filter_map(|chat| chat.held.front().map(|(deadline, _)| *deadline))
+            .min()
+    }
+
+    /// Everything still held, for shutdown.
+    pub fn drain(&mut self) -> Vec<QueuedEvent> {
+        self.chats
+            .values_mut()
+            .flat_map(|chat| chat.held.drain(..).map(|(_, event)| event))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::constants::TauriEvents;
+
+    fn turn_event(
+        name: &'static str,
+        chat_id: &str,
+        message_id: &str,
+    ) -> crate::events::ordering::QueuedEvent {
+        let payload = if name == TauriEvents::MESSAGE_STARTED {
+            serde_json::json!({
+                "chat_id": chat_id,
+                "user_message_id": "user",
+                "assistant_message_id": message_id,
+            })
+        } else {
+            serde_json::json!({ "chat_id": chat_id, "message_id": message_id })
+        };
+        crate::events::ordering::QueuedEvent { name, payload }
+    }
+
+    /// Every tool event comes after the `message-started` of its message.
+    fn assert_tool_events_follow_their_message(events: &[(&str, serde_json::Value)]) {
+        let tool_events = [
+            TauriEvents::TOOL_CALLS_DETECTED,
+            TauriEvents::TOOL_EXECUTION_STARTED,
+            TauriEvents::TOOL_EXECUTION_PROGRESS,
+            TauriEvents::TOOL_EXECUTION_COMPLETED,
+        ];
+        let mut announced = std::collections::HashSet::new();
+        for (name, payload) in events {
+            if *name == TauriEvents::MESSAGE_STARTED {
+                announced.insert(payload["assistant_message_id"].as_str().unwrap());
+            } else if tool_events.contains(name) {
+                let message_id = payload["message_id"].as_str().unwrap();
+                assert!(
+                    announced.contains(message_id),
+                    "{name} for {message_id} before its message-started: {events:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn early_tool_events_are_emitted_after_their_message_started() {
+        use crate::events::EventDispatcher;
+        use std::sync::{Arc, Mutex};
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let emitted: Arc<Mutex<Vec<(&'static str, serde_json::Value)>>> = Arc::default();
+            let sink = emitted.clone();
+            let dispatcher = EventDispatcher::spawn(move |name, payload| {
+                sink.lock().unwrap().push((name, payload));
+            });
+
+            // A provider that reports the second iteration's tool calls before
+            // the service announces that iteration's message
+            let script = [
+                (TauriEvents::MESSAGE_STARTED, "msg-1"),
+                (TauriEvents::MESSAGE_CHUNK, "msg-1"),
+                (TauriEvents::TOOL_CALLS_DETECTED, "msg-1"),
+                (TauriEvents::MESSAGE_COMPLETE, "msg-1"),
+                (TauriEvents::TOOL_EXECUTION_STARTED, "msg-1"),
+                (TauriEvents::TOOL_EXECUTION_COMPLETED, "msg-1"),
+                (TauriEvents::TOOL_CALLS_DETECTED, "msg-2"),
+                (TauriEvents::MESSAGE_CHUNK, "msg-2"),
+                (TauriEvents::MESSAGE_STARTED, "msg-2"),
+                (TauriEvents::MESSAGE_COMPLETE, "msg-2"),
+            ];
+            for (name, message_id) in script {
+                let event = turn_event(name, "chat-a", message_id);
+                dispatcher.dispatch(event.name, event.payload).unwrap();
+            }
+
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(2);
+            while emitted.lock().unwrap().len() < script.len() {
+                assert!(tokio::time::Instant::now() < deadline, "events not emitted");
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+
+            let emitted = emitted.lock().unwrap();
+            assert_tool_events_follow_their_message(&emitted);
+            let order: Vec<(&str, &str)> = emitted
+                .iter()
+                .map(|(name, payload)| {
+                    let id = payload
+                        .get("message_id")
+                        .or_else(|| payload.get("assistant_message_id"))
+                        .and_then(serde_json::Value::as_str)
+                        .unwrap();
+                    (*name, id)
+                })
+                .collect();
+            assert_eq!(
+                order,
+                vec![
+                    (TauriEvents::MESSAGE_STARTED, "msg-1"),
+                    (TauriEvents::MESSAGE_CHUNK, "msg-1"),
+                    (TauriEvents::TOOL_CALLS_DETECTED, "msg-1"),
+                    (TauriEvents::MESSAGE_COMPLETE, "msg-1"),
+                    (TauriEvents::TOOL_EXECUTION_STARTED, "msg-1"),
+                    (TauriEvents::TOOL_EXECUTION_COMPLETED, "msg-1"),
+                    (TauriEvents::MESSAGE_STARTED, "msg-2"),
+                    (TauriEvents::TOOL_CALLS_DETECTED, "msg-2"),
+                    (TauriEvents::MESSAGE_CHUNK, "msg-2"),
+                    (TauriEvents::MESSAGE_COMPLETE, "msg-2"),
+                ]
+            );
+        });
+    }
+
+    #[test]
+    fn held_tool_events_are_released_by_completion_or_after_the_window() {
+        use crate::events::ordering::{TurnOrdering, REORDER_WINDOW};
+        use tokio::time::Instant;
+
+        let names = |events: Vec<crate::events::ordering::QueuedEvent>| {
+            events.into_iter().map(|e| e.name).collect::<Vec<_>>()
+        };
+        let mut ordering = TurnOrdering::new();
+        let now = Instant::now();
+
+        // Chats that announced nothing (quick action runs) are never held
+        let released = ordering.push(
+            turn_event(TauriEvents::TOOL_CALLS_DETECTED, "run", "m"),
+            now,
+        );
+        assert_eq!(names(released), vec![TauriEvents::TOOL_CALLS_DETECTED]);
+
+        ordering.push(
+            turn_event(TauriEvents::MESSAGE_STARTED, "chat-a", "msg-1"),
+            now,
+        );
+        assert!(ordering
+            .push(
+                turn_event(TauriEvents::TOOL_CALLS_DETECTED, "chat-a", "msg-2"),
+                now
+            )
+            .is_empty());
+        // Later events of the chat queue behind the held one ...
+        assert!(ordering
+            .push(
+                turn_event(TauriEvents::MESSAGE_CHUNK, "chat-a", "msg-1"),
+                now
+            )
+            .is_empty());
+        // ... while other chats are not held up
+        ordering.push(
+            turn_event(TauriEvents::MESSAGE_STARTED, "chat-b", "msg-b"),
+            now,
+        );
+        let released = ordering.push(
+            turn_event(TauriEvents::MESSAGE_CHUNK, "chat-b", "msg-b"),
+            now,
+        );
+        assert_eq!(names(released), vec![TauriEvents::MESSAGE_CHUNK]);
+
+        // Nothing announced msg-2 within the window
+        assert_eq!(ordering.next_deadline(), Some(now + REORDER_WINDOW));
+        assert!(ordering.expire(now + REORDER_WINDOW / 2).is_empty());
+        assert_eq!(
+            names(ordering.expire(now + REORDER_WINDOW)),
+            vec![TauriEvents::TOOL_CALLS_DETECTED, TauriEvents::MESSAGE_CHUNK]
+        );
+        assert_eq!(ordering.next_deadline(), None);
+
+        // A completion flushes what the chat holds, ahead of itself
+        assert!(ordering
+            .push(
+                turn_event(TauriEvents::TOOL_EXECUTION_STARTED, "chat-a", "msg-3"),
+                now
+            )
+            .is_empty());
+        assert_eq!(
+            names(ordering.push(
+                turn_event(TauriEvents::MESSAGE_ERROR, "chat-a", "msg-1"),
+                now
+            )),
+            vec![
+                TauriEvents::TOOL_EXECUTION_STARTED,
+                TauriEvents::MESSAGE_ERROR
+            ]
+        );
+    }
+}
@@ -1,10 +1,10 @@
-use crate::constants::TauriEvents;
 use crate::error::AppError;
 use crate::events::{
-    ToolCall, ToolCallsDetectedEvent, ToolExecutionCompletedEvent, ToolExecutionErrorEvent,
-    ToolExecutionProgressEvent, ToolExecutionStartedEvent, ToolPermissionRequestEvent,
+    emit_event, ScratchpadUpdatedEvent, ToolCall, ToolCallsDetectedEvent,
+    ToolExecutionCompletedEvent, ToolExecutionErrorEvent, ToolExecutionProgressEvent,
+    ToolExecutionStartedEvent, ToolPermissionRequestEvent,
 };
-use tauri::{AppHandle, Emitter};
+use tauri::AppHandle;
 
 pub struct ToolEmitter {
     app: AppHandle,
@@ -21,18 +21,14 @@ impl ToolEmitter {
         message_id: String,
         tool_calls: Vec<ToolCall>,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_CALLS_DETECTED,
-                ToolCallsDetectedEvent {
-                    chat_id,
-                    message_id,
-                    tool_calls,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit tool-calls-detected event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            ToolCallsDetectedEvent {
+                chat_id,
+                message_id,
+                tool_calls,
+            },
+        )
     }
 
     pub fn emit_tool_execution_started(
@@ -41,18 +37,14 @@ impl ToolEmitter {
         message_id: String,
         tool_calls_count: usize,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_EXECUTION_STARTED,
-                ToolExecutionStartedEvent {
-                    chat_id,
-                    message_id,
-                    tool_calls_count,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit tool-execution-started event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            ToolExecutionStartedEvent {
+                chat_id,
+                message_id,
+                tool_calls_count,
+            },
+        )
     }
 
     pub fn emit_tool_execution_progress(
@@ -65,22 +57,18 @@ impl ToolEmitter {
         result: Option<serde_json::Value>,
         error: Option<String>,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_EXECUTION_PROGRESS,
-                ToolExecutionProgressEvent {
-                    chat_id,
-                    message_id,
-                    tool_call_id,
-                    tool_name,
-                    status,
-                    result,
-                    error,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit tool-execution-progress event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            ToolExecutionProgressEvent {
+                chat_id,
+                message_id,
+                tool_call_id,
+                tool_name,
+                status,
+                result,
+                error,
+            },
+        )
     }
 
     pub fn emit_tool_execution_completed(
@@ -91,22 +79,16 @@ impl ToolEmitter {
         successful_count: usize,
         failed_count: usize,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_EXECUTION_COMPLETED,
-                ToolExecutionCompletedEvent {
-                    chat_id,
-                    message_id,
-                    tool_calls_count,
-                    successful_count,
-                    failed_count,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!(
-                    "Failed to emit tool-execution-completed event: {e}"
-                ))
-            })
+        emit_event(
+            &self.app,
+            ToolExecutionCompletedEvent {
+                chat_id,
+                message_id,
+                tool_calls_count,
+                successful_count,
+                failed_count,
+            },
+        )
     }
 
     pub fn emit_tool_execution_error(
@@ -117,20 +99,16 @@ impl ToolEmitter {
         tool_name: String,
         error: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_EXECUTION_ERROR,
-                ToolExecutionErrorEvent {
-                    chat_id,
-                    message_id,
-                    tool_call_id,
-                    tool_name,
-                    error,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit tool-execution-error event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            ToolExecutionErrorEvent {
+                chat_id,
+                message_id,
+                tool_call_id,
+                tool_name,
+                error,
+            },
+        )
     }
 
     pub fn emit_tool_permission_request(
@@ -139,17 +117,21 @@ impl ToolEmitter {
         message_id: String,
         tool_calls: Vec<ToolCall>,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::TOOL_PERMISSION_REQUEST,
-                ToolPermissionRequestEvent {
-                    chat_id,
-                    message_id,
-                    tool_calls,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!("Failed to emit tool-permission-request event: {e}"))
-            })
+        emit_event(
+            &self.app,
+            ToolPermissionRequestEvent {
+                chat_id,
+                message_id,
+                tool_calls,
+            },
+        )
+    }
+
+    pub fn emit_scratchpad_updated(
+        &self,
+        chat_id: String,
+        content: String,
+    ) -> Result<(), AppError> {
+        emit_event(&self.app, ScratchpadUpdatedEvent { chat_id, content })
     }
 }
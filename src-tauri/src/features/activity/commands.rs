@@ -0,0 +1,27 @@
+use super::models::{ActivityEntry, ActivityReadTarget};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub async fn get_activity(
+    workspace_id: String,
+    unread_only: Option<bool>,
+    limit: Option<u32>,
+    offset: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ActivityEntry>, AppError> {
+    state
+        .activity_service
+        .get_activity(&workspace_id, unread_only.unwrap_or(false), limit, offset)
+}
+
+/// Returns how many entries changed from unread to read.
+#[tauri::command]
+pub async fn mark_activity_read(
+    workspace_id: String,
+    target: ActivityReadTarget,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    state.activity_service.mark_read(&workspace_id, &target)
+}
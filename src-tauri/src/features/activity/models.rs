@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+
+/// What an activity entry records.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityKind {
+    TurnCompleted,
+    AgentTaskCompleted,
+    AgentTaskFailed,
+    ToolDenied,
+    Error,
+}
+
+impl ActivityKind {
+    pub const ALL: [Self; 5] = [
+        Self::TurnCompleted,
+        Self::AgentTaskCompleted,
+        Self::AgentTaskFailed,
+        Self::ToolDenied,
+        Self::Error,
+    ];
+
+    /// Stored in `activity_log.kind`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::TurnCompleted => "turn_completed",
+            Self::AgentTaskCompleted => "agent_task_completed",
+            Self::AgentTaskFailed => "agent_task_failed",
+            Self::ToolDenied => "tool_denied",
+            Self::Error => "error",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == value)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ActivityEntry {
+    pub id: String,
+    pub workspace_id: String,
+    pub chat_id: String,
+    pub kind: String,
+    pub summary: String,
+    pub created_at: i64,
+    pub read: bool,
+}
+
+/// Which entries `mark_activity_read` applies to.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "snake_case")]
+pub enum ActivityReadTarget {
+    Ids(Vec<String>),
+    All,
+}
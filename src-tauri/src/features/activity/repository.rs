@@ -0,0 +1,260 @@
+use super::models::{ActivityEntry, ActivityReadTarget};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait ActivityRepository: Send + Sync {
+    /// Insert an entry and drop the workspace's oldest entries beyond `cap`.
+    fn create(&self, entry: &ActivityEntry, cap: u32) -> Result<(), AppError>;
+    fn get_by_workspace_id(
+        &self,
+        workspace_id: &str,
+        unread_only: bool,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<ActivityEntry>, AppError>;
+    fn mark_read(&self, workspace_id: &str, target: &ActivityReadTarget)
+        -> Result<usize, AppError>;
+    fn unread_counts(&self) -> Result<HashMap<String, i64>, AppError>;
+}
+
+pub struct SqliteActivityRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteActivityRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl ActivityRepository for SqliteActivityRepository {
+    fn create(&self, entry: &ActivityEntry, cap: u32) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        insert_entry(&conn, entry, cap)
+    }
+
+    fn get_by_workspace_id(
+        &self,
+        workspace_id: &str,
+        unread_only: bool,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<ActivityEntry>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        list_entries(&conn, workspace_id, unread_only, limit, offset)
+    }
+
+    fn mark_read(
+        &self,
+        workspace_id: &str,
+        target: &ActivityReadTarget,
+    ) -> Result<usize, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        mark_read(&conn, workspace_id, target)
+    }
+
+    fn unread_counts(&self) -> Result<HashMap<String, i64>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        unread_counts(&conn)
+    }
+}
+
+pub fn insert_entry(conn: &Connection, entry: &ActivityEntry, cap: u32) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO activity_log (id, workspace_id, chat_id, kind, summary, created_at, read)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            entry.id,
+            entry.workspace_id,
+            entry.chat_id,
+            entry.kind,
+            entry.summary,
+            entry.created_at,
+            i32::from(entry.read)
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM activity_log WHERE workspace_id = ?1 AND id NOT IN (
+            SELECT id FROM activity_log WHERE workspace_id = ?1
+            ORDER BY created_at DESC, rowid DESC LIMIT ?2
+        )",
+        params![entry.workspace_id, cap],
+    )?;
+    Ok(())
+}
+
+pub fn list_entries(
+    conn: &Connection,
+    workspace_id: &str,
+    unread_only: bool,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<ActivityEntry>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, workspace_id, chat_id, kind, summary, created_at, read
+         FROM activity_log
+         WHERE workspace_id = ?1 AND (?2 = 0 OR read = 0)
+         ORDER BY created_at DESC, rowid DESC
+         LIMIT ?3 OFFSET ?4",
+    )?;
+
+    let entries = stmt
+        .query_map(
+            params![workspace_id, i32::from(unread_only), limit, offset],
+            |row| {
+                Ok(ActivityEntry {
+                    id: row.get(0)?,
+                    workspace_id: row.get(1)?,
+                    chat_id: row.get(2)?,
+                    kind: row.get(3)?,
+                    summary: row.get(4)?,
+                    created_at: row.get(5)?,
+                    read: row.get::<_, i32>(6)? != 0,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Mark entries of a workspace as read; returns how many changed.
+pub fn mark_read(
+    conn: &Connection,
+    workspace_id: &str,
+    target: &ActivityReadTarget,
+) -> Result<usize, AppError> {
+    let changed = match target {
+        ActivityReadTarget::All => conn.execute(
+            "UPDATE activity_log SET read = 1 WHERE workspace_id = ?1 AND read = 0",
+            params![workspace_id],
+        )?,
+        ActivityReadTarget::Ids(ids) => {
+            let mut stmt = conn.prepare(
+                "UPDATE activity_log SET read = 1 WHERE workspace_id = ?1 AND id = ?2 AND read = 0",
+            )?;
+            let mut changed = 0;
+            for id in ids {
+                changed += stmt.execute(params![workspace_id, id])?;
+            }
+            changed
+        }
+    };
+    Ok(changed)
+}
+
+/// Unread entries per workspace; workspaces without any are left out.
+pub fn unread_counts(conn: &Connection) -> Result<HashMap<String, i64>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT workspace_id, COUNT(*) FROM activity_log WHERE read = 0 GROUP BY workspace_id",
+    )?;
+    let counts = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    fn activity(
+        workspace_id: &str,
+        created_at: i64,
+        foreground: bool,
+    ) -> crate::features::activity::ActivityEntry {
+        use crate::features::activity::{ActivityKind, ActivityService};
+
+        let mut entry = ActivityService::entry(
+            workspace_id,
+            "chat-1",
+            ActivityKind::TurnCompleted,
+            "Done",
+            foreground,
+        );
+        entry.created_at = created_at;
+        entry
+    }
+
+    #[test]
+    fn activity_prunes_oldest_entries_beyond_cap() {
+        use crate::features::activity::{insert_entry, list_entries, ACTIVITY_CAP_PER_WORKSPACE};
+
+        let conn = migrated_db();
+        insert_entry(&conn, &activity("w2", 0, false), ACTIVITY_CAP_PER_WORKSPACE).unwrap();
+        for created_at in 1..=505 {
+            insert_entry(
+                &conn,
+                &activity("w1", created_at, false),
+                ACTIVITY_CAP_PER_WORKSPACE,
+            )
+            .unwrap();
+        }
+
+        let kept = list_entries(&conn, "w1", false, 1000, 0).unwrap();
+        assert_eq!(kept.len(), 500);
+        assert_eq!(kept.first().unwrap().created_at, 505);
+        assert_eq!(kept.last().unwrap().created_at, 6);
+        // Other workspaces keep their own entries
+        assert_eq!(list_entries(&conn, "w2", false, 1000, 0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn activity_counts_and_marks_unread_entries() {
+        use crate::features::activity::{
+            insert_entry, list_entries, mark_read, unread_counts, ActivityReadTarget,
+        };
+
+        let conn = migrated_db();
+        let first = activity("w1", 1, false);
+        for entry in [
+            first.clone(),
+            activity("w1", 2, false),
+            activity("w1", 3, false),
+            activity("w1", 4, true),
+            activity("w2", 5, false),
+        ] {
+            insert_entry(&conn, &entry, 500).unwrap();
+        }
+
+        let counts = unread_counts(&conn).unwrap();
+        assert_eq!(counts.get("w1"), Some(&3));
+        assert_eq!(counts.get("w2"), Some(&1));
+        assert_eq!(list_entries(&conn, "w1", true, 50, 0).unwrap().len(), 3);
+
+        // Ids from another workspace are ignored
+        let changed = mark_read(
+            &conn,
+            "w2",
+            &ActivityReadTarget::Ids(vec![first.id.clone()]),
+        )
+        .unwrap();
+        assert_eq!(changed, 0);
+        let changed = mark_read(&conn, "w1", &ActivityReadTarget::Ids(vec![first.id])).unwrap();
+        assert_eq!(changed, 1);
+        assert_eq!(unread_counts(&conn).unwrap().get("w1"), Some(&2));
+
+        assert_eq!(mark_read(&conn, "w1", &ActivityReadTarget::All).unwrap(), 2);
+        let counts = unread_counts(&conn).unwrap();
+        assert_eq!(counts.get("w1"), None);
+        assert_eq!(counts.get("w2"), Some(&1));
+    }
+
+    #[test]
+    fn activity_foreground_turns_are_not_unread() {
+        use crate::features::activity::{insert_entry, list_entries, unread_counts};
+
+        let conn = migrated_db();
+        for created_at in 1..=3 {
+            insert_entry(&conn, &activity("w1", created_at, true), 500).unwrap();
+        }
+
+        assert!(unread_counts(&conn).unwrap().is_empty());
+        assert!(list_entries(&conn, "w1", true, 50, 0).unwrap().is_empty());
+        // They are still part of the feed history
+        assert_eq!(list_entries(&conn, "w1", false, 50, 0).unwrap().len(), 3);
+    }
+}
@@ -0,0 +1,107 @@
+use super::models::{ActivityEntry, ActivityKind, ActivityReadTarget};
+use super::repository::ActivityRepository;
+use crate::error::AppError;
+use crate::events::{emit_event, ActivityAddedEvent};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// Entries kept per workspace; older ones are pruned on insert.
+pub const ACTIVITY_CAP_PER_WORKSPACE: u32 = 500;
+/// Summaries are cut to this many characters.
+const SUMMARY_MAX_CHARS: usize = 200;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+
+pub struct ActivityService {
+    repository: Arc<dyn ActivityRepository>,
+}
+
+impl ActivityService {
+    pub const fn new(repository: Arc<dyn ActivityRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// Build an entry. Foreground activity (in the chat the user invoked) is
+    /// already on screen, so it is stored as read and does not count as unread.
+    pub fn entry(
+        workspace_id: &str,
+        chat_id: &str,
+        kind: ActivityKind,
+        summary: &str,
+        foreground: bool,
+    ) -> ActivityEntry {
+        ActivityEntry {
+            id: uuid::Uuid::new_v4().to_string(),
+            workspace_id: workspace_id.to_string(),
+            chat_id: chat_id.to_string(),
+            kind: kind.as_str().to_string(),
+            summary: summarize(summary),
+            created_at: chrono::Utc::now().timestamp_millis(),
+            read: foreground,
+        }
+    }
+
+    /// Store an entry in the background and notify open feeds. Failures are
+    /// only logged; activity never blocks or fails a chat turn.
+    pub fn record(&self, app: &AppHandle, entry: ActivityEntry) {
+        let repository = self.repository.clone();
+        let app = app.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = repository.create(&entry, ACTIVITY_CAP_PER_WORKSPACE) {
+                tracing::error!(error = ?e, "Failed to record activity");
+                return;
+            }
+            if let Err(e) = emit_event(
+                &app,
+                ActivityAddedEvent {
+                    id: entry.id,
+                    workspace_id: entry.workspace_id,
+                    chat_id: entry.chat_id,
+                    kind: entry.kind,
+                    summary: entry.summary,
+                    created_at: entry.created_at,
+                    read: entry.read,
+                },
+            ) {
+                tracing::warn!(error = %e, "Failed to emit activity-added event");
+            }
+        });
+    }
+
+    pub fn get_activity(
+        &self,
+        workspace_id: &str,
+        unread_only: bool,
+        limit: Option<u32>,
+        offset: Option<u32>,
+    ) -> Result<Vec<ActivityEntry>, AppError> {
+        self.repository.get_by_workspace_id(
+            workspace_id,
+            unread_only,
+            limit.unwrap_or(DEFAULT_PAGE_SIZE),
+            offset.unwrap_or(0),
+        )
+    }
+
+    pub fn mark_read(
+        &self,
+        workspace_id: &str,
+        target: &ActivityReadTarget,
+    ) -> Result<usize, AppError> {
+        self.repository.mark_read(workspace_id, target)
+    }
+
+    pub fn unread_counts(&self) -> Result<HashMap<String, i64>, AppError> {
+        self.repository.unread_counts()
+    }
+}
+
+/// First non-empty line of `text`, cut to `SUMMARY_MAX_CHARS`.
+fn summarize(text: &str) -> String {
+    let line = text
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    crate::lib::text::truncate(line, SUMMARY_MAX_CHARS, "…")
+}
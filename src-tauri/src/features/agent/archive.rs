@@ -0,0 +1,428 @@
+//! Portable agent archives.
+//!
+//! An archive is a zip holding `lunex-agent.json` (an `AgentArchiveManifest`)
+//! and the agent's files under `agent/`. Secret values in a bundled `mcp.json`
+//! are replaced with `{{FIELD}}` placeholders on export, and `content_hash`
+//! covers the files as written so a damaged archive is refused on import.
+
+use crate::features::redaction::secrets::is_secret_field;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+pub const ARCHIVE_FORMAT: &str = "lunex-agent";
+pub const ARCHIVE_FORMAT_VERSION: u32 = 1;
+pub const ARCHIVE_MANIFEST_FILE: &str = "lunex-agent.json";
+pub const ARCHIVE_AGENT_DIR: &str = "agent";
+/// MCP server config an agent may bundle next to its manifest.
+pub const MCP_CONFIG_FILE: &str = "mcp.json";
+
+/// Entries that belong to the local install rather than the agent.
+const SKIPPED_ENTRIES: &[&str] = &[".venv", ".git", "__pycache__", ".DS_Store"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentArchiveManifest {
+    pub format: String,
+    pub format_version: u32,
+    pub agent_id: String,
+    pub name: String,
+    pub version_ref: String,
+    pub exported_at: i64,
+    /// `content_hash` of the files under `agent/`
+    pub content_hash: String,
+    /// Secret fields replaced in `mcp.json`, to be filled in after import
+    #[serde(default)]
+    pub placeholders: Vec<String>,
+}
+
+/// How `import_agent` handles an agent id that is already installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionMode {
+    /// Refuse the import
+    #[default]
+    Fail,
+    /// Install the archive as the new current version of the existing agent
+    Replace,
+    /// Install under a new id next to the existing agent
+    KeepBoth,
+}
+
+/// SHA-256 of every agent file, keyed by `/`-separated relative path.
+pub fn hash_agent_files(root: &Path) -> Result<BTreeMap<String, String>> {
+    let mut hashes = BTreeMap::new();
+    for (relative, path) in agent_files(root)? {
+        let bytes = fs::read(&path).with_context(|| format!("Failed to read {relative}"))?;
+        hashes.insert(relative, hex::encode(Sha256::digest(&bytes)));
+    }
+    Ok(hashes)
+}
+
+/// One hash over a set of file hashes, independent of walk order.
+pub fn content_hash(file_hashes: &BTreeMap<String, String>) -> String {
+    let mut hasher = Sha256::new();
+    for (path, hash) in file_hashes {
+        hasher.update(path.as_bytes());
+        hasher.update([0]);
+        hasher.update(hash.as_bytes());
+        hasher.update(b"\n");
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// Paths whose hash differs between `installed` and `current`, including
+/// added and removed files.
+pub fn changed_files(
+    installed: &BTreeMap<String, String>,
+    current: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let mut changed: Vec<String> = installed
+        .iter()
+        .filter(|(path, hash)| current.get(*path) != Some(*hash))
+        .map(|(path, _)| path.clone())
+        .collect();
+    changed.extend(
+        current
+            .keys()
+            .filter(|path| !installed.contains_key(*path))
+            .cloned(),
+    );
+    changed.sort();
+    changed
+}
+
+/// Write `source_dir` as an archive at `dest`, returning its manifest.
+pub fn write_archive(
+    source_dir: &Path,
+    agent_id: &str,
+    name: &str,
+    version_ref: &str,
+    dest: &Path,
+) -> Result<AgentArchiveManifest> {
+    let mut contents = BTreeMap::new();
+    let mut placeholders = Vec::new();
+    for (relative, path) in agent_files(source_dir)? {
+        let mut bytes = fs::read(&path).with_context(|| format!("Failed to read {relative}"))?;
+        if relative == MCP_CONFIG_FILE {
+            let mut config: serde_json::Value =
+                serde_json::from_slice(&bytes).context("Failed to parse bundled mcp.json")?;
+            strip_secrets(&mut config, &mut placeholders);
+            bytes = serde_json::to_vec_pretty(&config)?;
+        }
+        contents.insert(relative, bytes);
+    }
+    placeholders.sort();
+    placeholders.dedup();
+
+    let file_hashes = contents
+        .iter()
+        .map(|(path, bytes)| (path.clone(), hex::encode(Sha256::digest(bytes))))
+        .collect();
+    let manifest = AgentArchiveManifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        format_version: ARCHIVE_FORMAT_VERSION,
+        agent_id: agent_id.to_string(),
+        name: name.to_string(),
+        version_ref: version_ref.to_string(),
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        content_hash: content_hash(&file_hashes),
+        placeholders,
+    };
+
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let file = fs::File::create(dest).context("Failed to create archive file")?;
+    let mut zip = zip::ZipWriter::new(file);
+    let options = zip::write::SimpleFileOptions::default();
+    zip.start_file(ARCHIVE_MANIFEST_FILE, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    for (relative, bytes) in &contents {
+        zip.start_file(format!("{ARCHIVE_AGENT_DIR}/{relative}"), options)?;
+        zip.write_all(bytes)?;
+    }
+    zip.finish().context("Failed to write archive")?;
+
+    Ok(manifest)
+}
+
+/// Read and check the manifest of an extracted archive, and that the files
+/// under `agent/` match its content hash.
+pub fn read_archive_manifest(extracted: &Path) -> Result<AgentArchiveManifest> {
+    let content = fs::read_to_string(extracted.join(ARCHIVE_MANIFEST_FILE))
+        .context("Not an agent archive: lunex-agent.json is missing")?;
+    let manifest: AgentArchiveManifest =
+        serde_json::from_str(&content).context("Failed to parse lunex-agent.json")?;
+
+    if manifest.format != ARCHIVE_FORMAT {
+        anyhow::bail!("Not an agent archive: unknown format '{}'", manifest.format);
+    }
+    if manifest.format_version != ARCHIVE_FORMAT_VERSION {
+        anyhow::bail!(
+            "Unsupported agent archive version: {}",
+            manifest.format_version
+        );
+    }
+
+    let actual = content_hash(&hash_agent_files(&extracted.join(ARCHIVE_AGENT_DIR))?);
+    if actual != manifest.content_hash {
+        anyhow::bail!("Agent archive is corrupt: content hash does not match");
+    }
+    Ok(manifest)
+}
+
+/// Give the agent in `agent_dir` a new id by rewriting its manifest.yaml.
+pub fn rewrite_manifest_id(agent_dir: &Path, new_id: &str) -> Result<()> {
+    let path = agent_dir.join("manifest.yaml");
+    let mut manifest: serde_yaml::Value = serde_yaml::from_str(&fs::read_to_string(&path)?)
+        .context("Failed to parse manifest.yaml")?;
+    manifest["id"] = serde_yaml::Value::String(new_id.to_string());
+    fs::write(path, serde_yaml::to_string(&manifest)?)?;
+    Ok(())
+}
+
+/// Agent files under `root`, sorted by relative path.
+fn agent_files(root: &Path) -> Result<Vec<(String, std::path::PathBuf)>> {
+    let mut files = Vec::new();
+    collect_files(root, "", &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, std::path::PathBuf)>,
+) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if SKIPPED_ENTRIES.contains(&name.as_str()) {
+            continue;
+        }
+        let relative = format!("{prefix}{name}");
+        if entry.file_type()?.is_dir() {
+            collect_files(&entry.path(), &format!("{relative}/"), files)?;
+        } else {
+            files.push((relative, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+/// Replace secret string values with `{{FIELD}}`. Keys of `env` and
+/// `headers` objects are checked as well as ordinary fields, so
+/// `{"env": {"GITHUB_TOKEN": "..."}}` is caught.
+fn strip_secrets(value: &mut serde_json::Value, placeholders: &mut Vec<String>) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                let secret = is_secret_field(key) || key.eq_ignore_ascii_case("authorization");
+                match field {
+                    serde_json::Value::String(s) if secret && !is_placeholder(s) => {
+                        *s = format!("{{{{{key}}}}}");
+                        placeholders.push(key.clone());
+                    }
+                    _ => strip_secrets(field, placeholders),
+                }
+            }
+        }
+        serde_json::Value::Array(items) => {
+            for item in items {
+                strip_secrets(item, placeholders);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn is_placeholder(value: &str) -> bool {
+    value.starts_with("{{") && value.ends_with("}}")
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::fs;
+    use std::path::Path;
+
+    /// An agent package in `dir` with a bundled MCP config holding a token.
+    pub fn write_agent_package(dir: &Path, id: &str) {
+        fs::create_dir_all(dir.join("tools")).unwrap();
+        fs::create_dir_all(dir.join("instructions")).unwrap();
+        fs::write(
+            dir.join("manifest.yaml"),
+            format!(
+                "schema_version: 1\nid: {id}\nname: Test Agent\ndescription: For tests\nauthor: Lunex\n"
+            ),
+        )
+        .unwrap();
+        fs::write(dir.join("tools/main.py"), "print('hi')\n").unwrap();
+        fs::write(dir.join("tools/requirements.txt"), "").unwrap();
+        fs::write(dir.join("instructions/persona.md"), "You help.\n").unwrap();
+        fs::write(
+            dir.join("mcp.json"),
+            r#"{"mcpServers":{"github":{"command":"npx","env":{"GITHUB_TOKEN":"ghp_live"}}}}"#,
+        )
+        .unwrap();
+    }
+
+    /// A manager whose "uv" is `true`, so venv setup succeeds without Python,
+    /// with `id` installed from a zip of its package.
+    #[cfg(unix)]
+    pub fn agent_manager_with(
+        dir: &Path,
+        id: &str,
+    ) -> crate::features::agent::manager::AgentManager {
+        use crate::features::agent::manager::AgentManager;
+        use std::io::Write;
+        use std::path::PathBuf;
+        use zip::write::SimpleFileOptions;
+        use zip::ZipWriter;
+
+        let package = dir.join("package");
+        write_agent_package(&package, id);
+        let zip_path = dir.join("package.zip");
+        let mut zip = ZipWriter::new(fs::File::create(&zip_path).unwrap());
+        for file in [
+            "manifest.yaml",
+            "tools/main.py",
+            "tools/requirements.txt",
+            "instructions/persona.md",
+            "mcp.json",
+        ] {
+            zip.start_file(file, SimpleFileOptions::default()).unwrap();
+            zip.write_all(&fs::read(package.join(file)).unwrap())
+                .unwrap();
+        }
+        zip.finish().unwrap();
+
+        let manager = AgentManager::new(dir.join("app"), PathBuf::from("true"));
+        assert_eq!(manager.install_from_zip(&zip_path).unwrap(), id);
+        manager
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exported_agent_imports_with_the_same_files_and_secrets_stripped() {
+        use crate::features::agent::archive::{hash_agent_files, CollisionMode};
+        use crate::features::agent::manager::AgentSource;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = agent_manager_with(dir.path(), "com.example.helper");
+        let archive = dir.path().join("helper.zip");
+        let exported = manager
+            .export_agent("com.example.helper", &archive)
+            .unwrap();
+        assert_eq!(exported.agent_id, "com.example.helper");
+        assert_eq!(exported.placeholders, vec!["GITHUB_TOKEN".to_string()]);
+
+        let target = crate::features::agent::manager::AgentManager::new(
+            dir.path().join("other"),
+            std::path::PathBuf::from("true"),
+        );
+        let id = target.import_agent(&archive, CollisionMode::Fail).unwrap();
+        assert_eq!(id, "com.example.helper");
+
+        let source_files =
+            hash_agent_files(&dir.path().join("app/agents/com.example.helper/current")).unwrap();
+        let imported_dir = dir.path().join("other/agents/com.example.helper/current");
+        let imported_files = hash_agent_files(&imported_dir).unwrap();
+        assert_eq!(
+            source_files.keys().collect::<Vec<_>>(),
+            imported_files.keys().collect::<Vec<_>>()
+        );
+        for (path, hash) in &source_files {
+            if path != "mcp.json" {
+                assert_eq!(imported_files.get(path), Some(hash), "{path} changed");
+            }
+        }
+        let mcp = std::fs::read_to_string(imported_dir.join("mcp.json")).unwrap();
+        assert!(mcp.contains("{{GITHUB_TOKEN}}"));
+        assert!(!mcp.contains("ghp_live"));
+
+        let installed = target.list_installed().unwrap();
+        let info = installed[0].install_info.as_ref().unwrap();
+        assert!(matches!(
+            &info.source,
+            AgentSource::Imported { content_hash, .. } if *content_hash == exported.content_hash
+        ));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn importing_an_installed_agent_follows_the_collision_mode() {
+        use crate::features::agent::archive::CollisionMode;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = agent_manager_with(dir.path(), "com.example.helper");
+        let archive = dir.path().join("helper.zip");
+        manager
+            .export_agent("com.example.helper", &archive)
+            .unwrap();
+
+        let err = manager
+            .import_agent(&archive, CollisionMode::Fail)
+            .unwrap_err();
+        assert!(err.to_string().contains("already installed"), "{err}");
+        assert_eq!(manager.list_installed().unwrap().len(), 1);
+
+        let copy = manager
+            .import_agent(&archive, CollisionMode::KeepBoth)
+            .unwrap();
+        assert_eq!(copy, "com.example.helper_imported");
+        let again = manager
+            .import_agent(&archive, CollisionMode::KeepBoth)
+            .unwrap();
+        assert_eq!(again, "com.example.helper_imported2");
+
+        let replaced = manager
+            .import_agent(&archive, CollisionMode::Replace)
+            .unwrap();
+        assert_eq!(replaced, "com.example.helper");
+        let mut ids: Vec<String> = manager
+            .list_installed()
+            .unwrap()
+            .into_iter()
+            .map(|agent| agent.manifest.id)
+            .collect();
+        ids.sort();
+        assert_eq!(
+            ids,
+            [
+                "com.example.helper",
+                "com.example.helper_imported",
+                "com.example.helper_imported2"
+            ]
+        );
+        let current =
+            std::fs::read_link(dir.path().join("app/agents/com.example.helper/current")).unwrap();
+        assert!(current
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .starts_with("imported-"));
+    }
+
+    #[test]
+    fn tampered_agent_archive_is_refused() {
+        use crate::features::agent::archive::{read_archive_manifest, write_archive};
+
+        let dir = tempfile::tempdir().unwrap();
+        let package = dir.path().join("package");
+        write_agent_package(&package, "com.example.helper");
+        let archive = dir.path().join("helper.zip");
+        write_archive(&package, "com.example.helper", "Test Agent", "v1", &archive).unwrap();
+
+        let extracted = dir.path().join("extracted");
+        crate::features::agent::common::extract_zip(&archive, &extracted).unwrap();
+        assert!(read_archive_manifest(&extracted).is_ok());
+
+        std::fs::write(extracted.join("agent/tools/main.py"), "import os\n").unwrap();
+        let err = read_archive_manifest(&extracted).unwrap_err();
+        assert!(err.to_string().contains("content hash"), "{err}");
+    }
+}
@@ -1,4 +1,8 @@
+use crate::features::agent::archive::{AgentArchiveManifest, CollisionMode};
+use crate::features::agent::manager::AgentUpdate;
+use crate::features::chat::tool_permissions;
 use crate::state::AppState;
+use std::collections::BTreeMap;
 use std::path::PathBuf;
 use tauri::State;
 
@@ -67,25 +71,72 @@ pub async fn delete_agent(state: State<'_, AppState>, agent_id: String) -> Resul
 }
 
 #[tauri::command]
-pub async fn update_agent(state: State<'_, AppState>, agent_id: String) -> Result<String, String> {
+pub async fn update_agent(
+    state: State<'_, AppState>,
+    agent_id: String,
+    keep_local_changes: Option<bool>,
+) -> Result<AgentUpdate, String> {
     state
         .agent_manager
-        .update_agent(&agent_id)
+        .update_agent(&agent_id, keep_local_changes)
         .await
         .map_err(|e| e.to_string())
 }
 
+#[tauri::command]
+pub async fn export_agent(
+    state: State<'_, AppState>,
+    agent_id: String,
+    path: String,
+) -> Result<AgentArchiveManifest, String> {
+    state
+        .agent_manager
+        .export_agent(&agent_id, &PathBuf::from(path))
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn import_agent(
+    state: State<'_, AppState>,
+    path: String,
+    collision_mode: Option<CollisionMode>,
+) -> Result<String, String> {
+    state
+        .agent_manager
+        .import_agent(&PathBuf::from(path), collision_mode.unwrap_or_default())
+        .map_err(|e| e.to_string())
+}
+
+/// Replace the permissions of the agent's tools, `"auto"`, `"require"` or
+/// `"deny"` by tool name; tools left out follow the workspace's policy.
+#[tauri::command]
+pub fn set_agent_tool_permissions(
+    state: State<'_, AppState>,
+    agent_id: String,
+    tool_permissions: BTreeMap<String, String>,
+) -> Result<BTreeMap<String, String>, String> {
+    state
+        .agent_manager
+        .set_tool_permissions(&agent_id, tool_permissions)
+        .map_err(|e| e.to_string())
+}
+
 #[derive(serde::Serialize)]
 pub struct AgentInfo {
     pub tools: Vec<crate::features::tool::models::MCPTool>,
     pub instructions: String,
+    /// Permission each tool runs with, by tool name
+    pub tool_permissions: BTreeMap<String, String>,
 }
 
+/// With `workspace_id`, tools the agent does not list show that
+/// workspace's policy for agent tools instead of the default.
 #[tauri::command]
 pub async fn get_agent_info(
     app: tauri::AppHandle,
     state: State<'_, AppState>,
     agent_id: String,
+    workspace_id: Option<String>,
 ) -> Result<AgentInfo, String> {
     let (tools, instructions) = state
         .agent_manager
@@ -93,8 +144,24 @@ pub async fn get_agent_info(
         .await
         .map_err(|e| e.to_string())?;
 
+    let unlisted = match workspace_id {
+        Some(workspace_id) => state
+            .workspace_feature
+            .settings_service
+            .get_stored(&workspace_id)
+            .map_err(|e| e.to_string())?
+            .and_then(|settings| settings.agent_tool_permission),
+        None => None,
+    };
+    let tool_permissions = tool_permissions::effective_agent_permissions(
+        &state.agent_manager.tool_permissions(&agent_id),
+        tools.iter().map(|tool| tool.name.as_str()),
+        unlisted.as_deref(),
+    );
+
     Ok(AgentInfo {
         tools,
         instructions,
+        tool_permissions,
     })
 }
@@ -1,5 +1,6 @@
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 use std::process::Command;
@@ -18,6 +19,13 @@ pub struct Manifest {
     pub repository: Option<String>,
     pub license: Option<String>,
     pub permissions: Option<Vec<String>>,
+    /// `false` leaves the workspace notes out of the agent's system prompt
+    pub include_workspace_notes: Option<bool>,
+    /// `false` leaves a chat's custom instructions out of the agent's system prompt
+    pub include_chat_instructions: Option<bool>,
+    /// Suggested permission of each tool, "auto" or "require"; seeds the
+    /// installed agent's tool permissions
+    pub tool_permissions: Option<BTreeMap<String, String>>,
 }
 
 /// Verify that a directory contains a valid agent package
@@ -1,8 +1,11 @@
+use super::archive::{self, AgentArchiveManifest, CollisionMode};
 use super::common;
 use super::downloader;
+use crate::features::chat::tool_permissions;
 use anyhow::{Context, Result};
 use rust_mcp_sdk::McpClient;
 use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 // use tauri::AppHandle; // If we need to emit events
@@ -18,6 +21,12 @@ pub enum AgentSource {
         revision: Option<String>,
         sub_path: Option<String>,
     },
+    /// Installed from an archive made by `export_agent`
+    #[serde(rename = "imported")]
+    Imported {
+        path: Option<String>,
+        content_hash: String,
+    },
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -25,6 +34,27 @@ pub struct InstallInfo {
     pub source: AgentSource,
     pub installed_at: i64,
     pub updated_at: i64,
+    /// Hash of each file as installed, to detect local edits before an update
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file_hashes: Option<BTreeMap<String, String>>,
+    /// Permission of each of the agent's tools, "auto" or "require"; tools
+    /// not listed follow the workspace's policy for agent tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_permissions: Option<BTreeMap<String, String>>,
+}
+
+/// Outcome of `update_agent`.
+#[derive(serde::Serialize, Clone, Debug)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum AgentUpdate {
+    Updated {
+        agent_id: String,
+        /// Archive of the locally modified copy, when it was kept
+        local_copy: Option<PathBuf>,
+    },
+    /// Nothing was changed: the installed files were edited since install.
+    /// Call again with `keep_local_changes` set to go ahead.
+    LocalChanges { changed_files: Vec<String> },
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
@@ -75,6 +105,7 @@ impl AgentManager {
         let agent_id = self.install_from_directory(&extract_dir, &hash)?;
 
         // 4. Save Install Info
+        let existing_info = self.get_install_info(&agent_id).ok();
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap_or_default()
@@ -86,6 +117,8 @@ impl AgentManager {
             },
             installed_at: now,
             updated_at: now,
+            file_hashes: self.current_file_hashes(&agent_id).ok(),
+            tool_permissions: self.seeded_tool_permissions(&agent_id, existing_info.as_ref()),
         };
 
         self.save_install_info(&agent_id, &info)?;
@@ -136,7 +169,7 @@ impl AgentManager {
             .unwrap_or_default()
             .as_millis() as i64;
 
-        let installed_at = existing_info.map_or(now, |i| i.installed_at);
+        let installed_at = existing_info.as_ref().map_or(now, |i| i.installed_at);
 
         let info = InstallInfo {
             source: AgentSource::Git {
@@ -146,6 +179,8 @@ impl AgentManager {
             },
             installed_at,
             updated_at: now,
+            file_hashes: self.current_file_hashes(&agent_id).ok(),
+            tool_permissions: self.seeded_tool_permissions(&agent_id, existing_info.as_ref()),
         };
 
         self.save_install_info(&agent_id, &info)?;
@@ -153,26 +188,158 @@ impl AgentManager {
         Ok(agent_id)
     }
 
-    /// Update an agent if source allows it
-    pub async fn update_agent(&self, agent_id: &str) -> Result<String> {
+    /// Update an agent if source allows it. When the installed files were
+    /// edited since install, returns `LocalChanges` without touching them
+    /// unless `keep_local_changes` says what to do: `true` exports the local
+    /// copy to `agent_backups/` first, `false` discards it.
+    pub async fn update_agent(
+        &self,
+        agent_id: &str,
+        keep_local_changes: Option<bool>,
+    ) -> Result<AgentUpdate> {
         let info = self
             .get_install_info(agent_id)
             .context("Cannot update agent: Missing installation info")?;
 
-        match info.source {
-            AgentSource::Git {
-                url,
-                revision,
-                sub_path,
-            } => {
-                // Re-install from git
-                self.install_from_git(&url, revision.as_deref(), sub_path.as_deref())
-                    .await
+        let AgentSource::Git {
+            url,
+            revision,
+            sub_path,
+        } = info.source
+        else {
+            anyhow::bail!("Cannot auto-update local or imported agent. Please reinstall it.")
+        };
+
+        let changed_files = match &info.file_hashes {
+            Some(installed) => {
+                archive::changed_files(installed, &self.current_file_hashes(agent_id)?)
             }
-            AgentSource::Local { .. } => {
-                anyhow::bail!("Cannot auto-update local agent. Please reinstall from zip.")
+            None => Vec::new(),
+        };
+        let local_copy = match (changed_files.is_empty(), keep_local_changes) {
+            (true, _) | (false, Some(false)) => None,
+            (false, None) => return Ok(AgentUpdate::LocalChanges { changed_files }),
+            (false, Some(true)) => {
+                let backup = self.base_dir.join("agent_backups").join(format!(
+                    "{agent_id}-{}.zip",
+                    chrono::Utc::now().format("%Y%m%d%H%M%S")
+                ));
+                self.export_agent(agent_id, &backup)
+                    .context("Failed to export local changes before updating")?;
+                Some(backup)
             }
+        };
+
+        // Re-install from git
+        let agent_id = self
+            .install_from_git(&url, revision.as_deref(), sub_path.as_deref())
+            .await?;
+        Ok(AgentUpdate::Updated {
+            agent_id,
+            local_copy,
+        })
+    }
+
+    /// Write the installed agent to a portable archive at `dest`.
+    pub fn export_agent(&self, agent_id: &str, dest: &Path) -> Result<AgentArchiveManifest> {
+        let agent_path = self.agents_dir().join(agent_id).join("current");
+        if !agent_path.exists() {
+            anyhow::bail!("Agent not found: {agent_id}");
+        }
+        let manifest = common::verify_agent_directory(&agent_path)?;
+        let version_ref = fs::read_link(&agent_path)
+            .ok()
+            .and_then(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
+            .unwrap_or_default();
+
+        archive::write_archive(&agent_path, agent_id, &manifest.name, &version_ref, dest)
+    }
+
+    /// Install an agent from an archive made by `export_agent`.
+    pub fn import_agent(
+        &self,
+        archive_path: &Path,
+        collision_mode: CollisionMode,
+    ) -> Result<String> {
+        let extract_dir = self
+            .tmp_dir()
+            .join(format!("import_{}", uuid::Uuid::new_v4()));
+        let result = self.import_extracted(archive_path, &extract_dir, collision_mode);
+        let _ = fs::remove_dir_all(&extract_dir);
+        result
+    }
+
+    fn import_extracted(
+        &self,
+        archive_path: &Path,
+        extract_dir: &Path,
+        collision_mode: CollisionMode,
+    ) -> Result<String> {
+        common::extract_zip(archive_path, extract_dir)?;
+        let archive_manifest = archive::read_archive_manifest(extract_dir)?;
+        let agent_dir = extract_dir.join(archive::ARCHIVE_AGENT_DIR);
+        let manifest = common::verify_agent_directory(&agent_dir)?;
+        if manifest.id != archive_manifest.agent_id {
+            anyhow::bail!(
+                "Agent archive is inconsistent: manifest id '{}' does not match '{}'",
+                manifest.id,
+                archive_manifest.agent_id
+            );
+        }
+
+        let existing = self.agents_dir().join(&manifest.id).join("current");
+        if existing.exists() {
+            match collision_mode {
+                CollisionMode::Fail => {
+                    anyhow::bail!("Agent '{}' is already installed", manifest.id)
+                }
+                CollisionMode::Replace => {}
+                CollisionMode::KeepBoth => {
+                    let new_id = self.free_agent_id(&manifest.id);
+                    archive::rewrite_manifest_id(&agent_dir, &new_id)?;
+                }
+            }
+        }
+
+        let short_hash =
+            &archive_manifest.content_hash[..12.min(archive_manifest.content_hash.len())];
+        let agent_id =
+            self.install_from_directory(&agent_dir, &format!("imported-{short_hash}"))?;
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let existing_info = match collision_mode {
+            CollisionMode::Replace => self.get_install_info(&agent_id).ok(),
+            CollisionMode::Fail | CollisionMode::KeepBoth => None,
+        };
+        let installed_at = existing_info.as_ref().map_or(now, |i| i.installed_at);
+        let info = InstallInfo {
+            source: AgentSource::Imported {
+                path: archive_path.to_str().map(std::string::ToString::to_string),
+                content_hash: archive_manifest.content_hash,
+            },
+            installed_at,
+            updated_at: now,
+            file_hashes: self.current_file_hashes(&agent_id).ok(),
+            tool_permissions: self.seeded_tool_permissions(&agent_id, existing_info.as_ref()),
+        };
+        self.save_install_info(&agent_id, &info)?;
+
+        Ok(agent_id)
+    }
+
+    /// `<id>_imported`, or `<id>_imported2`, ... if that is taken too.
+    fn free_agent_id(&self, agent_id: &str) -> String {
+        let mut candidate = format!("{agent_id}_imported");
+        let mut n = 2;
+        while self.agents_dir().join(&candidate).exists() {
+            candidate = format!("{agent_id}_imported{n}");
+            n += 1;
         }
+        candidate
+    }
+
+    fn current_file_hashes(&self, agent_id: &str) -> Result<BTreeMap<String, String>> {
+        archive::hash_agent_files(&self.agents_dir().join(agent_id).join("current"))
     }
 
     fn save_install_info(&self, agent_id: &str, info: &InstallInfo) -> Result<()> {
@@ -408,6 +575,78 @@ impl AgentManager {
         Ok(content)
     }
 
+    /// Whether the agent's system prompt gets the workspace notes; only an
+    /// agent whose manifest sets `include_workspace_notes: false` opts out.
+    pub fn includes_workspace_notes(&self, agent_id: &str) -> bool {
+        let agent_path = self.agents_dir().join(agent_id).join("current");
+        common::verify_agent_directory(&agent_path)
+            .ok()
+            .is_none_or(|manifest| manifest.include_workspace_notes != Some(false))
+    }
+
+    /// Whether the agent's persona is followed by the chat's custom
+    /// instructions; only `include_chat_instructions: false` opts out.
+    pub fn includes_chat_instructions(&self, agent_id: &str) -> bool {
+        let agent_path = self.agents_dir().join(agent_id).join("current");
+        common::verify_agent_directory(&agent_path)
+            .ok()
+            .is_none_or(|manifest| manifest.include_chat_instructions != Some(false))
+    }
+
+    /// The tool permissions the agent's manifest suggests, without values
+    /// other than "auto" and "require".
+    fn suggested_tool_permissions(&self, agent_id: &str) -> BTreeMap<String, String> {
+        let agent_path = self.agents_dir().join(agent_id).join("current");
+        common::verify_agent_directory(&agent_path)
+            .ok()
+            .and_then(|manifest| manifest.tool_permissions)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, permission)| tool_permissions::validate_permission(permission).is_ok())
+            .collect()
+    }
+
+    /// The manifest's suggestions under the permissions already set for the
+    /// agent, which a reinstall or update keeps.
+    fn seeded_tool_permissions(
+        &self,
+        agent_id: &str,
+        existing_info: Option<&InstallInfo>,
+    ) -> Option<BTreeMap<String, String>> {
+        let mut permissions = self.suggested_tool_permissions(agent_id);
+        if let Some(set) = existing_info.and_then(|info| info.tool_permissions.clone()) {
+            permissions.extend(set);
+        }
+        (!permissions.is_empty()).then_some(permissions)
+    }
+
+    /// The agent's tool permissions, or its manifest's suggestions for an
+    /// agent installed before it had any.
+    pub fn tool_permissions(&self, agent_id: &str) -> BTreeMap<String, String> {
+        self.get_install_info(agent_id)
+            .ok()
+            .and_then(|info| info.tool_permissions)
+            .unwrap_or_else(|| self.suggested_tool_permissions(agent_id))
+    }
+
+    /// Replace the agent's tool permissions.
+    pub fn set_tool_permissions(
+        &self,
+        agent_id: &str,
+        permissions: BTreeMap<String, String>,
+    ) -> Result<BTreeMap<String, String>> {
+        for (tool, permission) in &permissions {
+            tool_permissions::validate_tool_entry(tool, permission)
+                .map_err(|e| anyhow::anyhow!(e.to_string()))?;
+        }
+        let mut info = self
+            .get_install_info(agent_id)
+            .context("Cannot set tool permissions: Missing installation info")?;
+        info.tool_permissions = Some(permissions.clone());
+        self.save_install_info(agent_id, &info)?;
+        Ok(permissions)
+    }
+
     /// Get agent tools and instructions (for display purposes)
     pub async fn get_agent_info(
         &self,
@@ -497,3 +736,98 @@ impl AgentManager {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::features::agent::archive::tests::agent_manager_with;
+
+    #[cfg(unix)]
+    #[test]
+    fn updating_an_agent_with_local_changes_warns_first() {
+        use crate::features::agent::manager::{AgentSource, AgentUpdate, InstallInfo};
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = agent_manager_with(dir.path(), "com.example.helper");
+        let agent_root = dir.path().join("app/agents/com.example.helper");
+
+        // Pretend it came from the hub; the clone below then fails offline
+        let info_path = agent_root.join("install.json");
+        let mut info: InstallInfo =
+            serde_json::from_str(&std::fs::read_to_string(&info_path).unwrap()).unwrap();
+        info.source = AgentSource::Git {
+            url: dir.path().join("missing.git").to_string_lossy().to_string(),
+            revision: None,
+            sub_path: None,
+        };
+        std::fs::write(&info_path, serde_json::to_string(&info).unwrap()).unwrap();
+        std::fs::write(
+            agent_root.join("current/instructions/persona.md"),
+            "You help, tersely.\n",
+        )
+        .unwrap();
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let update = runtime
+            .block_on(manager.update_agent("com.example.helper", None))
+            .unwrap();
+        assert!(matches!(
+            update,
+            AgentUpdate::LocalChanges { ref changed_files }
+                if *changed_files == ["instructions/persona.md"]
+        ));
+        assert_eq!(
+            std::fs::read_to_string(agent_root.join("current/instructions/persona.md")).unwrap(),
+            "You help, tersely.\n"
+        );
+
+        // Keeping the changes exports them before the update is attempted
+        assert!(runtime
+            .block_on(manager.update_agent("com.example.helper", Some(true)))
+            .is_err());
+        let backups: Vec<_> = std::fs::read_dir(dir.path().join("app/agent_backups"))
+            .unwrap()
+            .collect();
+        assert_eq!(backups.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn agent_tool_permissions_fall_back_to_manifest_and_persist_once_set() {
+        use std::collections::BTreeMap;
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let manager = agent_manager_with(dir.path(), "com.example.helper");
+        let mut manifest = std::fs::OpenOptions::new()
+            .append(true)
+            .open(
+                dir.path()
+                    .join("app/agents/com.example.helper/current/manifest.yaml"),
+            )
+            .unwrap();
+        manifest
+            .write_all(
+                b"tool_permissions:\n  deploy: require\n  lookup: auto\n  bogus: sometimes\n",
+            )
+            .unwrap();
+
+        let suggested = manager.tool_permissions("com.example.helper");
+        assert_eq!(suggested.len(), 2);
+        assert_eq!(suggested["deploy"], "require");
+
+        let invalid = BTreeMap::from([("deploy".to_string(), "sometimes".to_string())]);
+        assert!(manager
+            .set_tool_permissions("com.example.helper", invalid)
+            .is_err());
+
+        let set = BTreeMap::from([("deploy".to_string(), "auto".to_string())]);
+        manager
+            .set_tool_permissions("com.example.helper", set.clone())
+            .unwrap();
+        let reopened = crate::features::agent::manager::AgentManager::new(
+            dir.path().join("app"),
+            std::path::PathBuf::from("true"),
+        );
+        assert_eq!(reopened.tool_permissions("com.example.helper"), set);
+    }
+}
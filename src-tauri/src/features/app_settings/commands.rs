@@ -1,5 +1,7 @@
 use super::models::AppSetting;
+use super::time::TimeSettings;
 use crate::error::AppError;
+use crate::features::tool::builtin::BuiltinToolSettings;
 use crate::state::AppState;
 use tauri::State;
 
@@ -33,3 +35,36 @@ pub fn get_all_app_settings(state: State<'_, AppState>) -> Result<Vec<AppSetting
         .get_all()
         .map_err(|e| AppError::Generic(e.to_string()))
 }
+
+#[tauri::command]
+pub fn get_time_settings(state: State<'_, AppState>) -> Result<TimeSettings, AppError> {
+    state.app_settings_service.get_time_settings()
+}
+
+/// Save how timestamps are shown. Rejects zones that are not IANA names.
+#[tauri::command]
+pub fn save_time_settings(
+    settings: TimeSettings,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.app_settings_service.save_time_settings(&settings)
+}
+
+#[tauri::command]
+pub fn get_builtin_tool_settings(
+    state: State<'_, AppState>,
+) -> Result<BuiltinToolSettings, AppError> {
+    state.app_settings_service.get_builtin_tool_settings()
+}
+
+/// Save the directories the builtin file tools may reach; none allows any
+/// absolute path.
+#[tauri::command]
+pub fn save_builtin_tool_settings(
+    settings: BuiltinToolSettings,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state
+        .app_settings_service
+        .save_builtin_tool_settings(&settings)
+}
@@ -2,3 +2,4 @@ pub mod commands;
 pub mod models;
 pub mod repository;
 pub mod service;
+pub mod time;
@@ -1,6 +1,8 @@
 use super::models::AppSetting;
 use super::repository::AppSettingsRepository;
+use super::time::{TimeFormatter, TimeSettings, TIME_SETTINGS_KEY};
 use crate::error::AppError;
+use crate::features::tool::builtin::{BuiltinToolSettings, BUILTIN_TOOL_SETTINGS_KEY};
 use std::sync::Arc;
 
 pub struct AppSettingsService {
@@ -34,4 +36,149 @@ impl AppSettingsService {
     pub fn get_all(&self) -> Result<Vec<AppSetting>, AppError> {
         self.repository.get_all()
     }
+
+    pub fn get_time_settings(&self) -> Result<TimeSettings, AppError> {
+        let Some(json) = self.get_by_key(TIME_SETTINGS_KEY)? else {
+            return Ok(TimeSettings::default());
+        };
+        serde_json::from_str(&json)
+            .map_err(|e| AppError::Generic(format!("Failed to parse time settings: {e}")))
+    }
+
+    /// Validate and store the display settings. Stored timestamps are not
+    /// touched; they render in the new zone from now on.
+    pub fn save_time_settings(&self, settings: &TimeSettings) -> Result<(), AppError> {
+        TimeFormatter::new(settings)?;
+        let json = serde_json::to_string(settings)
+            .map_err(|e| AppError::Generic(format!("Failed to serialize time settings: {e}")))?;
+        self.save(TIME_SETTINGS_KEY.to_string(), json)
+    }
+
+    pub fn get_builtin_tool_settings(&self) -> Result<BuiltinToolSettings, AppError> {
+        let Some(json) = self.get_by_key(BUILTIN_TOOL_SETTINGS_KEY)? else {
+            return Ok(BuiltinToolSettings::default());
+        };
+        serde_json::from_str(&json)
+            .map_err(|e| AppError::Generic(format!("Failed to parse builtin tool settings: {e}")))
+    }
+
+    /// Store where the builtin file tools may reach. Rejects relative
+    /// directories.
+    pub fn save_builtin_tool_settings(
+        &self,
+        settings: &BuiltinToolSettings,
+    ) -> Result<(), AppError> {
+        settings.validate()?;
+        let json = serde_json::to_string(settings).map_err(|e| {
+            AppError::Generic(format!("Failed to serialize builtin tool settings: {e}"))
+        })?;
+        self.save(BUILTIN_TOOL_SETTINGS_KEY.to_string(), json)
+    }
+
+    /// Formatter for the saved settings, or the system defaults when they
+    /// cannot be read.
+    pub fn time_formatter(&self) -> TimeFormatter {
+        self.get_time_settings()
+            .and_then(|settings| TimeFormatter::new(&settings))
+            .unwrap_or_else(|e| {
+                tracing::warn!(error = %e, "Falling back to default time settings");
+                TimeFormatter::default()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[derive(Default)]
+    struct MemoryAppSettingsRepository {
+        settings: std::sync::Mutex<Vec<crate::features::app_settings::models::AppSetting>>,
+    }
+
+    impl crate::features::app_settings::repository::AppSettingsRepository
+        for MemoryAppSettingsRepository
+    {
+        fn save(
+            &self,
+            setting: &crate::features::app_settings::models::AppSetting,
+        ) -> Result<(), crate::error::AppError> {
+            let mut settings = self.settings.lock().unwrap();
+            settings.retain(|stored| stored.key != setting.key);
+            settings.push(setting.clone());
+            Ok(())
+        }
+
+        fn get_by_key(&self, key: &str) -> Result<Option<String>, crate::error::AppError> {
+            Ok(self
+                .settings
+                .lock()
+                .unwrap()
+                .iter()
+                .find(|stored| stored.key == key)
+                .map(|stored| stored.value.clone()))
+        }
+
+        fn get_all(
+            &self,
+        ) -> Result<Vec<crate::features::app_settings::models::AppSetting>, crate::error::AppError>
+        {
+            Ok(self.settings.lock().unwrap().clone())
+        }
+    }
+
+    #[test]
+    fn saving_time_settings_leaves_stored_timestamps_alone() {
+        use crate::features::app_settings::service::AppSettingsService;
+        use crate::features::app_settings::time::{
+            DateStyle, HourCycle, TimeSettings, TIME_SETTINGS_KEY,
+        };
+        use crate::features::maintenance::DB_MAINTENANCE_LAST_REPORT;
+        use std::sync::Arc;
+
+        let service = AppSettingsService::new(Arc::new(MemoryAppSettingsRepository::default()));
+        let report = r#"{"automatic":true,"requested_at":1714573800000,"started_at":1714573800000,"finished_at":1714573805000,"tasks":[]}"#;
+        service
+            .save(DB_MAINTENANCE_LAST_REPORT.to_string(), report.to_string())
+            .unwrap();
+        assert_eq!(
+            service.get_time_settings().unwrap(),
+            TimeSettings::default()
+        );
+
+        let settings = TimeSettings {
+            timezone: Some("Asia/Tokyo".to_string()),
+            hour_cycle: HourCycle::H12,
+            date_style: DateStyle::Long,
+        };
+        service.save_time_settings(&settings).unwrap();
+        assert_eq!(service.get_time_settings().unwrap(), settings);
+        assert!(service
+            .save_time_settings(&TimeSettings {
+                timezone: Some("Not/A_Zone".to_string()),
+                ..TimeSettings::default()
+            })
+            .is_err());
+        assert_eq!(service.get_time_settings().unwrap(), settings);
+
+        // Only the display settings were written; the epoch values are as stored
+        assert_eq!(
+            service
+                .get_by_key(DB_MAINTENANCE_LAST_REPORT)
+                .unwrap()
+                .as_deref(),
+            Some(report)
+        );
+        let mut keys: Vec<String> = service
+            .get_all()
+            .unwrap()
+            .into_iter()
+            .map(|setting| setting.key)
+            .collect();
+        keys.sort();
+        assert_eq!(keys, vec![DB_MAINTENANCE_LAST_REPORT, TIME_SETTINGS_KEY]);
+        let after = service.time_formatter();
+        assert_eq!(
+            after.format_datetime(1_714_573_800_000),
+            "Wednesday, May 1, 2024 11:30 PM JST"
+        );
+    }
 }
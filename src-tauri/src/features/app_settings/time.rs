@@ -0,0 +1,278 @@
+//! Human-readable timestamps.
+//!
+//! Everything is stored as epoch milliseconds; these settings only change how
+//! a timestamp is rendered, and which instant a wall-clock time (such as a
+//! scheduled maintenance time) stands for. Changing them never rewrites a
+//! stored value.
+
+use crate::error::AppError;
+use chrono::{
+    DateTime, Local, LocalResult, NaiveDate, NaiveDateTime, Offset, TimeDelta, TimeZone, Utc,
+};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+
+/// App setting holding `TimeSettings` as JSON.
+pub const TIME_SETTINGS_KEY: &str = "timeSettings";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HourCycle {
+    #[default]
+    H24,
+    H12,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateStyle {
+    /// 2024-05-01
+    #[default]
+    Iso,
+    /// May 1, 2024
+    Medium,
+    /// Wednesday, May 1, 2024
+    Long,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeSettings {
+    /// IANA zone name such as `Europe/Berlin`; `None` follows the system
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default)]
+    pub hour_cycle: HourCycle,
+    #[serde(default)]
+    pub date_style: DateStyle,
+}
+
+impl TimeSettings {
+    fn zone(&self) -> Result<Option<Tz>, AppError> {
+        self.timezone
+            .as_deref()
+            .map(|name| {
+                name.parse::<Tz>().map_err(|_| {
+                    AppError::Validation(format!(
+                        "Unknown time zone: {name} (expected an IANA name such as Europe/Berlin)"
+                    ))
+                })
+            })
+            .transpose()
+    }
+}
+
+/// Renders timestamps and resolves wall-clock times in the configured zone.
+#[derive(Debug, Clone, Default)]
+pub struct TimeFormatter {
+    /// `None` for the system zone
+    zone: Option<Tz>,
+    hour_cycle: HourCycle,
+    date_style: DateStyle,
+}
+
+impl TimeFormatter {
+    /// Fails when the zone is not a known IANA name.
+    pub fn new(settings: &TimeSettings) -> Result<Self, AppError> {
+        Ok(Self {
+            zone: settings.zone()?,
+            hour_cycle: settings.hour_cycle,
+            date_style: settings.date_style,
+        })
+    }
+
+    /// Date, time and zone, e.g. `2024-05-01 14:30 CEST`.
+    pub fn format_datetime(&self, epoch_ms: i64) -> String {
+        let format = format!("{} {} %Z", self.date_format(), self.time_format());
+        self.render(epoch_ms, &format)
+    }
+
+    /// The calendar date of an instant in the configured zone.
+    pub fn local_date(&self, epoch_ms: i64) -> NaiveDate {
+        let utc = utc(epoch_ms);
+        match self.zone {
+            Some(zone) => utc.with_timezone(&zone).date_naive(),
+            None => utc.with_timezone(&Local).date_naive(),
+        }
+    }
+
+    /// The instant a wall-clock time stands for in the configured zone. A
+    /// time skipped by a daylight saving change (02:30 on a spring-forward
+    /// night) moves forward by the gap; a time that happens twice takes the
+    /// first.
+    pub fn resolve_local(&self, local: NaiveDateTime) -> i64 {
+        match self.zone {
+            Some(zone) => resolve_in(&zone, local),
+            None => resolve_in(&Local, local),
+        }
+    }
+
+    const fn date_format(&self) -> &'static str {
+        match self.date_style {
+            DateStyle::Iso => "%Y-%m-%d",
+            DateStyle::Medium => "%b %-d, %Y",
+            DateStyle::Long => "%A, %B %-d, %Y",
+        }
+    }
+
+    const fn time_format(&self) -> &'static str {
+        match self.hour_cycle {
+            HourCycle::H24 => "%H:%M",
+            HourCycle::H12 => "%-I:%M %p",
+        }
+    }
+
+    fn render(&self, epoch_ms: i64, format: &str) -> String {
+        let utc = utc(epoch_ms);
+        match self.zone {
+            Some(zone) => utc.with_timezone(&zone).format(format).to_string(),
+            None => utc.with_timezone(&Local).format(format).to_string(),
+        }
+    }
+}
+
+fn utc(epoch_ms: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(epoch_ms).unwrap_or_default()
+}
+
+fn resolve_in<Z: TimeZone>(zone: &Z, local: NaiveDateTime) -> i64 {
+    match zone.from_local_datetime(&local) {
+        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) => time.timestamp_millis(),
+        LocalResult::None => {
+            // Read the time with the offset in force before the gap
+            let before = zone
+                .offset_from_utc_datetime(&(local - TimeDelta::days(1)))
+                .fix();
+            (local - before).and_utc().timestamp_millis()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn time_formatter(
+        timezone: &str,
+        hour_cycle: crate::features::app_settings::time::HourCycle,
+        date_style: crate::features::app_settings::time::DateStyle,
+    ) -> crate::features::app_settings::time::TimeFormatter {
+        crate::features::app_settings::time::TimeFormatter::new(
+            &crate::features::app_settings::time::TimeSettings {
+                timezone: Some(timezone.to_string()),
+                hour_cycle,
+                date_style,
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn timestamps_render_in_the_configured_zone_and_style() {
+        use crate::features::app_settings::time::{
+            DateStyle, HourCycle, TimeFormatter, TimeSettings,
+        };
+
+        // 2024-05-01 14:30 UTC
+        let at = 1_714_573_800_000;
+        let cases = [
+            (
+                "UTC",
+                HourCycle::H24,
+                DateStyle::Iso,
+                "2024-05-01 14:30 UTC",
+            ),
+            (
+                "Europe/Berlin",
+                HourCycle::H24,
+                DateStyle::Medium,
+                "May 1, 2024 16:30 CEST",
+            ),
+            (
+                "America/New_York",
+                HourCycle::H12,
+                DateStyle::Long,
+                "Wednesday, May 1, 2024 10:30 AM EDT",
+            ),
+            (
+                "Asia/Tokyo",
+                HourCycle::H24,
+                DateStyle::Iso,
+                "2024-05-01 23:30 JST",
+            ),
+            (
+                "Pacific/Auckland",
+                HourCycle::H12,
+                DateStyle::Iso,
+                "2024-05-02 2:30 AM NZST",
+            ),
+        ];
+        for (zone, hour_cycle, date_style, expected) in cases {
+            let formatter = time_formatter(zone, hour_cycle, date_style);
+            assert_eq!(formatter.format_datetime(at), expected, "{zone}");
+        }
+        assert_eq!(
+            time_formatter("Pacific/Auckland", HourCycle::H24, DateStyle::Iso)
+                .local_date(at)
+                .to_string(),
+            "2024-05-02"
+        );
+
+        let unknown = TimeFormatter::new(&TimeSettings {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+            ..TimeSettings::default()
+        });
+        assert!(matches!(
+            unknown,
+            Err(crate::error::AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn wall_clock_times_in_a_dst_gap_move_forward() {
+        use crate::features::app_settings::time::{DateStyle, HourCycle};
+        use crate::features::maintenance::next_maintenance_run;
+        use chrono::{NaiveDate, NaiveTime};
+
+        let new_york = time_formatter("America/New_York", HourCycle::H24, DateStyle::Iso);
+        let at = |date: (i32, u32, u32), hour, minute| {
+            NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+                .unwrap()
+                .and_hms_opt(hour, minute, 0)
+                .unwrap()
+        };
+
+        // 02:30 does not exist on 2024-03-10; it becomes 03:30 EDT
+        let skipped = new_york.resolve_local(at((2024, 3, 10), 2, 30));
+        assert_eq!(skipped, 1_710_055_800_000);
+        assert_eq!(new_york.format_datetime(skipped), "2024-03-10 03:30 EDT");
+        // 01:30 happens twice on 2024-11-03; the first one (EDT) is taken
+        let repeated = new_york.resolve_local(at((2024, 11, 3), 1, 30));
+        assert_eq!(repeated, 1_730_611_800_000);
+        assert_eq!(new_york.format_datetime(repeated), "2024-11-03 01:30 EDT");
+        // Ordinary times are exact
+        assert_eq!(
+            new_york.resolve_local(at((2024, 5, 1), 10, 30)),
+            1_714_573_800_000
+        );
+
+        // A weekly maintenance at 02:30 lands in the gap one week on
+        let last_run = new_york.resolve_local(at((2024, 3, 3), 2, 30));
+        let next = next_maintenance_run(
+            Some("weekly"),
+            NaiveTime::from_hms_opt(2, 30, 0),
+            Some(last_run),
+            last_run,
+            &new_york,
+        );
+        assert_eq!(next, Some(skipped));
+
+        // The same settings in another zone give another instant
+        let tokyo = time_formatter("Asia/Tokyo", HourCycle::H24, DateStyle::Iso);
+        let next_in_tokyo = next_maintenance_run(
+            Some("weekly"),
+            NaiveTime::from_hms_opt(2, 30, 0),
+            Some(last_run),
+            last_run,
+            &tokyo,
+        );
+        assert_ne!(next_in_tokyo, next);
+    }
+}
@@ -0,0 +1,112 @@
+use super::file_uri;
+use super::models::AttachmentVerifyReport;
+use super::service::AttachmentService;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::http::{header::CONTENT_TYPE, Response, StatusCode};
+use tauri::{AppHandle, Manager, State};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+/// Let chats read a file outside the attachments directory for the rest of
+/// the session. The user confirms the resolved path in a native dialog, so
+/// the webview cannot widen file access on its own. Returns whether they did.
+#[tauri::command]
+pub async fn approve_file_access(
+    path: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<bool, AppError> {
+    let service = state.attachment_service.clone();
+    let file = AttachmentService::resolve_file(&path)?;
+    tauri::async_runtime::spawn_blocking(move || {
+        let approved = app
+            .dialog()
+            .message(format!(
+                "Allow chats to read this file until Lunex quits?\n\n{}",
+                file.display()
+            ))
+            .title("Allow file access")
+            .kind(MessageDialogKind::Warning)
+            .buttons(MessageDialogButtons::OkCancelCustom(
+                "Allow".to_string(),
+                "Deny".to_string(),
+            ))
+            .blocking_show();
+        if approved {
+            service.approve_file_access(file)?;
+        }
+        Ok(approved)
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("File access confirmation failed: {e}")))?
+}
+
+/// Check attachments against the database; with `fix`, quarantine broken files
+/// and mark them missing on their messages. Runs off the main thread.
+#[tauri::command]
+pub async fn verify_attachments(
+    fix: bool,
+    state: State<'_, AppState>,
+) -> Result<AttachmentVerifyReport, AppError> {
+    let service = state.attachment_service.clone();
+    tauri::async_runtime::spawn_blocking(move || service.verify_attachments(fix))
+        .await
+        .map_err(|e| AppError::Generic(format!("Attachment check failed: {e}")))?
+}
+
+#[tauri::command]
+pub async fn cancel_verify_attachments(state: State<'_, AppState>) -> Result<(), AppError> {
+    state.attachment_service.cancel_verify();
+    Ok(())
+}
+
+/// Resolve a `lunex-file://` URI to a data URL; `None` when the file is gone.
+#[tauri::command]
+pub fn resolve_lunex_file(
+    uri: String,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
+    state.attachment_service.resolve_file_uri(&uri)
+}
+
+/// Message content with its file references embedded, for copies and
+/// single-file exports.
+#[tauri::command]
+pub fn embed_lunex_files(content: String, state: State<'_, AppState>) -> String {
+    state.attachment_service.embed_file_references(&content)
+}
+
+/// Message content with its referenced files copied into `dest_dir/files`,
+/// for exports written next to their files.
+#[tauri::command]
+pub fn export_lunex_files(
+    content: String,
+    dest_dir: String,
+    state: State<'_, AppState>,
+) -> Result<String, AppError> {
+    state
+        .attachment_service
+        .export_file_references(&content, std::path::Path::new(&dest_dir))
+}
+
+/// Response of the `lunex-file` protocol: the stored file, or 404 once it
+/// is gone.
+pub fn serve_lunex_file(app: &AppHandle, uri: &str) -> Response<Vec<u8>> {
+    let file = app
+        .try_state::<AppState>()
+        .zip(file_uri::parse_file_uri(uri))
+        .and_then(|(state, hash)| state.attachment_service.locate(hash).ok().flatten())
+        .and_then(|path| std::fs::read(&path).ok().map(|bytes| (path, bytes)));
+    let response = match file {
+        Some((path, bytes)) => Response::builder()
+            .header(
+                CONTENT_TYPE,
+                file_uri::mime_type_for_path(&path.to_string_lossy()),
+            )
+            .body(bytes),
+        None => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Vec::new()),
+    };
+    response.unwrap_or_default()
+}
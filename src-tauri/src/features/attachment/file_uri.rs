@@ -0,0 +1,334 @@
+//! `lunex-file://<hash>` references to stored files from message content.
+//!
+//! Generated images and images returned by tools are stored like attachments
+//! and referenced from the message as `![generated image 1](lunex-file://<hash>)`,
+//! so the text and the image stay together when a message is copied,
+//! exported or sent back to a model. The hash is the SHA-256 of the file, so
+//! the same image always gets the same URI.
+
+use crate::error::AppError;
+use base64::{engine::general_purpose, Engine as _};
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+pub const FILE_URI_SCHEME: &str = "lunex-file";
+/// Alt text prefix of images a model generated.
+pub const GENERATED_IMAGE_LABEL: &str = "generated image";
+/// Alt text prefix of images a tool returned.
+pub const TOOL_IMAGE_LABEL: &str = "tool image";
+
+/// `![alt](lunex-file://<hash>)`, with the forms webviews use for custom
+/// schemes (`lunex-file://localhost/<hash>`, `http://lunex-file.localhost/<hash>`).
+static IMAGE_REFERENCE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"!\[([^\]]*)\]\(((?:lunex-file://(?:localhost/)?|https?://lunex-file\.localhost/)([0-9a-f]{64}))\)",
+    )
+    .unwrap()
+});
+
+/// `![alt](data:image/...;base64,...)`, as left by embedding references.
+static EMBEDDED_IMAGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"!\[([^\]]*)\]\((data:image/[a-z0-9.+-]+;base64,[A-Za-z0-9+/=]+)\)").unwrap()
+});
+
+/// The URI of a stored file.
+pub fn file_uri(hash: &str) -> String {
+    format!("{FILE_URI_SCHEME}://{hash}")
+}
+
+/// The hash a file URI points at, `None` for anything else.
+pub fn parse_file_uri(uri: &str) -> Option<&str> {
+    let uri = uri.trim();
+    let rest = uri
+        .strip_prefix("lunex-file://")
+        .map(|r| r.strip_prefix("localhost/").unwrap_or(r))
+        .or_else(|| uri.strip_prefix("http://lunex-file.localhost/"))
+        .or_else(|| uri.strip_prefix("https://lunex-file.localhost/"))?;
+    let hash = rest.trim_end_matches('/');
+    (hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')))
+        .then_some(hash)
+}
+
+/// Markdown image references for stored files, numbered from 1.
+pub fn image_references(label: &str, hashes: &[String]) -> Vec<String> {
+    hashes
+        .iter()
+        .enumerate()
+        .map(|(i, hash)| format!("![{label} {}]({})", i + 1, file_uri(hash)))
+        .collect()
+}
+
+/// `content` with the references appended, each on its own paragraph.
+/// References already in the content are not added again.
+pub fn append_references(content: &str, references: &[String]) -> String {
+    let mut result = content.trim_end().to_string();
+    for reference in references {
+        if result.contains(reference.as_str()) {
+            continue;
+        }
+        if !result.is_empty() {
+            result.push_str("\n\n");
+        }
+        result.push_str(reference);
+    }
+    result
+}
+
+/// Hashes of the files referenced from `content`, in order, without repeats.
+pub fn referenced_hashes(content: &str) -> Vec<String> {
+    let mut hashes: Vec<String> = Vec::new();
+    for captures in IMAGE_REFERENCE.captures_iter(content) {
+        let hash = &captures[3];
+        if !hashes.iter().any(|h| h == hash) {
+            hashes.push(hash.to_string());
+        }
+    }
+    hashes
+}
+
+/// `content` with each file reference pointed at what `resolve` returns for
+/// its hash, e.g. a data URL or a path next to an exported file. A file
+/// that no longer resolves becomes a visible placeholder instead of a broken
+/// image.
+pub fn rewrite_references(
+    content: &str,
+    mut resolve: impl FnMut(&str) -> Option<String>,
+) -> String {
+    IMAGE_REFERENCE
+        .replace_all(content, |captures: &regex::Captures| {
+            let alt = &captures[1];
+            resolve(&captures[3]).map_or_else(
+                || missing_placeholder(alt),
+                |target| format!("![{alt}]({target})"),
+            )
+        })
+        .into_owned()
+}
+
+/// What a reference to a deleted file is shown as.
+pub fn missing_placeholder(alt: &str) -> String {
+    let alt = alt.trim();
+    if alt.is_empty() {
+        "*[image no longer available]*".to_string()
+    } else {
+        format!("*[{alt} no longer available]*")
+    }
+}
+
+/// A data URL with the file's bytes, `None` when it cannot be read.
+pub fn data_url(path: &Path) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let mime_type = mime_type_for_path(&path.to_string_lossy());
+    Some(format!(
+        "data:{mime_type};base64,{}",
+        general_purpose::STANDARD.encode(bytes)
+    ))
+}
+
+/// Mime type and bytes of a base64 data URL, `None` for anything else.
+pub fn decode_data_url(url: &str) -> Option<(String, Vec<u8>)> {
+    let (header, data) = url.strip_prefix("data:")?.split_once(',')?;
+    let mime_type = header.strip_suffix(";base64")?;
+    let bytes = general_purpose::STANDARD.decode(data.trim()).ok()?;
+    Some((mime_type.to_string(), bytes))
+}
+
+/// `content` with images embedded as data URLs, as in single-file exports,
+/// pointed back at stored files. `store` stores an image's bytes and
+/// returns its hash; an image it cannot store stays embedded.
+pub fn store_embedded_images(
+    content: &str,
+    mut store: impl FnMut(&str, &[u8]) -> Option<String>,
+) -> String {
+    EMBEDDED_IMAGE
+        .replace_all(content, |captures: &regex::Captures| {
+            decode_data_url(&captures[2])
+                .and_then(|(mime_type, bytes)| store(&mime_type, &bytes))
+                .map_or_else(
+                    || captures[0].to_string(),
+                    |hash| format!("![{}]({})", &captures[1], file_uri(&hash)),
+                )
+        })
+        .into_owned()
+}
+
+/// Copy the files referenced from `content` into `dest_dir/files_dir_name`
+/// and point the references at the copies, for exports that are written to
+/// disk next to their files. `locate` finds the stored file of a hash.
+pub fn copy_referenced_files(
+    content: &str,
+    dest_dir: &Path,
+    files_dir_name: &str,
+    locate: impl Fn(&str) -> Option<PathBuf>,
+) -> Result<String, AppError> {
+    let files_dir = dest_dir.join(files_dir_name);
+    let mut error = None;
+    let rewritten = rewrite_references(content, |hash| {
+        let source = locate(hash).filter(|p| p.is_file())?;
+        let name = source.file_name()?.to_string_lossy().to_string();
+        let copied =
+            fs::create_dir_all(&files_dir).and_then(|()| fs::copy(&source, files_dir.join(&name)));
+        match copied {
+            Ok(_) => Some(format!("{files_dir_name}/{name}")),
+            Err(e) => {
+                error.get_or_insert(e);
+                None
+            }
+        }
+    });
+    error.map_or(Ok(rewritten), |e| {
+        Err(AppError::Generic(format!(
+            "Failed to copy exported file: {e}"
+        )))
+    })
+}
+
+/// Mime type of a stored file from its extension.
+pub fn mime_type_for_path(path: &str) -> &'static str {
+    let ext = path.rsplit('.').next().unwrap_or_default().to_lowercase();
+    match ext.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "pdf" => "application/pdf",
+        _ => "application/octet-stream",
+    }
+}
+
+/// File extension for an image mime type.
+pub fn extension_for_mime(mime_type: &str) -> &str {
+    match mime_type {
+        "image/jpeg" | "image/jpg" => "jpg",
+        "image/svg+xml" => "svg",
+        _ => mime_type
+            .split('/')
+            .nth(1)
+            .filter(|e| !e.is_empty())
+            .unwrap_or("bin"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn lunex_file_uris_are_stable_per_content() {
+        use crate::features::attachment::file_uri::{
+            append_references, file_uri, image_references, parse_file_uri, referenced_hashes,
+        };
+        use crate::features::attachment::AttachmentService;
+
+        let first = AttachmentService::hash_bytes(b"first image");
+        let second = AttachmentService::hash_bytes(b"second image");
+        assert_eq!(
+            file_uri(&first),
+            file_uri(&AttachmentService::hash_bytes(b"first image"))
+        );
+        assert_ne!(file_uri(&first), file_uri(&second));
+
+        // Forms webviews use for custom schemes resolve to the same hash
+        for uri in [
+            format!("lunex-file://{first}"),
+            format!("lunex-file://localhost/{first}"),
+            format!("http://lunex-file.localhost/{first}/"),
+        ] {
+            assert_eq!(parse_file_uri(&uri), Some(first.as_str()));
+        }
+        assert_eq!(
+            parse_file_uri(&format!("lunex-file://{}", first.to_uppercase())),
+            None
+        );
+        assert_eq!(parse_file_uri("lunex-file://abc"), None);
+        assert_eq!(parse_file_uri(&format!("file://{first}")), None);
+
+        let references = image_references("generated image", &[first.clone(), second.clone()]);
+        assert_eq!(
+            references[1],
+            format!("![generated image 2](lunex-file://{second})")
+        );
+        let content = append_references("Here you go.\n", &references);
+        assert_eq!(
+            content,
+            format!("Here you go.\n\n{}\n\n{}", references[0], references[1])
+        );
+        assert_eq!(append_references(&content, &references), content);
+        assert_eq!(referenced_hashes(&content), [first, second]);
+    }
+
+    #[test]
+    fn lunex_file_references_are_embedded_or_copied_for_exports() {
+        use crate::features::attachment::file_uri::{
+            copy_referenced_files, data_url, file_uri, rewrite_references,
+        };
+        use base64::{engine::general_purpose, Engine as _};
+
+        let dir = tempfile::tempdir().unwrap();
+        let stored = dir.path().join("store");
+        std::fs::create_dir_all(&stored).unwrap();
+        let present = "a".repeat(64);
+        let removed = "b".repeat(64);
+        let stored_path = stored.join(format!("{present}.png"));
+        std::fs::write(&stored_path, b"png bytes").unwrap();
+        let locate = |hash: &str| (hash == present).then(|| stored_path.clone());
+
+        let content = format!(
+            "A chart:\n\n![generated image 1]({})\n\n![tool image 1]({})",
+            file_uri(&present),
+            file_uri(&removed)
+        );
+
+        // Single-file exports and copies carry the bytes inline
+        let embedded = rewrite_references(&content, |hash| locate(hash).and_then(|p| data_url(&p)));
+        let encoded = general_purpose::STANDARD.encode(b"png bytes");
+        assert!(embedded.contains(&format!(
+            "![generated image 1](data:image/png;base64,{encoded})"
+        )));
+        // A collected file shows as a placeholder rather than a broken image
+        assert!(embedded.contains("*[tool image 1 no longer available]*"));
+        assert!(!embedded.contains("lunex-file://"));
+
+        // Exports written to disk get a copy next to them
+        let export_dir = dir.path().join("export");
+        let exported = copy_referenced_files(&content, &export_dir, "files", locate).unwrap();
+        assert!(exported.contains(&format!("![generated image 1](files/{present}.png)")));
+        assert!(exported.contains("*[tool image 1 no longer available]*"));
+        assert_eq!(
+            std::fs::read(export_dir.join("files").join(format!("{present}.png"))).unwrap(),
+            b"png bytes"
+        );
+    }
+
+    #[test]
+    fn embedded_images_are_stored_again_as_file_references() {
+        use crate::features::attachment::file_uri::{
+            decode_data_url, file_uri, store_embedded_images,
+        };
+
+        assert_eq!(
+            decode_data_url("data:image/png;base64,AQID"),
+            Some(("image/png".to_string(), vec![1, 2, 3]))
+        );
+        assert_eq!(decode_data_url("data:text/plain,hello"), None);
+        assert_eq!(decode_data_url("/tmp/file.png"), None);
+
+        let hash = "a".repeat(64);
+        let content =
+            "See ![chart](data:image/png;base64,AQID) and ![bad](data:image/png;base64,AAAA)";
+        let mut stored = Vec::new();
+        let restored = store_embedded_images(content, |mime_type, bytes| {
+            stored.push((mime_type.to_string(), bytes.to_vec()));
+            (bytes == [1, 2, 3]).then(|| hash.clone())
+        });
+        assert_eq!(
+            restored,
+            format!(
+                "See ![chart]({}) and ![bad](data:image/png;base64,AAAA)",
+                file_uri(&hash)
+            )
+        );
+        assert_eq!(stored.len(), 2);
+    }
+}
@@ -0,0 +1,8 @@
+pub mod commands;
+pub mod file_uri;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use repository::*;
+pub use service::*;
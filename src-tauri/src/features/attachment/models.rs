@@ -0,0 +1,62 @@
+use serde::{Deserialize, Serialize};
+
+/// A stored attachment, keyed by the SHA-256 of its decoded bytes.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct FileHash {
+    pub hash: String,
+    pub path: String,
+    pub size: i64,
+    pub ref_count: i64,
+    pub created_at: i64,
+}
+
+/// A provider-side copy of an attachment (e.g. a Google File API upload).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RemoteUpload {
+    pub hash: String,
+    pub provider_key: String,
+    pub file_uri: String,
+    pub file_name: String,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AttachmentIssueKind {
+    /// Referenced by a message but not on disk
+    Missing,
+    /// Zero-byte file
+    Empty,
+    /// Exists but cannot be opened
+    Unreadable,
+    /// `file_hashes` row whose file is gone
+    StaleHashEntry,
+    /// Hash recorded in message metadata with no `file_hashes` row
+    UntrackedHash,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AttachmentIssue {
+    pub kind: AttachmentIssueKind,
+    /// File path, or the content hash for hash-table issues
+    pub path: String,
+    pub message_id: Option<String>,
+}
+
+/// Result of `verify_attachments`. Counts are per distinct file; `examples`
+/// holds the first few issues found.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AttachmentVerifyReport {
+    pub messages_scanned: u64,
+    pub files_checked: u64,
+    pub missing: u64,
+    pub empty: u64,
+    pub unreadable: u64,
+    pub stale_hash_entries: u64,
+    pub untracked_hashes: u64,
+    pub quarantined: u64,
+    pub messages_updated: u64,
+    pub hash_entries_removed: u64,
+    pub cancelled: bool,
+    pub examples: Vec<AttachmentIssue>,
+}
@@ -0,0 +1,260 @@
+use super::models::{FileHash, RemoteUpload};
+use crate::error::AppError;
+use crate::features::encryption::{FieldCipher, ENCRYPTED_PREFIX};
+use rusqlite::params;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait AttachmentRepository: Send + Sync {
+    fn get_by_hash(&self, hash: &str) -> Result<Option<FileHash>, AppError>;
+    fn get_by_path(&self, path: &str) -> Result<Option<FileHash>, AppError>;
+    fn get_all(&self) -> Result<Vec<FileHash>, AppError>;
+    /// Insert `file` unless its hash is already stored, returning the row kept
+    /// under the hash.
+    fn create(&self, file: &FileHash) -> Result<FileHash, AppError>;
+    fn update_path(&self, hash: &str, path: &str) -> Result<(), AppError>;
+    fn adjust_ref_count(&self, hash: &str, delta: i64) -> Result<(), AppError>;
+    fn set_ref_count(&self, hash: &str, ref_count: i64) -> Result<(), AppError>;
+    fn delete(&self, hash: &str) -> Result<(), AppError>;
+    fn get_message_metadata(&self) -> Result<Vec<String>, AppError>;
+    /// Messages with file references, in rowid order after `after_rowid`, as
+    /// `(rowid, id, metadata)`.
+    fn get_message_files_batch(
+        &self,
+        after_rowid: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String, String)>, AppError>;
+    fn update_message_metadata(&self, message_id: &str, metadata: &str) -> Result<(), AppError>;
+    fn get_remote_upload(
+        &self,
+        hash: &str,
+        provider_key: &str,
+    ) -> Result<Option<RemoteUpload>, AppError>;
+    fn save_remote_upload(&self, upload: &RemoteUpload) -> Result<(), AppError>;
+    fn delete_remote_upload(&self, hash: &str, provider_key: &str) -> Result<(), AppError>;
+}
+
+pub struct SqliteAttachmentRepository {
+    app: Arc<AppHandle>,
+    // Message metadata may be encrypted at rest
+    cipher: Arc<FieldCipher>,
+}
+
+impl SqliteAttachmentRepository {
+    pub const fn new(app: Arc<AppHandle>, cipher: Arc<FieldCipher>) -> Self {
+        Self { app, cipher }
+    }
+
+    fn map_file_hash(row: &rusqlite::Row) -> rusqlite::Result<FileHash> {
+        Ok(FileHash {
+            hash: row.get(0)?,
+            path: row.get(1)?,
+            size: row.get(2)?,
+            ref_count: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    }
+}
+
+impl AttachmentRepository for SqliteAttachmentRepository {
+    fn get_by_hash(&self, hash: &str) -> Result<Option<FileHash>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let result = conn.query_row(
+            "SELECT hash, path, size, ref_count, created_at FROM file_hashes WHERE hash = ?1",
+            params![hash],
+            Self::map_file_hash,
+        );
+
+        match result {
+            Ok(file) => Ok(Some(file)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_by_path(&self, path: &str) -> Result<Option<FileHash>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let result = conn.query_row(
+            "SELECT hash, path, size, ref_count, created_at FROM file_hashes WHERE path = ?1",
+            params![path],
+            Self::map_file_hash,
+        );
+
+        match result {
+            Ok(file) => Ok(Some(file)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn get_all(&self) -> Result<Vec<FileHash>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let mut stmt =
+            conn.prepare("SELECT hash, path, size, ref_count, created_at FROM file_hashes")?;
+
+        let files = stmt
+            .query_map([], Self::map_file_hash)?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(files)
+    }
+
+    fn create(&self, file: &FileHash) -> Result<FileHash, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        // A concurrent store of the same content may have inserted it first
+        conn.execute(
+            "INSERT INTO file_hashes (hash, path, size, ref_count, created_at) VALUES (?1, ?2, ?3, ?4, ?5)
+             ON CONFLICT(hash) DO NOTHING",
+            params![file.hash, file.path, file.size, file.ref_count, file.created_at],
+        )?;
+        Ok(conn.query_row(
+            "SELECT hash, path, size, ref_count, created_at FROM file_hashes WHERE hash = ?1",
+            params![file.hash],
+            Self::map_file_hash,
+        )?)
+    }
+
+    fn update_path(&self, hash: &str, path: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE file_hashes SET path = ?1 WHERE hash = ?2",
+            params![path, hash],
+        )?;
+        Ok(())
+    }
+
+    fn adjust_ref_count(&self, hash: &str, delta: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE file_hashes SET ref_count = MAX(ref_count + ?1, 0) WHERE hash = ?2",
+            params![delta, hash],
+        )?;
+        Ok(())
+    }
+
+    fn set_ref_count(&self, hash: &str, ref_count: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE file_hashes SET ref_count = ?1 WHERE hash = ?2",
+            params![ref_count, hash],
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self, hash: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "DELETE FROM remote_file_uploads WHERE hash = ?1",
+            params![hash],
+        )?;
+        conn.execute("DELETE FROM file_hashes WHERE hash = ?1", params![hash])?;
+        Ok(())
+    }
+
+    fn get_message_metadata(&self) -> Result<Vec<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        // Encrypted metadata cannot be matched in SQL, so it is filtered
+        // after decrypting
+        let mut stmt = conn.prepare(
+            "SELECT metadata FROM messages WHERE metadata LIKE '%\"fileHashes\"%' OR metadata LIKE ?1",
+        )?;
+
+        let metadata = stmt
+            .query_map(params![format!("{ENCRYPTED_PREFIX}%")], |row| row.get(0))?
+            .collect::<Result<Vec<String>, _>>()?;
+
+        let mut decrypted = Vec::with_capacity(metadata.len());
+        for metadata in metadata {
+            let metadata = self.cipher.open(metadata)?;
+            if metadata.contains("\"fileHashes\"") {
+                decrypted.push(metadata);
+            }
+        }
+        Ok(decrypted)
+    }
+
+    fn get_message_files_batch(
+        &self,
+        after_rowid: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String, String)>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        // Encrypted rows are returned whether or not they list files
+        let mut stmt = conn.prepare(
+            "SELECT rowid, id, metadata FROM messages
+             WHERE rowid > ?1 AND (metadata LIKE '%\"files\"%' OR metadata LIKE '%\"images\"%' OR metadata LIKE ?3)
+             ORDER BY rowid LIMIT ?2",
+        )?;
+
+        let rows = stmt
+            .query_map(
+                params![after_rowid, limit, format!("{ENCRYPTED_PREFIX}%")],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )?
+            .collect::<Result<Vec<(i64, String, String)>, _>>()?;
+
+        rows.into_iter()
+            .map(|(rowid, id, metadata)| Ok((rowid, id, self.cipher.open(metadata)?)))
+            .collect()
+    }
+
+    fn update_message_metadata(&self, message_id: &str, metadata: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE messages SET metadata = ?1 WHERE id = ?2",
+            params![self.cipher.seal(metadata)?, message_id],
+        )?;
+        Ok(())
+    }
+
+    fn get_remote_upload(
+        &self,
+        hash: &str,
+        provider_key: &str,
+    ) -> Result<Option<RemoteUpload>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let result = conn.query_row(
+            "SELECT hash, provider_key, file_uri, file_name, expires_at FROM remote_file_uploads WHERE hash = ?1 AND provider_key = ?2",
+            params![hash, provider_key],
+            |row| {
+                Ok(RemoteUpload {
+                    hash: row.get(0)?,
+                    provider_key: row.get(1)?,
+                    file_uri: row.get(2)?,
+                    file_name: row.get(3)?,
+                    expires_at: row.get(4)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(upload) => Ok(Some(upload)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save_remote_upload(&self, upload: &RemoteUpload) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO remote_file_uploads (hash, provider_key, file_uri, file_name, expires_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                upload.hash,
+                upload.provider_key,
+                upload.file_uri,
+                upload.file_name,
+                upload.expires_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete_remote_upload(&self, hash: &str, provider_key: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "DELETE FROM remote_file_uploads WHERE hash = ?1 AND provider_key = ?2",
+            params![hash, provider_key],
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,886 @@
+use super::file_uri;
+use super::models::{
+    AttachmentIssue, AttachmentIssueKind, AttachmentVerifyReport, FileHash, RemoteUpload,
+};
+use super::repository::AttachmentRepository;
+use crate::error::AppError;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Google deletes File API uploads after 48 hours. Cached URIs are dropped an
+/// hour earlier so a request never references a file that expires mid-flight.
+pub const GOOGLE_FILE_TTL_MS: i64 = 47 * 60 * 60 * 1000;
+
+/// App setting that runs `verify_attachments` at startup: "report" or "fix".
+pub const VERIFY_ATTACHMENTS_ON_STARTUP: &str = "verifyAttachmentsOnStartup";
+
+/// Messages read per query during `verify_attachments`.
+const VERIFY_BATCH_SIZE: u32 = 200;
+const VERIFY_MAX_EXAMPLES: usize = 20;
+/// Subdirectory of `files_dir` that empty or unreadable attachments are moved to.
+const QUARANTINE_DIR: &str = ".quarantine";
+
+pub struct AttachmentService {
+    repository: Arc<dyn AttachmentRepository>,
+    // Attachments directory (app_data_dir/files); always readable
+    files_dir: PathBuf,
+    // Files outside files_dir the user allowed this session (canonical paths)
+    approved_paths: Mutex<HashSet<PathBuf>>,
+    verify_running: AtomicBool,
+    verify_cancelled: AtomicBool,
+}
+
+impl AttachmentService {
+    pub fn new(repository: Arc<dyn AttachmentRepository>, files_dir: PathBuf) -> Self {
+        Self {
+            repository,
+            files_dir,
+            approved_paths: Mutex::new(HashSet::new()),
+            verify_running: AtomicBool::new(false),
+            verify_cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Where stored attachments live.
+    pub fn files_dir(&self) -> &Path {
+        &self.files_dir
+    }
+
+    fn now() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    /// SHA-256 of the given bytes as lowercase hex.
+    pub fn hash_bytes(bytes: &[u8]) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(bytes);
+        hex::encode(hasher.finalize())
+    }
+
+    /// Extract the attachment hashes recorded in a message's metadata.
+    pub fn extract_file_hashes(metadata: &str) -> Vec<String> {
+        serde_json::from_str::<serde_json::Value>(metadata)
+            .ok()
+            .and_then(|meta| meta.get("fileHashes").cloned())
+            .and_then(|hashes| hashes.as_object().cloned())
+            .map(|hashes| {
+                hashes
+                    .values()
+                    .filter_map(|h| h.as_str().map(std::string::ToString::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Store decoded attachment bytes under `files_dir`, reusing the existing file
+    /// when identical content was stored before. Returns `(path, hash)`.
+    ///
+    /// New entries start with a `ref_count` of 0; references are counted when a
+    /// message carrying the hash is persisted (see `retain`).
+    pub fn store(
+        &self,
+        files_dir: &Path,
+        bytes: &[u8],
+        ext: &str,
+    ) -> Result<(String, String), AppError> {
+        let hash = Self::hash_bytes(bytes);
+
+        if let Some(existing) = self.repository.get_by_hash(&hash)? {
+            if Path::new(&existing.path).exists() {
+                return Ok((existing.path, hash));
+            }
+
+            // The file disappeared from disk; write it again and repoint the entry
+            let file_path = files_dir.join(format!("{hash}.{ext}"));
+            fs::write(&file_path, bytes)
+                .map_err(|e| AppError::Generic(format!("Failed to write file: {e}")))?;
+            let path = file_path.to_string_lossy().to_string();
+            self.repository.update_path(&hash, &path)?;
+            return Ok((path, hash));
+        }
+
+        let file_path = files_dir.join(format!("{hash}.{ext}"));
+        fs::write(&file_path, bytes)
+            .map_err(|e| AppError::Generic(format!("Failed to write file: {e}")))?;
+        let path = file_path.to_string_lossy().to_string();
+
+        let stored = self.repository.create(&FileHash {
+            hash: hash.clone(),
+            path: path.clone(),
+            size: i64::try_from(bytes.len()).unwrap_or(i64::MAX),
+            ref_count: 0,
+            created_at: Self::now(),
+        })?;
+
+        // Another store of the same content won the insert; keep its entry
+        if stored.path != path {
+            let _ = fs::remove_file(&file_path);
+        }
+
+        Ok((stored.path, hash))
+    }
+
+    /// Store an image a model generated or a tool returned, returning its
+    /// hash. The caller records the hash under `fileHashes` of the message
+    /// that references it.
+    pub fn store_image(&self, bytes: &[u8], mime_type: &str) -> Result<String, AppError> {
+        fs::create_dir_all(&self.files_dir)
+            .map_err(|e| AppError::Generic(format!("Failed to create files directory: {e}")))?;
+        let ext = file_uri::extension_for_mime(mime_type);
+        let (_path, hash) = self.store(&self.files_dir, bytes, ext)?;
+        Ok(hash)
+    }
+
+    /// Copy the file a reference from elsewhere names into the attachments
+    /// directory, returning its stored path and hash. Only data URLs and
+    /// paths `is_file_access_allowed` lets through are read; `None` for the
+    /// rest and for files that cannot be stored.
+    pub fn store_reference(&self, reference: &str) -> Option<(String, String)> {
+        let (bytes, extension) =
+            if let Some((mime_type, bytes)) = file_uri::decode_data_url(reference) {
+                (bytes, file_uri::extension_for_mime(&mime_type).to_string())
+            } else if self.is_file_access_allowed(reference) && Path::new(reference).is_file() {
+                let extension = Path::new(reference).extension().map_or_else(
+                    || "bin".to_string(),
+                    |ext| ext.to_string_lossy().to_string(),
+                );
+                (fs::read(reference).ok()?, extension)
+            } else {
+                return None;
+            };
+        fs::create_dir_all(&self.files_dir).ok()?;
+        self.store(&self.files_dir, &bytes, &extension)
+            .map_err(|e| tracing::warn!(error = %e, "Failed to store imported file"))
+            .ok()
+    }
+
+    /// The stored file a hash points at, `None` once it is gone from disk.
+    pub fn locate(&self, hash: &str) -> Result<Option<PathBuf>, AppError> {
+        Ok(self
+            .repository
+            .get_by_hash(hash)?
+            .map(|file| PathBuf::from(file.path))
+            .filter(|path| path.is_file()))
+    }
+
+    /// A `lunex-file://` URI resolved to a data URL. `None` when the file was
+    /// removed, so the UI can show a placeholder.
+    pub fn resolve_file_uri(&self, uri: &str) -> Result<Option<String>, AppError> {
+        let hash = file_uri::parse_file_uri(uri)
+            .ok_or_else(|| AppError::Validation(format!("Not a file URI: {uri}")))?;
+        Ok(self
+            .locate(hash)?
+            .and_then(|path| file_uri::data_url(&path)))
+    }
+
+    /// `content` with its file references embedded as data URLs, for copies
+    /// and single-file exports.
+    pub fn embed_file_references(&self, content: &str) -> String {
+        file_uri::rewrite_references(content, |hash| {
+            self.locate(hash)
+                .map_err(|e| tracing::warn!(hash, error = %e, "Failed to look up file"))
+                .ok()
+                .flatten()
+                .and_then(|path| file_uri::data_url(&path))
+        })
+    }
+
+    /// `content` with its referenced files copied next to an export in `dest_dir`.
+    pub fn export_file_references(
+        &self,
+        content: &str,
+        dest_dir: &Path,
+    ) -> Result<String, AppError> {
+        file_uri::copy_referenced_files(content, dest_dir, "files", |hash| {
+            self.locate(hash).ok().flatten()
+        })
+    }
+
+    /// Whether a file reference from message metadata may be read and sent to a
+    /// provider: data URLs and remote URLs, files inside the attachments
+    /// directory, and files the user approved this session.
+    ///
+    /// Paths are canonicalized first, so `..` segments and symlinks cannot be
+    /// used to escape the attachments directory.
+    pub fn is_file_access_allowed(&self, path_or_data: &str) -> bool {
+        if path_or_data.starts_with("data:")
+            || path_or_data.starts_with("http://")
+            || path_or_data.starts_with("https://")
+        {
+            return true;
+        }
+
+        let Ok(canonical) = fs::canonicalize(path_or_data) else {
+            return false;
+        };
+
+        if fs::canonicalize(&self.files_dir).is_ok_and(|dir| canonical.starts_with(dir)) {
+            return true;
+        }
+
+        self.approved_paths
+            .lock()
+            .is_ok_and(|approved| approved.contains(&canonical))
+    }
+
+    /// The file `path` names, with links resolved, as the user is asked to
+    /// confirm it.
+    pub fn resolve_file(path: &str) -> Result<PathBuf, AppError> {
+        let canonical = fs::canonicalize(path)
+            .map_err(|e| AppError::Validation(format!("Cannot access file {path}: {e}")))?;
+        if !canonical.is_file() {
+            return Err(AppError::Validation(format!("Not a file: {path}")));
+        }
+        Ok(canonical)
+    }
+
+    /// Allow a file from `resolve_file` outside the attachments directory to
+    /// be read for the rest of the session, after the user confirmed it.
+    pub fn approve_file_access(&self, canonical: PathBuf) -> Result<(), AppError> {
+        self.approved_paths
+            .lock()
+            .map_err(|e| AppError::Generic(format!("Failed to lock approved paths: {e}")))?
+            .insert(canonical);
+        Ok(())
+    }
+
+    /// Look up the content hash of a previously stored attachment by its path.
+    pub fn hash_for_path(&self, path: &str) -> Result<Option<String>, AppError> {
+        Ok(self.repository.get_by_path(path)?.map(|f| f.hash))
+    }
+
+    /// Count one reference for every attachment listed in the metadata.
+    pub fn retain(&self, metadata: Option<&str>) -> Result<(), AppError> {
+        if let Some(metadata) = metadata {
+            for hash in Self::extract_file_hashes(metadata) {
+                self.repository.adjust_ref_count(&hash, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Drop one reference for every attachment listed in the metadata.
+    /// Files are only removed from disk by `collect_garbage`.
+    pub fn release(&self, metadata: Option<&str>) -> Result<(), AppError> {
+        if let Some(metadata) = metadata {
+            for hash in Self::extract_file_hashes(metadata) {
+                self.repository.adjust_ref_count(&hash, -1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reconcile ref counts against the messages that actually reference each
+    /// attachment, then delete unreferenced files. Returns the number removed.
+    ///
+    /// Messages removed through cascading deletes never pass through `release`,
+    /// so counts are recomputed from message metadata rather than trusted.
+    pub fn collect_garbage(&self) -> Result<usize, AppError> {
+        let mut references: HashMap<String, i64> = HashMap::new();
+        for metadata in self.repository.get_message_metadata()? {
+            for hash in Self::extract_file_hashes(&metadata) {
+                *references.entry(hash).or_insert(0) += 1;
+            }
+        }
+
+        let mut removed = 0;
+        for file in self.repository.get_all()? {
+            let count = references.get(&file.hash).copied().unwrap_or(0);
+            if count > 0 {
+                if count != file.ref_count {
+                    self.repository.set_ref_count(&file.hash, count)?;
+                }
+                continue;
+            }
+
+            if let Err(e) = fs::remove_file(&file.path) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(path = %file.path, error = %e, "Failed to remove attachment");
+                    continue;
+                }
+            }
+            self.repository.delete(&file.hash)?;
+            removed += 1;
+        }
+
+        Ok(removed)
+    }
+
+    /// Check that every file referenced from message metadata is present and
+    /// readable, and that the hash table matches the disk.
+    ///
+    /// With `fix`, empty or unreadable files in the attachments directory are
+    /// moved to `files/.quarantine`, affected messages get a `missingFiles`
+    /// list so the UI can show a placeholder, and hash entries whose file is
+    /// gone are removed. Messages are read in batches; `cancel_verify` stops
+    /// the scan after the current batch.
+    pub fn verify_attachments(&self, fix: bool) -> Result<AttachmentVerifyReport, AppError> {
+        if self.verify_running.swap(true, Ordering::SeqCst) {
+            return Err(AppError::Validation(
+                "An attachment check is already running".to_string(),
+            ));
+        }
+        self.verify_cancelled.store(false, Ordering::SeqCst);
+        let result = self.run_verify(fix);
+        self.verify_running.store(false, Ordering::SeqCst);
+
+        let report = result?;
+        log::info!(
+            target: "diagnostics",
+            "Attachment check (fix: {fix}): {}",
+            serde_json::to_string(&report).unwrap_or_default()
+        );
+        if report.quarantined > 0 {
+            self.write_quarantine_report(&report);
+        }
+        Ok(report)
+    }
+
+    pub fn cancel_verify(&self) {
+        self.verify_cancelled.store(true, Ordering::SeqCst);
+    }
+
+    fn run_verify(&self, fix: bool) -> Result<AttachmentVerifyReport, AppError> {
+        let mut report = AttachmentVerifyReport::default();
+        // Result per path, so a file shared by many messages is checked once
+        let mut checked: HashMap<String, Option<AttachmentIssueKind>> = HashMap::new();
+        let mut known_hashes: HashMap<String, bool> = HashMap::new();
+        let mut after_rowid = 0;
+
+        loop {
+            if self.verify_cancelled.load(Ordering::SeqCst) {
+                report.cancelled = true;
+                return Ok(report);
+            }
+
+            let batch = self
+                .repository
+                .get_message_files_batch(after_rowid, VERIFY_BATCH_SIZE)?;
+            let Some((last_rowid, _, _)) = batch.last() else {
+                break;
+            };
+            after_rowid = *last_rowid;
+
+            for (_, message_id, metadata) in &batch {
+                report.messages_scanned += 1;
+                let Ok(mut meta) = serde_json::from_str::<serde_json::Value>(metadata) else {
+                    continue;
+                };
+
+                let mut unavailable = Vec::new();
+                for path in Self::referenced_paths(&meta) {
+                    let issue = if let Some(issue) = checked.get(&path) {
+                        *issue
+                    } else {
+                        report.files_checked += 1;
+                        let issue = self.check_file(&path, fix, &mut report);
+                        if let Some(kind) = issue {
+                            Self::record_issue(&mut report, kind, &path, Some(message_id));
+                        }
+                        checked.insert(path.clone(), issue);
+                        issue
+                    };
+                    if issue.is_some() {
+                        unavailable.push(path);
+                    }
+                }
+
+                for hash in Self::extract_file_hashes(metadata) {
+                    if known_hashes.contains_key(&hash) {
+                        continue;
+                    }
+                    let known = self.repository.get_by_hash(&hash)?.is_some();
+                    if !known {
+                        Self::record_issue(
+                            &mut report,
+                            AttachmentIssueKind::UntrackedHash,
+                            &hash,
+                            Some(message_id),
+                        );
+                    }
+                    known_hashes.insert(hash, known);
+                }
+
+                if fix && Self::mark_missing(&mut meta, &unavailable) {
+                    self.repository
+                        .update_message_metadata(message_id, &meta.to_string())?;
+                    report.messages_updated += 1;
+                }
+            }
+        }
+
+        for file in self.repository.get_all()? {
+            if Path::new(&file.path).exists() {
+                continue;
+            }
+            Self::record_issue(
+                &mut report,
+                AttachmentIssueKind::StaleHashEntry,
+                &file.hash,
+                None,
+            );
+            if fix {
+                self.repository.delete(&file.hash)?;
+                report.hash_entries_removed += 1;
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Local file paths listed under `files` (or the older `images`) in message
+    /// metadata. Data URLs and remote URLs are skipped.
+    fn referenced_paths(meta: &serde_json::Value) -> Vec<String> {
+        ["files", "images"]
+            .iter()
+            .filter_map(|key| meta.get(key).and_then(serde_json::Value::as_array))
+            .flatten()
+            .filter_map(|entry| {
+                entry
+                    .as_str()
+                    .or_else(|| entry.get("path").and_then(serde_json::Value::as_str))
+            })
+            .filter(|path| {
+                !path.starts_with("data:")
+                    && !path.starts_with("http://")
+                    && !path.starts_with("https://")
+            })
+            .map(str::to_string)
+            .collect()
+    }
+
+    /// Classify one file, quarantining it when `fix` is set and it is empty or
+    /// unreadable. Only files inside the attachments directory are moved.
+    fn check_file(
+        &self,
+        path: &str,
+        fix: bool,
+        report: &mut AttachmentVerifyReport,
+    ) -> Option<AttachmentIssueKind> {
+        let kind = match fs::metadata(path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Some(AttachmentIssueKind::Missing)
+            }
+            Err(_) => AttachmentIssueKind::Unreadable,
+            Ok(meta) if meta.len() == 0 => AttachmentIssueKind::Empty,
+            Ok(_) => match fs::File::open(path) {
+                Ok(_) => return None,
+                Err(_) => AttachmentIssueKind::Unreadable,
+            },
+        };
+
+        if fix && Path::new(path).starts_with(&self.files_dir) {
+            match self.quarantine(Path::new(path)) {
+                Ok(()) => report.quarantined += 1,
+                Err(e) => {
+                    tracing::warn!(path = %path, error = %e, "Failed to quarantine attachment");
+                }
+            }
+        }
+        Some(kind)
+    }
+
+    fn quarantine(&self, path: &Path) -> Result<(), AppError> {
+        let dir = self.files_dir.join(QUARANTINE_DIR);
+        fs::create_dir_all(&dir).map_err(|e| {
+            AppError::Generic(format!("Failed to create quarantine directory: {e}"))
+        })?;
+
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut target = dir.join(&name);
+        if target.exists() {
+            target = dir.join(format!("{}-{name}", Self::now()));
+        }
+        fs::rename(path, &target)
+            .map_err(|e| AppError::Generic(format!("Failed to move file: {e}")))
+    }
+
+    fn write_quarantine_report(&self, report: &AttachmentVerifyReport) {
+        let path = self
+            .files_dir
+            .join(QUARANTINE_DIR)
+            .join(format!("report-{}.json", Self::now()));
+        let written = serde_json::to_string_pretty(report)
+            .map_err(|e| e.to_string())
+            .and_then(|json| fs::write(&path, json).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            tracing::warn!(error = %e, "Failed to write quarantine report");
+        }
+    }
+
+    /// Add the paths to the message's `missingFiles` list. Returns whether the
+    /// metadata changed.
+    fn mark_missing(meta: &mut serde_json::Value, paths: &[String]) -> bool {
+        let Some(obj) = meta.as_object_mut() else {
+            return false;
+        };
+        let mut missing: Vec<String> = obj
+            .get("missingFiles")
+            .and_then(serde_json::Value::as_array)
+            .map(|list| {
+                list.iter()
+                    .filter_map(|p| p.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let before = missing.len();
+        for path in paths {
+            if !missing.contains(path) {
+                missing.push(path.clone());
+            }
+        }
+        if missing.len() == before {
+            return false;
+        }
+
+        obj.insert("missingFiles".to_string(), serde_json::json!(missing));
+        true
+    }
+
+    fn record_issue(
+        report: &mut AttachmentVerifyReport,
+        kind: AttachmentIssueKind,
+        path: &str,
+        message_id: Option<&String>,
+    ) {
+        match kind {
+            AttachmentIssueKind::Missing => report.missing += 1,
+            AttachmentIssueKind::Empty => report.empty += 1,
+            AttachmentIssueKind::Unreadable => report.unreadable += 1,
+            AttachmentIssueKind::StaleHashEntry => report.stale_hash_entries += 1,
+            AttachmentIssueKind::UntrackedHash => report.untracked_hashes += 1,
+        }
+        if report.examples.len() < VERIFY_MAX_EXAMPLES {
+            report.examples.push(AttachmentIssue {
+                kind,
+                path: path.to_string(),
+                message_id: message_id.cloned(),
+            });
+        }
+    }
+
+    /// Get a cached provider upload for this content, if it has not expired.
+    pub fn get_remote_upload(
+        &self,
+        hash: &str,
+        provider_key: &str,
+    ) -> Result<Option<RemoteUpload>, AppError> {
+        match self.repository.get_remote_upload(hash, provider_key)? {
+            Some(upload) if upload.expires_at > Self::now() => Ok(Some(upload)),
+            Some(_) => {
+                self.repository.delete_remote_upload(hash, provider_key)?;
+                Ok(None)
+            }
+            None => Ok(None),
+        }
+    }
+
+    pub fn save_remote_upload(
+        &self,
+        hash: &str,
+        provider_key: &str,
+        file_uri: &str,
+        file_name: &str,
+        ttl_ms: i64,
+    ) -> Result<(), AppError> {
+        self.repository.save_remote_upload(&RemoteUpload {
+            hash: hash.to_string(),
+            provider_key: provider_key.to_string(),
+            file_uri: file_uri.to_string(),
+            file_name: file_name.to_string(),
+            expires_at: Self::now() + ttl_ms,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::MemoryAttachmentRepository;
+
+    #[test]
+    fn concurrent_stores_of_the_same_content_share_one_entry() {
+        use crate::features::attachment::models::FileHash;
+        use crate::features::attachment::{AttachmentRepository, AttachmentService};
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repository = Arc::new(MemoryAttachmentRepository::default());
+        let attachments = AttachmentService::new(repository.clone(), dir.path().to_path_buf());
+
+        // The other store wins the insert after this one found nothing
+        let hash = AttachmentService::hash_bytes(b"png bytes");
+        let winner = dir.path().join(format!("{hash}.jpg"));
+        std::fs::write(&winner, b"png bytes").unwrap();
+        let winner = winner.to_string_lossy().to_string();
+        *repository.racing.lock().unwrap() = Some(FileHash {
+            hash: hash.clone(),
+            path: winner.clone(),
+            size: 9,
+            ref_count: 1,
+            created_at: 0,
+        });
+
+        assert_eq!(
+            attachments.store(dir.path(), b"png bytes", "png").unwrap(),
+            (winner.clone(), hash.clone())
+        );
+        let stored = repository.get_by_hash(&hash).unwrap().unwrap();
+        assert_eq!((stored.path, stored.ref_count), (winner, 1));
+        assert_eq!(repository.get_all().unwrap().len(), 1);
+        assert!(!dir.path().join(format!("{hash}.png")).exists());
+    }
+
+    #[test]
+    fn attachments_are_stored_once_and_counted_per_message() {
+        use crate::features::attachment::{AttachmentRepository, AttachmentService};
+        use crate::features::message::MessageService;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let repository = Arc::new(MemoryAttachmentRepository::default());
+        let attachments = Arc::new(AttachmentService::new(
+            repository.clone(),
+            dir.path().to_path_buf(),
+        ));
+
+        // The same bytes are written once, whatever the upload
+        let (path, hash) = attachments.store(dir.path(), b"png bytes", "png").unwrap();
+        assert_eq!(
+            attachments.store(dir.path(), b"png bytes", "png").unwrap(),
+            (path.clone(), hash.clone())
+        );
+        let (_, other) = attachments.store(dir.path(), b"other", "png").unwrap();
+        assert_ne!(other, hash);
+        assert_eq!(repository.get_all().unwrap().len(), 2);
+        // A file lost from disk is written again under the same entry
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            attachments
+                .store(dir.path(), b"png bytes", "png")
+                .unwrap()
+                .0,
+            path
+        );
+        assert_eq!(std::fs::read(&path).unwrap(), b"png bytes");
+
+        let ref_count = |hash: &str| repository.get_by_hash(hash).unwrap().unwrap().ref_count;
+        let messages = MessageService::new(repository.messages.clone(), attachments.clone());
+        let metadata = serde_json::json!({ "fileHashes": { "photo.png": hash } }).to_string();
+        for id in ["u1", "u2"] {
+            messages
+                .create(
+                    id.to_string(),
+                    "c1".to_string(),
+                    "user".to_string(),
+                    "See attached".to_string(),
+                    Some(100),
+                    None,
+                    None,
+                    Some(metadata.clone()),
+                )
+                .unwrap();
+        }
+        assert_eq!((ref_count(&hash), ref_count(&other)), (2, 0));
+
+        messages.delete("u1".to_string()).unwrap();
+        assert_eq!(ref_count(&hash), 1);
+
+        // Unreferenced files go; referenced ones stay
+        assert_eq!(attachments.collect_garbage().unwrap(), 1);
+        assert!(repository.get_by_hash(&other).unwrap().is_none());
+        assert!(std::path::Path::new(&path).exists());
+
+        // A chat deleted in one statement never releases its messages
+        crate::features::message::MessageRepository::delete(&*repository.messages, "u2").unwrap();
+        assert_eq!(ref_count(&hash), 1);
+        assert_eq!(attachments.collect_garbage().unwrap(), 1);
+        assert!(repository.get_by_hash(&hash).unwrap().is_none());
+        assert!(!std::path::Path::new(&path).exists());
+    }
+
+    #[test]
+    fn remote_upload_uris_expire_before_the_provider_drops_them() {
+        use crate::features::attachment::{
+            AttachmentRepository, AttachmentService, GOOGLE_FILE_TTL_MS,
+        };
+        use std::sync::Arc;
+
+        // Google keeps uploads for 48 hours; the cached URI is dropped sooner
+        const { assert!(GOOGLE_FILE_TTL_MS < 48 * 60 * 60 * 1000) };
+
+        let repository = Arc::new(MemoryAttachmentRepository::default());
+        let attachments = AttachmentService::new(repository.clone(), std::path::PathBuf::new());
+        let account = "https://generativelanguage.googleapis.com|key";
+        attachments
+            .save_remote_upload("h1", account, "files/abc", "photo.png", GOOGLE_FILE_TTL_MS)
+            .unwrap();
+        let upload = attachments
+            .get_remote_upload("h1", account)
+            .unwrap()
+            .unwrap();
+        assert_eq!(upload.file_uri, "files/abc");
+        // Uploads are per account
+        assert!(attachments
+            .get_remote_upload("h1", "https://other|key")
+            .unwrap()
+            .is_none());
+
+        // Past its TTL the upload is forgotten and must be sent again
+        attachments
+            .save_remote_upload("h1", account, "files/abc", "photo.png", -1)
+            .unwrap();
+        assert!(attachments
+            .get_remote_upload("h1", account)
+            .unwrap()
+            .is_none());
+        assert!(repository
+            .get_remote_upload("h1", account)
+            .unwrap()
+            .is_none());
+    }
+
+    /// A user message whose metadata lists `files`; `timestamp` orders it.
+    fn message_with_files(
+        id: &str,
+        timestamp: i64,
+        files: &[&std::path::Path],
+    ) -> crate::features::message::Message {
+        crate::features::message::Message {
+            id: id.to_string(),
+            chat_id: "c1".to_string(),
+            role: "user".to_string(),
+            content: "See attached".to_string(),
+            reasoning: None,
+            timestamp,
+            assistant_message_id: None,
+            tool_call_id: None,
+            metadata: Some(serde_json::json!({ "files": files }).to_string()),
+            reply_to_message_id: None,
+            branch_id: None,
+        }
+    }
+
+    #[test]
+    fn integrity_check_reports_missing_files_and_quarantines_empty_ones() {
+        use crate::features::attachment::models::AttachmentIssueKind;
+        use crate::features::attachment::AttachmentService;
+        use crate::features::message::MessageRepository;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let files_dir = dir.path().join("files");
+        std::fs::create_dir(&files_dir).unwrap();
+        let photo = files_dir.join("photo.png");
+        let empty = files_dir.join("empty.png");
+        let gone = files_dir.join("gone.png");
+        std::fs::write(&photo, b"png bytes").unwrap();
+        std::fs::write(&empty, b"").unwrap();
+
+        let repository = Arc::new(MemoryAttachmentRepository::default());
+        let attachments = AttachmentService::new(repository.clone(), files_dir.clone());
+        for message in [
+            message_with_files("u1", 1, &[&photo, &empty]),
+            message_with_files("u2", 2, &[&gone, &empty]),
+        ] {
+            repository.messages.create(&message).unwrap();
+        }
+        let missing_files = |id: &str| {
+            let message = repository.messages.get_by_id(id).unwrap().unwrap();
+            let metadata: serde_json::Value =
+                serde_json::from_str(message.metadata.as_deref().unwrap()).unwrap();
+            metadata["missingFiles"].clone()
+        };
+
+        // Without `fix` the check only reports; a file shared by messages counts once
+        let report = attachments.verify_attachments(false).unwrap();
+        assert_eq!((report.messages_scanned, report.files_checked), (2, 3));
+        assert_eq!(
+            (report.missing, report.empty, report.quarantined),
+            (1, 1, 0)
+        );
+        assert!(report.examples.iter().any(|issue| {
+            issue.kind == AttachmentIssueKind::Missing
+                && issue.path == gone.to_string_lossy()
+                && issue.message_id.as_deref() == Some("u2")
+        }));
+        assert!(empty.exists());
+        assert!(missing_files("u1").is_null());
+
+        // With `fix` the empty file is quarantined and both messages note it
+        let report = attachments.verify_attachments(true).unwrap();
+        assert_eq!((report.quarantined, report.messages_updated), (1, 2));
+        assert!(!empty.exists());
+        let quarantine = files_dir.join(".quarantine");
+        assert!(quarantine.join("empty.png").exists());
+        assert!(std::fs::read_dir(&quarantine).unwrap().any(|entry| entry
+            .unwrap()
+            .file_name()
+            .to_string_lossy()
+            .starts_with("report-")));
+        assert!(photo.exists());
+        assert_eq!(missing_files("u1"), serde_json::json!([empty]));
+        assert_eq!(missing_files("u2"), serde_json::json!([gone, empty]));
+
+        // The quarantined file is missing now, and already noted
+        let report = attachments.verify_attachments(true).unwrap();
+        assert_eq!((report.missing, report.empty), (2, 0));
+        assert_eq!((report.quarantined, report.messages_updated), (0, 0));
+    }
+
+    #[test]
+    fn integrity_check_reads_batches_and_stops_when_cancelled() {
+        use crate::features::attachment::service::VERIFY_BATCH_SIZE;
+        use crate::features::attachment::AttachmentService;
+        use crate::features::message::MessageRepository;
+        use std::sync::atomic::Ordering;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let photo = dir.path().join("photo.png");
+        std::fs::write(&photo, b"png bytes").unwrap();
+        let repository = Arc::new(MemoryAttachmentRepository::default());
+        let attachments = Arc::new(AttachmentService::new(
+            repository.clone(),
+            dir.path().to_path_buf(),
+        ));
+        // Two full batches and part of a third
+        let total = 2 * i64::from(VERIFY_BATCH_SIZE) + 50;
+        for i in 0..total {
+            repository
+                .messages
+                .create(&message_with_files(&format!("u{i}"), i, &[&photo]))
+                .unwrap();
+        }
+        // Messages without files are never read
+        let mut plain = message_with_files("plain", 0, &[]);
+        plain.metadata = None;
+        repository.messages.create(&plain).unwrap();
+
+        let report = attachments.verify_attachments(false).unwrap();
+        assert!(!report.cancelled);
+        assert_eq!(report.messages_scanned, u64::try_from(total).unwrap());
+        assert_eq!(report.files_checked, 1);
+        // Three batches, then the empty one that ends the scan
+        assert_eq!(repository.batches.swap(0, Ordering::SeqCst), 4);
+
+        // Cancelled during the first batch: that batch finishes, no other is read
+        *repository.cancel_after.lock().unwrap() = Some(Arc::downgrade(&attachments));
+        let report = attachments.verify_attachments(false).unwrap();
+        assert!(report.cancelled);
+        assert_eq!(report.messages_scanned, u64::from(VERIFY_BATCH_SIZE));
+        assert_eq!(repository.batches.swap(0, Ordering::SeqCst), 1);
+
+        // A cancel does not carry over to the next check
+        let report = attachments.verify_attachments(false).unwrap();
+        assert!(!report.cancelled);
+        assert_eq!(report.messages_scanned, u64::try_from(total).unwrap());
+    }
+}
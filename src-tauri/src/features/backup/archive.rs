@@ -0,0 +1,370 @@
+//! Backup archives of the data directory.
+//!
+//! A backup is a zip holding `manifest.json`, a consistent copy of the
+//! database made with `VACUUM INTO`, and the attachment files under
+//! `files/`. Values encrypted at rest stay encrypted in it, so a restored
+//! database needs the keys of the OS keychain it was made with.
+//!
+//! The database cannot be swapped while the app has it open, so a restore is
+//! staged: the backup is extracted and checked into `restore-pending/`, and
+//! `apply_pending_restore` moves it into place at the next start, before the
+//! database is opened.
+
+use super::models::{BackupInfo, BackupManifest, BackupReason};
+use crate::db::connection::DATABASE_FILE;
+use crate::db::migrations;
+use crate::error::AppError;
+use rusqlite::params;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+pub const BACKUPS_DIR: &str = "backups";
+pub const FILES_DIR: &str = "files";
+pub const RESTORE_DIR: &str = "restore-pending";
+const BACKUP_EXTENSION: &str = "zip";
+const MANIFEST_FILE: &str = "manifest.json";
+const FORMAT_VERSION: u32 = 1;
+
+/// Snapshot the database at `db_path` and the attachments in `files_dir`
+/// into a new archive in `backups_dir`. The archive only appears under its
+/// name once complete.
+pub fn create_backup(
+    db_path: &Path,
+    files_dir: &Path,
+    backups_dir: &Path,
+    reason: BackupReason,
+) -> Result<BackupInfo, AppError> {
+    fs::create_dir_all(backups_dir)?;
+    let now = chrono::Utc::now();
+    let id = format!("backup-{}", now.format("%Y%m%d-%H%M%S%3f"));
+    let snapshot = backups_dir.join(format!(".{id}.db"));
+    let partial = backups_dir.join(format!(".{id}.partial"));
+    let path = backups_dir.join(format!("{id}.{BACKUP_EXTENSION}"));
+
+    let result = write_backup(
+        db_path,
+        files_dir,
+        &snapshot,
+        &partial,
+        reason,
+        now.timestamp_millis(),
+    )
+    .and_then(|()| fs::rename(&partial, &path).map_err(AppError::from));
+    let _ = fs::remove_file(&snapshot);
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result?;
+    read_backup(&path)
+}
+
+fn write_backup(
+    db_path: &Path,
+    files_dir: &Path,
+    snapshot: &Path,
+    partial: &Path,
+    reason: BackupReason,
+    created_at: i64,
+) -> Result<(), AppError> {
+    let conn = crate::db::open_connection(db_path)?;
+    let schema_version = migrations::applied_migrations(&conn)?
+        .last()
+        .map_or(0, |m| m.version);
+    conn.execute("VACUUM INTO ?1", params![snapshot.to_string_lossy()])?;
+    drop(conn);
+
+    let files = attachment_files(files_dir)?;
+    let manifest = BackupManifest {
+        format_version: FORMAT_VERSION,
+        reason,
+        created_at,
+        schema_version,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        file_count: u32::try_from(files.len()).unwrap_or(u32::MAX),
+    };
+
+    let mut zip = zip::ZipWriter::new(fs::File::create(partial)?);
+    let options = zip::write::SimpleFileOptions::default().large_file(true);
+    // Attachments are mostly compressed already
+    let stored = options.compression_method(zip::CompressionMethod::Stored);
+    zip.start_file(MANIFEST_FILE, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(&manifest)?)?;
+    zip.start_file(DATABASE_FILE, options)?;
+    std::io::copy(&mut fs::File::open(snapshot)?, &mut zip)?;
+    for (relative, file) in &files {
+        zip.start_file(format!("{FILES_DIR}/{relative}"), stored)?;
+        std::io::copy(&mut fs::File::open(file)?, &mut zip)?;
+    }
+    zip.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Files under `dir` by path relative to it, with `/` separators. A missing
+/// directory has none.
+fn attachment_files(dir: &Path) -> Result<Vec<(String, PathBuf)>, AppError> {
+    let mut files = Vec::new();
+    if dir.is_dir() {
+        collect_files(dir, "", &mut files)?;
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn collect_files(
+    dir: &Path,
+    prefix: &str,
+    files: &mut Vec<(String, PathBuf)>,
+) -> Result<(), AppError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let relative = format!("{prefix}{name}");
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), &format!("{relative}/"), files)?;
+        } else if file_type.is_file() {
+            files.push((relative, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+/// Read the manifest of the archive at `path`.
+pub fn read_backup(path: &Path) -> Result<BackupInfo, AppError> {
+    let file = fs::File::open(path)?;
+    let size_bytes = file.metadata()?.len();
+    let mut archive = zip::ZipArchive::new(file)?;
+    let mut json = String::new();
+    archive
+        .by_name(MANIFEST_FILE)
+        .map_err(|_| AppError::Validation(format!("Not a backup: {}", path.display())))?
+        .read_to_string(&mut json)?;
+    let manifest: BackupManifest = serde_json::from_str(&json)?;
+    Ok(BackupInfo {
+        id: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        path: path.to_string_lossy().to_string(),
+        size_bytes,
+        reason: manifest.reason,
+        created_at: manifest.created_at,
+        schema_version: manifest.schema_version,
+        app_version: manifest.app_version,
+        file_count: manifest.file_count,
+    })
+}
+
+/// Backups in `backups_dir`, newest first. Archives that cannot be read are
+/// skipped.
+pub fn list_backups(backups_dir: &Path) -> Result<Vec<BackupInfo>, AppError> {
+    if !backups_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in fs::read_dir(backups_dir)? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some(BACKUP_EXTENSION) {
+            continue;
+        }
+        match read_backup(&path) {
+            Ok(backup) => backups.push(backup),
+            Err(e) => {
+                tracing::warn!(path = %path.display(), error = %e, "Skipping unreadable backup");
+            }
+        }
+    }
+    backups.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    Ok(backups)
+}
+
+/// Delete all but the newest `keep` backups; returns the deleted ones.
+pub fn prune_backups(backups_dir: &Path, keep: usize) -> Result<Vec<BackupInfo>, AppError> {
+    let removed: Vec<BackupInfo> = list_backups(backups_dir)?.into_iter().skip(keep).collect();
+    for backup in &removed {
+        fs::remove_file(&backup.path)?;
+    }
+    Ok(removed)
+}
+
+/// Extract the backup at `path` into `data_dir/restore-pending` for
+/// `apply_pending_restore`, after checking it can be opened by this release
+/// and its database is intact. Replaces a restore staged before.
+pub fn stage_restore(path: &Path, data_dir: &Path) -> Result<BackupInfo, AppError> {
+    let backup = read_backup(path)?;
+    let latest = migrations::latest_version();
+    if backup.schema_version > latest {
+        return Err(AppError::Validation(format!(
+            "The backup was made by a newer release (schema version {}, this one knows up to {latest})",
+            backup.schema_version
+        )));
+    }
+
+    let staging = data_dir.join(format!("{RESTORE_DIR}.partial"));
+    let staged = data_dir.join(RESTORE_DIR);
+    let _ = fs::remove_dir_all(&staging);
+    let result = extract_backup(path, &staging).and_then(|()| {
+        let _ = fs::remove_dir_all(&staged);
+        fs::rename(&staging, &staged).map_err(AppError::from)
+    });
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&staging);
+    }
+    result?;
+    Ok(backup)
+}
+
+fn extract_backup(path: &Path, dest: &Path) -> Result<(), AppError> {
+    fs::create_dir_all(dest.join(FILES_DIR))?;
+    let mut archive = zip::ZipArchive::new(fs::File::open(path)?)?;
+    for i in 0..archive.len() {
+        let mut entry = archive.by_index(i)?;
+        let Some(relative) = entry.enclosed_name() else {
+            continue;
+        };
+        let keep = relative == Path::new(DATABASE_FILE)
+            || (relative.starts_with(FILES_DIR) && !entry.is_dir());
+        if !keep {
+            continue;
+        }
+        let out = dest.join(&relative);
+        if let Some(parent) = out.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        std::io::copy(&mut entry, &mut fs::File::create(&out)?)?;
+    }
+
+    let db_path = dest.join(DATABASE_FILE);
+    if !db_path.is_file() {
+        return Err(AppError::Validation(
+            "The backup has no database".to_string(),
+        ));
+    }
+    let conn = crate::db::open_connection(&db_path)?;
+    let check: String = conn.query_row("PRAGMA quick_check", [], |row| row.get(0))?;
+    if check != "ok" {
+        return Err(AppError::Validation(format!(
+            "The backup's database is damaged: {check}"
+        )));
+    }
+    Ok(())
+}
+
+/// Move a restore staged by `stage_restore` into `data_dir`. Returns whether
+/// there was one. Safe to run again after an interruption: the attachments
+/// are swapped first and the database last.
+pub fn apply_pending_restore(data_dir: &Path) -> Result<bool, AppError> {
+    let staged = data_dir.join(RESTORE_DIR);
+    let staged_db = staged.join(DATABASE_FILE);
+    if !staged_db.is_file() {
+        if staged.exists() {
+            fs::remove_dir_all(&staged)?;
+        }
+        return Ok(false);
+    }
+
+    let staged_files = staged.join(FILES_DIR);
+    if staged_files.is_dir() {
+        let files_dir = data_dir.join(FILES_DIR);
+        let replaced = data_dir.join(format!("{FILES_DIR}.replaced"));
+        let _ = fs::remove_dir_all(&replaced);
+        if files_dir.exists() {
+            fs::rename(&files_dir, &replaced)?;
+        }
+        fs::rename(&staged_files, &files_dir)?;
+        let _ = fs::remove_dir_all(&replaced);
+    }
+
+    // The write-ahead log belongs to the database being replaced
+    let db_path = data_dir.join(DATABASE_FILE);
+    for suffix in ["-wal", "-shm"] {
+        let sidecar = data_dir.join(format!("{DATABASE_FILE}{suffix}"));
+        if sidecar.exists() {
+            fs::remove_file(sidecar)?;
+        }
+    }
+    fs::rename(&staged_db, &db_path)?;
+    fs::remove_dir_all(&staged)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn backups_snapshot_prune_and_restore_the_data_directory() {
+        use crate::db::connection::DATABASE_FILE;
+        use crate::features::backup::archive::{
+            apply_pending_restore, create_backup, list_backups, prune_backups, stage_restore,
+            BACKUPS_DIR, FILES_DIR, RESTORE_DIR,
+        };
+        use crate::features::backup::service::backup_due;
+        use crate::features::backup::BackupReason;
+
+        let dir = tempfile::tempdir().unwrap();
+        let data = dir.path();
+        let db_path = data.join(DATABASE_FILE);
+        let files = data.join(FILES_DIR);
+        let backups = data.join(BACKUPS_DIR);
+        let conn = crate::db::open_connection(&db_path).unwrap();
+        crate::db::configure_database(&conn).unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Kept', 0)",
+            [],
+        )
+        .unwrap();
+        std::fs::create_dir_all(files.join("ab")).unwrap();
+        std::fs::write(files.join("ab").join("cd.png"), b"png").unwrap();
+
+        let backup = create_backup(&db_path, &files, &backups, BackupReason::Manual).unwrap();
+        assert_eq!(backup.reason, BackupReason::Manual);
+        assert_eq!(backup.file_count, 1);
+        assert_eq!(
+            backup.schema_version,
+            crate::db::migrations::latest_version()
+        );
+        // Only the finished archive is left behind
+        assert_eq!(std::fs::read_dir(&backups).unwrap().count(), 1);
+
+        // Changes after the backup are undone once the restore is applied
+        conn.execute("DELETE FROM workspaces", []).unwrap();
+        drop(conn);
+        std::fs::remove_dir_all(&files).unwrap();
+        std::fs::create_dir_all(&files).unwrap();
+        std::fs::write(files.join("new.txt"), b"new").unwrap();
+        stage_restore(std::path::Path::new(&backup.path), data).unwrap();
+        assert!(data.join(RESTORE_DIR).join(DATABASE_FILE).is_file());
+        assert!(apply_pending_restore(data).unwrap());
+        assert!(!data.join(RESTORE_DIR).exists());
+        assert!(!apply_pending_restore(data).unwrap());
+        let conn = crate::db::open_connection(&db_path).unwrap();
+        let name: String = conn
+            .query_row("SELECT name FROM workspaces WHERE id = 'w1'", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(name, "Kept");
+        assert_eq!(
+            std::fs::read(files.join("ab").join("cd.png")).unwrap(),
+            b"png"
+        );
+        assert!(!files.join("new.txt").exists());
+        drop(conn);
+
+        // Retention keeps the newest backups
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let second = create_backup(&db_path, &files, &backups, BackupReason::Scheduled).unwrap();
+        let removed = prune_backups(&backups, 1).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].id, backup.id);
+        assert_eq!(list_backups(&backups).unwrap(), vec![second]);
+
+        let day = 24 * 60 * 60 * 1000;
+        assert!(backup_due(None, None, 0));
+        assert!(!backup_due(None, Some(0), day - 1));
+        assert!(backup_due(None, Some(0), day));
+        assert!(!backup_due(Some("weekly"), Some(0), day));
+        assert!(!backup_due(Some("off"), None, 0));
+    }
+}
@@ -0,0 +1,26 @@
+use super::models::{BackupInfo, BackupReason};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Back up the database and attachment files now.
+#[tauri::command]
+pub async fn backup_now(state: State<'_, AppState>) -> Result<BackupInfo, AppError> {
+    state.backup_service.backup_now(BackupReason::Manual).await
+}
+
+/// Backups in the data directory, newest first.
+#[tauri::command]
+pub async fn list_backups(state: State<'_, AppState>) -> Result<Vec<BackupInfo>, AppError> {
+    state.backup_service.list()
+}
+
+/// Restore a backup. The current data is backed up first, and the backup
+/// replaces it the next time the app starts, so restart it to finish.
+#[tauri::command]
+pub async fn restore_backup(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<BackupInfo, AppError> {
+    state.backup_service.restore(&id).await
+}
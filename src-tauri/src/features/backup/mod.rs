@@ -0,0 +1,7 @@
+pub mod archive;
+pub mod commands;
+pub mod models;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
@@ -0,0 +1,41 @@
+use serde::{Deserialize, Serialize};
+
+/// Why a backup was taken.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackupReason {
+    /// Asked for with `backup_now`
+    Manual,
+    Scheduled,
+    /// Taken at startup before migrations changed the schema
+    PreMigration,
+    /// The data a restore replaced
+    PreRestore,
+}
+
+/// `manifest.json` of a backup archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupManifest {
+    pub format_version: u32,
+    pub reason: BackupReason,
+    pub created_at: i64,
+    /// Schema version of the database in the backup
+    pub schema_version: u32,
+    pub app_version: String,
+    /// Attachment files in the backup
+    pub file_count: u32,
+}
+
+/// A backup archive in the backups directory.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackupInfo {
+    /// File name without extension, what `restore_backup` takes
+    pub id: String,
+    pub path: String,
+    pub size_bytes: u64,
+    pub reason: BackupReason,
+    pub created_at: i64,
+    pub schema_version: u32,
+    pub app_version: String,
+    pub file_count: u32,
+}
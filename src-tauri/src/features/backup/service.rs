@@ -0,0 +1,143 @@
+use super::archive::{self, BACKUPS_DIR, FILES_DIR};
+use super::models::{BackupInfo, BackupReason};
+use crate::db::connection::DATABASE_FILE;
+use crate::error::AppError;
+use crate::features::app_settings::service::AppSettingsService;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// App setting choosing how often backups are taken: `daily` (default),
+/// `weekly` or `off`.
+pub const BACKUP_SCHEDULE: &str = "backupSchedule";
+/// App setting with how many backups to keep, 7 when unset.
+pub const BACKUP_RETENTION: &str = "backupRetention";
+const DEFAULT_RETENTION: usize = 7;
+/// How often the schedule looks whether a backup is due.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_mins(30);
+
+pub struct BackupService {
+    data_dir: PathBuf,
+    app_settings_service: Arc<AppSettingsService>,
+    /// One backup or restore at a time
+    run_lock: tokio::sync::Mutex<()>,
+}
+
+impl BackupService {
+    pub fn new(data_dir: PathBuf, app_settings_service: Arc<AppSettingsService>) -> Self {
+        Self {
+            data_dir,
+            app_settings_service,
+            run_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    fn backups_dir(&self) -> PathBuf {
+        self.data_dir.join(BACKUPS_DIR)
+    }
+
+    /// Back up the database and attachments now, then delete the backups
+    /// past the retention setting.
+    pub async fn backup_now(&self, reason: BackupReason) -> Result<BackupInfo, AppError> {
+        let _running = self.run_lock.lock().await;
+        self.create(reason).await
+    }
+
+    async fn create(&self, reason: BackupReason) -> Result<BackupInfo, AppError> {
+        let data_dir = self.data_dir.clone();
+        let keep = self.retention()?;
+        tauri::async_runtime::spawn_blocking(move || {
+            let backups_dir = data_dir.join(BACKUPS_DIR);
+            let backup = archive::create_backup(
+                &data_dir.join(DATABASE_FILE),
+                &data_dir.join(FILES_DIR),
+                &backups_dir,
+                reason,
+            )?;
+            for removed in archive::prune_backups(&backups_dir, keep)? {
+                tracing::info!(id = %removed.id, "Deleted backup past retention");
+            }
+            Ok(backup)
+        })
+        .await
+        .map_err(|e| AppError::Generic(format!("Backup failed: {e}")))?
+    }
+
+    /// Backups, newest first.
+    pub fn list(&self) -> Result<Vec<BackupInfo>, AppError> {
+        archive::list_backups(&self.backups_dir())
+    }
+
+    /// Stage the backup `id` to replace the database and attachments at the
+    /// next start, after backing up the current ones.
+    pub async fn restore(&self, id: &str) -> Result<BackupInfo, AppError> {
+        let _running = self.run_lock.lock().await;
+        let backup = self
+            .list()?
+            .into_iter()
+            .find(|backup| backup.id == id)
+            .ok_or_else(|| AppError::NotFound(format!("Backup not found: {id}")))?;
+        let path = PathBuf::from(&backup.path);
+        let data_dir = self.data_dir.clone();
+        let staged =
+            tauri::async_runtime::spawn_blocking(move || archive::stage_restore(&path, &data_dir))
+                .await
+                .map_err(|e| AppError::Generic(format!("Restore failed: {e}")))??;
+        self.create(BackupReason::PreRestore).await?;
+        Ok(staged)
+    }
+
+    fn retention(&self) -> Result<usize, AppError> {
+        Ok(self
+            .app_settings_service
+            .get_by_key(BACKUP_RETENTION)?
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .map_or(DEFAULT_RETENTION, |keep| keep.max(1)))
+    }
+
+    /// Check every `SCHEDULE_CHECK_INTERVAL` whether a backup is due and
+    /// take it if so.
+    pub fn start_schedule(self: Arc<Self>) {
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.backup_if_due().await {
+                    tracing::error!(error = %e, "Scheduled backup failed");
+                }
+            }
+        });
+    }
+
+    async fn backup_if_due(&self) -> Result<(), AppError> {
+        let schedule = self.app_settings_service.get_by_key(BACKUP_SCHEDULE)?;
+        let last_created_at = self.list()?.first().map(|backup| backup.created_at);
+        if backup_due(
+            schedule.as_deref(),
+            last_created_at,
+            chrono::Utc::now().timestamp_millis(),
+        ) {
+            self.backup_now(BackupReason::Scheduled).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Interval of a backup schedule setting, `None` when switched off.
+pub fn backup_interval(schedule: Option<&str>) -> Option<Duration> {
+    match schedule {
+        Some("off") => None,
+        Some("weekly") => Some(Duration::from_hours(24 * 7)),
+        _ => Some(Duration::from_hours(24)),
+    }
+}
+
+/// Whether a scheduled backup is due: an interval after the newest backup
+/// of any kind, or now when there is none.
+pub fn backup_due(schedule: Option<&str>, last_created_at: Option<i64>, now: i64) -> bool {
+    backup_interval(schedule).is_some_and(|interval| {
+        let interval_ms = i64::try_from(interval.as_millis()).unwrap_or(i64::MAX);
+        last_created_at.is_none_or(|last| now >= last.saturating_add(interval_ms))
+    })
+}
@@ -0,0 +1,338 @@
+//! Everything the chat screen needs before the composer can render, gathered
+//! in one call instead of one round trip per piece.
+
+use super::model_pinning::{resolve_turn_model, split_model_selection};
+use crate::error::AppError;
+use crate::features::agent::manager::InstalledAgent;
+use crate::features::chat::input_settings::models::ChatInputSettings;
+use crate::features::llm_connection::models::LLMConnection;
+use crate::features::mcp_connection::models::MCPServerConnection;
+use crate::features::tool::models::MCPTool;
+use crate::features::tool::service::{ToolService, FILE_SYSTEM_TOOLS, SCRATCHPAD_TOOLS, WEB_TOOLS};
+use crate::features::tool::strict_schema;
+use crate::features::workspace::settings::WorkspaceSettings;
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Result of `get_chat_boot_data`. A part that failed to load is `None` and
+/// listed in `errors`; the rest is still returned.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct ChatBootData {
+    pub workspace_settings: Option<WorkspaceSettings>,
+    pub llm_connections: Option<Vec<LLMConnection>>,
+    pub chat_input_settings: Option<ChatInputSettings>,
+    pub mcp_connections: Option<Vec<MCPServerConnection>>,
+    pub installed_agents: Option<Vec<InstalledAgent>>,
+    /// What the next message would run with
+    pub effective_model: Option<EffectiveModel>,
+    pub tools: Option<Vec<ToolSummary>>,
+    /// Assistant messages of the chat waiting for a tool permission answer
+    pub pending_permission_message_ids: Vec<String>,
+    pub errors: Vec<BootError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct EffectiveModel {
+    pub connection_id: String,
+    pub provider: String,
+    pub model: String,
+}
+
+/// A tool of the workspace catalog, without its schema.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ToolSummary {
+    pub name: String,
+    /// MCP connection offering the tool, or "builtin"
+    pub connection_id: String,
+    pub enabled: bool,
+    /// Whether the schema has a strict variant, so the tool runs strict on
+    /// connections with strict tools
+    pub strict: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct BootError {
+    pub part: String,
+    pub message: String,
+}
+
+/// Load the boot data of a workspace, and of `chat_id` when given. The
+/// independent lookups run concurrently.
+pub async fn gather(state: &AppState, workspace_id: &str, chat_id: Option<&str>) -> ChatBootData {
+    let settings_service = state.workspace_feature.settings_service.clone();
+    let llm_connection_service = state.llm_connection_service.clone();
+    let input_settings_service = state.chat_input_settings_service.clone();
+    let mcp_connection_service = state.mcp_connection_service.clone();
+    let agent_manager = state.agent_manager.clone();
+    let (settings_workspace_id, input_workspace_id) =
+        (workspace_id.to_string(), workspace_id.to_string());
+
+    let (workspace_settings, llm_connections, chat_input_settings, mcp_connections, agents) = tokio::join!(
+        blocking(move || settings_service.get_by_workspace_id(&settings_workspace_id)),
+        blocking(move || llm_connection_service.get_all()),
+        blocking(move || input_settings_service.get_by_workspace_id(&input_workspace_id)),
+        blocking(move || mcp_connection_service.get_all()),
+        blocking(move || {
+            agent_manager
+                .list_installed()
+                .map_err(|e| AppError::Generic(e.to_string()))
+        }),
+    );
+
+    let mut data = ChatBootData::default();
+    data.workspace_settings = keep(&mut data.errors, "workspace_settings", workspace_settings)
+        .and_then(|settings| {
+            settings.or_else(|| {
+                data.errors.push(BootError {
+                    part: "workspace_settings".to_string(),
+                    message: format!("Workspace settings not found: {workspace_id}"),
+                });
+                None
+            })
+        });
+    data.llm_connections = keep(&mut data.errors, "llm_connections", llm_connections);
+    data.chat_input_settings =
+        keep(&mut data.errors, "chat_input_settings", chat_input_settings).flatten();
+    data.mcp_connections = keep(&mut data.errors, "mcp_connections", mcp_connections);
+    data.installed_agents = keep(&mut data.errors, "installed_agents", agents);
+
+    if let (Some(settings), Some(connections)) = (&data.workspace_settings, &data.llm_connections) {
+        let effective = effective_model(settings, connections, data.chat_input_settings.as_ref());
+        data.effective_model = keep(&mut data.errors, "effective_model", effective);
+    }
+    if let (Some(settings), Some(connections)) = (&data.workspace_settings, &data.mcp_connections) {
+        data.tools = Some(tool_catalog(settings, connections));
+    }
+    if let Some(chat_id) = chat_id {
+        let pending = pending_permission_message_ids(state, chat_id);
+        data.pending_permission_message_ids =
+            keep(&mut data.errors, "pending_permissions", pending).unwrap_or_default();
+    }
+    data
+}
+
+/// The connection and model the composer would send with, resolved the way
+/// `send_message` resolves them. A saved selection whose connection no
+/// longer exists is ignored, as the composer does.
+pub fn effective_model(
+    settings: &WorkspaceSettings,
+    connections: &[LLMConnection],
+    input_settings: Option<&ChatInputSettings>,
+) -> Result<EffectiveModel, AppError> {
+    let (connection_override, selected_model) = input_settings
+        .and_then(|s| s.selected_model.as_deref())
+        .filter(|s| !s.is_empty())
+        .map(split_model_selection)
+        .filter(|(connection_id, _)| {
+            connection_id
+                .as_ref()
+                .is_none_or(|id| connections.iter().any(|c| &c.id == id))
+        })
+        .map_or((None, None), |(connection_id, model)| {
+            (connection_id, Some(model))
+        });
+
+    let (connection, model) =
+        resolve_turn_model(connection_override, selected_model, settings, |id| {
+            Ok(connections.iter().find(|c| c.id == id).cloned())
+        })?;
+    Ok(EffectiveModel {
+        connection_id: connection.id,
+        provider: connection.provider,
+        model,
+    })
+}
+
+/// Tools offered to the workspace: builtin tools, then the cached tools of
+/// connected MCP servers. A tool is enabled when the workspace selected it
+/// from that server.
+pub fn tool_catalog(
+    settings: &WorkspaceSettings,
+    connections: &[MCPServerConnection],
+) -> Vec<ToolSummary> {
+    let selected: HashMap<String, String> = settings
+        .mcp_tool_ids
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    let builtin_enabled = settings.internal_tools_enabled == Some(1);
+    let builtin_schemas: HashMap<String, Option<serde_json::Value>> =
+        ToolService::get_builtin_tools()
+            .into_iter()
+            .map(|tool| (tool.function.name, tool.function.parameters))
+            .collect();
+
+    let mut tools: Vec<ToolSummary> = FILE_SYSTEM_TOOLS
+        .iter()
+        .chain(SCRATCHPAD_TOOLS)
+        .chain(WEB_TOOLS)
+        .map(|name| ToolSummary {
+            name: (*name).to_string(),
+            connection_id: "builtin".to_string(),
+            enabled: builtin_enabled,
+            strict: has_strict_variant(builtin_schemas.get(*name).cloned().flatten()),
+        })
+        .collect();
+
+    for connection in connections.iter().filter(|c| c.status == "connected") {
+        let cached: Vec<MCPTool> = connection
+            .tools_json
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+        tools.extend(cached.into_iter().map(|tool| {
+            ToolSummary {
+                enabled: selected.get(&tool.name) == Some(&connection.id),
+                strict: has_strict_variant(
+                    tool.input_schema
+                        .as_deref()
+                        .and_then(|s| serde_json::from_str(s).ok()),
+                ),
+                name: tool.name,
+                connection_id: connection.id.clone(),
+            }
+        }));
+    }
+    tools
+}
+
+/// Whether a tool schema converts to strict mode; a tool without a schema
+/// takes no arguments.
+fn has_strict_variant(schema: Option<serde_json::Value>) -> bool {
+    let schema =
+        schema.unwrap_or_else(|| serde_json::json!({ "type": "object", "properties": {} }));
+    strict_schema::to_strict(&schema).is_ok()
+}
+
+fn pending_permission_message_ids(
+    state: &AppState,
+    chat_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let message_ids: Vec<String> = state
+        .pending_tool_permissions
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Failed to lock pending_tool_permissions: {e}")))?
+        .keys()
+        .cloned()
+        .collect();
+
+    let mut pending = Vec::new();
+    for message_id in message_ids {
+        if state
+            .message_service
+            .get_by_id(&message_id)?
+            .is_some_and(|m| m.chat_id == chat_id)
+        {
+            pending.push(message_id);
+        }
+    }
+    Ok(pending)
+}
+
+async fn blocking<T: Send + 'static>(
+    task: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, AppError> {
+    tokio::task::spawn_blocking(task)
+        .await
+        .map_err(|e| AppError::Generic(format!("Task failed: {e}")))?
+}
+
+/// Keep a loaded part, or record why it is missing.
+fn keep<T>(errors: &mut Vec<BootError>, part: &str, result: Result<T, AppError>) -> Option<T> {
+    result
+        .map_err(|e| {
+            tracing::warn!(part = %part, error = %e, "Chat boot data part failed to load");
+            errors.push(BootError {
+                part: part.to_string(),
+                message: e.to_string(),
+            });
+        })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{boot_workspace_settings, connection, pin_connections};
+
+    #[test]
+    fn boot_effective_model_matches_send_message() {
+        use crate::features::chat::boot::effective_model;
+        use crate::features::chat::input_settings::models::ChatInputSettings;
+        use crate::features::chat::model_pinning::{resolve_turn_model, split_model_selection};
+
+        let connections = pin_connections();
+        let input = |selection: Option<&str>| ChatInputSettings {
+            workspace_id: "ws".to_string(),
+            selected_model: selection.map(str::to_string),
+            stream_enabled: 1,
+            temperature: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            max_tokens: None,
+            created_at: 0,
+            updated_at: 0,
+        };
+        // What send_message runs with when the composer sends `selection`,
+        // split the way the composer splits it
+        let sent = |settings: &crate::features::workspace::settings::WorkspaceSettings,
+                    selection: Option<&str>| {
+            let (connection_id, model) = selection.map(split_model_selection).unzip();
+            resolve_turn_model(connection_id.flatten(), model, settings, |id| {
+                Ok(connections.iter().find(|c| c.id == id).cloned())
+            })
+            .map(|(connection, model)| (connection.id, model))
+            .ok()
+        };
+        let booted = |settings: &crate::features::workspace::settings::WorkspaceSettings,
+                      selection: Option<&str>| {
+            effective_model(settings, &connections, Some(&input(selection)))
+                .map(|m| (m.connection_id, m.model))
+                .ok()
+        };
+
+        let cases = [
+            // Saved selection on another connection than the workspace's
+            (Some("local"), Some("qwen3"), Some("cloud::gpt-4o")),
+            // Bare model id: workspace connection
+            (Some("cloud"), None, Some("gpt-4o")),
+            // Nothing saved: workspace default model, then connection default
+            (Some("local"), Some("qwen3"), None),
+            (Some("cloud"), None, None),
+            // No connection anywhere
+            (None, Some("qwen3"), None),
+        ];
+        for (connection_id, default_model, selection) in cases {
+            let settings = boot_workspace_settings(connection_id, default_model);
+            assert_eq!(
+                booted(&settings, selection),
+                sent(&settings, selection),
+                "settings {connection_id:?}/{default_model:?}, selection {selection:?}"
+            );
+        }
+
+        let settings = boot_workspace_settings(Some("local"), Some("qwen3"));
+        let model =
+            effective_model(&settings, &connections, Some(&input(Some("cloud::gpt-4o")))).unwrap();
+        assert_eq!(
+            (
+                model.connection_id.as_str(),
+                model.provider.as_str(),
+                model.model.as_str()
+            ),
+            ("cloud", "openai", "gpt-4o")
+        );
+        assert_eq!(
+            booted(&boot_workspace_settings(Some("cloud"), None), None),
+            Some(("cloud".to_string(), "gpt-4o-mini".to_string()))
+        );
+
+        // A saved selection whose connection is gone falls back to the
+        // workspace defaults, like the composer does
+        assert_eq!(
+            booted(&settings, Some("deleted::some-model")),
+            Some(("local".to_string(), "qwen3".to_string()))
+        );
+    }
+}
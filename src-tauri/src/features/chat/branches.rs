@@ -0,0 +1,170 @@
+//! Branches of a chat: alternative continuations from one of its messages.
+//!
+//! A chat starts on its main branch, whose messages have no branch id. A
+//! branch forks from a message of the branch that was active when it was
+//! made and holds the messages written while it is active. The history of a
+//! branch is its ancestors' messages up to each fork, then its own; messages
+//! of other branches stay stored and return when their branch is switched
+//! back to. Forks are cut by timestamp, so a branch keeps working when its
+//! fork message is deleted later.
+
+use crate::features::message::Message;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ChatBranch {
+    pub id: String,
+    pub chat_id: String,
+    /// `None` when forked from the main branch
+    pub parent_branch_id: Option<String>,
+    /// Last message shared with the parent, `None` when forked before the
+    /// first message
+    pub fork_message_id: Option<String>,
+    pub fork_timestamp: Option<i64>,
+    pub name: String,
+    pub created_at: i64,
+    /// Messages written on this branch, not counting the shared ones
+    #[serde(default)]
+    pub message_count: u32,
+}
+
+/// Result of `list_branches`. The main branch is implicit.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatBranches {
+    /// `None` while the main branch is active
+    pub active_branch_id: Option<String>,
+    pub branches: Vec<ChatBranch>,
+}
+
+/// The messages of a chat that belong to the history of branch `active`,
+/// `None` being the main branch. An unknown branch shows the main one.
+pub fn on_branch_path(
+    messages: Vec<Message>,
+    branches: &[ChatBranch],
+    active: Option<&str>,
+) -> Vec<Message> {
+    if branches.is_empty() {
+        return messages;
+    }
+    let active = active.filter(|id| branches.iter().any(|b| b.id == *id));
+
+    // Latest timestamp shown of each branch on the path; none for the active
+    // one. An ancestor is cut at the earliest fork below it.
+    let mut cutoffs: HashMap<Option<&str>, Option<i64>> = HashMap::from([(active, None)]);
+    let mut current = active;
+    let mut cutoff: Option<i64> = None;
+    while let Some(branch) = current.and_then(|id| branches.iter().find(|b| b.id == id)) {
+        let parent = branch.parent_branch_id.as_deref();
+        if cutoffs.contains_key(&parent) {
+            break;
+        }
+        let fork = branch.fork_timestamp.unwrap_or(i64::MIN);
+        cutoff = Some(cutoff.map_or(fork, |c| c.min(fork)));
+        cutoffs.insert(parent, cutoff);
+        current = parent;
+    }
+
+    messages
+        .into_iter()
+        .filter(|m| match cutoffs.get(&m.branch_id.as_deref()) {
+            Some(None) => true,
+            Some(Some(cutoff)) => m.timestamp <= *cutoff,
+            None => false,
+        })
+        .collect()
+}
+
+/// The message a resend of `message_id` forks after: the one before it in
+/// `history`, `None` when it is the first.
+pub fn fork_point_before(history: &[Message], message_id: &str) -> Option<String> {
+    let mut ordered: Vec<&Message> = history.iter().collect();
+    // A tool call and its result share a timestamp; the id orders them
+    ordered.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+    let index = ordered.iter().position(|m| m.id == message_id)?;
+    index.checked_sub(1).map(|i| ordered[i].id.clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn branches_keep_forked_continuations_apart() {
+        use crate::features::chat::branches::{fork_point_before, ChatBranch};
+        use crate::features::chat::repository::{
+            chat_branches, insert_branch, set_active_branch_id,
+        };
+        use crate::features::encryption::FieldCipher;
+        use crate::features::message::repository::{insert_message, read_chat_messages};
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('chat', 'w1', 'Forks', 0, 0);",
+        )
+        .unwrap();
+        let cipher = FieldCipher::default();
+        let write = |id: &str, role: &str, timestamp: i64| {
+            let mut message = chat_message(id, role, id);
+            message.timestamp = timestamp;
+            insert_message(&conn, &message, &cipher).unwrap();
+        };
+        let shown = || {
+            read_chat_messages(&conn, "chat", &cipher)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.id)
+                .collect::<Vec<_>>()
+        };
+        let branch = |id: &str, parent: Option<&str>, fork: &str, at: i64| ChatBranch {
+            id: id.to_string(),
+            chat_id: "chat".to_string(),
+            parent_branch_id: parent.map(str::to_string),
+            fork_message_id: Some(fork.to_string()),
+            fork_timestamp: Some(at),
+            name: id.to_string(),
+            created_at: at,
+            message_count: 0,
+        };
+
+        write("m1", "user", 10);
+        write("m2", "assistant", 20);
+        write("m3", "user", 30);
+        write("m4", "assistant", 40);
+        assert_eq!(shown(), ["m1", "m2", "m3", "m4"]);
+
+        // Resending m3 forks after m2
+        let history = read_chat_messages(&conn, "chat", &cipher).unwrap();
+        assert_eq!(fork_point_before(&history, "m3").as_deref(), Some("m2"));
+        assert_eq!(fork_point_before(&history, "m1"), None);
+
+        insert_branch(&conn, &branch("b1", None, "m2", 20)).unwrap();
+        set_active_branch_id(&conn, "chat", Some("b1")).unwrap();
+        write("m5", "user", 50);
+        write("m6", "assistant", 60);
+        assert_eq!(shown(), ["m1", "m2", "m5", "m6"]);
+
+        // A fork of the fork cuts the main branch at the earlier point
+        insert_branch(&conn, &branch("b2", Some("b1"), "m1", 10)).unwrap();
+        set_active_branch_id(&conn, "chat", Some("b2")).unwrap();
+        write("m7", "user", 70);
+        assert_eq!(shown(), ["m1", "m7"]);
+
+        set_active_branch_id(&conn, "chat", None).unwrap();
+        assert_eq!(shown(), ["m1", "m2", "m3", "m4"]);
+        set_active_branch_id(&conn, "chat", Some("b1")).unwrap();
+        assert_eq!(shown(), ["m1", "m2", "m5", "m6"]);
+        assert!(set_active_branch_id(&conn, "missing", None).is_err());
+
+        let counts: Vec<(String, u32)> = chat_branches(&conn, "chat")
+            .unwrap()
+            .into_iter()
+            .map(|b| (b.id, b.message_count))
+            .collect();
+        assert_eq!(counts, [("b1".to_string(), 2), ("b2".to_string(), 1)]);
+    }
+}
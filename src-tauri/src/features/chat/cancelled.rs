@@ -0,0 +1,233 @@
+//! Turns the user cancelled mid-stream.
+//!
+//! Providers stop reading when the cancel arrives and return what streamed
+//! so far with finish reason `cancelled`. The answer is stored as far as it
+//! got and marked cancelled; tool calls the model had begun never run, so
+//! the turn ends with it.
+
+use crate::error::AppError;
+use crate::features::message::MessageService;
+use crate::models::llm_types::LLMChatResponse;
+
+/// Finish reason of an answer the user cancelled.
+pub const CANCELLED_FINISH_REASON: &str = "cancelled";
+
+pub fn is_cancelled(response: &LLMChatResponse) -> bool {
+    response.finish_reason.as_deref() == Some(CANCELLED_FINISH_REASON)
+}
+
+/// Status the turn's usage is recorded with.
+pub fn usage_status(response: &LLMChatResponse) -> &'static str {
+    if is_cancelled(response) {
+        "cancelled"
+    } else {
+        "success"
+    }
+}
+
+/// Store a cancelled answer: its content and reasoning, and `metadata`
+/// marked cancelled. Drops the answer's tool calls so nothing runs after it.
+pub fn store_partial_answer(
+    messages: &MessageService,
+    message_id: &str,
+    response: &mut LLMChatResponse,
+    mut metadata: serde_json::Value,
+) -> Result<(), AppError> {
+    response.tool_calls = None;
+    messages.update(
+        message_id.to_string(),
+        response.content.clone(),
+        response.reasoning.clone(),
+        None,
+    )?;
+    metadata["cancelled"] = serde_json::json!(true);
+    messages.update_metadata(message_id.to_string(), Some(metadata.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{MemoryMessageRepository, NoAttachments};
+
+    /// A chunk of a mock provider's stream, as the provider's parser
+    /// accumulates it.
+    enum StreamChunk {
+        Text(&'static str),
+        Thinking(&'static str),
+        /// A function call whose arguments were still arriving
+        ToolCall(&'static str, &'static str),
+        Usage(u32, u32),
+    }
+
+    /// The read loop of the streaming providers: chunks accumulate until the
+    /// stream ends or the cancel arrives, and whatever arrived is returned.
+    async fn mock_streaming_provider(
+        mut chunks: tokio::sync::mpsc::UnboundedReceiver<StreamChunk>,
+        cancellation: tokio::sync::broadcast::Receiver<()>,
+    ) -> crate::models::llm_types::LLMChatResponse {
+        use crate::models::llm_types::{TokenUsage, ToolCall, ToolCallFunction};
+
+        let mut cancellation = Some(cancellation);
+        let (mut content, mut reasoning) = (String::new(), String::new());
+        let (mut tool_calls, mut usage) = (Vec::new(), None);
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
+            biased;
+            next = chunks.recv() => next,
+            () = crate::services::llm::providers::wait_for_cancellation(&mut cancellation) => {
+                cancelled = true;
+                None
+            }
+        } {
+            match chunk {
+                StreamChunk::Text(text) => content.push_str(text),
+                StreamChunk::Thinking(text) => reasoning.push_str(text),
+                StreamChunk::ToolCall(id, arguments) => tool_calls.push(ToolCall {
+                    id: id.to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: "web_search".to_string(),
+                        arguments: arguments.to_string(),
+                    },
+                }),
+                StreamChunk::Usage(prompt, completion) => {
+                    usage = Some(TokenUsage {
+                        prompt_tokens: Some(prompt),
+                        completion_tokens: Some(completion),
+                        total_tokens: Some(prompt + completion),
+                        cached_tokens: None,
+                        cache_write_tokens: None,
+                    });
+                }
+            }
+        }
+        crate::models::llm_types::LLMChatResponse {
+            content,
+            finish_reason: Some(if cancelled { "cancelled" } else { "stop" }.to_string()),
+            tool_calls: (!tool_calls.is_empty()).then_some(tool_calls),
+            usage,
+            reasoning: (!reasoning.is_empty()).then_some(reasoning),
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        }
+    }
+
+    /// What a cancelled stream left: the response after storing it, and
+    /// the stored message with its metadata.
+    struct CancelledTurn {
+        response: crate::models::llm_types::LLMChatResponse,
+        stored: crate::features::message::Message,
+        metadata: serde_json::Value,
+    }
+
+    /// Stream `chunks`, cancel once they are read, and store the answer as
+    /// the chat turn does.
+    fn cancel_after(chunks: Vec<StreamChunk>) -> CancelledTurn {
+        use crate::features::attachment::AttachmentService;
+        use crate::features::chat::cancelled;
+        use crate::features::message::MessageService;
+        use std::sync::Arc;
+
+        let attachments = Arc::new(AttachmentService::new(
+            Arc::new(NoAttachments),
+            std::path::PathBuf::new(),
+        ));
+        let messages =
+            MessageService::new(Arc::new(MemoryMessageRepository::default()), attachments);
+        messages
+            .create(
+                "a1".to_string(),
+                "c1".to_string(),
+                "assistant".to_string(),
+                String::new(),
+                Some(1),
+                None,
+                None,
+                None,
+            )
+            .unwrap();
+
+        let (chunk_tx, chunk_rx) = tokio::sync::mpsc::unbounded_channel();
+        for chunk in chunks {
+            chunk_tx.send(chunk).unwrap();
+        }
+        let (cancel_tx, cancel_rx) = tokio::sync::broadcast::channel(1);
+        cancel_tx.send(()).unwrap();
+        let mut response = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(mock_streaming_provider(chunk_rx, cancel_rx));
+        drop(chunk_tx);
+
+        assert!(cancelled::is_cancelled(&response));
+        assert_eq!(cancelled::usage_status(&response), "cancelled");
+        let metadata = response.usage.as_ref().map_or_else(
+            || serde_json::json!({}),
+            |usage| serde_json::json!({ "tokenUsage": usage }),
+        );
+        cancelled::store_partial_answer(&messages, "a1", &mut response, metadata).unwrap();
+
+        let stored = messages.get_by_id("a1").unwrap().unwrap();
+        let metadata = serde_json::from_str(stored.metadata.as_deref().unwrap()).unwrap();
+        CancelledTurn {
+            response,
+            stored,
+            metadata,
+        }
+    }
+
+    #[test]
+    fn cancel_mid_tool_call_keeps_the_text_and_drops_the_call() {
+        use StreamChunk::{Text, ToolCall};
+
+        // OpenAI: a function call was mid-arguments when the cancel came
+        let turn = cancel_after(vec![
+            Text("The capital "),
+            Text("of France"),
+            ToolCall("call_1", r#"{"query": "Par"#),
+        ]);
+        assert!(turn.response.tool_calls.is_none());
+        assert_eq!(turn.stored.content, "The capital of France");
+        assert_eq!(turn.stored.reasoning, None);
+        assert_eq!(turn.metadata["cancelled"], true);
+    }
+
+    #[test]
+    fn cancel_after_thinking_keeps_the_reasoning_and_usage() {
+        use StreamChunk::{Text, Thinking, Usage};
+
+        // Anthropic: thinking, then text, with usage from message_start
+        let turn = cancel_after(vec![
+            Usage(12, 3),
+            Thinking("The user asks for a capital."),
+            Text("Paris is"),
+        ]);
+        assert_eq!(turn.stored.content, "Paris is");
+        assert_eq!(
+            turn.stored.reasoning.as_deref(),
+            Some("The user asks for a capital.")
+        );
+        assert_eq!(turn.metadata["cancelled"], true);
+        assert_eq!(turn.metadata["tokenUsage"]["prompt_tokens"], 12);
+    }
+
+    #[test]
+    fn cancel_between_text_parts_keeps_the_text_so_far() {
+        use StreamChunk::{Text, Usage};
+
+        // Gemini: text parts with usage metadata on each chunk
+        let turn = cancel_after(vec![Text("Paris"), Usage(9, 4), Text(", the capital")]);
+        assert_eq!(turn.stored.content, "Paris, the capital");
+        assert_eq!(turn.metadata["cancelled"], true);
+        assert!(turn.metadata.get("tokenUsage").is_some());
+    }
+
+    #[test]
+    fn cancel_before_any_chunk_stores_an_empty_answer_marked_cancelled() {
+        let turn = cancel_after(Vec::new());
+        assert_eq!(turn.stored.content, "");
+        assert_eq!(turn.stored.reasoning, None);
+        assert_eq!(turn.metadata["cancelled"], true);
+        assert!(turn.metadata.get("tokenUsage").is_none());
+    }
+}
@@ -1,7 +1,18 @@
-use super::models::Chat;
+use super::branches::{ChatBranch, ChatBranches};
+use super::comparison::ComparisonResult;
+use super::export::{self, ChatExport, ExportFormat};
+use super::models::{Chat, OneshotOptions, OneshotResult};
+use super::summary::ChatSummary;
+use super::tool_permissions::{RememberScope, RememberedToolPermission};
+use super::ChatService;
 use crate::error::AppError;
+use crate::features::message::Message;
+use crate::features::usage::models::UsageDeleteFilter;
+use crate::services::llm::reproducibility;
 use crate::state::AppState;
+use std::sync::Arc;
 use tauri::{AppHandle, State};
+use tauri_plugin_dialog::DialogExt;
 
 #[tauri::command]
 pub fn create_chat(
@@ -37,6 +48,12 @@ pub fn get_chats(workspace_id: String, state: State<'_, AppState>) -> Result<Vec
         .map_err(|e| AppError::Generic(e.to_string()))
 }
 
+/// Called when the user has viewed a chat to its end; the frontend debounces it.
+#[tauri::command]
+pub fn mark_chat_read(chat_id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.chat_service.mark_read(&chat_id)
+}
+
 #[tauri::command]
 pub fn update_chat(
     id: String,
@@ -54,8 +71,16 @@ pub fn update_chat(
 pub fn delete_chat(id: String, state: State<'_, AppState>) -> Result<(), AppError> {
     state
         .chat_service
-        .delete(id)
-        .map_err(|e| AppError::Generic(e.to_string()))
+        .delete(id.clone())
+        .map_err(|e| AppError::Generic(e.to_string()))?;
+    crate::features::usage::commands::purge_deleted_usage(
+        &state,
+        &UsageDeleteFilter {
+            chat_id: Some(id),
+            ..UsageDeleteFilter::default()
+        },
+    );
+    Ok(())
 }
 
 #[tauri::command]
@@ -65,15 +90,39 @@ pub fn delete_all_chats_by_workspace(
 ) -> Result<(), AppError> {
     state
         .chat_service
-        .delete_by_workspace_id(workspace_id)
-        .map_err(|e| AppError::Generic(e.to_string()))
+        .delete_by_workspace_id(workspace_id.clone())
+        .map_err(|e| AppError::Generic(e.to_string()))?;
+    crate::features::usage::commands::purge_deleted_usage(
+        &state,
+        &UsageDeleteFilter {
+            workspace_id: Some(workspace_id),
+            ..UsageDeleteFilter::default()
+        },
+    );
+    Ok(())
 }
 
 #[derive(serde::Serialize)]
 pub struct SendMessageResult {
     pub assistant_message_id: String,
+    /// Set instead of an answer when the model could not be reached and the
+    /// message waits in the chat's outbox
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outbox_item_id: Option<String>,
+}
+
+impl SendMessageResult {
+    const fn queued(outbox_item_id: String) -> Self {
+        Self {
+            assistant_message_id: String::new(),
+            outbox_item_id: Some(outbox_item_id),
+        }
+    }
 }
 
+/// `reply_to_message_id` makes the message a reply to an earlier user or
+/// assistant message of the same chat. `seed` asks providers that support
+/// one for a reproducible answer.
 #[tauri::command]
 pub async fn send_message(
     chat_id: String,
@@ -83,9 +132,13 @@ pub async fn send_message(
     selected_model: Option<String>,
     reasoning_effort: Option<String>,
     llm_connection_id: Option<String>,
+    reply_to_message_id: Option<String>,
+    seed: Option<i64>,
     app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<SendMessageResult, AppError> {
+    let metadata = super::reply::with_reply_to(metadata, reply_to_message_id);
+    let metadata = reproducibility::with_seed(metadata, seed);
     let result = state
         .chat_service
         .send_message(
@@ -100,13 +153,40 @@ pub async fn send_message(
         )
         .await;
 
-    let (assistant_message_id, _) = result.map_err(|e| AppError::Generic(e.to_string()))?;
+    let (assistant_message_id, _) = match result {
+        Ok(sent) => sent,
+        Err(AppError::Queued(item_id)) => return Ok(SendMessageResult::queued(item_id)),
+        // Kept as is so the UI can tell the kill switch from a failure
+        Err(AppError::Halted) => return Err(AppError::Halted),
+        Err(e) => return Err(AppError::Generic(e.to_string())),
+    };
 
     Ok(SendMessageResult {
         assistant_message_id,
+        outbox_item_id: None,
     })
 }
 
+/// Send one message to two or three `(connection_id, model)` pairs at once;
+/// each answers in its own message.
+#[tauri::command]
+pub async fn send_message_compare(
+    chat_id: String,
+    content: String,
+    files: Option<Vec<String>>,
+    connection_model_pairs: Vec<(String, String)>,
+    seed: Option<i64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<ComparisonResult, AppError> {
+    state
+        .chat_service
+        .send_message_compare(chat_id, content, files, connection_model_pairs, seed, app)
+        .await
+}
+
+/// `reuse_seed` sends the new message with the seed of the answer it
+/// replaces, to tell the edit's effect apart from sampling.
 #[tauri::command]
 pub async fn edit_and_resend_message(
     chat_id: String,
@@ -117,10 +197,11 @@ pub async fn edit_and_resend_message(
     selected_model: Option<String>,
     reasoning_effort: Option<String>,
     llm_connection_id: Option<String>,
+    reuse_seed: Option<bool>,
     app: AppHandle,
     state: State<'_, AppState>,
 ) -> Result<SendMessageResult, AppError> {
-    let (assistant_message_id, _) = state
+    let result = state
         .chat_service
         .edit_and_resend_message(
             chat_id,
@@ -131,22 +212,78 @@ pub async fn edit_and_resend_message(
             selected_model,
             reasoning_effort,
             llm_connection_id,
+            reuse_seed.unwrap_or(false),
+            app,
+        )
+        .await;
+    let (assistant_message_id, _) = match result {
+        Ok(sent) => sent,
+        Err(AppError::Queued(item_id)) => return Ok(SendMessageResult::queued(item_id)),
+        // Kept as is so the UI can tell a declined or halted turn from a failure
+        Err(e @ (AppError::CostNotConfirmed(_) | AppError::Halted)) => return Err(e),
+        Err(e) => return Err(AppError::Generic(e.to_string())),
+    };
+
+    Ok(SendMessageResult {
+        assistant_message_id,
+        outbox_item_id: None,
+    })
+}
+
+/// Answer the last user message again. The answers it had are kept as
+/// variants of the new one; see `select_message_variant`.
+#[tauri::command]
+pub async fn regenerate_message(
+    chat_id: String,
+    selected_model: Option<String>,
+    reasoning_effort: Option<String>,
+    llm_connection_id: Option<String>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SendMessageResult, AppError> {
+    let (assistant_message_id, _) = state
+        .chat_service
+        .regenerate_message(
+            chat_id,
+            selected_model,
+            reasoning_effort,
+            llm_connection_id,
             app,
         )
         .await
-        .map_err(|e| AppError::Generic(e.to_string()))?;
+        .map_err(|e| match e {
+            // Kept as is so the UI can tell a declined or refused turn from a failure
+            e @ (AppError::CostNotConfirmed(_) | AppError::Halted | AppError::Validation(_)) => e,
+            e => AppError::Generic(e.to_string()),
+        })?;
 
     Ok(SendMessageResult {
         assistant_message_id,
+        outbox_item_id: None,
     })
 }
 
+/// Show generation `index` of a regenerated answer, counted from the first.
+#[tauri::command]
+pub fn select_message_variant(
+    chat_id: String,
+    message_id: String,
+    index: usize,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Message, AppError> {
+    state
+        .chat_service
+        .select_message_variant(&app, &chat_id, &message_id, index)
+}
+
 #[tauri::command]
 
 pub fn respond_tool_permission(
     message_id: String,
     approved: bool,
     allowed_tool_ids: Option<Vec<String>>,
+    remember: Option<RememberScope>,
     state: State<'_, AppState>,
 ) -> Result<(), AppError> {
     // Get the sender from pending permissions
@@ -162,6 +299,7 @@ pub fn respond_tool_permission(
         let decision = crate::state::PermissionDecision {
             approved,
             allowed_tool_ids: allowed_tool_ids.unwrap_or_default(),
+            remember,
         };
         sender.send(decision).map_err(|_| {
             AppError::Generic(format!(
@@ -176,6 +314,45 @@ pub fn respond_tool_permission(
     }
 }
 
+/// The "always allow" and "always deny" answers in effect in a workspace.
+#[tauri::command]
+pub fn list_remembered_tool_permissions(
+    workspace_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<RememberedToolPermission>, AppError> {
+    state
+        .chat_service
+        .list_remembered_tool_permissions(&workspace_id)
+}
+
+/// Forget an "always" answer for a tool, in `chat_id` or, without one, for
+/// the workspace; the tool asks for permission again.
+#[tauri::command]
+pub fn revoke_remembered_tool_permission(
+    workspace_id: String,
+    tool_name: String,
+    chat_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.chat_service.revoke_remembered_tool_permission(
+        &workspace_id,
+        &tool_name,
+        chat_id.as_deref(),
+    )
+}
+
+/// Approve or decline a turn held for its estimated cost.
+#[tauri::command]
+pub fn respond_cost_confirmation(
+    message_id: String,
+    approved: bool,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state
+        .chat_service
+        .respond_cost_confirmation(&message_id, approved)
+}
+
 #[tauri::command]
 pub async fn generate_chat_title(
     chat_id: String,
@@ -190,3 +367,255 @@ pub async fn generate_chat_title(
         .generate_chat_title(app, chat_id, user_prompt, model, llm_connection_id);
     Ok(())
 }
+
+/// Fold all but the recent turns of a chat into its rolling summary now.
+#[tauri::command]
+pub async fn summarize_chat(
+    chat_id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<Option<ChatSummary>, AppError> {
+    state.chat_service.summarize_chat(&app, &chat_id).await
+}
+
+#[tauri::command]
+pub async fn get_chat_scratchpad(
+    chat_id: String,
+    state: State<'_, AppState>,
+) -> Result<String, AppError> {
+    state
+        .chat_service
+        .get_scratchpad(&chat_id)
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
+
+#[tauri::command]
+pub async fn set_chat_scratchpad(
+    chat_id: String,
+    content: String,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state
+        .chat_service
+        .set_scratchpad(&chat_id, &content)
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
+
+/// The chat's custom instructions, `None` when it has none.
+#[tauri::command]
+pub fn get_chat_instructions(
+    chat_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
+    state.chat_service.get_instructions(&chat_id)
+}
+
+/// Replace the chat's custom instructions; blank removes them. They apply
+/// from the next turn on, edits and resends included.
+#[tauri::command]
+pub fn set_chat_instructions(
+    chat_id: String,
+    instructions: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Option<String>, AppError> {
+    state
+        .chat_service
+        .set_instructions(&chat_id, instructions.as_deref())
+}
+
+#[tauri::command]
+pub async fn notify_workspace_active(
+    workspace_id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state
+        .chat_service
+        .notify_workspace_active(app, workspace_id);
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn run_prompt_oneshot(
+    workspace_id: String,
+    prompt: String,
+    options: Option<OneshotOptions>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<OneshotResult, AppError> {
+    state
+        .chat_service
+        .run_prompt_oneshot(workspace_id, prompt, options.unwrap_or_default(), app)
+        .await
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
+
+/// Everything the chat screen needs to render the composer, in one call.
+/// Parts that fail to load are `None` and listed in `errors`.
+#[tauri::command]
+pub async fn get_chat_boot_data(
+    workspace_id: String,
+    chat_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<super::boot::ChatBootData, AppError> {
+    Ok(super::boot::gather(&state, &workspace_id, chat_id.as_deref()).await)
+}
+
+/// The timeline of the turn that ended with this assistant message, or
+/// `None` when it has none.
+#[tauri::command]
+pub fn get_turn_timeline(
+    message_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<super::timeline::TurnTimeline>, AppError> {
+    let message = state
+        .message_service
+        .get_by_id(&message_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Message not found: {message_id}")))?;
+    Ok(super::timeline::TurnTimeline::from_metadata(
+        message.metadata.as_deref(),
+    ))
+}
+
+/// What the chat is doing right now; later changes arrive as
+/// `active-chat-status-changed` events.
+#[tauri::command]
+pub fn get_active_chat_status(
+    chat_id: String,
+    state: State<'_, AppState>,
+) -> Result<super::status::ActiveChatStatus, AppError> {
+    state.chat_service.get_active_chat_status(&chat_id)
+}
+
+/// A link to a message, with the chat and an excerpt to label it.
+#[tauri::command]
+pub fn get_message_permalink(
+    message_id: String,
+    state: State<'_, AppState>,
+) -> Result<super::permalink::MessagePermalink, AppError> {
+    let message = state
+        .message_service
+        .get_by_id(&message_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Message not found: {message_id}")))?;
+    let chat = state
+        .chat_service
+        .get_by_id(&message.chat_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Chat not found: {}", message.chat_id)))?;
+    Ok(super::permalink::message_permalink(&chat, &message))
+}
+
+/// Follow a `lunex://` link from a note, the activity feed or a
+/// notification. The result is also sent as a `navigate-to-message` event.
+#[tauri::command]
+pub fn open_permalink(uri: String, app: AppHandle) -> crate::events::NavigateToMessageEvent {
+    super::permalink::navigate(&app, &uri)
+}
+
+/// The link the app was opened with, once.
+#[tauri::command]
+pub fn take_pending_navigation(
+    state: State<'_, AppState>,
+) -> Result<Option<crate::events::NavigateToMessageEvent>, AppError> {
+    let mut pending = state
+        .pending_navigation
+        .lock()
+        .map_err(|e| AppError::Generic(format!("Failed to read pending navigation: {e}")))?;
+    Ok(pending.take())
+}
+
+/// Export a chat's messages, tool calls, reasoning and attachments to a file
+/// the user picks. Returns the path written, `None` when the dialog was
+/// cancelled.
+#[tauri::command]
+pub async fn export_chat(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    chat_id: String,
+    format: ExportFormat,
+) -> Result<Option<String>, AppError> {
+    let export = state.chat_service.export_chat(&chat_id)?;
+    let file_name = export::export_file_name(&export.chats[0].title, format);
+    save_export(app, state.chat_service.clone(), export, format, file_name).await
+}
+
+/// Export every chat of a workspace to one file the user picks.
+#[tauri::command]
+pub async fn export_workspace(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    workspace_id: String,
+    format: ExportFormat,
+) -> Result<Option<String>, AppError> {
+    let workspace = state
+        .workspace_feature
+        .service
+        .get_by_id(&workspace_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Workspace {workspace_id} not found")))?;
+    let export = state.chat_service.export_workspace(&workspace_id)?;
+    let file_name = export::export_file_name(&workspace.name, format);
+    save_export(app, state.chat_service.clone(), export, format, file_name).await
+}
+
+/// Ask for a path off the main thread, then render and write the export.
+async fn save_export(
+    app: AppHandle,
+    chat_service: Arc<ChatService>,
+    export: ChatExport,
+    format: ExportFormat,
+    file_name: String,
+) -> Result<Option<String>, AppError> {
+    tauri::async_runtime::spawn_blocking(move || {
+        let Some(path) = app
+            .dialog()
+            .file()
+            .set_title("Export chats")
+            .set_file_name(&file_name)
+            .add_filter(format.label(), &[format.extension()])
+            .blocking_save_file()
+        else {
+            return Ok(None);
+        };
+        let path = path
+            .into_path()
+            .map_err(|e| AppError::Generic(format!("Invalid export path: {e}")))?;
+        std::fs::write(&path, chat_service.render_export(&export, format)?)?;
+        Ok(Some(path.to_string_lossy().to_string()))
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Chat export failed: {e}")))?
+}
+
+#[tauri::command]
+pub fn list_branches(
+    chat_id: String,
+    state: State<'_, AppState>,
+) -> Result<ChatBranches, AppError> {
+    state.chat_service.list_branches(&chat_id)
+}
+
+/// Fork the chat's active branch after `from_message_id` (before its first
+/// message when omitted) and switch to the new branch.
+#[tauri::command]
+pub fn create_branch(
+    chat_id: String,
+    from_message_id: Option<String>,
+    name: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<ChatBranch, AppError> {
+    state
+        .chat_service
+        .create_branch(&chat_id, from_message_id.as_deref(), name.as_deref())
+}
+
+/// Show another branch of the chat, the main one when `branch_id` is
+/// omitted. Returns its messages.
+#[tauri::command]
+pub fn switch_branch(
+    chat_id: String,
+    branch_id: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Message>, AppError> {
+    state
+        .chat_service
+        .switch_branch(&chat_id, branch_id.as_deref())
+}
@@ -0,0 +1,271 @@
+//! Comparison mode: one user message answered by two or three models at once.
+//!
+//! Each leg has its own assistant message and streams to it like a normal
+//! answer, without tools or the agent loop. The legs share a group id, kept
+//! in every answer's metadata, so the UI can show them side by side. A leg
+//! that fails leaves the others running; cancelling the chat stops them all.
+
+use crate::error::AppError;
+use crate::models::llm_types::LLMChatResponse;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tokio::sync::broadcast;
+
+/// Metadata key of the comparison an answer belongs to.
+pub const COMPARISON_METADATA_KEY: &str = "comparison";
+/// Usage records of comparison answers have this request type.
+pub const COMPARISON_REQUEST_TYPE: &str = "comparison";
+pub const MIN_LEGS: usize = 2;
+pub const MAX_LEGS: usize = 3;
+
+/// The `(connection_id, model)` pairs to compare, two or three of them.
+pub fn validate_targets(targets: &[(String, String)]) -> Result<(), AppError> {
+    if !(MIN_LEGS..=MAX_LEGS).contains(&targets.len()) {
+        return Err(AppError::Validation(format!(
+            "Comparison needs {MIN_LEGS} to {MAX_LEGS} models, got {}",
+            targets.len()
+        )));
+    }
+    if let Some((connection_id, model)) = targets
+        .iter()
+        .find(|(connection_id, model)| connection_id.is_empty() || model.is_empty())
+    {
+        return Err(AppError::Validation(format!(
+            "Comparison target needs a connection and a model, got '{connection_id}' / '{model}'"
+        )));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LegStatus {
+    Success,
+    Cancelled,
+    Error,
+}
+
+/// The `comparison` metadata of one answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonLeg {
+    pub group_id: String,
+    /// Position of the answer in the comparison, from 0
+    pub leg: usize,
+    pub connection_id: String,
+    pub model: String,
+    /// `None` while the leg runs
+    pub status: Option<LegStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// How one leg ended.
+#[derive(Debug)]
+pub struct LegOutcome {
+    pub leg: usize,
+    pub message_id: String,
+    pub result: Result<LLMChatResponse, AppError>,
+    /// Order in which the legs finished, from 0
+    pub finished: usize,
+}
+
+impl LegStatus {
+    /// The status usage records of the leg get.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Success => "success",
+            Self::Cancelled => "cancelled",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A cancelled stream still returns what arrived before the cancel.
+pub fn leg_status(result: &Result<LLMChatResponse, AppError>) -> LegStatus {
+    match result {
+        Ok(response) if response.finish_reason.as_deref() == Some("cancelled") => {
+            LegStatus::Cancelled
+        }
+        Ok(_) => LegStatus::Success,
+        Err(_) => LegStatus::Error,
+    }
+}
+
+impl LegOutcome {
+    pub fn status(&self) -> LegStatus {
+        leg_status(&self.result)
+    }
+}
+
+/// Run `call` for every answer in `legs`, a message id and what that leg
+/// needs, at once. Each leg gets its own receiver of `cancellation`, so one
+/// cancel reaches all of them, and every leg runs to its end whatever the
+/// others do.
+pub async fn run_legs<T, F, Fut>(
+    legs: Vec<(String, T)>,
+    cancellation: &broadcast::Sender<()>,
+    mut call: F,
+) -> Vec<LegOutcome>
+where
+    F: FnMut(String, T, broadcast::Receiver<()>) -> Fut,
+    Fut: Future<Output = Result<LLMChatResponse, AppError>>,
+{
+    let finished = AtomicUsize::new(0);
+    let legs = legs
+        .into_iter()
+        .enumerate()
+        .map(|(leg, (message_id, input))| {
+            let call = call(message_id.clone(), input, cancellation.subscribe());
+            let finished = &finished;
+            async move {
+                let result = call.await;
+                LegOutcome {
+                    leg,
+                    message_id,
+                    result,
+                    finished: finished.fetch_add(1, Ordering::SeqCst),
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    futures::future::join_all(legs).await
+}
+
+/// The leg whose answer becomes the chat's last message: the one that
+/// completed last, or `None` when none completed.
+pub fn last_completed(outcomes: &[LegOutcome]) -> Option<&LegOutcome> {
+    outcomes
+        .iter()
+        .filter(|outcome| outcome.status() == LegStatus::Success)
+        .max_by_key(|outcome| outcome.finished)
+}
+
+/// One answer of a finished comparison.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonAnswer {
+    pub message_id: String,
+    pub content: String,
+    #[serde(flatten)]
+    pub leg: ComparisonLeg,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ComparisonResult {
+    pub group_id: String,
+    pub user_message_id: String,
+    /// In the order the targets were given
+    pub answers: Vec<ComparisonAnswer>,
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{mock_provider, MockReply};
+
+    #[test]
+    fn comparison_targets_are_two_or_three_pairs() {
+        use crate::features::chat::comparison::validate_targets;
+
+        let pair = |c: &str, m: &str| (c.to_string(), m.to_string());
+        assert!(validate_targets(&[pair("a", "gpt-4o")]).is_err());
+        assert!(validate_targets(&[pair("a", "gpt-4o"), pair("b", "claude")]).is_ok());
+        // The same connection with two models is a comparison too
+        assert!(validate_targets(&[pair("a", "gpt-4o"), pair("a", "o3"), pair("b", "x")]).is_ok());
+        assert!(validate_targets(&vec![pair("a", "gpt-4o"); 4]).is_err());
+        assert!(validate_targets(&[pair("a", "gpt-4o"), pair("b", "")]).is_err());
+    }
+
+    #[test]
+    fn comparison_failed_leg_leaves_the_other_running() {
+        use crate::features::chat::comparison::{
+            last_completed, run_legs, ComparisonLeg, LegStatus,
+        };
+
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let legs = vec![
+            ("m-a".to_string(), MockReply::Fail("502 Bad Gateway")),
+            ("m-b".to_string(), MockReply::Answer("Paris", 30)),
+        ];
+        let outcomes = tokio::runtime::Runtime::new().unwrap().block_on(run_legs(
+            legs,
+            &cancellation,
+            |_, reply, rx| mock_provider(reply, rx),
+        ));
+
+        let statuses: Vec<_> = outcomes
+            .iter()
+            .map(|o| (o.message_id.as_str(), o.status()))
+            .collect();
+        assert_eq!(
+            statuses,
+            [("m-a", LegStatus::Error), ("m-b", LegStatus::Success)]
+        );
+        assert_eq!((outcomes[0].finished, outcomes[1].finished), (0, 1));
+        assert_eq!(outcomes[1].result.as_ref().unwrap().content, "Paris");
+        let last = last_completed(&outcomes).unwrap();
+        assert_eq!(last.message_id, "m-b");
+
+        // What the failed answer keeps in its metadata
+        let leg = ComparisonLeg {
+            group_id: "g1".to_string(),
+            leg: 0,
+            connection_id: "openai".to_string(),
+            model: "gpt-4o".to_string(),
+            status: Some(outcomes[0].status()),
+            error: outcomes[0].result.as_ref().err().map(ToString::to_string),
+        };
+        let json = serde_json::json!(leg);
+        assert_eq!(json["groupId"], "g1");
+        assert_eq!(json["status"], "error");
+        assert!(json["error"].as_str().unwrap().contains("502"));
+    }
+
+    #[test]
+    fn comparison_cancel_stops_every_running_leg() {
+        use crate::features::chat::comparison::{last_completed, run_legs, LegStatus};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let cancel_soon = || {
+            let sender = cancellation.clone();
+            runtime.spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                sender.send(()).unwrap();
+            })
+        };
+
+        // One leg finished before the cancel; the other stops where it was
+        cancel_soon();
+        let legs = vec![
+            ("m-a".to_string(), MockReply::Answer("Done", 0)),
+            ("m-b".to_string(), MockReply::UntilCancelled("Half an ans")),
+        ];
+        let started = std::time::Instant::now();
+        let outcomes = runtime.block_on(run_legs(legs, &cancellation, |_, reply, rx| {
+            mock_provider(reply, rx)
+        }));
+        assert!(started.elapsed() < std::time::Duration::from_secs(5));
+        assert_eq!(outcomes[0].status(), LegStatus::Success);
+        assert_eq!(outcomes[1].status(), LegStatus::Cancelled);
+        assert_eq!(outcomes[1].result.as_ref().unwrap().content, "Half an ans");
+        assert_eq!(last_completed(&outcomes).unwrap().message_id, "m-a");
+
+        // Cancelled mid-way, no leg completed
+        cancel_soon();
+        let legs = vec![
+            ("m-c".to_string(), MockReply::UntilCancelled("One")),
+            ("m-d".to_string(), MockReply::Answer("Two", 10_000)),
+            ("m-e".to_string(), MockReply::UntilCancelled("Three")),
+        ];
+        let outcomes = runtime.block_on(run_legs(legs, &cancellation, |_, reply, rx| {
+            mock_provider(reply, rx)
+        }));
+        assert!(outcomes
+            .iter()
+            .all(|outcome| outcome.status() == LegStatus::Cancelled));
+        assert!(last_completed(&outcomes).is_none());
+    }
+}
@@ -0,0 +1,325 @@
+//! Keeping a turn's prompt inside the model's context window.
+//!
+//! Token counts are estimates. GPT models are counted the way their BPE
+//! tokenizer splits text: a token per short word, digits in groups of three,
+//! punctuation in pairs. Other models are counted at about four characters
+//! per token. When the history would push the prompt past the window, the
+//! oldest turns are left out, a whole turn at a time so tool calls keep their
+//! results. Models whose window is not known get their full history.
+
+use super::cost_gate::{
+    estimate_prompt_tokens, estimate_text_tokens, ATTACHMENT_TOKENS, MESSAGE_OVERHEAD_TOKENS,
+};
+use super::user_files;
+use crate::features::llm_connection::models::LLMConnection;
+use crate::features::message::Message;
+use crate::features::tool::mcp_resources;
+use crate::models::llm_types::ChatCompletionTool;
+
+/// Completion tokens kept free when the workspace sets no `max_tokens`.
+pub const DEFAULT_RESERVED_OUTPUT_TOKENS: u32 = 4_096;
+
+/// Context windows of model families, matched against the model id in
+/// order, so a more specific family goes before the one it belongs to.
+const KNOWN_WINDOWS: [(&str, u32); 12] = [
+    // OpenAI
+    ("gpt-4.1", 1_047_576),
+    ("gpt-4o", 128_000),
+    ("gpt-4-turbo", 128_000),
+    ("gpt-4", 8_192),
+    ("gpt-3.5-turbo", 16_385),
+    // Anthropic
+    ("claude", 200_000),
+    // Google
+    ("gemini-1.5-pro", 2_097_152),
+    ("gemini", 1_048_576),
+    // Open models
+    ("llama3.1", 128_000),
+    ("llama3", 8_192),
+    ("mistral-large", 128_000),
+    ("qwen2.5", 32_768),
+];
+
+/// Letters a BPE token covers in an ASCII word, on average
+const LETTERS_PER_BPE_TOKEN: usize = 6;
+/// Digits BPE tokenizers keep in one token
+const DIGITS_PER_BPE_TOKEN: usize = 3;
+/// Punctuation characters BPE tokenizers merge into one token, on average
+const SYMBOLS_PER_BPE_TOKEN: usize = 2;
+
+/// The context window of `model`: the `contextWindow` its connection lists
+/// for it, else the known window of its family.
+pub fn context_window(model: &str, connection: Option<&LLMConnection>) -> Option<u32> {
+    connection
+        .and_then(|c| c.models_json.as_deref())
+        .and_then(|json| serde_json::from_str::<Vec<serde_json::Value>>(json).ok())
+        .and_then(|models| {
+            models
+                .iter()
+                .find(|m| m.get("id").and_then(|id| id.as_str()) == Some(model))
+                .and_then(|m| m.get("contextWindow")?.as_u64())
+        })
+        .and_then(|window| u32::try_from(window).ok())
+        .filter(|window| *window > 0)
+        .or_else(|| {
+            let model_lower = model.to_lowercase();
+            KNOWN_WINDOWS
+                .into_iter()
+                .find(|(family, _)| model_lower.contains(family))
+                .map(|(_, window)| window)
+        })
+}
+
+/// Whether `model` is counted with the BPE estimate of OpenAI's tokenizers.
+pub fn uses_bpe_tokenizer(model: &str) -> bool {
+    let model_lower = model.to_lowercase();
+    let name = model_lower.rsplit('/').next().unwrap_or(&model_lower);
+    name.starts_with("gpt-")
+        || name.starts_with("chatgpt-")
+        || ["o1", "o3", "o4"]
+            .iter()
+            .any(|family| name == *family || name.starts_with(&format!("{family}-")))
+}
+
+/// Tokens of `text` as a BPE tokenizer splits it: ASCII words (with the space
+/// before them) by length, other letters one each, digits in groups, runs of
+/// punctuation in pairs, and runs of whitespace not before a word as one.
+pub fn estimate_bpe_tokens(text: &str) -> u32 {
+    #[derive(PartialEq, Eq, Clone, Copy)]
+    enum Run {
+        Word,
+        Digits,
+        Symbols,
+        Space,
+    }
+    fn tokens(run: Run, len: usize) -> usize {
+        match run {
+            Run::Word => len.div_ceil(LETTERS_PER_BPE_TOKEN),
+            Run::Digits => len.div_ceil(DIGITS_PER_BPE_TOKEN),
+            Run::Symbols => len.div_ceil(SYMBOLS_PER_BPE_TOKEN),
+            Run::Space => 1,
+        }
+    }
+
+    let mut total = 0usize;
+    let mut current: Option<(Run, usize)> = None;
+    for c in text.chars() {
+        let run = if c.is_ascii_alphabetic() {
+            Run::Word
+        } else if c.is_ascii_digit() {
+            Run::Digits
+        } else if c.is_whitespace() {
+            Run::Space
+        } else if c.is_alphanumeric() {
+            // Letters outside ASCII take a token or more each
+            if let Some((run, len)) = current.take() {
+                total += tokens(run, len);
+            }
+            total += 1;
+            continue;
+        } else {
+            Run::Symbols
+        };
+        current = match current {
+            Some((same, len)) if same == run => Some((same, len + 1)),
+            // A single space joins the word after it
+            Some((Run::Space, 1)) if run == Run::Word => Some((Run::Word, 1)),
+            Some((previous, len)) => {
+                total += tokens(previous, len);
+                Some((run, 1))
+            }
+            None => Some((run, 1)),
+        };
+    }
+    if let Some((run, len)) = current {
+        total += tokens(run, len);
+    }
+    u32::try_from(total).unwrap_or(u32::MAX)
+}
+
+/// History the prompt left out to fit the model's context window.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextTrim {
+    /// User and assistant messages left out, oldest first
+    pub dropped_messages: usize,
+    /// Estimated tokens of the prompt as sent
+    pub estimated_tokens: u32,
+    pub context_window: u32,
+}
+
+/// How much of a model's context window the history of a turn may use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContextBudget {
+    pub context_window: u32,
+    /// Tokens kept free for the answer and the tool schemas
+    pub reserved_tokens: u32,
+    bpe: bool,
+}
+
+impl ContextBudget {
+    /// The budget of a request to `model`, or `None` when its window is not
+    /// known. The answer keeps `max_tokens`, or a default when unset.
+    pub fn for_request(
+        model: &str,
+        connection: Option<&LLMConnection>,
+        max_tokens: Option<u32>,
+        tools: Option<&[ChatCompletionTool]>,
+    ) -> Option<Self> {
+        let (_, tool_tokens) = estimate_prompt_tokens(&[], tools);
+        Some(Self {
+            context_window: context_window(model, connection)?,
+            reserved_tokens: max_tokens
+                .unwrap_or(DEFAULT_RESERVED_OUTPUT_TOKENS)
+                .saturating_add(tool_tokens),
+            bpe: uses_bpe_tokenizer(model),
+        })
+    }
+
+    pub fn text_tokens(&self, text: &str) -> u32 {
+        if self.bpe {
+            estimate_bpe_tokens(text)
+        } else {
+            estimate_text_tokens(text)
+        }
+    }
+
+    /// Tokens a stored message adds to the prompt, its attached files
+    /// included.
+    pub fn message_tokens(&self, message: &Message) -> u32 {
+        let files = message
+            .metadata
+            .as_deref()
+            .and_then(user_files::metadata_files)
+            .map_or(0, |files| files.len());
+        self.content_tokens(&message.content, files)
+            .saturating_add(self.resource_tokens(message.metadata.as_deref()))
+    }
+
+    /// Tokens of the MCP resources attached in a message's metadata.
+    pub fn resource_tokens(&self, metadata: Option<&str>) -> u32 {
+        metadata
+            .and_then(mcp_resources::resources_context)
+            .map_or(0, |context| self.text_tokens(&context))
+    }
+
+    /// Tokens of the parts every prompt of the turn sends whatever the
+    /// history: the system prompt and the new message with its files.
+    pub fn fixed_tokens(&self, system_prompt: &str, user_content: &str, user_files: usize) -> u32 {
+        self.content_tokens(system_prompt, 0)
+            .saturating_add(self.content_tokens(user_content, user_files))
+    }
+
+    fn content_tokens(&self, text: &str, files: usize) -> u32 {
+        let file_tokens = u32::try_from(files)
+            .unwrap_or(u32::MAX)
+            .saturating_mul(ATTACHMENT_TOKENS);
+        self.text_tokens(text)
+            .saturating_add(file_tokens)
+            .saturating_add(MESSAGE_OVERHEAD_TOKENS)
+    }
+
+    /// The newest part of `history` that fits beside `fixed_tokens` (system
+    /// prompt and new message), cut where a user message starts. The full
+    /// history and no trim when it all fits.
+    pub fn fit_history<'a>(
+        &self,
+        mut history: Vec<&'a Message>,
+        fixed_tokens: u32,
+    ) -> (Vec<&'a Message>, Option<ContextTrim>) {
+        let budget = self
+            .context_window
+            .saturating_sub(self.reserved_tokens)
+            .saturating_sub(fixed_tokens);
+        let costs: Vec<u32> = history.iter().map(|m| self.message_tokens(m)).collect();
+        let total = costs.iter().fold(0, |sum: u32, c| sum.saturating_add(*c));
+        if total <= budget {
+            return (history, None);
+        }
+
+        let mut used = 0u32;
+        let mut kept_tokens = 0;
+        let mut start = history.len();
+        for (index, message) in history.iter().enumerate().rev() {
+            used = used.saturating_add(costs[index]);
+            if used > budget {
+                break;
+            }
+            if message.role == "user" {
+                start = index;
+                kept_tokens = used;
+            }
+        }
+
+        let dropped_messages = history[..start]
+            .iter()
+            .filter(|m| m.role == "user" || m.role == "assistant")
+            .count();
+        let trim = ContextTrim {
+            dropped_messages,
+            estimated_tokens: fixed_tokens.saturating_add(kept_tokens),
+            context_window: self.context_window,
+        };
+        (history.split_off(start), Some(trim))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{chat_message, connection};
+
+    #[test]
+    fn history_is_trimmed_to_the_context_window_a_turn_at_a_time() {
+        use crate::features::chat::context_window::{
+            context_window, estimate_bpe_tokens, uses_bpe_tokenizer, ContextBudget,
+        };
+
+        assert_eq!(estimate_bpe_tokens("Hello world"), 2);
+        assert_eq!(estimate_bpe_tokens("1234567"), 3);
+        assert_eq!(estimate_bpe_tokens("tokenization!!"), 3);
+        assert!(uses_bpe_tokenizer("openai/gpt-4o-mini"));
+        assert!(uses_bpe_tokenizer("o3-mini"));
+        assert!(!uses_bpe_tokenizer("claude-3-5-sonnet"));
+
+        assert_eq!(context_window("gpt-4o-mini", None), Some(128_000));
+        assert_eq!(context_window("gpt-4-0613", None), Some(8_192));
+        assert_eq!(context_window("my-local-model", None), None);
+        let local = connection(
+            "local",
+            "ollama",
+            "my-local-model",
+            serde_json::json!([
+                { "id": "my-local-model", "name": "Local", "contextWindow": 2_000 }
+            ]),
+        );
+        assert_eq!(context_window("my-local-model", Some(&local)), Some(2_000));
+
+        // Models without a known window get their full history
+        assert!(ContextBudget::for_request("my-local-model", None, None, None).is_none());
+
+        // 404 tokens each, and 9 for the system prompt and new message
+        let long = "x".repeat(1_600);
+        let history = vec![
+            chat_message("u1", "user", &long),
+            chat_message("a1", "assistant", &long),
+            chat_message("u2", "user", "Short question"),
+            chat_message("a2", "assistant", &long),
+        ];
+        let budget =
+            ContextBudget::for_request("my-local-model", Some(&local), Some(1_000), None).unwrap();
+        let fixed = budget.fixed_tokens("", "Next", 0);
+        assert_eq!(fixed, 9);
+
+        // a1 would fit on its own, but the cut goes where a turn starts
+        let (kept, trim) = budget.fit_history(history.iter().collect(), fixed);
+        let kept: Vec<&str> = kept.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(kept, ["u2", "a2"]);
+        let trim = trim.unwrap();
+        assert_eq!(trim.dropped_messages, 2);
+        assert_eq!(trim.estimated_tokens, 9 + 8 + 404);
+        assert_eq!(trim.context_window, 2_000);
+
+        let (kept, trim) = budget.fit_history(history[2..].iter().collect(), fixed);
+        assert_eq!(kept.len(), 2);
+        assert!(trim.is_none());
+    }
+}
@@ -0,0 +1,488 @@
+//! Cost preview and confirmation before the first model call of a turn.
+//!
+//! Token counts are rough (about four characters per token, a flat size per
+//! attachment) and prices come from the usage pricing table, so an estimate
+//! tells "this turn will be expensive", not what it will be billed. Models
+//! without a price are never held.
+
+use crate::error::AppError;
+use crate::features::system::halt;
+use crate::features::usage::model_prices;
+use crate::models::llm_types::{
+    AssistantContent, ChatCompletionTool, ChatMessage, ContentPart, UserContent,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{broadcast, oneshot};
+
+/// How long a turn waits for the user before it is dropped.
+pub const COST_CONFIRMATION_TIMEOUT: Duration = Duration::from_mins(2);
+/// Completion tokens assumed when the workspace does not set a size.
+pub const DEFAULT_ASSUMED_COMPLETION_TOKENS: u32 = 1_000;
+pub const CHARS_PER_TOKEN: usize = 4;
+/// Flat size of an image or file part, whose tokens depend on the provider
+pub const ATTACHMENT_TOKENS: u32 = 1_500;
+/// Role and separator tokens every message adds
+pub const MESSAGE_OVERHEAD_TOKENS: u32 = 4;
+
+/// What the first call of a turn is expected to cost, in dollars.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct CostEstimate {
+    pub model: String,
+    /// Messages and tool schemas together
+    pub prompt_tokens: u32,
+    /// Part of `prompt_tokens` spent on tool schemas
+    pub tool_schema_tokens: u32,
+    pub assumed_completion_tokens: u32,
+    pub input_cost: f64,
+    pub output_cost: f64,
+    /// `input_cost + output_cost`; this is what the threshold is checked against
+    pub estimated_cost: f64,
+    /// The turn's cost if the agent loop runs all its calls, each sending the
+    /// prompt plus every earlier completion. Only set when tools are offered.
+    pub projected_loop_cost: Option<f64>,
+    /// Model calls `projected_loop_cost` counts, the first one included
+    pub projected_calls: Option<u32>,
+}
+
+/// The user's answer (or lack of one) to a cost confirmation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CostConfirmationOutcome {
+    Approved,
+    Denied,
+    TimedOut,
+    /// The turn was stopped, or the request dropped, while waiting
+    Cancelled,
+    /// Headless run configured to deny anything that needs permission
+    AutoDenied,
+}
+
+impl CostConfirmationOutcome {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Approved => "approved",
+            Self::Denied => "denied",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+            Self::AutoDenied => "auto_denied",
+        }
+    }
+
+    /// The error a turn that was not approved fails with.
+    pub fn into_error(self) -> Option<AppError> {
+        let reason = match self {
+            Self::Approved => return None,
+            Self::Denied => "The turn was not sent: its estimated cost was declined",
+            Self::TimedOut => "The turn was not sent: its estimated cost was not confirmed in time",
+            Self::Cancelled => "The turn was cancelled while waiting for cost confirmation",
+            Self::AutoDenied => {
+                "The turn was not sent: its estimated cost needs confirmation in a headless run"
+            }
+        };
+        Some(AppError::CostNotConfirmed(reason.to_string()))
+    }
+}
+
+pub fn estimate_text_tokens(text: &str) -> u32 {
+    u32::try_from(text.chars().count().div_ceil(CHARS_PER_TOKEN)).unwrap_or(u32::MAX)
+}
+
+fn estimate_parts_tokens(parts: &[ContentPart]) -> u32 {
+    parts
+        .iter()
+        .map(|part| match part {
+            ContentPart::Text { text } => estimate_text_tokens(text),
+            ContentPart::ImageUrl { .. }
+            | ContentPart::FileUrl { .. }
+            | ContentPart::InlineData { .. } => ATTACHMENT_TOKENS,
+        })
+        .fold(0, u32::saturating_add)
+}
+
+/// Estimated tokens of the messages and tool schemas of a request, returned
+/// as `(total, tool schemas)`.
+pub fn estimate_prompt_tokens(
+    messages: &[ChatMessage],
+    tools: Option<&[ChatCompletionTool]>,
+) -> (u32, u32) {
+    let message_tokens = messages
+        .iter()
+        .map(|message| {
+            let content = match message {
+                ChatMessage::System { content } | ChatMessage::Tool { content, .. } => {
+                    estimate_text_tokens(content)
+                }
+                ChatMessage::User {
+                    content: UserContent::Text(text),
+                } => estimate_text_tokens(text),
+                ChatMessage::User {
+                    content: UserContent::Parts(parts),
+                } => estimate_parts_tokens(parts),
+                ChatMessage::Assistant {
+                    content,
+                    tool_calls,
+                } => {
+                    let text = match content {
+                        AssistantContent::Text(text) => estimate_text_tokens(text),
+                        AssistantContent::Parts(parts) => estimate_parts_tokens(parts),
+                    };
+                    let calls = tool_calls.as_deref().unwrap_or_default().iter().fold(
+                        0,
+                        |sum: u32, call| {
+                            sum.saturating_add(estimate_text_tokens(&call.function.name))
+                                .saturating_add(estimate_text_tokens(&call.function.arguments))
+                        },
+                    );
+                    text.saturating_add(calls)
+                }
+            };
+            content.saturating_add(MESSAGE_OVERHEAD_TOKENS)
+        })
+        .fold(0, u32::saturating_add);
+    let tool_tokens = tools
+        .unwrap_or_default()
+        .iter()
+        .map(|tool| {
+            serde_json::to_string(tool)
+                .map(|schema| estimate_text_tokens(&schema))
+                .unwrap_or(0)
+        })
+        .fold(0, u32::saturating_add);
+    (message_tokens.saturating_add(tool_tokens), tool_tokens)
+}
+
+/// Estimate the first call of a turn, or `None` when the model has no price.
+/// `loop_calls` is how many more calls the agent loop may make after it.
+pub fn estimate_turn_cost(
+    model: &str,
+    messages: &[ChatMessage],
+    tools: Option<&[ChatCompletionTool]>,
+    assumed_completion_tokens: u32,
+    loop_calls: u32,
+) -> Option<CostEstimate> {
+    let (input_price, output_price) = model_prices(model)?;
+    let cost = |tokens: u32, price: f64| f64::from(tokens) / 1_000_000.0 * price;
+
+    let (prompt_tokens, tool_schema_tokens) = estimate_prompt_tokens(messages, tools);
+    let input_cost = cost(prompt_tokens, input_price);
+    let output_cost = cost(assumed_completion_tokens, output_price);
+    let estimated_cost = input_cost + output_cost;
+
+    let offers_tools = tools.is_some_and(|tools| !tools.is_empty());
+    let projection = (offers_tools && loop_calls > 0).then(|| {
+        let mut prompt = prompt_tokens;
+        let mut total = estimated_cost;
+        for _ in 0..loop_calls {
+            prompt = prompt.saturating_add(assumed_completion_tokens);
+            total += cost(prompt, input_price) + output_cost;
+        }
+        (total, loop_calls.saturating_add(1))
+    });
+
+    Some(CostEstimate {
+        model: model.to_string(),
+        prompt_tokens,
+        tool_schema_tokens,
+        assumed_completion_tokens,
+        input_cost,
+        output_cost,
+        estimated_cost,
+        projected_loop_cost: projection.map(|(total, _)| total),
+        projected_calls: projection.map(|(_, calls)| calls),
+    })
+}
+
+/// The threshold and estimate of a turn that must be confirmed, or `None`
+/// when it may go ahead: the workspace sets no threshold (`None`), the model
+/// is not priced, or the estimate is within the threshold.
+pub fn confirmation_needed(
+    threshold: Option<f64>,
+    estimate: Option<CostEstimate>,
+) -> Option<(f64, CostEstimate)> {
+    let threshold = threshold?;
+    estimate
+        .filter(|estimate| estimate.estimated_cost > threshold)
+        .map(|estimate| (threshold, estimate))
+}
+
+/// Turns waiting for their cost to be confirmed, by assistant message id.
+#[derive(Default)]
+pub struct CostConfirmations {
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl CostConfirmations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the turn of `message_id`: `request` tells the user, then this
+    /// waits for `respond`, `timeout`, or a cancel of the chat's turn.
+    pub async fn confirm(
+        &self,
+        message_id: &str,
+        timeout: Duration,
+        cancellation_rx: &mut broadcast::Receiver<()>,
+        request: impl FnOnce() -> Result<(), AppError>,
+    ) -> Result<CostConfirmationOutcome, AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.lock_pending()?.insert(message_id.to_string(), tx);
+        if let Err(e) = request() {
+            self.lock_pending()?.remove(message_id);
+            return Err(e);
+        }
+
+        let outcome = tokio::select! {
+            answer = rx => match answer {
+                Ok(true) => CostConfirmationOutcome::Approved,
+                Ok(false) => CostConfirmationOutcome::Denied,
+                Err(_) => CostConfirmationOutcome::Cancelled,
+            },
+            () = tokio::time::sleep(timeout) => CostConfirmationOutcome::TimedOut,
+            _ = cancellation_rx.recv() => CostConfirmationOutcome::Cancelled,
+        };
+        // Answered requests are already gone; the others must not linger
+        self.lock_pending()?.remove(message_id);
+        Ok(outcome)
+    }
+
+    /// Answer the pending confirmation of `message_id`.
+    pub fn respond(&self, message_id: &str, approved: bool) -> Result<(), AppError> {
+        let sender = self.lock_pending()?.remove(message_id).ok_or_else(|| {
+            AppError::Validation(format!(
+                "No pending cost confirmation found for message {message_id}"
+            ))
+        })?;
+        sender.send(approved).map_err(|_| {
+            AppError::Generic(format!(
+                "Failed to send cost confirmation for message {message_id}"
+            ))
+        })
+    }
+
+    /// End every pending confirmation as cancelled, returning how many there
+    /// were.
+    pub fn cancel_all(&self) -> Result<usize, AppError> {
+        Ok(halt::drop_pending(&mut self.lock_pending()?))
+    }
+
+    fn lock_pending(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<bool>>>, AppError> {
+        self.pending.lock().map_err(|e| {
+            AppError::Generic(format!("Failed to lock pending cost confirmations: {e}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn expensive_turn() -> (
+        Vec<crate::models::llm_types::ChatMessage>,
+        Vec<crate::models::llm_types::ChatCompletionTool>,
+    ) {
+        use crate::models::llm_types::{
+            ChatCompletionTool, ChatCompletionToolFunction, ChatMessage, ContentPart, UserContent,
+        };
+
+        // A long document extraction and a handful of tool schemas
+        let messages = vec![
+            ChatMessage::System {
+                content: "You are helpful.".to_string(),
+            },
+            ChatMessage::User {
+                content: UserContent::Parts(vec![
+                    ContentPart::Text {
+                        text: "page text ".repeat(40_000),
+                    },
+                    ContentPart::Text {
+                        text: "Summarize this.".to_string(),
+                    },
+                ]),
+            },
+        ];
+        let tools = (0..30)
+            .map(|i| ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: ChatCompletionToolFunction {
+                    name: format!("tool_{i}"),
+                    description: Some("Does something useful with a file".to_string()),
+                    parameters: Some(serde_json::json!({
+                        "type": "object",
+                        "properties": { "path": { "type": "string" } }
+                    })),
+                },
+            })
+            .collect();
+        (messages, tools)
+    }
+
+    #[test]
+    fn turn_cost_estimate_counts_prompt_tools_and_the_agent_loop() {
+        use crate::features::chat::cost_gate::estimate_turn_cost;
+
+        let (messages, tools) = expensive_turn();
+        let estimate =
+            estimate_turn_cost("claude-3-opus-20240229", &messages, Some(&tools), 1_000, 4)
+                .unwrap();
+
+        // 400k characters of text is about 100k tokens
+        assert!(
+            (100_000..110_000).contains(&estimate.prompt_tokens),
+            "{}",
+            estimate.prompt_tokens
+        );
+        assert!(estimate.tool_schema_tokens > 0);
+        // Opus: $15 in and $75 out per million tokens
+        assert!((estimate.output_cost - 0.075).abs() < 1e-9);
+        let input = f64::from(estimate.prompt_tokens) * 15.0 / 1_000_000.0;
+        assert!((estimate.input_cost - input).abs() < 1e-9);
+        assert!((estimate.estimated_cost - (input + 0.075)).abs() < 1e-9);
+        assert_eq!(estimate.projected_calls, Some(5));
+        assert!(estimate.projected_loop_cost.unwrap() > estimate.estimated_cost * 5.0);
+
+        // Without tools there is no loop to project
+        let no_tools =
+            estimate_turn_cost("claude-3-opus-20240229", &messages, None, 1_000, 4).unwrap();
+        assert_eq!(no_tools.tool_schema_tokens, 0);
+        assert_eq!(no_tools.projected_loop_cost, None);
+    }
+
+    #[test]
+    fn disabled_cost_confirmation_never_holds_a_turn() {
+        use crate::features::chat::cost_gate::{confirmation_needed, estimate_turn_cost};
+
+        let (messages, tools) = expensive_turn();
+        let estimate =
+            || estimate_turn_cost("claude-3-opus-20240229", &messages, Some(&tools), 1_000, 0);
+
+        assert!(confirmation_needed(None, estimate()).is_none());
+        assert!(confirmation_needed(Some(100.0), estimate()).is_none());
+        // Local models have no price, so they are never held
+        let unpriced = estimate_turn_cost("llama3.1:8b", &messages, Some(&tools), 1_000, 0);
+        assert!(unpriced.is_none());
+        assert!(confirmation_needed(Some(0.000_001), unpriced).is_none());
+
+        let (threshold, held) = confirmation_needed(Some(0.5), estimate()).unwrap();
+        assert!((threshold - 0.5).abs() < f64::EPSILON);
+        assert!(held.estimated_cost > 0.5);
+    }
+
+    #[test]
+    fn approved_cost_confirmation_lets_the_turn_continue() {
+        use crate::features::chat::cost_gate::{CostConfirmationOutcome, CostConfirmations};
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let confirmations = CostConfirmations::new();
+            let (_cancel, mut cancellation_rx) = tokio::sync::broadcast::channel(1);
+            let mut asked = 0;
+
+            // The user answers as soon as the request is shown
+            let outcome = confirmations
+                .confirm(
+                    "msg-1",
+                    Duration::from_secs(5),
+                    &mut cancellation_rx,
+                    || {
+                        asked += 1;
+                        confirmations.respond("msg-1", true)
+                    },
+                )
+                .await
+                .unwrap();
+
+            assert_eq!(asked, 1);
+            assert_eq!(outcome, CostConfirmationOutcome::Approved);
+            assert!(outcome.into_error().is_none());
+        });
+    }
+
+    #[test]
+    fn denied_cost_confirmation_fails_the_turn_with_its_own_error() {
+        use crate::error::AppError;
+        use crate::features::chat::cost_gate::{CostConfirmationOutcome, CostConfirmations};
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let confirmations = Arc::new(CostConfirmations::new());
+            let (_cancel, mut cancellation_rx) = tokio::sync::broadcast::channel(1);
+
+            let responder = confirmations.clone();
+            let answer = tokio::spawn(async move {
+                while responder.respond("msg-1", false).is_err() {
+                    tokio::time::sleep(Duration::from_millis(5)).await;
+                }
+            });
+            let outcome = confirmations
+                .confirm(
+                    "msg-1",
+                    Duration::from_secs(5),
+                    &mut cancellation_rx,
+                    || Ok(()),
+                )
+                .await
+                .unwrap();
+            answer.await.unwrap();
+
+            assert_eq!(outcome, CostConfirmationOutcome::Denied);
+            let error = outcome.into_error().unwrap();
+            assert!(matches!(error, AppError::CostNotConfirmed(_)));
+            assert!(error.to_string().starts_with("[CostNotConfirmed]"));
+            // Nothing is left waiting for an answer
+            assert!(matches!(
+                confirmations.respond("msg-1", true),
+                Err(AppError::Validation(_))
+            ));
+        });
+    }
+
+    #[test]
+    fn unanswered_cost_confirmation_times_out_or_is_cancelled() {
+        use crate::features::chat::cost_gate::{CostConfirmationOutcome, CostConfirmations};
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let confirmations = CostConfirmations::new();
+            let (cancel, mut cancellation_rx) = tokio::sync::broadcast::channel(1);
+
+            let outcome = confirmations
+                .confirm(
+                    "msg-1",
+                    Duration::from_millis(20),
+                    &mut cancellation_rx,
+                    || Ok(()),
+                )
+                .await
+                .unwrap();
+            assert_eq!(outcome, CostConfirmationOutcome::TimedOut);
+            assert!(outcome.into_error().is_some());
+            assert!(confirmations.respond("msg-1", true).is_err());
+
+            // Stopping the turn while it waits ends the wait at once
+            let outcome = confirmations
+                .confirm(
+                    "msg-2",
+                    Duration::from_secs(5),
+                    &mut cancellation_rx,
+                    || {
+                        cancel
+                            .send(())
+                            .map(|_| ())
+                            .map_err(|_| crate::error::AppError::Generic("no receiver".to_string()))
+                    },
+                )
+                .await
+                .unwrap();
+            assert_eq!(outcome, CostConfirmationOutcome::Cancelled);
+            assert!(confirmations.respond("msg-2", true).is_err());
+        });
+    }
+}
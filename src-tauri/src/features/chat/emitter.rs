@@ -1,7 +1,9 @@
-use crate::constants::TauriEvents;
 use crate::error::AppError;
-use crate::events::ChatUpdatedEvent;
-use tauri::{AppHandle, Emitter};
+use crate::events::{
+    emit_event, ActiveChatStatusChangedEvent, ChatUpdatedEvent, CostConfirmationRequestEvent,
+    ModelCapabilityWarningEvent, NavigateToMessageEvent,
+};
+use tauri::AppHandle;
 
 pub struct ChatEmitter {
     app: AppHandle,
@@ -13,11 +15,42 @@ impl ChatEmitter {
     }
 
     pub fn emit_chat_updated(&self, chat_id: String, title: String) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::CHAT_UPDATED,
-                ChatUpdatedEvent { chat_id, title },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit chat-updated event: {e}")))
+        emit_event(&self.app, ChatUpdatedEvent { chat_id, title })
+    }
+
+    pub fn emit_model_capability_warning(
+        &self,
+        chat_id: String,
+        prompt_id: String,
+        model: String,
+        missing_capabilities: Vec<String>,
+    ) -> Result<(), AppError> {
+        emit_event(
+            &self.app,
+            ModelCapabilityWarningEvent {
+                chat_id,
+                prompt_id,
+                model,
+                missing_capabilities,
+            },
+        )
+    }
+
+    pub fn emit_navigate_to_message(&self, event: NavigateToMessageEvent) -> Result<(), AppError> {
+        emit_event(&self.app, event)
+    }
+
+    pub fn emit_active_chat_status_changed(
+        &self,
+        status: super::status::ActiveChatStatus,
+    ) -> Result<(), AppError> {
+        emit_event(&self.app, ActiveChatStatusChangedEvent { status })
+    }
+
+    pub fn emit_cost_confirmation_request(
+        &self,
+        event: CostConfirmationRequestEvent,
+    ) -> Result<(), AppError> {
+        emit_event(&self.app, event)
     }
 }
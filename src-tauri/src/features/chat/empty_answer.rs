@@ -0,0 +1,244 @@
+//! Recovery from a blank final answer after tool use.
+//!
+//! Some models answer the last agent-loop request with nothing but
+//! whitespace after many tool turns. The loop then asks once more, without
+//! tools, for a summary; if that is blank too, the answer is built from the
+//! tool results so the work is not lost behind an empty bubble.
+
+use crate::error::AppError;
+use crate::models::llm_types::{ChatMessage, LLMChatResponse, UserContent};
+use std::collections::HashMap;
+use std::future::Future;
+
+/// Set in the assistant message metadata to "summary" or "fallback".
+pub const EMPTY_RESPONSE_RECOVERED_METADATA_KEY: &str = "empty_response_recovered";
+
+/// Appended as a user message for the extra summary request.
+pub const SUMMARY_INSTRUCTION: &str =
+    "Summarize the results of the tools you used and answer the user's original question.";
+
+/// Longest tool result excerpt in the fallback answer, in characters.
+const FALLBACK_EXCERPT_CHARS: usize = 200;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// The extra summary request produced the answer
+    Summary,
+    /// The answer was built from the tool results
+    Fallback,
+}
+
+impl Recovery {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Summary => "summary",
+            Self::Fallback => "fallback",
+        }
+    }
+}
+
+/// A final response with no text and nothing left to call.
+pub fn is_empty_answer(response: &LLMChatResponse) -> bool {
+    response.content.trim().is_empty() && response.tool_calls.as_ref().is_none_or(Vec::is_empty)
+}
+
+/// Ask for a summary with `call`, which sends the given messages without
+/// tools. Falls back to `fallback_answer` when the summary is blank too, or
+/// the request fails for any reason other than a cancel.
+pub async fn recover<F, Fut>(
+    history: &[ChatMessage],
+    call: F,
+) -> Result<(LLMChatResponse, Recovery), AppError>
+where
+    F: FnOnce(Vec<ChatMessage>) -> Fut,
+    Fut: Future<Output = Result<LLMChatResponse, AppError>>,
+{
+    let mut messages = history.to_vec();
+    messages.push(ChatMessage::User {
+        content: UserContent::Text(SUMMARY_INSTRUCTION.to_string()),
+    });
+
+    let failed = match call(messages).await {
+        Ok(response) if !is_empty_answer(&response) => return Ok((response, Recovery::Summary)),
+        Ok(response) => response,
+        Err(AppError::Cancelled) => return Err(AppError::Cancelled),
+        Err(e) => {
+            tracing::warn!(error = %e, "Summary request after an empty answer failed");
+            LLMChatResponse {
+                content: String::new(),
+                finish_reason: None,
+                tool_calls: None,
+                usage: None,
+                reasoning: None,
+                images: None,
+                parameter_notes: Vec::new(),
+                model: None,
+                system_fingerprint: None,
+            }
+        }
+    };
+    Ok((
+        LLMChatResponse {
+            content: fallback_answer(history),
+            tool_calls: None,
+            ..failed
+        },
+        Recovery::Fallback,
+    ))
+}
+
+/// An answer listing the tools of the current turn (after the last user
+/// message) with the first line of each result.
+pub fn fallback_answer(history: &[ChatMessage]) -> String {
+    let turn_start = history
+        .iter()
+        .rposition(|m| matches!(m, ChatMessage::User { .. }))
+        .map_or(0, |i| i + 1);
+    let turn = &history[turn_start..];
+
+    let names: HashMap<&str, &str> = turn
+        .iter()
+        .filter_map(|m| match m {
+            ChatMessage::Assistant {
+                tool_calls: Some(calls),
+                ..
+            } => Some(calls),
+            _ => None,
+        })
+        .flatten()
+        .map(|call| (call.id.as_str(), call.function.name.as_str()))
+        .collect();
+
+    let lines: Vec<String> = turn
+        .iter()
+        .filter_map(|m| match m {
+            ChatMessage::Tool {
+                content,
+                tool_call_id,
+            } => {
+                let name = names.get(tool_call_id.as_str()).copied().unwrap_or("tool");
+                Some(format!("- **{name}**: {}", first_line(content)))
+            }
+            _ => None,
+        })
+        .collect();
+
+    if lines.is_empty() {
+        return "The model returned an empty answer. Please try again.".to_string();
+    }
+    format!(
+        "The model returned an empty answer after using tools. Here is what the tools returned:\n\n{}",
+        lines.join("\n")
+    )
+}
+
+fn first_line(content: &str) -> String {
+    let line = content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or("(no output)");
+    crate::lib::text::truncate(line, FALLBACK_EXCERPT_CHARS, "…")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::scripted_response;
+
+    fn empty_answer_history() -> Vec<crate::models::llm_types::ChatMessage> {
+        use crate::models::llm_types::{
+            AssistantContent, ChatMessage, ToolCall, ToolCallFunction, UserContent,
+        };
+        let call = |id: &str, name: &str| ToolCall {
+            id: id.to_string(),
+            r#type: "function".to_string(),
+            function: ToolCallFunction {
+                name: name.to_string(),
+                arguments: "{}".to_string(),
+            },
+        };
+        vec![
+            ChatMessage::User {
+                content: UserContent::Text("What's in the repo?".to_string()),
+            },
+            ChatMessage::Assistant {
+                content: AssistantContent::Text(String::new()),
+                tool_calls: Some(vec![call("c1", "list_files"), call("c2", "read_file")]),
+            },
+            ChatMessage::Tool {
+                content: "\n  src/\nCargo.toml\n".to_string(),
+                tool_call_id: "c1".to_string(),
+            },
+            ChatMessage::Tool {
+                content: String::new(),
+                tool_call_id: "c2".to_string(),
+            },
+        ]
+    }
+
+    #[test]
+    fn empty_answer_after_tools_is_summarized() {
+        use crate::features::chat::empty_answer::{self, Recovery, SUMMARY_INSTRUCTION};
+        use crate::models::llm_types::{ChatMessage, UserContent};
+
+        // The provider answers the last loop request with whitespace only
+        let history = empty_answer_history();
+        assert!(empty_answer::is_empty_answer(&scripted_response(" \n\t")));
+        assert!(!empty_answer::is_empty_answer(&scripted_response("Done.")));
+
+        let mut requests = Vec::new();
+        let (response, recovery) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(empty_answer::recover(&history, |messages| {
+                requests.push(messages);
+                async { Ok(scripted_response("The repo has a src folder.")) }
+            }))
+            .unwrap();
+
+        assert_eq!(recovery, Recovery::Summary);
+        assert_eq!(recovery.as_str(), "summary");
+        assert_eq!(response.content, "The repo has a src folder.");
+        // One extra request: the history plus the summary instruction
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].len(), history.len() + 1);
+        assert!(matches!(
+            requests[0].last(),
+            Some(ChatMessage::User { content: UserContent::Text(text) }) if text == SUMMARY_INSTRUCTION
+        ));
+    }
+
+    #[test]
+    fn empty_answer_twice_falls_back_to_tool_results() {
+        use crate::error::AppError;
+        use crate::features::chat::empty_answer::{self, Recovery};
+
+        let history = empty_answer_history();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let (response, recovery) = runtime
+            .block_on(empty_answer::recover(&history, |_| async {
+                Ok(scripted_response("   "))
+            }))
+            .unwrap();
+        assert_eq!(recovery, Recovery::Fallback);
+        assert!(response.content.contains("- **list_files**: src/"));
+        assert!(response.content.contains("- **read_file**: (no output)"));
+        // Usage of the summary call is kept
+        assert_eq!(response.finish_reason.as_deref(), Some("stop"));
+
+        // A failed summary request falls back too; a cancel does not
+        let (failed, recovery) = runtime
+            .block_on(empty_answer::recover(&history, |_| async {
+                Err(AppError::Generic("HTTP 500".to_string()))
+            }))
+            .unwrap();
+        assert_eq!(recovery, Recovery::Fallback);
+        assert_eq!(failed.content, response.content);
+        assert!(matches!(
+            runtime.block_on(empty_answer::recover(&history, |_| async {
+                Err(AppError::Cancelled)
+            })),
+            Err(AppError::Cancelled)
+        ));
+    }
+}
@@ -0,0 +1,496 @@
+//! Chats written out as Markdown, JSON or a self-contained HTML page.
+//!
+//! An export holds the user and assistant messages of each chat, with the
+//! reasoning of an answer and the tool calls it made, each with its result.
+//! Files attached to a message are listed by name; the HTML page embeds
+//! attached images so it can be opened anywhere. Images referenced from
+//! message content are embedded by the chat service before rendering.
+
+use super::models::Chat;
+use crate::error::AppError;
+use crate::features::message::models::Message;
+use crate::features::message::tool_exchange::tool_call_id_of;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::Path;
+use std::sync::LazyLock;
+
+/// Version of the JSON export layout.
+pub const CHAT_EXPORT_VERSION: u32 = 1;
+
+/// Markdown images whose target is a data URL, as left by embedding.
+static EMBEDDED_IMAGE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"!\[([^\]]*)\]\((data:image/[^)\s]+)\)").unwrap());
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Markdown,
+    Json,
+    Html,
+}
+
+impl ExportFormat {
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Markdown => "md",
+            Self::Json => "json",
+            Self::Html => "html",
+        }
+    }
+
+    /// Name of the format in the save dialog's file filter.
+    pub const fn label(self) -> &'static str {
+        match self {
+            Self::Markdown => "Markdown",
+            Self::Json => "JSON",
+            Self::Html => "HTML",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ChatExport {
+    pub version: u32,
+    pub exported_at: i64,
+    /// Set when the export bundles all chats of a workspace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    pub chats: Vec<ExportedChat>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedChat {
+    pub id: String,
+    pub workspace_id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub custom_instructions: Option<String>,
+    pub messages: Vec<ExportedMessage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedMessage {
+    pub id: String,
+    pub role: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    pub timestamp: i64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tool_calls: Vec<ExportedToolCall>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<ExportedFile>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedToolCall {
+    pub id: String,
+    pub name: String,
+    /// As shown in the chat, so with secrets masked
+    pub arguments: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportedFile {
+    pub name: String,
+    /// Where the file is stored, or the URL it was attached from
+    pub path: String,
+}
+
+impl ExportedFile {
+    fn from_reference(reference: &str) -> Self {
+        let name = if reference.starts_with("data:") {
+            "attachment".to_string()
+        } else {
+            Path::new(reference).file_name().map_or_else(
+                || reference.to_string(),
+                |name| name.to_string_lossy().to_string(),
+            )
+        };
+        Self {
+            name,
+            path: reference.to_string(),
+        }
+    }
+}
+
+/// A chat as exported: its user and assistant messages in order, each
+/// answer with the tool calls made for it.
+pub fn exported_chat(chat: &Chat, messages: &[Message]) -> ExportedChat {
+    let results: HashMap<&str, &str> = messages
+        .iter()
+        .filter(|m| m.role == "tool")
+        .filter_map(|m| Some((tool_call_id_of(m)?, m.content.as_str())))
+        .collect();
+    let mut tool_calls: HashMap<&str, Vec<ExportedToolCall>> = HashMap::new();
+    for message in messages.iter().filter(|m| m.role == "tool_call") {
+        let (Some(assistant_id), Some(call_id)) = (
+            message.assistant_message_id.as_deref(),
+            tool_call_id_of(message),
+        ) else {
+            continue;
+        };
+        let data: serde_json::Value = serde_json::from_str(&message.content).unwrap_or_default();
+        let text = |key: &str| data.get(key).and_then(|v| v.as_str()).map(str::to_string);
+        tool_calls
+            .entry(assistant_id)
+            .or_default()
+            .push(ExportedToolCall {
+                id: call_id.to_string(),
+                name: text("name").unwrap_or_default(),
+                arguments: text("arguments").unwrap_or_default(),
+                status: text("status"),
+                result: results.get(call_id).map(|r| (*r).to_string()),
+            });
+    }
+
+    ExportedChat {
+        id: chat.id.clone(),
+        workspace_id: chat.workspace_id.clone(),
+        title: chat.title.clone(),
+        created_at: chat.created_at,
+        updated_at: chat.updated_at,
+        custom_instructions: chat.custom_instructions.clone(),
+        messages: messages
+            .iter()
+            .filter(|m| matches!(m.role.as_str(), "user" | "assistant"))
+            .map(|m| ExportedMessage {
+                id: m.id.clone(),
+                role: m.role.clone(),
+                content: m.content.clone(),
+                reasoning: m.reasoning.clone().filter(|r| !r.trim().is_empty()),
+                timestamp: m.timestamp,
+                tool_calls: tool_calls.remove(m.id.as_str()).unwrap_or_default(),
+                files: attached_files(m.metadata.as_deref()),
+            })
+            .collect(),
+    }
+}
+
+/// Files listed in a message's metadata.
+fn attached_files(metadata: Option<&str>) -> Vec<ExportedFile> {
+    let Some(meta) = metadata.and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+    else {
+        return Vec::new();
+    };
+    meta.get("files")
+        .and_then(|f| f.as_array())
+        .into_iter()
+        .flatten()
+        .filter_map(|f| f.as_str())
+        .map(ExportedFile::from_reference)
+        .collect()
+}
+
+/// A file name for an export of `title`, without characters file systems
+/// reject.
+pub fn export_file_name(title: &str, format: ExportFormat) -> String {
+    let name: String = title
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || matches!(c, ' ' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let name = name.trim();
+    let name = if name.is_empty() { "chat" } else { name };
+    format!("{name}.{}", format.extension())
+}
+
+/// The export in `format`. For HTML, `embed` gives the data URL of an
+/// attached image, or `None` to list the file by name only.
+pub fn render_export(
+    export: &ChatExport,
+    format: ExportFormat,
+    embed: impl Fn(&ExportedFile) -> Option<String>,
+) -> Result<String, AppError> {
+    match format {
+        ExportFormat::Json => Ok(serde_json::to_string_pretty(export)?),
+        ExportFormat::Markdown => Ok(render_markdown(export)),
+        ExportFormat::Html => Ok(render_html(export, embed)),
+    }
+}
+
+fn role_label(role: &str) -> &'static str {
+    if role == "user" {
+        "User"
+    } else {
+        "Assistant"
+    }
+}
+
+fn format_timestamp(millis: i64) -> String {
+    chrono::DateTime::from_timestamp_millis(millis)
+        .map(|t| t.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default()
+}
+
+/// A fence longer than any backtick run in `text`, so it cannot close early.
+fn code_fence(text: &str) -> String {
+    let longest = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or_default();
+    "`".repeat(longest.max(2) + 1)
+}
+
+fn render_markdown(export: &ChatExport) -> String {
+    let mut out = String::new();
+    for (i, chat) in export.chats.iter().enumerate() {
+        if i > 0 {
+            out.push_str("\n---\n\n");
+        }
+        let _ = writeln!(out, "# {}\n", chat.title);
+        let _ = writeln!(out, "*Created {}*\n", format_timestamp(chat.created_at));
+        if let Some(instructions) = &chat.custom_instructions {
+            let _ = writeln!(out, "> **Instructions:** {}\n", instructions.trim());
+        }
+        for message in &chat.messages {
+            let _ = writeln!(
+                out,
+                "## {} · {}\n",
+                role_label(&message.role),
+                format_timestamp(message.timestamp)
+            );
+            if let Some(reasoning) = &message.reasoning {
+                let _ = writeln!(
+                    out,
+                    "<details>\n<summary>Reasoning</summary>\n\n{}\n\n</details>\n",
+                    reasoning.trim()
+                );
+            }
+            if !message.content.trim().is_empty() {
+                let _ = writeln!(out, "{}\n", message.content.trim());
+            }
+            for call in &message.tool_calls {
+                let _ = writeln!(out, "**Tool call:** `{}`\n", call.name);
+                let fence = code_fence(&call.arguments);
+                let _ = writeln!(out, "{fence}json\n{}\n{fence}\n", call.arguments);
+                if let Some(result) = &call.result {
+                    let fence = code_fence(result);
+                    let _ = writeln!(out, "Result:\n\n{fence}\n{}\n{fence}\n", result.trim());
+                }
+            }
+            if !message.files.is_empty() {
+                out.push_str("**Attachments:**\n\n");
+                for file in &message.files {
+                    let _ = writeln!(out, "- {}", file.name);
+                }
+                out.push('\n');
+            }
+        }
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// Message text as HTML: escaped, with embedded images shown inline.
+fn html_content(text: &str) -> String {
+    EMBEDDED_IMAGE
+        .replace_all(&escape_html(text), r#"<img alt="$1" src="$2">"#)
+        .into_owned()
+}
+
+const HTML_STYLE: &str = "body{font-family:system-ui,sans-serif;max-width:860px;margin:2rem auto;padding:0 1rem;color:#1f2328}\
+h1{border-bottom:1px solid #d0d7de;padding-bottom:.3rem}\
+.message{border:1px solid #d0d7de;border-radius:8px;padding:.75rem 1rem;margin:1rem 0}\
+.message.user{background:#f6f8fa}\
+.meta{color:#656d76;font-size:.85rem;margin-bottom:.5rem}\
+.content{white-space:pre-wrap;word-wrap:break-word}\
+pre{background:#f6f8fa;padding:.5rem;overflow-x:auto;white-space:pre-wrap}\
+img{max-width:100%}";
+
+fn render_html(export: &ChatExport, embed: impl Fn(&ExportedFile) -> Option<String>) -> String {
+    let title = match export.chats.as_slice() {
+        [chat] => chat.title.clone(),
+        _ => "Chat export".to_string(),
+    };
+    let mut out = String::new();
+    let _ = write!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{HTML_STYLE}</style>\n</head>\n<body>\n",
+        escape_html(&title)
+    );
+    for chat in &export.chats {
+        let _ = writeln!(out, "<section>\n<h1>{}</h1>", escape_html(&chat.title));
+        let _ = writeln!(
+            out,
+            "<p class=\"meta\">Created {}</p>",
+            format_timestamp(chat.created_at)
+        );
+        if let Some(instructions) = &chat.custom_instructions {
+            let _ = writeln!(
+                out,
+                "<p class=\"meta\"><strong>Instructions:</strong> {}</p>",
+                escape_html(instructions.trim())
+            );
+        }
+        for message in &chat.messages {
+            let _ = writeln!(
+                out,
+                "<div class=\"message {}\">\n<div class=\"meta\">{} · {}</div>",
+                escape_html(&message.role),
+                role_label(&message.role),
+                format_timestamp(message.timestamp)
+            );
+            if let Some(reasoning) = &message.reasoning {
+                let _ = writeln!(
+                    out,
+                    "<details><summary>Reasoning</summary><div class=\"content\">{}</div></details>",
+                    html_content(reasoning.trim())
+                );
+            }
+            let _ = writeln!(
+                out,
+                "<div class=\"content\">{}</div>",
+                html_content(message.content.trim())
+            );
+            for call in &message.tool_calls {
+                let _ = writeln!(
+                    out,
+                    "<details><summary>Tool call: <code>{}</code></summary>\n<pre>{}</pre>",
+                    escape_html(&call.name),
+                    escape_html(&call.arguments)
+                );
+                if let Some(result) = &call.result {
+                    let _ = writeln!(
+                        out,
+                        "<div class=\"meta\">Result</div>\n<pre>{}</pre>",
+                        escape_html(result.trim())
+                    );
+                }
+                out.push_str("</details>\n");
+            }
+            for file in &message.files {
+                match embed(file) {
+                    Some(url) => {
+                        let _ = writeln!(
+                            out,
+                            "<p><img alt=\"{0}\" src=\"{1}\"></p>",
+                            escape_html(&file.name),
+                            escape_html(&url)
+                        );
+                    }
+                    None => {
+                        let _ = writeln!(
+                            out,
+                            "<p class=\"meta\">Attachment: {}</p>",
+                            escape_html(&file.name)
+                        );
+                    }
+                }
+            }
+            out.push_str("</div>\n");
+        }
+        out.push_str("</section>\n");
+    }
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn chat_export_groups_tool_calls_and_renders_every_format() {
+        use crate::features::chat::export::{
+            export_file_name, exported_chat, render_export, ChatExport, ExportFormat,
+        };
+        use crate::features::chat::models::Chat;
+
+        let chat = Chat {
+            id: "chat".to_string(),
+            workspace_id: "ws".to_string(),
+            title: "Plans: <Q3>".to_string(),
+            last_message: None,
+            created_at: 0,
+            updated_at: 0,
+            agent_id: None,
+            parent_id: None,
+            last_read_at: None,
+            unread_count: 0,
+            custom_instructions: None,
+        };
+        let mut question = chat_message("m1", "user", "What is on the <agenda>?");
+        question.metadata =
+            Some(r#"{"files":["/data/files/notes.pdf","/data/files/chart.png"]}"#.to_string());
+        let mut answer = chat_message("m2", "assistant", "Two items.");
+        answer.reasoning = Some("Check the calendar".to_string());
+        let mut call = chat_message(
+            "tool_call_c1",
+            "tool_call",
+            r#"{"name":"calendar","arguments":"{\"day\":1}","status":"completed"}"#,
+        );
+        call.assistant_message_id = Some("m2".to_string());
+        let mut result = chat_message("tool_result_c1", "tool", "Standup, review");
+        result.tool_call_id = Some("c1".to_string());
+
+        let exported = exported_chat(&chat, &[question, answer, call, result]);
+        assert_eq!(exported.messages.len(), 2);
+        let files: Vec<&str> = exported.messages[0]
+            .files
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        assert_eq!(files, ["notes.pdf", "chart.png"]);
+        let calls = &exported.messages[1].tool_calls;
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "calendar");
+        assert_eq!(calls[0].arguments, "{\"day\":1}");
+        assert_eq!(calls[0].result.as_deref(), Some("Standup, review"));
+
+        let export = ChatExport {
+            version: 1,
+            exported_at: 0,
+            workspace_id: None,
+            chats: vec![exported],
+        };
+        let json = render_export(&export, ExportFormat::Json, |_| None).unwrap();
+        assert_eq!(serde_json::from_str::<ChatExport>(&json).unwrap(), export);
+
+        let markdown = render_export(&export, ExportFormat::Markdown, |_| None).unwrap();
+        assert!(markdown.starts_with("# Plans: <Q3>"));
+        assert!(markdown.contains("<summary>Reasoning</summary>"));
+        assert!(markdown.contains("**Tool call:** `calendar`"));
+        assert!(markdown.contains("- notes.pdf"));
+
+        let html = render_export(&export, ExportFormat::Html, |file| {
+            file.name
+                .ends_with(".png")
+                .then(|| "data:image/png;base64,AAAA".to_string())
+        })
+        .unwrap();
+        assert!(html.contains("<title>Plans: &lt;Q3&gt;</title>"));
+        assert!(html.contains("What is on the &lt;agenda&gt;?"));
+        assert!(html.contains(r#"<img alt="chart.png" src="data:image/png;base64,AAAA">"#));
+        assert!(html.contains("Attachment: notes.pdf"));
+
+        assert_eq!(
+            export_file_name("Plans: <Q3>", ExportFormat::Html),
+            "Plans_ _Q3_.html"
+        );
+        assert_eq!(export_file_name(" / ", ExportFormat::Markdown), "_.md");
+        assert_eq!(export_file_name("", ExportFormat::Json), "chat.json");
+    }
+}
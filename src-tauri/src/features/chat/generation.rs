@@ -0,0 +1,180 @@
+//! Sampling parameters of a chat turn.
+//!
+//! A workspace sets them for all its chats and the chat input may override
+//! them one by one. What neither sets is left to the provider, except the
+//! temperature, which keeps its long-standing default. Each provider maps
+//! the parameters onto its own request format and drops those it has no
+//! equivalent for.
+
+use super::input_settings::models::ChatInputSettings;
+use crate::error::AppError;
+use crate::features::workspace::settings::WorkspaceSettings;
+use serde::{Deserialize, Serialize};
+
+/// Temperature of turns whose workspace and chat input set none.
+pub const DEFAULT_TEMPERATURE: f64 = 0.7;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GenerationParams {
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+    pub max_tokens: Option<u32>,
+}
+
+impl GenerationParams {
+    /// Check each set parameter is within the range providers accept.
+    pub fn validate(&self) -> Result<(), AppError> {
+        let ranges = [
+            ("Temperature", self.temperature, 0.0, 2.0),
+            ("Top P", self.top_p, 0.0, 1.0),
+            ("Frequency penalty", self.frequency_penalty, -2.0, 2.0),
+            ("Presence penalty", self.presence_penalty, -2.0, 2.0),
+        ];
+        for (name, value, min, max) in ranges {
+            if let Some(value) = value.filter(|v| !(min..=max).contains(v)) {
+                return Err(AppError::Validation(format!(
+                    "{name} must be between {min} and {max}, got {value}"
+                )));
+            }
+        }
+        if self.max_tokens == Some(0) {
+            return Err(AppError::Validation(
+                "Max tokens must be a positive whole number".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn of_workspace(settings: &WorkspaceSettings) -> Self {
+        Self {
+            temperature: settings.temperature,
+            top_p: settings.top_p,
+            frequency_penalty: settings.frequency_penalty,
+            presence_penalty: settings.presence_penalty,
+            max_tokens: settings.max_tokens.and_then(|n| u32::try_from(n).ok()),
+        }
+    }
+
+    pub fn of_input(settings: &ChatInputSettings) -> Self {
+        Self {
+            temperature: settings.temperature,
+            top_p: settings.top_p,
+            frequency_penalty: settings.frequency_penalty,
+            presence_penalty: settings.presence_penalty,
+            max_tokens: settings.max_tokens.and_then(|n| u32::try_from(n).ok()),
+        }
+    }
+
+    /// These parameters, with those they leave unset taken from `fallback`.
+    #[must_use]
+    pub fn or(self, fallback: Self) -> Self {
+        Self {
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            frequency_penalty: self.frequency_penalty.or(fallback.frequency_penalty),
+            presence_penalty: self.presence_penalty.or(fallback.presence_penalty),
+            max_tokens: self.max_tokens.or(fallback.max_tokens),
+        }
+    }
+
+    /// The parameters a turn runs with: the chat input's over the
+    /// workspace's, at the default temperature when neither sets one.
+    pub fn resolve(workspace: &WorkspaceSettings, input: Option<&ChatInputSettings>) -> Self {
+        let params = input.map(Self::of_input).unwrap_or_default();
+        let params = params.or(Self::of_workspace(workspace));
+        Self {
+            temperature: params.temperature.or(Some(DEFAULT_TEMPERATURE)),
+            ..params
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::services::llm::reproducibility::tests::seeded_request;
+    use crate::test_support::boot_workspace_settings;
+
+    #[test]
+    fn generation_params_override_the_workspace_and_reach_providers() {
+        use crate::features::chat::generation::{GenerationParams, DEFAULT_TEMPERATURE};
+        use crate::features::chat::input_settings::models::ChatInputSettings;
+        use crate::services::llm::providers::{ollama, AnthropicProvider};
+
+        let mut workspace = boot_workspace_settings(None, None);
+        assert_eq!(
+            GenerationParams::resolve(&workspace, None),
+            GenerationParams {
+                temperature: Some(DEFAULT_TEMPERATURE),
+                ..GenerationParams::default()
+            }
+        );
+
+        workspace.temperature = Some(0.2);
+        workspace.top_p = Some(0.9);
+        workspace.max_tokens = Some(2048);
+        let input = ChatInputSettings {
+            workspace_id: "ws".to_string(),
+            selected_model: None,
+            stream_enabled: 1,
+            temperature: Some(1.1),
+            top_p: None,
+            frequency_penalty: Some(0.5),
+            presence_penalty: None,
+            max_tokens: None,
+            created_at: 0,
+            updated_at: 0,
+        };
+        let params = GenerationParams::resolve(&workspace, Some(&input));
+        assert_eq!(params.temperature, Some(1.1));
+        assert_eq!(params.top_p, Some(0.9));
+        assert_eq!(params.frequency_penalty, Some(0.5));
+        assert_eq!(params.presence_penalty, None);
+        assert_eq!(params.max_tokens, Some(2048));
+
+        assert!(params.validate().is_ok());
+        for invalid in [
+            GenerationParams {
+                temperature: Some(2.5),
+                ..GenerationParams::default()
+            },
+            GenerationParams {
+                top_p: Some(-0.1),
+                ..GenerationParams::default()
+            },
+            GenerationParams {
+                presence_penalty: Some(3.0),
+                ..GenerationParams::default()
+            },
+            GenerationParams {
+                max_tokens: Some(0),
+                ..GenerationParams::default()
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{invalid:?}");
+        }
+
+        // Ollama takes them as options; Anthropic has no penalties
+        let mut request = seeded_request(None);
+        request.top_p = params.top_p;
+        request.frequency_penalty = params.frequency_penalty;
+        let body = ollama::chat_body(&request);
+        assert_eq!(body["options"]["top_p"], 0.9);
+        assert_eq!(body["options"]["frequency_penalty"], 0.5);
+        let compat = serde_json::to_value(&request).unwrap();
+        assert_eq!(compat["top_p"], 0.9);
+        assert!(compat.get("presence_penalty").is_none());
+        let body = serde_json::to_value(AnthropicProvider::build_request(request.clone())).unwrap();
+        assert_eq!(body["temperature"], 0.7);
+        assert_eq!(body["top_p"], 0.9);
+        assert!(body.get("frequency_penalty").is_none());
+        // Thinking takes no sampling parameters
+        request.model = "claude-3-7-sonnet-20250219".to_string();
+        request.reasoning_effort = Some("high".to_string());
+        let body = serde_json::to_value(AnthropicProvider::build_request(request)).unwrap();
+        assert!(body.get("thinking").is_some());
+        assert!(body.get("temperature").is_none() && body.get("top_p").is_none());
+    }
+}
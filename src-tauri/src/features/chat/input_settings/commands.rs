@@ -1,5 +1,6 @@
 use super::models::ChatInputSettings;
 use crate::error::AppError;
+use crate::features::chat::generation::GenerationParams;
 use crate::state::AppState;
 use tauri::State;
 
@@ -19,10 +20,16 @@ pub fn save_chat_input_settings(
     workspace_id: String,
     selected_model: Option<String>,
     stream_enabled: bool,
+    generation_params: Option<GenerationParams>,
     state: State<'_, AppState>,
 ) -> Result<(), AppError> {
     state
         .chat_input_settings_service
-        .save(&workspace_id, selected_model.as_deref(), stream_enabled)
+        .save(
+            &workspace_id,
+            selected_model.as_deref(),
+            stream_enabled,
+            generation_params,
+        )
         .map_err(|e| AppError::Generic(e.to_string()))
 }
@@ -5,6 +5,12 @@ pub struct ChatInputSettings {
     pub workspace_id: String,
     pub selected_model: Option<String>, // Format: "connectionId::modelId"
     pub stream_enabled: i64,            // 1 for true, 0 for false
+    // Generation parameters overriding the workspace's, NULL to keep them
+    pub temperature: Option<f64>,
+    pub top_p: Option<f64>,
+    pub frequency_penalty: Option<f64>,
+    pub presence_penalty: Option<f64>,
+    pub max_tokens: Option<i64>,
     pub created_at: i64,
     pub updated_at: i64,
 }
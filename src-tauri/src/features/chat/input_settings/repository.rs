@@ -37,13 +37,13 @@ impl ChatInputSettingsRepository for SqliteChatInputSettingsRepository {
 
         if exists {
             conn.execute(
-                "UPDATE chat_input_settings SET selected_model = ?1, stream_enabled = ?2, updated_at = ?3 WHERE workspace_id = ?4",
-                params![settings.selected_model, settings.stream_enabled, settings.updated_at, settings.workspace_id],
+                "UPDATE chat_input_settings SET selected_model = ?1, stream_enabled = ?2, temperature = ?3, top_p = ?4, frequency_penalty = ?5, presence_penalty = ?6, max_tokens = ?7, updated_at = ?8 WHERE workspace_id = ?9",
+                params![settings.selected_model, settings.stream_enabled, settings.temperature, settings.top_p, settings.frequency_penalty, settings.presence_penalty, settings.max_tokens, settings.updated_at, settings.workspace_id],
             )?;
         } else {
             conn.execute(
-                "INSERT INTO chat_input_settings (workspace_id, selected_model, stream_enabled, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-                params![settings.workspace_id, settings.selected_model, settings.stream_enabled, settings.created_at, settings.updated_at],
+                "INSERT INTO chat_input_settings (workspace_id, selected_model, stream_enabled, temperature, top_p, frequency_penalty, presence_penalty, max_tokens, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                params![settings.workspace_id, settings.selected_model, settings.stream_enabled, settings.temperature, settings.top_p, settings.frequency_penalty, settings.presence_penalty, settings.max_tokens, settings.created_at, settings.updated_at],
             )?;
         }
 
@@ -56,7 +56,7 @@ impl ChatInputSettingsRepository for SqliteChatInputSettingsRepository {
     ) -> Result<Option<ChatInputSettings>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT workspace_id, selected_model, stream_enabled, created_at, updated_at FROM chat_input_settings WHERE workspace_id = ?1",
+            "SELECT workspace_id, selected_model, stream_enabled, created_at, updated_at, temperature, top_p, frequency_penalty, presence_penalty, max_tokens FROM chat_input_settings WHERE workspace_id = ?1",
             params![workspace_id],
             |row| {
                 Ok(ChatInputSettings {
@@ -65,6 +65,11 @@ impl ChatInputSettingsRepository for SqliteChatInputSettingsRepository {
                     stream_enabled: row.get(2)?,
                     created_at: row.get(3)?,
                     updated_at: row.get(4)?,
+                    temperature: row.get(5)?,
+                    top_p: row.get(6)?,
+                    frequency_penalty: row.get(7)?,
+                    presence_penalty: row.get(8)?,
+                    max_tokens: row.get(9)?,
                 })
             },
         );
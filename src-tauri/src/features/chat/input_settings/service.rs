@@ -1,6 +1,7 @@
 use super::models::ChatInputSettings;
 use super::repository::ChatInputSettingsRepository;
 use crate::error::AppError;
+use crate::features::chat::generation::GenerationParams;
 use std::sync::Arc;
 
 pub struct ChatInputSettingsService {
@@ -17,6 +18,7 @@ impl ChatInputSettingsService {
         workspace_id: &str,
         selected_model: Option<&str>,
         stream_enabled: bool,
+        generation_params: Option<GenerationParams>,
     ) -> Result<(), AppError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -24,11 +26,28 @@ impl ChatInputSettingsService {
             .as_millis() as i64;
 
         let stream_enabled_i64: i64 = i64::from(stream_enabled);
+        // Keep the stored overrides when the caller doesn't specify them
+        let generation_params = match generation_params {
+            Some(params) => {
+                params.validate()?;
+                params
+            }
+            None => self
+                .repository
+                .get_by_workspace_id(workspace_id)?
+                .map(|s| GenerationParams::of_input(&s))
+                .unwrap_or_default(),
+        };
 
         let settings = ChatInputSettings {
             workspace_id: workspace_id.to_string(),
             selected_model: selected_model.map(std::string::ToString::to_string),
             stream_enabled: stream_enabled_i64,
+            temperature: generation_params.temperature,
+            top_p: generation_params.top_p,
+            frequency_penalty: generation_params.frequency_penalty,
+            presence_penalty: generation_params.presence_penalty,
+            max_tokens: generation_params.max_tokens.map(i64::from),
             created_at: now,
             updated_at: now,
         };
@@ -1,10 +1,34 @@
+pub mod boot;
+pub mod branches;
+pub mod cancelled;
 pub mod commands;
+pub mod comparison;
+pub mod context_window;
+pub mod cost_gate;
 pub mod emitter;
+pub mod empty_answer;
+pub mod export;
+pub mod generation;
 pub mod input_settings;
+pub mod model_pinning;
 pub mod models;
+pub mod oneshot;
+pub mod paste_dedup;
+pub mod permalink;
 pub mod prompts;
+pub mod reply;
 pub mod repository;
+pub mod scratchpad;
 pub mod service;
+pub mod status;
+pub mod summary;
+pub mod timeline;
+pub mod tool_caps;
+pub mod tool_concurrency;
+pub mod tool_pairing;
+pub mod tool_permissions;
+pub mod user_files;
+pub mod variants;
 
 pub use emitter::ChatEmitter;
 pub use repository::*;
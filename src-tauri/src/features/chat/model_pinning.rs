@@ -0,0 +1,339 @@
+//! Model choice for a turn: the chat, workspace and connection defaults, and
+//! prompts that declare a preferred model or required capabilities.
+
+use crate::error::AppError;
+use crate::features::llm_connection::model_filter::ModelFilter;
+use crate::features::llm_connection::models::LLMConnection;
+use crate::features::prompt::{ModelCapability, PromptModelPreference};
+use crate::features::workspace::settings::WorkspaceSettings;
+use serde_json::Value;
+
+/// Model names that accept image input, for model lists that do not carry a
+/// `supportsVision` flag.
+const VISION_MODEL_HINTS: &[&str] = &[
+    "vision", "gpt-4o", "gpt-4.1", "gpt-5", "claude", "gemini", "llava", "pixtral", "-vl",
+];
+
+/// Connection and model a turn runs with before any prompt pin: the given
+/// connection, else the workspace's; the selected model, else the workspace
+/// default, else the connection default.
+pub fn resolve_turn_model(
+    connection_override: Option<String>,
+    selected_model: Option<String>,
+    settings: &WorkspaceSettings,
+    find_connection: impl FnOnce(&str) -> Result<Option<LLMConnection>, AppError>,
+) -> Result<(LLMConnection, String), AppError> {
+    let connection_id = connection_override
+        .or_else(|| settings.llm_connection_id.clone())
+        .ok_or_else(|| {
+            AppError::Validation("LLM connection not configured for workspace".to_string())
+        })?;
+    let connection = find_connection(&connection_id)?
+        .ok_or_else(|| AppError::NotFound(format!("LLM connection not found: {connection_id}")))?;
+    let model = selected_model
+        .or_else(|| settings.default_model.clone())
+        .or_else(|| connection.default_model.clone())
+        .ok_or_else(|| AppError::Validation("No model selected".to_string()))?;
+    Ok((connection, model))
+}
+
+/// Split a composer selection (`connectionId::modelId`, or a bare model id)
+/// into the connection and model the composer sends with a message.
+pub fn split_model_selection(selection: &str) -> (Option<String>, String) {
+    selection.split_once("::").map_or_else(
+        || (None, selection.to_string()),
+        |(connection_id, model)| (Some(connection_id.to_string()), model.to_string()),
+    )
+}
+
+/// What to do with the turn's model.
+#[derive(Debug, Clone)]
+pub enum ModelPin {
+    /// The turn's model satisfies the prompt
+    Keep,
+    /// Use this connection and model for this turn only
+    Override {
+        connection: Box<LLMConnection>,
+        model: String,
+    },
+    /// Nothing available satisfies the prompt; the turn's model is used anyway
+    Warn { missing: Vec<ModelCapability> },
+}
+
+/// Decide which model runs a turn sent from a prompt.
+///
+/// `current_model` is what the turn would use otherwise (chat selection, else
+/// workspace default, else connection default). It is kept when it has every
+/// required capability; a prompt that requires none is satisfied only by its
+/// preferred provider/model. Otherwise the first enabled connection, the
+/// turn's own first, offering a preferred model with the capabilities is used.
+/// Without one the turn's model is kept, with a warning if it lacks a
+/// capability.
+pub fn resolve_model_pin(
+    preference: &PromptModelPreference,
+    current: &LLMConnection,
+    current_model: &str,
+    connections: &[LLMConnection],
+) -> ModelPin {
+    if preference.is_empty() {
+        return ModelPin::Keep;
+    }
+
+    let missing = missing_capabilities(preference, current, current_model);
+    let satisfied = missing.is_empty()
+        && (!preference.required_capabilities.is_empty()
+            || matches_preference(preference, current, current_model));
+    if satisfied {
+        return ModelPin::Keep;
+    }
+
+    let candidates = std::iter::once(current)
+        .chain(connections.iter().filter(|c| c.id != current.id))
+        .filter(|c| c.enabled);
+    for connection in candidates {
+        let found = candidate_models(preference, connection)
+            .into_iter()
+            .find(|model| {
+                matches_preference(preference, connection, model)
+                    && missing_capabilities(preference, connection, model).is_empty()
+            });
+        if let Some(model) = found {
+            return ModelPin::Override {
+                connection: Box::new(connection.clone()),
+                model,
+            };
+        }
+    }
+
+    if missing.is_empty() {
+        ModelPin::Keep
+    } else {
+        ModelPin::Warn { missing }
+    }
+}
+
+/// Capabilities of a model, from the connection's model list. Vision falls
+/// back to the model name when the list does not say.
+pub fn model_capabilities(connection: &LLMConnection, model: &str) -> Vec<ModelCapability> {
+    let entry = listed_models(connection)
+        .into_iter()
+        .find(|m| m.get("id").and_then(Value::as_str) == Some(model));
+    let flag = |key: &str| {
+        entry
+            .as_ref()
+            .and_then(|m| m.get(key))
+            .and_then(Value::as_bool)
+    };
+
+    let mut capabilities = Vec::new();
+    let lower = model.to_lowercase();
+    if flag("supportsVision")
+        .unwrap_or_else(|| VISION_MODEL_HINTS.iter().any(|hint| lower.contains(hint)))
+    {
+        capabilities.push(ModelCapability::Vision);
+    }
+    if flag("supportsTools") == Some(true) {
+        capabilities.push(ModelCapability::Tools);
+    }
+    if flag("supportsThinking") == Some(true) {
+        capabilities.push(ModelCapability::Thinking);
+    }
+    capabilities
+}
+
+fn missing_capabilities(
+    preference: &PromptModelPreference,
+    connection: &LLMConnection,
+    model: &str,
+) -> Vec<ModelCapability> {
+    let available = model_capabilities(connection, model);
+    preference
+        .required_capabilities
+        .iter()
+        .copied()
+        .filter(|c| !available.contains(c))
+        .collect()
+}
+
+fn matches_preference(
+    preference: &PromptModelPreference,
+    connection: &LLMConnection,
+    model: &str,
+) -> bool {
+    preference
+        .preferred_provider
+        .as_deref()
+        .is_none_or(|p| connection.provider.eq_ignore_ascii_case(p))
+        && preference
+            .preferred_model
+            .as_deref()
+            .is_none_or(|m| m == model)
+}
+
+/// Models of a connection worth trying: the preferred model if the connection
+/// offers it, otherwise its default model followed by the rest of its list.
+/// Models the connection's model lists do not permit are left out.
+fn candidate_models(preference: &PromptModelPreference, connection: &LLMConnection) -> Vec<String> {
+    let filter = ModelFilter::for_connection(connection);
+    let permitted = |model: &String| {
+        filter
+            .as_ref()
+            .is_none_or(|f| f.check(model).is_permitted())
+    };
+    let listed: Vec<String> = listed_models(connection)
+        .iter()
+        .filter_map(|m| m.get("id").and_then(Value::as_str).map(str::to_string))
+        .filter(|m| permitted(m))
+        .collect();
+
+    if let Some(model) = &preference.preferred_model {
+        let offered = listed.contains(model)
+            || (connection.default_model.as_ref() == Some(model) && permitted(model));
+        return if offered {
+            vec![model.clone()]
+        } else {
+            Vec::new()
+        };
+    }
+
+    let mut models: Vec<String> = connection
+        .default_model
+        .iter()
+        .filter(|m| permitted(m))
+        .cloned()
+        .collect();
+    models.extend(
+        listed
+            .into_iter()
+            .filter(|m| connection.default_model.as_ref() != Some(m)),
+    );
+    models
+}
+
+fn listed_models(connection: &LLMConnection) -> Vec<Value> {
+    connection
+        .models_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::pin_connections;
+
+    #[test]
+    fn model_pin_overrides_the_turn_model_only_when_needed() {
+        use crate::features::chat::model_pinning::{resolve_model_pin, ModelPin};
+        use crate::features::prompt::{ModelCapability, PromptModelPreference};
+
+        let connections = pin_connections();
+        let local = &connections[0];
+        let vision_prompt = PromptModelPreference {
+            preferred_provider: Some("openai".to_string()),
+            preferred_model: Some("gpt-4o".to_string()),
+            required_capabilities: vec![ModelCapability::Vision],
+        };
+
+        // The chat's selection wins over the workspace default as usual, and
+        // the prompt's model wins over both for this turn
+        let workspace_default = Some("qwen3".to_string());
+        let chat_selection = Some("llama3".to_string());
+        let turn_model = chat_selection.or(workspace_default).unwrap();
+        match resolve_model_pin(&vision_prompt, local, &turn_model, &connections) {
+            ModelPin::Override { connection, model } => {
+                assert_eq!(
+                    (connection.id.as_str(), model.as_str()),
+                    ("cloud", "gpt-4o")
+                );
+            }
+            other => panic!("expected an override, got {other:?}"),
+        }
+
+        // A chat model that already has the capabilities is kept, even though
+        // it is not the preferred one
+        assert!(matches!(
+            resolve_model_pin(&vision_prompt, &connections[1], "gpt-4o-mini", &connections),
+            ModelPin::Keep
+        ));
+
+        // Without required capabilities the preference itself is the requirement
+        let preference_only = PromptModelPreference {
+            preferred_model: Some("qwen3".to_string()),
+            ..Default::default()
+        };
+        match resolve_model_pin(&preference_only, &connections[1], "gpt-4o", &connections) {
+            ModelPin::Override { connection, model } => {
+                assert_eq!((connection.id.as_str(), model.as_str()), ("local", "qwen3"));
+            }
+            other => panic!("expected an override, got {other:?}"),
+        }
+
+        // A capability-only prompt stays on the turn's connection when it can
+        let thinking = PromptModelPreference {
+            required_capabilities: vec![ModelCapability::Thinking],
+            ..Default::default()
+        };
+        match resolve_model_pin(&thinking, local, "llama3", &connections) {
+            ModelPin::Override { connection, model } => {
+                assert_eq!((connection.id.as_str(), model.as_str()), ("local", "qwen3"));
+            }
+            other => panic!("expected an override, got {other:?}"),
+        }
+
+        // No preference: nothing to resolve
+        assert!(matches!(
+            resolve_model_pin(
+                &PromptModelPreference::default(),
+                local,
+                "llama3",
+                &connections
+            ),
+            ModelPin::Keep
+        ));
+    }
+
+    #[test]
+    fn model_pin_warns_when_no_model_satisfies_the_prompt() {
+        use crate::features::chat::model_pinning::{resolve_model_pin, ModelPin};
+        use crate::features::prompt::{ModelCapability, PromptModelPreference};
+
+        let mut connections = pin_connections();
+        let vision = PromptModelPreference {
+            required_capabilities: vec![ModelCapability::Vision],
+            ..Default::default()
+        };
+        // The only vision models are on a disabled connection
+        connections[1].enabled = false;
+        match resolve_model_pin(&vision, &connections[0], "llama3", &connections) {
+            ModelPin::Warn { missing } => assert_eq!(missing, [ModelCapability::Vision]),
+            other => panic!("expected a warning, got {other:?}"),
+        }
+
+        // The preferred model is not offered anywhere, but the turn's model
+        // has what the prompt needs: proceed quietly
+        let unavailable = PromptModelPreference {
+            preferred_model: Some("gpt-5".to_string()),
+            required_capabilities: vec![ModelCapability::Tools],
+            ..Default::default()
+        };
+        assert!(matches!(
+            resolve_model_pin(&unavailable, &connections[0], "llama3", &connections),
+            ModelPin::Keep
+        ));
+
+        let thinking_vision = PromptModelPreference {
+            required_capabilities: vec![ModelCapability::Thinking, ModelCapability::Vision],
+            ..Default::default()
+        };
+        match resolve_model_pin(&thinking_vision, &connections[0], "llama3", &connections) {
+            ModelPin::Warn { missing } => {
+                assert_eq!(
+                    missing,
+                    [ModelCapability::Thinking, ModelCapability::Vision]
+                );
+            }
+            other => panic!("expected a warning, got {other:?}"),
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use crate::error::AppError;
+use crate::models::llm_types::TokenUsage;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -10,4 +12,66 @@ pub struct Chat {
     pub updated_at: i64,
     pub agent_id: Option<String>,
     pub parent_id: Option<String>,
+    /// When the user last viewed the chat to the end, `None` if never
+    #[serde(default)]
+    pub last_read_at: Option<i64>,
+    /// Answers and tool calls since `last_read_at`; computed, never stored
+    #[serde(default)]
+    pub unread_count: i64,
+    /// Standing instructions for every turn of this chat, after the workspace's
+    #[serde(default)]
+    pub custom_instructions: Option<String>,
+}
+
+/// Graphemes of an answer kept in `Chat.last_message`.
+pub const LAST_MESSAGE_MAX_GRAPHEMES: usize = 100;
+
+/// Characters allowed in `Chat.custom_instructions`.
+pub const CHAT_INSTRUCTIONS_MAX_CHARS: usize = 4_000;
+
+impl Chat {
+    /// The `last_message` preview of an answer.
+    pub fn last_message_preview(content: &str) -> String {
+        crate::lib::text::truncate(content, LAST_MESSAGE_MAX_GRAPHEMES, "...")
+    }
+
+    /// Custom instructions as they are stored: trimmed, `None` when blank,
+    /// rejected beyond `CHAT_INSTRUCTIONS_MAX_CHARS`.
+    pub fn normalize_instructions(instructions: Option<&str>) -> Result<Option<String>, AppError> {
+        let Some(instructions) = instructions.map(str::trim).filter(|text| !text.is_empty()) else {
+            return Ok(None);
+        };
+        let chars = instructions.chars().count();
+        if chars > CHAT_INSTRUCTIONS_MAX_CHARS {
+            return Err(AppError::Validation(format!(
+                "Chat instructions are limited to {CHAT_INSTRUCTIONS_MAX_CHARS} characters ({chars} given)"
+            )));
+        }
+        Ok(Some(instructions.to_string()))
+    }
+}
+
+/// Options for `run_prompt_oneshot`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct OneshotOptions {
+    /// Chat to run in. Defaults to the workspace scratch chat, or a fresh chat
+    /// when `ephemeral` is set. Providing it lets callers cancel the run.
+    pub chat_id: Option<String>,
+    /// Delete the chat afterwards if it was created for this run
+    pub ephemeral: Option<bool>,
+    /// Fail instead of waiting when a tool requires permission
+    pub auto_deny_permissions: Option<bool>,
+    pub timeout_ms: Option<u64>,
+    pub selected_model: Option<String>,
+    pub reasoning_effort: Option<String>,
+    pub llm_connection_id: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct OneshotResult {
+    pub chat_id: String,
+    pub assistant_message_id: String,
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+    pub tool_calls_executed: usize,
 }
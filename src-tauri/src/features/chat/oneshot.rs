@@ -0,0 +1,325 @@
+//! Headless turns started through `run_prompt_oneshot`.
+//!
+//! A run that outlives its timeout is cancelled like any other turn and
+//! awaited until it has wound down, so partial content, tool results and
+//! usage are stored before the run reports the timeout.
+
+use crate::error::AppError;
+use crate::features::chat::tool_permissions::PermissionOutcome;
+use crate::features::message::models::Message;
+use std::future::Future;
+use std::time::Duration;
+
+/// How a oneshot turn ended.
+#[derive(Debug)]
+pub enum RunEnd<T> {
+    Finished(Result<T, AppError>),
+    /// Outlived the timeout; holds what the cancelled turn returned
+    TimedOut(Result<T, AppError>),
+}
+
+/// Await `send`; past `timeout`, run `cancel` and keep awaiting `send` so
+/// the turn finishes down its cancelled path.
+pub async fn run_with_timeout<T, F, C>(send: F, timeout: Option<Duration>, cancel: C) -> RunEnd<T>
+where
+    F: Future<Output = Result<T, AppError>>,
+    C: Future<Output = ()>,
+{
+    tokio::pin!(send);
+    let Some(timeout) = timeout else {
+        return RunEnd::Finished(send.await);
+    };
+    if let Ok(outcome) = tokio::time::timeout(timeout, &mut send).await {
+        return RunEnd::Finished(outcome);
+    }
+    cancel.await;
+    RunEnd::TimedOut(send.await)
+}
+
+/// The answer for the gated tool calls of a turn. A headless run that
+/// auto-denies never asks, so it cannot hang on a prompt nobody sees; any
+/// other turn awaits `ask`.
+pub async fn permission_outcome<F>(auto_deny: bool, ask: F) -> Result<PermissionOutcome, AppError>
+where
+    F: Future<Output = Result<PermissionOutcome, AppError>>,
+{
+    if auto_deny {
+        return Ok(PermissionOutcome::AutoDenied);
+    }
+    ask.await
+}
+
+/// Tool calls of the run started at `started_at` that ran. Calls skipped for
+/// lack of permission or truncated arguments are stored too but never ran.
+pub fn executed_tool_calls(messages: &[Message], started_at: i64) -> usize {
+    messages
+        .iter()
+        .filter(|m| m.role == "tool_call" && m.timestamp >= started_at)
+        .filter(|m| {
+            serde_json::from_str::<serde_json::Value>(&m.content).map_or(true, |data| {
+                data.get("permission").is_none() && data.get("skipped").is_none()
+            })
+        })
+        .count()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+    use crate::test_support::{mock_provider, MemoryMessageRepository, MockReply};
+    use std::time::Duration;
+
+    /// A turn that stores whatever the provider returned, cancelled or not.
+    async fn storing_turn(
+        reply: MockReply,
+        cancellation: tokio::sync::broadcast::Receiver<()>,
+        stored: &std::sync::atomic::AtomicBool,
+    ) -> Result<crate::models::llm_types::LLMChatResponse, AppError> {
+        let response = mock_provider(reply, cancellation).await?;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        stored.store(true, std::sync::atomic::Ordering::SeqCst);
+        Ok(response)
+    }
+
+    /// A tool-using oneshot turn, run the way the chat service runs it: the
+    /// model calls `read_file`, which the workspace allows, and `run_command`,
+    /// which always asks; the calls that may run run and are stored, skipped
+    /// ones are stored with their reason, and the model answers. Returns the
+    /// answer, the tool calls that ran, and whether the user was asked.
+    async fn tool_using_oneshot(auto_deny: bool) -> (String, usize, bool, Vec<String>) {
+        use crate::features::chat::oneshot::{
+            executed_tool_calls, permission_outcome, run_with_timeout, RunEnd,
+        };
+        use crate::features::chat::tool_permissions::{
+            partition_by_permission, resolve_gated, PermissionOutcome,
+        };
+        use crate::features::message::{Message, MessageRepository};
+        use crate::state::PermissionDecision;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let messages = MemoryMessageRepository::default();
+        let asked = AtomicBool::new(false);
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let store = |id: &str, data: serde_json::Value| {
+            messages
+                .create(&Message {
+                    id: format!("tool_call_{id}"),
+                    chat_id: "chat".to_string(),
+                    role: "tool_call".to_string(),
+                    content: data.to_string(),
+                    reasoning: None,
+                    timestamp: 10,
+                    assistant_message_id: Some("m1".to_string()),
+                    tool_call_id: None,
+                    metadata: None,
+                    reply_to_message_id: None,
+                    branch_id: None,
+                })
+                .unwrap();
+        };
+        let turn = async {
+            let calls = mock_provider(
+                MockReply::ToolCalls(&["read_file", "run_command"]),
+                cancellation.subscribe(),
+            )
+            .await?
+            .tool_calls
+            .unwrap_or_default();
+            let (ready, gated) = partition_by_permission(&calls, Some(r#"{"read_file":"auto"}"#));
+            let ask = async {
+                asked.store(true, Ordering::SeqCst);
+                Ok(PermissionOutcome::Decided(PermissionDecision {
+                    approved: true,
+                    allowed_tool_ids: gated.iter().map(|tc| tc.id.clone()).collect(),
+                    remember: None,
+                }))
+            };
+            let outcome = permission_outcome(auto_deny, ask).await?;
+            let (allowed, skipped) = resolve_gated(gated, &outcome);
+            for call in ready.iter().chain(&allowed) {
+                store(
+                    &call.id,
+                    serde_json::json!({ "name": call.function.name, "status": "success" }),
+                );
+            }
+            for (call, reason) in &skipped {
+                let mut data = serde_json::json!({
+                    "name": call.function.name,
+                    "error": reason.message(),
+                    "status": "error",
+                });
+                data[reason.field()] = serde_json::json!(reason.as_str());
+                store(&call.id, data);
+            }
+            mock_provider(MockReply::Answer("Done", 0), cancellation.subscribe()).await
+        };
+        let end = run_with_timeout(turn, Some(Duration::from_secs(5)), async {}).await;
+        let response = match end {
+            RunEnd::Finished(Ok(response)) => response,
+            other => panic!("the oneshot run did not finish: {other:?}"),
+        };
+        let stored = messages.get_by_chat_id("chat").unwrap();
+        let errors = stored
+            .iter()
+            .filter_map(|m| {
+                let data: serde_json::Value = serde_json::from_str(&m.content).ok()?;
+                Some(data.get("error")?.as_str()?.to_string())
+            })
+            .collect();
+        (
+            response.content,
+            executed_tool_calls(&stored, 0),
+            asked.load(Ordering::SeqCst),
+            errors,
+        )
+    }
+
+    #[test]
+    fn oneshot_timeout_cancels_then_waits_for_the_turn() {
+        use crate::features::chat::oneshot::{run_with_timeout, RunEnd};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let stored = AtomicBool::new(false);
+
+        let started = std::time::Instant::now();
+        let end = runtime.block_on(run_with_timeout(
+            storing_turn(
+                MockReply::UntilCancelled("Half an ans"),
+                cancellation.subscribe(),
+                &stored,
+            ),
+            Some(Duration::from_millis(50)),
+            async {
+                cancellation.send(()).unwrap();
+            },
+        ));
+        assert!(started.elapsed() < Duration::from_secs(5));
+        match end {
+            RunEnd::TimedOut(Ok(response)) => {
+                assert_eq!(response.content, "Half an ans");
+                assert_eq!(response.finish_reason.as_deref(), Some("cancelled"));
+            }
+            other => panic!("expected a wound-down timeout, got {other:?}"),
+        }
+        // The run reported the timeout only after the turn stored its answer
+        assert!(stored.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn oneshot_within_its_timeout_is_left_alone() {
+        use crate::features::chat::oneshot::{run_with_timeout, RunEnd};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let stored = AtomicBool::new(false);
+        let end = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(run_with_timeout(
+                storing_turn(
+                    MockReply::Answer("Done", 0),
+                    cancellation.subscribe(),
+                    &stored,
+                ),
+                Some(Duration::from_secs(5)),
+                async { panic!("a turn within its timeout was cancelled") },
+            ));
+        assert!(matches!(end, RunEnd::Finished(Ok(ref r)) if r.content == "Done"));
+        assert!(stored.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn oneshot_without_timeout_runs_to_the_end() {
+        use crate::features::chat::oneshot::{run_with_timeout, RunEnd};
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let stored = AtomicBool::new(false);
+        let end = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(run_with_timeout(
+                storing_turn(
+                    MockReply::Answer("Done", 30),
+                    cancellation.subscribe(),
+                    &stored,
+                ),
+                None,
+                async { panic!("a turn without a timeout was cancelled") },
+            ));
+        assert!(matches!(end, RunEnd::Finished(Ok(ref r)) if r.content == "Done"));
+        assert!(stored.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn oneshot_with_auto_deny_skips_gated_tools_without_asking() {
+        let (answer, executed, asked, errors) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(tool_using_oneshot(true));
+        assert_eq!(answer, "Done");
+        assert!(!asked);
+        assert_eq!(executed, 1);
+        assert_eq!(
+            errors,
+            ["Tool execution denied: permission required in headless run"]
+        );
+    }
+
+    #[test]
+    fn oneshot_without_auto_deny_runs_the_tools_the_user_allows() {
+        let (answer, executed, asked, errors) = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(tool_using_oneshot(false));
+        assert_eq!(answer, "Done");
+        assert!(asked);
+        assert_eq!(executed, 2);
+        assert!(errors.is_empty());
+    }
+
+    #[test]
+    fn oneshot_auto_deny_counts_only_tools_that_ran() {
+        use crate::features::chat::oneshot::executed_tool_calls;
+        use crate::features::chat::tool_permissions::SkipReason;
+        use crate::features::message::Message;
+
+        let started_at = 1_000;
+        // Stored the way the turn stores ran and skipped calls
+        let stored = |id: &str, timestamp: i64, reason: Option<SkipReason>| {
+            let mut data = serde_json::json!({ "name": "read_file", "status": "success" });
+            if let Some(reason) = reason {
+                data["status"] = serde_json::json!("error");
+                data["error"] = serde_json::json!(reason.message());
+                data[reason.field()] = serde_json::json!(reason.as_str());
+            }
+            Message {
+                id: format!("tool_call_{id}"),
+                chat_id: "chat".to_string(),
+                role: "tool_call".to_string(),
+                content: data.to_string(),
+                reasoning: None,
+                timestamp,
+                assistant_message_id: None,
+                tool_call_id: None,
+                metadata: None,
+                reply_to_message_id: None,
+                branch_id: None,
+            }
+        };
+        let messages = vec![
+            stored("1", started_at + 10, None),
+            // A headless run with auto-deny never asks
+            stored("2", started_at + 10, Some(SkipReason::AutoDenied)),
+            stored("3", started_at + 10, Some(SkipReason::AutoDenied)),
+            // Cut off mid-stream and never repaired
+            stored("4", started_at + 10, Some(SkipReason::TruncatedArguments)),
+            // A call from an earlier turn of the same chat
+            stored("0", started_at - 1, None),
+        ];
+
+        assert_eq!(executed_tool_calls(&messages, started_at), 1);
+        assert!(messages[1]
+            .content
+            .contains("permission required in headless run"));
+        assert_eq!(executed_tool_calls(&messages, 0), 2);
+    }
+}
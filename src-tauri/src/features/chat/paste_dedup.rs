@@ -0,0 +1,362 @@
+//! Deduplication of large pastes repeated across a chat's history.
+//!
+//! A block is a fenced code block, or a paragraph of consecutive non-blank
+//! lines outside fences, of at least `MIN_PASTE_BYTES` once whitespace is
+//! normalized. The first occurrence is sent as is; later ones are replaced
+//! with a short marker in the request only. Stored messages are untouched.
+
+use crate::models::llm_types::{ContentPart, UserContent};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// Smallest normalized block worth deduplicating.
+pub const MIN_PASTE_BYTES: usize = 2048;
+/// Message metadata key caching the blocks of the message content.
+pub const PASTE_INDEX_METADATA_KEY: &str = "pasteIndex";
+
+/// Large blocks of one message, cached in its metadata. `content_hash` ties
+/// the cache to the content it was computed from, so edits invalidate it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct PasteIndex {
+    pub content_hash: String,
+    pub block_hashes: Vec<String>,
+}
+
+impl PasteIndex {
+    pub fn compute(content: &str) -> Self {
+        Self {
+            content_hash: sha256(content),
+            block_hashes: find_blocks(content).into_iter().map(|b| b.hash).collect(),
+        }
+    }
+
+    /// The index cached in `metadata`, if it was computed from `content`.
+    pub fn cached(metadata: Option<&str>, content: &str) -> Option<Self> {
+        let meta: serde_json::Value = serde_json::from_str(metadata?).ok()?;
+        let index: Self =
+            serde_json::from_value(meta.get(PASTE_INDEX_METADATA_KEY)?.clone()).ok()?;
+        (index.content_hash == sha256(content)).then_some(index)
+    }
+
+    /// `metadata` with this index stored in it.
+    pub fn store_in(&self, metadata: Option<&str>) -> String {
+        let mut meta = metadata
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .filter(serde_json::Value::is_object)
+            .unwrap_or_else(|| serde_json::json!({}));
+        meta[PASTE_INDEX_METADATA_KEY] = serde_json::json!(self);
+        meta.to_string()
+    }
+}
+
+struct PasteBlock {
+    /// Byte range in the text, fences included
+    range: Range<usize>,
+    hash: String,
+    lines: usize,
+    label: Option<String>,
+}
+
+/// Remembers the blocks already sent in a request, oldest message first.
+#[derive(Default)]
+pub struct PasteDeduper {
+    seen: HashSet<String>,
+}
+
+impl PasteDeduper {
+    /// Replace repeated blocks in the text of a history message. `index`
+    /// comes from the message's stored content; when none of its blocks was
+    /// seen before, the content is left alone without being parsed again.
+    /// Image and file parts are kept as they are.
+    pub fn dedupe(&mut self, content: &mut UserContent, index: &PasteIndex) {
+        let mut in_message = HashSet::new();
+        let repeated = index
+            .block_hashes
+            .iter()
+            .any(|h| self.seen.contains(h) || !in_message.insert(h));
+        if !repeated {
+            self.seen.extend(index.block_hashes.iter().cloned());
+            return;
+        }
+
+        match content {
+            UserContent::Text(text) => self.rewrite(text),
+            UserContent::Parts(parts) => {
+                for part in parts {
+                    if let ContentPart::Text { text } = part {
+                        self.rewrite(text);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Remember the blocks of a message that must be sent unchanged.
+    pub fn remember(&mut self, index: &PasteIndex) {
+        self.seen.extend(index.block_hashes.iter().cloned());
+    }
+
+    fn rewrite(&mut self, text: &mut String) {
+        let mut rewritten = String::with_capacity(text.len());
+        let mut last = 0;
+        for block in find_blocks(text) {
+            if self.seen.insert(block.hash) {
+                continue;
+            }
+            rewritten.push_str(&text[last..block.range.start]);
+            rewritten.push_str(&marker(block.label.as_deref(), block.lines));
+            last = block.range.end;
+        }
+        if last > 0 {
+            rewritten.push_str(&text[last..]);
+            *text = rewritten;
+        }
+    }
+}
+
+fn marker(label: Option<&str>, lines: usize) -> String {
+    let lines = if lines == 1 {
+        "1 line".to_string()
+    } else {
+        format!("{lines} lines")
+    };
+    label.map_or_else(
+        || format!("[same content as pasted earlier, {lines}]"),
+        |label| format!("[same content as pasted earlier: {label}, {lines}]"),
+    )
+}
+
+/// Large blocks of `text`, in order.
+fn find_blocks(text: &str) -> Vec<PasteBlock> {
+    let mut blocks = Vec::new();
+    let mut lines = Vec::new();
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+
+    let mut i = 0;
+    while i < lines.len() {
+        let (start, line) = lines[i];
+        if let Some((fence, info)) = opening_fence(line) {
+            // A fence left open runs to the end of the text
+            let close = lines[i + 1..]
+                .iter()
+                .position(|(_, l)| closes_fence(l, fence))
+                .map(|p| i + 1 + p);
+            let inner_end = close.unwrap_or(lines.len());
+            let end = close.map_or(text.len(), |c| line_end(lines[c]));
+            let inner: Vec<&str> = lines[i + 1..inner_end].iter().map(|(_, l)| *l).collect();
+            push_block(&mut blocks, start..end, &inner, fence_label(info));
+            i = close.map_or(lines.len(), |c| c + 1);
+        } else if line.trim().is_empty() {
+            i += 1;
+        } else {
+            let run_end = lines[i..]
+                .iter()
+                .position(|(_, l)| l.trim().is_empty() || opening_fence(l).is_some())
+                .map_or(lines.len(), |p| i + p);
+            let end = line_end(lines[run_end - 1]);
+            let paragraph: Vec<&str> = lines[i..run_end].iter().map(|(_, l)| *l).collect();
+            push_block(&mut blocks, start..end, &paragraph, None);
+            i = run_end;
+        }
+    }
+    blocks
+}
+
+/// Offset of the line break ending a line (trailing spaces belong to the line).
+fn line_end((start, line): (usize, &str)) -> usize {
+    start + line.trim_end_matches(['\n', '\r']).len()
+}
+
+fn push_block(
+    blocks: &mut Vec<PasteBlock>,
+    range: Range<usize>,
+    lines: &[&str],
+    label: Option<String>,
+) {
+    let normalized = normalize(lines);
+    if normalized.len() < MIN_PASTE_BYTES {
+        return;
+    }
+    blocks.push(PasteBlock {
+        range,
+        hash: sha256(&normalized),
+        lines: lines.iter().filter(|l| !l.trim().is_empty()).count(),
+        label,
+    });
+}
+
+/// Trim lines, collapse runs of whitespace and drop blank lines, so copies
+/// that differ only in indentation, trailing spaces or line endings match.
+fn normalize(lines: &[&str]) -> String {
+    lines
+        .iter()
+        .map(|l| l.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// The fence marker and info string of a line opening a fenced block.
+fn opening_fence(line: &str) -> Option<(&str, &str)> {
+    let trimmed = line.trim();
+    let marker_len = trimmed
+        .find(|c| c != '`' && c != '~')
+        .unwrap_or(trimmed.len());
+    let marker = &trimmed[..marker_len];
+    let uniform = marker.chars().all(|c| c == '`') || marker.chars().all(|c| c == '~');
+    (marker_len >= 3 && uniform).then(|| (marker, trimmed[marker_len..].trim()))
+}
+
+/// Whether `line` closes a block opened with `fence`.
+fn closes_fence(line: &str, fence: &str) -> bool {
+    let trimmed = line.trim();
+    trimmed.len() >= fence.len() && trimmed.chars().all(|c| fence.starts_with(c))
+}
+
+/// A file name from the info string (`yaml title=config.yaml`), else the
+/// language.
+fn fence_label(info: &str) -> Option<String> {
+    let words: Vec<&str> = info
+        .split_whitespace()
+        .map(|w| w.rsplit('=').next().unwrap_or(w).trim_matches('"'))
+        .filter(|w| !w.is_empty())
+        .collect();
+    words
+        .iter()
+        .find(|w| w.contains('.'))
+        .or_else(|| words.first())
+        .map(|w| (*w).to_string())
+}
+
+fn sha256(text: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(text.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    fn pasted_log(lines: usize) -> String {
+        (0..lines)
+            .map(|i| format!("2024-05-01T10:00:{i:02}Z ERROR worker-{i} failed to connect to db"))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    #[test]
+    fn paste_dedup_replaces_exact_duplicates() {
+        use crate::features::chat::paste_dedup::{PasteDeduper, PasteIndex};
+        use crate::models::llm_types::UserContent;
+
+        let log = pasted_log(60);
+        let config = format!("```yaml title=config.yaml\n{}\n```", pasted_log(50));
+        let first = format!("I get this:\n\n{log}\n\nand my config:\n{config}");
+        let second = format!("Still failing:\n\n{log}\n\nSame config:\n{config}\n\nAny idea?");
+        let short = "ok\n\nok".to_string();
+
+        let mut deduper = PasteDeduper::default();
+        let mut sent = Vec::new();
+        for text in [&first, &short, &second] {
+            let mut content = UserContent::Text(text.clone());
+            deduper.dedupe(&mut content, &PasteIndex::compute(text));
+            let UserContent::Text(content) = content else {
+                panic!("text stays text");
+            };
+            sent.push(content);
+        }
+
+        // First occurrence and messages without large blocks are untouched
+        assert_eq!(sent[0], first);
+        assert_eq!(sent[1], short);
+        assert_eq!(
+            sent[2],
+            "Still failing:\n\n[same content as pasted earlier, 60 lines]\n\nSame config:\n\
+             [same content as pasted earlier: config.yaml, 50 lines]\n\nAny idea?"
+        );
+
+        // Small blocks are never indexed
+        assert!(PasteIndex::compute(&short).block_hashes.is_empty());
+        assert_eq!(PasteIndex::compute(&first).block_hashes.len(), 2);
+    }
+
+    #[test]
+    fn paste_dedup_matches_whitespace_variants_and_caches_its_index() {
+        use crate::features::chat::paste_dedup::{PasteDeduper, PasteIndex};
+        use crate::models::llm_types::UserContent;
+
+        let log = pasted_log(60);
+        let variant = log
+            .lines()
+            .map(|l| format!("    {}  ", l.replace(' ', "  ")))
+            .collect::<Vec<_>>()
+            .join("\r\n");
+        assert_eq!(
+            PasteIndex::compute(&log).block_hashes,
+            PasteIndex::compute(&variant).block_hashes
+        );
+
+        let mut deduper = PasteDeduper::default();
+        let mut first = UserContent::Text(log.clone());
+        deduper.dedupe(&mut first, &PasteIndex::compute(&log));
+        let mut second = UserContent::Text(format!("again\n\n{variant}"));
+        deduper.dedupe(
+            &mut second,
+            &PasteIndex::compute(&format!("again\n\n{variant}")),
+        );
+        assert!(matches!(
+            second,
+            UserContent::Text(ref t) if t == "again\n\n[same content as pasted earlier, 60 lines]"
+        ));
+
+        // The index is cached in metadata next to other keys, and dropped
+        // when the content changes
+        let index = PasteIndex::compute(&log);
+        let metadata = index.store_in(Some(r#"{"files":["a.png"]}"#));
+        assert!(metadata.contains("a.png"));
+        assert_eq!(PasteIndex::cached(Some(&metadata), &log), Some(index));
+        assert_eq!(PasteIndex::cached(Some(&metadata), &variant), None);
+        assert_eq!(PasteIndex::cached(None, &log), None);
+    }
+
+    #[test]
+    fn paste_dedup_keeps_the_parts_of_a_message_with_an_image() {
+        use crate::features::chat::paste_dedup::{PasteDeduper, PasteIndex};
+        use crate::models::llm_types::{ContentPart, ImageUrl, UserContent};
+
+        let log = pasted_log(60);
+        let mut deduper = PasteDeduper::default();
+        let mut first = UserContent::Text(log.clone());
+        deduper.dedupe(&mut first, &PasteIndex::compute(&log));
+
+        let text = format!("Screenshot and the log:\n\n{log}");
+        let mut content = UserContent::Parts(vec![
+            ContentPart::Text { text: text.clone() },
+            ContentPart::ImageUrl {
+                image_url: ImageUrl {
+                    url: "data:image/png;base64,AAAA".to_string(),
+                },
+            },
+        ]);
+        deduper.dedupe(&mut content, &PasteIndex::compute(&text));
+
+        let UserContent::Parts(parts) = content else {
+            panic!("parts stay parts");
+        };
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(
+            &parts[0],
+            ContentPart::Text { text } if text == "Screenshot and the log:\n\n[same content as pasted earlier, 60 lines]"
+        ));
+        assert!(matches!(
+            &parts[1],
+            ContentPart::ImageUrl { image_url } if image_url.url == "data:image/png;base64,AAAA"
+        ));
+    }
+}
@@ -0,0 +1,346 @@
+//! Links to a chat or one of its messages.
+//!
+//! `lunex://chat/<chat_id>` opens a chat and
+//! `lunex://chat/<chat_id>/message/<message_id>` scrolls to a message. Opening
+//! a link (from the OS, a note or the activity feed) resolves it against the
+//! database and sends one `NavigateToMessageEvent`, which says why when the
+//! target is gone.
+
+use super::models::Chat;
+use super::ChatEmitter;
+use crate::error::AppError;
+use crate::events::NavigateToMessageEvent;
+use crate::features::message::Message;
+use crate::state::AppState;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+pub const PERMALINK_SCHEME: &str = "lunex";
+const CHAT_HOST: &str = "chat";
+const MESSAGE_SEGMENT: &str = "message";
+/// Characters of the message shown next to a copied link.
+const EXCERPT_CHARS: usize = 120;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Permalink {
+    pub chat_id: String,
+    pub message_id: Option<String>,
+}
+
+/// Result of `get_message_permalink`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessagePermalink {
+    pub uri: String,
+    pub workspace_id: String,
+    pub chat_id: String,
+    pub chat_title: String,
+    pub message_id: String,
+    pub role: String,
+    /// First line of the message, shortened
+    pub excerpt: String,
+    pub timestamp: i64,
+}
+
+impl Permalink {
+    pub fn to_uri(&self) -> String {
+        let chat = format!("{PERMALINK_SCHEME}://{CHAT_HOST}/{}", self.chat_id);
+        self.message_id.as_ref().map_or_else(
+            || chat.clone(),
+            |message_id| format!("{chat}/{MESSAGE_SEGMENT}/{message_id}"),
+        )
+    }
+
+    /// Parse a link. The scheme and host are case-insensitive, a trailing
+    /// slash, query or fragment is ignored, and UUID ids are normalized to
+    /// the lowercase form they are stored in.
+    pub fn parse(uri: &str) -> Result<Self, AppError> {
+        let invalid =
+            |reason: &str| AppError::Validation(format!("Invalid link '{uri}': {reason}"));
+        let trimmed = uri.trim();
+        let (scheme, rest) = trimmed
+            .split_once("://")
+            .ok_or_else(|| invalid("not a link"))?;
+        if !scheme.eq_ignore_ascii_case(PERMALINK_SCHEME) {
+            return Err(invalid("unknown scheme"));
+        }
+        let rest = rest.split(['?', '#']).next().unwrap_or_default();
+        let mut segments = rest.trim_end_matches('/').split('/');
+        let host = segments.next().unwrap_or_default();
+        if !host.eq_ignore_ascii_case(CHAT_HOST) {
+            return Err(invalid("unknown host"));
+        }
+
+        let segments: Vec<&str> = segments.collect();
+        if segments.iter().any(|s| s.is_empty()) {
+            return Err(invalid("empty path segment"));
+        }
+        match segments.as_slice() {
+            [chat_id] => Ok(Self {
+                chat_id: normalize_id(chat_id),
+                message_id: None,
+            }),
+            [chat_id, segment, message_id] if segment.eq_ignore_ascii_case(MESSAGE_SEGMENT) => {
+                Ok(Self {
+                    chat_id: normalize_id(chat_id),
+                    message_id: Some(normalize_id(message_id)),
+                })
+            }
+            [] => Err(invalid("missing chat id")),
+            _ => Err(invalid("unknown path")),
+        }
+    }
+
+    /// The navigation event of this link, looking the chat and message up
+    /// with the given functions.
+    pub fn resolve<C, M>(
+        &self,
+        uri: &str,
+        get_chat: C,
+        get_message: M,
+    ) -> Result<NavigateToMessageEvent, AppError>
+    where
+        C: FnOnce(&str) -> Result<Option<Chat>, AppError>,
+        M: FnOnce(&str) -> Result<Option<Message>, AppError>,
+    {
+        let Some(chat) = get_chat(&self.chat_id)? else {
+            return Ok(not_found(
+                uri,
+                Some(&self.chat_id),
+                "The chat no longer exists",
+            ));
+        };
+        let message_id = match &self.message_id {
+            Some(message_id) => match get_message(message_id)? {
+                Some(message) if message.chat_id == chat.id => Some(message.id),
+                Some(_) => {
+                    return Ok(not_found(
+                        uri,
+                        Some(&chat.id),
+                        "The message belongs to another chat",
+                    ))
+                }
+                None => {
+                    return Ok(NavigateToMessageEvent {
+                        workspace_id: Some(chat.workspace_id),
+                        ..not_found(uri, Some(&chat.id), "The message no longer exists")
+                    })
+                }
+            },
+            None => None,
+        };
+        Ok(NavigateToMessageEvent {
+            uri: uri.to_string(),
+            found: true,
+            workspace_id: Some(chat.workspace_id),
+            chat_id: Some(chat.id),
+            message_id,
+            error: None,
+        })
+    }
+}
+
+/// The navigation event of a link that cannot be followed.
+pub fn not_found(uri: &str, chat_id: Option<&str>, error: &str) -> NavigateToMessageEvent {
+    NavigateToMessageEvent {
+        uri: uri.to_string(),
+        found: false,
+        workspace_id: None,
+        chat_id: chat_id.map(str::to_string),
+        message_id: None,
+        error: Some(error.to_string()),
+    }
+}
+
+/// The permalink of `message`, with enough context to label it.
+pub fn message_permalink(chat: &Chat, message: &Message) -> MessagePermalink {
+    let line = message
+        .content
+        .lines()
+        .map(str::trim)
+        .find(|l| !l.is_empty())
+        .unwrap_or_default();
+    let excerpt = crate::lib::text::truncate(line, EXCERPT_CHARS, "…");
+    MessagePermalink {
+        uri: Permalink {
+            chat_id: chat.id.clone(),
+            message_id: Some(message.id.clone()),
+        }
+        .to_uri(),
+        workspace_id: chat.workspace_id.clone(),
+        chat_id: chat.id.clone(),
+        chat_title: chat.title.clone(),
+        message_id: message.id.clone(),
+        role: message.role.clone(),
+        excerpt,
+        timestamp: message.timestamp,
+    }
+}
+
+/// Follow a link: resolve it, bring the main window forward and send the
+/// navigation event.
+pub fn navigate(app: &AppHandle, uri: &str) -> NavigateToMessageEvent {
+    let event = app.try_state::<AppState>().map_or_else(
+        || not_found(uri, None, "Lunex is not ready yet"),
+        |state| {
+            Permalink::parse(uri)
+                .and_then(|link| {
+                    link.resolve(
+                        uri,
+                        |id| state.chat_service.get_by_id(id),
+                        |id| state.message_service.get_by_id(id),
+                    )
+                })
+                .unwrap_or_else(|e| not_found(uri, None, &e.to_string()))
+        },
+    );
+
+    if let Some(window) = app.get_webview_window("main") {
+        for result in [window.unminimize(), window.show(), window.set_focus()] {
+            if let Err(e) = result {
+                tracing::warn!(error = %e, "Failed to focus window for link");
+            }
+        }
+    }
+    if let Err(e) = ChatEmitter::new(app.clone()).emit_navigate_to_message(event.clone()) {
+        tracing::error!(error = ?e, "Failed to emit navigate-to-message event");
+    }
+    event
+}
+
+/// Follow a link the OS opened the app with. The event is also kept for
+/// `take_pending_navigation`, since the window may not listen yet.
+pub fn open_from_os(app: &AppHandle, uri: &str) {
+    let event = navigate(app, uri);
+    if let Some(state) = app.try_state::<AppState>() {
+        if let Ok(mut pending) = state.pending_navigation.lock() {
+            *pending = Some(event);
+        }
+    }
+}
+
+/// The permalink among the launch arguments, if any.
+pub fn launch_uri(mut args: impl Iterator<Item = String>) -> Option<String> {
+    let prefix = format!("{PERMALINK_SCHEME}://");
+    args.find(|arg| {
+        arg.get(..prefix.len())
+            .is_some_and(|p| p.eq_ignore_ascii_case(&prefix))
+    })
+}
+
+/// Lowercase UUIDs; other ids (like scratch chats) are kept as they are.
+fn normalize_id(id: &str) -> String {
+    uuid::Uuid::parse_str(id).map_or_else(|_| id.to_string(), |uuid| uuid.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn permalinks_parse_loosely_written_links() {
+        use crate::features::chat::permalink::Permalink;
+
+        let chat_id = "0b6f1c2e-8a4d-4f3b-9c1e-2d7a5e9f0a11";
+        let message_id = "7c9e6679-7425-40de-944b-e07fc1f90ae7";
+        let link = Permalink {
+            chat_id: chat_id.to_string(),
+            message_id: Some(message_id.to_string()),
+        };
+        let uri = link.to_uri();
+        assert_eq!(uri, format!("lunex://chat/{chat_id}/message/{message_id}"));
+
+        for variant in [
+            uri.clone(),
+            format!("{uri}/"),
+            format!("  {uri}?from=notes#top "),
+            uri.to_uppercase(),
+            format!(
+                "LUNEX://Chat/{chat_id}/Message/{}",
+                message_id.to_uppercase()
+            ),
+        ] {
+            assert_eq!(Permalink::parse(&variant).unwrap(), link, "{variant}");
+        }
+
+        // Ids that are not UUIDs are kept as written
+        let scratch = Permalink::parse("lunex://chat/scratch_WS1/").unwrap();
+        assert_eq!(scratch.chat_id, "scratch_WS1");
+        assert_eq!(scratch.message_id, None);
+        assert_eq!(scratch.to_uri(), "lunex://chat/scratch_WS1");
+
+        for bad in [
+            "lunex://workspace/abc",
+            "lunex://chats/abc",
+            "https://chat/abc",
+            "lunex:chat/abc",
+            "lunex://chat",
+            "lunex://chat//message/abc",
+            "lunex://chat/abc/message",
+            "lunex://chat/abc/reply/def",
+            "lunex://chat/abc/message/def/extra",
+        ] {
+            assert!(Permalink::parse(bad).is_err(), "{bad} should be rejected");
+        }
+    }
+
+    #[test]
+    fn permalinks_to_missing_content_resolve_to_not_found() {
+        use crate::features::chat::models::Chat;
+        use crate::features::chat::permalink::Permalink;
+
+        let chat = Chat {
+            id: "chat".to_string(),
+            workspace_id: "ws".to_string(),
+            title: "Notes".to_string(),
+            last_message: None,
+            created_at: 0,
+            updated_at: 0,
+            agent_id: None,
+            parent_id: None,
+            last_read_at: None,
+            unread_count: 0,
+            custom_instructions: None,
+        };
+        let get_chat = |id: &str| Ok((id == "chat").then(|| chat.clone()));
+        let get_message = |id: &str| {
+            Ok(match id {
+                "m1" => Some(chat_message("m1", "assistant", "Hello")),
+                "elsewhere" => Some(crate::features::message::Message {
+                    chat_id: "other".to_string(),
+                    ..chat_message("elsewhere", "user", "Hi")
+                }),
+                _ => None,
+            })
+        };
+        let resolve = |uri: &str| {
+            Permalink::parse(uri)
+                .unwrap()
+                .resolve(uri, get_chat, get_message)
+                .unwrap()
+        };
+
+        let found = resolve("lunex://chat/chat/message/m1");
+        assert!(found.found);
+        assert_eq!(found.workspace_id.as_deref(), Some("ws"));
+        assert_eq!(found.message_id.as_deref(), Some("m1"));
+        assert!(resolve("lunex://chat/chat").found);
+
+        let deleted = resolve("lunex://chat/chat/message/gone");
+        assert!(!deleted.found);
+        assert_eq!(deleted.chat_id.as_deref(), Some("chat"));
+        assert_eq!(deleted.message_id, None);
+        assert_eq!(
+            deleted.error.as_deref(),
+            Some("The message no longer exists")
+        );
+
+        let misplaced = resolve("lunex://chat/chat/message/elsewhere");
+        assert!(!misplaced.found);
+        assert_eq!(misplaced.message_id, None);
+
+        let no_chat = resolve("lunex://chat/gone/message/m1");
+        assert!(!no_chat.found);
+        assert_eq!(no_chat.workspace_id, None);
+        assert_eq!(no_chat.error.as_deref(), Some("The chat no longer exists"));
+    }
+}
@@ -1,3 +1,5 @@
+use crate::features::app_settings::time::TimeFormatter;
+
 pub const LUNEX_BASE_PROMPT: &str = r#"# LUNEX CORE INSTRUCTIONS
 
 ## IDENTITY
@@ -21,7 +23,7 @@ pub const LUNEX_BASE_PROMPT: &str = r#"# LUNEX CORE INSTRUCTIONS
 - Maintain the persona of a senior software engineer: helpful, direct, and focused on correctness.
 "#;
 
-pub fn get_app_prompt() -> String {
+pub fn get_app_prompt(time: &TimeFormatter) -> String {
     let mut prompt = String::from(LUNEX_BASE_PROMPT);
 
     // Add environment information
@@ -29,11 +31,8 @@ pub fn get_app_prompt() -> String {
     prompt.push_str(&format!("- Operating System: {}\n", std::env::consts::OS));
     prompt.push_str(&format!("- Architecture: {}\n", std::env::consts::ARCH));
 
-    let now = chrono::Local::now();
-    prompt.push_str(&format!(
-        "- Current Time: {}\n",
-        now.format("%Y-%m-%d %H:%M:%S %:z")
-    ));
+    let now = chrono::Utc::now().timestamp_millis();
+    prompt.push_str(&format!("- Current Time: {}\n", time.format_datetime(now)));
 
     // Add more environment details if needed (locale, etc.)
     if let Ok(lang) = std::env::var("LANG").or_else(|_| std::env::var("LC_ALL")) {
@@ -42,3 +41,192 @@ pub fn get_app_prompt() -> String {
 
     prompt
 }
+
+/// The parts of a system prompt, in the order they are sent.
+#[derive(Debug, Default)]
+pub struct SystemPromptLayers<'a> {
+    pub app: String,
+    /// The agent's persona, or else the workspace's system message
+    pub instructions: Option<&'a str>,
+    pub workspace_notes: Option<&'a str>,
+    pub skills: Option<&'a str>,
+    /// The chat's own custom instructions, after everything from the workspace
+    pub chat_instructions: Option<&'a str>,
+    /// Standing instructions of the LLM connection, always last
+    pub connection_instructions: Option<&'a str>,
+}
+
+/// Join the layers, leaving out blank ones. Notes, chat and connection
+/// instructions go in labelled sections.
+pub fn compose_system_prompt(layers: &SystemPromptLayers<'_>) -> String {
+    let mut prompt = layers.app.clone();
+    let mut push = |section: &str| {
+        if !prompt.is_empty() {
+            prompt.push_str("\n\n");
+        }
+        prompt.push_str(section);
+    };
+    if let Some(instructions) = present(layers.instructions) {
+        push(instructions);
+    }
+    if let Some(notes) = present(layers.workspace_notes) {
+        push(&format!("<workspace_notes>\n{notes}\n</workspace_notes>"));
+    }
+    if let Some(skills) = present(layers.skills) {
+        push(skills);
+    }
+    if let Some(chat) = present(layers.chat_instructions) {
+        push(&format!(
+            "<chat_instructions>\n{chat}\n</chat_instructions>"
+        ));
+    }
+    if let Some(suffix) = present(layers.connection_instructions) {
+        push(&format!(
+            "<connection_instructions>\n{suffix}\n</connection_instructions>"
+        ));
+    }
+    prompt
+}
+
+fn present(layer: Option<&str>) -> Option<&str> {
+    layer.filter(|text| !text.trim().is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn workspace_notes_sit_between_instructions_and_skills() {
+        use crate::features::chat::prompts::{compose_system_prompt, SystemPromptLayers};
+
+        let prompt = compose_system_prompt(&SystemPromptLayers {
+            app: "APP".to_string(),
+            instructions: Some("You are the release agent."),
+            workspace_notes: Some("Ship on Thursdays."),
+            skills: Some("SKILLS"),
+            chat_instructions: None,
+            connection_instructions: Some("Answer in English."),
+        });
+        assert_eq!(
+            prompt,
+            "APP\n\nYou are the release agent.\n\n<workspace_notes>\nShip on Thursdays.\n</workspace_notes>\n\nSKILLS\n\n<connection_instructions>\nAnswer in English.\n</connection_instructions>"
+        );
+
+        // Blank layers leave no empty section behind
+        let prompt = compose_system_prompt(&SystemPromptLayers {
+            app: "APP".to_string(),
+            instructions: Some("  "),
+            workspace_notes: Some("\n"),
+            skills: Some(""),
+            chat_instructions: Some(" "),
+            connection_instructions: None,
+        });
+        assert_eq!(prompt, "APP");
+    }
+
+    #[test]
+    fn workspace_notes_are_capped_within_the_prompt_budget() {
+        use crate::features::chat::cost_gate::{estimate_prompt_tokens, estimate_text_tokens};
+        use crate::features::chat::prompts::{compose_system_prompt, SystemPromptLayers};
+        use crate::features::workspace::notes::{
+            context_notes, NOTES_CONTEXT_TOKEN_CAP, NOTES_TRUNCATION_MARKER,
+        };
+        use crate::models::llm_types::ChatMessage;
+
+        assert_eq!(context_notes("  \n ", NOTES_CONTEXT_TOKEN_CAP), None);
+        assert_eq!(
+            context_notes(" # Brief\nShort. ", NOTES_CONTEXT_TOKEN_CAP).as_deref(),
+            Some("# Brief\nShort.")
+        );
+
+        // Long notes, accents included, are cut to the cap with the marker
+        let long = "Le déploiement se fait le jeudi. ".repeat(1_000);
+        let cut = context_notes(&long, NOTES_CONTEXT_TOKEN_CAP).unwrap();
+        assert!(cut.ends_with(NOTES_TRUNCATION_MARKER));
+        assert!(estimate_text_tokens(&cut) <= NOTES_CONTEXT_TOKEN_CAP);
+        assert!(estimate_text_tokens(&cut) > NOTES_CONTEXT_TOKEN_CAP - 10);
+
+        // Combining marks make graphemes of several characters
+        let combining = "e\u{301}\u{302}".repeat(20_000);
+        let cut = context_notes(&combining, 100).unwrap();
+        assert!(estimate_text_tokens(&cut) <= 100);
+
+        // The notes add at most their cap and labels to the estimated prompt
+        let system = |notes: Option<&str>| {
+            vec![ChatMessage::System {
+                content: compose_system_prompt(&SystemPromptLayers {
+                    app: "APP".to_string(),
+                    instructions: Some("Be brief."),
+                    workspace_notes: notes,
+                    ..SystemPromptLayers::default()
+                }),
+            }]
+        };
+        let notes = context_notes(&long, NOTES_CONTEXT_TOKEN_CAP).unwrap();
+        let (without, _) = estimate_prompt_tokens(&system(None), None);
+        let (with, _) = estimate_prompt_tokens(&system(Some(&notes)), None);
+        assert!(with > without);
+        assert!(with - without <= NOTES_CONTEXT_TOKEN_CAP + 12);
+    }
+
+    #[test]
+    fn chat_instructions_follow_the_workspace_for_chats_and_agents() {
+        use crate::features::agent::common::Manifest;
+        use crate::features::chat::prompts::{compose_system_prompt, SystemPromptLayers};
+        use crate::features::chat::ChatService;
+
+        // A plain chat: after the workspace's system message, notes and skills
+        let prompt = compose_system_prompt(&SystemPromptLayers {
+            app: "APP".to_string(),
+            instructions: Some("Workspace system message."),
+            workspace_notes: Some("Ship on Thursdays."),
+            skills: Some("SKILLS"),
+            chat_instructions: Some("Answer in bullet points, max 200 words."),
+            connection_instructions: Some("Answer in English."),
+        });
+        assert_eq!(
+            prompt,
+            "APP\n\nWorkspace system message.\n\n<workspace_notes>\nShip on Thursdays.\n</workspace_notes>\n\nSKILLS\n\n<chat_instructions>\nAnswer in bullet points, max 200 words.\n</chat_instructions>\n\n<connection_instructions>\nAnswer in English.\n</connection_instructions>"
+        );
+
+        // An agent's persona replaces the system message; the chat's
+        // instructions are appended to it
+        let agent = |chat_instructions| {
+            compose_system_prompt(&SystemPromptLayers {
+                app: "APP".to_string(),
+                instructions: Some("You are the release agent."),
+                chat_instructions,
+                ..SystemPromptLayers::default()
+            })
+        };
+        assert_eq!(
+            agent(Some("Be terse.")),
+            "APP\n\nYou are the release agent.\n\n<chat_instructions>\nBe terse.\n</chat_instructions>"
+        );
+        assert_eq!(agent(None), "APP\n\nYou are the release agent.");
+
+        // Only a manifest that says so opts out
+        let manifest = |extra: &str| -> Manifest {
+            serde_yaml::from_str(&format!(
+                "schema_version: 1\nid: a\nname: A\ndescription: d\nauthor: x\n{extra}"
+            ))
+            .unwrap()
+        };
+        assert_eq!(manifest("").include_chat_instructions, None);
+        assert_eq!(
+            manifest("include_chat_instructions: false").include_chat_instructions,
+            Some(false)
+        );
+
+        // Answers record whether instructions applied and which version
+        let first = ChatService::chat_instructions_metadata(Some("Be terse.")).unwrap();
+        assert_eq!(first["applied"], true);
+        assert_eq!(
+            ChatService::chat_instructions_metadata(Some(" Be terse.\n")).unwrap(),
+            first
+        );
+        let edited = ChatService::chat_instructions_metadata(Some("Be thorough.")).unwrap();
+        assert_ne!(edited["hash"], first["hash"]);
+        assert_eq!(ChatService::chat_instructions_metadata(None), None);
+        assert_eq!(ChatService::chat_instructions_metadata(Some("  ")), None);
+    }
+}
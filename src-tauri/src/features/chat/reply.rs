@@ -0,0 +1,218 @@
+//! Replies to an earlier message of the same chat.
+
+use crate::error::AppError;
+use crate::features::message::{Message, REPLY_TO_METADATA_KEY};
+
+/// Longest quoted excerpt of the replied-to message, in characters.
+pub const REPLY_EXCERPT_MAX_CHARS: usize = 500;
+
+/// Store `reply_to` in the outgoing message metadata.
+pub fn with_reply_to(metadata: Option<String>, reply_to: Option<String>) -> Option<String> {
+    let Some(reply_to) = reply_to.filter(|id| !id.is_empty()) else {
+        return metadata;
+    };
+    let mut meta = metadata
+        .and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok())
+        .filter(serde_json::Value::is_object)
+        .unwrap_or_else(|| serde_json::json!({}));
+    meta[REPLY_TO_METADATA_KEY] = serde_json::json!(reply_to);
+    Some(meta.to_string())
+}
+
+/// Check that a reply target exists, belongs to `chat_id` and is a user or
+/// assistant message.
+pub fn validate_reply_target(target: Option<&Message>, chat_id: &str) -> Result<(), AppError> {
+    let target =
+        target.ok_or_else(|| AppError::Validation("Reply target not found".to_string()))?;
+    if target.chat_id != chat_id {
+        return Err(AppError::Validation(
+            "Reply target belongs to another chat".to_string(),
+        ));
+    }
+    if !matches!(target.role.as_str(), "user" | "assistant") {
+        return Err(AppError::Validation(format!(
+            "Cannot reply to a {} message",
+            target.role
+        )));
+    }
+    Ok(())
+}
+
+/// Quote `content` as a Markdown blockquote, cut to `REPLY_EXCERPT_MAX_CHARS`.
+/// A code fence left open by the cut is closed so it does not swallow the
+/// reply that follows.
+pub fn reply_excerpt(content: &str) -> String {
+    let content = content.trim();
+    let kept = crate::lib::text::truncate(content, REPLY_EXCERPT_MAX_CHARS, "");
+    let cut = kept.len() < content.len();
+
+    let mut lines = Vec::new();
+    let mut open_fence: Option<String> = None;
+    for line in kept.trim_end().lines() {
+        let trimmed = line.trim_start();
+        let marker: String = trimmed
+            .chars()
+            .take_while(|c| *c == '`' || *c == '~')
+            .collect();
+        if marker.len() >= 3 {
+            match &open_fence {
+                Some(open) if marker.starts_with(open.as_str()) => open_fence = None,
+                Some(_) => {}
+                None => open_fence = Some(marker),
+            }
+        }
+        lines.push(line.to_string());
+    }
+
+    if cut {
+        match open_fence {
+            Some(fence) => {
+                lines.push(fence);
+                lines.push("…".to_string());
+            }
+            None => match lines.last_mut() {
+                Some(last) => last.push('…'),
+                None => lines.push("…".to_string()),
+            },
+        }
+    }
+
+    lines
+        .iter()
+        .map(|line| {
+            if line.is_empty() {
+                ">".to_string()
+            } else {
+                format!("> {line}")
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// User content as sent to the model: the quoted excerpt, then the reply.
+pub fn quote_reply(target: &Message, content: &str) -> String {
+    format!("{}\n\n{content}", reply_excerpt(&target.content))
+}
+
+/// Make sure `history`, a subset of `messages` in chat order, contains the
+/// replied-to message and the user message right before it (the question it
+/// answered), wherever they are in the chat. Order is preserved.
+pub fn keep_reply_anchor<'a>(
+    messages: &'a [Message],
+    history: Vec<&'a Message>,
+    reply_to: Option<&str>,
+) -> Vec<&'a Message> {
+    let Some(target_index) = reply_to.and_then(|id| messages.iter().position(|m| m.id == id))
+    else {
+        return history;
+    };
+
+    let mut anchor = vec![&messages[target_index]];
+    if messages[target_index].role == "assistant" {
+        if let Some(question) = messages[..target_index]
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+        {
+            anchor.push(question);
+        }
+    }
+    if anchor.iter().all(|a| history.iter().any(|m| m.id == a.id)) {
+        return history;
+    }
+
+    messages
+        .iter()
+        .filter(|m| history.iter().any(|h| h.id == m.id) || anchor.iter().any(|a| a.id == m.id))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn reply_anchor_survives_a_truncated_history() {
+        use crate::features::chat::reply::{keep_reply_anchor, validate_reply_target};
+
+        let messages = vec![
+            chat_message("u1", "user", "Give me three options"),
+            chat_message("a1", "assistant", "1. A\n2. B\n3. C"),
+            chat_message("u2", "user", "Thanks"),
+            chat_message("a2", "assistant", "You're welcome"),
+            chat_message("t1", "tool", "{}"),
+            chat_message("u3", "user", "Something else"),
+            chat_message("a3", "assistant", "Sure"),
+        ];
+        let ids = |history: &[&crate::features::message::Message]| {
+            history.iter().map(|m| m.id.clone()).collect::<Vec<_>>()
+        };
+
+        // Only the last exchange fits: the answer replied to and its question
+        // come back, in chat order
+        let window: Vec<_> = messages[5..].iter().collect();
+        let history = keep_reply_anchor(&messages, window.clone(), Some("a1"));
+        assert_eq!(ids(&history), ["u1", "a1", "u3", "a3"]);
+
+        // Replying to a user message needs only that message
+        let history = keep_reply_anchor(&messages, window.clone(), Some("u2"));
+        assert_eq!(ids(&history), ["u2", "u3", "a3"]);
+
+        // Already present, unknown or no target: the history is unchanged
+        let full: Vec<_> = messages.iter().collect();
+        assert_eq!(
+            ids(&keep_reply_anchor(&messages, full, Some("a1"))).len(),
+            7
+        );
+        assert_eq!(
+            ids(&keep_reply_anchor(&messages, window.clone(), Some("gone"))),
+            ["u3", "a3"]
+        );
+        assert_eq!(
+            ids(&keep_reply_anchor(&messages, window, None)),
+            ["u3", "a3"]
+        );
+
+        assert!(validate_reply_target(Some(&messages[1]), "chat").is_ok());
+        assert!(validate_reply_target(Some(&messages[4]), "chat").is_err());
+        assert!(validate_reply_target(Some(&messages[1]), "other-chat").is_err());
+        assert!(validate_reply_target(None, "chat").is_err());
+    }
+
+    #[test]
+    fn reply_excerpt_quotes_and_closes_code_fences() {
+        use crate::features::chat::reply::{reply_excerpt, REPLY_EXCERPT_MAX_CHARS};
+
+        // Short content is quoted line by line, fences intact
+        let short = "Option two:\n\n```rust\nlet x = 2;\n```\nDone.";
+        assert_eq!(
+            reply_excerpt(short),
+            "> Option two:\n>\n> ```rust\n> let x = 2;\n> ```\n> Done."
+        );
+
+        // Cut inside a fence: the fence is closed before the ellipsis
+        let code = format!("Here:\n```python\n{}", "print('hi')\n".repeat(100));
+        let excerpt = reply_excerpt(&code);
+        let lines: Vec<&str> = excerpt.lines().collect();
+        assert_eq!(lines[..2], ["> Here:", "> ```python"]);
+        assert_eq!(lines[lines.len() - 2..], ["> ```", "> …"]);
+        assert!(lines.iter().all(|l| l.starts_with('>')));
+
+        // Cut in plain text after a closed fence: no extra fence
+        let prose = format!("```\ncode\n```\n{}", "word ".repeat(200));
+        let excerpt = reply_excerpt(&prose);
+        assert!(excerpt.ends_with('…'));
+        assert_eq!(excerpt.matches("```").count(), 2);
+
+        // Tilde fences and the character cap, counted in characters
+        let tilde = format!("~~~~\n{}", "é".repeat(REPLY_EXCERPT_MAX_CHARS));
+        let excerpt = reply_excerpt(&tilde);
+        assert!(excerpt.ends_with("> ~~~~\n> …"));
+        let quoted: usize = excerpt
+            .lines()
+            .map(|l| l.trim_start_matches("> ").chars().count())
+            .sum();
+        assert!(quoted <= REPLY_EXCERPT_MAX_CHARS + 5);
+    }
+}
@@ -1,6 +1,10 @@
+use super::branches::ChatBranch;
 use super::models::Chat;
+use super::summary::ChatSummary;
+use super::tool_permissions::{RememberScope, RememberedToolPermission};
 use crate::error::AppError;
-use rusqlite::params;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use tauri::AppHandle;
 
@@ -21,6 +25,306 @@ pub trait ChatRepository: Send + Sync {
     ) -> Result<(), AppError>;
     fn delete(&self, id: &str) -> Result<(), AppError>;
     fn delete_by_workspace_id(&self, workspace_id: &str) -> Result<(), AppError>;
+    fn get_scratchpad(&self, id: &str) -> Result<Option<String>, AppError>;
+    fn set_scratchpad(&self, id: &str, content: Option<&str>) -> Result<(), AppError>;
+    fn append_scratchpad(&self, id: &str, content: &str) -> Result<(), AppError>;
+    fn get_summary(&self, id: &str) -> Result<Option<ChatSummary>, AppError>;
+    /// Replace the chat's rolling summary; `None` removes it.
+    fn set_summary(&self, id: &str, summary: Option<&ChatSummary>) -> Result<(), AppError>;
+    fn set_metadata(&self, id: &str, metadata: Option<&str>) -> Result<(), AppError>;
+    fn mark_read(&self, id: &str, read_at: i64) -> Result<(), AppError>;
+    fn set_custom_instructions(&self, id: &str, instructions: Option<&str>)
+        -> Result<(), AppError>;
+    fn get_branches(&self, chat_id: &str) -> Result<Vec<ChatBranch>, AppError>;
+    fn get_active_branch(&self, chat_id: &str) -> Result<Option<String>, AppError>;
+    fn create_branch(&self, branch: &ChatBranch) -> Result<(), AppError>;
+    /// Show `branch_id`, `None` being the main branch.
+    fn set_active_branch(&self, chat_id: &str, branch_id: Option<&str>) -> Result<(), AppError>;
+    fn get_tool_permissions(&self, chat_id: &str) -> Result<BTreeMap<String, String>, AppError>;
+    /// Keep `permission` for a tool of the chat, `None` forgetting it.
+    fn set_tool_permission(
+        &self,
+        chat_id: &str,
+        tool_name: &str,
+        permission: Option<&str>,
+    ) -> Result<(), AppError>;
+    fn get_workspace_tool_permissions(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<RememberedToolPermission>, AppError>;
+}
+
+/// Chat columns, with the messages the user has not seen yet: answers and
+/// tool calls newer than the chat was last read. Counted on every read, so
+/// deleted or rewritten history never leaves a stale count behind.
+const CHAT_COLUMNS: &str = "id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id, last_read_at, custom_instructions,
+    (SELECT COUNT(*) FROM messages m WHERE m.chat_id = chats.id
+        AND m.role IN ('assistant', 'tool_call')
+        AND m.timestamp > COALESCE(chats.last_read_at, 0))";
+
+fn chat_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Chat> {
+    Ok(Chat {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        title: row.get(2)?,
+        last_message: row.get(3)?,
+        created_at: row.get(4)?,
+        updated_at: row.get(5)?,
+        agent_id: row.get(6)?,
+        parent_id: row.get(7)?,
+        last_read_at: row.get(8)?,
+        custom_instructions: row.get(9)?,
+        unread_count: row.get(10)?,
+    })
+}
+
+pub fn chats_by_workspace(conn: &Connection, workspace_id: &str) -> Result<Vec<Chat>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {CHAT_COLUMNS} FROM chats WHERE workspace_id = ?1 ORDER BY updated_at DESC"
+    ))?;
+    let chats = stmt
+        .query_map(params![workspace_id], chat_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(chats)
+}
+
+/// Insert a chat row. Its custom instructions go with it, so a chat created
+/// from a copy of another keeps them.
+pub fn insert_chat(conn: &Connection, chat: &Chat) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO chats (id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id, last_read_at, custom_instructions) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+        params![chat.id, chat.workspace_id, chat.title, chat.last_message, chat.created_at, chat.updated_at, chat.agent_id, chat.parent_id, chat.last_read_at, chat.custom_instructions],
+    )?;
+    Ok(())
+}
+
+/// Replace the custom instructions of a chat; `None` removes them. The chat
+/// keeps its place in the list.
+pub fn set_chat_instructions(
+    conn: &Connection,
+    id: &str,
+    instructions: Option<&str>,
+) -> Result<(), AppError> {
+    let changed = conn.execute(
+        "UPDATE chats SET custom_instructions = ?1 WHERE id = ?2",
+        params![instructions, id],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Chat not found: {id}")));
+    }
+    Ok(())
+}
+
+/// Record that the chat was read up to `read_at`. A single statement; an
+/// older time than the stored one (a late, debounced call) changes nothing.
+pub fn mark_chat_read(conn: &Connection, id: &str, read_at: i64) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE chats SET last_read_at = MAX(COALESCE(last_read_at, 0), ?1) WHERE id = ?2",
+        params![read_at, id],
+    )?;
+    Ok(())
+}
+
+pub fn chat_scratchpad(conn: &Connection, id: &str) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT scratchpad FROM chats WHERE id = ?1",
+            params![id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+pub fn set_chat_scratchpad(
+    conn: &Connection,
+    id: &str,
+    content: Option<&str>,
+) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE chats SET scratchpad = ?1 WHERE id = ?2",
+        params![content, id],
+    )?;
+    Ok(())
+}
+
+/// Appends in a single statement so concurrent writers never interleave.
+pub fn append_chat_scratchpad(conn: &Connection, id: &str, content: &str) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE chats SET scratchpad = CASE
+            WHEN scratchpad IS NULL OR scratchpad = '' THEN ?1
+            ELSE scratchpad || char(10) || ?1
+        END WHERE id = ?2",
+        params![content, id],
+    )?;
+    Ok(())
+}
+
+pub fn chat_summary(conn: &Connection, id: &str) -> Result<Option<ChatSummary>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT summary, summary_through FROM chats WHERE id = ?1",
+            params![id],
+            |row| {
+                let content: Option<String> = row.get(0)?;
+                let through: Option<i64> = row.get(1)?;
+                Ok(content
+                    .zip(through)
+                    .map(|(content, through)| ChatSummary { content, through }))
+            },
+        )
+        .optional()?
+        .flatten())
+}
+
+pub fn set_chat_summary(
+    conn: &Connection,
+    id: &str,
+    summary: Option<&ChatSummary>,
+) -> Result<(), AppError> {
+    let changed = conn.execute(
+        "UPDATE chats SET summary = ?1, summary_through = ?2 WHERE id = ?3",
+        params![
+            summary.map(|s| s.content.as_str()),
+            summary.map(|s| s.through),
+            id
+        ],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Chat not found: {id}")));
+    }
+    Ok(())
+}
+
+/// The branches of a chat, oldest first, with the messages written on each.
+pub fn chat_branches(conn: &Connection, chat_id: &str) -> Result<Vec<ChatBranch>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT b.id, b.chat_id, b.parent_branch_id, b.fork_message_id, b.fork_timestamp,
+                b.name, b.created_at,
+                (SELECT COUNT(*) FROM messages m WHERE m.branch_id = b.id)
+         FROM chat_branches b WHERE b.chat_id = ?1 ORDER BY b.created_at, b.rowid",
+    )?;
+    let branches = stmt
+        .query_map(params![chat_id], |row| {
+            Ok(ChatBranch {
+                id: row.get(0)?,
+                chat_id: row.get(1)?,
+                parent_branch_id: row.get(2)?,
+                fork_message_id: row.get(3)?,
+                fork_timestamp: row.get(4)?,
+                name: row.get(5)?,
+                created_at: row.get(6)?,
+                message_count: row.get(7)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(branches)
+}
+
+/// The branch a chat shows, `None` for the main branch.
+pub fn active_branch_id(conn: &Connection, chat_id: &str) -> Result<Option<String>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT active_branch_id FROM chats WHERE id = ?1",
+            params![chat_id],
+            |row| row.get(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+pub fn insert_branch(conn: &Connection, branch: &ChatBranch) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO chat_branches (id, chat_id, parent_branch_id, fork_message_id, fork_timestamp, name, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            branch.id,
+            branch.chat_id,
+            branch.parent_branch_id,
+            branch.fork_message_id,
+            branch.fork_timestamp,
+            branch.name,
+            branch.created_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn set_active_branch_id(
+    conn: &Connection,
+    chat_id: &str,
+    branch_id: Option<&str>,
+) -> Result<(), AppError> {
+    let changed = conn.execute(
+        "UPDATE chats SET active_branch_id = ?1 WHERE id = ?2",
+        params![branch_id, chat_id],
+    )?;
+    if changed == 0 {
+        return Err(AppError::NotFound(format!("Chat not found: {chat_id}")));
+    }
+    Ok(())
+}
+
+/// The answers kept for the chat's tools, by tool name.
+pub fn chat_tool_permissions(
+    conn: &Connection,
+    chat_id: &str,
+) -> Result<BTreeMap<String, String>, AppError> {
+    let mut stmt =
+        conn.prepare("SELECT tool_name, permission FROM chat_tool_permissions WHERE chat_id = ?1")?;
+    let permissions = stmt
+        .query_map(params![chat_id], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<BTreeMap<_, _>, _>>()?;
+    Ok(permissions)
+}
+
+pub fn set_chat_tool_permission(
+    conn: &Connection,
+    chat_id: &str,
+    tool_name: &str,
+    permission: Option<&str>,
+) -> Result<(), AppError> {
+    match permission {
+        Some(permission) => conn.execute(
+            "INSERT INTO chat_tool_permissions (chat_id, tool_name, permission, created_at)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (chat_id, tool_name) DO UPDATE SET permission = ?3, created_at = ?4",
+            params![
+                chat_id,
+                tool_name,
+                permission,
+                chrono::Utc::now().timestamp_millis()
+            ],
+        )?,
+        None => conn.execute(
+            "DELETE FROM chat_tool_permissions WHERE chat_id = ?1 AND tool_name = ?2",
+            params![chat_id, tool_name],
+        )?,
+    };
+    Ok(())
+}
+
+/// The answers kept for the chats of a workspace, by chat and tool.
+pub fn workspace_chat_tool_permissions(
+    conn: &Connection,
+    workspace_id: &str,
+) -> Result<Vec<RememberedToolPermission>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT p.chat_id, p.tool_name, p.permission FROM chat_tool_permissions p
+         JOIN chats c ON c.id = p.chat_id
+         WHERE c.workspace_id = ?1 ORDER BY p.chat_id, p.tool_name",
+    )?;
+    let permissions = stmt
+        .query_map(params![workspace_id], |row| {
+            Ok(RememberedToolPermission {
+                scope: RememberScope::Chat,
+                chat_id: Some(row.get(0)?),
+                tool_name: row.get(1)?,
+                permission: row.get(2)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(permissions)
 }
 
 pub struct SqliteChatRepository {
@@ -36,54 +340,20 @@ impl SqliteChatRepository {
 impl ChatRepository for SqliteChatRepository {
     fn create(&self, chat: &Chat) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
-        conn.execute(
-            "INSERT INTO chats (id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
-            params![chat.id, chat.workspace_id, chat.title, chat.last_message, chat.created_at, chat.updated_at, chat.agent_id, chat.parent_id],
-        )?;
-        Ok(())
+        insert_chat(&conn, chat)
     }
 
     fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<Chat>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id FROM chats WHERE workspace_id = ?1 ORDER BY updated_at DESC"
-        )?;
-
-        let chats = stmt
-            .query_map(params![workspace_id], |row| {
-                Ok(Chat {
-                    id: row.get(0)?,
-                    workspace_id: row.get(1)?,
-                    title: row.get(2)?,
-                    last_message: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    agent_id: row.get(6)?,
-                    parent_id: row.get(7)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(chats)
+        chats_by_workspace(&conn, workspace_id)
     }
 
     fn get_by_id(&self, id: &str) -> Result<Option<Chat>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id FROM chats WHERE id = ?1",
+            &format!("SELECT {CHAT_COLUMNS} FROM chats WHERE id = ?1"),
             params![id],
-            |row| {
-                Ok(Chat {
-                    id: row.get(0)?,
-                    workspace_id: row.get(1)?,
-                    title: row.get(2)?,
-                    last_message: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    agent_id: row.get(6)?,
-                    parent_id: row.get(7)?,
-                })
-            },
+            chat_from_row,
         );
 
         match result {
@@ -100,20 +370,9 @@ impl ChatRepository for SqliteChatRepository {
     ) -> Result<Option<Chat>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT id, workspace_id, title, last_message, created_at, updated_at, agent_id, parent_id FROM chats WHERE parent_id = ?1 AND agent_id = ?2",
+            &format!("SELECT {CHAT_COLUMNS} FROM chats WHERE parent_id = ?1 AND agent_id = ?2"),
             params![parent_id, agent_id],
-            |row| {
-                Ok(Chat {
-                    id: row.get(0)?,
-                    workspace_id: row.get(1)?,
-                    title: row.get(2)?,
-                    last_message: row.get(3)?,
-                    created_at: row.get(4)?,
-                    updated_at: row.get(5)?,
-                    agent_id: row.get(6)?,
-                    parent_id: row.get(7)?,
-                })
-            },
+            chat_from_row,
         );
 
         match result {
@@ -166,4 +425,265 @@ impl ChatRepository for SqliteChatRepository {
         )?;
         Ok(())
     }
+
+    fn get_scratchpad(&self, id: &str) -> Result<Option<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        chat_scratchpad(&conn, id)
+    }
+
+    fn set_scratchpad(&self, id: &str, content: Option<&str>) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        set_chat_scratchpad(&conn, id, content)
+    }
+
+    fn append_scratchpad(&self, id: &str, content: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        append_chat_scratchpad(&conn, id, content)
+    }
+
+    fn get_summary(&self, id: &str) -> Result<Option<ChatSummary>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        chat_summary(&conn, id)
+    }
+
+    fn set_summary(&self, id: &str, summary: Option<&ChatSummary>) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        set_chat_summary(&conn, id, summary)
+    }
+
+    fn set_metadata(&self, id: &str, metadata: Option<&str>) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE chats SET metadata = ?1 WHERE id = ?2",
+            params![metadata, id],
+        )?;
+        Ok(())
+    }
+
+    fn mark_read(&self, id: &str, read_at: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        mark_chat_read(&conn, id, read_at)
+    }
+
+    fn set_custom_instructions(
+        &self,
+        id: &str,
+        instructions: Option<&str>,
+    ) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        set_chat_instructions(&conn, id, instructions)
+    }
+
+    fn get_branches(&self, chat_id: &str) -> Result<Vec<ChatBranch>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        chat_branches(&conn, chat_id)
+    }
+
+    fn get_active_branch(&self, chat_id: &str) -> Result<Option<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        active_branch_id(&conn, chat_id)
+    }
+
+    fn create_branch(&self, branch: &ChatBranch) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        insert_branch(&conn, branch)
+    }
+
+    fn set_active_branch(&self, chat_id: &str, branch_id: Option<&str>) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        set_active_branch_id(&conn, chat_id, branch_id)
+    }
+
+    fn get_tool_permissions(&self, chat_id: &str) -> Result<BTreeMap<String, String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        chat_tool_permissions(&conn, chat_id)
+    }
+
+    fn set_tool_permission(
+        &self,
+        chat_id: &str,
+        tool_name: &str,
+        permission: Option<&str>,
+    ) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        set_chat_tool_permission(&conn, chat_id, tool_name, permission)
+    }
+
+    fn get_workspace_tool_permissions(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<RememberedToolPermission>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        workspace_chat_tool_permissions(&conn, workspace_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    fn read_receipt_db() -> rusqlite::Connection {
+        let conn = migrated_db();
+        conn.execute(
+            "INSERT INTO chats (id, workspace_id, title, created_at, updated_at) VALUES ('c1', 'ws', 'Chat', 0, 0), ('c2', 'ws', 'Other', 0, 0)",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    fn insert_chat_message(conn: &rusqlite::Connection, id: &str, role: &str, timestamp: i64) {
+        conn.execute(
+            "INSERT INTO messages (id, chat_id, role, content, timestamp) VALUES (?1, 'c1', ?2, 'x', ?3)",
+            rusqlite::params![id, role, timestamp],
+        )
+        .unwrap();
+    }
+
+    fn unread_count(conn: &rusqlite::Connection, chat_id: &str) -> i64 {
+        crate::features::chat::chats_by_workspace(conn, "ws")
+            .unwrap()
+            .into_iter()
+            .find(|chat| chat.id == chat_id)
+            .unwrap()
+            .unread_count
+    }
+
+    #[test]
+    fn read_receipts_count_visible_messages_after_last_read() {
+        use crate::features::chat::mark_chat_read;
+
+        let conn = read_receipt_db();
+        for (id, role, timestamp) in [
+            ("u1", "user", 10),
+            ("a1", "assistant", 11),
+            ("tc1", "tool_call", 12),
+            ("t1", "tool", 12),
+            ("a2", "assistant", 13),
+        ] {
+            insert_chat_message(&conn, id, role, timestamp);
+        }
+        // Never read: every answer and tool call counts, user and tool results do not
+        assert_eq!(unread_count(&conn, "c1"), 3);
+        assert_eq!(unread_count(&conn, "c2"), 0);
+
+        mark_chat_read(&conn, "c1", 13).unwrap();
+        assert_eq!(unread_count(&conn, "c1"), 0);
+
+        // A turn that finished in the background shows up as unread
+        insert_chat_message(&conn, "u2", "user", 20);
+        insert_chat_message(&conn, "a3", "assistant", 21);
+        assert_eq!(unread_count(&conn, "c1"), 1);
+
+        // A late, debounced call does not move the receipt back
+        mark_chat_read(&conn, "c1", 21).unwrap();
+        mark_chat_read(&conn, "c1", 5).unwrap();
+        assert_eq!(unread_count(&conn, "c1"), 0);
+        let chats = crate::features::chat::chats_by_workspace(&conn, "ws").unwrap();
+        let chat = chats.iter().find(|chat| chat.id == "c1").unwrap();
+        assert_eq!(chat.last_read_at, Some(21));
+        // Reading does not reorder the chat list
+        assert_eq!(chat.updated_at, 0);
+    }
+
+    #[test]
+    fn read_receipts_recount_after_edit_and_resend() {
+        use crate::features::chat::mark_chat_read;
+
+        let conn = read_receipt_db();
+        for (id, role, timestamp) in [
+            ("u1", "user", 10),
+            ("a1", "assistant", 11),
+            ("u2", "user", 20),
+            ("tc1", "tool_call", 21),
+            ("t1", "tool", 21),
+            ("a2", "assistant", 22),
+        ] {
+            insert_chat_message(&conn, id, role, timestamp);
+        }
+        mark_chat_read(&conn, "c1", 15).unwrap();
+        assert_eq!(unread_count(&conn, "c1"), 2);
+
+        // Editing u2 removes it and everything after it, then sends again
+        conn.execute("DELETE FROM messages WHERE timestamp >= 20", [])
+            .unwrap();
+        assert_eq!(unread_count(&conn, "c1"), 0);
+        insert_chat_message(&conn, "u3", "user", 30);
+        insert_chat_message(&conn, "a3", "assistant", 31);
+        assert_eq!(unread_count(&conn, "c1"), 1);
+
+        // Rewriting again never adds to the old count
+        conn.execute("DELETE FROM messages WHERE timestamp >= 30", [])
+            .unwrap();
+        insert_chat_message(&conn, "u4", "user", 40);
+        insert_chat_message(&conn, "a4", "assistant", 41);
+        assert_eq!(unread_count(&conn, "c1"), 1);
+    }
+
+    #[test]
+    fn chat_instructions_are_capped_and_copied_with_the_chat() {
+        use crate::error::AppError;
+        use crate::features::chat::models::{Chat, CHAT_INSTRUCTIONS_MAX_CHARS};
+        use crate::features::chat::{chats_by_workspace, insert_chat, set_chat_instructions};
+
+        assert_eq!(Chat::normalize_instructions(None).unwrap(), None);
+        assert_eq!(Chat::normalize_instructions(Some(" \n ")).unwrap(), None);
+        assert_eq!(
+            Chat::normalize_instructions(Some("  Be terse.\n"))
+                .unwrap()
+                .as_deref(),
+            Some("Be terse.")
+        );
+        // The cap counts characters, not bytes
+        let at_cap = "é".repeat(CHAT_INSTRUCTIONS_MAX_CHARS);
+        assert!(Chat::normalize_instructions(Some(&at_cap))
+            .unwrap()
+            .is_some());
+        let over = format!("{at_cap}é");
+        assert!(matches!(
+            Chat::normalize_instructions(Some(&over)),
+            Err(AppError::Validation(_))
+        ));
+
+        let conn = migrated_db();
+        let chat = Chat {
+            id: "c1".to_string(),
+            workspace_id: "ws".to_string(),
+            title: "Chat".to_string(),
+            last_message: None,
+            created_at: 0,
+            updated_at: 0,
+            agent_id: None,
+            parent_id: None,
+            last_read_at: None,
+            unread_count: 0,
+            custom_instructions: None,
+        };
+        insert_chat(&conn, &chat).unwrap();
+        set_chat_instructions(&conn, "c1", Some("Be terse.")).unwrap();
+        let stored = chats_by_workspace(&conn, "ws").unwrap().remove(0);
+        assert_eq!(stored.custom_instructions.as_deref(), Some("Be terse."));
+        // Setting instructions does not move the chat in the list
+        assert_eq!(stored.updated_at, 0);
+
+        // A copy of the chat takes its instructions along
+        let fork = Chat {
+            id: "c2".to_string(),
+            ..stored
+        };
+        insert_chat(&conn, &fork).unwrap();
+        let chats = chats_by_workspace(&conn, "ws").unwrap();
+        assert!(chats
+            .iter()
+            .all(|chat| chat.custom_instructions.as_deref() == Some("Be terse.")));
+
+        set_chat_instructions(&conn, "c1", None).unwrap();
+        let chats = chats_by_workspace(&conn, "ws").unwrap();
+        let original = chats.iter().find(|chat| chat.id == "c1").unwrap();
+        assert_eq!(original.custom_instructions, None);
+        assert!(matches!(
+            set_chat_instructions(&conn, "missing", Some("x")),
+            Err(AppError::NotFound(_))
+        ));
+    }
 }
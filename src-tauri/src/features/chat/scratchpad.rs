@@ -0,0 +1,416 @@
+//! The per-chat scratchpad: working notes the model keeps across turns
+//! through the `read_scratchpad` / `update_scratchpad` builtin tools, and
+//! that are put into the system prompt of every request.
+
+use super::repository::ChatRepository;
+use crate::error::AppError;
+use crate::models::llm_types::ChatMessage;
+
+/// Scratchpad text injected into the system prompt is capped at about 2k tokens.
+pub const SCRATCHPAD_PROMPT_MAX_CHARS: usize = 8_000;
+/// Upper bound on the stored scratchpad, so appends cannot grow it without limit.
+pub const SCRATCHPAD_MAX_CHARS: usize = 32_000;
+const SCRATCHPAD_OPEN_TAG: &str = "<chat_scratchpad>";
+
+pub fn check_size(chars: usize) -> Result<(), AppError> {
+    if chars > SCRATCHPAD_MAX_CHARS {
+        return Err(AppError::Validation(format!(
+            "Scratchpad is limited to {SCRATCHPAD_MAX_CHARS} characters ({chars} requested); condense it first"
+        )));
+    }
+    Ok(())
+}
+
+/// Current scratchpad for a chat (empty when none has been written).
+pub fn read(chats: &dyn ChatRepository, chat_id: &str) -> Result<String, AppError> {
+    Ok(chats.get_scratchpad(chat_id)?.unwrap_or_default())
+}
+
+pub fn write(chats: &dyn ChatRepository, chat_id: &str, content: &str) -> Result<(), AppError> {
+    check_size(content.chars().count())?;
+    chats.set_scratchpad(chat_id, Some(content).filter(|c| !c.is_empty()))
+}
+
+/// Run the `read_scratchpad` / `update_scratchpad` builtin tools for a chat.
+/// Returns the tool result, and the scratchpad when the tool changed it.
+/// Tool calls in a batch run one after another and appends are a single
+/// statement, so concurrent updates never interleave.
+pub fn run_tool(
+    chats: &dyn ChatRepository,
+    chat_id: &str,
+    tool_name: &str,
+    arguments: &serde_json::Value,
+) -> Result<(serde_json::Value, Option<String>), AppError> {
+    if tool_name == "read_scratchpad" {
+        let content = read(chats, chat_id)?;
+        return Ok((serde_json::json!({ "content": content }), None));
+    }
+
+    let content = arguments
+        .get("content")
+        .and_then(serde_json::Value::as_str)
+        .ok_or_else(|| AppError::Validation("Missing 'content' parameter".to_string()))?;
+    match arguments
+        .get("mode")
+        .and_then(serde_json::Value::as_str)
+        .unwrap_or("replace")
+    {
+        "replace" => write(chats, chat_id, content)?,
+        "append" => {
+            let current = read(chats, chat_id)?.chars().count();
+            check_size(current + 1 + content.chars().count())?;
+            chats.append_scratchpad(chat_id, content)?;
+        }
+        other => {
+            return Err(AppError::Validation(format!(
+                "Unknown mode '{other}', expected 'replace' or 'append'"
+            )))
+        }
+    }
+
+    let updated = read(chats, chat_id)?;
+    let result = serde_json::json!({ "status": "updated", "length": updated.chars().count() });
+    Ok((result, Some(updated)))
+}
+
+/// Put `scratchpad` at the end of the system message, replacing any earlier
+/// copy. Safe to call before every request.
+pub fn inject(scratchpad: &str, messages: &mut Vec<ChatMessage>) {
+    let block = prompt_block(scratchpad);
+
+    if let Some(ChatMessage::System { content }) = messages.first_mut() {
+        if let Some(idx) = content.find(SCRATCHPAD_OPEN_TAG) {
+            content.truncate(idx);
+            content.truncate(content.trim_end().len());
+        }
+        if let Some(block) = block {
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            content.push_str(&block);
+        }
+        if content.is_empty() {
+            messages.remove(0);
+        }
+    } else if let Some(block) = block {
+        messages.insert(0, ChatMessage::System { content: block });
+    }
+}
+
+/// Delimited scratchpad section for the system prompt, capped at
+/// `SCRATCHPAD_PROMPT_MAX_CHARS`. None when the scratchpad is empty.
+pub fn prompt_block(scratchpad: &str) -> Option<String> {
+    let scratchpad = scratchpad.trim();
+    if scratchpad.is_empty() {
+        return None;
+    }
+
+    let total = scratchpad.chars().count();
+    let mut text: String = scratchpad
+        .chars()
+        .take(SCRATCHPAD_PROMPT_MAX_CHARS)
+        .collect();
+    if total > SCRATCHPAD_PROMPT_MAX_CHARS {
+        text = format!(
+            "{text}\n[... {} more characters; call read_scratchpad for the full notes]",
+            total - SCRATCHPAD_PROMPT_MAX_CHARS
+        );
+    }
+
+    Some(format!(
+        "{SCRATCHPAD_OPEN_TAG}\nWorking notes for this chat, kept across turns:\n{text}\n</chat_scratchpad>"
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    /// Chats in an in-memory database, for the scratchpad; nothing else is reached.
+    struct ScratchpadChats(std::sync::Mutex<rusqlite::Connection>);
+
+    impl crate::features::chat::repository::ChatRepository for ScratchpadChats {
+        fn create(
+            &self,
+            _: &crate::features::chat::models::Chat,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_by_workspace_id(
+            &self,
+            _: &str,
+        ) -> Result<Vec<crate::features::chat::models::Chat>, crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_by_id(
+            &self,
+            _: &str,
+        ) -> Result<Option<crate::features::chat::models::Chat>, crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_specialist_session(
+            &self,
+            _: &str,
+            _: &str,
+        ) -> Result<Option<crate::features::chat::models::Chat>, crate::error::AppError> {
+            unreachable!()
+        }
+        fn update(
+            &self,
+            _: &str,
+            _: Option<&str>,
+            _: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn delete(&self, _: &str) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn delete_by_workspace_id(&self, _: &str) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn set_metadata(&self, _: &str, _: Option<&str>) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn mark_read(&self, _: &str, _: i64) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn set_custom_instructions(
+            &self,
+            _: &str,
+            _: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_scratchpad(&self, id: &str) -> Result<Option<String>, crate::error::AppError> {
+            crate::features::chat::repository::chat_scratchpad(&self.0.lock().unwrap(), id)
+        }
+        fn set_scratchpad(
+            &self,
+            id: &str,
+            content: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::chat::repository::set_chat_scratchpad(
+                &self.0.lock().unwrap(),
+                id,
+                content,
+            )
+        }
+        fn append_scratchpad(&self, id: &str, content: &str) -> Result<(), crate::error::AppError> {
+            crate::features::chat::repository::append_chat_scratchpad(
+                &self.0.lock().unwrap(),
+                id,
+                content,
+            )
+        }
+        fn get_summary(
+            &self,
+            _: &str,
+        ) -> Result<Option<crate::features::chat::summary::ChatSummary>, crate::error::AppError>
+        {
+            unreachable!()
+        }
+        fn set_summary(
+            &self,
+            _: &str,
+            _: Option<&crate::features::chat::summary::ChatSummary>,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_branches(
+            &self,
+            _: &str,
+        ) -> Result<Vec<crate::features::chat::branches::ChatBranch>, crate::error::AppError>
+        {
+            unreachable!()
+        }
+        fn get_active_branch(&self, _: &str) -> Result<Option<String>, crate::error::AppError> {
+            unreachable!()
+        }
+        fn create_branch(
+            &self,
+            _: &crate::features::chat::branches::ChatBranch,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn set_active_branch(
+            &self,
+            _: &str,
+            _: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_tool_permissions(
+            &self,
+            _: &str,
+        ) -> Result<std::collections::BTreeMap<String, String>, crate::error::AppError> {
+            unreachable!()
+        }
+        fn set_tool_permission(
+            &self,
+            _: &str,
+            _: &str,
+            _: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+        fn get_workspace_tool_permissions(
+            &self,
+            _: &str,
+        ) -> Result<
+            Vec<crate::features::chat::tool_permissions::RememberedToolPermission>,
+            crate::error::AppError,
+        > {
+            unreachable!()
+        }
+    }
+
+    fn scratchpad_chats() -> ScratchpadChats {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('chat', 'w1', 'Notes', 0, 0);",
+        )
+        .unwrap();
+        ScratchpadChats(std::sync::Mutex::new(conn))
+    }
+
+    #[test]
+    fn scratchpad_is_injected_once_at_the_end_of_the_system_prompt() {
+        use crate::features::chat::scratchpad::inject;
+        use crate::models::llm_types::{ChatMessage, UserContent};
+
+        let system = |messages: &[ChatMessage]| match messages.first() {
+            Some(ChatMessage::System { content }) => Some(content.clone()),
+            _ => None,
+        };
+        let mut messages = vec![
+            ChatMessage::System {
+                content: "You are helpful.".to_string(),
+            },
+            ChatMessage::User {
+                content: UserContent::Text("Hi".to_string()),
+            },
+        ];
+
+        inject("Plan: step 1", &mut messages);
+        let prompt = system(&messages).unwrap();
+        assert!(prompt.starts_with("You are helpful.\n\n<chat_scratchpad>\n"));
+        assert!(prompt.ends_with("Plan: step 1\n</chat_scratchpad>"));
+
+        // Every request re-injects it: the newer notes replace the older ones
+        inject("Plan: step 2", &mut messages);
+        let prompt = system(&messages).unwrap();
+        assert_eq!(prompt.matches("<chat_scratchpad>").count(), 1);
+        assert!(prompt.contains("Plan: step 2") && !prompt.contains("step 1"));
+
+        // Cleared: the system prompt is as it was
+        inject("  ", &mut messages);
+        assert_eq!(system(&messages).unwrap(), "You are helpful.");
+
+        // Without a system prompt the notes get one of their own, and take it away again
+        messages.remove(0);
+        inject("Plan", &mut messages);
+        assert_eq!(messages.len(), 2);
+        assert!(system(&messages).unwrap().starts_with("<chat_scratchpad>"));
+        inject("", &mut messages);
+        assert_eq!(messages.len(), 1);
+        assert!(system(&messages).is_none());
+    }
+
+    #[test]
+    fn scratchpad_tools_round_trip_through_the_chat() {
+        use crate::features::chat::scratchpad::{read, run_tool};
+
+        let chats = scratchpad_chats();
+        let run =
+            |tool: &str, arguments: serde_json::Value| run_tool(&chats, "chat", tool, &arguments);
+
+        let (result, updated) = run("read_scratchpad", serde_json::json!({})).unwrap();
+        assert_eq!(result["content"], "");
+        assert!(updated.is_none());
+
+        let (result, updated) = run(
+            "update_scratchpad",
+            serde_json::json!({ "content": "Plan: read the logs" }),
+        )
+        .unwrap();
+        assert_eq!(
+            result,
+            serde_json::json!({ "status": "updated", "length": 19 })
+        );
+        assert_eq!(updated.as_deref(), Some("Plan: read the logs"));
+
+        let (_, updated) = run(
+            "update_scratchpad",
+            serde_json::json!({ "content": "Open: which service?", "mode": "append" }),
+        )
+        .unwrap();
+        assert_eq!(
+            updated.as_deref(),
+            Some("Plan: read the logs\nOpen: which service?")
+        );
+        let (result, _) = run("read_scratchpad", serde_json::json!({})).unwrap();
+        assert_eq!(
+            result["content"],
+            "Plan: read the logs\nOpen: which service?"
+        );
+
+        // Bad arguments change nothing
+        assert!(run("update_scratchpad", serde_json::json!({ "mode": "append" })).is_err());
+        assert!(run(
+            "update_scratchpad",
+            serde_json::json!({ "content": "x", "mode": "prepend" })
+        )
+        .is_err());
+
+        // Replacing with nothing clears it
+        run("update_scratchpad", serde_json::json!({ "content": "" })).unwrap();
+        assert_eq!(read(&chats, "chat").unwrap(), "");
+        assert_eq!(
+            crate::features::chat::repository::chat_scratchpad(&chats.0.lock().unwrap(), "chat")
+                .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn scratchpad_is_capped_in_storage_and_in_the_prompt() {
+        use crate::features::chat::scratchpad::{
+            prompt_block, read, run_tool, write, SCRATCHPAD_MAX_CHARS, SCRATCHPAD_PROMPT_MAX_CHARS,
+        };
+
+        // The prompt gets the first notes and a pointer to the rest
+        let notes = format!(
+            "{}{}",
+            "a".repeat(SCRATCHPAD_PROMPT_MAX_CHARS),
+            "b".repeat(120)
+        );
+        let block = prompt_block(&notes).unwrap();
+        assert!(block.contains(&"a".repeat(SCRATCHPAD_PROMPT_MAX_CHARS)));
+        assert!(!block.contains('b'));
+        assert!(
+            block.contains("[... 120 more characters; call read_scratchpad for the full notes]")
+        );
+        let short = prompt_block("é".repeat(SCRATCHPAD_PROMPT_MAX_CHARS).as_str()).unwrap();
+        assert!(!short.contains("more characters"));
+
+        // Storage refuses to grow past its limit, by replace or by append
+        let chats = scratchpad_chats();
+        let full = "x".repeat(SCRATCHPAD_MAX_CHARS);
+        write(&chats, "chat", &full).unwrap();
+        assert!(write(&chats, "chat", &format!("{full}x")).is_err());
+        let append = serde_json::json!({ "content": "more", "mode": "append" });
+        assert!(run_tool(&chats, "chat", "update_scratchpad", &append).is_err());
+        assert_eq!(read(&chats, "chat").unwrap(), full);
+
+        // Appends count the line break they add
+        let almost = "x".repeat(SCRATCHPAD_MAX_CHARS - 5);
+        write(&chats, "chat", &almost).unwrap();
+        let append = serde_json::json!({ "content": "12345", "mode": "append" });
+        assert!(run_tool(&chats, "chat", "update_scratchpad", &append).is_err());
+        let append = serde_json::json!({ "content": "1234", "mode": "append" });
+        let (_, updated) = run_tool(&chats, "chat", "update_scratchpad", &append).unwrap();
+        assert_eq!(updated.unwrap().chars().count(), SCRATCHPAD_MAX_CHARS);
+    }
+}
@@ -1,20 +1,87 @@
-use super::models::Chat;
+use super::branches::{self, ChatBranch, ChatBranches};
+use super::cancelled;
+use super::comparison::{
+    self, ComparisonAnswer, ComparisonLeg, ComparisonResult, LegStatus, COMPARISON_METADATA_KEY,
+};
+use super::context_window::{ContextBudget, ContextTrim};
+use super::cost_gate::{self, CostConfirmationOutcome, CostConfirmations};
+use super::empty_answer;
+use super::export::{self, ChatExport, ExportFormat, ExportedChat};
+use super::generation::GenerationParams;
+use super::input_settings::ChatInputSettingsService;
+use super::model_pinning::{self, ModelPin};
+use super::models::{Chat, OneshotOptions, OneshotResult};
+use super::oneshot::{self, RunEnd};
+use super::paste_dedup::{PasteDeduper, PasteIndex};
+use super::prompts::{self, SystemPromptLayers};
+use super::reply;
 use super::repository::ChatRepository;
+use super::scratchpad;
+use super::status::{ActiveChatStatus, ChatPhase, ChatStatusBoard, StatusChange};
+use super::summary::{self, ChatSummary};
+use super::timeline::{self, PhaseStart, TimelineEntry, TimelinePhase, TurnTimeline};
+use super::tool_caps::{self, TurnToolBudget};
+use super::tool_concurrency;
+use super::tool_pairing;
+use super::tool_permissions::{
+    self, PermissionOutcome, RememberScope, RememberedToolPermission, SkipReason,
+};
+use super::user_files;
+use super::variants;
 use crate::error::AppError;
-use crate::events::{AgentEmitter, ToolEmitter};
+use crate::events::{
+    AgentEmitter, McpSamplingRequestEvent, TokenUsage as EventTokenUsage, ToolEmitter,
+};
+use crate::features::activity::{ActivityKind, ActivityService};
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::attachment::file_uri::{self, GENERATED_IMAGE_LABEL, TOOL_IMAGE_LABEL};
+use crate::features::attachment::AttachmentService;
+use crate::features::knowledge::context::{self as knowledge_context, KNOWLEDGE_METADATA_KEY};
+use crate::features::knowledge::{KnowledgeHit, KnowledgeService};
+use crate::features::llm_connection::config::ConnectionConfig;
+use crate::features::llm_connection::model_filter::ModelFilter;
+use crate::features::llm_connection::models::LLMConnection;
 use crate::features::llm_connection::LLMConnectionService;
 use crate::features::message::{Message, MessageEmitter, MessageService};
+use crate::features::outbox::{
+    self, OutboxItem, OutboxParams, OutboxService, OutboxStatus, OUTBOX_CHECK_INTERVAL,
+    QUEUED_METADATA_KEY,
+};
+use crate::features::post_processing::PostProcessingService;
+use crate::features::quick_action::{
+    channel, QuickAction, QuickActionOutputMode, QuickActionRun, QuickActionService,
+    QUICK_ACTION_METADATA_KEY, QUICK_ACTION_REQUEST_TYPE,
+};
+use crate::features::redaction::secrets::{self, SECRETS_MASKED_METADATA_KEY};
+use crate::features::redaction::{RedactionService, RedactionSummary};
 use crate::features::skill::SkillService;
-use crate::features::tool::service::ToolService;
+use crate::features::system::halt::{self, ActivityHalt};
+use crate::features::tool::mcp_resources;
+use crate::features::tool::mcp_sampling::{self, SamplingApprovals, SamplingOutcome};
+use crate::features::tool::result_descriptor::ToolResultDescriptor;
+use crate::features::tool::service::{ToolService, SCRATCHPAD_TOOLS};
+use crate::features::tool_catalog::{ToolCatalogService, TOOL_CATALOG_METADATA_KEY};
 use crate::features::usage::UsageService;
+use crate::features::webhook::{WebhookDispatcher, WebhookEvent};
+use crate::features::workspace::notes::WorkspaceNotesService;
 use crate::features::workspace::settings::{WorkspaceSettings, WorkspaceSettingsService};
 use crate::models::llm_types::{
     AssistantContent, ChatCompletionTool, ChatMessage, ContentPart, FileUrl, ImageUrl,
-    LLMChatRequest, LLMChatResponse, ToolChoice, UserContent,
+    LLMChatRequest, LLMChatResponse, TokenUsage, ToolChoice, UserContent,
+};
+use crate::services::llm::json_repair::{self, RepairCounts};
+use crate::services::llm::output_limit::{self, OutputLimits, OUTPUT_LIMIT_METADATA_KEY};
+use crate::services::llm::reproducibility::{self, Reproducibility, REPRODUCIBILITY_METADATA_KEY};
+use crate::services::llm::stream_recovery::{
+    self, StreamRecovery, MAX_STREAM_CONTINUATIONS, STREAM_INTERRUPTED,
+    STREAM_INTERRUPTED_METADATA_KEY,
 };
 use crate::services::LLMService;
 use base64::{engine::general_purpose, Engine as _};
-use rust_mcp_sdk::{schema::CallToolRequestParams, McpClient};
+use rust_mcp_sdk::{
+    schema::{CallToolRequestParams, CreateMessageRequestParams, CreateMessageResult},
+    McpClient,
+};
 use serde_json;
 use std::collections::HashMap;
 use std::fs;
@@ -24,18 +91,48 @@ use tauri::AppHandle;
 use tauri::Manager;
 use tokio::sync::Mutex;
 
+/// How often a kept-warm model is pinged again while its workspace stays active.
+const KEEP_WARM_INTERVAL: std::time::Duration = std::time::Duration::from_mins(10);
+
+/// State for a chat turn started through `run_prompt_oneshot`
+struct HeadlessRun {
+    auto_deny_permissions: bool,
+    usage: Option<TokenUsage>,
+}
+
 pub struct ChatService {
     repository: Arc<dyn ChatRepository>,
     llm_service: Arc<LLMService>,
     message_service: Arc<MessageService>,
     workspace_settings_service: Arc<WorkspaceSettingsService>,
+    chat_input_settings_service: Arc<ChatInputSettingsService>,
+    workspace_notes_service: Arc<WorkspaceNotesService>,
     llm_connection_service: Arc<LLMConnectionService>,
     tool_service: Arc<ToolService>,
     usage_service: Arc<UsageService>,
     agent_manager: Arc<crate::features::agent::manager::AgentManager>,
     skill_service: Arc<SkillService>,
+    attachment_service: Arc<AttachmentService>,
+    redaction_service: Arc<RedactionService>,
+    activity_service: Arc<ActivityService>,
+    webhook_dispatcher: Arc<WebhookDispatcher>,
+    app_settings_service: Arc<AppSettingsService>,
+    tool_catalog_service: Arc<ToolCatalogService>,
+    outbox_service: Arc<OutboxService>,
+    activity_halt: Arc<ActivityHalt>,
+    knowledge_service: Arc<KnowledgeService>,
     // Cancellation channels for each chat_id
     cancellation_senders: Arc<Mutex<HashMap<String, tokio::sync::broadcast::Sender<()>>>>,
+    // Headless (oneshot) runs in progress, by chat_id
+    headless_runs: Arc<Mutex<HashMap<String, HeadlessRun>>>,
+    // Timeline of the turn in progress, by chat_id
+    turn_timelines: Arc<Mutex<HashMap<String, TurnTimeline>>>,
+    /// What chats with a running turn are doing, for the chat header
+    chat_statuses: Arc<std::sync::Mutex<ChatStatusBoard>>,
+    /// Turns held until the user confirms their estimated cost
+    cost_confirmations: Arc<CostConfirmations>,
+    /// MCP sampling requests held until the user approves them
+    sampling_approvals: Arc<SamplingApprovals>,
 }
 
 impl ChatService {
@@ -44,26 +141,66 @@ impl ChatService {
         llm_service: Arc<LLMService>,
         message_service: Arc<MessageService>,
         workspace_settings_service: Arc<WorkspaceSettingsService>,
+        chat_input_settings_service: Arc<ChatInputSettingsService>,
+        workspace_notes_service: Arc<WorkspaceNotesService>,
         llm_connection_service: Arc<LLMConnectionService>,
         tool_service: Arc<ToolService>,
         usage_service: Arc<UsageService>,
         agent_manager: Arc<crate::features::agent::manager::AgentManager>,
         skill_service: Arc<SkillService>,
+        attachment_service: Arc<AttachmentService>,
+        redaction_service: Arc<RedactionService>,
+        activity_service: Arc<ActivityService>,
+        webhook_dispatcher: Arc<WebhookDispatcher>,
+        app_settings_service: Arc<AppSettingsService>,
+        tool_catalog_service: Arc<ToolCatalogService>,
+        outbox_service: Arc<OutboxService>,
+        activity_halt: Arc<ActivityHalt>,
+        knowledge_service: Arc<KnowledgeService>,
     ) -> Self {
         Self {
             repository,
             llm_service,
             message_service,
             workspace_settings_service,
+            chat_input_settings_service,
+            workspace_notes_service,
             llm_connection_service,
             tool_service,
             usage_service,
             agent_manager,
             skill_service,
+            attachment_service,
+            redaction_service,
+            activity_service,
+            webhook_dispatcher,
+            app_settings_service,
+            tool_catalog_service,
+            outbox_service,
+            activity_halt,
+            knowledge_service,
             cancellation_senders: Arc::new(Mutex::new(HashMap::new())),
+            headless_runs: Arc::new(Mutex::new(HashMap::new())),
+            turn_timelines: Arc::new(Mutex::new(HashMap::new())),
+            chat_statuses: Arc::new(std::sync::Mutex::new(ChatStatusBoard::default())),
+            cost_confirmations: Arc::new(CostConfirmations::new()),
+            sampling_approvals: Arc::new(SamplingApprovals::new()),
         }
     }
 
+    /// Generation parameters of a turn in a workspace: the chat input's
+    /// overrides over the workspace's settings.
+    fn generation_params(&self, workspace_settings: &WorkspaceSettings) -> GenerationParams {
+        let input_settings = self
+            .chat_input_settings_service
+            .get_by_workspace_id(&workspace_settings.workspace_id)
+            .unwrap_or_else(|e| {
+                tracing::warn!(workspace_id = %workspace_settings.workspace_id, error = %e, "Ignoring unreadable chat input settings");
+                None
+            });
+        GenerationParams::resolve(workspace_settings, input_settings.as_ref())
+    }
+
     /// Save a base64 file to disk and return the file path.
     fn save_file_to_disk(&self, app: &AppHandle, file_data: &str) -> Result<String, AppError> {
         // 1. Check if it's likely already a path or url (doesn't start with data:)
@@ -127,14 +264,111 @@ impl ChatService {
                 .map_err(|e| AppError::Generic(format!("Failed to create files directory: {e}")))?;
         }
 
-        let filename = format!("{}.{}", uuid::Uuid::new_v4(), ext);
-        let file_path = files_dir.join(filename);
+        // 5. Write to disk, reusing an identical earlier attachment if there is one
+        let (path, _hash) = self.attachment_service.store(&files_dir, &bytes, ext)?;
+
+        Ok(path)
+    }
+
+    /// Record the content hash of each stored attachment under `fileHashes` (path -> hash).
+    fn attach_file_hashes(
+        &self,
+        meta_obj: &mut serde_json::Value,
+        file_list: &[String],
+    ) -> Result<(), AppError> {
+        let mut hashes = serde_json::Map::new();
+        for path in file_list {
+            if let Some(hash) = self.attachment_service.hash_for_path(path)? {
+                hashes.insert(path.clone(), serde_json::Value::String(hash));
+            }
+        }
+        if !hashes.is_empty() {
+            meta_obj["fileHashes"] = serde_json::Value::Object(hashes);
+        }
+        Ok(())
+    }
+
+    /// Store base64 image data, returning its hash. Failures are logged.
+    fn store_image_data(&self, data: &str, mime_type: &str) -> Option<String> {
+        let stored = general_purpose::STANDARD
+            .decode(data.trim())
+            .map_err(|e| AppError::Validation(format!("Failed to decode image: {e}")))
+            .and_then(|bytes| self.attachment_service.store_image(&bytes, mime_type));
+        stored
+            .map_err(|e| tracing::warn!(mime_type, error = %e, "Failed to store image"))
+            .ok()
+    }
+
+    /// Store the images of a response and append references to them to its
+    /// content. Returns the hashes, and data URLs of images that could not be
+    /// stored.
+    fn store_generated_images(&self, response: &mut LLMChatResponse) -> (Vec<String>, Vec<String>) {
+        let mut hashes = Vec::new();
+        let mut unstored = Vec::new();
+        for image in response.images.iter().flatten() {
+            match self.store_image_data(&image.data, &image.mime_type) {
+                Some(hash) => hashes.push(hash),
+                None => unstored.push(format!("data:{};base64,{}", image.mime_type, image.data)),
+            }
+        }
+        if !hashes.is_empty() {
+            let references = file_uri::image_references(GENERATED_IMAGE_LABEL, &hashes);
+            response.content = file_uri::append_references(&response.content, &references);
+        }
+        (hashes, unstored)
+    }
 
-        // 5. Write to disk
-        fs::write(&file_path, bytes)
-            .map_err(|e| AppError::Generic(format!("Failed to write file: {e}")))?;
+    /// Store the image blocks of a tool result. Returns the hashes of the
+    /// images the result holds or already references.
+    fn store_tool_result_images(&self, result: &serde_json::Value) -> Vec<String> {
+        let blocks = result
+            .as_array()
+            .or_else(|| result.get("content").and_then(|c| c.as_array()));
+        let mut hashes: Vec<String> = blocks
+            .into_iter()
+            .flatten()
+            .filter(|b| b.get("type").and_then(|t| t.as_str()) == Some("image"))
+            .filter_map(|b| {
+                let data = b.get("data").and_then(|d| d.as_str())?;
+                let mime_type = b
+                    .get("mimeType")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or("image/png");
+                self.store_image_data(data, mime_type)
+            })
+            .collect();
+        for hash in file_uri::referenced_hashes(&result.to_string()) {
+            if !hashes.contains(&hash) {
+                hashes.push(hash);
+            }
+        }
+        hashes
+    }
 
-        Ok(file_path.to_string_lossy().to_string())
+    /// Record stored files referenced from a message under `fileHashes`, so
+    /// they are counted as in use and kept by garbage collection.
+    fn reference_stored_files(
+        &self,
+        meta_obj: &mut serde_json::Value,
+        hashes: &[String],
+    ) -> Result<(), AppError> {
+        if hashes.is_empty() {
+            return Ok(());
+        }
+        let added: serde_json::Map<String, serde_json::Value> = hashes
+            .iter()
+            .map(|hash| (file_uri::file_uri(hash), serde_json::json!(hash)))
+            .collect();
+        self.attachment_service.retain(Some(
+            &serde_json::json!({ "fileHashes": added }).to_string(),
+        ))?;
+        if !meta_obj["fileHashes"].is_object() {
+            meta_obj["fileHashes"] = serde_json::json!({});
+        }
+        if let Some(existing) = meta_obj["fileHashes"].as_object_mut() {
+            existing.extend(added);
+        }
+        Ok(())
     }
 
     /// Process a list of files: save base64 strings to disk and return paths.
@@ -199,6 +433,33 @@ impl ChatService {
         Some(description)
     }
 
+    /// The content part of an attached file the chat may read; a file that
+    /// cannot be loaded is sent as its path.
+    fn file_part(&self, file_path: &str) -> ContentPart {
+        let (file_content, actual_mime) = self.load_file_content(file_path).unwrap_or_else(|_| {
+            (
+                file_path.to_string(),
+                "application/octet-stream".to_string(),
+            )
+        });
+        if actual_mime.starts_with("image/") {
+            ContentPart::ImageUrl {
+                image_url: ImageUrl { url: file_content },
+            }
+        } else {
+            ContentPart::FileUrl {
+                file_url: FileUrl {
+                    url: file_content,
+                    mime_type: actual_mime,
+                    content_hash: self
+                        .attachment_service
+                        .hash_for_path(file_path)
+                        .unwrap_or(None),
+                },
+            }
+        }
+    }
+
     /// Load a file from a path and convert to base64 data URL with mime type.
     fn load_file_content(&self, path_or_data: &str) -> Result<(String, String), AppError> {
         if path_or_data.starts_with("data:") {
@@ -212,6 +473,12 @@ impl ChatService {
                 .to_string();
             Ok((path_or_data.to_string(), mime_type))
         } else {
+            if !self.attachment_service.is_file_access_allowed(path_or_data) {
+                return Err(AppError::Validation(format!(
+                    "File access not allowed: {path_or_data}"
+                )));
+            }
+
             let path = PathBuf::from(path_or_data);
             if path.exists() {
                 let bytes = fs::read(&path)
@@ -266,16 +533,478 @@ impl ChatService {
         Ok(())
     }
 
+    /// Stop the running turn of every chat, its stream, tool calls and
+    /// waits, and end any keep-warm loop. Returns how many chats were
+    /// stopped and how many of them were running tools.
+    pub async fn halt_all(&self) -> (usize, usize) {
+        let tool_executions = self
+            .chat_statuses
+            .lock()
+            .map_or(0, |board| board.count_in(ChatPhase::ToolExec));
+        let chats = halt::cancel_all(&*self.cancellation_senders.lock().await);
+        self.llm_service.begin_keep_warm();
+        (chats, tool_executions)
+    }
+
     /// Get or create a cancellation receiver for a chat
     async fn get_cancellation_receiver(
         &self,
         chat_id: &str,
     ) -> tokio::sync::broadcast::Receiver<()> {
+        self.cancellation_sender(chat_id).await.subscribe()
+    }
+
+    /// Get or create the cancellation channel of a chat
+    async fn cancellation_sender(&self, chat_id: &str) -> tokio::sync::broadcast::Sender<()> {
         let mut senders = self.cancellation_senders.lock().await;
-        let sender = senders
+        senders
             .entry(chat_id.to_string())
-            .or_insert_with(|| tokio::sync::broadcast::channel(1).0);
-        sender.subscribe()
+            .or_insert_with(|| tokio::sync::broadcast::channel(1).0)
+            .clone()
+    }
+
+    /// Whether the chat's turn is a headless run
+    async fn is_headless(&self, chat_id: &str) -> bool {
+        self.headless_runs.lock().await.contains_key(chat_id)
+    }
+
+    /// Whether the chat's turn is a headless run that denies tools requiring
+    /// permission instead of asking
+    async fn headless_auto_deny(&self, chat_id: &str) -> bool {
+        self.headless_runs
+            .lock()
+            .await
+            .get(chat_id)
+            .is_some_and(|run| run.auto_deny_permissions)
+    }
+
+    /// Whether tool-call arguments of this chat are masked for display and storage
+    fn masks_secrets(&self, chat_id: &str) -> bool {
+        match self.repository.get_by_id(chat_id) {
+            Ok(Some(chat)) => self
+                .workspace_settings_service
+                .secret_masking_enabled(&chat.workspace_id),
+            _ => true,
+        }
+    }
+
+    /// Add an LLM response's token usage to the running total of a headless run
+    async fn accumulate_headless_usage(&self, chat_id: &str, usage: Option<&TokenUsage>) {
+        let Some(usage) = usage else {
+            return;
+        };
+        let mut runs = self.headless_runs.lock().await;
+        if let Some(run) = runs.get_mut(chat_id) {
+            let sum = |a: Option<u32>, b: Option<u32>| {
+                if a.is_none() && b.is_none() {
+                    None
+                } else {
+                    Some(a.unwrap_or(0) + b.unwrap_or(0))
+                }
+            };
+            let total = run.usage.take().unwrap_or(TokenUsage {
+                prompt_tokens: None,
+                completion_tokens: None,
+                total_tokens: None,
+                cached_tokens: None,
+                cache_write_tokens: None,
+            });
+            run.usage = Some(TokenUsage {
+                prompt_tokens: sum(total.prompt_tokens, usage.prompt_tokens),
+                completion_tokens: sum(total.completion_tokens, usage.completion_tokens),
+                total_tokens: sum(total.total_tokens, usage.total_tokens),
+                cached_tokens: sum(total.cached_tokens, usage.cached_tokens),
+                cache_write_tokens: sum(total.cache_write_tokens, usage.cache_write_tokens),
+            });
+        }
+    }
+
+    /// Send a prompt and wait for the whole turn, including the agent loop, to
+    /// finish. Streaming is disabled for the run; permission prompts, iteration
+    /// limits and cancellation behave as for interactive chats.
+    pub async fn run_prompt_oneshot(
+        &self,
+        workspace_id: String,
+        prompt: String,
+        options: OneshotOptions,
+        app: AppHandle,
+    ) -> Result<OneshotResult, AppError> {
+        self.activity_halt.ensure_running()?;
+        let ephemeral = options.ephemeral.unwrap_or(false);
+        let chat_id = options.chat_id.clone().unwrap_or_else(|| {
+            if ephemeral {
+                uuid::Uuid::new_v4().to_string()
+            } else {
+                format!("scratch_{workspace_id}")
+            }
+        });
+
+        let created = match self.repository.get_by_id(&chat_id)? {
+            Some(chat) if chat.workspace_id != workspace_id => {
+                return Err(AppError::Validation(format!(
+                    "Chat {chat_id} does not belong to workspace {workspace_id}"
+                )));
+            }
+            Some(_) => false,
+            None => {
+                let title = if ephemeral { "Oneshot" } else { "Scratch" };
+                self.create(
+                    chat_id.clone(),
+                    workspace_id.clone(),
+                    title.to_string(),
+                    None,
+                    None,
+                )?;
+                true
+            }
+        };
+
+        {
+            let mut runs = self.headless_runs.lock().await;
+            if runs.contains_key(&chat_id) {
+                return Err(AppError::Validation(format!(
+                    "A oneshot run is already in progress for chat {chat_id}"
+                )));
+            }
+            runs.insert(
+                chat_id.clone(),
+                HeadlessRun {
+                    auto_deny_permissions: options.auto_deny_permissions.unwrap_or(false),
+                    usage: None,
+                },
+            );
+        }
+
+        let started_at = chrono::Utc::now().timestamp_millis();
+        let send = self.send_message(
+            chat_id.clone(),
+            prompt,
+            None,
+            None,
+            options.selected_model,
+            options.reasoning_effort,
+            options.llm_connection_id,
+            app.clone(),
+        );
+
+        let timeout = options.timeout_ms.map(tokio::time::Duration::from_millis);
+        let cancel = async {
+            if let Some(sender) = self.cancellation_senders.lock().await.get(&chat_id) {
+                let _ = sender.send(());
+            }
+        };
+        let outcome = match oneshot::run_with_timeout(send, timeout, cancel).await {
+            RunEnd::Finished(outcome) => outcome,
+            RunEnd::TimedOut(wound_down) => {
+                if let Err(e) = wound_down {
+                    tracing::warn!(chat_id = %chat_id, error = %e, "Timed out oneshot run ended with an error");
+                }
+                let timeout_ms = options.timeout_ms.unwrap_or_default();
+                let error =
+                    AppError::Generic(format!("Oneshot run timed out after {timeout_ms}ms"));
+                self.activity_service.record(
+                    &app,
+                    ActivityService::entry(
+                        &workspace_id,
+                        &chat_id,
+                        ActivityKind::Error,
+                        &error.to_string(),
+                        false,
+                    ),
+                );
+                self.webhook_dispatcher.dispatch(WebhookEvent::new(
+                    ActivityKind::Error,
+                    &workspace_id,
+                    &chat_id,
+                    &error.to_string(),
+                ));
+                Err(error)
+            }
+        };
+
+        let usage = self
+            .headless_runs
+            .lock()
+            .await
+            .remove(&chat_id)
+            .and_then(|run| run.usage);
+
+        let result = outcome.and_then(|(assistant_message_id, content)| {
+            let tool_calls_executed = oneshot::executed_tool_calls(
+                &self.message_service.get_by_chat_id(&chat_id)?,
+                started_at,
+            );
+
+            Ok(OneshotResult {
+                chat_id: chat_id.clone(),
+                assistant_message_id,
+                content,
+                usage,
+                tool_calls_executed,
+            })
+        });
+
+        if ephemeral && created {
+            if let Err(e) = self.delete_with_messages(&chat_id) {
+                tracing::warn!(chat_id = %chat_id, error = %e, "Failed to clean up oneshot chat");
+            }
+        }
+
+        result
+    }
+
+    /// Run a quick action on `selection`. Previews stream on the quick action
+    /// channel only; `append_to_chat` adds the prompt and answer to `chat_id`,
+    /// or to the workspace scratch chat that `run_prompt_oneshot` also uses.
+    pub async fn run_quick_action(
+        &self,
+        action: QuickAction,
+        selection: &str,
+        chat_id: Option<String>,
+        seed: Option<i64>,
+        app: AppHandle,
+    ) -> Result<QuickActionRun, AppError> {
+        self.activity_halt.ensure_running()?;
+        let prompt = QuickActionService::render(&action.prompt_template, selection)?;
+        let settings = self
+            .workspace_settings_service
+            .get_by_workspace_id(&action.workspace_id)?
+            .ok_or_else(|| {
+                AppError::NotFound(format!(
+                    "Workspace settings not found: {}",
+                    action.workspace_id
+                ))
+            })?;
+        let connection_id = settings.llm_connection_id.clone().ok_or_else(|| {
+            AppError::Validation("No LLM connection configured for workspace".to_string())
+        })?;
+        let connection = self
+            .llm_connection_service
+            .get_by_id(&connection_id)?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("LLM connection not found: {connection_id}"))
+            })?;
+        let model = action
+            .preferred_model
+            .clone()
+            .or_else(|| settings.default_model.clone())
+            .or_else(|| connection.default_model.clone())
+            .filter(|m| !m.is_empty())
+            .ok_or_else(|| {
+                AppError::Validation("No model available for quick action".to_string())
+            })?;
+
+        let run_id = uuid::Uuid::new_v4().to_string();
+        let target = match action.output_mode {
+            QuickActionOutputMode::ReplacePreview => None,
+            QuickActionOutputMode::AppendToChat => {
+                Some(self.start_quick_action_exchange(&action, chat_id, &prompt, &app)?)
+            }
+        };
+        let usage_ids = target
+            .clone()
+            .unwrap_or_else(|| (String::new(), run_id.clone()));
+
+        let mut messages = vec![ChatMessage::User {
+            content: UserContent::Text(prompt),
+        }];
+        let redaction_scope = target.as_ref().map_or_else(
+            || channel::channel_id(&run_id),
+            |(chat_id, _)| chat_id.clone(),
+        );
+        let redaction =
+            self.redact_request(&redaction_scope, &settings, &connection, &mut messages)?;
+        let request = LLMChatRequest {
+            model: model.clone(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: ModelFilter::for_connection(&connection),
+            connection_config: Some(ConnectionConfig::for_connection(&connection)),
+            seed,
+            output_limits: Some(OutputLimits::for_workspace(settings.max_output_bytes)),
+        };
+        let reproducibility = Reproducibility::of_request(&connection.provider, &request);
+
+        let started = std::time::Instant::now();
+        let result = channel::run(
+            &run_id,
+            &action,
+            target.clone(),
+            |event| {
+                if let Err(e) = crate::events::emit_event(&app, event) {
+                    tracing::error!(error = ?e, "Failed to emit quick action event");
+                }
+            },
+            |chat_id, message_id| async {
+                let mut response = self
+                    .llm_service
+                    .chat(
+                        &connection.base_url,
+                        Some(&connection.api_key),
+                        request,
+                        chat_id,
+                        message_id,
+                        app.clone(),
+                        None,
+                        &connection.provider,
+                    )
+                    .await?;
+                self.unredact_response(&redaction_scope, redaction.as_ref(), &mut response)?;
+                Ok(response)
+            },
+        )
+        .await;
+        let latency = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+
+        if let Err(e) = self.usage_service.record_usage_with_type(
+            action.workspace_id.clone(),
+            usage_ids.0,
+            usage_ids.1,
+            connection.provider.clone(),
+            model,
+            result.as_ref().ok().and_then(|r| r.usage.clone()),
+            latency,
+            true,
+            if result.is_ok() { "success" } else { "error" }.to_string(),
+            QUICK_ACTION_REQUEST_TYPE.to_string(),
+        ) {
+            tracing::error!(error = ?e, "Failed to record quick action usage");
+        }
+
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                // A failed run leaves no half exchange behind in the chat
+                if let Some((_, message_id)) = &target {
+                    if let Err(e) = self.remove_quick_action_exchange(message_id) {
+                        tracing::warn!(error = %e, "Failed to remove failed quick action exchange");
+                    }
+                }
+                return Err(e);
+            }
+        };
+        if let Some((_, message_id)) = &target {
+            self.message_service.update(
+                message_id.clone(),
+                response.content.clone(),
+                None,
+                None,
+            )?;
+            if let Some(record) = reproducibility.metadata(&response) {
+                self.merge_message_metadata(message_id, |meta| {
+                    meta[REPRODUCIBILITY_METADATA_KEY] = record;
+                })?;
+            }
+        }
+
+        Ok(QuickActionRun {
+            action_run_id: run_id,
+            output_mode: action.output_mode,
+            chat_id: target.as_ref().map(|(chat_id, _)| chat_id.clone()),
+            message_id: target.map(|(_, message_id)| message_id),
+            content: response.content,
+            usage: response.usage,
+        })
+    }
+
+    /// Create the user message and empty answer of an `append_to_chat` run,
+    /// returning the chat and answer ids.
+    fn start_quick_action_exchange(
+        &self,
+        action: &QuickAction,
+        chat_id: Option<String>,
+        prompt: &str,
+        app: &AppHandle,
+    ) -> Result<(String, String), AppError> {
+        let chat_id = chat_id.unwrap_or_else(|| format!("scratch_{}", action.workspace_id));
+        match self.repository.get_by_id(&chat_id)? {
+            Some(chat) if chat.workspace_id != action.workspace_id => {
+                return Err(AppError::Validation(format!(
+                    "Chat {chat_id} does not belong to workspace {}",
+                    action.workspace_id
+                )));
+            }
+            Some(_) => {}
+            None => {
+                self.create(
+                    chat_id.clone(),
+                    action.workspace_id.clone(),
+                    "Scratch".to_string(),
+                    None,
+                    None,
+                )?;
+            }
+        }
+
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let user_message_id = uuid::Uuid::new_v4().to_string();
+        let assistant_message_id = uuid::Uuid::new_v4().to_string();
+        let metadata = serde_json::json!({ QUICK_ACTION_METADATA_KEY: action.id }).to_string();
+        self.message_service.create(
+            user_message_id.clone(),
+            chat_id.clone(),
+            "user".to_string(),
+            prompt.to_string(),
+            Some(timestamp),
+            None,
+            None,
+            Some(metadata.clone()),
+        )?;
+        self.message_service.create(
+            assistant_message_id.clone(),
+            chat_id.clone(),
+            "assistant".to_string(),
+            String::new(),
+            Some(timestamp + 1),
+            None,
+            None,
+            Some(metadata),
+        )?;
+        MessageEmitter::new(app.clone()).emit_message_started(
+            chat_id.clone(),
+            user_message_id,
+            assistant_message_id.clone(),
+        )?;
+        Ok((chat_id, assistant_message_id))
+    }
+
+    /// Delete the answer of a failed `append_to_chat` run and the prompt
+    /// before it.
+    fn remove_quick_action_exchange(&self, assistant_message_id: &str) -> Result<(), AppError> {
+        let Some(answer) = self.message_service.get_by_id(assistant_message_id)? else {
+            return Ok(());
+        };
+        let prompt = self
+            .message_service
+            .get_by_chat_id(&answer.chat_id)?
+            .into_iter()
+            .filter(|m| m.role == "user" && m.timestamp < answer.timestamp)
+            .max_by_key(|m| m.timestamp);
+        self.message_service.delete(answer.id)?;
+        if let Some(prompt) = prompt {
+            self.message_service.delete(prompt.id)?;
+        }
+        Ok(())
+    }
+
+    /// Delete a chat and its messages, releasing any attachments they reference
+    fn delete_with_messages(&self, chat_id: &str) -> Result<(), AppError> {
+        for message in self.message_service.get_by_chat_id(chat_id)? {
+            self.message_service.delete(message.id)?;
+        }
+        self.repository.delete(chat_id)
     }
 
     pub fn create(
@@ -300,12 +1029,33 @@ impl ChatService {
             updated_at: now,
             agent_id,
             parent_id,
+            last_read_at: None,
+            unread_count: 0,
+            custom_instructions: None,
         };
 
         self.repository.create(&chat)?;
         Ok(chat)
     }
 
+    /// Store a chat brought in from elsewhere as is, keeping its timestamps,
+    /// together with its `metadata` JSON.
+    pub fn create_imported(
+        &self,
+        chat: &Chat,
+        metadata: &serde_json::Value,
+    ) -> Result<(), AppError> {
+        self.repository.create(chat)?;
+        self.repository
+            .set_metadata(&chat.id, Some(&metadata.to_string()))
+    }
+
+    /// The user has seen the chat up to now.
+    pub fn mark_read(&self, chat_id: &str) -> Result<(), AppError> {
+        self.repository
+            .mark_read(chat_id, chrono::Utc::now().timestamp_millis())
+    }
+
     pub fn get_or_create_specialist_session(
         &self,
         parent_chat_id: String,
@@ -366,7 +1116,8 @@ impl ChatService {
     }
 
     pub fn delete(&self, id: String) -> Result<(), AppError> {
-        self.repository.delete(&id)
+        self.repository.delete(&id)?;
+        self.redaction_service.forget(&id)
     }
 
     pub fn delete_by_workspace_id(&self, workspace_id: String) -> Result<(), AppError> {
@@ -410,94 +1161,875 @@ impl ChatService {
         llm_connection_id_override: Option<String>,
         app: AppHandle,
     ) -> Result<(String, String), AppError> {
-        // Track chat message operation
-        crate::lib::sentry_helpers::add_breadcrumb(
-            "chat",
-            format!("Sending message to chat {chat_id}"),
-            sentry::Level::Info,
-        );
+        self.send_turn(
+            chat_id,
+            content,
+            files,
+            metadata,
+            selected_model,
+            reasoning_effort,
+            llm_connection_id_override,
+            None,
+            None,
+            app,
+        )
+        .await
+    }
 
-        // Process files: Save incoming base64 files to disk and get paths with mime types
-        let processed_files = self.process_incoming_files(&app, files.clone())?;
+    /// Run a turn with its timeline and status; `queued` resends a message
+    /// of the chat's outbox, `regenerating` answers a stored user message
+    /// again.
+    #[allow(clippy::too_many_arguments)]
+    async fn send_turn(
+        &self,
+        chat_id: String,
+        content: String,
+        files: Option<Vec<String>>,
+        metadata: Option<String>,
+        selected_model: Option<String>,
+        reasoning_effort: Option<String>,
+        llm_connection_id_override: Option<String>,
+        queued: Option<OutboxItem>,
+        regenerating: Option<Message>,
+        app: AppHandle,
+    ) -> Result<(String, String), AppError> {
+        self.activity_halt.ensure_running()?;
+        self.turn_timelines
+            .lock()
+            .await
+            .insert(chat_id.clone(), TurnTimeline::default());
+        let result = self
+            .run_turn(
+                chat_id.clone(),
+                content,
+                files,
+                metadata,
+                selected_model,
+                reasoning_effort,
+                llm_connection_id_override,
+                queued,
+                regenerating,
+                app.clone(),
+            )
+            .await;
+        let timeline = self.turn_timelines.lock().await.remove(&chat_id);
+        if let (Ok((message_id, _)), Some(timeline)) = (&result, timeline) {
+            if let Err(e) = self.merge_message_metadata(message_id, |meta| timeline.store_in(meta))
+            {
+                tracing::warn!(chat_id = %chat_id, error = %e, "Failed to store turn timeline");
+            }
+        }
+        self.update_status(&app, &chat_id, StatusChange::Finished);
+        // A queued message has not had its turn yet
+        if !matches!(result, Err(AppError::Queued(_))) {
+            self.record_turn_activity(&app, &chat_id, &result).await;
+        }
+        result
+    }
 
-        // 1. Get chat to find workspace_id
-        let chat = self
-            .repository
-            .get_by_id(&chat_id)?
-            .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
-
-        let workspace_id = chat.workspace_id;
-
-        // Track workspace context
-        crate::lib::sentry_helpers::track_workspace_operation(&workspace_id, "send_message");
-
-        // 2. Get workspace settings
+    /// Send one message to two or three `(connection_id, model)` targets at
+    /// once and keep every answer. The answers stream to their own messages
+    /// without tools; see `comparison`.
+    pub async fn send_message_compare(
+        &self,
+        chat_id: String,
+        content: String,
+        files: Option<Vec<String>>,
+        targets: Vec<(String, String)>,
+        seed: Option<i64>,
+        app: AppHandle,
+    ) -> Result<ComparisonResult, AppError> {
+        self.activity_halt.ensure_running()?;
+        comparison::validate_targets(&targets)?;
+        let chat = self
+            .repository
+            .get_by_id(&chat_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
         let workspace_settings = self
             .workspace_settings_service
-            .get_by_workspace_id(&workspace_id)?
+            .get_by_workspace_id(&chat.workspace_id)?
             .ok_or_else(|| AppError::Validation("Workspace settings not found".to_string()))?;
+        let connections = targets
+            .iter()
+            .map(|(connection_id, _)| {
+                self.llm_connection_service
+                    .get_by_id(connection_id)?
+                    .ok_or_else(|| {
+                        AppError::NotFound(format!("LLM connection not found: {connection_id}"))
+                    })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
 
-        let llm_connection_id = llm_connection_id_override
-            .or(workspace_settings.llm_connection_id.clone())
-            .ok_or_else(|| {
-                AppError::Validation("LLM connection not configured for workspace".to_string())
-            })?;
+        let (processed_files, user_metadata) =
+            self.prepare_user_files(&app, &chat_id, files, None)?;
+        // Every leg gets the same seed, so the models differ and not the draw
+        let user_metadata = reproducibility::with_seed(user_metadata, seed);
+        let existing_messages = self.message_service.get_by_chat_id(&chat_id)?;
+        let user_timestamp = chrono::Utc::now().timestamp_millis();
+        let user_message_id = uuid::Uuid::new_v4().to_string();
+        self.message_service.create(
+            user_message_id.clone(),
+            chat_id.clone(),
+            "user".to_string(),
+            content.clone(),
+            Some(user_timestamp),
+            None,
+            None,
+            user_metadata.clone(),
+        )?;
 
-        // 3. Get LLM connection
-        let llm_connection = self
-            .llm_connection_service
-            .get_by_id(&llm_connection_id)?
-            .ok_or_else(|| {
-                AppError::NotFound(format!("LLM connection not found: {llm_connection_id}"))
+        let group_id = uuid::Uuid::new_v4().to_string();
+        let message_emitter = MessageEmitter::new(app.clone());
+        let mut legs = Vec::with_capacity(targets.len());
+        for (index, (connection_id, model)) in targets.into_iter().enumerate() {
+            let leg = ComparisonLeg {
+                group_id: group_id.clone(),
+                leg: index,
+                connection_id,
+                model,
+                status: None,
+                error: None,
+            };
+            let message_id = uuid::Uuid::new_v4().to_string();
+            self.message_service.create(
+                message_id.clone(),
+                chat_id.clone(),
+                "assistant".to_string(),
+                String::new(),
+                Some(user_timestamp + 1 + i64::try_from(index).unwrap_or(0)),
+                None,
+                None,
+                Some(serde_json::json!({ COMPARISON_METADATA_KEY: leg }).to_string()),
+            )?;
+            message_emitter.emit_message_started(
+                chat_id.clone(),
+                user_message_id.clone(),
+                message_id.clone(),
+            )?;
+            legs.push((message_id, leg));
+        }
+
+        let generation = self.generation_params(&workspace_settings);
+        let stream_enabled = workspace_settings.stream_enabled.is_none_or(|v| v == 1);
+        let workspace_notes = self.context_notes(&chat.workspace_id, chat.agent_id.as_deref());
+        let chat_instructions = self.chat_instructions(&chat);
+        let chat_summary = self.repository.get_summary(&chat_id)?;
+        let knowledge = self.turn_knowledge(&chat.workspace_id, &content).await;
+        let mut requests = Vec::with_capacity(legs.len());
+        let mut leg_reproducibility = Vec::with_capacity(legs.len());
+        for ((message_id, leg), connection) in legs.iter().zip(&connections) {
+            let budget = ContextBudget::for_request(
+                &leg.model,
+                Some(connection),
+                generation.max_tokens,
+                None,
+            );
+            let (mut api_messages, context_trim) = self.prepare_messages(
+                &existing_messages,
+                &workspace_settings,
+                &content,
+                processed_files.as_deref(),
+                None,
+                None,
+                workspace_notes.as_deref(),
+                chat_instructions.as_deref(),
+                Some(connection),
+                budget.as_ref(),
+                chat_summary.as_ref(),
+                &knowledge,
+            )?;
+            if let Some(trim) = &context_trim {
+                message_emitter.emit_context_trimmed(chat_id.clone(), message_id.clone(), trim)?;
+            }
+            self.apply_scratchpad(&chat_id, &mut api_messages)?;
+            let redaction =
+                self.redact_request(&chat_id, &workspace_settings, connection, &mut api_messages)?;
+            let request = LLMChatRequest {
+                model: leg.model.clone(),
+                messages: api_messages,
+                temperature: generation.temperature,
+                max_tokens: generation.max_tokens,
+                top_p: generation.top_p,
+                frequency_penalty: generation.frequency_penalty,
+                presence_penalty: generation.presence_penalty,
+                stream: stream_enabled,
+                tools: None,
+                tool_choice: None,
+                reasoning_effort: None,
+                stream_options: Some(serde_json::json!({ "include_usage": true })),
+                response_modalities: None,
+                image_config: None,
+                context_cache_scope: None,
+                model_filter: ModelFilter::for_connection(connection),
+                connection_config: Some(ConnectionConfig::for_connection(connection)),
+                seed,
+                output_limits: Some(OutputLimits::for_workspace(
+                    workspace_settings.max_output_bytes,
+                )),
+            };
+            let mut confirmation_rx = self.get_cancellation_receiver(&chat_id).await;
+            if let Err(e) = self
+                .confirm_turn_cost(
+                    &app,
+                    &chat_id,
+                    message_id,
+                    &workspace_settings,
+                    &request,
+                    &mut confirmation_rx,
+                )
+                .await
+            {
+                // The whole comparison is dropped, not just this answer
+                for (other_id, _) in legs.iter().filter(|(id, _)| id != message_id) {
+                    self.message_service.delete(other_id.clone())?;
+                    message_emitter.emit_message_cancelled(
+                        chat_id.clone(),
+                        other_id.clone(),
+                        String::new(),
+                    )?;
+                }
+                return Err(e);
+            }
+            leg_reproducibility.push(Reproducibility::of_request(&connection.provider, &request));
+            requests.push((request, redaction, connection));
+        }
+
+        self.update_status(
+            &app,
+            &chat_id,
+            StatusChange::Streaming {
+                model: legs
+                    .iter()
+                    .map(|(_, leg)| leg.model.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" | "),
+                provider: connections[0].provider.clone(),
+                iteration: 1,
+            },
+        );
+        let cancellation = self.cancellation_sender(&chat_id).await;
+        let runs = legs
+            .iter()
+            .map(|(id, _)| id.clone())
+            .zip(requests)
+            .collect();
+        let outcomes = comparison::run_legs(
+            runs,
+            &cancellation,
+            |message_id, (request, redaction, connection), rx| {
+                let chat_id = chat_id.clone();
+                let workspace_id = chat.workspace_id.clone();
+                let app = app.clone();
+                async move {
+                    let started = std::time::Instant::now();
+                    let model = request.model.clone();
+                    let result = self
+                        .llm_service
+                        .chat(
+                            &connection.base_url,
+                            Some(&connection.api_key),
+                            request,
+                            chat_id.clone(),
+                            message_id.clone(),
+                            app,
+                            Some(rx),
+                            &connection.provider,
+                        )
+                        .await
+                        .and_then(|mut response| {
+                            self.unredact_response(&chat_id, redaction.as_ref(), &mut response)?;
+                            Ok(response)
+                        });
+                    let latency = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+                    if let Err(e) = self.usage_service.record_usage_with_type(
+                        workspace_id,
+                        chat_id,
+                        message_id,
+                        connection.provider.clone(),
+                        model,
+                        result.as_ref().ok().and_then(|r| r.usage.clone()),
+                        latency,
+                        stream_enabled,
+                        comparison::leg_status(&result).as_str().to_string(),
+                        comparison::COMPARISON_REQUEST_TYPE.to_string(),
+                    ) {
+                        tracing::error!(error = ?e, "Failed to record comparison usage");
+                    }
+                    result
+                }
+            },
+        )
+        .await;
+        self.update_status(&app, &chat_id, StatusChange::Finished);
+        let last_completed = comparison::last_completed(&outcomes).map(|outcome| outcome.leg);
+
+        let mut answers = Vec::with_capacity(outcomes.len());
+        for ((mut outcome, (message_id, mut leg)), reproducibility) in
+            outcomes.into_iter().zip(legs).zip(leg_reproducibility)
+        {
+            let status = outcome.status();
+            leg.status = Some(status);
+            let mut metadata = serde_json::json!({});
+            let content = match &mut outcome.result {
+                Ok(response) => {
+                    if let Some(raw) = Self::post_process(&workspace_settings, response) {
+                        PostProcessingService::record_raw_content(&mut metadata, &raw);
+                    }
+                    self.message_service.update(
+                        message_id.clone(),
+                        response.content.clone(),
+                        response.reasoning.clone(),
+                        None,
+                    )?;
+                    if let Some(usage) = &response.usage {
+                        metadata["tokenUsage"] = serde_json::json!(usage);
+                    }
+                    if let Some(thinking) = Self::thinking_metadata(response.reasoning.as_deref()) {
+                        metadata["thinking"] = thinking;
+                    }
+                    if status == LegStatus::Cancelled {
+                        metadata["cancelled"] = serde_json::json!(true);
+                    }
+                    if let Some(record) = reproducibility.metadata(response) {
+                        metadata[REPRODUCIBILITY_METADATA_KEY] = record;
+                    }
+                    // Comparison legs keep a broken-off answer as it is
+                    if let Some(interruption) = stream_recovery::interruption_metadata(response, 0)
+                    {
+                        metadata[STREAM_INTERRUPTED_METADATA_KEY] = interruption;
+                    }
+                    if let Some(limit) =
+                        output_limit::limit_metadata(response.finish_reason.as_deref())
+                    {
+                        metadata[OUTPUT_LIMIT_METADATA_KEY] = limit;
+                    }
+                    response.content.clone()
+                }
+                Err(e) => {
+                    tracing::warn!(chat_id = %chat_id, model = %leg.model, error = %e, "Comparison leg failed");
+                    leg.error = Some(e.to_string());
+                    if let Err(e) =
+                        message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &e)
+                    {
+                        tracing::error!(error = ?e, "Failed to emit message error");
+                    }
+                    String::new()
+                }
+            };
+            if let Some(instructions) =
+                Self::chat_instructions_metadata(chat_instructions.as_deref())
+            {
+                metadata["chatInstructions"] = instructions;
+            }
+            if let Some(citations) = knowledge_context::citations_metadata(&knowledge) {
+                metadata[KNOWLEDGE_METADATA_KEY] = citations;
+            }
+            metadata[COMPARISON_METADATA_KEY] = serde_json::json!(leg);
+            self.merge_message_metadata(&message_id, |meta| {
+                if let (Some(meta), Some(update)) = (meta.as_object_mut(), metadata.as_object()) {
+                    meta.extend(update.clone());
+                }
             })?;
+            if let Err(e) =
+                message_emitter.emit_message_metadata_updated(chat_id.clone(), message_id.clone())
+            {
+                tracing::error!(error = ?e, "Failed to emit metadata-updated event");
+            }
+            answers.push(ComparisonAnswer {
+                message_id,
+                content,
+                leg,
+            });
+        }
 
-        // 4. Determine model to use
-        let model = selected_model
-            .clone()
-            .or(workspace_settings.default_model.clone())
-            .or(llm_connection.default_model.clone())
-            .ok_or_else(|| AppError::Validation("No model selected".to_string()))?;
+        // The answer that completed last is the chat's last message
+        if let Some(answer) = last_completed.and_then(|leg| answers.get(leg)) {
+            let last_message = Chat::last_message_preview(&answer.content);
+            self.repository
+                .update(&chat_id, None, Some(&last_message))?;
+        }
 
-        // 5. Get conversation history
-        let existing_messages = self.message_service.get_by_chat_id(&chat_id)?;
+        Ok(ComparisonResult {
+            group_id,
+            user_message_id,
+            answers,
+        })
+    }
 
-        // 6. Create user message
-        let user_timestamp = std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap()
-            .as_millis() as i64;
-        let user_message_id = uuid::Uuid::new_v4().to_string();
+    /// The status of a chat, idle when no turn is running.
+    pub fn get_active_chat_status(&self, chat_id: &str) -> Result<ActiveChatStatus, AppError> {
+        self.chat_statuses
+            .lock()
+            .map(|board| board.get(chat_id))
+            .map_err(|e| AppError::Generic(format!("Failed to read chat status: {e}")))
+    }
+
+    /// How long no turn has been running in any chat, `None` while one is.
+    pub fn idle_for(&self) -> Result<Option<std::time::Duration>, AppError> {
+        self.chat_statuses
+            .lock()
+            .map(|board| board.idle_for())
+            .map_err(|e| AppError::Generic(format!("Failed to read chat status: {e}")))
+    }
+
+    /// Apply a step of the turn to the chat's status and tell the frontend
+    /// if that changed it.
+    fn update_status(&self, app: &AppHandle, chat_id: &str, change: StatusChange) {
+        let changed = match self.chat_statuses.lock() {
+            Ok(mut board) => board.apply(chat_id, change),
+            Err(e) => {
+                tracing::warn!(chat_id = %chat_id, error = %e, "Failed to update chat status");
+                None
+            }
+        };
+        if let Some(status) = changed {
+            if let Err(e) =
+                super::ChatEmitter::new(app.clone()).emit_active_chat_status_changed(status)
+            {
+                tracing::error!(chat_id = %chat_id, error = ?e, "Failed to emit chat status");
+            }
+        }
+    }
+
+    /// Add an entry to the timeline of the chat's running turn.
+    async fn record_timeline(&self, chat_id: &str, entry: TimelineEntry) {
+        if let Some(timeline) = self.turn_timelines.lock().await.get_mut(chat_id) {
+            timeline.record(entry);
+        }
+    }
+
+    /// Update some keys of a message's metadata, keeping the others.
+    /// The `thinking` metadata of an answer with a thinking block. Providers
+    /// count thinking tokens as output tokens, so its size is estimated at
+    /// four characters per token.
+    pub fn thinking_metadata(reasoning: Option<&str>) -> Option<serde_json::Value> {
+        let reasoning = reasoning.filter(|r| !r.trim().is_empty())?;
+        let tokens = reasoning.chars().count().div_ceil(4);
+        Some(serde_json::json!({ "present": true, "estimatedTokens": tokens }))
+    }
+
+    fn merge_message_metadata(
+        &self,
+        message_id: &str,
+        update: impl FnOnce(&mut serde_json::Value),
+    ) -> Result<(), AppError> {
+        let mut metadata = self
+            .message_service
+            .get_by_id(message_id)?
+            .and_then(|m| m.metadata)
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok())
+            .filter(serde_json::Value::is_object)
+            .unwrap_or_else(|| serde_json::json!({}));
+        update(&mut metadata);
+        self.message_service
+            .update_metadata(message_id.to_string(), Some(metadata.to_string()))
+    }
+
+    fn clear_queued_flag(&self, message_id: &str) -> Result<(), AppError> {
+        self.merge_message_metadata(message_id, |meta| {
+            if let Some(obj) = meta.as_object_mut() {
+                obj.remove(QUEUED_METADATA_KEY);
+            }
+        })
+    }
+
+    /// Keep a message whose model could not be reached in the chat's outbox:
+    /// the user message stays, marked queued, and its empty answer goes.
+    /// `error` counts as a failed attempt.
+    fn queue_turn(
+        &self,
+        app: &AppHandle,
+        item: OutboxItem,
+        is_new: bool,
+        assistant_message_id: Option<&str>,
+        error: Option<&AppError>,
+    ) -> Result<(String, String), AppError> {
+        if let Some(id) = assistant_message_id {
+            self.message_service.delete(id.to_string())?;
+        }
+        self.merge_message_metadata(&item.user_message_id, |meta| {
+            meta[QUEUED_METADATA_KEY] = serde_json::json!(true);
+        })?;
+        let item = self.outbox_service.queue(item, is_new, error)?;
+        let error = error.map(ToString::to_string);
+        tracing::info!(chat_id = %item.chat_id, item_id = %item.id, attempts = item.attempts, "Message queued in the outbox");
+        self.outbox_service
+            .emit_status(app, &item, OutboxStatus::Queued, error, None);
+        Err(AppError::Queued(item.id))
+    }
+
+    /// Send the chat's queued messages in order, through the normal send
+    /// path. Stops at the first one whose model still cannot be reached;
+    /// one failing for another reason leaves the outbox and the rest go on.
+    pub async fn flush_outbox(&self, chat_id: &str, app: &AppHandle) -> Result<(), AppError> {
+        self.activity_halt.ensure_running()?;
+        self.outbox_service
+            .flush(
+                chat_id,
+                |item| async move {
+                    let params = item.params.clone();
+                    self.send_turn(
+                        chat_id.to_string(),
+                        item.content.clone(),
+                        None,
+                        params.metadata,
+                        params.selected_model,
+                        params.reasoning_effort,
+                        params.llm_connection_id,
+                        Some(item),
+                        None,
+                        app.clone(),
+                    )
+                    .await
+                    .map(|(assistant_message_id, _)| assistant_message_id)
+                },
+                |item, status, error, assistant_message_id| {
+                    if status == OutboxStatus::Failed {
+                        if let Err(e) = self.clear_queued_flag(&item.user_message_id) {
+                            tracing::warn!(item_id = %item.id, error = %e, "Failed to unmark queued message");
+                        }
+                    }
+                    self.outbox_service
+                        .emit_status(app, item, status, error, assistant_message_id);
+                },
+            )
+            .await
+    }
+
+    /// Drop a queued message along with its user message.
+    pub fn discard_outbox_item(&self, id: &str, app: &AppHandle) -> Result<(), AppError> {
+        let item = self.outbox_service.get_by_id(id)?;
+        let Some(_flush) = self.outbox_service.begin_flush(&item.chat_id) else {
+            return Err(AppError::Validation(
+                "The chat's queued messages are being sent".to_string(),
+            ));
+        };
+        self.outbox_service.remove(id)?;
+        self.message_service.delete(item.user_message_id.clone())?;
+        self.outbox_service
+            .emit_status(app, &item, OutboxStatus::Discarded, None, None);
+        Ok(())
+    }
+
+    /// Check every `OUTBOX_CHECK_INTERVAL` whether the connections of chats
+    /// with queued messages answer again, and send those chats' messages.
+    pub fn start_outbox_watcher(self: Arc<Self>, app: AppHandle) {
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(OUTBOX_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if self.activity_halt.is_halted() {
+                    continue;
+                }
+                let chats = match self.outbox_service.pending_chats() {
+                    Ok(chats) => chats,
+                    Err(e) => {
+                        tracing::error!(error = %e, "Failed to read the outbox");
+                        continue;
+                    }
+                };
+                for (chat_id, base_url) in chats {
+                    if !self.outbox_service.is_reachable(&base_url).await {
+                        continue;
+                    }
+                    if let Err(e) = self.flush_outbox(&chat_id, &app).await {
+                        tracing::error!(chat_id = %chat_id, error = %e, "Failed to send queued messages");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Leave an activity entry for a finished turn. Headless runs are the
+    /// background turns; specialist sessions report through their agent task.
+    async fn record_turn_activity(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        result: &Result<(String, String), AppError>,
+    ) {
+        let chat = match self.repository.get_by_id(chat_id) {
+            Ok(Some(chat)) if chat.parent_id.is_none() => chat,
+            Ok(_) => return,
+            Err(e) => {
+                tracing::warn!(chat_id = %chat_id, error = %e, "Failed to load chat for activity");
+                return;
+            }
+        };
+        let foreground = !self.is_headless(chat_id).await;
+        let (kind, summary) = match result {
+            Ok((_, content)) if content.trim().is_empty() => {
+                (ActivityKind::TurnCompleted, chat.title.clone())
+            }
+            Ok((_, content)) => (ActivityKind::TurnCompleted, content.clone()),
+            Err(e) => (ActivityKind::Error, e.to_string()),
+        };
+        self.activity_service.record(
+            app,
+            ActivityService::entry(&chat.workspace_id, chat_id, kind, &summary, foreground),
+        );
+
+        // Webhooks get the chat title, and the answer only when they opt in
+        let event = match result {
+            Ok((_, content)) => WebhookEvent::new(kind, &chat.workspace_id, chat_id, &chat.title)
+                .with_content(content),
+            Err(e) => WebhookEvent::new(kind, &chat.workspace_id, chat_id, &e.to_string()),
+        };
+        self.webhook_dispatcher.dispatch(event);
+    }
+
+    /// Store the files of a new user message and merge them into its
+    /// metadata. Returns the files the model may read and the metadata.
+    fn prepare_user_files(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        files: Option<Vec<String>>,
+        metadata: Option<&str>,
+    ) -> Result<(Option<Vec<String>>, Option<String>), AppError> {
+        // Process files: Save incoming base64 files to disk and get paths with mime types
+        let processed_files = self.process_incoming_files(app, files)?;
+
+        // Drop references to files the user has not allowed us to read (e.g. paths
+        // carried over from an imported chat); they are noted in the metadata
+        let (processed_files, blocked_files) = processed_files.map_or_else(
+            || (None, Vec::new()),
+            |file_list| {
+                let (allowed, blocked): (Vec<String>, Vec<String>) = file_list
+                    .into_iter()
+                    .partition(|f| self.attachment_service.is_file_access_allowed(f));
+                for path in &blocked {
+                    tracing::warn!(chat_id = %chat_id, path = %path, "Blocked file reference outside the attachments directory");
+                }
+                (Some(allowed), blocked)
+            },
+        );
 
         // Merge incoming metadata with processed files
-        let final_metadata = if let Some(meta_str) = &metadata {
+        let final_metadata = if let Some(meta_str) = metadata {
             let mut meta_obj: serde_json::Value =
                 serde_json::from_str(meta_str).unwrap_or(serde_json::json!({}));
             if let Some(file_list) = &processed_files {
-                if !file_list.is_empty() {
+                if file_list.is_empty() {
+                    // Don't keep blocked references from the incoming metadata
+                    if let Some(obj) = meta_obj.as_object_mut() {
+                        obj.remove("files");
+                    }
+                } else {
                     meta_obj["files"] = serde_json::json!(file_list);
+                    self.attach_file_hashes(&mut meta_obj, file_list)?;
                 }
             }
+            if !blocked_files.is_empty() {
+                meta_obj["blockedFiles"] = serde_json::json!(blocked_files);
+            }
             Some(meta_obj.to_string())
         } else if let Some(file_list) = &processed_files {
-            if file_list.is_empty() {
+            if file_list.is_empty() && blocked_files.is_empty() {
                 None
             } else {
-                Some(serde_json::json!({ "files": file_list }).to_string())
+                let mut meta_obj = serde_json::json!({});
+                if !file_list.is_empty() {
+                    meta_obj["files"] = serde_json::json!(file_list);
+                    self.attach_file_hashes(&mut meta_obj, file_list)?;
+                }
+                if !blocked_files.is_empty() {
+                    meta_obj["blockedFiles"] = serde_json::json!(blocked_files);
+                }
+                Some(meta_obj.to_string())
             }
         } else {
             None
         };
 
-        self.message_service.create(
-            user_message_id.clone(),
-            chat_id.clone(),
-            "user".to_string(),
-            content.clone(),
-            Some(user_timestamp),
-            None,
-            None,
-            final_metadata,
+        Ok((processed_files, final_metadata))
+    }
+
+    /// The files a stored user message was sent with.
+    fn stored_files(metadata: Option<&str>) -> Option<Vec<String>> {
+        let metadata = serde_json::from_str::<serde_json::Value>(metadata?).ok()?;
+        let files: Vec<String> = metadata
+            .get("files")?
+            .as_array()?
+            .iter()
+            .filter_map(|f| f.as_str().map(str::to_string))
+            .collect();
+        Some(files).filter(|f| !f.is_empty())
+    }
+
+    /// Run one chat turn: store the user message, call the model and, when it
+    /// asks for tools, the agent loop. A `queued` message resends its stored
+    /// user message; a turn whose model cannot be reached may be queued in
+    /// the outbox, ending in `AppError::Queued`. A `regenerating` turn answers
+    /// its stored user message again, from the history before it, and is
+    /// never queued.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_turn(
+        &self,
+        chat_id: String,
+        content: String,
+        files: Option<Vec<String>>,
+        metadata: Option<String>,
+        selected_model: Option<String>,
+        reasoning_effort: Option<String>,
+        llm_connection_id_override: Option<String>,
+        queued: Option<OutboxItem>,
+        regenerating: Option<Message>,
+        app: AppHandle,
+    ) -> Result<(String, String), AppError> {
+        // Track chat message operation
+        crate::lib::sentry_helpers::add_breadcrumb(
+            "chat",
+            format!("Sending message to chat {chat_id}"),
+            sentry::Level::Info,
+        );
+
+        // How the message was sent, kept should it wait in the outbox
+        let outbox_params = OutboxParams {
+            metadata: metadata.clone(),
+            selected_model: selected_model.clone(),
+            reasoning_effort: reasoning_effort.clone(),
+            llm_connection_id: llm_connection_id_override.clone(),
+        };
+
+        // A queued message's files were stored when it was queued
+        let (processed_files, final_metadata) = match (&queued, &regenerating) {
+            (Some(item), _) => (Some(item.files.clone()).filter(|f| !f.is_empty()), None),
+            (None, Some(message)) => (Self::stored_files(message.metadata.as_deref()), None),
+            (None, None) => self.prepare_user_files(&app, &chat_id, files, metadata.as_deref())?,
+        };
+
+        // 1. Get chat to find workspace_id
+        let chat = self
+            .repository
+            .get_by_id(&chat_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
+
+        let workspace_id = chat.workspace_id.clone();
+
+        // Track workspace context
+        crate::lib::sentry_helpers::track_workspace_operation(&workspace_id, "send_message");
+
+        // 2. Get workspace settings
+        let workspace_settings = self
+            .workspace_settings_service
+            .get_by_workspace_id(&workspace_id)?
+            .ok_or_else(|| AppError::Validation("Workspace settings not found".to_string()))?;
+
+        // 3-4. Get LLM connection and determine model to use
+        let (llm_connection, model) = model_pinning::resolve_turn_model(
+            llm_connection_id_override,
+            selected_model.clone(),
+            &workspace_settings,
+            |id| self.llm_connection_service.get_by_id(id),
         )?;
+        let llm_connection_id = llm_connection.id.clone();
+
+        // 4.5 A message sent from a prompt may need another model for this turn
+        let mut model_override = None;
+        let (llm_connection, llm_connection_id, model, selected_model) = match self
+            .prompt_model_pin(&app, metadata.as_deref(), &llm_connection, &model)?
+        {
+            Some((
+                prompt_id,
+                ModelPin::Override {
+                    connection,
+                    model: pinned,
+                },
+            )) => {
+                tracing::info!(chat_id = %chat_id, prompt_id = %prompt_id, model = %pinned, "Using the prompt's preferred model for this turn");
+                model_override = Some(serde_json::json!({
+                    "promptId": prompt_id,
+                    "connectionId": connection.id,
+                    "model": pinned,
+                    "replacedModel": model,
+                }));
+                let connection_id = connection.id.clone();
+                (*connection, connection_id, pinned.clone(), Some(pinned))
+            }
+            Some((prompt_id, ModelPin::Warn { missing })) => {
+                let missing = missing.iter().map(|c| c.as_str().to_string()).collect();
+                if let Err(e) = super::ChatEmitter::new(app.clone()).emit_model_capability_warning(
+                    chat_id.clone(),
+                    prompt_id,
+                    model.clone(),
+                    missing,
+                ) {
+                    tracing::warn!(error = %e, "Failed to emit model capability warning");
+                }
+                (llm_connection, llm_connection_id, model, selected_model)
+            }
+            _ => (llm_connection, llm_connection_id, model, selected_model),
+        };
+
+        // 5. Get conversation history; messages still in the outbox are not
+        // part of it yet
+        let existing_messages: Vec<Message> = self
+            .message_service
+            .get_by_chat_id(&chat_id)?
+            .into_iter()
+            .filter(|m| !outbox::is_queued(m.metadata.as_deref()))
+            .take_while(|m| regenerating.as_ref().is_none_or(|r| r.id != m.id))
+            .collect();
+
+        // A reply must point at a user or assistant message of this chat
+        if let Some(reply_to) =
+            crate::features::message::reply_to_from_metadata(metadata.as_deref())
+        {
+            reply::validate_reply_target(
+                existing_messages.iter().find(|m| m.id == reply_to),
+                &chat_id,
+            )?;
+        }
+
+        // 6. Create user message, or take the queued one out of the outbox
+        let (user_message_id, user_timestamp) = if let Some(item) = &queued {
+            let message = self
+                .message_service
+                .get_by_id(&item.user_message_id)?
+                .ok_or_else(|| {
+                    AppError::NotFound(format!("Message not found: {}", item.user_message_id))
+                })?;
+            self.clear_queued_flag(&message.id)?;
+            (message.id, message.timestamp)
+        } else if let Some(message) = &regenerating {
+            (message.id.clone(), message.timestamp)
+        } else {
+            let user_timestamp = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_millis() as i64;
+            let user_message_id = uuid::Uuid::new_v4().to_string();
+
+            self.message_service.create(
+                user_message_id.clone(),
+                chat_id.clone(),
+                "user".to_string(),
+                content.clone(),
+                Some(user_timestamp),
+                None,
+                None,
+                final_metadata,
+            )?;
+            (user_message_id, user_timestamp)
+        };
+
+        // Messages sent while others wait in the outbox queue up behind them
+        if queued.is_none()
+            && regenerating.is_none()
+            && self.outbox_service.has_pending(&chat_id)?
+        {
+            let item = OutboxItem::new(
+                &chat_id,
+                &user_message_id,
+                &content,
+                processed_files.clone().unwrap_or_default(),
+                outbox_params,
+                &llm_connection.base_url,
+            );
+            return self.queue_turn(&app, item, true, None, None);
+        }
 
         // 6.5 Check for Agent Mention (Routing)
         let agent_regex = regex::Regex::new(r"^@([a-zA-Z0-9\.\-_]+)\s+(.*)").unwrap();
@@ -554,6 +2086,7 @@ impl ChatService {
                 let app_handle_for_emit = app.clone(); // Clone for emitting events later
                 let status_message_id = assistant_message_id.clone();
                 let agent_id_owned = agent_id.to_string();
+                let workspace_id_owned = workspace_id.clone();
 
                 tokio::spawn(async move {
                     let chat_service = {
@@ -581,6 +2114,41 @@ impl ChatService {
                     };
                     let summary = result.as_ref().ok().cloned().unwrap_or_default();
 
+                    // The task ran in the specialist session, away from the chat on screen
+                    let (kind, activity_summary) = match &result {
+                        Ok(content) => (
+                            ActivityKind::AgentTaskCompleted,
+                            content.trim_start().to_string(),
+                        ),
+                        Err(e) => (ActivityKind::AgentTaskFailed, e.to_string()),
+                    };
+                    chat_service.activity_service.record(
+                        &app_handle_for_emit,
+                        ActivityService::entry(
+                            &workspace_id_owned,
+                            &parent_chat_id,
+                            kind,
+                            &format!("@{agent_id_owned}: {activity_summary}"),
+                            false,
+                        ),
+                    );
+                    let event = match &result {
+                        Ok(content) => WebhookEvent::new(
+                            kind,
+                            &workspace_id_owned,
+                            &parent_chat_id,
+                            &format!("@{agent_id_owned} completed its task"),
+                        )
+                        .with_content(content),
+                        Err(e) => WebhookEvent::new(
+                            kind,
+                            &workspace_id_owned,
+                            &parent_chat_id,
+                            &format!("@{agent_id_owned}: {e}"),
+                        ),
+                    };
+                    chat_service.webhook_dispatcher.dispatch(event);
+
                     let metadata = serde_json::json!({
                         "type": "agent_card",
                         "agent_id": agent_id_owned,
@@ -690,18 +2258,68 @@ impl ChatService {
         };
 
         // 9. Prepare messages for API
-        let api_messages = self.prepare_messages(
+        let workspace_notes = self.context_notes(&workspace_id, chat.agent_id.as_deref());
+        let chat_instructions = self.chat_instructions(&chat);
+        let generation = self.generation_params(&workspace_settings);
+        let budget = ContextBudget::for_request(
+            &model,
+            Some(&llm_connection),
+            generation.max_tokens,
+            tools.as_deref(),
+        );
+        let chat_summary = self
+            .turn_summary(
+                &app,
+                &chat,
+                &workspace_settings,
+                &existing_messages,
+                &llm_connection,
+                &model,
+                budget.as_ref(),
+            )
+            .await;
+        let knowledge = self.turn_knowledge(&workspace_id, &content).await;
+        let (mut api_messages, context_trim) = self.prepare_messages(
             &existing_messages,
             &workspace_settings,
             &content,
             processed_files.as_deref(),
             metadata.as_deref(),
             system_prompt_override.clone(),
-            Some(&llm_connection.provider),
+            workspace_notes.as_deref(),
+            chat_instructions.as_deref(),
+            Some(&llm_connection),
+            budget.as_ref(),
+            chat_summary.as_ref(),
+            &knowledge,
+        )?;
+        if let Some(trim) = &context_trim {
+            message_emitter.emit_context_trimmed(
+                chat_id.clone(),
+                assistant_message_id.clone(),
+                trim,
+            )?;
+        }
+        self.apply_scratchpad(&chat_id, &mut api_messages)?;
+        let redaction = self.redact_request(
+            &chat_id,
+            &workspace_settings,
+            &llm_connection,
+            &mut api_messages,
         )?;
 
         // 10. Determine if streaming is enabled
-        let stream_enabled = workspace_settings.stream_enabled.is_none_or(|v| v == 1); // Default to true
+        let stream_enabled = workspace_settings.stream_enabled.is_none_or(|v| v == 1) // Default to true
+            && !self.is_headless(&chat_id).await;
+        let context_cache_scope =
+            (workspace_settings.context_cache_enabled == Some(1)).then(|| workspace_id.clone());
+        // Only foreground turns of top-level chats wait in the outbox; a
+        // regenerated one gets its previous answer back instead
+        let queue_offline = queued.is_some()
+            || (regenerating.is_none()
+                && workspace_settings.outbox_enabled == Some(1)
+                && chat.parent_id.is_none()
+                && !self.is_headless(&chat_id).await);
 
         let tool_choice: Option<ToolChoice> = None; // Use "auto" by default
 
@@ -711,8 +2329,11 @@ impl ChatService {
         let llm_request = LLMChatRequest {
             model: model.clone(), // Clone here since we use it below
             messages: api_messages,
-            temperature: Some(0.7),
-            max_tokens: None,
+            temperature: generation.temperature,
+            max_tokens: generation.max_tokens,
+            top_p: generation.top_p,
+            frequency_penalty: generation.frequency_penalty,
+            presence_penalty: generation.presence_penalty,
             stream: stream_enabled,
             tools: tools.clone(),
             tool_choice,
@@ -722,14 +2343,45 @@ impl ChatService {
             })),
             response_modalities: None, // Provider-specific, will be set by provider if needed
             image_config: None,        // Provider-specific, will be set by provider if needed
+            context_cache_scope,
+            model_filter: ModelFilter::for_connection(&llm_connection),
+            connection_config: Some(ConnectionConfig::for_connection(&llm_connection)),
+            seed: reproducibility::requested_seed(metadata.as_deref()),
+            output_limits: Some(OutputLimits::for_workspace(
+                workspace_settings.max_output_bytes,
+            )),
         };
+        let reproducibility = Reproducibility::of_request(&llm_connection.provider, &llm_request);
 
         // 12. Get cancellation receiver for this chat
-        let cancellation_rx = self.get_cancellation_receiver(&chat_id).await;
+        let mut cancellation_rx = self.get_cancellation_receiver(&chat_id).await;
+
+        // 12.5 Hold a turn estimated above the workspace's cost threshold
+        // until the user confirms it; later agent loop calls are not held
+        self.confirm_turn_cost(
+            &app,
+            &chat_id,
+            &assistant_message_id,
+            &workspace_settings,
+            &llm_request,
+            &mut cancellation_rx,
+        )
+        .await?;
 
         // 13. Call LLM service
-        let start_time = std::time::Instant::now();
-        let llm_response = self
+        self.update_status(
+            &app,
+            &chat_id,
+            StatusChange::Streaming {
+                model: model.clone(),
+                provider: llm_connection.provider.clone(),
+                iteration: 1,
+            },
+        );
+        let continuation_base = Self::continuation_base(&workspace_settings, &llm_request);
+        let continuation_rx = cancellation_rx.resubscribe();
+        let call_start = PhaseStart::now();
+        let result = self
             .llm_service
             .chat(
                 &llm_connection.base_url,
@@ -741,8 +2393,50 @@ impl ChatService {
                 Some(cancellation_rx),
                 &llm_connection.provider,
             )
-            .await?;
-        let latency = start_time.elapsed().as_millis() as u64;
+            .await;
+        let call_entry = call_start.finish(
+            TimelinePhase::LlmCall,
+            model.clone(),
+            Some(assistant_message_id.clone()),
+            timeline::llm_call_outcome(&result),
+        );
+        let latency = u64::try_from(call_entry.duration_ms()).unwrap_or(0);
+        self.record_timeline(&chat_id, call_entry).await;
+
+        // 13.5 Keep the message for later when the model could not be reached
+        let result = match result {
+            Err(e) if queue_offline && outbox::is_network_error(&e) => {
+                let is_new = queued.is_none();
+                let item = queued.unwrap_or_else(|| {
+                    OutboxItem::new(
+                        &chat_id,
+                        &user_message_id,
+                        &content,
+                        processed_files.clone().unwrap_or_default(),
+                        outbox_params,
+                        &llm_connection.base_url,
+                    )
+                });
+                return self.queue_turn(&app, item, is_new, Some(&assistant_message_id), Some(&e));
+            }
+            result => result,
+        };
+        let (mut llm_response, continuations) = self
+            .recover_interrupted_stream(
+                &app,
+                &llm_connection,
+                continuation_base.as_ref(),
+                &chat_id,
+                &assistant_message_id,
+                &continuation_rx,
+                result?,
+            )
+            .await;
+        let stream_interruption =
+            stream_recovery::interruption_metadata(&llm_response, continuations);
+        self.unredact_response(&chat_id, redaction.as_ref(), &mut llm_response)?;
+        let argument_repair = json_repair::repair_tool_calls(&mut llm_response);
+        let raw_content = Self::post_process(&workspace_settings, &mut llm_response);
 
         // Track LLM call performance
         crate::lib::sentry_helpers::track_llm_call(
@@ -753,7 +2447,12 @@ impl ChatService {
             &Ok::<(), Box<dyn std::error::Error>>(()),
         );
 
+        // A cancelled stream still returns whatever content arrived before the cancel
+        let cancelled = cancelled::is_cancelled(&llm_response);
+
         // Record usage
+        self.accumulate_headless_usage(&chat_id, llm_response.usage.as_ref())
+            .await;
         let usage_service = self.usage_service.clone();
         let r_workspace_id = workspace_id.clone();
         let r_chat_id = chat_id.clone();
@@ -762,6 +2461,7 @@ impl ChatService {
         let r_model = model_for_usage;
         let r_usage = llm_response.usage.clone();
         let r_is_stream = stream_enabled;
+        let r_status = cancelled::usage_status(&llm_response);
 
         tokio::task::spawn_blocking(move || {
             if let Err(e) = usage_service.record_usage(
@@ -773,12 +2473,16 @@ impl ChatService {
                 r_usage,
                 latency,
                 r_is_stream,
-                "success".to_string(),
+                r_status.to_string(),
             ) {
                 tracing::error!(error = ?e, "Failed to record usage");
             }
         });
 
+        // Generated images are stored and referenced from the answer, so they
+        // stay with the text when it is copied, exported or sent back
+        let (generated_hashes, unstored_images) = self.store_generated_images(&mut llm_response);
+
         // 13. Update assistant message with final content
         self.message_service.update(
             assistant_message_id.clone(),
@@ -794,39 +2498,111 @@ impl ChatService {
             metadata_obj["tokenUsage"] = serde_json::json!(usage);
         }
 
-        // Add generated images to metadata if present
-        if let Some(images) = &llm_response.images {
-            if !images.is_empty() {
-                // Convert images to data URLs for frontend display
-                let image_urls: Vec<String> = images
-                    .iter()
-                    .map(|img| format!("data:{};base64,{}", img.mime_type, img.data))
-                    .collect();
+        // The stream broke off: how often the answer was continued, and
+        // whether it is still cut short
+        if let Some(interruption) = stream_interruption {
+            metadata_obj[STREAM_INTERRUPTED_METADATA_KEY] = interruption;
+        }
 
-                metadata_obj["images"] = serde_json::json!(image_urls);
-            }
+        // The answer passed an output cap and was kept up to it
+        if let Some(limit) = output_limit::limit_metadata(llm_response.finish_reason.as_deref()) {
+            metadata_obj[OUTPUT_LIMIT_METADATA_KEY] = limit;
         }
 
-        if !metadata_obj.as_object().unwrap().is_empty() {
-            self.message_service
-                .update_metadata(assistant_message_id.clone(), Some(metadata_obj.to_string()))?;
+        // Record that connection instructions shaped this answer
+        if let Some(suffix) = Self::connection_suffix_metadata(&llm_connection) {
+            metadata_obj["connectionSuffix"] = suffix;
+        }
 
-            // Emit metadata updated event with delay to ensure DB has flushed
-            let app_clone = app.clone();
-            let chat_id_clone = chat_id.clone();
-            let assistant_message_id_clone = assistant_message_id.clone();
+        // The chat's custom instructions shaped this answer, in this version
+        if let Some(instructions) = Self::chat_instructions_metadata(chat_instructions.as_deref()) {
+            metadata_obj["chatInstructions"] = instructions;
+        }
 
-            tokio::spawn(async move {
-                // Small delay to ensure DB has flushed
-                tokio::time::sleep(tokio::time::Duration::from_millis(150)).await;
+        // The document chunks the answer was given, by the numbers it cites
+        if let Some(citations) = knowledge_context::citations_metadata(&knowledge) {
+            metadata_obj[KNOWLEDGE_METADATA_KEY] = citations;
+        }
 
-                let message_emitter = MessageEmitter::new(app_clone);
-                if let Err(e) = message_emitter
-                    .emit_message_metadata_updated(chat_id_clone, assistant_message_id_clone)
-                {
-                    tracing::error!(error = ?e, "Failed to emit metadata-updated event");
-                }
-            });
+        // What was masked before the request left the machine
+        if let Some(summary) = redaction.as_ref().filter(|s| !s.counts.is_empty()) {
+            metadata_obj["redaction"] = serde_json::json!(summary);
+        }
+
+        // Whether the model thought before answering, and for how long
+        if let Some(thinking) = Self::thinking_metadata(llm_response.reasoning.as_deref()) {
+            metadata_obj["thinking"] = thinking;
+        }
+
+        // Settings the model could not honour, so the UI can explain them
+        if !llm_response.parameter_notes.is_empty() {
+            metadata_obj["parameterNotes"] = serde_json::json!(llm_response.parameter_notes);
+        }
+
+        // The seed, model snapshot and sampling parameters to reproduce it by
+        if let Some(record) = reproducibility.metadata(&llm_response) {
+            metadata_obj[REPRODUCIBILITY_METADATA_KEY] = record;
+        }
+
+        // Tool calls whose arguments were cut off mid-stream
+        if !argument_repair.is_empty() {
+            metadata_obj["argumentRepair"] = serde_json::json!(argument_repair);
+        }
+
+        // The answer as the model wrote it, before post-processing
+        if let Some(raw) = &raw_content {
+            PostProcessingService::record_raw_content(&mut metadata_obj, raw);
+        }
+
+        // The prompt the message came from picked the model for this turn
+        if let Some(model_override) = model_override {
+            metadata_obj["modelOverride"] = model_override;
+        }
+
+        // The last change to the workspace's MCP tools, for turns that behave oddly
+        if let Some(change) = self.tool_catalog_service.turn_metadata(&workspace_id) {
+            metadata_obj[TOOL_CATALOG_METADATA_KEY] = change;
+        }
+
+        // Stored generated images; ones that could not be stored stay data URLs
+        self.reference_stored_files(&mut metadata_obj, &generated_hashes)?;
+        if !unstored_images.is_empty() {
+            metadata_obj["images"] = serde_json::json!(unstored_images);
+        }
+
+        // A cancelled answer is stored as far as it got, without its tool calls
+        let metadata_stored = if cancelled {
+            cancelled::store_partial_answer(
+                &self.message_service,
+                &assistant_message_id,
+                &mut llm_response,
+                metadata_obj,
+            )?;
+            true
+        } else if metadata_obj.as_object().unwrap().is_empty() {
+            false
+        } else {
+            self.message_service
+                .update_metadata(assistant_message_id.clone(), Some(metadata_obj.to_string()))?;
+            true
+        };
+        if metadata_stored {
+            // The update is committed, and the dispatcher keeps this after
+            // the message's earlier events
+            if let Err(e) = message_emitter
+                .emit_message_metadata_updated(chat_id.clone(), assistant_message_id.clone())
+            {
+                tracing::error!(error = ?e, "Failed to emit metadata-updated event");
+            }
+        }
+        if cancelled {
+            if let Err(e) = message_emitter.emit_partial_message_complete(
+                chat_id.clone(),
+                assistant_message_id.clone(),
+                llm_response.content.clone(),
+            ) {
+                tracing::error!(error = ?e, "Failed to emit partial message-complete event");
+            }
         }
 
         // 14. Emit tool calls event if detected
@@ -851,15 +2627,11 @@ impl ChatService {
         }
 
         // 15. Update chat last message
-        let last_message = if llm_response.content.len() > 100 {
-            llm_response.content.chars().take(100).collect::<String>() + "..."
-        } else {
-            llm_response.content.clone()
-        };
+        let last_message = Chat::last_message_preview(&llm_response.content);
         self.repository
             .update(&chat_id, None, Some(&last_message))?;
 
-        // 16. Handle agent loop if tool calls detected
+        // 16. Handle agent loop if tool calls detected (a cancelled turn has none left)
         if let Some(tool_calls) = &llm_response.tool_calls {
             if !tool_calls.is_empty() {
                 return self
@@ -907,7 +2679,8 @@ impl ChatService {
         Ok((assistant_message_id, llm_response.content))
     }
 
-    /// Edit a message and resend - deletes the message and subsequent messages, then sends new message
+    /// Edit a message and resend: the edit is sent on a new branch forked
+    /// before the message, keeping the original continuation on its branch
     pub async fn edit_and_resend_message(
         &self,
         chat_id: String,
@@ -918,11 +2691,22 @@ impl ChatService {
         selected_model: Option<String>,
         reasoning_effort: Option<String>,
         llm_connection_id: Option<String>,
+        reuse_seed: bool,
         app: AppHandle,
     ) -> Result<(String, String), AppError> {
+        self.activity_halt.ensure_running()?;
         // Process new files
         let processed_new_files = self.process_incoming_files(&app, new_files)?;
 
+        // The seed of the answer about to be replaced, unless a new one is given
+        let metadata =
+            if reuse_seed && reproducibility::requested_seed(metadata.as_deref()).is_none() {
+                let history = self.message_service.get_by_chat_id(&chat_id)?;
+                reproducibility::with_seed(metadata, Self::seed_to_reuse(&history, &message_id))
+            } else {
+                metadata
+            };
+
         // 1. Verify message exists
         if self.message_service.get_by_id(&message_id)?.is_none() {
             // Message not found, just delete messages after it and proceed
@@ -943,14 +2727,11 @@ impl ChatService {
                 .await;
         }
 
-        // 2. Delete the message being edited and all messages after it
-        // This uses delete_messages_after which:
-        // - Finds the position of message_id in the chat
-        // - Deletes all messages after that position
-        // Then we delete the message itself
-        self.message_service
-            .delete_messages_after(chat_id.clone(), message_id.clone())?;
-        self.message_service.delete(message_id.clone())?;
+        // 2. Fork just before the edited message, so it and what followed
+        // stay on the current branch while the edit continues on a new one
+        let history = self.message_service.get_by_chat_id(&chat_id)?;
+        let fork = branches::fork_point_before(&history, &message_id);
+        self.create_branch(&chat_id, fork.as_deref(), None)?;
 
         // 3. Send message with new content (this will create a new user message and trigger agent loop with tool calls)
         self.send_message(
@@ -966,6 +2747,166 @@ impl ChatService {
         .await
     }
 
+    /// The seed a resend of `message_id` reuses: the one the first answer
+    /// after it was generated with, or else the one the message asked for.
+    pub fn seed_to_reuse(history: &[Message], message_id: &str) -> Option<i64> {
+        let position = history.iter().position(|m| m.id == message_id)?;
+        history[position + 1..]
+            .iter()
+            .take_while(|m| m.role != "user")
+            .filter(|m| m.role == "assistant")
+            .find_map(|m| reproducibility::answer_seed(m.metadata.as_deref()))
+            .or_else(|| reproducibility::requested_seed(history[position].metadata.as_deref()))
+    }
+
+    /// Answer the chat's last user message again without resending it. The
+    /// answer it had becomes an earlier variant of the new one, see
+    /// `variants`; should the new turn fail, the previous one is put back.
+    pub async fn regenerate_message(
+        &self,
+        chat_id: String,
+        selected_model: Option<String>,
+        reasoning_effort: Option<String>,
+        llm_connection_id: Option<String>,
+        app: AppHandle,
+    ) -> Result<(String, String), AppError> {
+        self.ensure_no_turn_running(&chat_id)?;
+        if self.outbox_service.has_pending(&chat_id)? {
+            return Err(AppError::Validation(
+                "The chat has messages waiting in the outbox".to_string(),
+            ));
+        }
+        let history = self.message_service.get_by_chat_id(&chat_id)?;
+        let user_index = history
+            .iter()
+            .rposition(|m| m.role == "user")
+            .ok_or_else(|| {
+                AppError::Validation("The chat has no message to answer again".to_string())
+            })?;
+        let user_message = history[user_index].clone();
+        let previous_turn = history[user_index + 1..].to_vec();
+        let previous_answer = previous_turn.iter().rfind(|m| m.role == "assistant");
+        let earlier = previous_answer
+            .map(variants::generations)
+            .unwrap_or_default();
+
+        // The previous answer's files stay referenced by its variants
+        let kept_files = previous_answer.and_then(|m| m.metadata.clone());
+        self.attachment_service.retain(kept_files.as_deref())?;
+        self.message_service
+            .delete_messages_after(chat_id.clone(), user_message.id.clone())?;
+
+        let result = self
+            .send_turn(
+                chat_id.clone(),
+                user_message.content.clone(),
+                None,
+                user_message.metadata.clone(),
+                selected_model,
+                reasoning_effort,
+                llm_connection_id,
+                None,
+                Some(user_message.clone()),
+                app.clone(),
+            )
+            .await;
+
+        match &result {
+            Ok((message_id, _)) => {
+                self.merge_message_metadata(message_id, |meta| variants::attach(meta, &earlier))?;
+                if let Some(message) = self.message_service.get_by_id(message_id)? {
+                    Self::emit_variants_updated(&app, &message);
+                }
+            }
+            Err(e) => {
+                tracing::warn!(chat_id = %chat_id, error = %e, "Regeneration failed, restoring the previous answer");
+                if let Err(e) = self.restore_turn(&chat_id, &user_message.id, &previous_turn) {
+                    tracing::error!(chat_id = %chat_id, error = %e, "Failed to restore the previous answer");
+                }
+                // Retained again when it was restored
+                self.attachment_service.release(kept_files.as_deref())?;
+            }
+        }
+        result
+    }
+
+    /// Put back the messages of a turn whose regeneration failed, in place
+    /// of what the failed turn left.
+    fn restore_turn(
+        &self,
+        chat_id: &str,
+        user_message_id: &str,
+        turn: &[Message],
+    ) -> Result<(), AppError> {
+        self.message_service
+            .delete_messages_after(chat_id.to_string(), user_message_id.to_string())?;
+        for message in turn {
+            self.message_service.create(
+                message.id.clone(),
+                chat_id.to_string(),
+                message.role.clone(),
+                message.content.clone(),
+                Some(message.timestamp),
+                message.assistant_message_id.clone(),
+                message.tool_call_id.clone(),
+                message.metadata.clone(),
+            )?;
+            if message.reasoning.is_some() {
+                self.message_service.update(
+                    message.id.clone(),
+                    message.content.clone(),
+                    message.reasoning.clone(),
+                    None,
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Show generation `index` of an answer in place of the shown one.
+    pub fn select_message_variant(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        message_id: &str,
+        index: usize,
+    ) -> Result<Message, AppError> {
+        self.ensure_no_turn_running(chat_id)?;
+        let message = self
+            .message_service
+            .get_by_id(message_id)?
+            .filter(|m| m.chat_id == chat_id && m.role == "assistant")
+            .ok_or_else(|| AppError::NotFound(format!("Message not found: {message_id}")))?;
+        let selected = variants::select(&message, index)?;
+        self.message_service.update(
+            selected.id.clone(),
+            selected.content.clone(),
+            selected.reasoning.clone(),
+            None,
+        )?;
+        self.message_service
+            .update_metadata(selected.id.clone(), selected.metadata.clone())?;
+
+        let history = self.message_service.get_by_chat_id(chat_id)?;
+        if history.last().is_some_and(|m| m.id == selected.id) {
+            let preview = Chat::last_message_preview(&selected.content);
+            self.repository.update(chat_id, None, Some(&preview))?;
+        }
+        Self::emit_variants_updated(app, &selected);
+        Ok(selected)
+    }
+
+    fn emit_variants_updated(app: &AppHandle, message: &Message) {
+        let emitter = MessageEmitter::new(app.clone());
+        if let Err(e) = emitter.emit_message_variants_updated(
+            message.chat_id.clone(),
+            message.id.clone(),
+            variants::position(message),
+        ) {
+            tracing::error!(error = ?e, "Failed to emit message-variants-updated event");
+        }
+    }
+
     /// Send message with agent loop - handles tool calls and continues conversation
     async fn send_message_with_agent_loop(
         &self,
@@ -987,7 +2928,7 @@ impl ChatService {
             .repository
             .get_by_id(&chat_id)?
             .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
-        let workspace_id = chat.workspace_id;
+        let workspace_id = chat.workspace_id.clone();
 
         let workspace_settings = self
             .workspace_settings_service
@@ -996,25 +2937,17 @@ impl ChatService {
 
         let max_iterations = workspace_settings.max_agent_iterations.unwrap_or(25) as usize;
 
-        let llm_connection_id = llm_connection_id_override
-            .or(workspace_settings.llm_connection_id.clone())
-            .ok_or_else(|| {
-                AppError::Validation("LLM connection not configured for workspace".to_string())
-            })?;
-
-        let llm_connection = self
-            .llm_connection_service
-            .get_by_id(&llm_connection_id)?
-            .ok_or_else(|| {
-                AppError::NotFound(format!("LLM connection not found: {llm_connection_id}"))
-            })?;
-
-        let model = selected_model
-            .or(workspace_settings.default_model.clone())
-            .or(llm_connection.default_model.clone())
-            .ok_or_else(|| AppError::Validation("No model selected".to_string()))?;
+        let (llm_connection, model) = model_pinning::resolve_turn_model(
+            llm_connection_id_override,
+            selected_model,
+            &workspace_settings,
+            |id| self.llm_connection_service.get_by_id(id),
+        )?;
 
-        let stream_enabled = workspace_settings.stream_enabled.is_none_or(|v| v == 1);
+        let stream_enabled = workspace_settings.stream_enabled.is_none_or(|v| v == 1)
+            && !self.is_headless(&chat_id).await;
+        let context_cache_scope =
+            (workspace_settings.context_cache_enabled == Some(1)).then(|| workspace_id.clone());
 
         // Get tools
         // Get tools if not provided
@@ -1030,21 +2963,50 @@ impl ChatService {
         };
 
         let mut assistant_message_id = initial_assistant_message_id;
-        let mut current_messages = self.prepare_messages_for_agent_loop(
+        let workspace_notes = self.context_notes(&workspace_id, chat.agent_id.as_deref());
+        let chat_instructions = self.chat_instructions(&chat);
+        let instructions_metadata = Self::chat_instructions_metadata(chat_instructions.as_deref());
+        let suffix_metadata = Self::connection_suffix_metadata(&llm_connection);
+        let seed = reproducibility::requested_seed(user_metadata.as_deref());
+        let budget = ContextBudget::for_request(
+            &model,
+            Some(&llm_connection),
+            self.generation_params(&workspace_settings).max_tokens,
+            tools.as_deref(),
+        );
+        // The turn's first call already brought the summary up to date
+        let chat_summary = self.repository.get_summary(&chat_id)?;
+        let knowledge = self.turn_knowledge(&workspace_id, &user_content).await;
+        let citations = knowledge_context::citations_metadata(&knowledge);
+        let (mut current_messages, context_trim) = self.prepare_messages_for_agent_loop(
             &chat_id,
             &workspace_settings,
             &user_content,
             user_metadata.as_deref(),
             system_prompt_override.clone(),
-            Some(&llm_connection.provider),
+            workspace_notes.as_deref(),
+            chat_instructions.as_deref(),
+            Some(&llm_connection),
+            budget.as_ref(),
+            chat_summary.as_ref(),
+            &knowledge,
         )?;
 
         // Create emitters once for agent loop
         let agent_emitter = AgentEmitter::new(app.clone());
         let message_emitter = MessageEmitter::new(app.clone());
+        if let Some(trim) = &context_trim {
+            message_emitter.emit_context_trimmed(
+                chat_id.clone(),
+                assistant_message_id.clone(),
+                trim,
+            )?;
+        }
 
         // Get cancellation receiver for this chat (reused across iterations)
         let mut cancellation_rx = self.get_cancellation_receiver(&chat_id).await;
+        // Tool calls run so far, counted across the iterations of this turn
+        let mut tool_budget = TurnToolBudget::new(&workspace_settings);
 
         // Agent loop - allow up to max_iterations + 1 (last one for final summary)
         for iteration in 0..=max_iterations {
@@ -1088,87 +3050,266 @@ impl ChatService {
             }
 
             // Check if we have an initial response for the first iteration
-            let llm_response = if iteration == 0 && initial_llm_response.is_some() {
-                initial_llm_response.take().unwrap()
-            } else {
-                // Determine tools for this LLM call. If it's the last iteration, no tools.
-                let llm_tools = if is_last_iteration {
-                    None
+            // (its redaction, argument repair and post-processing were recorded
+            // by `send_message`)
+            let mut reproducibility = None;
+            let (mut llm_response, redaction, argument_repair, raw_content, stream_interruption) =
+                if iteration == 0 && initial_llm_response.is_some() {
+                    (
+                        initial_llm_response.take().unwrap(),
+                        None,
+                        RepairCounts::default(),
+                        None,
+                        None,
+                    )
                 } else {
-                    tools.clone()
-                };
+                    // Determine tools for this LLM call. If it's the last iteration, no tools.
+                    let llm_tools = if is_last_iteration {
+                        None
+                    } else {
+                        tools.clone()
+                    };
 
-                // Call LLM
-                let model_for_usage = model.clone();
-
-                let llm_request = LLMChatRequest {
-                    model: model.clone(),
-                    messages: current_messages.clone(),
-                    temperature: Some(0.7),
-                    max_tokens: None,
-                    stream: stream_enabled,
-                    tools: llm_tools,
-                    tool_choice: None,
-                    reasoning_effort: reasoning_effort.clone(),
-                    stream_options: Some(serde_json::json!({
-                        "include_usage": true
-                    })),
-                    response_modalities: None, // Provider-specific, will be set by provider if needed
-                    image_config: None, // Provider-specific, will be set by provider if needed
-                };
+                    // Tools may have changed the scratchpad since the last request
+                    self.apply_scratchpad(&chat_id, &mut current_messages)?;
+                    let mut request_messages = current_messages.clone();
+                    let redaction = self.redact_request(
+                        &chat_id,
+                        &workspace_settings,
+                        &llm_connection,
+                        &mut request_messages,
+                    )?;
 
-                let start_time = std::time::Instant::now();
-                let resp = self
-                    .llm_service
-                    .chat(
-                        &llm_connection.base_url,
-                        Some(&llm_connection.api_key),
-                        llm_request,
-                        chat_id.clone(),
-                        assistant_message_id.clone(),
-                        app.clone(),
-                        Some(cancellation_rx.resubscribe()),
+                    // Call LLM
+                    let model_for_usage = model.clone();
+
+                    let generation = self.generation_params(&workspace_settings);
+                    let llm_request = LLMChatRequest {
+                        model: model.clone(),
+                        messages: request_messages,
+                        temperature: generation.temperature,
+                        max_tokens: generation.max_tokens,
+                        top_p: generation.top_p,
+                        frequency_penalty: generation.frequency_penalty,
+                        presence_penalty: generation.presence_penalty,
+                        stream: stream_enabled,
+                        tools: llm_tools,
+                        tool_choice: None,
+                        reasoning_effort: reasoning_effort.clone(),
+                        stream_options: Some(serde_json::json!({
+                            "include_usage": true
+                        })),
+                        response_modalities: None, // Provider-specific, will be set by provider if needed
+                        image_config: None, // Provider-specific, will be set by provider if needed
+                        context_cache_scope: context_cache_scope.clone(),
+                        model_filter: ModelFilter::for_connection(&llm_connection),
+                        connection_config: Some(ConnectionConfig::for_connection(&llm_connection)),
+                        seed,
+                        output_limits: Some(OutputLimits::for_workspace(
+                            workspace_settings.max_output_bytes,
+                        )),
+                    };
+                    reproducibility = Some(Reproducibility::of_request(
                         &llm_connection.provider,
+                        &llm_request,
+                    ));
+
+                    self.update_status(
+                        &app,
+                        &chat_id,
+                        StatusChange::Streaming {
+                            model: model.clone(),
+                            provider: llm_connection.provider.clone(),
+                            iteration: u32::try_from(iteration + 1).unwrap_or(u32::MAX),
+                        },
+                    );
+                    let continuation_base =
+                        Self::continuation_base(&workspace_settings, &llm_request);
+                    let call_start = PhaseStart::now();
+                    let result = self
+                        .llm_service
+                        .chat(
+                            &llm_connection.base_url,
+                            Some(&llm_connection.api_key),
+                            llm_request,
+                            chat_id.clone(),
+                            assistant_message_id.clone(),
+                            app.clone(),
+                            Some(cancellation_rx.resubscribe()),
+                            &llm_connection.provider,
+                        )
+                        .await;
+                    let call_entry = call_start.finish(
+                        TimelinePhase::LlmCall,
+                        model.clone(),
+                        Some(assistant_message_id.clone()),
+                        timeline::llm_call_outcome(&result),
+                    );
+                    let latency = u64::try_from(call_entry.duration_ms()).unwrap_or(0);
+                    self.record_timeline(&chat_id, call_entry).await;
+                    let (mut resp, continuations) = self
+                        .recover_interrupted_stream(
+                            &app,
+                            &llm_connection,
+                            continuation_base.as_ref(),
+                            &chat_id,
+                            &assistant_message_id,
+                            &cancellation_rx,
+                            result?,
+                        )
+                        .await;
+                    let stream_interruption =
+                        stream_recovery::interruption_metadata(&resp, continuations);
+                    self.unredact_response(&chat_id, redaction.as_ref(), &mut resp)?;
+                    let argument_repair = json_repair::repair_tool_calls(&mut resp);
+                    let raw_content = Self::post_process(&workspace_settings, &mut resp);
+
+                    // Record usage
+                    self.accumulate_headless_usage(&chat_id, resp.usage.as_ref())
+                        .await;
+                    let r_status = cancelled::usage_status(&resp);
+                    let usage_service = self.usage_service.clone();
+                    let r_workspace_id = workspace_id.clone();
+                    let r_chat_id = chat_id.clone();
+                    let r_message_id = assistant_message_id.clone();
+                    let r_provider = llm_connection.provider.clone();
+                    let r_model = model_for_usage;
+                    let r_usage = resp.usage.clone();
+                    let r_is_stream = stream_enabled;
+
+                    tokio::task::spawn_blocking(move || {
+                        if let Err(e) = usage_service.record_usage(
+                            r_workspace_id,
+                            r_chat_id,
+                            r_message_id,
+                            r_provider,
+                            r_model,
+                            r_usage,
+                            latency,
+                            r_is_stream,
+                            r_status.to_string(),
+                        ) {
+                            tracing::error!(error = ?e, "Failed to record usage");
+                        }
+                    });
+
+                    // Update assistant message content (only for new calls, initial response already updated message)
+                    self.message_service.update(
+                        assistant_message_id.clone(),
+                        resp.content.clone(),
+                        resp.reasoning.clone(),
+                        None,
+                    )?;
+
+                    (
+                        resp,
+                        redaction.filter(|s| !s.counts.is_empty()),
+                        argument_repair,
+                        raw_content,
+                        stream_interruption,
                     )
-                    .await?;
-                let latency = start_time.elapsed().as_millis() as u64;
-
-                // Record usage
-                let usage_service = self.usage_service.clone();
-                let r_workspace_id = workspace_id.clone();
-                let r_chat_id = chat_id.clone();
-                let r_message_id = assistant_message_id.clone();
-                let r_provider = llm_connection.provider.clone();
-                let r_model = model_for_usage;
-                let r_usage = resp.usage.clone();
-                let r_is_stream = stream_enabled;
-
-                tokio::task::spawn_blocking(move || {
-                    if let Err(e) = usage_service.record_usage(
-                        r_workspace_id,
-                        r_chat_id,
-                        r_message_id,
-                        r_provider,
-                        r_model,
-                        r_usage,
-                        latency,
-                        r_is_stream,
-                        "success".to_string(),
-                    ) {
-                        tracing::error!(error = ?e, "Failed to record usage");
-                    }
-                });
+                };
 
-                // Update assistant message content (only for new calls, initial response already updated message)
-                self.message_service.update(
+            // Cancelled mid-stream: keep the partial content and end the turn
+            if cancelled::is_cancelled(&llm_response) {
+                let mut metadata = serde_json::json!({});
+                if let Some(instructions) = &instructions_metadata {
+                    metadata["chatInstructions"] = instructions.clone();
+                }
+                if let Some(suffix) = &suffix_metadata {
+                    metadata["connectionSuffix"] = suffix.clone();
+                }
+                if let Some(citations) = &citations {
+                    metadata[KNOWLEDGE_METADATA_KEY] = citations.clone();
+                }
+                if let Some(record) = reproducibility
+                    .as_ref()
+                    .and_then(|r| r.metadata(&llm_response))
+                {
+                    metadata[REPRODUCIBILITY_METADATA_KEY] = record;
+                }
+                if let Some(summary) = &redaction {
+                    metadata["redaction"] = serde_json::json!(summary);
+                }
+                if let Some(interruption) = stream_interruption {
+                    metadata[STREAM_INTERRUPTED_METADATA_KEY] = interruption;
+                }
+                if let Some(raw) = &raw_content {
+                    PostProcessingService::record_raw_content(&mut metadata, raw);
+                }
+                cancelled::store_partial_answer(
+                    &self.message_service,
+                    &assistant_message_id,
+                    &mut llm_response,
+                    metadata,
+                )?;
+                message_emitter
+                    .emit_message_metadata_updated(chat_id.clone(), assistant_message_id.clone())?;
+                message_emitter.emit_partial_message_complete(
+                    chat_id.clone(),
                     assistant_message_id.clone(),
-                    resp.content.clone(),
-                    resp.reasoning.clone(),
-                    None,
+                    llm_response.content.clone(),
                 )?;
 
-                resp
-            };
+                let last_message = Chat::last_message_preview(&llm_response.content);
+                self.repository
+                    .update(&chat_id, None, Some(&last_message))?;
+
+                return Ok((assistant_message_id, llm_response.content));
+            }
+
+            let thinking = Self::thinking_metadata(llm_response.reasoning.as_deref());
+            let reproduction = reproducibility
+                .as_ref()
+                .and_then(|r| r.metadata(&llm_response));
+            let limit = output_limit::limit_metadata(llm_response.finish_reason.as_deref());
+            if redaction.is_some()
+                || !argument_repair.is_empty()
+                || raw_content.is_some()
+                || thinking.is_some()
+                || stream_interruption.is_some()
+                || instructions_metadata.is_some()
+                || suffix_metadata.is_some()
+                || citations.is_some()
+                || reproduction.is_some()
+                || limit.is_some()
+            {
+                let mut metadata = serde_json::json!({});
+                if let Some(record) = reproduction {
+                    metadata[REPRODUCIBILITY_METADATA_KEY] = record;
+                }
+                if let Some(instructions) = &instructions_metadata {
+                    metadata["chatInstructions"] = instructions.clone();
+                }
+                if let Some(suffix) = &suffix_metadata {
+                    metadata["connectionSuffix"] = suffix.clone();
+                }
+                if let Some(citations) = &citations {
+                    metadata[KNOWLEDGE_METADATA_KEY] = citations.clone();
+                }
+                if let Some(summary) = &redaction {
+                    metadata["redaction"] = serde_json::json!(summary);
+                }
+                if let Some(interruption) = stream_interruption {
+                    metadata[STREAM_INTERRUPTED_METADATA_KEY] = interruption;
+                }
+                if let Some(limit) = limit {
+                    metadata[OUTPUT_LIMIT_METADATA_KEY] = limit;
+                }
+                if let Some(thinking) = thinking {
+                    metadata["thinking"] = thinking;
+                }
+                if !argument_repair.is_empty() {
+                    metadata["argumentRepair"] = serde_json::json!(argument_repair);
+                }
+                if let Some(raw) = &raw_content {
+                    PostProcessingService::record_raw_content(&mut metadata, raw);
+                }
+                self.message_service
+                    .update_metadata(assistant_message_id.clone(), Some(metadata.to_string()))?;
+                message_emitter
+                    .emit_message_metadata_updated(chat_id.clone(), assistant_message_id.clone())?;
+            }
 
             // Check if we have tool calls
             if let Some(tool_calls) = &llm_response.tool_calls {
@@ -1181,50 +3322,20 @@ impl ChatService {
                         true,
                     )?;
 
-                    // Check permissions
-                    let allowed_tools = self
-                        .check_and_filter_tool_permissions(
+                    // Gated tools wait for the user while the others run; denials
+                    // come back as tool results, not errors
+                    let tool_results = self
+                        .run_tool_calls(
                             &app,
                             &chat_id,
+                            chat.agent_id.as_deref(),
                             &assistant_message_id,
-                            tool_calls.clone(),
+                            tool_calls,
                             &workspace_settings,
-                        )
-                        .await?;
-
-                    // Handle tool calls
-                    // Use match instead of ? to prevent agent loop from stopping on error
-                    let tool_results = match self
-                        .handle_tool_calls(
-                            &chat_id,
-                            &assistant_message_id,
-                            &allowed_tools,
-                            &app,
+                            &mut tool_budget,
                             &mut cancellation_rx,
                         )
-                        .await
-                    {
-                        Ok(results) => results,
-                        Err(e) => {
-                            // Log error but continue with empty results
-                            tracing::error!(
-                                chat_id = %chat_id,
-                                error = ?e,
-                                "Tool execution failed in agent loop"
-                            );
-                            // Emit error event to notify frontend
-                            let tool_emitter = ToolEmitter::new(app.clone());
-                            let _ = tool_emitter.emit_tool_execution_error(
-                                chat_id.clone(),
-                                assistant_message_id.clone(),
-                                "agent_loop_error".to_string(),
-                                "tool_execution".to_string(),
-                                format!("Tool execution failed: {e}"),
-                            );
-                            // Return empty results to continue agent loop
-                            Vec::new()
-                        }
-                    };
+                        .await?;
 
                     // Add assistant message with tool calls to conversation
                     let assistant_msg_with_tools = ChatMessage::Assistant {
@@ -1250,12 +3361,30 @@ impl ChatService {
             }
 
             // No tool calls - we're done
+            let mut llm_response = llm_response;
+            if iteration > 0 && empty_answer::is_empty_answer(&llm_response) {
+                llm_response = self
+                    .recover_empty_answer(
+                        &current_messages,
+                        &workspace_id,
+                        &chat_id,
+                        &assistant_message_id,
+                        &workspace_settings,
+                        &llm_connection,
+                        &model,
+                        reasoning_effort.clone(),
+                        stream_enabled,
+                        context_cache_scope.clone(),
+                        &app,
+                        &cancellation_rx,
+                    )
+                    .await?;
+                message_emitter
+                    .emit_message_metadata_updated(chat_id.clone(), assistant_message_id.clone())?;
+            }
+
             // Update chat last message
-            let last_message = if llm_response.content.len() > 100 {
-                llm_response.content.chars().take(100).collect::<String>() + "..."
-            } else {
-                llm_response.content.clone()
-            };
+            let last_message = Chat::last_message_preview(&llm_response.content);
             self.repository
                 .update(&chat_id, None, Some(&last_message))?;
 
@@ -1303,50 +3432,980 @@ impl ChatService {
                 );
                 return;
             }
-
-            // Call internal function
-            generate_chat_title_internal(app, chat_id, user_content, model, llm_connection_id)
-                .await;
+
+            // Call internal function
+            generate_chat_title_internal(app, chat_id, user_content, model, llm_connection_id)
+                .await;
+        });
+    }
+
+    /// The connection's system prompt suffix, if it has a non-blank one.
+    /// Mask PII in a request to a cloud connection when the workspace asks
+    /// for it. `None` means the request goes out unchanged.
+    fn redact_request(
+        &self,
+        chat_id: &str,
+        workspace_settings: &WorkspaceSettings,
+        connection: &LLMConnection,
+        messages: &mut [ChatMessage],
+    ) -> Result<Option<RedactionSummary>, AppError> {
+        let Some(config) = RedactionService::config_for(
+            workspace_settings.redaction_config.as_deref(),
+            connection,
+        )?
+        else {
+            return Ok(None);
+        };
+        self.redaction_service
+            .redact_messages(chat_id, &config, messages)
+            .map(Some)
+    }
+
+    /// Run the workspace's post-processors over a final answer. Returns the
+    /// content as the model wrote it when processing changed it.
+    fn post_process(
+        workspace_settings: &WorkspaceSettings,
+        response: &mut LLMChatResponse,
+    ) -> Option<String> {
+        let pipeline = PostProcessingService::pipeline_for(
+            workspace_settings.post_processing_config.as_deref(),
+        )?;
+        let processed = pipeline.run(&response.content);
+        (processed != response.content).then(|| std::mem::replace(&mut response.content, processed))
+    }
+
+    /// The request to keep for continuing an answer whose stream breaks off,
+    /// when the workspace opted in to continuing them.
+    fn continuation_base(
+        workspace_settings: &WorkspaceSettings,
+        request: &LLMChatRequest,
+    ) -> Option<LLMChatRequest> {
+        (workspace_settings.auto_continue_interrupted == Some(1)).then(|| request.clone())
+    }
+
+    /// Salvage an answer whose stream broke off after part of it arrived.
+    /// With a `continuation_base` the model is asked to go on where it
+    /// stopped, into the same message, up to `MAX_STREAM_CONTINUATIONS`
+    /// times; without one the part is kept as it is. Returns the answer and
+    /// how often it was continued.
+    #[allow(clippy::too_many_arguments)]
+    async fn recover_interrupted_stream(
+        &self,
+        app: &AppHandle,
+        llm_connection: &LLMConnection,
+        continuation_base: Option<&LLMChatRequest>,
+        chat_id: &str,
+        message_id: &str,
+        cancellation_rx: &tokio::sync::broadcast::Receiver<()>,
+        mut response: LLMChatResponse,
+    ) -> (LLMChatResponse, u32) {
+        let mut continuations = 0;
+        while response.finish_reason.as_deref() == Some(STREAM_INTERRUPTED)
+            && continuations < MAX_STREAM_CONTINUATIONS
+        {
+            let recovery = StreamRecovery::decide(true, continuation_base.is_some());
+            let (StreamRecovery::SalvageAndContinue, Some(base)) = (recovery, continuation_base)
+            else {
+                break;
+            };
+            continuations += 1;
+            tracing::info!(
+                chat_id,
+                continuations,
+                "Continuing an answer whose stream broke off"
+            );
+            let result = self
+                .llm_service
+                .chat(
+                    &llm_connection.base_url,
+                    Some(&llm_connection.api_key),
+                    stream_recovery::continuation_request(base.clone(), &response.content),
+                    chat_id.to_string(),
+                    message_id.to_string(),
+                    app.clone(),
+                    Some(cancellation_rx.resubscribe()),
+                    &llm_connection.provider,
+                )
+                .await;
+            match result {
+                Ok(continuation) => {
+                    response = stream_recovery::append_continuation(response, continuation);
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, chat_id, "Failed to continue an interrupted answer");
+                    break;
+                }
+            }
+        }
+
+        // The continuation completed with its own part; show the whole answer
+        if continuations > 0 {
+            if let Err(e) = MessageEmitter::new(app.clone()).emit_message_complete(
+                chat_id.to_string(),
+                message_id.to_string(),
+                response.content.clone(),
+                response.usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            ) {
+                tracing::error!(error = ?e, "Failed to emit message-complete event");
+            }
+        }
+        (response, continuations)
+    }
+
+    /// Replace a blank final answer after tool use: ask once more without
+    /// tools for a summary, else build the answer from the tool results.
+    /// Stores the answer and flags the message in its metadata.
+    #[allow(clippy::too_many_arguments)]
+    async fn recover_empty_answer(
+        &self,
+        history: &[ChatMessage],
+        workspace_id: &str,
+        chat_id: &str,
+        assistant_message_id: &str,
+        workspace_settings: &WorkspaceSettings,
+        llm_connection: &LLMConnection,
+        model: &str,
+        reasoning_effort: Option<String>,
+        stream: bool,
+        context_cache_scope: Option<String>,
+        app: &AppHandle,
+        cancellation_rx: &tokio::sync::broadcast::Receiver<()>,
+    ) -> Result<LLMChatResponse, AppError> {
+        tracing::warn!(chat_id = %chat_id, "Empty final answer after tool use, asking for a summary");
+        let cancellation_rx = cancellation_rx.resubscribe();
+        let summary_call = move |mut messages: Vec<ChatMessage>| async move {
+            let redaction =
+                self.redact_request(chat_id, workspace_settings, llm_connection, &mut messages)?;
+            let generation = self.generation_params(workspace_settings);
+            let llm_request = LLMChatRequest {
+                model: model.to_string(),
+                messages,
+                temperature: generation.temperature,
+                max_tokens: generation.max_tokens,
+                top_p: generation.top_p,
+                frequency_penalty: generation.frequency_penalty,
+                presence_penalty: generation.presence_penalty,
+                stream,
+                tools: None,
+                tool_choice: None,
+                reasoning_effort,
+                stream_options: Some(serde_json::json!({
+                    "include_usage": true
+                })),
+                response_modalities: None,
+                image_config: None,
+                context_cache_scope,
+                model_filter: ModelFilter::for_connection(llm_connection),
+                connection_config: Some(ConnectionConfig::for_connection(llm_connection)),
+                seed: None,
+                output_limits: Some(OutputLimits::for_workspace(
+                    workspace_settings.max_output_bytes,
+                )),
+            };
+
+            let call_start = PhaseStart::now();
+            let result = self
+                .llm_service
+                .chat(
+                    &llm_connection.base_url,
+                    Some(&llm_connection.api_key),
+                    llm_request,
+                    chat_id.to_string(),
+                    assistant_message_id.to_string(),
+                    app.clone(),
+                    Some(cancellation_rx),
+                    &llm_connection.provider,
+                )
+                .await;
+            let call_entry = call_start.finish(
+                TimelinePhase::Retry,
+                format!("{model}: summary after an empty answer"),
+                Some(assistant_message_id.to_string()),
+                timeline::llm_call_outcome(&result),
+            );
+            let latency = u64::try_from(call_entry.duration_ms()).unwrap_or(0);
+            self.record_timeline(chat_id, call_entry).await;
+            let mut resp = result?;
+            self.unredact_response(chat_id, redaction.as_ref(), &mut resp)?;
+
+            // The summary request is billed like any other
+            self.accumulate_headless_usage(chat_id, resp.usage.as_ref())
+                .await;
+            let usage_service = self.usage_service.clone();
+            let r_workspace_id = workspace_id.to_string();
+            let r_chat_id = chat_id.to_string();
+            let r_message_id = assistant_message_id.to_string();
+            let r_provider = llm_connection.provider.clone();
+            let r_model = model.to_string();
+            let r_usage = resp.usage.clone();
+            tokio::task::spawn_blocking(move || {
+                if let Err(e) = usage_service.record_usage(
+                    r_workspace_id,
+                    r_chat_id,
+                    r_message_id,
+                    r_provider,
+                    r_model,
+                    r_usage,
+                    latency,
+                    stream,
+                    "success".to_string(),
+                ) {
+                    tracing::error!(error = ?e, "Failed to record usage");
+                }
+            });
+
+            if resp.finish_reason.as_deref() == Some("cancelled") {
+                return Err(AppError::Cancelled);
+            }
+            Ok(resp)
+        };
+
+        let (mut response, recovery) = empty_answer::recover(history, summary_call).await?;
+        if recovery == empty_answer::Recovery::Fallback {
+            let fallback = PhaseStart::now().finish(
+                TimelinePhase::Fallback,
+                "answer built from tool results",
+                Some(assistant_message_id.to_string()),
+                "success",
+            );
+            self.record_timeline(chat_id, fallback).await;
+        }
+        let raw_content = (recovery == empty_answer::Recovery::Summary)
+            .then(|| Self::post_process(workspace_settings, &mut response))
+            .flatten();
+        self.message_service.update(
+            assistant_message_id.to_string(),
+            response.content.clone(),
+            response.reasoning.clone(),
+            None,
+        )?;
+
+        self.merge_message_metadata(assistant_message_id, |metadata| {
+            metadata[empty_answer::EMPTY_RESPONSE_RECOVERED_METADATA_KEY] =
+                serde_json::json!(recovery.as_str());
+            if let Some(raw) = &raw_content {
+                PostProcessingService::record_raw_content(metadata, raw);
+            }
+        })?;
+        Ok(response)
+    }
+
+    /// Put original values back into an answer to a redacted request, when
+    /// the workspace allows it.
+    fn unredact_response(
+        &self,
+        chat_id: &str,
+        redaction: Option<&RedactionSummary>,
+        response: &mut LLMChatResponse,
+    ) -> Result<(), AppError> {
+        if redaction.is_some_and(|s| s.unredacted) {
+            self.redaction_service
+                .unredact_response(chat_id, response)?;
+        }
+        Ok(())
+    }
+
+    /// Model decision for a message sent from a prompt (`prompt_id` in its
+    /// metadata). None when the message did not come from a prompt, or the
+    /// prompt is gone or has no model preference.
+    fn prompt_model_pin(
+        &self,
+        app: &AppHandle,
+        metadata: Option<&str>,
+        connection: &LLMConnection,
+        model: &str,
+    ) -> Result<Option<(String, ModelPin)>, AppError> {
+        let Some(prompt_id) = metadata
+            .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+            .and_then(|m| m.get("prompt_id")?.as_str().map(str::to_string))
+        else {
+            return Ok(None);
+        };
+        let prompt = app
+            .state::<crate::state::AppState>()
+            .prompt_service
+            .get_by_id(&prompt_id)?;
+        let Some(prompt) = prompt.filter(|p| !p.model_preference.is_empty()) else {
+            return Ok(None);
+        };
+
+        let connections = self.llm_connection_service.get_all()?;
+        let pin = model_pinning::resolve_model_pin(
+            &prompt.model_preference,
+            connection,
+            model,
+            &connections,
+        );
+        Ok(Some((prompt_id, pin)))
+    }
+
+    fn connection_prompt_suffix(connection: &LLMConnection) -> Option<&str> {
+        connection
+            .system_prompt_suffix
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    }
+
+    /// Short SHA-256 of the suffix, enough to tell which version was applied.
+    fn suffix_hash(suffix: &str) -> String {
+        let digest = <sha2::Sha256 as sha2::Digest>::digest(suffix.as_bytes());
+        hex::encode(&digest[..8])
+    }
+
+    /// What an answer's metadata keeps of the connection's system prompt
+    /// suffix, when it has one.
+    fn connection_suffix_metadata(connection: &LLMConnection) -> Option<serde_json::Value> {
+        let suffix = Self::connection_prompt_suffix(connection)?;
+        Some(serde_json::json!({
+            "applied": true,
+            "hash": Self::suffix_hash(suffix),
+        }))
+    }
+
+    pub fn get_instructions(&self, chat_id: &str) -> Result<Option<String>, AppError> {
+        Ok(self
+            .repository
+            .get_by_id(chat_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?
+            .custom_instructions)
+    }
+
+    /// Store the chat's custom instructions as `Chat::normalize_instructions`
+    /// has them, and return what was stored.
+    pub fn set_instructions(
+        &self,
+        chat_id: &str,
+        instructions: Option<&str>,
+    ) -> Result<Option<String>, AppError> {
+        let instructions = Chat::normalize_instructions(instructions)?;
+        self.repository
+            .set_custom_instructions(chat_id, instructions.as_deref())?;
+        Ok(instructions)
+    }
+
+    /// The branches of a chat and the one it shows.
+    pub fn list_branches(&self, chat_id: &str) -> Result<ChatBranches, AppError> {
+        Ok(ChatBranches {
+            active_branch_id: self.repository.get_active_branch(chat_id)?,
+            branches: self.repository.get_branches(chat_id)?,
+        })
+    }
+
+    /// Fork the active branch after `from_message_id`, or before its first
+    /// message, and switch to the new branch. What follows the fork stays on
+    /// the branch it was written on.
+    pub fn create_branch(
+        &self,
+        chat_id: &str,
+        from_message_id: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<ChatBranch, AppError> {
+        self.ensure_no_turn_running(chat_id)?;
+        let fork_timestamp = match from_message_id {
+            Some(id) => Some(
+                self.message_service
+                    .get_by_chat_id(chat_id)?
+                    .iter()
+                    .find(|m| m.id == id)
+                    .ok_or_else(|| {
+                        AppError::NotFound(format!("Message {id} is not on the active branch"))
+                    })?
+                    .timestamp,
+            ),
+            None => None,
+        };
+        let existing = self.repository.get_branches(chat_id)?;
+        let branch = ChatBranch {
+            id: uuid::Uuid::new_v4().to_string(),
+            chat_id: chat_id.to_string(),
+            parent_branch_id: self.repository.get_active_branch(chat_id)?,
+            fork_message_id: from_message_id.map(str::to_string),
+            fork_timestamp,
+            name: name
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map_or_else(|| format!("Branch {}", existing.len() + 1), str::to_string),
+            created_at: chrono::Utc::now().timestamp_millis(),
+            message_count: 0,
+        };
+        self.repository.create_branch(&branch)?;
+        self.repository
+            .set_active_branch(chat_id, Some(&branch.id))?;
+        Ok(branch)
+    }
+
+    /// Show another branch of a chat, `None` being the main branch. Returns
+    /// the messages of the branch.
+    pub fn switch_branch(
+        &self,
+        chat_id: &str,
+        branch_id: Option<&str>,
+    ) -> Result<Vec<Message>, AppError> {
+        self.ensure_no_turn_running(chat_id)?;
+        if let Some(id) = branch_id {
+            if !self
+                .repository
+                .get_branches(chat_id)?
+                .iter()
+                .any(|b| b.id == id)
+            {
+                return Err(AppError::NotFound(format!(
+                    "Branch {id} not found in chat {chat_id}"
+                )));
+            }
+        }
+        self.repository.set_active_branch(chat_id, branch_id)?;
+        self.message_service.get_by_chat_id(chat_id)
+    }
+
+    /// Branches and variants change the history a running turn reads and
+    /// writes.
+    fn ensure_no_turn_running(&self, chat_id: &str) -> Result<(), AppError> {
+        if self.get_active_chat_status(chat_id)?.phase == ChatPhase::Idle {
+            Ok(())
+        } else {
+            Err(AppError::Validation(
+                "Wait for the current answer to finish first".to_string(),
+            ))
+        }
+    }
+
+    /// Current scratchpad for a chat (empty when none has been written).
+    pub fn get_scratchpad(&self, chat_id: &str) -> Result<String, AppError> {
+        scratchpad::read(self.repository.as_ref(), chat_id)
+    }
+
+    pub fn set_scratchpad(&self, chat_id: &str, content: &str) -> Result<(), AppError> {
+        scratchpad::write(self.repository.as_ref(), chat_id, content)
+    }
+
+    /// Fold all but the recent turns of a chat into its summary now, whether
+    /// or not the workspace summarizes automatically. Returns the summary
+    /// prompts will use, `None` when the chat is too short to have one.
+    pub async fn summarize_chat(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+    ) -> Result<Option<ChatSummary>, AppError> {
+        let chat = self
+            .repository
+            .get_by_id(chat_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
+        let workspace_settings = self
+            .workspace_settings_service
+            .get_by_workspace_id(&chat.workspace_id)?
+            .ok_or_else(|| AppError::Validation("Workspace settings not found".to_string()))?;
+        let (connection, model) =
+            model_pinning::resolve_turn_model(None, None, &workspace_settings, |id| {
+                self.llm_connection_service.get_by_id(id)
+            })?;
+        let history = self.message_service.get_by_chat_id(chat_id)?;
+        let stored = self.repository.get_summary(chat_id)?;
+
+        let refreshed = self
+            .refresh_summary(
+                app,
+                &chat,
+                &workspace_settings,
+                &history,
+                &connection,
+                &model,
+                stored.as_ref(),
+            )
+            .await?;
+        Ok(refreshed.or(stored))
+    }
+
+    /// The chat's summary for a turn's prompt. It is refreshed first when the
+    /// workspace summarizes automatically and the chat is due; a refresh that
+    /// fails is logged and the stored summary used.
+    #[allow(clippy::too_many_arguments)]
+    async fn turn_summary(
+        &self,
+        app: &AppHandle,
+        chat: &Chat,
+        workspace_settings: &WorkspaceSettings,
+        history: &[Message],
+        connection: &LLMConnection,
+        model: &str,
+        budget: Option<&ContextBudget>,
+    ) -> Option<ChatSummary> {
+        let stored = self.repository.get_summary(&chat.id).unwrap_or_else(|e| {
+            tracing::warn!(chat_id = %chat.id, error = %e, "Failed to read chat summary");
+            None
+        });
+        let pending = summary::unsummarized(history, stored.as_ref());
+        let over_budget = budget.is_some_and(|b| b.fit_history(pending.clone(), 0).1.is_some());
+        if !summary::is_due(
+            &pending,
+            workspace_settings.summarize_after_messages,
+            over_budget,
+        ) {
+            return stored;
+        }
+
+        match self
+            .refresh_summary(
+                app,
+                chat,
+                workspace_settings,
+                history,
+                connection,
+                model,
+                stored.as_ref(),
+            )
+            .await
+        {
+            Ok(refreshed) => refreshed.or(stored),
+            Err(e) => {
+                tracing::warn!(chat_id = %chat.id, error = %e, "Failed to summarize chat");
+                stored
+            }
+        }
+    }
+
+    /// Ask the workspace's summary model to fold the turns before the recent
+    /// ones into `previous`, and store the result. `None` when there are no
+    /// such turns.
+    #[allow(clippy::too_many_arguments)]
+    async fn refresh_summary(
+        &self,
+        app: &AppHandle,
+        chat: &Chat,
+        workspace_settings: &WorkspaceSettings,
+        history: &[Message],
+        connection: &LLMConnection,
+        model: &str,
+        previous: Option<&ChatSummary>,
+    ) -> Result<Option<ChatSummary>, AppError> {
+        let pending = summary::unsummarized(history, previous);
+        let Some(recent) = summary::recent_start(&pending, summary::RECENT_TURNS) else {
+            return Ok(None);
+        };
+        let folded = &pending[..recent];
+        let Some(through) = folded.last().map(|m| m.timestamp) else {
+            return Ok(None);
+        };
+
+        let mut messages = summary::summary_prompt(previous.map(|s| s.content.as_str()), folded);
+        let redaction =
+            self.redact_request(&chat.id, workspace_settings, connection, &mut messages)?;
+        let model = workspace_settings
+            .summary_model
+            .clone()
+            .unwrap_or_else(|| model.to_string());
+        let request = LLMChatRequest {
+            model: model.clone(),
+            messages,
+            temperature: Some(0.2),
+            max_tokens: Some(summary::SUMMARY_MAX_TOKENS),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: ModelFilter::for_connection(connection),
+            connection_config: Some(ConnectionConfig::for_connection(connection)),
+            seed: None,
+            output_limits: None,
+        };
+
+        // Dummy ids keep the request's events away from the chat's messages
+        let usage_message_id = format!("summary_{}", chat.id);
+        let started = std::time::Instant::now();
+        let result = self
+            .llm_service
+            .chat(
+                &connection.base_url,
+                Some(&connection.api_key),
+                request,
+                "system_auto_summary".to_string(),
+                usage_message_id.clone(),
+                app.clone(),
+                None,
+                &connection.provider,
+            )
+            .await;
+        let latency = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        if let Err(e) = self.usage_service.record_usage_with_type(
+            chat.workspace_id.clone(),
+            chat.id.clone(),
+            usage_message_id,
+            connection.provider.clone(),
+            model,
+            result.as_ref().ok().and_then(|r| r.usage.clone()),
+            latency,
+            false,
+            if result.is_ok() { "success" } else { "error" }.to_string(),
+            summary::SUMMARY_REQUEST_TYPE.to_string(),
+        ) {
+            tracing::error!(error = ?e, "Failed to record summary usage");
+        }
+
+        let mut response = result?;
+        self.unredact_response(&chat.id, redaction.as_ref(), &mut response)?;
+        let content = response.content.trim();
+        if content.is_empty() {
+            return Err(AppError::Llm(
+                "The model returned an empty chat summary".to_string(),
+            ));
+        }
+        let refreshed = ChatSummary {
+            content: content.to_string(),
+            through,
+        };
+        self.repository.set_summary(&chat.id, Some(&refreshed))?;
+        Ok(Some(refreshed))
+    }
+
+    /// One chat for export, with the images its messages reference embedded.
+    /// The chunks of the workspace's documents a turn on `message` is
+    /// given. A turn goes on without them when retrieval fails.
+    async fn turn_knowledge(&self, workspace_id: &str, message: &str) -> Vec<KnowledgeHit> {
+        self.knowledge_service
+            .retrieve(workspace_id, message)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::warn!(workspace_id = %workspace_id, error = %e, "Failed to retrieve document chunks");
+                Vec::new()
+            })
+    }
+
+    pub fn export_chat(&self, chat_id: &str) -> Result<ChatExport, AppError> {
+        let chat = self
+            .repository
+            .get_by_id(chat_id)?
+            .ok_or_else(|| AppError::NotFound(format!("Chat {chat_id} not found")))?;
+        Ok(ChatExport {
+            version: export::CHAT_EXPORT_VERSION,
+            exported_at: chrono::Utc::now().timestamp_millis(),
+            workspace_id: None,
+            chats: vec![self.exported_chat(&chat)?],
+        })
+    }
+
+    /// All chats of a workspace for export, oldest first.
+    pub fn export_workspace(&self, workspace_id: &str) -> Result<ChatExport, AppError> {
+        let mut chats = self.repository.get_by_workspace_id(workspace_id)?;
+        chats.sort_by_key(|chat| chat.created_at);
+        Ok(ChatExport {
+            version: export::CHAT_EXPORT_VERSION,
+            exported_at: chrono::Utc::now().timestamp_millis(),
+            workspace_id: Some(workspace_id.to_string()),
+            chats: chats
+                .iter()
+                .map(|chat| self.exported_chat(chat))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn exported_chat(&self, chat: &Chat) -> Result<ExportedChat, AppError> {
+        let mut exported =
+            export::exported_chat(chat, &self.message_service.get_by_chat_id(&chat.id)?);
+        for message in &mut exported.messages {
+            message.content = self
+                .attachment_service
+                .embed_file_references(&message.content);
+        }
+        Ok(exported)
+    }
+
+    /// An export in `format`; HTML pages embed the attached images that are
+    /// still readable.
+    pub fn render_export(
+        &self,
+        export: &ChatExport,
+        format: ExportFormat,
+    ) -> Result<String, AppError> {
+        export::render_export(export, format, |file| {
+            if file.path.starts_with("data:image/") {
+                return Some(file.path.clone());
+            }
+            let path = std::path::Path::new(&file.path);
+            (file_uri::mime_type_for_path(&file.path).starts_with("image/")
+                && self.attachment_service.is_file_access_allowed(&file.path))
+            .then(|| file_uri::data_url(path))
+            .flatten()
+        })
+    }
+
+    /// Run the `read_scratchpad` / `update_scratchpad` builtin tools for a
+    /// chat, telling the UI when the scratchpad changed.
+    fn execute_scratchpad_tool(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        tool_name: &str,
+        arguments: &serde_json::Value,
+    ) -> Result<serde_json::Value, AppError> {
+        let (result, updated) =
+            scratchpad::run_tool(self.repository.as_ref(), chat_id, tool_name, arguments)?;
+        if let Some(updated) = updated {
+            ToolEmitter::new(app.clone()).emit_scratchpad_updated(chat_id.to_string(), updated)?;
+        }
+        Ok(result)
+    }
+
+    /// Put the chat's current scratchpad at the end of the system message,
+    /// replacing any earlier copy. Safe to call before every request.
+    fn apply_scratchpad(
+        &self,
+        chat_id: &str,
+        messages: &mut Vec<ChatMessage>,
+    ) -> Result<(), AppError> {
+        scratchpad::inject(&self.get_scratchpad(chat_id)?, messages);
+        Ok(())
+    }
+
+    /// Called when the user focuses or navigates to a workspace. If the
+    /// workspace's connection has `keep_warm` set, its model is warmed up in the
+    /// background and pinged again every `KEEP_WARM_INTERVAL` until another
+    /// workspace becomes active. Warm-up failures are only logged.
+    pub fn notify_workspace_active(&self, app: AppHandle, workspace_id: String) {
+        if self.activity_halt.is_halted() {
+            return;
+        }
+        let generation = self.llm_service.begin_keep_warm();
+        tokio::spawn(async move {
+            let state = app.state::<crate::state::AppState>();
+            let chat_service = &state.chat_service;
+
+            while chat_service.llm_service.is_keep_warm_current(generation) {
+                if !chat_service.warm_up_workspace_model(&workspace_id).await {
+                    break;
+                }
+                tokio::time::sleep(KEEP_WARM_INTERVAL).await;
+            }
         });
     }
 
-    /// Check tool permissions and filter allowed tools
-    async fn check_and_filter_tool_permissions(
+    /// Send one warm-up request for the workspace's model, unless it was warmed
+    /// recently. Returns false when there is nothing to keep warm.
+    async fn warm_up_workspace_model(&self, workspace_id: &str) -> bool {
+        let Ok(Some(settings)) = self
+            .workspace_settings_service
+            .get_by_workspace_id(workspace_id)
+        else {
+            return false;
+        };
+        let Some(conn_id) = settings.llm_connection_id else {
+            return false;
+        };
+        let connection = match self.llm_connection_service.get_by_id(&conn_id) {
+            Ok(Some(conn)) if conn.enabled && conn.keep_warm => conn,
+            _ => return false,
+        };
+        if !LLMService::supports_warmup(&connection.provider) {
+            return false;
+        }
+        let Some(model) = settings
+            .default_model
+            .or_else(|| connection.default_model.clone())
+            .filter(|m| !m.is_empty())
+        else {
+            return false;
+        };
+
+        if !self.llm_service.try_claim_warmup(
+            &connection.id,
+            &model,
+            chrono::Utc::now().timestamp_millis(),
+        ) {
+            return true;
+        }
+
+        let start = std::time::Instant::now();
+        let status = self
+            .llm_service
+            .warm_up_status(
+                &connection.base_url,
+                Some(&connection.api_key),
+                &connection.provider,
+                &model,
+            )
+            .await;
+
+        if let Err(e) = self.usage_service.record_warmup_usage(
+            workspace_id.to_string(),
+            connection.provider,
+            model,
+            u64::try_from(start.elapsed().as_millis()).unwrap_or(u64::MAX),
+            status.to_string(),
+        ) {
+            tracing::warn!(error = %e, "Failed to record warm-up usage");
+        }
+        true
+    }
+
+    /// Execute a batch of tool calls. Calls that need permission wait for the
+    /// user while the others run; denied or timed-out calls get a tool result
+    /// saying so instead of failing the turn, so the model can adapt. The same
+    /// goes for calls over the turn's tool call caps: `budget` counts every
+    /// call right before it is dispatched, the ones that run without asking
+    /// first, since they start while the user is still deciding. In an agent
+    /// chat the agent's tool permissions apply instead of the workspace's.
+    /// Up to the workspace's `max_parallel_tools` calls run at once.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_tool_calls(
         &self,
         app: &AppHandle,
         chat_id: &str,
+        agent_id: Option<&str>,
         assistant_message_id: &str,
-        tool_calls: Vec<crate::models::llm_types::ToolCall>,
+        tool_calls: &[crate::models::llm_types::ToolCall],
         workspace_settings: &WorkspaceSettings,
-    ) -> Result<Vec<crate::models::llm_types::ToolCall>, AppError> {
-        let tool_emitter = ToolEmitter::new(app.clone());
+        budget: &mut TurnToolBudget,
+        cancellation_rx: &mut tokio::sync::broadcast::Receiver<()>,
+    ) -> Result<Vec<ChatMessage>, AppError> {
+        // Calls whose arguments were cut off and could not be repaired never
+        // run; the model gets a tool result asking it to send them again
+        let (complete_calls, truncated_calls): (Vec<_>, Vec<_>) = tool_calls
+            .iter()
+            .cloned()
+            .partition(json_repair::has_valid_arguments);
+        // Answers the user chose to keep settle their tools' calls first
+        let permission_config = match agent_id {
+            Some(agent_id) => self.agent_manager.tool_permissions(agent_id),
+            None => tool_permissions::parse_permission_config(
+                workspace_settings.tool_permission_config.as_deref(),
+            ),
+        };
+        let (remembered_allowed, remembered_denied, complete_calls) =
+            tool_permissions::take_remembered(
+                complete_calls,
+                &self.repository.get_tool_permissions(chat_id)?,
+                &permission_config,
+            );
+        let (mut auto_calls, gated_calls) = match agent_id {
+            Some(_) => tool_permissions::partition_by_agent_permission(
+                &complete_calls,
+                &permission_config,
+                workspace_settings.agent_tool_permission.as_deref(),
+            ),
+            None => tool_permissions::partition_by_permission(
+                &complete_calls,
+                workspace_settings.tool_permission_config.as_deref(),
+            ),
+        };
+        auto_calls.extend(remembered_allowed);
+        let (auto_calls, mut clamped_calls) = budget.admit(auto_calls);
+        let max_parallel = tool_concurrency::max_parallel_tools(workspace_settings);
+
+        let (mut results, permission) = tokio::join!(
+            self.execute_tool_batch(
+                chat_id,
+                assistant_message_id,
+                &auto_calls,
+                max_parallel,
+                app,
+                cancellation_rx
+            ),
+            self.check_and_filter_tool_permissions(app, chat_id, assistant_message_id, gated_calls),
+        );
+        let (approved_calls, skipped_calls) = permission?;
+        // Denied calls never reach the budget
+        let (approved_calls, clamped_approved) = budget.admit(approved_calls);
+        clamped_calls.extend(clamped_approved);
+
+        results.extend(
+            self.execute_tool_batch(
+                chat_id,
+                assistant_message_id,
+                &approved_calls,
+                max_parallel,
+                app,
+                cancellation_rx,
+            )
+            .await,
+        );
+        for (tool_call, reason) in &skipped_calls {
+            results.push(self.record_skipped_tool_call(
+                app,
+                chat_id,
+                assistant_message_id,
+                tool_call,
+                *reason,
+            )?);
+        }
+        for tool_call in &remembered_denied {
+            results.push(self.record_skipped_tool_call(
+                app,
+                chat_id,
+                assistant_message_id,
+                tool_call,
+                SkipReason::AlwaysDenied,
+            )?);
+        }
+        for tool_call in &truncated_calls {
+            results.push(self.record_skipped_tool_call(
+                app,
+                chat_id,
+                assistant_message_id,
+                tool_call,
+                SkipReason::TruncatedArguments,
+            )?);
+        }
+        if !clamped_calls.is_empty() {
+            for (tool_call, reason) in &clamped_calls {
+                results.push(self.record_skipped_tool_call(
+                    app,
+                    chat_id,
+                    assistant_message_id,
+                    tool_call,
+                    *reason,
+                )?);
+            }
+            AgentEmitter::new(app.clone()).emit_agent_loop_warning(
+                chat_id.to_string(),
+                assistant_message_id.to_string(),
+                "tool_call_cap".to_string(),
+                tool_caps::clamp_warning(&clamped_calls),
+                clamped_calls
+                    .iter()
+                    .map(|(tool_call, _)| tool_call.id.clone())
+                    .collect(),
+            )?;
+        }
 
-        // Parse tool permission config
-        let tool_permission_config: HashMap<String, String> =
-            if let Some(config_str) = &workspace_settings.tool_permission_config {
-                serde_json::from_str(config_str).unwrap_or_default()
-            } else {
-                HashMap::new()
-            };
+        Ok(tool_permissions::order_results(tool_calls, results))
+    }
 
-        // Check if ANY tool requires permission
-        let require_permission = tool_calls.iter().any(|tc| {
-            match tool_permission_config
-                .get(&tc.function.name)
-                .map(std::string::String::as_str)
-            {
-                Some("require") => true,
-                _ => false,
-            }
-        });
+    /// Ask the user about the gated calls of a batch and wait for the answer.
+    /// Returns the calls that may run and the skipped ones with their reason.
+    async fn check_and_filter_tool_permissions(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        assistant_message_id: &str,
+        gated_calls: Vec<crate::models::llm_types::ToolCall>,
+    ) -> Result<
+        (
+            Vec<crate::models::llm_types::ToolCall>,
+            Vec<(crate::models::llm_types::ToolCall, SkipReason)>,
+        ),
+        AppError,
+    > {
+        if gated_calls.is_empty() {
+            return Ok((Vec::new(), Vec::new()));
+        }
 
-        if require_permission {
+        let ask = async {
             // Create oneshot channel for approval response
             let (tx, rx) = tokio::sync::oneshot::channel::<crate::state::PermissionDecision>();
 
             // Store sender in AppState
             {
-                use tauri::Manager;
                 let app_state: tauri::State<crate::state::AppState> = app.state();
                 let mut pending = app_state.pending_tool_permissions.lock().map_err(|e| {
                     AppError::Generic(format!("Failed to lock pending_tool_permissions: {e}"))
@@ -1354,79 +4413,574 @@ impl ChatService {
                 pending.insert(assistant_message_id.to_string(), tx);
             }
 
-            // Emit tool permission request event
-            let permission_tool_calls: Vec<crate::events::ToolCall> = tool_calls
+            // Only the gated calls are shown to the user; the rest are already running
+            let mask_secrets = self.masks_secrets(chat_id);
+            let permission_tool_calls: Vec<crate::events::ToolCall> = gated_calls
                 .iter()
-                .map(|tc| crate::events::ToolCall {
-                    id: tc.id.clone(),
-                    name: tc.function.name.clone(),
-                    arguments: serde_json::from_str(tc.function.arguments.trim())
-                        .unwrap_or_else(|_| serde_json::json!({})),
+                .map(|tc| {
+                    let arguments = serde_json::from_str(tc.function.arguments.trim())
+                        .unwrap_or_else(|_| serde_json::json!({}));
+                    crate::events::ToolCall {
+                        id: tc.id.clone(),
+                        name: tc.function.name.clone(),
+                        arguments: if mask_secrets {
+                            secrets::mask_value(&arguments).unwrap_or(arguments)
+                        } else {
+                            arguments
+                        },
+                    }
                 })
                 .collect();
 
-            tool_emitter.emit_tool_permission_request(
+            self.update_status(app, chat_id, StatusChange::PermissionWait);
+            ToolEmitter::new(app.clone()).emit_tool_permission_request(
                 chat_id.to_string(),
                 assistant_message_id.to_string(),
                 permission_tool_calls,
             )?;
 
-            // Wait for user approval with timeout (60 seconds)
-            let decision = match tokio::time::timeout(tokio::time::Duration::from_secs(60), rx)
-                .await
+            let wait_start = PhaseStart::now();
+            let outcome = match tokio::time::timeout(tool_permissions::PERMISSION_TIMEOUT, rx).await
             {
-                Ok(Ok(decision)) => decision,
-                Ok(Err(_)) => {
-                    return Err(AppError::Generic(
-                        "Tool permission request cancelled".to_string(),
-                    ));
-                }
+                Ok(Ok(decision)) => PermissionOutcome::Decided(decision),
+                Ok(Err(_)) => PermissionOutcome::Cancelled,
                 Err(_) => {
                     // Timeout cleanup
-                    use tauri::Manager;
                     let app_state: tauri::State<crate::state::AppState> = app.state();
                     let mut pending = app_state.pending_tool_permissions.lock().map_err(|e| {
                         AppError::Generic(format!("Failed to lock pending_tool_permissions: {e}"))
                     })?;
                     pending.remove(assistant_message_id);
-
-                    return Err(AppError::Generic(
-                        "Tool permission request timed out (60s)".to_string(),
-                    ));
+                    PermissionOutcome::TimedOut
                 }
             };
+            self.update_status(app, chat_id, StatusChange::PermissionResolved);
+            let subject = gated_calls
+                .iter()
+                .map(|tc| tc.function.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let wait = wait_start.finish(
+                TimelinePhase::PermissionWait,
+                subject,
+                Some(assistant_message_id.to_string()),
+                outcome.as_str(),
+            );
+            self.record_timeline(chat_id, wait).await;
+            Ok::<_, AppError>(outcome)
+        };
+        let outcome =
+            oneshot::permission_outcome(self.headless_auto_deny(chat_id).await, ask).await?;
+
+        if let PermissionOutcome::Decided(decision) = &outcome {
+            if let Some(scope) = decision.remember {
+                // The answer still applies to this batch if it cannot be kept
+                if let Err(e) =
+                    self.remember_tool_permissions(chat_id, scope, &gated_calls, decision)
+                {
+                    tracing::warn!(chat_id = %chat_id, error = %e, "Failed to remember tool permission answer");
+                }
+            }
+        }
 
-            // If not approved, return error
-            if !decision.approved {
-                return Err(AppError::Generic(
-                    "Tool execution denied by user".to_string(),
-                ));
+        Ok(tool_permissions::resolve_gated(gated_calls, &outcome))
+    }
+
+    /// Keep an "always" answer for the tools it was given for. In an agent
+    /// chat the workspace scope is the agent's tool permissions, which its
+    /// chats follow instead of the workspace's.
+    fn remember_tool_permissions(
+        &self,
+        chat_id: &str,
+        scope: RememberScope,
+        gated_calls: &[crate::models::llm_types::ToolCall],
+        decision: &crate::state::PermissionDecision,
+    ) -> Result<(), AppError> {
+        let permissions = tool_permissions::remembered_permissions(gated_calls, decision);
+        match scope {
+            RememberScope::Chat => {
+                for (tool, permission) in &permissions {
+                    self.repository
+                        .set_tool_permission(chat_id, tool, Some(permission))?;
+                }
+            }
+            RememberScope::Workspace => {
+                let chat = self
+                    .repository
+                    .get_by_id(chat_id)?
+                    .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
+                match &chat.agent_id {
+                    Some(agent_id) => {
+                        let mut agent_permissions = self.agent_manager.tool_permissions(agent_id);
+                        agent_permissions.extend(permissions.clone());
+                        self.agent_manager
+                            .set_tool_permissions(agent_id, agent_permissions)
+                            .map_err(|e| AppError::Generic(e.to_string()))?;
+                    }
+                    None => self
+                        .workspace_settings_service
+                        .merge_tool_permissions(&chat.workspace_id, permissions.clone())?,
+                }
             }
+        }
+        tracing::info!(chat_id = %chat_id, ?scope, ?permissions, "Remembered tool permission answer");
+        Ok(())
+    }
 
-            // Filter tool calls based on allowed_tool_ids
-            let filtered_calls: Vec<_> = tool_calls
+    /// The "always" answers in effect in a workspace: the entries of its
+    /// tool permission config that let a tool run or refuse it, and the
+    /// answers kept for its chats. An agent's are in its own tool permissions.
+    pub fn list_remembered_tool_permissions(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<RememberedToolPermission>, AppError> {
+        let config = self
+            .workspace_settings_service
+            .get_stored(workspace_id)?
+            .and_then(|settings| settings.tool_permission_config);
+        let mut remembered: Vec<RememberedToolPermission> =
+            tool_permissions::parse_permission_config(config.as_deref())
                 .into_iter()
-                .filter(|tc| decision.allowed_tool_ids.contains(&tc.id))
+                .filter(|(_, permission)| permission != tool_permissions::PERMISSION_REQUIRE)
+                .map(|(tool_name, permission)| RememberedToolPermission {
+                    scope: RememberScope::Workspace,
+                    chat_id: None,
+                    tool_name,
+                    permission,
+                })
                 .collect();
+        remembered.extend(
+            self.repository
+                .get_workspace_tool_permissions(workspace_id)?,
+        );
+        Ok(remembered)
+    }
+
+    /// Forget an "always" answer, so the tool asks again: in `chat_id`, or
+    /// for the whole workspace when it is `None`.
+    pub fn revoke_remembered_tool_permission(
+        &self,
+        workspace_id: &str,
+        tool_name: &str,
+        chat_id: Option<&str>,
+    ) -> Result<(), AppError> {
+        match chat_id {
+            Some(chat_id) => {
+                self.repository
+                    .get_by_id(chat_id)?
+                    .filter(|chat| chat.workspace_id == workspace_id)
+                    .ok_or_else(|| AppError::NotFound(format!("Chat not found: {chat_id}")))?;
+                self.repository
+                    .set_tool_permission(chat_id, tool_name, None)
+            }
+            None => self.workspace_settings_service.merge_tool_permissions(
+                workspace_id,
+                std::collections::BTreeMap::from([(
+                    tool_name.to_string(),
+                    tool_permissions::PERMISSION_REQUIRE.to_string(),
+                )]),
+            ),
+        }
+    }
+
+    /// Ask the user to confirm the cost of a turn whose first call is
+    /// estimated above `confirm_above_estimated_cost`. A turn that is not
+    /// approved leaves no answer behind: its placeholder message is removed
+    /// and it fails with `AppError::CostNotConfirmed`.
+    async fn confirm_turn_cost(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        assistant_message_id: &str,
+        workspace_settings: &WorkspaceSettings,
+        llm_request: &LLMChatRequest,
+        cancellation_rx: &mut tokio::sync::broadcast::Receiver<()>,
+    ) -> Result<(), AppError> {
+        let assumed_completion_tokens = workspace_settings
+            .assumed_completion_tokens
+            .and_then(|tokens| u32::try_from(tokens).ok())
+            .unwrap_or(cost_gate::DEFAULT_ASSUMED_COMPLETION_TOKENS);
+        // The agent loop makes up to max_iterations + 1 calls after this one
+        let loop_calls = workspace_settings
+            .max_agent_iterations
+            .and_then(|n| u32::try_from(n).ok())
+            .unwrap_or(25)
+            .saturating_add(1);
+        let estimate = cost_gate::estimate_turn_cost(
+            &llm_request.model,
+            &llm_request.messages,
+            llm_request.tools.as_deref(),
+            assumed_completion_tokens,
+            loop_calls,
+        );
+        let Some((threshold, estimate)) = cost_gate::confirmation_needed(
+            workspace_settings.confirm_above_estimated_cost,
+            estimate,
+        ) else {
+            return Ok(());
+        };
+
+        let wait_start = PhaseStart::now();
+        let outcome = if self.headless_auto_deny(chat_id).await {
+            CostConfirmationOutcome::AutoDenied
+        } else {
+            self.update_status(app, chat_id, StatusChange::PermissionWait);
+            let event = crate::events::CostConfirmationRequestEvent {
+                chat_id: chat_id.to_string(),
+                message_id: assistant_message_id.to_string(),
+                threshold,
+                estimate,
+                timeout_secs: cost_gate::COST_CONFIRMATION_TIMEOUT.as_secs(),
+            };
+            let outcome = self
+                .cost_confirmations
+                .confirm(
+                    assistant_message_id,
+                    cost_gate::COST_CONFIRMATION_TIMEOUT,
+                    cancellation_rx,
+                    || super::ChatEmitter::new(app.clone()).emit_cost_confirmation_request(event),
+                )
+                .await;
+            self.update_status(app, chat_id, StatusChange::PermissionResolved);
+            outcome?
+        };
+        let wait = wait_start.finish(
+            TimelinePhase::PermissionWait,
+            "cost confirmation".to_string(),
+            Some(assistant_message_id.to_string()),
+            outcome.as_str(),
+        );
+        self.record_timeline(chat_id, wait).await;
+
+        let Some(error) = outcome.into_error() else {
+            return Ok(());
+        };
+        tracing::info!(chat_id = %chat_id, outcome = outcome.as_str(), "Turn dropped before its model call");
+        self.message_service
+            .delete(assistant_message_id.to_string())?;
+        MessageEmitter::new(app.clone()).emit_message_cancelled(
+            chat_id.to_string(),
+            assistant_message_id.to_string(),
+            String::new(),
+        )?;
+        Err(error)
+    }
+
+    /// Answer the cost confirmation a turn is waiting on.
+    pub fn respond_cost_confirmation(
+        &self,
+        message_id: &str,
+        approved: bool,
+    ) -> Result<(), AppError> {
+        self.cost_confirmations.respond(message_id, approved)
+    }
+
+    /// Answer a completion request of the MCP server of `mcp_connection_id`
+    /// with the LLM connection of the most recently updated workspace using
+    /// it, once the user approves. Declined and unanswered requests fail
+    /// with `Cancelled`.
+    pub async fn sample_for_mcp(
+        &self,
+        app: &AppHandle,
+        mcp_connection_id: &str,
+        server_name: &str,
+        params: CreateMessageRequestParams,
+    ) -> Result<CreateMessageResult, AppError> {
+        self.activity_halt.ensure_running()?;
+        let workspace_id = self
+            .workspace_settings_service
+            .workspaces_using_mcp_connection(mcp_connection_id)?
+            .into_iter()
+            .next()
+            .ok_or_else(|| {
+                AppError::Validation(format!(
+                    "No workspace with an LLM connection uses MCP server {server_name}"
+                ))
+            })?;
+        let settings = self
+            .workspace_settings_service
+            .get_by_workspace_id(&workspace_id)?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("Workspace settings not found: {workspace_id}"))
+            })?;
+        let connection_id = settings.llm_connection_id.clone().ok_or_else(|| {
+            AppError::Validation("No LLM connection configured for workspace".to_string())
+        })?;
+        let connection = self
+            .llm_connection_service
+            .get_by_id(&connection_id)?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("LLM connection not found: {connection_id}"))
+            })?;
+        let model = mcp_sampling::pick_model(
+            &params,
+            &mcp_sampling::model_ids(connection.models_json.as_deref()),
+            settings
+                .default_model
+                .clone()
+                .or_else(|| connection.default_model.clone())
+                .filter(|m| !m.is_empty()),
+        )
+        .ok_or_else(|| AppError::Validation("No model available for sampling".to_string()))?;
+        let max_tokens = mcp_sampling::max_tokens(&params);
+
+        let request_id = uuid::Uuid::new_v4().to_string();
+        let outcome = self
+            .sampling_approvals
+            .approve(&request_id, mcp_sampling::SAMPLING_APPROVAL_TIMEOUT, || {
+                crate::events::emit_event(
+                    app,
+                    McpSamplingRequestEvent {
+                        request_id: request_id.clone(),
+                        mcp_connection_id: mcp_connection_id.to_string(),
+                        server_name: server_name.to_string(),
+                        workspace_id: workspace_id.clone(),
+                        llm_connection_id: connection.id.clone(),
+                        model: model.clone(),
+                        system_prompt: params.system_prompt.clone(),
+                        messages: mcp_sampling::preview_messages(&params),
+                        max_tokens,
+                        timeout_secs: mcp_sampling::SAMPLING_APPROVAL_TIMEOUT.as_secs(),
+                    },
+                )
+            })
+            .await?;
+        if outcome != SamplingOutcome::Approved {
+            tracing::info!(
+                server = %server_name,
+                outcome = ?outcome,
+                "MCP sampling request not approved"
+            );
+            return Err(AppError::Cancelled);
+        }
+
+        let mut messages = mcp_sampling::chat_messages(&params);
+        let redaction_scope = format!("mcp_sampling_{request_id}");
+        let redaction =
+            self.redact_request(&redaction_scope, &settings, &connection, &mut messages)?;
+        let request = LLMChatRequest {
+            model: model.clone(),
+            messages,
+            temperature: params.temperature,
+            max_tokens: Some(max_tokens),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: ModelFilter::for_connection(&connection),
+            connection_config: Some(ConnectionConfig::for_connection(&connection)),
+            seed: None,
+            output_limits: None,
+        };
+
+        // Dummy ids keep the request's events away from any chat
+        let started = std::time::Instant::now();
+        let result = self
+            .llm_service
+            .chat(
+                &connection.base_url,
+                Some(&connection.api_key),
+                request,
+                "system_mcp_sampling".to_string(),
+                request_id.clone(),
+                app.clone(),
+                None,
+                &connection.provider,
+            )
+            .await;
+        let latency = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+        if let Err(e) = self.usage_service.record_usage_with_type(
+            workspace_id,
+            String::new(),
+            mcp_connection_id.to_string(),
+            connection.provider.clone(),
+            model.clone(),
+            result.as_ref().ok().and_then(|r| r.usage.clone()),
+            latency,
+            false,
+            if result.is_ok() { "success" } else { "error" }.to_string(),
+            mcp_sampling::SAMPLING_REQUEST_TYPE.to_string(),
+        ) {
+            tracing::error!(error = ?e, "Failed to record MCP sampling usage");
+        }
+
+        let mut response = result?;
+        self.unredact_response(&redaction_scope, redaction.as_ref(), &mut response)?;
+        Ok(mcp_sampling::create_message_result(
+            response.content,
+            response.model.unwrap_or(model),
+            response.finish_reason.as_deref(),
+        ))
+    }
+
+    /// Answer a pending MCP sampling request.
+    pub fn respond_mcp_sampling(&self, request_id: &str, approved: bool) -> Result<(), AppError> {
+        self.sampling_approvals.respond(request_id, approved)
+    }
+
+    /// End the cost confirmations and MCP sampling requests waiting for the
+    /// user, returning how many of each there were.
+    pub fn cancel_pending_confirmations(&self) -> Result<(usize, usize), AppError> {
+        Ok((
+            self.cost_confirmations.cancel_all()?,
+            self.sampling_approvals.cancel_all()?,
+        ))
+    }
 
-            if filtered_calls.is_empty() {
-                return Err(AppError::Generic("No tools allowed to run".to_string()));
+    /// Execute tool calls, reporting a batch failure instead of returning it
+    /// so the agent loop keeps going.
+    async fn execute_tool_batch(
+        &self,
+        chat_id: &str,
+        assistant_message_id: &str,
+        tool_calls: &[crate::models::llm_types::ToolCall],
+        max_parallel: usize,
+        app: &AppHandle,
+        cancellation_rx: &tokio::sync::broadcast::Receiver<()>,
+    ) -> Vec<ChatMessage> {
+        if tool_calls.is_empty() {
+            return Vec::new();
+        }
+        self.update_status(app, chat_id, StatusChange::ToolExec);
+
+        match self
+            .handle_tool_calls(
+                chat_id,
+                assistant_message_id,
+                tool_calls,
+                max_parallel,
+                app,
+                cancellation_rx,
+            )
+            .await
+        {
+            Ok(results) => results,
+            Err(e) => {
+                tracing::error!(
+                    chat_id = %chat_id,
+                    error = ?e,
+                    "Tool execution failed in agent loop"
+                );
+                // Emit error event to notify frontend
+                let tool_emitter = ToolEmitter::new(app.clone());
+                let _ = tool_emitter.emit_tool_execution_error(
+                    chat_id.to_string(),
+                    assistant_message_id.to_string(),
+                    "agent_loop_error".to_string(),
+                    "tool_execution".to_string(),
+                    format!("Tool execution failed: {e}"),
+                );
+                Vec::new()
             }
+        }
+    }
+
+    /// Store a tool call that did not run, with an error result for the model.
+    fn record_skipped_tool_call(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        assistant_message_id: &str,
+        tool_call: &crate::models::llm_types::ToolCall,
+        reason: SkipReason,
+    ) -> Result<ChatMessage, AppError> {
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let masked_arguments = if self.masks_secrets(chat_id) {
+            secrets::mask_arguments(&tool_call.function.arguments)
+        } else {
+            None
+        };
+        let mut tool_call_data = serde_json::json!({
+            "name": tool_call.function.name,
+            "arguments": masked_arguments.as_deref().unwrap_or(&tool_call.function.arguments),
+            "error": reason.message(),
+            "status": "error",
+        });
+        tool_call_data[reason.field()] = serde_json::json!(reason.as_str());
+        self.message_service.create(
+            format!("tool_call_{}", tool_call.id),
+            chat_id.to_string(),
+            "tool_call".to_string(),
+            serde_json::to_string(&tool_call_data)?,
+            Some(timestamp),
+            Some(assistant_message_id.to_string()),
+            None,
+            masked_arguments
+                .is_some()
+                .then(|| serde_json::json!({ SECRETS_MASKED_METADATA_KEY: true }).to_string()),
+        )?;
+
+        let result = tool_permissions::skipped_result(tool_call, reason);
+        if let ChatMessage::Tool { content, .. } = &result {
+            self.message_service.create(
+                format!("tool_result_{}", tool_call.id),
+                chat_id.to_string(),
+                "tool".to_string(),
+                content.clone(),
+                Some(timestamp),
+                None,
+                Some(tool_call.id.clone()),
+                None,
+            )?;
+        }
 
-            return Ok(filtered_calls);
+        ToolEmitter::new(app.clone()).emit_tool_execution_progress(
+            chat_id.to_string(),
+            assistant_message_id.to_string(),
+            tool_call.id.clone(),
+            tool_call.function.name.clone(),
+            "error".to_string(),
+            None,
+            Some(reason.message().to_string()),
+        )?;
+
+        // A denial the user just clicked is not news; timeouts and headless denials are
+        if !reason.is_permission() {
+            return Ok(result);
+        }
+        if let Ok(Some(chat)) = self.repository.get_by_id(chat_id) {
+            self.activity_service.record(
+                app,
+                ActivityService::entry(
+                    &chat.workspace_id,
+                    chat_id,
+                    ActivityKind::ToolDenied,
+                    &format!("{}: {}", tool_call.function.name, reason.message()),
+                    matches!(
+                        reason,
+                        SkipReason::Denied | SkipReason::Cancelled | SkipReason::AlwaysDenied
+                    ),
+                ),
+            );
+            self.webhook_dispatcher.dispatch(WebhookEvent::new(
+                ActivityKind::ToolDenied,
+                &chat.workspace_id,
+                chat_id,
+                &format!("{}: {}", tool_call.function.name, reason.message()),
+            ));
         }
 
-        Ok(tool_calls)
+        Ok(result)
     }
 
-    /// Handle tool calls - execute tools and return results
+    /// Handle tool calls - execute tools and return results. Up to
+    /// `max_parallel` calls run at once, each reporting its own progress; the
+    /// results are in the order of `tool_calls`.
     async fn handle_tool_calls(
         &self,
         chat_id: &str,
         assistant_message_id: &str,
         tool_calls: &[crate::models::llm_types::ToolCall],
+        max_parallel: usize,
         app: &AppHandle,
-        cancellation_rx: &mut tokio::sync::broadcast::Receiver<()>,
+        cancellation_rx: &tokio::sync::broadcast::Receiver<()>,
     ) -> Result<Vec<ChatMessage>, AppError> {
         // Emit tool execution started event
         let tool_emitter = ToolEmitter::new(app.clone());
@@ -1436,10 +4990,6 @@ impl ChatService {
             tool_calls.len(),
         )?;
 
-        let mut tool_results: Vec<ChatMessage> = Vec::new();
-        let mut successful_count = 0;
-        let mut failed_count = 0;
-
         // Get MCP connections to find which connection each tool belongs to
         // Get chat info to determine context (Workspace or Agent)
         let chat = self
@@ -1449,6 +4999,9 @@ impl ChatService {
 
         let workspace_id = chat.workspace_id;
         let agent_id = chat.agent_id;
+        let mask_secrets = self
+            .workspace_settings_service
+            .secret_masking_enabled(&workspace_id);
 
         // Prepare execution context
         let (tool_to_connection, agent_client) = if let Some(aid) = &agent_id {
@@ -1460,332 +5013,507 @@ impl ChatService {
                 .map_err(|e| AppError::Generic(e.to_string()))?;
             (HashMap::new(), Some(client))
         } else {
-            // Workspace Context: Get tool mapping
-            let map = self
-                .tool_service
-                .get_tool_to_connection_map(&workspace_id)?;
-            (map, None)
+            // Workspace Context: Get tool mapping
+            let map = self
+                .tool_service
+                .get_tool_to_connection_map(&workspace_id)?;
+            (map, None)
+        };
+
+        // Each call gets its own receiver so a cancellation reaches all of them
+        let outcomes = tool_concurrency::run_bounded(
+            tool_calls.iter().map(|tool_call| {
+                self.execute_tool_call(
+                    app,
+                    chat_id,
+                    assistant_message_id,
+                    &workspace_id,
+                    agent_id.as_deref(),
+                    mask_secrets,
+                    &tool_to_connection,
+                    agent_client.as_ref(),
+                    tool_call,
+                    cancellation_rx.resubscribe(),
+                )
+            }),
+            max_parallel,
+        )
+        .await;
+
+        let mut tool_results: Vec<ChatMessage> = Vec::with_capacity(outcomes.len());
+        let mut successful_count = 0;
+        let mut failed_count = 0;
+        for outcome in outcomes {
+            let (result, succeeded) = outcome?;
+            if succeeded {
+                successful_count += 1;
+            } else {
+                failed_count += 1;
+            }
+            tool_results.push(result);
+        }
+
+        // Emit tool execution completed event
+        tool_emitter.emit_tool_execution_completed(
+            chat_id.to_string(),
+            assistant_message_id.to_string(),
+            tool_calls.len(),
+            successful_count,
+            failed_count,
+        )?;
+
+        Ok(tool_results)
+    }
+
+    /// Run one tool call of a batch: store it, execute it and store its
+    /// result. Returns the result for the model and whether the tool ran
+    /// without error.
+    #[allow(clippy::too_many_arguments)]
+    async fn execute_tool_call(
+        &self,
+        app: &AppHandle,
+        chat_id: &str,
+        assistant_message_id: &str,
+        workspace_id: &str,
+        agent_id: Option<&str>,
+        mask_secrets: bool,
+        tool_to_connection: &HashMap<String, String>,
+        agent_client: Option<&std::sync::Arc<rust_mcp_sdk::mcp_client::ClientRuntime>>,
+        tool_call: &crate::models::llm_types::ToolCall,
+        mut cancellation_rx: tokio::sync::broadcast::Receiver<()>,
+    ) -> Result<(ChatMessage, bool), AppError> {
+        let tool_emitter = ToolEmitter::new(app.clone());
+
+        // Create tool_call message in database
+        let tool_call_message_id = format!("tool_call_{}", tool_call.id);
+        let tool_call_timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        // Shown and stored masked; the tool still gets the original arguments
+        let masked_arguments = if mask_secrets {
+            secrets::mask_arguments(&tool_call.function.arguments)
+        } else {
+            None
         };
+        let shown_arguments = masked_arguments
+            .as_deref()
+            .unwrap_or(&tool_call.function.arguments);
+        let mut tool_call_metadata = serde_json::json!({});
+        if masked_arguments.is_some() {
+            tool_call_metadata[SECRETS_MASKED_METADATA_KEY] = serde_json::json!(true);
+        }
 
-        // Execute each tool call
-        for tool_call in tool_calls {
-            // Create tool_call message in database
-            let tool_call_message_id = format!("tool_call_{}", tool_call.id);
-            let tool_call_timestamp = std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)
-                .unwrap()
-                .as_millis() as i64;
-
-            let tool_call_data = serde_json::json!({
-                "name": tool_call.function.name,
-                "arguments": tool_call.function.arguments,
-                "status": "executing"
-            });
+        let tool_call_data = serde_json::json!({
+            "name": tool_call.function.name,
+            "arguments": shown_arguments,
+            "status": "executing"
+        });
 
-            self.message_service.create(
-                tool_call_message_id.clone(),
-                chat_id.to_string(),
-                "tool_call".to_string(),
-                serde_json::to_string(&tool_call_data)?,
-                Some(tool_call_timestamp),
-                Some(assistant_message_id.to_string()),
-                None,
-                None,
-            )?;
+        self.message_service.create(
+            tool_call_message_id.clone(),
+            chat_id.to_string(),
+            "tool_call".to_string(),
+            serde_json::to_string(&tool_call_data)?,
+            Some(tool_call_timestamp),
+            Some(assistant_message_id.to_string()),
+            None,
+            masked_arguments
+                .is_some()
+                .then(|| tool_call_metadata.to_string()),
+        )?;
 
-            // Emit progress event immediately after creating tool_call message (before execution)
-            // This allows UI to display the tool_call message right away
-            tool_emitter.emit_tool_execution_progress(
-                chat_id.to_string(),
-                assistant_message_id.to_string(),
-                tool_call.id.clone(),
-                tool_call.function.name.clone(),
-                "executing".to_string(),
-                None,
-                None,
-            )?;
+        // Emit progress event immediately after creating tool_call message (before execution)
+        // This allows UI to display the tool_call message right away
+        tool_emitter.emit_tool_execution_progress(
+            chat_id.to_string(),
+            assistant_message_id.to_string(),
+            tool_call.id.clone(),
+            tool_call.function.name.clone(),
+            "executing".to_string(),
+            None,
+            None,
+        )?;
 
-            // Log tool execution start for debugging
-            tracing::debug!(
-                tool = %tool_call.function.name,
-                chat_id = %chat_id,
-                tool_call_id = %tool_call.id,
-                "Starting tool execution"
-            );
+        // Log tool execution start for debugging
+        tracing::debug!(
+            tool = %tool_call.function.name,
+            chat_id = %chat_id,
+            tool_call_id = %tool_call.id,
+            "Starting tool execution"
+        );
 
-            // Find connection for this tool
-            // Execute tool logic
-            let execution_result = if let Some(client) = &agent_client {
-                // Agent Execution
-                let arguments_str = tool_call.function.arguments.trim();
-                let arguments_map = if arguments_str.is_empty() {
-                    Some(serde_json::Map::new())
-                } else {
-                    match serde_json::from_str::<serde_json::Value>(arguments_str) {
-                        Ok(serde_json::Value::Object(map)) => Some(map),
-                        Ok(_) => Some(serde_json::Map::new()),
-                        Err(e) => {
-                            return Err(AppError::Validation(format!("Invalid arguments: {e}")));
-                        }
+        let tool_connection_id = agent_id
+            .map(str::to_string)
+            .or_else(|| tool_to_connection.get(&tool_call.function.name).cloned());
+        let execution_start = std::time::Instant::now();
+        let phase_start = PhaseStart::now();
+
+        // Find connection for this tool
+        // Execute tool logic
+        let execution_result = if let Some(client) = agent_client {
+            // Agent Execution
+            let arguments_str = tool_call.function.arguments.trim();
+            let arguments_map = if arguments_str.is_empty() {
+                Some(serde_json::Map::new())
+            } else {
+                match serde_json::from_str::<serde_json::Value>(arguments_str) {
+                    Ok(serde_json::Value::Object(map)) => Some(map),
+                    Ok(_) => Some(serde_json::Map::new()),
+                    Err(e) => {
+                        self.spawn_record_tool_usage(
+                            workspace_id,
+                            chat_id,
+                            assistant_message_id,
+                            &tool_call.function.name,
+                            tool_connection_id,
+                            "error",
+                            execution_start.elapsed(),
+                            0,
+                        );
+                        return Err(AppError::Validation(format!("Invalid arguments: {e}")));
                     }
-                };
+                }
+            };
 
-                let params = CallToolRequestParams {
-                    name: tool_call.function.name.clone(),
-                    arguments: arguments_map,
-                };
+            let params = CallToolRequestParams {
+                name: tool_call.function.name.clone(),
+                arguments: arguments_map,
+            };
 
-                // Call tool on agent client with timeout (60 seconds) and cancellation support
-                let tool_call_future = client.call_tool(params);
-
-                // Use tokio::select to handle both timeout and cancellation
-                tokio::select! {
-                    result = tokio::time::timeout(tokio::time::Duration::from_secs(60), tool_call_future) => {
-                        match result {
-                            Ok(Ok(res)) => {
-                                // Serialize content to match expected generic JSON
-                                match serde_json::to_value(&res.content) {
-                                    Ok(value) => {
-                                        // Try to extract text content to simplify response for LLM
-                                        let extracted_text = if let serde_json::Value::Array(items) = &value
-                                        {
-                                            let mut texts = Vec::new();
-                                            let mut has_text = false;
-
-                                            for item in items {
-                                                if let Some(type_str) =
-                                                    item.get("type").and_then(|t| t.as_str())
-                                                {
-                                                    if type_str == "text" {
-                                                        if let Some(text) =
-                                                            item.get("text").and_then(|t| t.as_str())
-                                                        {
-                                                            texts.push(text);
-                                                            has_text = true;
-                                                        }
-                                                    } else if type_str == "image" {
-                                                        // Used by some tools to return screenshots
-                                                        texts.push("[Image Content]");
+            // Call tool on agent client with timeout (60 seconds) and cancellation support
+            let tool_call_future = client.call_tool(params);
+
+            // Use tokio::select to handle both timeout and cancellation
+            tokio::select! {
+                result = tokio::time::timeout(tokio::time::Duration::from_secs(60), tool_call_future) => {
+                    match result {
+                        Ok(Ok(res)) => {
+                            // Serialize content to match expected generic JSON
+                            match serde_json::to_value(&res.content) {
+                                Ok(value) => {
+                                    // Try to extract text content to simplify response for LLM
+                                    let extracted_text = if let serde_json::Value::Array(items) = &value
+                                    {
+                                        let mut texts = Vec::new();
+                                        let mut has_text = false;
+
+                                        for item in items {
+                                            if let Some(type_str) =
+                                                item.get("type").and_then(|t| t.as_str())
+                                            {
+                                                if type_str == "text" {
+                                                    if let Some(text) =
+                                                        item.get("text").and_then(|t| t.as_str())
+                                                    {
+                                                        texts.push(text);
+                                                        has_text = true;
                                                     }
+                                                } else if type_str == "image" {
+                                                    // Used by some tools to return screenshots
+                                                    texts.push("[Image Content]");
                                                 }
                                             }
+                                        }
 
-                                            if has_text {
-                                                Some(texts.join("\n\n"))
-                                            } else {
-                                                None
-                                            }
+                                        if has_text {
+                                            Some(texts.join("\n\n"))
                                         } else {
                                             None
-                                        };
-
-                                        if let Some(text) = extracted_text {
-                                            Ok(serde_json::Value::String(text))
-                                        } else {
-                                            Ok(value)
                                         }
+                                    } else {
+                                        None
+                                    };
+
+                                    if let Some(text) = extracted_text {
+                                        Ok(serde_json::Value::String(text))
+                                    } else {
+                                        Ok(value)
                                     }
-                                    Err(e) => Err(AppError::Generic(format!(
-                                        "Failed to serialize tool response: {e}"
-                                    ))),
                                 }
+                                Err(e) => Err(AppError::Generic(format!(
+                                    "Failed to serialize tool response: {e}"
+                                ))),
                             }
-                            Ok(Err(e)) => Err(AppError::Generic(format!("Tool execution failed: {e}"))),
-                            Err(_) => Err(AppError::Generic(
-                                "Tool execution timed out after 60 seconds".to_string(),
-                            )),
                         }
+                        Ok(Err(e)) => Err(AppError::Generic(format!("Tool execution failed: {e}"))),
+                        Err(_) => Err(AppError::Generic(
+                            "Tool execution timed out after 60 seconds".to_string(),
+                        )),
                     }
-                    _ = cancellation_rx.recv() => {
-                        // Cancellation received
-                        Err(AppError::Cancelled)
-                    }
                 }
+                _ = cancellation_rx.recv() => {
+                    // Cancellation received
+                    Err(AppError::Cancelled)
+                }
+            }
+        } else {
+            // Standard Execution
+            let connection_id = match tool_to_connection.get(&tool_call.function.name) {
+                Some(id) => id,
+                None => {
+                    // Return error instead of using ?, so it gets handled in match below
+                    &String::new() // Dummy value, will be caught below
+                }
+            };
+
+            // Validate connection exists first
+            if connection_id.is_empty() {
+                Err(AppError::Validation(format!(
+                    "Tool {} not found in any MCP connection",
+                    tool_call.function.name
+                )))
             } else {
-                // Standard Execution
-                let connection_id = match tool_to_connection.get(&tool_call.function.name) {
-                    Some(id) => id,
-                    None => {
-                        // Return error instead of using ?, so it gets handled in match below
-                        &String::new() // Dummy value, will be caught below
-                    }
-                };
+                let arguments_str = tool_call.function.arguments.trim();
 
-                // Validate connection exists first
-                if connection_id.is_empty() {
-                    Err(AppError::Validation(format!(
-                        "Tool {} not found in any MCP connection",
-                        tool_call.function.name
-                    )))
-                } else {
-                    let arguments_str = tool_call.function.arguments.trim();
+                // Parse arguments - return error instead of propagating with ?
+                let arguments_result: Result<serde_json::Value, AppError> =
+                    if arguments_str.is_empty() {
+                        Ok(serde_json::json!({}))
+                    } else {
+                        serde_json::from_str(arguments_str).map_err(|e| {
+                            AppError::Validation(format!(
+                                "Failed to parse tool arguments for '{}': {} (arguments: '{}')",
+                                tool_call.function.name,
+                                e,
+                                shown_arguments.trim()
+                            ))
+                        })
+                    };
 
-                    // Parse arguments - return error instead of propagating with ?
-                    let arguments_result: Result<serde_json::Value, AppError> =
-                        if arguments_str.is_empty() {
-                            Ok(serde_json::json!({}))
-                        } else {
-                            serde_json::from_str(arguments_str).map_err(|e| {
-                                AppError::Validation(format!(
-                                    "Failed to parse tool arguments for '{}': {} (arguments: '{}')",
-                                    tool_call.function.name, e, arguments_str
-                                ))
-                            })
-                        };
-
-                    // Only proceed with execution if arguments parsed successfully
-                    match arguments_result {
-                        Ok(arguments) => {
-                            // Execute with timeout and cancellation support
-                            let tool_exec_future = self.tool_service.execute_tool(
-                                connection_id,
-                                &tool_call.function.name,
-                                arguments,
-                            );
-
-                            tokio::select! {
-                                result = tokio::time::timeout(tokio::time::Duration::from_secs(60), tool_exec_future) => {
-                                    match result {
-                                        Ok(r) => r,
-                                        Err(_) => Err(AppError::Generic(
-                                            "Tool execution timed out after 60 seconds".to_string(),
-                                        )),
-                                    }
-                                }
-                                _ = cancellation_rx.recv() => {
-                                    Err(AppError::Cancelled)
+                // Only proceed with execution if arguments parsed successfully
+                match arguments_result {
+                    // Scratchpad tools act on this chat, so they run here
+                    Ok(arguments)
+                        if connection_id == "builtin"
+                            && SCRATCHPAD_TOOLS.contains(&tool_call.function.name.as_str()) =>
+                    {
+                        self.execute_scratchpad_tool(
+                            app,
+                            chat_id,
+                            &tool_call.function.name,
+                            &arguments,
+                        )
+                    }
+                    Ok(arguments) => {
+                        // Execute with timeout and cancellation support
+                        let tool_exec_future = self.tool_service.execute_tool(
+                            workspace_id,
+                            connection_id,
+                            &tool_call.function.name,
+                            arguments,
+                        );
+
+                        tokio::select! {
+                            result = tokio::time::timeout(tokio::time::Duration::from_secs(60), tool_exec_future) => {
+                                match result {
+                                    Ok(r) => r,
+                                    Err(_) => Err(AppError::Generic(
+                                        "Tool execution timed out after 60 seconds".to_string(),
+                                    )),
                                 }
                             }
+                            _ = cancellation_rx.recv() => {
+                                Err(AppError::Cancelled)
+                            }
                         }
-                        Err(e) => Err(e), // Return parse error to be handled below
                     }
+                    Err(e) => Err(e), // Return parse error to be handled below
                 }
-            };
+            }
+        };
 
-            let result = match execution_result {
-                Ok(result) => {
-                    successful_count += 1;
+        let execution_duration = execution_start.elapsed();
+        let mut execution_status = "success";
+
+        let result = match execution_result {
+            Ok(result) => {
+                // Images the tool returned are stored and previewed as references
+                let image_hashes = self.store_tool_result_images(&result);
+                self.reference_stored_files(&mut tool_call_metadata, &image_hashes)?;
+
+                // Update tool_call message with success
+                let mut completed_data = serde_json::json!({
+                    "name": tool_call.function.name,
+                    "arguments": shown_arguments,
+                    "result": result,
+                    "status": "completed"
+                });
+                if !image_hashes.is_empty() {
+                    completed_data["preview"] = serde_json::json!(file_uri::image_references(
+                        TOOL_IMAGE_LABEL,
+                        &image_hashes
+                    )
+                    .join("\n\n"));
+                }
+                self.message_service.update(
+                    tool_call_message_id.clone(),
+                    serde_json::to_string(&completed_data)?,
+                    None,
+                    None,
+                )?;
 
-                    // Update tool_call message with success
-                    let completed_data = serde_json::json!({
-                        "name": tool_call.function.name,
-                        "arguments": tool_call.function.arguments,
-                        "result": result,
-                        "status": "completed"
-                    });
-                    self.message_service.update(
-                        tool_call_message_id.clone(),
-                        serde_json::to_string(&completed_data)?,
-                        None,
-                        None,
-                    )?;
+                // Emit progress event
+                tool_emitter.emit_tool_execution_progress(
+                    chat_id.to_string(),
+                    assistant_message_id.to_string(),
+                    tool_call.id.clone(),
+                    tool_call.function.name.clone(),
+                    "completed".to_string(),
+                    Some(result.clone()),
+                    None,
+                )?;
 
-                    // Emit progress event
-                    tool_emitter.emit_tool_execution_progress(
-                        chat_id.to_string(),
-                        assistant_message_id.to_string(),
-                        tool_call.id.clone(),
-                        tool_call.function.name.clone(),
-                        "completed".to_string(),
-                        Some(result.clone()),
-                        None,
-                    )?;
+                // Log successful tool execution
+                tracing::debug!(
+                    tool = %tool_call.function.name,
+                    chat_id = %chat_id,
+                    tool_call_id = %tool_call.id,
+                    "Tool execution completed"
+                );
 
-                    // Log successful tool execution
-                    tracing::debug!(
-                        tool = %tool_call.function.name,
-                        chat_id = %chat_id,
-                        tool_call_id = %tool_call.id,
-                        "Tool execution completed"
-                    );
+                result
+            }
+            Err(e) => {
+                // Tool errors often echo the arguments they were given
+                let error_msg = e.to_string();
+                let error_msg = if mask_secrets {
+                    secrets::mask_text(&error_msg).unwrap_or(error_msg)
+                } else {
+                    error_msg
+                };
+                execution_status = match &e {
+                    AppError::Cancelled => "cancelled",
+                    _ if error_msg.contains("timed out") => "timeout",
+                    _ => "error",
+                };
 
-                    result
-                }
-                Err(e) => {
-                    failed_count += 1;
-                    let error_msg = e.to_string();
-
-                    // Log error for debugging
-                    tracing::error!(
-                        tool = %tool_call.function.name,
-                        chat_id = %chat_id,
-                        error = %error_msg,
-                        "Tool execution failed"
-                    );
+                // Log error for debugging
+                tracing::error!(
+                    tool = %tool_call.function.name,
+                    chat_id = %chat_id,
+                    error = %error_msg,
+                    "Tool execution failed"
+                );
 
-                    // Update tool_call message with error
-                    let error_data = serde_json::json!({
-                        "name": tool_call.function.name,
-                        "arguments": tool_call.function.arguments,
-                        "error": error_msg,
-                        "status": "error"
-                    });
-                    self.message_service.update(
-                        tool_call_message_id.clone(),
-                        serde_json::to_string(&error_data)?,
-                        None,
-                        None,
-                    )?;
+                // Update tool_call message with error
+                let error_data = serde_json::json!({
+                    "name": tool_call.function.name,
+                    "arguments": shown_arguments,
+                    "error": error_msg,
+                    "status": "error"
+                });
+                self.message_service.update(
+                    tool_call_message_id.clone(),
+                    serde_json::to_string(&error_data)?,
+                    None,
+                    None,
+                )?;
 
-                    // Emit error event
-                    tool_emitter.emit_tool_execution_error(
-                        chat_id.to_string(),
-                        assistant_message_id.to_string(),
-                        tool_call.id.clone(),
-                        tool_call.function.name.clone(),
-                        error_msg.clone(),
-                    )?;
+                // Emit error event
+                tool_emitter.emit_tool_execution_error(
+                    chat_id.to_string(),
+                    assistant_message_id.to_string(),
+                    tool_call.id.clone(),
+                    tool_call.function.name.clone(),
+                    error_msg.clone(),
+                )?;
 
-                    // Emit progress event with error
-                    tool_emitter.emit_tool_execution_progress(
-                        chat_id.to_string(),
-                        assistant_message_id.to_string(),
-                        tool_call.id.clone(),
-                        tool_call.function.name.clone(),
-                        "error".to_string(),
-                        None,
-                        Some(error_msg.clone()),
-                    )?;
+                // Emit progress event with error
+                tool_emitter.emit_tool_execution_progress(
+                    chat_id.to_string(),
+                    assistant_message_id.to_string(),
+                    tool_call.id.clone(),
+                    tool_call.function.name.clone(),
+                    "error".to_string(),
+                    None,
+                    Some(error_msg.clone()),
+                )?;
 
-                    serde_json::json!({ "error": error_msg })
-                }
-            };
+                serde_json::json!({ "error": error_msg })
+            }
+        };
 
-            // Create tool result message
-            let tool_result_message_id = format!("tool_result_{}", tool_call.id);
-            let tool_result_timestamp = tool_call_timestamp;
+        let result_content = serde_json::to_string(&result)?;
 
-            self.message_service.create(
-                tool_result_message_id,
-                chat_id.to_string(),
-                "tool".to_string(),
-                serde_json::to_string(&result)?,
-                Some(tool_result_timestamp),
-                None,
-                Some(tool_call.id.clone()),
-                None,
+        // Describe successful results so the UI can choose a renderer cheaply
+        if execution_status == "success" {
+            let descriptor = ToolResultDescriptor::describe(&result, &result_content);
+            tool_call_metadata["resultDescriptor"] = serde_json::json!(descriptor);
+            self.message_service.update_metadata(
+                tool_call_message_id.clone(),
+                Some(tool_call_metadata.to_string()),
             )?;
-
-            // Add tool result to conversation
-            tool_results.push(ChatMessage::Tool {
-                content: serde_json::to_string(&result)?,
-                tool_call_id: tool_call.id.clone(),
-            });
         }
 
-        // Emit tool execution completed event
-        tool_emitter.emit_tool_execution_completed(
+        self.spawn_record_tool_usage(
+            workspace_id,
+            chat_id,
+            assistant_message_id,
+            &tool_call.function.name,
+            tool_connection_id,
+            execution_status,
+            execution_duration,
+            result_content.len(),
+        );
+        let tool_entry = phase_start.finish(
+            TimelinePhase::ToolExec,
+            tool_call.function.name.clone(),
+            Some(tool_call.id.clone()),
+            execution_status,
+        );
+        self.record_timeline(chat_id, tool_entry).await;
+
+        // Create tool result message
+        let tool_result_message_id = format!("tool_result_{}", tool_call.id);
+        let tool_result_timestamp = tool_call_timestamp;
+
+        self.message_service.create(
+            tool_result_message_id,
             chat_id.to_string(),
-            assistant_message_id.to_string(),
-            tool_calls.len(),
-            successful_count,
-            failed_count,
+            "tool".to_string(),
+            result_content.clone(),
+            Some(tool_result_timestamp),
+            None,
+            Some(tool_call.id.clone()),
+            None,
         )?;
 
-        Ok(tool_results)
+        let result = ChatMessage::Tool {
+            content: result_content,
+            tool_call_id: tool_call.id.clone(),
+        };
+        Ok((result, execution_status == "success"))
+    }
+
+    /// Record a tool execution in the usage stats without blocking the agent loop.
+    #[allow(clippy::too_many_arguments)]
+    fn spawn_record_tool_usage(
+        &self,
+        workspace_id: &str,
+        chat_id: &str,
+        message_id: &str,
+        tool_name: &str,
+        connection_id: Option<String>,
+        status: &str,
+        duration: std::time::Duration,
+        result_bytes: usize,
+    ) {
+        self.usage_service.record_tool_usage_detached(
+            workspace_id.to_string(),
+            chat_id.to_string(),
+            message_id.to_string(),
+            tool_name.to_string(),
+            connection_id,
+            status.to_string(),
+            u64::try_from(duration.as_millis()).unwrap_or(u64::MAX),
+            u64::try_from(result_bytes).unwrap_or(u64::MAX),
+        );
     }
 
     /// Prepare messages for agent loop - includes tool calls and tool results
+    #[allow(clippy::too_many_arguments)]
     fn prepare_messages_for_agent_loop(
         &self,
         chat_id: &str,
@@ -1793,8 +5521,13 @@ impl ChatService {
         user_content: &str,
         user_metadata: Option<&str>,
         system_prompt_override: Option<String>,
-        provider: Option<&str>,
-    ) -> Result<Vec<ChatMessage>, AppError> {
+        workspace_notes: Option<&str>,
+        chat_instructions: Option<&str>,
+        connection: Option<&LLMConnection>,
+        budget: Option<&ContextBudget>,
+        chat_summary: Option<&ChatSummary>,
+        knowledge: &[KnowledgeHit],
+    ) -> Result<(Vec<ChatMessage>, Option<ContextTrim>), AppError> {
         let existing_messages = self.message_service.get_by_chat_id(chat_id)?;
         self.prepare_messages(
             &existing_messages,
@@ -1803,10 +5536,70 @@ impl ChatService {
             None,
             user_metadata,
             system_prompt_override,
-            provider,
+            workspace_notes,
+            chat_instructions,
+            connection,
+            budget,
+            chat_summary,
+            knowledge,
         )
     }
 
+    /// The workspace notes for the system prompt of a chat, when the
+    /// workspace includes them in context and the chat's agent, if any,
+    /// does not opt out.
+    fn context_notes(&self, workspace_id: &str, agent_id: Option<&str>) -> Option<String> {
+        if agent_id.is_some_and(|id| !self.agent_manager.includes_workspace_notes(id)) {
+            return None;
+        }
+        self.workspace_notes_service
+            .context_notes(workspace_id)
+            .unwrap_or_else(|e| {
+                tracing::warn!(workspace_id = %workspace_id, error = %e, "Failed to read workspace notes");
+                None
+            })
+    }
+
+    /// The custom instructions for the system prompt of a chat, unless its
+    /// agent opts out of them.
+    fn chat_instructions(&self, chat: &Chat) -> Option<String> {
+        let instructions = chat.custom_instructions.clone()?;
+        if chat
+            .agent_id
+            .as_deref()
+            .is_some_and(|id| !self.agent_manager.includes_chat_instructions(id))
+        {
+            return None;
+        }
+        Some(instructions)
+    }
+
+    /// What an answer's metadata keeps of the chat instructions it was
+    /// written under, to tell apart answers from before and after an edit.
+    pub fn chat_instructions_metadata(instructions: Option<&str>) -> Option<serde_json::Value> {
+        let instructions = instructions.map(str::trim).filter(|s| !s.is_empty())?;
+        Some(serde_json::json!({
+            "applied": true,
+            "hash": Self::suffix_hash(instructions),
+        }))
+    }
+
+    /// Large blocks of a stored message, computed once and cached in its metadata
+    fn paste_index(&self, msg: &Message) -> PasteIndex {
+        if let Some(index) = PasteIndex::cached(msg.metadata.as_deref(), &msg.content) {
+            return index;
+        }
+        let index = PasteIndex::compute(&msg.content);
+        if let Err(e) = self.message_service.update_metadata(
+            msg.id.clone(),
+            Some(index.store_in(msg.metadata.as_deref())),
+        ) {
+            tracing::warn!(message_id = %msg.id, error = %e, "Failed to cache paste index");
+        }
+        index
+    }
+
+    #[allow(clippy::too_many_arguments)]
     fn prepare_messages(
         &self,
         existing_messages: &[Message],
@@ -1815,53 +5608,104 @@ impl ChatService {
         user_files: Option<&[String]>,
         user_metadata: Option<&str>,
         system_prompt_override: Option<String>,
-        provider: Option<&str>,
-    ) -> Result<Vec<ChatMessage>, AppError> {
+        workspace_notes: Option<&str>,
+        chat_instructions: Option<&str>,
+        connection: Option<&LLMConnection>,
+        budget: Option<&ContextBudget>,
+        chat_summary: Option<&ChatSummary>,
+        knowledge: &[KnowledgeHit],
+    ) -> Result<(Vec<ChatMessage>, Option<ContextTrim>), AppError> {
         let mut api_messages: Vec<ChatMessage> = Vec::new();
+        let provider = connection.map(|c| c.provider.as_str());
 
         // Add system message if available (allow override)
         let system_message =
             system_prompt_override.or_else(|| workspace_settings.system_message.clone());
 
-        let mut final_system_message = crate::features::chat::prompts::get_app_prompt();
-
-        if let Some(msg) = system_message {
-            if !msg.trim().is_empty() {
-                final_system_message.push_str("\n\n");
-                final_system_message.push_str(&msg);
-            }
-        }
-
         // Inject skills metadata if workspace has selected skills
+        let mut skills_content = String::new();
         if let Some(skill_ids_json) = &workspace_settings.selected_skill_ids {
             let skill_ids: Vec<String> = serde_json::from_str(skill_ids_json).unwrap_or_default();
 
             if !skill_ids.is_empty() {
                 // Use XML for Anthropic, Markdown for others
-                let skills_content =
+                skills_content =
                     if provider.map(|p| p.to_lowercase()).as_deref() == Some("anthropic") {
                         self.skill_service.generate_skills_xml(&skill_ids)?
                     } else {
                         self.skill_service.generate_skills_markdown(&skill_ids)?
                     };
-
-                if !skills_content.is_empty() {
-                    if !final_system_message.is_empty() {
-                        final_system_message.push_str("\n\n");
-                    }
-                    final_system_message.push_str(&skills_content);
-                }
             }
         }
 
+        // Workspace or agent instructions, then the workspace notes and
+        // skills, then the chat's own instructions; connection-level
+        // standing instructions go last
+        let final_system_message = prompts::compose_system_prompt(&SystemPromptLayers {
+            app: prompts::get_app_prompt(&self.app_settings_service.time_formatter()),
+            instructions: system_message.as_deref(),
+            workspace_notes,
+            skills: Some(&skills_content),
+            chat_instructions,
+            connection_instructions: connection.and_then(Self::connection_prompt_suffix),
+        });
+
+        // The chat's summary stands in for the turns it covers, and the
+        // oldest turns are left out when the rest would not fit the model's
+        // context window; a reply still needs the message it answers
+        let knowledge_prompt = knowledge_context::knowledge_prompt(knowledge);
+        let mut fitted = summary::unsummarized(existing_messages, chat_summary);
+        let mut context_trim = None;
+        if let Some(budget) = budget {
+            let knowledge_tokens = knowledge_prompt
+                .as_deref()
+                .map_or(0, |prompt| budget.text_tokens(prompt));
+            let fixed = budget
+                .fixed_tokens(
+                    &final_system_message,
+                    user_content,
+                    user_files.map_or(0, <[String]>::len),
+                )
+                .saturating_add(chat_summary.map_or(0, |s| budget.text_tokens(&s.content)))
+                .saturating_add(knowledge_tokens)
+                .saturating_add(budget.resource_tokens(user_metadata));
+            (fitted, context_trim) = budget.fit_history(fitted, fixed);
+        }
+        let reply_to = crate::features::message::reply_to_from_metadata(user_metadata);
+        let history = reply::keep_reply_anchor(existing_messages, fitted, reply_to.as_deref());
+
         if !final_system_message.trim().is_empty() {
             api_messages.push(ChatMessage::System {
                 content: final_system_message,
             });
         }
+        if let Some(chat_summary) = chat_summary {
+            api_messages.push(summary::summary_message(chat_summary));
+        }
+        if let Some(content) = knowledge_prompt {
+            api_messages.push(ChatMessage::System { content });
+        }
+        let reply_target = |id: &Option<String>| {
+            id.as_deref()
+                .and_then(|id| existing_messages.iter().find(|m| m.id == id))
+        };
+
+        // Pastes repeated across the history are sent once. The agent loop
+        // finds the current message already stored; it is never rewritten
+        let mut paste_deduper =
+            (workspace_settings.paste_dedup_enabled == Some(1)).then(PasteDeduper::default);
+        let current_stored_id = history
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .filter(|m| m.content == user_content)
+            .map(|m| m.id.clone());
+
+        // Calls of history assistant messages come from their tool_call rows
+        let mut history_calls = tool_pairing::history_tool_calls(history.iter().copied());
 
         // Add conversation history (filter out tool_call messages for API)
-        for msg in existing_messages {
+        for msg in history {
             if msg.role == "tool_call" {
                 // Skip tool_call messages - they're UI-only
                 continue;
@@ -1870,95 +5714,42 @@ impl ChatService {
             let chat_msg = match msg.role.as_str() {
                 "user" => {
                     // Check for files and flow in metadata
-                    let mut effective_content = msg.content.clone();
-                    let mut files = None;
-
-                    if let Some(metadata) = &msg.metadata {
-                        // 1. Extract flow description if present
+                    let mut effective_content = reply_target(&msg.reply_to_message_id).map_or_else(
+                        || msg.content.clone(),
+                        |t| reply::quote_reply(t, &msg.content),
+                    );
+                    let files = msg.metadata.as_deref().and_then(|metadata| {
                         if let Some(flow_desc) = self.extract_flow_description(metadata) {
                             effective_content.push_str(&flow_desc);
                         }
-
-                        // 2. Extract files
-                        if let Ok(meta_json) = serde_json::from_str::<serde_json::Value>(metadata) {
-                            // Try new format first (files array)
-                            if let Some(file_array) =
-                                meta_json.get("files").and_then(|f| f.as_array())
-                            {
-                                files = Some(
-                                    file_array
-                                        .iter()
-                                        .filter_map(|f| {
-                                            f.as_str().map(std::string::ToString::to_string)
-                                        })
-                                        .collect::<Vec<String>>(),
-                                );
-                            }
-                            // Fallback to old format (images only)
-                            else if let Some(imgs) =
-                                meta_json.get("images").and_then(|i| i.as_array())
-                            {
-                                files = Some(
-                                    imgs.iter()
-                                        .filter_map(|i| {
-                                            i.as_str().map(std::string::ToString::to_string)
-                                        })
-                                        .collect::<Vec<String>>(),
-                                );
-                            }
+                        if let Some(resources) = mcp_resources::resources_context(metadata) {
+                            effective_content.push_str(&resources);
                         }
-                    }
+                        user_files::metadata_files(metadata)
+                    });
+
+                    let mut content = user_files::user_content(
+                        effective_content,
+                        files.as_deref(),
+                        |path| self.attachment_service.is_file_access_allowed(path),
+                        |path| self.file_part(path),
+                    );
 
-                    let content = if let Some(file_list) = files {
-                        if file_list.is_empty() {
-                            UserContent::Text(effective_content)
+                    if let Some(deduper) = paste_deduper.as_mut() {
+                        let index = self.paste_index(msg);
+                        if current_stored_id.as_ref() == Some(&msg.id) {
+                            deduper.remember(&index);
                         } else {
-                            let mut parts = Vec::new();
-                            // Text part (with flow desc if any)
-                            if !effective_content.is_empty() {
-                                parts.push(ContentPart::Text {
-                                    text: effective_content,
-                                });
-                            }
-                            // File parts
-                            for file_path in file_list {
-                                let (file_content, actual_mime) =
-                                    self.load_file_content(&file_path).unwrap_or((
-                                        file_path.clone(),
-                                        "application/octet-stream".to_string(),
-                                    ));
-
-                                // Check if it's an image to maintain backward compatibility
-                                if actual_mime.starts_with("image/") {
-                                    parts.push(ContentPart::ImageUrl {
-                                        image_url: ImageUrl { url: file_content },
-                                    });
-                                } else {
-                                    parts.push(ContentPart::FileUrl {
-                                        file_url: FileUrl {
-                                            url: file_content,
-                                            mime_type: actual_mime,
-                                        },
-                                    });
-                                }
-                            }
-                            UserContent::Parts(parts)
+                            deduper.dedupe(&mut content, &index);
                         }
-                    } else {
-                        UserContent::Text(effective_content)
-                    };
+                    }
 
                     ChatMessage::User { content }
                 }
-                "assistant" => {
-                    // For assistant messages, we don't reconstruct tool_calls from history
-                    // Tool calls are only included when they're part of the current conversation flow
-                    // In history, we just include the content
-                    ChatMessage::Assistant {
-                        content: AssistantContent::Text(msg.content.clone()),
-                        tool_calls: None,
-                    }
-                }
+                "assistant" => ChatMessage::Assistant {
+                    content: AssistantContent::Text(msg.content.clone()),
+                    tool_calls: history_calls.remove(&msg.id),
+                },
                 "tool" => {
                     // Tool result messages need tool_call_id
                     if let Some(tool_call_id) = &msg.tool_call_id {
@@ -1975,57 +5766,32 @@ impl ChatService {
             };
             api_messages.push(chat_msg);
         }
+        let mut api_messages = tool_pairing::pair_tool_messages(api_messages);
 
-        // Add current user message
-        let mut effective_user_content = user_content.to_string();
+        // Add current user message, quoting the message it replies to
+        let mut effective_user_content = reply_target(&reply_to).map_or_else(
+            || user_content.to_string(),
+            |t| reply::quote_reply(t, user_content),
+        );
         if let Some(metadata) = user_metadata {
             if let Some(flow_desc) = self.extract_flow_description(metadata) {
                 effective_user_content.push_str(&flow_desc);
             }
+            if let Some(resources) = mcp_resources::resources_context(metadata) {
+                effective_user_content.push_str(&resources);
+            }
         }
 
-        let content = if let Some(file_list) = user_files {
-            if file_list.is_empty() {
-                UserContent::Text(effective_user_content)
-            } else {
-                let mut parts = Vec::new();
-                // Text part
-                if !effective_user_content.is_empty() {
-                    parts.push(ContentPart::Text {
-                        text: effective_user_content,
-                    });
-                }
-                // File parts
-                for file_path in file_list {
-                    let (file_content, actual_mime) =
-                        self.load_file_content(file_path).unwrap_or((
-                            file_path.to_string(),
-                            "application/octet-stream".to_string(),
-                        ));
-
-                    // Check if it's an image
-                    if actual_mime.starts_with("image/") {
-                        parts.push(ContentPart::ImageUrl {
-                            image_url: ImageUrl { url: file_content },
-                        });
-                    } else {
-                        parts.push(ContentPart::FileUrl {
-                            file_url: FileUrl {
-                                url: file_content,
-                                mime_type: actual_mime,
-                            },
-                        });
-                    }
-                }
-                UserContent::Parts(parts)
-            }
-        } else {
-            UserContent::Text(effective_user_content)
-        };
+        let content = user_files::user_content(
+            effective_user_content,
+            user_files,
+            |path| self.attachment_service.is_file_access_allowed(path),
+            |path| self.file_part(path),
+        );
 
         api_messages.push(ChatMessage::User { content });
 
-        Ok(api_messages)
+        Ok((api_messages, context_trim))
     }
 }
 
@@ -2102,7 +5868,7 @@ async fn generate_chat_title_internal(
     let system_prompt = "You are a concise chat title generator. Generate a 3-6 word title that captures the intent of the user's prompt. Output only the title without any formatting, quotes, or punctuation.";
     let user_prompt = format!("User Prompt: {user_content}");
 
-    let messages = vec![
+    let mut messages = vec![
         ChatMessage::System {
             content: system_prompt.to_string(),
         },
@@ -2111,11 +5877,40 @@ async fn generate_chat_title_internal(
         },
     ];
 
+    // The title prompt carries the user's message, so it is masked like the chat
+    let workspace_settings = chat_service
+        .repository
+        .get_by_id(&chat_id)
+        .ok()
+        .flatten()
+        .and_then(|chat| {
+            chat_service
+                .workspace_settings_service
+                .get_by_workspace_id(&chat.workspace_id)
+                .ok()
+                .flatten()
+        });
+    let redaction = match &workspace_settings {
+        Some(settings) => {
+            match chat_service.redact_request(&chat_id, settings, &llm_connection, &mut messages) {
+                Ok(redaction) => redaction,
+                Err(e) => {
+                    tracing::error!(chat_id = %chat_id, error = ?e, "Failed to redact title prompt");
+                    return;
+                }
+            }
+        }
+        None => None,
+    };
+
     let request = LLMChatRequest {
         model,
         messages,
         temperature: Some(0.3),
         max_tokens: Some(30),
+        top_p: None,
+        frequency_penalty: None,
+        presence_penalty: None,
         stream: false,
         tools: None,
         tool_choice: None,
@@ -2123,31 +5918,51 @@ async fn generate_chat_title_internal(
         stream_options: None,
         response_modalities: None,
         image_config: None,
+        context_cache_scope: None,
+        model_filter: ModelFilter::for_connection(&llm_connection),
+        connection_config: Some(ConnectionConfig::for_connection(&llm_connection)),
+        seed: None,
+        output_limits: None,
     };
 
-    // 3. Call LLM
+    // 3. Call LLM (identical prompts are answered from the response cache when it is on)
     // Use dummy IDs to avoid interfering with current chat UI
+    let title_model = request.model.clone();
     let result = chat_service
         .llm_service
-        .chat(
+        .chat_cached(
             &llm_connection.base_url,
             Some(&llm_connection.api_key),
             request,
             "system_auto_rename".to_string(),
             format!("rename_{chat_id}"),
             app.clone(),
-            None,
             &llm_connection.provider,
         )
         .await;
 
-    if let Ok(response) = result {
-        let mut title = response.content.trim().to_string();
+    if let Ok((_, true)) = &result {
+        if let Ok(Some(chat)) = chat_service.repository.get_by_id(&chat_id) {
+            if let Err(e) = chat_service.usage_service.record_cached_usage(
+                chat.workspace_id,
+                chat_id.clone(),
+                format!("rename_{chat_id}"),
+                llm_connection.provider.clone(),
+                title_model,
+                "title".to_string(),
+            ) {
+                tracing::error!(error = ?e, "Failed to record cached usage");
+            }
+        }
+    }
 
-        // Clean up any quotes if the model ignored directions
-        if title.starts_with('"') && title.ends_with('"') && title.len() > 2 {
-            title = title[1..title.len() - 1].to_string();
+    if let Ok((mut response, _)) = result {
+        if let Err(e) = chat_service.unredact_response(&chat_id, redaction.as_ref(), &mut response)
+        {
+            tracing::warn!(chat_id = %chat_id, error = ?e, "Failed to unredact chat title");
         }
+        // Clean up any quotes if the model ignored directions
+        let title = crate::lib::text::strip_surrounding_quotes(&response.content).to_string();
 
         if !title.is_empty() {
             // 4. Update Database
@@ -2172,3 +5987,76 @@ async fn generate_chat_title_internal(
         tracing::error!(chat_id = %chat_id, error = ?e, "Error generating chat title");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::connection;
+
+    #[test]
+    fn connection_suffix_goes_last_in_every_system_prompt() {
+        use crate::features::chat::prompts::{compose_system_prompt, SystemPromptLayers};
+        use crate::features::chat::ChatService;
+
+        let mut with_suffix = connection("c1", "openai", "gpt-4o", serde_json::json!([]));
+        with_suffix.system_prompt_suffix = Some("  Answer in English.\n".to_string());
+        let suffix = ChatService::connection_prompt_suffix(&with_suffix);
+        assert_eq!(suffix, Some("Answer in English."));
+        let section = "<connection_instructions>\nAnswer in English.\n</connection_instructions>";
+
+        // An agent's prompt and the chat's instructions come first
+        let agent = compose_system_prompt(&SystemPromptLayers {
+            app: "APP".to_string(),
+            instructions: Some("You are the release agent."),
+            chat_instructions: Some("Be terse."),
+            connection_instructions: suffix,
+            ..SystemPromptLayers::default()
+        });
+        assert_eq!(
+            agent,
+            format!("APP\n\nYou are the release agent.\n\n<chat_instructions>\nBe terse.\n</chat_instructions>\n\n{section}")
+        );
+
+        // Every layer of a workspace chat comes first
+        let layered = compose_system_prompt(&SystemPromptLayers {
+            app: "APP".to_string(),
+            instructions: Some("Workspace system message."),
+            workspace_notes: Some("Ship on Thursdays."),
+            skills: Some("SKILLS"),
+            chat_instructions: Some("Be terse."),
+            connection_instructions: suffix,
+        });
+        assert!(layered.starts_with("APP\n\nWorkspace system message.\n\n<workspace_notes>"));
+        assert!(layered.ends_with(&format!("</chat_instructions>\n\n{section}")));
+
+        // Without any other layer the suffix is the whole prompt
+        let plain = compose_system_prompt(&SystemPromptLayers {
+            connection_instructions: suffix,
+            ..SystemPromptLayers::default()
+        });
+        assert_eq!(plain, section);
+
+        // A blank suffix adds nothing, and answers record none
+        let mut blank = with_suffix.clone();
+        blank.system_prompt_suffix = Some(" \n".to_string());
+        assert_eq!(ChatService::connection_prompt_suffix(&blank), None);
+        assert_eq!(ChatService::connection_suffix_metadata(&blank), None);
+        let recorded = ChatService::connection_suffix_metadata(&with_suffix).unwrap();
+        assert_eq!(recorded["applied"], true);
+        assert_eq!(
+            recorded["hash"],
+            ChatService::suffix_hash("Answer in English.")
+        );
+    }
+
+    #[test]
+    fn thinking_metadata_reports_presence_and_size() {
+        use crate::features::chat::ChatService;
+
+        assert_eq!(ChatService::thinking_metadata(None), None);
+        assert_eq!(ChatService::thinking_metadata(Some("  ")), None);
+        assert_eq!(
+            ChatService::thinking_metadata(Some("Let me think.")),
+            Some(serde_json::json!({"present": true, "estimatedTokens": 4}))
+        );
+    }
+}
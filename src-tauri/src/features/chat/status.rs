@@ -0,0 +1,218 @@
+//! What a chat is doing right now, for the chat header.
+//!
+//! The chat service updates the status of a chat at the phase changes of a
+//! turn and sends `ActiveChatStatusChangedEvent` only when something changed,
+//! never per chunk. A chat without a running turn has no entry and reads as
+//! idle.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ChatPhase {
+    #[default]
+    Idle,
+    /// Waiting for or receiving a model response
+    Streaming,
+    ToolExec,
+    PermissionWait,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+pub struct ActiveChatStatus {
+    pub chat_id: String,
+    pub phase: ChatPhase,
+    pub streaming: bool,
+    /// Agent loop iteration of the running model call, from 1
+    pub current_iteration: u32,
+    pub model: Option<String>,
+    pub provider: Option<String>,
+    /// Tool calls of the turn wait for the user. Auto-approved calls of the
+    /// same batch may run meanwhile.
+    pub pending_permission: bool,
+    /// Share of the model's context window used by the last request, when
+    /// the provider reports the window
+    pub context_percent: Option<f64>,
+}
+
+impl ActiveChatStatus {
+    pub fn idle(chat_id: &str) -> Self {
+        Self {
+            chat_id: chat_id.to_string(),
+            ..Self::default()
+        }
+    }
+}
+
+/// A step of a turn that can change the status.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StatusChange {
+    /// A model call started
+    Streaming {
+        model: String,
+        provider: String,
+        iteration: u32,
+    },
+    ToolExec,
+    PermissionWait,
+    /// The user answered, or the request timed out or was cancelled
+    PermissionResolved,
+    /// The turn ended
+    Finished,
+}
+
+/// Statuses of the chats with a running turn.
+#[derive(Debug, Default)]
+pub struct ChatStatusBoard {
+    statuses: HashMap<String, ActiveChatStatus>,
+    /// When a turn last changed phase, `None` before the first turn
+    last_change: Option<Instant>,
+}
+
+impl ChatStatusBoard {
+    pub fn get(&self, chat_id: &str) -> ActiveChatStatus {
+        self.statuses
+            .get(chat_id)
+            .cloned()
+            .unwrap_or_else(|| ActiveChatStatus::idle(chat_id))
+    }
+
+    /// Apply `change` to the chat, returning the new status if it differs
+    /// from the old one. A finished turn removes the entry.
+    pub fn apply(&mut self, chat_id: &str, change: StatusChange) -> Option<ActiveChatStatus> {
+        self.last_change = Some(Instant::now());
+        let old = self.get(chat_id);
+        let mut new = old.clone();
+        match change {
+            StatusChange::Streaming {
+                model,
+                provider,
+                iteration,
+            } => {
+                new.phase = ChatPhase::Streaming;
+                new.model = Some(model);
+                new.provider = Some(provider);
+                new.current_iteration = iteration;
+            }
+            StatusChange::ToolExec => new.phase = ChatPhase::ToolExec,
+            StatusChange::PermissionWait => {
+                new.phase = ChatPhase::PermissionWait;
+                new.pending_permission = true;
+            }
+            StatusChange::PermissionResolved => {
+                if new.phase == ChatPhase::PermissionWait {
+                    new.phase = ChatPhase::ToolExec;
+                }
+                new.pending_permission = false;
+            }
+            StatusChange::Finished => new = ActiveChatStatus::idle(chat_id),
+        }
+        new.streaming = new.phase == ChatPhase::Streaming;
+
+        if new.phase == ChatPhase::Idle {
+            self.statuses.remove(chat_id);
+        } else {
+            self.statuses.insert(chat_id.to_string(), new.clone());
+        }
+        (new != old).then_some(new)
+    }
+
+    /// How many chats with a running turn are in `phase`.
+    pub fn count_in(&self, phase: ChatPhase) -> usize {
+        self.statuses
+            .values()
+            .filter(|status| status.phase == phase)
+            .count()
+    }
+
+    /// How long no turn has been running, `None` while one is. Before the
+    /// first turn this is `Duration::MAX`.
+    pub fn idle_for(&self) -> Option<Duration> {
+        if !self.statuses.is_empty() {
+            return None;
+        }
+        Some(
+            self.last_change
+                .map_or(Duration::MAX, |last_change| last_change.elapsed()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn chat_status_follows_a_tool_using_turn() {
+        use crate::features::chat::status::{ChatPhase, ChatStatusBoard, StatusChange};
+
+        let streaming = |iteration| StatusChange::Streaming {
+            model: "gpt-4o".to_string(),
+            provider: "openai".to_string(),
+            iteration,
+        };
+        let mut board = ChatStatusBoard::default();
+        assert_eq!(board.get("chat").phase, ChatPhase::Idle);
+
+        let steps = [
+            streaming(1),
+            // A second chunk-level update of the same call changes nothing
+            streaming(1),
+            StatusChange::ToolExec,
+            StatusChange::PermissionWait,
+            StatusChange::PermissionResolved,
+            streaming(2),
+            StatusChange::Finished,
+        ];
+        let emitted: Vec<_> = steps
+            .into_iter()
+            .filter_map(|change| board.apply("chat", change))
+            .map(|s| {
+                (
+                    s.phase,
+                    s.streaming,
+                    s.pending_permission,
+                    s.current_iteration,
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            emitted,
+            [
+                (ChatPhase::Streaming, true, false, 1),
+                (ChatPhase::ToolExec, false, false, 1),
+                (ChatPhase::PermissionWait, false, true, 1),
+                (ChatPhase::ToolExec, false, false, 1),
+                (ChatPhase::Streaming, true, false, 2),
+                (ChatPhase::Idle, false, false, 0),
+            ]
+        );
+        let idle = board.get("chat");
+        assert_eq!(idle.model, None);
+        assert!(board.apply("chat", StatusChange::Finished).is_none());
+        assert_eq!(board.get("other").chat_id, "other");
+    }
+
+    #[test]
+    fn chat_status_board_counts_chats_by_phase() {
+        use crate::features::chat::status::{ChatPhase, ChatStatusBoard, StatusChange};
+
+        let mut board = ChatStatusBoard::default();
+        let streaming = || StatusChange::Streaming {
+            model: "gpt-4o".to_string(),
+            provider: "openai".to_string(),
+            iteration: 1,
+        };
+        board.apply("a", streaming());
+        board.apply("b", streaming());
+        board.apply("b", StatusChange::ToolExec);
+        board.apply("c", StatusChange::ToolExec);
+        assert_eq!(board.count_in(ChatPhase::ToolExec), 2);
+        assert_eq!(board.count_in(ChatPhase::Streaming), 1);
+
+        board.apply("c", StatusChange::Finished);
+        assert_eq!(board.count_in(ChatPhase::ToolExec), 1);
+    }
+}
@@ -0,0 +1,194 @@
+//! Rolling summaries of long chats.
+//!
+//! Once a chat has more than the workspace's `summarize_after_messages`
+//! messages past its summary, or its history no longer fits the model's
+//! context window, the older turns are folded into the summary by the
+//! workspace's `summary_model` (the turn's model when unset). Prompts then
+//! carry the summary in place of those turns, followed by the recent turns
+//! as they were written. The summary is kept on the chat row with the time of
+//! the newest message it covers, so a refresh only reads what came after.
+
+use crate::features::message::Message;
+use crate::models::llm_types::{ChatMessage, UserContent};
+use serde::{Deserialize, Serialize};
+
+/// Usage request type of summary requests.
+pub const SUMMARY_REQUEST_TYPE: &str = "summary";
+/// Turns always sent as written after the summary
+pub const RECENT_TURNS: usize = 4;
+/// Completion cap of a summary request
+pub const SUMMARY_MAX_TOKENS: u32 = 1_024;
+
+const SUMMARY_SYSTEM_PROMPT: &str = "You maintain a running summary of a conversation between a user and an assistant. Merge the new messages into the previous summary. Keep facts, decisions, names, numbers, code identifiers and open questions; drop greetings and repetition. Write plain prose or short bullet points, at most 400 words. Output only the summary.";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChatSummary {
+    pub content: String,
+    /// Timestamp of the newest message the summary covers
+    pub through: i64,
+}
+
+/// The messages of `history` the summary does not cover, in chat order.
+pub fn unsummarized<'a>(history: &'a [Message], summary: Option<&ChatSummary>) -> Vec<&'a Message> {
+    let through = summary.map_or(i64::MIN, |s| s.through);
+    history.iter().filter(|m| m.timestamp > through).collect()
+}
+
+/// Where the last `turns` turns start in `messages`: at their first user
+/// message. `None` when there are no older turns to fold.
+pub fn recent_start(messages: &[&Message], turns: usize) -> Option<usize> {
+    let start = messages
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, m)| m.role == "user")
+        .nth(turns.saturating_sub(1))
+        .map(|(index, _)| index)?;
+    (start > 0).then_some(start)
+}
+
+/// Whether a chat that summarizes after `after_messages` messages is due a
+/// new summary: more user and assistant messages than that past the
+/// summary, or a history too large for the context window. Never when the
+/// workspace does not summarize automatically.
+pub fn is_due(unsummarized: &[&Message], after_messages: Option<i64>, over_budget: bool) -> bool {
+    let Some(after) = after_messages
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|n| *n > 0)
+    else {
+        return false;
+    };
+    over_budget
+        || unsummarized
+            .iter()
+            .filter(|m| m.role == "user" || m.role == "assistant")
+            .count()
+            > after
+}
+
+/// The request asking to fold `messages` into the `previous` summary.
+/// Only what the user and assistant wrote is read; tool traffic is left out.
+pub fn summary_prompt(previous: Option<&str>, messages: &[&Message]) -> Vec<ChatMessage> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str("Previous summary:\n");
+        transcript.push_str(previous);
+        transcript.push_str("\n\n");
+    }
+    transcript.push_str("New messages:\n");
+    for message in messages {
+        let speaker = match message.role.as_str() {
+            "user" => "User",
+            "assistant" => "Assistant",
+            _ => continue,
+        };
+        if message.content.trim().is_empty() {
+            continue;
+        }
+        transcript.push_str(&format!("{speaker}: {}\n", message.content.trim()));
+    }
+
+    vec![
+        ChatMessage::System {
+            content: SUMMARY_SYSTEM_PROMPT.to_string(),
+        },
+        ChatMessage::User {
+            content: UserContent::Text(transcript),
+        },
+    ]
+}
+
+/// The system message a prompt carries the summary in, before the recent turns.
+pub fn summary_message(summary: &ChatSummary) -> ChatMessage {
+    ChatMessage::System {
+        content: format!(
+            "Summary of the earlier conversation, which is not shown:\n{}",
+            summary.content
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn long_chats_fold_older_turns_into_a_summary() {
+        use crate::features::chat::repository::{chat_summary, set_chat_summary};
+        use crate::features::chat::summary::{
+            is_due, recent_start, summary_message, summary_prompt, unsummarized, ChatSummary,
+        };
+        use crate::models::llm_types::{ChatMessage, UserContent};
+
+        let at = |mut m: crate::features::message::Message, timestamp: i64| {
+            m.timestamp = timestamp;
+            m
+        };
+        let history = vec![
+            at(chat_message("u1", "user", "Plan the trip"), 1),
+            at(chat_message("a1", "assistant", "To Hanoi in May"), 2),
+            at(chat_message("u2", "user", "Book a hotel"), 3),
+            at(chat_message("t1", "tool", "booked"), 4),
+            at(chat_message("a2", "assistant", "Booked"), 5),
+            at(chat_message("u3", "user", "And a train?"), 6),
+        ];
+        let summary = ChatSummary {
+            content: "Trip to Hanoi in May.".to_string(),
+            through: 2,
+        };
+        let pending = unsummarized(&history, Some(&summary));
+        assert_eq!(
+            pending.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(),
+            ["u2", "t1", "a2", "u3"]
+        );
+        assert_eq!(unsummarized(&history, None).len(), history.len());
+
+        // Whole turns are kept; nothing folds when every turn is recent
+        let all: Vec<_> = history.iter().collect();
+        assert_eq!(recent_start(&all, 2), Some(2));
+        assert_eq!(recent_start(&all, 3), None);
+        assert_eq!(recent_start(&all, 4), None);
+
+        // Tool traffic does not count toward the threshold
+        assert!(is_due(&pending, Some(2), false));
+        assert!(!is_due(&pending, Some(3), false));
+        assert!(is_due(&pending, Some(3), true));
+        assert!(!is_due(&pending, None, true));
+        assert!(!is_due(&pending, Some(0), true));
+
+        let prompt = summary_prompt(Some(&summary.content), &pending);
+        let ChatMessage::User {
+            content: UserContent::Text(transcript),
+        } = &prompt[1]
+        else {
+            panic!("expected a user transcript");
+        };
+        assert_eq!(
+            transcript,
+            "Previous summary:\nTrip to Hanoi in May.\n\nNew messages:\nUser: Book a hotel\nAssistant: Booked\nUser: And a train?\n"
+        );
+        assert!(matches!(
+            summary_message(&summary),
+            ChatMessage::System { content } if content.ends_with("Trip to Hanoi in May.")
+        ));
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('c1', 'w1', 'One', 0, 0);",
+        )
+        .unwrap();
+        assert_eq!(chat_summary(&conn, "c1").unwrap(), None);
+        set_chat_summary(&conn, "c1", Some(&summary)).unwrap();
+        assert_eq!(chat_summary(&conn, "c1").unwrap(), Some(summary));
+        set_chat_summary(&conn, "c1", None).unwrap();
+        assert_eq!(chat_summary(&conn, "c1").unwrap(), None);
+        assert!(matches!(
+            set_chat_summary(&conn, "missing", None),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+//! What happened during a turn, for debugging long agent runs.
+//!
+//! The chat service records an entry for each model call, tool execution and
+//! permission wait while the turn runs, in memory only, and stores the list
+//! on the final assistant message when the turn ends. No events are emitted
+//! for it.
+
+use crate::error::AppError;
+use crate::models::llm_types::LLMChatResponse;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::time::Instant;
+
+/// Message metadata key holding the timeline of the turn.
+pub const TURN_TIMELINE_METADATA_KEY: &str = "turn_timeline";
+/// Entries kept on a message; older ones are collapsed into a summary.
+pub const MAX_TIMELINE_ENTRIES: usize = 200;
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "snake_case")]
+pub enum TimelinePhase {
+    LlmCall,
+    ToolExec,
+    PermissionWait,
+    /// An extra model call made to recover from a bad response
+    Retry,
+    /// An answer built locally instead of by the model
+    Fallback,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TimelineEntry {
+    pub phase: TimelinePhase,
+    /// Milliseconds since the epoch
+    pub started_at: i64,
+    pub ended_at: i64,
+    /// Model, tool name, or what was recovered
+    pub subject: String,
+    /// Tool call or message the entry belongs to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reference: Option<String>,
+    /// "success", "error", "cancelled", "approved", "denied", ...
+    pub outcome: String,
+}
+
+impl TimelineEntry {
+    pub const fn duration_ms(&self) -> i64 {
+        self.ended_at - self.started_at
+    }
+}
+
+/// Count and total time of collapsed entries of one phase.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PhaseTotals {
+    pub count: usize,
+    pub total_ms: i64,
+}
+
+/// The oldest entries of a timeline over the cap.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct CollapsedEntries {
+    pub count: usize,
+    pub started_at: i64,
+    pub ended_at: i64,
+    pub phases: BTreeMap<TimelinePhase, PhaseTotals>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TurnTimeline {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub collapsed: Option<CollapsedEntries>,
+    pub entries: Vec<TimelineEntry>,
+}
+
+/// Start of a timed phase: wall clock for the entry, monotonic clock for its
+/// duration.
+#[derive(Debug, Clone, Copy)]
+pub struct PhaseStart {
+    at: i64,
+    instant: Instant,
+}
+
+impl PhaseStart {
+    pub fn now() -> Self {
+        Self {
+            at: chrono::Utc::now().timestamp_millis(),
+            instant: Instant::now(),
+        }
+    }
+
+    /// The entry of a phase that started here and ends now.
+    pub fn finish(
+        self,
+        phase: TimelinePhase,
+        subject: impl Into<String>,
+        reference: Option<String>,
+        outcome: impl Into<String>,
+    ) -> TimelineEntry {
+        let elapsed = i64::try_from(self.instant.elapsed().as_millis()).unwrap_or(i64::MAX);
+        TimelineEntry {
+            phase,
+            started_at: self.at,
+            ended_at: self.at.saturating_add(elapsed),
+            subject: subject.into(),
+            reference,
+            outcome: outcome.into(),
+        }
+    }
+}
+
+impl TurnTimeline {
+    pub fn record(&mut self, entry: TimelineEntry) {
+        self.entries.push(entry);
+    }
+
+    /// Entries in start order, with all but the newest `max` collapsed.
+    /// Phases that ran concurrently finish out of order, so entries are
+    /// sorted here rather than when recorded.
+    pub fn capped(mut self, max: usize) -> Self {
+        self.entries.sort_by_key(|e| e.started_at);
+        let excess = self.entries.len().saturating_sub(max);
+        if excess == 0 {
+            return self;
+        }
+
+        let mut collapsed = self.collapsed.take().unwrap_or_else(|| CollapsedEntries {
+            started_at: self.entries[0].started_at,
+            ..CollapsedEntries::default()
+        });
+        for entry in self.entries.drain(..excess) {
+            collapsed.count += 1;
+            collapsed.started_at = collapsed.started_at.min(entry.started_at);
+            collapsed.ended_at = collapsed.ended_at.max(entry.ended_at);
+            let totals = collapsed.phases.entry(entry.phase).or_default();
+            totals.count += 1;
+            totals.total_ms += entry.duration_ms();
+        }
+        self.collapsed = Some(collapsed);
+        self
+    }
+
+    /// The timeline stored in message metadata, if any.
+    pub fn from_metadata(metadata: Option<&str>) -> Option<Self> {
+        let meta: serde_json::Value = serde_json::from_str(metadata?).ok()?;
+        serde_json::from_value(meta.get(TURN_TIMELINE_METADATA_KEY)?.clone()).ok()
+    }
+
+    /// Store the capped timeline in `metadata`, keeping its other keys.
+    pub fn store_in(self, metadata: &mut serde_json::Value) {
+        metadata[TURN_TIMELINE_METADATA_KEY] = serde_json::json!(self.capped(MAX_TIMELINE_ENTRIES));
+    }
+}
+
+/// Outcome of a model call for its timeline entry.
+pub fn llm_call_outcome(result: &Result<LLMChatResponse, AppError>) -> &'static str {
+    match result {
+        Ok(response) if response.finish_reason.as_deref() == Some("cancelled") => "cancelled",
+        Ok(_) => "success",
+        Err(AppError::Cancelled) => "cancelled",
+        Err(_) => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn turn_timeline_orders_a_scripted_agent_run() {
+        use crate::features::chat::timeline::{PhaseStart, TimelinePhase, TurnTimeline};
+        use std::sync::Mutex;
+        use std::time::Duration;
+
+        let timeline = Mutex::new(TurnTimeline::default());
+        let step = |phase, subject: &str, reference: &str, outcome: &str, ms| {
+            let timeline = &timeline;
+            let (subject, reference, outcome) = (
+                subject.to_string(),
+                reference.to_string(),
+                outcome.to_string(),
+            );
+            async move {
+                let start = PhaseStart::now();
+                tokio::time::sleep(Duration::from_millis(ms)).await;
+                let entry = start.finish(phase, subject, Some(reference), outcome);
+                timeline.lock().unwrap().record(entry);
+            }
+        };
+
+        // Iteration 1 asks for three tools; `write_file` needs permission and
+        // waits while the two others run. Iteration 2 answers.
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            step(TimelinePhase::LlmCall, "gpt-4o", "msg-1", "success", 20).await;
+            tokio::join!(
+                async {
+                    step(TimelinePhase::ToolExec, "list_files", "c1", "success", 10).await;
+                    step(TimelinePhase::ToolExec, "read_file", "c2", "error", 10).await;
+                },
+                step(
+                    TimelinePhase::PermissionWait,
+                    "write_file",
+                    "msg-1",
+                    "approved",
+                    40
+                ),
+            );
+            step(TimelinePhase::ToolExec, "write_file", "c3", "success", 10).await;
+            step(TimelinePhase::LlmCall, "gpt-4o", "msg-2", "success", 20).await;
+        });
+
+        let mut metadata = serde_json::json!({ "raw_content": "kept" });
+        timeline.into_inner().unwrap().store_in(&mut metadata);
+        assert_eq!(metadata["raw_content"], "kept");
+        let stored = TurnTimeline::from_metadata(Some(&metadata.to_string())).unwrap();
+
+        let phases: Vec<_> = stored
+            .entries
+            .iter()
+            .map(|e| (e.phase, e.subject.as_str()))
+            .collect();
+        // The permission wait finished after the tools it overlapped, but is
+        // listed by start time
+        assert_eq!(
+            phases,
+            [
+                (TimelinePhase::LlmCall, "gpt-4o"),
+                (TimelinePhase::ToolExec, "list_files"),
+                (TimelinePhase::PermissionWait, "write_file"),
+                (TimelinePhase::ToolExec, "read_file"),
+                (TimelinePhase::ToolExec, "write_file"),
+                (TimelinePhase::LlmCall, "gpt-4o"),
+            ]
+        );
+        let wait = &stored.entries[2];
+        assert_eq!(wait.outcome, "approved");
+        assert!(wait.duration_ms() >= 40);
+        for pair in stored.entries.windows(2) {
+            assert!(pair[0].started_at <= pair[1].started_at);
+        }
+        for entry in &stored.entries {
+            assert!(entry.ended_at >= entry.started_at);
+            assert!(entry.duration_ms() >= 10 && entry.duration_ms() < 5_000);
+        }
+        // The approved call runs after the wait, the answer after every tool
+        assert!(stored.entries[4].started_at >= wait.ended_at);
+        assert!(stored.entries[5].started_at >= stored.entries[4].ended_at);
+        assert!(stored.collapsed.is_none());
+    }
+
+    #[test]
+    fn turn_timeline_collapses_oldest_entries_over_the_cap() {
+        use crate::features::chat::timeline::{
+            TimelineEntry, TimelinePhase, TurnTimeline, MAX_TIMELINE_ENTRIES,
+        };
+
+        let mut timeline = TurnTimeline::default();
+        for i in 0..i64::try_from(MAX_TIMELINE_ENTRIES + 5).unwrap() {
+            timeline.record(TimelineEntry {
+                phase: if i % 2 == 0 {
+                    TimelinePhase::LlmCall
+                } else {
+                    TimelinePhase::ToolExec
+                },
+                started_at: 1_000 + i * 10,
+                ended_at: 1_000 + i * 10 + 4,
+                subject: format!("step {i}"),
+                reference: None,
+                outcome: "success".to_string(),
+            });
+        }
+
+        let capped = timeline.capped(MAX_TIMELINE_ENTRIES);
+        assert_eq!(capped.entries.len(), MAX_TIMELINE_ENTRIES);
+        assert_eq!(capped.entries[0].subject, "step 5");
+        let collapsed = capped.collapsed.unwrap();
+        assert_eq!(collapsed.count, 5);
+        assert_eq!((collapsed.started_at, collapsed.ended_at), (1_000, 1_044));
+        assert_eq!(collapsed.phases[&TimelinePhase::LlmCall].count, 3);
+        assert_eq!(collapsed.phases[&TimelinePhase::ToolExec].total_ms, 8);
+    }
+}
@@ -0,0 +1,291 @@
+//! Limits on how many tool calls a turn may run, apart from the iteration cap.
+//!
+//! A turn is one user message and every agent loop iteration it leads to.
+//! Calls are counted when they are dispatched, so a parallel batch cannot
+//! overshoot a cap, and only calls that actually run count: a call the user
+//! denied leaves the budget as it was.
+
+use super::tool_permissions::SkipReason;
+use crate::error::AppError;
+use crate::features::workspace::settings::WorkspaceSettings;
+use crate::models::llm_types::ToolCall;
+use std::collections::HashMap;
+
+/// Parse a per-tool cap map, `{ "tool_name": max_calls, ... }`. Every cap
+/// must be a positive whole number.
+pub fn parse_tool_call_caps(json: &str) -> Result<HashMap<String, u32>, AppError> {
+    let value: serde_json::Value = serde_json::from_str(json)
+        .map_err(|e| AppError::Validation(format!("Invalid tool call caps: {e}")))?;
+    let serde_json::Value::Object(map) = value else {
+        return Err(AppError::Validation(
+            "Tool call caps must be an object of tool names to call counts".to_string(),
+        ));
+    };
+    map.into_iter()
+        .map(
+            |(tool, cap)| match cap.as_u64().and_then(|n| u32::try_from(n).ok()) {
+                Some(n) if n > 0 => Ok((tool, n)),
+                _ => Err(AppError::Validation(format!(
+                    "Tool call cap for {tool} must be a positive whole number, got {cap}"
+                ))),
+            },
+        )
+        .collect()
+}
+
+/// The calls a turn has run so far, against the workspace caps.
+#[derive(Debug, Default)]
+pub struct TurnToolBudget {
+    max_per_turn: Option<u32>,
+    max_per_tool: HashMap<String, u32>,
+    total: u32,
+    per_tool: HashMap<String, u32>,
+}
+
+impl TurnToolBudget {
+    /// Caps a stored map that no longer parses are ignored, with a warning.
+    pub fn new(settings: &WorkspaceSettings) -> Self {
+        let max_per_tool = settings
+            .tool_call_caps
+            .as_deref()
+            .map(|json| {
+                parse_tool_call_caps(json).unwrap_or_else(|e| {
+                    tracing::warn!(workspace_id = %settings.workspace_id, error = %e, "Ignoring invalid tool call caps");
+                    HashMap::new()
+                })
+            })
+            .unwrap_or_default();
+        Self::with_caps(
+            settings
+                .max_tool_calls_per_turn
+                .and_then(|n| u32::try_from(n).ok())
+                .filter(|&n| n > 0),
+            max_per_tool,
+        )
+    }
+
+    pub fn with_caps(max_per_turn: Option<u32>, max_per_tool: HashMap<String, u32>) -> Self {
+        Self {
+            max_per_turn,
+            max_per_tool,
+            total: 0,
+            per_tool: HashMap::new(),
+        }
+    }
+
+    /// Count the calls about to be dispatched, in order. Returns the calls
+    /// that may run and the ones over a cap, which must not run.
+    pub fn admit(&mut self, calls: Vec<ToolCall>) -> (Vec<ToolCall>, Vec<(ToolCall, SkipReason)>) {
+        let mut admitted = Vec::with_capacity(calls.len());
+        let mut clamped = Vec::new();
+        for call in calls {
+            let name = call.function.name.as_str();
+            let used = self.per_tool.get(name).copied().unwrap_or(0);
+            if self.max_per_turn.is_some_and(|max| self.total >= max) {
+                clamped.push((call, SkipReason::TurnToolCapReached));
+            } else if self.max_per_tool.get(name).is_some_and(|&max| used >= max) {
+                clamped.push((call, SkipReason::ToolCapReached));
+            } else {
+                self.total += 1;
+                self.per_tool.insert(name.to_string(), used + 1);
+                admitted.push(call);
+            }
+        }
+        (admitted, clamped)
+    }
+}
+
+/// Warning shown when calls of a batch were over a cap, naming their tools.
+pub fn clamp_warning(clamped: &[(ToolCall, SkipReason)]) -> String {
+    let tools = clamped
+        .iter()
+        .map(|(call, _)| call.function.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!(
+        "{} tool call(s) over the tool call caps for this turn were not run: {tools}",
+        clamped.len()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{
+        boot_workspace_settings, tool_call, tool_result_ids, PERMISSION_CONFIG,
+    };
+
+    #[test]
+    fn tool_caps_per_turn_cap_spans_iterations() {
+        use crate::features::chat::tool_caps::TurnToolBudget;
+        use crate::features::chat::tool_permissions::{
+            partition_by_permission, resolve_gated, PermissionOutcome, SkipReason,
+        };
+        use crate::state::PermissionDecision;
+
+        let mut settings = boot_workspace_settings(None, None);
+        settings.max_tool_calls_per_turn = Some(4);
+        let mut budget = TurnToolBudget::new(&settings);
+
+        // First iteration: three calls run
+        let (admitted, clamped) = budget.admit(vec![
+            tool_call("1", "read_file"),
+            tool_call("2", "list_dir"),
+            tool_call("3", "read_file"),
+        ]);
+        assert_eq!(admitted.len(), 3);
+        assert!(clamped.is_empty());
+
+        // Second iteration: the denied call does not use up the last slot
+        let batch = vec![
+            tool_call("4", "delete_file"),
+            tool_call("5", "read_file"),
+            tool_call("6", "list_dir"),
+        ];
+        let (auto, gated) = partition_by_permission(&batch, Some(PERMISSION_CONFIG));
+        let (approved, denied) = resolve_gated(
+            gated,
+            &PermissionOutcome::Decided(PermissionDecision {
+                approved: false,
+                allowed_tool_ids: Vec::new(),
+                remember: None,
+            }),
+        );
+        assert!(approved.is_empty());
+        assert_eq!(denied.len(), 1);
+        let (admitted, clamped) = budget.admit(auto);
+        assert_eq!(
+            admitted.iter().map(|tc| tc.id.as_str()).collect::<Vec<_>>(),
+            ["5"]
+        );
+        assert_eq!(
+            clamped
+                .iter()
+                .map(|(tc, reason)| (tc.id.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            [("6", SkipReason::TurnToolCapReached)]
+        );
+
+        // Third iteration: nothing runs any more
+        let (admitted, clamped) = budget.admit(vec![tool_call("7", "list_dir")]);
+        assert!(admitted.is_empty());
+        assert_eq!(clamped[0].1, SkipReason::TurnToolCapReached);
+        assert_eq!(clamped[0].1.field(), "skipped");
+        assert!(!clamped[0].1.is_permission());
+
+        // Without caps nothing is held back
+        let mut unlimited = TurnToolBudget::new(&boot_workspace_settings(None, None));
+        let calls = (0..50)
+            .map(|i| tool_call(&i.to_string(), "read_file"))
+            .collect();
+        assert_eq!(unlimited.admit(calls).0.len(), 50);
+    }
+
+    #[test]
+    fn tool_caps_per_tool_cap_with_parallel_batches() {
+        use crate::features::chat::tool_caps::{clamp_warning, TurnToolBudget};
+        use crate::features::chat::tool_permissions::{
+            order_results, partition_by_permission, resolve_gated, skipped_result,
+            PermissionOutcome, SkipReason,
+        };
+        use crate::models::llm_types::ChatMessage;
+        use crate::state::PermissionDecision;
+
+        let mut settings = boot_workspace_settings(None, None);
+        settings.tool_call_caps = Some(r#"{"run_command": 2, "read_file": 1}"#.to_string());
+        let mut budget = TurnToolBudget::new(&settings);
+
+        // One parallel batch: calls are counted before dispatch, so the batch
+        // cannot run more than the cap even though its calls run together
+        let batch = vec![
+            tool_call("1", "read_file"),
+            tool_call("2", "run_command"),
+            tool_call("3", "read_file"),
+            tool_call("4", "list_dir"),
+            tool_call("5", "run_command"),
+            tool_call("6", "run_command"),
+        ];
+        let (auto, gated) = partition_by_permission(&batch, Some(PERMISSION_CONFIG));
+        let (auto, mut clamped) = budget.admit(auto);
+        assert_eq!(
+            auto.iter().map(|tc| tc.id.as_str()).collect::<Vec<_>>(),
+            ["1", "4"]
+        );
+        // The user allows two of the three gated calls; only those count
+        let (approved, denied) = resolve_gated(
+            gated,
+            &PermissionOutcome::Decided(PermissionDecision {
+                approved: true,
+                allowed_tool_ids: vec!["2".to_string(), "6".to_string()],
+                remember: None,
+            }),
+        );
+        assert_eq!(denied[0].0.id, "5");
+        let (approved, clamped_approved) = budget.admit(approved);
+        clamped.extend(clamped_approved);
+        assert_eq!(
+            approved.iter().map(|tc| tc.id.as_str()).collect::<Vec<_>>(),
+            ["2", "6"]
+        );
+        assert_eq!(
+            clamped
+                .iter()
+                .map(|(tc, reason)| (tc.id.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            [("3", SkipReason::ToolCapReached)]
+        );
+        assert!(clamp_warning(&clamped).contains("read_file"));
+
+        // The clamped call still gets a result explaining the cap
+        let results: Vec<ChatMessage> = auto
+            .iter()
+            .chain(&approved)
+            .map(|tc| ChatMessage::Tool {
+                content: r#"{"ok": true}"#.to_string(),
+                tool_call_id: tc.id.clone(),
+            })
+            .chain(
+                denied
+                    .iter()
+                    .chain(&clamped)
+                    .map(|(tc, reason)| skipped_result(tc, *reason)),
+            )
+            .collect();
+        let results = order_results(&batch, results);
+        assert_eq!(tool_result_ids(&results), ["1", "2", "3", "4", "5", "6"]);
+        let ChatMessage::Tool { content, .. } = &results[2] else {
+            unreachable!();
+        };
+        assert!(content.contains(SkipReason::ToolCapReached.message()));
+
+        // The next iteration of the turn: run_command is used up
+        let (admitted, clamped) = budget.admit(vec![
+            tool_call("7", "run_command"),
+            tool_call("8", "list_dir"),
+        ]);
+        assert_eq!(admitted[0].id, "8");
+        assert_eq!(clamped[0].0.id, "7");
+    }
+
+    #[test]
+    fn tool_caps_settings_validation() {
+        use crate::error::AppError;
+        use crate::features::chat::tool_caps::parse_tool_call_caps;
+
+        let caps = parse_tool_call_caps(r#"{"search": 3}"#).unwrap();
+        assert_eq!(caps["search"], 3);
+        assert!(parse_tool_call_caps("{}").unwrap().is_empty());
+        for invalid in [
+            r#"{"search": 0}"#,
+            r#"{"search": -1}"#,
+            r#"{"search": 1.5}"#,
+            r#"{"search": "3"}"#,
+            r#"["search"]"#,
+            "not json",
+        ] {
+            assert!(
+                matches!(parse_tool_call_caps(invalid), Err(AppError::Validation(_))),
+                "{invalid} should be rejected"
+            );
+        }
+    }
+}
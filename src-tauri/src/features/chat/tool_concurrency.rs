@@ -0,0 +1,81 @@
+//! How many tool calls of one batch run at once.
+//!
+//! A model may ask for several tools in one answer. They run side by side,
+//! up to the workspace's `max_parallel_tools`, so one slow tool does not hold
+//! up the others, and their results go back to the model in the order it
+//! asked for them, whatever order they finish in.
+
+use crate::features::workspace::settings::WorkspaceSettings;
+use std::future::Future;
+use tokio::sync::Semaphore;
+
+pub const DEFAULT_MAX_PARALLEL_TOOLS: usize = 4;
+
+/// The workspace's limit, or the default when it has none.
+pub fn max_parallel_tools(settings: &WorkspaceSettings) -> usize {
+    settings
+        .max_parallel_tools
+        .and_then(|n| usize::try_from(n).ok())
+        .filter(|n| *n > 0)
+        .unwrap_or(DEFAULT_MAX_PARALLEL_TOOLS)
+}
+
+/// Run `tasks` with at most `limit` of them at a time. The outputs are in the
+/// order of `tasks`. A task does nothing until it is first polled, so work it
+/// does before its first await still waits for a slot.
+pub async fn run_bounded<F: Future>(
+    tasks: impl IntoIterator<Item = F>,
+    limit: usize,
+) -> Vec<F::Output> {
+    let slots = &Semaphore::new(limit.max(1));
+    futures::future::join_all(tasks.into_iter().map(|task| async move {
+        // The semaphore is never closed, so a slot always comes
+        let _slot = slots.acquire().await.ok();
+        task.await
+    }))
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::boot_workspace_settings;
+
+    #[test]
+    fn tool_batches_run_side_by_side_up_to_the_workspace_limit() {
+        use crate::features::chat::tool_concurrency::{self, DEFAULT_MAX_PARALLEL_TOOLS};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::time::Duration;
+
+        let mut settings = boot_workspace_settings(None, None);
+        assert_eq!(
+            tool_concurrency::max_parallel_tools(&settings),
+            DEFAULT_MAX_PARALLEL_TOOLS
+        );
+        settings.max_parallel_tools = Some(2);
+        assert_eq!(tool_concurrency::max_parallel_tools(&settings), 2);
+        settings.max_parallel_tools = Some(0);
+        assert_eq!(
+            tool_concurrency::max_parallel_tools(&settings),
+            DEFAULT_MAX_PARALLEL_TOOLS
+        );
+
+        let running = AtomicUsize::new(0);
+        let peak = AtomicUsize::new(0);
+        // Later calls finish first; the results keep the order they were asked in
+        let tasks = (0..5u64).map(|i| {
+            let (running, peak) = (&running, &peak);
+            async move {
+                let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+                peak.fetch_max(now, Ordering::SeqCst);
+                tokio::time::sleep(Duration::from_millis(50 - i * 10)).await;
+                running.fetch_sub(1, Ordering::SeqCst);
+                i
+            }
+        });
+        let results = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(tool_concurrency::run_bounded(tasks, 2));
+        assert_eq!(results, [0, 1, 2, 3, 4]);
+        assert_eq!(peak.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,134 @@
+//! Tool calls and results in the history sent to the model.
+//!
+//! Every provider requires a tool result to answer a call of the assistant
+//! message right before it, and most also require every call to be answered. History assistant messages get their calls back from the
+//! stored `tool_call` messages, and `pair_tool_messages` drops whatever is
+//! left unmatched, e.g. by rows deleted before deletions were tool aware.
+
+use crate::features::message::tool_exchange::tool_call_id_of;
+use crate::features::message::Message;
+use crate::models::llm_types::{AssistantContent, ChatMessage, ToolCall, ToolCallFunction};
+use std::collections::{HashMap, HashSet};
+
+/// The tool calls of each assistant message, rebuilt from its `tool_call`
+/// messages in history order.
+pub fn history_tool_calls<'a>(
+    history: impl IntoIterator<Item = &'a Message>,
+) -> HashMap<String, Vec<ToolCall>> {
+    let mut calls: HashMap<String, Vec<ToolCall>> = HashMap::new();
+    for msg in history {
+        let (Some(assistant_id), Some(call_id)) =
+            (msg.assistant_message_id.as_deref(), tool_call_id_of(msg))
+        else {
+            continue;
+        };
+        if msg.role != "tool_call" {
+            continue;
+        }
+        let data: serde_json::Value = serde_json::from_str(&msg.content).unwrap_or_default();
+        let Some(name) = data.get("name").and_then(|n| n.as_str()) else {
+            continue;
+        };
+        let arguments = match data.get("arguments") {
+            Some(serde_json::Value::String(s)) => s.clone(),
+            Some(serde_json::Value::Null) | None => "{}".to_string(),
+            Some(other) => other.to_string(),
+        };
+        calls
+            .entry(assistant_id.to_string())
+            .or_default()
+            .push(ToolCall {
+                id: call_id.to_string(),
+                r#type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: name.to_string(),
+                    arguments,
+                },
+            });
+    }
+    calls
+}
+
+/// Keep only complete tool exchanges: an assistant message keeps the calls
+/// answered by the tool results right after it, and results that answer no
+/// such call are dropped. An assistant message left without text or calls
+/// is dropped too.
+pub fn pair_tool_messages(messages: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    // Results that directly follow each assistant message
+    let mut answered: Vec<HashSet<String>> = vec![HashSet::new(); messages.len()];
+    let mut owner = None;
+    for (i, msg) in messages.iter().enumerate() {
+        match msg {
+            ChatMessage::Assistant { .. } => owner = Some(i),
+            ChatMessage::Tool { tool_call_id, .. } => {
+                if let Some(o) = owner {
+                    answered[o].insert(tool_call_id.clone());
+                }
+            }
+            _ => owner = None,
+        }
+    }
+
+    let mut paired = Vec::with_capacity(messages.len());
+    let mut pending: HashSet<String> = HashSet::new();
+    let mut dropped_results = Vec::new();
+    let mut dropped_calls = 0;
+    for (msg, answered) in messages.into_iter().zip(answered) {
+        match msg {
+            ChatMessage::Assistant {
+                content,
+                tool_calls,
+            } => {
+                let calls = tool_calls.unwrap_or_default();
+                let total = calls.len();
+                let kept: Vec<ToolCall> = calls
+                    .into_iter()
+                    .filter(|c| answered.contains(&c.id))
+                    .collect();
+                dropped_calls += total - kept.len();
+                pending = kept.iter().map(|c| c.id.clone()).collect();
+                if kept.is_empty() && is_blank(&content) {
+                    continue;
+                }
+                paired.push(ChatMessage::Assistant {
+                    content,
+                    tool_calls: (!kept.is_empty()).then_some(kept),
+                });
+            }
+            ChatMessage::Tool {
+                content,
+                tool_call_id,
+            } => {
+                // A call is answered once
+                if pending.remove(&tool_call_id) {
+                    paired.push(ChatMessage::Tool {
+                        content,
+                        tool_call_id,
+                    });
+                } else {
+                    dropped_results.push(tool_call_id);
+                }
+            }
+            other => {
+                pending.clear();
+                paired.push(other);
+            }
+        }
+    }
+
+    if !dropped_results.is_empty() || dropped_calls > 0 {
+        tracing::warn!(
+            dropped_results = ?dropped_results,
+            dropped_calls,
+            "Dropped unpaired tool messages from the history"
+        );
+    }
+    paired
+}
+
+fn is_blank(content: &AssistantContent) -> bool {
+    match content {
+        AssistantContent::Text(text) => text.trim().is_empty(),
+        AssistantContent::Parts(parts) => parts.is_empty(),
+    }
+}
@@ -0,0 +1,623 @@
+use crate::error::AppError;
+use crate::features::tool::builtin;
+use crate::models::llm_types::{ChatMessage, ToolCall};
+use crate::state::PermissionDecision;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// A tool that runs without asking.
+pub const PERMISSION_AUTO: &str = "auto";
+/// A tool that waits for the user's approval.
+pub const PERMISSION_REQUIRE: &str = "require";
+/// A tool the user chose to always deny; its calls are refused without asking.
+pub const PERMISSION_DENY: &str = "deny";
+
+/// How long gated tool calls wait for the user before they are skipped.
+pub const PERMISSION_TIMEOUT: std::time::Duration = std::time::Duration::from_mins(1);
+
+/// Where an "always allow" or "always deny" answer is kept.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RememberScope {
+    /// The workspace's tool permission config, or in an agent chat the
+    /// agent's tool permissions
+    Workspace,
+    /// The chat the request came from
+    Chat,
+}
+
+/// A remembered answer, as the review list shows it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RememberedToolPermission {
+    pub scope: RememberScope,
+    /// Set for answers kept for one chat
+    pub chat_id: Option<String>,
+    pub tool_name: String,
+    /// `auto` or `deny`
+    pub permission: String,
+}
+
+/// The user's answer (or lack of one) for the gated part of a tool call batch.
+#[derive(Debug)]
+pub enum PermissionOutcome {
+    Decided(PermissionDecision),
+    TimedOut,
+    /// The pending request was dropped before an answer arrived
+    Cancelled,
+    /// Headless run configured to deny anything that needs permission
+    AutoDenied,
+}
+
+impl PermissionOutcome {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Decided(decision) if decision.approved => "approved",
+            Self::Decided(_) => "denied",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+            Self::AutoDenied => "auto_denied",
+        }
+    }
+}
+
+/// Why a tool call was not executed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    Denied,
+    TimedOut,
+    Cancelled,
+    AutoDenied,
+    /// Arguments were cut off mid-stream and could not be repaired
+    TruncatedArguments,
+    /// The turn already ran `max_tool_calls_per_turn` calls
+    TurnToolCapReached,
+    /// The turn already ran the workspace cap for this tool
+    ToolCapReached,
+    /// The user chose to always deny this tool
+    AlwaysDenied,
+}
+
+impl SkipReason {
+    /// Stored in the `tool_call` message under `field()`.
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Denied => "denied",
+            Self::TimedOut => "timed_out",
+            Self::Cancelled => "cancelled",
+            Self::AutoDenied => "auto_denied",
+            Self::TruncatedArguments => "truncated_arguments",
+            Self::TurnToolCapReached => "turn_tool_cap_reached",
+            Self::ToolCapReached => "tool_cap_reached",
+            Self::AlwaysDenied => "always_denied",
+        }
+    }
+
+    /// Whether the call was skipped for lack of permission.
+    pub const fn is_permission(self) -> bool {
+        matches!(
+            self,
+            Self::Denied | Self::TimedOut | Self::Cancelled | Self::AutoDenied | Self::AlwaysDenied
+        )
+    }
+
+    /// `permission` for permission outcomes, `skipped` otherwise.
+    pub const fn field(self) -> &'static str {
+        if self.is_permission() {
+            "permission"
+        } else {
+            "skipped"
+        }
+    }
+
+    /// The error the model sees as the tool result.
+    pub const fn message(self) -> &'static str {
+        match self {
+            Self::Denied => "Tool execution denied by user",
+            Self::TimedOut => "Tool permission request timed out (60s)",
+            Self::Cancelled => "Tool permission request cancelled",
+            Self::AutoDenied => "Tool execution denied: permission required in headless run",
+            Self::AlwaysDenied => "Tool execution denied: the user chose to always deny this tool",
+            Self::TruncatedArguments => {
+                "The tool call arguments were cut off before they were complete, so the tool was not run. Send the call again with the complete arguments."
+            }
+            Self::TurnToolCapReached => {
+                "This turn reached its limit of tool calls, so the tool was not run. Answer with the results you already have."
+            }
+            Self::ToolCapReached => {
+                "This tool reached its limit of calls for this turn, so it was not run. Use the results you already have or other tools."
+            }
+        }
+    }
+}
+
+/// Split a batch into calls that run right away and calls whose tool is set
+/// to "require" in the workspace permission config, or that it leaves out
+/// and that ask by default (see `builtin::default_permission`). Tools that
+/// always ask (see `builtin::always_confirmed`) are gated whatever the
+/// config says. A "deny" that reaches the split asks rather than runs;
+/// `take_remembered` refuses those first.
+pub fn partition_by_permission(
+    tool_calls: &[ToolCall],
+    permission_config: Option<&str>,
+) -> (Vec<ToolCall>, Vec<ToolCall>) {
+    let config = parse_permission_config(permission_config);
+
+    tool_calls.iter().cloned().partition(|tc| {
+        let name = tc.function.name.as_str();
+        !builtin::always_confirmed(name)
+            && !is_gated(
+                config
+                    .get(name)
+                    .map(String::as_str)
+                    .or_else(|| builtin::default_permission(name)),
+            )
+    })
+}
+
+/// A stored permission config, `{ "tool_name": permission, ... }`; one that
+/// does not parse counts as empty.
+pub fn parse_permission_config(permission_config: Option<&str>) -> BTreeMap<String, String> {
+    permission_config
+        .and_then(|c| serde_json::from_str(c).ok())
+        .unwrap_or_default()
+}
+
+fn is_gated(permission: Option<&str>) -> bool {
+    matches!(permission, Some(PERMISSION_REQUIRE | PERMISSION_DENY))
+}
+
+/// The permissions an "always" answer keeps for the tools of the gated
+/// calls: `auto` for a tool the user allowed a call of, `deny` for the rest.
+/// Allowing a tool that always asks is not kept.
+pub fn remembered_permissions(
+    gated: &[ToolCall],
+    decision: &PermissionDecision,
+) -> BTreeMap<String, String> {
+    let mut permissions = BTreeMap::new();
+    for tc in gated {
+        let allowed = decision.approved && decision.allowed_tool_ids.contains(&tc.id);
+        let permission = permissions
+            .entry(tc.function.name.clone())
+            .or_insert(PERMISSION_DENY);
+        if allowed {
+            *permission = PERMISSION_AUTO;
+        }
+    }
+    permissions
+        .into_iter()
+        .filter(|(tool, permission)| {
+            *permission != PERMISSION_AUTO || !builtin::always_confirmed(tool)
+        })
+        .map(|(tool, permission)| (tool, permission.to_string()))
+        .collect()
+}
+
+/// Take the calls the user's remembered answers settle out of a batch.
+/// `chat` holds the answers kept for this chat, which win over the "deny"
+/// entries of `config`, the workspace's or agent's permission config.
+/// Returns the calls that run without asking, the calls refused and the
+/// rest, which go on to the permission split.
+pub fn take_remembered(
+    tool_calls: Vec<ToolCall>,
+    chat: &BTreeMap<String, String>,
+    config: &BTreeMap<String, String>,
+) -> (Vec<ToolCall>, Vec<ToolCall>, Vec<ToolCall>) {
+    let mut allowed = Vec::new();
+    let mut denied = Vec::new();
+    let mut rest = Vec::new();
+    for tc in tool_calls {
+        let name = &tc.function.name;
+        match chat.get(name).map(String::as_str) {
+            Some(PERMISSION_AUTO) if !builtin::always_confirmed(name) => allowed.push(tc),
+            Some(PERMISSION_DENY) => denied.push(tc),
+            _ if config.get(name).map(String::as_str) == Some(PERMISSION_DENY) => denied.push(tc),
+            _ => rest.push(tc),
+        }
+    }
+    (allowed, denied, rest)
+}
+
+/// A permission of a tool in a permission config: `validate_permission`'s,
+/// or `deny`.
+pub fn validate_tool_permission(permission: &str) -> Result<(), AppError> {
+    if permission == PERMISSION_DENY {
+        Ok(())
+    } else {
+        validate_permission(permission)
+    }
+}
+
+/// `validate_tool_permission`, refusing "auto" for a tool that always asks.
+pub fn validate_tool_entry(tool: &str, permission: &str) -> Result<(), AppError> {
+    validate_tool_permission(permission)?;
+    if permission == PERMISSION_AUTO && builtin::always_confirmed(tool) {
+        return Err(AppError::Validation(format!(
+            "{tool} asks before every run and cannot be set to \"{PERMISSION_AUTO}\""
+        )));
+    }
+    Ok(())
+}
+
+pub fn validate_permission(permission: &str) -> Result<(), AppError> {
+    if permission == PERMISSION_AUTO || permission == PERMISSION_REQUIRE {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!(
+            "Tool permission must be \"{PERMISSION_AUTO}\" or \"{PERMISSION_REQUIRE}\", got \"{permission}\""
+        )))
+    }
+}
+
+/// The permission of a tool in an agent chat: the agent's own config, and
+/// for tools it does not list the workspace's `unlisted` policy, which
+/// requires permission when unset.
+pub fn agent_tool_permission<'a>(
+    agent_config: &'a BTreeMap<String, String>,
+    tool: &str,
+    unlisted: Option<&'a str>,
+) -> &'a str {
+    agent_config
+        .get(tool)
+        .map(String::as_str)
+        .or(unlisted)
+        .unwrap_or(PERMISSION_REQUIRE)
+}
+
+/// The permission of each of an agent's `tools` and of each tool its config
+/// lists, as the agent's chats apply them.
+pub fn effective_agent_permissions<'t>(
+    agent_config: &BTreeMap<String, String>,
+    tools: impl IntoIterator<Item = &'t str>,
+    unlisted: Option<&str>,
+) -> BTreeMap<String, String> {
+    let mut permissions = agent_config.clone();
+    for tool in tools {
+        let permission = agent_tool_permission(agent_config, tool, unlisted).to_string();
+        permissions.insert(tool.to_string(), permission);
+    }
+    permissions
+}
+
+/// Split a batch of an agent chat into calls that run right away and calls
+/// that wait for the user. The workspace permission config does not apply:
+/// agent tools are not workspace tools.
+pub fn partition_by_agent_permission(
+    tool_calls: &[ToolCall],
+    agent_config: &BTreeMap<String, String>,
+    unlisted: Option<&str>,
+) -> (Vec<ToolCall>, Vec<ToolCall>) {
+    tool_calls.iter().cloned().partition(|tc| {
+        !builtin::always_confirmed(&tc.function.name)
+            && !is_gated(Some(agent_tool_permission(
+                agent_config,
+                &tc.function.name,
+                unlisted,
+            )))
+    })
+}
+
+/// Gated calls the user allowed, and the rest paired with why they are skipped.
+pub fn resolve_gated(
+    gated: Vec<ToolCall>,
+    outcome: &PermissionOutcome,
+) -> (Vec<ToolCall>, Vec<(ToolCall, SkipReason)>) {
+    let reason = match outcome {
+        PermissionOutcome::Decided(decision) if decision.approved => {
+            let (allowed, denied): (Vec<_>, Vec<_>) = gated
+                .into_iter()
+                .partition(|tc| decision.allowed_tool_ids.contains(&tc.id));
+            return (
+                allowed,
+                denied
+                    .into_iter()
+                    .map(|tc| (tc, SkipReason::Denied))
+                    .collect(),
+            );
+        }
+        PermissionOutcome::Decided(_) => SkipReason::Denied,
+        PermissionOutcome::TimedOut => SkipReason::TimedOut,
+        PermissionOutcome::Cancelled => SkipReason::Cancelled,
+        PermissionOutcome::AutoDenied => SkipReason::AutoDenied,
+    };
+    (
+        Vec::new(),
+        gated.into_iter().map(|tc| (tc, reason)).collect(),
+    )
+}
+
+/// Tool result sent to the model for a skipped call.
+pub fn skipped_result(tool_call: &ToolCall, reason: SkipReason) -> ChatMessage {
+    ChatMessage::Tool {
+        content: serde_json::json!({ "error": reason.message() }).to_string(),
+        tool_call_id: tool_call.id.clone(),
+    }
+}
+
+/// Tool results in the order the model issued the calls; results for ids not
+/// in the batch keep their relative order at the end.
+pub fn order_results(tool_calls: &[ToolCall], mut results: Vec<ChatMessage>) -> Vec<ChatMessage> {
+    let position: HashMap<&str, usize> = tool_calls
+        .iter()
+        .enumerate()
+        .map(|(i, tc)| (tc.id.as_str(), i))
+        .collect();
+    results.sort_by_key(|message| match message {
+        ChatMessage::Tool { tool_call_id, .. } => position
+            .get(tool_call_id.as_str())
+            .copied()
+            .unwrap_or(usize::MAX),
+        _ => usize::MAX,
+    });
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+    use crate::test_support::{tool_call, tool_result_ids, PERMISSION_CONFIG};
+
+    #[test]
+    fn tool_permissions_mixed_batch() {
+        use crate::features::chat::tool_permissions::{
+            partition_by_permission, resolve_gated, PermissionOutcome, SkipReason,
+        };
+        use crate::state::PermissionDecision;
+
+        let batch = vec![
+            tool_call("1", "read_file"),
+            tool_call("2", "delete_file"),
+            tool_call("3", "list_dir"),
+            tool_call("4", "run_command"),
+        ];
+        let (auto, gated) = partition_by_permission(&batch, Some(PERMISSION_CONFIG));
+        assert_eq!(
+            auto.iter().map(|tc| tc.id.as_str()).collect::<Vec<_>>(),
+            ["1", "3"]
+        );
+        assert_eq!(
+            gated.iter().map(|tc| tc.id.as_str()).collect::<Vec<_>>(),
+            ["2", "4"]
+        );
+
+        // The user allows one of the two gated calls
+        let (approved, skipped) = resolve_gated(
+            gated,
+            &PermissionOutcome::Decided(PermissionDecision {
+                approved: true,
+                allowed_tool_ids: vec!["4".to_string()],
+                remember: None,
+            }),
+        );
+        assert_eq!(approved.len(), 1);
+        assert_eq!(approved[0].id, "4");
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0.id, "2");
+        assert_eq!(skipped[0].1, SkipReason::Denied);
+    }
+
+    #[test]
+    fn tool_permissions_timeout_keeps_partial_results() {
+        use crate::features::chat::tool_permissions::{
+            order_results, partition_by_permission, resolve_gated, skipped_result,
+            PermissionOutcome, SkipReason,
+        };
+        use crate::models::llm_types::ChatMessage;
+
+        let batch = vec![
+            tool_call("1", "delete_file"),
+            tool_call("2", "read_file"),
+            tool_call("3", "list_dir"),
+        ];
+        let (auto, gated) = partition_by_permission(&batch, Some(PERMISSION_CONFIG));
+        let (approved, skipped) = resolve_gated(gated, &PermissionOutcome::TimedOut);
+        assert!(approved.is_empty());
+
+        // Auto-allowed calls finished while the prompt was waiting
+        let mut results: Vec<ChatMessage> = auto
+            .iter()
+            .map(|tc| ChatMessage::Tool {
+                content: r#"{"ok": true}"#.to_string(),
+                tool_call_id: tc.id.clone(),
+            })
+            .collect();
+        results.extend(
+            skipped
+                .iter()
+                .map(|(tc, reason)| skipped_result(tc, *reason)),
+        );
+        let results = order_results(&batch, results);
+
+        assert_eq!(tool_result_ids(&results), ["1", "2", "3"]);
+        let ChatMessage::Tool { content, .. } = &results[0] else {
+            unreachable!();
+        };
+        assert!(content.contains(SkipReason::TimedOut.message()));
+    }
+
+    #[test]
+    fn tool_permissions_full_denial_answers_every_gated_call() {
+        use crate::features::chat::tool_permissions::{
+            partition_by_permission, resolve_gated, PermissionOutcome, SkipReason,
+        };
+        use crate::state::PermissionDecision;
+
+        let batch = vec![tool_call("1", "delete_file"), tool_call("2", "run_command")];
+        let (auto, gated) = partition_by_permission(&batch, Some(PERMISSION_CONFIG));
+        assert!(auto.is_empty());
+
+        let (approved, skipped) = resolve_gated(
+            gated,
+            &PermissionOutcome::Decided(PermissionDecision {
+                approved: false,
+                allowed_tool_ids: vec!["1".to_string()],
+                remember: None,
+            }),
+        );
+        assert!(approved.is_empty());
+        assert_eq!(
+            skipped
+                .iter()
+                .map(|(tc, reason)| (tc.id.as_str(), *reason))
+                .collect::<Vec<_>>(),
+            [("1", SkipReason::Denied), ("2", SkipReason::Denied)]
+        );
+
+        let (_, auto_denied) = resolve_gated(
+            vec![tool_call("3", "delete_file")],
+            &PermissionOutcome::AutoDenied,
+        );
+        assert_eq!(auto_denied[0].1, SkipReason::AutoDenied);
+    }
+
+    #[test]
+    fn agent_chat_gates_required_agent_tools_regardless_of_workspace_config() {
+        use crate::features::chat::tool_permissions::{
+            partition_by_agent_permission, partition_by_permission,
+        };
+        use std::collections::BTreeMap;
+
+        let calls = vec![tool_call("call_1", "deploy"), tool_call("call_2", "lookup")];
+        let agent_config = BTreeMap::from([("deploy".to_string(), "require".to_string())]);
+        let workspace_config = Some(r#"{"deploy":"auto"}"#);
+
+        let (auto, gated) = partition_by_permission(&calls, workspace_config);
+        assert_eq!((auto.len(), gated.len()), (2, 0));
+
+        let (auto, gated) = partition_by_agent_permission(&calls, &agent_config, Some("auto"));
+        assert_eq!(gated.len(), 1);
+        assert_eq!(gated[0].function.name, "deploy");
+        assert_eq!(auto[0].function.name, "lookup");
+
+        // Tools the agent does not list ask when the workspace sets no policy
+        let (auto, gated) = partition_by_agent_permission(&calls, &BTreeMap::new(), None);
+        assert!(auto.is_empty());
+        assert_eq!(gated.len(), 2);
+    }
+
+    #[test]
+    fn effective_agent_permissions_cover_every_tool() {
+        use crate::features::chat::tool_permissions::{
+            effective_agent_permissions, validate_permission,
+        };
+        use std::collections::BTreeMap;
+
+        let agent_config = BTreeMap::from([("deploy".to_string(), "require".to_string())]);
+        let permissions =
+            effective_agent_permissions(&agent_config, ["deploy", "lookup"], Some("auto"));
+        assert_eq!(permissions["deploy"], "require");
+        assert_eq!(permissions["lookup"], "auto");
+
+        assert!(validate_permission("auto").is_ok());
+        assert!(matches!(
+            validate_permission("sometimes"),
+            Err(AppError::Validation(_))
+        ));
+    }
+
+    #[test]
+    fn remembered_tool_permission_answers_settle_later_calls() {
+        use crate::features::chat::repository::{
+            chat_tool_permissions, set_chat_tool_permission, workspace_chat_tool_permissions,
+        };
+        use crate::features::chat::tool_permissions::{
+            parse_permission_config, partition_by_permission, remembered_permissions,
+            take_remembered, validate_permission, validate_tool_entry, validate_tool_permission,
+            RememberScope,
+        };
+        use crate::state::PermissionDecision;
+        use std::collections::BTreeMap;
+
+        // "Always" for a batch keeps auto for allowed tools, deny for the rest
+        let gated = vec![
+            tool_call("1", "delete_file"),
+            tool_call("2", "send_email"),
+            tool_call("3", "send_email"),
+        ];
+        let decision = PermissionDecision {
+            approved: true,
+            allowed_tool_ids: vec!["3".to_string()],
+            remember: Some(RememberScope::Chat),
+        };
+        let kept = remembered_permissions(&gated, &decision);
+        assert_eq!(
+            kept,
+            BTreeMap::from([
+                ("delete_file".to_string(), "deny".to_string()),
+                ("send_email".to_string(), "auto".to_string()),
+            ])
+        );
+
+        // Chat answers win over the config; a config deny refuses without asking
+        let config = parse_permission_config(Some(
+            r#"{"delete_file": "require", "send_email": "require", "drop_table": "deny"}"#,
+        ));
+        let batch = vec![
+            tool_call("1", "delete_file"),
+            tool_call("2", "send_email"),
+            tool_call("3", "drop_table"),
+            tool_call("4", "read_file"),
+        ];
+        let (allowed, denied, rest) = take_remembered(batch.clone(), &kept, &config);
+        let ids = |calls: &[crate::models::llm_types::ToolCall]| {
+            calls.iter().map(|tc| tc.id.clone()).collect::<Vec<_>>()
+        };
+        assert_eq!(ids(&allowed), ["2"]);
+        assert_eq!(ids(&denied), ["1", "3"]);
+        assert_eq!(ids(&rest), ["4"]);
+        // A deny that reaches the split asks rather than runs
+        let (auto, gated) = partition_by_permission(&batch, Some(r#"{"drop_table": "deny"}"#));
+        assert_eq!(ids(&auto), ["1", "2", "4"]);
+        assert_eq!(ids(&gated), ["3"]);
+
+        // Shell commands ask every time: allowing one is not kept, and a
+        // kept "auto" does not settle them
+        let gated = vec![tool_call("1", "run_command")];
+        let decision = PermissionDecision {
+            approved: true,
+            allowed_tool_ids: vec!["1".to_string()],
+            remember: Some(RememberScope::Workspace),
+        };
+        assert!(remembered_permissions(&gated, &decision).is_empty());
+        let kept = BTreeMap::from([("run_command".to_string(), "auto".to_string())]);
+        let (allowed, _, rest) = take_remembered(gated, &kept, &BTreeMap::new());
+        assert!(allowed.is_empty());
+        assert_eq!(ids(&rest), ["1"]);
+        assert!(validate_tool_entry("run_command", "auto").is_err());
+        assert!(validate_tool_entry("run_command", "deny").is_ok());
+        assert!(validate_tool_entry("send_email", "auto").is_ok());
+
+        assert!(validate_tool_permission("deny").is_ok());
+        assert!(validate_permission("deny").is_err());
+
+        // Chat answers are stored per chat and listed for the workspace
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('c1', 'w1', 'One', 0, 0), ('c2', 'w1', 'Two', 0, 0);",
+        )
+        .unwrap();
+        set_chat_tool_permission(&conn, "c1", "run_command", Some("auto")).unwrap();
+        set_chat_tool_permission(&conn, "c1", "run_command", Some("deny")).unwrap();
+        set_chat_tool_permission(&conn, "c2", "delete_file", Some("deny")).unwrap();
+        assert_eq!(
+            chat_tool_permissions(&conn, "c1").unwrap(),
+            BTreeMap::from([("run_command".to_string(), "deny".to_string())])
+        );
+        let listed = workspace_chat_tool_permissions(&conn, "w1").unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed.iter().all(|p| p.scope == RememberScope::Chat));
+        assert_eq!(listed[1].chat_id.as_deref(), Some("c2"));
+
+        // Revoked answers ask again; deleted chats take theirs along
+        set_chat_tool_permission(&conn, "c1", "run_command", None).unwrap();
+        assert!(chat_tool_permissions(&conn, "c1").unwrap().is_empty());
+        conn.execute("PRAGMA foreign_keys = ON", []).unwrap();
+        conn.execute("DELETE FROM chats WHERE id = 'c2'", [])
+            .unwrap();
+        assert!(workspace_chat_tool_permissions(&conn, "w1")
+            .unwrap()
+            .is_empty());
+    }
+}
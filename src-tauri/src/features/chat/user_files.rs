@@ -0,0 +1,126 @@
+//! Files attached to user messages, sent to the model as content parts.
+//!
+//! The paths come from message metadata, which is only as trustworthy as
+//! the database. A file the chat may not read is skipped before it is
+//! opened, so a tampered or stale reference costs that file, never the turn.
+
+use crate::models::llm_types::{ContentPart, UserContent};
+
+/// The files a user message's metadata references: the `files` array, or
+/// the `images` array of older messages.
+pub fn metadata_files(metadata: &str) -> Option<Vec<String>> {
+    let meta_json = serde_json::from_str::<serde_json::Value>(metadata).ok()?;
+    let files = meta_json
+        .get("files")
+        .or_else(|| meta_json.get("images"))?
+        .as_array()?;
+    Some(
+        files
+            .iter()
+            .filter_map(|f| f.as_str().map(ToString::to_string))
+            .collect(),
+    )
+}
+
+/// `text` followed by a part for every file `allowed` lets the chat read;
+/// `load` is only called for those.
+pub fn user_content(
+    text: String,
+    files: Option<&[String]>,
+    allowed: impl Fn(&str) -> bool,
+    mut load: impl FnMut(&str) -> ContentPart,
+) -> UserContent {
+    let Some(files) = files.filter(|files| !files.is_empty()) else {
+        return UserContent::Text(text);
+    };
+    let mut parts = Vec::new();
+    if !text.is_empty() {
+        parts.push(ContentPart::Text { text });
+    }
+    for file_path in files {
+        if !allowed(file_path) {
+            tracing::warn!(
+                path = %file_path,
+                "Skipping file reference outside the attachments directory"
+            );
+            continue;
+        }
+        parts.push(load(file_path));
+    }
+    UserContent::Parts(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::NoAttachments;
+
+    #[test]
+    fn traversal_file_references_are_never_read() {
+        use crate::features::attachment::AttachmentService;
+        use crate::features::chat::user_files::{metadata_files, user_content};
+        use crate::models::llm_types::{ContentPart, ImageUrl, UserContent};
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let files_dir = dir.path().join("files");
+        std::fs::create_dir(&files_dir).unwrap();
+        std::fs::write(files_dir.join("photo.png"), b"png").unwrap();
+        std::fs::write(dir.path().join("secret.txt"), b"key").unwrap();
+        let attachments = AttachmentService::new(Arc::new(NoAttachments), files_dir.clone());
+
+        // Metadata edited to reach out of the attachments directory
+        let metadata = serde_json::json!({
+            "files": [
+                files_dir.join("photo.png"),
+                files_dir.join("../secret.txt"),
+                "/etc/passwd",
+            ]
+        })
+        .to_string();
+        let files = metadata_files(&metadata).unwrap();
+        assert_eq!(files.len(), 3);
+
+        let mut loaded = Vec::new();
+        let content = user_content(
+            "Look at this".to_string(),
+            Some(files.as_slice()),
+            |path| attachments.is_file_access_allowed(path),
+            |path| {
+                loaded.push(path.to_string());
+                ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: path.to_string(),
+                    },
+                }
+            },
+        );
+        assert_eq!(loaded, [files[0].clone()]);
+        // The turn goes on with the text and the file it may read
+        let UserContent::Parts(parts) = content else {
+            panic!("expected parts");
+        };
+        assert_eq!(parts.len(), 2);
+        assert!(matches!(&parts[0], ContentPart::Text { text } if text == "Look at this"));
+
+        // Approving the resolved file lets it through for the session
+        let secret = AttachmentService::resolve_file(&files[1]).unwrap();
+        assert_eq!(
+            secret,
+            dir.path().join("secret.txt").canonicalize().unwrap()
+        );
+        attachments.approve_file_access(secret).unwrap();
+        assert!(attachments.is_file_access_allowed(&files[1]));
+        assert!(AttachmentService::resolve_file(dir.path().to_str().unwrap()).is_err());
+
+        // Older messages list images only; no files means plain text
+        assert_eq!(
+            metadata_files(r#"{"images": ["a.png"]}"#).unwrap(),
+            ["a.png"]
+        );
+        assert!(metadata_files(r#"{"model": "gpt-4o"}"#).is_none());
+        assert!(matches!(
+            user_content("Hi".to_string(), Some(&[][..]), |_| true, |_| unreachable!()),
+            UserContent::Text(text) if text == "Hi"
+        ));
+    }
+}
@@ -0,0 +1,198 @@
+//! Earlier generations of an answer, kept when it is regenerated.
+//!
+//! A regenerated answer is a new assistant message. The answers it replaced
+//! move into its metadata under `variants`, oldest first, and `variantIndex`
+//! is the place of the shown answer among all generations. Selecting another
+//! one swaps it with the shown answer, so the history sent to the model is
+//! always the selected answer. Only a generation's final answer is kept, not
+//! the tool calls that led to it.
+//!
+//! The top-level `fileHashes` hold the files of every generation, so stored
+//! images of a hidden variant are counted as referenced.
+
+use crate::error::AppError;
+use crate::features::message::Message;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+pub const VARIANTS_METADATA_KEY: &str = "variants";
+pub const VARIANT_INDEX_METADATA_KEY: &str = "variantIndex";
+const FILE_HASHES_KEY: &str = "fileHashes";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct MessageVariant {
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning: Option<String>,
+    /// The answer's own metadata, without the variant keys
+    #[serde(default, skip_serializing_if = "Map::is_empty")]
+    pub metadata: Map<String, Value>,
+}
+
+/// Every generation of `message` in order, the shown one included.
+pub fn generations(message: &Message) -> Vec<MessageVariant> {
+    let mut metadata = metadata_object(message.metadata.as_deref());
+    let mut others: Vec<MessageVariant> = metadata
+        .remove(VARIANTS_METADATA_KEY)
+        .and_then(|variants| serde_json::from_value(variants).ok())
+        .unwrap_or_default();
+    let index = metadata
+        .remove(VARIANT_INDEX_METADATA_KEY)
+        .as_ref()
+        .and_then(Value::as_u64)
+        .and_then(|index| usize::try_from(index).ok())
+        .map_or(others.len(), |index| index.min(others.len()));
+    others.insert(
+        index,
+        MessageVariant {
+            content: message.content.clone(),
+            reasoning: message.reasoning.clone(),
+            metadata,
+        },
+    );
+    others
+}
+
+/// Keep `earlier` generations in the metadata of a new answer, which is
+/// shown after them.
+pub fn attach(metadata: &mut Value, earlier: &[MessageVariant]) {
+    if earlier.is_empty() {
+        return;
+    }
+    if !metadata.is_object() {
+        *metadata = Value::Object(Map::new());
+    }
+    let index = earlier.len();
+    store(metadata, earlier, index);
+}
+
+/// `message` showing generation `index`, its content, reasoning and metadata
+/// swapped with the shown one's.
+pub fn select(message: &Message, index: usize) -> Result<Message, AppError> {
+    let mut all = generations(message);
+    if index >= all.len() {
+        return Err(AppError::Validation(format!(
+            "The message has {} variants, not {}",
+            all.len(),
+            index + 1
+        )));
+    }
+    let shown = all.remove(index);
+    let mut metadata = Value::Object(shown.metadata);
+    store(&mut metadata, &all, index);
+    Ok(Message {
+        content: shown.content,
+        reasoning: shown.reasoning,
+        metadata: Some(metadata.to_string()),
+        ..message.clone()
+    })
+}
+
+/// The shown generation's place and the number of generations of `message`.
+pub fn position(message: &Message) -> (usize, usize) {
+    let metadata = metadata_object(message.metadata.as_deref());
+    let count = metadata
+        .get(VARIANTS_METADATA_KEY)
+        .and_then(Value::as_array)
+        .map_or(0, Vec::len);
+    let index = metadata
+        .get(VARIANT_INDEX_METADATA_KEY)
+        .and_then(Value::as_u64)
+        .and_then(|index| usize::try_from(index).ok())
+        .map_or(count, |index| index.min(count));
+    (index, count + 1)
+}
+
+fn store(metadata: &mut Value, others: &[MessageVariant], index: usize) {
+    let mut hashes = metadata
+        .get(FILE_HASHES_KEY)
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_default();
+    for variant in others {
+        if let Some(theirs) = variant
+            .metadata
+            .get(FILE_HASHES_KEY)
+            .and_then(Value::as_object)
+        {
+            for (file, hash) in theirs {
+                hashes.entry(file.clone()).or_insert_with(|| hash.clone());
+            }
+        }
+    }
+    if !hashes.is_empty() {
+        metadata[FILE_HASHES_KEY] = Value::Object(hashes);
+    }
+    metadata[VARIANTS_METADATA_KEY] = serde_json::json!(others);
+    metadata[VARIANT_INDEX_METADATA_KEY] = serde_json::json!(index);
+}
+
+fn metadata_object(metadata: Option<&str>) -> Map<String, Value> {
+    metadata
+        .and_then(|m| serde_json::from_str::<Value>(m).ok())
+        .and_then(|m| match m {
+            Value::Object(map) => Some(map),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{chat_message, contents};
+
+    #[test]
+    fn regenerated_answers_keep_earlier_generations_as_selectable_variants() {
+        use crate::features::chat::variants::{self, VARIANTS_METADATA_KEY};
+        use crate::features::message::client_update::{apply_update, MessageUpdate};
+
+        let mut first = chat_message("a1", "assistant", "First answer");
+        first.reasoning = Some("thought".to_string());
+        first.metadata =
+            Some(r#"{"tokenUsage":{"total_tokens":5},"fileHashes":{"a.png":"h1"}}"#.to_string());
+        assert_eq!(variants::position(&first), (0, 1));
+
+        // Regenerated: the first answer moves into the new one's variants
+        let mut second = chat_message("a2", "assistant", "Second answer");
+        let mut metadata = serde_json::json!({ "fileHashes": { "b.png": "h2" } });
+        variants::attach(&mut metadata, &variants::generations(&first));
+        second.metadata = Some(metadata.to_string());
+        assert_eq!(variants::position(&second), (1, 2));
+        assert_eq!(metadata["fileHashes"]["a.png"], "h1");
+        assert_eq!(metadata["fileHashes"]["b.png"], "h2");
+
+        let shown_first = variants::select(&second, 0).unwrap();
+        assert_eq!(shown_first.content, "First answer");
+        assert_eq!(shown_first.reasoning.as_deref(), Some("thought"));
+        assert_eq!(variants::position(&shown_first), (0, 2));
+        let meta: serde_json::Value =
+            serde_json::from_str(shown_first.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(meta["tokenUsage"]["total_tokens"], 5);
+        assert_eq!(meta[VARIANTS_METADATA_KEY][0]["content"], "Second answer");
+        // Files of every generation stay referenced
+        assert_eq!(meta["fileHashes"].as_object().unwrap().len(), 2);
+
+        // A third generation keeps both earlier ones, in order
+        let mut third = chat_message("a3", "assistant", "Third answer");
+        let mut metadata = serde_json::json!({});
+        variants::attach(&mut metadata, &variants::generations(&shown_first));
+        third.metadata = Some(metadata.to_string());
+        let contents: Vec<String> = variants::generations(&third)
+            .into_iter()
+            .map(|v| v.content)
+            .collect();
+        assert_eq!(contents, ["First answer", "Second answer", "Third answer"]);
+        assert_eq!(
+            variants::select(&third, 1).unwrap().content,
+            "Second answer"
+        );
+        assert!(variants::select(&third, 3).is_err());
+
+        // The frontend cannot rewrite the variants
+        let update = MessageUpdate {
+            metadata: Some(serde_json::json!({ VARIANTS_METADATA_KEY: [] })),
+            ..MessageUpdate::default()
+        };
+        assert!(apply_update(&third, update).is_err());
+    }
+}
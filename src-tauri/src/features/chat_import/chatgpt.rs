@@ -0,0 +1,318 @@
+//! ChatGPT's `conversations.json`.
+//!
+//! Each conversation is a tree of nodes (`mapping`) where regenerated answers
+//! and edited prompts start sibling branches; `current_node` is the leaf of
+//! the branch the user last looked at, which is the one imported.
+
+use super::models::ImportedConversation;
+use crate::error::AppError;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+const UNTITLED: &str = "Imported ChatGPT chat";
+
+#[derive(Deserialize)]
+struct Conversation {
+    #[serde(default)]
+    id: Option<String>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    update_time: Option<f64>,
+    #[serde(default)]
+    mapping: HashMap<String, Node>,
+    #[serde(default)]
+    current_node: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Node {
+    #[serde(default)]
+    message: Option<Message>,
+    #[serde(default)]
+    parent: Option<String>,
+    #[serde(default)]
+    children: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    author: Author,
+    #[serde(default)]
+    create_time: Option<f64>,
+    #[serde(default)]
+    content: Option<Content>,
+    #[serde(default)]
+    metadata: serde_json::Value,
+    #[serde(default)]
+    recipient: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Author {
+    role: String,
+}
+
+#[derive(Deserialize)]
+struct Content {
+    content_type: String,
+    #[serde(default)]
+    parts: Vec<serde_json::Value>,
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn millis(seconds: f64) -> i64 {
+    (seconds * 1000.0).round() as i64
+}
+
+/// Convert one element of the export.
+pub fn convert(value: serde_json::Value) -> Result<ImportedConversation, AppError> {
+    let conversation: Conversation = serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("Unrecognized ChatGPT conversation: {e}")))?;
+    let path = canonical_path(&conversation);
+    let on_path: HashSet<&str> = path.iter().map(String::as_str).collect();
+
+    let mut imported = ImportedConversation {
+        external_id: conversation
+            .conversation_id
+            .clone()
+            .or_else(|| conversation.id.clone())
+            .unwrap_or_default(),
+        title: conversation
+            .title
+            .clone()
+            .filter(|title| !title.trim().is_empty())
+            .unwrap_or_else(|| UNTITLED.to_string()),
+        created_at: conversation.create_time.map_or(0, millis),
+        branched: conversation
+            .mapping
+            .values()
+            .any(|node| node.children.len() > 1),
+        ..ImportedConversation::default()
+    };
+
+    for (id, node) in &conversation.mapping {
+        if node.message.is_some() && !on_path.contains(id.as_str()) {
+            imported.skip("other branch");
+        }
+    }
+    for id in &path {
+        if let Some(message) = conversation
+            .mapping
+            .get(id)
+            .and_then(|node| node.message.as_ref())
+        {
+            convert_message(message, &mut imported);
+        }
+    }
+
+    if imported.created_at == 0 {
+        imported.created_at = imported.messages.first().map_or(0, |m| m.timestamp);
+    }
+    imported.updated_at = conversation
+        .update_time
+        .map(millis)
+        .or_else(|| imported.messages.last().map(|m| m.timestamp))
+        .unwrap_or(imported.created_at);
+    Ok(imported)
+}
+
+/// Node ids from the root to `current_node`. Without a usable
+/// `current_node`, the newest child is followed down from the root.
+fn canonical_path(conversation: &Conversation) -> Vec<String> {
+    let mapping = &conversation.mapping;
+    let leaf = conversation
+        .current_node
+        .clone()
+        .filter(|id| mapping.contains_key(id))
+        .or_else(|| {
+            let mut id = mapping
+                .iter()
+                .find(|(_, node)| node.parent.is_none())?
+                .0
+                .clone();
+            let mut seen = HashSet::new();
+            while seen.insert(id.clone()) {
+                match mapping.get(&id).and_then(|node| node.children.last()) {
+                    Some(child) if mapping.contains_key(child) => id.clone_from(child),
+                    _ => break,
+                }
+            }
+            Some(id)
+        });
+
+    let mut path = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = leaf;
+    while let Some(id) = next {
+        if !seen.insert(id.clone()) {
+            break;
+        }
+        next = mapping.get(&id).and_then(|node| node.parent.clone());
+        path.push(id);
+    }
+    path.reverse();
+    path
+}
+
+fn convert_message(message: &Message, imported: &mut ImportedConversation) {
+    let role = match message.author.role.as_str() {
+        "user" => "user",
+        "assistant" => "assistant",
+        "system" => return imported.skip("system message"),
+        "tool" => return imported.skip("tool output"),
+        other => return imported.skip(format!("unsupported role: {other}")),
+    };
+    let hidden = [
+        "is_visually_hidden_from_conversation",
+        "is_user_system_message",
+    ]
+    .iter()
+    .any(|flag| {
+        message
+            .metadata
+            .get(flag)
+            .and_then(serde_json::Value::as_bool)
+            == Some(true)
+    });
+    if hidden {
+        return imported.skip("hidden message");
+    }
+    if message
+        .recipient
+        .as_deref()
+        .is_some_and(|recipient| recipient != "all")
+    {
+        return imported.skip("tool call");
+    }
+    let Some(content) = &message.content else {
+        return imported.skip("empty message");
+    };
+
+    let mut attachments: Vec<String> = Vec::new();
+    let text = match content.content_type.as_str() {
+        "text" | "multimodal_text" => {
+            let mut texts = Vec::new();
+            for part in &content.parts {
+                match part {
+                    serde_json::Value::String(text) if !text.is_empty() => texts.push(text.clone()),
+                    serde_json::Value::String(_) => {}
+                    // Images and audio reference files the export keeps apart
+                    other => attachments.push(
+                        other
+                            .get("content_type")
+                            .and_then(serde_json::Value::as_str)
+                            .unwrap_or("file")
+                            .to_string(),
+                    ),
+                }
+            }
+            texts.join("\n")
+        }
+        other => return imported.skip(format!("unsupported content: {other}")),
+    };
+
+    let named = message
+        .metadata
+        .get("attachments")
+        .and_then(serde_json::Value::as_array)
+        .map(|list| {
+            list.iter()
+                .filter_map(|attachment| attachment.get("name").and_then(serde_json::Value::as_str))
+                .map(str::to_string)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    // Uploaded files appear both as parts and by name; the names say more
+    if !named.is_empty() {
+        let unnamed_images = attachments.len().saturating_sub(named.len());
+        attachments = named;
+        attachments.extend((0..unnamed_images).map(|_| "image_asset_pointer".to_string()));
+    }
+
+    imported.push(role, text, message.create_time.map(millis), &attachments);
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{contents, read_fixture};
+
+    #[test]
+    fn chatgpt_export_imports_the_canonical_path_without_tool_internals() {
+        use crate::features::chat_import::chatgpt;
+
+        let values = read_fixture(
+            "conversations.json",
+            include_str!("../features/chat_import/fixtures/chatgpt_conversations.json"),
+        )
+        .unwrap();
+        let converted: Vec<_> = values
+            .into_iter()
+            .map(|value| chatgpt::convert(value).unwrap())
+            .collect();
+        assert_eq!(converted.len(), 3);
+
+        let branched = &converted[0];
+        assert_eq!(branched.external_id, "6632a1f0-0000-4000-8000-000000000001");
+        assert_eq!(branched.title, "Rust ownership");
+        assert!(branched.branched);
+        assert_eq!(branched.created_at, 1_714_550_400_123);
+        assert_eq!(branched.updated_at, 1_714_550_700_500);
+        assert_eq!(
+            contents(branched),
+            vec![
+                ("user", "What does the borrow checker do?"),
+                (
+                    "assistant",
+                    "It checks that references never outlive the data they point to."
+                ),
+                (
+                    "user",
+                    "Count the lines in this file.\n\n_[Attachment not imported: main.rs]_"
+                ),
+                ("assistant", "The file has 42 lines."),
+            ]
+        );
+        let timestamps: Vec<i64> = branched.messages.iter().map(|m| m.timestamp).collect();
+        assert_eq!(
+            timestamps,
+            vec![
+                1_714_550_401_000,
+                1_714_550_410_000,
+                1_714_550_500_000,
+                1_714_550_504_000
+            ]
+        );
+        let mut skipped = branched.skipped.clone();
+        skipped.sort();
+        assert_eq!(
+            skipped,
+            vec!["other branch", "system message", "tool call", "tool output"]
+        );
+        assert_eq!(branched.skipped_attachments, 1);
+
+        // Untitled, with an image part and a reply stamped before its prompt
+        let image = &converted[1];
+        assert!(!image.branched);
+        assert_eq!(image.title, "Imported ChatGPT chat");
+        assert_eq!(
+            contents(image),
+            vec![
+                (
+                    "user",
+                    "What is in this picture?\n\n_[Attachment not imported: image_asset_pointer]_"
+                ),
+                ("assistant", "A cat on a keyboard."),
+            ]
+        );
+        assert_eq!(image.messages[1].timestamp, image.messages[0].timestamp + 1);
+
+        let hidden = &converted[2];
+        assert!(hidden.messages.is_empty());
+        assert_eq!(hidden.skipped, vec!["hidden message"]);
+    }
+}
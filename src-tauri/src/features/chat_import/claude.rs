@@ -0,0 +1,235 @@
+//! Claude's `conversations.json`.
+//!
+//! Messages are listed oldest first. Newer exports link each message to its
+//! parent, and a retried answer or edited prompt then starts a sibling
+//! branch; the branch ending in the newest message is the one imported.
+
+use super::models::ImportedConversation;
+use crate::error::AppError;
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+
+const UNTITLED: &str = "Imported Claude chat";
+
+#[derive(Deserialize)]
+struct Conversation {
+    uuid: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    updated_at: Option<String>,
+    #[serde(default)]
+    chat_messages: Vec<Message>,
+}
+
+#[derive(Deserialize)]
+struct Message {
+    #[serde(default)]
+    uuid: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+    #[serde(default)]
+    content: Vec<ContentBlock>,
+    sender: String,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    attachments: Vec<File>,
+    #[serde(default)]
+    files: Vec<File>,
+    #[serde(default)]
+    parent_message_uuid: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct File {
+    #[serde(default)]
+    file_name: Option<String>,
+}
+
+fn millis(timestamp: Option<&str>) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc3339(timestamp?)
+        .ok()
+        .map(|time| time.timestamp_millis())
+}
+
+/// Convert one element of the export.
+pub fn convert(value: serde_json::Value) -> Result<ImportedConversation, AppError> {
+    let conversation: Conversation = serde_json::from_value(value)
+        .map_err(|e| AppError::Validation(format!("Unrecognized Claude conversation: {e}")))?;
+    let (path, branched) = canonical_path(&conversation.chat_messages);
+
+    let mut imported = ImportedConversation {
+        external_id: conversation.uuid.clone(),
+        title: conversation
+            .name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| UNTITLED.to_string()),
+        created_at: millis(conversation.created_at.as_deref()).unwrap_or(0),
+        branched,
+        ..ImportedConversation::default()
+    };
+
+    let on_path: HashSet<usize> = path.iter().copied().collect();
+    for index in 0..conversation.chat_messages.len() {
+        if !on_path.contains(&index) {
+            imported.skip("other branch");
+        }
+    }
+    for index in path {
+        convert_message(&conversation.chat_messages[index], &mut imported);
+    }
+
+    if imported.created_at == 0 {
+        imported.created_at = imported.messages.first().map_or(0, |m| m.timestamp);
+    }
+    imported.updated_at = millis(conversation.updated_at.as_deref())
+        .or_else(|| imported.messages.last().map(|m| m.timestamp))
+        .unwrap_or(imported.created_at);
+    Ok(imported)
+}
+
+/// Indexes of the messages to import, in order, and whether the
+/// conversation branched. Without parent links every message is imported.
+fn canonical_path(messages: &[Message]) -> (Vec<usize>, bool) {
+    let linked = messages.iter().any(|m| m.parent_message_uuid.is_some());
+    if !linked || messages.is_empty() {
+        return ((0..messages.len()).collect(), false);
+    }
+
+    let by_uuid: HashMap<&str, usize> = messages
+        .iter()
+        .enumerate()
+        .filter_map(|(index, m)| Some((m.uuid.as_deref()?, index)))
+        .collect();
+    let mut children: HashMap<&str, usize> = HashMap::new();
+    for parent in messages
+        .iter()
+        .filter_map(|m| m.parent_message_uuid.as_deref())
+    {
+        *children.entry(parent).or_default() += 1;
+    }
+    let branched = children.values().any(|&count| count > 1);
+
+    let mut path = Vec::new();
+    let mut seen = HashSet::new();
+    let mut next = Some(messages.len() - 1);
+    while let Some(index) = next {
+        if !seen.insert(index) {
+            break;
+        }
+        path.push(index);
+        next = messages[index]
+            .parent_message_uuid
+            .as_deref()
+            .and_then(|parent| by_uuid.get(parent).copied());
+    }
+    path.reverse();
+    (path, branched)
+}
+
+fn convert_message(message: &Message, imported: &mut ImportedConversation) {
+    let role = match message.sender.as_str() {
+        "human" => "user",
+        "assistant" => "assistant",
+        other => return imported.skip(format!("unsupported role: {other}")),
+    };
+
+    // `content` holds thinking and tool blocks besides the text; only the
+    // text is kept. Older exports only have `text`.
+    let text = if message.content.is_empty() {
+        message.text.clone().unwrap_or_default()
+    } else {
+        message
+            .content
+            .iter()
+            .filter(|block| block.kind == "text")
+            .filter_map(|block| block.text.as_deref())
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    };
+    let attachments: Vec<String> = message
+        .attachments
+        .iter()
+        .chain(&message.files)
+        .map(|file| file.file_name.clone().unwrap_or_else(|| "file".to_string()))
+        .collect();
+
+    imported.push(
+        role,
+        text,
+        millis(message.created_at.as_deref()),
+        &attachments,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{contents, read_fixture};
+
+    #[test]
+    fn claude_export_keeps_text_and_notes_attachments() {
+        use crate::features::chat_import::claude;
+
+        let values = read_fixture(
+            "conversations.json",
+            include_str!("../features/chat_import/fixtures/claude_conversations.json"),
+        )
+        .unwrap();
+        let converted: Vec<_> = values
+            .into_iter()
+            .map(|value| claude::convert(value).unwrap())
+            .collect();
+        assert_eq!(converted.len(), 2);
+
+        let linear = &converted[0];
+        assert!(!linear.branched);
+        assert_eq!(linear.title, "Parsing a CSV");
+        assert_eq!(
+            contents(linear),
+            vec![
+                (
+                    "user",
+                    "How do I parse this CSV in Rust?\n\n_[Attachment not imported: prices.csv]_\n\n_[Attachment not imported: screenshot.png]_"
+                ),
+                (
+                    "assistant",
+                    "Use the `csv` crate with a `Reader`.\n\nThe artifact shows a full example."
+                ),
+            ]
+        );
+        let created = chrono::DateTime::parse_from_rfc3339("2024-05-01T09:00:01Z")
+            .unwrap()
+            .timestamp_millis();
+        assert_eq!(linear.messages[0].timestamp, created);
+        assert_eq!(linear.messages[1].timestamp, created + 8_500);
+        assert_eq!(linear.skipped, vec!["empty message"]);
+        assert_eq!(linear.skipped_attachments, 2);
+
+        // The retried answer starts a branch; the newest one is imported
+        let branched = &converted[1];
+        assert!(branched.branched);
+        assert_eq!(branched.title, "Imported Claude chat");
+        assert_eq!(
+            contents(branched),
+            vec![
+                ("user", "Name a prime number."),
+                ("assistant", "7"),
+                ("user", "Thanks!"),
+            ]
+        );
+        assert_eq!(branched.skipped, vec!["other branch"]);
+    }
+}
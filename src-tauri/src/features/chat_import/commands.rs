@@ -0,0 +1,63 @@
+use super::models::{ChatImportReport, ChatImportSource};
+use super::reader;
+use crate::error::AppError;
+use crate::events::emit_event;
+use crate::state::AppState;
+use std::path::PathBuf;
+use tauri::{AppHandle, State};
+
+/// Import the conversations of an export in the format `source` names into
+/// a workspace. The file is read off the main thread with
+/// `chat-import-progress` events; the first one carries the id to pass to
+/// `cancel_chat_import`.
+#[tauri::command]
+pub async fn import_external_chats(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    source: ChatImportSource,
+    workspace_id: String,
+) -> Result<ChatImportReport, AppError> {
+    let service = state.chat_import_service.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        service.import(&PathBuf::from(path), source, &workspace_id, &|progress| {
+            if let Err(e) = emit_event(&app, progress) {
+                tracing::warn!(error = %e, "Failed to emit chat import progress");
+            }
+        })
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Chat import failed: {e}")))?
+}
+
+/// Import chats from a Lunex JSON export, a ChatGPT or Claude
+/// `conversations.json`, or a JSONL file of messages into a workspace. The
+/// format is detected from the file unless `source` is given; otherwise
+/// this works like `import_external_chats`.
+#[tauri::command]
+pub async fn import_chat(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    workspace_id: String,
+    source: Option<ChatImportSource>,
+) -> Result<ChatImportReport, AppError> {
+    let path = PathBuf::from(path);
+    let source = match source {
+        Some(source) => source,
+        None => reader::detect_source(&path)?,
+    };
+    import_external_chats(
+        app,
+        state,
+        path.to_string_lossy().to_string(),
+        source,
+        workspace_id,
+    )
+    .await
+}
+
+#[tauri::command]
+pub fn cancel_chat_import(state: State<'_, AppState>, import_id: String) -> Result<(), AppError> {
+    state.chat_import_service.cancel(&import_id)
+}
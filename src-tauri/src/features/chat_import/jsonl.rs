@@ -0,0 +1,239 @@
+//! Generic JSONL: one message per line.
+//!
+//! Each line is an object with `role` and `content`, where `content` is text
+//! or a list of `{ "type": "text", "text": ... }` parts. `timestamp` (epoch
+//! seconds or milliseconds, or RFC 3339), `title` and `files` (data URLs or
+//! paths) are optional. Lines with a `conversation_id` are grouped into one
+//! chat per id; a file without any is a single chat named after the file.
+
+use super::models::{ImportedConversation, ImportedMessage};
+use crate::error::AppError;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+const UNTITLED: &str = "Imported chat";
+/// Numeric timestamps below this are in seconds rather than milliseconds.
+const SECONDS_BEFORE: f64 = 100_000_000_000.0;
+
+#[derive(Deserialize)]
+struct Line {
+    role: String,
+    #[serde(default)]
+    content: Content,
+    #[serde(default)]
+    timestamp: Option<Timestamp>,
+    #[serde(default)]
+    conversation_id: Option<String>,
+    #[serde(default)]
+    title: Option<String>,
+    #[serde(default)]
+    files: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Content {
+    Text(String),
+    Parts(Vec<Part>),
+}
+
+impl Default for Content {
+    fn default() -> Self {
+        Self::Text(String::new())
+    }
+}
+
+#[derive(Deserialize)]
+struct Part {
+    #[serde(default, rename = "type")]
+    kind: Option<String>,
+    #[serde(default)]
+    text: Option<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Timestamp {
+    Number(f64),
+    Text(String),
+}
+
+impl Timestamp {
+    #[allow(clippy::cast_possible_truncation)]
+    fn millis(&self) -> Option<i64> {
+        match self {
+            Self::Number(n) if *n < SECONDS_BEFORE => Some((n * 1000.0) as i64),
+            Self::Number(n) => Some(*n as i64),
+            Self::Text(text) => chrono::DateTime::parse_from_rfc3339(text)
+                .ok()
+                .map(|time| time.timestamp_millis()),
+        }
+    }
+}
+
+/// The conversations of the JSONL file at `path`, in order of their first
+/// line. Lines that cannot be read are skipped with a reason.
+pub fn read(path: &Path) -> Result<Vec<ImportedConversation>, AppError> {
+    let file = File::open(path)
+        .map_err(|e| AppError::Validation(format!("Failed to open {}: {e}", path.display())))?;
+    let default_title = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .filter(|stem| !stem.trim().is_empty())
+        .unwrap_or_else(|| UNTITLED.to_string());
+    convert_lines(
+        BufReader::new(file).lines(),
+        &default_title,
+        chrono::Utc::now().timestamp_millis(),
+    )
+}
+
+/// Messages without a timestamp are placed at `now`, or just after the
+/// message before them.
+pub fn convert_lines(
+    lines: impl Iterator<Item = std::io::Result<String>>,
+    default_title: &str,
+    now: i64,
+) -> Result<Vec<ImportedConversation>, AppError> {
+    let mut conversations: Vec<ImportedConversation> = Vec::new();
+    for line in lines {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let parsed = serde_json::from_str::<Line>(&line);
+        let conversation_id = parsed
+            .as_ref()
+            .ok()
+            .and_then(|l| l.conversation_id.clone())
+            .unwrap_or_default();
+        let index = conversations
+            .iter()
+            .position(|c| c.external_id == conversation_id)
+            .unwrap_or_else(|| {
+                conversations.push(ImportedConversation {
+                    external_id: conversation_id,
+                    title: default_title.to_string(),
+                    created_at: now,
+                    ..ImportedConversation::default()
+                });
+                conversations.len() - 1
+            });
+        let conversation = &mut conversations[index];
+        match parsed {
+            Ok(line) => convert_line(line, conversation),
+            Err(_) => conversation.skip("unreadable line"),
+        }
+    }
+
+    for conversation in &mut conversations {
+        if let Some(first) = conversation.messages.first() {
+            conversation.created_at = first.timestamp;
+        }
+        conversation.updated_at = conversation
+            .messages
+            .last()
+            .map_or(conversation.created_at, |m| m.timestamp);
+    }
+    Ok(conversations)
+}
+
+fn convert_line(line: Line, conversation: &mut ImportedConversation) {
+    if let Some(title) = line.title.filter(|t| !t.trim().is_empty()) {
+        conversation.title = title;
+    }
+    let role = match line.role.to_lowercase().as_str() {
+        "user" | "human" => "user",
+        "assistant" | "ai" | "model" | "bot" => "assistant",
+        other => return conversation.skip(format!("unsupported role: {other}")),
+    };
+    let content = match line.content {
+        Content::Text(text) => text,
+        Content::Parts(parts) => parts
+            .into_iter()
+            .filter(|part| part.kind.as_deref().is_none_or(|kind| kind == "text"))
+            .filter_map(|part| part.text)
+            .filter(|text| !text.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+    };
+    conversation.push_message(
+        ImportedMessage {
+            role: role.to_string(),
+            content,
+            files: line.files,
+            ..ImportedMessage::default()
+        },
+        line.timestamp.as_ref().and_then(Timestamp::millis),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::contents;
+
+    #[test]
+    fn jsonl_imports_group_by_conversation_and_normalize_timestamps() {
+        use crate::features::chat_import::jsonl::convert_lines;
+        use crate::features::chat_import::reader::detect_source;
+        use crate::features::chat_import::ChatImportSource;
+
+        let lines = [
+            r#"{"role":"user","content":"Hi","timestamp":1700000000}"#,
+            r#"{"role":"assistant","content":[{"type":"text","text":"Hello"},{"type":"image"}],"timestamp":1700000000500}"#,
+            "",
+            r#"{"role":"system","content":"Be nice"}"#,
+            "not json",
+            r#"{"role":"human","content":"Other","conversation_id":"b","title":"Second","timestamp":"2024-01-01T00:00:00Z"}"#,
+            r#"{"role":"ai","content":"Reply","conversation_id":"b"}"#,
+        ];
+        let conversations = convert_lines(
+            lines.iter().map(|line| Ok((*line).to_string())),
+            "notes",
+            42,
+        )
+        .unwrap();
+        assert_eq!(conversations.len(), 2);
+
+        let first = &conversations[0];
+        assert_eq!(first.title, "notes");
+        assert_eq!(contents(first), [("user", "Hi"), ("assistant", "Hello")]);
+        assert_eq!(first.messages[0].timestamp, 1_700_000_000_000);
+        assert_eq!(first.messages[1].timestamp, 1_700_000_000_500);
+        assert_eq!(
+            first.skipped,
+            ["unsupported role: system", "unreadable line"]
+        );
+        assert_eq!(first.created_at, 1_700_000_000_000);
+        assert_eq!(first.updated_at, 1_700_000_000_500);
+
+        let second = &conversations[1];
+        assert_eq!(second.title, "Second");
+        assert_eq!(
+            contents(second),
+            [("user", "Other"), ("assistant", "Reply")]
+        );
+        assert_eq!(second.messages[0].timestamp, 1_704_067_200_000);
+        assert_eq!(second.messages[1].timestamp, 1_704_067_200_001);
+
+        let dir = tempfile::tempdir().unwrap();
+        let detect = |name: &str, contents: &str| {
+            let path = dir.path().join(name);
+            std::fs::write(&path, contents).unwrap();
+            detect_source(&path)
+        };
+        assert_eq!(detect("a.jsonl", "").unwrap(), ChatImportSource::Jsonl);
+        assert_eq!(detect("b.json", lines[0]).unwrap(), ChatImportSource::Jsonl);
+        assert_eq!(
+            detect("c.json", r#"[{"title":"x","mapping":{}}]"#).unwrap(),
+            ChatImportSource::Chatgpt
+        );
+        assert_eq!(
+            detect("d.json", r#"[{"uuid":"x","chat_messages":[]}]"#).unwrap(),
+            ChatImportSource::Claude
+        );
+        assert!(detect("e.txt", "hello").is_err());
+    }
+}
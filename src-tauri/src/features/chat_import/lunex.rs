@@ -0,0 +1,197 @@
+//! JSON exports of this app, from `export_chat` and `export_workspace`.
+//!
+//! They carry what the chat showed: reasoning, tool calls with their results
+//! and attached files, with images from message content embedded as data
+//! URLs. The export is one JSON object rather than an array, so it is read
+//! whole. Attached files are copied into the attachments directory; the
+//! paths the export names are never kept.
+
+use super::models::{ImportedConversation, ImportedMessage, ImportedToolCall};
+use crate::error::AppError;
+use crate::features::attachment::AttachmentService;
+use crate::features::chat::export::{ChatExport, ExportedChat, CHAT_EXPORT_VERSION};
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+
+/// The chats of the export at `path`, converted.
+pub fn read(
+    path: &Path,
+    attachments: &AttachmentService,
+) -> Result<Vec<ImportedConversation>, AppError> {
+    let file = File::open(path)
+        .map_err(|e| AppError::Validation(format!("Failed to open {}: {e}", path.display())))?;
+    let export: ChatExport = serde_json::from_reader(BufReader::new(file))
+        .map_err(|e| AppError::Validation(format!("Invalid Lunex export: {e}")))?;
+    if export.version > CHAT_EXPORT_VERSION {
+        return Err(AppError::Validation(format!(
+            "The export was made by a newer version of Lunex (format {})",
+            export.version
+        )));
+    }
+    Ok(export
+        .chats
+        .into_iter()
+        .map(|chat| convert(chat, attachments))
+        .collect())
+}
+
+/// Convert one chat, storing its attached files. A file that cannot be read
+/// from here becomes a note in its message.
+pub fn convert(chat: ExportedChat, attachments: &AttachmentService) -> ImportedConversation {
+    let mut imported = ImportedConversation {
+        external_id: chat.id,
+        title: chat.title,
+        created_at: chat.created_at,
+        updated_at: chat.updated_at,
+        custom_instructions: chat.custom_instructions,
+        ..ImportedConversation::default()
+    };
+    for message in chat.messages {
+        if !matches!(message.role.as_str(), "user" | "assistant") {
+            imported.skip(format!("unsupported role: {}", message.role));
+            continue;
+        }
+        // A call without a result would leave history providers reject
+        let (tool_calls, unanswered): (Vec<_>, Vec<_>) = message
+            .tool_calls
+            .into_iter()
+            .partition(|call| call.result.is_some());
+        for _ in unanswered {
+            imported.skip("tool call without result");
+        }
+        let mut content = message.content;
+        let mut files = Vec::new();
+        let mut missing = Vec::new();
+        for file in message.files {
+            match attachments.store_reference(&file.path) {
+                Some((path, _)) => files.push(path),
+                None => missing.push(file.name),
+            }
+        }
+        imported.note_attachments(&mut content, &missing);
+        imported.push_message(
+            ImportedMessage {
+                role: message.role,
+                content,
+                reasoning: message.reasoning,
+                files,
+                tool_calls: tool_calls
+                    .into_iter()
+                    .map(|call| ImportedToolCall {
+                        name: call.name,
+                        arguments: call.arguments,
+                        status: call.status,
+                        result: call.result.unwrap_or_default(),
+                    })
+                    .collect(),
+                ..ImportedMessage::default()
+            },
+            Some(message.timestamp),
+        );
+    }
+    imported
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    #[test]
+    fn lunex_exports_import_back_with_tool_calls_and_are_detected() {
+        use crate::features::attachment::AttachmentService;
+        use crate::features::chat::export::{exported_chat, ChatExport, CHAT_EXPORT_VERSION};
+        use crate::features::chat::models::Chat;
+        use crate::features::chat_import::reader::detect_source;
+        use crate::features::chat_import::{lunex, ChatImportSource};
+        use crate::test_support::MemoryAttachmentRepository;
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let files_dir = dir.path().join("files");
+        let attachments = AttachmentService::new(
+            Arc::new(MemoryAttachmentRepository::default()),
+            files_dir.clone(),
+        );
+        // A file the export points at but this app never stored
+        let outside = dir.path().join("map.png");
+        std::fs::write(&outside, b"map").unwrap();
+
+        let chat = Chat {
+            id: "chat".to_string(),
+            workspace_id: "ws".to_string(),
+            title: "Trip".to_string(),
+            last_message: None,
+            created_at: 5,
+            updated_at: 50,
+            agent_id: None,
+            parent_id: None,
+            last_read_at: None,
+            unread_count: 0,
+            custom_instructions: Some("Be brief".to_string()),
+        };
+        let mut question = chat_message("m1", "user", "Weather?");
+        question.timestamp = 10;
+        question.metadata = Some(
+            serde_json::json!({ "files": ["data:image/png;base64,cG5n", outside] }).to_string(),
+        );
+        let mut answer = chat_message("m2", "assistant", "");
+        answer.timestamp = 20;
+        answer.reasoning = Some("Look it up".to_string());
+        let mut call = chat_message(
+            "tool_call_c1",
+            "tool_call",
+            r#"{"name":"weather","arguments":"{}","status":"completed"}"#,
+        );
+        call.assistant_message_id = Some("m2".to_string());
+        let mut unanswered = chat_message(
+            "tool_call_c2",
+            "tool_call",
+            r#"{"name":"weather","arguments":"{}"}"#,
+        );
+        unanswered.assistant_message_id = Some("m2".to_string());
+        let mut result = chat_message("tool_result_c1", "tool", "Sunny");
+        result.tool_call_id = Some("c1".to_string());
+        let export = ChatExport {
+            version: CHAT_EXPORT_VERSION,
+            exported_at: 0,
+            workspace_id: None,
+            chats: vec![exported_chat(
+                &chat,
+                &[question, answer, call, unanswered, result],
+            )],
+        };
+
+        let path = dir.path().join("Trip.json");
+        std::fs::write(&path, serde_json::to_string_pretty(&export).unwrap()).unwrap();
+        assert_eq!(detect_source(&path).unwrap(), ChatImportSource::Lunex);
+
+        let conversations = lunex::read(&path, &attachments).unwrap();
+        assert_eq!(conversations.len(), 1);
+        let imported = &conversations[0];
+        assert_eq!(imported.title, "Trip");
+        assert_eq!(imported.custom_instructions.as_deref(), Some("Be brief"));
+        assert_eq!((imported.created_at, imported.updated_at), (5, 50));
+        assert_eq!(imported.skipped, ["tool call without result"]);
+        assert_eq!(imported.messages.len(), 2);
+        // Files are copied in; paths outside the attachments directory are not read
+        let question = &imported.messages[0];
+        assert_eq!(question.files.len(), 1);
+        assert!(std::path::Path::new(&question.files[0]).starts_with(&files_dir));
+        assert_eq!(
+            question.content,
+            "Weather?\n\n_[Attachment not imported: map.png]_"
+        );
+        assert_eq!(imported.skipped_attachments, 1);
+        // The answer has no text but is kept for its tool call
+        let answer = &imported.messages[1];
+        assert_eq!(answer.timestamp, 20);
+        assert_eq!(answer.reasoning.as_deref(), Some("Look it up"));
+        assert_eq!(answer.tool_calls.len(), 1);
+        assert_eq!(answer.tool_calls[0].result, "Sunny");
+
+        let newer = path.with_file_name("newer.json");
+        std::fs::write(&newer, r#"{"version":99,"exported_at":0,"chats":[]}"#).unwrap();
+        assert!(lunex::read(&newer, &attachments).is_err());
+    }
+}
@@ -0,0 +1,11 @@
+pub mod chatgpt;
+pub mod claude;
+pub mod commands;
+pub mod jsonl;
+pub mod lunex;
+pub mod models;
+pub mod reader;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The tool an export file comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChatImportSource {
+    /// `conversations.json` from ChatGPT's "Export data"
+    Chatgpt,
+    /// `conversations.json` from Claude's "Export data"
+    Claude,
+    /// A JSON export of this app, of one chat or a whole workspace
+    Lunex,
+    /// One message per line with `role` and `content`, optionally grouped
+    /// into conversations by `conversation_id`
+    Jsonl,
+}
+
+impl ChatImportSource {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Chatgpt => "chatgpt",
+            Self::Claude => "claude",
+            Self::Lunex => "lunex",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// A conversation of an export, converted but not stored yet.
+#[derive(Debug, Clone, Default)]
+pub struct ImportedConversation {
+    pub external_id: String,
+    pub title: String,
+    pub created_at: i64,
+    pub updated_at: i64,
+    /// Standing instructions of the chat, from exports of this app
+    pub custom_instructions: Option<String>,
+    /// The canonical path only, oldest first, with increasing timestamps
+    pub messages: Vec<ImportedMessage>,
+    /// Messages left out, with why
+    pub skipped: Vec<String>,
+    /// Attachments replaced by a note in their message
+    pub skipped_attachments: usize,
+    /// The conversation had branches; only the canonical path is imported
+    pub branched: bool,
+}
+
+impl ImportedConversation {
+    /// Add a message of the canonical path, with a note in place of each
+    /// attachment the export does not include.
+    pub fn push(
+        &mut self,
+        role: &str,
+        mut content: String,
+        timestamp: Option<i64>,
+        attachments: &[String],
+    ) {
+        self.note_attachments(&mut content, attachments);
+        self.push_message(
+            ImportedMessage {
+                role: role.to_string(),
+                content,
+                ..ImportedMessage::default()
+            },
+            timestamp,
+        );
+    }
+
+    /// Append a note to `content` for each attachment that is not imported.
+    pub fn note_attachments(&mut self, content: &mut String, names: &[String]) {
+        for name in names {
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            content.push_str(&format!("_[Attachment not imported: {name}]_"));
+        }
+        self.skipped_attachments += names.len();
+    }
+
+    /// Add a message that may carry more than text, as exports of this app
+    /// do. One without text is kept when it has files or tool calls.
+    /// Timestamps missing from the export, or going back in time, are moved
+    /// just after the previous one so the chat keeps its order.
+    pub fn push_message(&mut self, mut message: ImportedMessage, timestamp: Option<i64>) {
+        if message.content.trim().is_empty()
+            && message.files.is_empty()
+            && message.tool_calls.is_empty()
+        {
+            self.skip("empty message");
+            return;
+        }
+
+        let after_previous = self.messages.last().map(|previous| previous.timestamp + 1);
+        message.timestamp = match (timestamp, after_previous) {
+            (Some(timestamp), Some(floor)) => timestamp.max(floor),
+            (Some(timestamp), None) => timestamp,
+            (None, Some(floor)) => floor,
+            (None, None) => self.created_at,
+        };
+        self.messages.push(message);
+    }
+
+    pub fn skip(&mut self, reason: impl Into<String>) {
+        self.skipped.push(reason.into());
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    pub content: String,
+    pub timestamp: i64,
+    pub reasoning: Option<String>,
+    /// Attached files to store again, as data URLs or paths
+    pub files: Vec<String>,
+    /// Tool calls of an assistant message, each with its result
+    pub tool_calls: Vec<ImportedToolCall>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportedToolCall {
+    pub name: String,
+    pub arguments: String,
+    pub status: Option<String>,
+    pub result: String,
+}
+
+/// What happened to one conversation of an export.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConversationImportReport {
+    pub external_id: String,
+    pub title: String,
+    /// The new chat, `None` when nothing was imported
+    pub chat_id: Option<String>,
+    pub imported_messages: usize,
+    pub skipped_messages: usize,
+    /// Skipped messages by reason
+    pub skip_reasons: BTreeMap<String, usize>,
+    pub skipped_attachments: usize,
+    pub branched: bool,
+    /// Why the conversation was not imported
+    pub error: Option<String>,
+}
+
+/// Result of `import_external_chats`.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatImportReport {
+    /// Id to cancel the import with, also carried by its progress events
+    pub import_id: String,
+    pub source: ChatImportSource,
+    pub imported_chats: usize,
+    pub conversations: Vec<ConversationImportReport>,
+    /// Stopped by `cancel_chat_import`; chats imported before stay
+    pub cancelled: bool,
+    /// The file turned out unreadable part way; chats imported before stay
+    pub error: Option<String>,
+}
@@ -0,0 +1,211 @@
+//! Streaming reader for export files.
+//!
+//! Exports are a single JSON array of conversations and can run to hundreds
+//! of megabytes, so the array is parsed one element at a time straight from
+//! the file and only the current conversation is held in memory.
+
+use super::models::{ChatImportSource, ImportedConversation};
+use crate::error::AppError;
+use serde::de::{self, SeqAccess, Visitor};
+use serde::Deserializer as _;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// How far through the file the reader is.
+#[derive(Debug, Clone, Copy)]
+pub struct ReadProgress {
+    pub bytes_read: u64,
+    pub total_bytes: u64,
+    /// Conversations read so far, the current one included
+    pub conversations: usize,
+}
+
+/// Call `each` with every conversation of the export at `path`, in file
+/// order. Stops with `AppError::Cancelled` once `cancelled` is set, and with
+/// the error `each` returns.
+pub fn read_export(
+    path: &Path,
+    cancelled: &AtomicBool,
+    mut each: impl FnMut(serde_json::Value, ReadProgress) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    let file = File::open(path)
+        .map_err(|e| AppError::Validation(format!("Failed to open {}: {e}", path.display())))?;
+    let total_bytes = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let bytes_read = Arc::new(AtomicU64::new(0));
+    let reader = BufReader::new(CountingReader {
+        inner: file,
+        read: bytes_read.clone(),
+    });
+
+    let mut conversations = 0;
+    let mut stopped = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = deserializer.deserialize_seq(Elements {
+        each: |value| {
+            if cancelled.load(Ordering::SeqCst) {
+                return Err(AppError::Cancelled);
+            }
+            conversations += 1;
+            let progress = ReadProgress {
+                bytes_read: bytes_read.load(Ordering::SeqCst),
+                total_bytes,
+                conversations,
+            };
+            each(value, progress)
+        },
+        stopped: &mut stopped,
+    });
+    if let Some(e) = stopped {
+        return Err(e);
+    }
+    result
+        .and_then(|()| deserializer.end())
+        .map_err(|e| AppError::Validation(format!("Invalid export file: {e}")))
+}
+
+/// Hand over conversations of a format that is read whole, stopping like
+/// `read_export` does.
+pub fn read_converted(
+    conversations: Vec<ImportedConversation>,
+    total_bytes: u64,
+    cancelled: &AtomicBool,
+    mut each: impl FnMut(ImportedConversation, ReadProgress) -> Result<(), AppError>,
+) -> Result<(), AppError> {
+    for (index, conversation) in conversations.into_iter().enumerate() {
+        if cancelled.load(Ordering::SeqCst) {
+            return Err(AppError::Cancelled);
+        }
+        let progress = ReadProgress {
+            bytes_read: total_bytes,
+            total_bytes,
+            conversations: index + 1,
+        };
+        each(conversation, progress)?;
+    }
+    Ok(())
+}
+
+/// Bytes looked at to tell export formats apart.
+const DETECT_BYTES: u64 = 64 * 1024;
+
+/// The format of the export at `path`, from its extension and first bytes.
+pub fn detect_source(path: &Path) -> Result<ChatImportSource, AppError> {
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_lowercase());
+    if matches!(extension.as_deref(), Some("jsonl" | "ndjson")) {
+        return Ok(ChatImportSource::Jsonl);
+    }
+
+    let file = File::open(path)
+        .map_err(|e| AppError::Validation(format!("Failed to open {}: {e}", path.display())))?;
+    let mut head = Vec::new();
+    file.take(DETECT_BYTES).read_to_end(&mut head)?;
+    let head = String::from_utf8_lossy(&head);
+    let source = match head
+        .trim_start_matches('\u{feff}')
+        .trim_start()
+        .chars()
+        .next()
+    {
+        // One object: an export of this app, or the first line of a JSONL file
+        Some('{') if head.contains("\"chats\"") && head.contains("\"version\"") => {
+            Some(ChatImportSource::Lunex)
+        }
+        Some('{') => Some(ChatImportSource::Jsonl),
+        Some('[') if head.contains("\"mapping\"") => Some(ChatImportSource::Chatgpt),
+        Some('[') if head.contains("\"chat_messages\"") => Some(ChatImportSource::Claude),
+        _ => None,
+    };
+    source.ok_or_else(|| {
+        AppError::Validation(
+            "Unrecognized chat export; expected a Lunex, ChatGPT or Claude export, or JSONL"
+                .to_string(),
+        )
+    })
+}
+
+struct CountingReader<R> {
+    inner: R,
+    read: Arc<AtomicU64>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read.fetch_add(n as u64, Ordering::SeqCst);
+        Ok(n)
+    }
+}
+
+/// Visits the top-level array, handing each element over as it is parsed.
+struct Elements<'a, F> {
+    each: F,
+    /// The error `each` stopped with; serde only carries its own errors
+    stopped: &'a mut Option<AppError>,
+}
+
+impl<'de, F> Visitor<'de> for Elements<'_, F>
+where
+    F: FnMut(serde_json::Value) -> Result<(), AppError>,
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a list of conversations")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(element) = seq.next_element::<serde_json::Value>()? {
+            if let Err(e) = (self.each)(element) {
+                *self.stopped = Some(e);
+                return Err(de::Error::custom("import stopped"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::read_fixture;
+
+    #[test]
+    fn chat_import_reader_stops_on_cancel_and_rejects_non_arrays() {
+        use crate::error::AppError;
+        use crate::features::chat_import::reader::read_export;
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conversations.json");
+        std::fs::write(
+            &path,
+            include_str!("../features/chat_import/fixtures/chatgpt_conversations.json"),
+        )
+        .unwrap();
+
+        let cancelled = AtomicBool::new(false);
+        let mut read = 0;
+        let result = read_export(&path, &cancelled, |_, _| {
+            read += 1;
+            cancelled.store(true, Ordering::SeqCst);
+            Ok(())
+        });
+        assert!(matches!(result, Err(AppError::Cancelled)));
+        assert_eq!(read, 1);
+
+        // Errors from the callback come back as they are
+        let result = read_export(&path, &AtomicBool::new(false), |_, _| {
+            Err(AppError::Validation("stop".to_string()))
+        });
+        assert!(matches!(result, Err(AppError::Validation(message)) if message == "stop"));
+
+        assert!(read_fixture("object.json", r#"{"conversations": []}"#).is_err());
+        assert!(read_fixture("truncated.json", r#"[{"uuid": "a"}, {"uuid""#).is_err());
+        assert_eq!(read_fixture("empty.json", " [ ] ").unwrap().len(), 0);
+    }
+}
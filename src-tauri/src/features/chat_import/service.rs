@@ -0,0 +1,358 @@
+use super::models::{
+    ChatImportReport, ChatImportSource, ConversationImportReport, ImportedConversation,
+    ImportedMessage,
+};
+use super::reader::ReadProgress;
+use super::{chatgpt, claude, jsonl, lunex, reader};
+use crate::error::AppError;
+use crate::events::ChatImportProgressEvent;
+use crate::features::attachment::{file_uri, AttachmentService};
+use crate::features::chat::models::Chat;
+use crate::features::chat::ChatService;
+use crate::features::message::tool_exchange::TOOL_CALL_ID_PREFIX;
+use crate::features::message::MessageService;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Conversations between two progress events.
+const PROGRESS_EVERY: usize = 25;
+/// Chat metadata key describing where an imported chat came from.
+pub const IMPORT_METADATA_KEY: &str = "import";
+
+pub struct ChatImportService {
+    chat_service: Arc<ChatService>,
+    message_service: Arc<MessageService>,
+    attachment_service: Arc<AttachmentService>,
+    /// Cancel flags of running imports, by import id
+    running: Mutex<HashMap<String, Arc<AtomicBool>>>,
+}
+
+impl ChatImportService {
+    pub fn new(
+        chat_service: Arc<ChatService>,
+        message_service: Arc<MessageService>,
+        attachment_service: Arc<AttachmentService>,
+    ) -> Self {
+        Self {
+            chat_service,
+            message_service,
+            attachment_service,
+            running: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Import every conversation of the export at `path` into `workspace_id`,
+    /// one chat each. The file is read as it is imported; `on_progress` gets
+    /// the import id first, then periodic progress, then a `done` event.
+    pub fn import(
+        &self,
+        path: &Path,
+        source: ChatImportSource,
+        workspace_id: &str,
+        on_progress: &dyn Fn(ChatImportProgressEvent),
+    ) -> Result<ChatImportReport, AppError> {
+        let import_id = uuid::Uuid::new_v4().to_string();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.lock_running()?
+            .insert(import_id.clone(), cancelled.clone());
+
+        let progress = |bytes_read, total_bytes, conversations, done| ChatImportProgressEvent {
+            import_id: import_id.clone(),
+            source: source.as_str().to_string(),
+            bytes_read,
+            total_bytes,
+            conversations,
+            done,
+        };
+        let total_bytes = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+        on_progress(progress(0, total_bytes, 0, false));
+
+        let mut report = ChatImportReport {
+            import_id: import_id.clone(),
+            source,
+            imported_chats: 0,
+            conversations: Vec::new(),
+            cancelled: false,
+            error: None,
+        };
+        let mut each = |conversation: Result<ImportedConversation, AppError>,
+                        read: ReadProgress|
+         -> Result<(), AppError> {
+            let entry = match conversation {
+                Ok(conversation) => self.store(&conversation, source, workspace_id),
+                Err(e) => ConversationImportReport {
+                    error: Some(e.to_string()),
+                    ..ConversationImportReport::default()
+                },
+            };
+            if entry.chat_id.is_some() {
+                report.imported_chats += 1;
+            }
+            report.conversations.push(entry);
+            if read.conversations % PROGRESS_EVERY == 0 {
+                on_progress(progress(
+                    read.bytes_read,
+                    read.total_bytes,
+                    read.conversations,
+                    false,
+                ));
+            }
+            Ok(())
+        };
+        let result = match source {
+            ChatImportSource::Chatgpt => reader::read_export(path, &cancelled, |value, read| {
+                each(chatgpt::convert(value), read)
+            }),
+            ChatImportSource::Claude => reader::read_export(path, &cancelled, |value, read| {
+                each(claude::convert(value), read)
+            }),
+            ChatImportSource::Lunex => {
+                lunex::read(path, &self.attachment_service).and_then(|conversations| {
+                    reader::read_converted(conversations, total_bytes, &cancelled, |c, read| {
+                        each(Ok(c), read)
+                    })
+                })
+            }
+            ChatImportSource::Jsonl => jsonl::read(path).and_then(|conversations| {
+                reader::read_converted(conversations, total_bytes, &cancelled, |c, read| {
+                    each(Ok(c), read)
+                })
+            }),
+        };
+        self.lock_running()?.remove(&import_id);
+
+        match result {
+            Ok(()) => {}
+            Err(AppError::Cancelled) => report.cancelled = true,
+            // Keep what was imported before the file went bad
+            Err(e) if !report.conversations.is_empty() => report.error = Some(e.to_string()),
+            Err(e) => return Err(e),
+        }
+        on_progress(progress(
+            total_bytes,
+            total_bytes,
+            report.conversations.len(),
+            true,
+        ));
+        Ok(report)
+    }
+
+    /// Stop a running import after the conversation it is on.
+    pub fn cancel(&self, import_id: &str) -> Result<(), AppError> {
+        let running = self.lock_running()?;
+        let flag = running
+            .get(import_id)
+            .ok_or_else(|| AppError::NotFound(format!("No running chat import {import_id}")))?;
+        flag.store(true, Ordering::SeqCst);
+        Ok(())
+    }
+
+    fn store(
+        &self,
+        conversation: &ImportedConversation,
+        source: ChatImportSource,
+        workspace_id: &str,
+    ) -> ConversationImportReport {
+        let mut entry = ConversationImportReport {
+            external_id: conversation.external_id.clone(),
+            title: conversation.title.clone(),
+            imported_messages: conversation.messages.len(),
+            skipped_messages: conversation.skipped.len(),
+            skipped_attachments: conversation.skipped_attachments,
+            branched: conversation.branched,
+            ..ConversationImportReport::default()
+        };
+        for reason in &conversation.skipped {
+            *entry.skip_reasons.entry(reason.clone()).or_default() += 1;
+        }
+        if conversation.messages.is_empty() {
+            entry.error = Some("No messages to import".to_string());
+            return entry;
+        }
+
+        match self.store_chat(conversation, source, workspace_id) {
+            Ok((chat_id, unrestored_files)) => {
+                entry.chat_id = Some(chat_id);
+                entry.skipped_attachments += unrestored_files;
+            }
+            Err(e) => {
+                entry.imported_messages = 0;
+                entry.error = Some(e.to_string());
+            }
+        }
+        entry
+    }
+
+    /// Create the chat and its messages under new ids, removing the chat
+    /// again when a message cannot be stored. Returns the chat id and the
+    /// number of attached files that could not be stored again.
+    fn store_chat(
+        &self,
+        conversation: &ImportedConversation,
+        source: ChatImportSource,
+        workspace_id: &str,
+    ) -> Result<(String, usize), AppError> {
+        let last_message = conversation
+            .messages
+            .last()
+            .map(|message| Chat::last_message_preview(&message.content));
+        let chat = Chat {
+            id: uuid::Uuid::new_v4().to_string(),
+            workspace_id: workspace_id.to_string(),
+            title: conversation.title.clone(),
+            last_message,
+            created_at: conversation.created_at,
+            updated_at: conversation.updated_at,
+            agent_id: None,
+            parent_id: None,
+            // The imported history is not news
+            last_read_at: Some(chrono::Utc::now().timestamp_millis()),
+            unread_count: 0,
+            custom_instructions: None,
+        };
+        let metadata = serde_json::json!({
+            IMPORT_METADATA_KEY: {
+                "source": source.as_str(),
+                "externalId": conversation.external_id,
+                "canonicalPathOnly": conversation.branched,
+            }
+        });
+        self.chat_service.create_imported(&chat, &metadata)?;
+
+        let stored = conversation
+            .custom_instructions
+            .as_deref()
+            .map_or(Ok(None), |instructions| {
+                self.chat_service
+                    .set_instructions(&chat.id, Some(instructions))
+            })
+            .and_then(|_| {
+                conversation
+                    .messages
+                    .iter()
+                    .try_fold(0, |unrestored, message| {
+                        Ok(unrestored + self.store_message(&chat.id, message)?)
+                    })
+            });
+        match stored {
+            Ok(unrestored) => Ok((chat.id, unrestored)),
+            Err(e) => {
+                if let Err(cleanup) = self.chat_service.delete(chat.id.clone()) {
+                    tracing::warn!(error = %cleanup, chat_id = %chat.id, "Failed to remove partly imported chat");
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Store a message with its reasoning, files and tool calls. Returns the
+    /// number of attached files that could not be stored again.
+    fn store_message(&self, chat_id: &str, message: &ImportedMessage) -> Result<usize, AppError> {
+        let (content, metadata, unrestored) = self.restore_files(message);
+        let id = uuid::Uuid::new_v4().to_string();
+        self.message_service.create(
+            id.clone(),
+            chat_id.to_string(),
+            message.role.clone(),
+            content.clone(),
+            Some(message.timestamp),
+            None,
+            None,
+            metadata,
+        )?;
+        if message.reasoning.is_some() {
+            self.message_service.update(
+                id.clone(),
+                content,
+                message.reasoning.clone(),
+                Some(message.timestamp),
+            )?;
+        }
+
+        for call in &message.tool_calls {
+            let call_id = uuid::Uuid::new_v4().to_string();
+            let data = serde_json::json!({
+                "name": call.name,
+                "arguments": call.arguments,
+                "status": call.status.as_deref().unwrap_or("completed"),
+            });
+            self.message_service.create(
+                format!("{TOOL_CALL_ID_PREFIX}{call_id}"),
+                chat_id.to_string(),
+                "tool_call".to_string(),
+                data.to_string(),
+                Some(message.timestamp),
+                Some(id.clone()),
+                None,
+                None,
+            )?;
+            self.message_service.create(
+                format!("tool_result_{call_id}"),
+                chat_id.to_string(),
+                "tool".to_string(),
+                call.result.clone(),
+                Some(message.timestamp),
+                None,
+                Some(call_id),
+                None,
+            )?;
+        }
+        Ok(unrestored)
+    }
+
+    /// The message's content and metadata with its files stored again:
+    /// images embedded in the content become stored-file references and
+    /// attached files become attachments. A file that cannot be stored, or
+    /// a path outside the attachments directory, becomes a note instead.
+    fn restore_files(&self, message: &ImportedMessage) -> (String, Option<String>, usize) {
+        let mut hashes = serde_json::Map::new();
+        let mut content = file_uri::store_embedded_images(&message.content, |mime_type, bytes| {
+            let hash = self
+                .attachment_service
+                .store_image(bytes, mime_type)
+                .map_err(|e| tracing::warn!(error = %e, "Failed to store imported image"))
+                .ok()?;
+            hashes.insert(file_uri::file_uri(&hash), serde_json::json!(hash));
+            Some(hash)
+        });
+
+        let mut files = Vec::new();
+        let mut unrestored = 0;
+        for reference in &message.files {
+            if let Some((path, hash)) = self.attachment_service.store_reference(reference) {
+                hashes.insert(path.clone(), serde_json::json!(hash));
+                files.push(path);
+                continue;
+            }
+            let name = Path::new(reference)
+                .file_name()
+                .filter(|_| !reference.starts_with("data:"))
+                .map_or_else(|| "file".into(), |name| name.to_string_lossy());
+            if !content.is_empty() {
+                content.push_str("\n\n");
+            }
+            content.push_str(&format!("_[Attachment not imported: {name}]_"));
+            unrestored += 1;
+        }
+
+        let mut metadata = serde_json::json!({});
+        if !files.is_empty() {
+            metadata["files"] = serde_json::json!(files);
+        }
+        if !hashes.is_empty() {
+            metadata["fileHashes"] = serde_json::Value::Object(hashes);
+        }
+        let metadata = (metadata != serde_json::json!({})).then(|| metadata.to_string());
+        (content, metadata, unrestored)
+    }
+
+    fn lock_running(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<String, Arc<AtomicBool>>>, AppError> {
+        self.running
+            .lock()
+            .map_err(|e| AppError::Generic(format!("Failed to lock running chat imports: {e}")))
+    }
+}
@@ -0,0 +1,6 @@
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use repository::*;
+pub use service::*;
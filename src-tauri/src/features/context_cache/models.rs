@@ -0,0 +1,12 @@
+use serde::{Deserialize, Serialize};
+
+/// A provider-side cached prompt prefix (e.g. a Gemini `cachedContents` entry).
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ContextCache {
+    pub scope_id: String, // Workspace that owns the cache
+    pub provider_key: String,
+    pub prefix_hash: String,
+    pub cache_name: String,
+    pub model: String,
+    pub expires_at: i64,
+}
@@ -0,0 +1,124 @@
+use super::models::ContextCache;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait ContextCacheRepository: Send + Sync {
+    fn get(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+    ) -> Result<Option<ContextCache>, AppError>;
+    fn save(&self, cache: &ContextCache) -> Result<(), AppError>;
+    fn delete(&self, scope_id: &str, provider_key: &str, prefix_hash: &str)
+        -> Result<(), AppError>;
+    fn delete_expired(&self, now: i64) -> Result<(), AppError>;
+}
+
+pub struct SqliteContextCacheRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteContextCacheRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl ContextCacheRepository for SqliteContextCacheRepository {
+    fn get(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+    ) -> Result<Option<ContextCache>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        get_cache(&conn, scope_id, provider_key, prefix_hash)
+    }
+
+    fn save(&self, cache: &ContextCache) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        save_cache(&conn, cache)
+    }
+
+    fn delete(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+    ) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        delete_cache(&conn, scope_id, provider_key, prefix_hash)
+    }
+
+    fn delete_expired(&self, now: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        delete_expired_caches(&conn, now)
+    }
+}
+
+pub fn get_cache(
+    conn: &Connection,
+    scope_id: &str,
+    provider_key: &str,
+    prefix_hash: &str,
+) -> Result<Option<ContextCache>, AppError> {
+    let result = conn.query_row(
+        "SELECT scope_id, provider_key, prefix_hash, cache_name, model, expires_at FROM context_caches WHERE scope_id = ?1 AND provider_key = ?2 AND prefix_hash = ?3",
+        params![scope_id, provider_key, prefix_hash],
+        |row| {
+            Ok(ContextCache {
+                scope_id: row.get(0)?,
+                provider_key: row.get(1)?,
+                prefix_hash: row.get(2)?,
+                cache_name: row.get(3)?,
+                model: row.get(4)?,
+                expires_at: row.get(5)?,
+            })
+        },
+    );
+
+    match result {
+        Ok(cache) => Ok(Some(cache)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn save_cache(conn: &Connection, cache: &ContextCache) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT OR REPLACE INTO context_caches (scope_id, provider_key, prefix_hash, cache_name, model, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            cache.scope_id,
+            cache.provider_key,
+            cache.prefix_hash,
+            cache.cache_name,
+            cache.model,
+            cache.expires_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn delete_cache(
+    conn: &Connection,
+    scope_id: &str,
+    provider_key: &str,
+    prefix_hash: &str,
+) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM context_caches WHERE scope_id = ?1 AND provider_key = ?2 AND prefix_hash = ?3",
+        params![scope_id, provider_key, prefix_hash],
+    )?;
+    Ok(())
+}
+
+pub fn delete_expired_caches(conn: &Connection, now: i64) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM context_caches WHERE expires_at <= ?1",
+        params![now],
+    )?;
+    Ok(())
+}
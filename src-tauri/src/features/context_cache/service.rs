@@ -0,0 +1,217 @@
+use super::models::ContextCache;
+use super::repository::ContextCacheRepository;
+use crate::error::AppError;
+use std::sync::Arc;
+
+/// Cached prefixes are kept per workspace, account and prefix, so switching
+/// between models or system prompts reuses each one until its TTL runs out
+/// instead of replacing a single cache back and forth.
+pub struct ContextCacheService {
+    repository: Arc<dyn ContextCacheRepository>,
+}
+
+impl ContextCacheService {
+    pub fn new(repository: Arc<dyn ContextCacheRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// The cache of this prefix, unless it has expired.
+    pub fn get(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+    ) -> Result<Option<ContextCache>, AppError> {
+        let now = chrono::Utc::now().timestamp_millis();
+        Ok(self
+            .repository
+            .get(scope_id, provider_key, prefix_hash)?
+            .filter(|cache| cache.expires_at > now))
+    }
+
+    /// Store a new cache, dropping those past their TTL; the provider has
+    /// deleted them by then.
+    pub fn save(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+        cache_name: &str,
+        model: &str,
+        ttl_ms: i64,
+    ) -> Result<(), AppError> {
+        let now = chrono::Utc::now().timestamp_millis();
+        self.repository.delete_expired(now)?;
+        self.repository.save(&ContextCache {
+            scope_id: scope_id.to_string(),
+            provider_key: provider_key.to_string(),
+            prefix_hash: prefix_hash.to_string(),
+            cache_name: cache_name.to_string(),
+            model: model.to_string(),
+            expires_at: now + ttl_ms,
+        })
+    }
+
+    pub fn invalidate(
+        &self,
+        scope_id: &str,
+        provider_key: &str,
+        prefix_hash: &str,
+    ) -> Result<(), AppError> {
+        self.repository.delete(scope_id, provider_key, prefix_hash)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_json_endpoint;
+
+    struct MemoryContextCacheRepository(std::sync::Mutex<rusqlite::Connection>);
+
+    impl crate::features::context_cache::ContextCacheRepository for MemoryContextCacheRepository {
+        fn get(
+            &self,
+            scope_id: &str,
+            provider_key: &str,
+            prefix_hash: &str,
+        ) -> Result<
+            Option<crate::features::context_cache::models::ContextCache>,
+            crate::error::AppError,
+        > {
+            crate::features::context_cache::get_cache(
+                &self.0.lock().unwrap(),
+                scope_id,
+                provider_key,
+                prefix_hash,
+            )
+        }
+
+        fn save(
+            &self,
+            cache: &crate::features::context_cache::models::ContextCache,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::context_cache::save_cache(&self.0.lock().unwrap(), cache)
+        }
+
+        fn delete(
+            &self,
+            scope_id: &str,
+            provider_key: &str,
+            prefix_hash: &str,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::context_cache::delete_cache(
+                &self.0.lock().unwrap(),
+                scope_id,
+                provider_key,
+                prefix_hash,
+            )
+        }
+
+        fn delete_expired(&self, now: i64) -> Result<(), crate::error::AppError> {
+            crate::features::context_cache::delete_expired_caches(&self.0.lock().unwrap(), now)
+        }
+    }
+
+    #[test]
+    fn gemini_context_caches_are_kept_per_prefix_and_expire() {
+        use crate::features::context_cache::ContextCacheService;
+        use crate::services::llm::providers::GoogleProvider;
+        use serde_json::json;
+        use std::sync::Arc;
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        let repository = Arc::new(MemoryContextCacheRepository(std::sync::Mutex::new(conn)));
+        let service = ContextCacheService::new(repository.clone());
+        let provider = GoogleProvider::new(Arc::new(reqwest::Client::new()));
+        let body = |instruction: &str| {
+            json!({
+                "systemInstruction": { "parts": [{ "text": instruction.repeat(20_000) }] },
+                "contents": [{ "role": "user", "parts": [{ "text": "Hi" }] }]
+            })
+        };
+        let (pro, flash) = ("gemini-2.5-pro", "gemini-2.5-flash");
+        let rows = || -> Vec<String> {
+            let conn = repository.0.lock().unwrap();
+            let names = conn
+                .prepare("SELECT cache_name FROM context_caches ORDER BY cache_name")
+                .unwrap()
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            drop(conn);
+            names
+        };
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let (base_url, requests) = mock_json_endpoint(
+                ["one", "two", "three", "four"]
+                    .map(|name| (200, json!({ "name": format!("cachedContents/{name}") })))
+                    .to_vec(),
+            )
+            .await;
+            let long = body("a");
+
+            // Created on first use, then served from the stored entry
+            for _ in 0..2 {
+                let name = provider
+                    .resolve_context_cache(&service, Some("w1"), &base_url, "key", pro, &long, false)
+                    .await;
+                assert_eq!(name.as_deref(), Some("cachedContents/one"));
+            }
+            assert_eq!(requests.lock().unwrap().len(), 1);
+
+            // Another model gets a cache of its own; the first one is kept
+            let name = provider
+                .resolve_context_cache(&service, Some("w1"), &base_url, "key", flash, &long, false)
+                .await;
+            assert_eq!(name.as_deref(), Some("cachedContents/two"));
+            let name = provider
+                .resolve_context_cache(&service, Some("w1"), &base_url, "key", pro, &long, false)
+                .await;
+            assert_eq!(name.as_deref(), Some("cachedContents/one"));
+            assert_eq!(rows(), ["cachedContents/one", "cachedContents/two"]);
+
+            // Past its TTL the cache is created again
+            repository
+                .0
+                .lock()
+                .unwrap()
+                .execute(
+                    "UPDATE context_caches SET expires_at = 0 WHERE cache_name = 'cachedContents/one'",
+                    [],
+                )
+                .unwrap();
+            let name = provider
+                .resolve_context_cache(&service, Some("w1"), &base_url, "key", pro, &long, false)
+                .await;
+            assert_eq!(name.as_deref(), Some("cachedContents/three"));
+            assert_eq!(rows(), ["cachedContents/three", "cachedContents/two"]);
+
+            // Evicted early by Google: replaced when the request says so
+            let name = provider
+                .resolve_context_cache(&service, Some("w1"), &base_url, "key", pro, &long, true)
+                .await;
+            assert_eq!(name.as_deref(), Some("cachedContents/four"));
+            assert_eq!(rows(), ["cachedContents/four", "cachedContents/two"]);
+
+            // Workspaces that did not opt in, and small prefixes, never reach Google
+            let name = provider
+                .resolve_context_cache(&service, None, &base_url, "key", pro, &long, false)
+                .await;
+            assert!(name.is_none());
+            let short = json!({ "systemInstruction": { "parts": [{ "text": "Be brief" }] } });
+            let name = provider
+                .resolve_context_cache(&service, Some("w1"), &base_url, "key", pro, &short, false)
+                .await;
+            assert!(name.is_none());
+
+            let requests = requests.lock().unwrap().clone();
+            assert_eq!(requests.len(), 4);
+            assert!(requests
+                .iter()
+                .all(|r| r == "POST /cachedContents?key=key"));
+        });
+    }
+}
@@ -0,0 +1,302 @@
+//! Column-level encryption of values stored in SQLite.
+//!
+//! An encrypted value is `enc:v1:` followed by the base64 of a random 96-bit
+//! nonce and the AES-256-GCM ciphertext, so encrypted and plaintext rows can
+//! sit side by side while a migration is running: reads decrypt what carries
+//! the prefix and pass everything else through. API keys are sealed the same
+//! way under `sec:v1:` with a key of their own, whether or not the rest of
+//! the database is encrypted.
+
+use crate::error::AppError;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+
+pub const ENCRYPTED_PREFIX: &str = "enc:v1:";
+pub const SECRET_PREFIX: &str = "sec:v1:";
+pub const KEY_LEN: usize = 32;
+const NONCE_LEN: usize = 12;
+const TAG_LEN: usize = 16;
+
+/// Shared by the repositories that store encrypted columns. Holds the master
+/// key while encryption is enabled or being turned on or off.
+pub struct FieldCipher {
+    prefix: &'static str,
+    cipher: RwLock<Option<Aes256Gcm>>,
+    // New writes are encrypted; off while disabling so rows stay plaintext
+    encrypt_writes: AtomicBool,
+    // Encryption is on but its key is missing, so envelopes cannot be opened
+    locked: AtomicBool,
+}
+
+impl Default for FieldCipher {
+    fn default() -> Self {
+        Self::with_prefix(ENCRYPTED_PREFIX)
+    }
+}
+
+impl FieldCipher {
+    /// The cipher of API keys, sealing under `SECRET_PREFIX`.
+    pub fn secrets() -> Self {
+        Self::with_prefix(SECRET_PREFIX)
+    }
+
+    fn with_prefix(prefix: &'static str) -> Self {
+        Self {
+            prefix,
+            cipher: RwLock::new(None),
+            encrypt_writes: AtomicBool::new(false),
+            locked: AtomicBool::new(false),
+        }
+    }
+
+    /// A new random master key.
+    pub fn generate_key() -> Vec<u8> {
+        Aes256Gcm::generate_key(OsRng).to_vec()
+    }
+
+    pub fn is_encrypted(value: &str) -> bool {
+        value.starts_with(ENCRYPTED_PREFIX)
+    }
+
+    pub fn is_secret(value: &str) -> bool {
+        value.starts_with(SECRET_PREFIX)
+    }
+
+    /// Use `key` to decrypt (and, with `set_encrypt_writes`, encrypt); `None`
+    /// forgets the key.
+    pub fn set_key(&self, key: Option<&[u8]>) -> Result<(), AppError> {
+        let cipher = key
+            .map(|key| {
+                Aes256Gcm::new_from_slice(key).map_err(|_| {
+                    AppError::Validation(format!("Encryption key must be {KEY_LEN} bytes"))
+                })
+            })
+            .transpose()?;
+        *self
+            .cipher
+            .write()
+            .map_err(|e| AppError::Generic(format!("Encryption key lock poisoned: {e}")))? = cipher;
+        Ok(())
+    }
+
+    pub fn has_key(&self) -> bool {
+        self.cipher.read().is_ok_and(|c| c.is_some())
+    }
+
+    pub fn set_encrypt_writes(&self, encrypt: bool) {
+        self.encrypt_writes.store(encrypt, Ordering::SeqCst);
+    }
+
+    pub fn encrypts_writes(&self) -> bool {
+        self.encrypt_writes.load(Ordering::SeqCst)
+    }
+
+    /// Mark the stored rows as encrypted under a key that is not loaded, so
+    /// reading them fails instead of returning ciphertext.
+    pub fn set_locked(&self, locked: bool) {
+        self.locked.store(locked, Ordering::SeqCst);
+    }
+
+    /// Whether `value` is shaped like something this cipher sealed: the
+    /// prefix, then base64 long enough for a nonce and tag. Plaintext that
+    /// merely starts with the prefix is not.
+    pub fn is_envelope(&self, value: &str) -> bool {
+        value
+            .strip_prefix(self.prefix)
+            .and_then(|encoded| general_purpose::STANDARD.decode(encoded).ok())
+            .is_some_and(|sealed| sealed.len() >= NONCE_LEN + TAG_LEN)
+    }
+
+    /// `value` as it should be written: encrypted while encryption is on,
+    /// unchanged otherwise. Values that look sealed are encrypted again, so
+    /// plaintext starting with the prefix reads back as written.
+    pub fn seal(&self, value: &str) -> Result<String, AppError> {
+        if self.encrypts_writes() {
+            self.encrypt(value)
+        } else {
+            Ok(value.to_string())
+        }
+    }
+
+    pub fn seal_opt(&self, value: Option<&str>) -> Result<Option<String>, AppError> {
+        value.map(|v| self.seal(v)).transpose()
+    }
+
+    /// `value` as read from the database, decrypted if it is encrypted.
+    /// Plaintext that starts with the prefix but is no envelope is returned
+    /// as it is, and so is plaintext shaped like one that was written while
+    /// encryption was off: the loaded key fails to authenticate it, or no
+    /// key is loaded because encryption is off.
+    pub fn open(&self, value: String) -> Result<String, AppError> {
+        if !self.is_envelope(&value) {
+            return Ok(value);
+        }
+        if !self.has_key() && !self.locked.load(Ordering::SeqCst) {
+            return Ok(value);
+        }
+        match self.decrypt(&value) {
+            Err(e) if self.has_key() => {
+                tracing::warn!(error = %e, "Reading a value shaped like an encrypted one as plaintext");
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+
+    pub fn open_opt(&self, value: Option<String>) -> Result<Option<String>, AppError> {
+        value.map(|v| self.open(v)).transpose()
+    }
+
+    /// Encrypt with a fresh nonce, whether or not writes are encrypted.
+    pub fn encrypt(&self, value: &str) -> Result<String, AppError> {
+        let guard = self
+            .cipher
+            .read()
+            .map_err(|e| AppError::Generic(format!("Encryption key lock poisoned: {e}")))?;
+        let cipher = guard
+            .as_ref()
+            .ok_or_else(|| AppError::Generic("Encryption key is not loaded".to_string()))?;
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = cipher
+            .encrypt(&nonce, value.as_bytes())
+            .map_err(|_| AppError::Generic("Failed to encrypt value".to_string()))?;
+
+        let mut sealed = Vec::with_capacity(NONCE_LEN + ciphertext.len());
+        sealed.extend_from_slice(&nonce);
+        sealed.extend_from_slice(&ciphertext);
+        Ok(format!(
+            "{}{}",
+            self.prefix,
+            general_purpose::STANDARD.encode(sealed)
+        ))
+    }
+
+    pub fn decrypt(&self, value: &str) -> Result<String, AppError> {
+        let encoded = value
+            .strip_prefix(self.prefix)
+            .ok_or_else(|| AppError::Validation("Value is not encrypted".to_string()))?;
+        let sealed = general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|e| AppError::Generic(format!("Corrupt encrypted value: {e}")))?;
+        if sealed.len() < NONCE_LEN {
+            return Err(AppError::Generic(
+                "Corrupt encrypted value: too short".to_string(),
+            ));
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let guard = self
+            .cipher
+            .read()
+            .map_err(|e| AppError::Generic(format!("Encryption key lock poisoned: {e}")))?;
+        let cipher = guard.as_ref().ok_or_else(|| {
+            AppError::Generic("Data is encrypted but the encryption key is not loaded".to_string())
+        })?;
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| {
+                AppError::Generic("Failed to decrypt value: wrong key or corrupt data".to_string())
+            })?;
+        String::from_utf8(plaintext)
+            .map_err(|e| AppError::Generic(format!("Decrypted value is not UTF-8: {e}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn field_cipher_round_trips_and_passes_plaintext_through() {
+        use crate::features::encryption::FieldCipher;
+
+        let cipher = FieldCipher::default();
+        // No key and writes not encrypted: values are stored as given
+        assert_eq!(cipher.seal("hello").unwrap(), "hello");
+        assert_eq!(cipher.open("hello".to_string()).unwrap(), "hello");
+
+        cipher.set_key(Some(&FieldCipher::generate_key())).unwrap();
+        cipher.set_encrypt_writes(true);
+        let a = cipher.seal("hello").unwrap();
+        let b = cipher.seal("hello").unwrap();
+        assert!(FieldCipher::is_encrypted(&a));
+        assert!(!a.contains("hello"));
+        assert_ne!(a, b, "each value gets its own nonce");
+        assert_eq!(cipher.open(a.clone()).unwrap(), "hello");
+        assert_eq!(cipher.open_opt(None).unwrap(), None);
+        // Plaintext rows left by an unfinished migration still read
+        assert_eq!(cipher.open("plain".to_string()).unwrap(), "plain");
+
+        // A value the key did not seal is never decrypted
+        let other = FieldCipher::default();
+        other.set_key(Some(&FieldCipher::generate_key())).unwrap();
+        assert!(other.decrypt(&a).is_err(), "wrong key is refused");
+        assert_eq!(other.open(a.clone()).unwrap(), a);
+        let mut tampered = a;
+        tampered.pop();
+        tampered.push(if tampered.ends_with('A') { 'B' } else { 'A' });
+        assert!(cipher.decrypt(&tampered).is_err());
+        assert!(FieldCipher::default().set_key(Some(&[0; 16])).is_err());
+    }
+
+    #[test]
+    fn plaintext_starting_with_the_prefix_round_trips() {
+        use crate::features::encryption::{FieldCipher, ENCRYPTED_PREFIX};
+
+        let cipher = FieldCipher::default();
+        cipher.set_key(Some(&FieldCipher::generate_key())).unwrap();
+        cipher.set_encrypt_writes(true);
+
+        let plaintext = format!("{ENCRYPTED_PREFIX}not really encrypted");
+        let sealed = cipher.seal(&plaintext).unwrap();
+        assert_ne!(sealed, plaintext, "the value is encrypted, not kept as is");
+        assert_eq!(cipher.open(sealed).unwrap(), plaintext);
+
+        // A sealed value sealed again opens to the sealed value
+        let once = cipher.seal("hello").unwrap();
+        let twice = cipher.seal(&once).unwrap();
+        assert_eq!(cipher.open(twice).unwrap(), once);
+
+        // Rows written unencrypted before this fix still read
+        assert_eq!(cipher.open(plaintext.clone()).unwrap(), plaintext);
+        assert_eq!(
+            cipher.open(ENCRYPTED_PREFIX.to_string()).unwrap(),
+            ENCRYPTED_PREFIX
+        );
+    }
+
+    #[test]
+    fn plaintext_shaped_like_an_envelope_reads_as_written() {
+        use crate::features::encryption::{FieldCipher, ENCRYPTED_PREFIX};
+        use base64::{engine::general_purpose, Engine as _};
+
+        // Written while encryption was off: prefix, then base64 long enough
+        let plaintext = format!(
+            "{ENCRYPTED_PREFIX}{}",
+            general_purpose::STANDARD.encode([7u8; 40])
+        );
+        let cipher = FieldCipher::default();
+        assert!(cipher.is_envelope(&plaintext));
+
+        // Encryption off, no key
+        assert_eq!(cipher.open(plaintext.clone()).unwrap(), plaintext);
+
+        // Encryption turned on since; the key fails to authenticate it
+        cipher.set_key(Some(&FieldCipher::generate_key())).unwrap();
+        assert_eq!(cipher.open(plaintext.clone()).unwrap(), plaintext);
+    }
+
+    #[test]
+    fn envelopes_fail_to_read_while_the_key_is_missing() {
+        use crate::features::encryption::FieldCipher;
+
+        let sealing = FieldCipher::default();
+        sealing.set_key(Some(&FieldCipher::generate_key())).unwrap();
+        let sealed = sealing.encrypt("hello").unwrap();
+
+        let cipher = FieldCipher::default();
+        cipher.set_locked(true);
+        assert!(cipher.open(sealed).is_err());
+    }
+}
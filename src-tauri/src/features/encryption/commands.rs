@@ -0,0 +1,43 @@
+use super::models::EncryptionStatus;
+use crate::error::AppError;
+use crate::events::emit_event;
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+/// Encrypt message content and API keys at rest. Existing rows are rewritten
+/// off the main thread with `encryption-progress` events; calling it again
+/// after an interruption resumes the rewrite.
+#[tauri::command]
+pub async fn enable_encryption(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<EncryptionStatus, AppError> {
+    let service = state.encryption_service.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        service.enable(&|progress| {
+            if let Err(e) = emit_event(&app, progress) {
+                tracing::warn!(error = %e, "Failed to emit encryption progress");
+            }
+        })
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Enabling encryption failed: {e}")))?
+}
+
+/// Decrypt everything and remove the key from the keychain.
+#[tauri::command]
+pub async fn disable_encryption(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<EncryptionStatus, AppError> {
+    let service = state.encryption_service.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        service.disable(&|progress| {
+            if let Err(e) = emit_event(&app, progress) {
+                tracing::warn!(error = %e, "Failed to emit encryption progress");
+            }
+        })
+    })
+    .await
+    .map_err(|e| AppError::Generic(format!("Disabling encryption failed: {e}")))?
+}
@@ -0,0 +1,70 @@
+use crate::error::AppError;
+use base64::{engine::general_purpose, Engine as _};
+
+const KEYCHAIN_SERVICE: &str = "lunex";
+const DATABASE_KEY_ACCOUNT: &str = "database-encryption-key";
+const API_KEYS_KEY_ACCOUNT: &str = "api-key-encryption-key";
+
+/// Where the master key lives, outside the database it protects.
+pub trait KeyStore: Send + Sync {
+    fn get(&self) -> Result<Option<Vec<u8>>, AppError>;
+    fn set(&self, key: &[u8]) -> Result<(), AppError>;
+    fn delete(&self) -> Result<(), AppError>;
+}
+
+/// The OS keychain: Keychain on macOS, Credential Manager on Windows and the
+/// Secret Service on Linux. Each key has an account of its own.
+pub struct KeychainKeyStore {
+    account: &'static str,
+}
+
+impl KeychainKeyStore {
+    /// The master key of database encryption.
+    pub const fn database() -> Self {
+        Self {
+            account: DATABASE_KEY_ACCOUNT,
+        }
+    }
+
+    /// The key API keys are sealed with.
+    pub const fn api_keys() -> Self {
+        Self {
+            account: API_KEYS_KEY_ACCOUNT,
+        }
+    }
+
+    fn entry(&self) -> Result<keyring::Entry, AppError> {
+        keyring::Entry::new(KEYCHAIN_SERVICE, self.account)
+            .map_err(|e| AppError::Generic(format!("Keychain unavailable: {e}")))
+    }
+}
+
+impl KeyStore for KeychainKeyStore {
+    fn get(&self) -> Result<Option<Vec<u8>>, AppError> {
+        match self.entry()?.get_password() {
+            Ok(encoded) => general_purpose::STANDARD
+                .decode(encoded)
+                .map(Some)
+                .map_err(|e| AppError::Generic(format!("Corrupt encryption key in keychain: {e}"))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(AppError::Generic(format!(
+                "Failed to read encryption key from keychain: {e}"
+            ))),
+        }
+    }
+
+    fn set(&self, key: &[u8]) -> Result<(), AppError> {
+        self.entry()?
+            .set_password(&general_purpose::STANDARD.encode(key))
+            .map_err(|e| AppError::Generic(format!("Failed to store encryption key: {e}")))
+    }
+
+    fn delete(&self) -> Result<(), AppError> {
+        match self.entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(AppError::Generic(format!(
+                "Failed to remove encryption key from keychain: {e}"
+            ))),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+pub mod cipher;
+pub mod commands;
+pub mod key_store;
+pub mod models;
+pub mod repository;
+pub mod secrets;
+pub mod service;
+
+pub use cipher::*;
+pub use key_store::*;
+pub use models::*;
+pub use repository::*;
+pub use secrets::*;
+pub use service::*;
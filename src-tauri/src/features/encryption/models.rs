@@ -0,0 +1,61 @@
+use serde::{Deserialize, Serialize};
+
+/// Persisted encryption mode. While enabling or disabling, `after_rowid` is
+/// the last message rewritten, so an interrupted migration resumes there.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum EncryptionState {
+    #[default]
+    Disabled,
+    Enabling {
+        after_rowid: i64,
+    },
+    Enabled,
+    Disabling {
+        after_rowid: i64,
+    },
+}
+
+impl EncryptionState {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Disabled => "disabled",
+            Self::Enabling { .. } => "enabling",
+            Self::Enabled => "enabled",
+            Self::Disabling { .. } => "disabling",
+        }
+    }
+
+    /// Whether any stored row may be encrypted.
+    pub const fn is_active(self) -> bool {
+        !matches!(self, Self::Disabled)
+    }
+
+    /// Whether new writes are encrypted.
+    pub const fn encrypts_writes(self) -> bool {
+        matches!(self, Self::Enabling { .. } | Self::Enabled)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct EncryptionStatus {
+    /// "disabled", "enabling", "enabled" or "disabling"
+    pub mode: &'static str,
+    /// An enable or disable run was interrupted and has rows left to rewrite
+    pub migration_pending: bool,
+    /// The master key is loaded; `false` while active means the keychain
+    /// entry is missing and encrypted rows cannot be read
+    pub key_loaded: bool,
+    /// Searching message content; off while any content may be encrypted
+    pub message_search_available: bool,
+}
+
+/// The encrypted columns of one message row, as stored.
+#[derive(Debug, Clone)]
+pub struct StoredMessageFields {
+    pub rowid: i64,
+    pub id: String,
+    pub content: String,
+    pub reasoning: Option<String>,
+    pub metadata: Option<String>,
+}
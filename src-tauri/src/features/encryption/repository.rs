@@ -0,0 +1,181 @@
+use super::models::{EncryptionState, StoredMessageFields};
+use crate::error::AppError;
+use crate::features::search::sync_message;
+use rusqlite::{params, Connection, OptionalExtension, TransactionBehavior};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// App setting holding the JSON `EncryptionState`.
+pub const ENCRYPTION_STATE_KEY: &str = "encryptionState";
+
+/// Raw access to the encrypted columns, bypassing the cipher, for the
+/// enable and disable migrations.
+pub trait EncryptionRepository: Send + Sync {
+    fn get_state(&self) -> Result<EncryptionState, AppError>;
+    fn save_state(&self, state: EncryptionState) -> Result<(), AppError>;
+    /// Messages with a rowid up to `through_rowid`, or all of them.
+    fn count_messages(&self, through_rowid: Option<i64>) -> Result<u64, AppError>;
+    /// Apply `rewrite` to up to `limit` messages after `after_rowid`, in
+    /// rowid order, reading and writing them in one transaction so edits made
+    /// meanwhile are not lost. Returns the last rowid and the row count, or
+    /// `None` when no message is left.
+    fn rewrite_messages(
+        &self,
+        after_rowid: i64,
+        limit: u32,
+        rewrite: &dyn Fn(&mut StoredMessageFields) -> Result<(), AppError>,
+    ) -> Result<Option<(i64, u64)>, AppError>;
+    /// Apply `rewrite` to every LLM connection's API key in one transaction.
+    fn rewrite_connection_keys(
+        &self,
+        rewrite: &dyn Fn(&str) -> Result<String, AppError>,
+    ) -> Result<(), AppError>;
+}
+
+pub struct SqliteEncryptionRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteEncryptionRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl EncryptionRepository for SqliteEncryptionRepository {
+    fn get_state(&self) -> Result<EncryptionState, AppError> {
+        load_encryption_state(&crate::db::get_connection(&self.app)?)
+    }
+
+    fn save_state(&self, state: EncryptionState) -> Result<(), AppError> {
+        save_encryption_state(&crate::db::get_connection(&self.app)?, state)
+    }
+
+    fn count_messages(&self, through_rowid: Option<i64>) -> Result<u64, AppError> {
+        count_messages(&crate::db::get_connection(&self.app)?, through_rowid)
+    }
+
+    fn rewrite_messages(
+        &self,
+        after_rowid: i64,
+        limit: u32,
+        rewrite: &dyn Fn(&mut StoredMessageFields) -> Result<(), AppError>,
+    ) -> Result<Option<(i64, u64)>, AppError> {
+        rewrite_message_batch(
+            &mut crate::db::get_connection(&self.app)?,
+            after_rowid,
+            limit,
+            rewrite,
+        )
+    }
+
+    fn rewrite_connection_keys(
+        &self,
+        rewrite: &dyn Fn(&str) -> Result<String, AppError>,
+    ) -> Result<(), AppError> {
+        rewrite_connection_keys(&mut crate::db::get_connection(&self.app)?, rewrite)
+    }
+}
+
+pub fn load_encryption_state(conn: &Connection) -> Result<EncryptionState, AppError> {
+    let value: Option<String> = conn
+        .query_row(
+            "SELECT value FROM app_settings WHERE key = ?1",
+            params![ENCRYPTION_STATE_KEY],
+            |row| row.get(0),
+        )
+        .optional()?;
+    Ok(match value {
+        Some(value) => serde_json::from_str(&value)?,
+        None => EncryptionState::Disabled,
+    })
+}
+
+pub fn save_encryption_state(conn: &Connection, state: EncryptionState) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO app_settings (key, value, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value, updated_at = excluded.updated_at",
+        params![
+            ENCRYPTION_STATE_KEY,
+            serde_json::to_string(&state)?,
+            chrono::Utc::now().timestamp_millis()
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn count_messages(conn: &Connection, through_rowid: Option<i64>) -> Result<u64, AppError> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM messages WHERE ?1 IS NULL OR rowid <= ?1",
+        params![through_rowid],
+        |row| row.get(0),
+    )?;
+    Ok(count.unsigned_abs())
+}
+
+pub fn rewrite_message_batch(
+    conn: &mut Connection,
+    after_rowid: i64,
+    limit: u32,
+    rewrite: &dyn Fn(&mut StoredMessageFields) -> Result<(), AppError>,
+) -> Result<Option<(i64, u64)>, AppError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let mut rows = {
+        let mut stmt = tx.prepare(
+            "SELECT rowid, id, content, reasoning, metadata FROM messages
+             WHERE rowid > ?1 ORDER BY rowid LIMIT ?2",
+        )?;
+        let rows = stmt
+            .query_map(params![after_rowid, limit], |row| {
+                Ok(StoredMessageFields {
+                    rowid: row.get(0)?,
+                    id: row.get(1)?,
+                    content: row.get(2)?,
+                    reasoning: row.get(3)?,
+                    metadata: row.get(4)?,
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        rows
+    };
+    let Some(last_rowid) = rows.last().map(|row| row.rowid) else {
+        return Ok(None);
+    };
+
+    for row in &mut rows {
+        rewrite(row)?;
+        tx.execute(
+            "UPDATE messages SET content = ?1, reasoning = ?2, metadata = ?3 WHERE rowid = ?4",
+            params![row.content, row.reasoning, row.metadata, row.rowid],
+        )?;
+        // Encrypted content leaves the search index, decrypted content returns
+        sync_message(&tx, &row.id)?;
+    }
+    tx.commit()?;
+    Ok(Some((last_rowid, rows.len() as u64)))
+}
+
+pub fn rewrite_connection_keys(
+    conn: &mut Connection,
+    rewrite: &dyn Fn(&str) -> Result<String, AppError>,
+) -> Result<(), AppError> {
+    let tx = conn.transaction_with_behavior(TransactionBehavior::Immediate)?;
+    let keys = {
+        let mut stmt = tx.prepare("SELECT id, api_key FROM llm_connections")?;
+        let keys = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        keys
+    };
+
+    for (id, api_key) in keys {
+        tx.execute(
+            "UPDATE llm_connections SET api_key = ?1 WHERE id = ?2",
+            params![rewrite(&api_key)?, id],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
@@ -0,0 +1,292 @@
+use super::cipher::FieldCipher;
+use super::key_store::KeyStore;
+use super::repository::EncryptionRepository;
+use crate::error::AppError;
+use std::sync::Arc;
+
+/// Keeps LLM connection API keys encrypted at rest with a key of their own
+/// from the OS keychain, whether or not database encryption is on. With both
+/// on, a stored key is the `enc:v1:` form of its `sec:v1:` form, so turning
+/// database encryption off leaves API keys sealed.
+pub struct SecretsService {
+    repository: Arc<dyn EncryptionRepository>,
+    key_store: Arc<dyn KeyStore>,
+    secrets: Arc<FieldCipher>,
+    field_cipher: Arc<FieldCipher>,
+}
+
+impl SecretsService {
+    pub fn new(
+        repository: Arc<dyn EncryptionRepository>,
+        key_store: Arc<dyn KeyStore>,
+        secrets: Arc<FieldCipher>,
+        field_cipher: Arc<FieldCipher>,
+    ) -> Self {
+        Self {
+            repository,
+            key_store,
+            secrets,
+            field_cipher,
+        }
+    }
+
+    /// Load the key, generating it on first run, then seal the API keys
+    /// still stored in plaintext. Call after `EncryptionService::load`.
+    /// Without a keychain, API keys are kept in plaintext and a warning is
+    /// logged so the app still starts.
+    pub fn load(&self) -> Result<(), AppError> {
+        let key = match self.key_store.get() {
+            Ok(Some(key)) => key,
+            Ok(None) => {
+                let key = FieldCipher::generate_key();
+                if let Err(e) = self.key_store.set(&key) {
+                    tracing::warn!(error = %e, "API keys stay unencrypted: cannot store their key");
+                    return Ok(());
+                }
+                key
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "API keys stay unencrypted: cannot read their key");
+                // Keys sealed on an earlier run must not be sent as they are
+                self.secrets.set_locked(true);
+                return Ok(());
+            }
+        };
+        self.secrets.set_key(Some(&key))?;
+        self.secrets.set_encrypt_writes(true);
+
+        self.repository
+            .rewrite_connection_keys(&|stored| self.seal_stored(stored))
+    }
+
+    /// `stored` with the API key inside sealed, keeping the database
+    /// encryption around it. Keys already sealed, empty ones, and ones
+    /// encrypted with a database key that is not loaded are left as they are.
+    fn seal_stored(&self, stored: &str) -> Result<String, AppError> {
+        let database_encrypted = FieldCipher::is_encrypted(stored);
+        if database_encrypted && !self.field_cipher.has_key() {
+            return Ok(stored.to_string());
+        }
+        let api_key = self.field_cipher.open(stored.to_string())?;
+        if api_key.is_empty() || self.secrets.is_envelope(&api_key) {
+            return Ok(stored.to_string());
+        }
+        let sealed = self.secrets.encrypt(&api_key)?;
+        if database_encrypted {
+            self.field_cipher.encrypt(&sealed)
+        } else {
+            Ok(sealed)
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::error::AppError;
+    use crate::features::encryption::{
+        count_messages, load_encryption_state, rewrite_connection_keys, rewrite_message_batch,
+        save_encryption_state, EncryptionRepository, EncryptionService, EncryptionState,
+        FieldCipher, KeyStore, StoredMessageFields,
+    };
+    use crate::test_support::{connection, migrated_db};
+    use rusqlite::{params, Connection};
+    use std::sync::{Arc, Mutex};
+
+    /// A keychain kept in memory.
+    #[derive(Default)]
+    pub struct MemoryKeyStore(pub Mutex<Option<Vec<u8>>>);
+
+    impl KeyStore for MemoryKeyStore {
+        fn get(&self) -> Result<Option<Vec<u8>>, AppError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+        fn set(&self, key: &[u8]) -> Result<(), AppError> {
+            *self.0.lock().unwrap() = Some(key.to_vec());
+            Ok(())
+        }
+        fn delete(&self) -> Result<(), AppError> {
+            *self.0.lock().unwrap() = None;
+            Ok(())
+        }
+    }
+
+    /// The SQL repository over an in-memory database. While `batches_left`
+    /// is set, message batches past it fail, as if the app quit mid-run.
+    pub struct MemoryEncryptionRepository {
+        pub conn: Mutex<Connection>,
+        pub batches_left: Mutex<Option<usize>>,
+    }
+
+    impl EncryptionRepository for MemoryEncryptionRepository {
+        fn get_state(&self) -> Result<EncryptionState, AppError> {
+            load_encryption_state(&self.conn.lock().unwrap())
+        }
+
+        fn save_state(&self, state: EncryptionState) -> Result<(), AppError> {
+            save_encryption_state(&self.conn.lock().unwrap(), state)
+        }
+
+        fn count_messages(&self, through_rowid: Option<i64>) -> Result<u64, AppError> {
+            count_messages(&self.conn.lock().unwrap(), through_rowid)
+        }
+
+        fn rewrite_messages(
+            &self,
+            after_rowid: i64,
+            limit: u32,
+            rewrite: &dyn Fn(&mut StoredMessageFields) -> Result<(), AppError>,
+        ) -> Result<Option<(i64, u64)>, AppError> {
+            if let Some(left) = self.batches_left.lock().unwrap().as_mut() {
+                if *left == 0 {
+                    return Err(AppError::Generic("quit".to_string()));
+                }
+                *left -= 1;
+            }
+            rewrite_message_batch(&mut self.conn.lock().unwrap(), after_rowid, limit, rewrite)
+        }
+
+        fn rewrite_connection_keys(
+            &self,
+            rewrite: &dyn Fn(&str) -> Result<String, AppError>,
+        ) -> Result<(), AppError> {
+            rewrite_connection_keys(&mut self.conn.lock().unwrap(), rewrite)
+        }
+    }
+
+    /// `count` messages in chat `c1`, every third with reasoning and metadata,
+    /// and one LLM connection.
+    pub fn encryption_fixture(count: i64) -> Connection {
+        let conn = migrated_db();
+        for i in 0..count {
+            let with_extras = i % 3 == 0;
+            conn.execute(
+                "INSERT INTO messages (id, chat_id, role, content, reasoning, timestamp, metadata)
+                 VALUES (?1, 'c1', ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    format!("m{i}"),
+                    if i % 2 == 0 { "user" } else { "assistant" },
+                    format!("Message {i}: {}", "lorem ipsum dolor sit amet ".repeat(12)),
+                    with_extras.then(|| format!("Thinking about {i}")),
+                    i,
+                    with_extras.then(|| r#"{"model":"gpt-4o","replyToMessageId":"m0"}"#),
+                ],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO llm_connections (id, name, base_url, provider, api_key, created_at, updated_at)
+             VALUES ('openai', 'OpenAI', 'https://api.openai.com/v1', 'openai', 'sk-secret', 0, 0)",
+            [],
+        )
+        .unwrap();
+        conn
+    }
+
+    /// An encryption service over `conn`, with its repository and cipher.
+    pub fn encryption_service(
+        conn: Connection,
+    ) -> (
+        EncryptionService,
+        Arc<MemoryEncryptionRepository>,
+        Arc<FieldCipher>,
+    ) {
+        let repository = Arc::new(MemoryEncryptionRepository {
+            conn: Mutex::new(conn),
+            batches_left: Mutex::new(None),
+        });
+        let cipher = Arc::new(FieldCipher::default());
+        let service = EncryptionService::new(
+            repository.clone(),
+            Arc::new(MemoryKeyStore::default()),
+            cipher.clone(),
+        );
+        (service, repository, cipher)
+    }
+
+    /// Stored `(content, reasoning, metadata)` of every message, and the API key.
+    pub fn stored_rows(
+        repository: &MemoryEncryptionRepository,
+    ) -> (Vec<(String, Option<String>, Option<String>)>, String) {
+        let conn = repository.conn.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT content, reasoning, metadata FROM messages ORDER BY rowid")
+            .unwrap();
+        let rows = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let api_key = conn
+            .query_row("SELECT api_key FROM llm_connections", [], |row| row.get(0))
+            .unwrap();
+        (rows, api_key)
+    }
+
+    #[test]
+    fn api_keys_are_sealed_with_their_own_key_and_kept_out_of_logs() {
+        use crate::features::encryption::{SecretsService, ENCRYPTED_PREFIX, SECRET_PREFIX};
+        use crate::features::redaction::secrets;
+
+        // Plaintext keys are sealed on load, once
+        let (encryption, repository, cipher) = encryption_service(encryption_fixture(3));
+        let secrets_cipher = Arc::new(FieldCipher::secrets());
+        let service = SecretsService::new(
+            repository.clone(),
+            Arc::new(MemoryKeyStore::default()),
+            secrets_cipher.clone(),
+            cipher.clone(),
+        );
+        service.load().unwrap();
+        let (_, sealed) = stored_rows(&repository);
+        assert!(sealed.starts_with(SECRET_PREFIX));
+        assert_eq!(secrets_cipher.open(sealed.clone()).unwrap(), "sk-secret");
+        service.load().unwrap();
+        assert_eq!(stored_rows(&repository).1, sealed);
+        assert!(secrets_cipher.is_envelope(&secrets_cipher.seal("sk-new").unwrap()));
+
+        // Database encryption wraps the sealed key and leaves it sealed when off
+        encryption.enable(&|_| {}).unwrap();
+        let (_, stored) = stored_rows(&repository);
+        assert!(stored.starts_with(ENCRYPTED_PREFIX));
+        assert_eq!(cipher.open(stored).unwrap(), sealed);
+        encryption.disable(&|_| {}).unwrap();
+        assert_eq!(stored_rows(&repository).1, sealed);
+
+        // Keys encrypted before sealing existed are sealed inside
+        let (encryption, repository, cipher) = encryption_service(encryption_fixture(1));
+        encryption.enable(&|_| {}).unwrap();
+        let secrets_cipher = Arc::new(FieldCipher::secrets());
+        SecretsService::new(
+            repository.clone(),
+            Arc::new(MemoryKeyStore::default()),
+            secrets_cipher.clone(),
+            cipher.clone(),
+        )
+        .load()
+        .unwrap();
+        let inner = cipher.open(stored_rows(&repository).1).unwrap();
+        assert!(inner.starts_with(SECRET_PREFIX));
+        assert_eq!(secrets_cipher.open(inner).unwrap(), "sk-secret");
+
+        // Debug output and scrubbed log lines never show a key
+        let mut llm = connection("openai", "openai", "gpt-4o", serde_json::json!([]));
+        llm.api_key = "plain-key-1234".to_string();
+        let debug = format!("{llm:?}");
+        assert!(!debug.contains("plain-key-1234"));
+        assert!(debug.contains("[redacted]"));
+        secrets::remember("plain-key-1234");
+        secrets::remember("short");
+        assert_eq!(
+            secrets::scrub("401 with plain-key-1234, a short reply"),
+            "401 with pl****34, a short reply"
+        );
+        assert_eq!(
+            secrets::scrub("Bearer sk-abcdefghijklmnop rejected"),
+            "Bearer sk****op rejected"
+        );
+        assert!(matches!(
+            secrets::scrub("nothing to hide"),
+            std::borrow::Cow::Borrowed(_)
+        ));
+    }
+}
@@ -0,0 +1,375 @@
+use super::cipher::FieldCipher;
+use super::key_store::KeyStore;
+use super::models::{EncryptionState, EncryptionStatus, StoredMessageFields};
+use super::repository::EncryptionRepository;
+use crate::error::AppError;
+use crate::events::EncryptionProgressEvent;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Messages rewritten per transaction by `enable` and `disable`.
+const MIGRATION_BATCH_SIZE: u32 = 200;
+
+/// Turns encryption at rest on and off. Repositories encrypt and decrypt
+/// through the shared `FieldCipher`; this service owns the master key and
+/// rewrites existing rows when the mode changes.
+pub struct EncryptionService {
+    repository: Arc<dyn EncryptionRepository>,
+    key_store: Arc<dyn KeyStore>,
+    cipher: Arc<FieldCipher>,
+    migration_running: AtomicBool,
+}
+
+impl EncryptionService {
+    pub fn new(
+        repository: Arc<dyn EncryptionRepository>,
+        key_store: Arc<dyn KeyStore>,
+        cipher: Arc<FieldCipher>,
+    ) -> Self {
+        Self {
+            repository,
+            key_store,
+            cipher,
+            migration_running: AtomicBool::new(false),
+        }
+    }
+
+    /// Load the master key for the stored mode, before any repository reads.
+    /// A missing key is logged rather than returned so the app still starts;
+    /// encrypted rows then fail to read until the key is restored.
+    pub fn load(&self) -> Result<EncryptionState, AppError> {
+        let state = self.repository.get_state()?;
+        if state.is_active() {
+            match self.key_store.get()? {
+                Some(key) => self.cipher.set_key(Some(&key))?,
+                None => tracing::error!(
+                    mode = state.as_str(),
+                    "Encryption is on but the key is missing from the keychain"
+                ),
+            }
+        }
+        self.cipher
+            .set_locked(state.is_active() && !self.cipher.has_key());
+        self.cipher
+            .set_encrypt_writes(state.encrypts_writes() && self.cipher.has_key());
+        Ok(state)
+    }
+
+    pub fn status(&self) -> Result<EncryptionStatus, AppError> {
+        let state = self.repository.get_state()?;
+        Ok(EncryptionStatus {
+            mode: state.as_str(),
+            migration_pending: matches!(
+                state,
+                EncryptionState::Enabling { .. } | EncryptionState::Disabling { .. }
+            ) && !self.migration_running.load(Ordering::SeqCst),
+            key_loaded: self.cipher.has_key(),
+            message_search_available: !state.is_active(),
+        })
+    }
+
+    /// Encrypt new writes and every stored message and API key, generating
+    /// the master key on first use. Resumes an interrupted run where it
+    /// stopped.
+    pub fn enable(
+        &self,
+        on_progress: &dyn Fn(EncryptionProgressEvent),
+    ) -> Result<EncryptionStatus, AppError> {
+        self.run_migration(|| {
+            let state = self.repository.get_state()?;
+            let after_rowid = match state {
+                EncryptionState::Enabled => return Ok(()),
+                EncryptionState::Enabling { after_rowid } => after_rowid,
+                EncryptionState::Disabled | EncryptionState::Disabling { .. } => 0,
+            };
+
+            let key = match self.key_store.get()? {
+                Some(key) => key,
+                None if state == EncryptionState::Disabled => {
+                    let key = FieldCipher::generate_key();
+                    self.key_store.set(&key)?;
+                    key
+                }
+                None => return Err(missing_key_error()),
+            };
+            self.cipher.set_key(Some(&key))?;
+            self.cipher.set_encrypt_writes(true);
+
+            self.rewrite_rows(true, after_rowid, on_progress)?;
+            self.repository.save_state(EncryptionState::Enabled)
+        })
+    }
+
+    /// Store everything as plaintext again and remove the master key from
+    /// the keychain. Resumes an interrupted run where it stopped.
+    pub fn disable(
+        &self,
+        on_progress: &dyn Fn(EncryptionProgressEvent),
+    ) -> Result<EncryptionStatus, AppError> {
+        self.run_migration(|| {
+            let after_rowid = match self.repository.get_state()? {
+                EncryptionState::Disabled => return Ok(()),
+                EncryptionState::Disabling { after_rowid } => after_rowid,
+                EncryptionState::Enabled | EncryptionState::Enabling { .. } => 0,
+            };
+
+            if !self.cipher.has_key() {
+                let key = self.key_store.get()?.ok_or_else(missing_key_error)?;
+                self.cipher.set_key(Some(&key))?;
+            }
+            self.cipher.set_encrypt_writes(false);
+
+            self.rewrite_rows(false, after_rowid, on_progress)?;
+            self.repository.save_state(EncryptionState::Disabled)?;
+            self.key_store.delete()?;
+            self.cipher.set_key(None)
+        })
+    }
+
+    /// Finish an enable or disable that was interrupted, e.g. by quitting.
+    pub fn resume(&self, on_progress: &dyn Fn(EncryptionProgressEvent)) -> Result<(), AppError> {
+        match self.repository.get_state()? {
+            EncryptionState::Enabling { .. } => self.enable(on_progress).map(|_| ()),
+            EncryptionState::Disabling { .. } => self.disable(on_progress).map(|_| ()),
+            EncryptionState::Disabled | EncryptionState::Enabled => Ok(()),
+        }
+    }
+
+    fn run_migration(
+        &self,
+        migrate: impl FnOnce() -> Result<(), AppError>,
+    ) -> Result<EncryptionStatus, AppError> {
+        if self.migration_running.swap(true, Ordering::SeqCst) {
+            return Err(AppError::Validation(
+                "Encryption is already being turned on or off".to_string(),
+            ));
+        }
+        let result = migrate();
+        self.migration_running.store(false, Ordering::SeqCst);
+        result?;
+        self.status()
+    }
+
+    /// Rewrite messages after `after_rowid` in batches, saving the position
+    /// after each one, then the API keys.
+    fn rewrite_rows(
+        &self,
+        encrypt: bool,
+        mut after_rowid: i64,
+        on_progress: &dyn Fn(EncryptionProgressEvent),
+    ) -> Result<(), AppError> {
+        let in_progress = |after_rowid| {
+            if encrypt {
+                EncryptionState::Enabling { after_rowid }
+            } else {
+                EncryptionState::Disabling { after_rowid }
+            }
+        };
+        self.repository.save_state(in_progress(after_rowid))?;
+
+        let total = self.repository.count_messages(None)?;
+        let mut processed = self.repository.count_messages(Some(after_rowid))?;
+        let progress = |processed, done| EncryptionProgressEvent {
+            enabling: encrypt,
+            processed,
+            total: total.max(processed),
+            done,
+        };
+        on_progress(progress(processed, false));
+
+        let rewrite = |row: &mut StoredMessageFields| -> Result<(), AppError> {
+            row.content = self.convert(&row.content, encrypt)?;
+            row.reasoning = row
+                .reasoning
+                .as_deref()
+                .map(|r| self.convert(r, encrypt))
+                .transpose()?;
+            row.metadata = row
+                .metadata
+                .as_deref()
+                .map(|m| self.convert(m, encrypt))
+                .transpose()?;
+            Ok(())
+        };
+        while let Some((last_rowid, count)) =
+            self.repository
+                .rewrite_messages(after_rowid, MIGRATION_BATCH_SIZE, &rewrite)?
+        {
+            after_rowid = last_rowid;
+            processed += count;
+            self.repository.save_state(in_progress(after_rowid))?;
+            on_progress(progress(processed, false));
+        }
+
+        self.repository
+            .rewrite_connection_keys(&|api_key| self.convert(api_key, encrypt))?;
+        on_progress(progress(processed, true));
+        Ok(())
+    }
+
+    /// `value` in the target form; values already in it are kept, so a batch
+    /// rewritten twice is unchanged.
+    fn convert(&self, value: &str, encrypt: bool) -> Result<String, AppError> {
+        match (encrypt, self.cipher.is_envelope(value)) {
+            (true, false) => self.cipher.encrypt(value),
+            (false, true) => self.cipher.open(value.to_string()),
+            _ => Ok(value.to_string()),
+        }
+    }
+}
+
+fn missing_key_error() -> AppError {
+    AppError::Validation(
+        "The encryption key is missing from the OS keychain, so encrypted data cannot be read"
+            .to_string(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::encryption::secrets::tests::{
+        encryption_fixture, encryption_service, stored_rows,
+    };
+
+    #[test]
+    fn enabling_and_disabling_encryption_rewrites_every_row() {
+        use crate::features::encryption::{FieldCipher, ENCRYPTED_PREFIX};
+        use crate::features::message::repository::read_chat_messages;
+
+        let (service, repository, cipher) = encryption_service(encryption_fixture(450));
+        let (plain_rows, _) = stored_rows(&repository);
+        let before = read_chat_messages(&repository.conn.lock().unwrap(), "c1", &cipher).unwrap();
+        assert!(service.status().unwrap().message_search_available);
+
+        let events = std::sync::Mutex::new(Vec::new());
+        let status = service
+            .enable(&|progress| events.lock().unwrap().push(progress))
+            .unwrap();
+        assert_eq!(status.mode, "enabled");
+        assert!(status.key_loaded);
+        assert!(!status.message_search_available);
+        let events = events.into_inner().unwrap();
+        let last = events.last().unwrap();
+        assert!(last.done && last.enabling);
+        assert_eq!((last.processed, last.total), (450, 450));
+        assert!(events.windows(2).all(|w| w[0].processed <= w[1].processed));
+
+        let (rows, api_key) = stored_rows(&repository);
+        assert!(api_key.starts_with(ENCRYPTED_PREFIX));
+        for (content, reasoning, metadata) in &rows {
+            assert!(FieldCipher::is_encrypted(content));
+            assert!(reasoning
+                .iter()
+                .chain(metadata)
+                .all(|v| FieldCipher::is_encrypted(v)));
+        }
+        // Reads above the repository see the same messages
+        let after = read_chat_messages(&repository.conn.lock().unwrap(), "c1", &cipher).unwrap();
+        assert_eq!(
+            serde_json::to_value(&after).unwrap(),
+            serde_json::to_value(&before).unwrap()
+        );
+        assert_eq!(after[0].reply_to_message_id.as_deref(), Some("m0"));
+        // New writes are encrypted too
+        assert!(FieldCipher::is_encrypted(&cipher.seal("new").unwrap()));
+
+        let status = service.disable(&|_| {}).unwrap();
+        assert_eq!(status.mode, "disabled");
+        assert!(!status.key_loaded);
+        assert!(status.message_search_available);
+        assert_eq!(
+            stored_rows(&repository),
+            (plain_rows, "sk-secret".to_string())
+        );
+        assert_eq!(cipher.seal("new").unwrap(), "new");
+    }
+
+    #[test]
+    fn interrupted_encryption_resumes_from_the_last_batch() {
+        use crate::features::encryption::{EncryptionState, FieldCipher};
+
+        let (service, repository, cipher) = encryption_service(encryption_fixture(450));
+        *repository.batches_left.lock().unwrap() = Some(1);
+        assert!(service.enable(&|_| {}).is_err());
+
+        // The first batch was committed and its position saved
+        let status = service.status().unwrap();
+        assert_eq!(status.mode, "enabling");
+        assert!(status.migration_pending);
+        let state =
+            crate::features::encryption::load_encryption_state(&repository.conn.lock().unwrap())
+                .unwrap();
+        let EncryptionState::Enabling { after_rowid } = state else {
+            panic!("unexpected state {state:?}");
+        };
+        let (rows, _) = stored_rows(&repository);
+        let encrypted = rows
+            .iter()
+            .filter(|(content, _, _)| FieldCipher::is_encrypted(content))
+            .count();
+        assert_eq!(encrypted, 200);
+        assert_eq!(after_rowid, 200);
+
+        *repository.batches_left.lock().unwrap() = None;
+        let events = std::sync::Mutex::new(Vec::new());
+        service
+            .resume(&|progress| events.lock().unwrap().push(progress))
+            .unwrap();
+        let events = events.into_inner().unwrap();
+        assert_eq!(events[0].processed, 200, "resumed after the saved batch");
+        assert_eq!(service.status().unwrap().mode, "enabled");
+        assert!(stored_rows(&repository)
+            .0
+            .iter()
+            .all(|(content, _, _)| FieldCipher::is_encrypted(content)));
+        // Rows of the first batch were not encrypted twice
+        let messages = crate::features::message::repository::read_chat_messages(
+            &repository.conn.lock().unwrap(),
+            "c1",
+            &cipher,
+        )
+        .unwrap();
+        assert!(messages[0].content.starts_with("Message 0: lorem"));
+        assert_eq!(messages[0].reasoning.as_deref(), Some("Thinking about 0"));
+    }
+
+    /// Run with `cargo test --release -- --ignored encrypted_get_messages`.
+    #[test]
+    #[ignore = "benchmark; needs a release build"]
+    fn encrypted_get_messages_overhead_is_under_ten_percent() {
+        use crate::features::message::repository::read_chat_messages;
+        use std::time::{Duration, Instant};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.db");
+        encryption_fixture(1_000)
+            .execute("VACUUM INTO ?1", [path.to_str().unwrap()])
+            .unwrap();
+        let (service, _, cipher) = encryption_service(rusqlite::Connection::open(&path).unwrap());
+
+        // Like the repository: a fresh connection per call
+        let median = |cipher: &crate::features::encryption::FieldCipher| -> Duration {
+            let mut runs: Vec<Duration> = (0..31)
+                .map(|_| {
+                    let start = Instant::now();
+                    let conn = rusqlite::Connection::open(&path).unwrap();
+                    let messages = read_chat_messages(&conn, "c1", cipher).unwrap();
+                    assert_eq!(messages.len(), 1_000);
+                    start.elapsed()
+                })
+                .collect();
+            runs.sort();
+            runs[runs.len() / 2]
+        };
+
+        // Warm the page cache first
+        median(cipher.as_ref());
+        let plain = median(cipher.as_ref());
+        service.enable(&|_| {}).unwrap();
+        let encrypted = median(cipher.as_ref());
+        assert!(
+            encrypted.as_secs_f64() < plain.as_secs_f64() * 1.10,
+            "get_messages took {encrypted:?} encrypted vs {plain:?} plaintext"
+        );
+    }
+}
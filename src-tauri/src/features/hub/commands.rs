@@ -1,12 +1,16 @@
 use crate::error::AppError;
 use crate::features::hub::mcp_config::MCPConfigService;
 use crate::features::hub::models::{
-    HubAgent, HubGitInstall, HubMCPServer, HubMCPServerConfig, HubPrompt,
+    HubAgent, HubGitInstall, HubMCPServer, HubMCPServerConfig, HubPack, HubPackItemKind, HubPrompt,
+};
+use crate::features::hub::packs::{
+    self, PackInstallReport, PackItemReport, PackItemStatus, PackMember, PackMemberInstaller,
 };
 use crate::features::hub::service::HubService;
 use crate::features::mcp_connection::MCPServerConnection;
 use crate::features::prompt::{ParsedPromptTemplate, Prompt, PromptTemplateService};
 use crate::state::AppState;
+use async_trait::async_trait;
 use serde::Deserialize;
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -51,6 +55,13 @@ pub async fn fetch_prompt_template(path: String) -> Result<ParsedPromptTemplate,
 pub async fn install_prompt_from_hub(
     payload: InstallPromptFromHubPayload,
     state: State<'_, AppState>,
+) -> Result<Prompt, AppError> {
+    install_prompt(&state, payload).await
+}
+
+async fn install_prompt(
+    state: &AppState,
+    payload: InstallPromptFromHubPayload,
 ) -> Result<Prompt, AppError> {
     let template_service = Arc::new(PromptTemplateService::new());
 
@@ -75,6 +86,9 @@ pub async fn install_prompt_from_hub(
         content,
         created_at: now,
         updated_at: now,
+        folder: None,
+        source: None,
+        model_preference: parsed.model_preference,
     };
 
     // Save to database
@@ -84,6 +98,7 @@ pub async fn install_prompt_from_hub(
             prompt.id.clone(),
             prompt.name.clone(),
             prompt.content.clone(),
+            prompt.model_preference.clone(),
         )
         .map_err(|e| AppError::Prompt(e.to_string()))?;
 
@@ -107,6 +122,13 @@ pub async fn refresh_hub_index() -> Result<(), AppError> {
 pub async fn install_mcp_server_from_hub(
     payload: InstallMCPServerFromHubPayload,
     state: State<'_, AppState>,
+) -> Result<MCPServerConnection, AppError> {
+    install_mcp_server(&state, payload)
+}
+
+fn install_mcp_server(
+    state: &AppState,
+    payload: InstallMCPServerFromHubPayload,
 ) -> Result<MCPServerConnection, AppError> {
     let config_service = Arc::new(MCPConfigService::new());
 
@@ -130,8 +152,10 @@ pub async fn install_mcp_server_from_hub(
         url,
         r#type: payload.server_type,
         headers,
+        auth_token: None,
         env_vars,
         runtime_path,
+        auto_connect: false,
         status: "disconnected".to_string(),
         tools_json: None,
         error_message: None,
@@ -148,6 +172,7 @@ pub async fn install_mcp_server_from_hub(
             connection.url.clone(),
             connection.r#type.clone(),
             connection.headers.clone(),
+            connection.auth_token.clone(),
             connection.env_vars.clone(),
             connection.runtime_path.clone(),
         )
@@ -177,13 +202,169 @@ pub async fn install_agent_from_hub(
     payload: InstallAgentFromHubPayload,
     state: State<'_, AppState>,
 ) -> Result<String, AppError> {
+    install_agent(&state, &payload.git_install).await
+}
+
+async fn install_agent(state: &AppState, git_install: &HubGitInstall) -> Result<String, AppError> {
     state
         .agent_manager
         .install_from_git(
-            &payload.git_install.repository_url,
-            Some(&payload.git_install.revision),
-            Some(&payload.git_install.subpath),
+            &git_install.repository_url,
+            Some(&git_install.revision),
+            Some(&git_install.subpath),
         )
         .await
         .map_err(|e| AppError::Agent(e.to_string()))
 }
+
+#[derive(Deserialize)]
+pub struct InstallPackFromHubPayload {
+    #[serde(rename = "packId")]
+    pub pack_id: String,
+    #[serde(rename = "workspaceId")]
+    pub workspace_id: String,
+    /// Values for the pack's secrets and server variables
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Keep the members that installed when a required one fails
+    #[serde(default, rename = "partialOk")]
+    pub partial_ok: bool,
+}
+
+#[tauri::command]
+pub async fn fetch_hub_packs() -> Result<Vec<HubPack>, AppError> {
+    let hub_service = Arc::new(HubService::new());
+    hub_service.get_packs().await
+}
+
+/// Install every member of a pack, then add its permission defaults to the
+/// workspace. Member failures are reported per item rather than as an error.
+#[tauri::command]
+pub async fn install_pack_from_hub(
+    payload: InstallPackFromHubPayload,
+    state: State<'_, AppState>,
+) -> Result<PackInstallReport, AppError> {
+    let hub_service = Arc::new(HubService::new());
+    let index = hub_service.get_index().await?;
+    let pack = index
+        .resources
+        .packs
+        .iter()
+        .find(|p| p.id == payload.pack_id)
+        .cloned()
+        .ok_or_else(|| AppError::NotFound(format!("Hub pack not found: {}", payload.pack_id)))?;
+
+    let installer = StateInstaller { state: &state };
+    let mut report = packs::install_pack(
+        &index,
+        &pack,
+        &installer,
+        &payload.variables,
+        payload.partial_ok,
+    )
+    .await;
+    if report.rolled_back {
+        return Ok(report);
+    }
+    state.hub_pack_service.record_install(&report)?;
+
+    let settings_service = &state.workspace_feature.settings_service;
+    let current = settings_service
+        .get_by_workspace_id(&payload.workspace_id)?
+        .and_then(|s| s.tool_permission_config);
+    let (merged, added) = packs::merge_permission_defaults(current.as_deref(), &pack.permissions)?;
+    if !added.is_empty() {
+        settings_service.set_tool_permission_config(&payload.workspace_id, merged)?;
+    }
+    report.permissions_applied = added;
+    Ok(report)
+}
+
+/// Remove the members a pack installed, keeping those other installed packs
+/// also list and those that existed before the pack.
+#[tauri::command]
+pub async fn uninstall_pack_from_hub(
+    pack_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<PackItemReport>, AppError> {
+    let records = state.hub_pack_service.records()?;
+    if !records.iter().any(|r| r.pack_id == pack_id) {
+        return Err(AppError::NotFound(format!(
+            "Hub pack not installed: {pack_id}"
+        )));
+    }
+
+    let installer = StateInstaller { state: &state };
+    let items = packs::uninstall_pack(&pack_id, &records, &installer).await;
+    let failed: Vec<PackItemReport> = items
+        .iter()
+        .filter(|item| item.status == PackItemStatus::Failed)
+        .cloned()
+        .collect();
+    state.hub_pack_service.forget(&pack_id, &failed)?;
+    Ok(items)
+}
+
+/// Pack members go through the same paths as single hub installs.
+struct StateInstaller<'a> {
+    state: &'a AppState,
+}
+
+#[async_trait]
+impl PackMemberInstaller for StateInstaller<'_> {
+    async fn is_installed(&self, kind: HubPackItemKind, id: &str) -> Result<bool, AppError> {
+        match kind {
+            HubPackItemKind::Prompt => Ok(self.state.prompt_service.get_by_id(id)?.is_some()),
+            HubPackItemKind::McpServer => {
+                Ok(self.state.mcp_connection_service.get_by_id(id)?.is_some())
+            }
+            HubPackItemKind::Agent => Ok(self
+                .state
+                .agent_manager
+                .list_installed()
+                .map_err(|e| AppError::Agent(e.to_string()))?
+                .iter()
+                .any(|agent| agent.manifest.id == id)),
+        }
+    }
+
+    async fn install(
+        &self,
+        member: &PackMember,
+        variables: &HashMap<String, String>,
+    ) -> Result<String, AppError> {
+        match member {
+            PackMember::Prompt(prompt) => {
+                let payload = InstallPromptFromHubPayload {
+                    prompt_id: prompt.id.clone(),
+                    name: prompt.name.clone(),
+                    path: prompt.path.clone(),
+                };
+                Ok(install_prompt(self.state, payload).await?.id)
+            }
+            PackMember::McpServer(server) => {
+                let payload = InstallMCPServerFromHubPayload {
+                    server_id: server.id.clone(),
+                    name: server.name.clone(),
+                    server_type: server.r#type.clone(),
+                    config: server.config.clone(),
+                    variables: variables.clone(),
+                };
+                Ok(install_mcp_server(self.state, payload)?.id)
+            }
+            PackMember::Agent(agent) => install_agent(self.state, &agent.git_install).await,
+        }
+    }
+
+    async fn uninstall(&self, kind: HubPackItemKind, id: &str) -> Result<(), AppError> {
+        match kind {
+            HubPackItemKind::Prompt => self.state.prompt_service.delete(id.to_string()),
+            HubPackItemKind::McpServer => self.state.mcp_connection_service.delete(id.to_string()),
+            HubPackItemKind::Agent => self
+                .state
+                .agent_manager
+                .delete_agent(id)
+                .map_err(|e| AppError::Agent(e.to_string())),
+        }
+    }
+}
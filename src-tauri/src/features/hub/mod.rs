@@ -1,4 +1,6 @@
 pub mod commands;
 pub mod mcp_config;
 pub mod models;
+pub mod packs;
+pub mod repository;
 pub mod service;
@@ -15,6 +15,8 @@ pub struct HubResources {
     pub mcp_servers: Vec<HubMCPServer>,
     #[serde(default)]
     pub agents: Vec<HubAgent>,
+    #[serde(default)]
+    pub packs: Vec<HubPack>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -24,6 +26,8 @@ pub struct HubPrompt {
     pub description: String,
     pub icon: String,
     pub path: String,
+    #[serde(default)]
+    pub version: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,6 +38,8 @@ pub struct HubMCPServer {
     pub icon: String,
     pub r#type: String, // "stdio" | "sse"
     pub config: HubMCPServerConfig,
+    #[serde(default)]
+    pub version: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,3 +70,75 @@ pub struct HubGitInstall {
     pub revision: String,
     pub subpath: String,
 }
+
+/// A bundle of hub entries installed together, e.g. MCP servers with the
+/// prompt that uses them and recommended tool permissions.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubPack {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    #[serde(default)]
+    pub icon: String,
+    pub items: Vec<HubPackItem>,
+    /// Tool name -> "require" | "auto", merged into the workspace
+    /// `tool_permission_config` without overriding the user's entries
+    #[serde(default)]
+    pub permissions: std::collections::BTreeMap<String, String>,
+    #[serde(default)]
+    pub required_secrets: Vec<HubPackSecret>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum HubPackItemKind {
+    Prompt,
+    McpServer,
+    Agent,
+}
+
+impl HubPackItemKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Prompt => "prompt",
+            Self::McpServer => "mcp_server",
+            Self::Agent => "agent",
+        }
+    }
+
+    pub fn parse(kind: &str) -> Option<Self> {
+        match kind {
+            "prompt" => Some(Self::Prompt),
+            "mcp_server" => Some(Self::McpServer),
+            "agent" => Some(Self::Agent),
+            _ => None,
+        }
+    }
+}
+
+/// A hub entry of the pack, by id and optionally pinned to a version.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubPackItem {
+    pub kind: HubPackItemKind,
+    pub id: String,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// A failed required item rolls the whole pack back
+    #[serde(default = "default_required")]
+    pub required: bool,
+}
+
+const fn default_required() -> bool {
+    true
+}
+
+/// A variable the pack's MCP servers need, such as an API key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubPackSecret {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Ids of the pack items using it
+    #[serde(default)]
+    pub used_by: Vec<String>,
+}
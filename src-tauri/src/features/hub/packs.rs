@@ -0,0 +1,557 @@
+//! Hub packs: several hub entries installed, and uninstalled, as one.
+//!
+//! Members are installed in pack order through the regular hub install
+//! paths. When a required member fails, the members installed so far are
+//! removed again unless the caller accepts a partial install. Entries that
+//! were already installed are left alone and never removed with the pack.
+
+use super::models::{
+    HubAgent, HubIndex, HubMCPServer, HubPack, HubPackItem, HubPackItemKind, HubPrompt,
+};
+use super::repository::{HubPackRecord, HubPackRepository};
+use crate::error::AppError;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+/// Values accepted in the workspace `tool_permission_config`.
+const PERMISSION_VALUES: &[&str] = &["require", "auto"];
+/// Reason of a member skipped because it existed before the pack.
+const ALREADY_INSTALLED: &str = "Already installed";
+
+/// A pack item resolved against the hub index.
+#[derive(Debug, Clone)]
+pub enum PackMember {
+    Prompt(HubPrompt),
+    McpServer(HubMCPServer),
+    Agent(HubAgent),
+}
+
+/// Installs and removes single hub entries. Implemented over `AppState` by
+/// the hub commands.
+#[async_trait]
+pub trait PackMemberInstaller: Send + Sync {
+    async fn is_installed(&self, kind: HubPackItemKind, id: &str) -> Result<bool, AppError>;
+    /// Returns the local id of the installed entry.
+    async fn install(
+        &self,
+        member: &PackMember,
+        variables: &HashMap<String, String>,
+    ) -> Result<String, AppError>;
+    async fn uninstall(&self, kind: HubPackItemKind, id: &str) -> Result<(), AppError>;
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PackItemStatus {
+    Installed,
+    /// Already installed, or not attempted after the install was aborted
+    Skipped,
+    Failed,
+    /// Installed, then removed because a required member failed
+    RolledBack,
+    /// Removed by a pack uninstall
+    Removed,
+    /// Kept by a pack uninstall
+    Kept,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackItemReport {
+    pub kind: HubPackItemKind,
+    pub id: String,
+    pub status: PackItemStatus,
+    pub reason: Option<String>,
+    /// Secrets this item uses that were not provided
+    pub missing_secrets: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackInstallReport {
+    pub pack_id: String,
+    pub items: Vec<PackItemReport>,
+    /// A required member failed and the installed members were removed
+    pub rolled_back: bool,
+    /// Tools whose permission default was added to the workspace
+    pub permissions_applied: Vec<String>,
+    /// Secrets of the pack that were not provided
+    pub missing_secrets: Vec<String>,
+}
+
+impl PackInstallReport {
+    /// Members to record for the pack; `owned` ones were installed by it.
+    pub fn records(&self) -> Vec<HubPackRecord> {
+        if self.rolled_back {
+            return Vec::new();
+        }
+        self.items
+            .iter()
+            .filter(|item| {
+                item.status == PackItemStatus::Installed
+                    || (item.status == PackItemStatus::Skipped
+                        && item.reason.as_deref() == Some(ALREADY_INSTALLED))
+            })
+            .map(|item| HubPackRecord {
+                pack_id: self.pack_id.clone(),
+                kind: item.kind,
+                item_id: item.id.clone(),
+                owned: item.status == PackItemStatus::Installed,
+            })
+            .collect()
+    }
+}
+
+/// Find a pack item in the index, checking the pinned version when the
+/// entry has one.
+pub fn resolve_item(index: &HubIndex, item: &HubPackItem) -> Result<PackMember, String> {
+    let resources = &index.resources;
+    let (member, version) = match item.kind {
+        HubPackItemKind::Prompt => resources
+            .prompts
+            .iter()
+            .find(|p| p.id == item.id)
+            .map(|p| (PackMember::Prompt(p.clone()), p.version.clone())),
+        HubPackItemKind::McpServer => resources
+            .mcp_servers
+            .iter()
+            .find(|s| s.id == item.id)
+            .map(|s| (PackMember::McpServer(s.clone()), s.version.clone())),
+        HubPackItemKind::Agent => resources
+            .agents
+            .iter()
+            .find(|a| a.id == item.id)
+            .map(|a| (PackMember::Agent(a.clone()), Some(a.version.clone()))),
+    }
+    .ok_or_else(|| format!("No {} '{}' in the hub", item.kind.as_str(), item.id))?;
+
+    match (&item.version, version) {
+        (Some(wanted), Some(available)) if *wanted != available => Err(format!(
+            "Pack wants version {wanted}, the hub has {available}"
+        )),
+        _ => Ok(member),
+    }
+}
+
+/// Install the members of `pack` in order. Permissions are not applied here;
+/// see `merge_permission_defaults`.
+pub async fn install_pack(
+    index: &HubIndex,
+    pack: &HubPack,
+    installer: &dyn PackMemberInstaller,
+    variables: &HashMap<String, String>,
+    partial_ok: bool,
+) -> PackInstallReport {
+    let missing: Vec<_> = pack
+        .required_secrets
+        .iter()
+        .filter(|s| variables.get(&s.name).is_none_or(|v| v.trim().is_empty()))
+        .collect();
+    let mut report = PackInstallReport {
+        pack_id: pack.id.clone(),
+        items: Vec::new(),
+        rolled_back: false,
+        permissions_applied: Vec::new(),
+        missing_secrets: missing.iter().map(|s| s.name.clone()).collect(),
+    };
+    // Local ids of the members installed by this run, by report position
+    let mut installed_ids: Vec<(usize, String)> = Vec::new();
+    let mut aborted_by: Option<String> = None;
+
+    for item in &pack.items {
+        let mut entry = PackItemReport {
+            kind: item.kind,
+            id: item.id.clone(),
+            status: PackItemStatus::Skipped,
+            reason: None,
+            missing_secrets: Vec::new(),
+        };
+        if let Some(failed) = &aborted_by {
+            entry.reason = Some(format!("Not installed because '{failed}' failed"));
+            report.items.push(entry);
+            continue;
+        }
+
+        let result = match resolve_item(index, item) {
+            Err(reason) => Err(reason),
+            Ok(member) => match installer.is_installed(item.kind, &item.id).await {
+                Ok(true) => Ok(None),
+                Ok(false) => installer
+                    .install(&member, variables)
+                    .await
+                    .map(Some)
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            },
+        };
+        match result {
+            // Already there before the pack: neither installed nor removed by it
+            Ok(None) => entry.reason = Some(ALREADY_INSTALLED.to_string()),
+            Ok(Some(local_id)) => {
+                entry.status = PackItemStatus::Installed;
+                entry.id.clone_from(&local_id);
+                entry.missing_secrets = missing
+                    .iter()
+                    .filter(|s| s.used_by.contains(&item.id))
+                    .map(|s| s.name.clone())
+                    .collect();
+                installed_ids.push((report.items.len(), local_id));
+            }
+            Err(reason) => {
+                entry.status = PackItemStatus::Failed;
+                entry.reason = Some(reason);
+                if item.required && !partial_ok {
+                    aborted_by = Some(item.id.clone());
+                }
+            }
+        }
+        report.items.push(entry);
+    }
+
+    if let Some(failed) = aborted_by {
+        report.rolled_back = true;
+        for (position, local_id) in installed_ids.into_iter().rev() {
+            let entry = &mut report.items[position];
+            match installer.uninstall(entry.kind, &local_id).await {
+                Ok(()) => {
+                    entry.status = PackItemStatus::RolledBack;
+                    entry.reason = Some(format!("Removed because '{failed}' failed"));
+                }
+                Err(e) => {
+                    tracing::warn!(item = %local_id, error = %e, "Failed to roll back pack member");
+                    entry.reason = Some(format!("Could not be rolled back: {e}"));
+                }
+            }
+            entry.missing_secrets.clear();
+        }
+    }
+    report
+}
+
+/// Add the pack's permission defaults to a `tool_permission_config`. Tools
+/// the user already configured keep their setting. Returns the merged
+/// config and the tools that were added.
+pub fn merge_permission_defaults(
+    current: Option<&str>,
+    defaults: &BTreeMap<String, String>,
+) -> Result<(String, Vec<String>), AppError> {
+    let mut config: serde_json::Map<String, serde_json::Value> = match current {
+        Some(json) if !json.trim().is_empty() => serde_json::from_str(json)
+            .map_err(|e| AppError::Validation(format!("Invalid tool permission config: {e}")))?,
+        _ => serde_json::Map::new(),
+    };
+
+    let mut added = Vec::new();
+    for (tool, permission) in defaults {
+        if !PERMISSION_VALUES.contains(&permission.as_str()) || config.contains_key(tool) {
+            continue;
+        }
+        config.insert(tool.clone(), serde_json::json!(permission));
+        added.push(tool.clone());
+    }
+    Ok((serde_json::Value::Object(config).to_string(), added))
+}
+
+/// Remove the members `pack_id` installed, except those another installed
+/// pack also lists.
+pub async fn uninstall_pack(
+    pack_id: &str,
+    records: &[HubPackRecord],
+    installer: &dyn PackMemberInstaller,
+) -> Vec<PackItemReport> {
+    let mut items = Vec::new();
+    for record in records.iter().filter(|r| r.pack_id == pack_id) {
+        let mut entry = PackItemReport {
+            kind: record.kind,
+            id: record.item_id.clone(),
+            status: PackItemStatus::Kept,
+            reason: None,
+            missing_secrets: Vec::new(),
+        };
+        let shared_with = records
+            .iter()
+            .find(|r| r.pack_id != pack_id && r.kind == record.kind && r.item_id == record.item_id);
+        if !record.owned {
+            entry.reason = Some("Installed before the pack".to_string());
+        } else if let Some(other) = shared_with {
+            entry.reason = Some(format!("Also part of pack '{}'", other.pack_id));
+        } else {
+            match installer.uninstall(record.kind, &record.item_id).await {
+                Ok(()) => entry.status = PackItemStatus::Removed,
+                Err(e) => {
+                    entry.status = PackItemStatus::Failed;
+                    entry.reason = Some(e.to_string());
+                }
+            }
+        }
+        items.push(entry);
+    }
+    items
+}
+
+/// Keeps track of which hub entries each installed pack brought in.
+pub struct HubPackService {
+    repository: Arc<dyn HubPackRepository>,
+}
+
+impl HubPackService {
+    pub fn new(repository: Arc<dyn HubPackRepository>) -> Self {
+        Self { repository }
+    }
+
+    pub fn record_install(&self, report: &PackInstallReport) -> Result<(), AppError> {
+        self.repository
+            .replace_pack(&report.pack_id, &report.records())
+    }
+
+    pub fn records(&self) -> Result<Vec<HubPackRecord>, AppError> {
+        self.repository.get_all()
+    }
+
+    /// Forget the pack, keeping the records of members that failed to be
+    /// removed so a later uninstall can retry them.
+    pub fn forget(&self, pack_id: &str, failed: &[PackItemReport]) -> Result<(), AppError> {
+        let remaining: Vec<HubPackRecord> = failed
+            .iter()
+            .map(|item| HubPackRecord {
+                pack_id: pack_id.to_string(),
+                kind: item.kind,
+                item_id: item.id.clone(),
+                owned: true,
+            })
+            .collect();
+        self.repository.replace_pack(pack_id, &remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    const HUB_FIXTURE: &str = r#"{
+        "version": "1",
+        "last_updated": "2026-01-01",
+        "hub_name": "fixture",
+        "resources": {
+            "prompts": [
+                {"id": "research", "name": "Research", "description": "", "icon": "", "path": "prompts/research.md"}
+            ],
+            "mcp_servers": [
+                {"id": "brave-search", "name": "Brave", "description": "", "icon": "", "type": "stdio",
+                 "config": {"command": "npx", "args": ["brave", "{BRAVE_API_KEY}"]}},
+                {"id": "fetch", "name": "Fetch", "description": "", "icon": "", "type": "stdio",
+                 "config": {"command": "uvx", "args": ["mcp-server-fetch"]}}
+            ],
+            "packs": [
+                {"id": "web-research", "name": "Web research", "description": "",
+                 "items": [
+                    {"kind": "mcp_server", "id": "brave-search"},
+                    {"kind": "mcp_server", "id": "fetch"},
+                    {"kind": "prompt", "id": "research", "required": false},
+                    {"kind": "agent", "id": "missing.agent"}
+                 ],
+                 "permissions": {"brave_web_search": "auto", "fetch": "require", "rm": "never"},
+                 "required_secrets": [{"name": "BRAVE_API_KEY", "used_by": ["brave-search"]}]}
+            ]
+        }
+    }"#;
+
+    /// Installs everything except the ids in `failing`; entries in
+    /// `present` count as installed before the pack.
+    #[derive(Default)]
+    struct FakeInstaller {
+        present: Vec<String>,
+        failing: Vec<String>,
+        installed: std::sync::Mutex<Vec<String>>,
+    }
+
+    #[async_trait::async_trait]
+    impl crate::features::hub::packs::PackMemberInstaller for FakeInstaller {
+        async fn is_installed(
+            &self,
+            _kind: crate::features::hub::models::HubPackItemKind,
+            id: &str,
+        ) -> Result<bool, crate::error::AppError> {
+            Ok(self.present.iter().any(|p| p == id))
+        }
+
+        async fn install(
+            &self,
+            member: &crate::features::hub::packs::PackMember,
+            _variables: &std::collections::HashMap<String, String>,
+        ) -> Result<String, crate::error::AppError> {
+            use crate::features::hub::packs::PackMember;
+
+            let id = match member {
+                PackMember::Prompt(p) => p.id.clone(),
+                PackMember::McpServer(s) => s.id.clone(),
+                PackMember::Agent(a) => a.id.clone(),
+            };
+            if self.failing.contains(&id) {
+                return Err(crate::error::AppError::Hub(format!("{id} broke")));
+            }
+            self.installed.lock().unwrap().push(id.clone());
+            Ok(id)
+        }
+
+        async fn uninstall(
+            &self,
+            _kind: crate::features::hub::models::HubPackItemKind,
+            id: &str,
+        ) -> Result<(), crate::error::AppError> {
+            self.installed.lock().unwrap().retain(|i| i != id);
+            Ok(())
+        }
+    }
+
+    fn install_fixture_pack(
+        installer: &FakeInstaller,
+        partial_ok: bool,
+    ) -> crate::features::hub::packs::PackInstallReport {
+        use crate::features::hub::models::HubIndex;
+        use crate::features::hub::packs::install_pack;
+
+        let index: HubIndex = serde_json::from_str(HUB_FIXTURE).unwrap();
+        let pack = index.resources.packs[0].clone();
+        tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(install_pack(
+                &index,
+                &pack,
+                installer,
+                &std::collections::HashMap::new(),
+                partial_ok,
+            ))
+    }
+
+    #[test]
+    fn hub_pack_rolls_back_when_a_required_member_fails() {
+        use crate::features::hub::packs::PackItemStatus;
+
+        let installer = FakeInstaller {
+            present: vec!["fetch".to_string()],
+            failing: vec!["research".to_string()],
+            ..FakeInstaller::default()
+        };
+        let report = install_fixture_pack(&installer, false);
+
+        // The optional prompt fails without aborting; the agent missing from
+        // the index does, and brave-search is removed again. fetch was
+        // already there and stays.
+        assert!(report.rolled_back);
+        let statuses: Vec<_> = report
+            .items
+            .iter()
+            .map(|i| (i.id.as_str(), i.status))
+            .collect();
+        assert_eq!(
+            statuses,
+            [
+                ("brave-search", PackItemStatus::RolledBack),
+                ("fetch", PackItemStatus::Skipped),
+                ("research", PackItemStatus::Failed),
+                ("missing.agent", PackItemStatus::Failed),
+            ]
+        );
+        assert!(installer.installed.lock().unwrap().is_empty());
+        assert!(report.records().is_empty());
+        assert_eq!(report.missing_secrets, ["BRAVE_API_KEY"]);
+
+        let partial = install_fixture_pack(&FakeInstaller::default(), true);
+        assert!(!partial.rolled_back);
+        assert_eq!(partial.items[0].status, PackItemStatus::Installed);
+        assert_eq!(partial.items[0].missing_secrets, ["BRAVE_API_KEY"]);
+        assert_eq!(partial.items[3].status, PackItemStatus::Failed);
+        assert_eq!(partial.records().len(), 3);
+    }
+
+    #[test]
+    fn hub_pack_uninstall_keeps_shared_and_preexisting_members() {
+        use crate::features::hub::models::HubPackItemKind;
+        use crate::features::hub::packs::{uninstall_pack, PackItemStatus};
+        use crate::features::hub::repository::{
+            list_pack_records, replace_pack_records, HubPackRecord,
+        };
+
+        let record = |pack_id: &str, item_id: &str, owned: bool| HubPackRecord {
+            pack_id: pack_id.to_string(),
+            kind: HubPackItemKind::McpServer,
+            item_id: item_id.to_string(),
+            owned,
+        };
+        let mut conn = migrated_db();
+        replace_pack_records(
+            &mut conn,
+            "web-research",
+            &[
+                record("web-research", "brave-search", true),
+                record("web-research", "fetch", true),
+                record("web-research", "github", false),
+            ],
+        )
+        .unwrap();
+        replace_pack_records(&mut conn, "docs", &[record("docs", "fetch", false)]).unwrap();
+        let records = list_pack_records(&conn).unwrap();
+        assert_eq!(records.len(), 4);
+
+        let installer = FakeInstaller::default();
+        installer
+            .installed
+            .lock()
+            .unwrap()
+            .extend(["brave-search", "fetch", "github"].map(String::from));
+        let items = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(uninstall_pack("web-research", &records, &installer));
+
+        let statuses: Vec<_> = items.iter().map(|i| (i.id.as_str(), i.status)).collect();
+        assert_eq!(
+            statuses,
+            [
+                ("brave-search", PackItemStatus::Removed),
+                ("fetch", PackItemStatus::Kept),
+                ("github", PackItemStatus::Kept),
+            ]
+        );
+        assert_eq!(*installer.installed.lock().unwrap(), ["fetch", "github"]);
+
+        replace_pack_records(&mut conn, "web-research", &[]).unwrap();
+        assert_eq!(
+            list_pack_records(&conn).unwrap(),
+            [record("docs", "fetch", false)]
+        );
+    }
+
+    #[test]
+    fn hub_pack_permissions_merge_without_overriding_user_entries() {
+        use crate::features::hub::models::HubIndex;
+        use crate::features::hub::packs::merge_permission_defaults;
+
+        let index: HubIndex = serde_json::from_str(HUB_FIXTURE).unwrap();
+        let defaults = &index.resources.packs[0].permissions;
+
+        let (merged, added) =
+            merge_permission_defaults(Some(r#"{"fetch":"auto","write_file":"require"}"#), defaults)
+                .unwrap();
+        let merged: serde_json::Value = serde_json::from_str(&merged).unwrap();
+        // The user's "auto" for fetch wins; invalid values are ignored
+        assert_eq!(
+            merged,
+            serde_json::json!({
+                "fetch": "auto",
+                "write_file": "require",
+                "brave_web_search": "auto"
+            })
+        );
+        assert_eq!(added, ["brave_web_search"]);
+
+        let (fresh, added) = merge_permission_defaults(None, defaults).unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&fresh).unwrap(),
+            serde_json::json!({"brave_web_search": "auto", "fetch": "require"})
+        );
+        assert_eq!(added, ["brave_web_search", "fetch"]);
+
+        assert!(merge_permission_defaults(Some("not json"), defaults).is_err());
+    }
+}
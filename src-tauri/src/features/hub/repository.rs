@@ -0,0 +1,99 @@
+use super::models::HubPackItemKind;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// A hub entry listed by an installed pack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HubPackRecord {
+    pub pack_id: String,
+    pub kind: HubPackItemKind,
+    pub item_id: String,
+    /// Installed by the pack, as opposed to already present
+    pub owned: bool,
+}
+
+pub trait HubPackRepository: Send + Sync {
+    fn get_all(&self) -> Result<Vec<HubPackRecord>, AppError>;
+    /// Replace the records of `pack_id`; an empty list forgets the pack.
+    fn replace_pack(&self, pack_id: &str, records: &[HubPackRecord]) -> Result<(), AppError>;
+}
+
+pub struct SqliteHubPackRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteHubPackRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl HubPackRepository for SqliteHubPackRepository {
+    fn get_all(&self) -> Result<Vec<HubPackRecord>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        list_pack_records(&conn)
+    }
+
+    fn replace_pack(&self, pack_id: &str, records: &[HubPackRecord]) -> Result<(), AppError> {
+        let mut conn = crate::db::get_connection(&self.app)?;
+        replace_pack_records(&mut conn, pack_id, records)
+    }
+}
+
+pub fn list_pack_records(conn: &Connection) -> Result<Vec<HubPackRecord>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT pack_id, item_kind, item_id, owned FROM hub_pack_items ORDER BY installed_at, rowid",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+            row.get::<_, i64>(3)?,
+        ))
+    })?;
+
+    let mut records = Vec::new();
+    for row in rows {
+        let (pack_id, kind, item_id, owned) = row?;
+        // Kinds written by a newer version are left alone
+        if let Some(kind) = HubPackItemKind::parse(&kind) {
+            records.push(HubPackRecord {
+                pack_id,
+                kind,
+                item_id,
+                owned: owned == 1,
+            });
+        }
+    }
+    Ok(records)
+}
+
+pub fn replace_pack_records(
+    conn: &mut Connection,
+    pack_id: &str,
+    records: &[HubPackRecord],
+) -> Result<(), AppError> {
+    let now = chrono::Utc::now().timestamp_millis();
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM hub_pack_items WHERE pack_id = ?1",
+        params![pack_id],
+    )?;
+    for record in records {
+        tx.execute(
+            "INSERT OR REPLACE INTO hub_pack_items (pack_id, item_kind, item_id, owned, installed_at) VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                pack_id,
+                record.kind.as_str(),
+                record.item_id,
+                i64::from(record.owned),
+                now
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
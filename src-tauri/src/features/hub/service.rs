@@ -1,5 +1,5 @@
 use crate::error::AppError;
-use crate::features::hub::models::{HubAgent, HubIndex, HubMCPServer, HubPrompt};
+use crate::features::hub::models::{HubAgent, HubIndex, HubMCPServer, HubPack, HubPrompt};
 use std::sync::{Arc, RwLock};
 use std::time::{Duration, SystemTime};
 
@@ -59,6 +59,12 @@ impl HubService {
         Ok(index.resources.agents)
     }
 
+    /// Get only packs from hub index
+    pub async fn get_packs(&self) -> Result<Vec<HubPack>, AppError> {
+        let index = self.get_index().await?;
+        Ok(index.resources.packs)
+    }
+
     fn get_from_cache(&self) -> Option<HubIndex> {
         let cache = self.cached_index.read().ok()?;
         let cached = cache.as_ref()?;
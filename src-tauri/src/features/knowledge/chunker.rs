@@ -0,0 +1,52 @@
+//! Splitting document text into chunks small enough to embed and to send.
+
+/// Longest chunk, in characters
+pub const CHUNK_CHARS: usize = 1_500;
+
+/// `text` split at paragraph breaks into chunks of at most `max_chars`
+/// characters. Paragraphs are kept together while they fit; a longer one is
+/// cut at the last whitespace before the limit.
+pub fn chunk_text(text: &str, max_chars: usize) -> Vec<String> {
+    let max_chars = max_chars.max(1);
+    let text = text.replace("\r\n", "\n");
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    let mut current_chars = 0;
+    for piece in pieces(&text, max_chars) {
+        let piece_chars = piece.chars().count();
+        if !current.is_empty() && current_chars + 2 + piece_chars > max_chars {
+            chunks.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        if !current.is_empty() {
+            current.push_str("\n\n");
+            current_chars += 2;
+        }
+        current.push_str(piece);
+        current_chars += piece_chars;
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// The paragraphs of `text`, with those over `max_chars` cut into pieces.
+fn pieces(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let mut rest = paragraph;
+        while let Some((limit, _)) = rest.char_indices().nth(max_chars) {
+            let cut = rest[..limit]
+                .rfind(char::is_whitespace)
+                .filter(|i| *i > 0)
+                .unwrap_or(limit);
+            pieces.push(rest[..cut].trim_end());
+            rest = rest[cut..].trim_start();
+        }
+        if !rest.is_empty() {
+            pieces.push(rest);
+        }
+    }
+    pieces
+}
@@ -0,0 +1,46 @@
+use super::models::{KnowledgeDocument, KnowledgeHit};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Attach a file or folder to the workspace: its text files are chunked and
+/// embedded, replacing what an earlier attach of the same files stored.
+#[tauri::command]
+pub async fn index_document(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    path: String,
+) -> Result<Vec<KnowledgeDocument>, AppError> {
+    state
+        .knowledge_service
+        .index_path(&workspace_id, &path)
+        .await
+}
+
+#[tauri::command]
+pub async fn list_knowledge_documents(
+    state: State<'_, AppState>,
+    workspace_id: String,
+) -> Result<Vec<KnowledgeDocument>, AppError> {
+    state.knowledge_service.list_documents(&workspace_id)
+}
+
+/// Chunks of the workspace's documents closest in meaning to `query`.
+#[tauri::command]
+pub async fn search_knowledge(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<KnowledgeHit>, AppError> {
+    state
+        .knowledge_service
+        .search(&workspace_id, &query, limit)
+        .await
+}
+
+/// Detach a document; the file itself is left alone.
+#[tauri::command]
+pub async fn delete_document(state: State<'_, AppState>, id: String) -> Result<(), AppError> {
+    state.knowledge_service.delete_document(&id)
+}
@@ -0,0 +1,50 @@
+//! How retrieved chunks go into a prompt, and what the answer keeps of them.
+
+use super::models::KnowledgeHit;
+
+/// Metadata key of the sources an answer was given.
+pub const KNOWLEDGE_METADATA_KEY: &str = "knowledge";
+
+/// The system prompt carrying `hits`, numbered from 1 so the answer can
+/// cite them. `None` without hits.
+pub fn knowledge_prompt(hits: &[KnowledgeHit]) -> Option<String> {
+    if hits.is_empty() {
+        return None;
+    }
+    let mut content = String::from(
+        "Excerpts from the workspace's documents that may help with the next message. \
+         Cite the ones you use by their number, as [1]; ignore the ones that do not help.",
+    );
+    for (number, hit) in (1..).zip(hits) {
+        content.push_str(&format!(
+            "\n\n[{number}] {} ({})\n{}",
+            hit.title,
+            hit.path,
+            hit.content.trim()
+        ));
+    }
+    Some(content)
+}
+
+/// The sources of an answer for its metadata, by the numbers it cites them
+/// with; the chunk text stays with the document.
+pub fn citations_metadata(hits: &[KnowledgeHit]) -> Option<serde_json::Value> {
+    if hits.is_empty() {
+        return None;
+    }
+    Some(serde_json::Value::Array(
+        (1..)
+            .zip(hits)
+            .map(|(number, hit): (u32, _)| {
+                serde_json::json!({
+                    "number": number,
+                    "documentId": hit.document_id,
+                    "chunkIndex": hit.chunk_index,
+                    "path": hit.path,
+                    "title": hit.title,
+                    "score": hit.score,
+                })
+            })
+            .collect(),
+    ))
+}
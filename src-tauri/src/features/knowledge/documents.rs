@@ -0,0 +1,80 @@
+//! Which files of an attached path are read, and reading them.
+
+use crate::error::AppError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Larger files are left out; they are rarely prose worth retrieving.
+pub const MAX_FILE_BYTES: u64 = 2 * 1024 * 1024;
+/// Files read from one attached folder, at most
+pub const MAX_FOLDER_FILES: usize = 1_000;
+
+/// Extensions of the files read from a folder. A file attached on its own is
+/// read whatever its extension, as long as it is text.
+const TEXT_EXTENSIONS: [&str; 38] = [
+    "md", "markdown", "mdx", "txt", "rst", "adoc", "org", "tex", "csv", "tsv", "json", "yaml",
+    "yml", "toml", "xml", "html", "htm", "css", "ini", "cfg", "sql", "sh", "rs", "py", "js", "jsx",
+    "ts", "tsx", "go", "java", "kt", "c", "h", "cpp", "hpp", "cs", "rb", "swift",
+];
+
+pub fn has_text_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| TEXT_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// The files to read for `path`: the file itself, or the text files of the
+/// folder and its subfolders in path order. Hidden entries and links to
+/// folders are left out.
+pub fn collect_files(path: &Path) -> Result<Vec<PathBuf>, AppError> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    if !path.is_dir() {
+        return Err(AppError::Validation(format!(
+            "Not a file or folder: {}",
+            path.display()
+        )));
+    }
+
+    let mut files = Vec::new();
+    let mut folders = vec![path.to_path_buf()];
+    while let Some(folder) = folders.pop() {
+        for entry in fs::read_dir(&folder)? {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let file_type = entry.file_type()?;
+            if file_type.is_dir() {
+                folders.push(entry.path());
+            } else if has_text_extension(&entry.path()) {
+                files.push(entry.path());
+            }
+        }
+    }
+    files.sort();
+    if files.len() > MAX_FOLDER_FILES {
+        tracing::warn!(
+            path = %path.display(),
+            found = files.len(),
+            "Folder has more text files than are read, keeping the first {MAX_FOLDER_FILES}"
+        );
+        files.truncate(MAX_FOLDER_FILES);
+    }
+    Ok(files)
+}
+
+/// The text of the file at `path`, or `None` when it is too large or not
+/// UTF-8 text.
+pub fn read_text(path: &Path) -> Result<Option<String>, AppError> {
+    if fs::metadata(path)?.len() > MAX_FILE_BYTES {
+        return Ok(None);
+    }
+    let bytes = fs::read(path)?;
+    // A NUL byte gives away a binary file that happens to be valid UTF-8
+    if bytes.contains(&0) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
@@ -0,0 +1,22 @@
+//! Documents attached to a workspace, retrieved into the prompts of its chats.
+//!
+//! Attaching a file or folder reads its text files, splits them into chunks
+//! of a few paragraphs and embeds every chunk with the model the semantic
+//! index is set to use, or the local hashing model when none is chosen. A
+//! turn embeds the user's message and sends the closest chunks of the
+//! workspace along with the system prompt, numbered so the answer can cite
+//! them; the answer's metadata lists the sources it was given. Chunks are
+//! scored by brute force, as messages are in the semantic index. Documents
+//! are not watched: attaching one again reads it again.
+
+pub mod chunker;
+pub mod commands;
+pub mod context;
+pub mod documents;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// A file attached to a workspace, as indexed.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct KnowledgeDocument {
+    pub id: String,
+    pub workspace_id: String,
+    /// Absolute path the file was read from
+    pub path: String,
+    /// File name, shown with citations
+    pub title: String,
+    pub size_bytes: u64,
+    pub chunk_count: u32,
+    /// Embeddings model of its chunks; another model makes it unsearchable
+    /// until it is indexed again
+    pub model: String,
+    pub indexed_at: i64,
+}
+
+/// A chunk of a document with its vector, as stored.
+#[derive(Debug, Clone)]
+pub struct KnowledgeChunk {
+    pub document_id: String,
+    pub chunk_index: u32,
+    pub workspace_id: String,
+    pub content: String,
+    pub vector: Vec<f32>,
+    pub model: String,
+}
+
+/// A chunk a search found, as read for its hit.
+#[derive(Debug, Clone)]
+pub struct FoundChunk {
+    pub document_id: String,
+    pub chunk_index: u32,
+    pub path: String,
+    pub title: String,
+    pub content: String,
+}
+
+/// A chunk found by `search_knowledge`, or given to a turn.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct KnowledgeHit {
+    pub document_id: String,
+    pub chunk_index: u32,
+    pub path: String,
+    pub title: String,
+    pub content: String,
+    /// Cosine similarity to the query
+    pub score: f32,
+}
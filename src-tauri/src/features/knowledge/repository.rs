@@ -0,0 +1,377 @@
+use super::models::{FoundChunk, KnowledgeChunk, KnowledgeDocument};
+use crate::error::AppError;
+use crate::features::semantic_index::vector;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait KnowledgeRepository: Send + Sync {
+    /// Store `document` with `chunks` in place of what it had before.
+    fn save_document(
+        &self,
+        document: &KnowledgeDocument,
+        chunks: &[KnowledgeChunk],
+    ) -> Result<(), AppError>;
+    fn find_document(
+        &self,
+        workspace_id: &str,
+        path: &str,
+    ) -> Result<Option<KnowledgeDocument>, AppError>;
+    fn documents(&self, workspace_id: &str) -> Result<Vec<KnowledgeDocument>, AppError>;
+    fn delete_document(&self, id: &str) -> Result<(), AppError>;
+    fn has_documents(&self, workspace_id: &str) -> Result<bool, AppError>;
+    /// Stored vectors of the workspace's chunks made with `model`, keyed by
+    /// document and chunk index.
+    fn vectors(
+        &self,
+        workspace_id: &str,
+        model: &str,
+    ) -> Result<Vec<((String, u32), Vec<f32>)>, AppError>;
+    fn chunks(&self, keys: &[(String, u32)]) -> Result<Vec<FoundChunk>, AppError>;
+}
+
+pub struct SqliteKnowledgeRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteKnowledgeRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl KnowledgeRepository for SqliteKnowledgeRepository {
+    fn save_document(
+        &self,
+        document: &KnowledgeDocument,
+        chunks: &[KnowledgeChunk],
+    ) -> Result<(), AppError> {
+        let mut conn = crate::db::get_connection(&self.app)?;
+        save_document(&mut conn, document, chunks)
+    }
+
+    fn find_document(
+        &self,
+        workspace_id: &str,
+        path: &str,
+    ) -> Result<Option<KnowledgeDocument>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        find_document(&conn, workspace_id, path)
+    }
+
+    fn documents(&self, workspace_id: &str) -> Result<Vec<KnowledgeDocument>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        workspace_documents(&conn, workspace_id)
+    }
+
+    fn delete_document(&self, id: &str) -> Result<(), AppError> {
+        let mut conn = crate::db::get_connection(&self.app)?;
+        delete_document(&mut conn, id)
+    }
+
+    fn has_documents(&self, workspace_id: &str) -> Result<bool, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        Ok(conn.query_row(
+            "SELECT EXISTS (SELECT 1 FROM knowledge_documents WHERE workspace_id = ?1)",
+            params![workspace_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    fn vectors(
+        &self,
+        workspace_id: &str,
+        model: &str,
+    ) -> Result<Vec<((String, u32), Vec<f32>)>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        workspace_vectors(&conn, workspace_id, model)
+    }
+
+    fn chunks(&self, keys: &[(String, u32)]) -> Result<Vec<FoundChunk>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        found_chunks(&conn, keys)
+    }
+}
+
+const DOCUMENT_COLUMNS: &str =
+    "id, workspace_id, path, title, size_bytes, chunk_count, model, indexed_at";
+
+fn document_from_row(row: &Row) -> rusqlite::Result<KnowledgeDocument> {
+    Ok(KnowledgeDocument {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        path: row.get(2)?,
+        title: row.get(3)?,
+        size_bytes: u64::try_from(row.get::<_, i64>(4)?).unwrap_or_default(),
+        chunk_count: u32::try_from(row.get::<_, i64>(5)?).unwrap_or_default(),
+        model: row.get(6)?,
+        indexed_at: row.get(7)?,
+    })
+}
+
+pub fn save_document(
+    conn: &mut Connection,
+    document: &KnowledgeDocument,
+    chunks: &[KnowledgeChunk],
+) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM knowledge_chunks WHERE document_id = ?1",
+        params![document.id],
+    )?;
+    tx.execute(
+        "INSERT INTO knowledge_documents (id, workspace_id, path, title, size_bytes, chunk_count, model, indexed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+         ON CONFLICT(id) DO UPDATE SET title = ?4, size_bytes = ?5, chunk_count = ?6, model = ?7, indexed_at = ?8",
+        params![
+            document.id,
+            document.workspace_id,
+            document.path,
+            document.title,
+            i64::try_from(document.size_bytes).unwrap_or(i64::MAX),
+            document.chunk_count,
+            document.model,
+            document.indexed_at
+        ],
+    )?;
+    for chunk in chunks {
+        tx.execute(
+            "INSERT INTO knowledge_chunks (document_id, chunk_index, workspace_id, content, vector, model)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                chunk.document_id,
+                chunk.chunk_index,
+                chunk.workspace_id,
+                chunk.content,
+                vector::to_blob(&chunk.vector),
+                chunk.model
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn find_document(
+    conn: &Connection,
+    workspace_id: &str,
+    path: &str,
+) -> Result<Option<KnowledgeDocument>, AppError> {
+    Ok(conn
+        .query_row(
+            &format!(
+                "SELECT {DOCUMENT_COLUMNS} FROM knowledge_documents WHERE workspace_id = ?1 AND path = ?2"
+            ),
+            params![workspace_id, path],
+            document_from_row,
+        )
+        .optional()?)
+}
+
+/// The workspace's documents in path order.
+pub fn workspace_documents(
+    conn: &Connection,
+    workspace_id: &str,
+) -> Result<Vec<KnowledgeDocument>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {DOCUMENT_COLUMNS} FROM knowledge_documents WHERE workspace_id = ?1 ORDER BY path"
+    ))?;
+    let documents = stmt
+        .query_map(params![workspace_id], document_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(documents)
+}
+
+pub fn delete_document(conn: &mut Connection, id: &str) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+    tx.execute(
+        "DELETE FROM knowledge_chunks WHERE document_id = ?1",
+        params![id],
+    )?;
+    let deleted = tx.execute("DELETE FROM knowledge_documents WHERE id = ?1", params![id])?;
+    if deleted == 0 {
+        return Err(AppError::NotFound(format!("Document not found: {id}")));
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub fn workspace_vectors(
+    conn: &Connection,
+    workspace_id: &str,
+    model: &str,
+) -> Result<Vec<((String, u32), Vec<f32>)>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT document_id, chunk_index, vector FROM knowledge_chunks
+         WHERE workspace_id = ?1 AND model = ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![workspace_id, model], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, u32>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(document_id, index, blob)| {
+            Some(((document_id, index), vector::from_blob(&blob)?))
+        })
+        .collect())
+}
+
+pub fn found_chunks(
+    conn: &Connection,
+    keys: &[(String, u32)],
+) -> Result<Vec<FoundChunk>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT c.document_id, c.chunk_index, d.path, d.title, c.content
+         FROM knowledge_chunks c JOIN knowledge_documents d ON d.id = c.document_id
+         WHERE c.document_id = ?1 AND c.chunk_index = ?2",
+    )?;
+    let mut chunks = Vec::with_capacity(keys.len());
+    for (document_id, chunk_index) in keys {
+        let chunk = stmt
+            .query_row(params![document_id, chunk_index], |row| {
+                Ok(FoundChunk {
+                    document_id: row.get(0)?,
+                    chunk_index: row.get(1)?,
+                    path: row.get(2)?,
+                    title: row.get(3)?,
+                    content: row.get(4)?,
+                })
+            })
+            .optional()?;
+        chunks.extend(chunk);
+    }
+    Ok(chunks)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+
+    #[test]
+    fn documents_are_chunked_retrieved_and_cited() {
+        use crate::features::knowledge::chunker::chunk_text;
+        use crate::features::knowledge::context::{citations_metadata, knowledge_prompt};
+        use crate::features::knowledge::documents::collect_files;
+        use crate::features::knowledge::repository::{
+            delete_document, find_document, found_chunks, save_document, workspace_documents,
+            workspace_vectors,
+        };
+        use crate::features::knowledge::{KnowledgeChunk, KnowledgeDocument, KnowledgeHit};
+
+        // Paragraphs stay together while they fit; a long one is cut at a space
+        let text = "First paragraph.\r\n\r\nSecond one.\n\nthree four five six seven eight nine ten eleven";
+        assert_eq!(
+            chunk_text(text, 30),
+            [
+                "First paragraph.\n\nSecond one.",
+                "three four five six seven",
+                "eight nine ten eleven"
+            ]
+        );
+        assert!(chunk_text(" \n\n ", 30).is_empty());
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("docs")).unwrap();
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        std::fs::write(dir.path().join("docs/guide.md"), "Guide").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "Notes").unwrap();
+        std::fs::write(dir.path().join("logo.png"), [0u8, 1, 2]).unwrap();
+        std::fs::write(dir.path().join(".git/config.toml"), "x").unwrap();
+        assert_eq!(
+            collect_files(dir.path()).unwrap(),
+            [
+                dir.path().join("docs/guide.md"),
+                dir.path().join("notes.txt")
+            ]
+        );
+
+        let hit = KnowledgeHit {
+            document_id: "d1".to_string(),
+            chunk_index: 2,
+            path: "/docs/guide.md".to_string(),
+            title: "guide.md".to_string(),
+            content: " Deploy with make release. ".to_string(),
+            score: 0.5,
+        };
+        assert_eq!(knowledge_prompt(&[]), None);
+        assert!(knowledge_prompt(std::slice::from_ref(&hit))
+            .unwrap()
+            .ends_with("\n\n[1] guide.md (/docs/guide.md)\nDeploy with make release."));
+        assert_eq!(
+            citations_metadata(&[hit]),
+            Some(serde_json::json!([{
+                "number": 1,
+                "documentId": "d1",
+                "chunkIndex": 2,
+                "path": "/docs/guide.md",
+                "title": "guide.md",
+                "score": 0.5,
+            }]))
+        );
+
+        let mut conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0)",
+            [],
+        )
+        .unwrap();
+        let document = KnowledgeDocument {
+            id: "d1".to_string(),
+            workspace_id: "w1".to_string(),
+            path: "/docs/guide.md".to_string(),
+            title: "guide.md".to_string(),
+            size_bytes: 42,
+            chunk_count: 2,
+            model: "local-hash-256".to_string(),
+            indexed_at: 1,
+        };
+        let chunk = |index: u32, content: &str| KnowledgeChunk {
+            document_id: "d1".to_string(),
+            chunk_index: index,
+            workspace_id: "w1".to_string(),
+            content: content.to_string(),
+            vector: vec![1.0, 0.0],
+            model: "local-hash-256".to_string(),
+        };
+        save_document(&mut conn, &document, &[chunk(0, "Old"), chunk(1, "Older")]).unwrap();
+
+        // Indexing the file again replaces its chunks
+        let reindexed = KnowledgeDocument {
+            chunk_count: 1,
+            indexed_at: 2,
+            ..document
+        };
+        save_document(&mut conn, &reindexed, &[chunk(0, "New")]).unwrap();
+        assert_eq!(
+            find_document(&conn, "w1", "/docs/guide.md").unwrap(),
+            Some(reindexed.clone())
+        );
+        assert_eq!(workspace_documents(&conn, "w1").unwrap(), [reindexed]);
+        let vectors = workspace_vectors(&conn, "w1", "local-hash-256").unwrap();
+        assert_eq!(vectors, [(("d1".to_string(), 0), vec![1.0, 0.0])]);
+        assert!(workspace_vectors(&conn, "w1", "other-model")
+            .unwrap()
+            .is_empty());
+        let found = found_chunks(&conn, &[("d1".to_string(), 0), ("d1".to_string(), 1)]).unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].content, "New");
+        assert_eq!(found[0].title, "guide.md");
+
+        delete_document(&mut conn, "d1").unwrap();
+        assert!(workspace_documents(&conn, "w1").unwrap().is_empty());
+        assert!(workspace_vectors(&conn, "w1", "local-hash-256")
+            .unwrap()
+            .is_empty());
+        assert!(matches!(
+            delete_document(&mut conn, "d1"),
+            Err(AppError::NotFound(_))
+        ));
+    }
+}
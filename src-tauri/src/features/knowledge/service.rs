@@ -0,0 +1,235 @@
+use super::chunker::{self, CHUNK_CHARS};
+use super::documents;
+use super::models::{KnowledgeChunk, KnowledgeDocument, KnowledgeHit};
+use super::repository::KnowledgeRepository;
+use crate::error::AppError;
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::llm_connection::LLMConnectionService;
+use crate::features::semantic_index::embedder::{self, Embedder};
+use crate::features::semantic_index::indexer::{BATCH_SIZE, MIN_SIMILARITY};
+use crate::features::semantic_index::vector;
+use crate::services::LLMService;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Chunks a turn is given, at most
+pub const TURN_CHUNKS: usize = 4;
+const DEFAULT_SEARCH_LIMIT: u32 = 10;
+const MAX_SEARCH_LIMIT: u32 = 50;
+
+pub struct KnowledgeService {
+    repository: Arc<dyn KnowledgeRepository>,
+    app_settings_service: Arc<AppSettingsService>,
+    llm_connection_service: Arc<LLMConnectionService>,
+    llm_service: Arc<LLMService>,
+}
+
+impl KnowledgeService {
+    pub fn new(
+        repository: Arc<dyn KnowledgeRepository>,
+        app_settings_service: Arc<AppSettingsService>,
+        llm_connection_service: Arc<LLMConnectionService>,
+        llm_service: Arc<LLMService>,
+    ) -> Self {
+        Self {
+            repository,
+            app_settings_service,
+            llm_connection_service,
+            llm_service,
+        }
+    }
+
+    fn embedder(&self) -> Result<Box<dyn Embedder>, AppError> {
+        embedder::configured(
+            &self.app_settings_service,
+            &self.llm_connection_service,
+            &self.llm_service,
+        )
+    }
+
+    /// Index the file at `path`, or the text files of the folder, for the
+    /// workspace. Files indexed before are read again. In a folder, files
+    /// that cannot be read or embedded are skipped; a file attached on its
+    /// own fails instead.
+    pub async fn index_path(
+        &self,
+        workspace_id: &str,
+        path: &str,
+    ) -> Result<Vec<KnowledgeDocument>, AppError> {
+        let root = std::fs::canonicalize(path)
+            .map_err(|e| AppError::Validation(format!("Cannot access {path}: {e}")))?;
+        let single_file = root.is_file();
+        let files = documents::collect_files(&root)?;
+        if files.is_empty() {
+            return Err(AppError::Validation(format!(
+                "No text files to index in {path}"
+            )));
+        }
+
+        let embedder = self.embedder()?;
+        let mut indexed = Vec::new();
+        let mut last_error = None;
+        for file in &files {
+            match self.index_file(workspace_id, file, embedder.as_ref()).await {
+                Ok(Some(document)) => indexed.push(document),
+                Ok(None) if single_file => {
+                    return Err(AppError::Validation(format!(
+                        "{path} is not a text file or is too large"
+                    )));
+                }
+                Ok(None) => {}
+                Err(e) if single_file => return Err(e),
+                Err(e) => {
+                    tracing::warn!(path = %file.display(), error = %e, "Failed to index document, skipped");
+                    last_error = Some(e);
+                }
+            }
+        }
+        match last_error {
+            Some(e) if indexed.is_empty() => Err(e),
+            _ => Ok(indexed),
+        }
+    }
+
+    /// Chunk and embed one file. `None` when it is not text or has none.
+    async fn index_file(
+        &self,
+        workspace_id: &str,
+        file: &Path,
+        embedder: &dyn Embedder,
+    ) -> Result<Option<KnowledgeDocument>, AppError> {
+        let Some(text) = documents::read_text(file)? else {
+            return Ok(None);
+        };
+        let contents = chunker::chunk_text(&text, CHUNK_CHARS);
+        if contents.is_empty() {
+            return Ok(None);
+        }
+
+        let mut vectors = Vec::with_capacity(contents.len());
+        for batch in contents.chunks(BATCH_SIZE as usize) {
+            let batch_vectors = embedder.embed(batch).await?;
+            if batch_vectors.len() != batch.len() {
+                return Err(AppError::Llm(format!(
+                    "Model {} returned {} embeddings for {} chunks",
+                    embedder.model(),
+                    batch_vectors.len(),
+                    batch.len()
+                )));
+            }
+            vectors.extend(batch_vectors);
+        }
+
+        let path = file.to_string_lossy().to_string();
+        let id = self
+            .repository
+            .find_document(workspace_id, &path)?
+            .map_or_else(|| uuid::Uuid::new_v4().to_string(), |existing| existing.id);
+        let model = embedder.model().to_string();
+        let chunks: Vec<KnowledgeChunk> = (0..)
+            .zip(contents.into_iter().zip(vectors))
+            .map(|(chunk_index, (content, vector))| KnowledgeChunk {
+                document_id: id.clone(),
+                chunk_index,
+                workspace_id: workspace_id.to_string(),
+                content,
+                vector,
+                model: model.clone(),
+            })
+            .collect();
+        let document = KnowledgeDocument {
+            id,
+            workspace_id: workspace_id.to_string(),
+            title: file
+                .file_name()
+                .map_or_else(|| path.clone(), |name| name.to_string_lossy().to_string()),
+            path,
+            size_bytes: text.len() as u64,
+            chunk_count: u32::try_from(chunks.len()).unwrap_or(u32::MAX),
+            model,
+            indexed_at: chrono::Utc::now().timestamp_millis(),
+        };
+        self.repository.save_document(&document, &chunks)?;
+        Ok(Some(document))
+    }
+
+    pub fn list_documents(&self, workspace_id: &str) -> Result<Vec<KnowledgeDocument>, AppError> {
+        self.repository.documents(workspace_id)
+    }
+
+    pub fn delete_document(&self, id: &str) -> Result<(), AppError> {
+        self.repository.delete_document(id)
+    }
+
+    /// Chunks of the workspace's documents closest in meaning to `query`,
+    /// best first.
+    pub async fn search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<KnowledgeHit>, AppError> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let limit = limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        self.closest_chunks(workspace_id, query, limit as usize)
+            .await
+    }
+
+    /// The chunks a turn in the workspace is given for `message`: none
+    /// when the workspace has no documents.
+    pub async fn retrieve(
+        &self,
+        workspace_id: &str,
+        message: &str,
+    ) -> Result<Vec<KnowledgeHit>, AppError> {
+        if message.trim().is_empty() || !self.repository.has_documents(workspace_id)? {
+            return Ok(Vec::new());
+        }
+        self.closest_chunks(workspace_id, message.trim(), TURN_CHUNKS)
+            .await
+    }
+
+    async fn closest_chunks(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        limit: usize,
+    ) -> Result<Vec<KnowledgeHit>, AppError> {
+        let embedder = self.embedder()?;
+        let Some(query_vector) = embedder.embed(&[query.to_string()]).await?.pop() else {
+            return Ok(Vec::new());
+        };
+        let stored = self.repository.vectors(workspace_id, embedder.model())?;
+        let closest: Vec<((String, u32), f32)> = vector::top_k(&query_vector, stored, limit)
+            .into_iter()
+            .filter(|(_, similarity)| *similarity >= MIN_SIMILARITY)
+            .collect();
+        let keys: Vec<(String, u32)> = closest.iter().map(|(key, _)| key.clone()).collect();
+        let mut found: HashMap<(String, u32), _> = self
+            .repository
+            .chunks(&keys)?
+            .into_iter()
+            .map(|chunk| ((chunk.document_id.clone(), chunk.chunk_index), chunk))
+            .collect();
+        Ok(closest
+            .into_iter()
+            .filter_map(|(key, score)| {
+                let chunk = found.remove(&key)?;
+                Some(KnowledgeHit {
+                    document_id: chunk.document_id,
+                    chunk_index: chunk.chunk_index,
+                    path: chunk.path,
+                    title: chunk.title,
+                    content: chunk.content,
+                    score,
+                })
+            })
+            .collect())
+    }
+}
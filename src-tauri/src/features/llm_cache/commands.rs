@@ -0,0 +1,20 @@
+use super::models::LLMCacheStats;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+#[tauri::command]
+pub fn get_llm_cache_stats(state: State<'_, AppState>) -> Result<LLMCacheStats, AppError> {
+    state
+        .llm_cache_service
+        .get_stats()
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
+
+#[tauri::command]
+pub fn clear_llm_cache(state: State<'_, AppState>) -> Result<(), AppError> {
+    state
+        .llm_cache_service
+        .clear()
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
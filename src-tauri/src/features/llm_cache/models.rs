@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LLMCacheEntry {
+    pub cache_key: String,
+    pub provider: String,
+    pub model: String,
+    pub response: String, // Serialized LLMChatResponse
+    pub size_bytes: i64,
+    pub created_at: i64,
+    pub last_accessed_at: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LLMCacheStats {
+    pub entries: u32,
+    pub total_bytes: i64,
+    pub hits: u64,   // Since app start
+    pub misses: u64, // Since app start
+}
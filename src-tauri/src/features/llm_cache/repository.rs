@@ -0,0 +1,134 @@
+use super::models::LLMCacheEntry;
+use crate::error::AppError;
+use rusqlite::params;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait LLMCacheRepository: Send + Sync {
+    fn get(&self, cache_key: &str) -> Result<Option<LLMCacheEntry>, AppError>;
+    fn save(&self, entry: &LLMCacheEntry) -> Result<(), AppError>;
+    fn touch(&self, cache_key: &str, accessed_at: i64) -> Result<(), AppError>;
+    fn delete(&self, cache_key: &str) -> Result<(), AppError>;
+    fn delete_expired(&self, now: i64) -> Result<(), AppError>;
+    /// Delete least recently used entries until at most `max_entries` entries
+    /// totalling at most `max_bytes` remain.
+    fn evict_lru(&self, max_entries: i64, max_bytes: i64) -> Result<(), AppError>;
+    fn get_totals(&self) -> Result<(u32, i64), AppError>;
+    fn delete_all(&self) -> Result<(), AppError>;
+}
+
+pub struct SqliteLLMCacheRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteLLMCacheRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl LLMCacheRepository for SqliteLLMCacheRepository {
+    fn get(&self, cache_key: &str) -> Result<Option<LLMCacheEntry>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let result = conn.query_row(
+            "SELECT cache_key, provider, model, response, size_bytes, created_at, last_accessed_at, expires_at FROM llm_response_cache WHERE cache_key = ?1",
+            params![cache_key],
+            |row| {
+                Ok(LLMCacheEntry {
+                    cache_key: row.get(0)?,
+                    provider: row.get(1)?,
+                    model: row.get(2)?,
+                    response: row.get(3)?,
+                    size_bytes: row.get(4)?,
+                    created_at: row.get(5)?,
+                    last_accessed_at: row.get(6)?,
+                    expires_at: row.get(7)?,
+                })
+            },
+        );
+
+        match result {
+            Ok(entry) => Ok(Some(entry)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn save(&self, entry: &LLMCacheEntry) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "INSERT OR REPLACE INTO llm_response_cache (cache_key, provider, model, response, size_bytes, created_at, last_accessed_at, expires_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.cache_key,
+                entry.provider,
+                entry.model,
+                entry.response,
+                entry.size_bytes,
+                entry.created_at,
+                entry.last_accessed_at,
+                entry.expires_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn touch(&self, cache_key: &str, accessed_at: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE llm_response_cache SET last_accessed_at = ?1 WHERE cache_key = ?2",
+            params![accessed_at, cache_key],
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self, cache_key: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "DELETE FROM llm_response_cache WHERE cache_key = ?1",
+            params![cache_key],
+        )?;
+        Ok(())
+    }
+
+    fn delete_expired(&self, now: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "DELETE FROM llm_response_cache WHERE expires_at <= ?1",
+            params![now],
+        )?;
+        Ok(())
+    }
+
+    fn evict_lru(&self, max_entries: i64, max_bytes: i64) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        // Keep the most recently used rows whose running size stays under the cap
+        conn.execute(
+            "DELETE FROM llm_response_cache WHERE cache_key NOT IN (
+                SELECT cache_key FROM (
+                    SELECT cache_key,
+                        ROW_NUMBER() OVER (ORDER BY last_accessed_at DESC) AS rank,
+                        SUM(size_bytes) OVER (ORDER BY last_accessed_at DESC ROWS UNBOUNDED PRECEDING) AS running_bytes
+                    FROM llm_response_cache
+                ) WHERE rank <= ?1 AND running_bytes <= ?2
+            )",
+            params![max_entries, max_bytes],
+        )?;
+        Ok(())
+    }
+
+    fn get_totals(&self) -> Result<(u32, i64), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let totals = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(size_bytes), 0) FROM llm_response_cache",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        Ok(totals)
+    }
+
+    fn delete_all(&self) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute("DELETE FROM llm_response_cache", [])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,377 @@
+use super::models::{LLMCacheEntry, LLMCacheStats};
+use super::repository::LLMCacheRepository;
+use crate::error::AppError;
+use crate::models::llm_types::{LLMChatRequest, LLMChatResponse};
+use sha2::{Digest, Sha256};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// App setting switching the response cache on: `true`, off otherwise.
+pub const LLM_CACHE_ENABLED: &str = "llmCacheEnabled";
+/// Cached utility responses are reused for a week.
+pub const LLM_CACHE_TTL_MS: i64 = 7 * 24 * 60 * 60 * 1000;
+const LLM_CACHE_MAX_ENTRIES: i64 = 500;
+const LLM_CACHE_MAX_BYTES: i64 = 5 * 1024 * 1024;
+/// Above this temperature responses are not deterministic enough to reuse.
+const LLM_CACHE_MAX_TEMPERATURE: f64 = 0.3;
+
+pub struct LLMCacheService {
+    repository: Arc<dyn LLMCacheRepository>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+impl LLMCacheService {
+    pub fn new(repository: Arc<dyn LLMCacheRepository>) -> Self {
+        Self {
+            repository,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Only non-streaming, low-temperature requests are eligible.
+    pub fn is_cacheable(request: &LLMChatRequest) -> bool {
+        !request.stream
+            && request
+                .temperature
+                .is_some_and(|t| t <= LLM_CACHE_MAX_TEMPERATURE)
+    }
+
+    /// Hash of (provider, endpoint, model, serialized messages, `max_tokens`,
+    /// the sampling parameters besides the temperature, the tools and
+    /// reasoning effort, and the seed). Two connections of one provider may
+    /// serve different models under the same name, so the endpoint is part
+    /// of the key.
+    pub fn cache_key(
+        provider: &str,
+        base_url: &str,
+        request: &LLMChatRequest,
+    ) -> Result<String, AppError> {
+        let key_source = serde_json::to_string(&serde_json::json!([
+            provider.to_lowercase(),
+            base_url.trim_end_matches('/'),
+            request.model,
+            request.messages,
+            request.max_tokens,
+            request.top_p,
+            request.frequency_penalty,
+            request.presence_penalty,
+            request.tools,
+            request.tool_choice,
+            request.reasoning_effort,
+            request.seed,
+        ]))?;
+
+        let mut hasher = Sha256::new();
+        hasher.update(key_source.as_bytes());
+        Ok(hex::encode(hasher.finalize()))
+    }
+
+    pub fn get(&self, cache_key: &str) -> Result<Option<LLMChatResponse>, AppError> {
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let entry = match self.repository.get(cache_key)? {
+            Some(entry) if entry.expires_at > now => entry,
+            Some(_) => {
+                self.repository.delete(cache_key)?;
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+            None => {
+                self.misses.fetch_add(1, Ordering::Relaxed);
+                return Ok(None);
+            }
+        };
+
+        // Unreadable entries (e.g. from an older response format) count as misses
+        let Ok(response) = serde_json::from_str::<LLMChatResponse>(&entry.response) else {
+            self.repository.delete(cache_key)?;
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            return Ok(None);
+        };
+
+        self.repository.touch(cache_key, now)?;
+        self.hits.fetch_add(1, Ordering::Relaxed);
+        Ok(Some(response))
+    }
+
+    pub fn put(
+        &self,
+        cache_key: &str,
+        provider: &str,
+        model: &str,
+        response: &LLMChatResponse,
+    ) -> Result<(), AppError> {
+        let now = chrono::Utc::now().timestamp_millis();
+        let serialized = serde_json::to_string(response)?;
+
+        self.repository.save(&LLMCacheEntry {
+            cache_key: cache_key.to_string(),
+            provider: provider.to_string(),
+            model: model.to_string(),
+            size_bytes: i64::try_from(serialized.len()).unwrap_or(i64::MAX),
+            response: serialized,
+            created_at: now,
+            last_accessed_at: now,
+            expires_at: now + LLM_CACHE_TTL_MS,
+        })?;
+
+        self.repository.delete_expired(now)?;
+        self.repository
+            .evict_lru(LLM_CACHE_MAX_ENTRIES, LLM_CACHE_MAX_BYTES)
+    }
+
+    pub fn get_stats(&self) -> Result<LLMCacheStats, AppError> {
+        let (entries, total_bytes) = self.repository.get_totals()?;
+        Ok(LLMCacheStats {
+            entries,
+            total_bytes,
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+        })
+    }
+
+    pub fn clear(&self) -> Result<(), AppError> {
+        self.repository.delete_all()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::llm_types::LLMChatRequest;
+
+    /// A deterministic, non-streaming request of the kind the cache serves.
+    fn utility_request() -> LLMChatRequest {
+        use crate::models::llm_types::{ChatMessage, UserContent};
+
+        LLMChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage::User {
+                content: UserContent::Text("Hi".to_string()),
+            }],
+            temperature: Some(0.0),
+            max_tokens: Some(256),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: false,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed: None,
+            output_limits: None,
+        }
+    }
+
+    #[derive(Default)]
+    struct MemoryLLMCacheRepository(
+        std::sync::Mutex<
+            std::collections::HashMap<String, crate::features::llm_cache::models::LLMCacheEntry>,
+        >,
+    );
+
+    impl crate::features::llm_cache::LLMCacheRepository for MemoryLLMCacheRepository {
+        fn get(
+            &self,
+            cache_key: &str,
+        ) -> Result<Option<crate::features::llm_cache::models::LLMCacheEntry>, crate::error::AppError>
+        {
+            Ok(self.0.lock().unwrap().get(cache_key).cloned())
+        }
+
+        fn save(
+            &self,
+            entry: &crate::features::llm_cache::models::LLMCacheEntry,
+        ) -> Result<(), crate::error::AppError> {
+            self.0
+                .lock()
+                .unwrap()
+                .insert(entry.cache_key.clone(), entry.clone());
+            Ok(())
+        }
+
+        fn touch(&self, cache_key: &str, accessed_at: i64) -> Result<(), crate::error::AppError> {
+            if let Some(entry) = self.0.lock().unwrap().get_mut(cache_key) {
+                entry.last_accessed_at = accessed_at;
+            }
+            Ok(())
+        }
+
+        fn delete(&self, cache_key: &str) -> Result<(), crate::error::AppError> {
+            self.0.lock().unwrap().remove(cache_key);
+            Ok(())
+        }
+
+        fn delete_expired(&self, now: i64) -> Result<(), crate::error::AppError> {
+            self.0.lock().unwrap().retain(|_, e| e.expires_at > now);
+            Ok(())
+        }
+
+        fn evict_lru(&self, _: i64, _: i64) -> Result<(), crate::error::AppError> {
+            Ok(())
+        }
+
+        fn get_totals(&self) -> Result<(u32, i64), crate::error::AppError> {
+            let entries = self.0.lock().unwrap();
+            Ok((
+                u32::try_from(entries.len()).unwrap(),
+                entries.values().map(|e| e.size_bytes).sum(),
+            ))
+        }
+
+        fn delete_all(&self) -> Result<(), crate::error::AppError> {
+            self.0.lock().unwrap().clear();
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn llm_cache_key_covers_endpoint_tools_and_effort() {
+        use crate::features::llm_cache::LLMCacheService;
+        use crate::models::llm_types::{
+            ChatCompletionTool, ChatCompletionToolFunction, ToolChoice,
+        };
+
+        let base = utility_request();
+        let key = |provider: &str, base_url: &str, request: &LLMChatRequest| {
+            LLMCacheService::cache_key(provider, base_url, request).unwrap()
+        };
+        let openai = "https://api.openai.com/v1";
+        let reference = key("openai", openai, &base);
+        assert_eq!(
+            key("OpenAI", "https://api.openai.com/v1/", &base),
+            reference
+        );
+
+        let tools = Some(vec![ChatCompletionTool {
+            r#type: "function".to_string(),
+            function: ChatCompletionToolFunction {
+                name: "read_file".to_string(),
+                description: None,
+                parameters: None,
+            },
+        }]);
+        let with_tools = LLMChatRequest {
+            tools: tools.clone(),
+            ..utility_request()
+        };
+        let variants = [
+            key("openai", "http://localhost:8080/v1", &base),
+            key("openai", openai, &with_tools),
+            key(
+                "openai",
+                openai,
+                &LLMChatRequest {
+                    tools,
+                    tool_choice: Some(ToolChoice::String("none".to_string())),
+                    ..utility_request()
+                },
+            ),
+            key(
+                "openai",
+                openai,
+                &LLMChatRequest {
+                    reasoning_effort: Some("high".to_string()),
+                    ..utility_request()
+                },
+            ),
+            key(
+                "openai",
+                openai,
+                &LLMChatRequest {
+                    max_tokens: Some(1024),
+                    ..utility_request()
+                },
+            ),
+            key(
+                "openai",
+                openai,
+                &LLMChatRequest {
+                    seed: Some(42),
+                    ..utility_request()
+                },
+            ),
+            key(
+                "openai",
+                openai,
+                &LLMChatRequest {
+                    top_p: Some(0.5),
+                    ..utility_request()
+                },
+            ),
+        ];
+        for variant in &variants {
+            assert_ne!(variant, &reference);
+        }
+        assert_ne!(variants[1], variants[2]);
+    }
+
+    #[test]
+    fn llm_cache_hits_misses_expires_and_skips_streams() {
+        use crate::features::llm_cache::{LLMCacheRepository, LLMCacheService};
+        use crate::models::llm_types::LLMChatResponse;
+
+        let request = utility_request();
+        // Streaming and sampled requests never reach the cache
+        assert!(LLMCacheService::is_cacheable(&request));
+        assert!(!LLMCacheService::is_cacheable(&LLMChatRequest {
+            stream: true,
+            ..utility_request()
+        }));
+        assert!(!LLMCacheService::is_cacheable(&LLMChatRequest {
+            temperature: Some(0.7),
+            ..utility_request()
+        }));
+        assert!(!LLMCacheService::is_cacheable(&LLMChatRequest {
+            temperature: None,
+            ..utility_request()
+        }));
+
+        let repository = std::sync::Arc::new(MemoryLLMCacheRepository::default());
+        let cache = LLMCacheService::new(repository.clone());
+        let key =
+            LLMCacheService::cache_key("openai", "https://api.openai.com/v1", &request).unwrap();
+        let response = LLMChatResponse {
+            content: "Weekly sync notes".to_string(),
+            finish_reason: Some("stop".to_string()),
+            tool_calls: None,
+            usage: None,
+            reasoning: None,
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        };
+
+        assert!(cache.get(&key).unwrap().is_none());
+        cache.put(&key, "openai", "gpt-4o", &response).unwrap();
+        let hit = cache.get(&key).unwrap().unwrap();
+        assert_eq!(hit.content, "Weekly sync notes");
+        let stats = cache.get_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        // Past its TTL the entry is a miss and is dropped
+        let mut entry = repository.get(&key).unwrap().unwrap();
+        entry.expires_at = chrono::Utc::now().timestamp_millis() - 1;
+        repository.save(&entry).unwrap();
+        assert!(cache.get(&key).unwrap().is_none());
+        assert!(repository.get(&key).unwrap().is_none());
+        let stats = cache.get_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 0));
+
+        // Unreadable entries are misses too
+        cache.put(&key, "openai", "gpt-4o", &response).unwrap();
+        let mut entry = repository.get(&key).unwrap().unwrap();
+        entry.response = "{\"old\": true}".to_string();
+        repository.save(&entry).unwrap();
+        assert!(cache.get(&key).unwrap().is_none());
+        assert!(repository.get(&key).unwrap().is_none());
+    }
+}
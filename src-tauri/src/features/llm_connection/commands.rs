@@ -1,7 +1,18 @@
+use super::model_filter::{self, FilteredModel, ModelFilter};
 use super::models::LLMConnection;
 use crate::error::AppError;
+use crate::events::{emit_event, OllamaPullProgressEvent};
+use crate::services::llm::providers::ollama::OllamaModelInfo;
+use crate::services::llm::rate_limit::RateLimitStatus;
 use crate::state::AppState;
-use tauri::State;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, State};
+
+/// Least time between two progress events of the same download step.
+const PULL_PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+/// Inputs `embed_texts` takes in one call
+const MAX_EMBED_INPUTS: usize = 256;
 
 #[tauri::command]
 #[allow(clippy::too_many_arguments)]
@@ -13,6 +24,16 @@ pub fn create_llm_connection(
     api_key: String,
     models_json: Option<String>,
     default_model: Option<String>,
+    keep_warm: Option<bool>,
+    system_prompt_suffix: Option<String>,
+    skip_redaction: Option<bool>,
+    model_allowlist: Option<String>,
+    model_blocklist: Option<String>,
+    config: Option<String>,
+    deployment_name: Option<String>,
+    api_version: Option<String>,
+    aws_region: Option<String>,
+    aws_profile: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<LLMConnection, AppError> {
     state
@@ -25,6 +46,16 @@ pub fn create_llm_connection(
             api_key,
             models_json,
             default_model,
+            keep_warm.unwrap_or(false),
+            system_prompt_suffix,
+            skip_redaction.unwrap_or(false),
+            model_allowlist,
+            model_blocklist,
+            config,
+            deployment_name,
+            api_version,
+            aws_region,
+            aws_profile,
         )
         .map_err(|e| AppError::Generic(e.to_string()))
 }
@@ -48,6 +79,16 @@ pub fn update_llm_connection(
     models_json: Option<String>,
     default_model: Option<String>,
     enabled: Option<bool>,
+    keep_warm: Option<bool>,
+    system_prompt_suffix: Option<String>,
+    skip_redaction: Option<bool>,
+    model_allowlist: Option<String>,
+    model_blocklist: Option<String>,
+    config: Option<String>,
+    deployment_name: Option<String>,
+    api_version: Option<String>,
+    aws_region: Option<String>,
+    aws_profile: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), AppError> {
     state
@@ -61,6 +102,16 @@ pub fn update_llm_connection(
             models_json,
             default_model,
             enabled,
+            keep_warm,
+            system_prompt_suffix,
+            skip_redaction,
+            model_allowlist,
+            model_blocklist,
+            config,
+            deployment_name,
+            api_version,
+            aws_region,
+            aws_profile,
         )
         .map_err(|e| AppError::Generic(e.to_string()))
 }
@@ -73,18 +124,263 @@ pub fn delete_llm_connection(id: String, state: State<'_, AppState>) -> Result<(
         .map_err(|e| AppError::Generic(e.to_string()))
 }
 
+/// Models offered by a server. The allow and block lists being edited, when
+/// given, are applied to the result. An Azure OpenAI connection offers its
+/// deployment, once it answered; a Bedrock connection the models of its
+/// region, signed with the keys of its profile.
 #[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn test_llm_connection(
     base_url: String,
     provider: String,
     api_key: Option<String>,
+    model_allowlist: Option<String>,
+    model_blocklist: Option<String>,
+    deployment_name: Option<String>,
+    api_version: Option<String>,
+    aws_region: Option<String>,
+    aws_profile: Option<String>,
     _state: State<'_, AppState>,
 ) -> Result<Vec<crate::models::llm_types::LLMModel>, AppError> {
+    use crate::services::llm::providers::{azure, bedrock};
     use crate::services::LLMService;
 
+    let filter = ModelFilter {
+        connection_name: base_url.clone(),
+        allow: model_filter::parse_list(model_allowlist.as_deref())?,
+        block: model_filter::parse_list(model_blocklist.as_deref())?,
+    };
     let llm_service = LLMService::new();
-    llm_service
-        .fetch_models(&base_url, api_key.as_deref(), &provider)
+    let models = if azure::is_azure(&provider) {
+        azure::validate_deployment(
+            &provider,
+            deployment_name.as_deref(),
+            api_version.as_deref(),
+        )?;
+        let api_version = api_version
+            .as_deref()
+            .map(str::trim)
+            .filter(|v| !v.is_empty());
+        vec![
+            llm_service
+                .check_azure_deployment(
+                    &base_url,
+                    api_key.as_deref(),
+                    deployment_name.as_deref().unwrap_or_default().trim(),
+                    api_version,
+                )
+                .await?,
+        ]
+    } else if bedrock::is_bedrock(&provider) {
+        bedrock::validate_settings(&provider, aws_region.as_deref())?;
+        llm_service
+            .fetch_bedrock_models(
+                aws_region.as_deref().unwrap_or_default().trim(),
+                aws_profile.as_deref(),
+            )
+            .await?
+    } else {
+        llm_service
+            .fetch_models(&base_url, api_key.as_deref(), &provider)
+            .await?
+    };
+    Ok(filter.apply(models).into_iter().map(|m| m.model).collect())
+}
+
+/// Models of a connection that its allow and block lists permit, from its
+/// saved model list, or from the server when none is saved.
+#[tauri::command]
+pub async fn get_effective_models(
+    connection_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<FilteredModel>, AppError> {
+    let connection = state
+        .llm_connection_service
+        .get_by_id(&connection_id)?
+        .ok_or_else(|| AppError::NotFound(format!("LLM connection not found: {connection_id}")))?;
+
+    let saved = connection
+        .models_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok());
+    let models = match saved {
+        Some(models) => models,
+        None => match connection.aws_region.as_deref() {
+            // Bedrock lists with the keys of the connection's profile
+            Some(region)
+                if crate::services::llm::providers::bedrock::is_bedrock(&connection.provider) =>
+            {
+                crate::services::LLMService::new()
+                    .fetch_bedrock_models(region, connection.aws_profile.as_deref())
+                    .await?
+            }
+            _ => {
+                crate::services::LLMService::new()
+                    .fetch_models(
+                        &connection.base_url,
+                        Some(&connection.api_key),
+                        &connection.provider,
+                    )
+                    .await?
+            }
+        },
+    };
+    Ok(ModelFilter::for_connection(&connection)
+        .unwrap_or_default()
+        .apply(models))
+}
+
+/// The last rate limit the connection's account ran into, if its chats have
+/// not succeeded since. `active` tells whether the provider's wait is still
+/// running.
+#[tauri::command]
+pub fn get_connection_rate_status(
+    connection_id: String,
+    state: State<'_, AppState>,
+) -> Result<Option<RateLimitStatus>, AppError> {
+    let connection = state
+        .llm_connection_service
+        .get_by_id(&connection_id)?
+        .ok_or_else(|| AppError::NotFound(format!("LLM connection not found: {connection_id}")))?;
+    Ok(state
+        .llm_service
+        .rate_limit_status(&connection.base_url, Some(&connection.api_key)))
+}
+
+/// Embed `inputs` with an embeddings model of the connection: one vector
+/// per input, in order, as the provider returns it.
+#[tauri::command]
+pub async fn embed_texts(
+    connection_id: String,
+    model: String,
+    inputs: Vec<String>,
+    state: State<'_, AppState>,
+) -> Result<Vec<Vec<f32>>, AppError> {
+    if model.trim().is_empty() {
+        return Err(AppError::Validation(
+            "An embeddings model is required".to_string(),
+        ));
+    }
+    if inputs.len() > MAX_EMBED_INPUTS {
+        return Err(AppError::Validation(format!(
+            "At most {MAX_EMBED_INPUTS} inputs can be embedded at once"
+        )));
+    }
+    if inputs.is_empty() {
+        return Ok(Vec::new());
+    }
+    let connection = state
+        .llm_connection_service
+        .get_by_id(&connection_id)?
+        .ok_or_else(|| AppError::NotFound(format!("LLM connection not found: {connection_id}")))?;
+    state
+        .llm_service
+        .embed(
+            &connection.base_url,
+            Some(&connection.api_key)
+                .filter(|key| !key.is_empty())
+                .map(String::as_str),
+            &connection.provider,
+            model.trim(),
+            &inputs,
+        )
+        .await
+}
+
+/// The connection, if it is an Ollama one.
+fn ollama_connection(state: &AppState, connection_id: &str) -> Result<LLMConnection, AppError> {
+    let connection = state
+        .llm_connection_service
+        .get_by_id(connection_id)?
+        .ok_or_else(|| AppError::NotFound(format!("LLM connection not found: {connection_id}")))?;
+    if !connection.provider.eq_ignore_ascii_case("ollama") {
+        return Err(AppError::Validation(format!(
+            "Connection {} is not an Ollama connection",
+            connection.name
+        )));
+    }
+    Ok(connection)
+}
+
+/// Capabilities and details of a model on an Ollama connection's server.
+#[tauri::command]
+pub async fn get_ollama_model_info(
+    connection_id: String,
+    model: String,
+    state: State<'_, AppState>,
+) -> Result<OllamaModelInfo, AppError> {
+    let connection = ollama_connection(&state, &connection_id)?;
+    state
+        .llm_service
+        .ollama_model_info(&connection.base_url, Some(&connection.api_key), &model)
         .await
-    // No map_err needed as fetch_models returns AppError
+}
+
+/// Download a model to an Ollama connection's server, reporting its progress
+/// as `OllamaPullProgressEvent`s. Returns once the download is done.
+#[tauri::command]
+pub async fn pull_ollama_model(
+    connection_id: String,
+    model: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    let connection = ollama_connection(&state, &connection_id)?;
+    let event = |status: String| OllamaPullProgressEvent {
+        connection_id: connection_id.clone(),
+        model: model.clone(),
+        status,
+        digest: None,
+        total: None,
+        completed: None,
+        done: false,
+        error: None,
+    };
+
+    // The server reports many times a second; a new step always gets through
+    let last_emit: Mutex<Option<(String, Instant)>> = Mutex::new(None);
+    let result = state
+        .llm_service
+        .pull_ollama_model(
+            &connection.base_url,
+            Some(&connection.api_key),
+            &model,
+            |progress| {
+                let step = format!(
+                    "{}|{}",
+                    progress.status,
+                    progress.digest.as_deref().unwrap_or("")
+                );
+                let mut last = last_emit
+                    .lock()
+                    .unwrap_or_else(std::sync::PoisonError::into_inner);
+                let due = last.as_ref().is_none_or(|(last_step, at)| {
+                    *last_step != step || at.elapsed() >= PULL_PROGRESS_INTERVAL
+                });
+                if !due || progress.is_success() {
+                    return;
+                }
+                *last = Some((step, Instant::now()));
+                let payload = OllamaPullProgressEvent {
+                    digest: progress.digest.clone(),
+                    total: progress.total,
+                    completed: progress.completed,
+                    ..event(progress.status.clone())
+                };
+                if let Err(e) = emit_event(&app, payload) {
+                    tracing::debug!(error = %e, "Failed to emit pull progress");
+                }
+            },
+        )
+        .await;
+
+    let last = match &result {
+        Ok(()) => event("success".to_string()),
+        Err(e) => OllamaPullProgressEvent {
+            error: Some(e.to_string()),
+            ..event("failed".to_string())
+        },
+    };
+    emit_event(&app, OllamaPullProgressEvent { done: true, ..last })?;
+    result
 }
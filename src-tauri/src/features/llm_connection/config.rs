@@ -0,0 +1,113 @@
+//! Provider options of a connection, stored as a JSON object.
+//!
+//! `{"thinking_budgets": {"low": 1024, "medium": 4096, "high": 16384}}` sets
+//! the extended thinking budget each reasoning effort maps to on Anthropic.
+//! `{"strict_tools": true}` sends tools to `OpenAI` with strict function
+//! calling, and `{"parallel_tool_calls": false}` asks for one call at a time.
+//! `{"prompt_caching": true}` marks the system prompt and tools of Anthropic
+//! requests for its prompt cache, as a workspace with context caching does.
+//! Keys left out keep their defaults.
+
+use super::models::LLMConnection;
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+
+/// Smallest thinking budget Anthropic accepts.
+pub const MIN_THINKING_BUDGET: u32 = 1024;
+/// Reasoning effort that turns thinking off.
+pub const REASONING_EFFORT_NONE: &str = "none";
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ConnectionConfig {
+    pub thinking_budgets: ThinkingBudgets,
+    /// Strict function calling for tools whose schema allows it
+    pub strict_tools: bool,
+    /// Sent as `parallel_tool_calls` when set
+    pub parallel_tool_calls: Option<bool>,
+    /// Cache the system prompt and tools of Anthropic requests
+    pub prompt_caching: bool,
+    /// Azure OpenAI's api-version, from the connection's own column
+    #[serde(skip)]
+    pub api_version: Option<String>,
+    /// AWS Bedrock's region, from the connection's own column
+    #[serde(skip)]
+    pub aws_region: Option<String>,
+    /// AWS Bedrock's credential profile, from the connection's own column
+    #[serde(skip)]
+    pub aws_profile: Option<String>,
+}
+
+/// Thinking tokens per reasoning effort.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ThinkingBudgets {
+    pub low: u32,
+    pub medium: u32,
+    pub high: u32,
+}
+
+impl Default for ThinkingBudgets {
+    fn default() -> Self {
+        Self {
+            low: 1024,
+            medium: 4096,
+            high: 16384,
+        }
+    }
+}
+
+impl ThinkingBudgets {
+    /// The budget of a reasoning effort, `None` when thinking is off. No
+    /// effort or an unknown one gets the medium budget.
+    pub fn for_effort(&self, effort: Option<&str>) -> Option<u32> {
+        let effort = effort.map(|e| e.trim().to_lowercase());
+        match effort.as_deref() {
+            Some(REASONING_EFFORT_NONE) => None,
+            Some("minimal" | "low") => Some(self.low),
+            Some("high" | "max" | "xhigh") => Some(self.high),
+            _ => Some(self.medium),
+        }
+    }
+}
+
+impl ConnectionConfig {
+    /// The config of a connection. A stored config that no longer parses is
+    /// ignored.
+    pub fn for_connection(connection: &LLMConnection) -> Self {
+        let config = parse_config(connection.config.as_deref())
+            .map_err(|e| {
+                tracing::warn!(connection_id = %connection.id, error = %e, "Ignoring invalid connection config");
+            })
+            .unwrap_or_default();
+        Self {
+            api_version: connection.api_version.clone(),
+            aws_region: connection.aws_region.clone(),
+            aws_profile: connection.aws_profile.clone(),
+            ..config
+        }
+    }
+}
+
+/// Parse a stored or submitted config; empty means the defaults.
+pub fn parse_config(raw: Option<&str>) -> Result<ConnectionConfig, AppError> {
+    let Some(raw) = raw.map(str::trim).filter(|r| !r.is_empty()) else {
+        return Ok(ConnectionConfig::default());
+    };
+    let config: ConnectionConfig = serde_json::from_str(raw)
+        .map_err(|e| AppError::Validation(format!("Invalid connection config: {e}")))?;
+
+    let budgets = config.thinking_budgets;
+    for (effort, budget) in [
+        ("low", budgets.low),
+        ("medium", budgets.medium),
+        ("high", budgets.high),
+    ] {
+        if budget < MIN_THINKING_BUDGET {
+            return Err(AppError::Validation(format!(
+                "Thinking budget for {effort} is {budget} tokens; the minimum is {MIN_THINKING_BUDGET}"
+            )));
+        }
+    }
+    Ok(config)
+}
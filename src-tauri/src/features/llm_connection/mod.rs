@@ -1,4 +1,6 @@
 pub mod commands;
+pub mod config;
+pub mod model_filter;
 pub mod models;
 pub mod repository;
 pub mod service;
@@ -0,0 +1,284 @@
+//! Connection-scoped model allow and block lists.
+//!
+//! Both lists hold model ids or simple globs (`*` for any run of characters,
+//! `?` for one), matched case-insensitively. A model matching the blocklist
+//! is never used, even if the allowlist also matches it. When the allowlist
+//! is not empty, only the models it matches are used.
+
+use super::models::LLMConnection;
+use crate::error::AppError;
+use crate::models::llm_types::LLMModel;
+use serde::{Deserialize, Serialize};
+
+/// Longest pattern accepted in a list.
+const MAX_PATTERN_CHARS: usize = 200;
+
+/// Parsed lists of one connection.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModelFilter {
+    /// Connection name, for error messages
+    pub connection_name: String,
+    pub allow: Vec<String>,
+    pub block: Vec<String>,
+}
+
+/// Which rule decided about a model.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(tag = "rule", content = "pattern", rename_all = "snake_case")]
+pub enum ModelMatch {
+    /// The connection has no allowlist and the blocklist does not match
+    Unrestricted,
+    /// Allowed by this allowlist pattern
+    Allowlisted(String),
+    /// Blocked by this blocklist pattern
+    Blocklisted(String),
+    /// The allowlist matches nothing
+    NotAllowlisted,
+}
+
+impl ModelMatch {
+    pub const fn is_permitted(&self) -> bool {
+        matches!(self, Self::Unrestricted | Self::Allowlisted(_))
+    }
+}
+
+/// A model of `get_effective_models`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilteredModel {
+    pub model: LLMModel,
+    pub matched: ModelMatch,
+}
+
+impl ModelFilter {
+    /// The filter of a connection, or `None` when both lists are empty. A
+    /// stored list that no longer parses is ignored.
+    pub fn for_connection(connection: &LLMConnection) -> Option<Self> {
+        let parse = |raw: Option<&str>, list: &str| {
+            parse_list(raw)
+                .map_err(|e| {
+                    tracing::warn!(connection_id = %connection.id, list = %list, error = %e, "Ignoring invalid model list");
+                })
+                .unwrap_or_default()
+        };
+        let filter = Self {
+            connection_name: connection.name.clone(),
+            allow: parse(connection.model_allowlist.as_deref(), "allowlist"),
+            block: parse(connection.model_blocklist.as_deref(), "blocklist"),
+        };
+        (!filter.allow.is_empty() || !filter.block.is_empty()).then_some(filter)
+    }
+
+    pub fn check(&self, model: &str) -> ModelMatch {
+        if let Some(pattern) = self.block.iter().find(|p| glob_matches(p, model)) {
+            return ModelMatch::Blocklisted(pattern.clone());
+        }
+        if self.allow.is_empty() {
+            return ModelMatch::Unrestricted;
+        }
+        self.allow
+            .iter()
+            .find(|p| glob_matches(p, model))
+            .map_or(ModelMatch::NotAllowlisted, |p| {
+                ModelMatch::Allowlisted(p.clone())
+            })
+    }
+
+    /// A validation error naming the list that rejects `model`.
+    pub fn ensure_permitted(&self, model: &str) -> Result<(), AppError> {
+        match self.check(model) {
+            ModelMatch::Blocklisted(pattern) => Err(AppError::Validation(format!(
+                "Model '{model}' is blocked by '{pattern}' in the model blocklist of connection '{}'",
+                self.connection_name
+            ))),
+            ModelMatch::NotAllowlisted => Err(AppError::Validation(format!(
+                "Model '{model}' is not in the model allowlist of connection '{}'",
+                self.connection_name
+            ))),
+            ModelMatch::Unrestricted | ModelMatch::Allowlisted(_) => Ok(()),
+        }
+    }
+
+    /// The permitted models of `models`, with the rule that let each through.
+    pub fn apply(&self, models: Vec<LLMModel>) -> Vec<FilteredModel> {
+        models
+            .into_iter()
+            .map(|model| FilteredModel {
+                matched: self.check(&model.id),
+                model,
+            })
+            .filter(|m| m.matched.is_permitted())
+            .collect()
+    }
+}
+
+/// Whether `model` may be used on `connection`.
+pub fn ensure_model_permitted(connection: &LLMConnection, model: &str) -> Result<(), AppError> {
+    ModelFilter::for_connection(connection).map_or(Ok(()), |f| f.ensure_permitted(model))
+}
+
+/// Parse a stored list: a JSON array of non-empty ids or patterns. Empty or
+/// missing means no list.
+pub fn parse_list(raw: Option<&str>) -> Result<Vec<String>, AppError> {
+    let Some(raw) = raw.filter(|r| !r.trim().is_empty()) else {
+        return Ok(Vec::new());
+    };
+    let list: Vec<String> = serde_json::from_str(raw).map_err(|e| {
+        AppError::Validation(format!("Model list must be a JSON array of strings: {e}"))
+    })?;
+    list.into_iter()
+        .map(|pattern| {
+            let pattern = pattern.trim().to_string();
+            if pattern.is_empty() {
+                return Err(AppError::Validation(
+                    "Model list contains an empty pattern".to_string(),
+                ));
+            }
+            if pattern.chars().count() > MAX_PATTERN_CHARS {
+                return Err(AppError::Validation(format!(
+                    "Model pattern is longer than {MAX_PATTERN_CHARS} characters"
+                )));
+            }
+            Ok(pattern)
+        })
+        .collect()
+}
+
+/// Match `text` against a glob of `*` and `?`, ignoring ASCII case.
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_ascii_lowercase().chars().collect();
+    let text: Vec<char> = text.to_ascii_lowercase().chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` and the text position it was tried at
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::connection;
+
+    fn filtered_connection(
+        allowlist: Option<&str>,
+        blocklist: Option<&str>,
+    ) -> crate::features::llm_connection::models::LLMConnection {
+        let mut connection = connection(
+            "router",
+            "openrouter",
+            "openai/gpt-4o",
+            serde_json::json!([]),
+        );
+        connection.name = "OpenRouter".to_string();
+        connection.model_allowlist = allowlist.map(str::to_string);
+        connection.model_blocklist = blocklist.map(str::to_string);
+        connection
+    }
+
+    #[test]
+    fn model_blocklist_wins_over_allowlist() {
+        use crate::features::llm_connection::model_filter::{
+            glob_matches, ModelFilter, ModelMatch,
+        };
+        use crate::models::llm_types::LLMModel;
+
+        assert!(glob_matches("gpt-4o*", "GPT-4o-mini"));
+        assert!(glob_matches("anthropic/*", "anthropic/claude-3.5-sonnet"));
+        assert!(glob_matches("llama-?b", "llama-8b"));
+        assert!(!glob_matches("gpt-4o*", "openai/gpt-4o"));
+        assert!(!glob_matches("gpt-4", "gpt-4o"));
+
+        let filter = ModelFilter::for_connection(&filtered_connection(
+            Some(r#"["openai/gpt-4o*", "anthropic/*"]"#),
+            Some(r#"["*-preview", "anthropic/claude-3-opus"]"#),
+        ))
+        .unwrap();
+        assert_eq!(
+            filter.check("openai/gpt-4o-mini"),
+            ModelMatch::Allowlisted("openai/gpt-4o*".to_string())
+        );
+        // Listed by both: the blocklist decides
+        assert_eq!(
+            filter.check("anthropic/claude-3-opus"),
+            ModelMatch::Blocklisted("anthropic/claude-3-opus".to_string())
+        );
+        assert_eq!(
+            filter.check("openai/gpt-4o-preview"),
+            ModelMatch::Blocklisted("*-preview".to_string())
+        );
+        assert_eq!(filter.check("meta/llama-3"), ModelMatch::NotAllowlisted);
+
+        let error = filter
+            .ensure_permitted("meta/llama-3")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("allowlist") && error.contains("OpenRouter"));
+        let error = filter
+            .ensure_permitted("x-preview")
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("blocklist") && error.contains("*-preview"));
+
+        let model = |id: &str| LLMModel {
+            id: id.to_string(),
+            name: id.to_string(),
+            created: None,
+            owned_by: None,
+            supports_tools: false,
+            supports_thinking: false,
+            supports_image_generation: false,
+        };
+        let effective = filter.apply(vec![
+            model("openai/gpt-4o"),
+            model("openai/o1-preview"),
+            model("anthropic/claude-3-haiku"),
+            model("mistral/large"),
+        ]);
+        let ids: Vec<_> = effective.iter().map(|m| m.model.id.as_str()).collect();
+        assert_eq!(ids, ["openai/gpt-4o", "anthropic/claude-3-haiku"]);
+
+        // A blocklist alone leaves everything else unrestricted
+        let block_only =
+            ModelFilter::for_connection(&filtered_connection(None, Some(r#"["*-preview"]"#)))
+                .unwrap();
+        assert_eq!(block_only.check("openai/gpt-4o"), ModelMatch::Unrestricted);
+        assert!(ModelFilter::for_connection(&filtered_connection(Some("[]"), None)).is_none());
+    }
+
+    #[test]
+    fn default_model_is_checked_against_connection_lists() {
+        use crate::features::llm_connection::model_filter::{ensure_model_permitted, parse_list};
+
+        let connection =
+            filtered_connection(Some(r#"["gpt-4o*"]"#), Some(r#"["gpt-4o-realtime*"]"#));
+        assert!(ensure_model_permitted(&connection, "gpt-4o-mini").is_ok());
+        assert!(ensure_model_permitted(&connection, "o1").is_err());
+        assert!(ensure_model_permitted(&connection, "gpt-4o-realtime-preview").is_err());
+        // Without lists every model is accepted
+        assert!(ensure_model_permitted(&filtered_connection(None, None), "o1").is_ok());
+
+        // Lists are validated when the connection is saved
+        assert_eq!(parse_list(Some(" gpt-4o* ")).ok(), None);
+        assert_eq!(parse_list(Some(r#"[" gpt-4o* "]"#)).unwrap(), ["gpt-4o*"]);
+        assert!(parse_list(Some(r#"["ok", ""]"#)).is_err());
+        assert!(parse_list(None).unwrap().is_empty());
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct LLMConnection {
     pub id: String,
     pub name: String,
@@ -10,6 +10,51 @@ pub struct LLMConnection {
     pub models_json: Option<String>,   // JSON string of models array
     pub default_model: Option<String>, // Default model ID for this connection
     pub enabled: bool,                 // Whether the connection is enabled
+    pub keep_warm: bool, // Ping the model while a workspace using it is active (local servers)
+    pub system_prompt_suffix: Option<String>, // Standing instructions appended to every system prompt
+    pub skip_redaction: bool,                 // Trusted: send prompts without PII redaction
+    pub model_allowlist: Option<String>, // JSON array of model ids or globs; only these are used
+    pub model_blocklist: Option<String>, // JSON array of model ids or globs never used
+    pub config: Option<String>,          // JSON object of provider options, see `config`
+    pub deployment_name: Option<String>, // Azure OpenAI: deployment the connection routes to
+    pub api_version: Option<String>,     // Azure OpenAI: api-version query parameter
+    pub aws_region: Option<String>,      // AWS Bedrock: region of the runtime endpoint
+    pub aws_profile: Option<String>,     // AWS Bedrock: credential profile signing requests
     pub created_at: i64,
     pub updated_at: i64,
 }
+
+// Written by hand so the API key never reaches a log line
+impl std::fmt::Debug for LLMConnection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LLMConnection")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("base_url", &self.base_url)
+            .field("provider", &self.provider)
+            .field(
+                "api_key",
+                &if self.api_key.is_empty() {
+                    ""
+                } else {
+                    "[redacted]"
+                },
+            )
+            .field("models_json", &self.models_json)
+            .field("default_model", &self.default_model)
+            .field("enabled", &self.enabled)
+            .field("keep_warm", &self.keep_warm)
+            .field("system_prompt_suffix", &self.system_prompt_suffix)
+            .field("skip_redaction", &self.skip_redaction)
+            .field("model_allowlist", &self.model_allowlist)
+            .field("model_blocklist", &self.model_blocklist)
+            .field("config", &self.config)
+            .field("deployment_name", &self.deployment_name)
+            .field("api_version", &self.api_version)
+            .field("aws_region", &self.aws_region)
+            .field("aws_profile", &self.aws_profile)
+            .field("created_at", &self.created_at)
+            .field("updated_at", &self.updated_at)
+            .finish()
+    }
+}
@@ -1,5 +1,7 @@
 use super::models::LLMConnection;
 use crate::error::AppError;
+use crate::features::encryption::FieldCipher;
+use crate::features::redaction::secrets;
 use rusqlite::params;
 use std::sync::Arc;
 use tauri::AppHandle;
@@ -19,17 +21,58 @@ pub trait LLMConnectionRepository: Send + Sync {
         models_json: Option<&str>,
         default_model: Option<&str>,
         enabled: Option<bool>,
+        keep_warm: Option<bool>,
+        system_prompt_suffix: Option<&str>,
+        skip_redaction: Option<bool>,
+        model_allowlist: Option<&str>,
+        model_blocklist: Option<&str>,
+        config: Option<&str>,
+        deployment_name: Option<&str>,
+        api_version: Option<&str>,
+        aws_region: Option<&str>,
+        aws_profile: Option<&str>,
     ) -> Result<(), AppError>;
     fn delete(&self, id: &str) -> Result<(), AppError>;
 }
 
 pub struct SqliteLLMConnectionRepository {
     app: Arc<AppHandle>,
+    cipher: Arc<FieldCipher>,
+    // Seals API keys inside `cipher`, whether or not the database is encrypted
+    secrets: Arc<FieldCipher>,
 }
 
 impl SqliteLLMConnectionRepository {
-    pub const fn new(app: Arc<AppHandle>) -> Self {
-        Self { app }
+    pub const fn new(
+        app: Arc<AppHandle>,
+        cipher: Arc<FieldCipher>,
+        secrets: Arc<FieldCipher>,
+    ) -> Self {
+        Self {
+            app,
+            cipher,
+            secrets,
+        }
+    }
+
+    fn seal_api_key(&self, api_key: &str) -> Result<String, AppError> {
+        secrets::remember(api_key);
+        if api_key.is_empty() {
+            return self.cipher.seal(api_key);
+        }
+        self.cipher.seal(&self.secrets.seal(api_key)?)
+    }
+
+    /// The stored API key in plaintext. A key sealed with a key the keychain
+    /// no longer has stays sealed, for the readiness check to report.
+    fn open_api_key(&self, stored: String) -> Result<String, AppError> {
+        let api_key = self.cipher.open(stored)?;
+        let api_key = self.secrets.open(api_key.clone()).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Failed to decrypt an API key");
+            api_key
+        });
+        secrets::remember(&api_key);
+        Ok(api_key)
     }
 }
 
@@ -37,8 +80,8 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
     fn create(&self, connection: &LLMConnection) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute(
-            "INSERT INTO llm_connections (id, name, base_url, provider, api_key, models_json, default_model, enabled, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
-            params![connection.id, connection.name, connection.base_url, connection.provider, connection.api_key, connection.models_json, connection.default_model, connection.enabled, connection.created_at, connection.updated_at],
+            "INSERT INTO llm_connections (id, name, base_url, provider, api_key, models_json, default_model, enabled, keep_warm, system_prompt_suffix, skip_redaction, model_allowlist, model_blocklist, config, deployment_name, api_version, aws_region, aws_profile, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)",
+            params![connection.id, connection.name, connection.base_url, connection.provider, self.seal_api_key(&connection.api_key)?, connection.models_json, connection.default_model, connection.enabled, connection.keep_warm, connection.system_prompt_suffix, connection.skip_redaction, connection.model_allowlist, connection.model_blocklist, connection.config, connection.deployment_name, connection.api_version, connection.aws_region, connection.aws_profile, connection.created_at, connection.updated_at],
         )?;
         Ok(())
     }
@@ -46,7 +89,7 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
     fn get_all(&self) -> Result<Vec<LLMConnection>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, base_url, provider, api_key, models_json, default_model, enabled, created_at, updated_at FROM llm_connections ORDER BY created_at DESC"
+            "SELECT id, name, base_url, provider, api_key, models_json, default_model, enabled, created_at, updated_at, keep_warm, system_prompt_suffix, skip_redaction, model_allowlist, model_blocklist, config, deployment_name, api_version, aws_region, aws_profile FROM llm_connections ORDER BY created_at DESC"
         )?;
 
         let connections = stmt
@@ -62,17 +105,33 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
                     enabled: row.get::<_, i64>(7)? != 0, // Convert INTEGER to bool
                     created_at: row.get(8)?,
                     updated_at: row.get(9)?,
+                    keep_warm: row.get::<_, i64>(10)? != 0,
+                    system_prompt_suffix: row.get(11)?,
+                    skip_redaction: row.get::<_, i64>(12)? != 0,
+                    model_allowlist: row.get(13)?,
+                    model_blocklist: row.get(14)?,
+                    config: row.get(15)?,
+                    deployment_name: row.get(16)?,
+                    api_version: row.get(17)?,
+                    aws_region: row.get(18)?,
+                    aws_profile: row.get(19)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(connections)
+        connections
+            .into_iter()
+            .map(|mut connection: LLMConnection| {
+                connection.api_key = self.open_api_key(connection.api_key)?;
+                Ok(connection)
+            })
+            .collect()
     }
 
     fn get_by_id(&self, id: &str) -> Result<Option<LLMConnection>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT id, name, base_url, provider, api_key, models_json, default_model, enabled, created_at, updated_at FROM llm_connections WHERE id = ?1",
+            "SELECT id, name, base_url, provider, api_key, models_json, default_model, enabled, created_at, updated_at, keep_warm, system_prompt_suffix, skip_redaction, model_allowlist, model_blocklist, config, deployment_name, api_version, aws_region, aws_profile FROM llm_connections WHERE id = ?1",
             params![id],
             |row| {
                 Ok(LLMConnection {
@@ -86,12 +145,25 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
                     enabled: row.get::<_, i64>(7)? != 0, // Convert INTEGER to bool
                     created_at: row.get(8)?,
                     updated_at: row.get(9)?,
+                    keep_warm: row.get::<_, i64>(10)? != 0,
+                    system_prompt_suffix: row.get(11)?,
+                    skip_redaction: row.get::<_, i64>(12)? != 0,
+                    model_allowlist: row.get(13)?,
+                    model_blocklist: row.get(14)?,
+                    config: row.get(15)?,
+                    deployment_name: row.get(16)?,
+                    api_version: row.get(17)?,
+                    aws_region: row.get(18)?,
+                    aws_profile: row.get(19)?,
                 })
             },
         );
 
         match result {
-            Ok(connection) => Ok(Some(connection)),
+            Ok(mut connection) => {
+                connection.api_key = self.open_api_key(connection.api_key)?;
+                Ok(Some(connection))
+            }
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e.into()),
         }
@@ -107,6 +179,16 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
         models_json: Option<&str>,
         default_model: Option<&str>,
         enabled: Option<bool>,
+        keep_warm: Option<bool>,
+        system_prompt_suffix: Option<&str>,
+        skip_redaction: Option<bool>,
+        model_allowlist: Option<&str>,
+        model_blocklist: Option<&str>,
+        config: Option<&str>,
+        deployment_name: Option<&str>,
+        api_version: Option<&str>,
+        aws_region: Option<&str>,
+        aws_profile: Option<&str>,
     ) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let now = std::time::SystemTime::now()
@@ -138,7 +220,7 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
         if let Some(api_key) = api_key {
             conn.execute(
                 "UPDATE llm_connections SET api_key = ?1, updated_at = ?2 WHERE id = ?3",
-                params![api_key, now, id],
+                params![self.seal_api_key(api_key)?, now, id],
             )?;
         }
 
@@ -170,6 +252,87 @@ impl LLMConnectionRepository for SqliteLLMConnectionRepository {
             )?;
         }
 
+        if let Some(keep_warm) = keep_warm {
+            conn.execute(
+                "UPDATE llm_connections SET keep_warm = ?1, updated_at = ?2 WHERE id = ?3",
+                params![i64::from(keep_warm), now, id],
+            )?;
+        }
+
+        if let Some(suffix) = system_prompt_suffix {
+            // An empty string clears the suffix
+            let suffix = (!suffix.trim().is_empty()).then_some(suffix);
+            conn.execute(
+                "UPDATE llm_connections SET system_prompt_suffix = ?1, updated_at = ?2 WHERE id = ?3",
+                params![suffix, now, id],
+            )?;
+        }
+
+        if let Some(skip_redaction) = skip_redaction {
+            conn.execute(
+                "UPDATE llm_connections SET skip_redaction = ?1, updated_at = ?2 WHERE id = ?3",
+                params![i64::from(skip_redaction), now, id],
+            )?;
+        }
+
+        if let Some(allowlist) = model_allowlist {
+            // An empty string clears the list
+            let allowlist = (!allowlist.trim().is_empty()).then_some(allowlist);
+            conn.execute(
+                "UPDATE llm_connections SET model_allowlist = ?1, updated_at = ?2 WHERE id = ?3",
+                params![allowlist, now, id],
+            )?;
+        }
+
+        if let Some(blocklist) = model_blocklist {
+            let blocklist = (!blocklist.trim().is_empty()).then_some(blocklist);
+            conn.execute(
+                "UPDATE llm_connections SET model_blocklist = ?1, updated_at = ?2 WHERE id = ?3",
+                params![blocklist, now, id],
+            )?;
+        }
+
+        if let Some(config) = config {
+            let config = (!config.trim().is_empty()).then_some(config);
+            conn.execute(
+                "UPDATE llm_connections SET config = ?1, updated_at = ?2 WHERE id = ?3",
+                params![config, now, id],
+            )?;
+        }
+
+        if let Some(deployment_name) = deployment_name {
+            let deployment_name =
+                (!deployment_name.trim().is_empty()).then_some(deployment_name.trim());
+            conn.execute(
+                "UPDATE llm_connections SET deployment_name = ?1, updated_at = ?2 WHERE id = ?3",
+                params![deployment_name, now, id],
+            )?;
+        }
+
+        if let Some(api_version) = api_version {
+            let api_version = (!api_version.trim().is_empty()).then_some(api_version.trim());
+            conn.execute(
+                "UPDATE llm_connections SET api_version = ?1, updated_at = ?2 WHERE id = ?3",
+                params![api_version, now, id],
+            )?;
+        }
+
+        if let Some(aws_region) = aws_region {
+            let aws_region = (!aws_region.trim().is_empty()).then_some(aws_region.trim());
+            conn.execute(
+                "UPDATE llm_connections SET aws_region = ?1, updated_at = ?2 WHERE id = ?3",
+                params![aws_region, now, id],
+            )?;
+        }
+
+        if let Some(aws_profile) = aws_profile {
+            let aws_profile = (!aws_profile.trim().is_empty()).then_some(aws_profile.trim());
+            conn.execute(
+                "UPDATE llm_connections SET aws_profile = ?1, updated_at = ?2 WHERE id = ?3",
+                params![aws_profile, now, id],
+            )?;
+        }
+
         Ok(())
     }
 
@@ -1,8 +1,14 @@
+use super::config;
+use super::model_filter;
 use super::models::LLMConnection;
 use super::repository::LLMConnectionRepository;
 use crate::error::AppError;
+use crate::services::llm::providers::{azure, bedrock};
 use std::sync::Arc;
 
+/// Longest allowed connection system prompt suffix, in characters.
+const SYSTEM_PROMPT_SUFFIX_MAX_CHARS: usize = 4_000;
+
 pub struct LLMConnectionService {
     repository: Arc<dyn LLMConnectionRepository>,
 }
@@ -22,7 +28,33 @@ impl LLMConnectionService {
         api_key: String,
         models_json: Option<String>,
         default_model: Option<String>,
+        keep_warm: bool,
+        system_prompt_suffix: Option<String>,
+        skip_redaction: bool,
+        model_allowlist: Option<String>,
+        model_blocklist: Option<String>,
+        config: Option<String>,
+        deployment_name: Option<String>,
+        api_version: Option<String>,
+        aws_region: Option<String>,
+        aws_profile: Option<String>,
     ) -> Result<LLMConnection, AppError> {
+        Self::validate_system_prompt_suffix(system_prompt_suffix.as_deref())?;
+        model_filter::parse_list(model_allowlist.as_deref())?;
+        model_filter::parse_list(model_blocklist.as_deref())?;
+        config::parse_config(config.as_deref())?;
+        azure::validate_deployment(
+            &provider,
+            deployment_name.as_deref(),
+            api_version.as_deref(),
+        )?;
+        bedrock::validate_settings(&provider, aws_region.as_deref())?;
+        let aws_region = non_blank(aws_region);
+        // Bedrock connections reach the runtime endpoint of their region
+        let base_url = match aws_region.as_deref() {
+            Some(region) if bedrock::is_bedrock(&provider) => bedrock::runtime_url(region),
+            _ => base_url,
+        };
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -37,6 +69,16 @@ impl LLMConnectionService {
             models_json,
             default_model,
             enabled: true, // New connections are enabled by default
+            keep_warm,
+            system_prompt_suffix: system_prompt_suffix.filter(|s| !s.trim().is_empty()),
+            skip_redaction,
+            model_allowlist: model_allowlist.filter(|l| !l.trim().is_empty()),
+            model_blocklist: model_blocklist.filter(|l| !l.trim().is_empty()),
+            config: config.filter(|c| !c.trim().is_empty()),
+            deployment_name: non_blank(deployment_name),
+            api_version: non_blank(api_version),
+            aws_region,
+            aws_profile: non_blank(aws_profile),
             created_at: now,
             updated_at: now,
         };
@@ -64,7 +106,52 @@ impl LLMConnectionService {
         models_json: Option<String>,
         default_model: Option<String>,
         enabled: Option<bool>,
+        keep_warm: Option<bool>,
+        system_prompt_suffix: Option<String>,
+        skip_redaction: Option<bool>,
+        model_allowlist: Option<String>,
+        model_blocklist: Option<String>,
+        config: Option<String>,
+        deployment_name: Option<String>,
+        api_version: Option<String>,
+        aws_region: Option<String>,
+        aws_profile: Option<String>,
     ) -> Result<(), AppError> {
+        Self::validate_system_prompt_suffix(system_prompt_suffix.as_deref())?;
+        model_filter::parse_list(model_allowlist.as_deref())?;
+        model_filter::parse_list(model_blocklist.as_deref())?;
+        config::parse_config(config.as_deref())?;
+        let mut base_url = base_url;
+        if deployment_name.is_some()
+            || api_version.is_some()
+            || aws_region.is_some()
+            || provider.is_some()
+        {
+            // Check the connection as it will be once updated
+            let current = self.repository.get_by_id(&id)?;
+            let provider = provider
+                .clone()
+                .or_else(|| current.as_ref().map(|c| c.provider.clone()))
+                .unwrap_or_default();
+            let deployment_name = deployment_name
+                .clone()
+                .or_else(|| current.as_ref().and_then(|c| c.deployment_name.clone()));
+            let api_version = api_version
+                .clone()
+                .or_else(|| current.as_ref().and_then(|c| c.api_version.clone()));
+            azure::validate_deployment(
+                &provider,
+                deployment_name.as_deref(),
+                api_version.as_deref(),
+            )?;
+            let region = aws_region
+                .clone()
+                .or_else(|| current.as_ref().and_then(|c| c.aws_region.clone()));
+            bedrock::validate_settings(&provider, region.as_deref())?;
+            if let Some(region) = region.filter(|_| bedrock::is_bedrock(&provider)) {
+                base_url = Some(bedrock::runtime_url(region.trim()));
+            }
+        }
         self.repository.update(
             &id,
             name.as_deref(),
@@ -74,10 +161,36 @@ impl LLMConnectionService {
             models_json.as_deref(),
             default_model.as_deref(),
             enabled,
+            keep_warm,
+            system_prompt_suffix.as_deref(),
+            skip_redaction,
+            model_allowlist.as_deref(),
+            model_blocklist.as_deref(),
+            config.as_deref(),
+            deployment_name.as_deref(),
+            api_version.as_deref(),
+            aws_region.as_deref(),
+            aws_profile.as_deref(),
         )
     }
 
+    fn validate_system_prompt_suffix(suffix: Option<&str>) -> Result<(), AppError> {
+        let length = suffix.map_or(0, |s| s.chars().count());
+        if length > SYSTEM_PROMPT_SUFFIX_MAX_CHARS {
+            return Err(AppError::Validation(format!(
+                "System prompt suffix is too long ({length}/{SYSTEM_PROMPT_SUFFIX_MAX_CHARS} characters)"
+            )));
+        }
+        Ok(())
+    }
+
     pub fn delete(&self, id: String) -> Result<(), AppError> {
         self.repository.delete(&id)
     }
 }
+
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
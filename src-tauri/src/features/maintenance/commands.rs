@@ -0,0 +1,27 @@
+use super::models::{DbInfo, MaintenanceReport, MaintenanceTask};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Run database maintenance now. Without `tasks` this reclaims free pages,
+/// refreshes planner statistics, checks integrity and optimizes full-text
+/// indexes. Waits for turns in flight to finish first.
+#[tauri::command]
+pub async fn run_db_maintenance(
+    state: State<'_, AppState>,
+    tasks: Option<Vec<MaintenanceTask>>,
+) -> Result<MaintenanceReport, AppError> {
+    let tasks = tasks.unwrap_or_else(|| MaintenanceTask::DEFAULT.to_vec());
+    if tasks.is_empty() {
+        return Err(AppError::Validation(
+            "No maintenance tasks given".to_string(),
+        ));
+    }
+    state.maintenance_service.run(tasks, false).await
+}
+
+/// The database's schema version and its applied and pending migrations.
+#[tauri::command]
+pub async fn get_db_info(state: State<'_, AppState>) -> Result<DbInfo, AppError> {
+    state.maintenance_service.db_info()
+}
@@ -0,0 +1,7 @@
+pub mod commands;
+pub mod models;
+pub mod service;
+pub mod tasks;
+
+pub use models::*;
+pub use service::*;
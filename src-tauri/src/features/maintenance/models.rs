@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+/// A database maintenance step.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// Give free pages back to the file system in small steps, so other
+    /// connections only ever wait for one step. Needs incremental
+    /// auto-vacuum, which a full `Vacuum` switches on.
+    IncrementalVacuum,
+    /// Rebuild the whole file. Reads continue meanwhile, but writes from
+    /// turns wait for it, so it only runs when asked for.
+    Vacuum,
+    /// Refresh the statistics the query planner uses
+    Analyze,
+    IntegrityCheck,
+    /// Merge the segments of full-text indexes, when there are any
+    FtsOptimize,
+}
+
+impl MaintenanceTask {
+    /// What `run_db_maintenance` runs when no tasks are given.
+    pub const DEFAULT: [Self; 4] = [
+        Self::IncrementalVacuum,
+        Self::Analyze,
+        Self::IntegrityCheck,
+        Self::FtsOptimize,
+    ];
+    /// What the automatic schedule runs.
+    pub const CHEAP: [Self; 3] = [Self::IncrementalVacuum, Self::Analyze, Self::FtsOptimize];
+}
+
+/// Outcome of one maintenance step. Sizes are of the database file and its
+/// write-ahead log together.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceTaskReport {
+    pub task: MaintenanceTask,
+    pub duration_ms: u64,
+    pub size_before: u64,
+    pub size_after: u64,
+    pub ok: bool,
+    /// Problems found, why the step did nothing, or why it failed
+    pub detail: Option<String>,
+}
+
+/// A maintenance run, as kept for diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MaintenanceReport {
+    /// Started by the schedule rather than `run_db_maintenance`
+    pub automatic: bool,
+    /// When the run was asked for; it may have waited for turns to finish
+    pub requested_at: i64,
+    pub started_at: i64,
+    pub finished_at: i64,
+    pub tasks: Vec<MaintenanceTaskReport>,
+}
+
+/// A versioned schema migration. `applied_at` is `None` while it is pending.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MigrationInfo {
+    pub version: u32,
+    pub name: String,
+    pub applied_at: Option<i64>,
+}
+
+/// Where the database schema stands.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DbInfo {
+    /// Version of the last migration that ran, 0 for none
+    pub schema_version: u32,
+    /// Version of the last migration this release knows
+    pub latest_version: u32,
+    pub applied: Vec<MigrationInfo>,
+    pub pending: Vec<MigrationInfo>,
+}
@@ -0,0 +1,256 @@
+use super::models::{DbInfo, MaintenanceReport, MaintenanceTask, MigrationInfo};
+use super::tasks;
+use crate::db::migrations;
+use crate::error::AppError;
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::app_settings::time::TimeFormatter;
+use crate::features::chat::ChatService;
+use chrono::NaiveTime;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// App setting choosing the automatic schedule: `weekly` (default), `daily`
+/// or `off`.
+pub const DB_MAINTENANCE_SCHEDULE: &str = "dbMaintenanceSchedule";
+/// App setting with the time of day, `HH:MM` in the display zone, automatic
+/// runs start at. Unset, they start as soon as they are due.
+pub const DB_MAINTENANCE_TIME: &str = "dbMaintenanceTime";
+/// App setting holding the last `MaintenanceReport` as JSON.
+pub const DB_MAINTENANCE_LAST_REPORT: &str = "dbMaintenanceLastReport";
+/// How often the schedule looks whether a run is due.
+const SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_mins(30);
+/// Time without a running turn before an automatic run may start.
+pub const IDLE_BEFORE_MAINTENANCE: Duration = Duration::from_mins(10);
+/// How often a queued run looks whether the running turns have finished.
+const TURN_POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+pub struct MaintenanceService {
+    db_path: PathBuf,
+    app_settings_service: Arc<AppSettingsService>,
+    chat_service: Arc<ChatService>,
+    /// One run at a time; a second one waits for the first
+    run_lock: tokio::sync::Mutex<()>,
+}
+
+impl MaintenanceService {
+    pub fn new(
+        db_path: PathBuf,
+        app_settings_service: Arc<AppSettingsService>,
+        chat_service: Arc<ChatService>,
+    ) -> Self {
+        Self {
+            db_path,
+            app_settings_service,
+            chat_service,
+            run_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    /// Run `tasks`, after any turn in flight has finished, and keep the
+    /// report for diagnostics.
+    pub async fn run(
+        &self,
+        tasks: Vec<MaintenanceTask>,
+        automatic: bool,
+    ) -> Result<MaintenanceReport, AppError> {
+        let requested_at = chrono::Utc::now().timestamp_millis();
+        let _running = self.run_lock.lock().await;
+        while self.chat_service.idle_for()?.is_none() {
+            tokio::time::sleep(TURN_POLL_INTERVAL).await;
+        }
+
+        let started_at = chrono::Utc::now().timestamp_millis();
+        let db_path = self.db_path.clone();
+        let task_reports =
+            tauri::async_runtime::spawn_blocking(move || tasks::run_tasks(&db_path, &tasks))
+                .await
+                .map_err(|e| AppError::Generic(format!("Database maintenance failed: {e}")))??;
+        let report = MaintenanceReport {
+            automatic,
+            requested_at,
+            started_at,
+            finished_at: chrono::Utc::now().timestamp_millis(),
+            tasks: task_reports,
+        };
+
+        match serde_json::to_string(&report) {
+            Ok(json) => {
+                if let Err(e) = self
+                    .app_settings_service
+                    .save(DB_MAINTENANCE_LAST_REPORT.to_string(), json)
+                {
+                    tracing::warn!(error = %e, "Failed to store database maintenance report");
+                }
+            }
+            Err(e) => tracing::warn!(error = %e, "Failed to serialize database maintenance report"),
+        }
+        Ok(report)
+    }
+
+    /// The schema version and the migrations that ran or are pending.
+    pub fn db_info(&self) -> Result<DbInfo, AppError> {
+        db_info(&crate::db::open_connection(&self.db_path)?)
+    }
+
+    /// The report of the last run, if any.
+    pub fn last_report(&self) -> Result<Option<MaintenanceReport>, AppError> {
+        let Some(json) = self
+            .app_settings_service
+            .get_by_key(DB_MAINTENANCE_LAST_REPORT)?
+        else {
+            return Ok(None);
+        };
+        Ok(serde_json::from_str(&json).ok())
+    }
+
+    /// Check every `SCHEDULE_CHECK_INTERVAL` whether the cheap tasks are due
+    /// and the app has been idle long enough, and run them if so.
+    pub fn start_schedule(self: Arc<Self>) {
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(SCHEDULE_CHECK_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.run_if_due().await {
+                    tracing::error!(error = %e, "Scheduled database maintenance failed");
+                }
+            }
+        });
+    }
+
+    /// When the next automatic run is due, `None` when the schedule is off.
+    /// Nothing is stored: it follows the current settings, so a new zone or
+    /// time applies from the next check on.
+    pub fn next_run_at(&self) -> Result<Option<i64>, AppError> {
+        let schedule = self
+            .app_settings_service
+            .get_by_key(DB_MAINTENANCE_SCHEDULE)?;
+        let preferred_time = self
+            .app_settings_service
+            .get_by_key(DB_MAINTENANCE_TIME)?
+            .and_then(|value| match NaiveTime::parse_from_str(&value, "%H:%M") {
+                Ok(time) => Some(time),
+                Err(e) => {
+                    tracing::warn!(value = %value, error = %e, "Ignoring invalid maintenance time");
+                    None
+                }
+            });
+        let last_started_at = self.last_report()?.map(|report| report.started_at);
+        Ok(next_maintenance_run(
+            schedule.as_deref(),
+            preferred_time,
+            last_started_at,
+            chrono::Utc::now().timestamp_millis(),
+            &self.app_settings_service.time_formatter(),
+        ))
+    }
+
+    async fn run_if_due(&self) -> Result<(), AppError> {
+        let due = maintenance_due(
+            self.next_run_at()?,
+            chrono::Utc::now().timestamp_millis(),
+            self.chat_service.idle_for()?,
+        );
+        if due {
+            self.run(MaintenanceTask::CHEAP.to_vec(), true).await?;
+        }
+        Ok(())
+    }
+}
+
+/// Interval of an automatic schedule setting, `None` when switched off.
+pub fn schedule_interval(schedule: Option<&str>) -> Option<Duration> {
+    match schedule {
+        Some("off") => None,
+        Some("daily") => Some(Duration::from_hours(24)),
+        _ => Some(Duration::from_hours(24 * 7)),
+    }
+}
+
+/// When the next automatic run is due: an interval after the last run, or
+/// now when there was none. With a preferred time, the run moves to that
+/// time on the same local day, so it keeps to the time of day even when a
+/// run started late. A preferred time skipped by a daylight saving change
+/// moves forward by the gap.
+pub fn next_maintenance_run(
+    schedule: Option<&str>,
+    preferred_time: Option<NaiveTime>,
+    last_started_at: Option<i64>,
+    now: i64,
+    time: &TimeFormatter,
+) -> Option<i64> {
+    let interval = schedule_interval(schedule)?;
+    let interval_ms = i64::try_from(interval.as_millis()).unwrap_or(i64::MAX);
+    let earliest = last_started_at.map_or(now, |last| last.saturating_add(interval_ms));
+    Some(preferred_time.map_or(earliest, |preferred| {
+        time.resolve_local(time.local_date(earliest).and_time(preferred))
+    }))
+}
+
+/// Whether the automatic run should start now: it is due and no turn has
+/// run for `IDLE_BEFORE_MAINTENANCE`.
+pub fn maintenance_due(next_run_at: Option<i64>, now: i64, idle_for: Option<Duration>) -> bool {
+    next_run_at.is_some_and(|next| now >= next)
+        && idle_for.is_some_and(|idle| idle >= IDLE_BEFORE_MAINTENANCE)
+}
+
+pub fn db_info(conn: &rusqlite::Connection) -> Result<DbInfo, AppError> {
+    let applied = migrations::applied_migrations(conn)?;
+    let pending = migrations::pending_migrations(conn)?;
+    Ok(DbInfo {
+        schema_version: applied.last().map_or(0, |m| m.version),
+        latest_version: migrations::latest_version(),
+        applied: applied
+            .into_iter()
+            .map(|m| MigrationInfo {
+                version: m.version,
+                name: m.name,
+                applied_at: Some(m.applied_at),
+            })
+            .collect(),
+        pending: pending
+            .into_iter()
+            .map(|m| MigrationInfo {
+                version: m.version,
+                name: m.name.to_string(),
+                applied_at: None,
+            })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn db_maintenance_schedule_waits_for_interval_and_idle_time() {
+        use crate::features::app_settings::time::TimeFormatter;
+        use crate::features::maintenance::{
+            maintenance_due, next_maintenance_run, IDLE_BEFORE_MAINTENANCE,
+        };
+        use std::time::Duration;
+
+        let day = 24 * 60 * 60 * 1000;
+        let now = 100 * day;
+        let idle = Some(IDLE_BEFORE_MAINTENANCE);
+        let due = |schedule, last, idle_for| {
+            let next = next_maintenance_run(schedule, None, last, now, &TimeFormatter::default());
+            maintenance_due(next, now, idle_for)
+        };
+
+        // Weekly by default, and right away when it never ran
+        assert!(due(None, None, idle));
+        assert!(!due(None, Some(now - 6 * day), idle));
+        assert!(due(Some("weekly"), Some(now - 7 * day), idle));
+        assert!(due(Some("daily"), Some(now - day), idle));
+        assert!(!due(Some("off"), None, idle));
+
+        // Not while a turn runs, nor right after one
+        assert!(!due(None, None, None));
+        assert!(!due(
+            None,
+            None,
+            Some(IDLE_BEFORE_MAINTENANCE - Duration::from_secs(1))
+        ));
+    }
+}
@@ -0,0 +1,337 @@
+//! The maintenance steps themselves, on a database file.
+//!
+//! Every step runs on its own connection with the usual busy timeout, and
+//! none of them holds a write lock for long except `Vacuum`: the database is
+//! in WAL mode, so readers are never blocked, and writers only wait for the
+//! step that is writing.
+
+use super::models::{MaintenanceTask, MaintenanceTaskReport};
+use crate::db::open_connection;
+use crate::error::AppError;
+use rusqlite::types::ValueRef;
+use rusqlite::Connection;
+use std::path::Path;
+use std::time::Instant;
+
+/// Pages `IncrementalVacuum` frees per statement.
+const VACUUM_STEP_PAGES: i64 = 256;
+/// `PRAGMA auto_vacuum` value of incremental auto-vacuum
+const AUTO_VACUUM_INCREMENTAL: i64 = 2;
+/// Integrity problems kept in a report; the check lists up to 100.
+const MAX_REPORTED_PROBLEMS: usize = 10;
+
+/// Run `tasks` in order on the database at `path`. A failing step is
+/// reported and the following ones still run.
+pub fn run_tasks(
+    path: &Path,
+    tasks: &[MaintenanceTask],
+) -> Result<Vec<MaintenanceTaskReport>, AppError> {
+    let conn = open_connection(path)?;
+    Ok(tasks
+        .iter()
+        .map(|&task| {
+            let size_before = database_size(path);
+            let started = Instant::now();
+            let result = run_task(&conn, task);
+            let duration_ms = u64::try_from(started.elapsed().as_millis()).unwrap_or(u64::MAX);
+            let (ok, detail) = match result {
+                Ok(outcome) => outcome,
+                Err(e) => (false, Some(e.to_string())),
+            };
+            if ok {
+                tracing::info!(?task, duration_ms, "Database maintenance step done");
+            } else {
+                tracing::warn!(?task, duration_ms, detail = ?detail, "Database maintenance step failed");
+            }
+            MaintenanceTaskReport {
+                task,
+                duration_ms,
+                size_before,
+                size_after: database_size(path),
+                ok,
+                detail,
+            }
+        })
+        .collect())
+}
+
+/// Bytes of the database file and its write-ahead log.
+pub fn database_size(path: &Path) -> u64 {
+    let size = |path: &Path| std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    let mut wal = path.as_os_str().to_owned();
+    wal.push("-wal");
+    size(path) + size(Path::new(&wal))
+}
+
+fn run_task(conn: &Connection, task: MaintenanceTask) -> Result<(bool, Option<String>), AppError> {
+    match task {
+        MaintenanceTask::IncrementalVacuum => incremental_vacuum(conn),
+        MaintenanceTask::Vacuum => {
+            conn.execute("PRAGMA auto_vacuum = INCREMENTAL", [])?;
+            conn.execute("VACUUM", [])?;
+            checkpoint(conn)?;
+            Ok((true, None))
+        }
+        MaintenanceTask::Analyze => {
+            conn.execute("ANALYZE", [])?;
+            Ok((true, None))
+        }
+        MaintenanceTask::IntegrityCheck => {
+            let problems: Vec<String> = pragma_values(conn, "PRAGMA integrity_check")?
+                .into_iter()
+                .filter(|line| line != "ok")
+                .collect();
+            if problems.is_empty() {
+                return Ok((true, None));
+            }
+            let mut detail = problems
+                .iter()
+                .take(MAX_REPORTED_PROBLEMS)
+                .cloned()
+                .collect::<Vec<_>>()
+                .join("; ");
+            if problems.len() > MAX_REPORTED_PROBLEMS {
+                detail.push_str(&format!(
+                    "; and {} more",
+                    problems.len() - MAX_REPORTED_PROBLEMS
+                ));
+            }
+            Ok((false, Some(detail)))
+        }
+        MaintenanceTask::FtsOptimize => fts_optimize(conn),
+    }
+}
+
+fn incremental_vacuum(conn: &Connection) -> Result<(bool, Option<String>), AppError> {
+    let mode: i64 = conn.query_row("PRAGMA auto_vacuum", [], |row| row.get(0))?;
+    if mode != AUTO_VACUUM_INCREMENTAL {
+        return Ok((
+            true,
+            Some(
+                "Incremental vacuum is off for this database; a full vacuum switches it on"
+                    .to_string(),
+            ),
+        ));
+    }
+
+    let freelist = |conn: &Connection| -> Result<i64, AppError> {
+        Ok(conn.query_row("PRAGMA freelist_count", [], |row| row.get(0))?)
+    };
+    let free_pages = freelist(conn)?;
+    let mut remaining = free_pages;
+    while remaining > 0 {
+        // One short write per step; turns writing meanwhile wait at most that long
+        pragma_values(
+            conn,
+            &format!("PRAGMA incremental_vacuum({VACUUM_STEP_PAGES})"),
+        )?;
+        let left = freelist(conn)?;
+        if left >= remaining {
+            break;
+        }
+        remaining = left;
+    }
+    checkpoint(conn)?;
+    Ok((
+        true,
+        Some(format!("{} free pages released", free_pages - remaining)),
+    ))
+}
+
+fn fts_optimize(conn: &Connection) -> Result<(bool, Option<String>), AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT name FROM sqlite_master WHERE type = 'table' AND lower(sql) LIKE '%using fts%'",
+    )?;
+    let tables = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if tables.is_empty() {
+        return Ok((true, Some("No full-text indexes".to_string())));
+    }
+    for table in &tables {
+        let name = table.replace('"', "\"\"");
+        conn.execute(
+            &format!("INSERT INTO \"{name}\"(\"{name}\") VALUES ('optimize')"),
+            [],
+        )?;
+    }
+    Ok((
+        true,
+        Some(format!("{} full-text indexes optimized", tables.len())),
+    ))
+}
+
+/// Move the write-ahead log into the database file and truncate it, so
+/// freed space shows on disk. Readers still using the log keep it; that is
+/// not an error.
+fn checkpoint(conn: &Connection) -> Result<(), AppError> {
+    pragma_values(conn, "PRAGMA wal_checkpoint(TRUNCATE)")?;
+    Ok(())
+}
+
+/// First column of every row a pragma returns, as text.
+fn pragma_values(conn: &Connection, sql: &str) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare(sql)?;
+    let columns = stmt.column_count();
+    let mut rows = stmt.query([])?;
+    let mut values = Vec::new();
+    while let Some(row) = rows.next()? {
+        if columns == 0 {
+            continue;
+        }
+        values.push(match row.get_ref(0)? {
+            ValueRef::Text(text) => String::from_utf8_lossy(text).into_owned(),
+            ValueRef::Integer(value) => value.to_string(),
+            ValueRef::Real(value) => value.to_string(),
+            ValueRef::Null | ValueRef::Blob(_) => String::new(),
+        });
+    }
+    Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+    /// A database in the app's configuration with `rows` rows of about 2 KB,
+    /// of which all but every tenth have been deleted again.
+    fn maintenance_fixture(path: &std::path::Path, configured: bool, rows: i64) {
+        let conn = crate::db::open_connection(path).unwrap();
+        if configured {
+            crate::db::configure_database(&conn).unwrap();
+        } else {
+            conn.query_row("PRAGMA journal_mode = WAL", [], |row| {
+                row.get::<_, String>(0)
+            })
+            .unwrap();
+        }
+        conn.execute_batch(
+            "CREATE TABLE messages (id INTEGER PRIMARY KEY, content TEXT NOT NULL);
+             CREATE VIRTUAL TABLE messages_fts USING fts5(content);",
+        )
+        .unwrap();
+        let padding = "lorem ipsum ".repeat(170);
+        for id in 0..rows {
+            conn.execute(
+                "INSERT INTO messages (id, content) VALUES (?1, ?2)",
+                rusqlite::params![id, format!("message {id} {padding}")],
+            )
+            .unwrap();
+        }
+        conn.execute(
+            "INSERT INTO messages_fts (content) SELECT content FROM messages",
+            [],
+        )
+        .unwrap();
+        conn.execute("DELETE FROM messages WHERE id % 10 != 0", [])
+            .unwrap();
+        conn.execute(
+            "DELETE FROM messages_fts WHERE rowid NOT IN (SELECT id + 1 FROM messages)",
+            [],
+        )
+        .unwrap();
+        conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+            .unwrap();
+    }
+
+    #[test]
+    fn db_maintenance_reclaims_space_of_deleted_rows() {
+        use crate::features::maintenance::tasks::{database_size, run_tasks};
+        use crate::features::maintenance::MaintenanceTask;
+
+        let dir = tempfile::tempdir().unwrap();
+
+        // New databases vacuum incrementally
+        let path = dir.path().join("new.db");
+        maintenance_fixture(&path, true, 2_000);
+        let before = database_size(&path);
+        let reports = run_tasks(
+            &path,
+            &[
+                MaintenanceTask::IncrementalVacuum,
+                MaintenanceTask::Analyze,
+                MaintenanceTask::IntegrityCheck,
+                MaintenanceTask::FtsOptimize,
+            ],
+        )
+        .unwrap();
+        assert!(reports.iter().all(|report| report.ok), "{reports:?}");
+        assert_eq!(reports[0].size_before, before);
+        assert!(
+            reports[0].size_after < before / 2,
+            "{} -> {}",
+            before,
+            reports[0].size_after
+        );
+        assert_eq!(
+            reports[3].detail.as_deref(),
+            Some("1 full-text indexes optimized")
+        );
+        assert!(database_size(&path) < before / 2);
+
+        // Older databases need one full vacuum, which switches it on
+        let path = dir.path().join("old.db");
+        maintenance_fixture(&path, false, 2_000);
+        let before = database_size(&path);
+        let reports = run_tasks(&path, &[MaintenanceTask::IncrementalVacuum]).unwrap();
+        assert!(reports[0].ok);
+        assert_eq!(reports[0].size_after, before);
+        assert!(reports[0]
+            .detail
+            .as_deref()
+            .is_some_and(|detail| detail.contains("full vacuum")));
+
+        let reports = run_tasks(&path, &[MaintenanceTask::Vacuum]).unwrap();
+        assert!(reports[0].ok, "{reports:?}");
+        assert!(reports[0].size_after < before / 2);
+        let conn = crate::db::open_connection(&path).unwrap();
+        let mode: i64 = conn
+            .query_row("PRAGMA auto_vacuum", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, 2);
+    }
+
+    #[test]
+    fn db_maintenance_does_not_break_concurrent_reads() {
+        use crate::features::maintenance::tasks::run_tasks;
+        use crate::features::maintenance::MaintenanceTask;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("database.db");
+        maintenance_fixture(&path, false, 2_000);
+
+        let done = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let path = path.clone();
+            let done = done.clone();
+            std::thread::spawn(move || {
+                let conn = crate::db::open_connection(&path).unwrap();
+                let mut reads = 0;
+                while !done.load(Ordering::SeqCst) || reads == 0 {
+                    let count: i64 = conn
+                        .query_row("SELECT count(*) FROM messages", [], |row| row.get(0))
+                        .expect("read during maintenance failed");
+                    assert_eq!(count, 200);
+                    reads += 1;
+                    std::thread::sleep(std::time::Duration::from_millis(1));
+                }
+                reads
+            })
+        };
+
+        let reports = run_tasks(
+            &path,
+            &[
+                MaintenanceTask::Vacuum,
+                MaintenanceTask::Analyze,
+                MaintenanceTask::IntegrityCheck,
+                MaintenanceTask::FtsOptimize,
+                MaintenanceTask::IncrementalVacuum,
+            ],
+        )
+        .unwrap();
+        done.store(true, Ordering::SeqCst);
+        assert!(reader.join().unwrap() > 0);
+        assert!(reports.iter().all(|report| report.ok), "{reports:?}");
+    }
+}
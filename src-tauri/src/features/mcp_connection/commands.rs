@@ -1,5 +1,10 @@
-use super::models::MCPServerConnection;
+use super::models::{
+    ConfigIssue, ConfigIssueKind, ConfigIssueSeverity, MCPConfigValidation, MCPConnectionConfig,
+    MCPServerConnection, SavedMCPConnection,
+};
+use super::validation::{self, HostEnvironment, HANDSHAKE_TIMEOUT};
 use crate::error::AppError;
+use crate::features::tool::mcp_client::MCPClientService;
 use crate::state::AppState;
 use tauri::State;
 
@@ -10,13 +15,23 @@ pub fn create_mcp_server_connection(
     url: String,
     r#type: String,
     headers: String,
+    auth_token: Option<String>,
     env_vars: Option<String>,
     runtime_path: Option<String>,
     state: State<'_, AppState>,
-) -> Result<MCPServerConnection, AppError> {
+) -> Result<SavedMCPConnection, AppError> {
     state
         .mcp_connection_service
-        .create(id, name, url, r#type, headers, env_vars, runtime_path)
+        .create(
+            id,
+            name,
+            url,
+            r#type,
+            headers,
+            auth_token,
+            env_vars,
+            runtime_path,
+        )
         .map_err(|e| AppError::Mcp(e.to_string()))
 }
 
@@ -30,6 +45,7 @@ pub fn get_mcp_server_connections(
         .map_err(|e| AppError::Mcp(e.to_string()))
 }
 
+/// Update the given fields of a connection; an empty `auth_token` removes it.
 #[tauri::command]
 pub fn update_mcp_server_connection(
     id: String,
@@ -37,13 +53,23 @@ pub fn update_mcp_server_connection(
     url: Option<String>,
     r#type: Option<String>,
     headers: Option<String>,
+    auth_token: Option<String>,
     env_vars: Option<String>,
     runtime_path: Option<String>,
     state: State<'_, AppState>,
-) -> Result<(), AppError> {
+) -> Result<Vec<ConfigIssue>, AppError> {
     state
         .mcp_connection_service
-        .update(id, name, url, r#type, headers, env_vars, runtime_path)
+        .update(
+            id,
+            name,
+            url,
+            r#type,
+            headers,
+            auth_token,
+            env_vars,
+            runtime_path,
+        )
         .map_err(|e| AppError::Mcp(e.to_string()))
 }
 
@@ -61,6 +87,19 @@ pub fn update_mcp_server_status(
         .map_err(|e| AppError::Mcp(e.to_string()))
 }
 
+/// Connect the server in the background whenever the app starts, or stop.
+#[tauri::command]
+pub fn set_mcp_server_auto_connect(
+    id: String,
+    auto_connect: bool,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state
+        .mcp_connection_service
+        .set_auto_connect(id, auto_connect)
+        .map_err(|e| AppError::Mcp(e.to_string()))
+}
+
 #[tauri::command]
 pub fn delete_mcp_server_connection(
     id: String,
@@ -71,3 +110,40 @@ pub fn delete_mcp_server_connection(
         .delete(id)
         .map_err(|e| AppError::Mcp(e.to_string()))
 }
+
+/// Check a connection config without saving it. With `handshake`, a config
+/// that passed the other checks is also started for at most five seconds to
+/// read the server's name, version and capabilities; the server is not kept.
+#[tauri::command]
+pub async fn validate_mcp_connection_config(
+    app: tauri::AppHandle,
+    config: MCPConnectionConfig,
+    handshake: Option<bool>,
+) -> Result<MCPConfigValidation, AppError> {
+    let mut report = validation::check_config(&config, &HostEnvironment::current());
+    if !handshake.unwrap_or(false) || !report.valid {
+        return Ok(report);
+    }
+
+    let headers = config.request_headers();
+    let start = MCPClientService::create_and_start_client(
+        &app,
+        config.url,
+        config.r#type,
+        headers,
+        config.env_vars,
+        config.runtime_path,
+    );
+    match validation::handshake(start, HANDSHAKE_TIMEOUT).await {
+        Ok(declared) => report.handshake = Some(declared),
+        Err(e) => {
+            report.valid = false;
+            report.issues.push(ConfigIssue {
+                kind: ConfigIssueKind::HandshakeFailed,
+                severity: ConfigIssueSeverity::Error,
+                message: e.to_string(),
+            });
+        }
+    }
+    Ok(report)
+}
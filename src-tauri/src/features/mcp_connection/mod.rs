@@ -2,6 +2,7 @@ pub mod commands;
 pub mod models;
 pub mod repository;
 pub mod service;
+pub mod validation;
 
 pub use models::*;
 pub use repository::*;
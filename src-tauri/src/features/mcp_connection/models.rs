@@ -7,11 +7,132 @@ pub struct MCPServerConnection {
     pub url: String,
     pub r#type: String,  // "sse" | "stdio" | "http-streamable" | "streamable-http"
     pub headers: String, // JSON string
+    /// Sent as a bearer token to remote servers; kept encrypted at rest
+    #[serde(default)]
+    pub auth_token: Option<String>,
     pub env_vars: Option<String>, // JSON string (optional, for stdio)
     pub runtime_path: Option<String>, // Path to the specific runtime (optional)
-    pub status: String,  // "disconnected" | "connecting" | "connected"
+    /// Connected in the background when the app starts
+    #[serde(default)]
+    pub auto_connect: bool,
+    pub status: String,             // "disconnected" | "connecting" | "connected"
     pub tools_json: Option<String>, // JSON string of tools array
     pub error_message: Option<String>, // Error message if connection failed
     pub created_at: i64,
     pub updated_at: i64,
 }
+
+/// The fields of an MCP connection that decide how it connects, checked by
+/// `validate_mcp_connection_config` before anything is saved.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MCPConnectionConfig {
+    pub url: String,
+    pub r#type: String,
+    #[serde(default)]
+    pub headers: Option<String>,
+    #[serde(default)]
+    pub auth_token: Option<String>,
+    #[serde(default)]
+    pub env_vars: Option<String>,
+    #[serde(default)]
+    pub runtime_path: Option<String>,
+}
+
+impl From<&MCPServerConnection> for MCPConnectionConfig {
+    fn from(connection: &MCPServerConnection) -> Self {
+        Self {
+            url: connection.url.clone(),
+            r#type: connection.r#type.clone(),
+            headers: Some(connection.headers.clone()),
+            auth_token: connection.auth_token.clone(),
+            env_vars: connection.env_vars.clone(),
+            runtime_path: connection.runtime_path.clone(),
+        }
+    }
+}
+
+impl MCPServerConnection {
+    /// The headers a client sends, with the auth token added.
+    pub fn request_headers(&self) -> Option<String> {
+        super::validation::auth_headers(
+            &self.r#type,
+            Some(&self.headers),
+            self.auth_token.as_deref(),
+        )
+    }
+}
+
+impl MCPConnectionConfig {
+    /// The headers a client sends, with the auth token added.
+    pub fn request_headers(&self) -> Option<String> {
+        super::validation::auth_headers(
+            &self.r#type,
+            self.headers.as_deref(),
+            self.auth_token.as_deref(),
+        )
+    }
+}
+
+/// What is wrong with a connection config.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigIssueKind {
+    UnsupportedType,
+    InvalidArgs,
+    CommandNotFound,
+    InvalidEnv,
+    InvalidHeaders,
+    InvalidUrl,
+    MissingPlaceholder,
+    /// A credential would be sent without TLS, or not at all
+    InsecureAuth,
+    HandshakeFailed,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigIssueSeverity {
+    /// The connection cannot work as configured
+    Error,
+    /// The connection may work, depending on the machine it runs on
+    Warning,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ConfigIssue {
+    pub kind: ConfigIssueKind,
+    pub severity: ConfigIssueSeverity,
+    pub message: String,
+}
+
+/// What a server declared when it was started and initialized.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ServerHandshake {
+    pub name: String,
+    pub version: String,
+    pub protocol_version: String,
+    pub capabilities: serde_json::Value,
+    pub instructions: Option<String>,
+}
+
+/// Result of a dry-run check of a connection config.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct MCPConfigValidation {
+    /// No issue of `Error` severity
+    pub valid: bool,
+    /// Where the stdio command was found
+    pub resolved_command: Option<String>,
+    /// Placeholders in env vars or headers that nothing fills in
+    pub missing_placeholders: Vec<String>,
+    pub issues: Vec<ConfigIssue>,
+    /// Only when a handshake was asked for and succeeded
+    pub handshake: Option<ServerHandshake>,
+}
+
+/// A saved connection, with what the config checks found worth a look.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SavedMCPConnection {
+    #[serde(flatten)]
+    pub connection: MCPServerConnection,
+    pub warnings: Vec<ConfigIssue>,
+}
@@ -1,5 +1,7 @@
 use super::models::MCPServerConnection;
 use crate::error::AppError;
+use crate::features::encryption::FieldCipher;
+use crate::features::redaction::secrets;
 use rusqlite::params;
 use std::sync::Arc;
 use tauri::AppHandle;
@@ -15,6 +17,7 @@ pub trait MCPConnectionRepository: Send + Sync {
         url: Option<&str>,
         r#type: Option<&str>,
         headers: Option<&str>,
+        auth_token: Option<&str>,
         env_vars: Option<&str>,
         runtime_path: Option<&str>,
     ) -> Result<(), AppError>;
@@ -25,25 +28,70 @@ pub trait MCPConnectionRepository: Send + Sync {
         tools_json: Option<&str>,
         error_message: Option<&str>,
     ) -> Result<(), AppError>;
+    fn set_auto_connect(&self, id: &str, auto_connect: bool) -> Result<(), AppError>;
     fn delete(&self, id: &str) -> Result<(), AppError>;
 }
 
 pub struct SqliteMCPConnectionRepository {
     app: Arc<AppHandle>,
+    cipher: Arc<FieldCipher>,
+    /// Seals auth tokens with the API key encryption key
+    secrets: Arc<FieldCipher>,
 }
 
 impl SqliteMCPConnectionRepository {
-    pub const fn new(app: Arc<AppHandle>) -> Self {
-        Self { app }
+    pub const fn new(
+        app: Arc<AppHandle>,
+        cipher: Arc<FieldCipher>,
+        secrets: Arc<FieldCipher>,
+    ) -> Self {
+        Self {
+            app,
+            cipher,
+            secrets,
+        }
+    }
+
+    /// The auth token as stored; blank ones are not kept.
+    fn seal_auth_token(&self, auth_token: &str) -> Result<Option<String>, AppError> {
+        if auth_token.trim().is_empty() {
+            return Ok(None);
+        }
+        secrets::remember(auth_token);
+        Ok(Some(self.cipher.seal(&self.secrets.seal(auth_token)?)?))
+    }
+
+    /// The stored auth token in plaintext. One sealed with a key the keychain
+    /// no longer has stays sealed, and the server then rejects it.
+    fn open_auth_token(&self, stored: Option<String>) -> Result<Option<String>, AppError> {
+        let Some(auth_token) = self.cipher.open_opt(stored)? else {
+            return Ok(None);
+        };
+        let auth_token = self.secrets.open(auth_token.clone()).unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "Failed to decrypt an MCP auth token");
+            auth_token
+        });
+        secrets::remember(&auth_token);
+        Ok(Some(auth_token))
+    }
+
+    fn open_connection(
+        &self,
+        mut connection: MCPServerConnection,
+    ) -> Result<MCPServerConnection, AppError> {
+        connection.auth_token = self.open_auth_token(connection.auth_token)?;
+        Ok(connection)
     }
 }
 
 impl MCPConnectionRepository for SqliteMCPConnectionRepository {
     fn create(&self, connection: &MCPServerConnection) -> Result<(), AppError> {
+        let auth_token =
+            self.seal_auth_token(connection.auth_token.as_deref().unwrap_or_default())?;
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute(
-            "INSERT INTO mcp_server_connections (id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-            params![connection.id, connection.name, connection.url, connection.r#type, connection.headers, connection.env_vars, connection.runtime_path, connection.status, connection.tools_json, connection.error_message, connection.created_at, connection.updated_at],
+            "INSERT INTO mcp_server_connections (id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at, auth_token, auto_connect) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+            params![connection.id, connection.name, connection.url, connection.r#type, connection.headers, connection.env_vars, connection.runtime_path, connection.status, connection.tools_json, connection.error_message, connection.created_at, connection.updated_at, auth_token, connection.auto_connect],
         )?;
         Ok(())
     }
@@ -51,7 +99,7 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
     fn get_all(&self) -> Result<Vec<MCPServerConnection>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at FROM mcp_server_connections ORDER BY created_at DESC"
+            "SELECT id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at, auth_token, auto_connect FROM mcp_server_connections ORDER BY created_at DESC"
         )?;
 
         let connections = stmt
@@ -69,17 +117,22 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
                     error_message: row.get(9)?,
                     created_at: row.get(10)?,
                     updated_at: row.get(11)?,
+                    auth_token: row.get(12)?,
+                    auto_connect: row.get(13)?,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
 
-        Ok(connections)
+        connections
+            .into_iter()
+            .map(|connection| self.open_connection(connection))
+            .collect()
     }
 
     fn get_by_id(&self, id: &str) -> Result<Option<MCPServerConnection>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at FROM mcp_server_connections WHERE id = ?1",
+            "SELECT id, name, url, type, headers, env_vars, runtime_path, status, tools_json, error_message, created_at, updated_at, auth_token, auto_connect FROM mcp_server_connections WHERE id = ?1",
             params![id],
             |row| {
                 Ok(MCPServerConnection {
@@ -95,12 +148,14 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
                     error_message: row.get(9)?,
                     created_at: row.get(10)?,
                     updated_at: row.get(11)?,
+                    auth_token: row.get(12)?,
+                    auto_connect: row.get(13)?,
                 })
             },
         );
 
         match result {
-            Ok(connection) => Ok(Some(connection)),
+            Ok(connection) => Ok(Some(self.open_connection(connection)?)),
             Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
             Err(e) => Err(e.into()),
         }
@@ -113,6 +168,7 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
         url: Option<&str>,
         r#type: Option<&str>,
         headers: Option<&str>,
+        auth_token: Option<&str>,
         env_vars: Option<&str>,
         runtime_path: Option<&str>,
     ) -> Result<(), AppError> {
@@ -150,6 +206,14 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
             )?;
         }
 
+        // An empty token removes it
+        if let Some(auth_token) = auth_token {
+            conn.execute(
+                "UPDATE mcp_server_connections SET auth_token = ?1, updated_at = ?2 WHERE id = ?3",
+                params![self.seal_auth_token(auth_token)?, now, id],
+            )?;
+        }
+
         if let Some(env_vars) = env_vars {
             conn.execute(
                 "UPDATE mcp_server_connections SET env_vars = ?1, updated_at = ?2 WHERE id = ?3",
@@ -188,6 +252,25 @@ impl MCPConnectionRepository for SqliteMCPConnectionRepository {
         Ok(())
     }
 
+    fn set_auto_connect(&self, id: &str, auto_connect: bool) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+
+        let updated = conn.execute(
+            "UPDATE mcp_server_connections SET auto_connect = ?1, updated_at = ?2 WHERE id = ?3",
+            params![auto_connect, now, id],
+        )?;
+        if updated == 0 {
+            return Err(AppError::NotFound(format!(
+                "MCP connection not found: {id}"
+            )));
+        }
+        Ok(())
+    }
+
     fn delete(&self, id: &str) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute(
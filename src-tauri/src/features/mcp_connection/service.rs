@@ -1,15 +1,24 @@
-use super::models::MCPServerConnection;
+use super::models::{ConfigIssue, MCPConnectionConfig, MCPServerConnection, SavedMCPConnection};
 use super::repository::MCPConnectionRepository;
+use super::validation::{self, HostEnvironment};
 use crate::error::AppError;
+use crate::features::tool_catalog::ToolCatalogService;
 use std::sync::Arc;
 
 pub struct MCPConnectionService {
     repository: Arc<dyn MCPConnectionRepository>,
+    tool_catalog_service: Arc<ToolCatalogService>,
 }
 
 impl MCPConnectionService {
-    pub fn new(repository: Arc<dyn MCPConnectionRepository>) -> Self {
-        Self { repository }
+    pub fn new(
+        repository: Arc<dyn MCPConnectionRepository>,
+        tool_catalog_service: Arc<ToolCatalogService>,
+    ) -> Self {
+        Self {
+            repository,
+            tool_catalog_service,
+        }
     }
 
     pub fn create(
@@ -19,9 +28,10 @@ impl MCPConnectionService {
         url: String,
         r#type: String,
         headers: String,
+        auth_token: Option<String>,
         env_vars: Option<String>,
         runtime_path: Option<String>,
-    ) -> Result<MCPServerConnection, AppError> {
+    ) -> Result<SavedMCPConnection, AppError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -33,8 +43,10 @@ impl MCPConnectionService {
             url,
             r#type,
             headers,
+            auth_token: auth_token.filter(|token| !token.trim().is_empty()),
             env_vars,
             runtime_path,
+            auto_connect: false,
             status: "disconnected".to_string(),
             tools_json: None,
             error_message: None,
@@ -43,7 +55,21 @@ impl MCPConnectionService {
         };
 
         self.repository.create(&connection)?;
-        Ok(connection)
+        let warnings = Self::config_warnings(&connection);
+        Ok(SavedMCPConnection {
+            connection,
+            warnings,
+        })
+    }
+
+    /// What the checks that need no server find in a connection's config.
+    /// Saving goes ahead regardless; a missing command may be installed later.
+    fn config_warnings(connection: &MCPServerConnection) -> Vec<ConfigIssue> {
+        validation::check_config(
+            &MCPConnectionConfig::from(connection),
+            &HostEnvironment::current(),
+        )
+        .issues
     }
 
     pub fn get_all(&self) -> Result<Vec<MCPServerConnection>, AppError> {
@@ -61,18 +87,25 @@ impl MCPConnectionService {
         url: Option<String>,
         r#type: Option<String>,
         headers: Option<String>,
+        auth_token: Option<String>,
         env_vars: Option<String>,
         runtime_path: Option<String>,
-    ) -> Result<(), AppError> {
+    ) -> Result<Vec<ConfigIssue>, AppError> {
         self.repository.update(
             &id,
             name.as_deref(),
             url.as_deref(),
             r#type.as_deref(),
             headers.as_deref(),
+            auth_token.as_deref(),
             env_vars.as_deref(),
             runtime_path.as_deref(),
-        )
+        )?;
+        Ok(self
+            .repository
+            .get_by_id(&id)?
+            .map(|connection| Self::config_warnings(&connection))
+            .unwrap_or_default())
     }
 
     pub fn update_status(
@@ -87,7 +120,16 @@ impl MCPConnectionService {
             &status,
             tools_json.as_deref(),
             error_message.as_deref(),
-        )
+        )?;
+        if let Some(tools_json) = tools_json.as_deref() {
+            self.tool_catalog_service
+                .record_refresh_logged(&id, tools_json);
+        }
+        Ok(())
+    }
+
+    pub fn set_auto_connect(&self, id: String, auto_connect: bool) -> Result<(), AppError> {
+        self.repository.set_auto_connect(&id, auto_connect)
     }
 
     pub fn delete(&self, id: String) -> Result<(), AppError> {
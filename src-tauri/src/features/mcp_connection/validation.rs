@@ -0,0 +1,733 @@
+//! Dry-run checks of an MCP connection config.
+//!
+//! `check_config` covers what can be told without starting anything: the
+//! transport type, the stdio command line and where its command resolves,
+//! env vars and headers as flat string maps with their placeholders, and the
+//! URL of remote transports and whether credentials would cross it in the
+//! clear. `handshake` starts the server for a few seconds and reports what
+//! it declares, without registering a client.
+
+use super::models::{
+    ConfigIssue, ConfigIssueKind, ConfigIssueSeverity, MCPConfigValidation, MCPConnectionConfig,
+    ServerHandshake,
+};
+use crate::error::AppError;
+use crate::features::redaction::secrets::is_secret_field;
+use rust_mcp_sdk::mcp_client::ClientRuntime;
+use rust_mcp_sdk::McpClient;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ffi::{OsStr, OsString};
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Transport types a connection can use.
+pub const SUPPORTED_TYPES: &[&str] = &["sse", "http-streamable", "streamable-http", "stdio"];
+
+/// How long a handshake may take before the server counts as unresponsive.
+pub const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Commands served from the bundled runtimes when they are not on PATH.
+const BUNDLED_RUNTIME_COMMANDS: &[&str] = &["python", "python3", "uv", "node", "npm", "npx"];
+
+/// Commands a configured runtime path replaces at launch.
+const RUNTIME_REPLACED_COMMANDS: &[&str] = &["python", "python3", "node", "npm"];
+
+/// The parts of the machine the checks look at.
+pub struct HostEnvironment {
+    /// The `PATH` commands are searched in
+    pub path: Option<OsString>,
+    /// Extensions a bare command may be missing, `.cmd` and the like on Windows
+    pub extensions: Vec<String>,
+    /// Names of the environment variables that are set
+    pub variables: HashSet<String>,
+}
+
+impl HostEnvironment {
+    pub fn current() -> Self {
+        Self {
+            path: std::env::var_os("PATH"),
+            extensions: executable_extensions(),
+            variables: std::env::vars_os()
+                .filter_map(|(name, _)| name.into_string().ok())
+                .collect(),
+        }
+    }
+}
+
+/// The `PATHEXT` extensions on Windows, none elsewhere.
+pub fn executable_extensions() -> Vec<String> {
+    if !cfg!(windows) {
+        return Vec::new();
+    }
+    std::env::var("PATHEXT")
+        .unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string())
+        .split(';')
+        .filter(|extension| !extension.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Where `command` resolves: a path as is, a bare name in one of the `path`
+/// directories. Both may leave out one of `extensions`, so `npx` finds the
+/// `npx.cmd` shim on Windows.
+pub fn resolve_command(
+    command: &str,
+    path: Option<&OsStr>,
+    extensions: &[String],
+) -> Option<PathBuf> {
+    let candidate = Path::new(command);
+    if candidate.is_absolute() || candidate.components().count() > 1 {
+        return with_extensions(candidate, extensions).find(|file| is_executable(file));
+    }
+    std::env::split_paths(path?)
+        .flat_map(|dir| with_extensions(&dir.join(command), extensions).collect::<Vec<_>>())
+        .find(|file| is_executable(file))
+}
+
+fn with_extensions<'a>(
+    path: &'a Path,
+    extensions: &'a [String],
+) -> impl Iterator<Item = PathBuf> + 'a {
+    std::iter::once(path.to_path_buf()).chain(extensions.iter().map(move |extension| {
+        let mut file = path.as_os_str().to_owned();
+        file.push(extension);
+        PathBuf::from(file)
+    }))
+}
+
+#[cfg(unix)]
+fn is_executable(file: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    file.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(file: &Path) -> bool {
+    file.is_file()
+}
+
+/// A JSON object of string values, as env vars and headers are stored.
+/// Blank counts as empty.
+pub fn parse_string_map(json: &str) -> Result<BTreeMap<String, String>, String> {
+    if json.trim().is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let value: serde_json::Value =
+        serde_json::from_str(json).map_err(|e| format!("is not valid JSON: {e}"))?;
+    let serde_json::Value::Object(object) = value else {
+        return Err("must be a JSON object of names to values".to_string());
+    };
+    let not_strings: Vec<&str> = object
+        .iter()
+        .filter(|(_, value)| !value.is_string())
+        .map(|(name, _)| name.as_str())
+        .collect();
+    if !not_strings.is_empty() {
+        return Err(format!(
+            "must have string values, not for {}",
+            not_strings.join(", ")
+        ));
+    }
+    Ok(object
+        .into_iter()
+        .filter_map(|(name, value)| Some((name, value.as_str()?.to_string())))
+        .collect())
+}
+
+/// Names of the `${NAME}` and `{{NAME}}` placeholders in `value`, in order.
+pub fn placeholders(value: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let mut rest = value;
+    while let Some((_, end, name)) = next_placeholder(rest) {
+        names.push(name.to_string());
+        rest = &rest[end..];
+    }
+    names
+}
+
+/// `value` with its placeholders filled in by `lookup`. Those it has no
+/// value for are left as they are.
+pub fn expand_placeholders(value: &str, lookup: impl Fn(&str) -> Option<String>) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some((start, end, name)) = next_placeholder(rest) {
+        expanded.push_str(&rest[..start]);
+        match lookup(name) {
+            Some(filled) => expanded.push_str(&filled),
+            None => expanded.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// Start, end and name of the first placeholder in `text`.
+fn next_placeholder(text: &str) -> Option<(usize, usize, &str)> {
+    let mut from = 0;
+    loop {
+        let (start, open, close) = [("${", "}"), ("{{", "}}")]
+            .into_iter()
+            .filter_map(|(open, close)| Some((from + text[from..].find(open)?, open, close)))
+            .min_by_key(|(start, _, _)| *start)?;
+        let name_start = start + open.len();
+        if let Some(length) = text[name_start..].find(close) {
+            let name = &text[name_start..name_start + length];
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+            {
+                return Some((start, name_start + length + close.len(), name));
+            }
+        }
+        from = name_start;
+    }
+}
+
+fn is_loopback(url: &reqwest::Url) -> bool {
+    url.host_str().is_some_and(|host| {
+        host.eq_ignore_ascii_case("localhost")
+            || host
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+                .parse::<std::net::IpAddr>()
+                .is_ok_and(|ip| ip.is_loopback())
+    })
+}
+
+/// The headers to send to a server: `headers` with `auth_token` as a bearer
+/// `Authorization` header, unless one is set already. A token that names its
+/// scheme, such as `Basic …`, is sent as written. Stdio servers get no
+/// headers added, since theirs stand in for env vars.
+pub fn auth_headers(
+    r#type: &str,
+    headers: Option<&str>,
+    auth_token: Option<&str>,
+) -> Option<String> {
+    let headers = headers.filter(|json| !json.trim().is_empty());
+    let Some(token) = auth_token
+        .map(str::trim)
+        .filter(|token| !token.is_empty() && r#type != "stdio")
+    else {
+        return headers.map(str::to_string);
+    };
+    let Ok(mut map) = headers.map_or_else(|| Ok(BTreeMap::new()), parse_string_map) else {
+        // Invalid headers are reported by `check_config`; send them as they are
+        return headers.map(str::to_string);
+    };
+    if !map
+        .keys()
+        .any(|name| name.eq_ignore_ascii_case("authorization"))
+    {
+        let value = if token.contains(' ') {
+            token.to_string()
+        } else {
+            format!("Bearer {token}")
+        };
+        map.insert("Authorization".to_string(), value);
+    }
+    serde_json::to_string(&map).ok()
+}
+
+fn issue(kind: ConfigIssueKind, severity: ConfigIssueSeverity, message: String) -> ConfigIssue {
+    ConfigIssue {
+        kind,
+        severity,
+        message,
+    }
+}
+
+/// The checks that need no server. Never fails; what is wrong is listed.
+pub fn check_config(config: &MCPConnectionConfig, host: &HostEnvironment) -> MCPConfigValidation {
+    use ConfigIssueSeverity::{Error, Warning};
+
+    let mut issues = Vec::new();
+    let mut resolved_command = None;
+    let mut maps = Vec::new();
+    let auth_token = config
+        .auth_token
+        .as_deref()
+        .filter(|token| !token.trim().is_empty());
+
+    if !SUPPORTED_TYPES.contains(&config.r#type.as_str()) {
+        issues.push(issue(
+            ConfigIssueKind::UnsupportedType,
+            Error,
+            format!(
+                "Unsupported transport type '{}', expected one of {}",
+                config.r#type,
+                SUPPORTED_TYPES.join(", ")
+            ),
+        ));
+    }
+
+    for (label, json, kind) in [
+        ("Env vars", &config.env_vars, ConfigIssueKind::InvalidEnv),
+        ("Headers", &config.headers, ConfigIssueKind::InvalidHeaders),
+    ] {
+        match json.as_deref().map(parse_string_map) {
+            Some(Ok(map)) => maps.push(map),
+            Some(Err(e)) => issues.push(issue(kind, Error, format!("{label} {e}"))),
+            None => {}
+        }
+    }
+
+    if config.r#type == "stdio" {
+        match shell_words::split(&config.url) {
+            Err(e) => issues.push(issue(
+                ConfigIssueKind::InvalidArgs,
+                Error,
+                format!("The command line cannot be parsed: {e}"),
+            )),
+            Ok(parts) if parts.is_empty() => issues.push(issue(
+                ConfigIssueKind::InvalidArgs,
+                Error,
+                "The command line is empty".to_string(),
+            )),
+            Ok(parts) => {
+                // A configured runtime replaces generic interpreter commands at launch
+                let runtime = config
+                    .runtime_path
+                    .as_deref()
+                    .filter(|path| !path.is_empty() && *path != "default");
+                let command = match runtime {
+                    Some(runtime) if RUNTIME_REPLACED_COMMANDS.contains(&parts[0].as_str()) => {
+                        runtime
+                    }
+                    _ => parts[0].as_str(),
+                };
+                match resolve_command(command, host.path.as_deref(), &host.extensions) {
+                    Some(path) => resolved_command = Some(path.display().to_string()),
+                    None if BUNDLED_RUNTIME_COMMANDS.contains(&command) => issues.push(issue(
+                        ConfigIssueKind::CommandNotFound,
+                        Warning,
+                        format!(
+                            "'{command}' is not on PATH; the bundled runtime is used if it is installed"
+                        ),
+                    )),
+                    None => issues.push(issue(
+                        ConfigIssueKind::CommandNotFound,
+                        Error,
+                        format!("Command '{command}' was not found on PATH or as a file"),
+                    )),
+                }
+            }
+        }
+        if auth_token.is_some() {
+            issues.push(issue(
+                ConfigIssueKind::InsecureAuth,
+                Warning,
+                "The auth token is only sent to remote servers; pass it to a local one in an env var".to_string(),
+            ));
+        }
+    } else if SUPPORTED_TYPES.contains(&config.r#type.as_str()) {
+        let url = config.url.trim();
+        match reqwest::Url::parse(url) {
+            Ok(parsed)
+                if matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some() =>
+            {
+                let sends_credentials = auth_token.is_some()
+                    || config
+                        .headers
+                        .as_deref()
+                        .and_then(|json| parse_string_map(json).ok())
+                        .is_some_and(|headers| {
+                            headers.keys().any(|name| {
+                                name.eq_ignore_ascii_case("authorization") || is_secret_field(name)
+                            })
+                        });
+                if sends_credentials && parsed.scheme() == "http" && !is_loopback(&parsed) {
+                    issues.push(issue(
+                        ConfigIssueKind::InsecureAuth,
+                        Warning,
+                        format!("Credentials are sent unencrypted to '{url}'; use https"),
+                    ));
+                }
+            }
+            Ok(_) => issues.push(issue(
+                ConfigIssueKind::InvalidUrl,
+                Error,
+                format!("'{url}' is not an http or https URL"),
+            )),
+            Err(e) => issues.push(issue(
+                ConfigIssueKind::InvalidUrl,
+                Error,
+                format!("'{url}' is not a valid URL: {e}"),
+            )),
+        }
+    }
+
+    let missing_placeholders: Vec<String> = maps
+        .iter()
+        .flat_map(BTreeMap::values)
+        .map(String::as_str)
+        .chain(auth_token)
+        .flat_map(placeholders)
+        .filter(|name| !host.variables.contains(name))
+        .collect::<BTreeSet<_>>()
+        .into_iter()
+        .collect();
+    if !missing_placeholders.is_empty() {
+        issues.push(issue(
+            ConfigIssueKind::MissingPlaceholder,
+            Warning,
+            format!("No value is set for {}", missing_placeholders.join(", ")),
+        ));
+    }
+
+    MCPConfigValidation {
+        valid: !issues.iter().any(|found| found.severity == Error),
+        resolved_command,
+        missing_placeholders,
+        issues,
+        handshake: None,
+    }
+}
+
+/// Start a server with `start`, read what it declared on initialization and
+/// shut it down again, all within `timeout`.
+pub async fn handshake<F>(start: F, timeout: Duration) -> Result<ServerHandshake, AppError>
+where
+    F: Future<Output = Result<Arc<ClientRuntime>, AppError>>,
+{
+    let client = tokio::time::timeout(timeout, start).await.map_err(|_| {
+        AppError::Mcp(format!(
+            "The server did not complete its handshake within {}s",
+            timeout.as_secs_f32()
+        ))
+    })??;
+    let info = client.server_info();
+    if let Err(e) = client.shut_down().await {
+        tracing::warn!(error = %e, "Failed to shut down MCP server after its handshake");
+    }
+    let info = info.ok_or_else(|| {
+        AppError::Mcp("The server did not declare itself on initialization".to_string())
+    })?;
+    Ok(ServerHandshake {
+        name: info.server_info.name,
+        version: info.server_info.version,
+        protocol_version: info.protocol_version,
+        capabilities: serde_json::to_value(&info.capabilities)?,
+        instructions: info.instructions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(unix)]
+    fn executable(dir: &std::path::Path, name: &str) -> std::path::PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let file = dir.join(name);
+        std::fs::write(&file, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&file, std::fs::Permissions::from_mode(0o755)).unwrap();
+        file
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn mcp_config_checks_report_each_failure_class() {
+        use crate::features::mcp_connection::validation::{
+            check_config, expand_placeholders, HostEnvironment,
+        };
+        use crate::features::mcp_connection::{
+            ConfigIssueKind, ConfigIssueSeverity, MCPConfigValidation, MCPConnectionConfig,
+        };
+
+        let bin = tempfile::tempdir().unwrap();
+        let server = executable(bin.path(), "echo-server");
+        executable(bin.path(), "tool.cmd");
+        // Not executable, so not a command
+        std::fs::write(bin.path().join("notes.txt"), "").unwrap();
+        let host = HostEnvironment {
+            path: Some(bin.path().as_os_str().to_owned()),
+            extensions: vec![".cmd".to_string()],
+            variables: ["TOKEN".to_string()].into_iter().collect(),
+        };
+        let config = |r#type: &str, url: &str, env_vars: Option<&str>, headers: Option<&str>| {
+            MCPConnectionConfig {
+                url: url.to_string(),
+                r#type: r#type.to_string(),
+                headers: headers.map(str::to_string),
+                auth_token: None,
+                env_vars: env_vars.map(str::to_string),
+                runtime_path: None,
+            }
+        };
+        let kinds = |report: &MCPConfigValidation| {
+            report
+                .issues
+                .iter()
+                .map(|issue| (issue.kind, issue.severity))
+                .collect::<Vec<_>>()
+        };
+        let error = |kind| vec![(kind, ConfigIssueSeverity::Error)];
+
+        let report = check_config(
+            &config(
+                "stdio",
+                "echo-server --name 'a b'",
+                Some(r#"{"TOKEN": "${TOKEN}"}"#),
+                Some(""),
+            ),
+            &host,
+        );
+        assert!(report.valid, "{:?}", report.issues);
+        assert!(report.issues.is_empty());
+        assert_eq!(
+            report.resolved_command.as_deref(),
+            Some(server.to_str().unwrap())
+        );
+
+        // Bare names find their shims, paths are taken as they are
+        let report = check_config(&config("stdio", "tool --x", None, None), &host);
+        assert!(report.resolved_command.unwrap().ends_with("tool.cmd"));
+        let absolute = format!("'{}' --x", server.display());
+        assert!(check_config(&config("stdio", &absolute, None, None), &host).valid);
+
+        let report = check_config(&config("websocket", "ws://localhost", None, None), &host);
+        assert_eq!(kinds(&report), error(ConfigIssueKind::UnsupportedType));
+        assert!(!report.valid);
+
+        for url in ["echo-server 'unterminated", "   "] {
+            let report = check_config(&config("stdio", url, None, None), &host);
+            assert_eq!(kinds(&report), error(ConfigIssueKind::InvalidArgs), "{url}");
+        }
+
+        for url in ["no-such-server", "notes.txt", "/no/such/server"] {
+            let report = check_config(&config("stdio", url, None, None), &host);
+            assert_eq!(
+                kinds(&report),
+                error(ConfigIssueKind::CommandNotFound),
+                "{url}"
+            );
+        }
+
+        // Commands the bundled runtimes provide only warn
+        let report = check_config(&config("stdio", "npx -y @scope/server", None, None), &host);
+        assert!(report.valid);
+        assert_eq!(
+            kinds(&report),
+            vec![(
+                ConfigIssueKind::CommandNotFound,
+                ConfigIssueSeverity::Warning
+            )]
+        );
+
+        for env in ["{", "[]", r#"{"PORT": 8080}"#] {
+            let report = check_config(&config("stdio", "echo-server", Some(env), None), &host);
+            assert_eq!(kinds(&report), error(ConfigIssueKind::InvalidEnv), "{env}");
+        }
+        let report = check_config(
+            &config("stdio", "echo-server", Some(r#"{"PORT": 8080}"#), None),
+            &host,
+        );
+        assert!(report.issues[0].message.contains("PORT"));
+
+        let report = check_config(
+            &config(
+                "sse",
+                "https://example.com/sse",
+                None,
+                Some(r#"{"X-Retries": 3}"#),
+            ),
+            &host,
+        );
+        assert_eq!(kinds(&report), error(ConfigIssueKind::InvalidHeaders));
+
+        assert!(
+            check_config(
+                &config("sse", "https://example.com/sse", None, Some("")),
+                &host
+            )
+            .valid
+        );
+        for url in ["not a url", "ftp://example.com/mcp", "https://"] {
+            let report = check_config(&config("streamable-http", url, None, None), &host);
+            assert_eq!(kinds(&report), error(ConfigIssueKind::InvalidUrl), "{url}");
+        }
+
+        // Placeholders nothing fills in are listed, and only warn
+        let report = check_config(
+            &config(
+                "stdio",
+                "echo-server",
+                Some(r#"{"A": "${TOKEN}", "B": "{{API_KEY}}", "C": "x ${MISSING} y ${MISSING}"}"#),
+                None,
+            ),
+            &host,
+        );
+        assert!(report.valid);
+        assert_eq!(report.missing_placeholders, vec!["API_KEY", "MISSING"]);
+        assert_eq!(
+            kinds(&report),
+            vec![(
+                ConfigIssueKind::MissingPlaceholder,
+                ConfigIssueSeverity::Warning
+            )]
+        );
+
+        let lookup = |name: &str| (name == "API_KEY").then(|| "secret".to_string());
+        assert_eq!(
+            expand_placeholders("Bearer {{API_KEY}} ${OTHER} costs $5 {x}", lookup),
+            "Bearer secret ${OTHER} costs $5 {x}"
+        );
+    }
+
+    #[test]
+    #[ignore = "requires python on PATH"]
+    fn mcp_handshake_reads_what_the_server_declares() {
+        use crate::features::mcp_connection::validation::{
+            executable_extensions, handshake, resolve_command,
+        };
+        use crate::features::tool::mcp_client::MCPClientService;
+        use std::time::Duration;
+
+        // A dummy MCP server that answers initialize and echoes an empty
+        // result to everything else
+        const ECHO_SERVER: &str = r#"
+import json, sys
+for line in sys.stdin:
+    message = json.loads(line)
+    if "id" not in message:
+        continue
+    result = {}
+    if message.get("method") == "initialize":
+        result = {
+            "protocolVersion": message["params"]["protocolVersion"],
+            "capabilities": {"tools": {"listChanged": False}},
+            "serverInfo": {"name": "echo", "version": "1.2.3"},
+            "instructions": "Echoes",
+        }
+    print(json.dumps({"jsonrpc": "2.0", "id": message["id"], "result": result}), flush=True)
+"#;
+        const SILENT_SERVER: &str = "import sys\nfor line in sys.stdin:\n    pass\n";
+
+        let path = std::env::var_os("PATH");
+        let python = ["python3", "python"]
+            .into_iter()
+            .find_map(|name| resolve_command(name, path.as_deref(), &executable_extensions()))
+            .expect("no Python on PATH to run the dummy MCP server");
+        let python = python.to_string_lossy().to_string();
+        let run = |script: &str, timeout: Duration| {
+            let start = async {
+                let client = MCPClientService::stdio_client(
+                    &python,
+                    vec!["-c".to_string(), script.to_string()],
+                    None,
+                )?;
+                MCPClientService::start(client, "dummy server").await
+            };
+            tokio::runtime::Runtime::new()
+                .unwrap()
+                .block_on(handshake(start, timeout))
+        };
+
+        let declared = run(ECHO_SERVER, Duration::from_secs(5)).unwrap();
+        assert_eq!(declared.name, "echo");
+        assert_eq!(declared.version, "1.2.3");
+        assert_eq!(declared.instructions.as_deref(), Some("Echoes"));
+        assert!(declared.capabilities.get("tools").is_some());
+
+        let error = run(SILENT_SERVER, Duration::from_millis(500)).unwrap_err();
+        assert!(error.to_string().contains("did not complete its handshake"));
+    }
+
+    #[test]
+    fn mcp_auth_tokens_are_sent_as_bearer_headers_to_remote_servers() {
+        use crate::features::mcp_connection::validation::{
+            auth_headers, check_config, HostEnvironment,
+        };
+        use crate::features::mcp_connection::{
+            ConfigIssueKind, ConfigIssueSeverity, MCPConnectionConfig,
+        };
+
+        let parsed = |headers: Option<String>| -> serde_json::Value {
+            serde_json::from_str(&headers.unwrap()).unwrap()
+        };
+        assert_eq!(
+            parsed(auth_headers(
+                "streamable-http",
+                Some(r#"{"Accept": "application/json"}"#),
+                Some("tok123")
+            )),
+            serde_json::json!({ "Accept": "application/json", "Authorization": "Bearer tok123" })
+        );
+        // A token naming its scheme is sent as written
+        assert_eq!(
+            parsed(auth_headers("sse", None, Some("Basic dXNlcjpwYXNz"))),
+            serde_json::json!({ "Authorization": "Basic dXNlcjpwYXNz" })
+        );
+        // A header set by hand wins
+        assert_eq!(
+            parsed(auth_headers(
+                "sse",
+                Some(r#"{"authorization": "Token abc"}"#),
+                Some("tok123")
+            )),
+            serde_json::json!({ "authorization": "Token abc" })
+        );
+        // Stdio headers stand in for env vars, so no token is added
+        assert_eq!(
+            auth_headers("stdio", Some("{}"), Some("tok123")),
+            Some("{}".to_string())
+        );
+        assert_eq!(auth_headers("sse", Some(" "), None), None);
+        assert_eq!(auth_headers("sse", None, Some("  ")), None);
+
+        let host = HostEnvironment {
+            path: None,
+            extensions: Vec::new(),
+            variables: std::collections::HashSet::new(),
+        };
+        let check = |r#type: &str, url: &str, auth_token: &str| {
+            check_config(
+                &MCPConnectionConfig {
+                    url: url.to_string(),
+                    r#type: r#type.to_string(),
+                    headers: None,
+                    auth_token: Some(auth_token.to_string()),
+                    env_vars: None,
+                    runtime_path: None,
+                },
+                &host,
+            )
+        };
+        let insecure = vec![(ConfigIssueKind::InsecureAuth, ConfigIssueSeverity::Warning)];
+        let kinds = |r#type: &str, url: &str, auth_token: &str| {
+            check(r#type, url, auth_token)
+                .issues
+                .iter()
+                .map(|issue| (issue.kind, issue.severity))
+                .collect::<Vec<_>>()
+        };
+        assert!(kinds("sse", "https://mcp.example.com/sse", "tok123").is_empty());
+        assert!(kinds("streamable-http", "http://localhost:3000/mcp", "tok123").is_empty());
+        assert!(kinds("streamable-http", "http://[::1]:3000/mcp", "tok123").is_empty());
+        assert_eq!(
+            kinds("streamable-http", "http://mcp.example.com/mcp", "tok123"),
+            insecure
+        );
+        assert!(check("streamable-http", "http://mcp.example.com/mcp", "tok123").valid);
+        assert!(kinds("stdio", "echo-server", "tok123").contains(&insecure[0]));
+        assert!(kinds("sse", "https://mcp.example.com/sse", " ").is_empty());
+        assert_eq!(
+            check("sse", "https://mcp.example.com/sse", "${MCP_TOKEN}").missing_placeholders,
+            vec!["MCP_TOKEN"]
+        );
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        let has_column: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM pragma_table_info('mcp_server_connections') WHERE name = 'auth_token')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(has_column);
+    }
+}
@@ -0,0 +1,305 @@
+//! Message changes requested by the frontend.
+//!
+//! `prepare_messages` rebuilds the conversation from each message's role,
+//! chat, timestamp and tool call link, so those are fixed once a message is
+//! created, and a change to them is refused. Metadata is merged key by key
+//! unless the caller asks to replace it, and the keys the chat service keeps
+//! (attachments, token usage, turn timeline, variants) can only be written by it,
+//! through `MessageService::update` and `MessageService::update_metadata`.
+
+use super::models::Message;
+use crate::error::AppError;
+use crate::features::chat::timeline::TURN_TIMELINE_METADATA_KEY;
+use crate::features::chat::variants::{VARIANTS_METADATA_KEY, VARIANT_INDEX_METADATA_KEY};
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+/// Metadata keys only internal paths may set, change or remove.
+pub const RESERVED_METADATA_KEYS: &[&str] = &[
+    "files",
+    "fileHashes",
+    "tokenUsage",
+    TURN_TIMELINE_METADATA_KEY,
+    VARIANTS_METADATA_KEY,
+    VARIANT_INDEX_METADATA_KEY,
+];
+
+/// A change to a message from the frontend. Fields left `None` are kept.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MessageUpdate {
+    pub content: Option<String>,
+    /// An empty string clears the reasoning
+    pub reasoning: Option<String>,
+    /// JSON object merged into the metadata; a `null` value removes its key
+    pub metadata: Option<Value>,
+    /// Replace the metadata with `metadata` instead of merging it
+    #[serde(default)]
+    pub replace_metadata: bool,
+    // Fixed at creation; only accepted when unchanged
+    pub role: Option<String>,
+    pub chat_id: Option<String>,
+    pub timestamp: Option<i64>,
+    pub tool_call_id: Option<String>,
+}
+
+/// `message` with `update` applied, or a validation error naming the first
+/// field or metadata key the frontend may not change.
+pub fn apply_update(message: &Message, update: MessageUpdate) -> Result<Message, AppError> {
+    refuse_change("role", Some(&message.role), update.role.as_ref())?;
+    refuse_change("chat_id", Some(&message.chat_id), update.chat_id.as_ref())?;
+    refuse_change(
+        "timestamp",
+        Some(&message.timestamp),
+        update.timestamp.as_ref(),
+    )?;
+    refuse_change(
+        "tool_call_id",
+        message.tool_call_id.as_ref(),
+        update.tool_call_id.as_ref(),
+    )?;
+
+    let mut updated = message.clone();
+    if let Some(content) = update.content {
+        updated.content = content;
+    }
+    if let Some(reasoning) = update.reasoning {
+        updated.reasoning = Some(reasoning).filter(|r| !r.is_empty());
+    }
+    if let Some(patch) = update.metadata {
+        let Value::Object(patch) = patch else {
+            return Err(AppError::Validation(
+                "Message metadata must be a JSON object".to_string(),
+            ));
+        };
+        let stored = stored_metadata(message.metadata.as_deref());
+        let metadata = if update.replace_metadata {
+            replace_metadata(&stored, patch)?
+        } else {
+            merge_metadata(stored, patch)?
+        };
+        updated.metadata = Some(Value::Object(metadata).to_string());
+    }
+    Ok(updated)
+}
+
+fn refuse_change<T: PartialEq>(
+    field: &str,
+    current: Option<&T>,
+    requested: Option<&T>,
+) -> Result<(), AppError> {
+    match requested {
+        Some(value) if current != Some(value) => Err(AppError::Validation(format!(
+            "Message field `{field}` cannot be changed after creation"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+fn stored_metadata(metadata: Option<&str>) -> Map<String, Value> {
+    metadata
+        .and_then(|m| serde_json::from_str::<Value>(m).ok())
+        .and_then(|v| match v {
+            Value::Object(map) => Some(map),
+            _ => None,
+        })
+        .unwrap_or_default()
+}
+
+fn refuse_reserved(patch: &Map<String, Value>) -> Result<(), AppError> {
+    RESERVED_METADATA_KEYS
+        .iter()
+        .find(|k| patch.contains_key(**k))
+        .map_or(Ok(()), |key| {
+            Err(AppError::Validation(format!(
+                "Message metadata key `{key}` is managed by the app and cannot be set"
+            )))
+        })
+}
+
+fn merge_metadata(
+    mut stored: Map<String, Value>,
+    patch: Map<String, Value>,
+) -> Result<Map<String, Value>, AppError> {
+    refuse_reserved(&patch)?;
+    for (key, value) in patch {
+        if value.is_null() {
+            stored.remove(&key);
+        } else {
+            stored.insert(key, value);
+        }
+    }
+    Ok(stored)
+}
+
+/// `patch` as the new metadata, keeping the reserved keys already stored.
+fn replace_metadata(
+    stored: &Map<String, Value>,
+    patch: Map<String, Value>,
+) -> Result<Map<String, Value>, AppError> {
+    refuse_reserved(&patch)?;
+    let mut metadata = patch;
+    for key in RESERVED_METADATA_KEYS {
+        if let Some(value) = stored.get(*key) {
+            metadata.insert((*key).to_string(), value.clone());
+        }
+    }
+    Ok(metadata)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::guarded_message_service;
+
+    #[test]
+    fn frontend_message_updates_cannot_touch_structure() {
+        use crate::error::AppError;
+        use crate::features::message::client_update::MessageUpdate;
+        use serde_json::json;
+
+        let service = guarded_message_service();
+        let attempts = [
+            (
+                "role",
+                MessageUpdate {
+                    role: Some("user".to_string()),
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "chat_id",
+                MessageUpdate {
+                    chat_id: Some("c2".to_string()),
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "timestamp",
+                MessageUpdate {
+                    timestamp: Some(5),
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "tool_call_id",
+                MessageUpdate {
+                    tool_call_id: Some("call_1".to_string()),
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "tokenUsage",
+                MessageUpdate {
+                    metadata: Some(json!({ "tokenUsage": null })),
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "files",
+                MessageUpdate {
+                    metadata: Some(json!({ "files": ["/etc/passwd"] })),
+                    replace_metadata: true,
+                    ..MessageUpdate::default()
+                },
+            ),
+            (
+                "turn_timeline",
+                MessageUpdate {
+                    content: Some("Edited".to_string()),
+                    metadata: Some(json!({ "turn_timeline": [] })),
+                    ..MessageUpdate::default()
+                },
+            ),
+        ];
+        for (field, update) in attempts {
+            match service.update_from_client("a1", update) {
+                Err(AppError::Validation(message)) => {
+                    assert!(message.contains(&format!("`{field}`")), "{message}");
+                }
+                other => panic!("{field} change was not refused: {other:?}"),
+            }
+        }
+        // Nothing was written by the refused attempts
+        let stored = service.get_by_id("a1").unwrap().unwrap();
+        assert_eq!(stored.content, "Answer");
+        assert_eq!(stored.role, "assistant");
+        assert!(matches!(
+            service.update_from_client(
+                "a1",
+                MessageUpdate {
+                    metadata: Some(json!(["not", "an", "object"])),
+                    ..MessageUpdate::default()
+                }
+            ),
+            Err(AppError::Validation(_))
+        ));
+        assert!(matches!(
+            service.update_from_client("missing", MessageUpdate::default()),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn frontend_message_updates_merge_metadata_and_keep_internal_keys() {
+        use crate::features::message::client_update::MessageUpdate;
+        use serde_json::json;
+
+        let service = guarded_message_service();
+        let metadata = |service: &crate::features::message::MessageService| {
+            let stored = service.get_by_id("a1").unwrap().unwrap();
+            serde_json::from_str::<serde_json::Value>(&stored.metadata.unwrap()).unwrap()
+        };
+
+        // Unchanged structural fields are accepted, so whole messages can be sent back
+        let updated = service
+            .update_from_client(
+                "a1",
+                MessageUpdate {
+                    content: Some("Edited answer".to_string()),
+                    role: Some("assistant".to_string()),
+                    chat_id: Some("c1".to_string()),
+                    timestamp: Some(100),
+                    metadata: Some(json!({ "pinned": true, "model": null })),
+                    ..MessageUpdate::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(updated.content, "Edited answer");
+        assert_eq!(
+            metadata(&service),
+            json!({ "tokenUsage": { "input_tokens": 10 }, "turn_timeline": [], "pinned": true })
+        );
+
+        // Replacing keeps what the app manages
+        service
+            .update_from_client(
+                "a1",
+                MessageUpdate {
+                    metadata: Some(json!({ "label": "draft" })),
+                    replace_metadata: true,
+                    ..MessageUpdate::default()
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            metadata(&service),
+            json!({ "tokenUsage": { "input_tokens": 10 }, "turn_timeline": [], "label": "draft" })
+        );
+
+        // Internal paths still write reserved keys and structural fields
+        service
+            .update_metadata(
+                "a1".to_string(),
+                Some(json!({ "tokenUsage": { "input_tokens": 20 } }).to_string()),
+            )
+            .unwrap();
+        service
+            .update("a1".to_string(), "Regenerated".to_string(), None, Some(200))
+            .unwrap();
+        let stored = service.get_by_id("a1").unwrap().unwrap();
+        assert_eq!(
+            (stored.content.as_str(), stored.timestamp),
+            ("Regenerated", 200)
+        );
+        assert_eq!(metadata(&service)["tokenUsage"]["input_tokens"], 20);
+    }
+}
@@ -1,8 +1,14 @@
+use super::client_update::MessageUpdate;
+use super::diff::{DiffMode, MessageDiff};
 use super::models::Message;
 use crate::error::AppError;
 use crate::state::AppState;
+use std::time::Duration;
 use tauri::State;
 
+/// Longest a `diff_messages` call may take.
+const DIFF_TIMEOUT: Duration = Duration::from_secs(10);
+
 #[tauri::command]
 pub fn create_message(
     id: String,
@@ -31,16 +37,36 @@ pub fn get_messages(chat_id: String, state: State<'_, AppState>) -> Result<Vec<M
         .map_err(|e| AppError::Generic(e.to_string()))
 }
 
+/// Change a message's content, reasoning or metadata. `metadata` is merged
+/// into the stored metadata unless `replace_metadata` is set. `role`,
+/// `chat_id`, `timestamp` and `tool_call_id` are only accepted unchanged.
+#[allow(clippy::too_many_arguments)]
 #[tauri::command]
 pub fn update_message(
     id: String,
-    content: String,
+    content: Option<String>,
+    reasoning: Option<String>,
+    metadata: Option<serde_json::Value>,
+    replace_metadata: Option<bool>,
+    role: Option<String>,
+    chat_id: Option<String>,
+    timestamp: Option<i64>,
+    tool_call_id: Option<String>,
     state: State<'_, AppState>,
-) -> Result<(), AppError> {
-    state
-        .message_service
-        .update(id, content, None, None)
-        .map_err(|e| AppError::Generic(e.to_string()))
+) -> Result<Message, AppError> {
+    state.message_service.update_from_client(
+        &id,
+        MessageUpdate {
+            content,
+            reasoning,
+            metadata,
+            replace_metadata: replace_metadata.unwrap_or(false),
+            role,
+            chat_id,
+            timestamp,
+            tool_call_id,
+        },
+    )
 }
 
 #[tauri::command]
@@ -65,3 +91,22 @@ pub fn cancel_message(chat_id: String, state: State<'_, AppState>) -> Result<(),
 
     Ok(())
 }
+
+/// Diff two assistant answers for the compare view; `mode` defaults to
+/// `auto`. Runs off the main thread.
+#[tauri::command]
+pub async fn diff_messages(
+    message_id_a: String,
+    message_id_b: String,
+    mode: Option<DiffMode>,
+    state: State<'_, AppState>,
+) -> Result<MessageDiff, AppError> {
+    let service = state.message_service.clone();
+    let task = tokio::task::spawn_blocking(move || {
+        service.diff_messages(&message_id_a, &message_id_b, mode.unwrap_or(DiffMode::Auto))
+    });
+    tokio::time::timeout(DIFF_TIMEOUT, task)
+        .await
+        .map_err(|_| AppError::Generic("Comparing the messages took too long".to_string()))?
+        .map_err(|e| AppError::Generic(format!("Comparing the messages failed: {e}")))?
+}
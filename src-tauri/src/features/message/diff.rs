@@ -0,0 +1,351 @@
+//! Differences between two answers, for the compare view.
+//!
+//! The contents are split into words (runs of letters and digits, runs of
+//! whitespace, and single punctuation characters) or into lines, and diffed
+//! token by token. Spans carry byte offsets into each UTF-8 content; they
+//! always fall on character boundaries, so `content[start..end]` is valid in
+//! Rust, and the frontend slices the `TextEncoder` bytes of the content with
+//! them rather than the UTF-16 string.
+
+use serde::{Deserialize, Serialize};
+use similar::{Algorithm, DiffTag};
+use std::time::{Duration, Instant};
+
+/// Combined size above which the diff is taken line by line whatever the
+/// mode, to stay fast.
+pub const LINE_MODE_ABOVE_BYTES: usize = 200 * 1024;
+/// Share of lines inside code fences from which `auto` diffs by line.
+const CODE_SHARE_FOR_LINES: f64 = 0.3;
+/// How long the diff looks for the smallest set of changes before it
+/// settles for a coarser one.
+const DIFF_DEADLINE: Duration = Duration::from_secs(2);
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffMode {
+    /// Line by line for code-heavy or large content, word by word otherwise
+    Auto,
+    Word,
+    Line,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DiffOpKind {
+    Equal,
+    Insert,
+    Delete,
+}
+
+/// One span of the diff: `a_start..a_end` in the first content and
+/// `b_start..b_end` in the second. An insert has an empty range in `a` at
+/// the position it goes, a delete an empty range in `b`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DiffSpan {
+    pub op: DiffOpKind,
+    pub a_start: usize,
+    pub a_end: usize,
+    pub b_start: usize,
+    pub b_end: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DiffStats {
+    /// Inserted and deleted bytes over the bytes of both contents, 0–100
+    pub percent_changed: f64,
+    /// Words or lines, by the mode used; whitespace does not count as a word
+    pub added: usize,
+    pub removed: usize,
+}
+
+/// Result of `diff_messages`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MessageDiff {
+    /// `word` or `line`, as chosen for `auto` or forced by size
+    pub mode: DiffMode,
+    pub spans: Vec<DiffSpan>,
+    pub stats: DiffStats,
+}
+
+/// Diff `a` against `b`.
+pub fn diff_texts(a: &str, b: &str, mode: DiffMode) -> MessageDiff {
+    let mode = resolve_mode(a, b, mode);
+    let a_tokens = tokenize(a, mode);
+    let b_tokens = tokenize(b, mode);
+    let a_offsets = offsets(&a_tokens);
+    let b_offsets = offsets(&b_tokens);
+
+    let ops = similar::capture_diff_slices_deadline(
+        Algorithm::Patience,
+        &a_tokens,
+        &b_tokens,
+        Some(Instant::now() + DIFF_DEADLINE),
+    );
+
+    let mut spans: Vec<DiffSpan> = Vec::new();
+    let mut stats = DiffStats {
+        percent_changed: 0.0,
+        added: 0,
+        removed: 0,
+    };
+    let counts = |tokens: &[&str]| {
+        tokens
+            .iter()
+            .filter(|t| mode == DiffMode::Line || !t.trim().is_empty())
+            .count()
+    };
+    for op in &ops {
+        let (tag, a_range, b_range) = op.as_tag_tuple();
+        let a_span = (a_offsets[a_range.start], a_offsets[a_range.end]);
+        let b_span = (b_offsets[b_range.start], b_offsets[b_range.end]);
+        match tag {
+            DiffTag::Equal => push_span(&mut spans, DiffOpKind::Equal, a_span, b_span),
+            DiffTag::Delete | DiffTag::Replace | DiffTag::Insert => {
+                if !a_range.is_empty() {
+                    stats.removed += counts(&a_tokens[a_range]);
+                    push_span(&mut spans, DiffOpKind::Delete, a_span, (b_span.0, b_span.0));
+                }
+                if !b_range.is_empty() {
+                    stats.added += counts(&b_tokens[b_range]);
+                    push_span(&mut spans, DiffOpKind::Insert, (a_span.1, a_span.1), b_span);
+                }
+            }
+        }
+    }
+
+    let total = a.len() + b.len();
+    if total > 0 {
+        let changed: usize = spans
+            .iter()
+            .filter(|s| s.op != DiffOpKind::Equal)
+            .map(|s| (s.a_end - s.a_start) + (s.b_end - s.b_start))
+            .sum();
+        #[allow(clippy::cast_precision_loss)]
+        let percent = changed as f64 * 100.0 / total as f64;
+        stats.percent_changed = (percent * 10.0).round() / 10.0;
+    }
+    MessageDiff { mode, spans, stats }
+}
+
+/// The mode `mode` runs as for these contents: large contents always go by
+/// line, and `auto` does too when much of the content is code.
+pub fn resolve_mode(a: &str, b: &str, mode: DiffMode) -> DiffMode {
+    if a.len() + b.len() > LINE_MODE_ABOVE_BYTES {
+        return DiffMode::Line;
+    }
+    match mode {
+        DiffMode::Auto if code_share(a).max(code_share(b)) >= CODE_SHARE_FOR_LINES => {
+            DiffMode::Line
+        }
+        DiffMode::Auto => DiffMode::Word,
+        mode => mode,
+    }
+}
+
+/// Share of the lines of `text` inside fenced code blocks, fences included.
+fn code_share(text: &str) -> f64 {
+    let mut lines = 0_u32;
+    let mut code_lines = 0_u32;
+    let mut in_fence = false;
+    for line in text.lines() {
+        lines += 1;
+        let fence = line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~");
+        if fence || in_fence {
+            code_lines += 1;
+        }
+        if fence {
+            in_fence = !in_fence;
+        }
+    }
+    if lines == 0 {
+        0.0
+    } else {
+        f64::from(code_lines) / f64::from(lines)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum CharClass {
+    Space,
+    Word,
+    Other,
+}
+
+fn tokenize(text: &str, mode: DiffMode) -> Vec<&str> {
+    if mode == DiffMode::Line {
+        return text.split_inclusive('\n').collect();
+    }
+    let class = |c: char| {
+        if c.is_whitespace() {
+            CharClass::Space
+        } else if c.is_alphanumeric() || c == '_' {
+            CharClass::Word
+        } else {
+            CharClass::Other
+        }
+    };
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut current: Option<CharClass> = None;
+    for (i, c) in text.char_indices() {
+        let next = class(c);
+        // Punctuation is a token per character
+        if current.is_some_and(|cur| cur != next || cur == CharClass::Other) {
+            tokens.push(&text[start..i]);
+            start = i;
+        }
+        current = Some(next);
+    }
+    if start < text.len() {
+        tokens.push(&text[start..]);
+    }
+    tokens
+}
+
+/// Byte offset of each token's start, followed by the total length.
+fn offsets(tokens: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(tokens.len() + 1);
+    let mut offset = 0;
+    offsets.push(offset);
+    for token in tokens {
+        offset += token.len();
+        offsets.push(offset);
+    }
+    offsets
+}
+
+/// Add a span, extending the previous one when it is of the same kind.
+fn push_span(spans: &mut Vec<DiffSpan>, op: DiffOpKind, a: (usize, usize), b: (usize, usize)) {
+    if let Some(last) = spans.last_mut() {
+        if last.op == op && last.a_end == a.0 && last.b_end == b.0 {
+            last.a_end = a.1;
+            last.b_end = b.1;
+            return;
+        }
+    }
+    spans.push(DiffSpan {
+        op,
+        a_start: a.0,
+        a_end: a.1,
+        b_start: b.0,
+        b_end: b.1,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    /// Check the spans cover both contents in order, and rebuild each side
+    /// from them.
+    fn replay_diff(a: &str, b: &str, diff: &crate::features::message::diff::MessageDiff) {
+        use crate::features::message::diff::DiffOpKind;
+
+        let (mut a_pos, mut b_pos) = (0, 0);
+        let (mut rebuilt_a, mut rebuilt_b) = (String::new(), String::new());
+        for span in &diff.spans {
+            assert_eq!((span.a_start, span.b_start), (a_pos, b_pos), "{span:?}");
+            match span.op {
+                DiffOpKind::Equal => {
+                    assert_eq!(&a[span.a_start..span.a_end], &b[span.b_start..span.b_end])
+                }
+                DiffOpKind::Insert => assert_eq!(span.a_start, span.a_end),
+                DiffOpKind::Delete => assert_eq!(span.b_start, span.b_end),
+            }
+            rebuilt_a.push_str(&a[span.a_start..span.a_end]);
+            rebuilt_b.push_str(&b[span.b_start..span.b_end]);
+            (a_pos, b_pos) = (span.a_end, span.b_end);
+        }
+        assert_eq!((rebuilt_a.as_str(), rebuilt_b.as_str()), (a, b));
+    }
+
+    #[test]
+    fn answer_diffs_cover_identical_and_different_answers() {
+        use crate::features::message::diff::{diff_texts, DiffMode, DiffOpKind};
+
+        let answer = "Paris is the capital of France.";
+        let same = diff_texts(answer, answer, DiffMode::Auto);
+        assert_eq!(same.mode, DiffMode::Word);
+        assert_eq!(same.spans.len(), 1);
+        assert_eq!(same.spans[0].op, DiffOpKind::Equal);
+        assert_eq!(same.stats.percent_changed, 0.0);
+        assert_eq!((same.stats.added, same.stats.removed), (0, 0));
+        replay_diff(answer, answer, &same);
+
+        let other = "42";
+        let different = diff_texts(answer, other, DiffMode::Word);
+        assert!(different.spans.iter().all(|s| s.op != DiffOpKind::Equal));
+        assert_eq!(different.stats.percent_changed, 100.0);
+        assert_eq!((different.stats.added, different.stats.removed), (1, 7));
+        replay_diff(answer, other, &different);
+
+        let empty = diff_texts("", "", DiffMode::Auto);
+        assert!(empty.spans.is_empty());
+        assert_eq!(empty.stats.percent_changed, 0.0);
+    }
+
+    #[test]
+    fn answer_diffs_use_byte_offsets_through_multibyte_text() {
+        use crate::features::message::diff::{diff_texts, DiffMode, DiffOpKind};
+
+        let a = "Café ☕ costs 3€ in Hà Nội 🇻🇳, cheap.";
+        let b = "Café ☕ costs 4€ in Hà Nội 🇻🇳, very cheap.";
+        let diff = diff_texts(a, b, DiffMode::Word);
+        replay_diff(a, b, &diff);
+
+        let changed: Vec<(DiffOpKind, &str)> = diff
+            .spans
+            .iter()
+            .filter(|s| s.op != DiffOpKind::Equal)
+            .map(|s| match s.op {
+                DiffOpKind::Delete => (s.op, &a[s.a_start..s.a_end]),
+                _ => (s.op, &b[s.b_start..s.b_end]),
+            })
+            .collect();
+        assert_eq!(
+            changed,
+            [
+                (DiffOpKind::Delete, "3"),
+                (DiffOpKind::Insert, "4"),
+                (DiffOpKind::Insert, "very "),
+            ]
+        );
+        // "Café ☕ costs " is 16 bytes for 13 characters
+        assert_eq!(diff.spans[1].a_start, "Café ☕ costs ".len());
+        assert_eq!(diff.spans[1].a_start, 16);
+        assert_eq!((diff.stats.added, diff.stats.removed), (2, 1));
+    }
+
+    #[test]
+    fn answer_diffs_go_line_by_line_for_code_and_large_answers() {
+        use crate::features::message::diff::{
+            diff_texts, resolve_mode, DiffMode, DiffOpKind, LINE_MODE_ABOVE_BYTES,
+        };
+
+        let a = "Use this:\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\n";
+        let b = "Use this:\n```rust\nfn main() {\n    println!(\"hello\");\n}\n```\n";
+        let diff = diff_texts(a, b, DiffMode::Auto);
+        assert_eq!(diff.mode, DiffMode::Line);
+        assert_eq!((diff.stats.added, diff.stats.removed), (1, 1));
+        let deleted = diff
+            .spans
+            .iter()
+            .find(|s| s.op == DiffOpKind::Delete)
+            .unwrap();
+        assert_eq!(
+            &a[deleted.a_start..deleted.a_end],
+            "    println!(\"hi\");\n"
+        );
+        replay_diff(a, b, &diff);
+        // Asked for words, prose stays word by word
+        assert_eq!(diff_texts(a, b, DiffMode::Word).mode, DiffMode::Word);
+
+        let long_a = "word ".repeat(LINE_MODE_ABOVE_BYTES / 10 + 1);
+        let long_b = format!("{long_a}\nmore");
+        assert_eq!(
+            resolve_mode(&long_a, &long_b, DiffMode::Word),
+            DiffMode::Line
+        );
+        let diff = diff_texts(&long_a, &long_b, DiffMode::Word);
+        assert_eq!(diff.mode, DiffMode::Line);
+        replay_diff(&long_a, &long_b, &diff);
+    }
+}
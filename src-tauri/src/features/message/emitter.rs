@@ -1,11 +1,19 @@
-use crate::constants::TauriEvents;
 use crate::error::AppError;
 use crate::events::{
-    MessageChunkEvent, MessageCompleteEvent, MessageErrorEvent, MessageMetadataUpdatedEvent,
-    MessageStartedEvent, ThinkingChunkEvent,
+    emit_event, ContextTrimmedEvent, MessageCancelledEvent, MessageChunkEvent,
+    MessageCompleteEvent, MessageErrorEvent, MessageMetadataUpdatedEvent, MessageStartedEvent,
+    MessageStreamStatsEvent, MessageVariantsUpdatedEvent, OutputLimitReachedEvent,
+    ThinkingChunkEvent,
 };
-use tauri::{AppHandle, Emitter};
+use crate::features::chat::context_window::ContextTrim;
+use crate::features::quick_action::channel;
+use crate::services::llm::rate_limit::RateLimitKind;
+use crate::services::llm::stream_stats::StreamStatsSnapshot;
+use tauri::AppHandle;
 
+/// Message events for a quick action preview channel (see
+/// `quick_action::channel`) are not sent as message events: chunks go out as
+/// quick action events and the rest is dropped.
 pub struct MessageEmitter {
     app: AppHandle,
 }
@@ -21,16 +29,17 @@ impl MessageEmitter {
         user_message_id: String,
         assistant_message_id: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::MESSAGE_STARTED,
-                MessageStartedEvent {
-                    chat_id,
-                    user_message_id,
-                    assistant_message_id,
-                },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit message-started event: {e}")))
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageStartedEvent {
+                chat_id,
+                user_message_id,
+                assistant_message_id,
+            },
+        )
     }
 
     pub fn emit_message_chunk(
@@ -39,16 +48,17 @@ impl MessageEmitter {
         message_id: String,
         chunk: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::MESSAGE_CHUNK,
-                MessageChunkEvent {
-                    chat_id,
-                    message_id,
-                    chunk,
-                },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit message-chunk event: {e}")))
+        if let Some(run_id) = channel::run_id_of(&chat_id) {
+            return emit_event(&self.app, channel::chunk_event(run_id, chunk));
+        }
+        emit_event(
+            &self.app,
+            MessageChunkEvent {
+                chat_id,
+                message_id,
+                chunk,
+            },
+        )
     }
 
     pub fn emit_thinking_chunk(
@@ -57,16 +67,17 @@ impl MessageEmitter {
         message_id: String,
         chunk: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::THINKING_CHUNK,
-                ThinkingChunkEvent {
-                    chat_id,
-                    message_id,
-                    chunk,
-                },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit thinking-chunk event: {e}")))
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            ThinkingChunkEvent {
+                chat_id,
+                message_id,
+                chunk,
+            },
+        )
     }
 
     pub fn emit_message_complete(
@@ -76,17 +87,41 @@ impl MessageEmitter {
         content: String,
         token_usage: Option<crate::events::TokenUsage>,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::MESSAGE_COMPLETE,
-                MessageCompleteEvent {
-                    chat_id,
-                    message_id,
-                    content,
-                    token_usage,
-                },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit message-complete event: {e}")))
+        self.emit_complete(chat_id, message_id, content, token_usage, false)
+    }
+
+    /// Completion of a cancelled message, with the content stored up to the
+    /// cancel.
+    pub fn emit_partial_message_complete(
+        &self,
+        chat_id: String,
+        message_id: String,
+        content: String,
+    ) -> Result<(), AppError> {
+        self.emit_complete(chat_id, message_id, content, None, true)
+    }
+
+    fn emit_complete(
+        &self,
+        chat_id: String,
+        message_id: String,
+        content: String,
+        token_usage: Option<crate::events::TokenUsage>,
+        partial: bool,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageCompleteEvent {
+                chat_id,
+                message_id,
+                content,
+                token_usage,
+                partial,
+            },
+        )
     }
 
     pub fn emit_message_error(
@@ -95,16 +130,141 @@ impl MessageEmitter {
         message_id: String,
         error: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::MESSAGE_ERROR,
-                MessageErrorEvent {
-                    chat_id,
-                    message_id,
-                    error,
-                },
-            )
-            .map_err(|e| AppError::Generic(format!("Failed to emit message-error event: {e}")))
+        self.emit_error(chat_id, message_id, error, None, None)
+    }
+
+    /// Like `emit_message_error` for a provider error, passing on the wait
+    /// and limit of a rate-limited request.
+    pub fn emit_api_error(
+        &self,
+        chat_id: String,
+        message_id: String,
+        error: &AppError,
+    ) -> Result<(), AppError> {
+        match error {
+            AppError::RateLimited {
+                message,
+                retry_after_ms,
+                limit_kind,
+            } => self.emit_error(
+                chat_id,
+                message_id,
+                message.clone(),
+                *retry_after_ms,
+                *limit_kind,
+            ),
+            AppError::Llm(message) => {
+                self.emit_error(chat_id, message_id, message.clone(), None, None)
+            }
+            error => self.emit_error(chat_id, message_id, error.to_string(), None, None),
+        }
+    }
+
+    fn emit_error(
+        &self,
+        chat_id: String,
+        message_id: String,
+        error: String,
+        retry_after_ms: Option<u64>,
+        limit_kind: Option<RateLimitKind>,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageErrorEvent {
+                chat_id,
+                message_id,
+                error,
+                retry_after_ms,
+                limit_kind,
+            },
+        )
+    }
+
+    pub fn emit_message_cancelled(
+        &self,
+        chat_id: String,
+        message_id: String,
+        content: String,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageCancelledEvent {
+                chat_id,
+                message_id,
+                content,
+            },
+        )
+    }
+
+    /// Tell the UI the answer was cut off at the `limit_bytes` cap of its
+    /// `kind` of output.
+    pub fn emit_output_limit_reached(
+        &self,
+        chat_id: String,
+        message_id: String,
+        kind: &str,
+        limit_bytes: usize,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            OutputLimitReachedEvent {
+                chat_id,
+                message_id,
+                kind: kind.to_string(),
+                limit_bytes,
+            },
+        )
+    }
+
+    pub fn emit_context_trimmed(
+        &self,
+        chat_id: String,
+        message_id: String,
+        trim: &ContextTrim,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            ContextTrimmedEvent {
+                chat_id,
+                message_id,
+                dropped_messages: trim.dropped_messages,
+                estimated_tokens: trim.estimated_tokens,
+                context_window: trim.context_window,
+            },
+        )
+    }
+
+    pub fn emit_message_stream_stats(
+        &self,
+        chat_id: String,
+        message_id: String,
+        stats: StreamStatsSnapshot,
+    ) -> Result<(), AppError> {
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageStreamStatsEvent {
+                chat_id,
+                message_id,
+                est_tokens_so_far: stats.est_tokens_so_far,
+                tokens_per_sec: stats.tokens_per_sec,
+                elapsed_ms: stats.elapsed_ms,
+            },
+        )
     }
 
     pub fn emit_message_metadata_updated(
@@ -112,18 +272,32 @@ impl MessageEmitter {
         chat_id: String,
         message_id: String,
     ) -> Result<(), AppError> {
-        self.app
-            .emit(
-                TauriEvents::MESSAGE_METADATA_UPDATED,
-                MessageMetadataUpdatedEvent {
-                    chat_id,
-                    message_id,
-                },
-            )
-            .map_err(|e| {
-                AppError::Generic(format!(
-                    "Failed to emit message-metadata-updated event: {e}"
-                ))
-            })
+        if channel::run_id_of(&chat_id).is_some() {
+            return Ok(());
+        }
+        emit_event(
+            &self.app,
+            MessageMetadataUpdatedEvent {
+                chat_id,
+                message_id,
+            },
+        )
+    }
+
+    pub fn emit_message_variants_updated(
+        &self,
+        chat_id: String,
+        message_id: String,
+        (variant_index, variant_count): (usize, usize),
+    ) -> Result<(), AppError> {
+        emit_event(
+            &self.app,
+            MessageVariantsUpdatedEvent {
+                chat_id,
+                message_id,
+                variant_index,
+                variant_count,
+            },
+        )
     }
 }
@@ -1,8 +1,11 @@
+pub mod client_update;
 pub mod commands;
+pub mod diff;
 pub mod emitter;
 pub mod models;
 pub mod repository;
 pub mod service;
+pub mod tool_exchange;
 
 pub use emitter::MessageEmitter;
 pub use models::*;
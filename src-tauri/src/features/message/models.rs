@@ -11,4 +11,22 @@ pub struct Message {
     pub assistant_message_id: Option<String>, // For tool_call messages: ID of the assistant message
     pub tool_call_id: Option<String>,         // For tool messages: ID of the tool call
     pub metadata: Option<String>,             // JSON metadata including agent info
+    /// Earlier message of the chat this one replies to (from the metadata)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to_message_id: Option<String>,
+    /// Branch the message was written on, `None` for the chat's main branch
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub branch_id: Option<String>,
+}
+
+/// Metadata key of the message a user message replies to
+pub const REPLY_TO_METADATA_KEY: &str = "reply_to_message_id";
+
+/// The replied-to message id stored in a message's metadata, if any.
+pub fn reply_to_from_metadata(metadata: Option<&str>) -> Option<String> {
+    let meta: serde_json::Value = serde_json::from_str(metadata?).ok()?;
+    meta.get(REPLY_TO_METADATA_KEY)?
+        .as_str()
+        .filter(|id| !id.is_empty())
+        .map(str::to_string)
 }
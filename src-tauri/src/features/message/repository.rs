@@ -1,6 +1,11 @@
-use super::models::Message;
+use super::models::{reply_to_from_metadata, Message};
 use crate::error::AppError;
-use rusqlite::params;
+use crate::features::chat::branches::on_branch_path;
+use crate::features::chat::repository::{active_branch_id, chat_branches};
+use crate::features::encryption::FieldCipher;
+use crate::features::search::sync_message;
+use crate::features::semantic_index::forget_message;
+use rusqlite::{params, Connection, OptionalExtension};
 use std::sync::Arc;
 use tauri::AppHandle;
 
@@ -16,82 +21,130 @@ pub trait MessageRepository: Send + Sync {
         timestamp: Option<i64>,
     ) -> Result<(), AppError>;
     fn delete(&self, id: &str) -> Result<(), AppError>;
-    fn delete_messages_after(&self, chat_id: &str, message_id: &str) -> Result<(), AppError>;
+    /// Delete the given messages in one transaction.
+    fn delete_many(&self, ids: &[String]) -> Result<(), AppError>;
     fn update_metadata(&self, id: &str, metadata: Option<&str>) -> Result<(), AppError>;
 }
 
+const MESSAGE_COLUMNS: &str =
+    "id, chat_id, role, content, reasoning, timestamp, assistant_message_id, tool_call_id, metadata, branch_id";
+
+/// A message row as stored; content, reasoning and metadata may still be
+/// encrypted.
+fn message_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        role: row.get(2)?,
+        content: row.get(3)?,
+        reasoning: row.get(4)?,
+        timestamp: row.get(5)?,
+        assistant_message_id: row.get(6)?,
+        tool_call_id: row.get(7)?,
+        reply_to_message_id: None,
+        metadata: row.get(8)?,
+        branch_id: row.get(9)?,
+    })
+}
+
+/// Decrypt the encrypted columns of a stored message.
+fn open_message(cipher: &FieldCipher, mut message: Message) -> Result<Message, AppError> {
+    message.content = cipher.open(message.content)?;
+    message.reasoning = cipher.open_opt(message.reasoning)?;
+    message.metadata = cipher.open_opt(message.metadata)?;
+    message.reply_to_message_id = reply_to_from_metadata(message.metadata.as_deref());
+    Ok(message)
+}
+
+/// The messages of a chat's active branch in timestamp order, decrypted.
+pub fn read_chat_messages(
+    conn: &Connection,
+    chat_id: &str,
+    cipher: &FieldCipher,
+) -> Result<Vec<Message>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {MESSAGE_COLUMNS} FROM messages WHERE chat_id = ?1 ORDER BY timestamp ASC"
+    ))?;
+    let messages = stmt
+        .query_map(params![chat_id], message_from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    let branches = chat_branches(conn, chat_id)?;
+    let messages = if branches.is_empty() {
+        messages
+    } else {
+        on_branch_path(
+            messages,
+            &branches,
+            active_branch_id(conn, chat_id)?.as_deref(),
+        )
+    };
+    messages
+        .into_iter()
+        .map(|message| open_message(cipher, message))
+        .collect()
+}
+
+/// Stores a new message, encrypted, on the branch its chat has active.
+pub fn insert_message(
+    conn: &Connection,
+    message: &Message,
+    cipher: &FieldCipher,
+) -> Result<(), AppError> {
+    conn.execute(
+        &format!(
+            "INSERT INTO messages ({MESSAGE_COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9,
+                (SELECT active_branch_id FROM chats WHERE id = ?2))"
+        ),
+        params![
+            message.id,
+            message.chat_id,
+            message.role,
+            cipher.seal(&message.content)?,
+            cipher.seal_opt(message.reasoning.as_deref())?,
+            message.timestamp,
+            message.assistant_message_id,
+            message.tool_call_id,
+            cipher.seal_opt(message.metadata.as_deref())?
+        ],
+    )?;
+    sync_message(conn, &message.id)?;
+    Ok(())
+}
+
 pub struct SqliteMessageRepository {
     app: Arc<AppHandle>,
+    cipher: Arc<FieldCipher>,
 }
 
 impl SqliteMessageRepository {
-    pub const fn new(app: Arc<AppHandle>) -> Self {
-        Self { app }
+    pub const fn new(app: Arc<AppHandle>, cipher: Arc<FieldCipher>) -> Self {
+        Self { app, cipher }
     }
 }
 
 impl MessageRepository for SqliteMessageRepository {
     fn create(&self, message: &Message) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
-        conn.execute(
-            "INSERT INTO messages (id, chat_id, role, content, reasoning, timestamp, assistant_message_id, tool_call_id, metadata) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
-            params![message.id, message.chat_id, message.role, message.content, message.reasoning, message.timestamp, message.assistant_message_id, message.tool_call_id, message.metadata],
-        )?;
-        Ok(())
+        insert_message(&conn, message, &self.cipher)
     }
 
     fn get_by_chat_id(&self, chat_id: &str) -> Result<Vec<Message>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
-        let mut stmt = conn.prepare(
-            "SELECT id, chat_id, role, content, reasoning, timestamp, assistant_message_id, tool_call_id, metadata FROM messages WHERE chat_id = ?1 ORDER BY timestamp ASC"
-        )?;
-
-        let messages = stmt
-            .query_map(params![chat_id], |row| {
-                let reasoning: Option<String> = row.get(4)?;
-
-                Ok(Message {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    role: row.get(2)?,
-                    content: row.get(3)?,
-                    reasoning,
-                    timestamp: row.get(5)?,
-                    assistant_message_id: row.get(6)?,
-                    tool_call_id: row.get(7)?,
-                    metadata: row.get(8)?,
-                })
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        Ok(messages)
+        read_chat_messages(&conn, chat_id, &self.cipher)
     }
 
     fn get_by_id(&self, id: &str) -> Result<Option<Message>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
-        let result = conn.query_row(
-            "SELECT id, chat_id, role, content, reasoning, timestamp, assistant_message_id, tool_call_id, metadata FROM messages WHERE id = ?1",
-            params![id],
-            |row| {
-                Ok(Message {
-                    id: row.get(0)?,
-                    chat_id: row.get(1)?,
-                    role: row.get(2)?,
-                    content: row.get(3)?,
-                    reasoning: row.get(4)?,
-                    timestamp: row.get(5)?,
-                    assistant_message_id: row.get(6)?,
-                    tool_call_id: row.get(7)?,
-                    metadata: row.get(8)?,
-                })
-            },
-        );
-
-        match result {
-            Ok(message) => Ok(Some(message)),
-            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-            Err(e) => Err(e.into()),
-        }
+        let message = conn
+            .query_row(
+                &format!("SELECT {MESSAGE_COLUMNS} FROM messages WHERE id = ?1"),
+                params![id],
+                message_from_row,
+            )
+            .optional()?;
+        message
+            .map(|message| open_message(&self.cipher, message))
+            .transpose()
     }
 
     fn update(
@@ -106,14 +159,25 @@ impl MessageRepository for SqliteMessageRepository {
         if let Some(ts) = timestamp {
             conn.execute(
                 "UPDATE messages SET content = ?1, reasoning = ?2, timestamp = ?3 WHERE id = ?4",
-                params![content, reasoning, ts, id],
+                params![
+                    self.cipher.seal(content)?,
+                    self.cipher.seal_opt(reasoning)?,
+                    ts,
+                    id
+                ],
             )?;
         } else {
             conn.execute(
                 "UPDATE messages SET content = ?1, reasoning = ?2 WHERE id = ?3",
-                params![content, reasoning, id],
+                params![
+                    self.cipher.seal(content)?,
+                    self.cipher.seal_opt(reasoning)?,
+                    id
+                ],
             )?;
         }
+        forget_message(&conn, id)?;
+        sync_message(&conn, id)?;
 
         Ok(())
     }
@@ -121,32 +185,18 @@ impl MessageRepository for SqliteMessageRepository {
     fn delete(&self, id: &str) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+        forget_message(&conn, id)?;
         Ok(())
     }
 
-    fn delete_messages_after(&self, chat_id: &str, message_id: &str) -> Result<(), AppError> {
-        let conn = crate::db::get_connection(&self.app)?;
-
-        // Get all messages for this chat ordered by timestamp
-        let mut stmt = conn.prepare(
-            "SELECT id, timestamp FROM messages WHERE chat_id = ?1 ORDER BY timestamp ASC, id ASC",
-        )?;
-
-        let messages: Vec<(String, i64)> = stmt
-            .query_map(params![chat_id], |row| Ok((row.get(0)?, row.get(1)?)))?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        // Find the index of the message we want to keep
-        let message_index = messages
-            .iter()
-            .position(|(id, _)| id == message_id)
-            .ok_or_else(|| AppError::NotFound(format!("Message not found: {message_id}")))?;
-
-        // Delete all messages after this one
-        for (id, _) in messages.iter().skip(message_index + 1) {
-            conn.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+    fn delete_many(&self, ids: &[String]) -> Result<(), AppError> {
+        let mut conn = crate::db::get_connection(&self.app)?;
+        let tx = conn.transaction()?;
+        for id in ids {
+            tx.execute("DELETE FROM messages WHERE id = ?1", params![id])?;
+            forget_message(&tx, id)?;
         }
-
+        tx.commit()?;
         Ok(())
     }
 
@@ -154,7 +204,7 @@ impl MessageRepository for SqliteMessageRepository {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute(
             "UPDATE messages SET metadata = ?1 WHERE id = ?2",
-            params![metadata, id],
+            params![self.cipher.seal_opt(metadata)?, id],
         )?;
         Ok(())
     }
@@ -1,15 +1,27 @@
-use super::models::Message;
+use super::client_update::{self, MessageUpdate};
+use super::diff::{self, DiffMode, MessageDiff};
+use super::models::{reply_to_from_metadata, Message};
 use super::repository::MessageRepository;
+use super::tool_exchange;
 use crate::error::AppError;
+use crate::features::attachment::AttachmentService;
+use std::collections::HashSet;
 use std::sync::Arc;
 
 pub struct MessageService {
     repository: Arc<dyn MessageRepository>,
+    attachment_service: Arc<AttachmentService>,
 }
 
 impl MessageService {
-    pub fn new(repository: Arc<dyn MessageRepository>) -> Self {
-        Self { repository }
+    pub fn new(
+        repository: Arc<dyn MessageRepository>,
+        attachment_service: Arc<AttachmentService>,
+    ) -> Self {
+        Self {
+            repository,
+            attachment_service,
+        }
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -39,11 +51,15 @@ impl MessageService {
             timestamp,
             assistant_message_id,
             tool_call_id,
+            reply_to_message_id: reply_to_from_metadata(metadata.as_deref()),
+            branch_id: None,
             metadata,
             reasoning: None,
         };
 
         self.repository.create(&message)?;
+        self.attachment_service
+            .retain(message.metadata.as_deref())?;
         Ok(message)
     }
 
@@ -55,6 +71,33 @@ impl MessageService {
         self.repository.get_by_id(id)
     }
 
+    /// Diff two assistant answers, e.g. a regenerated one against the
+    /// original, for the compare view.
+    pub fn diff_messages(
+        &self,
+        message_id_a: &str,
+        message_id_b: &str,
+        mode: DiffMode,
+    ) -> Result<MessageDiff, AppError> {
+        let load = |id: &str| {
+            let message = self
+                .get_by_id(id)?
+                .ok_or_else(|| AppError::NotFound(format!("Message not found: {id}")))?;
+            if message.role != "assistant" {
+                return Err(AppError::Validation(format!(
+                    "Only assistant messages can be compared, {id} is a {} message",
+                    message.role
+                )));
+            }
+            Ok(message)
+        };
+        let a = load(message_id_a)?;
+        let b = load(message_id_b)?;
+        Ok(diff::diff_texts(&a.content, &b.content, mode))
+    }
+
+    /// Internal write of a message's content, reasoning and timestamp, for
+    /// the chat service; frontend changes go through `update_from_client`.
     pub fn update(
         &self,
         id: String,
@@ -66,19 +109,87 @@ impl MessageService {
             .update(&id, &content, reasoning.as_deref(), timestamp)
     }
 
+    /// Internal write of the whole metadata, reserved keys included.
     pub fn update_metadata(&self, id: String, metadata: Option<String>) -> Result<(), AppError> {
         self.repository.update_metadata(&id, metadata.as_deref())
     }
 
+    /// Apply a change the frontend asked for, refusing changes to the fields
+    /// and metadata keys the conversation structure depends on.
+    pub fn update_from_client(&self, id: &str, update: MessageUpdate) -> Result<Message, AppError> {
+        let message = self
+            .get_by_id(id)?
+            .ok_or_else(|| AppError::NotFound(format!("Message not found: {id}")))?;
+        let updated = client_update::apply_update(&message, update)?;
+        if updated.content != message.content || updated.reasoning != message.reasoning {
+            self.repository
+                .update(id, &updated.content, updated.reasoning.as_deref(), None)?;
+        }
+        if updated.metadata != message.metadata {
+            self.repository
+                .update_metadata(id, updated.metadata.as_deref())?;
+        }
+        Ok(updated)
+    }
+
+    /// Delete a message, with the rest of its tool exchange if it is part of one.
     pub fn delete(&self, id: String) -> Result<(), AppError> {
-        self.repository.delete(&id)
+        let Some(message) = self.repository.get_by_id(&id)? else {
+            return self.repository.delete(&id);
+        };
+        if message.role == "user" {
+            self.attachment_service
+                .release(message.metadata.as_deref())?;
+            return self.repository.delete(&id);
+        }
+        let messages = self.repository.get_by_chat_id(&message.chat_id)?;
+        self.delete_exchanges(&message.chat_id, &messages, &HashSet::from([id]))
     }
 
+    /// Delete the messages after `message_id`, with the rest of any tool
+    /// exchange cut in half, so the remaining history stays sendable.
     pub fn delete_messages_after(
         &self,
         chat_id: String,
         message_id: String,
     ) -> Result<(), AppError> {
-        self.repository.delete_messages_after(&chat_id, &message_id)
+        let mut messages = self.repository.get_by_chat_id(&chat_id)?;
+        // A tool call and its result share a timestamp; the id orders them
+        messages.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+        let index = messages
+            .iter()
+            .position(|m| m.id == message_id)
+            .ok_or_else(|| AppError::NotFound(format!("Message not found: {message_id}")))?;
+
+        let after = messages[index + 1..].iter().map(|m| m.id.clone()).collect();
+        self.delete_exchanges(&chat_id, &messages, &after)
+    }
+
+    /// Delete `ids` of the chat's `messages`, grown to whole tool exchanges.
+    fn delete_exchanges(
+        &self,
+        chat_id: &str,
+        messages: &[Message],
+        ids: &HashSet<String>,
+    ) -> Result<(), AppError> {
+        let removed = tool_exchange::exchange_closure(messages, ids);
+        if removed.len() > ids.len() {
+            tracing::info!(
+                chat_id = %chat_id,
+                extra = removed.len() - ids.len(),
+                "Removing the rest of tool exchanges cut by the deletion"
+            );
+        }
+
+        let removed: Vec<&Message> = messages
+            .iter()
+            .filter(|m| removed.contains(&m.id))
+            .collect();
+        for message in &removed {
+            self.attachment_service
+                .release(message.metadata.as_deref())?;
+        }
+        let ids: Vec<String> = removed.iter().map(|m| m.id.clone()).collect();
+        self.repository.delete_many(&ids)
     }
 }
@@ -0,0 +1,291 @@
+//! Tool exchanges as stored messages.
+//!
+//! A tool call of an assistant message is stored as a `tool_call_<id>`
+//! message (UI only, `assistant_message_id` set to the assistant message)
+//! and a `tool_result_<id>` message (role `tool`, `tool_call_id` set to the
+//! call id). Deleting one part of an exchange without the others leaves
+//! history that providers reject, so deletions go through `exchange_closure`.
+//! The assistant message keeps no tool call list of its own: the calls sent
+//! with it are rebuilt from the `tool_call` messages that remain.
+
+use super::models::Message;
+use std::collections::HashSet;
+
+pub const TOOL_CALL_ID_PREFIX: &str = "tool_call_";
+
+/// The id of the tool call a `tool_call` or `tool` message belongs to.
+pub fn tool_call_id_of(message: &Message) -> Option<&str> {
+    match message.role.as_str() {
+        "tool_call" => message.id.strip_prefix(TOOL_CALL_ID_PREFIX),
+        "tool" => message.tool_call_id.as_deref(),
+        _ => None,
+    }
+}
+
+/// `removed` grown to whole tool exchanges: an assistant message takes its
+/// tool calls along, and a tool call and its result go together.
+pub fn exchange_closure(messages: &[Message], removed: &HashSet<String>) -> HashSet<String> {
+    let is_removed = |m: &Message| removed.contains(&m.id);
+    let removed_assistants: HashSet<&str> = messages
+        .iter()
+        .filter(|m| m.role == "assistant" && is_removed(m))
+        .map(|m| m.id.as_str())
+        .collect();
+
+    // Calls whose call, result or assistant message goes
+    let removed_calls: HashSet<&str> = messages
+        .iter()
+        .filter(|m| {
+            is_removed(m)
+                || (m.role == "tool_call"
+                    && m.assistant_message_id
+                        .as_deref()
+                        .is_some_and(|a| removed_assistants.contains(a)))
+        })
+        .filter_map(tool_call_id_of)
+        .collect();
+
+    messages
+        .iter()
+        .filter(|m| {
+            is_removed(m) || tool_call_id_of(m).is_some_and(|id| removed_calls.contains(id))
+        })
+        .map(|m| m.id.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::chat_message;
+
+    fn tool_exchange_chat() -> Vec<crate::features::message::Message> {
+        let at = |mut m: crate::features::message::Message, timestamp: i64| {
+            m.timestamp = timestamp;
+            m
+        };
+        let call = |id: &str, timestamp: i64| {
+            let mut m = chat_message(
+                &format!("tool_call_{id}"),
+                "tool_call",
+                r#"{"name": "search", "arguments": "{\"q\": \"rust\"}", "status": "completed"}"#,
+            );
+            m.assistant_message_id = Some("a1".to_string());
+            at(m, timestamp)
+        };
+        let result = |id: &str, timestamp: i64| {
+            let mut m = chat_message(&format!("tool_result_{id}"), "tool", "found");
+            m.tool_call_id = Some(id.to_string());
+            at(m, timestamp)
+        };
+        vec![
+            at(chat_message("u1", "user", "Look it up"), 1),
+            at(chat_message("a1", "assistant", ""), 2),
+            call("x", 3),
+            result("x", 3),
+            call("y", 4),
+            result("y", 4),
+            at(chat_message("a2", "assistant", "Found it"), 5),
+            at(chat_message("u2", "user", "Thanks"), 6),
+            at(chat_message("a3", "assistant", "Anytime"), 7),
+        ]
+    }
+
+    /// The history as prepare_messages sends it, before the new user message
+    fn tool_history(
+        messages: &[crate::features::message::Message],
+    ) -> Vec<crate::models::llm_types::ChatMessage> {
+        use crate::features::chat::tool_pairing::{history_tool_calls, pair_tool_messages};
+        use crate::models::llm_types::{AssistantContent, ChatMessage, UserContent};
+
+        let mut calls = history_tool_calls(messages);
+        let history = messages
+            .iter()
+            .filter_map(|m| match m.role.as_str() {
+                "user" => Some(ChatMessage::User {
+                    content: UserContent::Text(m.content.clone()),
+                }),
+                "assistant" => Some(ChatMessage::Assistant {
+                    content: AssistantContent::Text(m.content.clone()),
+                    tool_calls: calls.remove(&m.id),
+                }),
+                "tool" => Some(ChatMessage::Tool {
+                    content: m.content.clone(),
+                    tool_call_id: m.tool_call_id.clone()?,
+                }),
+                _ => None,
+            })
+            .collect();
+        pair_tool_messages(history)
+    }
+
+    /// OpenAI and Google: the calls of an assistant message are answered by
+    /// exactly the tool messages right after it, and no others exist.
+    fn assert_tool_calls_paired(messages: &[crate::models::llm_types::ChatMessage]) {
+        use crate::models::llm_types::ChatMessage;
+
+        let mut i = 0;
+        while i < messages.len() {
+            match &messages[i] {
+                ChatMessage::Assistant { tool_calls, .. } => {
+                    let calls: Vec<&str> =
+                        tool_calls.iter().flatten().map(|c| c.id.as_str()).collect();
+                    let results: Vec<&str> = messages[i + 1..]
+                        .iter()
+                        .map_while(|m| match m {
+                            ChatMessage::Tool { tool_call_id, .. } => Some(tool_call_id.as_str()),
+                            _ => None,
+                        })
+                        .collect();
+                    assert_eq!(calls, results, "calls of message {i} not answered in order");
+                    i += 1 + results.len();
+                }
+                ChatMessage::Tool { tool_call_id, .. } => {
+                    panic!("tool result {tool_call_id} answers no call")
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    /// Anthropic: tool_result blocks answer tool_use blocks of the message
+    /// right before them.
+    fn assert_anthropic_tool_use_paired(messages: Vec<crate::models::llm_types::ChatMessage>) {
+        use crate::models::llm_types::{ChatMessage, LLMChatRequest, UserContent};
+        use crate::services::llm::providers::AnthropicProvider;
+
+        let mut messages = messages;
+        messages.push(ChatMessage::User {
+            content: UserContent::Text("Try again".to_string()),
+        });
+        let request = LLMChatRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            messages,
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed: None,
+            output_limits: None,
+        };
+        let body = serde_json::to_value(AnthropicProvider::build_request(request)).unwrap();
+        let messages = body["messages"].as_array().unwrap();
+        let block_ids = |message: &serde_json::Value, kind: &str, key: &str| -> Vec<String> {
+            message["content"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|b| b["type"] == kind)
+                .map(|b| b[key].as_str().unwrap().to_string())
+                .collect()
+        };
+        for (i, message) in messages.iter().enumerate() {
+            let results = block_ids(message, "tool_result", "tool_use_id");
+            let uses = block_ids(message, "tool_use", "id");
+            if !uses.is_empty() {
+                assert_eq!(
+                    messages
+                        .get(i + 1)
+                        .map(|m| block_ids(m, "tool_result", "tool_use_id")),
+                    Some(uses)
+                );
+            }
+            if !results.is_empty() {
+                assert!(i > 0);
+                assert_eq!(block_ids(&messages[i - 1], "tool_use", "id"), results);
+            }
+        }
+    }
+
+    #[test]
+    fn tool_exchanges_are_deleted_whole() {
+        use crate::features::message::tool_exchange::exchange_closure;
+        use std::collections::HashSet;
+
+        let messages = tool_exchange_chat();
+        let closure = |ids: &[&str]| {
+            let ids = ids.iter().map(|id| (*id).to_string()).collect();
+            let mut removed: Vec<String> = exchange_closure(&messages, &ids).into_iter().collect();
+            removed.sort();
+            removed
+        };
+
+        // Editing at a tool result takes its call along
+        assert_eq!(
+            closure(&["tool_result_x", "tool_call_y", "tool_result_y", "a2"]),
+            [
+                "a2",
+                "tool_call_x",
+                "tool_call_y",
+                "tool_result_x",
+                "tool_result_y"
+            ]
+        );
+        // Editing at a call takes its result along
+        assert_eq!(closure(&["tool_call_y"]), ["tool_call_y", "tool_result_y"]);
+        // An assistant message takes all its calls along
+        assert_eq!(closure(&["a1"]).len(), 5);
+        assert_eq!(closure(&["u2", "a3"]), ["a3", "u2"]);
+        assert!(exchange_closure(&messages, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn edit_resend_inside_a_tool_exchange_sends_a_valid_history() {
+        use crate::features::message::tool_exchange::exchange_closure;
+
+        let messages = tool_exchange_chat();
+        assert_tool_calls_paired(&tool_history(&messages));
+        assert_anthropic_tool_use_paired(tool_history(&messages));
+
+        // Resending from any message keeps the history valid for every provider
+        for index in 0..messages.len() {
+            let removed = exchange_closure(
+                &messages,
+                &messages[index..].iter().map(|m| m.id.clone()).collect(),
+            );
+            let kept: Vec<_> = messages
+                .iter()
+                .filter(|m| !removed.contains(&m.id))
+                .cloned()
+                .collect();
+            let history = tool_history(&kept);
+            assert_tool_calls_paired(&history);
+            assert_anthropic_tool_use_paired(history);
+        }
+    }
+
+    #[test]
+    fn unpaired_tool_rows_are_left_out_of_the_history() {
+        use crate::models::llm_types::ChatMessage;
+
+        // Rows left behind by deletions that were not tool aware
+        let messages = tool_exchange_chat();
+        let orphaned: Vec<_> = messages
+            .iter()
+            .filter(|m| m.id != "tool_call_x" && m.id != "tool_result_y")
+            .cloned()
+            .collect();
+        let history = tool_history(&orphaned);
+        assert_tool_calls_paired(&history);
+        assert_anthropic_tool_use_paired(history.clone());
+        assert!(!history.iter().any(|m| matches!(
+            m,
+            ChatMessage::Tool { .. }
+                | ChatMessage::Assistant {
+                    tool_calls: Some(_),
+                    ..
+                }
+        )));
+        assert_eq!(history.len(), 4);
+    }
+}
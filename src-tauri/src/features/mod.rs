@@ -1,16 +1,35 @@
+pub mod backup;
 pub mod chat;
+pub mod chat_import;
+pub mod context_cache;
+pub mod encryption;
 pub mod hub;
+pub mod knowledge;
+pub mod llm_cache;
 pub mod llm_connection;
+pub mod maintenance;
 pub mod mcp_connection;
 pub mod message;
+pub mod outbox;
+pub mod post_processing;
 pub mod prompt;
+pub mod quick_action;
+pub mod redaction;
 pub mod runtime;
+pub mod search;
+pub mod semantic_index;
+pub mod share;
 pub mod skill;
+pub mod system;
 pub mod tool;
+pub mod tool_catalog;
 pub mod usage;
+pub mod webhook;
 pub mod workspace;
 
+pub mod activity;
 pub mod addon;
 pub mod agent;
 pub mod app_settings;
+pub mod attachment;
 pub mod notes;
@@ -0,0 +1,34 @@
+use super::models::OutboxItem;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+/// The chat's queued messages, oldest first.
+#[tauri::command]
+pub fn get_outbox(
+    chat_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<OutboxItem>, AppError> {
+    state.outbox_service.get_by_chat_id(&chat_id)
+}
+
+/// Send the chat's queued messages now instead of waiting for the
+/// connectivity check; they stay queued while the model is unreachable.
+#[tauri::command]
+pub async fn retry_outbox(
+    chat_id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.chat_service.flush_outbox(&chat_id, &app).await
+}
+
+/// Drop a queued message along with its user message.
+#[tauri::command]
+pub fn discard_outbox_item(
+    id: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<(), AppError> {
+    state.chat_service.discard_outbox_item(&id, &app)
+}
@@ -0,0 +1,132 @@
+use crate::error::AppError;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Metadata key of a user message waiting in the outbox
+pub const QUEUED_METADATA_KEY: &str = "queued";
+
+/// A message written while its connection could not be reached, waiting to
+/// be sent again in order with the rest of its chat's outbox.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxItem {
+    pub id: String,
+    pub chat_id: String,
+    /// The user message kept in the chat, marked `queued`
+    pub user_message_id: String,
+    pub content: String,
+    /// Stored paths of the message's files
+    pub files: Vec<String>,
+    pub params: OutboxParams,
+    /// Base URL of the connection the message was for, probed for when it
+    /// is back
+    pub base_url: String,
+    pub created_at: i64,
+    /// Sends tried so far
+    pub attempts: i64,
+    pub last_error: Option<String>,
+}
+
+/// How the message was sent, so the resend picks the same model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OutboxParams {
+    pub metadata: Option<String>,
+    pub selected_model: Option<String>,
+    pub reasoning_effort: Option<String>,
+    pub llm_connection_id: Option<String>,
+}
+
+impl OutboxItem {
+    pub fn new(
+        chat_id: &str,
+        user_message_id: &str,
+        content: &str,
+        files: Vec<String>,
+        params: OutboxParams,
+        base_url: &str,
+    ) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            chat_id: chat_id.to_string(),
+            user_message_id: user_message_id.to_string(),
+            content: content.to_string(),
+            files,
+            params,
+            base_url: base_url.to_string(),
+            created_at: chrono::Utc::now().timestamp_millis(),
+            attempts: 0,
+            last_error: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutboxStatus {
+    /// Waiting for the connection
+    Queued,
+    Sending,
+    Sent,
+    /// The resend failed for a reason other than the network; the message
+    /// left the outbox
+    Failed,
+    Discarded,
+}
+
+/// Whether `error` means the model could not be reached at all, as opposed
+/// to a refusal or a failed answer: worth keeping the message for later.
+/// A stream only ends in `StreamInterrupted` when it broke off before any
+/// of the answer arrived; a half-streamed answer is kept and continued
+/// instead, so it is never queued and sent again.
+pub fn is_network_error(error: &AppError) -> bool {
+    match error {
+        AppError::Http(e) => e.is_connect() || e.is_timeout() || e.is_request(),
+        AppError::StreamInterrupted(_) => true,
+        _ => false,
+    }
+}
+
+/// Whether a message's metadata marks it as waiting in the outbox.
+pub fn is_queued(metadata: Option<&str>) -> bool {
+    metadata
+        .and_then(|m| serde_json::from_str::<serde_json::Value>(m).ok())
+        .and_then(|m| {
+            m.get(QUEUED_METADATA_KEY)
+                .and_then(serde_json::Value::as_bool)
+        })
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+
+    #[test]
+    fn only_unreachable_models_queue_messages() {
+        use crate::features::outbox::{is_network_error, is_queued};
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            // A port nothing listens on any more
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!(
+                "http://{}/v1/chat/completions",
+                listener.local_addr().unwrap()
+            );
+            drop(listener);
+
+            let error = reqwest::Client::new().post(&url).send().await.unwrap_err();
+            assert!(is_network_error(&AppError::Http(error)));
+        });
+        assert!(is_network_error(&AppError::StreamInterrupted(
+            "connection reset".to_string()
+        )));
+        assert!(!is_network_error(&AppError::Llm(
+            "401 Unauthorized".to_string()
+        )));
+        assert!(!is_network_error(&AppError::Cancelled));
+
+        assert!(is_queued(Some(r#"{"queued":true,"files":[]}"#)));
+        assert!(!is_queued(Some(r#"{"queued":false}"#)));
+        assert!(!is_queued(Some("not json")));
+        assert!(!is_queued(None));
+    }
+}
@@ -0,0 +1,221 @@
+use super::models::OutboxItem;
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait OutboxRepository: Send + Sync {
+    fn insert(&self, item: &OutboxItem) -> Result<(), AppError>;
+    fn get_by_id(&self, id: &str) -> Result<Option<OutboxItem>, AppError>;
+    /// The chat's items, oldest first
+    fn get_by_chat_id(&self, chat_id: &str) -> Result<Vec<OutboxItem>, AppError>;
+    /// Chats with items waiting, each with the base URL of its oldest item
+    fn pending_chats(&self) -> Result<Vec<(String, String)>, AppError>;
+    fn record_attempt(&self, id: &str, error: Option<&str>) -> Result<(), AppError>;
+    fn delete(&self, id: &str) -> Result<(), AppError>;
+}
+
+pub struct SqliteOutboxRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteOutboxRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl OutboxRepository for SqliteOutboxRepository {
+    fn insert(&self, item: &OutboxItem) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        insert_item(&conn, item)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Option<OutboxItem>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        item_by_id(&conn, id)
+    }
+
+    fn get_by_chat_id(&self, chat_id: &str) -> Result<Vec<OutboxItem>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        items_of_chat(&conn, chat_id)
+    }
+
+    fn pending_chats(&self) -> Result<Vec<(String, String)>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        pending_chats(&conn)
+    }
+
+    fn record_attempt(&self, id: &str, error: Option<&str>) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        record_attempt(&conn, id, error)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        delete_item(&conn, id)
+    }
+}
+
+const COLUMNS: &str =
+    "id, chat_id, user_message_id, content, files, params, base_url, created_at, attempts, last_error";
+
+pub fn insert_item(conn: &Connection, item: &OutboxItem) -> Result<(), AppError> {
+    conn.execute(
+        &format!("INSERT INTO outbox ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)"),
+        params![
+            item.id,
+            item.chat_id,
+            item.user_message_id,
+            item.content,
+            serde_json::to_string(&item.files)?,
+            serde_json::to_string(&item.params)?,
+            item.base_url,
+            item.created_at,
+            item.attempts,
+            item.last_error
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn item_by_id(conn: &Connection, id: &str) -> Result<Option<OutboxItem>, AppError> {
+    let result = conn.query_row(
+        &format!("SELECT {COLUMNS} FROM outbox WHERE id = ?1"),
+        params![id],
+        row_to_item,
+    );
+    match result {
+        Ok(item) => Ok(Some(item)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub fn items_of_chat(conn: &Connection, chat_id: &str) -> Result<Vec<OutboxItem>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM outbox WHERE chat_id = ?1 ORDER BY created_at, rowid"
+    ))?;
+    let items = stmt
+        .query_map(params![chat_id], row_to_item)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(items)
+}
+
+/// Chats with items waiting, the one waiting longest first.
+pub fn pending_chats(conn: &Connection) -> Result<Vec<(String, String)>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT chat_id, base_url FROM outbox o
+         WHERE rowid = (SELECT rowid FROM outbox WHERE chat_id = o.chat_id ORDER BY created_at, rowid LIMIT 1)
+         ORDER BY created_at",
+    )?;
+    let chats = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(chats)
+}
+
+pub fn record_attempt(conn: &Connection, id: &str, error: Option<&str>) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE outbox SET attempts = attempts + 1, last_error = ?1 WHERE id = ?2",
+        params![error, id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_item(conn: &Connection, id: &str) -> Result<(), AppError> {
+    conn.execute("DELETE FROM outbox WHERE id = ?1", params![id])?;
+    Ok(())
+}
+
+fn row_to_item(row: &rusqlite::Row) -> rusqlite::Result<OutboxItem> {
+    let files: String = row.get(4)?;
+    let params: String = row.get(5)?;
+    Ok(OutboxItem {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        user_message_id: row.get(2)?,
+        content: row.get(3)?,
+        files: serde_json::from_str(&files).unwrap_or_default(),
+        params: serde_json::from_str(&params).unwrap_or_default(),
+        base_url: row.get(6)?,
+        created_at: row.get(7)?,
+        attempts: row.get(8)?,
+        last_error: row.get(9)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    fn outbox_item(
+        chat_id: &str,
+        message_id: &str,
+        created_at: i64,
+    ) -> crate::features::outbox::OutboxItem {
+        use crate::features::outbox::{OutboxItem, OutboxParams};
+
+        let mut item = OutboxItem::new(
+            chat_id,
+            message_id,
+            &format!("text of {message_id}"),
+            vec!["/attachments/a.png".to_string()],
+            OutboxParams {
+                selected_model: Some("gpt-4o".to_string()),
+                ..OutboxParams::default()
+            },
+            &format!("http://{chat_id}.local"),
+        );
+        item.created_at = created_at;
+        item
+    }
+
+    #[test]
+    fn outbox_items_wait_in_order_per_chat() {
+        use crate::features::outbox::{
+            delete_item, insert_item, item_by_id, items_of_chat, pending_chats, record_attempt,
+        };
+
+        let conn = migrated_db();
+        let later = outbox_item("c1", "u2", 20);
+        let first = outbox_item("c1", "u1", 10);
+        let other = outbox_item("c2", "u3", 15);
+        for item in [&later, &first, &other] {
+            insert_item(&conn, item).unwrap();
+        }
+
+        let queued = items_of_chat(&conn, "c1").unwrap();
+        assert_eq!(
+            queued
+                .iter()
+                .map(|i| i.user_message_id.as_str())
+                .collect::<Vec<_>>(),
+            ["u1", "u2"]
+        );
+        // Files and send parameters survive the round trip
+        assert_eq!(queued[0], first);
+        assert_eq!(
+            pending_chats(&conn).unwrap(),
+            [
+                ("c1".to_string(), "http://c1.local".to_string()),
+                ("c2".to_string(), "http://c2.local".to_string()),
+            ]
+        );
+
+        record_attempt(&conn, &first.id, Some("[Network] connection refused")).unwrap();
+        let attempted = item_by_id(&conn, &first.id).unwrap().unwrap();
+        assert_eq!(attempted.attempts, 1);
+        assert_eq!(
+            attempted.last_error.as_deref(),
+            Some("[Network] connection refused")
+        );
+
+        // Once sent, the next message of the chat is first in line
+        delete_item(&conn, &first.id).unwrap();
+        assert_eq!(items_of_chat(&conn, "c1").unwrap(), [later]);
+        delete_item(&conn, &other.id).unwrap();
+        assert_eq!(pending_chats(&conn).unwrap().len(), 1);
+        assert!(item_by_id(&conn, &other.id).unwrap().is_none());
+    }
+}
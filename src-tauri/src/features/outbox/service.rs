@@ -0,0 +1,367 @@
+use super::models::{OutboxItem, OutboxStatus};
+use super::repository::OutboxRepository;
+use crate::error::AppError;
+use crate::events::{emit_event, OutboxStatusEvent};
+use std::collections::HashSet;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// How often the connections of chats with queued messages are probed.
+pub const OUTBOX_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+/// How long a probe waits for any answer from the connection.
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct OutboxService {
+    repository: Arc<dyn OutboxRepository>,
+    client: reqwest::Client,
+    /// Chats whose outbox is being sent, so a retry and the watcher do not
+    /// send the same message twice
+    flushing: Arc<Mutex<HashSet<String>>>,
+}
+
+/// Held while a chat's outbox is being sent.
+pub struct FlushGuard {
+    chat_id: String,
+    flushing: Arc<Mutex<HashSet<String>>>,
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        if let Ok(mut flushing) = self.flushing.lock() {
+            flushing.remove(&self.chat_id);
+        }
+    }
+}
+
+impl OutboxService {
+    pub fn new(repository: Arc<dyn OutboxRepository>) -> Self {
+        Self {
+            repository,
+            client: reqwest::Client::builder()
+                .timeout(PROBE_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            flushing: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    pub fn enqueue(&self, item: &OutboxItem) -> Result<(), AppError> {
+        self.repository.insert(item)
+    }
+
+    /// Keep `item` for later, adding it when `is_new`. `error`, the send
+    /// that could not reach the model, counts as a failed attempt. Returns
+    /// the item as it now stands.
+    pub fn queue(
+        &self,
+        mut item: OutboxItem,
+        is_new: bool,
+        error: Option<&AppError>,
+    ) -> Result<OutboxItem, AppError> {
+        if is_new {
+            self.enqueue(&item)?;
+        }
+        if let Some(error) = error {
+            let error = error.to_string();
+            self.record_attempt(&item.id, Some(&error))?;
+            item.attempts += 1;
+            item.last_error = Some(error);
+        }
+        Ok(item)
+    }
+
+    pub fn get_by_id(&self, id: &str) -> Result<OutboxItem, AppError> {
+        self.repository
+            .get_by_id(id)?
+            .ok_or_else(|| AppError::NotFound(format!("Outbox item not found: {id}")))
+    }
+
+    pub fn get_by_chat_id(&self, chat_id: &str) -> Result<Vec<OutboxItem>, AppError> {
+        self.repository.get_by_chat_id(chat_id)
+    }
+
+    pub fn has_pending(&self, chat_id: &str) -> Result<bool, AppError> {
+        Ok(!self.repository.get_by_chat_id(chat_id)?.is_empty())
+    }
+
+    pub fn pending_chats(&self) -> Result<Vec<(String, String)>, AppError> {
+        self.repository.pending_chats()
+    }
+
+    pub fn record_attempt(&self, id: &str, error: Option<&str>) -> Result<(), AppError> {
+        self.repository.record_attempt(id, error)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<(), AppError> {
+        self.repository.delete(id)
+    }
+
+    /// Claim the sending of a chat's outbox; `None` while it is already
+    /// being sent.
+    pub fn begin_flush(&self, chat_id: &str) -> Option<FlushGuard> {
+        let mut flushing = self.flushing.lock().ok()?;
+        flushing.insert(chat_id.to_string()).then(|| FlushGuard {
+            chat_id: chat_id.to_string(),
+            flushing: self.flushing.clone(),
+        })
+    }
+
+    /// Send the chat's queued messages in order with `send`, which answers
+    /// with the id of the stored answer. Stops at the first one queued
+    /// again or halted; one failing for another reason leaves the outbox
+    /// and the rest go on. `report` hears of each status with its error or
+    /// answer. Does nothing while the chat's outbox is already being sent.
+    pub async fn flush<S, F>(
+        &self,
+        chat_id: &str,
+        mut send: S,
+        report: impl Fn(&OutboxItem, OutboxStatus, Option<String>, Option<String>),
+    ) -> Result<(), AppError>
+    where
+        S: FnMut(OutboxItem) -> F,
+        F: Future<Output = Result<String, AppError>>,
+    {
+        let Some(_flush) = self.begin_flush(chat_id) else {
+            return Ok(());
+        };
+        while let Some(item) = self.get_by_chat_id(chat_id)?.into_iter().next() {
+            report(&item, OutboxStatus::Sending, None, None);
+            match send(item.clone()).await {
+                Ok(assistant_message_id) => {
+                    self.remove(&item.id)?;
+                    report(&item, OutboxStatus::Sent, None, Some(assistant_message_id));
+                }
+                // Halted meanwhile: the message stays queued for after the resume
+                Err(AppError::Queued(_) | AppError::Halted) => break,
+                Err(e) => {
+                    tracing::warn!(chat_id, item_id = %item.id, error = %e, "Queued message could not be sent");
+                    self.remove(&item.id)?;
+                    report(&item, OutboxStatus::Failed, Some(e.to_string()), None);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn is_reachable(&self, base_url: &str) -> bool {
+        reachable(&self.client, base_url).await
+    }
+
+    pub fn emit_status(
+        &self,
+        app: &AppHandle,
+        item: &OutboxItem,
+        status: OutboxStatus,
+        error: Option<String>,
+        assistant_message_id: Option<String>,
+    ) {
+        let event = OutboxStatusEvent {
+            chat_id: item.chat_id.clone(),
+            item_id: item.id.clone(),
+            user_message_id: item.user_message_id.clone(),
+            status,
+            attempts: item.attempts,
+            error,
+            assistant_message_id,
+        };
+        if let Err(e) = emit_event(app, event) {
+            tracing::warn!(item_id = %item.id, error = %e, "Failed to emit outbox status");
+        }
+    }
+}
+
+/// Whether the server at `base_url` answers at all. Any HTTP response
+/// counts, an error status included: only the network is in question.
+pub async fn reachable(client: &reqwest::Client, base_url: &str) -> bool {
+    match client.head(base_url).send().await {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::debug!(base_url, error = %e, "Connection still unreachable");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::webhook::dispatcher::tests::mock_webhook_receiver;
+    use crate::test_support::{migrated_db, mock_provider, MockReply};
+
+    /// The outbox table of an in-memory database.
+    struct MemoryOutboxRepository(std::sync::Mutex<rusqlite::Connection>);
+
+    impl crate::features::outbox::OutboxRepository for MemoryOutboxRepository {
+        fn insert(
+            &self,
+            item: &crate::features::outbox::OutboxItem,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::outbox::insert_item(&self.0.lock().unwrap(), item)
+        }
+
+        fn get_by_id(
+            &self,
+            id: &str,
+        ) -> Result<Option<crate::features::outbox::OutboxItem>, crate::error::AppError> {
+            crate::features::outbox::item_by_id(&self.0.lock().unwrap(), id)
+        }
+
+        fn get_by_chat_id(
+            &self,
+            chat_id: &str,
+        ) -> Result<Vec<crate::features::outbox::OutboxItem>, crate::error::AppError> {
+            crate::features::outbox::items_of_chat(&self.0.lock().unwrap(), chat_id)
+        }
+
+        fn pending_chats(&self) -> Result<Vec<(String, String)>, crate::error::AppError> {
+            crate::features::outbox::pending_chats(&self.0.lock().unwrap())
+        }
+
+        fn record_attempt(
+            &self,
+            id: &str,
+            error: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::outbox::record_attempt(&self.0.lock().unwrap(), id, error)
+        }
+
+        fn delete(&self, id: &str) -> Result<(), crate::error::AppError> {
+            crate::features::outbox::delete_item(&self.0.lock().unwrap(), id)
+        }
+    }
+
+    #[test]
+    fn unreachable_turns_wait_in_the_outbox_until_it_is_flushed() {
+        use crate::error::AppError;
+        use crate::features::outbox::{
+            is_network_error, OutboxItem, OutboxParams, OutboxService, OutboxStatus,
+        };
+        use crate::services::llm::stream_recovery::GuardedStream;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::{Arc, Mutex};
+
+        let outbox =
+            OutboxService::new(Arc::new(MemoryOutboxRepository(Mutex::new(migrated_db()))));
+        let cancellation = tokio::sync::broadcast::channel(1).0;
+        let online = AtomicBool::new(false);
+        let answers = Mutex::new(Vec::new());
+        // A turn as `send_turn` runs it: a model it cannot reach queues the
+        // message, an answer is stored
+        let turn = |item: OutboxItem, is_new: bool| {
+            let rx = cancellation.subscribe();
+            let (outbox, online, answers) = (&outbox, &online, &answers);
+            async move {
+                let reply = if online.load(Ordering::SeqCst) {
+                    MockReply::Answer("Back online", 0)
+                } else {
+                    MockReply::Unreachable
+                };
+                match mock_provider(reply, rx).await {
+                    Ok(response) => {
+                        answers
+                            .lock()
+                            .unwrap()
+                            .push((item.user_message_id, response.content));
+                        Ok("a1".to_string())
+                    }
+                    Err(e) if is_network_error(&e) => {
+                        let item = outbox.queue(item, is_new, Some(&e))?;
+                        Err(AppError::Queued(item.id))
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+        };
+        let statuses = Mutex::new(Vec::new());
+        let report =
+            |_: &OutboxItem, status: OutboxStatus, _: Option<String>, _: Option<String>| {
+                statuses.lock().unwrap().push(status);
+            };
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let item = OutboxItem::new(
+                "c1",
+                "u1",
+                "Hello",
+                Vec::new(),
+                OutboxParams::default(),
+                "http://offline.local",
+            );
+            let queued = turn(item.clone(), true).await;
+            assert!(matches!(queued, Err(AppError::Queued(ref id)) if *id == item.id));
+            let waiting = outbox.get_by_chat_id("c1").unwrap();
+            assert_eq!(waiting.len(), 1);
+            assert_eq!(waiting[0].attempts, 1);
+            assert!(waiting[0].last_error.is_some());
+
+            // Still offline: the flush stops at the message, which stays
+            outbox
+                .flush("c1", |item| turn(item, false), report)
+                .await
+                .unwrap();
+            assert_eq!(outbox.get_by_chat_id("c1").unwrap()[0].attempts, 2);
+            assert!(answers.lock().unwrap().is_empty());
+
+            online.store(true, Ordering::SeqCst);
+            outbox
+                .flush("c1", |item| turn(item, false), report)
+                .await
+                .unwrap();
+            assert_eq!(
+                *answers.lock().unwrap(),
+                [("u1".to_string(), "Back online".to_string())]
+            );
+            assert!(outbox.get_by_chat_id("c1").unwrap().is_empty());
+            assert_eq!(
+                *statuses.lock().unwrap(),
+                [
+                    OutboxStatus::Sending,
+                    OutboxStatus::Sending,
+                    OutboxStatus::Sent
+                ]
+            );
+
+            // A stream that broke off is a network error only when nothing
+            // of the answer arrived; a half-streamed one is kept instead, so
+            // it is never queued and sent a second time
+            let broken = || {
+                futures::stream::iter([
+                    Ok("Hel"),
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::ConnectionReset,
+                        "connection reset",
+                    )),
+                ])
+            };
+            let mut stream = GuardedStream::new(broken());
+            while stream.next().await.is_some() {}
+            assert!(matches!(stream.finish(true), Ok(true)));
+            let error = stream.finish(false).unwrap_err();
+            assert!(is_network_error(&error));
+        });
+    }
+
+    #[test]
+    fn outbox_probe_tells_when_the_connection_is_back() {
+        use crate::features::outbox::reachable;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let client = reqwest::Client::builder()
+                .timeout(std::time::Duration::from_secs(5))
+                .build()
+                .unwrap();
+
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let offline = format!("http://{}", listener.local_addr().unwrap());
+            drop(listener);
+            assert!(!reachable(&client, &offline).await);
+
+            // Any answer means the network is back, an error status included
+            let (online, received) = mock_webhook_receiver(vec![404]).await;
+            assert!(reachable(&client, &online).await);
+            assert_eq!(received.lock().unwrap().len(), 1);
+        });
+    }
+}
@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+
+/// Per-workspace cleanup of final answers, stored as JSON in
+/// `workspace_settings.post_processing_config`. Processors run in list order.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PostProcessingConfig {
+    pub processors: Vec<ProcessorConfig>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ProcessorConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub processor: Processor,
+}
+
+const fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Processor {
+    /// Replace every match of `pattern`; `$1` and `${name}` refer to groups
+    RegexReplace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+        #[serde(default, rename = "caseInsensitive")]
+        case_insensitive: bool,
+    },
+    /// Drop closing pleasantries ("Let me know if you have any questions!")
+    /// from the end of the answer. An empty list uses the default phrases.
+    BoilerplateStripper {
+        #[serde(default)]
+        phrases: Vec<String>,
+    },
+    /// Shift Markdown headings so the highest one is at `top_level`
+    HeadingNormalizer {
+        #[serde(default = "default_top_level", rename = "topLevel")]
+        top_level: u8,
+    },
+    /// Remove trailing spaces on each line and blank lines at the end
+    TrailingWhitespace,
+}
+
+const fn default_top_level() -> u8 {
+    2
+}
@@ -0,0 +1,469 @@
+use super::models::{PostProcessingConfig, Processor};
+use crate::error::AppError;
+use regex::{Regex, RegexBuilder};
+use std::sync::mpsc;
+use std::time::Duration;
+
+/// Message metadata key holding the answer as the model wrote it.
+pub const RAW_CONTENT_METADATA_KEY: &str = "raw_content";
+/// Larger originals are not kept; the metadata only notes the processing.
+pub const RAW_CONTENT_MAX_BYTES: usize = 64 * 1024;
+
+/// Longest user-supplied pattern accepted.
+const MAX_PATTERN_CHARS: usize = 500;
+/// Compiled program size limit, so huge repetitions are rejected on save.
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+/// A pattern that cannot process the probe text within this time is rejected.
+const PATTERN_TEST_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Closing lines dropped by the boilerplate stripper when no phrases are
+/// configured. A sentence is dropped when it starts with one of them.
+pub const DEFAULT_BOILERPLATE_PHRASES: &[&str] = &[
+    "let me know if you have any questions",
+    "let me know if you have any other questions",
+    "let me know if you have questions",
+    "let me know if you need anything else",
+    "let me know if you need any further",
+    "let me know if you need more help",
+    "let me know if there is anything else",
+    "is there anything else i can help",
+    "feel free to ask",
+    "feel free to reach out",
+    "i hope this helps",
+    "hope this helps",
+    "happy coding",
+];
+
+/// A validated config, ready to run.
+pub struct Pipeline {
+    steps: Vec<Step>,
+}
+
+enum Step {
+    RegexReplace { regex: Regex, replacement: String },
+    BoilerplateStripper { phrases: Vec<String> },
+    HeadingNormalizer { top_level: usize },
+    TrailingWhitespace,
+}
+
+impl Pipeline {
+    /// Apply the enabled processors in order.
+    pub fn run(&self, content: &str) -> String {
+        let mut text = content.to_string();
+        for step in &self.steps {
+            text = match step {
+                Step::RegexReplace { regex, replacement } => {
+                    regex.replace_all(&text, replacement.as_str()).into_owned()
+                }
+                Step::BoilerplateStripper { phrases } => strip_boilerplate(&text, phrases),
+                Step::HeadingNormalizer { top_level } => normalize_headings(&text, *top_level),
+                Step::TrailingWhitespace => trim_trailing_whitespace(&text),
+            };
+        }
+        text
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// Cleans up final assistant answers before they are stored. Streaming chunks
+/// are never processed; the UI replaces them with the stored content.
+pub struct PostProcessingService;
+
+impl PostProcessingService {
+    /// Parse a config before it is saved, rejecting patterns that do not
+    /// compile, are too long, or are too slow on a probe text. Disabled
+    /// processors are checked too, so enabling one later cannot fail.
+    pub fn parse_config(raw: &str) -> Result<PostProcessingConfig, AppError> {
+        let config: PostProcessingConfig = serde_json::from_str(raw)
+            .map_err(|e| AppError::Validation(format!("Invalid post-processing config: {e}")))?;
+        for processor in &config.processors {
+            if let Processor::RegexReplace {
+                pattern,
+                case_insensitive,
+                ..
+            } = &processor.processor
+            {
+                probe_pattern(compile_pattern(pattern, *case_insensitive)?, pattern)?;
+            }
+        }
+        Self::pipeline(&config)?;
+        Ok(config)
+    }
+
+    /// The pipeline of a workspace, or `None` when nothing is enabled. A
+    /// stored config that no longer parses is ignored rather than failing
+    /// the turn.
+    pub fn pipeline_for(raw: Option<&str>) -> Option<Pipeline> {
+        let raw = raw.filter(|r| !r.trim().is_empty())?;
+        let pipeline = serde_json::from_str::<PostProcessingConfig>(raw)
+            .map_err(|e| AppError::Validation(e.to_string()))
+            .and_then(|config| Self::pipeline(&config))
+            .map_err(|e| tracing::warn!(error = %e, "Ignoring invalid post-processing config"))
+            .ok()?;
+        (!pipeline.is_empty()).then_some(pipeline)
+    }
+
+    /// Keep the original answer in message metadata so the UI can show it,
+    /// unless it is too large.
+    pub fn record_raw_content(metadata: &mut serde_json::Value, raw: &str) {
+        if raw.len() <= RAW_CONTENT_MAX_BYTES {
+            metadata[RAW_CONTENT_METADATA_KEY] = serde_json::json!(raw);
+        } else {
+            metadata["rawContentOmitted"] = serde_json::json!(true);
+        }
+    }
+
+    pub fn pipeline(config: &PostProcessingConfig) -> Result<Pipeline, AppError> {
+        let mut steps = Vec::new();
+        for processor in config.processors.iter().filter(|p| p.enabled) {
+            steps.push(match &processor.processor {
+                Processor::RegexReplace {
+                    pattern,
+                    replacement,
+                    case_insensitive,
+                } => Step::RegexReplace {
+                    regex: compile_pattern(pattern, *case_insensitive)?,
+                    replacement: replacement.clone(),
+                },
+                Processor::BoilerplateStripper { phrases } => {
+                    let phrases: Vec<String> = if phrases.is_empty() {
+                        DEFAULT_BOILERPLATE_PHRASES
+                            .iter()
+                            .map(|p| (*p).to_string())
+                            .collect()
+                    } else {
+                        phrases.iter().map(|p| normalize_sentence(p)).collect()
+                    };
+                    Step::BoilerplateStripper {
+                        phrases: phrases.into_iter().filter(|p| !p.is_empty()).collect(),
+                    }
+                }
+                Processor::HeadingNormalizer { top_level } => {
+                    if !(1..=6).contains(top_level) {
+                        return Err(AppError::Validation(format!(
+                            "Heading level must be between 1 and 6, got {top_level}"
+                        )));
+                    }
+                    Step::HeadingNormalizer {
+                        top_level: usize::from(*top_level),
+                    }
+                }
+                Processor::TrailingWhitespace => Step::TrailingWhitespace,
+            });
+        }
+        Ok(Pipeline { steps })
+    }
+}
+
+fn compile_pattern(pattern: &str, case_insensitive: bool) -> Result<Regex, AppError> {
+    if pattern.is_empty() {
+        return Err(AppError::Validation(
+            "Post-processing pattern is empty".to_string(),
+        ));
+    }
+    if pattern.chars().count() > MAX_PATTERN_CHARS {
+        return Err(AppError::Validation(format!(
+            "Post-processing pattern is longer than {MAX_PATTERN_CHARS} characters"
+        )));
+    }
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .dfa_size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| AppError::Validation(format!("Invalid post-processing pattern: {e}")))
+}
+
+/// Run `regex` once on a large, repetitive text in the background; a pattern
+/// that cannot finish in time would stall every answer.
+fn probe_pattern(regex: Regex, pattern: &str) -> Result<(), AppError> {
+    let (tx, rx) = mpsc::channel();
+    std::thread::spawn(move || {
+        let probe = format!(
+            "{}\n{}\n{}",
+            "a".repeat(16_384),
+            "ab ".repeat(8_192),
+            "# Heading\n\n- item\n".repeat(512)
+        );
+        let _ = regex.replace_all(&probe, "x");
+        let _ = tx.send(());
+    });
+    rx.recv_timeout(PATTERN_TEST_TIMEOUT).map_err(|_| {
+        AppError::Validation(format!("Post-processing pattern is too slow: {pattern}"))
+    })
+}
+
+/// Lowercase words only, so punctuation, emphasis and emoji do not matter.
+fn normalize_sentence(sentence: &str) -> String {
+    sentence
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '\'' {
+                c.to_ascii_lowercase()
+            } else {
+                ' '
+            }
+        })
+        .filter(|c| *c != '\'')
+        .collect::<String>()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Drop trailing sentences that start with one of `phrases`, then lines left
+/// empty by that, from the end of the text only.
+fn strip_boilerplate(text: &str, phrases: &[String]) -> String {
+    let mut lines: Vec<String> = text.trim_end().lines().map(str::to_string).collect();
+    let mut changed = false;
+    while let Some(last) = lines.pop() {
+        if last.trim().is_empty() && changed {
+            continue;
+        }
+        let kept = strip_trailing_sentences(&last, phrases);
+        if kept.len() == last.len() {
+            lines.push(last);
+            break;
+        }
+        changed = true;
+        if !kept.trim().is_empty() {
+            lines.push(kept);
+            break;
+        }
+    }
+    if changed {
+        lines.join("\n")
+    } else {
+        text.to_string()
+    }
+}
+
+fn strip_trailing_sentences(line: &str, phrases: &[String]) -> String {
+    // Sentence starts: the line start and every position after ".", "!" or
+    // "?" followed by whitespace
+    let mut starts = vec![0];
+    let mut after_stop = false;
+    for (i, c) in line.char_indices() {
+        if matches!(c, '.' | '!' | '?') {
+            after_stop = true;
+        } else if after_stop && c.is_whitespace() {
+            starts.push(i);
+            after_stop = false;
+        } else {
+            after_stop = false;
+        }
+    }
+
+    let mut end = line.len();
+    for &start in starts.iter().rev() {
+        let sentence = normalize_sentence(&line[start..end]);
+        if sentence.is_empty() || !phrases.iter().any(|p| sentence.starts_with(p.as_str())) {
+            break;
+        }
+        end = start;
+    }
+    line[..end].trim_end().to_string()
+}
+
+/// Shift ATX headings outside code fences down so the highest one is at
+/// `top_level`. Headings are never promoted, and stop at level 6.
+fn normalize_headings(text: &str, top_level: usize) -> String {
+    let levels: Vec<Option<usize>> = heading_levels(text);
+    let Some(highest) = levels.iter().flatten().min() else {
+        return text.to_string();
+    };
+    let shift = top_level.saturating_sub(*highest);
+    if shift == 0 {
+        return text.to_string();
+    }
+
+    let mut output = String::with_capacity(text.len() + shift * 4);
+    for (line, level) in text.split_inclusive('\n').zip(levels) {
+        if let Some(level) = level {
+            let indent = line.len() - line.trim_start().len();
+            output.push_str(&line[..indent]);
+            output.push_str(&"#".repeat((level + shift).min(6)));
+            output.push_str(&line[indent + level..]);
+        } else {
+            output.push_str(line);
+        }
+    }
+    output
+}
+
+/// Heading level of each line of `text`, `None` for other lines.
+fn heading_levels(text: &str) -> Vec<Option<usize>> {
+    let mut in_fence = false;
+    text.split_inclusive('\n')
+        .map(|line| {
+            let trimmed = line.trim_start();
+            if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+                in_fence = !in_fence;
+                return None;
+            }
+            if in_fence || line.len() - trimmed.len() > 3 {
+                return None;
+            }
+            let level = trimmed.chars().take_while(|c| *c == '#').count();
+            let rest = &trimmed[level..];
+            ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(char::is_whitespace)))
+                .then_some(level)
+        })
+        .collect()
+}
+
+/// Trailing spaces and tabs on each line, and blank lines at the end.
+fn trim_trailing_whitespace(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    for line in text.split_inclusive('\n') {
+        let ending = &line[line.trim_end_matches(['\n', '\r']).len()..];
+        output.push_str(line.trim_end());
+        output.push_str(ending);
+    }
+    output.truncate(output.trim_end().len());
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    fn post_processing_pipeline(
+        config: serde_json::Value,
+    ) -> crate::features::post_processing::Pipeline {
+        use crate::features::post_processing::PostProcessingService;
+
+        let config = PostProcessingService::parse_config(&config.to_string()).unwrap();
+        PostProcessingService::pipeline(&config).unwrap()
+    }
+
+    #[test]
+    fn post_processing_regex_replace_and_validation() {
+        use crate::features::post_processing::PostProcessingService;
+
+        let pipeline = post_processing_pipeline(serde_json::json!({
+            "processors": [
+                {"type": "regex_replace", "pattern": "[“”]", "replacement": "\""},
+                {"type": "regex_replace", "pattern": "colou?r", "replacement": "hue", "caseInsensitive": true},
+                {"type": "regex_replace", "pattern": "never", "replacement": "x", "enabled": false}
+            ]
+        }));
+        assert_eq!(
+            pipeline.run("“Colour” is never a “color”"),
+            "\"hue\" is never a \"hue\""
+        );
+
+        let too_long = "a".repeat(501);
+        for (pattern, error) in [
+            ("(unclosed", "Invalid post-processing pattern"),
+            ("", "empty"),
+            (too_long.as_str(), "longer than 500"),
+            ("(a{1000}){1000}", "Invalid post-processing pattern"),
+        ] {
+            let config = serde_json::json!({
+                "processors": [{"type": "regex_replace", "pattern": pattern, "enabled": false}]
+            });
+            let err = PostProcessingService::parse_config(&config.to_string())
+                .unwrap_err()
+                .to_string();
+            assert!(err.contains(error), "{pattern}: {err}");
+        }
+        assert!(PostProcessingService::parse_config(
+            r#"{"processors": [{"type": "heading_normalizer", "topLevel": 7}]}"#
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn post_processing_strips_trailing_boilerplate_only() {
+        let pipeline = post_processing_pipeline(
+            serde_json::json!({"processors": [{"type": "boilerplate_stripper"}]}),
+        );
+
+        assert_eq!(
+            pipeline.run(
+                "Run `cargo fix`.\n\nI hope this helps! Let me know if you have any questions 😊\n"
+            ),
+            "Run `cargo fix`."
+        );
+        assert_eq!(
+            pipeline.run("Restart the server. Let me know if you need anything else!"),
+            "Restart the server."
+        );
+        // Only the end of the answer is touched
+        let middle = "Hope this helps explain it.\n\nThe fix is in `main.rs`.";
+        assert_eq!(pipeline.run(middle), middle);
+
+        let custom = post_processing_pipeline(serde_json::json!({
+            "processors": [{"type": "boilerplate_stripper", "phrases": ["Cheers!"]}]
+        }));
+        assert_eq!(custom.run("Done.\nCheers, Bob"), "Done.");
+        assert_eq!(
+            custom.run("Done. Hope this helps!"),
+            "Done. Hope this helps!"
+        );
+    }
+
+    #[test]
+    fn post_processing_normalizes_headings_outside_code() {
+        let pipeline = post_processing_pipeline(
+            serde_json::json!({"processors": [{"type": "heading_normalizer"}]}),
+        );
+
+        assert_eq!(
+            pipeline.run("# Setup\n\n## Steps\n\n```sh\n# comment\n```\n#hashtag\n"),
+            "## Setup\n\n### Steps\n\n```sh\n# comment\n```\n#hashtag\n"
+        );
+        // Already at the wanted level, or deeper: unchanged
+        assert_eq!(pipeline.run("### Notes\ntext"), "### Notes\ntext");
+
+        let top = post_processing_pipeline(serde_json::json!({
+            "processors": [{"type": "heading_normalizer", "topLevel": 3}]
+        }));
+        assert_eq!(
+            top.run("# A\n##### B\n###### C"),
+            "### A\n###### B\n###### C"
+        );
+    }
+
+    #[test]
+    fn post_processing_trims_trailing_whitespace() {
+        let pipeline = post_processing_pipeline(
+            serde_json::json!({"processors": [{"type": "trailing_whitespace"}]}),
+        );
+
+        assert_eq!(
+            pipeline.run("line one  \r\n\tindented\t\n\ncode  \n\n  \n"),
+            "line one\r\n\tindented\n\ncode"
+        );
+    }
+
+    #[test]
+    fn post_processing_runs_processors_in_order() {
+        // The footer is appended before the stripper runs, so it is removed
+        // again; in the other order it stays
+        let footer = serde_json::json!({"type": "regex_replace", "pattern": "\\z", "replacement": "\n\nHope this helps!"});
+        let stripper = serde_json::json!({"type": "boilerplate_stripper"});
+
+        let strip_last = post_processing_pipeline(serde_json::json!({
+            "processors": [footer.clone(), stripper.clone()]
+        }));
+        assert_eq!(strip_last.run("# Answer\nDone."), "# Answer\nDone.");
+
+        let strip_first = post_processing_pipeline(serde_json::json!({
+            "processors": [stripper, footer, {"type": "heading_normalizer"}, {"type": "trailing_whitespace"}]
+        }));
+        assert_eq!(
+            strip_first.run("# Answer  \nDone."),
+            "## Answer\nDone.\n\nHope this helps!"
+        );
+
+        let mut metadata = serde_json::json!({});
+        crate::features::post_processing::PostProcessingService::record_raw_content(
+            &mut metadata,
+            "# Answer",
+        );
+        assert_eq!(metadata["raw_content"], "# Answer");
+    }
+}
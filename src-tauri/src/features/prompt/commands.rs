@@ -1,4 +1,7 @@
-use super::models::Prompt;
+use super::models::{
+    Prompt, PromptCollisionMode, PromptFileFormat, PromptFromMessagesOptions,
+    PromptFromMessagesResult, PromptImportResult, PromptModelPreference,
+};
 use crate::error::AppError;
 use crate::state::AppState;
 use tauri::State;
@@ -8,11 +11,12 @@ pub fn create_prompt(
     id: String,
     name: String,
     content: String,
+    model_preference: Option<PromptModelPreference>,
     state: State<'_, AppState>,
 ) -> Result<Prompt, AppError> {
     state
         .prompt_service
-        .create(id, name, content)
+        .create(id, name, content, model_preference.unwrap_or_default())
         .map_err(|e| AppError::Prompt(e.to_string()))
 }
 
@@ -24,16 +28,18 @@ pub fn get_prompts(state: State<'_, AppState>) -> Result<Vec<Prompt>, AppError>
         .map_err(|e| AppError::Prompt(e.to_string()))
 }
 
+/// `model_preference`, when given, replaces the stored one as a whole.
 #[tauri::command]
 pub fn update_prompt(
     id: String,
     name: Option<String>,
     content: Option<String>,
+    model_preference: Option<PromptModelPreference>,
     state: State<'_, AppState>,
 ) -> Result<(), AppError> {
     state
         .prompt_service
-        .update(id, name, content)
+        .update(id, name, content, model_preference)
         .map_err(|e| AppError::Prompt(e.to_string()))
 }
 
@@ -44,3 +50,50 @@ pub fn delete_prompt(id: String, state: State<'_, AppState>) -> Result<(), AppEr
         .delete(id)
         .map_err(|e| AppError::Prompt(e.to_string()))
 }
+
+#[tauri::command]
+pub async fn export_prompts(
+    path: String,
+    format: PromptFileFormat,
+    state: State<'_, AppState>,
+) -> Result<usize, AppError> {
+    state
+        .prompt_service
+        .export_to_file(&path, format)
+        .map_err(|e| AppError::Prompt(e.to_string()))
+}
+
+/// Validation errors are passed through unchanged so the frontend can list
+/// every invalid entry.
+#[tauri::command]
+pub async fn import_prompts(
+    path: String,
+    collision_mode: PromptCollisionMode,
+    state: State<'_, AppState>,
+) -> Result<PromptImportResult, AppError> {
+    state.prompt_service.import_from_file(&path, collision_mode)
+}
+
+/// Save selected chat messages as a prompt. `variables_to_extract` are literals
+/// to turn into placeholders; validation errors are passed through unchanged.
+#[tauri::command]
+pub async fn create_prompt_from_messages(
+    chat_id: String,
+    message_ids: Vec<String>,
+    name: String,
+    folder: Option<String>,
+    variables_to_extract: Option<Vec<String>>,
+    options: Option<PromptFromMessagesOptions>,
+    state: State<'_, AppState>,
+) -> Result<PromptFromMessagesResult, AppError> {
+    let messages = state.message_service.get_by_chat_id(&chat_id)?;
+    state.prompt_service.create_from_messages(
+        &chat_id,
+        &messages,
+        &message_ids,
+        &name,
+        folder,
+        &variables_to_extract.unwrap_or_default(),
+        &options.unwrap_or_default(),
+    )
+}
@@ -3,6 +3,7 @@ pub mod models;
 pub mod repository;
 pub mod service;
 pub mod template_service;
+pub mod variable_extraction;
 
 pub use models::*;
 pub use repository::*;
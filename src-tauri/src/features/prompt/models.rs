@@ -7,6 +7,61 @@ pub struct Prompt {
     pub content: String,
     pub created_at: i64,
     pub updated_at: i64,
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// JSON provenance for prompts created from chat messages
+    #[serde(default)]
+    pub source: Option<String>,
+    #[serde(flatten)]
+    pub model_preference: PromptModelPreference,
+}
+
+/// A model feature a prompt can depend on
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum ModelCapability {
+    Vision,
+    Tools,
+    Thinking,
+}
+
+impl ModelCapability {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Vision => "vision",
+            Self::Tools => "tools",
+            Self::Thinking => "thinking",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "vision" => Some(Self::Vision),
+            "tools" => Some(Self::Tools),
+            "thinking" => Some(Self::Thinking),
+            _ => None,
+        }
+    }
+}
+
+/// Model a prompt is written for. When a message is sent from the prompt and
+/// the chat's model lacks a required capability, the preferred model is used
+/// for that turn instead.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PromptModelPreference {
+    /// Provider of the preferred connection ("openai", "anthropic", ...)
+    pub preferred_provider: Option<String>,
+    pub preferred_model: Option<String>,
+    pub required_capabilities: Vec<ModelCapability>,
+}
+
+impl PromptModelPreference {
+    pub const fn is_empty(&self) -> bool {
+        self.preferred_provider.is_none()
+            && self.preferred_model.is_none()
+            && self.required_capabilities.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -15,4 +70,87 @@ pub struct ParsedPromptTemplate {
     pub description: String,
     pub content: String,
     pub variables: Vec<String>,
+    /// Declared in an optional `## Model` section
+    #[serde(default)]
+    pub model_preference: PromptModelPreference,
+}
+
+/// File format for bulk prompt import/export
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PromptFileFormat {
+    Json,
+    Yaml,
+}
+
+/// What to do when an imported prompt has the same name as an existing one
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PromptCollisionMode {
+    Skip,
+    Overwrite,
+    Duplicate,
+}
+
+/// One prompt in an export file. `id` records where the prompt came from
+/// (hub-installed prompts keep their hub id); `variables` is informational
+/// and recomputed from the content on import.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptExportEntry {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub id: Option<String>,
+    pub name: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub variables: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptExportFile {
+    pub version: u32,
+    pub prompts: Vec<PromptExportEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PromptImportResult {
+    pub created: usize,
+    pub overwritten: usize,
+    pub duplicated: usize,
+    pub skipped: usize,
+}
+
+/// What to do with tool messages selected for a prompt
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolMessageHandling {
+    #[default]
+    Reject,
+    Stringify,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PromptFromMessagesOptions {
+    /// Join message contents without "User:" / "Assistant:" labels
+    pub merge_roles: bool,
+    pub tool_messages: ToolMessageHandling,
+    /// Also replace quoted literals, file names and dates with placeholders
+    pub auto_detect_variables: bool,
+    /// Return the content and variables without saving a prompt
+    pub preview: bool,
+}
+
+/// A literal replaced by `{{name}}` in an extracted prompt
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ExtractedVariable {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PromptFromMessagesResult {
+    /// None in preview mode
+    pub prompt: Option<Prompt>,
+    pub content: String,
+    pub variables: Vec<ExtractedVariable>,
 }
@@ -1,4 +1,4 @@
-use super::models::Prompt;
+use super::models::{Prompt, PromptModelPreference};
 use crate::error::AppError;
 use rusqlite::params;
 use std::sync::Arc;
@@ -7,9 +7,13 @@ use tauri::AppHandle;
 pub trait PromptRepository: Send + Sync {
     fn create(&self, prompt: &Prompt) -> Result<(), AppError>;
     fn get_all(&self) -> Result<Vec<Prompt>, AppError>;
-    #[allow(dead_code)]
     fn get_by_id(&self, id: &str) -> Result<Option<Prompt>, AppError>;
     fn update(&self, id: &str, name: Option<&str>, content: Option<&str>) -> Result<(), AppError>;
+    fn update_model_preference(
+        &self,
+        id: &str,
+        preference: &PromptModelPreference,
+    ) -> Result<(), AppError>;
     fn delete(&self, id: &str) -> Result<(), AppError>;
 }
 
@@ -27,8 +31,19 @@ impl PromptRepository for SqlitePromptRepository {
     fn create(&self, prompt: &Prompt) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute(
-            "INSERT INTO prompts (id, name, content, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-            params![prompt.id, prompt.name, prompt.content, prompt.created_at, prompt.updated_at],
+            "INSERT INTO prompts (id, name, content, created_at, updated_at, folder, source, preferred_provider, preferred_model, required_capabilities) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                prompt.id,
+                prompt.name,
+                prompt.content,
+                prompt.created_at,
+                prompt.updated_at,
+                prompt.folder,
+                prompt.source,
+                prompt.model_preference.preferred_provider,
+                prompt.model_preference.preferred_model,
+                capabilities_to_json(&prompt.model_preference)
+            ],
         )?;
         Ok(())
     }
@@ -36,19 +51,11 @@ impl PromptRepository for SqlitePromptRepository {
     fn get_all(&self) -> Result<Vec<Prompt>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let mut stmt = conn.prepare(
-            "SELECT id, name, content, created_at, updated_at FROM prompts ORDER BY updated_at DESC"
+            "SELECT id, name, content, created_at, updated_at, folder, source, preferred_provider, preferred_model, required_capabilities FROM prompts ORDER BY updated_at DESC"
         )?;
 
         let prompts = stmt
-            .query_map([], |row| {
-                Ok(Prompt {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    content: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                })
-            })?
+            .query_map([], row_to_prompt)?
             .collect::<Result<Vec<_>, _>>()?;
 
         Ok(prompts)
@@ -57,17 +64,9 @@ impl PromptRepository for SqlitePromptRepository {
     fn get_by_id(&self, id: &str) -> Result<Option<Prompt>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT id, name, content, created_at, updated_at FROM prompts WHERE id = ?1",
+            "SELECT id, name, content, created_at, updated_at, folder, source, preferred_provider, preferred_model, required_capabilities FROM prompts WHERE id = ?1",
             params![id],
-            |row| {
-                Ok(Prompt {
-                    id: row.get(0)?,
-                    name: row.get(1)?,
-                    content: row.get(2)?,
-                    created_at: row.get(3)?,
-                    updated_at: row.get(4)?,
-                })
-            },
+            row_to_prompt,
         );
 
         match result {
@@ -101,9 +100,60 @@ impl PromptRepository for SqlitePromptRepository {
         Ok(())
     }
 
+    fn update_model_preference(
+        &self,
+        id: &str,
+        preference: &PromptModelPreference,
+    ) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE prompts SET preferred_provider = ?1, preferred_model = ?2, required_capabilities = ?3, updated_at = ?4 WHERE id = ?5",
+            params![
+                preference.preferred_provider,
+                preference.preferred_model,
+                capabilities_to_json(preference),
+                chrono::Utc::now().timestamp_millis(),
+                id
+            ],
+        )?;
+        Ok(())
+    }
+
     fn delete(&self, id: &str) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute("DELETE FROM prompts WHERE id = ?1", params![id])?;
         Ok(())
     }
 }
+
+fn row_to_prompt(row: &rusqlite::Row) -> rusqlite::Result<Prompt> {
+    let capabilities: Option<String> = row.get(9)?;
+    Ok(Prompt {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        content: row.get(2)?,
+        created_at: row.get(3)?,
+        updated_at: row.get(4)?,
+        folder: row.get(5)?,
+        source: row.get(6)?,
+        model_preference: PromptModelPreference {
+            preferred_provider: row.get(7)?,
+            preferred_model: row.get(8)?,
+            // Unknown capability names from newer versions are dropped
+            required_capabilities: capabilities
+                .and_then(|json| serde_json::from_str::<Vec<serde_json::Value>>(&json).ok())
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|v| serde_json::from_value(v).ok())
+                .collect(),
+        },
+    })
+}
+
+/// Required capabilities as a JSON array, or NULL when there are none
+fn capabilities_to_json(preference: &PromptModelPreference) -> Option<String> {
+    if preference.required_capabilities.is_empty() {
+        return None;
+    }
+    serde_json::to_string(&preference.required_capabilities).ok()
+}
@@ -1,8 +1,23 @@
-use super::models::Prompt;
+use super::models::{
+    Prompt, PromptCollisionMode, PromptExportEntry, PromptExportFile, PromptFileFormat,
+    PromptFromMessagesOptions, PromptFromMessagesResult, PromptImportResult, PromptModelPreference,
+    ToolMessageHandling,
+};
 use super::repository::PromptRepository;
+use super::template_service::PromptTemplateService;
+use super::variable_extraction::extract_variables;
 use crate::error::AppError;
+use crate::features::message::Message;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
 use std::sync::Arc;
 
+/// Version written to prompt export files; newer files are rejected on import.
+const PROMPT_FILE_VERSION: u32 = 1;
+/// Longest prompt that can be created from chat messages.
+const PROMPT_FROM_MESSAGES_MAX_CHARS: usize = 20_000;
+
 pub struct PromptService {
     repository: Arc<dyn PromptRepository>,
 }
@@ -12,7 +27,13 @@ impl PromptService {
         Self { repository }
     }
 
-    pub fn create(&self, id: String, name: String, content: String) -> Result<Prompt, AppError> {
+    pub fn create(
+        &self,
+        id: String,
+        name: String,
+        content: String,
+        model_preference: PromptModelPreference,
+    ) -> Result<Prompt, AppError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
             .unwrap()
@@ -24,6 +45,9 @@ impl PromptService {
             content,
             created_at: now,
             updated_at: now,
+            folder: None,
+            source: None,
+            model_preference,
         };
 
         self.repository.create(&prompt)?;
@@ -34,7 +58,6 @@ impl PromptService {
         self.repository.get_all()
     }
 
-    #[allow(dead_code)]
     pub fn get_by_id(&self, id: &str) -> Result<Option<Prompt>, AppError> {
         self.repository.get_by_id(id)
     }
@@ -44,12 +67,444 @@ impl PromptService {
         id: String,
         name: Option<String>,
         content: Option<String>,
+        model_preference: Option<PromptModelPreference>,
     ) -> Result<(), AppError> {
         self.repository
-            .update(&id, name.as_deref(), content.as_deref())
+            .update(&id, name.as_deref(), content.as_deref())?;
+        if let Some(preference) = model_preference {
+            self.repository.update_model_preference(&id, &preference)?;
+        }
+        Ok(())
     }
 
     pub fn delete(&self, id: String) -> Result<(), AppError> {
         self.repository.delete(&id)
     }
+
+    /// Build a prompt from selected messages of a chat, in chat order.
+    /// `chat_messages` are all messages of the chat; every selected id must be
+    /// among them. `literals` (and, with auto-detection, quoted text, file
+    /// names and dates) are replaced with placeholders and returned so the
+    /// user can confirm or rename them.
+    #[allow(clippy::too_many_arguments)]
+    pub fn create_from_messages(
+        &self,
+        chat_id: &str,
+        chat_messages: &[Message],
+        message_ids: &[String],
+        name: &str,
+        folder: Option<String>,
+        literals: &[String],
+        options: &PromptFromMessagesOptions,
+    ) -> Result<PromptFromMessagesResult, AppError> {
+        if name.trim().is_empty() && !options.preview {
+            return Err(AppError::Validation("Prompt name is required".to_string()));
+        }
+        if message_ids.is_empty() {
+            return Err(AppError::Validation("No messages selected".to_string()));
+        }
+        if let Some(id) = message_ids
+            .iter()
+            .find(|id| !chat_messages.iter().any(|m| &m.id == *id))
+        {
+            return Err(AppError::Validation(format!(
+                "Message {id} does not belong to chat {chat_id}"
+            )));
+        }
+
+        let mut sections = Vec::new();
+        let mut source_ids = Vec::new();
+        for message in chat_messages.iter().filter(|m| message_ids.contains(&m.id)) {
+            let label = match message.role.as_str() {
+                "user" => "User",
+                "assistant" => "Assistant",
+                "tool" | "tool_call" if options.tool_messages == ToolMessageHandling::Reject => {
+                    return Err(AppError::Validation(format!(
+                        "Message {} is a tool message; deselect it or stringify tool messages",
+                        message.id
+                    )));
+                }
+                "tool_call" => "Tool call",
+                "tool" => "Tool result",
+                _ => "Message",
+            };
+            source_ids.push(message.id.clone());
+
+            let content = message.content.trim();
+            if content.is_empty() {
+                continue;
+            }
+            sections.push(if options.merge_roles {
+                content.to_string()
+            } else {
+                format!("{label}:\n{content}")
+            });
+        }
+
+        let (content, variables) = extract_variables(
+            &sections.join("\n\n"),
+            literals,
+            options.auto_detect_variables,
+        );
+        if content.trim().is_empty() {
+            return Err(AppError::Validation(
+                "The selected messages have no content".to_string(),
+            ));
+        }
+        let length = content.chars().count();
+        if length > PROMPT_FROM_MESSAGES_MAX_CHARS {
+            return Err(AppError::Validation(format!(
+                "Prompt is too long ({length}/{PROMPT_FROM_MESSAGES_MAX_CHARS} characters); select fewer messages"
+            )));
+        }
+
+        if options.preview {
+            return Ok(PromptFromMessagesResult {
+                prompt: None,
+                content,
+                variables,
+            });
+        }
+
+        let now = chrono::Utc::now().timestamp_millis();
+        let prompt = Prompt {
+            id: uuid::Uuid::new_v4().to_string(),
+            name: name.trim().to_string(),
+            content: content.clone(),
+            created_at: now,
+            updated_at: now,
+            folder: folder.filter(|f| !f.trim().is_empty()),
+            source: Some(
+                serde_json::json!({
+                    "type": "chat",
+                    "chatId": chat_id,
+                    "messageIds": source_ids,
+                })
+                .to_string(),
+            ),
+            model_preference: PromptModelPreference::default(),
+        };
+        self.repository.create(&prompt)?;
+
+        Ok(PromptFromMessagesResult {
+            prompt: Some(prompt),
+            content,
+            variables,
+        })
+    }
+
+    /// Write every prompt to a single JSON or YAML file. Returns the number of
+    /// prompts written.
+    pub fn export_to_file(&self, path: &str, format: PromptFileFormat) -> Result<usize, AppError> {
+        let template_service = PromptTemplateService::new();
+        let mut prompts = self.repository.get_all()?;
+        // Sorted by name so exports diff cleanly between runs
+        prompts.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let file = PromptExportFile {
+            version: PROMPT_FILE_VERSION,
+            prompts: prompts
+                .into_iter()
+                .map(|p| PromptExportEntry {
+                    variables: template_service.extract_variables(&p.content),
+                    id: Some(p.id),
+                    name: p.name,
+                    content: p.content,
+                })
+                .collect(),
+        };
+
+        let text = match format {
+            PromptFileFormat::Json => serde_json::to_string_pretty(&file)?,
+            // Multi-line bodies are written as block scalars; ones YAML cannot
+            // hold that way (CR line endings, tabs) are escaped in quotes.
+            // Either way they round-trip unchanged
+            PromptFileFormat::Yaml => serde_yaml::to_string(&file)
+                .map_err(|e| AppError::Prompt(format!("Failed to serialize prompts: {e}")))?,
+        };
+        fs::write(path, text)
+            .map_err(|e| AppError::Prompt(format!("Failed to write {path}: {e}")))?;
+
+        Ok(file.prompts.len())
+    }
+
+    /// Import prompts from a JSON or YAML export file. Existing prompts are
+    /// matched by name and handled according to `collision_mode`.
+    ///
+    /// The whole file is validated first; if any entry is invalid nothing is
+    /// imported and all entry errors are returned in one `Validation` error.
+    pub fn import_from_file(
+        &self,
+        path: &str,
+        collision_mode: PromptCollisionMode,
+    ) -> Result<PromptImportResult, AppError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| AppError::Prompt(format!("Failed to read {path}: {e}")))?;
+        let entries = Self::parse_prompt_file(path, &text)?;
+
+        let mut by_name: HashMap<String, Prompt> = HashMap::new();
+        for prompt in self.repository.get_all()? {
+            by_name.entry(prompt.name.clone()).or_insert(prompt);
+        }
+
+        let mut result = PromptImportResult::default();
+        for entry in entries {
+            match (by_name.get(&entry.name), collision_mode) {
+                (None, _) => {
+                    let prompt = self.create(
+                        self.import_id(entry.id.as_deref())?,
+                        entry.name,
+                        entry.content,
+                        PromptModelPreference::default(),
+                    )?;
+                    by_name.insert(prompt.name.clone(), prompt);
+                    result.created += 1;
+                }
+                (Some(_), PromptCollisionMode::Skip) => result.skipped += 1,
+                (Some(existing), PromptCollisionMode::Overwrite) => {
+                    self.repository
+                        .update(&existing.id, None, Some(&entry.content))?;
+                    result.overwritten += 1;
+                }
+                (Some(_), PromptCollisionMode::Duplicate) => {
+                    let mut n = 2;
+                    let name = loop {
+                        let candidate = format!("{} ({n})", entry.name);
+                        if !by_name.contains_key(&candidate) {
+                            break candidate;
+                        }
+                        n += 1;
+                    };
+                    let prompt = self.create(
+                        uuid::Uuid::new_v4().to_string(),
+                        name,
+                        entry.content,
+                        PromptModelPreference::default(),
+                    )?;
+                    by_name.insert(prompt.name.clone(), prompt);
+                    result.duplicated += 1;
+                }
+            }
+        }
+
+        Ok(result)
+    }
+
+    /// Keep the exported id (hub provenance) unless it is already taken.
+    fn import_id(&self, id: Option<&str>) -> Result<String, AppError> {
+        match id.filter(|id| !id.is_empty()) {
+            Some(id) if self.repository.get_by_id(id)?.is_none() => Ok(id.to_string()),
+            _ => Ok(uuid::Uuid::new_v4().to_string()),
+        }
+    }
+
+    /// Parse an export file (by extension: `.json` as JSON, anything else as
+    /// YAML) into validated entries. Accepts either the export object or a bare
+    /// list of prompts.
+    fn parse_prompt_file(path: &str, text: &str) -> Result<Vec<PromptExportEntry>, AppError> {
+        let is_json = Path::new(path)
+            .extension()
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        let value: serde_json::Value = if is_json {
+            serde_json::from_str(text)
+                .map_err(|e| AppError::Validation(format!("Invalid JSON: {e}")))?
+        } else {
+            serde_yaml::from_str(text)
+                .map_err(|e| AppError::Validation(format!("Invalid YAML: {e}")))?
+        };
+
+        let items = match value {
+            serde_json::Value::Array(items) => items,
+            serde_json::Value::Object(mut file) => {
+                if let Some(version) = file.get("version").and_then(serde_json::Value::as_u64) {
+                    if version > u64::from(PROMPT_FILE_VERSION) {
+                        return Err(AppError::Validation(format!(
+                            "Unsupported prompt file version {version}"
+                        )));
+                    }
+                }
+                match file.remove("prompts") {
+                    Some(serde_json::Value::Array(items)) => items,
+                    _ => {
+                        return Err(AppError::Validation(
+                            "Expected a \"prompts\" list".to_string(),
+                        ))
+                    }
+                }
+            }
+            _ => {
+                return Err(AppError::Validation(
+                    "Expected a list of prompts".to_string(),
+                ))
+            }
+        };
+
+        let mut entries = Vec::new();
+        let mut errors = Vec::new();
+        for (index, item) in items.into_iter().enumerate() {
+            let number = index + 1;
+            match serde_json::from_value::<PromptExportEntry>(item) {
+                Ok(entry) if entry.name.trim().is_empty() => {
+                    errors.push(format!("Entry {number}: name is empty"));
+                }
+                Ok(entry) if entry.content.trim().is_empty() => {
+                    errors.push(format!("Entry {number} ({}): content is empty", entry.name));
+                }
+                Ok(entry) => entries.push(entry),
+                Err(e) => errors.push(format!("Entry {number}: {e}")),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Invalid prompt file:\n{}",
+                errors.join("\n")
+            )));
+        }
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Prompts kept in memory, for service tests.
+    #[derive(Default)]
+    struct MemoryPromptRepository(std::sync::Mutex<Vec<crate::features::prompt::models::Prompt>>);
+
+    impl crate::features::prompt::repository::PromptRepository for MemoryPromptRepository {
+        fn create(
+            &self,
+            prompt: &crate::features::prompt::models::Prompt,
+        ) -> Result<(), crate::error::AppError> {
+            self.0.lock().unwrap().push(prompt.clone());
+            Ok(())
+        }
+
+        fn get_all(
+            &self,
+        ) -> Result<Vec<crate::features::prompt::models::Prompt>, crate::error::AppError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn get_by_id(
+            &self,
+            id: &str,
+        ) -> Result<Option<crate::features::prompt::models::Prompt>, crate::error::AppError>
+        {
+            Ok(self.0.lock().unwrap().iter().find(|p| p.id == id).cloned())
+        }
+
+        fn update(
+            &self,
+            id: &str,
+            name: Option<&str>,
+            content: Option<&str>,
+        ) -> Result<(), crate::error::AppError> {
+            if let Some(prompt) = self.0.lock().unwrap().iter_mut().find(|p| p.id == id) {
+                if let Some(name) = name {
+                    prompt.name = name.to_string();
+                }
+                if let Some(content) = content {
+                    prompt.content = content.to_string();
+                }
+            }
+            Ok(())
+        }
+
+        fn update_model_preference(
+            &self,
+            _: &str,
+            _: &crate::features::prompt::models::PromptModelPreference,
+        ) -> Result<(), crate::error::AppError> {
+            unreachable!()
+        }
+
+        fn delete(&self, id: &str) -> Result<(), crate::error::AppError> {
+            self.0.lock().unwrap().retain(|p| p.id != id);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn prompt_files_round_trip_fences_crlf_and_unicode() {
+        use crate::features::prompt::models::{
+            PromptCollisionMode, PromptFileFormat, PromptModelPreference,
+        };
+        use crate::features::prompt::service::PromptService;
+        use std::sync::Arc;
+
+        let bodies = [
+            (
+                "Code review",
+                "Review this:\n\n```rust\nfn main() {\n    println!(\"{{greeting}}\");\n}\n```\n\nBe terse.",
+            ),
+            ("Windows notes", "First line\r\nSecond line\r\n\r\nLast line\r\n"),
+            (
+                "Unicode",
+                "Résumé für {{name}} — 日本語のテキスト 🚀\n\tIndented with a tab\n",
+            ),
+            ("Indented", "    starts indented\n\n\ntrailing blank lines\n\n\n"),
+        ];
+        let dir = tempfile::tempdir().unwrap();
+        let source = PromptService::new(Arc::new(MemoryPromptRepository::default()));
+        for (i, (name, content)) in bodies.iter().enumerate() {
+            source
+                .create(
+                    format!("p{i}"),
+                    (*name).to_string(),
+                    (*content).to_string(),
+                    PromptModelPreference::default(),
+                )
+                .unwrap();
+        }
+
+        for (format, file) in [
+            (PromptFileFormat::Json, "prompts.json"),
+            (PromptFileFormat::Yaml, "prompts.yaml"),
+        ] {
+            let path = dir.path().join(file);
+            let path = path.to_str().unwrap();
+            assert_eq!(source.export_to_file(path, format).unwrap(), bodies.len());
+            if format == PromptFileFormat::Yaml {
+                let text = std::fs::read_to_string(path).unwrap();
+                // LF bodies are block scalars, so the fence stays readable
+                assert!(text.lines().any(|line| line.trim_start() == "```rust"));
+                // YAML has no block scalar for CR; that body is escaped instead
+                assert!(text.contains("First line\\r\\nSecond line"));
+            }
+
+            let target = PromptService::new(Arc::new(MemoryPromptRepository::default()));
+            let result = target
+                .import_from_file(path, PromptCollisionMode::Skip)
+                .unwrap();
+            assert_eq!(result.created, bodies.len());
+            for (i, (name, content)) in bodies.iter().enumerate() {
+                let prompt = target
+                    .get_all()
+                    .unwrap()
+                    .into_iter()
+                    .find(|p| p.name == *name);
+                let prompt = prompt.unwrap();
+                assert_eq!(prompt.content, *content, "{file}: {name}");
+                assert_eq!(prompt.id, format!("p{i}"));
+            }
+
+            // Importing the same file again follows the collision mode
+            let again = target
+                .import_from_file(path, PromptCollisionMode::Overwrite)
+                .unwrap();
+            assert_eq!((again.created, again.overwritten), (0, bodies.len()));
+            let again = target
+                .import_from_file(path, PromptCollisionMode::Duplicate)
+                .unwrap();
+            assert_eq!(again.duplicated, bodies.len());
+            let copy = target
+                .get_all()
+                .unwrap()
+                .into_iter()
+                .find(|p| p.name == "Windows notes (2)")
+                .unwrap();
+            assert_eq!(copy.content, bodies[1].1);
+        }
+    }
 }
@@ -1,4 +1,4 @@
-use super::models::ParsedPromptTemplate;
+use super::models::{ModelCapability, ParsedPromptTemplate, PromptModelPreference};
 use crate::error::AppError;
 use regex::Regex;
 
@@ -51,14 +51,66 @@ impl PromptTemplateService {
         // Extract variables from content using regex {{variable_name}}
         let variables = self.extract_variables(&content);
 
+        let model_preference = self.extract_model_preference(markdown);
+
         Ok(ParsedPromptTemplate {
             title: title.unwrap_or_else(|| "Untitled".to_string()),
             description,
             content,
             variables,
+            model_preference,
         })
     }
 
+    /// Extract the model preference from an optional ## Model section:
+    ///
+    /// ```text
+    /// ## Model
+    /// - provider: anthropic
+    /// - model: claude-sonnet-4
+    /// - requires: vision, tools
+    /// ```
+    ///
+    /// Unknown keys and capabilities are ignored.
+    fn extract_model_preference(&self, markdown: &str) -> PromptModelPreference {
+        let mut preference = PromptModelPreference::default();
+        let mut in_model_section = false;
+
+        for line in markdown.lines() {
+            let trimmed = line.trim();
+            if trimmed.starts_with("## ") {
+                in_model_section = trimmed == "## Model";
+                continue;
+            }
+            if !in_model_section {
+                continue;
+            }
+
+            let entry = trimmed.trim_start_matches(['-', '*']).trim();
+            let Some((key, value)) = entry.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim().to_lowercase().as_str() {
+                "provider" => preference.preferred_provider = Some(value.to_lowercase()),
+                "model" => preference.preferred_model = Some(value.to_string()),
+                "requires" | "capabilities" => {
+                    for capability in value.split(',').filter_map(ModelCapability::parse) {
+                        if !preference.required_capabilities.contains(&capability) {
+                            preference.required_capabilities.push(capability);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        preference
+    }
+
     /// Extract title from first # header
     fn extract_title(&self, markdown: &str) -> Option<String> {
         let title_regex = Regex::new(r"^#\s+(.+)$").ok()?;
@@ -89,8 +141,8 @@ impl PromptTemplateService {
                 continue;
             }
 
-            // Stop at ## Prompt section
-            if trimmed.starts_with("## Prompt") {
+            // Stop at ## Prompt section (or a ## Model section placed before it)
+            if trimmed.starts_with("## Prompt") || trimmed == "## Model" {
                 break;
             }
 
@@ -140,14 +192,21 @@ impl PromptTemplateService {
             }
         } else {
             // Freestyle: if no ## Prompt section, use everything except the primary title
+            // and the ## Model section
             let mut found_title = false;
+            let mut in_model_section = false;
             for line in lines {
                 let trimmed = line.trim();
                 if trimmed.starts_with("# ") && !found_title {
                     found_title = true;
                     continue;
                 }
-                content_lines.push(line);
+                if trimmed.starts_with("## ") {
+                    in_model_section = trimmed == "## Model";
+                }
+                if !in_model_section {
+                    content_lines.push(line);
+                }
             }
         }
 
@@ -155,7 +214,7 @@ impl PromptTemplateService {
     }
 
     /// Extract variables from content using regex {{`variable_name`}}
-    fn extract_variables(&self, content: &str) -> Vec<String> {
+    pub fn extract_variables(&self, content: &str) -> Vec<String> {
         let variable_regex = Regex::new(r"\{\{(\w+)\}\}").unwrap();
         let mut variables = std::collections::HashSet::new();
 
@@ -176,3 +235,33 @@ impl Default for PromptTemplateService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn prompt_template_reads_the_model_section() {
+        use crate::features::prompt::{ModelCapability, PromptTemplateService};
+
+        let markdown = "# Describe image\n\nDescribes a picture.\n\n## Model\n- provider: OpenAI\n- model: gpt-4o\n- requires: vision, tools, teleport\n\n## Prompt\nDescribe {{subject}}.\n";
+        let parsed = PromptTemplateService::new()
+            .parse_markdown_template(markdown)
+            .unwrap();
+        assert_eq!(parsed.description, "Describes a picture.");
+        assert_eq!(parsed.content, "Describe {{subject}}.");
+        let preference = parsed.model_preference;
+        assert_eq!(preference.preferred_provider.as_deref(), Some("openai"));
+        assert_eq!(preference.preferred_model.as_deref(), Some("gpt-4o"));
+        assert_eq!(
+            preference.required_capabilities,
+            [ModelCapability::Vision, ModelCapability::Tools]
+        );
+
+        // Freestyle templates do not leak the section into the content
+        let freestyle = "# Note\n\nSummarize this.\n\n## Model\nmodel: llama3\n";
+        let parsed = PromptTemplateService::new()
+            .parse_markdown_template(freestyle)
+            .unwrap();
+        assert_eq!(parsed.content, "Summarize this.");
+        assert!(parsed.model_preference.required_capabilities.is_empty());
+    }
+}
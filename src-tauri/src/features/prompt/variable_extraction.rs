@@ -0,0 +1,256 @@
+use super::models::ExtractedVariable;
+use regex::Regex;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{\s*(\w+)\s*\}\}").unwrap());
+
+static DATE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:\d{4}-\d{2}-\d{2}|\d{1,2}/\d{1,2}/\d{2,4}|(?:Jan|Feb|Mar|Apr|May|Jun|Jul|Aug|Sep|Sept|Oct|Nov|Dec)[a-z]*\.? \d{1,2}(?:, \d{4})?)\b",
+    )
+    .unwrap()
+});
+
+static FILE_NAME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b[\w-]+(?:\.[\w-]+)*\.(?:txt|md|csv|tsv|json|ya?ml|toml|xml|html?|css|pdf|docx?|xlsx?|pptx?|png|jpe?g|gif|webp|svg|py|rs|js|jsx|ts|tsx|go|java|rb|sh|sql|log)\b",
+    )
+    .unwrap()
+});
+
+/// Double-quoted, curly-quoted, or single-quoted text (single quotes only at
+/// word boundaries, so apostrophes are not treated as quotes).
+static QUOTED: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r#""([^"\n]{2,80})"|“([^”\n]{2,80})”|(?:^|[\s(\[])'([^'\n]{2,80})'(?:$|[\s.,;:!?)\]])"#,
+    )
+    .unwrap()
+});
+
+/// Replace literals in `text` with `{{placeholders}}`.
+///
+/// `literals` are always replaced; with `auto_detect`, dates, file names and
+/// quoted text are added as candidates. Every occurrence of a literal maps to
+/// the same placeholder, text inside existing `{{...}}` placeholders is left
+/// alone, and new names never reuse an existing placeholder name. Longer
+/// literals win when candidates overlap.
+pub fn extract_variables(
+    text: &str,
+    literals: &[String],
+    auto_detect: bool,
+) -> (String, Vec<ExtractedVariable>) {
+    let placeholders: Vec<(usize, usize)> = PLACEHOLDER
+        .find_iter(text)
+        .map(|m| (m.start(), m.end()))
+        .collect();
+    let mut taken_names: HashSet<String> = PLACEHOLDER
+        .captures_iter(text)
+        .filter_map(|c| c.get(1).map(|m| m.as_str().to_string()))
+        .collect();
+
+    // (literal, base name), user-selected literals first
+    let mut candidates: Vec<(String, String)> = literals
+        .iter()
+        .map(|l| l.trim())
+        .filter(|l| !l.is_empty())
+        .map(|l| (l.to_string(), slug(l, "value")))
+        .collect();
+
+    if auto_detect {
+        for (regex, base) in [(&*DATE, "date"), (&*FILE_NAME, "file_name")] {
+            for m in regex.find_iter(text) {
+                if !overlaps(&placeholders, m.start(), m.end()) {
+                    candidates.push((m.as_str().to_string(), base.to_string()));
+                }
+            }
+        }
+        for caps in QUOTED.captures_iter(text) {
+            let Some(inner) = caps.iter().skip(1).flatten().next() else {
+                continue;
+            };
+            let literal = inner.as_str().trim();
+            if !literal.is_empty() && !overlaps(&placeholders, inner.start(), inner.end()) {
+                candidates.push((literal.to_string(), slug(literal, "text")));
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    candidates.retain(|(literal, _)| seen.insert(literal.clone()));
+    candidates.sort_by_key(|(literal, _)| std::cmp::Reverse(literal.len()));
+
+    // (start, end, variable index)
+    let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+    let mut variables: Vec<ExtractedVariable> = Vec::new();
+    for (literal, base) in candidates {
+        let mut index = None;
+        for (start, _) in text.match_indices(&literal) {
+            let end = start + literal.len();
+            if !at_word_boundary(text, start, end)
+                || overlaps(&placeholders, start, end)
+                || spans.iter().any(|&(s, e, _)| start < e && s < end)
+            {
+                continue;
+            }
+            let index = *index.get_or_insert_with(|| {
+                variables.push(ExtractedVariable {
+                    name: unique_name(&base, &mut taken_names),
+                    value: literal.clone(),
+                });
+                variables.len() - 1
+            });
+            spans.push((start, end, index));
+        }
+    }
+
+    spans.sort_unstable();
+    let mut output = String::with_capacity(text.len());
+    let mut cursor = 0;
+    for (start, end, index) in spans {
+        output.push_str(&text[cursor..start]);
+        output.push_str("{{");
+        output.push_str(&variables[index].name);
+        output.push_str("}}");
+        cursor = end;
+    }
+    output.push_str(&text[cursor..]);
+
+    (output, variables)
+}
+
+fn overlaps(ranges: &[(usize, usize)], start: usize, end: usize) -> bool {
+    ranges.iter().any(|&(s, e)| start < e && s < end)
+}
+
+/// A match that starts or ends with a word character must not continue a
+/// longer word ("cat" in "category").
+fn at_word_boundary(text: &str, start: usize, end: usize) -> bool {
+    let is_word = |c: char| c.is_alphanumeric() || c == '_';
+    let literal = &text[start..end];
+    let before_ok = !literal.starts_with(is_word) || !text[..start].ends_with(is_word);
+    let after_ok = !literal.ends_with(is_word) || !text[end..].starts_with(is_word);
+    before_ok && after_ok
+}
+
+/// Placeholder name from a literal: lowercase ASCII words joined by `_`,
+/// at most four words. Falls back to `fallback` when nothing usable remains.
+fn slug(literal: &str, fallback: &str) -> String {
+    let words: Vec<String> = literal
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .take(4)
+        .map(str::to_ascii_lowercase)
+        .collect();
+    let name = words.join("_");
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        fallback.to_string()
+    } else {
+        name
+    }
+}
+
+fn unique_name(base: &str, taken: &mut HashSet<String>) -> String {
+    let mut name = base.to_string();
+    let mut n = 2;
+    while taken.contains(&name) {
+        name = format!("{base}_{n}");
+        n += 1;
+    }
+    taken.insert(name.clone());
+    name
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn a_literal_seen_twice_becomes_one_variable() {
+        use crate::features::prompt::variable_extraction::extract_variables;
+        use crate::features::prompt::ExtractedVariable;
+
+        let variable = |name: &str, value: &str| ExtractedVariable {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        // Selected twice, and detected as a file name at both places
+        let (text, variables) = extract_variables(
+            "Summarize report.pdf for Acme. Then email Acme the report.pdf summary.",
+            &["Acme".to_string(), " Acme ".to_string()],
+            true,
+        );
+        assert_eq!(
+            text,
+            "Summarize {{file_name}} for {{acme}}. Then email {{acme}} the {{file_name}} summary."
+        );
+        assert_eq!(
+            variables,
+            [
+                variable("file_name", "report.pdf"),
+                variable("acme", "Acme")
+            ]
+        );
+
+        // Quoted once and bare once: both are the same variable
+        let (text, variables) = extract_variables(
+            r#"Translate "good morning" into French. Keep good morning casual."#,
+            &[],
+            true,
+        );
+        assert_eq!(
+            text,
+            r#"Translate "{{good_morning}}" into French. Keep {{good_morning}} casual."#
+        );
+        assert_eq!(variables, [variable("good_morning", "good morning")]);
+
+        // Inside a longer word it is not the literal
+        let (text, variables) =
+            extract_variables("The cat sat in the category.", &["cat".to_string()], false);
+        assert_eq!(text, "The {{cat}} sat in the category.");
+        assert_eq!(variables.len(), 1);
+    }
+
+    #[test]
+    fn existing_placeholders_are_kept_and_never_reused() {
+        use crate::features::prompt::variable_extraction::extract_variables;
+        use crate::features::prompt::ExtractedVariable;
+
+        let variable = |name: &str, value: &str| ExtractedVariable {
+            name: name.to_string(),
+            value: value.to_string(),
+        };
+
+        let (text, variables) = extract_variables(
+            "Hi {{name}}, your file is {{ file_name }}. Send notes.txt to name@example.com by 2024-05-01.",
+            &["name".to_string()],
+            true,
+        );
+        // The placeholders stay as they were; new names step around theirs
+        assert_eq!(
+            text,
+            "Hi {{name}}, your file is {{ file_name }}. Send {{file_name_2}} to {{name_2}}@example.com by {{date}}."
+        );
+        assert_eq!(
+            variables,
+            [
+                variable("date", "2024-05-01"),
+                variable("file_name_2", "notes.txt"),
+                variable("name_2", "name"),
+            ]
+        );
+
+        // A literal only found inside placeholders extracts nothing
+        let original = "Dear {{name}}, see {{report}}.";
+        let (text, variables) = extract_variables(
+            original,
+            &[
+                "{{name}}".to_string(),
+                "name".to_string(),
+                "report".to_string(),
+            ],
+            true,
+        );
+        assert_eq!(text, original);
+        assert!(variables.is_empty());
+    }
+}
@@ -0,0 +1,208 @@
+//! The ephemeral channel quick action runs report on.
+//!
+//! A preview streams through the provider like a chat turn, under a channel
+//! id in place of the chat id and the run id in place of a message id. The
+//! message emitter sends chunks for such ids as `QuickActionEvent`s and drops
+//! the provider's other message events; the run reports its start, answer
+//! and failure itself, for previews and chat answers alike.
+
+use super::models::QuickAction;
+use crate::error::AppError;
+use crate::events::{QuickActionEvent, TokenUsage};
+use crate::models::llm_types::LLMChatResponse;
+use std::future::Future;
+
+/// Chat ids starting with this belong to a quick action preview.
+pub const CHANNEL_PREFIX: &str = "quick_action:";
+
+/// The chat id a preview run streams under.
+pub fn channel_id(run_id: &str) -> String {
+    format!("{CHANNEL_PREFIX}{run_id}")
+}
+
+/// The run id of a preview channel, or `None` for a real chat.
+pub fn run_id_of(chat_id: &str) -> Option<&str> {
+    chat_id.strip_prefix(CHANNEL_PREFIX)
+}
+
+/// The event a provider chunk on the channel of `run_id` is sent as.
+pub fn chunk_event(run_id: &str, chunk: String) -> QuickActionEvent {
+    QuickActionEvent::Chunk {
+        action_run_id: run_id.to_string(),
+        chunk,
+    }
+}
+
+/// Run a quick action with `call`, which sends the request under the given
+/// chat and message ids. `target` is the chat and assistant message of an
+/// `append_to_chat` run; previews use the run's channel.
+pub async fn run<E, F, Fut>(
+    run_id: &str,
+    action: &QuickAction,
+    target: Option<(String, String)>,
+    mut emit: E,
+    call: F,
+) -> Result<LLMChatResponse, AppError>
+where
+    E: FnMut(QuickActionEvent),
+    F: FnOnce(String, String) -> Fut,
+    Fut: Future<Output = Result<LLMChatResponse, AppError>>,
+{
+    emit(QuickActionEvent::Started {
+        action_run_id: run_id.to_string(),
+        action_id: action.id.clone(),
+        output_mode: action.output_mode.as_str().to_string(),
+        chat_id: target.as_ref().map(|(chat_id, _)| chat_id.clone()),
+        message_id: target.as_ref().map(|(_, message_id)| message_id.clone()),
+    });
+
+    let (chat_id, message_id) = target.unwrap_or_else(|| (channel_id(run_id), run_id.to_string()));
+    let result = call(chat_id, message_id).await;
+
+    emit(match &result {
+        Ok(response) => QuickActionEvent::Complete {
+            action_run_id: run_id.to_string(),
+            content: response.content.clone(),
+            token_usage: response.usage.as_ref().map(|u| TokenUsage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+            }),
+        },
+        Err(e) => QuickActionEvent::Error {
+            action_run_id: run_id.to_string(),
+            error: e.to_string(),
+        },
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    fn quick_action(
+        output_mode: crate::features::quick_action::QuickActionOutputMode,
+    ) -> crate::features::quick_action::QuickAction {
+        crate::features::quick_action::QuickAction {
+            id: "qa-1".to_string(),
+            workspace_id: "ws-1".to_string(),
+            name: "Make it shorter".to_string(),
+            prompt_template: "Shorten this:\n\n{{selection}}".to_string(),
+            preferred_model: None,
+            output_mode,
+            shortcut: Some("Mod+Shift+S".to_string()),
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    fn quick_action_response(content: &str) -> crate::models::llm_types::LLMChatResponse {
+        crate::models::llm_types::LLMChatResponse {
+            content: content.to_string(),
+            finish_reason: Some("stop".to_string()),
+            tool_calls: None,
+            usage: None,
+            reasoning: None,
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        }
+    }
+
+    #[test]
+    fn quick_action_preview_streams_on_its_own_channel() {
+        use crate::features::quick_action::{channel, QuickActionOutputMode};
+        use std::sync::{Arc, Mutex};
+
+        let action = quick_action(QuickActionOutputMode::ReplacePreview);
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let sink = events.clone();
+        let provider_events = events.clone();
+
+        let response = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(channel::run(
+                "run-1",
+                &action,
+                None,
+                move |event| sink.lock().unwrap().push(event),
+                |chat_id, message_id| async move {
+                    // What the message emitter does with the provider's chunks
+                    assert_eq!(message_id, "run-1");
+                    let run_id = channel::run_id_of(&chat_id).expect("preview uses its channel");
+                    for chunk in ["Short", "er."] {
+                        provider_events
+                            .lock()
+                            .unwrap()
+                            .push(channel::chunk_event(run_id, chunk.to_string()));
+                    }
+                    Ok(quick_action_response("Shorter."))
+                },
+            ))
+            .unwrap();
+
+        assert_eq!(response.content, "Shorter.");
+        let events: Vec<_> = events
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|e| serde_json::to_value(e).unwrap())
+            .collect();
+        assert_eq!(
+            events,
+            [
+                serde_json::json!({"kind": "started", "action_run_id": "run-1", "action_id": "qa-1", "output_mode": "replace_preview", "chat_id": null, "message_id": null}),
+                serde_json::json!({"kind": "chunk", "action_run_id": "run-1", "chunk": "Short"}),
+                serde_json::json!({"kind": "chunk", "action_run_id": "run-1", "chunk": "er."}),
+                serde_json::json!({"kind": "complete", "action_run_id": "run-1", "content": "Shorter.", "token_usage": null}),
+            ]
+        );
+    }
+
+    #[test]
+    fn quick_action_appended_to_chat_streams_into_the_exchange() {
+        use crate::error::AppError;
+        use crate::features::quick_action::{channel, QuickActionOutputMode};
+
+        let action = quick_action(QuickActionOutputMode::AppendToChat);
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let target = Some(("chat-1".to_string(), "answer-1".to_string()));
+
+        let mut events = Vec::new();
+        let mut streamed_to = None;
+        let response = runtime
+            .block_on(channel::run(
+                "run-2",
+                &action,
+                target.clone(),
+                |event| events.push(serde_json::to_value(event).unwrap()),
+                |chat_id, message_id| {
+                    // A real chat is not a channel, so its chunks stay message events
+                    assert!(channel::run_id_of(&chat_id).is_none());
+                    streamed_to = Some((chat_id, message_id));
+                    async { Ok(quick_action_response("Done.")) }
+                },
+            ))
+            .unwrap();
+
+        assert_eq!(response.content, "Done.");
+        assert_eq!(streamed_to, target);
+        assert_eq!(events[0]["chat_id"], "chat-1");
+        assert_eq!(events[0]["message_id"], "answer-1");
+        assert_eq!(events[0]["output_mode"], "append_to_chat");
+        assert_eq!(events[1]["kind"], "complete");
+
+        let mut events = Vec::new();
+        let failed = runtime.block_on(channel::run(
+            "run-3",
+            &action,
+            target,
+            |event| events.push(serde_json::to_value(event).unwrap()),
+            |_, _| async { Err(AppError::Llm("rate limited".to_string())) },
+        ));
+        assert!(failed.is_err());
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1]["kind"], "error");
+        assert_eq!(events[1]["action_run_id"], "run-3");
+    }
+}
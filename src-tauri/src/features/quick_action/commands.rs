@@ -0,0 +1,79 @@
+use super::models::{QuickAction, QuickActionOutputMode, QuickActionRun};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub fn create_quick_action(
+    workspace_id: String,
+    name: String,
+    prompt_template: String,
+    preferred_model: Option<String>,
+    output_mode: QuickActionOutputMode,
+    shortcut: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<QuickAction, AppError> {
+    state.quick_action_service.create(
+        workspace_id,
+        &name,
+        prompt_template,
+        preferred_model,
+        output_mode,
+        shortcut,
+    )
+}
+
+#[tauri::command]
+pub fn get_quick_actions(
+    workspace_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<QuickAction>, AppError> {
+    state
+        .quick_action_service
+        .get_by_workspace_id(&workspace_id)
+}
+
+/// Fields left out are kept; an empty `preferred_model` or `shortcut` clears it.
+#[tauri::command]
+pub fn update_quick_action(
+    id: String,
+    name: Option<String>,
+    prompt_template: Option<String>,
+    preferred_model: Option<String>,
+    output_mode: Option<QuickActionOutputMode>,
+    shortcut: Option<String>,
+    state: State<'_, AppState>,
+) -> Result<QuickAction, AppError> {
+    state.quick_action_service.update(
+        &id,
+        name,
+        prompt_template,
+        preferred_model,
+        output_mode,
+        shortcut,
+    )
+}
+
+#[tauri::command]
+pub fn delete_quick_action(id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.quick_action_service.delete(&id)
+}
+
+/// Run a quick action on the selected text. Progress is reported as
+/// `quick-action` events keyed by the returned `action_run_id`. `seed` asks
+/// providers that support one for a reproducible answer.
+#[tauri::command]
+pub async fn run_quick_action(
+    action_id: String,
+    selection_text: String,
+    chat_id: Option<String>,
+    seed: Option<i64>,
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<QuickActionRun, AppError> {
+    let action = state.quick_action_service.get_by_id(&action_id)?;
+    state
+        .chat_service
+        .run_quick_action(action, &selection_text, chat_id, seed, app)
+        .await
+}
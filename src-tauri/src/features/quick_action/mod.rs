@@ -0,0 +1,9 @@
+pub mod channel;
+pub mod commands;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
@@ -0,0 +1,60 @@
+use crate::models::llm_types::TokenUsage;
+use serde::{Deserialize, Serialize};
+
+/// Where the result of a quick action goes
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum QuickActionOutputMode {
+    /// Streamed to the quick action channel only, for a preview the user can
+    /// accept in place of the selection
+    ReplacePreview,
+    /// Added to a chat as a user message and an answer
+    AppendToChat,
+}
+
+impl QuickActionOutputMode {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::ReplacePreview => "replace_preview",
+            Self::AppendToChat => "append_to_chat",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "replace_preview" => Some(Self::ReplacePreview),
+            "append_to_chat" => Some(Self::AppendToChat),
+            _ => None,
+        }
+    }
+}
+
+/// A prompt run against selected text from a keyboard shortcut.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickAction {
+    pub id: String,
+    pub workspace_id: String,
+    pub name: String,
+    /// Must contain `{{selection}}`
+    pub prompt_template: String,
+    /// Model to run with; the workspace default model when unset
+    pub preferred_model: Option<String>,
+    pub output_mode: QuickActionOutputMode,
+    /// Key combination the frontend binds, e.g. "Mod+Shift+E"
+    pub shortcut: Option<String>,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+/// Result of `run_quick_action`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuickActionRun {
+    pub action_run_id: String,
+    pub output_mode: QuickActionOutputMode,
+    /// Chat the exchange was added to, for `append_to_chat`
+    pub chat_id: Option<String>,
+    /// Assistant message holding the answer, for `append_to_chat`
+    pub message_id: Option<String>,
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+}
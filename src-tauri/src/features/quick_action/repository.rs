@@ -0,0 +1,114 @@
+use super::models::{QuickAction, QuickActionOutputMode};
+use crate::error::AppError;
+use rusqlite::params;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait QuickActionRepository: Send + Sync {
+    fn create(&self, action: &QuickAction) -> Result<(), AppError>;
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<QuickAction>, AppError>;
+    fn get_by_id(&self, id: &str) -> Result<Option<QuickAction>, AppError>;
+    fn update(&self, action: &QuickAction) -> Result<(), AppError>;
+    fn delete(&self, id: &str) -> Result<(), AppError>;
+}
+
+pub struct SqliteQuickActionRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteQuickActionRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+const COLUMNS: &str = "id, workspace_id, name, prompt_template, preferred_model, output_mode, shortcut, created_at, updated_at";
+
+impl QuickActionRepository for SqliteQuickActionRepository {
+    fn create(&self, action: &QuickAction) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            &format!(
+                "INSERT INTO quick_actions ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+            ),
+            params![
+                action.id,
+                action.workspace_id,
+                action.name,
+                action.prompt_template,
+                action.preferred_model,
+                action.output_mode.as_str(),
+                action.shortcut,
+                action.created_at,
+                action.updated_at
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<QuickAction>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {COLUMNS} FROM quick_actions WHERE workspace_id = ?1 ORDER BY name COLLATE NOCASE"
+        ))?;
+        let actions = stmt
+            .query_map(params![workspace_id], row_to_quick_action)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(actions)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Option<QuickAction>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let result = conn.query_row(
+            &format!("SELECT {COLUMNS} FROM quick_actions WHERE id = ?1"),
+            params![id],
+            row_to_quick_action,
+        );
+
+        match result {
+            Ok(action) => Ok(Some(action)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn update(&self, action: &QuickAction) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "UPDATE quick_actions SET name = ?1, prompt_template = ?2, preferred_model = ?3, output_mode = ?4, shortcut = ?5, updated_at = ?6 WHERE id = ?7",
+            params![
+                action.name,
+                action.prompt_template,
+                action.preferred_model,
+                action.output_mode.as_str(),
+                action.shortcut,
+                action.updated_at,
+                action.id
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute("DELETE FROM quick_actions WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+fn row_to_quick_action(row: &rusqlite::Row) -> rusqlite::Result<QuickAction> {
+    let output_mode: String = row.get(5)?;
+    Ok(QuickAction {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        name: row.get(2)?,
+        prompt_template: row.get(3)?,
+        preferred_model: row.get(4)?,
+        // Modes from newer versions fall back to a preview
+        output_mode: QuickActionOutputMode::parse(&output_mode)
+            .unwrap_or(QuickActionOutputMode::ReplacePreview),
+        shortcut: row.get(6)?,
+        created_at: row.get(7)?,
+        updated_at: row.get(8)?,
+    })
+}
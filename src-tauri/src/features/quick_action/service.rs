@@ -0,0 +1,198 @@
+use super::models::{QuickAction, QuickActionOutputMode};
+use super::repository::QuickActionRepository;
+use crate::error::AppError;
+use crate::features::prompt::PromptTemplateService;
+use std::sync::Arc;
+
+/// Usage request type of quick action runs.
+pub const QUICK_ACTION_REQUEST_TYPE: &str = "quick_action";
+/// Message metadata key holding the quick action an exchange came from.
+pub const QUICK_ACTION_METADATA_KEY: &str = "quick_action_id";
+/// The one variable a quick action template may use.
+pub const SELECTION_VARIABLE: &str = "selection";
+/// Longest selection a quick action runs on, in characters.
+pub const MAX_SELECTION_CHARS: usize = 20_000;
+/// Longest prompt template accepted, in characters.
+const MAX_TEMPLATE_CHARS: usize = 8_000;
+const MAX_NAME_CHARS: usize = 100;
+
+pub struct QuickActionService {
+    repository: Arc<dyn QuickActionRepository>,
+}
+
+impl QuickActionService {
+    pub fn new(repository: Arc<dyn QuickActionRepository>) -> Self {
+        Self { repository }
+    }
+
+    pub fn create(
+        &self,
+        workspace_id: String,
+        name: &str,
+        prompt_template: String,
+        preferred_model: Option<String>,
+        output_mode: QuickActionOutputMode,
+        shortcut: Option<String>,
+    ) -> Result<QuickAction, AppError> {
+        let name = Self::validate_name(name)?;
+        Self::validate_template(&prompt_template)?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let action = QuickAction {
+            id: uuid::Uuid::new_v4().to_string(),
+            workspace_id,
+            name,
+            prompt_template,
+            preferred_model: non_empty(preferred_model),
+            output_mode,
+            shortcut: non_empty(shortcut),
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.repository.create(&action)?;
+        Ok(action)
+    }
+
+    pub fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<QuickAction>, AppError> {
+        self.repository.get_by_workspace_id(workspace_id)
+    }
+
+    pub fn get_by_id(&self, id: &str) -> Result<QuickAction, AppError> {
+        self.repository
+            .get_by_id(id)?
+            .ok_or_else(|| AppError::NotFound(format!("Quick action not found: {id}")))
+    }
+
+    /// Fields left `None` are kept; an empty model or shortcut clears it.
+    pub fn update(
+        &self,
+        id: &str,
+        name: Option<String>,
+        prompt_template: Option<String>,
+        preferred_model: Option<String>,
+        output_mode: Option<QuickActionOutputMode>,
+        shortcut: Option<String>,
+    ) -> Result<QuickAction, AppError> {
+        let mut action = self.get_by_id(id)?;
+        if let Some(name) = name {
+            action.name = Self::validate_name(&name)?;
+        }
+        if let Some(template) = prompt_template {
+            Self::validate_template(&template)?;
+            action.prompt_template = template;
+        }
+        if preferred_model.is_some() {
+            action.preferred_model = non_empty(preferred_model);
+        }
+        if let Some(mode) = output_mode {
+            action.output_mode = mode;
+        }
+        if shortcut.is_some() {
+            action.shortcut = non_empty(shortcut);
+        }
+        action.updated_at = chrono::Utc::now().timestamp_millis();
+
+        self.repository.update(&action)?;
+        Ok(action)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.repository.delete(id)
+    }
+
+    /// A template must use `{{selection}}` and no other variable, since the
+    /// selection is all a quick action is given.
+    pub fn validate_template(template: &str) -> Result<(), AppError> {
+        if template.trim().is_empty() {
+            return Err(AppError::Validation(
+                "Quick action prompt is empty".to_string(),
+            ));
+        }
+        if template.chars().count() > MAX_TEMPLATE_CHARS {
+            return Err(AppError::Validation(format!(
+                "Quick action prompt is longer than {MAX_TEMPLATE_CHARS} characters"
+            )));
+        }
+        let variables = PromptTemplateService::new().extract_variables(template);
+        if let Some(unknown) = variables.iter().find(|v| *v != SELECTION_VARIABLE) {
+            return Err(AppError::Validation(format!(
+                "Quick action prompt uses unknown variable {{{{{unknown}}}}}; only {{{{{SELECTION_VARIABLE}}}}} is available"
+            )));
+        }
+        if variables.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Quick action prompt must contain {{{{{SELECTION_VARIABLE}}}}}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// The prompt of `template` for `selection`, which must not be blank or
+    /// longer than `MAX_SELECTION_CHARS`.
+    pub fn render(template: &str, selection: &str) -> Result<String, AppError> {
+        Self::validate_template(template)?;
+        if selection.trim().is_empty() {
+            return Err(AppError::Validation("Selection is empty".to_string()));
+        }
+        let chars = selection.chars().count();
+        if chars > MAX_SELECTION_CHARS {
+            return Err(AppError::Validation(format!(
+                "Selection is {chars} characters long; quick actions take at most {MAX_SELECTION_CHARS}"
+            )));
+        }
+        Ok(template.replace(&format!("{{{{{SELECTION_VARIABLE}}}}}"), selection))
+    }
+
+    fn validate_name(name: &str) -> Result<String, AppError> {
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(AppError::Validation(
+                "Quick action name is empty".to_string(),
+            ));
+        }
+        if name.chars().count() > MAX_NAME_CHARS {
+            return Err(AppError::Validation(format!(
+                "Quick action name is longer than {MAX_NAME_CHARS} characters"
+            )));
+        }
+        Ok(name.to_string())
+    }
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn quick_action_templates_and_selections_are_checked() {
+        use crate::features::quick_action::{QuickActionService, MAX_SELECTION_CHARS};
+
+        assert_eq!(
+            QuickActionService::render("Explain:\n{{selection}}", "let x = 1;").unwrap(),
+            "Explain:\nlet x = 1;"
+        );
+        for template in [
+            "Explain this",
+            "Translate {{selection}} to {{language}}",
+            "  ",
+        ] {
+            assert!(
+                QuickActionService::validate_template(template).is_err(),
+                "{template:?} should be rejected"
+            );
+        }
+        assert!(QuickActionService::render("{{selection}}", " \n").is_err());
+        assert!(
+            QuickActionService::render("{{selection}}", &"é".repeat(MAX_SELECTION_CHARS)).is_ok()
+        );
+        assert!(
+            QuickActionService::render("{{selection}}", &"é".repeat(MAX_SELECTION_CHARS + 1))
+                .is_err()
+        );
+    }
+}
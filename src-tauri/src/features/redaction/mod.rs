@@ -0,0 +1,6 @@
+pub mod models;
+pub mod secrets;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
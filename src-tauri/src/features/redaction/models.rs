@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Per-workspace PII redaction settings, stored as JSON in
+/// `workspace_settings.redaction_config`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RedactionConfig {
+    pub enabled: bool,
+    /// Put the original values back into answers and tool calls before they
+    /// are stored, shown or executed
+    pub unredact_responses: bool,
+    /// Extra patterns, e.g. national ID formats
+    pub custom_patterns: Vec<CustomPattern>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct CustomPattern {
+    /// Placeholder prefix: "national id" masks matches as `[NATIONAL_ID_1]`
+    pub label: String,
+    pub pattern: String,
+}
+
+/// What was masked in one outgoing request, stored in the assistant message
+/// metadata under `redaction`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct RedactionSummary {
+    /// Masked occurrences by category, e.g. `{"EMAIL": 2, "PHONE": 1}`
+    pub counts: BTreeMap<String, u32>,
+    /// Whether placeholders in the answer were replaced with the original values
+    pub unredacted: bool,
+}
@@ -0,0 +1,583 @@
+use super::models::{RedactionConfig, RedactionSummary};
+use crate::error::AppError;
+use crate::features::llm_connection::models::LLMConnection;
+use crate::models::llm_types::{
+    AssistantContent, ChatMessage, ContentPart, LLMChatResponse, UserContent,
+};
+use regex::{Captures, Regex};
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+use std::sync::{LazyLock, Mutex, MutexGuard};
+
+static EMAIL: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(?:\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}\b").unwrap()
+});
+
+/// 13-19 digits, optionally grouped by spaces or dashes; checked with Luhn.
+static CARD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\b(?:\d[ -]?){12,18}\d\b").unwrap());
+
+static IP_ADDRESS: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"\b(?:(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\.){3}(?:25[0-5]|2[0-4]\d|1\d\d|[1-9]?\d)\b|\b(?:[0-9A-Fa-f]{1,4}:){7}[0-9A-Fa-f]{1,4}\b|\b(?:[0-9A-Fa-f]{1,4}:){1,6}:(?:[0-9A-Fa-f]{1,4}:){0,5}[0-9A-Fa-f]{1,4}\b",
+    )
+    .unwrap()
+});
+
+/// Grouped numbers ("555-123-4567", "(555) 123 4567") or `+` followed by
+/// digits. Bare digit runs are left alone so ids and timestamps in tool
+/// results survive.
+static PHONE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?:^|[^\w+])(\+?(?:\d{1,3}[ .-]?)?(?:\(\d{1,4}\)[ .-]?)?\d{2,4}(?:[ .-]\d{2,4}){1,4}|\+\d{9,15})\b",
+    )
+    .unwrap()
+});
+
+static DATE_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^\d{4}[.-]\d{1,2}[.-]\d{1,2}\b").unwrap());
+
+static PLACEHOLDER: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\[[A-Z][A-Z0-9_]*_\d+\]").unwrap());
+
+/// Providers that normally run on the user's own machine or network.
+const SELF_HOSTED_PROVIDERS: &[&str] = &["ollama", "lmstudio", "localai"];
+
+struct Detector {
+    category: String,
+    regex: Regex,
+    accept: fn(&str) -> bool,
+}
+
+/// Placeholder assignments for one chat. The same value always gets the same
+/// placeholder, so history and tool results stay consistent across requests.
+#[derive(Default)]
+struct PlaceholderMap {
+    by_value: HashMap<String, String>,
+    by_placeholder: HashMap<String, String>,
+    next_index: HashMap<String, u32>,
+}
+
+impl PlaceholderMap {
+    fn placeholder_for(&mut self, category: &str, value: &str) -> String {
+        if let Some(placeholder) = self.by_value.get(value) {
+            return placeholder.clone();
+        }
+        let index = self.next_index.entry(category.to_string()).or_insert(0);
+        *index += 1;
+        let placeholder = format!("[{category}_{index}]");
+        self.by_value.insert(value.to_string(), placeholder.clone());
+        self.by_placeholder
+            .insert(placeholder.clone(), value.to_string());
+        placeholder
+    }
+
+    /// Put original values back. `json_string` escapes them for use inside a
+    /// JSON string literal (tool call arguments).
+    fn restore(&self, text: &str, json_string: bool) -> String {
+        PLACEHOLDER
+            .replace_all(text, |caps: &Captures| {
+                let placeholder = &caps[0];
+                match self.by_placeholder.get(placeholder) {
+                    Some(value) if json_string => {
+                        let quoted = serde_json::Value::from(value.as_str()).to_string();
+                        quoted[1..quoted.len() - 1].to_string()
+                    }
+                    Some(value) => value.clone(),
+                    None => placeholder.to_string(),
+                }
+            })
+            .into_owned()
+    }
+}
+
+/// Masks PII in requests sent to cloud providers. Stored messages are never
+/// changed; originals are only kept in memory, per chat, so answers that echo
+/// a placeholder can be turned back into the real value.
+pub struct RedactionService {
+    maps: Mutex<HashMap<String, PlaceholderMap>>,
+}
+
+impl Default for RedactionService {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RedactionService {
+    pub fn new() -> Self {
+        Self {
+            maps: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Parse a stored config, rejecting custom patterns that do not compile.
+    pub fn parse_config(raw: &str) -> Result<RedactionConfig, AppError> {
+        let config: RedactionConfig = serde_json::from_str(raw)
+            .map_err(|e| AppError::Validation(format!("Invalid redaction config: {e}")))?;
+        Self::detectors(&config)?;
+        Ok(config)
+    }
+
+    /// The config to apply to a request sent through `connection`, or `None`
+    /// when nothing is masked: redaction is off, the connection is marked as
+    /// trusted, or it points at a local or self-hosted server.
+    pub fn config_for(
+        raw: Option<&str>,
+        connection: &LLMConnection,
+    ) -> Result<Option<RedactionConfig>, AppError> {
+        let Some(raw) = raw.filter(|r| !r.trim().is_empty()) else {
+            return Ok(None);
+        };
+        let config = Self::parse_config(raw)?;
+        if !config.enabled || connection.skip_redaction || Self::is_self_hosted(connection) {
+            return Ok(None);
+        }
+        Ok(Some(config))
+    }
+
+    /// Runs on the user's machine or network, by provider or host.
+    pub fn is_self_hosted(connection: &LLMConnection) -> bool {
+        if SELF_HOSTED_PROVIDERS
+            .iter()
+            .any(|p| connection.provider.eq_ignore_ascii_case(p))
+        {
+            return true;
+        }
+
+        let Some(host) = reqwest::Url::parse(&connection.base_url)
+            .ok()
+            .and_then(|url| url.host_str().map(str::to_ascii_lowercase))
+        else {
+            return false;
+        };
+        if matches!(host.rsplit('.').next(), Some("localhost" | "local")) {
+            return true;
+        }
+        match host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+        {
+            Ok(IpAddr::V4(ip)) => ip.is_loopback() || ip.is_private() || ip.is_link_local(),
+            Ok(IpAddr::V6(ip)) => {
+                // Loopback or unique local (fc00::/7)
+                ip.is_loopback() || (ip.segments()[0] & 0xfe00) == 0xfc00
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Replace PII in every text part of `messages`, including tool call
+    /// arguments and tool results. Returns the counts per category.
+    pub fn redact_messages(
+        &self,
+        chat_id: &str,
+        config: &RedactionConfig,
+        messages: &mut [ChatMessage],
+    ) -> Result<RedactionSummary, AppError> {
+        let detectors = Self::detectors(config)?;
+        let mut maps = self.lock()?;
+        let map = maps.entry(chat_id.to_string()).or_default();
+        let mut counts = BTreeMap::new();
+
+        for message in messages.iter_mut() {
+            match message {
+                ChatMessage::System { content } | ChatMessage::Tool { content, .. } => {
+                    Self::redact_in_place(content, &detectors, map, &mut counts);
+                }
+                ChatMessage::User { content } => match content {
+                    UserContent::Text(text) => {
+                        Self::redact_in_place(text, &detectors, map, &mut counts);
+                    }
+                    UserContent::Parts(parts) => {
+                        Self::redact_parts(parts, &detectors, map, &mut counts);
+                    }
+                },
+                ChatMessage::Assistant {
+                    content,
+                    tool_calls,
+                } => {
+                    match content {
+                        AssistantContent::Text(text) => {
+                            Self::redact_in_place(text, &detectors, map, &mut counts);
+                        }
+                        AssistantContent::Parts(parts) => {
+                            Self::redact_parts(parts, &detectors, map, &mut counts);
+                        }
+                    }
+                    for call in tool_calls.iter_mut().flatten() {
+                        Self::redact_in_place(
+                            &mut call.function.arguments,
+                            &detectors,
+                            map,
+                            &mut counts,
+                        );
+                    }
+                }
+            }
+        }
+        drop(maps);
+
+        Ok(RedactionSummary {
+            counts,
+            unredacted: config.unredact_responses,
+        })
+    }
+
+    /// Replace placeholders in a model answer (content, reasoning and tool call
+    /// arguments) with the values they stand for in this chat.
+    pub fn unredact_response(
+        &self,
+        chat_id: &str,
+        response: &mut LLMChatResponse,
+    ) -> Result<(), AppError> {
+        let maps = self.lock()?;
+        let Some(map) = maps.get(chat_id) else {
+            return Ok(());
+        };
+
+        response.content = map.restore(&response.content, false);
+        if let Some(reasoning) = response.reasoning.as_mut() {
+            *reasoning = map.restore(reasoning, false);
+        }
+        for call in response.tool_calls.iter_mut().flatten() {
+            call.function.arguments = map.restore(&call.function.arguments, true);
+        }
+        drop(maps);
+        Ok(())
+    }
+
+    /// Drop the placeholder map of a deleted chat.
+    pub fn forget(&self, chat_id: &str) -> Result<(), AppError> {
+        self.lock()?.remove(chat_id);
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<MutexGuard<'_, HashMap<String, PlaceholderMap>>, AppError> {
+        self.maps
+            .lock()
+            .map_err(|e| AppError::Generic(format!("Failed to lock redaction maps: {e}")))
+    }
+
+    /// Custom patterns first, so a user-defined ID format wins over a generic
+    /// phone match of the same span.
+    fn detectors(config: &RedactionConfig) -> Result<Vec<Detector>, AppError> {
+        let mut detectors = Vec::new();
+        for custom in &config.custom_patterns {
+            let regex = Regex::new(&custom.pattern).map_err(|e| {
+                AppError::Validation(format!(
+                    "Invalid redaction pattern for {}: {e}",
+                    custom.label
+                ))
+            })?;
+            detectors.push(Detector {
+                category: Self::category_label(&custom.label),
+                regex,
+                accept: |_| true,
+            });
+        }
+
+        let builtin: [(&str, &Regex, fn(&str) -> bool); 4] = [
+            ("EMAIL", &EMAIL, |_| true),
+            ("CARD", &CARD, Self::is_card_number),
+            ("IP", &IP_ADDRESS, |_| true),
+            ("PHONE", &PHONE, Self::is_phone_number),
+        ];
+        for (category, regex, accept) in builtin {
+            detectors.push(Detector {
+                category: category.to_string(),
+                regex: regex.clone(),
+                accept,
+            });
+        }
+        Ok(detectors)
+    }
+
+    /// `"national id"` -> `NATIONAL_ID`
+    fn category_label(label: &str) -> String {
+        let label: String = label
+            .trim()
+            .chars()
+            .map(|c| {
+                if c.is_ascii_alphanumeric() {
+                    c.to_ascii_uppercase()
+                } else {
+                    '_'
+                }
+            })
+            .collect();
+        let label = label.trim_matches('_');
+        if label.is_empty() {
+            "PII".to_string()
+        } else if label.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("PII_{label}")
+        } else {
+            label.to_string()
+        }
+    }
+
+    fn redact_parts(
+        parts: &mut [ContentPart],
+        detectors: &[Detector],
+        map: &mut PlaceholderMap,
+        counts: &mut BTreeMap<String, u32>,
+    ) {
+        for part in parts {
+            if let ContentPart::Text { text } = part {
+                Self::redact_in_place(text, detectors, map, counts);
+            }
+        }
+    }
+
+    fn redact_in_place(
+        text: &mut String,
+        detectors: &[Detector],
+        map: &mut PlaceholderMap,
+        counts: &mut BTreeMap<String, u32>,
+    ) {
+        let redacted = Self::redact_text(text, detectors, map, counts);
+        *text = redacted;
+    }
+
+    /// Longer matches win when candidates overlap; on equal length the earlier
+    /// detector wins. Text inside an existing placeholder is never matched.
+    fn redact_text(
+        text: &str,
+        detectors: &[Detector],
+        map: &mut PlaceholderMap,
+        counts: &mut BTreeMap<String, u32>,
+    ) -> String {
+        let placeholders: Vec<(usize, usize)> = PLACEHOLDER
+            .find_iter(text)
+            .map(|m| (m.start(), m.end()))
+            .collect();
+
+        // (start, end, detector index)
+        let mut candidates: Vec<(usize, usize, usize)> = Vec::new();
+        for (index, detector) in detectors.iter().enumerate() {
+            for caps in detector.regex.captures_iter(text) {
+                let Some(m) = caps.get(1).or_else(|| caps.get(0)) else {
+                    continue;
+                };
+                if !m.is_empty() && (detector.accept)(m.as_str()) {
+                    candidates.push((m.start(), m.end(), index));
+                }
+            }
+        }
+        candidates
+            .sort_by_key(|&(start, end, index)| (std::cmp::Reverse(end - start), index, start));
+
+        let mut spans: Vec<(usize, usize, usize)> = Vec::new();
+        for (start, end, index) in candidates {
+            let overlaps = |&(s, e): &(usize, usize)| start < e && s < end;
+            if placeholders.iter().any(overlaps) || spans.iter().any(|&(s, e, _)| overlaps(&(s, e)))
+            {
+                continue;
+            }
+            spans.push((start, end, index));
+        }
+        if spans.is_empty() {
+            return text.to_string();
+        }
+
+        spans.sort_unstable();
+        let mut output = String::with_capacity(text.len());
+        let mut cursor = 0;
+        for (start, end, index) in spans {
+            let category = &detectors[index].category;
+            output.push_str(&text[cursor..start]);
+            output.push_str(&map.placeholder_for(category, &text[start..end]));
+            *counts.entry(category.clone()).or_insert(0) += 1;
+            cursor = end;
+        }
+        output.push_str(&text[cursor..]);
+        output
+    }
+
+    /// Luhn check, so order numbers and other long ids are not masked as cards.
+    fn is_card_number(candidate: &str) -> bool {
+        let digits: Vec<u32> = candidate.chars().filter_map(|c| c.to_digit(10)).collect();
+        if !(13..=19).contains(&digits.len()) {
+            return false;
+        }
+        let sum: u32 = digits
+            .iter()
+            .rev()
+            .enumerate()
+            .map(|(i, &d)| match (i % 2, d * 2) {
+                (0, _) => d,
+                (_, doubled) if doubled > 9 => doubled - 9,
+                (_, doubled) => doubled,
+            })
+            .sum();
+        sum.is_multiple_of(10)
+    }
+
+    fn is_phone_number(candidate: &str) -> bool {
+        let digits = candidate.chars().filter(char::is_ascii_digit).count();
+        (9..=15).contains(&digits) && !DATE_PREFIX.is_match(candidate)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn redaction_config() -> crate::features::redaction::RedactionConfig {
+        crate::features::redaction::RedactionConfig {
+            enabled: true,
+            unredact_responses: true,
+            custom_patterns: vec![crate::features::redaction::CustomPattern {
+                label: "national id".to_string(),
+                pattern: r"\b\d{3}-\d{2}-\d{4}\b".to_string(),
+            }],
+        }
+    }
+
+    fn user_text(message: &crate::models::llm_types::ChatMessage) -> &str {
+        use crate::models::llm_types::{ChatMessage, UserContent};
+        match message {
+            ChatMessage::User {
+                content: UserContent::Text(text),
+            } => text,
+            other => panic!("expected a user text message, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn redaction_resolves_overlapping_matches() {
+        use crate::features::redaction::RedactionService;
+        use crate::models::llm_types::{ChatMessage, UserContent};
+
+        let service = RedactionService::new();
+        let mut messages = vec![ChatMessage::User {
+            content: UserContent::Text(
+                "Mail jane.doe+5551234567@example.com or call +1 555-123-4567 from 192.168.10.200, ID 123-45-6789.".to_string(),
+            ),
+        }];
+        let summary = service
+            .redact_messages("chat-1", &redaction_config(), &mut messages)
+            .unwrap();
+
+        assert_eq!(
+            user_text(&messages[0]),
+            "Mail [EMAIL_1] or call [PHONE_1] from [IP_1], ID [NATIONAL_ID_1]."
+        );
+        for category in ["EMAIL", "PHONE", "IP", "NATIONAL_ID"] {
+            assert_eq!(summary.counts.get(category), Some(&1), "{category}");
+        }
+    }
+
+    #[test]
+    fn redaction_placeholders_survive_the_agent_loop() {
+        use crate::features::redaction::RedactionService;
+        use crate::models::llm_types::{
+            AssistantContent, ChatMessage, ToolCall, ToolCallFunction, UserContent,
+        };
+
+        let service = RedactionService::new();
+        let config = redaction_config();
+        let user = ChatMessage::User {
+            content: UserContent::Text("Forward this to bob@corp.com".to_string()),
+        };
+
+        let mut first = vec![user.clone()];
+        service
+            .redact_messages("chat-1", &config, &mut first)
+            .unwrap();
+        assert_eq!(user_text(&first[0]), "Forward this to [EMAIL_1]");
+
+        // Next iteration: history is rebuilt from stored (unredacted) messages
+        let mut second = vec![
+            user,
+            ChatMessage::Assistant {
+                content: AssistantContent::Text(String::new()),
+                tool_calls: Some(vec![ToolCall {
+                    id: "call_1".to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: "send_mail".to_string(),
+                        arguments: r#"{"to":"bob@corp.com"}"#.to_string(),
+                    },
+                }]),
+            },
+            ChatMessage::Tool {
+                content: "Sent to bob@corp.com, cc alice@corp.com".to_string(),
+                tool_call_id: "call_1".to_string(),
+            },
+        ];
+        let summary = service
+            .redact_messages("chat-1", &config, &mut second)
+            .unwrap();
+
+        assert_eq!(user_text(&second[0]), "Forward this to [EMAIL_1]");
+        let ChatMessage::Assistant {
+            tool_calls: Some(calls),
+            ..
+        } = &second[1]
+        else {
+            panic!("expected an assistant message with tool calls");
+        };
+        assert_eq!(calls[0].function.arguments, r#"{"to":"[EMAIL_1]"}"#);
+        let ChatMessage::Tool { content, .. } = &second[2] else {
+            panic!("expected a tool message");
+        };
+        assert_eq!(content, "Sent to [EMAIL_1], cc [EMAIL_2]");
+        assert_eq!(summary.counts.get("EMAIL"), Some(&4));
+
+        // Placeholders are per chat
+        let mut other = vec![ChatMessage::User {
+            content: UserContent::Text("alice@corp.com".to_string()),
+        }];
+        service
+            .redact_messages("chat-2", &config, &mut other)
+            .unwrap();
+        assert_eq!(user_text(&other[0]), "[EMAIL_1]");
+    }
+
+    #[test]
+    fn redaction_unredacts_echoed_placeholders() {
+        use crate::features::redaction::RedactionService;
+        use crate::models::llm_types::{
+            ChatMessage, LLMChatResponse, ToolCall, ToolCallFunction, UserContent,
+        };
+
+        let service = RedactionService::new();
+        let mut messages = vec![ChatMessage::User {
+            content: UserContent::Text("My email is bob@corp.com".to_string()),
+        }];
+        service
+            .redact_messages("chat-1", &redaction_config(), &mut messages)
+            .unwrap();
+
+        let mut response = LLMChatResponse {
+            content: "I will write to [EMAIL_1], not [EMAIL_9].".to_string(),
+            finish_reason: Some("tool_calls".to_string()),
+            tool_calls: Some(vec![ToolCall {
+                id: "call_1".to_string(),
+                r#type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: "send_mail".to_string(),
+                    arguments: r#"{"to":"[EMAIL_1]"}"#.to_string(),
+                },
+            }]),
+            usage: None,
+            reasoning: Some("The user is [EMAIL_1]".to_string()),
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        };
+        service.unredact_response("chat-1", &mut response).unwrap();
+
+        assert_eq!(
+            response.content,
+            "I will write to bob@corp.com, not [EMAIL_9]."
+        );
+        assert_eq!(
+            response.reasoning.as_deref(),
+            Some("The user is bob@corp.com")
+        );
+        assert_eq!(
+            response.tool_calls.unwrap()[0].function.arguments,
+            r#"{"to":"bob@corp.com"}"#
+        );
+    }
+}
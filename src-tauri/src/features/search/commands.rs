@@ -0,0 +1,33 @@
+use super::models::{ChatSearchHit, MessageSearchHit};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Messages containing every word of `query`, ranked, with a snippet of
+/// each match and the chat it is in. Narrowed to a workspace or a chat when
+/// given.
+#[tauri::command]
+pub fn search_messages(
+    state: State<'_, AppState>,
+    query: String,
+    workspace_id: Option<String>,
+    chat_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<MessageSearchHit>, AppError> {
+    state
+        .search_service
+        .search_messages(&query, workspace_id.as_deref(), chat_id.as_deref(), limit)
+}
+
+/// Chats whose title or messages contain every word of `query`, ranked.
+#[tauri::command]
+pub fn search_chats(
+    state: State<'_, AppState>,
+    query: String,
+    workspace_id: Option<String>,
+    limit: Option<u32>,
+) -> Result<Vec<ChatSearchHit>, AppError> {
+    state
+        .search_service
+        .search_chats(&query, workspace_id.as_deref(), limit)
+}
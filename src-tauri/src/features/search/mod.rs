@@ -0,0 +1,17 @@
+//! Full-text search over chats and messages.
+//!
+//! `message_search` holds the plain text of user and assistant messages and
+//! `message_search_fts`, an FTS5 table over it, the index; triggers keep
+//! the two in step. The message repository syncs a message into it on every
+//! write, and deleting a message, or its chat, cascades to its row. Content
+//! stored encrypted is never indexed, so while encryption is on only chat
+//! titles can be searched.
+
+pub mod commands;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+/// Marks the start of a matched term in a snippet.
+pub const HIGHLIGHT_START: &str = "\u{2}";
+/// Marks the end of a matched term in a snippet.
+pub const HIGHLIGHT_END: &str = "\u{3}";
+
+/// A message found by `search_messages`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MessageSearchHit {
+    pub message_id: String,
+    pub chat_id: String,
+    pub chat_title: String,
+    pub workspace_id: String,
+    pub role: String,
+    /// The part of the content around the matches, which are wrapped in
+    /// `HIGHLIGHT_START` and `HIGHLIGHT_END`
+    pub snippet: String,
+    pub timestamp: i64,
+    /// Higher is better, comparable within one search only
+    pub score: f64,
+}
+
+/// A chat found by `search_chats`, by its title or its messages.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ChatSearchHit {
+    pub chat_id: String,
+    pub workspace_id: String,
+    pub title: String,
+    pub updated_at: i64,
+    /// Whether the title contains every word of the query
+    pub title_match: bool,
+    /// Messages of the chat that match
+    pub message_matches: u32,
+    /// The best matching message and its snippet, when any matches
+    pub best_message_id: Option<String>,
+    pub snippet: Option<String>,
+    /// Higher is better, comparable within one search only
+    pub score: f64,
+}
@@ -0,0 +1,368 @@
+use super::models::{ChatSearchHit, MessageSearchHit, HIGHLIGHT_END, HIGHLIGHT_START};
+use crate::error::AppError;
+use crate::features::encryption::ENCRYPTED_PREFIX;
+use rusqlite::{params, params_from_iter, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// Words of message content a snippet spans.
+const SNIPPET_WORDS: u32 = 24;
+
+pub trait SearchRepository: Send + Sync {
+    /// Messages matching an FTS5 query, best first.
+    fn messages(
+        &self,
+        fts_query: &str,
+        workspace_id: Option<&str>,
+        chat_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<MessageSearchHit>, AppError>;
+    /// Chats with messages matching an FTS5 query, by their best match.
+    fn chats_by_messages(
+        &self,
+        fts_query: &str,
+        workspace_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ChatSearchHit>, AppError>;
+    /// Chats whose title contains every term, most recently updated first.
+    fn chats_by_title(
+        &self,
+        terms: &[String],
+        workspace_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ChatSearchHit>, AppError>;
+}
+
+pub struct SqliteSearchRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteSearchRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl SearchRepository for SqliteSearchRepository {
+    fn messages(
+        &self,
+        fts_query: &str,
+        workspace_id: Option<&str>,
+        chat_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<MessageSearchHit>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        search_messages(&conn, fts_query, workspace_id, chat_id, limit)
+    }
+
+    fn chats_by_messages(
+        &self,
+        fts_query: &str,
+        workspace_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ChatSearchHit>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        search_chats_by_messages(&conn, fts_query, workspace_id, limit)
+    }
+
+    fn chats_by_title(
+        &self,
+        terms: &[String],
+        workspace_id: Option<&str>,
+        limit: u32,
+    ) -> Result<Vec<ChatSearchHit>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        search_chats_by_title(&conn, terms, workspace_id, limit)
+    }
+}
+
+/// The words of a search, without the quotes FTS5 would read as syntax.
+pub fn query_terms(query: &str) -> Vec<String> {
+    query
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .collect()
+}
+
+/// An FTS5 query matching messages that contain every word of `query`,
+/// the last one also as the start of a word while it is being typed. None
+/// when there is nothing to search for.
+pub fn fts_query(query: &str) -> Option<String> {
+    let terms = query_terms(query);
+    let typing = !query.ends_with(char::is_whitespace);
+    let last = terms.len().checked_sub(1)?;
+    Some(
+        terms
+            .iter()
+            .enumerate()
+            .map(|(i, term)| {
+                if i == last && typing {
+                    format!("\"{term}\"*")
+                } else {
+                    format!("\"{term}\"")
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+/// Bring the index entry of a message in line with the stored row: user and
+/// assistant messages stored as plain text are indexed, others dropped.
+pub fn sync_message(conn: &Connection, message_id: &str) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM message_search WHERE message_id = ?1",
+        params![message_id],
+    )?;
+    conn.execute(
+        "INSERT INTO message_search (message_id, chat_id, content)
+         SELECT id, chat_id, content FROM messages
+         WHERE id = ?1 AND role IN ('user', 'assistant') AND content != ''
+           AND content NOT LIKE ?2",
+        params![message_id, format!("{ENCRYPTED_PREFIX}%")],
+    )?;
+    Ok(())
+}
+
+pub fn search_messages(
+    conn: &Connection,
+    fts_query: &str,
+    workspace_id: Option<&str>,
+    chat_id: Option<&str>,
+    limit: u32,
+) -> Result<Vec<MessageSearchHit>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT s.message_id, s.chat_id, c.title, c.workspace_id, m.role,
+                snippet(message_search_fts, 0, ?2, ?3, '…', {SNIPPET_WORDS}),
+                m.timestamp, bm25(message_search_fts)
+         FROM message_search_fts
+         JOIN message_search s ON s.id = message_search_fts.rowid
+         JOIN messages m ON m.id = s.message_id
+         JOIN chats c ON c.id = s.chat_id
+         WHERE message_search_fts MATCH ?1
+           AND (?4 IS NULL OR c.workspace_id = ?4)
+           AND (?5 IS NULL OR s.chat_id = ?5)
+         ORDER BY bm25(message_search_fts) LIMIT ?6"
+    ))?;
+    let hits = stmt
+        .query_map(
+            params![
+                fts_query,
+                HIGHLIGHT_START,
+                HIGHLIGHT_END,
+                workspace_id,
+                chat_id,
+                limit
+            ],
+            |row| {
+                Ok(MessageSearchHit {
+                    message_id: row.get(0)?,
+                    chat_id: row.get(1)?,
+                    chat_title: row.get(2)?,
+                    workspace_id: row.get(3)?,
+                    role: row.get(4)?,
+                    snippet: row.get(5)?,
+                    timestamp: row.get(6)?,
+                    score: -row.get::<_, f64>(7)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(hits)
+}
+
+pub fn search_chats_by_messages(
+    conn: &Connection,
+    fts_query: &str,
+    workspace_id: Option<&str>,
+    limit: u32,
+) -> Result<Vec<ChatSearchHit>, AppError> {
+    // Materialized, so the ranking functions run in the full-text query
+    // rather than in the grouping; the bare columns come from the best hit
+    let mut stmt = conn.prepare(&format!(
+        "WITH hits AS MATERIALIZED (
+             SELECT s.chat_id, s.message_id, bm25(message_search_fts) AS rank,
+                    snippet(message_search_fts, 0, ?2, ?3, '…', {SNIPPET_WORDS}) AS snippet
+             FROM message_search_fts
+             JOIN message_search s ON s.id = message_search_fts.rowid
+             WHERE message_search_fts MATCH ?1
+         )
+         SELECT c.id, c.workspace_id, c.title, c.updated_at, COUNT(*), MIN(h.rank),
+                h.message_id, h.snippet
+         FROM hits h JOIN chats c ON c.id = h.chat_id
+         WHERE ?4 IS NULL OR c.workspace_id = ?4
+         GROUP BY c.id ORDER BY MIN(h.rank) LIMIT ?5"
+    ))?;
+    let hits = stmt
+        .query_map(
+            params![
+                fts_query,
+                HIGHLIGHT_START,
+                HIGHLIGHT_END,
+                workspace_id,
+                limit
+            ],
+            |row| {
+                Ok(ChatSearchHit {
+                    chat_id: row.get(0)?,
+                    workspace_id: row.get(1)?,
+                    title: row.get(2)?,
+                    updated_at: row.get(3)?,
+                    title_match: false,
+                    message_matches: row.get(4)?,
+                    score: -row.get::<_, f64>(5)?,
+                    best_message_id: row.get(6)?,
+                    snippet: row.get(7)?,
+                })
+            },
+        )?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(hits)
+}
+
+/// `LIKE` ignores case for ASCII letters only.
+pub fn search_chats_by_title(
+    conn: &Connection,
+    terms: &[String],
+    workspace_id: Option<&str>,
+    limit: u32,
+) -> Result<Vec<ChatSearchHit>, AppError> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conditions: String = (0..terms.len())
+        .map(|i| format!(" AND title LIKE ?{} ESCAPE '\\'", i + 2))
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, workspace_id, title, updated_at FROM chats
+         WHERE (?1 IS NULL OR workspace_id = ?1){conditions}
+         ORDER BY updated_at DESC LIMIT {limit}"
+    ))?;
+    let patterns = terms.iter().map(|term| {
+        let escaped = term
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        Some(format!("%{escaped}%"))
+    });
+    let values: Vec<Option<String>> = std::iter::once(workspace_id.map(str::to_string))
+        .chain(patterns)
+        .collect();
+    let hits = stmt
+        .query_map(params_from_iter(values), |row| {
+            Ok(ChatSearchHit {
+                chat_id: row.get(0)?,
+                workspace_id: row.get(1)?,
+                title: row.get(2)?,
+                updated_at: row.get(3)?,
+                title_match: true,
+                message_matches: 0,
+                best_message_id: None,
+                snippet: None,
+                score: 0.0,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(hits)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn full_text_search_ranks_highlights_and_follows_message_changes() {
+        use crate::features::search::{
+            fts_query, merge_chat_hits, search_chats_by_messages, search_chats_by_title,
+            search_messages, sync_message, HIGHLIGHT_END, HIGHLIGHT_START,
+        };
+
+        assert_eq!(fts_query("  "), None);
+        assert_eq!(fts_query("rust bor").as_deref(), Some("\"rust\" \"bor\"*"));
+        assert_eq!(fts_query("say \"hi\" ").as_deref(), Some("\"say\" \"hi\""));
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "PRAGMA foreign_keys = ON;
+             INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('c1', 'w1', 'Borrow checker', 0, 10),
+                    ('c2', 'w1', 'Groceries', 0, 20);",
+        )
+        .unwrap();
+        let messages = [
+            ("m1", "c1", "user", "How does the Rust borrow checker work?"),
+            (
+                "m2",
+                "c1",
+                "assistant",
+                "Rust tracks borrows at compile time.",
+            ),
+            ("m3", "c2", "user", "Buy rust remover"),
+            ("m4", "c2", "system", "Rust is a language"),
+            ("m5", "c2", "user", "enc:v1:rust-ciphertext"),
+        ];
+        for (i, (id, chat_id, role, content)) in messages.into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO messages (id, chat_id, role, content, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                rusqlite::params![id, chat_id, role, content, i],
+            )
+            .unwrap();
+            sync_message(&conn, id).unwrap();
+        }
+
+        // System and encrypted messages stay out of the index
+        let hits = search_messages(&conn, "\"rust\"", None, None, 10).unwrap();
+        let mut ids: Vec<&str> = hits.iter().map(|h| h.message_id.as_str()).collect();
+        ids.sort_unstable();
+        assert_eq!(ids, ["m1", "m2", "m3"]);
+        assert!(hits.windows(2).all(|w| w[0].score >= w[1].score));
+        let hit = hits.iter().find(|h| h.message_id == "m1").unwrap();
+        assert_eq!(hit.chat_title, "Borrow checker");
+        assert!(hit
+            .snippet
+            .contains(&format!("{HIGHLIGHT_START}Rust{HIGHLIGHT_END}")));
+
+        // Prefix match on the last term, narrowed to a chat
+        let hits = search_messages(&conn, &fts_query("borr").unwrap(), None, Some("c1"), 10);
+        assert_eq!(hits.unwrap().len(), 2);
+
+        // Edits and deletes reach the index
+        conn.execute(
+            "UPDATE messages SET content = 'Buy apples' WHERE id = 'm3'",
+            [],
+        )
+        .unwrap();
+        sync_message(&conn, "m3").unwrap();
+        conn.execute("DELETE FROM messages WHERE id = 'm2'", [])
+            .unwrap();
+        let hits = search_messages(&conn, "\"rust\"", None, None, 10).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_id, "m1");
+        assert_eq!(
+            search_messages(&conn, "\"apples\"", Some("w1"), None, 10)
+                .unwrap()
+                .len(),
+            1
+        );
+
+        let by_messages = search_chats_by_messages(&conn, "\"buy\"", None, 10).unwrap();
+        assert_eq!(by_messages.len(), 1);
+        assert_eq!(by_messages[0].best_message_id.as_deref(), Some("m3"));
+        let by_title = search_chats_by_title(&conn, &["groc".to_string()], Some("w1"), 10).unwrap();
+        assert_eq!(by_title.len(), 1);
+        let merged = merge_chat_hits(
+            search_chats_by_messages(&conn, "\"rust\"", None, 10).unwrap(),
+            search_chats_by_title(&conn, &["buy".to_string()], None, 10)
+                .unwrap()
+                .into_iter()
+                .chain(by_title)
+                .collect(),
+            10,
+        );
+        let order: Vec<&str> = merged.iter().map(|h| h.chat_id.as_str()).collect();
+        assert_eq!(order, ["c2", "c1"]);
+        assert!(merged[0].title_match && !merged[1].title_match);
+    }
+}
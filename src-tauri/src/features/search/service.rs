@@ -0,0 +1,116 @@
+use super::models::{ChatSearchHit, MessageSearchHit};
+use super::repository::{self, SearchRepository};
+use crate::error::AppError;
+use crate::features::encryption::EncryptionService;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+const MAX_SEARCH_LIMIT: u32 = 100;
+/// Added to the score of chats whose title matches, above any message match
+/// of a typical search.
+const TITLE_MATCH_BOOST: f64 = 10.0;
+
+pub struct SearchService {
+    repository: Arc<dyn SearchRepository>,
+    encryption_service: Arc<EncryptionService>,
+}
+
+impl SearchService {
+    pub fn new(
+        repository: Arc<dyn SearchRepository>,
+        encryption_service: Arc<EncryptionService>,
+    ) -> Self {
+        Self {
+            repository,
+            encryption_service,
+        }
+    }
+
+    fn message_search_available(&self) -> Result<bool, AppError> {
+        Ok(self.encryption_service.status()?.message_search_available)
+    }
+
+    /// Messages containing every word of `query`, best first, within a
+    /// workspace or a chat when given.
+    pub fn search_messages(
+        &self,
+        query: &str,
+        workspace_id: Option<&str>,
+        chat_id: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<Vec<MessageSearchHit>, AppError> {
+        if !self.message_search_available()? {
+            return Err(AppError::Validation(
+                "Message search is not available while encryption is on".to_string(),
+            ));
+        }
+        let Some(fts_query) = repository::fts_query(query) else {
+            return Ok(Vec::new());
+        };
+        self.repository
+            .messages(&fts_query, workspace_id, chat_id, clamp_limit(limit))
+    }
+
+    /// Chats whose title or messages contain every word of `query`, best
+    /// first. While encryption is on, only titles are searched.
+    pub fn search_chats(
+        &self,
+        query: &str,
+        workspace_id: Option<&str>,
+        limit: Option<u32>,
+    ) -> Result<Vec<ChatSearchHit>, AppError> {
+        let limit = clamp_limit(limit);
+        let terms = repository::query_terms(query);
+        let by_messages = match repository::fts_query(query) {
+            Some(fts_query) if self.message_search_available()? => self
+                .repository
+                .chats_by_messages(&fts_query, workspace_id, limit)?,
+            _ => Vec::new(),
+        };
+        let by_title = self
+            .repository
+            .chats_by_title(&terms, workspace_id, limit)?;
+        Ok(merge_chat_hits(by_messages, by_title, limit as usize))
+    }
+}
+
+fn clamp_limit(limit: Option<u32>) -> u32 {
+    limit
+        .unwrap_or(DEFAULT_SEARCH_LIMIT)
+        .clamp(1, MAX_SEARCH_LIMIT)
+}
+
+/// One hit per chat, title matches ranked above the chats found by their
+/// messages alone.
+pub fn merge_chat_hits(
+    by_messages: Vec<ChatSearchHit>,
+    by_title: Vec<ChatSearchHit>,
+    limit: usize,
+) -> Vec<ChatSearchHit> {
+    let mut hits: HashMap<String, ChatSearchHit> = by_messages
+        .into_iter()
+        .map(|hit| (hit.chat_id.clone(), hit))
+        .collect();
+    for title_hit in by_title {
+        hits.entry(title_hit.chat_id.clone())
+            .and_modify(|hit| hit.title_match = true)
+            .or_insert(title_hit);
+    }
+    let mut hits: Vec<ChatSearchHit> = hits
+        .into_values()
+        .map(|mut hit| {
+            if hit.title_match {
+                hit.score += TITLE_MATCH_BOOST;
+            }
+            hit
+        })
+        .collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then(b.updated_at.cmp(&a.updated_at))
+    });
+    hits.truncate(limit);
+    hits
+}
@@ -0,0 +1,37 @@
+use super::models::SemanticHit;
+use crate::error::AppError;
+use crate::events::{emit_event, SemanticIndexProgressEvent};
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+/// Messages of the workspace closest in meaning to `query`, merged with the
+/// ones containing its words. Needs the semantic index switched on.
+#[tauri::command]
+pub async fn semantic_search_messages(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    query: String,
+    limit: Option<u32>,
+) -> Result<Vec<SemanticHit>, AppError> {
+    state
+        .semantic_index_service
+        .search(&workspace_id, &query, limit)
+        .await
+}
+
+/// Embed every message not indexed yet, with `semantic-index-progress`
+/// events after each batch.
+#[tauri::command]
+pub async fn backfill_semantic_index(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<SemanticIndexProgressEvent, AppError> {
+    let service = state.semantic_index_service.clone();
+    service
+        .backfill(&|progress| {
+            if let Err(e) = emit_event(&app, progress) {
+                tracing::warn!(error = %e, "Failed to emit semantic index progress");
+            }
+        })
+        .await
+}
@@ -0,0 +1,179 @@
+//! What turns text into vectors: an LLM connection's embeddings model, or
+//! the local hashing model when no connection is chosen.
+
+use super::service::{SEMANTIC_INDEX_CONNECTION_ID, SEMANTIC_INDEX_MODEL};
+use super::vector;
+use crate::error::AppError;
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::llm_connection::LLMConnectionService;
+use crate::lib::text;
+use crate::services::LLMService;
+use async_trait::async_trait;
+use std::sync::Arc;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Longest input sent to a model; the start of a message says enough about
+/// what it is about.
+pub const MAX_INPUT_GRAPHEMES: usize = 6000;
+/// Model name vectors of the local hashing model are stored under.
+pub const LOCAL_MODEL: &str = "local-hash-256";
+const LOCAL_DIMS: usize = 256;
+
+#[async_trait]
+pub trait Embedder: Send + Sync {
+    /// Stored with every vector; vectors of another model are made again.
+    fn model(&self) -> &str;
+
+    /// One normalized vector per input, in order.
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, AppError>;
+}
+
+/// A provider's embeddings model, reached through an LLM connection.
+pub struct ProviderEmbedder {
+    pub llm_service: Arc<LLMService>,
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub provider: String,
+    pub model: String,
+}
+
+#[async_trait]
+impl Embedder for ProviderEmbedder {
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, AppError> {
+        let inputs: Vec<String> = inputs
+            .iter()
+            .map(|input| text::truncate(input, MAX_INPUT_GRAPHEMES, ""))
+            .collect();
+        let mut vectors = self
+            .llm_service
+            .embed(
+                &self.base_url,
+                self.api_key.as_deref(),
+                &self.provider,
+                &self.model,
+                &inputs,
+            )
+            .await?;
+        for vector in &mut vectors {
+            vector::normalize(vector);
+        }
+        Ok(vectors)
+    }
+}
+
+/// The connection and embeddings model chosen in the settings, if both are
+/// set.
+pub fn provider_setting(
+    app_settings_service: &AppSettingsService,
+) -> Result<Option<(String, String)>, AppError> {
+    let setting = |key| {
+        app_settings_service
+            .get_by_key(key)
+            .map(|value| value.filter(|value| !value.is_empty()))
+    };
+    Ok(setting(SEMANTIC_INDEX_CONNECTION_ID)?.zip(setting(SEMANTIC_INDEX_MODEL)?))
+}
+
+/// The embedder the settings choose: the chosen connection's embeddings
+/// model, or the local hashing model when none is chosen.
+pub fn configured(
+    app_settings_service: &AppSettingsService,
+    llm_connection_service: &LLMConnectionService,
+    llm_service: &Arc<LLMService>,
+) -> Result<Box<dyn Embedder>, AppError> {
+    let Some((connection_id, model)) = provider_setting(app_settings_service)? else {
+        return Ok(Box::new(LocalEmbedder));
+    };
+    let connection = llm_connection_service
+        .get_by_id(&connection_id)?
+        .ok_or_else(|| {
+            AppError::NotFound(format!(
+                "LLM connection {connection_id} of the semantic index"
+            ))
+        })?;
+    Ok(Box::new(ProviderEmbedder {
+        llm_service: llm_service.clone(),
+        base_url: connection.base_url,
+        api_key: Some(connection.api_key).filter(|key| !key.is_empty()),
+        provider: connection.provider,
+        model,
+    }))
+}
+
+/// Feature hashing of words and their character trigrams. It needs no
+/// download and no network, and finds messages sharing words or word parts
+/// with the query ("onboard" matches "onboarding"), but not paraphrases the
+/// way a trained model does.
+pub struct LocalEmbedder;
+
+impl LocalEmbedder {
+    pub fn embed_one(input: &str) -> Vec<f32> {
+        let mut vector = vec![0.0_f32; LOCAL_DIMS];
+        for word in input.unicode_words() {
+            let word = word.to_lowercase();
+            add_feature(&mut vector, word.as_bytes(), 1.0);
+            let padded: Vec<char> = format!(" {word} ").chars().collect();
+            for trigram in padded.windows(3) {
+                let trigram: String = trigram.iter().collect();
+                add_feature(&mut vector, trigram.as_bytes(), 0.5);
+            }
+        }
+        vector::normalize(&mut vector);
+        vector
+    }
+}
+
+#[async_trait]
+impl Embedder for LocalEmbedder {
+    fn model(&self) -> &str {
+        LOCAL_MODEL
+    }
+
+    async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, AppError> {
+        Ok(inputs
+            .iter()
+            .map(|input| Self::embed_one(&text::truncate(input, MAX_INPUT_GRAPHEMES, "")))
+            .collect())
+    }
+}
+
+/// Add `weight` to the slot `feature` hashes to, with a sign from the hash
+/// so unrelated features cancel out instead of piling up.
+fn add_feature(vector: &mut [f32], feature: &[u8], weight: f32) {
+    let hash = fnv1a(feature);
+    let slot = usize::try_from(hash % LOCAL_DIMS as u64).unwrap_or_default();
+    let sign = if hash >> 63 == 0 { 1.0 } else { -1.0 };
+    vector[slot] += sign * weight;
+}
+
+/// FNV-1a, stable across builds and platforms unlike the std hasher.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn local_embedder_is_deterministic_and_normalized() {
+        use crate::features::semantic_index::embedder::LocalEmbedder;
+        use crate::features::semantic_index::vector::{dot, from_blob, to_blob};
+
+        let a = LocalEmbedder::embed_one("Making onboarding less confusing");
+        assert_eq!(
+            a,
+            LocalEmbedder::embed_one("Making onboarding less confusing")
+        );
+        assert!((dot(&a, &a) - 1.0).abs() < 1e-5);
+        let related = LocalEmbedder::embed_one("the onboarding flow");
+        let unrelated = LocalEmbedder::embed_one("quarterly tax invoice");
+        assert!(dot(&a, &related) > dot(&a, &unrelated));
+        assert_eq!(from_blob(&to_blob(&a)).unwrap(), a);
+        assert!(from_blob(&[0, 1, 2]).is_none());
+    }
+}
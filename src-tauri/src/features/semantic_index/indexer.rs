@@ -0,0 +1,535 @@
+//! Indexing passes and searches, on a repository and an embedder.
+
+use super::embedder::Embedder;
+use super::models::{MessageEmbedding, SemanticHit};
+use super::repository::SemanticIndexRepository;
+use super::vector;
+use crate::error::AppError;
+use crate::events::SemanticIndexProgressEvent;
+use crate::lib::text;
+use std::collections::HashMap;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Messages embedded per request.
+pub const BATCH_SIZE: u32 = 32;
+/// Weight damping of reciprocal rank fusion; the usual value.
+const RANK_CONSTANT: f32 = 60.0;
+/// Semantic hits less similar than this are noise rather than matches.
+pub const MIN_SIMILARITY: f32 = 0.1;
+/// Query words the keyword side looks for.
+const MAX_KEYWORD_TERMS: usize = 8;
+const EXCERPT_GRAPHEMES: usize = 200;
+
+/// Embed every pending message, a batch at a time. When a batch fails, its
+/// messages are tried one by one: those the model still fails on are
+/// skipped until the next pass, which tries them again. The pass stops with
+/// the error when a whole batch fails that way, as the model is then likely
+/// unreachable.
+pub async fn index_pending(
+    repository: &dyn SemanticIndexRepository,
+    embedder: &dyn Embedder,
+    on_progress: &(dyn Fn(SemanticIndexProgressEvent) + Sync),
+) -> Result<SemanticIndexProgressEvent, AppError> {
+    let model = embedder.model().to_string();
+    let mut progress = SemanticIndexProgressEvent {
+        model: model.clone(),
+        indexed: 0,
+        skipped: 0,
+        total: repository.pending_count(&model)?,
+        done: false,
+    };
+
+    loop {
+        // Skipped messages stay pending, ahead of the rest
+        let skipped = u32::try_from(progress.skipped).unwrap_or(u32::MAX);
+        let batch = repository.pending(&model, BATCH_SIZE, skipped)?;
+        if batch.is_empty() {
+            break;
+        }
+        let inputs: Vec<String> = batch.iter().map(|m| m.content.clone()).collect();
+        let vectors = match embedder.embed(&inputs).await {
+            Ok(vectors) if vectors.len() == inputs.len() => {
+                vectors.into_iter().map(Some).collect::<Vec<_>>()
+            }
+            result => {
+                if let Err(e) = result {
+                    tracing::warn!(model = %model, error = %e, "Embedding batch failed, trying messages one by one");
+                }
+                let mut vectors = Vec::with_capacity(inputs.len());
+                let mut last_error = None;
+                for input in &inputs {
+                    match embedder.embed(std::slice::from_ref(input)).await {
+                        Ok(mut one) if one.len() == 1 => vectors.push(one.pop()),
+                        Ok(_) => vectors.push(None),
+                        Err(e) => {
+                            last_error = Some(e);
+                            vectors.push(None);
+                        }
+                    }
+                }
+                if vectors.iter().all(Option::is_none) {
+                    return Err(last_error.unwrap_or_else(|| {
+                        AppError::Llm(format!("Model {model} returned no embeddings"))
+                    }));
+                }
+                vectors
+            }
+        };
+
+        let created_at = chrono::Utc::now().timestamp_millis();
+        let mut embeddings = Vec::new();
+        for (message, vector) in batch.into_iter().zip(vectors) {
+            match vector.filter(|vector| !vector.is_empty()) {
+                Some(vector) => embeddings.push(MessageEmbedding {
+                    message_id: message.id,
+                    chat_id: message.chat_id,
+                    workspace_id: message.workspace_id,
+                    vector,
+                    model: model.clone(),
+                    created_at,
+                }),
+                None => {
+                    tracing::warn!(message_id = %message.id, model = %model, "Message could not be embedded, skipped");
+                    progress.skipped += 1;
+                }
+            }
+        }
+        repository.save(&embeddings)?;
+        progress.indexed += embeddings.len() as u64;
+        on_progress(progress.clone());
+    }
+
+    progress.done = true;
+    on_progress(progress.clone());
+    Ok(progress)
+}
+
+/// Lowercase words of `query` the keyword side matches, without repeats.
+pub fn keyword_terms(query: &str) -> Vec<String> {
+    let mut terms: Vec<String> = Vec::new();
+    for word in query.unicode_words().map(str::to_lowercase) {
+        if word.chars().count() > 1 && !terms.contains(&word) {
+            terms.push(word);
+        }
+    }
+    terms.truncate(MAX_KEYWORD_TERMS);
+    terms
+}
+
+/// A search hit before its message is read.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MergedHit {
+    pub message_id: String,
+    pub score: f32,
+    pub similarity: Option<f32>,
+    pub keyword_match: bool,
+}
+
+/// Merge semantic hits, best first with their similarity, and keyword hits,
+/// best first, by reciprocal rank fusion: a message scores `1 / (60 + rank)`
+/// for each list it is in, so one found both ways ranks above one found
+/// only one way at the same rank.
+#[allow(clippy::cast_precision_loss)]
+pub fn merge_hits(semantic: &[(String, f32)], keyword: &[String], limit: usize) -> Vec<MergedHit> {
+    let rank_score = |rank: usize| 1.0 / (RANK_CONSTANT + rank as f32 + 1.0);
+    let mut hits: HashMap<&str, MergedHit> = HashMap::new();
+    for (rank, (message_id, similarity)) in semantic.iter().enumerate() {
+        hits.insert(
+            message_id,
+            MergedHit {
+                message_id: message_id.clone(),
+                score: rank_score(rank),
+                similarity: Some(*similarity),
+                keyword_match: false,
+            },
+        );
+    }
+    for (rank, message_id) in keyword.iter().enumerate() {
+        let hit = hits.entry(message_id).or_insert_with(|| MergedHit {
+            message_id: message_id.clone(),
+            score: 0.0,
+            similarity: None,
+            keyword_match: false,
+        });
+        hit.score += rank_score(rank);
+        hit.keyword_match = true;
+    }
+
+    let mut hits: Vec<MergedHit> = hits.into_values().collect();
+    hits.sort_by(|a, b| {
+        b.score
+            .total_cmp(&a.score)
+            .then_with(|| {
+                b.similarity
+                    .unwrap_or(-1.0)
+                    .total_cmp(&a.similarity.unwrap_or(-1.0))
+            })
+            .then_with(|| a.message_id.cmp(&b.message_id))
+    });
+    hits.truncate(limit);
+    hits
+}
+
+/// Messages of the workspace closest in meaning to `query`, merged with the
+/// ones containing its words. When the query cannot be embedded, the
+/// keyword hits are returned alone.
+pub async fn search(
+    repository: &dyn SemanticIndexRepository,
+    embedder: &dyn Embedder,
+    workspace_id: &str,
+    query: &str,
+    limit: usize,
+) -> Result<Vec<SemanticHit>, AppError> {
+    let candidates = limit.saturating_mul(2);
+    let semantic = match embedder.embed(&[query.to_string()]).await {
+        Ok(mut vectors) if vectors.len() == 1 => {
+            let query_vector = vectors.remove(0);
+            let stored = repository.vectors(workspace_id, embedder.model())?;
+            vector::top_k(&query_vector, stored, candidates)
+                .into_iter()
+                .filter(|(_, similarity)| *similarity >= MIN_SIMILARITY)
+                .collect()
+        }
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            tracing::warn!(error = %e, "Could not embed the search query, using keyword hits only");
+            Vec::new()
+        }
+    };
+    let keyword = repository.keyword_matches(
+        workspace_id,
+        &keyword_terms(query),
+        u32::try_from(candidates).unwrap_or(u32::MAX),
+    )?;
+
+    let merged = merge_hits(&semantic, &keyword, limit);
+    let ids: Vec<String> = merged.iter().map(|hit| hit.message_id.clone()).collect();
+    let mut messages: HashMap<String, _> = repository
+        .messages(&ids)?
+        .into_iter()
+        .map(|message| (message.id.clone(), message))
+        .collect();
+    Ok(merged
+        .into_iter()
+        .filter_map(|hit| {
+            let message = messages.remove(&hit.message_id)?;
+            Some(SemanticHit {
+                message_id: hit.message_id,
+                chat_id: message.chat_id,
+                role: message.role,
+                excerpt: text::truncate(message.content.trim(), EXCERPT_GRAPHEMES, "…"),
+                timestamp: message.timestamp,
+                score: hit.score,
+                similarity: hit.similarity,
+                keyword_match: hit.keyword_match,
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    struct MemorySemanticIndexRepository(std::sync::Mutex<rusqlite::Connection>);
+
+    impl crate::features::semantic_index::SemanticIndexRepository for MemorySemanticIndexRepository {
+        fn pending(
+            &self,
+            model: &str,
+            limit: u32,
+            offset: u32,
+        ) -> Result<Vec<crate::features::semantic_index::PendingMessage>, crate::error::AppError>
+        {
+            crate::features::semantic_index::pending_messages(
+                &self.0.lock().unwrap(),
+                model,
+                limit,
+                offset,
+            )
+        }
+
+        fn pending_count(&self, model: &str) -> Result<u64, crate::error::AppError> {
+            crate::features::semantic_index::pending_count(&self.0.lock().unwrap(), model)
+        }
+
+        fn save(
+            &self,
+            embeddings: &[crate::features::semantic_index::MessageEmbedding],
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::semantic_index::save_embeddings(
+                &mut self.0.lock().unwrap(),
+                embeddings,
+            )
+        }
+
+        fn vectors(
+            &self,
+            workspace_id: &str,
+            model: &str,
+        ) -> Result<Vec<(String, Vec<f32>)>, crate::error::AppError> {
+            crate::features::semantic_index::workspace_vectors(
+                &self.0.lock().unwrap(),
+                workspace_id,
+                model,
+            )
+        }
+
+        fn keyword_matches(
+            &self,
+            workspace_id: &str,
+            terms: &[String],
+            limit: u32,
+        ) -> Result<Vec<String>, crate::error::AppError> {
+            crate::features::semantic_index::keyword_matches(
+                &self.0.lock().unwrap(),
+                workspace_id,
+                terms,
+                limit,
+            )
+        }
+
+        fn messages(
+            &self,
+            ids: &[String],
+        ) -> Result<Vec<crate::features::semantic_index::FoundMessage>, crate::error::AppError>
+        {
+            crate::features::semantic_index::found_messages(&self.0.lock().unwrap(), ids)
+        }
+
+        fn prune(&self) -> Result<usize, crate::error::AppError> {
+            crate::features::semantic_index::prune_embeddings(&self.0.lock().unwrap())
+        }
+
+        fn clear(&self) -> Result<usize, crate::error::AppError> {
+            Ok(self
+                .0
+                .lock()
+                .unwrap()
+                .execute("DELETE FROM message_embeddings", [])?)
+        }
+
+        fn stats(
+            &self,
+        ) -> Result<crate::features::semantic_index::SemanticIndexStats, crate::error::AppError>
+        {
+            crate::features::semantic_index::index_stats(&self.0.lock().unwrap())
+        }
+    }
+
+    /// Embeds text on three topic axes, so rankings are known in advance.
+    /// Inputs containing `fail_on` are rejected.
+    struct FakeEmbedder {
+        fail_on: std::sync::Mutex<Option<&'static str>>,
+    }
+
+    impl FakeEmbedder {
+        fn new() -> Self {
+            Self {
+                fail_on: std::sync::Mutex::new(None),
+            }
+        }
+
+        fn vector(text: &str) -> Vec<f32> {
+            let text = text.to_lowercase();
+            let topics: [&[&str]; 3] = [
+                &["onboarding", "sign-up", "confusing"],
+                &["invoice", "payment"],
+                &["rain", "forecast"],
+            ];
+            let mut vector: Vec<f32> = topics
+                .iter()
+                .map(|words| {
+                    words
+                        .iter()
+                        .map(|word| if text.contains(word) { 1.0 } else { 0.0 })
+                        .sum()
+                })
+                .collect();
+            vector.push(0.1);
+            crate::features::semantic_index::vector::normalize(&mut vector);
+            vector
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl crate::features::semantic_index::embedder::Embedder for FakeEmbedder {
+        fn model(&self) -> &str {
+            "fake-topics"
+        }
+
+        async fn embed(&self, inputs: &[String]) -> Result<Vec<Vec<f32>>, crate::error::AppError> {
+            let fail_on = *self.fail_on.lock().unwrap();
+            inputs
+                .iter()
+                .map(|input| match fail_on {
+                    Some(word) if input.contains(word) => {
+                        Err(crate::error::AppError::Llm("Input rejected".to_string()))
+                    }
+                    _ => Ok(Self::vector(input)),
+                })
+                .collect()
+        }
+    }
+
+    fn semantic_index_fixture() -> MemorySemanticIndexRepository {
+        let conn = migrated_db();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0), ('w2', 'Home', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+                 VALUES ('c1', 'w1', 'Product', 0, 0), ('c2', 'w2', 'Notes', 0, 0);
+             INSERT INTO messages (id, chat_id, role, content, timestamp) VALUES
+                 ('m1', 'c1', 'user', 'How do we make the sign-up flow less confusing for new users?', 1),
+                 ('m2', 'c1', 'assistant', 'Send an invoice after each payment.', 2),
+                 ('m3', 'c1', 'user', 'Will it rain tomorrow? Check the forecast.', 3),
+                 ('m4', 'c1', 'tool', 'onboarding tool output', 4),
+                 ('m5', 'c2', 'user', 'Onboarding checklist', 5),
+                 ('m6', 'c1', 'assistant', '', 6);",
+        )
+        .unwrap();
+        MemorySemanticIndexRepository(std::sync::Mutex::new(conn))
+    }
+
+    #[test]
+    fn semantic_search_ranks_by_meaning_within_the_workspace() {
+        use crate::features::semantic_index::indexer::{index_pending, search};
+
+        let repository = semantic_index_fixture();
+        let embedder = FakeEmbedder::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let progress = runtime
+            .block_on(index_pending(&repository, &embedder, &|_| {}))
+            .unwrap();
+        // Tool output and empty messages are not indexed
+        assert_eq!(
+            (progress.total, progress.indexed, progress.skipped),
+            (4, 4, 0)
+        );
+        assert!(progress.done);
+
+        let query = "that conversation about making the onboarding flow less confusing";
+        let hits = runtime
+            .block_on(search(&repository, &embedder, "w1", query, 5))
+            .unwrap();
+        let ids: Vec<&str> = hits.iter().map(|hit| hit.message_id.as_str()).collect();
+        // m5 matches too but belongs to another workspace; the rest are off topic
+        assert_eq!(ids, vec!["m1"]);
+        assert!(hits[0].similarity.unwrap() > 0.9);
+        assert!(!hits[0].keyword_match);
+        assert_eq!(hits[0].chat_id, "c1");
+
+        let hits = runtime
+            .block_on(search(&repository, &embedder, "w1", "rain forecast", 5))
+            .unwrap();
+        assert_eq!(hits[0].message_id, "m3");
+    }
+
+    #[test]
+    fn semantic_index_follows_new_edited_and_deleted_messages() {
+        use crate::features::semantic_index::indexer::index_pending;
+        use crate::features::semantic_index::{forget_message, SemanticIndexRepository};
+
+        let repository = semantic_index_fixture();
+        let embedder = FakeEmbedder::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(index_pending(&repository, &embedder, &|_| {}))
+            .unwrap();
+        assert_eq!(repository.pending_count("fake-topics").unwrap(), 0);
+        // Vectors of another model do not count as indexed
+        assert_eq!(repository.pending_count("other-model").unwrap(), 4);
+
+        {
+            let conn = repository.0.lock().unwrap();
+            conn.execute(
+                "INSERT INTO messages (id, chat_id, role, content, timestamp) VALUES ('m7', 'c1', 'user', 'The payment failed again', 7)",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "UPDATE messages SET content = 'Forecast says rain' WHERE id = 'm2'",
+                [],
+            )
+            .unwrap();
+            forget_message(&conn, "m2").unwrap();
+            conn.execute("DELETE FROM messages WHERE id = 'm3'", [])
+                .unwrap();
+        }
+        assert_eq!(repository.pending_count("fake-topics").unwrap(), 2);
+        assert_eq!(repository.prune().unwrap(), 1);
+        assert_eq!(repository.stats().unwrap().indexed_messages, 2);
+
+        // A message the model rejects is skipped and tried again later
+        *embedder.fail_on.lock().unwrap() = Some("failed");
+        let events = std::sync::Mutex::new(Vec::new());
+        let progress = runtime
+            .block_on(index_pending(&repository, &embedder, &|event| {
+                events.lock().unwrap().push(event);
+            }))
+            .unwrap();
+        assert_eq!(
+            (progress.total, progress.indexed, progress.skipped),
+            (2, 1, 1)
+        );
+        assert!(events.lock().unwrap().last().unwrap().done);
+        assert_eq!(repository.pending_count("fake-topics").unwrap(), 1);
+
+        // When every message fails the pass stops and nothing is lost
+        *embedder.fail_on.lock().unwrap() = Some("");
+        assert!(runtime
+            .block_on(index_pending(&repository, &embedder, &|_| {}))
+            .is_err());
+        assert_eq!(repository.pending_count("fake-topics").unwrap(), 1);
+
+        *embedder.fail_on.lock().unwrap() = None;
+        let progress = runtime
+            .block_on(index_pending(&repository, &embedder, &|_| {}))
+            .unwrap();
+        assert_eq!((progress.indexed, progress.skipped), (1, 0));
+        let stats = repository.stats().unwrap();
+        assert_eq!(stats.indexed_messages, 4);
+        assert_eq!(stats.storage_bytes, 4 * 4 * 4);
+    }
+
+    #[test]
+    fn semantic_hits_merge_with_keyword_hits() {
+        use crate::features::semantic_index::indexer::{index_pending, merge_hits, search};
+
+        let semantic = vec![("a".to_string(), 0.9), ("b".to_string(), 0.5)];
+        let keyword = vec!["b".to_string(), "c".to_string()];
+        let merged = merge_hits(&semantic, &keyword, 10);
+        let ids: Vec<&str> = merged.iter().map(|hit| hit.message_id.as_str()).collect();
+        assert_eq!(ids, vec!["b", "a", "c"]);
+        assert!(merged[0].keyword_match);
+        assert!(merged[0]
+            .similarity
+            .is_some_and(|similarity| (similarity - 0.5).abs() < f32::EPSILON));
+        assert!(!merged[1].keyword_match);
+        assert_eq!(merged[2].similarity, None);
+        assert_eq!(merge_hits(&semantic, &keyword, 1).len(), 1);
+
+        let repository = semantic_index_fixture();
+        let embedder = FakeEmbedder::new();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime
+            .block_on(index_pending(&repository, &embedder, &|_| {}))
+            .unwrap();
+        let hits = runtime
+            .block_on(search(&repository, &embedder, "w1", "invoice payment", 5))
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_id, "m2");
+        assert!(hits[0].keyword_match && hits[0].similarity.is_some());
+
+        // Without a query embedding the keyword hits still come back
+        *embedder.fail_on.lock().unwrap() = Some("");
+        let hits = runtime
+            .block_on(search(&repository, &embedder, "w1", "INVOICE", 5))
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].message_id, "m2");
+        assert_eq!(hits[0].similarity, None);
+        assert!(hits[0].keyword_match);
+    }
+}
@@ -0,0 +1,22 @@
+//! Opt-in semantic search over message content.
+//!
+//! When the index is switched on, a background task embeds user and
+//! assistant messages into `message_embeddings`, a batch at a time, with the
+//! embeddings model of a chosen LLM connection or, without one, a small
+//! local hashing model. Search embeds the query and scores every vector of
+//! the workspace by brute force: vectors are normalized when stored, so the
+//! score is a dot product over `f32` chunks the compiler can vectorize. That
+//! stays fast enough up to about 100k indexed messages; beyond that a real
+//! vector index would be needed.
+
+pub mod commands;
+pub mod embedder;
+pub mod indexer;
+pub mod models;
+pub mod repository;
+pub mod service;
+pub mod vector;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
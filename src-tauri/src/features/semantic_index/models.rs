@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// A message found by `semantic_search_messages`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SemanticHit {
+    pub message_id: String,
+    pub chat_id: String,
+    pub role: String,
+    /// Start of the message content
+    pub excerpt: String,
+    pub timestamp: i64,
+    /// Rank of the hit after merging semantic and keyword results; higher is
+    /// better, comparable within one search only
+    pub score: f32,
+    /// Cosine similarity to the query, when the message is indexed
+    pub similarity: Option<f32>,
+    /// Whether the message also contains the words of the query
+    pub keyword_match: bool,
+}
+
+/// A message waiting to be embedded.
+#[derive(Debug, Clone)]
+pub struct PendingMessage {
+    pub id: String,
+    pub chat_id: String,
+    pub workspace_id: String,
+    pub content: String,
+}
+
+/// A message a search found, as read for its hit.
+#[derive(Debug, Clone)]
+pub struct FoundMessage {
+    pub id: String,
+    pub chat_id: String,
+    pub role: String,
+    pub content: String,
+    pub timestamp: i64,
+}
+
+/// The embedding of one message, as stored.
+#[derive(Debug, Clone)]
+pub struct MessageEmbedding {
+    pub message_id: String,
+    pub chat_id: String,
+    pub workspace_id: String,
+    pub vector: Vec<f32>,
+    pub model: String,
+    pub created_at: i64,
+}
+
+/// Size of the index, for `get_system_status`.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct SemanticIndexStats {
+    pub indexed_messages: u64,
+    /// Bytes taken by the stored vectors
+    pub storage_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SemanticIndexStatus {
+    pub enabled: bool,
+    /// Model new vectors are made with
+    pub model: Option<String>,
+    #[serde(flatten)]
+    pub stats: SemanticIndexStats,
+}
@@ -0,0 +1,272 @@
+use super::models::{FoundMessage, MessageEmbedding, PendingMessage, SemanticIndexStats};
+use super::vector;
+use crate::error::AppError;
+use rusqlite::{params, params_from_iter, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait SemanticIndexRepository: Send + Sync {
+    /// User and assistant messages without a vector of `model`, oldest
+    /// first, leaving out the first `offset` of them.
+    fn pending(
+        &self,
+        model: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<PendingMessage>, AppError>;
+    fn pending_count(&self, model: &str) -> Result<u64, AppError>;
+    fn save(&self, embeddings: &[MessageEmbedding]) -> Result<(), AppError>;
+    /// Stored vectors of the workspace's messages made with `model`.
+    fn vectors(&self, workspace_id: &str, model: &str)
+        -> Result<Vec<(String, Vec<f32>)>, AppError>;
+    fn keyword_matches(
+        &self,
+        workspace_id: &str,
+        terms: &[String],
+        limit: u32,
+    ) -> Result<Vec<String>, AppError>;
+    fn messages(&self, ids: &[String]) -> Result<Vec<FoundMessage>, AppError>;
+    /// Drop vectors of messages that no longer exist.
+    fn prune(&self) -> Result<usize, AppError>;
+    fn clear(&self) -> Result<usize, AppError>;
+    fn stats(&self) -> Result<SemanticIndexStats, AppError>;
+}
+
+pub struct SqliteSemanticIndexRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteSemanticIndexRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl SemanticIndexRepository for SqliteSemanticIndexRepository {
+    fn pending(
+        &self,
+        model: &str,
+        limit: u32,
+        offset: u32,
+    ) -> Result<Vec<PendingMessage>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        pending_messages(&conn, model, limit, offset)
+    }
+
+    fn pending_count(&self, model: &str) -> Result<u64, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        pending_count(&conn, model)
+    }
+
+    fn save(&self, embeddings: &[MessageEmbedding]) -> Result<(), AppError> {
+        let mut conn = crate::db::get_connection(&self.app)?;
+        save_embeddings(&mut conn, embeddings)
+    }
+
+    fn vectors(
+        &self,
+        workspace_id: &str,
+        model: &str,
+    ) -> Result<Vec<(String, Vec<f32>)>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        workspace_vectors(&conn, workspace_id, model)
+    }
+
+    fn keyword_matches(
+        &self,
+        workspace_id: &str,
+        terms: &[String],
+        limit: u32,
+    ) -> Result<Vec<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        keyword_matches(&conn, workspace_id, terms, limit)
+    }
+
+    fn messages(&self, ids: &[String]) -> Result<Vec<FoundMessage>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        found_messages(&conn, ids)
+    }
+
+    fn prune(&self) -> Result<usize, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        prune_embeddings(&conn)
+    }
+
+    fn clear(&self) -> Result<usize, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        Ok(conn.execute("DELETE FROM message_embeddings", [])?)
+    }
+
+    fn stats(&self) -> Result<SemanticIndexStats, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        index_stats(&conn)
+    }
+}
+
+const PENDING_FROM: &str = "FROM messages m
+     JOIN chats c ON c.id = m.chat_id
+     LEFT JOIN message_embeddings e ON e.message_id = m.id AND e.model = ?1
+     WHERE e.message_id IS NULL AND m.role IN ('user', 'assistant') AND m.content != ''";
+
+pub fn pending_messages(
+    conn: &Connection,
+    model: &str,
+    limit: u32,
+    offset: u32,
+) -> Result<Vec<PendingMessage>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id, m.chat_id, c.workspace_id, m.content {PENDING_FROM}
+         ORDER BY m.timestamp, m.id LIMIT ?2 OFFSET ?3"
+    ))?;
+    let messages = stmt
+        .query_map(params![model, limit, offset], |row| {
+            Ok(PendingMessage {
+                id: row.get(0)?,
+                chat_id: row.get(1)?,
+                workspace_id: row.get(2)?,
+                content: row.get(3)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(messages)
+}
+
+pub fn pending_count(conn: &Connection, model: &str) -> Result<u64, AppError> {
+    let count: i64 = conn.query_row(
+        &format!("SELECT COUNT(*) {PENDING_FROM}"),
+        params![model],
+        |row| row.get(0),
+    )?;
+    Ok(u64::try_from(count).unwrap_or_default())
+}
+
+pub fn save_embeddings(
+    conn: &mut Connection,
+    embeddings: &[MessageEmbedding],
+) -> Result<(), AppError> {
+    let tx = conn.transaction()?;
+    for embedding in embeddings {
+        tx.execute(
+            "INSERT OR REPLACE INTO message_embeddings (message_id, chat_id, workspace_id, vector, model, dims, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                embedding.message_id,
+                embedding.chat_id,
+                embedding.workspace_id,
+                vector::to_blob(&embedding.vector),
+                embedding.model,
+                embedding.vector.len(),
+                embedding.created_at
+            ],
+        )?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// Forget the vector of a message whose content changed or that was
+/// deleted; an edited message is embedded again by the next pass.
+pub fn forget_message(conn: &Connection, message_id: &str) -> Result<(), AppError> {
+    conn.execute(
+        "DELETE FROM message_embeddings WHERE message_id = ?1",
+        params![message_id],
+    )?;
+    Ok(())
+}
+
+pub fn workspace_vectors(
+    conn: &Connection,
+    workspace_id: &str,
+    model: &str,
+) -> Result<Vec<(String, Vec<f32>)>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT e.message_id, e.vector FROM message_embeddings e
+         JOIN messages m ON m.id = e.message_id
+         WHERE e.workspace_id = ?1 AND e.model = ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![workspace_id, model], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows
+        .into_iter()
+        .filter_map(|(id, blob)| Some((id, vector::from_blob(&blob)?)))
+        .collect())
+}
+
+/// Messages of the workspace containing every term, newest first. `LIKE`
+/// ignores case for ASCII letters only.
+pub fn keyword_matches(
+    conn: &Connection,
+    workspace_id: &str,
+    terms: &[String],
+    limit: u32,
+) -> Result<Vec<String>, AppError> {
+    if terms.is_empty() {
+        return Ok(Vec::new());
+    }
+    let conditions: String = (0..terms.len())
+        .map(|i| format!(" AND m.content LIKE ?{} ESCAPE '\\'", i + 2))
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT m.id FROM messages m JOIN chats c ON c.id = m.chat_id
+         WHERE c.workspace_id = ?1 AND m.role IN ('user', 'assistant'){conditions}
+         ORDER BY m.timestamp DESC LIMIT {limit}"
+    ))?;
+    let patterns = terms.iter().map(|term| {
+        let escaped = term
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+        format!("%{escaped}%")
+    });
+    let values: Vec<String> = std::iter::once(workspace_id.to_string())
+        .chain(patterns)
+        .collect();
+    let ids = stmt
+        .query_map(params_from_iter(values), |row| row.get(0))?
+        .collect::<Result<Vec<String>, _>>()?;
+    Ok(ids)
+}
+
+pub fn found_messages(conn: &Connection, ids: &[String]) -> Result<Vec<FoundMessage>, AppError> {
+    if ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; ids.len()].join(", ");
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, chat_id, role, content, timestamp FROM messages WHERE id IN ({placeholders})"
+    ))?;
+    let messages = stmt
+        .query_map(params_from_iter(ids), |row| {
+            Ok(FoundMessage {
+                id: row.get(0)?,
+                chat_id: row.get(1)?,
+                role: row.get(2)?,
+                content: row.get(3)?,
+                timestamp: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(messages)
+}
+
+pub fn prune_embeddings(conn: &Connection) -> Result<usize, AppError> {
+    Ok(conn.execute(
+        "DELETE FROM message_embeddings WHERE message_id NOT IN (SELECT id FROM messages)",
+        [],
+    )?)
+}
+
+pub fn index_stats(conn: &Connection) -> Result<SemanticIndexStats, AppError> {
+    let (count, bytes): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(LENGTH(vector)), 0) FROM message_embeddings",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok(SemanticIndexStats {
+        indexed_messages: u64::try_from(count).unwrap_or_default(),
+        storage_bytes: u64::try_from(bytes).unwrap_or_default(),
+    })
+}
@@ -0,0 +1,198 @@
+use super::embedder::{self, Embedder, LOCAL_MODEL};
+use super::indexer;
+use super::models::{SemanticHit, SemanticIndexStatus};
+use super::repository::SemanticIndexRepository;
+use crate::error::AppError;
+use crate::events::SemanticIndexProgressEvent;
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::encryption::EncryptionService;
+use crate::features::llm_connection::LLMConnectionService;
+use crate::features::system::ActivityHalt;
+use crate::services::LLMService;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// App setting switching the index on: `true`, off otherwise.
+pub const SEMANTIC_INDEX_ENABLED: &str = "semanticIndexEnabled";
+/// App setting with the LLM connection whose embeddings model indexes.
+/// Unset, the local hashing model does.
+pub const SEMANTIC_INDEX_CONNECTION_ID: &str = "semanticIndexConnectionId";
+/// App setting with the embeddings model of that connection.
+pub const SEMANTIC_INDEX_MODEL: &str = "semanticIndexModel";
+/// How often the background task embeds new and edited messages.
+const INDEX_INTERVAL: Duration = Duration::from_mins(2);
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+const MAX_SEARCH_LIMIT: u32 = 100;
+
+pub struct SemanticIndexService {
+    repository: Arc<dyn SemanticIndexRepository>,
+    app_settings_service: Arc<AppSettingsService>,
+    llm_connection_service: Arc<LLMConnectionService>,
+    llm_service: Arc<LLMService>,
+    encryption_service: Arc<EncryptionService>,
+    activity_halt: Arc<ActivityHalt>,
+    /// One indexing pass at a time
+    run_lock: tokio::sync::Mutex<()>,
+}
+
+impl SemanticIndexService {
+    pub fn new(
+        repository: Arc<dyn SemanticIndexRepository>,
+        app_settings_service: Arc<AppSettingsService>,
+        llm_connection_service: Arc<LLMConnectionService>,
+        llm_service: Arc<LLMService>,
+        encryption_service: Arc<EncryptionService>,
+        activity_halt: Arc<ActivityHalt>,
+    ) -> Self {
+        Self {
+            repository,
+            app_settings_service,
+            llm_connection_service,
+            llm_service,
+            encryption_service,
+            activity_halt,
+            run_lock: tokio::sync::Mutex::new(()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> Result<bool, AppError> {
+        Ok(self
+            .app_settings_service
+            .get_by_key(SEMANTIC_INDEX_ENABLED)?
+            .is_some_and(|value| value == "true"))
+    }
+
+    fn embedder(&self) -> Result<Box<dyn Embedder>, AppError> {
+        embedder::configured(
+            &self.app_settings_service,
+            &self.llm_connection_service,
+            &self.llm_service,
+        )
+    }
+
+    /// Errors unless the index is on and message content is readable; while
+    /// encryption is on, nothing is indexed or searched.
+    fn ensure_available(&self) -> Result<(), AppError> {
+        if !self.is_enabled()? {
+            return Err(AppError::Validation(
+                "Semantic search is off; switch it on in the settings".to_string(),
+            ));
+        }
+        if !self.encryption_service.status()?.message_search_available {
+            return Err(AppError::Validation(
+                "Semantic search is not available while encryption is on".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Embed the messages not indexed yet. With encryption on, the index is
+    /// emptied instead, so no vector outlives the plain text it came from.
+    pub async fn run_pass(
+        &self,
+        on_progress: &(dyn Fn(SemanticIndexProgressEvent) + Sync),
+    ) -> Result<Option<SemanticIndexProgressEvent>, AppError> {
+        if !self.is_enabled()? {
+            return Ok(None);
+        }
+        let _running = self.run_lock.lock().await;
+        if !self.encryption_service.status()?.message_search_available {
+            let cleared = self.repository.clear()?;
+            if cleared > 0 {
+                tracing::info!(cleared, "Emptied the semantic index while encryption is on");
+            }
+            return Ok(None);
+        }
+
+        let pruned = self.repository.prune()?;
+        if pruned > 0 {
+            tracing::debug!(pruned, "Dropped vectors of deleted messages");
+        }
+        let embedder = self.embedder()?;
+        indexer::index_pending(self.repository.as_ref(), embedder.as_ref(), on_progress)
+            .await
+            .map(Some)
+    }
+
+    /// Index everything pending now, reporting progress as it goes.
+    pub async fn backfill(
+        &self,
+        on_progress: &(dyn Fn(SemanticIndexProgressEvent) + Sync),
+    ) -> Result<SemanticIndexProgressEvent, AppError> {
+        self.ensure_available()?;
+        self.activity_halt.ensure_running()?;
+        self.run_pass(on_progress)
+            .await?
+            .ok_or_else(|| AppError::Validation("Semantic search is off".to_string()))
+    }
+
+    /// Run an indexing pass every `INDEX_INTERVAL` while the index is on
+    /// and activity is not halted.
+    pub fn start_background(self: Arc<Self>) {
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(INDEX_INTERVAL);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+            loop {
+                interval.tick().await;
+                if self.activity_halt.is_halted() {
+                    continue;
+                }
+                match self.run_pass(&|_| {}).await {
+                    Ok(Some(progress)) if progress.indexed > 0 || progress.skipped > 0 => {
+                        tracing::info!(
+                            model = %progress.model,
+                            indexed = progress.indexed,
+                            skipped = progress.skipped,
+                            "Semantic index updated"
+                        );
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::warn!(error = %e, "Semantic indexing pass failed"),
+                }
+            }
+        });
+    }
+
+    pub async fn search(
+        &self,
+        workspace_id: &str,
+        query: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<SemanticHit>, AppError> {
+        self.ensure_available()?;
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let limit = limit
+            .unwrap_or(DEFAULT_SEARCH_LIMIT)
+            .clamp(1, MAX_SEARCH_LIMIT);
+        let embedder = self.embedder()?;
+        indexer::search(
+            self.repository.as_ref(),
+            embedder.as_ref(),
+            workspace_id,
+            query,
+            limit as usize,
+        )
+        .await
+    }
+
+    /// Whether the index is on, its model and how much it stores.
+    pub fn status(&self) -> Result<SemanticIndexStatus, AppError> {
+        let enabled = self.is_enabled()?;
+        let model = if enabled {
+            Some(
+                embedder::provider_setting(&self.app_settings_service)?
+                    .map_or_else(|| LOCAL_MODEL.to_string(), |(_, model)| model),
+            )
+        } else {
+            None
+        };
+        Ok(SemanticIndexStatus {
+            enabled,
+            model,
+            stats: self.repository.stats()?,
+        })
+    }
+}
@@ -0,0 +1,79 @@
+//! Vectors as stored and scored.
+//!
+//! Vectors are kept normalized to unit length, so the cosine similarity of
+//! two of them is their dot product. They are stored as little-endian `f32`
+//! bytes.
+
+/// Lanes summed side by side in `dot`, so the loop vectorizes.
+const LANES: usize = 8;
+
+/// Scale `vector` to unit length; a zero vector stays as it is.
+pub fn normalize(vector: &mut [f32]) {
+    let norm = dot(vector, vector).sqrt();
+    if norm > f32::EPSILON {
+        for value in vector.iter_mut() {
+            *value /= norm;
+        }
+    }
+}
+
+/// Dot product over the common length of `a` and `b`.
+pub fn dot(a: &[f32], b: &[f32]) -> f32 {
+    let len = a.len().min(b.len());
+    let (a, b) = (&a[..len], &b[..len]);
+    let mut sums = [0.0_f32; LANES];
+    let a_chunks = a.chunks_exact(LANES);
+    let b_chunks = b.chunks_exact(LANES);
+    let tail: f32 = a_chunks
+        .remainder()
+        .iter()
+        .zip(b_chunks.remainder())
+        .map(|(x, y)| x * y)
+        .sum();
+    for (a, b) in a_chunks.zip(b_chunks) {
+        for ((sum, x), y) in sums.iter_mut().zip(a).zip(b) {
+            *sum += x * y;
+        }
+    }
+    sums.iter().sum::<f32>() + tail
+}
+
+pub fn to_blob(vector: &[f32]) -> Vec<u8> {
+    vector
+        .iter()
+        .flat_map(|value| value.to_le_bytes())
+        .collect()
+}
+
+/// `None` when `bytes` is not a whole number of `f32`s.
+pub fn from_blob(bytes: &[u8]) -> Option<Vec<f32>> {
+    if bytes.len() % 4 != 0 {
+        return None;
+    }
+    Some(
+        bytes
+            .chunks_exact(4)
+            .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+            .collect(),
+    )
+}
+
+/// The `limit` candidates most similar to `query`, best first. Candidates of
+/// another dimension than the query are left out.
+pub fn top_k<T>(
+    query: &[f32],
+    candidates: impl IntoIterator<Item = (T, Vec<f32>)>,
+    limit: usize,
+) -> Vec<(T, f32)> {
+    let mut scored: Vec<(T, f32)> = candidates
+        .into_iter()
+        .filter(|(_, vector)| vector.len() == query.len())
+        .map(|(item, vector)| {
+            let score = dot(query, &vector);
+            (item, score)
+        })
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(limit);
+    scored
+}
@@ -0,0 +1,22 @@
+use super::models::{ChatShare, ChatShareOptions};
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Binds to 127.0.0.1 unless `options.allow_lan` is set.
+#[tauri::command]
+pub async fn start_chat_share(
+    chat_id: String,
+    options: Option<ChatShareOptions>,
+    state: State<'_, AppState>,
+) -> Result<ChatShare, AppError> {
+    state
+        .share_service
+        .start(&chat_id, options.unwrap_or_default())
+        .await
+}
+
+#[tauri::command]
+pub fn stop_chat_share(chat_id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.share_service.stop(&chat_id)
+}
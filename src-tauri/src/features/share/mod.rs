@@ -0,0 +1,8 @@
+pub mod commands;
+pub mod models;
+pub mod projection;
+pub mod server;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
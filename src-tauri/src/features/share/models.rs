@@ -0,0 +1,38 @@
+use serde::{Deserialize, Serialize};
+
+/// How a chat share is started.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ChatShareOptions {
+    /// Listen on all interfaces so others on the LAN can connect, instead of
+    /// only on 127.0.0.1
+    #[serde(default)]
+    pub allow_lan: bool,
+    /// Stream message text, reasoning, tool results and errors, with
+    /// secrets masked; otherwise only ids, tool names and statuses
+    #[serde(default)]
+    pub include_content: bool,
+    /// Defaults to `DEFAULT_SHARE_MINUTES`
+    pub expires_in_minutes: Option<u64>,
+}
+
+/// A running read-only share of one chat's live events.
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatShare {
+    pub id: String,
+    pub chat_id: String,
+    /// Viewer page, with the access token in the path
+    pub url: String,
+    pub allow_lan: bool,
+    pub include_content: bool,
+    pub started_at: i64,
+    pub expires_at: i64,
+    /// Viewers currently connected
+    pub viewers: usize,
+}
+
+/// One event as sent to viewers, under its catalog name.
+#[derive(Debug, Clone, Serialize)]
+pub struct SharedEvent {
+    pub event: &'static str,
+    pub data: serde_json::Value,
+}
@@ -0,0 +1,151 @@
+//! What a chat share forwards of the app's events.
+//!
+//! Only the streaming events listed here are forwarded, and of each only the
+//! listed fields, so ids of other chats, permission prompts and anything a
+//! viewer could act on never leave the app. Content fields are dropped unless
+//! the share includes content, and are then masked like tool-call arguments.
+//! Chunks are masked one by one, so a secret split across two chunks can get
+//! through; shares are meant for watching a run, not for sensitive chats.
+
+use super::models::SharedEvent;
+use crate::constants::TauriEvents;
+use crate::features::redaction::secrets;
+use serde_json::{Map, Value};
+
+struct SharedEventSpec {
+    name: &'static str,
+    fields: &'static [&'static str],
+    content_fields: &'static [&'static str],
+    /// Nothing is left to forward without the content fields
+    content_only: bool,
+}
+
+const SHARED_EVENTS: &[SharedEventSpec] = &[
+    SharedEventSpec {
+        name: TauriEvents::MESSAGE_STARTED,
+        fields: &["user_message_id", "assistant_message_id"],
+        content_fields: &[],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::MESSAGE_CHUNK,
+        fields: &["message_id"],
+        content_fields: &["chunk"],
+        content_only: true,
+    },
+    SharedEventSpec {
+        name: TauriEvents::THINKING_CHUNK,
+        fields: &["message_id"],
+        content_fields: &["chunk"],
+        content_only: true,
+    },
+    SharedEventSpec {
+        name: TauriEvents::MESSAGE_COMPLETE,
+        fields: &["message_id", "token_usage"],
+        content_fields: &["content"],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::MESSAGE_ERROR,
+        fields: &["message_id"],
+        content_fields: &["error"],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::MESSAGE_CANCELLED,
+        fields: &["message_id"],
+        content_fields: &["content"],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::AGENT_LOOP_ITERATION,
+        fields: &["iteration", "max_iterations", "has_tool_calls"],
+        content_fields: &[],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::TOOL_EXECUTION_STARTED,
+        fields: &["message_id", "tool_calls_count"],
+        content_fields: &[],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::TOOL_EXECUTION_PROGRESS,
+        fields: &["message_id", "tool_call_id", "tool_name", "status"],
+        content_fields: &["result", "error"],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::TOOL_EXECUTION_COMPLETED,
+        fields: &[
+            "message_id",
+            "tool_calls_count",
+            "successful_count",
+            "failed_count",
+        ],
+        content_fields: &[],
+        content_only: false,
+    },
+    SharedEventSpec {
+        name: TauriEvents::TOOL_EXECUTION_ERROR,
+        fields: &["message_id", "tool_call_id", "tool_name"],
+        content_fields: &["error"],
+        content_only: false,
+    },
+];
+
+/// Whether events named `name` are ever forwarded; checked before the
+/// payload is serialized.
+pub fn is_shared_event(name: &str) -> bool {
+    SHARED_EVENTS.iter().any(|spec| spec.name == name)
+}
+
+/// The chat a serialized payload belongs to.
+pub fn event_chat_id(payload: &Value) -> Option<&str> {
+    payload.get("chat_id").and_then(Value::as_str)
+}
+
+/// What viewers of a share see of the event, or `None` when it is not
+/// forwarded.
+pub fn project(name: &str, payload: &Value, include_content: bool) -> Option<SharedEvent> {
+    let spec = SHARED_EVENTS.iter().find(|spec| spec.name == name)?;
+    if spec.content_only && !include_content {
+        return None;
+    }
+
+    let mut data = Map::new();
+    for field in spec.fields {
+        if let Some(value) = payload.get(*field) {
+            data.insert((*field).to_string(), value.clone());
+        }
+    }
+    if include_content {
+        for field in spec.content_fields {
+            if let Some(value) = payload.get(*field) {
+                let masked = secrets::mask_value(value).unwrap_or_else(|| value.clone());
+                data.insert((*field).to_string(), masked);
+            }
+        }
+    }
+    Some(SharedEvent {
+        event: spec.name,
+        data: Value::Object(data),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn chat_share_without_content_sends_no_text() {
+        use crate::constants::TauriEvents;
+        use crate::features::share::projection::project;
+        use serde_json::json;
+
+        let chunk = json!({"chat_id": "c", "message_id": "m", "chunk": "secret plan"});
+        assert!(project(TauriEvents::MESSAGE_CHUNK, &chunk, false).is_none());
+        let complete = json!({"chat_id": "c", "message_id": "m", "content": "secret plan", "token_usage": null});
+        let shared = project(TauriEvents::MESSAGE_COMPLETE, &complete, false).unwrap();
+        assert_eq!(shared.data, json!({"message_id": "m", "token_usage": null}));
+        assert!(project(TauriEvents::TOOL_PERMISSION_REQUEST, &chunk, true).is_none());
+    }
+}
@@ -0,0 +1,246 @@
+//! The HTTP server behind one chat share.
+//!
+//! It answers two GET routes, both under the share's secret token: the
+//! viewer page at `/s/<token>` and its server-sent event stream at
+//! `/s/<token>/events`. Everything else, including a wrong token, is a 404,
+//! and nothing a viewer sends reaches the rest of the app.
+
+use super::models::SharedEvent;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, watch};
+use tokio::time::Instant;
+
+/// SSE event sent when the share is stopped or expires.
+pub const SHARE_ENDED_EVENT: &str = "share-ended";
+const VIEWER_PAGE: &str = include_str!("viewer.html");
+const MAX_REQUEST_HEAD_BYTES: usize = 8 * 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// Keeps idle connections from being dropped by proxies and sleeping laptops.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// What a share's connections need, shared by all of them.
+pub struct ShareEndpoint {
+    pub token: String,
+    pub events: broadcast::Sender<SharedEvent>,
+    /// Set to `true` when the share stops
+    pub closed: watch::Receiver<bool>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Route {
+    Viewer,
+    Events,
+    NotFound,
+    MethodNotAllowed,
+}
+
+/// Accept viewers until the share is closed or `deadline` passes.
+pub async fn serve(
+    listener: TcpListener,
+    endpoint: std::sync::Arc<ShareEndpoint>,
+    deadline: Instant,
+) {
+    let mut closed = endpoint.closed.clone();
+    loop {
+        tokio::select! {
+            () = tokio::time::sleep_until(deadline) => break,
+            _ = closed.changed() => break,
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    let endpoint = endpoint.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) = handle_connection(stream, &endpoint).await {
+                            tracing::debug!(error = %e, "Share viewer connection ended");
+                        }
+                    });
+                }
+                Err(e) => tracing::warn!(error = %e, "Share server failed to accept a connection"),
+            },
+        }
+    }
+}
+
+/// The route for an HTTP request line. The token is checked before the
+/// method, so a wrong token tells a caller nothing.
+pub fn route(request_line: &str, token: &str) -> Route {
+    let mut parts = request_line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Route::NotFound;
+    };
+    let path = target.split('?').next().unwrap_or_default();
+    let Some(rest) = path.strip_prefix("/s/") else {
+        return Route::NotFound;
+    };
+    let (given, tail) = rest.split_once('/').unwrap_or((rest, ""));
+    if !tokens_match(given, token) {
+        return Route::NotFound;
+    }
+    if method != "GET" {
+        return Route::MethodNotAllowed;
+    }
+    match tail {
+        "" => Route::Viewer,
+        "events" => Route::Events,
+        _ => Route::NotFound,
+    }
+}
+
+/// Compare without stopping at the first differing byte, so response times
+/// do not reveal how much of a guess was right.
+fn tokens_match(given: &str, token: &str) -> bool {
+    given.len() == token.len()
+        && given
+            .bytes()
+            .zip(token.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+async fn handle_connection(mut stream: TcpStream, endpoint: &ShareEndpoint) -> std::io::Result<()> {
+    let Ok(head) = tokio::time::timeout(REQUEST_TIMEOUT, read_request_head(&mut stream)).await
+    else {
+        return Ok(());
+    };
+    let head = head?;
+    let request_line = head.lines().next().unwrap_or_default();
+
+    match route(request_line, &endpoint.token) {
+        Route::Viewer => {
+            write_response(
+                &mut stream,
+                "200 OK",
+                "text/html; charset=utf-8",
+                VIEWER_PAGE,
+            )
+            .await
+        }
+        Route::Events => stream_events(stream, endpoint).await,
+        Route::MethodNotAllowed => {
+            write_response(
+                &mut stream,
+                "405 Method Not Allowed",
+                "text/plain",
+                "Method not allowed",
+            )
+            .await
+        }
+        Route::NotFound => {
+            write_response(&mut stream, "404 Not Found", "text/plain", "Not found").await
+        }
+    }
+}
+
+/// The request up to the blank line; bodies are never read.
+async fn read_request_head(stream: &mut TcpStream) -> std::io::Result<String> {
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") {
+        let read = stream.read(&mut buf).await?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+        if head.len() > MAX_REQUEST_HEAD_BYTES {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Request head too large",
+            ));
+        }
+    }
+    Ok(String::from_utf8_lossy(&head).into_owned())
+}
+
+async fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &str,
+) -> std::io::Result<()> {
+    let response = format!(
+        "HTTP/1.1 {status}\r\n{}Content-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        security_headers(),
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn stream_events(mut stream: TcpStream, endpoint: &ShareEndpoint) -> std::io::Result<()> {
+    // Subscribe before answering, so no event after the response is missed
+    let mut events = endpoint.events.subscribe();
+    let mut closed = endpoint.closed.clone();
+    let head = format!(
+        "HTTP/1.1 200 OK\r\n{}Content-Type: text/event-stream\r\nConnection: close\r\n\r\n: connected\n\n",
+        security_headers()
+    );
+    stream.write_all(head.as_bytes()).await?;
+
+    let mut heartbeat =
+        tokio::time::interval_at(Instant::now() + HEARTBEAT_INTERVAL, HEARTBEAT_INTERVAL);
+    loop {
+        let frame = tokio::select! {
+            _ = closed.changed() => {
+                stream
+                    .write_all(format!("event: {SHARE_ENDED_EVENT}\ndata: {{}}\n\n").as_bytes())
+                    .await?;
+                break;
+            }
+            received = events.recv() => match received {
+                Ok(event) => format!("event: {}\ndata: {}\n\n", event.event, event.data),
+                // A slow viewer misses events rather than holding up the app
+                Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                    format!(": skipped {skipped} events\n\n")
+                }
+                Err(broadcast::error::RecvError::Closed) => break,
+            },
+            _ = heartbeat.tick() => ": ping\n\n".to_string(),
+        };
+        stream.write_all(frame.as_bytes()).await?;
+    }
+    stream.shutdown().await
+}
+
+/// Keep the token out of referrers and caches, and the page from loading
+/// anything but its own event stream.
+const fn security_headers() -> &'static str {
+    "Cache-Control: no-store\r\nReferrer-Policy: no-referrer\r\nX-Content-Type-Options: nosniff\r\nContent-Security-Policy: default-src 'none'; script-src 'unsafe-inline'; style-src 'unsafe-inline'; connect-src 'self'\r\n"
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn chat_share_routes_only_the_exact_token() {
+        use crate::features::share::server::{route, Route};
+
+        let token = "a".repeat(64);
+        assert_eq!(
+            route(&format!("GET /s/{token} HTTP/1.1"), &token),
+            Route::Viewer
+        );
+        assert_eq!(
+            route(&format!("GET /s/{token}/events?x=1 HTTP/1.1"), &token),
+            Route::Events
+        );
+        for line in [
+            format!("GET /s/{} HTTP/1.1", "b".repeat(64)),
+            format!("GET /s/{}/events HTTP/1.1", &token[..63]),
+            format!("GET /s/{token}a/events HTTP/1.1"),
+            format!("GET /{token}/events HTTP/1.1"),
+            "GET /s/ HTTP/1.1".to_string(),
+            format!("GET /s/{token}/commands HTTP/1.1"),
+            "garbage".to_string(),
+        ] {
+            assert_eq!(route(&line, &token), Route::NotFound, "{line}");
+        }
+        assert_eq!(
+            route(&format!("POST /s/{token}/events HTTP/1.1"), &token),
+            Route::MethodNotAllowed
+        );
+        assert_eq!(
+            route(&format!("POST /s/{} HTTP/1.1", "b".repeat(64)), &token),
+            Route::NotFound
+        );
+    }
+}
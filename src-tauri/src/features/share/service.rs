@@ -0,0 +1,373 @@
+use super::models::{ChatShare, ChatShareOptions, SharedEvent};
+use super::projection;
+use super::server::{self, ShareEndpoint};
+use crate::error::AppError;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast, watch};
+
+pub const DEFAULT_SHARE_MINUTES: u64 = 60;
+pub const MAX_SHARE_MINUTES: u64 = 24 * 60;
+/// Events buffered for each viewer; a viewer further behind skips ahead.
+const SHARE_CHANNEL_CAPACITY: usize = 1024;
+
+struct ActiveShare {
+    share: ChatShare,
+    events: broadcast::Sender<SharedEvent>,
+    closed: watch::Sender<bool>,
+}
+
+/// Read-only live shares of chats, at most one per chat. Every emitted event
+/// is offered to `observe`, which forwards the shared chats' events.
+#[derive(Default)]
+pub struct ShareService {
+    shares: Mutex<HashMap<String, ActiveShare>>,
+    // Shares running, so `observe` costs nothing while there are none
+    active: AtomicUsize,
+}
+
+impl ShareService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start sharing `chat_id`, replacing its current share if any.
+    pub async fn start(
+        self: &Arc<Self>,
+        chat_id: &str,
+        options: ChatShareOptions,
+    ) -> Result<ChatShare, AppError> {
+        let minutes = options.expires_in_minutes.unwrap_or(DEFAULT_SHARE_MINUTES);
+        if !(1..=MAX_SHARE_MINUTES).contains(&minutes) {
+            return Err(AppError::Validation(format!(
+                "A share must expire within 1 to {MAX_SHARE_MINUTES} minutes"
+            )));
+        }
+        self.start_for(
+            chat_id,
+            options.allow_lan,
+            options.include_content,
+            Duration::from_secs(minutes * 60),
+        )
+        .await
+    }
+
+    pub async fn start_for(
+        self: &Arc<Self>,
+        chat_id: &str,
+        allow_lan: bool,
+        include_content: bool,
+        lifetime: Duration,
+    ) -> Result<ChatShare, AppError> {
+        let bind_ip = if allow_lan {
+            Ipv4Addr::UNSPECIFIED
+        } else {
+            Ipv4Addr::LOCALHOST
+        };
+        let listener = TcpListener::bind((bind_ip, 0))
+            .await
+            .map_err(|e| AppError::Generic(format!("Failed to start share server: {e}")))?;
+        let port = listener
+            .local_addr()
+            .map_err(|e| AppError::Generic(format!("Failed to start share server: {e}")))?
+            .port();
+        let host = if allow_lan {
+            lan_address().unwrap_or_else(|| {
+                tracing::warn!("No LAN address found; the share is only reachable locally");
+                IpAddr::V4(Ipv4Addr::LOCALHOST)
+            })
+        } else {
+            IpAddr::V4(Ipv4Addr::LOCALHOST)
+        };
+
+        let id = uuid::Uuid::new_v4().to_string();
+        let token = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let now = chrono::Utc::now().timestamp_millis();
+        let share = ChatShare {
+            id: id.clone(),
+            chat_id: chat_id.to_string(),
+            url: format!("http://{}/s/{token}", SocketAddr::new(host, port)),
+            allow_lan,
+            include_content,
+            started_at: now,
+            expires_at: now + i64::try_from(lifetime.as_millis()).unwrap_or(i64::MAX),
+            viewers: 0,
+        };
+
+        let (events, _) = broadcast::channel(SHARE_CHANNEL_CAPACITY);
+        let (closed, closed_rx) = watch::channel(false);
+        let endpoint = Arc::new(ShareEndpoint {
+            token,
+            events: events.clone(),
+            closed: closed_rx,
+        });
+        let replaced = self.lock_shares()?.insert(
+            chat_id.to_string(),
+            ActiveShare {
+                share: share.clone(),
+                events,
+                closed,
+            },
+        );
+        match replaced {
+            Some(old) => {
+                let _ = old.closed.send(true);
+            }
+            None => {
+                self.active.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let service = Arc::downgrade(self);
+        let chat_id = chat_id.to_string();
+        let deadline = tokio::time::Instant::now() + lifetime;
+        tokio::spawn(async move {
+            server::serve(listener, endpoint, deadline).await;
+            // Expired, or already stopped and this is a no-op
+            if let Some(service) = service.upgrade() {
+                service.remove(&chat_id, Some(&id));
+            }
+        });
+
+        tracing::info!(chat_id = %share.chat_id, allow_lan, include_content, "Started chat share");
+        Ok(share)
+    }
+
+    /// Stop sharing `chat_id`; viewers see the stream end.
+    pub fn stop(&self, chat_id: &str) -> Result<(), AppError> {
+        if self.remove(chat_id, None) {
+            Ok(())
+        } else {
+            Err(AppError::NotFound(format!(
+                "Chat is not being shared: {chat_id}"
+            )))
+        }
+    }
+
+    /// Running shares, with their current viewer counts.
+    pub fn list(&self) -> Vec<ChatShare> {
+        let Ok(shares) = self.shares.lock() else {
+            return Vec::new();
+        };
+        let mut list: Vec<ChatShare> = shares
+            .values()
+            .map(|active| ChatShare {
+                viewers: active.events.receiver_count(),
+                ..active.share.clone()
+            })
+            .collect();
+        list.sort_by_key(|share| share.started_at);
+        list
+    }
+
+    /// Forward an emitted event to the viewers of its chat's share.
+    pub fn observe<E: Serialize>(&self, name: &str, payload: &E) {
+        if self.active.load(Ordering::SeqCst) == 0 || !projection::is_shared_event(name) {
+            return;
+        }
+        let Ok(payload) = serde_json::to_value(payload) else {
+            return;
+        };
+        let Some(chat_id) = projection::event_chat_id(&payload) else {
+            return;
+        };
+        let Ok(shares) = self.shares.lock() else {
+            return;
+        };
+        if let Some(active) = shares.get(chat_id) {
+            if let Some(event) = projection::project(name, &payload, active.share.include_content) {
+                // No receivers just means nobody is watching yet
+                let _ = active.events.send(event);
+            }
+        }
+    }
+
+    /// Remove the share of `chat_id`, only if it is `share_id` when given.
+    fn remove(&self, chat_id: &str, share_id: Option<&str>) -> bool {
+        let Ok(mut shares) = self.shares.lock() else {
+            return false;
+        };
+        if share_id.is_some_and(|id| shares.get(chat_id).is_none_or(|s| s.share.id != id)) {
+            return false;
+        }
+        let Some(active) = shares.remove(chat_id) else {
+            return false;
+        };
+        let _ = active.closed.send(true);
+        self.active.fetch_sub(1, Ordering::SeqCst);
+        tracing::info!(chat_id, "Stopped chat share");
+        true
+    }
+
+    fn lock_shares(&self) -> Result<MutexGuard<'_, HashMap<String, ActiveShare>>, AppError> {
+        self.shares
+            .lock()
+            .map_err(|e| AppError::Generic(format!("Failed to lock chat shares: {e}")))
+    }
+}
+
+/// The address others on the LAN reach this machine at. Connecting a UDP
+/// socket sends nothing; it only picks the interface of the default route.
+fn lan_address() -> Option<IpAddr> {
+    let socket = std::net::UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 9)).ok()?;
+    socket
+        .local_addr()
+        .ok()
+        .map(|addr| addr.ip())
+        .filter(|ip| !ip.is_unspecified() && !ip.is_loopback())
+}
+
+#[cfg(test)]
+mod tests {
+    /// Open a share's event stream over a raw connection, past the headers.
+    async fn open_share_stream(url: &str) -> tokio::net::TcpStream {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let rest = url.strip_prefix("http://").unwrap();
+        let (addr, path) = rest.split_once('/').unwrap();
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(format!("GET /{path}/events HTTP/1.1\r\nHost: {addr}\r\n\r\n").as_bytes())
+            .await
+            .unwrap();
+        let mut received = String::new();
+        let mut buf = [0u8; 1024];
+        while !received.contains(": connected") {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert!(read > 0, "stream closed early: {received}");
+            received.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        assert!(received.starts_with("HTTP/1.1 200 OK"), "{received}");
+        stream
+    }
+
+    /// Read from a share stream until `marker` shows up or it closes.
+    async fn read_share_stream_until(stream: &mut tokio::net::TcpStream, marker: &str) -> String {
+        use tokio::io::AsyncReadExt;
+
+        let mut received = String::new();
+        let mut buf = [0u8; 1024];
+        while !received.contains(marker) {
+            let read =
+                tokio::time::timeout(std::time::Duration::from_secs(5), stream.read(&mut buf))
+                    .await
+                    .expect("timed out waiting for share events")
+                    .unwrap();
+            if read == 0 {
+                break;
+            }
+            received.push_str(&String::from_utf8_lossy(&buf[..read]));
+        }
+        received
+    }
+
+    #[test]
+    fn chat_share_forwards_only_its_own_chat() {
+        use crate::constants::TauriEvents;
+        use crate::events::{MessageChunkEvent, ToolExecutionProgressEvent};
+        use crate::features::share::ShareService;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let service = Arc::new(ShareService::new());
+            let share = service
+                .start_for("chat-a", false, true, Duration::from_secs(60))
+                .await
+                .unwrap();
+            assert!(share.url.starts_with("http://127.0.0.1:"));
+            let mut stream = open_share_stream(&share.url).await;
+
+            let chunk = |chat_id: &str, chunk: &str| MessageChunkEvent {
+                chat_id: chat_id.to_string(),
+                message_id: "m1".to_string(),
+                chunk: chunk.to_string(),
+            };
+            service.observe(TauriEvents::MESSAGE_CHUNK, &chunk("chat-b", "from-b"));
+            service.observe(
+                TauriEvents::TOOL_EXECUTION_PROGRESS,
+                &ToolExecutionProgressEvent {
+                    chat_id: "chat-b".to_string(),
+                    message_id: "m2".to_string(),
+                    tool_call_id: "t1".to_string(),
+                    tool_name: "tool-from-b".to_string(),
+                    status: "executing".to_string(),
+                    result: None,
+                    error: None,
+                },
+            );
+            // Not a shared event, even for the shared chat
+            service.observe(
+                TauriEvents::CHAT_UPDATED,
+                &crate::events::ChatUpdatedEvent {
+                    chat_id: "chat-a".to_string(),
+                    title: "title-of-a".to_string(),
+                },
+            );
+            service.observe(
+                TauriEvents::MESSAGE_CHUNK,
+                &chunk("chat-a", "key sk-live-abcdefghijklmnop1234 done"),
+            );
+            service.observe(TauriEvents::MESSAGE_CHUNK, &chunk("chat-a", "end-of-a"));
+
+            let received = read_share_stream_until(&mut stream, "end-of-a").await;
+            assert!(received.contains("event: message-chunk"), "{received}");
+            assert!(!received.contains("from-b"), "{received}");
+            assert!(!received.contains("tool-from-b"), "{received}");
+            assert!(!received.contains("title-of-a"), "{received}");
+            assert!(
+                !received.contains("chat-a"),
+                "chat ids are not forwarded: {received}"
+            );
+            assert!(
+                !received.contains("sk-live-abcdefghijklmnop1234"),
+                "{received}"
+            );
+            assert_eq!(service.list()[0].viewers, 1);
+        });
+    }
+
+    #[test]
+    fn chat_share_expires_and_closes_its_viewers() {
+        use crate::features::share::ShareService;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let service = Arc::new(ShareService::new());
+            let share = service
+                .start_for("chat-a", false, false, Duration::from_millis(300))
+                .await
+                .unwrap();
+            let mut stream = open_share_stream(&share.url).await;
+
+            let received = read_share_stream_until(&mut stream, "event: share-ended").await;
+            assert!(received.contains("event: share-ended"), "{received}");
+            // The server task removes the share right after it stops serving
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            assert!(service.list().is_empty());
+            assert!(service.stop("chat-a").is_err());
+
+            let addr = share
+                .url
+                .strip_prefix("http://")
+                .unwrap()
+                .split('/')
+                .next()
+                .unwrap();
+            assert!(tokio::net::TcpStream::connect(addr).await.is_err());
+        });
+    }
+}
@@ -0,0 +1,80 @@
+use super::halt::drop_pending;
+use super::models::{SystemCapabilities, SystemStatus};
+use crate::error::AppError;
+use crate::events::{emit_event, GlobalHaltEvent};
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub fn get_system_status(state: State<'_, AppState>) -> Result<SystemStatus, AppError> {
+    let encryption = state.encryption_service.status()?;
+    Ok(SystemStatus {
+        app_version: env!("CARGO_PKG_VERSION"),
+        capabilities: SystemCapabilities {
+            message_search: encryption.message_search_available,
+        },
+        encryption,
+        chat_shares: state.share_service.list(),
+        db_maintenance: state.maintenance_service.last_report()?,
+        db_maintenance_next_run: state.maintenance_service.next_run_at()?.map(|next_run_at| {
+            let now = chrono::Utc::now().timestamp_millis();
+            state
+                .app_settings_service
+                .time_formatter()
+                .format_datetime(next_run_at.max(now))
+        }),
+        semantic_index: state.semantic_index_service.status()?,
+        halted: state.activity_halt.is_halted(),
+    })
+}
+
+/// The kill switch: stop every running turn, its tool calls and waits, and
+/// the background work that calls models, until `resume_activity`.
+#[tauri::command]
+pub async fn panic_stop_all(
+    app: AppHandle,
+    state: State<'_, AppState>,
+) -> Result<GlobalHaltEvent, AppError> {
+    state.activity_halt.halt();
+    let (chats_stopped, tool_executions_stopped) = state.chat_service.halt_all().await;
+    // Dropping the senders ends each wait as cancelled
+    let permission_requests_cancelled =
+        drop_pending(&mut state.pending_tool_permissions.lock().map_err(|e| {
+            AppError::Generic(format!("Failed to lock pending_tool_permissions: {e}"))
+        })?);
+    let (cost_confirmations_cancelled, sampling_requests_cancelled) =
+        state.chat_service.cancel_pending_confirmations()?;
+    let event = GlobalHaltEvent {
+        halted: true,
+        chats_stopped,
+        tool_executions_stopped,
+        permission_requests_cancelled,
+        cost_confirmations_cancelled,
+        sampling_requests_cancelled,
+        outbox_chats_paused: state.outbox_service.pending_chats()?.len(),
+    };
+    tracing::warn!(?event, "All activity halted");
+    emit_event(&app, event.clone())?;
+    Ok(event)
+}
+
+/// Let turns and background work start again after `panic_stop_all`.
+#[tauri::command]
+pub fn resume_activity(app: AppHandle, state: State<'_, AppState>) -> Result<(), AppError> {
+    if state.activity_halt.resume() {
+        tracing::info!("Activity resumed");
+        emit_event(
+            &app,
+            GlobalHaltEvent {
+                halted: false,
+                chats_stopped: 0,
+                tool_executions_stopped: 0,
+                permission_requests_cancelled: 0,
+                cost_confirmations_cancelled: 0,
+                sampling_requests_cancelled: 0,
+                outbox_chats_paused: 0,
+            },
+        )?;
+    }
+    Ok(())
+}
@@ -0,0 +1,154 @@
+//! The kill switch: one command that stops every model and tool call now.
+//!
+//! While halted, new turns fail with `AppError::Halted`, and the background
+//! work that spends on models (outbox resends, keep-warm pings, semantic
+//! indexing) skips its runs. Nothing is torn down: turns that were running
+//! end through their normal cancellation, which keeps the partial answers
+//! and records their usage as cancelled, and MCP clients stay connected.
+
+use crate::error::AppError;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Debug, Default)]
+pub struct ActivityHalt {
+    halted: AtomicBool,
+}
+
+impl ActivityHalt {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Halt activity; false when it already was.
+    pub fn halt(&self) -> bool {
+        !self.halted.swap(true, Ordering::SeqCst)
+    }
+
+    /// Let activity start again; false when it was not halted.
+    pub fn resume(&self) -> bool {
+        self.halted.swap(false, Ordering::SeqCst)
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.halted.load(Ordering::SeqCst)
+    }
+
+    pub fn ensure_running(&self) -> Result<(), AppError> {
+        if self.is_halted() {
+            Err(AppError::Halted)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Signal every chat whose cancellation channel has a listener: its stream,
+/// its tool calls and its waits. Returns how many chats were signalled.
+pub fn cancel_all(senders: &HashMap<String, tokio::sync::broadcast::Sender<()>>) -> usize {
+    senders
+        .values()
+        .filter(|sender| sender.receiver_count() > 0)
+        .filter(|sender| sender.send(()).is_ok())
+        .count()
+}
+
+/// Drop every sender of `pending`, which ends each wait for an answer.
+/// Returns how many there were.
+pub fn drop_pending<T>(pending: &mut HashMap<String, tokio::sync::oneshot::Sender<T>>) -> usize {
+    let count = pending.len();
+    pending.clear();
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+
+    #[test]
+    fn kill_switch_stops_every_stream_tool_and_wait_and_resume_restores() {
+        use crate::features::chat::cost_gate::{CostConfirmationOutcome, CostConfirmations};
+        use crate::features::system::halt::{cancel_all, drop_pending, ActivityHalt};
+        use crate::features::tool::mcp_sampling::{SamplingApprovals, SamplingOutcome};
+        use crate::state::PermissionDecision;
+        use std::collections::HashMap;
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut senders = HashMap::new();
+            let mut running = Vec::new();
+            // Three chats streaming, one running a tool, one idle
+            for chat in ["a", "b", "c", "tool"] {
+                let sender = tokio::sync::broadcast::channel(1).0;
+                let mut cancellation = sender.subscribe();
+                senders.insert(chat.to_string(), sender);
+                running.push(tokio::spawn(async move {
+                    let mut chunks = 0u32;
+                    loop {
+                        tokio::select! {
+                            () = tokio::time::sleep(Duration::from_millis(if chat == "tool" { 60_000 } else { 5 })) => chunks += 1,
+                            _ = cancellation.recv() => return chunks,
+                        }
+                    }
+                }));
+            }
+            senders.insert("idle".to_string(), tokio::sync::broadcast::channel(1).0);
+
+            // One wait for the user of each kind
+            let mut permissions = HashMap::new();
+            let (permission_tx, permission_rx) = tokio::sync::oneshot::channel::<PermissionDecision>();
+            permissions.insert("call".to_string(), permission_tx);
+            let confirmations = Arc::new(CostConfirmations::new());
+            let confirmation = tokio::spawn({
+                let confirmations = confirmations.clone();
+                async move {
+                    let (_turn, mut cancellation) = tokio::sync::broadcast::channel(1);
+                    confirmations
+                        .confirm("m1", Duration::from_secs(60), &mut cancellation, || Ok(()))
+                        .await
+                        .unwrap()
+                }
+            });
+            let approvals = Arc::new(SamplingApprovals::new());
+            let sampling = tokio::spawn({
+                let approvals = approvals.clone();
+                async move {
+                    approvals
+                        .approve("req", Duration::from_secs(60), || Ok(()))
+                        .await
+                        .unwrap()
+                }
+            });
+            tokio::time::sleep(Duration::from_millis(30)).await;
+
+            let halt = ActivityHalt::new();
+            assert!(halt.halt());
+            assert!(!halt.halt());
+            let started = Instant::now();
+            assert_eq!(cancel_all(&senders), 4);
+            for task in running {
+                tokio::time::timeout(Duration::from_secs(1), task)
+                    .await
+                    .expect("a chat kept running")
+                    .unwrap();
+            }
+            assert_eq!(drop_pending(&mut permissions), 1);
+            assert_eq!(confirmations.cancel_all().unwrap(), 1);
+            assert_eq!(approvals.cancel_all().unwrap(), 1);
+            assert!(permission_rx.await.is_err());
+            assert_eq!(
+                confirmation.await.unwrap(),
+                CostConfirmationOutcome::Cancelled
+            );
+            assert_eq!(sampling.await.unwrap(), SamplingOutcome::Denied);
+            assert!(started.elapsed() < Duration::from_secs(1));
+            assert!(matches!(halt.ensure_running(), Err(AppError::Halted)));
+
+            assert!(halt.resume());
+            assert!(halt.ensure_running().is_ok());
+            // Nothing left listening: a second stop signals no chat
+            assert_eq!(cancel_all(&senders), 0);
+        });
+    }
+}
@@ -0,0 +1,6 @@
+pub mod commands;
+pub mod halt;
+pub mod models;
+
+pub use halt::ActivityHalt;
+pub use models::*;
@@ -0,0 +1,30 @@
+use crate::features::encryption::EncryptionStatus;
+use crate::features::maintenance::MaintenanceReport;
+use crate::features::semantic_index::SemanticIndexStatus;
+use crate::features::share::ChatShare;
+use serde::Serialize;
+
+/// App-wide state the settings screen and diagnostics show.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemStatus {
+    pub app_version: &'static str,
+    pub encryption: EncryptionStatus,
+    /// Chats being shared read-only over HTTP
+    pub chat_shares: Vec<ChatShare>,
+    /// Last database maintenance run, manual or scheduled
+    pub db_maintenance: Option<MaintenanceReport>,
+    /// When automatic maintenance runs next, in the display time settings
+    pub db_maintenance_next_run: Option<String>,
+    pub capabilities: SystemCapabilities,
+    /// Model and size of the semantic search index
+    pub semantic_index: SemanticIndexStatus,
+    /// Activity stopped by the kill switch until it is resumed
+    pub halted: bool,
+}
+
+/// Features that can be unavailable depending on other settings.
+#[derive(Debug, Clone, Serialize)]
+pub struct SystemCapabilities {
+    /// Off while message content may be encrypted at rest
+    pub message_search: bool,
+}
@@ -0,0 +1,302 @@
+//! The tools Lunex provides itself, without an MCP server: files, shell
+//! commands and HTTP fetch, run by `InternalToolService`.
+//!
+//! File tools, and the working directory of commands, are kept to the
+//! directories `BuiltinToolSettings` allows, and refused while it lists
+//! none. Tools that change things or reach the network wait for the user's
+//! approval unless the workspace permission config names them; shell
+//! commands, which can reach any path whatever their working directory,
+//! wait for it every time. `fetch_url` only reaches public addresses.
+
+use crate::error::AppError;
+use crate::features::chat::tool_permissions::PERMISSION_REQUIRE;
+use serde::{Deserialize, Serialize};
+use std::net::IpAddr;
+use std::path::{Component, Path, PathBuf};
+use std::time::Duration;
+
+/// App setting holding `BuiltinToolSettings` as JSON.
+pub const BUILTIN_TOOL_SETTINGS_KEY: &str = "builtinToolSettings";
+
+/// Builtin tools that ask before they run when the workspace permission
+/// config does not name them.
+pub const CONFIRMED_BUILTIN_TOOLS: &[&str] = &["write_file", "run_command", "fetch_url"];
+
+/// Builtin tools that ask before every run: neither the permission config
+/// nor a remembered answer can set them to "auto".
+pub const ALWAYS_CONFIRMED_BUILTIN_TOOLS: &[&str] = &["run_command"];
+
+/// Most of a response body `fetch_url` returns.
+pub const MAX_FETCH_BYTES: usize = 512 * 1024;
+
+pub const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Redirects `fetch_url` follows, each hop checked like the first URL.
+pub const MAX_FETCH_REDIRECTS: usize = 5;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuiltinToolSettings {
+    /// Absolute directories the file tools may reach; empty allows none
+    #[serde(default)]
+    pub allowed_dirs: Vec<String>,
+}
+
+impl BuiltinToolSettings {
+    pub fn validate(&self) -> Result<(), AppError> {
+        for dir in &self.allowed_dirs {
+            if !Path::new(dir).is_absolute() {
+                return Err(AppError::Validation(format!(
+                    "Allowed directory must be an absolute path: {dir}"
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// `path`, once it is absolute and inside an allowed directory. Links
+    /// are followed as far as the path exists, so none leads out.
+    pub fn check_path(&self, path: &str) -> Result<PathBuf, AppError> {
+        let path_buf = PathBuf::from(path);
+        if !path_buf.is_absolute() {
+            return Err(AppError::Validation(format!(
+                "Path must be absolute: {path}"
+            )));
+        }
+        if self.allowed_dirs.is_empty() {
+            return Err(AppError::Validation(format!(
+                "Cannot reach {path}: no directory is allowed for the file tools yet; add one in the builtin tool settings"
+            )));
+        }
+
+        let resolved = resolve(&path_buf);
+        if self
+            .allowed_dirs
+            .iter()
+            .any(|dir| resolved.starts_with(resolve(Path::new(dir))))
+        {
+            Ok(resolved)
+        } else {
+            Err(AppError::Validation(format!(
+                "Path {path} is outside the allowed directories: {}",
+                self.allowed_dirs.join(", ")
+            )))
+        }
+    }
+}
+
+/// `path` with `.` and `..` taken out, without touching the file system.
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// `path` normalized, with links resolved as far as it exists.
+fn resolve(path: &Path) -> PathBuf {
+    let normalized = normalize(path);
+    let mut existing = normalized.as_path();
+    let mut missing = Vec::new();
+    loop {
+        if let Ok(canonical) = existing.canonicalize() {
+            return missing
+                .iter()
+                .rev()
+                .fold(canonical, |resolved, name| resolved.join(name));
+        }
+        match (existing.parent(), existing.file_name()) {
+            (Some(parent), Some(name)) => {
+                missing.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => return normalized,
+        }
+    }
+}
+
+/// Whether `ip` is a public address `fetch_url` may connect to: not
+/// loopback, private, link-local, shared, documentation, multicast or
+/// unspecified, in IPv4 or IPv6, nor an IPv4 one of those mapped to IPv6.
+pub const fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [first, second, ..] = ip.octets();
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || ip.is_unspecified()
+                || first == 0
+                // Shared address space (carrier-grade NAT), 100.64.0.0/10
+                || (first == 100 && (second & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(mapped) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(mapped));
+            }
+            let first = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // Unique local, fc00::/7
+                || (first & 0xfe00) == 0xfc00
+                // Link-local, fe80::/10
+                || (first & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+/// Whether `tool_name` asks before every run, whatever its permission.
+pub fn always_confirmed(tool_name: &str) -> bool {
+    ALWAYS_CONFIRMED_BUILTIN_TOOLS.contains(&tool_name)
+}
+
+/// The permission `tool_name` has when the permission config does not
+/// name it.
+pub fn default_permission(tool_name: &str) -> Option<&'static str> {
+    CONFIRMED_BUILTIN_TOOLS
+        .contains(&tool_name)
+        .then_some(PERMISSION_REQUIRE)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::tool_call;
+
+    #[test]
+    fn builtin_tools_stay_in_allowed_dirs_and_ask_before_changing_things() {
+        use crate::error::AppError;
+        use crate::features::chat::tool_permissions::partition_by_permission;
+        use crate::features::tool::builtin::{
+            default_permission, normalize, BuiltinToolSettings, CONFIRMED_BUILTIN_TOOLS,
+        };
+        use std::path::{Path, PathBuf};
+
+        assert_eq!(
+            normalize(Path::new("/home/user/./project/../notes/a.txt")),
+            PathBuf::from("/home/user/notes/a.txt")
+        );
+
+        // No allowed directories: no path at all
+        let unset = BuiltinToolSettings::default();
+        for path in ["/etc/hosts", "notes/a.txt"] {
+            assert!(matches!(
+                unset.check_path(path),
+                Err(AppError::Validation(_))
+            ));
+        }
+
+        let root = tempfile::tempdir().unwrap();
+        let allowed = root.path().join("project");
+        std::fs::create_dir_all(allowed.join("src")).unwrap();
+        let settings = BuiltinToolSettings {
+            allowed_dirs: vec![allowed.to_string_lossy().into_owned()],
+        };
+        settings.validate().unwrap();
+        let inside = settings
+            .check_path(&allowed.join("src/new.rs").to_string_lossy())
+            .unwrap();
+        assert!(inside.ends_with("project/src/new.rs"));
+        for outside in [
+            root.path().join("secrets.txt"),
+            allowed.join("../secrets.txt"),
+            allowed.join("src/../../project-other/a.txt"),
+        ] {
+            assert!(matches!(
+                settings.check_path(&outside.to_string_lossy()),
+                Err(AppError::Validation(_))
+            ));
+        }
+        assert!(settings.check_path("project/src/new.rs").is_err());
+        assert!(BuiltinToolSettings {
+            allowed_dirs: vec!["project".to_string()],
+        }
+        .validate()
+        .is_err());
+
+        // Tools that change things or go online ask unless the config says so
+        assert_eq!(default_permission("write_file"), Some("require"));
+        assert_eq!(default_permission("read_file"), None);
+        let batch: Vec<_> = ["read_file", "list_dir"]
+            .iter()
+            .chain(CONFIRMED_BUILTIN_TOOLS)
+            .enumerate()
+            .map(|(i, name)| tool_call(&i.to_string(), name))
+            .collect();
+        let names = |calls: &[crate::models::llm_types::ToolCall]| {
+            calls
+                .iter()
+                .map(|tc| tc.function.name.clone())
+                .collect::<Vec<_>>()
+        };
+        let (auto, gated) = partition_by_permission(&batch, None);
+        assert_eq!(names(&auto), ["read_file", "list_dir"]);
+        assert_eq!(names(&gated), CONFIRMED_BUILTIN_TOOLS);
+        let (auto, gated) = partition_by_permission(
+            &batch,
+            Some(r#"{"fetch_url": "auto", "read_file": "require"}"#),
+        );
+        assert_eq!(names(&auto), ["list_dir", "fetch_url"]);
+        assert_eq!(names(&gated), ["read_file", "write_file", "run_command"]);
+        // Shell commands ask every time, whatever the config says
+        let (auto, gated) = partition_by_permission(&batch, Some(r#"{"run_command": "auto"}"#));
+        assert_eq!(names(&auto), ["read_file", "list_dir"]);
+        assert_eq!(names(&gated), CONFIRMED_BUILTIN_TOOLS);
+    }
+
+    #[test]
+    fn fetch_url_reaches_only_public_addresses() {
+        use crate::error::AppError;
+        use crate::features::tool::builtin::is_public_ip;
+        use crate::features::tool::internal::InternalToolService;
+        use std::net::IpAddr;
+
+        for public in ["93.184.216.34", "8.8.8.8", "2606:2800:220:1::1"] {
+            assert!(is_public_ip(public.parse::<IpAddr>().unwrap()), "{public}");
+        }
+        for private in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "224.0.0.1",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:192.168.1.1",
+        ] {
+            assert!(
+                !is_public_ip(private.parse::<IpAddr>().unwrap()),
+                "{private}"
+            );
+        }
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        for url in [
+            "http://127.0.0.1:1/",
+            "http://[::1]:1/",
+            "http://localhost:1/",
+            "http://169.254.169.254/latest/meta-data/",
+        ] {
+            let fetched = runtime.block_on(InternalToolService::fetch_url(
+                serde_json::json!({ "url": url }),
+            ));
+            assert!(matches!(fetched, Err(AppError::Validation(_))), "{url}");
+        }
+    }
+}
@@ -1,7 +1,13 @@
 use super::mcp_client::MCPClientService;
-use super::models::MCPTool;
+use super::mcp_resources::AttachedResource;
+use super::models::{
+    MCPPrompt, MCPPromptResult, MCPResource, MCPResourceContent, MCPResourceTemplate, MCPTool,
+    ToolOverride, WorkspaceToolInfo,
+};
 use crate::error::AppError;
+use crate::features::mcp_connection::validation;
 use crate::state::mcp_client_state::MCPClientState;
+use std::collections::HashMap;
 use tauri::State;
 
 #[tauri::command]
@@ -10,9 +16,11 @@ pub async fn test_mcp_connection_and_fetch_tools(
     url: String,
     r#type: String,
     headers: Option<String>,
+    auth_token: Option<String>,
     env_vars: Option<String>,
     runtime_path: Option<String>,
 ) -> Result<Vec<MCPTool>, AppError> {
+    let headers = validation::auth_headers(&r#type, headers.as_deref(), auth_token.as_deref());
     MCPClientService::test_connection_and_fetch_tools(
         &app,
         url,
@@ -31,9 +39,11 @@ pub async fn connect_mcp_server_and_fetch_tools(
     url: String,
     r#type: String,
     headers: Option<String>,
+    auth_token: Option<String>,
     env_vars: Option<String>,
     runtime_path: Option<String>,
 ) -> Result<Vec<MCPTool>, AppError> {
+    let headers = validation::auth_headers(&r#type, headers.as_deref(), auth_token.as_deref());
     // This is the same logic as test_mcp_connection_and_fetch_tools
     // but we'll use it for automatic connection when saving
     MCPClientService::test_connection_and_fetch_tools(
@@ -54,10 +64,12 @@ pub async fn get_mcp_client(
     url: String,
     r#type: String,
     headers: Option<String>,
+    auth_token: Option<String>,
     env_vars: Option<String>,
     runtime_path: Option<String>,
     state: State<'_, MCPClientState>,
 ) -> Result<(), AppError> {
+    let headers = validation::auth_headers(&r#type, headers.as_deref(), auth_token.as_deref());
     let mut connection_info = state.connection_info.lock().await;
 
     // Store connection info for later use
@@ -128,3 +140,128 @@ pub fn get_active_tools_for_workspace(
         .get_active_tools_info_for_workspace(&workspace_id)
         .map_err(|e| AppError::Generic(e.to_string()))
 }
+
+/// The MCP tools a workspace selected, with how it shows each to the model.
+#[tauri::command]
+pub fn get_workspace_tools(
+    workspace_id: String,
+    state: State<'_, crate::state::AppState>,
+) -> Result<Vec<WorkspaceToolInfo>, AppError> {
+    state.tool_service.get_workspace_tools(&workspace_id)
+}
+
+/// Hide, rename or redescribe one of a workspace's MCP tools; an override
+/// that changes nothing is removed.
+#[tauri::command]
+pub fn set_workspace_tool_override(
+    workspace_id: String,
+    tool_override: ToolOverride,
+    state: State<'_, crate::state::AppState>,
+) -> Result<ToolOverride, AppError> {
+    state
+        .tool_service
+        .set_tool_override(&workspace_id, tool_override)
+}
+
+/// Resources the server of a connected MCP connection lists.
+#[tauri::command]
+pub async fn list_mcp_resources(
+    connection_id: String,
+    state: State<'_, crate::state::AppState>,
+) -> Result<Vec<MCPResource>, AppError> {
+    state.tool_service.list_resources(&connection_id).await
+}
+
+/// Resource templates the server of a connected MCP connection offers.
+#[tauri::command]
+pub async fn list_mcp_resource_templates(
+    connection_id: String,
+    state: State<'_, crate::state::AppState>,
+) -> Result<Vec<MCPResourceTemplate>, AppError> {
+    state
+        .tool_service
+        .list_resource_templates(&connection_id)
+        .await
+}
+
+#[tauri::command]
+pub async fn read_mcp_resource(
+    connection_id: String,
+    uri: String,
+    state: State<'_, crate::state::AppState>,
+) -> Result<Vec<MCPResourceContent>, AppError> {
+    state.tool_service.read_resource(&connection_id, uri).await
+}
+
+/// Read a resource into an entry for the `mcpResources` list of the
+/// metadata of a message about to be sent; the model gets its text with the
+/// message.
+#[tauri::command]
+pub async fn attach_mcp_resource(
+    connection_id: String,
+    uri: String,
+    name: Option<String>,
+    state: State<'_, crate::state::AppState>,
+) -> Result<AttachedResource, AppError> {
+    state
+        .tool_service
+        .attach_resource(&connection_id, uri, name)
+        .await
+}
+
+/// Prompts the server of a connected MCP connection offers.
+#[tauri::command]
+pub async fn list_mcp_prompts(
+    connection_id: String,
+    state: State<'_, crate::state::AppState>,
+) -> Result<Vec<MCPPrompt>, AppError> {
+    state.tool_service.list_prompts(&connection_id).await
+}
+
+/// A server prompt filled in with `arguments`; its `text` goes in the
+/// message input.
+#[tauri::command]
+pub async fn get_mcp_prompt(
+    connection_id: String,
+    name: String,
+    arguments: Option<HashMap<String, String>>,
+    state: State<'_, crate::state::AppState>,
+) -> Result<MCPPromptResult, AppError> {
+    state
+        .tool_service
+        .get_prompt(&connection_id, name, arguments.unwrap_or_default())
+        .await
+}
+
+/// Fill in a server prompt and make it the chat's custom instructions,
+/// replacing those it had.
+#[tauri::command]
+pub async fn apply_mcp_prompt_to_chat(
+    chat_id: String,
+    connection_id: String,
+    name: String,
+    arguments: Option<HashMap<String, String>>,
+    state: State<'_, crate::state::AppState>,
+) -> Result<MCPPromptResult, AppError> {
+    let prompt = state
+        .tool_service
+        .get_prompt(&connection_id, name, arguments.unwrap_or_default())
+        .await?;
+    state
+        .chat_service
+        .set_instructions(&chat_id, Some(&prompt.text))?;
+    Ok(prompt)
+}
+
+/// Approve or decline a completion an MCP server asked for, announced by an
+/// `mcp-sampling-request` event.
+#[tauri::command]
+pub fn respond_mcp_sampling(
+    request_id: String,
+    approved: bool,
+    state: State<'_, crate::state::AppState>,
+) -> Result<(), AppError> {
+    state
+        .chat_service
+        .respond_mcp_sampling(&request_id, approved)
+}
@@ -1,6 +1,9 @@
+use super::builtin::{
+    is_public_ip, BuiltinToolSettings, FETCH_TIMEOUT, MAX_FETCH_BYTES, MAX_FETCH_REDIRECTS,
+};
 use crate::error::AppError;
 use serde_json::{json, Value};
-use std::path::PathBuf;
+use std::net::{IpAddr, SocketAddr};
 use tokio::fs;
 use tokio::process::Command;
 
@@ -10,24 +13,15 @@ use tauri::AppHandle;
 pub struct InternalToolService;
 
 impl InternalToolService {
-    /// Ensure the path is absolute
-    fn ensure_absolute(path: &str) -> Result<PathBuf, AppError> {
-        let path_buf = PathBuf::from(path);
-        if !path_buf.is_absolute() {
-            return Err(AppError::Validation(format!(
-                "Path must be absolute: {}",
-                path
-            )));
-        }
-        Ok(path_buf)
-    }
-
-    pub async fn read_file(arguments: Value) -> Result<Value, AppError> {
+    pub async fn read_file(
+        arguments: Value,
+        settings: &BuiltinToolSettings,
+    ) -> Result<Value, AppError> {
         let path_str = arguments["path"]
             .as_str()
             .ok_or_else(|| AppError::Validation("Missing 'path' parameter".to_string()))?;
 
-        let path = Self::ensure_absolute(path_str)?;
+        let path = settings.check_path(path_str)?;
 
         let content = fs::read_to_string(&path).await.map_err(|e| {
             AppError::Generic(format!("Cannot read file {}: {}", path.display(), e))
@@ -36,7 +30,10 @@ impl InternalToolService {
         Ok(json!({ "content": content }))
     }
 
-    pub async fn write_file(arguments: Value) -> Result<Value, AppError> {
+    pub async fn write_file(
+        arguments: Value,
+        settings: &BuiltinToolSettings,
+    ) -> Result<Value, AppError> {
         let path_str = arguments["path"]
             .as_str()
             .ok_or_else(|| AppError::Validation("Missing 'path' parameter".to_string()))?;
@@ -44,7 +41,7 @@ impl InternalToolService {
             .as_str()
             .ok_or_else(|| AppError::Validation("Missing 'content' parameter".to_string()))?;
 
-        let path = Self::ensure_absolute(path_str)?;
+        let path = settings.check_path(path_str)?;
 
         // Create parent directories if they don't exist
         if let Some(parent) = path.parent() {
@@ -64,12 +61,15 @@ impl InternalToolService {
         Ok(json!({ "status": "success", "path": path_str }))
     }
 
-    pub async fn list_dir(arguments: Value) -> Result<Value, AppError> {
+    pub async fn list_dir(
+        arguments: Value,
+        settings: &BuiltinToolSettings,
+    ) -> Result<Value, AppError> {
         let path_str = arguments["path"]
             .as_str()
             .ok_or_else(|| AppError::Validation("Missing 'path' parameter".to_string()))?;
 
-        let path = Self::ensure_absolute(path_str)?;
+        let path = settings.check_path(path_str)?;
 
         let mut entries = Vec::new();
         let mut read_dir = fs::read_dir(&path).await.map_err(|e| {
@@ -92,7 +92,11 @@ impl InternalToolService {
         Ok(json!({ "entries": entries }))
     }
 
-    pub async fn run_command(arguments: Value, app: &AppHandle) -> Result<Value, AppError> {
+    pub async fn run_command(
+        arguments: Value,
+        settings: &BuiltinToolSettings,
+        app: &AppHandle,
+    ) -> Result<Value, AppError> {
         let command = arguments["command"]
             .as_str()
             .ok_or_else(|| AppError::Validation("Missing 'command' parameter".to_string()))?;
@@ -125,7 +129,7 @@ impl InternalToolService {
 
         // Set CWD to provided value or system temp dir
         let cwd = match cwd_str {
-            Some(path) => Self::ensure_absolute(path)?,
+            Some(path) => settings.check_path(path)?,
             None => std::env::temp_dir(),
         };
         cmd.current_dir(cwd);
@@ -146,4 +150,111 @@ impl InternalToolService {
             "exit_code": output.status.code()
         }))
     }
+
+    /// GET an http(s) URL on a public address. Redirects are followed up
+    /// to `MAX_FETCH_REDIRECTS` times, each checked like the first URL. The
+    /// body comes back as text, cut off after `MAX_FETCH_BYTES`.
+    pub async fn fetch_url(arguments: Value) -> Result<Value, AppError> {
+        let url_str = arguments["url"]
+            .as_str()
+            .ok_or_else(|| AppError::Validation("Missing 'url' parameter".to_string()))?;
+        let mut url = reqwest::Url::parse(url_str)
+            .map_err(|e| AppError::Validation(format!("Invalid URL {url_str}: {e}")))?;
+
+        let mut redirects = 0;
+        let mut response = loop {
+            let response = public_client(&url)
+                .await?
+                .get(url.clone())
+                .header(reqwest::header::USER_AGENT, "Lunex")
+                .send()
+                .await
+                .map_err(|e| AppError::Generic(format!("Cannot fetch {url}: {e}")))?;
+            let location = response
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|value| value.to_str().ok());
+            match location {
+                Some(location) if response.status().is_redirection() => {
+                    if redirects == MAX_FETCH_REDIRECTS {
+                        return Err(AppError::Generic(format!(
+                            "Cannot fetch {url_str}: more than {MAX_FETCH_REDIRECTS} redirects"
+                        )));
+                    }
+                    redirects += 1;
+                    url = url.join(location).map_err(|e| {
+                        AppError::Generic(format!("Invalid redirect from {url}: {e}"))
+                    })?;
+                }
+                _ => break response,
+            }
+        };
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string);
+
+        let mut body = Vec::new();
+        let mut truncated = false;
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| AppError::Generic(format!("Error reading {url_str}: {e}")))?
+        {
+            let room = MAX_FETCH_BYTES - body.len();
+            if chunk.len() > room {
+                body.extend_from_slice(&chunk[..room]);
+                truncated = true;
+                break;
+            }
+            body.extend_from_slice(&chunk);
+        }
+
+        Ok(json!({
+            "status": status,
+            "content_type": content_type,
+            "content": String::from_utf8_lossy(&body),
+            "truncated": truncated
+        }))
+    }
+}
+
+/// A client for one request to `url`, which must be http(s) on a public
+/// address. It connects to the addresses checked here, so a second lookup
+/// cannot swap in a private one, and leaves redirects to the caller.
+async fn public_client(url: &reqwest::Url) -> Result<reqwest::Client, AppError> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(AppError::Validation(format!(
+            "Only http and https URLs can be fetched: {url}"
+        )));
+    }
+    let host = url
+        .host_str()
+        .ok_or_else(|| AppError::Validation(format!("URL has no host: {url}")))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let addrs: Vec<SocketAddr> = match host.trim_matches(['[', ']']).parse::<IpAddr>() {
+        Ok(ip) => vec![SocketAddr::new(ip, port)],
+        Err(_) => tokio::net::lookup_host((host, port))
+            .await
+            .map_err(|e| AppError::Generic(format!("Cannot resolve {host}: {e}")))?
+            .collect(),
+    };
+    if addrs.is_empty() {
+        return Err(AppError::Generic(format!("Cannot resolve {host}")));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(AppError::Validation(format!(
+            "Cannot fetch {url}: {} is not a public address",
+            addr.ip()
+        )));
+    }
+
+    reqwest::Client::builder()
+        .timeout(FETCH_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .resolve_to_addrs(host, &addrs)
+        .build()
+        .map_err(|e| AppError::Generic(format!("Cannot create HTTP client: {e}")))
 }
@@ -0,0 +1,203 @@
+//! Connecting the MCP servers flagged `auto_connect` when the app starts.
+//!
+//! They are connected together in the background, so the window opens
+//! without waiting for them, the way a manual connect does it: marked
+//! "connecting", their tools fetched, then marked "connected" with them or
+//! "disconnected" with the error. Each step is reported with an
+//! `mcp-auto-connect-progress` event.
+
+use super::mcp_client::MCPClientService;
+use super::models::MCPTool;
+use crate::error::AppError;
+use crate::events::McpAutoConnectProgressEvent;
+use crate::features::mcp_connection::{MCPConnectionService, MCPServerConnection};
+use crate::state::AppState;
+use futures::future::join_all;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::{AppHandle, Manager};
+
+/// How connecting a server ended, as stored on the connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectOutcome {
+    pub status: &'static str,
+    pub tools_json: Option<String>,
+    pub tool_count: Option<usize>,
+    pub error: Option<String>,
+}
+
+impl ConnectOutcome {
+    pub fn from_result(result: Result<Vec<MCPTool>, AppError>) -> Self {
+        let tools = result.and_then(|tools| {
+            let json = serde_json::to_string(&tools)
+                .map_err(|e| AppError::Generic(format!("Failed to serialize tools: {e}")))?;
+            Ok((tools.len(), json))
+        });
+        match tools {
+            Ok((count, json)) => Self {
+                status: "connected",
+                tools_json: Some(json),
+                tool_count: Some(count),
+                error: None,
+            },
+            Err(e) => Self {
+                status: "disconnected",
+                tools_json: None,
+                tool_count: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+/// Connect the flagged servers in the background.
+pub fn start(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = connect_flagged(&app).await {
+            tracing::error!("Failed to auto-connect MCP servers: {e}");
+        }
+    });
+}
+
+async fn connect_flagged(app: &AppHandle) -> Result<(), AppError> {
+    let service = app.state::<AppState>().mcp_connection_service.clone();
+    let connections: Vec<MCPServerConnection> = service
+        .get_all()?
+        .into_iter()
+        .filter(|connection| connection.auto_connect)
+        .collect();
+    let total = connections.len();
+    if total == 0 {
+        return Ok(());
+    }
+    tracing::info!("Auto-connecting {total} MCP server(s)");
+
+    for connection in &connections {
+        service.update_status(connection.id.clone(), "connecting".to_string(), None, None)?;
+        report(app, connection, "connecting", None, None, 0, total);
+    }
+
+    let completed = AtomicUsize::new(0);
+    join_all(
+        connections
+            .iter()
+            .map(|connection| connect_one(app, &service, connection, &completed, total)),
+    )
+    .await;
+    Ok(())
+}
+
+async fn connect_one(
+    app: &AppHandle,
+    service: &MCPConnectionService,
+    connection: &MCPServerConnection,
+    completed: &AtomicUsize,
+    total: usize,
+) {
+    let result = MCPClientService::test_connection_and_fetch_tools(
+        app,
+        connection.url.clone(),
+        connection.r#type.clone(),
+        connection.request_headers(),
+        connection.env_vars.clone(),
+        connection.runtime_path.clone(),
+    )
+    .await;
+    let outcome = ConnectOutcome::from_result(result);
+    if let Some(error) = &outcome.error {
+        tracing::warn!(
+            "Failed to auto-connect MCP server {} ({}): {error}",
+            connection.id,
+            connection.name
+        );
+    }
+
+    if let Err(e) = service.update_status(
+        connection.id.clone(),
+        outcome.status.to_string(),
+        outcome.tools_json,
+        outcome.error.clone(),
+    ) {
+        tracing::error!(
+            "Failed to store status of MCP connection {}: {e}",
+            connection.id
+        );
+    }
+    let completed = completed.fetch_add(1, Ordering::SeqCst) + 1;
+    report(
+        app,
+        connection,
+        outcome.status,
+        outcome.error,
+        outcome.tool_count,
+        completed,
+        total,
+    );
+}
+
+fn report(
+    app: &AppHandle,
+    connection: &MCPServerConnection,
+    status: &str,
+    error: Option<String>,
+    tool_count: Option<usize>,
+    completed: usize,
+    total: usize,
+) {
+    let event = McpAutoConnectProgressEvent {
+        connection_id: connection.id.clone(),
+        server_name: connection.name.clone(),
+        status: status.to_string(),
+        error,
+        tool_count,
+        completed,
+        total,
+    };
+    if let Err(e) = crate::events::emit_event(app, event) {
+        tracing::warn!("Failed to emit MCP auto-connect progress: {e}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+
+    #[test]
+    fn mcp_servers_flagged_to_auto_connect_store_how_connecting_ended() {
+        use crate::features::tool::mcp_autostart::ConnectOutcome;
+        use crate::features::tool::models::MCPTool;
+
+        let tools = vec![MCPTool {
+            name: "search".to_string(),
+            description: Some("Search the web".to_string()),
+            input_schema: None,
+        }];
+        let connected = ConnectOutcome::from_result(Ok(tools));
+        assert_eq!(connected.status, "connected");
+        assert_eq!(connected.tool_count, Some(1));
+        assert!(connected.tools_json.unwrap().contains("\"search\""));
+        assert_eq!(connected.error, None);
+
+        let failed = ConnectOutcome::from_result(Err(AppError::Mcp("spawn failed".to_string())));
+        assert_eq!(failed.status, "disconnected");
+        assert_eq!(failed.tools_json, None);
+        assert!(failed.error.unwrap().contains("spawn failed"));
+
+        // Connections saved before the flag existed stay manual
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO mcp_server_connections (id, name, url, type, headers, status, created_at, updated_at)
+             VALUES ('m1', 'Search', 'npx search', 'stdio', '{}', 'disconnected', 0, 0)",
+            [],
+        )
+        .unwrap();
+        let auto_connect: bool = conn
+            .query_row(
+                "SELECT auto_connect FROM mcp_server_connections WHERE id = 'm1'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!auto_connect);
+    }
+}
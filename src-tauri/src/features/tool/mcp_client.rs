@@ -1,13 +1,20 @@
-use super::models::MCPTool;
+use super::mcp_prompts;
+use super::models::{
+    MCPPrompt, MCPPromptArgument, MCPPromptResult, MCPResource, MCPResourceContent,
+    MCPResourceTemplate, MCPTool,
+};
 use crate::error::AppError;
 use crate::features::addon::models::AddonIndex;
+use crate::features::mcp_connection::{validation, MCPServerConnection};
 use crate::features::runtime::node::service::NodeRuntime;
 use crate::features::runtime::python::service::PythonRuntime;
 use rust_mcp_sdk::{
     mcp_client::{client_runtime, ClientHandler, ClientRuntime},
     schema::{
-        CallToolRequestParams, ClientCapabilities, Implementation, InitializeRequestParams,
-        LATEST_PROTOCOL_VERSION,
+        CallToolRequestParams, ClientCapabilities, CreateMessageRequest, CreateMessageResult,
+        GetPromptRequestParams, Implementation, InitializeRequestParams, ListPromptsRequestParams,
+        ListResourceTemplatesRequestParams, ListResourcesRequestParams, ReadResourceRequestParams,
+        ReadResourceResultContentsItem, RpcError, LATEST_PROTOCOL_VERSION,
     },
     McpClient,
 };
@@ -17,14 +24,70 @@ use rust_mcp_transport::{
 };
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tauri::{AppHandle, Manager};
 
-// Simple client handler - we don't need to handle any server messages for this use case
-struct SimpleClientHandler;
+/// Pages of a resource or prompt listing fetched before giving up on a
+/// server that keeps returning a cursor.
+const MAX_LIST_PAGES: usize = 50;
+
+/// Answers the requests servers send the client. Only a client started for
+/// a saved connection offers sampling, since its completions are approved,
+/// and their usage recorded, under that connection.
+#[derive(Default)]
+struct LunexClientHandler {
+    sampling: Option<SamplingContext>,
+}
+
+struct SamplingContext {
+    app: AppHandle,
+    connection_id: String,
+    server_name: String,
+}
+
+impl LunexClientHandler {
+    fn for_connection(app: &AppHandle, connection: &MCPServerConnection) -> Self {
+        Self {
+            sampling: Some(SamplingContext {
+                app: app.clone(),
+                connection_id: connection.id.clone(),
+                server_name: connection.name.clone(),
+            }),
+        }
+    }
+}
 
 #[async_trait::async_trait]
-impl ClientHandler for SimpleClientHandler {
-    // Empty implementation - we only need to list tools, not handle server messages
+impl ClientHandler for LunexClientHandler {
+    async fn handle_create_message_request(
+        &self,
+        request: CreateMessageRequest,
+        _runtime: &dyn McpClient,
+    ) -> Result<CreateMessageResult, RpcError> {
+        let Some(sampling) = &self.sampling else {
+            return Err(RpcError::method_not_found()
+                .with_message("Sampling is not offered on this connection".to_string()));
+        };
+        let state = sampling.app.state::<crate::state::AppState>();
+        state
+            .chat_service
+            .sample_for_mcp(
+                &sampling.app,
+                &sampling.connection_id,
+                &sampling.server_name,
+                request.params,
+            )
+            .await
+            .map_err(|e| match e {
+                // The code the protocol's examples use for a declined request
+                AppError::Cancelled => RpcError {
+                    code: -1,
+                    message: "User rejected sampling request".to_string(),
+                    data: None,
+                },
+                e => RpcError::internal_error().with_message(e.to_string()),
+            })
+    }
 }
 
 pub struct MCPClientService;
@@ -50,10 +113,28 @@ impl MCPClientService {
         None
     }
 
-    /// Create client details for MCP initialization
-    fn create_client_details() -> InitializeRequestParams {
+    /// Fill `${NAME}` and `{{NAME}}` placeholders in values from the app's
+    /// environment; those it does not set stay as written.
+    fn fill_placeholders(map: Option<HashMap<String, String>>) -> Option<HashMap<String, String>> {
+        map.map(|map| {
+            map.into_iter()
+                .map(|(key, value)| {
+                    let filled =
+                        validation::expand_placeholders(&value, |name| std::env::var(name).ok());
+                    (key, filled)
+                })
+                .collect()
+        })
+    }
+
+    /// Create client details for MCP initialization, offering sampling when
+    /// `sampling` is set.
+    fn create_client_details(sampling: bool) -> InitializeRequestParams {
         InitializeRequestParams {
-            capabilities: ClientCapabilities::default(),
+            capabilities: ClientCapabilities {
+                sampling: sampling.then(serde_json::Map::new),
+                ..ClientCapabilities::default()
+            },
             client_info: Implementation {
                 name: "lunex".to_string(),
                 title: None,
@@ -71,6 +152,27 @@ impl MCPClientService {
         headers: Option<String>,
         env_vars_json: Option<String>,
         runtime_path: Option<String>,
+    ) -> Result<Arc<ClientRuntime>, AppError> {
+        Self::start_client(
+            app,
+            url,
+            r#type,
+            headers,
+            env_vars_json,
+            runtime_path,
+            LunexClientHandler::default(),
+        )
+        .await
+    }
+
+    async fn start_client(
+        app: &AppHandle,
+        url: String,
+        r#type: String,
+        headers: Option<String>,
+        env_vars_json: Option<String>,
+        runtime_path: Option<String>,
+        handler: LunexClientHandler,
     ) -> Result<Arc<ClientRuntime>, AppError> {
         // Validate transport type
         if r#type != "sse"
@@ -83,16 +185,15 @@ impl MCPClientService {
             )));
         }
 
-        let custom_headers = Self::parse_json_map(&headers);
-        let mut env_vars = Self::parse_json_map(&env_vars_json);
+        let custom_headers = Self::fill_placeholders(Self::parse_json_map(&headers));
+        let mut env_vars = Self::fill_placeholders(Self::parse_json_map(&env_vars_json));
 
         // Fallback: If it's stdio and headers are provided but env_vars are not, use headers as env_vars (legacy support)
         if r#type == "stdio" && env_vars.is_none() && custom_headers.is_some() {
             env_vars = custom_headers.clone();
         }
 
-        let client_details = Self::create_client_details();
-        let handler = SimpleClientHandler {};
+        let client_details = Self::create_client_details(handler.sampling.is_some());
 
         let client = if r#type == "sse" {
             // Create SSE transport with custom headers if provided
@@ -310,27 +411,57 @@ impl MCPClientService {
                 }
             }
 
-            // Use environment variables
-            let transport = match StdioTransport::create_with_server_launch(
-                &command,
-                args.clone(),
-                env_vars,
-                TransportOptions::default(),
-            ) {
-                Ok(t) => t,
-                Err(e) => {
-                    let err_msg = format!(
-                        "Failed to create stdio transport for command '{command}' with args {args:?}: {e}"
-                    );
-                    tracing::error!("{}", err_msg);
-                    return Err(AppError::Generic(err_msg));
-                }
-            };
+            Self::stdio_client_with(&command, args, env_vars, handler)?
+        };
 
-            client_runtime::create_client(client_details, transport, handler)
+        Self::start(client, &url).await
+    }
+
+    /// Create a client that launches `command` and talks to it over stdio.
+    pub fn stdio_client(
+        command: &str,
+        args: Vec<String>,
+        env_vars: Option<HashMap<String, String>>,
+    ) -> Result<Arc<ClientRuntime>, AppError> {
+        Self::stdio_client_with(command, args, env_vars, LunexClientHandler::default())
+    }
+
+    fn stdio_client_with(
+        command: &str,
+        args: Vec<String>,
+        env_vars: Option<HashMap<String, String>>,
+        handler: LunexClientHandler,
+    ) -> Result<Arc<ClientRuntime>, AppError> {
+        // Use environment variables
+        let transport = match StdioTransport::create_with_server_launch(
+            command,
+            args.clone(),
+            env_vars,
+            TransportOptions::default(),
+        ) {
+            Ok(t) => t,
+            Err(e) => {
+                let err_msg = format!(
+                    "Failed to create stdio transport for command '{command}' with args {args:?}: {e}"
+                );
+                tracing::error!("{}", err_msg);
+                return Err(AppError::Generic(err_msg));
+            }
         };
 
-        // Start the client
+        Ok(client_runtime::create_client(
+            Self::create_client_details(handler.sampling.is_some()),
+            transport,
+            handler,
+        ))
+    }
+
+    /// Start a client, which runs the initialization handshake. `url` names
+    /// the server in errors.
+    pub async fn start(
+        client: Arc<ClientRuntime>,
+        url: &str,
+    ) -> Result<Arc<ClientRuntime>, AppError> {
         if let Err(e) = client.clone().start().await {
             let err_msg = format!("Failed to start MCP client for {url}: {e}");
             tracing::error!("{}", err_msg);
@@ -418,7 +549,35 @@ impl MCPClientService {
         )
         .await?;
 
-        // Call the tool
+        let result = Self::run_tool(&client, &url, tool_name, arguments).await;
+
+        // Clean up - shut down the client connection
+        let _ = client.shut_down().await;
+
+        result
+    }
+
+    /// Call a tool of a saved connection, whose server may ask for
+    /// completions (sampling) while the call runs.
+    pub async fn call_connection_tool(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+        tool_name: String,
+        arguments: serde_json::Value,
+    ) -> Result<String, AppError> {
+        let client = Self::connect(app, connection).await?;
+        let result = Self::run_tool(&client, &connection.url, tool_name, arguments).await;
+        let _ = client.shut_down().await;
+        result
+    }
+
+    /// Call a tool on a started client, returning its content as JSON.
+    async fn run_tool(
+        client: &Arc<ClientRuntime>,
+        url: &str,
+        tool_name: String,
+        arguments: serde_json::Value,
+    ) -> Result<String, AppError> {
         // Convert arguments from Value to Map if it's an object
         let arguments_map = match arguments {
             serde_json::Value::Object(map) => Some(map),
@@ -431,24 +590,287 @@ impl MCPClientService {
         let result = match client.call_tool(params).await {
             Ok(r) => r,
             Err(e) => {
-                let err_msg = format!(
-                    "Failed to call tool {} on MCP server {}: {}",
-                    tool_name.clone(),
-                    url.clone(),
-                    e
-                );
+                let err_msg = format!("Failed to call tool {tool_name} on MCP server {url}: {e}");
                 tracing::error!("{}", err_msg);
                 return Err(AppError::Generic(err_msg));
             }
         };
 
         // Serialize result to JSON string
-        let result_json = serde_json::to_string(&result.content)
-            .map_err(|e| AppError::Generic(format!("Failed to serialize result: {e}")))?;
+        serde_json::to_string(&result.content)
+            .map_err(|e| AppError::Generic(format!("Failed to serialize result: {e}")))
+    }
+
+    /// Start a client for a saved connection, with its auth token. The
+    /// server may ask it for completions (sampling).
+    async fn connect(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+    ) -> Result<Arc<ClientRuntime>, AppError> {
+        Self::start_client(
+            app,
+            connection.url.clone(),
+            connection.r#type.clone(),
+            connection.request_headers(),
+            connection.env_vars.clone(),
+            connection.runtime_path.clone(),
+            LunexClientHandler::for_connection(app, connection),
+        )
+        .await
+    }
+
+    /// Check that the server of `connection` starts and answers a ping
+    /// within `timeout`. A stdio server that exits or a remote one that
+    /// dropped fails to start.
+    pub async fn ping(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+        timeout: Duration,
+    ) -> Result<(), AppError> {
+        let client = tokio::time::timeout(timeout, Self::connect(app, connection))
+            .await
+            .map_err(|_| {
+                AppError::Mcp(format!(
+                    "MCP server {} did not start within {}s",
+                    connection.url,
+                    timeout.as_secs()
+                ))
+            })??;
+        let result = client.ping(Some(timeout)).await;
+        let _ = client.shut_down().await;
+        result.map(|_| ()).map_err(|e| {
+            AppError::Mcp(format!(
+                "MCP server {} did not answer a ping: {e}",
+                connection.url
+            ))
+        })
+    }
+
+    /// The resources the server of `connection` lists, over every page. A
+    /// server without the resources capability has none.
+    pub async fn list_resources(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+    ) -> Result<Vec<MCPResource>, AppError> {
+        let client = Self::connect(app, connection).await?;
+        if client.server_has_resources() == Some(false) {
+            let _ = client.shut_down().await;
+            return Ok(Vec::new());
+        }
+
+        let mut resources = Vec::new();
+        let mut cursor = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let page = match client
+                .list_resources(Some(ListResourcesRequestParams { cursor }))
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = client.shut_down().await;
+                    return Err(AppError::Mcp(format!(
+                        "Failed to list resources from MCP server {}: {e}",
+                        connection.url
+                    )));
+                }
+            };
+            resources.extend(page.resources.into_iter().map(|r| MCPResource {
+                uri: r.uri,
+                name: r.name,
+                title: r.title,
+                description: r.description,
+                mime_type: r.mime_type,
+                size: r.size,
+            }));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
 
-        // Clean up - shut down the client connection
         let _ = client.shut_down().await;
+        Ok(resources)
+    }
 
-        Ok(result_json)
+    /// The resource templates the server of `connection` offers, over every
+    /// page.
+    pub async fn list_resource_templates(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+    ) -> Result<Vec<MCPResourceTemplate>, AppError> {
+        let client = Self::connect(app, connection).await?;
+        if client.server_has_resources() == Some(false) {
+            let _ = client.shut_down().await;
+            return Ok(Vec::new());
+        }
+
+        let mut templates = Vec::new();
+        let mut cursor = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let page = match client
+                .list_resource_templates(Some(ListResourceTemplatesRequestParams { cursor }))
+                .await
+            {
+                Ok(page) => page,
+                Err(e) => {
+                    let _ = client.shut_down().await;
+                    return Err(AppError::Mcp(format!(
+                        "Failed to list resource templates from MCP server {}: {e}",
+                        connection.url
+                    )));
+                }
+            };
+            templates.extend(
+                page.resource_templates
+                    .into_iter()
+                    .map(|t| MCPResourceTemplate {
+                        uri_template: t.uri_template,
+                        name: t.name,
+                        title: t.title,
+                        description: t.description,
+                        mime_type: t.mime_type,
+                    }),
+            );
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+
+        let _ = client.shut_down().await;
+        Ok(templates)
+    }
+
+    /// Read the resource at `uri` from the server of `connection`.
+    pub async fn read_resource(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+        uri: String,
+    ) -> Result<Vec<MCPResourceContent>, AppError> {
+        let client = Self::connect(app, connection).await?;
+        let result = client
+            .read_resource(ReadResourceRequestParams { uri: uri.clone() })
+            .await;
+        let _ = client.shut_down().await;
+
+        let result = result.map_err(|e| {
+            AppError::Mcp(format!(
+                "Failed to read resource {uri} from MCP server {}: {e}",
+                connection.url
+            ))
+        })?;
+        Ok(result
+            .contents
+            .into_iter()
+            .map(|item| match item {
+                ReadResourceResultContentsItem::TextResourceContents(c) => MCPResourceContent {
+                    uri: c.uri,
+                    mime_type: c.mime_type,
+                    text: Some(c.text),
+                    blob: None,
+                },
+                ReadResourceResultContentsItem::BlobResourceContents(c) => MCPResourceContent {
+                    uri: c.uri,
+                    mime_type: c.mime_type,
+                    text: None,
+                    blob: Some(c.blob),
+                },
+            })
+            .collect())
+    }
+
+    /// The prompts the server of `connection` offers, over every page. A
+    /// server without the prompts capability has none.
+    pub async fn list_prompts(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+    ) -> Result<Vec<MCPPrompt>, AppError> {
+        let client = Self::connect(app, connection).await?;
+        let prompts = Self::fetch_prompts(&client, &connection.url).await;
+        let _ = client.shut_down().await;
+        prompts
+    }
+
+    async fn fetch_prompts(
+        client: &Arc<ClientRuntime>,
+        url: &str,
+    ) -> Result<Vec<MCPPrompt>, AppError> {
+        if client.server_has_prompts() == Some(false) {
+            return Ok(Vec::new());
+        }
+
+        let mut prompts = Vec::new();
+        let mut cursor = None;
+        for _ in 0..MAX_LIST_PAGES {
+            let page = client
+                .list_prompts(Some(ListPromptsRequestParams { cursor }))
+                .await
+                .map_err(|e| {
+                    AppError::Mcp(format!("Failed to list prompts from MCP server {url}: {e}"))
+                })?;
+            prompts.extend(page.prompts.into_iter().map(|p| {
+                MCPPrompt {
+                    name: p.name,
+                    title: p.title,
+                    description: p.description,
+                    arguments: p
+                        .arguments
+                        .into_iter()
+                        .map(|a| MCPPromptArgument {
+                            name: a.name,
+                            title: a.title,
+                            description: a.description,
+                            required: a.required.unwrap_or(false),
+                        })
+                        .collect(),
+                }
+            }));
+            cursor = page.next_cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        Ok(prompts)
+    }
+
+    /// Have the server of `connection` fill in the prompt `name` with
+    /// `arguments`, once they are checked against its definition.
+    pub async fn get_prompt(
+        app: &AppHandle,
+        connection: &MCPServerConnection,
+        name: String,
+        arguments: HashMap<String, String>,
+    ) -> Result<MCPPromptResult, AppError> {
+        let client = Self::connect(app, connection).await?;
+        let result = Self::fill_prompt(&client, &connection.url, name, arguments).await;
+        let _ = client.shut_down().await;
+        result
+    }
+
+    async fn fill_prompt(
+        client: &Arc<ClientRuntime>,
+        url: &str,
+        name: String,
+        arguments: HashMap<String, String>,
+    ) -> Result<MCPPromptResult, AppError> {
+        let prompts = Self::fetch_prompts(client, url).await?;
+        let prompt = prompts
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| AppError::NotFound(format!("MCP prompt not found: {name}")))?;
+        mcp_prompts::check_arguments(prompt, &arguments)?;
+
+        let result = client
+            .get_prompt(GetPromptRequestParams {
+                name: name.clone(),
+                arguments: (!arguments.is_empty()).then_some(arguments),
+            })
+            .await
+            .map_err(|e| {
+                AppError::Mcp(format!(
+                    "Failed to get prompt {name} from MCP server {url}: {e}"
+                ))
+            })?;
+        Ok(mcp_prompts::prompt_result(result))
     }
 }
@@ -0,0 +1,275 @@
+//! Health checks of connected MCP servers.
+//!
+//! Every connection marked "connected" is pinged once a minute. One that
+//! fails is marked "connecting" and tried again with exponential backoff
+//! until it answers, then marked "connected" again; after
+//! `MAX_RECONNECT_ATTEMPTS` failures in a row it is marked "disconnected".
+//! Each change is reported with an `mcp-server-status` event.
+
+use super::mcp_client::MCPClientService;
+use crate::error::AppError;
+use crate::events::McpServerStatusEvent;
+use crate::features::mcp_connection::{MCPConnectionRepository, MCPServerConnection};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often connected servers are pinged.
+pub const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a server has to start and answer a ping.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Failed checks in a row after which a server is given up on.
+pub const MAX_RECONNECT_ATTEMPTS: u32 = 8;
+
+const BASE_RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const MAX_RECONNECT_DELAY: Duration = Duration::from_secs(300);
+
+/// How often the monitor looks for checks that are due.
+const CHECK_TICK: Duration = Duration::from_secs(5);
+
+/// Wait before the first checks, so servers are not started while the app
+/// is starting.
+const STARTUP_DELAY: Duration = Duration::from_secs(30);
+
+/// The wait before the next attempt after `failures` failed checks in a
+/// row: 5s, doubling up to 5 minutes.
+pub fn reconnect_delay(failures: u32) -> Duration {
+    let doublings = failures.saturating_sub(1).min(16);
+    BASE_RECONNECT_DELAY
+        .saturating_mul(1 << doublings)
+        .min(MAX_RECONNECT_DELAY)
+}
+
+/// What a check changed for a server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthChange {
+    /// It answered, as it did before
+    Unchanged,
+    /// It answered after failing
+    Recovered,
+    /// It failed; the next attempt is in `retry_in`
+    Reconnecting { retry_in: Duration },
+    /// It failed `MAX_RECONNECT_ATTEMPTS` times in a row
+    GaveUp,
+}
+
+/// The health of a watched server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ServerHealth {
+    /// Failed checks in a row
+    pub failures: u32,
+    pub next_check: Instant,
+}
+
+impl ServerHealth {
+    pub fn healthy(now: Instant) -> Self {
+        Self {
+            failures: 0,
+            next_check: now + HEALTH_CHECK_INTERVAL,
+        }
+    }
+
+    /// The health after a check at `now` that answered or not.
+    pub fn record(self, answered: bool, now: Instant) -> (Self, HealthChange) {
+        if answered {
+            let change = if self.failures == 0 {
+                HealthChange::Unchanged
+            } else {
+                HealthChange::Recovered
+            };
+            return (Self::healthy(now), change);
+        }
+
+        let failures = self.failures + 1;
+        let retry_in = reconnect_delay(failures);
+        let change = if failures >= MAX_RECONNECT_ATTEMPTS {
+            HealthChange::GaveUp
+        } else {
+            HealthChange::Reconnecting { retry_in }
+        };
+        (
+            Self {
+                failures,
+                next_check: now + retry_in,
+            },
+            change,
+        )
+    }
+}
+
+pub struct MCPHealthMonitor {
+    app: tauri::AppHandle,
+    mcp_connection_repository: Arc<dyn MCPConnectionRepository>,
+}
+
+impl MCPHealthMonitor {
+    pub fn new(
+        app: tauri::AppHandle,
+        mcp_connection_repository: Arc<dyn MCPConnectionRepository>,
+    ) -> Self {
+        Self {
+            app,
+            mcp_connection_repository,
+        }
+    }
+
+    /// Start the background health checks
+    pub fn start_background_checks(self: Arc<Self>) {
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(STARTUP_DELAY).await;
+
+            let mut health = HashMap::new();
+            let mut interval = tokio::time::interval(CHECK_TICK);
+            interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+
+            loop {
+                interval.tick().await;
+                if let Err(e) = self.check_due(&mut health).await {
+                    tracing::error!("MCP health check failed: {e}");
+                }
+            }
+        });
+    }
+
+    /// Check the watched servers whose check is due. Watched are those
+    /// marked "connected" and those this monitor is reconnecting; a server
+    /// the user disconnects meanwhile is dropped.
+    async fn check_due(&self, health: &mut HashMap<String, ServerHealth>) -> Result<(), AppError> {
+        let watched: Vec<MCPServerConnection> = self
+            .mcp_connection_repository
+            .get_all()?
+            .into_iter()
+            .filter(|connection| {
+                connection.status == "connected"
+                    || (connection.status == "connecting"
+                        && health.get(&connection.id).is_some_and(|h| h.failures > 0))
+            })
+            .collect();
+        health.retain(|id, _| watched.iter().any(|connection| &connection.id == id));
+
+        for connection in watched {
+            let now = Instant::now();
+            let state = *health
+                .entry(connection.id.clone())
+                .or_insert_with(|| ServerHealth::healthy(now));
+            if state.next_check > now {
+                continue;
+            }
+
+            let result = MCPClientService::ping(&self.app, &connection, PING_TIMEOUT).await;
+            let (state, change) = state.record(result.is_ok(), Instant::now());
+            if change == HealthChange::GaveUp {
+                health.remove(&connection.id);
+            } else {
+                health.insert(connection.id.clone(), state);
+            }
+
+            let error = result.err().map(|e| e.to_string());
+            if let Err(e) = self.apply(&connection, change, state.failures, error) {
+                tracing::error!(
+                    "Failed to record health of MCP connection {} ({}): {}",
+                    connection.id,
+                    connection.name,
+                    e
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Store a changed status on the connection, keeping its tools, and
+    /// report it.
+    fn apply(
+        &self,
+        connection: &MCPServerConnection,
+        change: HealthChange,
+        failures: u32,
+        error: Option<String>,
+    ) -> Result<(), AppError> {
+        let (status, retry_in_secs) = match change {
+            HealthChange::Unchanged => return Ok(()),
+            HealthChange::Recovered => ("connected", None),
+            HealthChange::Reconnecting { retry_in } => ("connecting", Some(retry_in.as_secs())),
+            HealthChange::GaveUp => ("disconnected", None),
+        };
+        if let Some(error) = &error {
+            tracing::warn!(
+                "MCP server {} ({}) failed a health check ({failures} in a row): {error}",
+                connection.id,
+                connection.name
+            );
+        }
+
+        self.mcp_connection_repository.update_status(
+            &connection.id,
+            status,
+            connection.tools_json.as_deref(),
+            error.as_deref(),
+        )?;
+        crate::events::emit_event(
+            &self.app,
+            McpServerStatusEvent {
+                connection_id: connection.id.clone(),
+                server_name: connection.name.clone(),
+                status: status.to_string(),
+                error,
+                failures,
+                retry_in_secs,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn mcp_health_checks_back_off_while_reconnecting_and_give_up() {
+        use crate::features::tool::mcp_health::{
+            reconnect_delay, HealthChange, ServerHealth, HEALTH_CHECK_INTERVAL,
+            MAX_RECONNECT_ATTEMPTS,
+        };
+        use std::time::{Duration, Instant};
+
+        assert_eq!(reconnect_delay(1), Duration::from_secs(5));
+        assert_eq!(reconnect_delay(2), Duration::from_secs(10));
+        assert_eq!(reconnect_delay(4), Duration::from_secs(40));
+        assert_eq!(reconnect_delay(7), Duration::from_secs(300));
+        assert_eq!(reconnect_delay(u32::MAX), Duration::from_secs(300));
+
+        let now = Instant::now();
+        let healthy = ServerHealth::healthy(now);
+        let (still, change) = healthy.record(true, now);
+        assert_eq!(change, HealthChange::Unchanged);
+        assert_eq!(still.next_check, now + HEALTH_CHECK_INTERVAL);
+
+        // A failure is retried soon, and sooner than the next regular check
+        let (down, change) = healthy.record(false, now);
+        assert_eq!(
+            change,
+            HealthChange::Reconnecting {
+                retry_in: Duration::from_secs(5)
+            }
+        );
+        assert_eq!(down.failures, 1);
+        assert_eq!(down.next_check, now + Duration::from_secs(5));
+
+        let (back, change) = down.record(true, now);
+        assert_eq!(change, HealthChange::Recovered);
+        assert_eq!(back, ServerHealth::healthy(now));
+
+        let mut state = healthy;
+        let mut changes = Vec::new();
+        for _ in 0..MAX_RECONNECT_ATTEMPTS {
+            let (next, change) = state.record(false, now);
+            state = next;
+            changes.push(change);
+        }
+        assert_eq!(changes.last(), Some(&HealthChange::GaveUp));
+        assert!(changes[..changes.len() - 1]
+            .iter()
+            .all(|c| matches!(c, HealthChange::Reconnecting { .. })));
+    }
+}
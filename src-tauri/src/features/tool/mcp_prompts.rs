@@ -0,0 +1,185 @@
+//! MCP prompts: templates a server fills in with arguments, inserted as the
+//! content of a message or as a chat's custom instructions.
+
+use super::models::{MCPPrompt, MCPPromptMessage, MCPPromptResult};
+use crate::error::AppError;
+use rust_mcp_sdk::schema::{ContentBlock, EmbeddedResourceResource, GetPromptResult};
+use std::collections::HashMap;
+
+/// The text of one content block. Embedded text resources are inlined;
+/// links, binary resources, images and audio are only named.
+pub fn block_text(block: &ContentBlock) -> String {
+    match block {
+        ContentBlock::TextContent(content) => content.text.clone(),
+        ContentBlock::EmbeddedResource(content) => match &content.resource {
+            EmbeddedResourceResource::TextResourceContents(resource) => resource.text.clone(),
+            EmbeddedResourceResource::BlobResourceContents(resource) => {
+                format!("[Resource: {}]", resource.uri)
+            }
+        },
+        ContentBlock::ResourceLink(link) => format!("[Resource: {} ({})]", link.name, link.uri),
+        ContentBlock::ImageContent(image) => format!("[Image: {}]", image.mime_type),
+        ContentBlock::AudioContent(audio) => format!("[Audio: {}]", audio.mime_type),
+    }
+}
+
+/// A filled-in prompt as text and messages.
+pub fn prompt_result(result: GetPromptResult) -> MCPPromptResult {
+    let messages: Vec<MCPPromptMessage> = result
+        .messages
+        .into_iter()
+        .map(|message| MCPPromptMessage {
+            role: message.role.to_string(),
+            text: block_text(&message.content),
+        })
+        .collect();
+    MCPPromptResult {
+        description: result.description,
+        text: prompt_text(&messages),
+        messages,
+    }
+}
+
+/// The messages of a prompt as one text. Consecutive messages are joined by
+/// a blank line; a prompt that also speaks for the assistant labels every
+/// message with its role, so the exchange it sets up stays readable.
+pub fn prompt_text(messages: &[MCPPromptMessage]) -> String {
+    let labelled = messages.iter().any(|m| m.role != "user");
+    messages
+        .iter()
+        .filter(|m| !m.text.trim().is_empty())
+        .map(|m| {
+            if labelled {
+                format!("{}: {}", capitalize(&m.role), m.text)
+            } else {
+                m.text.clone()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars.next().map_or_else(String::new, |first| {
+        first.to_uppercase().chain(chars).collect()
+    })
+}
+
+/// Check `arguments` against the prompt's definition: every required
+/// argument is given and not blank, and no unknown one is.
+pub fn check_arguments(
+    prompt: &MCPPrompt,
+    arguments: &HashMap<String, String>,
+) -> Result<(), AppError> {
+    let missing: Vec<&str> = prompt
+        .arguments
+        .iter()
+        .filter(|a| a.required)
+        .filter(|a| arguments.get(&a.name).is_none_or(|v| v.trim().is_empty()))
+        .map(|a| a.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        return Err(AppError::Validation(format!(
+            "Prompt {} needs the arguments: {}",
+            prompt.name,
+            missing.join(", ")
+        )));
+    }
+    let mut unknown: Vec<&str> = arguments
+        .keys()
+        .filter(|name| !prompt.arguments.iter().any(|a| &a.name == *name))
+        .map(String::as_str)
+        .collect();
+    if !unknown.is_empty() {
+        unknown.sort_unstable();
+        return Err(AppError::Validation(format!(
+            "Prompt {} has no arguments named: {}",
+            prompt.name,
+            unknown.join(", ")
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn mcp_prompts_are_checked_filled_in_and_flattened_to_text() {
+        use crate::features::tool::mcp_prompts::{check_arguments, prompt_result, prompt_text};
+        use crate::features::tool::models::{MCPPrompt, MCPPromptArgument, MCPPromptMessage};
+        use rust_mcp_sdk::schema::GetPromptResult;
+        use std::collections::HashMap;
+
+        let prompt = MCPPrompt {
+            name: "review".to_string(),
+            title: None,
+            description: None,
+            arguments: vec![
+                MCPPromptArgument {
+                    name: "language".to_string(),
+                    title: None,
+                    description: None,
+                    required: true,
+                },
+                MCPPromptArgument {
+                    name: "focus".to_string(),
+                    title: None,
+                    description: None,
+                    required: false,
+                },
+            ],
+        };
+        let args = |pairs: &[(&str, &str)]| -> HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| ((*k).to_string(), (*v).to_string()))
+                .collect()
+        };
+        assert!(check_arguments(&prompt, &args(&[("language", "rust")])).is_ok());
+        assert!(check_arguments(&prompt, &args(&[("language", "rust"), ("focus", "")])).is_ok());
+        let missing = check_arguments(&prompt, &args(&[("language", "  ")])).unwrap_err();
+        assert!(missing.to_string().contains("language"));
+        let unknown =
+            check_arguments(&prompt, &args(&[("language", "rust"), ("tone", "x")])).unwrap_err();
+        assert!(unknown.to_string().contains("tone"));
+
+        // What the server filled in, with an embedded document and an image
+        let result: GetPromptResult = serde_json::from_value(serde_json::json!({
+            "description": "Code review",
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Review this rust code." } },
+                { "role": "user", "content": {
+                    "type": "resource",
+                    "resource": { "uri": "file:///src/lib.rs", "text": "fn main() {}" }
+                } },
+                { "role": "user", "content": { "type": "image", "data": "aGk=", "mimeType": "image/png" } },
+            ]
+        }))
+        .unwrap();
+        let filled = prompt_result(result);
+        assert_eq!(filled.description.as_deref(), Some("Code review"));
+        assert_eq!(filled.messages.len(), 3);
+        assert_eq!(
+            filled.text,
+            "Review this rust code.\n\nfn main() {}\n\n[Image: image/png]"
+        );
+
+        // A prompt that sets up an exchange keeps the roles apart
+        let exchange = [
+            MCPPromptMessage {
+                role: "user".to_string(),
+                text: "Hi".to_string(),
+            },
+            MCPPromptMessage {
+                role: "assistant".to_string(),
+                text: "Hello, what shall we review?".to_string(),
+            },
+        ];
+        assert_eq!(
+            prompt_text(&exchange),
+            "User: Hi\n\nAssistant: Hello, what shall we review?"
+        );
+        assert_eq!(prompt_text(&[]), "");
+    }
+}
@@ -1,22 +1,26 @@
 use super::mcp_client::MCPClientService;
 use crate::error::AppError;
 use crate::features::mcp_connection::MCPConnectionRepository;
+use crate::features::tool_catalog::ToolCatalogService;
 use std::sync::Arc;
 use std::time::Duration;
 
 pub struct MCPToolRefreshService {
     app: tauri::AppHandle,
     mcp_connection_repository: Arc<dyn MCPConnectionRepository>,
+    tool_catalog_service: Arc<ToolCatalogService>,
 }
 
 impl MCPToolRefreshService {
     pub fn new(
         app: tauri::AppHandle,
         mcp_connection_repository: Arc<dyn MCPConnectionRepository>,
+        tool_catalog_service: Arc<ToolCatalogService>,
     ) -> Self {
         Self {
             app,
             mcp_connection_repository,
+            tool_catalog_service,
         }
     }
 
@@ -64,11 +68,7 @@ impl MCPToolRefreshService {
                 &self.app,
                 connection.url.clone(),
                 connection.r#type.clone(),
-                if connection.headers.is_empty() {
-                    None
-                } else {
-                    Some(connection.headers.clone())
-                },
+                connection.request_headers(),
                 connection.env_vars.clone(),
                 connection.runtime_path.clone(),
             )
@@ -87,6 +87,8 @@ impl MCPToolRefreshService {
                         Some(&tools_json),
                         None,
                     )?;
+                    self.tool_catalog_service
+                        .record_refresh_logged(&connection.id, &tools_json);
                 }
                 Err(e) => {
                     // Log error but don't fail the entire refresh
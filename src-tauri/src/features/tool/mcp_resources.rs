@@ -0,0 +1,176 @@
+//! MCP resources attached to chat messages.
+//!
+//! A user message carries the resources attached to it under `mcpResources`
+//! in its metadata, with their text as read when they were attached, so the
+//! turn and its replays see the same document whether or not the server is
+//! still reachable. The text is added to the message content sent to the
+//! model, after what the user wrote.
+
+use super::models::MCPResourceContent;
+use serde::{Deserialize, Serialize};
+
+/// Message metadata key listing the attached resources.
+pub const METADATA_KEY: &str = "mcpResources";
+
+/// Characters of resource text kept in one attachment.
+pub const MAX_ATTACHED_CHARS: usize = 100_000;
+
+/// A resource attached to a message, as stored in its metadata.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AttachedResource {
+    pub connection_id: String,
+    pub uri: String,
+    pub name: String,
+    #[serde(default)]
+    pub mime_type: Option<String>,
+    pub text: String,
+    /// The text was cut at `MAX_ATTACHED_CHARS`
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+impl AttachedResource {
+    /// The attachment of what reading `uri` returned. Text items are joined;
+    /// binary ones are only named, since the model gets text.
+    pub fn from_contents(
+        connection_id: &str,
+        uri: &str,
+        name: &str,
+        contents: &[MCPResourceContent],
+    ) -> Self {
+        let mut text = String::new();
+        for item in contents {
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            match (&item.text, &item.blob) {
+                (Some(item_text), _) => text.push_str(item_text),
+                (None, Some(blob)) => text.push_str(&format!(
+                    "[binary content of {}: {}, {} bytes of base64]",
+                    item.uri,
+                    item.mime_type.as_deref().unwrap_or("unknown type"),
+                    blob.len()
+                )),
+                (None, None) => {}
+            }
+        }
+        let truncated = text.chars().count() > MAX_ATTACHED_CHARS;
+        if truncated {
+            text = text.chars().take(MAX_ATTACHED_CHARS).collect();
+        }
+        Self {
+            connection_id: connection_id.to_string(),
+            uri: uri.to_string(),
+            name: name.to_string(),
+            mime_type: contents.iter().find_map(|c| c.mime_type.clone()),
+            text,
+            truncated,
+        }
+    }
+}
+
+/// The resources a message's metadata lists; none when it has no valid list.
+pub fn attached_resources(metadata: &str) -> Vec<AttachedResource> {
+    serde_json::from_str::<serde_json::Value>(metadata)
+        .ok()
+        .and_then(|mut meta| meta.get_mut(METADATA_KEY).map(serde_json::Value::take))
+        .and_then(|list| serde_json::from_value(list).ok())
+        .unwrap_or_default()
+}
+
+/// The text the attached resources add to a message's content, `None` when
+/// it has none.
+pub fn resources_context(metadata: &str) -> Option<String> {
+    let resources = attached_resources(metadata);
+    if resources.is_empty() {
+        return None;
+    }
+    let mut context = String::new();
+    for resource in resources {
+        context.push_str(&format!(
+            "\n\n[Attached Resource: {} ({})]\n",
+            resource.name, resource.uri
+        ));
+        context.push_str(&resource.text);
+        if resource.truncated {
+            context.push_str("\n[Resource truncated]");
+        }
+        context.push_str("\n[End Resource]\n");
+    }
+    Some(context)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::contents;
+
+    #[test]
+    fn mcp_resources_attached_to_a_message_reach_the_model_as_text() {
+        use crate::features::chat::context_window::ContextBudget;
+        use crate::features::tool::mcp_resources::{
+            attached_resources, resources_context, AttachedResource, MAX_ATTACHED_CHARS,
+        };
+        use crate::features::tool::models::MCPResourceContent;
+
+        let contents = [
+            MCPResourceContent {
+                uri: "file:///docs/readme.md".to_string(),
+                mime_type: Some("text/markdown".to_string()),
+                text: Some("# Readme".to_string()),
+                blob: None,
+            },
+            MCPResourceContent {
+                uri: "file:///docs/logo.png".to_string(),
+                mime_type: Some("image/png".to_string()),
+                text: None,
+                blob: Some("iVBORw0KGgo=".to_string()),
+            },
+        ];
+        let attached =
+            AttachedResource::from_contents("conn-1", "file:///docs/", "Docs", &contents);
+        assert_eq!(attached.mime_type.as_deref(), Some("text/markdown"));
+        assert!(attached.text.starts_with("# Readme\n\n"));
+        // Binary items are named, never inlined
+        assert!(attached.text.contains("image/png, 12 bytes of base64"));
+        assert!(!attached.text.contains("iVBORw0KGgo="));
+        assert!(!attached.truncated);
+
+        // Stored under mcpResources, next to other metadata
+        let metadata = serde_json::json!({
+            "files": [],
+            "mcpResources": [&attached],
+        })
+        .to_string();
+        assert_eq!(attached_resources(&metadata), [attached.clone()]);
+        let context = resources_context(&metadata).unwrap();
+        assert!(context.contains("[Attached Resource: Docs (file:///docs/)]\n# Readme"));
+        assert!(context.ends_with("[End Resource]\n"));
+
+        // Nothing attached, or an invalid list, adds nothing
+        assert!(resources_context(r#"{"files": []}"#).is_none());
+        assert!(resources_context(r#"{"mcpResources": "oops"}"#).is_none());
+        assert!(resources_context("not json").is_none());
+
+        // Long documents are cut, and the model is told
+        let long = MCPResourceContent {
+            uri: "file:///big.txt".to_string(),
+            mime_type: None,
+            text: Some("é".repeat(MAX_ATTACHED_CHARS + 10)),
+            blob: None,
+        };
+        let big = AttachedResource::from_contents("conn-1", "file:///big.txt", "Big", &[long]);
+        assert!(big.truncated);
+        assert_eq!(big.text.chars().count(), MAX_ATTACHED_CHARS);
+        let metadata = serde_json::json!({ "mcpResources": [big] }).to_string();
+        assert!(resources_context(&metadata)
+            .unwrap()
+            .contains("[Resource truncated]"));
+
+        // The context budget counts the attached text
+        let budget = ContextBudget::for_request("gpt-4o", None, None, None).unwrap();
+        assert!(budget.resource_tokens(Some(&metadata)) > 1000);
+        assert_eq!(budget.resource_tokens(Some(r#"{"files": []}"#)), 0);
+        assert_eq!(budget.resource_tokens(None), 0);
+    }
+}
@@ -0,0 +1,334 @@
+//! MCP sampling: completions a connected server asks the client for.
+//!
+//! A server sends `sampling/createMessage` while one of its requests is in
+//! flight. The request is answered by the LLM connection of a workspace that
+//! uses the server, once the user approves it through an
+//! `mcp-sampling-request` event and `respond_mcp_sampling`. Nothing is sent
+//! to a model without that approval; a request left unanswered is declined.
+
+use crate::error::AppError;
+use crate::features::system::halt;
+use crate::models::llm_types::{AssistantContent, ChatMessage, ContentPart, ImageUrl, UserContent};
+use rust_mcp_sdk::schema::{
+    CreateMessageRequestParams, CreateMessageResult, Role, SamplingMessageContent, TextContent,
+};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::oneshot;
+
+/// `request_type` of the usage rows of sampling requests. Their
+/// `message_id` is the id of the MCP connection that asked.
+pub const SAMPLING_REQUEST_TYPE: &str = "mcp_sampling";
+
+/// How long a sampling request waits for the user.
+pub const SAMPLING_APPROVAL_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Completion tokens a request may ask for at most.
+pub const MAX_SAMPLING_TOKENS: u32 = 16_384;
+
+/// A message of a sampling request, as the user is shown it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, JsonSchema)]
+pub struct SamplingMessagePreview {
+    /// `user` or `assistant`
+    pub role: String,
+    pub text: String,
+}
+
+/// The messages of a request as text.
+pub fn preview_messages(params: &CreateMessageRequestParams) -> Vec<SamplingMessagePreview> {
+    params
+        .messages
+        .iter()
+        .map(|message| SamplingMessagePreview {
+            role: message.role.to_string(),
+            text: content_text(&message.content),
+        })
+        .collect()
+}
+
+/// The text of a message; images and audio are only named.
+fn content_text(content: &SamplingMessageContent) -> String {
+    match content {
+        SamplingMessageContent::TextContent(content) => content.text.clone(),
+        SamplingMessageContent::ImageContent(image) => format!("[Image: {}]", image.mime_type),
+        SamplingMessageContent::AudioContent(audio) => format!("[Audio: {}]", audio.mime_type),
+    }
+}
+
+/// The request as chat messages: its system prompt, then its messages.
+/// Images go as data URLs; audio, which providers take in few ways, only
+/// by name.
+pub fn chat_messages(params: &CreateMessageRequestParams) -> Vec<ChatMessage> {
+    let mut messages = Vec::new();
+    if let Some(system) = params
+        .system_prompt
+        .as_ref()
+        .filter(|s| !s.trim().is_empty())
+    {
+        messages.push(ChatMessage::System {
+            content: system.clone(),
+        });
+    }
+    for message in &params.messages {
+        let message = match (&message.role, &message.content) {
+            (Role::User, SamplingMessageContent::ImageContent(image)) => ChatMessage::User {
+                content: UserContent::Parts(vec![ContentPart::ImageUrl {
+                    image_url: ImageUrl {
+                        url: format!("data:{};base64,{}", image.mime_type, image.data),
+                    },
+                }]),
+            },
+            (Role::User, content) => ChatMessage::User {
+                content: UserContent::Text(content_text(content)),
+            },
+            (Role::Assistant, content) => ChatMessage::Assistant {
+                content: AssistantContent::Text(content_text(content)),
+                tool_calls: None,
+            },
+        };
+        messages.push(message);
+    }
+    messages
+}
+
+/// The model to answer with: the first of `models` a hint of the request
+/// names (hints are substrings, tried in order), else `fallback`.
+pub fn pick_model(
+    params: &CreateMessageRequestParams,
+    models: &[String],
+    fallback: Option<String>,
+) -> Option<String> {
+    let hints = params
+        .model_preferences
+        .as_ref()
+        .map(|preferences| preferences.hints.as_slice())
+        .unwrap_or_default();
+    hints
+        .iter()
+        .filter_map(|hint| hint.name.as_deref())
+        .map(str::to_lowercase)
+        .filter(|hint| !hint.is_empty())
+        .find_map(|hint| {
+            models
+                .iter()
+                .find(|model| model.to_lowercase().contains(&hint))
+                .cloned()
+        })
+        .or(fallback)
+}
+
+/// Model ids listed in a connection's `models_json`, an array of ids or of
+/// objects with an `id`.
+pub fn model_ids(models_json: Option<&str>) -> Vec<String> {
+    models_json
+        .and_then(|json| serde_json::from_str::<Vec<serde_json::Value>>(json).ok())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|model| {
+            model
+                .as_str()
+                .or_else(|| model.get("id").and_then(serde_json::Value::as_str))
+                .map(str::to_string)
+        })
+        .collect()
+}
+
+/// Completion tokens to ask the provider for: what the server asked, kept
+/// between 1 and `MAX_SAMPLING_TOKENS`.
+pub fn max_tokens(params: &CreateMessageRequestParams) -> u32 {
+    u32::try_from(params.max_tokens.clamp(1, i64::from(MAX_SAMPLING_TOKENS)))
+        .unwrap_or(MAX_SAMPLING_TOKENS)
+}
+
+/// The answer in MCP terms. Provider finish reasons become the stop reasons
+/// the protocol names.
+pub fn create_message_result(
+    text: String,
+    model: String,
+    finish_reason: Option<&str>,
+) -> CreateMessageResult {
+    let stop_reason = finish_reason.map(|reason| {
+        match reason {
+            "stop" | "end_turn" | "STOP" => "endTurn",
+            "length" | "max_tokens" | "MAX_TOKENS" => "maxTokens",
+            "stop_sequence" => "stopSequence",
+            other => other,
+        }
+        .to_string()
+    });
+    CreateMessageResult {
+        content: TextContent::new(text, None, None).into(),
+        meta: None,
+        model,
+        role: Role::Assistant,
+        stop_reason,
+    }
+}
+
+/// How the user answered a sampling request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SamplingOutcome {
+    Approved,
+    Denied,
+    TimedOut,
+}
+
+/// Sampling requests waiting for the user, by request id.
+#[derive(Default)]
+pub struct SamplingApprovals {
+    pending: Mutex<HashMap<String, oneshot::Sender<bool>>>,
+}
+
+impl SamplingApprovals {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Hold the request `request_id`: `request` asks the user, then this
+    /// waits for `respond` or `timeout`.
+    pub async fn approve(
+        &self,
+        request_id: &str,
+        timeout: Duration,
+        request: impl FnOnce() -> Result<(), AppError>,
+    ) -> Result<SamplingOutcome, AppError> {
+        let (tx, rx) = oneshot::channel();
+        self.lock_pending()?.insert(request_id.to_string(), tx);
+        if let Err(e) = request() {
+            self.lock_pending()?.remove(request_id);
+            return Err(e);
+        }
+
+        let outcome = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(true)) => SamplingOutcome::Approved,
+            Ok(Ok(false) | Err(_)) => SamplingOutcome::Denied,
+            Err(_) => SamplingOutcome::TimedOut,
+        };
+        self.lock_pending()?.remove(request_id);
+        Ok(outcome)
+    }
+
+    /// Answer the pending sampling request `request_id`.
+    pub fn respond(&self, request_id: &str, approved: bool) -> Result<(), AppError> {
+        let sender = self.lock_pending()?.remove(request_id).ok_or_else(|| {
+            AppError::Validation(format!("No pending sampling request found: {request_id}"))
+        })?;
+        sender.send(approved).map_err(|_| {
+            AppError::Generic(format!("Failed to answer sampling request {request_id}"))
+        })
+    }
+
+    /// Decline every pending request, returning how many there were.
+    pub fn cancel_all(&self) -> Result<usize, AppError> {
+        Ok(halt::drop_pending(&mut self.lock_pending()?))
+    }
+
+    fn lock_pending(
+        &self,
+    ) -> Result<std::sync::MutexGuard<'_, HashMap<String, oneshot::Sender<bool>>>, AppError> {
+        self.pending.lock().map_err(|e| {
+            AppError::Generic(format!("Failed to lock pending sampling requests: {e}"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn mcp_sampling_requests_wait_for_approval_and_map_to_chat_requests() {
+        use crate::features::tool::mcp_sampling::{
+            chat_messages, create_message_result, max_tokens, model_ids, pick_model,
+            preview_messages, SamplingApprovals, SamplingOutcome, MAX_SAMPLING_TOKENS,
+        };
+        use crate::models::llm_types::{ChatMessage, ContentPart, UserContent};
+        use rust_mcp_sdk::schema::{CreateMessageRequestParams, CreateMessageResultContent};
+        use std::time::Duration;
+
+        let params: CreateMessageRequestParams = serde_json::from_value(serde_json::json!({
+            "messages": [
+                { "role": "user", "content": { "type": "text", "text": "Summarize the diff" } },
+                { "role": "assistant", "content": { "type": "text", "text": "Which diff?" } },
+                { "role": "user", "content": { "type": "image", "data": "aGk=", "mimeType": "image/png" } },
+            ],
+            "systemPrompt": "You review code.",
+            "maxTokens": 1_000_000,
+            "modelPreferences": { "hints": [{ "name": "mini" }, { "name": "sonnet" }] },
+        }))
+        .unwrap();
+
+        let messages = chat_messages(&params);
+        assert_eq!(messages.len(), 4);
+        assert!(
+            matches!(&messages[0], ChatMessage::System { content } if content == "You review code.")
+        );
+        assert!(matches!(&messages[2], ChatMessage::Assistant { .. }));
+        let ChatMessage::User {
+            content: UserContent::Parts(parts),
+        } = &messages[3]
+        else {
+            panic!("expected an image part");
+        };
+        assert!(
+            matches!(&parts[0], ContentPart::ImageUrl { image_url } if image_url.url == "data:image/png;base64,aGk=")
+        );
+        // The user is shown text, never the image data
+        let preview = preview_messages(&params);
+        assert_eq!(preview[2].text, "[Image: image/png]");
+        assert_eq!(preview[1].role, "assistant");
+        assert_eq!(max_tokens(&params), MAX_SAMPLING_TOKENS);
+
+        // Hints are substrings tried in order, then the workspace's model
+        let models = model_ids(Some(
+            r#"[{"id": "claude-sonnet-4"}, {"id": "gpt-4o-mini"}, "llama3"]"#,
+        ));
+        assert_eq!(models, ["claude-sonnet-4", "gpt-4o-mini", "llama3"]);
+        assert_eq!(
+            pick_model(&params, &models, Some("llama3".to_string())).as_deref(),
+            Some("gpt-4o-mini")
+        );
+        assert_eq!(
+            pick_model(&params, &["llama3".to_string()], Some("llama3".to_string())).as_deref(),
+            Some("llama3")
+        );
+        assert!(model_ids(Some("not json")).is_empty());
+
+        let result = create_message_result(
+            "Looks good".to_string(),
+            "gpt-4o-mini".to_string(),
+            Some("length"),
+        );
+        assert_eq!(result.stop_reason.as_deref(), Some("maxTokens"));
+        assert!(
+            matches!(result.content, CreateMessageResultContent::TextContent(ref t) if t.text == "Looks good")
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let approvals = SamplingApprovals::new();
+            let approved = approvals
+                .approve("req-1", Duration::from_secs(5), || {
+                    approvals.respond("req-1", true)
+                })
+                .await
+                .unwrap();
+            assert_eq!(approved, SamplingOutcome::Approved);
+            let denied = approvals
+                .approve("req-2", Duration::from_secs(5), || {
+                    approvals.respond("req-2", false)
+                })
+                .await
+                .unwrap();
+            assert_eq!(denied, SamplingOutcome::Denied);
+            let unanswered = approvals
+                .approve("req-3", Duration::from_millis(20), || Ok(()))
+                .await
+                .unwrap();
+            assert_eq!(unanswered, SamplingOutcome::TimedOut);
+            // Nothing is left waiting for an answer
+            assert!(approvals.respond("req-3", true).is_err());
+        });
+    }
+}
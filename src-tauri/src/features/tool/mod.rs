@@ -1,6 +1,15 @@
+pub mod builtin;
 pub mod commands;
 pub mod internal;
+pub mod mcp_autostart;
 pub mod mcp_client;
+pub mod mcp_health;
+pub mod mcp_prompts;
 pub mod mcp_refresh;
+pub mod mcp_resources;
+pub mod mcp_sampling;
 pub mod models;
+pub mod overrides;
+pub mod result_descriptor;
 pub mod service;
+pub mod strict_schema;
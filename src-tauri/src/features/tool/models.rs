@@ -14,3 +14,97 @@ pub struct UnifiedToolInfo {
     pub server_name: String,
     pub description: Option<String>,
 }
+
+/// How a workspace shows one of its MCP tools to the model. `tool_name` is
+/// the name the server gives it.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct ToolOverride {
+    pub tool_name: String,
+    /// Hidden from the model
+    #[serde(default)]
+    pub disabled: bool,
+    /// Name the model sees and calls instead
+    #[serde(default)]
+    pub alias: Option<String>,
+    /// Description the model sees instead of the server's
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+/// An MCP tool a workspace selected, as its server describes it, with how
+/// the workspace shows it.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WorkspaceToolInfo {
+    pub name: String,
+    pub connection_id: String,
+    pub server_name: String,
+    pub description: Option<String>,
+    #[serde(rename = "override")]
+    pub tool_override: ToolOverride,
+}
+
+/// A resource an MCP server lists.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPResource {
+    pub uri: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+    /// Raw size in bytes, when the server knows it
+    pub size: Option<i64>,
+}
+
+/// A parameterized resource an MCP server offers, read by filling in its
+/// RFC 6570 `uri_template`.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPResourceTemplate {
+    pub uri_template: String,
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// One item of what reading a resource returned: `text`, or `blob` as base64.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPResourceContent {
+    pub uri: String,
+    pub mime_type: Option<String>,
+    pub text: Option<String>,
+    pub blob: Option<String>,
+}
+
+/// A prompt template an MCP server offers.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPPrompt {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub arguments: Vec<MCPPromptArgument>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPPromptArgument {
+    pub name: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub required: bool,
+}
+
+/// One message of a prompt the server filled in, as text.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPPromptMessage {
+    /// `user` or `assistant`
+    pub role: String,
+    pub text: String,
+}
+
+/// A prompt the server filled in with the arguments given.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct MCPPromptResult {
+    pub description: Option<String>,
+    pub messages: Vec<MCPPromptMessage>,
+    /// The messages as one text, for the message input or a system prompt
+    pub text: String,
+}
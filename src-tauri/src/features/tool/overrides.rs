@@ -0,0 +1,201 @@
+//! Per-workspace curation of the MCP tools the model sees.
+//!
+//! A workspace can hide tools it selected, and show others under another
+//! name or description. The model only sees and calls the names it is
+//! shown; `ToolService` maps them back to the server's name when it runs a
+//! tool. Tool permissions and call caps go by the name the model calls.
+
+use super::models::ToolOverride;
+use crate::error::AppError;
+use crate::models::llm_types::ChatCompletionTool;
+use std::collections::{HashMap, HashSet};
+
+/// Longest alias, which providers take as a function name.
+pub const MAX_ALIAS_LEN: usize = 64;
+
+/// The overrides of a workspace, by the server's tool name.
+#[derive(Debug, Default)]
+pub struct ToolOverrides {
+    by_tool: HashMap<String, ToolOverride>,
+}
+
+impl ToolOverrides {
+    pub fn new(overrides: Vec<ToolOverride>) -> Self {
+        Self {
+            by_tool: overrides
+                .into_iter()
+                .map(|o| (o.tool_name.clone(), o))
+                .collect(),
+        }
+    }
+
+    /// The override of `tool_name`, or one that changes nothing.
+    pub fn get(&self, tool_name: &str) -> ToolOverride {
+        self.by_tool
+            .get(tool_name)
+            .cloned()
+            .unwrap_or_else(|| ToolOverride {
+                tool_name: tool_name.to_string(),
+                ..ToolOverride::default()
+            })
+    }
+
+    /// The name the model sees `tool_name` under; `None` when it is hidden.
+    pub fn exposed_name(&self, tool_name: &str) -> Option<String> {
+        match self.by_tool.get(tool_name) {
+            Some(o) if o.disabled => None,
+            Some(o) => Some(o.alias.clone().unwrap_or_else(|| tool_name.to_string())),
+            None => Some(tool_name.to_string()),
+        }
+    }
+
+    /// The description the model sees for `tool_name`.
+    pub fn description(&self, tool_name: &str, description: Option<String>) -> Option<String> {
+        self.by_tool
+            .get(tool_name)
+            .and_then(|o| o.description.clone())
+            .or(description)
+    }
+
+    /// The server's name of the tool the model called `exposed`.
+    pub fn original_name(&self, exposed: &str) -> String {
+        self.by_tool
+            .values()
+            .find(|o| !o.disabled && o.alias.as_deref() == Some(exposed))
+            .map_or_else(|| exposed.to_string(), |o| o.tool_name.clone())
+    }
+
+    /// `tool` as the model sees it, or `None` when it is hidden.
+    pub fn apply(&self, mut tool: ChatCompletionTool) -> Option<ChatCompletionTool> {
+        let name = tool.function.name.clone();
+        tool.function.name = self.exposed_name(&name)?;
+        tool.function.description = self.description(&name, tool.function.description);
+        Some(tool)
+    }
+}
+
+/// Whether an override leaves its tool as the server describes it.
+pub fn is_noop(tool_override: &ToolOverride) -> bool {
+    !tool_override.disabled && tool_override.alias.is_none() && tool_override.description.is_none()
+}
+
+/// `tool_override` with blank fields dropped, once its alias is checked:
+/// a function name providers accept, and none of `taken`, the names the
+/// workspace shows its other tools under.
+pub fn check_override(
+    mut tool_override: ToolOverride,
+    taken: &HashSet<String>,
+) -> Result<ToolOverride, AppError> {
+    let blank_to_none = |value: Option<String>| {
+        value
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty())
+    };
+    tool_override.alias =
+        blank_to_none(tool_override.alias).filter(|alias| alias != &tool_override.tool_name);
+    tool_override.description = blank_to_none(tool_override.description);
+
+    if let Some(alias) = &tool_override.alias {
+        if alias.len() > MAX_ALIAS_LEN
+            || !alias
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(AppError::Validation(format!(
+                "Tool alias {alias} must be at most {MAX_ALIAS_LEN} letters, digits, '_' or '-'"
+            )));
+        }
+    }
+    let exposed = tool_override
+        .alias
+        .as_deref()
+        .unwrap_or(&tool_override.tool_name);
+    if !tool_override.disabled && taken.contains(exposed) {
+        return Err(AppError::Validation(format!(
+            "Another tool of this workspace is already called {exposed}"
+        )));
+    }
+    Ok(tool_override)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn workspace_tool_overrides_hide_rename_and_redescribe_mcp_tools() {
+        use crate::features::tool::models::ToolOverride;
+        use crate::features::tool::overrides::{check_override, is_noop, ToolOverrides};
+        use crate::models::llm_types::{ChatCompletionTool, ChatCompletionToolFunction};
+        use std::collections::HashSet;
+
+        let tool = |name: &str| ChatCompletionTool {
+            r#type: "function".to_string(),
+            function: ChatCompletionToolFunction {
+                name: name.to_string(),
+                description: Some(format!("{name} from the server")),
+                parameters: None,
+            },
+        };
+        let overrides = ToolOverrides::new(vec![
+            ToolOverride {
+                tool_name: "brave_web_search".to_string(),
+                alias: Some("search".to_string()),
+                description: Some("Search the web for current facts".to_string()),
+                ..ToolOverride::default()
+            },
+            ToolOverride {
+                tool_name: "delete_repo".to_string(),
+                disabled: true,
+                ..ToolOverride::default()
+            },
+        ]);
+
+        let shown = overrides.apply(tool("brave_web_search")).unwrap();
+        assert_eq!(shown.function.name, "search");
+        assert_eq!(
+            shown.function.description.as_deref(),
+            Some("Search the web for current facts")
+        );
+        assert!(overrides.apply(tool("delete_repo")).is_none());
+        let untouched = overrides.apply(tool("list_issues")).unwrap();
+        assert_eq!(untouched.function.name, "list_issues");
+        assert_eq!(
+            untouched.function.description.as_deref(),
+            Some("list_issues from the server")
+        );
+
+        // Calls by the alias reach the server under its own name
+        assert_eq!(overrides.original_name("search"), "brave_web_search");
+        assert_eq!(overrides.original_name("list_issues"), "list_issues");
+        assert_eq!(overrides.exposed_name("delete_repo"), None);
+        assert!(is_noop(&overrides.get("list_issues")));
+
+        let taken: HashSet<String> = ["search".to_string(), "read_file".to_string()].into();
+        let rename = |alias: &str| ToolOverride {
+            tool_name: "list_issues".to_string(),
+            alias: Some(alias.to_string()),
+            description: Some("  ".to_string()),
+            ..ToolOverride::default()
+        };
+        let checked = check_override(rename(" issues "), &taken).unwrap();
+        assert_eq!(checked.alias.as_deref(), Some("issues"));
+        assert_eq!(checked.description, None);
+        assert!(check_override(rename("search"), &taken).is_err());
+        assert!(check_override(rename("list issues"), &taken).is_err());
+        assert!(check_override(rename(&"x".repeat(65)), &taken).is_err());
+        // Renaming a tool to its own name is no rename
+        assert!(is_noop(
+            &check_override(rename("list_issues"), &taken).unwrap()
+        ));
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        let has_table: bool = conn
+            .query_row(
+                "SELECT EXISTS(SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'workspace_tool_overrides')",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(has_table);
+    }
+}
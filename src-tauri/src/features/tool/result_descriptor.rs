@@ -0,0 +1,286 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Text payloads larger than this are not parsed again to look for JSON inside;
+/// they are classified from their first bytes only.
+const SAMPLE_BYTES: usize = 64 * 1024;
+const PREVIEW_CHARS: usize = 500;
+const MAX_TOP_LEVEL_KEYS: usize = 10;
+/// Number of array elements inspected when deciding whether a result is table-like.
+const TABLE_SAMPLE_ROWS: usize = 50;
+const MAX_COLUMNS: usize = 50;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ToolResultKind {
+    JsonObject,
+    JsonArray,
+    TableLike,
+    Text,
+    Image,
+    Empty,
+}
+
+/// Hints about the shape of a tool result, stored in the `tool_call` message
+/// metadata so the UI can pick a renderer without parsing the full result.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ToolResultDescriptor {
+    pub kind: ToolResultKind,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_level_keys: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub array_len: Option<usize>,
+    /// Column names for arrays of flat objects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub columns: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    pub byte_size: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preview: Option<String>,
+}
+
+impl ToolResultDescriptor {
+    /// Describe a tool result. `serialized` is the result as stored in the tool
+    /// message; it is only used for the size and the preview.
+    ///
+    /// MCP results (`{"content": [{"type": "text", ...}]}`) and internal tool
+    /// results (`{"content": "..."}`) are unwrapped first, so a JSON document
+    /// returned as text is described as JSON.
+    pub fn describe(result: &Value, serialized: &str) -> Self {
+        let byte_size = serialized.len();
+
+        if let Some(blocks) = result.get("content").and_then(Value::as_array) {
+            return Self::describe_content_blocks(blocks, byte_size);
+        }
+        if let Some(Value::String(text)) = result.get("content") {
+            if result.as_object().is_some_and(|o| o.len() == 1) {
+                return Self::describe_text(text, byte_size);
+            }
+        }
+
+        match result {
+            Value::String(text) => Self::describe_text(text, byte_size),
+            _ => Self::describe_value(result, byte_size, serialized),
+        }
+    }
+
+    fn describe_content_blocks(blocks: &[Value], byte_size: usize) -> Self {
+        if let Some(image) = blocks
+            .iter()
+            .find(|b| b.get("type").and_then(Value::as_str) == Some("image"))
+        {
+            return Self {
+                mime_type: image
+                    .get("mimeType")
+                    .and_then(Value::as_str)
+                    .map(str::to_string),
+                ..Self::empty(ToolResultKind::Image, byte_size)
+            };
+        }
+
+        let texts: Vec<&str> = blocks
+            .iter()
+            .filter_map(|b| b.get("text").and_then(Value::as_str))
+            .collect();
+        match texts.as_slice() {
+            [] => Self::empty(ToolResultKind::Empty, byte_size),
+            [text] => Self::describe_text(text, byte_size),
+            _ => Self::describe_text(&texts.join("\n"), byte_size),
+        }
+    }
+
+    fn describe_text(text: &str, byte_size: usize) -> Self {
+        let trimmed = text.trim();
+        if trimmed.is_empty() {
+            return Self::empty(ToolResultKind::Empty, byte_size);
+        }
+
+        if trimmed.starts_with("data:image/") {
+            let mime_type = trimmed
+                .strip_prefix("data:")
+                .and_then(|rest| rest.split(';').next())
+                .map(str::to_string);
+            return Self {
+                mime_type,
+                ..Self::empty(ToolResultKind::Image, byte_size)
+            };
+        }
+
+        if trimmed.starts_with('{') || trimmed.starts_with('[') {
+            if trimmed.len() <= SAMPLE_BYTES {
+                if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+                    return Self::describe_value(&value, byte_size, trimmed);
+                }
+            } else {
+                // Too large to parse again; trust the opening bracket
+                let kind = if trimmed.starts_with('{') {
+                    ToolResultKind::JsonObject
+                } else {
+                    ToolResultKind::JsonArray
+                };
+                return Self {
+                    preview: Some(Self::preview(trimmed)),
+                    ..Self::empty(kind, byte_size)
+                };
+            }
+        }
+
+        Self {
+            preview: Some(Self::preview(trimmed)),
+            ..Self::empty(ToolResultKind::Text, byte_size)
+        }
+    }
+
+    fn describe_value(value: &Value, byte_size: usize, serialized: &str) -> Self {
+        match value {
+            Value::Null => Self::empty(ToolResultKind::Empty, byte_size),
+            Value::Object(map) => Self {
+                top_level_keys: Some(map.keys().take(MAX_TOP_LEVEL_KEYS).cloned().collect()),
+                preview: Some(Self::preview(serialized)),
+                ..Self::empty(ToolResultKind::JsonObject, byte_size)
+            },
+            Value::Array(items) => {
+                let columns = Self::table_columns(items);
+                let kind = if columns.is_some() {
+                    ToolResultKind::TableLike
+                } else {
+                    ToolResultKind::JsonArray
+                };
+                Self {
+                    array_len: Some(items.len()),
+                    columns,
+                    preview: Some(Self::preview(serialized)),
+                    ..Self::empty(kind, byte_size)
+                }
+            }
+            Value::String(text) => Self::describe_text(text, byte_size),
+            Value::Bool(_) | Value::Number(_) => Self {
+                preview: Some(Self::preview(serialized)),
+                ..Self::empty(ToolResultKind::Text, byte_size)
+            },
+        }
+    }
+
+    /// Column names when the (sampled) array elements are all objects whose
+    /// values are scalars, in order of first appearance.
+    fn table_columns(items: &[Value]) -> Option<Vec<String>> {
+        if items.is_empty() {
+            return None;
+        }
+
+        let mut columns: Vec<String> = Vec::new();
+        for item in items.iter().take(TABLE_SAMPLE_ROWS) {
+            let row = item.as_object()?;
+            for (key, value) in row {
+                if value.is_object() || value.is_array() {
+                    return None;
+                }
+                if columns.len() < MAX_COLUMNS && !columns.contains(key) {
+                    columns.push(key.clone());
+                }
+            }
+        }
+
+        (!columns.is_empty()).then_some(columns)
+    }
+
+    fn preview(text: &str) -> String {
+        crate::lib::text::truncate(text, PREVIEW_CHARS, "")
+    }
+
+    const fn empty(kind: ToolResultKind, byte_size: usize) -> Self {
+        Self {
+            kind,
+            top_level_keys: None,
+            array_len: None,
+            columns: None,
+            mime_type: None,
+            byte_size,
+            preview: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn tool_result_descriptors_match_common_mcp_outputs() {
+        use crate::features::tool::result_descriptor::{ToolResultDescriptor, ToolResultKind};
+
+        let describe = |result: serde_json::Value| {
+            ToolResultDescriptor::describe(&result, &serde_json::to_string(&result).unwrap())
+        };
+        let text_block =
+            |text: String| serde_json::json!({ "content": [{ "type": "text", "text": text }] });
+
+        // GitHub list_issues: a JSON array returned as text, with nested users and labels
+        let issues = serde_json::json!([
+            {
+                "number": 412,
+                "title": "Crash on startup",
+                "state": "open",
+                "user": { "login": "octocat" },
+                "labels": [{ "name": "bug" }]
+            },
+            {
+                "number": 398,
+                "title": "Dark mode",
+                "state": "closed",
+                "user": { "login": "hubot" },
+                "labels": []
+            }
+        ]);
+        let descriptor = describe(text_block(issues.to_string()));
+        assert_eq!(descriptor.kind, ToolResultKind::JsonArray);
+        assert_eq!(descriptor.array_len, Some(2));
+        assert!(descriptor.columns.is_none());
+        assert!(descriptor.preview.unwrap().contains("Crash on startup"));
+
+        // The same issues trimmed to flat rows can be shown as a table
+        let rows = serde_json::json!([
+            { "number": 412, "title": "Crash on startup", "state": "open" },
+            { "number": 398, "title": "Dark mode", "state": "closed", "comments": 3 }
+        ]);
+        let descriptor = describe(text_block(rows.to_string()));
+        assert_eq!(descriptor.kind, ToolResultKind::TableLike);
+        assert_eq!(
+            descriptor.columns.unwrap(),
+            ["number", "title", "state", "comments"]
+        );
+
+        // Filesystem read_file: plain text, previewed up to 500 characters
+        let source = "fn main() {\n    println!(\"hello\");\n}\n".repeat(40);
+        let result = text_block(source.clone());
+        let serialized = serde_json::to_string(&result).unwrap();
+        let descriptor = ToolResultDescriptor::describe(&result, &serialized);
+        assert_eq!(descriptor.kind, ToolResultKind::Text);
+        assert_eq!(descriptor.byte_size, serialized.len());
+        let preview = descriptor.preview.unwrap();
+        assert_eq!(preview.chars().count(), 500);
+        assert!(source.trim().starts_with(&preview));
+        assert!(descriptor.top_level_keys.is_none());
+
+        // A screenshot: an image block next to its caption, never previewed
+        let descriptor = describe(serde_json::json!({
+            "content": [
+                { "type": "text", "text": "Screenshot of https://example.com" },
+                { "type": "image", "data": "iVBORw0KGgo=".repeat(1000), "mimeType": "image/png" }
+            ]
+        }));
+        assert_eq!(descriptor.kind, ToolResultKind::Image);
+        assert_eq!(descriptor.mime_type.as_deref(), Some("image/png"));
+        assert!(descriptor.preview.is_none());
+
+        // No text at all, and a huge array only sampled from its opening bracket
+        assert_eq!(
+            describe(serde_json::json!({ "content": [] })).kind,
+            ToolResultKind::Empty
+        );
+        let huge = format!("[{}1]", "1,".repeat(64 * 1024));
+        let descriptor = describe(text_block(huge));
+        assert_eq!(descriptor.kind, ToolResultKind::JsonArray);
+        assert!(descriptor.array_len.is_none());
+    }
+}
@@ -1,18 +1,36 @@
 use super::internal::InternalToolService;
 use super::mcp_client::MCPClientService;
+use super::mcp_resources::AttachedResource;
+use super::overrides::{self, ToolOverrides};
 use crate::error::AppError;
-use crate::features::mcp_connection::MCPConnectionService;
-use crate::features::tool::models::{MCPTool, UnifiedToolInfo};
-use crate::features::workspace::settings::WorkspaceSettingsService;
+use crate::features::app_settings::service::AppSettingsService;
+use crate::features::mcp_connection::{MCPConnectionService, MCPServerConnection};
+use crate::features::tool::models::{
+    MCPPrompt, MCPPromptResult, MCPResource, MCPResourceContent, MCPResourceTemplate, MCPTool,
+    ToolOverride, UnifiedToolInfo, WorkspaceToolInfo,
+};
+use crate::features::workspace::settings::{WorkspaceSettings, WorkspaceSettingsService};
 use crate::models::llm_types::ChatCompletionTool;
 use serde_json;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tauri::AppHandle;
 
+/// Builtin tools that work on the calling chat's scratchpad. They are executed
+/// by the chat service, which knows the chat, rather than by `execute_tool`.
+pub const SCRATCHPAD_TOOLS: &[&str] = &["read_scratchpad", "update_scratchpad"];
+
+/// Builtin tools that work on the local machine, run by `execute_tool`.
+pub const FILE_SYSTEM_TOOLS: &[&str] = &["read_file", "write_file", "list_dir", "run_command"];
+
+/// Builtin tools that reach the network, run by `execute_tool`.
+pub const WEB_TOOLS: &[&str] = &["fetch_url"];
+
 pub struct ToolService {
     app: AppHandle,
     mcp_connection_service: Arc<MCPConnectionService>,
     workspace_settings_service: Arc<WorkspaceSettingsService>,
+    app_settings_service: Arc<AppSettingsService>,
 }
 
 impl ToolService {
@@ -20,11 +38,13 @@ impl ToolService {
         app: AppHandle,
         mcp_connection_service: Arc<MCPConnectionService>,
         workspace_settings_service: Arc<WorkspaceSettingsService>,
+        app_settings_service: Arc<AppSettingsService>,
     ) -> Self {
         Self {
             app,
             mcp_connection_service,
             workspace_settings_service,
+            app_settings_service,
         }
     }
 
@@ -65,6 +85,7 @@ impl ToolService {
 
         // Read tools from cached tools_json instead of fetching from MCP server
         let mut all_tools = Vec::new();
+        let overrides = self.tool_overrides(workspace_id)?;
 
         // Add internal tools if enabled
         if workspace_settings.internal_tools_enabled == Some(1) {
@@ -104,7 +125,8 @@ impl ToolService {
                                 parameters: input_schema,
                             },
                         };
-                        all_tools.push(tool);
+                        // Hidden tools are left out, the rest shown as the workspace names them
+                        all_tools.extend(overrides.apply(tool));
                     }
                 }
             }
@@ -113,9 +135,11 @@ impl ToolService {
         Ok(all_tools)
     }
 
-    /// Execute an MCP tool
+    /// Execute an MCP tool. `tool_name` is the name `workspace_id` shows it
+    /// under.
     pub async fn execute_tool(
         &self,
+        workspace_id: &str,
         connection_id: &str,
         tool_name: &str,
         arguments: serde_json::Value,
@@ -130,48 +154,31 @@ impl ToolService {
         let start_time = std::time::Instant::now();
 
         if connection_id == "builtin" {
+            if tool_name == "fetch_url" {
+                return InternalToolService::fetch_url(arguments).await;
+            }
+            let settings = self.app_settings_service.get_builtin_tool_settings()?;
             return match tool_name {
-                "read_file" => InternalToolService::read_file(arguments).await,
-                "write_file" => InternalToolService::write_file(arguments).await,
-                "list_dir" => InternalToolService::list_dir(arguments).await,
-                "run_command" => InternalToolService::run_command(arguments, &self.app).await,
+                "read_file" => InternalToolService::read_file(arguments, &settings).await,
+                "write_file" => InternalToolService::write_file(arguments, &settings).await,
+                "list_dir" => InternalToolService::list_dir(arguments, &settings).await,
+                "run_command" => {
+                    InternalToolService::run_command(arguments, &settings, &self.app).await
+                }
                 _ => Err(AppError::Validation(format!(
                     "Unknown internal tool: {tool_name}"
                 ))),
             };
         }
 
-        // Get MCP connection
-        let connection = self
-            .mcp_connection_service
-            .get_by_id(connection_id)?
-            .ok_or_else(|| {
-                AppError::NotFound(format!("MCP connection not found: {connection_id}"))
-            })?;
-
-        if connection.status != "connected" {
-            return Err(AppError::Validation(format!(
-                "MCP connection is not connected: {connection_id}"
-            )));
-        }
-
-        // Parse headers
-        let headers = if connection.headers.is_empty() {
-            None
-        } else {
-            Some(connection.headers.clone())
-        };
-
+        let connection = self.connected(connection_id)?;
+        let server_tool_name = self.tool_overrides(workspace_id)?.original_name(tool_name);
         // Execute tool using MCP client service
-        let result_json = MCPClientService::call_tool(
+        let result_json = MCPClientService::call_connection_tool(
             &self.app,
-            connection.url,
-            connection.r#type,
-            headers,
-            connection.env_vars,
-            tool_name.to_string(),
+            &connection,
+            server_tool_name,
             arguments,
-            connection.runtime_path,
         )
         .await
         .map_err(|e| AppError::Generic(format!("Failed to execute tool {tool_name}: {e}")))?;
@@ -192,6 +199,85 @@ impl ToolService {
         Ok(result)
     }
 
+    /// The MCP connection `connection_id`, which must be connected.
+    fn connected(&self, connection_id: &str) -> Result<MCPServerConnection, AppError> {
+        let connection = self
+            .mcp_connection_service
+            .get_by_id(connection_id)?
+            .ok_or_else(|| {
+                AppError::NotFound(format!("MCP connection not found: {connection_id}"))
+            })?;
+
+        if connection.status != "connected" {
+            return Err(AppError::Validation(format!(
+                "MCP connection is not connected: {connection_id}"
+            )));
+        }
+        Ok(connection)
+    }
+
+    /// Resources the server of an MCP connection lists.
+    pub async fn list_resources(&self, connection_id: &str) -> Result<Vec<MCPResource>, AppError> {
+        let connection = self.connected(connection_id)?;
+        MCPClientService::list_resources(&self.app, &connection).await
+    }
+
+    /// Resource templates the server of an MCP connection offers.
+    pub async fn list_resource_templates(
+        &self,
+        connection_id: &str,
+    ) -> Result<Vec<MCPResourceTemplate>, AppError> {
+        let connection = self.connected(connection_id)?;
+        MCPClientService::list_resource_templates(&self.app, &connection).await
+    }
+
+    /// Read the resource at `uri` from the server of an MCP connection.
+    pub async fn read_resource(
+        &self,
+        connection_id: &str,
+        uri: String,
+    ) -> Result<Vec<MCPResourceContent>, AppError> {
+        let connection = self.connected(connection_id)?;
+        MCPClientService::read_resource(&self.app, &connection, uri).await
+    }
+
+    /// Read the resource at `uri` into the entry a message lists under
+    /// `mcpResources` in its metadata. `name` defaults to the URI.
+    pub async fn attach_resource(
+        &self,
+        connection_id: &str,
+        uri: String,
+        name: Option<String>,
+    ) -> Result<AttachedResource, AppError> {
+        let contents = self.read_resource(connection_id, uri.clone()).await?;
+        let name = name
+            .filter(|n| !n.trim().is_empty())
+            .unwrap_or_else(|| uri.clone());
+        Ok(AttachedResource::from_contents(
+            connection_id,
+            &uri,
+            &name,
+            &contents,
+        ))
+    }
+
+    /// Prompts the server of an MCP connection offers.
+    pub async fn list_prompts(&self, connection_id: &str) -> Result<Vec<MCPPrompt>, AppError> {
+        let connection = self.connected(connection_id)?;
+        MCPClientService::list_prompts(&self.app, &connection).await
+    }
+
+    /// The prompt `name` of an MCP connection, filled in with `arguments`.
+    pub async fn get_prompt(
+        &self,
+        connection_id: &str,
+        name: String,
+        arguments: std::collections::HashMap<String, String>,
+    ) -> Result<MCPPromptResult, AppError> {
+        let connection = self.connected(connection_id)?;
+        MCPClientService::get_prompt(&self.app, &connection, name, arguments).await
+    }
+
     /// Get a map of tool names to connection IDs for a workspace
     pub fn get_tool_to_connection_map(
         &self,
@@ -213,12 +299,26 @@ impl ToolService {
             std::collections::HashMap::new()
         };
 
+        // Keyed by the names the model calls, without hidden tools
+        let overrides = self.tool_overrides(workspace_id)?;
+        mcp_tool_map = mcp_tool_map
+            .into_iter()
+            .filter_map(|(name, connection_id)| {
+                overrides
+                    .exposed_name(&name)
+                    .map(|exposed| (exposed, connection_id))
+            })
+            .collect();
+
         // Add internal tools to map if enabled
         if workspace_settings.internal_tools_enabled == Some(1) {
-            mcp_tool_map.insert("read_file".to_string(), "builtin".to_string());
-            mcp_tool_map.insert("write_file".to_string(), "builtin".to_string());
-            mcp_tool_map.insert("list_dir".to_string(), "builtin".to_string());
-            mcp_tool_map.insert("run_command".to_string(), "builtin".to_string());
+            for name in FILE_SYSTEM_TOOLS
+                .iter()
+                .chain(SCRATCHPAD_TOOLS)
+                .chain(WEB_TOOLS)
+            {
+                mcp_tool_map.insert((*name).to_string(), "builtin".to_string());
+            }
         }
 
         Ok(mcp_tool_map)
@@ -260,7 +360,25 @@ impl ToolService {
                 name: "run_command".to_string(),
                 server_name: "System".to_string(),
                 description: Some(
-                    "Run a shell command. Uses the app process environment. Default cwd is the system temp directory.".to_string(),
+                    "Run a shell command. Uses the app process environment. Default cwd is the system temp directory. Only the cwd is kept to the allowed directories; the command can reach any path, so the user confirms every run.".to_string(),
+                ),
+            });
+            tools_info.push(UnifiedToolInfo {
+                name: "read_scratchpad".to_string(),
+                server_name: "System".to_string(),
+                description: Some("Read this chat's working notes".to_string()),
+            });
+            tools_info.push(UnifiedToolInfo {
+                name: "update_scratchpad".to_string(),
+                server_name: "System".to_string(),
+                description: Some("Replace or append to this chat's working notes".to_string()),
+            });
+            tools_info.push(UnifiedToolInfo {
+                name: "fetch_url".to_string(),
+                server_name: "System".to_string(),
+                description: Some(
+                    "Fetch an http(s) URL on a public address and return its body as text"
+                        .to_string(),
                 ),
             });
         }
@@ -274,6 +392,7 @@ impl ToolService {
             };
 
         if !mcp_tool_map.is_empty() {
+            let overrides = self.tool_overrides(workspace_id)?;
             let connection_ids: std::collections::HashSet<String> =
                 mcp_tool_map.values().cloned().collect();
             let all_connections = self.mcp_connection_service.get_all()?;
@@ -291,11 +410,15 @@ impl ToolService {
 
                 for mcp_tool in mcp_tools {
                     if let Some(selected_connection_id) = mcp_tool_map.get(&mcp_tool.name) {
-                        if selected_connection_id == &connection.id {
+                        if selected_connection_id != &connection.id {
+                            continue;
+                        }
+                        if let Some(name) = overrides.exposed_name(&mcp_tool.name) {
                             tools_info.push(UnifiedToolInfo {
-                                name: mcp_tool.name,
+                                name,
                                 server_name: connection.name.clone(),
-                                description: mcp_tool.description,
+                                description: overrides
+                                    .description(&mcp_tool.name, mcp_tool.description),
                             });
                         }
                     }
@@ -306,7 +429,104 @@ impl ToolService {
         Ok(tools_info)
     }
 
-    fn get_builtin_tools() -> Vec<ChatCompletionTool> {
+    /// How the workspace shows its MCP tools, where it changes anything.
+    fn tool_overrides(&self, workspace_id: &str) -> Result<ToolOverrides, AppError> {
+        Ok(ToolOverrides::new(
+            self.workspace_settings_service
+                .get_tool_overrides(workspace_id)?,
+        ))
+    }
+
+    /// The MCP tools selected for a workspace, parsed leniently.
+    fn selected_mcp_tools(
+        &self,
+        workspace_id: &str,
+    ) -> Result<(WorkspaceSettings, HashMap<String, String>), AppError> {
+        let workspace_settings = self
+            .workspace_settings_service
+            .get_by_workspace_id(workspace_id)?
+            .ok_or_else(|| AppError::Validation("Workspace settings not found".to_string()))?;
+        let mcp_tool_map = workspace_settings
+            .mcp_tool_ids
+            .as_deref()
+            .and_then(|ids_json| serde_json::from_str(ids_json).ok())
+            .unwrap_or_default();
+        Ok((workspace_settings, mcp_tool_map))
+    }
+
+    /// The MCP tools a workspace selected from its connected servers, with
+    /// how it shows each to the model.
+    pub fn get_workspace_tools(
+        &self,
+        workspace_id: &str,
+    ) -> Result<Vec<WorkspaceToolInfo>, AppError> {
+        let (_, mcp_tool_map) = self.selected_mcp_tools(workspace_id)?;
+        let overrides = self.tool_overrides(workspace_id)?;
+
+        let mut tools = Vec::new();
+        for connection in self.mcp_connection_service.get_all()? {
+            if connection.status != "connected" {
+                continue;
+            }
+            let mcp_tools: Vec<MCPTool> = connection
+                .tools_json
+                .as_deref()
+                .and_then(|tools_json| serde_json::from_str(tools_json).ok())
+                .unwrap_or_default();
+            for mcp_tool in mcp_tools {
+                if mcp_tool_map.get(&mcp_tool.name) != Some(&connection.id) {
+                    continue;
+                }
+                tools.push(WorkspaceToolInfo {
+                    tool_override: overrides.get(&mcp_tool.name),
+                    name: mcp_tool.name,
+                    connection_id: connection.id.clone(),
+                    server_name: connection.name.clone(),
+                    description: mcp_tool.description,
+                });
+            }
+        }
+        Ok(tools)
+    }
+
+    /// Hide, rename or redescribe one of a workspace's MCP tools. An alias
+    /// may not be the name another of its tools is shown under.
+    pub fn set_tool_override(
+        &self,
+        workspace_id: &str,
+        tool_override: ToolOverride,
+    ) -> Result<ToolOverride, AppError> {
+        let (workspace_settings, mcp_tool_map) = self.selected_mcp_tools(workspace_id)?;
+        if !mcp_tool_map.contains_key(&tool_override.tool_name) {
+            return Err(AppError::Validation(format!(
+                "Tool {} is not selected in this workspace",
+                tool_override.tool_name
+            )));
+        }
+
+        let overrides = self.tool_overrides(workspace_id)?;
+        let mut taken: HashSet<String> = mcp_tool_map
+            .keys()
+            .filter(|name| **name != tool_override.tool_name)
+            .filter_map(|name| overrides.exposed_name(name))
+            .collect();
+        if workspace_settings.internal_tools_enabled == Some(1) {
+            taken.extend(
+                FILE_SYSTEM_TOOLS
+                    .iter()
+                    .chain(SCRATCHPAD_TOOLS)
+                    .chain(WEB_TOOLS)
+                    .map(|name| (*name).to_string()),
+            );
+        }
+
+        let tool_override = overrides::check_override(tool_override, &taken)?;
+        self.workspace_settings_service
+            .save_tool_override(workspace_id, &tool_override)?;
+        Ok(tool_override)
+    }
+
+    pub fn get_builtin_tools() -> Vec<ChatCompletionTool> {
         vec![
             ChatCompletionTool {
                 r#type: "function".to_string(),
@@ -372,6 +592,7 @@ impl ToolService {
                     name: "run_command".to_string(),
                     description: Some(
                         "Run a shell command. Uses the app process environment. Default cwd is the system temp directory. \
+                        Only the cwd is kept to the allowed directories; the command itself can reach any path, so the user confirms every run. \
                         Examples: \
                         - { \"command\": \"ls\", \"args\": [\"-la\"], \"cwd\": \"/abs/path\" } \
                         - { \"command\": \"git\", \"args\": [\"status\"] } \
@@ -389,6 +610,59 @@ impl ToolService {
                     })),
                 },
             },
+            ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: crate::models::llm_types::ChatCompletionToolFunction {
+                    name: "read_scratchpad".to_string(),
+                    description: Some(
+                        "Read the scratchpad: persistent working notes for this chat (plan, open questions, findings). \
+                        It is also shown in the system prompt, possibly truncated.".to_string(),
+                    ),
+                    parameters: Some(serde_json::json!({
+                        "type": "object",
+                        "properties": {}
+                    })),
+                },
+            },
+            ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: crate::models::llm_types::ChatCompletionToolFunction {
+                    name: "update_scratchpad".to_string(),
+                    description: Some(
+                        "Update the scratchpad for this chat. Notes survive context truncation, so keep your plan and open questions here. \
+                        Examples: \
+                        - { \"content\": \"## Plan\\n1. ...\", \"mode\": \"replace\" } \
+                        - { \"content\": \"- found the config in src/app.ts\", \"mode\": \"append\" }".to_string(),
+                    ),
+                    parameters: Some(serde_json::json!({
+                        "type": "object",
+                        "properties": {
+                            "content": { "type": "string", "description": "Text to write" },
+                            "mode": { "type": "string", "enum": ["replace", "append"], "description": "Replace the whole scratchpad or append a line. Defaults to replace." }
+                        },
+                        "required": ["content"]
+                    })),
+                },
+            },
+            ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: crate::models::llm_types::ChatCompletionToolFunction {
+                    name: "fetch_url".to_string(),
+                    description: Some(
+                        "Fetch a web page or API with an HTTP GET and return its status, content type and body as text (cut off after 512 KB). \
+                        Only public addresses can be reached, not localhost or private networks. \
+                        Examples: \
+                        - { \"url\": \"https://example.com/docs/page.html\" }".to_string(),
+                    ),
+                    parameters: Some(serde_json::json!({
+                        "type": "object",
+                        "properties": {
+                            "url": { "type": "string", "description": "http or https URL to fetch" }
+                        },
+                        "required": ["url"]
+                    })),
+                },
+            },
         ]
     }
 }
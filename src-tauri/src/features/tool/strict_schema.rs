@@ -0,0 +1,586 @@
+//! Tool schemas for `OpenAI` strict function calling.
+//!
+//! Strict mode only accepts a subset of JSON Schema: every object lists all
+//! its properties in `required` and sets `additionalProperties: false`, and
+//! some keywords are not allowed. `to_strict` rewrites an MCP input schema
+//! into that subset. Optional properties become required but nullable, so
+//! the model sends `null` for an argument it leaves out; `drop_null_optionals`
+//! removes those again before the tool runs. Schemas that cannot be
+//! rewritten without changing what they accept are rejected, and the tool
+//! is offered without strict mode.
+
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+
+/// Deepest object nesting strict mode accepts.
+const MAX_DEPTH: usize = 10;
+/// Most properties a strict schema may have in total.
+const MAX_PROPERTIES: usize = 5000;
+/// Most values an `enum` may have.
+const MAX_ENUM_VALUES: usize = 1000;
+
+/// Keywords strict mode rejects.
+const UNSUPPORTED_KEYWORDS: &[&str] = &[
+    "allOf",
+    "oneOf",
+    "not",
+    "if",
+    "then",
+    "else",
+    "dependentRequired",
+    "dependentSchemas",
+    "patternProperties",
+    "unevaluatedProperties",
+    "unevaluatedItems",
+    "propertyNames",
+    "minProperties",
+    "maxProperties",
+    "contains",
+    "minContains",
+    "maxContains",
+    "uniqueItems",
+];
+
+/// Annotations dropped from strict schemas; a default is kept in the
+/// description instead.
+const DROPPED_ANNOTATIONS: &[&str] = &["$schema", "$id", "default", "examples"];
+
+/// The strict variant of a tool's input schema, or why there is none.
+pub fn to_strict(schema: &Value) -> Result<Value, String> {
+    let mut root = schema.clone();
+    let obj = root
+        .as_object_mut()
+        .ok_or_else(|| "schema is not an object".to_string())?;
+    if !obj.contains_key("type") && obj.contains_key("properties") {
+        obj.insert("type".to_string(), json!("object"));
+    }
+    if obj.get("type") != Some(&json!("object")) {
+        return Err("root schema is not an object".to_string());
+    }
+    obj.entry("properties").or_insert_with(|| json!({}));
+
+    strictify(&mut root, 1, "$")?;
+    let violations = strict_violations(&root);
+    if violations.is_empty() {
+        Ok(root)
+    } else {
+        Err(violations.join("; "))
+    }
+}
+
+fn strictify(node: &mut Value, depth: usize, path: &str) -> Result<(), String> {
+    if depth > MAX_DEPTH {
+        return Err(format!("{path}: nested deeper than {MAX_DEPTH} levels"));
+    }
+    let obj = node
+        .as_object_mut()
+        .ok_or_else(|| format!("{path}: schema is not an object"))?;
+    if let Some(keyword) = UNSUPPORTED_KEYWORDS.iter().find(|k| obj.contains_key(**k)) {
+        return Err(format!("{path}: `{keyword}` is not supported"));
+    }
+    keep_default_in_description(obj);
+    for annotation in DROPPED_ANNOTATIONS {
+        obj.remove(*annotation);
+    }
+
+    for defs_key in ["$defs", "definitions"] {
+        if let Some(defs) = obj.get_mut(defs_key).and_then(Value::as_object_mut) {
+            for (name, def) in defs.iter_mut() {
+                strictify(def, depth, &format!("{path}.{defs_key}.{name}"))?;
+            }
+        }
+    }
+    if let Some(variants) = obj.get_mut("anyOf").and_then(Value::as_array_mut) {
+        for (i, variant) in variants.iter_mut().enumerate() {
+            strictify(variant, depth, &format!("{path}.anyOf[{i}]"))?;
+        }
+    }
+
+    if has_type(obj, "object") {
+        match obj.get("additionalProperties") {
+            None | Some(Value::Bool(false)) => {}
+            Some(_) => return Err(format!("{path}: free-form object")),
+        }
+        let required: HashSet<String> = obj
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(|r| r.as_str().map(str::to_string))
+            .collect();
+        let properties = obj
+            .get_mut("properties")
+            .and_then(Value::as_object_mut)
+            .ok_or_else(|| format!("{path}: object without properties"))?;
+        for (name, property) in properties.iter_mut() {
+            strictify(property, depth + 1, &format!("{path}.{name}"))?;
+            if !required.contains(name) {
+                make_nullable(property);
+            }
+        }
+        let all: Vec<Value> = properties.keys().map(|k| json!(k)).collect();
+        obj.insert("required".to_string(), Value::Array(all));
+        obj.insert("additionalProperties".to_string(), json!(false));
+    }
+    if has_type(obj, "array") {
+        match obj.get_mut("items") {
+            Some(items @ Value::Object(_)) => strictify(items, depth + 1, &format!("{path}[]"))?,
+            Some(_) => return Err(format!("{path}: tuple arrays are not supported")),
+            None => return Err(format!("{path}: array without items")),
+        }
+    }
+    Ok(())
+}
+
+/// Whether a schema's `type` is or includes `name`.
+fn has_type(obj: &Map<String, Value>, name: &str) -> bool {
+    match obj.get("type") {
+        Some(Value::String(t)) => t == name,
+        Some(Value::Array(types)) => types.iter().any(|t| t == name),
+        _ => false,
+    }
+}
+
+fn keep_default_in_description(obj: &mut Map<String, Value>) {
+    let Some(default) = obj.get("default").filter(|d| !d.is_null()) else {
+        return;
+    };
+    let note = format!("Default: {default}.");
+    let description = match obj.get("description").and_then(Value::as_str) {
+        Some(d) if !d.trim().is_empty() => format!("{} {note}", d.trim_end()),
+        _ => note,
+    };
+    obj.insert("description".to_string(), json!(description));
+}
+
+/// Let a schema also accept `null`.
+fn make_nullable(schema: &mut Value) {
+    let Some(obj) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(values) = obj.get_mut("enum").and_then(Value::as_array_mut) {
+        if !values.contains(&Value::Null) {
+            values.push(Value::Null);
+        }
+    }
+    match obj.get_mut("type") {
+        Some(t @ Value::String(_)) => {
+            if *t != "null" {
+                *t = json!([t.take(), "null"]);
+            }
+        }
+        Some(Value::Array(types)) => {
+            if !types.contains(&json!("null")) {
+                types.push(json!("null"));
+            }
+        }
+        _ => {
+            if let Some(variants) = obj.get_mut("anyOf").and_then(Value::as_array_mut) {
+                if !variants
+                    .iter()
+                    .any(|v| v.get("type") == Some(&json!("null")))
+                {
+                    variants.push(json!({ "type": "null" }));
+                }
+            } else if !obj.contains_key("enum") {
+                // A $ref or const: accept it or null
+                let inner = std::mem::take(schema);
+                *schema = json!({ "anyOf": [inner, { "type": "null" }] });
+            }
+        }
+    }
+}
+
+/// Where a schema breaks the strict mode rules; empty when it follows them.
+pub fn strict_violations(schema: &Value) -> Vec<String> {
+    let mut violations = Vec::new();
+    if schema.get("type") != Some(&json!("object")) {
+        violations.push("$: root schema is not an object".to_string());
+    }
+    if schema.get("anyOf").is_some() {
+        violations.push("$: root schema uses anyOf".to_string());
+    }
+    let mut properties = 0;
+    check_strict(schema, 1, "$", &mut properties, &mut violations);
+    if properties > MAX_PROPERTIES {
+        violations.push(format!("$: more than {MAX_PROPERTIES} properties"));
+    }
+    violations
+}
+
+fn check_strict(
+    node: &Value,
+    depth: usize,
+    path: &str,
+    properties: &mut usize,
+    violations: &mut Vec<String>,
+) {
+    let Some(obj) = node.as_object() else {
+        violations.push(format!("{path}: schema is not an object"));
+        return;
+    };
+    if depth > MAX_DEPTH {
+        violations.push(format!("{path}: nested deeper than {MAX_DEPTH} levels"));
+    }
+    for keyword in UNSUPPORTED_KEYWORDS
+        .iter()
+        .chain(DROPPED_ANNOTATIONS)
+        .filter(|k| obj.contains_key(**k))
+    {
+        violations.push(format!("{path}: `{keyword}` is not allowed"));
+    }
+    if !["type", "anyOf", "$ref", "enum", "const"]
+        .iter()
+        .any(|k| obj.contains_key(*k))
+    {
+        violations.push(format!("{path}: schema has no type"));
+    }
+    if obj
+        .get("enum")
+        .and_then(Value::as_array)
+        .is_some_and(|values| values.len() > MAX_ENUM_VALUES)
+    {
+        violations.push(format!("{path}: more than {MAX_ENUM_VALUES} enum values"));
+    }
+
+    for defs_key in ["$defs", "definitions"] {
+        for (name, def) in obj
+            .get(defs_key)
+            .and_then(Value::as_object)
+            .into_iter()
+            .flatten()
+        {
+            check_strict(
+                def,
+                depth,
+                &format!("{path}.{defs_key}.{name}"),
+                properties,
+                violations,
+            );
+        }
+    }
+    for (i, variant) in obj
+        .get("anyOf")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        check_strict(
+            variant,
+            depth,
+            &format!("{path}.anyOf[{i}]"),
+            properties,
+            violations,
+        );
+    }
+
+    if has_type(obj, "object") {
+        if obj.get("additionalProperties") != Some(&json!(false)) {
+            violations.push(format!("{path}: additionalProperties is not false"));
+        }
+        let props = obj.get("properties").and_then(Value::as_object);
+        let required: HashSet<&str> = obj
+            .get("required")
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .collect();
+        let keys: HashSet<&str> = props
+            .into_iter()
+            .flatten()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        if props.is_none() {
+            violations.push(format!("{path}: object without properties"));
+        } else if required != keys {
+            violations.push(format!("{path}: required does not list every property"));
+        }
+        for (name, property) in props.into_iter().flatten() {
+            *properties += 1;
+            check_strict(
+                property,
+                depth + 1,
+                &format!("{path}.{name}"),
+                properties,
+                violations,
+            );
+        }
+    }
+    if has_type(obj, "array") {
+        match obj.get("items") {
+            Some(items) => check_strict(
+                items,
+                depth + 1,
+                &format!("{path}[]"),
+                properties,
+                violations,
+            ),
+            None => violations.push(format!("{path}: array without items")),
+        }
+    }
+}
+
+/// Remove the `null`s a model sent for arguments `original` does not
+/// require, as if it had left them out.
+pub fn drop_null_optionals(arguments: &mut Value, original: &Value) {
+    let (Some(args), Some(properties)) = (
+        arguments.as_object_mut(),
+        original.get("properties").and_then(Value::as_object),
+    ) else {
+        return;
+    };
+    let required: HashSet<&str> = original
+        .get("required")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    args.retain(|name, value| !value.is_null() || required.contains(name.as_str()));
+    for (name, value) in args.iter_mut() {
+        let Some(property) = properties.get(name) else {
+            continue;
+        };
+        match value {
+            Value::Object(_) => drop_null_optionals(value, property),
+            Value::Array(items) => {
+                if let Some(item_schema) = property.get("items") {
+                    for item in items {
+                        drop_null_optionals(item, item_schema);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Input schemas as real MCP servers publish them.
+    fn mcp_tool_schemas() -> Vec<(&'static str, serde_json::Value)> {
+        use serde_json::json;
+        vec![
+            (
+                "read_file",
+                json!({
+                    "type": "object",
+                    "properties": { "path": { "type": "string" } },
+                    "required": ["path"]
+                }),
+            ),
+            (
+                "brave_web_search",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "query": { "type": "string", "description": "Search query" },
+                        "count": { "type": "number", "description": "Number of results", "default": 10 },
+                        "freshness": { "type": "string", "enum": ["pd", "pw", "pm"] }
+                    },
+                    "required": ["query"]
+                }),
+            ),
+            (
+                "create_issue",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "owner": { "type": "string" },
+                        "repo": { "type": "string" },
+                        "title": { "type": "string" },
+                        "body": { "type": "string" },
+                        "assignees": { "type": "array", "items": { "type": "string" } },
+                        "milestone": { "type": "number" }
+                    },
+                    "required": ["owner", "repo", "title"]
+                }),
+            ),
+            (
+                "puppeteer_screenshot",
+                json!({
+                    "type": "object",
+                    "properties": {
+                        "name": { "type": "string" },
+                        "selector": { "type": "string" },
+                        "clip": {
+                            "type": "object",
+                            "properties": {
+                                "width": { "type": "number" },
+                                "height": { "type": "number" }
+                            },
+                            "required": ["width"]
+                        }
+                    },
+                    "required": ["name"]
+                }),
+            ),
+            (
+                "zod_edit_file",
+                json!({
+                    "$schema": "http://json-schema.org/draft-07/schema#",
+                    "type": "object",
+                    "properties": {
+                        "path": { "type": "string" },
+                        "edits": {
+                            "type": "array",
+                            "items": {
+                                "type": "object",
+                                "properties": {
+                                    "oldText": { "type": "string" },
+                                    "newText": { "type": "string" }
+                                },
+                                "required": ["oldText", "newText"],
+                                "additionalProperties": false
+                            }
+                        },
+                        "dryRun": { "type": "boolean", "default": false }
+                    },
+                    "required": ["path", "edits"],
+                    "additionalProperties": false
+                }),
+            ),
+        ]
+    }
+
+    #[test]
+    fn mcp_tool_schemas_convert_to_strict_mode() {
+        use crate::features::tool::strict_schema::{strict_violations, to_strict};
+        use serde_json::json;
+
+        for (name, schema) in mcp_tool_schemas() {
+            assert!(
+                !strict_violations(&schema).is_empty(),
+                "{name} already strict"
+            );
+            let strict = to_strict(&schema).unwrap_or_else(|e| panic!("{name}: {e}"));
+            assert_eq!(strict_violations(&strict), Vec::<String>::new(), "{name}");
+        }
+
+        let schemas: std::collections::HashMap<_, _> = mcp_tool_schemas().into_iter().collect();
+        // Optional properties are required but accept null
+        let search = to_strict(&schemas["brave_web_search"]).unwrap();
+        let mut required: Vec<&str> = search["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter_map(|r| r.as_str())
+            .collect();
+        required.sort_unstable();
+        assert_eq!(required, ["count", "freshness", "query"]);
+        assert_eq!(search["properties"]["query"]["type"], json!("string"));
+        assert_eq!(
+            search["properties"]["count"]["type"],
+            json!(["number", "null"])
+        );
+        assert_eq!(
+            search["properties"]["count"]["description"],
+            json!("Number of results Default: 10.")
+        );
+        assert_eq!(
+            search["properties"]["freshness"]["enum"],
+            json!(["pd", "pw", "pm", null])
+        );
+        let screenshot = to_strict(&schemas["puppeteer_screenshot"]).unwrap();
+        let clip = &screenshot["properties"]["clip"];
+        assert_eq!(clip["type"], json!(["object", "null"]));
+        assert_eq!(clip["additionalProperties"], json!(false));
+        assert_eq!(
+            clip["properties"]["height"]["type"],
+            json!(["number", "null"])
+        );
+
+        // Schemas strict mode cannot express are left alone
+        let headers = json!({
+            "type": "object",
+            "properties": {
+                "url": { "type": "string" },
+                "headers": { "type": "object", "additionalProperties": { "type": "string" } }
+            },
+            "required": ["url"]
+        });
+        assert!(to_strict(&headers)
+            .unwrap_err()
+            .contains("free-form object"));
+        let one_of = json!({
+            "type": "object",
+            "properties": { "id": { "oneOf": [{ "type": "string" }, { "type": "number" }] } }
+        });
+        assert!(to_strict(&one_of).is_err());
+        assert!(to_strict(&json!({ "type": "string" })).is_err());
+    }
+
+    #[test]
+    fn strict_nulls_are_dropped_from_tool_arguments() {
+        use crate::features::tool::strict_schema::drop_null_optionals;
+        use serde_json::json;
+
+        let schemas: std::collections::HashMap<_, _> = mcp_tool_schemas().into_iter().collect();
+        let mut arguments = json!({
+            "name": "home",
+            "selector": null,
+            "clip": { "width": 800, "height": null }
+        });
+        drop_null_optionals(&mut arguments, &schemas["puppeteer_screenshot"]);
+        assert_eq!(
+            arguments,
+            json!({ "name": "home", "clip": { "width": 800 } })
+        );
+
+        // A required argument keeps an explicit null
+        let schema = json!({
+            "type": "object",
+            "properties": { "note": { "type": ["string", "null"] } },
+            "required": ["note"]
+        });
+        let mut arguments = json!({ "note": null });
+        drop_null_optionals(&mut arguments, &schema);
+        assert_eq!(arguments, json!({ "note": null }));
+    }
+
+    #[test]
+    fn openai_strict_tools_fall_back_per_tool() {
+        use crate::models::llm_types::{ChatCompletionTool, ChatCompletionToolFunction};
+        use crate::services::llm::providers::openai::OpenAIProvider;
+        use serde_json::json;
+
+        let tool = |name: &str, parameters: serde_json::Value| ChatCompletionTool {
+            r#type: "function".to_string(),
+            function: ChatCompletionToolFunction {
+                name: name.to_string(),
+                description: Some(format!("{name} tool")),
+                parameters: Some(parameters),
+            },
+        };
+        let schemas: std::collections::HashMap<_, _> = mcp_tool_schemas().into_iter().collect();
+        let free_form = json!({
+            "type": "object",
+            "properties": { "headers": { "type": "object" } }
+        });
+        let tools = vec![
+            tool("create_issue", schemas["create_issue"].clone()),
+            tool("fetch", free_form.clone()),
+        ];
+
+        let (sent, strict_schemas) = OpenAIProvider::responses_tools(tools.clone(), true);
+        assert_eq!(sent[0]["type"], json!("function"));
+        assert_eq!(sent[0]["name"], json!("create_issue"));
+        assert_eq!(sent[0]["strict"], json!(true));
+        assert_eq!(sent[0]["parameters"]["additionalProperties"], json!(false));
+        assert_eq!(sent[1]["strict"], json!(false));
+        assert_eq!(sent[1]["parameters"], free_form);
+        // The original schema is kept to clean up the model's arguments
+        assert_eq!(
+            strict_schemas.keys().collect::<Vec<_>>(),
+            [&"create_issue".to_string()]
+        );
+        assert_eq!(strict_schemas["create_issue"], schemas["create_issue"]);
+
+        let (sent, strict_schemas) = OpenAIProvider::responses_tools(tools, false);
+        assert!(sent.iter().all(|t| t["strict"] == json!(false)));
+        assert_eq!(sent[0]["parameters"], schemas["create_issue"]);
+        assert!(strict_schemas.is_empty());
+    }
+}
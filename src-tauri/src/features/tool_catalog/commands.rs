@@ -0,0 +1,14 @@
+use super::models::ToolCatalogChange;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// Changes to the tools of the workspace's MCP servers, newest first.
+#[tauri::command]
+pub async fn get_tool_catalog_history(
+    workspace_id: String,
+    limit: Option<u32>,
+    state: State<'_, AppState>,
+) -> Result<Vec<ToolCatalogChange>, AppError> {
+    state.tool_catalog_service.get_history(&workspace_id, limit)
+}
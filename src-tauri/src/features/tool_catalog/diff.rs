@@ -0,0 +1,143 @@
+//! Comparing two tool catalogs of an MCP server.
+//!
+//! Schemas are compared by hash. The hash is taken over a canonical form of
+//! the schema JSON, with object keys sorted and no whitespace, so a server
+//! that sends the same schema with its keys in another order does not count
+//! as a change.
+
+use super::models::{ChangedToolSchema, ToolCatalogDiff};
+use crate::features::tool::models::MCPTool;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+/// Hex digits of a schema hash kept.
+const SCHEMA_HASH_LEN: usize = 16;
+
+/// `value` as JSON with the keys of every object sorted.
+pub fn canonical_json(value: &serde_json::Value) -> String {
+    match value {
+        serde_json::Value::Object(map) => {
+            let fields: BTreeMap<&String, String> = map
+                .iter()
+                .map(|(key, value)| (key, canonical_json(value)))
+                .collect();
+            let fields: Vec<String> = fields
+                .into_iter()
+                .map(|(key, value)| format!("{}:{value}", serde_json::Value::from(key.as_str())))
+                .collect();
+            format!("{{{}}}", fields.join(","))
+        }
+        serde_json::Value::Array(items) => {
+            let items: Vec<String> = items.iter().map(canonical_json).collect();
+            format!("[{}]", items.join(","))
+        }
+        other => other.to_string(),
+    }
+}
+
+/// Hash of a tool's input schema. A schema that is not valid JSON is hashed
+/// as it is; a tool without a schema hashes like `null`.
+pub fn schema_hash(input_schema: Option<&str>) -> String {
+    let canonical = match input_schema.map(serde_json::from_str::<serde_json::Value>) {
+        Some(Ok(value)) => canonical_json(&value),
+        Some(Err(_)) => input_schema.unwrap_or_default().to_string(),
+        None => serde_json::Value::Null.to_string(),
+    };
+    let digest = hex::encode(Sha256::digest(canonical.as_bytes()));
+    digest[..SCHEMA_HASH_LEN].to_string()
+}
+
+/// Tools added, removed and with a changed schema, each sorted by name.
+pub fn diff_catalogs(old: &[MCPTool], new: &[MCPTool]) -> ToolCatalogDiff {
+    let hashes = |tools: &[MCPTool]| -> BTreeMap<String, String> {
+        tools
+            .iter()
+            .map(|tool| (tool.name.clone(), schema_hash(tool.input_schema.as_deref())))
+            .collect()
+    };
+    let old = hashes(old);
+    let new = hashes(new);
+
+    let mut diff = ToolCatalogDiff::default();
+    for (name, new_hash) in &new {
+        match old.get(name) {
+            None => diff.added.push(name.clone()),
+            Some(old_hash) if old_hash != new_hash => diff.changed.push(ChangedToolSchema {
+                name: name.clone(),
+                old_hash: old_hash.clone(),
+                new_hash: new_hash.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed = old
+        .into_keys()
+        .filter(|name| !new.contains_key(name))
+        .collect();
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    fn catalog_tool(name: &str, input_schema: &str) -> crate::features::tool::models::MCPTool {
+        crate::features::tool::models::MCPTool {
+            name: name.to_string(),
+            description: None,
+            input_schema: Some(input_schema.to_string()),
+        }
+    }
+
+    #[test]
+    fn tool_catalog_rename_is_a_removal_and_an_addition() {
+        use crate::features::tool_catalog::diff::diff_catalogs;
+
+        let schema = r#"{"type":"object","properties":{"query":{"type":"string"}}}"#;
+        let old = vec![catalog_tool("search", schema), catalog_tool("fetch", "{}")];
+        let new = vec![
+            catalog_tool("fetch", "{}"),
+            catalog_tool("web_search", schema),
+        ];
+
+        let diff = diff_catalogs(&old, &new);
+        assert_eq!(diff.added, vec!["web_search".to_string()]);
+        assert_eq!(diff.removed, vec!["search".to_string()]);
+        assert!(diff.changed.is_empty());
+        assert!(diff_catalogs(&new, &new).is_empty());
+    }
+
+    #[test]
+    fn tool_catalog_schema_change_is_detected_but_key_order_is_not() {
+        use crate::features::tool_catalog::diff::{diff_catalogs, schema_hash};
+
+        let old = vec![catalog_tool(
+            "search",
+            r#"{"type":"object","properties":{"query":{"type":"string"},"limit":{"type":"integer"}},"required":["query"]}"#,
+        )];
+        let reordered = vec![catalog_tool(
+            "search",
+            r#"{ "required": ["query"], "properties": { "limit": {"type": "integer"}, "query": {"type": "string"} }, "type": "object" }"#,
+        )];
+        assert!(diff_catalogs(&old, &reordered).is_empty());
+
+        // Array order is meaningful and is kept
+        let changed = vec![catalog_tool(
+            "search",
+            r#"{"type":"object","properties":{"query":{"type":"string"},"limit":{"type":"string"}},"required":["query"]}"#,
+        )];
+        let diff = diff_catalogs(&old, &changed);
+        assert!(diff.added.is_empty() && diff.removed.is_empty());
+        assert_eq!(diff.changed.len(), 1);
+        let change = &diff.changed[0];
+        assert_eq!(change.name, "search");
+        assert_eq!(change.old_hash, schema_hash(old[0].input_schema.as_deref()));
+        assert_eq!(
+            change.new_hash,
+            schema_hash(changed[0].input_schema.as_deref())
+        );
+        assert_ne!(change.old_hash, change.new_hash);
+        assert_ne!(
+            schema_hash(Some(r#"["a","b"]"#)),
+            schema_hash(Some(r#"["b","a"]"#))
+        );
+    }
+}
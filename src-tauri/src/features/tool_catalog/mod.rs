@@ -0,0 +1,9 @@
+pub mod commands;
+pub mod diff;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
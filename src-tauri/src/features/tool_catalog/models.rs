@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+
+/// A tool whose input schema changed, with the hashes of both schemas.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ChangedToolSchema {
+    pub name: String,
+    pub old_hash: String,
+    pub new_hash: String,
+}
+
+/// What changed between two catalogs of one MCP server. A renamed tool shows
+/// up as removed under its old name and added under the new one.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct ToolCatalogDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedToolSchema>,
+}
+
+impl ToolCatalogDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// A change to the tools of an MCP server a workspace uses.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolCatalogChange {
+    pub id: String,
+    pub workspace_id: String,
+    pub connection_id: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<ChangedToolSchema>,
+    pub created_at: i64,
+}
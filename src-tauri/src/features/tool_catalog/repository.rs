@@ -0,0 +1,226 @@
+use super::models::ToolCatalogChange;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait ToolCatalogRepository: Send + Sync {
+    /// Insert a change and drop the workspace's oldest changes beyond `cap`.
+    fn create(&self, change: &ToolCatalogChange, cap: u32) -> Result<(), AppError>;
+    fn get_by_workspace_id(
+        &self,
+        workspace_id: &str,
+        limit: u32,
+    ) -> Result<Vec<ToolCatalogChange>, AppError>;
+    fn latest(&self, workspace_id: &str) -> Result<Option<ToolCatalogChange>, AppError>;
+    fn workspaces_using_connection(&self, connection_id: &str) -> Result<Vec<String>, AppError>;
+    /// The tools the server had the last time they were fetched, as JSON.
+    fn get_snapshot(&self, connection_id: &str) -> Result<Option<String>, AppError>;
+    fn save_snapshot(&self, connection_id: &str, tools_json: &str) -> Result<(), AppError>;
+}
+
+pub struct SqliteToolCatalogRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteToolCatalogRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl ToolCatalogRepository for SqliteToolCatalogRepository {
+    fn create(&self, change: &ToolCatalogChange, cap: u32) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        insert_change(&conn, change, cap)
+    }
+
+    fn get_by_workspace_id(
+        &self,
+        workspace_id: &str,
+        limit: u32,
+    ) -> Result<Vec<ToolCatalogChange>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        list_changes(&conn, workspace_id, limit)
+    }
+
+    fn latest(&self, workspace_id: &str) -> Result<Option<ToolCatalogChange>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        Ok(list_changes(&conn, workspace_id, 1)?.into_iter().next())
+    }
+
+    fn workspaces_using_connection(&self, connection_id: &str) -> Result<Vec<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        workspaces_using_connection(&conn, connection_id)
+    }
+
+    fn get_snapshot(&self, connection_id: &str) -> Result<Option<String>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let tools_json = conn
+            .query_row(
+                "SELECT tools_json FROM tool_catalog_snapshots WHERE connection_id = ?1",
+                params![connection_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        Ok(tools_json)
+    }
+
+    fn save_snapshot(&self, connection_id: &str, tools_json: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute(
+            "INSERT INTO tool_catalog_snapshots (connection_id, tools_json, updated_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(connection_id) DO UPDATE SET tools_json = ?2, updated_at = ?3",
+            params![
+                connection_id,
+                tools_json,
+                chrono::Utc::now().timestamp_millis()
+            ],
+        )?;
+        Ok(())
+    }
+}
+
+pub fn insert_change(
+    conn: &Connection,
+    change: &ToolCatalogChange,
+    cap: u32,
+) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO tool_catalog_history (id, workspace_id, connection_id, added, removed, changed, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            change.id,
+            change.workspace_id,
+            change.connection_id,
+            serde_json::to_string(&change.added)?,
+            serde_json::to_string(&change.removed)?,
+            serde_json::to_string(&change.changed)?,
+            change.created_at
+        ],
+    )?;
+    conn.execute(
+        "DELETE FROM tool_catalog_history WHERE workspace_id = ?1 AND id NOT IN (
+            SELECT id FROM tool_catalog_history WHERE workspace_id = ?1
+            ORDER BY created_at DESC, rowid DESC LIMIT ?2
+        )",
+        params![change.workspace_id, cap],
+    )?;
+    Ok(())
+}
+
+/// Newest changes first.
+pub fn list_changes(
+    conn: &Connection,
+    workspace_id: &str,
+    limit: u32,
+) -> Result<Vec<ToolCatalogChange>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT id, workspace_id, connection_id, added, removed, changed, created_at
+         FROM tool_catalog_history
+         WHERE workspace_id = ?1
+         ORDER BY created_at DESC, rowid DESC
+         LIMIT ?2",
+    )?;
+    let rows = stmt
+        .query_map(params![workspace_id, limit], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, i64>(6)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    rows.into_iter()
+        .map(
+            |(id, workspace_id, connection_id, added, removed, changed, created_at)| {
+                Ok(ToolCatalogChange {
+                    id,
+                    workspace_id,
+                    connection_id,
+                    added: serde_json::from_str(&added)?,
+                    removed: serde_json::from_str(&removed)?,
+                    changed: serde_json::from_str(&changed)?,
+                    created_at,
+                })
+            },
+        )
+        .collect()
+}
+
+/// Workspaces that take at least one tool from the MCP server.
+pub fn workspaces_using_connection(
+    conn: &Connection,
+    connection_id: &str,
+) -> Result<Vec<String>, AppError> {
+    let mut stmt = conn.prepare(
+        "SELECT workspace_id, mcp_tool_ids FROM workspace_settings WHERE mcp_tool_ids IS NOT NULL",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(rows
+        .into_iter()
+        .filter(|(_, tool_ids)| {
+            serde_json::from_str::<HashMap<String, String>>(tool_ids)
+                .is_ok_and(|tools| tools.values().any(|id| id == connection_id))
+        })
+        .map(|(workspace_id, _)| workspace_id)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    #[test]
+    fn tool_catalog_history_is_capped_per_workspace() {
+        use crate::features::tool_catalog::{
+            insert_change, list_changes, workspaces_using_connection, ToolCatalogChange,
+        };
+
+        let conn = migrated_db();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0), ('w2', 'Home', 0);
+             INSERT INTO workspace_settings (workspace_id, mcp_tool_ids, created_at, updated_at)
+                 VALUES ('w1', '{\"search\":\"mcp-1\"}', 0, 0), ('w2', '{\"fetch\":\"mcp-2\"}', 0, 0);",
+        )
+        .unwrap();
+        assert_eq!(
+            workspaces_using_connection(&conn, "mcp-1").unwrap(),
+            vec!["w1".to_string()]
+        );
+
+        for (workspace_id, created_at) in [("w1", 1), ("w1", 2), ("w1", 3), ("w2", 1)] {
+            let change = ToolCatalogChange {
+                id: format!("{workspace_id}-{created_at}"),
+                workspace_id: workspace_id.to_string(),
+                connection_id: "mcp-1".to_string(),
+                added: vec!["web_search".to_string()],
+                removed: vec!["search".to_string()],
+                changed: Vec::new(),
+                created_at,
+            };
+            insert_change(&conn, &change, 2).unwrap();
+        }
+
+        let w1: Vec<String> = list_changes(&conn, "w1", 10)
+            .unwrap()
+            .into_iter()
+            .map(|change| change.id)
+            .collect();
+        assert_eq!(w1, vec!["w1-3".to_string(), "w1-2".to_string()]);
+        let w2 = list_changes(&conn, "w2", 10).unwrap();
+        assert_eq!(w2.len(), 1);
+        assert_eq!(w2[0].added, vec!["web_search".to_string()]);
+    }
+}
@@ -0,0 +1,105 @@
+use super::diff::diff_catalogs;
+use super::models::ToolCatalogChange;
+use super::repository::ToolCatalogRepository;
+use crate::error::AppError;
+use crate::features::tool::models::MCPTool;
+use std::sync::Arc;
+
+/// Changes kept per workspace; older ones are pruned on insert.
+pub const TOOL_CATALOG_HISTORY_CAP: u32 = 200;
+const DEFAULT_PAGE_SIZE: u32 = 50;
+/// Message metadata key of the last catalog change before a turn.
+pub const TOOL_CATALOG_METADATA_KEY: &str = "toolCatalogChange";
+
+pub struct ToolCatalogService {
+    repository: Arc<dyn ToolCatalogRepository>,
+}
+
+impl ToolCatalogService {
+    pub const fn new(repository: Arc<dyn ToolCatalogRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// Compare the tools an MCP server now has, as `tools_json`, with the
+    /// ones it had when they were last fetched, and record what changed for
+    /// every workspace using the server. The first catalog of a server is not
+    /// a change. The comparison survives disconnects, which clear the tools
+    /// stored on the connection.
+    pub fn record_refresh(
+        &self,
+        connection_id: &str,
+        tools_json: &str,
+    ) -> Result<Vec<ToolCatalogChange>, AppError> {
+        let new: Vec<MCPTool> = serde_json::from_str(tools_json)?;
+        let previous = self.repository.get_snapshot(connection_id)?;
+        self.repository.save_snapshot(connection_id, tools_json)?;
+        let Some(previous) = previous else {
+            return Ok(Vec::new());
+        };
+        let old: Vec<MCPTool> = serde_json::from_str(&previous)?;
+        let diff = diff_catalogs(&old, &new);
+        if diff.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let created_at = chrono::Utc::now().timestamp_millis();
+        let mut changes = Vec::new();
+        for workspace_id in self.repository.workspaces_using_connection(connection_id)? {
+            let change = ToolCatalogChange {
+                id: uuid::Uuid::new_v4().to_string(),
+                workspace_id,
+                connection_id: connection_id.to_string(),
+                added: diff.added.clone(),
+                removed: diff.removed.clone(),
+                changed: diff.changed.clone(),
+                created_at,
+            };
+            self.repository.create(&change, TOOL_CATALOG_HISTORY_CAP)?;
+            changes.push(change);
+        }
+        tracing::info!(
+            connection_id = %connection_id,
+            added = diff.added.len(),
+            removed = diff.removed.len(),
+            changed = diff.changed.len(),
+            workspaces = changes.len(),
+            "MCP tool catalog changed"
+        );
+        Ok(changes)
+    }
+
+    /// Like `record_refresh`, for callers that only log a failure.
+    pub fn record_refresh_logged(&self, connection_id: &str, tools_json: &str) {
+        if let Err(e) = self.record_refresh(connection_id, tools_json) {
+            tracing::warn!(connection_id = %connection_id, error = %e, "Failed to record tool catalog change");
+        }
+    }
+
+    /// Newest changes first.
+    pub fn get_history(
+        &self,
+        workspace_id: &str,
+        limit: Option<u32>,
+    ) -> Result<Vec<ToolCatalogChange>, AppError> {
+        self.repository.get_by_workspace_id(
+            workspace_id,
+            limit
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .min(TOOL_CATALOG_HISTORY_CAP),
+        )
+    }
+
+    /// The `toolCatalogChange` metadata of a turn: the workspace's latest
+    /// change, if it has any.
+    pub fn turn_metadata(&self, workspace_id: &str) -> Option<serde_json::Value> {
+        match self.repository.latest(workspace_id) {
+            Ok(change) => change.map(
+                |change| serde_json::json!({ "id": change.id, "createdAt": change.created_at }),
+            ),
+            Err(e) => {
+                tracing::warn!(workspace_id = %workspace_id, error = %e, "Failed to read tool catalog history");
+                None
+            }
+        }
+    }
+}
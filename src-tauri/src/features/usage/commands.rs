@@ -1,4 +1,8 @@
-use super::models::{UsageChartPoint, UsageFilter, UsageStat, UsageSummary};
+use super::models::{
+    ChatUsageBreakdown, ToolUsageSummary, UsageChartPoint, UsageDeleteFilter, UsageFilter,
+    UsagePurgeMode, UsagePurgeResult, UsageStat, UsageSummary,
+};
+use super::service::USAGE_ON_DELETE_SETTING;
 use crate::error::AppError;
 use crate::state::AppState;
 use tauri::State;
@@ -46,3 +50,63 @@ pub fn clear_usage(state: State<'_, AppState>) -> Result<(), AppError> {
         .clear_usage()
         .map_err(|e| AppError::Generic(e.to_string()))
 }
+
+#[tauri::command]
+pub fn get_usage_by_chat(
+    filter: UsageFilter,
+    state: State<'_, AppState>,
+) -> Result<Vec<ChatUsageBreakdown>, AppError> {
+    state
+        .usage_service
+        .get_chat_breakdown(filter)
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
+
+#[tauri::command]
+pub fn delete_usage_where(
+    filter: UsageDeleteFilter,
+    state: State<'_, AppState>,
+) -> Result<UsagePurgeResult, AppError> {
+    state.usage_service.delete_usage_where(&filter)
+}
+
+#[tauri::command]
+pub fn anonymize_usage_where(
+    filter: UsageDeleteFilter,
+    state: State<'_, AppState>,
+) -> Result<UsagePurgeResult, AppError> {
+    state.usage_service.anonymize_usage_where(&filter)
+}
+
+/// Delete or anonymize the usage of a deleted chat or workspace, as the
+/// `usageOnDelete` setting says. Failures are logged; the deletion stands.
+pub fn purge_deleted_usage(state: &AppState, filter: &UsageDeleteFilter) {
+    let setting = state
+        .app_settings_service
+        .get_by_key(USAGE_ON_DELETE_SETTING)
+        .ok()
+        .flatten();
+    let mode = UsagePurgeMode::parse(setting.as_deref());
+    match state.usage_service.purge_usage_where(filter, mode) {
+        Ok(result) => tracing::info!(
+            ?mode,
+            usage_rows = result.usage_rows,
+            tool_usage_rows = result.tool_usage_rows,
+            "Purged usage of deleted data"
+        ),
+        Err(e) => tracing::warn!(error = %e, "Failed to purge usage of deleted data"),
+    }
+}
+
+#[tauri::command]
+pub fn get_tool_usage_summary(
+    workspace_id: Option<String>,
+    from: Option<i64>,
+    to: Option<i64>,
+    state: State<'_, AppState>,
+) -> Result<ToolUsageSummary, AppError> {
+    state
+        .usage_service
+        .get_tool_usage_summary(workspace_id, from, to)
+        .map_err(|e| AppError::Generic(e.to_string()))
+}
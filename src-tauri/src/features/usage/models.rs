@@ -11,12 +11,15 @@ pub struct UsageStat {
     pub input_tokens: u32,
     pub output_tokens: u32,
     pub total_tokens: u32,
+    pub cached_tokens: u32, // Prompt tokens served from a provider-side cache
+    pub cache_write_tokens: u32, // Prompt tokens written to a provider-side cache
     pub latency_ms: u64,
     pub cost: f64,
     pub timestamp: i64,
     pub is_stream: bool,
     pub status: String,
     pub request_type: String, // "chat", "embedding", etc.
+    pub is_cached: bool,      // Served from the local response cache
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -31,6 +34,8 @@ pub struct UsageSummary {
     pub total_requests: u32,
     pub total_input_tokens: u32,
     pub total_output_tokens: u32,
+    pub total_cached_tokens: u32,
+    pub total_cache_write_tokens: u32,
     pub total_cost: f64,
     pub average_latency: f64,
 }
@@ -43,3 +48,112 @@ pub struct UsageChartPoint {
     pub output_tokens: u32,
     pub cost: f64,
 }
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolUsageStat {
+    pub id: String,
+    pub workspace_id: String,
+    pub chat_id: String,
+    pub message_id: String,
+    pub tool_name: String,
+    pub connection_id: Option<String>, // MCP connection, or agent id for agent chats
+    pub status: String,                // "success", "error", "timeout", "cancelled"
+    pub duration_ms: u64,
+    pub result_bytes: u64,
+    pub timestamp: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolUsageBreakdown {
+    pub tool_name: Option<String>, // None for per-connection rollups
+    pub connection_id: Option<String>,
+    pub call_count: u32,
+    pub error_count: u32,
+    pub error_rate: f64,
+    pub p50_latency_ms: u64,
+    pub p95_latency_ms: u64,
+    pub total_result_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ToolUsageSummary {
+    pub tools: Vec<ToolUsageBreakdown>,
+    pub connections: Vec<ToolUsageBreakdown>,
+}
+
+/// Rows to delete or anonymize. At least one condition must be set; the
+/// conditions combine with AND.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct UsageDeleteFilter {
+    /// Rows recorded before this time (ms)
+    pub older_than: Option<i64>,
+    pub workspace_id: Option<String>,
+    pub chat_id: Option<String>,
+}
+
+impl UsageDeleteFilter {
+    pub const fn is_empty(&self) -> bool {
+        self.older_than.is_none() && self.workspace_id.is_none() && self.chat_id.is_none()
+    }
+
+    /// The SQL condition of the filter, with its parameters in order.
+    pub fn where_clause(&self) -> (String, Vec<rusqlite::types::Value>) {
+        let mut conditions = Vec::new();
+        let mut params = Vec::new();
+        if let Some(older_than) = self.older_than {
+            conditions.push("timestamp < ?");
+            params.push(rusqlite::types::Value::Integer(older_than));
+        }
+        if let Some(ws_id) = &self.workspace_id {
+            conditions.push("workspace_id = ?");
+            params.push(rusqlite::types::Value::Text(ws_id.clone()));
+        }
+        if let Some(chat_id) = &self.chat_id {
+            conditions.push("chat_id = ?");
+            params.push(rusqlite::types::Value::Text(chat_id.clone()));
+        }
+        if conditions.is_empty() {
+            return ("1=1".to_string(), params);
+        }
+        (conditions.join(" AND "), params)
+    }
+}
+
+/// What happens to the usage of a deleted chat or workspace.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum UsagePurgeMode {
+    Delete,
+    /// Keep tokens and cost but drop the chat and message ids
+    #[default]
+    Anonymize,
+}
+
+impl UsagePurgeMode {
+    /// The mode of a stored setting value, anonymizing unless it says delete.
+    pub fn parse(value: Option<&str>) -> Self {
+        match value.map(str::trim) {
+            Some("delete") => Self::Delete,
+            _ => Self::Anonymize,
+        }
+    }
+}
+
+/// Rows touched by a usage purge.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+pub struct UsagePurgeResult {
+    pub usage_rows: usize,
+    pub tool_usage_rows: usize,
+}
+
+/// Requests and cost of one chat. Anonymized rows and rows of deleted chats
+/// are grouped under `chat_id: None`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChatUsageBreakdown {
+    pub chat_id: Option<String>,
+    pub chat_title: String,
+    pub requests: u32,
+    pub input_tokens: u32,
+    pub output_tokens: u32,
+    pub cost: f64,
+}
@@ -1,5 +1,8 @@
-use super::models::{UsageChartPoint, UsageFilter, UsageStat, UsageSummary};
-use rusqlite::{params, Result};
+use super::models::{
+    ChatUsageBreakdown, ToolUsageStat, UsageChartPoint, UsageDeleteFilter, UsageFilter,
+    UsagePurgeResult, UsageStat, UsageSummary,
+};
+use rusqlite::{params, params_from_iter, types::Value, Connection, Result};
 use std::sync::Arc;
 use tauri::AppHandle;
 
@@ -9,6 +12,70 @@ pub trait UsageRepository: Send + Sync {
     fn get_summary(&self, filter: UsageFilter) -> Result<UsageSummary>;
     fn get_chart_data(&self, filter: UsageFilter, interval: &str) -> Result<Vec<UsageChartPoint>>;
     fn delete_all(&self) -> Result<()>;
+    fn create_tool_usage(&self, stat: ToolUsageStat) -> Result<()>;
+    fn get_tool_usage(&self, filter: UsageFilter) -> Result<Vec<ToolUsageStat>>;
+    fn get_chat_breakdown(&self, filter: UsageFilter) -> Result<Vec<ChatUsageBreakdown>>;
+    /// Delete the usage and tool usage rows matching `filter`, at most
+    /// `batch_size` rows per transaction.
+    fn delete_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+        batch_size: usize,
+    ) -> Result<UsagePurgeResult>;
+    /// Clear the chat and message ids of the rows matching `filter`, keeping
+    /// their tokens and cost.
+    fn anonymize_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+        batch_size: usize,
+    ) -> Result<UsagePurgeResult>;
+}
+
+/// Label of usage whose chat was deleted or anonymized.
+pub const DELETED_CHAT_TITLE: &str = "Deleted chat";
+
+/// Run `step` with `batch_size` until it reports fewer rows than that,
+/// returning the total. Each step handles one batch of rows.
+pub fn run_in_batches<F>(batch_size: usize, mut step: F) -> Result<usize>
+where
+    F: FnMut(usize) -> Result<usize>,
+{
+    let batch_size = batch_size.max(1);
+    let mut total = 0;
+    loop {
+        let affected = step(batch_size)?;
+        total += affected;
+        if affected < batch_size {
+            return Ok(total);
+        }
+    }
+}
+
+/// Run the `action` statement of `table` (a `DELETE` or `UPDATE` without
+/// its `WHERE`) on batches of rows matching `condition`, each in its own
+/// transaction.
+fn purge_table(
+    conn: &mut Connection,
+    table: &str,
+    action: impl Fn(&str) -> String,
+    condition: &str,
+    params: &[Value],
+    batch_size: usize,
+) -> Result<usize> {
+    let sql = format!(
+        "{} WHERE rowid IN (SELECT rowid FROM {table} WHERE {condition} LIMIT ?)",
+        action(table)
+    );
+    run_in_batches(batch_size, |limit| {
+        let tx = conn.transaction()?;
+        let limit = i64::try_from(limit).unwrap_or(i64::MAX);
+        let affected = tx.execute(
+            &sql,
+            params_from_iter(params.iter().cloned().chain([Value::Integer(limit)])),
+        )?;
+        tx.commit()?;
+        Ok(affected)
+    })
 }
 
 pub struct SqliteUsageRepository {
@@ -29,8 +96,9 @@ impl UsageRepository for SqliteUsageRepository {
             "INSERT INTO usage_stats (
                 id, workspace_id, chat_id, message_id, provider, model,
                 input_tokens, output_tokens, total_tokens, latency_ms,
-                cost, timestamp, is_stream, status, request_type
-            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)",
+                cost, timestamp, is_stream, status, request_type, cached_tokens, is_cached,
+                cache_write_tokens
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)",
             params![
                 stat.id,
                 stat.workspace_id,
@@ -46,7 +114,10 @@ impl UsageRepository for SqliteUsageRepository {
                 stat.timestamp,
                 i32::from(stat.is_stream),
                 stat.status,
-                stat.request_type
+                stat.request_type,
+                stat.cached_tokens,
+                i32::from(stat.is_cached),
+                stat.cache_write_tokens
             ],
         )?;
         Ok(())
@@ -93,12 +164,15 @@ impl UsageRepository for SqliteUsageRepository {
                 input_tokens: row.get("input_tokens")?,
                 output_tokens: row.get("output_tokens")?,
                 total_tokens: row.get("total_tokens")?,
+                cached_tokens: row.get("cached_tokens")?,
+                cache_write_tokens: row.get("cache_write_tokens")?,
                 latency_ms: row.get("latency_ms")?,
                 cost: row.get("cost")?,
                 timestamp: row.get("timestamp")?,
                 is_stream: row.get::<_, i32>("is_stream")? != 0,
                 status: row.get("status")?,
                 request_type: row.get("request_type")?,
+                is_cached: row.get::<_, i32>("is_cached")? != 0,
             })
         })?;
 
@@ -118,7 +192,9 @@ impl UsageRepository for SqliteUsageRepository {
                 COALESCE(SUM(input_tokens), 0) as input,
                 COALESCE(SUM(output_tokens), 0) as output,
                 COALESCE(SUM(cost), 0.0) as cost,
-                COALESCE(AVG(latency_ms), 0.0) as latency
+                COALESCE(AVG(latency_ms), 0.0) as latency,
+                COALESCE(SUM(cached_tokens), 0) as cached,
+                COALESCE(SUM(cache_write_tokens), 0) as cache_written
              FROM usage_stats WHERE 1=1",
         );
         let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
@@ -146,6 +222,8 @@ impl UsageRepository for SqliteUsageRepository {
                 total_output_tokens: row.get(2)?,
                 total_cost: row.get(3)?,
                 average_latency: row.get(4)?,
+                total_cached_tokens: row.get(5)?,
+                total_cache_write_tokens: row.get(6)?,
             })
         })
     }
@@ -211,6 +289,405 @@ impl UsageRepository for SqliteUsageRepository {
         let conn = crate::db::get_connection(&self.app)
             .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
         conn.execute("DELETE FROM usage_stats", [])?;
+        conn.execute("DELETE FROM tool_usage_stats", [])?;
         Ok(())
     }
+
+    fn create_tool_usage(&self, stat: ToolUsageStat) -> Result<()> {
+        let conn = crate::db::get_connection(&self.app)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        conn.execute(
+            "INSERT INTO tool_usage_stats (
+                id, workspace_id, chat_id, message_id, tool_name, connection_id,
+                status, duration_ms, result_bytes, timestamp
+            ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                stat.id,
+                stat.workspace_id,
+                stat.chat_id,
+                stat.message_id,
+                stat.tool_name,
+                stat.connection_id,
+                stat.status,
+                stat.duration_ms,
+                stat.result_bytes,
+                stat.timestamp
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn get_tool_usage(&self, filter: UsageFilter) -> Result<Vec<ToolUsageStat>> {
+        let conn = crate::db::get_connection(&self.app)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        let mut query = String::from("SELECT * FROM tool_usage_stats WHERE 1=1");
+        let mut params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+        if let Some(ws_id) = filter.workspace_id {
+            query.push_str(" AND workspace_id = ?");
+            params.push(Box::new(ws_id));
+        }
+        if let Some(start) = filter.start_date {
+            query.push_str(" AND timestamp >= ?");
+            params.push(Box::new(start));
+        }
+        if let Some(end) = filter.end_date {
+            query.push_str(" AND timestamp <= ?");
+            params.push(Box::new(end));
+        }
+
+        let params_ref: Vec<&dyn rusqlite::ToSql> =
+            params.iter().map(std::convert::AsRef::as_ref).collect();
+
+        let mut stmt = conn.prepare(&query)?;
+        let rows = stmt.query_map(params_ref.as_slice(), |row| {
+            Ok(ToolUsageStat {
+                id: row.get("id")?,
+                workspace_id: row.get("workspace_id")?,
+                chat_id: row.get("chat_id")?,
+                message_id: row.get("message_id")?,
+                tool_name: row.get("tool_name")?,
+                connection_id: row.get("connection_id")?,
+                status: row.get("status")?,
+                duration_ms: row.get("duration_ms")?,
+                result_bytes: row.get("result_bytes")?,
+                timestamp: row.get("timestamp")?,
+            })
+        })?;
+
+        let mut stats = Vec::new();
+        for row in rows {
+            stats.push(row?);
+        }
+        Ok(stats)
+    }
+
+    fn get_chat_breakdown(&self, filter: UsageFilter) -> Result<Vec<ChatUsageBreakdown>> {
+        let conn = crate::db::get_connection(&self.app)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        chat_breakdown(&conn, filter)
+    }
+
+    fn delete_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+        batch_size: usize,
+    ) -> Result<UsagePurgeResult> {
+        let mut conn = crate::db::get_connection(&self.app)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        delete_usage(&mut conn, filter, batch_size)
+    }
+
+    fn anonymize_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+        batch_size: usize,
+    ) -> Result<UsagePurgeResult> {
+        let mut conn = crate::db::get_connection(&self.app)
+            .map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))?;
+        anonymize_usage(&mut conn, filter, batch_size)
+    }
+}
+
+/// Usage per chat. Rows whose chat is gone, anonymized rows among them, are
+/// grouped under no chat id with the `DELETED_CHAT_TITLE` label.
+pub fn chat_breakdown(conn: &Connection, filter: UsageFilter) -> Result<Vec<ChatUsageBreakdown>> {
+    let mut query = String::from(
+        "SELECT
+            c.id as chat,
+            COALESCE(c.title, ?) as title,
+            COUNT(*) as requests,
+            COALESCE(SUM(u.input_tokens), 0) as input,
+            COALESCE(SUM(u.output_tokens), 0) as output,
+            COALESCE(SUM(u.cost), 0.0) as cost
+         FROM usage_stats u
+         LEFT JOIN chats c ON c.id = u.chat_id
+         WHERE 1=1",
+    );
+    let mut params: Vec<Box<dyn rusqlite::ToSql>> = vec![Box::new(DELETED_CHAT_TITLE)];
+
+    if let Some(ws_id) = filter.workspace_id {
+        query.push_str(" AND u.workspace_id = ?");
+        params.push(Box::new(ws_id));
+    }
+    if let Some(start) = filter.start_date {
+        query.push_str(" AND u.timestamp >= ?");
+        params.push(Box::new(start));
+    }
+    if let Some(end) = filter.end_date {
+        query.push_str(" AND u.timestamp <= ?");
+        params.push(Box::new(end));
+    }
+
+    query.push_str(" GROUP BY chat ORDER BY cost DESC");
+
+    let params_ref: Vec<&dyn rusqlite::ToSql> =
+        params.iter().map(std::convert::AsRef::as_ref).collect();
+
+    let mut stmt = conn.prepare(&query)?;
+    let rows = stmt.query_map(params_ref.as_slice(), |row| {
+        Ok(ChatUsageBreakdown {
+            chat_id: row.get(0)?,
+            chat_title: row.get(1)?,
+            requests: row.get(2)?,
+            input_tokens: row.get(3)?,
+            output_tokens: row.get(4)?,
+            cost: row.get(5)?,
+        })
+    })?;
+
+    let mut breakdown = Vec::new();
+    for row in rows {
+        breakdown.push(row?);
+    }
+    Ok(breakdown)
+}
+
+pub fn delete_usage(
+    conn: &mut Connection,
+    filter: &UsageDeleteFilter,
+    batch_size: usize,
+) -> Result<UsagePurgeResult> {
+    let (condition, params) = filter.where_clause();
+    let statement = |table: &str| format!("DELETE FROM {table}");
+    Ok(UsagePurgeResult {
+        usage_rows: purge_table(
+            conn,
+            "usage_stats",
+            statement,
+            &condition,
+            &params,
+            batch_size,
+        )?,
+        tool_usage_rows: purge_table(
+            conn,
+            "tool_usage_stats",
+            statement,
+            &condition,
+            &params,
+            batch_size,
+        )?,
+    })
+}
+
+pub fn anonymize_usage(
+    conn: &mut Connection,
+    filter: &UsageDeleteFilter,
+    batch_size: usize,
+) -> Result<UsagePurgeResult> {
+    let (condition, params) = filter.where_clause();
+    // Anonymized rows have empty ids and are skipped, so every batch
+    // makes progress
+    let condition = format!("{condition} AND chat_id != ''");
+    let statement = |table: &str| format!("UPDATE {table} SET chat_id = '', message_id = ''");
+    Ok(UsagePurgeResult {
+        usage_rows: purge_table(
+            conn,
+            "usage_stats",
+            statement,
+            &condition,
+            &params,
+            batch_size,
+        )?,
+        tool_usage_rows: purge_table(
+            conn,
+            "tool_usage_stats",
+            statement,
+            &condition,
+            &params,
+            batch_size,
+        )?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    fn usage_db() -> rusqlite::Connection {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute_batch(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0);
+             INSERT INTO chats (id, workspace_id, title, created_at, updated_at)
+             VALUES ('c1', 'w1', 'Kept chat', 0, 0);",
+        )
+        .unwrap();
+        // (workspace, chat, timestamp); chat c2 has been deleted
+        let rows = [
+            ("w1", "c1", 100),
+            ("w1", "c1", 300),
+            ("w1", "c2", 100),
+            ("w1", "c2", 200),
+            ("w1", "c2", 300),
+            ("w2", "c3", 100),
+        ];
+        for (i, (workspace_id, chat_id, timestamp)) in rows.into_iter().enumerate() {
+            conn.execute(
+                "INSERT INTO usage_stats (id, workspace_id, chat_id, message_id, provider, model,
+                    input_tokens, output_tokens, cost, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, 'openai', 'gpt-4o', 10, 5, 1.0, ?5)",
+                rusqlite::params![i, workspace_id, chat_id, format!("m{i}"), timestamp],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO tool_usage_stats (id, workspace_id, chat_id, message_id, tool_name,
+                    status, timestamp)
+                 VALUES (?1, ?2, ?3, ?4, 'search', 'success', ?5)",
+                rusqlite::params![i, workspace_id, chat_id, format!("m{i}"), timestamp],
+            )
+            .unwrap();
+        }
+        conn
+    }
+
+    fn usage_count(conn: &rusqlite::Connection, condition: &str) -> usize {
+        conn.query_row(
+            &format!("SELECT COUNT(*) FROM usage_stats WHERE {condition}"),
+            [],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn usage_delete_filter_combines_each_dimension() {
+        use crate::features::usage::models::{UsageDeleteFilter, UsagePurgeMode};
+        use rusqlite::types::Value;
+
+        let filter = UsageDeleteFilter::default();
+        assert!(filter.is_empty());
+        assert_eq!(filter.where_clause().0, "1=1");
+
+        let filter = UsageDeleteFilter {
+            older_than: Some(200),
+            workspace_id: Some("w1".to_string()),
+            chat_id: Some("c2".to_string()),
+        };
+        let (condition, params) = filter.where_clause();
+        assert_eq!(
+            condition,
+            "timestamp < ? AND workspace_id = ? AND chat_id = ?"
+        );
+        assert_eq!(
+            params,
+            [
+                Value::Integer(200),
+                Value::Text("w1".to_string()),
+                Value::Text("c2".to_string()),
+            ]
+        );
+
+        assert_eq!(UsagePurgeMode::parse(None), UsagePurgeMode::Anonymize);
+        assert_eq!(
+            UsagePurgeMode::parse(Some("bogus")),
+            UsagePurgeMode::Anonymize
+        );
+        assert_eq!(
+            UsagePurgeMode::parse(Some(" delete ")),
+            UsagePurgeMode::Delete
+        );
+    }
+
+    #[test]
+    fn usage_delete_by_date_workspace_and_chat() {
+        use crate::features::usage::models::{UsageDeleteFilter, UsagePurgeResult};
+        use crate::features::usage::repository::delete_usage;
+
+        let mut conn = usage_db();
+        let by_chat = UsageDeleteFilter {
+            chat_id: Some("c1".to_string()),
+            ..UsageDeleteFilter::default()
+        };
+        assert_eq!(
+            delete_usage(&mut conn, &by_chat, 500).unwrap(),
+            UsagePurgeResult {
+                usage_rows: 2,
+                tool_usage_rows: 2
+            }
+        );
+
+        let older = UsageDeleteFilter {
+            older_than: Some(200),
+            ..UsageDeleteFilter::default()
+        };
+        // c2 at 100 and c3 at 100; the row at 200 is not older
+        assert_eq!(delete_usage(&mut conn, &older, 500).unwrap().usage_rows, 2);
+
+        let by_workspace = UsageDeleteFilter {
+            workspace_id: Some("w1".to_string()),
+            ..UsageDeleteFilter::default()
+        };
+        assert_eq!(
+            delete_usage(&mut conn, &by_workspace, 500)
+                .unwrap()
+                .usage_rows,
+            2
+        );
+        assert_eq!(usage_count(&conn, "1=1"), 0);
+    }
+
+    #[test]
+    fn usage_anonymize_in_batches_keeps_totals() {
+        use crate::features::usage::models::{UsageDeleteFilter, UsagePurgeResult};
+        use crate::features::usage::repository::{
+            anonymize_usage, chat_breakdown, run_in_batches, DELETED_CHAT_TITLE,
+        };
+
+        let mut batches = Vec::new();
+        let total = run_in_batches(2, |limit| {
+            let affected = [2, 2, 1][batches.len()].min(limit);
+            batches.push(affected);
+            Ok(affected)
+        })
+        .unwrap();
+        assert_eq!((total, batches), (5, vec![2, 2, 1]));
+
+        let mut conn = usage_db();
+        let deleted_chat = UsageDeleteFilter {
+            chat_id: Some("c2".to_string()),
+            ..UsageDeleteFilter::default()
+        };
+        // Three rows in batches of two
+        assert_eq!(
+            anonymize_usage(&mut conn, &deleted_chat, 2).unwrap(),
+            UsagePurgeResult {
+                usage_rows: 3,
+                tool_usage_rows: 3
+            }
+        );
+        assert_eq!(usage_count(&conn, "chat_id = '' AND message_id = ''"), 3);
+        let workspace = UsageDeleteFilter {
+            workspace_id: Some("w1".to_string()),
+            older_than: Some(200),
+            ..UsageDeleteFilter::default()
+        };
+        // Already anonymized rows are left alone
+        assert_eq!(
+            anonymize_usage(&mut conn, &workspace, 2)
+                .unwrap()
+                .usage_rows,
+            1
+        );
+        assert_eq!(usage_count(&conn, "chat_id = ''"), 4);
+
+        let breakdown = chat_breakdown(
+            &conn,
+            crate::features::usage::models::UsageFilter {
+                start_date: None,
+                end_date: None,
+                workspace_id: None,
+            },
+        )
+        .unwrap();
+        let rows: Vec<_> = breakdown
+            .iter()
+            .map(|b| (b.chat_id.as_deref(), b.chat_title.as_str(), b.requests))
+            .collect();
+        // Anonymized rows and the usage of chat c3, which no longer exists
+        assert_eq!(
+            rows,
+            [(None, DELETED_CHAT_TITLE, 5), (Some("c1"), "Kept chat", 1)]
+        );
+        let total: f64 = breakdown.iter().map(|b| b.cost).sum();
+        assert!((total - 6.0).abs() < f64::EPSILON);
+    }
 }
@@ -1,10 +1,73 @@
-use super::models::{UsageChartPoint, UsageFilter, UsageStat, UsageSummary};
+use super::models::{
+    ChatUsageBreakdown, ToolUsageBreakdown, ToolUsageStat, ToolUsageSummary, UsageChartPoint,
+    UsageDeleteFilter, UsageFilter, UsagePurgeMode, UsagePurgeResult, UsageStat, UsageSummary,
+};
 use super::repository::UsageRepository;
+use crate::error::AppError;
 use crate::models::llm_types::TokenUsage;
 use rusqlite::Result;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 use uuid::Uuid;
 
+/// App setting choosing what happens to the usage of deleted chats and
+/// workspaces: `"anonymize"` (default) or `"delete"`.
+pub const USAGE_ON_DELETE_SETTING: &str = "usageOnDelete";
+/// Rows deleted or anonymized per transaction.
+pub const USAGE_PURGE_BATCH_SIZE: usize = 500;
+
+/// Prices per 1M tokens as `(input, output)`, or `None` for models that are
+/// not priced (e.g. Ollama or local models).
+pub fn model_prices(model: &str) -> Option<(f64, f64)> {
+    let model_lower = model.to_lowercase();
+    // Prices per 1M tokens (approximate as of 2024)
+    // Format: (input_price, output_price)
+    let price_map: [(&str, f64, f64); 7] = [
+        // OpenAI
+        ("gpt-4o", 5.0, 15.0),
+        ("gpt-4o-mini", 0.15, 0.6),
+        ("gpt-4-turbo", 10.0, 30.0),
+        ("gpt-3.5-turbo", 0.5, 1.5),
+        // Anthropic
+        ("claude-3-5-sonnet", 3.0, 15.0),
+        ("claude-3-opus", 15.0, 75.0),
+        ("claude-3-haiku", 0.25, 1.25),
+    ];
+
+    price_map
+        .into_iter()
+        .find(|(key, _, _)| model_lower.contains(key))
+        .map(|(_, input_price, output_price)| (input_price, output_price))
+}
+
+/// Price of prompt tokens read from and written to Anthropic's prompt cache,
+/// as factors of the input price.
+const PROMPT_CACHE_READ_FACTOR: f64 = 0.1;
+const PROMPT_CACHE_WRITE_FACTOR: f64 = 1.25;
+
+/// What a request's prompt cache use adds to its cost at the plain input
+/// price: a discount for the tokens read, a surcharge for those written.
+/// Priced for Claude models only, whose prompt tokens count both.
+pub fn prompt_cache_cost_delta(
+    provider: &str,
+    model: &str,
+    cached_tokens: u32,
+    cache_write_tokens: u32,
+) -> f64 {
+    let claude = matches!(
+        provider.to_lowercase().as_str(),
+        "anthropic" | "claude" | "bedrock" | "aws-bedrock"
+    ) && model.to_lowercase().contains("claude");
+    match model_prices(model) {
+        Some((input_price, _)) if claude => {
+            let per_token = input_price / 1_000_000.0;
+            f64::from(cache_write_tokens) * per_token * (PROMPT_CACHE_WRITE_FACTOR - 1.0)
+                - f64::from(cached_tokens) * per_token * (1.0 - PROMPT_CACHE_READ_FACTOR)
+        }
+        _ => 0.0,
+    }
+}
+
 pub struct UsageService {
     repo: Arc<dyn UsageRepository>,
 }
@@ -15,30 +78,11 @@ impl UsageService {
     }
 
     pub fn calculate_cost(&self, model: &str, input_tokens: u32, output_tokens: u32) -> f64 {
-        let model_lower = model.to_lowercase();
-        // Prices per 1M tokens (approximate as of 2024)
-        // Format: (input_price, output_price)
-        let price_map: Vec<(&str, f64, f64)> = vec![
-            // OpenAI
-            ("gpt-4o", 5.0, 15.0),
-            ("gpt-4o-mini", 0.15, 0.6),
-            ("gpt-4-turbo", 10.0, 30.0),
-            ("gpt-3.5-turbo", 0.5, 1.5),
-            // Anthropic
-            ("claude-3-5-sonnet", 3.0, 15.0),
-            ("claude-3-opus", 15.0, 75.0),
-            ("claude-3-haiku", 0.25, 1.25),
-        ];
-
-        for (key, input_price, output_price) in price_map {
-            if model_lower.contains(key) {
-                let input_cost = (f64::from(input_tokens) / 1_000_000.0) * input_price;
-                let output_cost = (f64::from(output_tokens) / 1_000_000.0) * output_price;
-                return input_cost + output_cost;
-            }
-        }
-
-        0.0 // Default/Unknown (e.g., Ollama or local models)
+        model_prices(model).map_or(0.0, |(input_price, output_price)| {
+            let input_cost = (f64::from(input_tokens) / 1_000_000.0) * input_price;
+            let output_cost = (f64::from(output_tokens) / 1_000_000.0) * output_price;
+            input_cost + output_cost
+        })
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -53,6 +97,36 @@ impl UsageService {
         latency_ms: u64,
         is_stream: bool,
         status: String,
+    ) -> Result<()> {
+        self.record_usage_with_type(
+            workspace_id,
+            chat_id,
+            message_id,
+            provider,
+            model,
+            usage,
+            latency_ms,
+            is_stream,
+            status,
+            "chat".to_string(),
+        )
+    }
+
+    /// Like `record_usage`, for requests made outside a chat turn
+    /// (`"quick_action"`, ...).
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_usage_with_type(
+        &self,
+        workspace_id: String,
+        chat_id: String,
+        message_id: String,
+        provider: String,
+        model: String,
+        usage: Option<TokenUsage>,
+        latency_ms: u64,
+        is_stream: bool,
+        status: String,
+        request_type: String,
     ) -> Result<()> {
         let input_tokens = usage.as_ref().and_then(|u| u.prompt_tokens).unwrap_or(0);
         let output_tokens = usage
@@ -64,7 +138,14 @@ impl UsageService {
             .and_then(|u| u.total_tokens)
             .unwrap_or(input_tokens + output_tokens);
 
-        let cost = self.calculate_cost(&model, input_tokens, output_tokens);
+        let cached_tokens = usage.as_ref().and_then(|u| u.cached_tokens).unwrap_or(0);
+        let cache_write_tokens = usage
+            .as_ref()
+            .and_then(|u| u.cache_write_tokens)
+            .unwrap_or(0);
+
+        let cost = self.calculate_cost(&model, input_tokens, output_tokens)
+            + prompt_cache_cost_delta(&provider, &model, cached_tokens, cache_write_tokens);
 
         let stat = UsageStat {
             id: Uuid::new_v4().to_string(),
@@ -76,12 +157,83 @@ impl UsageService {
             input_tokens,
             output_tokens,
             total_tokens,
+            cached_tokens,
+            cache_write_tokens,
             latency_ms,
             cost,
             timestamp: chrono::Utc::now().timestamp_millis(),
             is_stream,
             status,
-            request_type: "chat".to_string(),
+            request_type,
+            is_cached: false,
+        };
+
+        self.repo.create(stat)
+    }
+
+    /// Record a request answered from the local response cache (no tokens spent).
+    pub fn record_cached_usage(
+        &self,
+        workspace_id: String,
+        chat_id: String,
+        message_id: String,
+        provider: String,
+        model: String,
+        request_type: String,
+    ) -> Result<()> {
+        let stat = UsageStat {
+            id: Uuid::new_v4().to_string(),
+            workspace_id,
+            chat_id,
+            message_id,
+            provider,
+            model,
+            input_tokens: 0,
+            output_tokens: 0,
+            total_tokens: 0,
+            cached_tokens: 0,
+            cache_write_tokens: 0,
+            latency_ms: 0,
+            cost: 0.0,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            is_stream: false,
+            status: "success".to_string(),
+            request_type,
+            is_cached: true,
+        };
+
+        self.repo.create(stat)
+    }
+
+    /// Record a background warm-up request for a local model. Warm-ups belong to
+    /// no chat, so the chat and message ids are left empty.
+    pub fn record_warmup_usage(
+        &self,
+        workspace_id: String,
+        provider: String,
+        model: String,
+        latency_ms: u64,
+        status: String,
+    ) -> Result<()> {
+        let stat = UsageStat {
+            id: Uuid::new_v4().to_string(),
+            workspace_id,
+            chat_id: String::new(),
+            message_id: String::new(),
+            provider,
+            model,
+            input_tokens: 0,
+            output_tokens: 0,
+            total_tokens: 0,
+            cached_tokens: 0,
+            cache_write_tokens: 0,
+            latency_ms,
+            cost: 0.0,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            is_stream: false,
+            status,
+            request_type: "warmup".to_string(),
+            is_cached: false,
         };
 
         self.repo.create(stat)
@@ -107,4 +259,414 @@ impl UsageService {
     pub fn clear_usage(&self) -> Result<()> {
         self.repo.delete_all()
     }
+
+    /// Requests and cost per chat; usage of deleted chats is grouped as
+    /// "Deleted chat" and still counts toward the totals.
+    pub fn get_chat_breakdown(&self, filter: UsageFilter) -> Result<Vec<ChatUsageBreakdown>> {
+        self.repo.get_chat_breakdown(filter)
+    }
+
+    pub fn delete_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+    ) -> Result<UsagePurgeResult, AppError> {
+        Self::require_condition(filter)?;
+        Ok(self
+            .repo
+            .delete_usage_where(filter, USAGE_PURGE_BATCH_SIZE)?)
+    }
+
+    pub fn anonymize_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+    ) -> Result<UsagePurgeResult, AppError> {
+        Self::require_condition(filter)?;
+        Ok(self
+            .repo
+            .anonymize_usage_where(filter, USAGE_PURGE_BATCH_SIZE)?)
+    }
+
+    pub fn purge_usage_where(
+        &self,
+        filter: &UsageDeleteFilter,
+        mode: UsagePurgeMode,
+    ) -> Result<UsagePurgeResult, AppError> {
+        match mode {
+            UsagePurgeMode::Delete => self.delete_usage_where(filter),
+            UsagePurgeMode::Anonymize => self.anonymize_usage_where(filter),
+        }
+    }
+
+    /// An empty filter would match every row; `clear_usage` does that.
+    fn require_condition(filter: &UsageDeleteFilter) -> Result<(), AppError> {
+        if filter.is_empty() {
+            return Err(AppError::Validation(
+                "Usage filter needs a date, workspace or chat".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_tool_usage(
+        &self,
+        workspace_id: String,
+        chat_id: String,
+        message_id: String,
+        tool_name: String,
+        connection_id: Option<String>,
+        status: String,
+        duration_ms: u64,
+        result_bytes: u64,
+    ) -> Result<()> {
+        let stat = ToolUsageStat {
+            id: Uuid::new_v4().to_string(),
+            workspace_id,
+            chat_id,
+            message_id,
+            tool_name,
+            connection_id,
+            status,
+            duration_ms,
+            result_bytes,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+        };
+
+        self.repo.create_tool_usage(stat)
+    }
+
+    /// `record_tool_usage` on the blocking pool, so the agent loop never
+    /// waits on the write. Failures are logged.
+    #[allow(clippy::too_many_arguments)]
+    pub fn record_tool_usage_detached(
+        self: &Arc<Self>,
+        workspace_id: String,
+        chat_id: String,
+        message_id: String,
+        tool_name: String,
+        connection_id: Option<String>,
+        status: String,
+        duration_ms: u64,
+        result_bytes: u64,
+    ) -> tokio::task::JoinHandle<()> {
+        let service = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(e) = service.record_tool_usage(
+                workspace_id,
+                chat_id,
+                message_id,
+                tool_name,
+                connection_id,
+                status,
+                duration_ms,
+                result_bytes,
+            ) {
+                tracing::error!(error = ?e, "Failed to record tool usage");
+            }
+        })
+    }
+
+    /// Per-tool and per-connection call counts, error rates, latency percentiles
+    /// and result sizes for tool executions in the given range.
+    pub fn get_tool_usage_summary(
+        &self,
+        workspace_id: Option<String>,
+        from: Option<i64>,
+        to: Option<i64>,
+    ) -> Result<ToolUsageSummary> {
+        let stats = self.repo.get_tool_usage(UsageFilter {
+            start_date: from,
+            end_date: to,
+            workspace_id,
+        })?;
+
+        let mut by_tool: BTreeMap<(String, Option<String>), Vec<&ToolUsageStat>> = BTreeMap::new();
+        let mut by_connection: BTreeMap<Option<String>, Vec<&ToolUsageStat>> = BTreeMap::new();
+        for stat in &stats {
+            by_tool
+                .entry((stat.tool_name.clone(), stat.connection_id.clone()))
+                .or_default()
+                .push(stat);
+            by_connection
+                .entry(stat.connection_id.clone())
+                .or_default()
+                .push(stat);
+        }
+
+        let tools = by_tool
+            .into_iter()
+            .map(|((tool_name, connection_id), calls)| {
+                Self::aggregate_tool_calls(Some(tool_name), connection_id, &calls)
+            })
+            .collect();
+        let connections = by_connection
+            .into_iter()
+            .map(|(connection_id, calls)| Self::aggregate_tool_calls(None, connection_id, &calls))
+            .collect();
+
+        Ok(ToolUsageSummary { tools, connections })
+    }
+
+    fn aggregate_tool_calls(
+        tool_name: Option<String>,
+        connection_id: Option<String>,
+        calls: &[&ToolUsageStat],
+    ) -> ToolUsageBreakdown {
+        let mut durations: Vec<u64> = calls.iter().map(|c| c.duration_ms).collect();
+        durations.sort_unstable();
+
+        let call_count = u32::try_from(calls.len()).unwrap_or(u32::MAX);
+        let error_count = u32::try_from(calls.iter().filter(|c| c.status != "success").count())
+            .unwrap_or(u32::MAX);
+        let error_rate = if call_count == 0 {
+            0.0
+        } else {
+            f64::from(error_count) / f64::from(call_count)
+        };
+
+        ToolUsageBreakdown {
+            tool_name,
+            connection_id,
+            call_count,
+            error_count,
+            error_rate,
+            p50_latency_ms: Self::percentile(&durations, 50),
+            p95_latency_ms: Self::percentile(&durations, 95),
+            total_result_bytes: calls.iter().map(|c| c.result_bytes).sum(),
+        }
+    }
+
+    /// Nearest-rank percentile of an ascending slice.
+    fn percentile(sorted: &[u64], pct: usize) -> u64 {
+        if sorted.is_empty() {
+            return 0;
+        }
+        let rank = (pct * sorted.len()).div_ceil(100).max(1);
+        sorted[rank.min(sorted.len()) - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    /// Tool usage kept in memory; every write takes `write_delay`.
+    #[derive(Default)]
+    struct MemoryToolUsageRepository {
+        stats: std::sync::Mutex<Vec<crate::features::usage::models::ToolUsageStat>>,
+        write_delay: std::time::Duration,
+    }
+
+    impl crate::features::usage::repository::UsageRepository for MemoryToolUsageRepository {
+        fn create(&self, _: crate::features::usage::models::UsageStat) -> rusqlite::Result<()> {
+            unreachable!()
+        }
+        fn get_logs(
+            &self,
+            _: crate::features::usage::models::UsageFilter,
+            _: u32,
+            _: u32,
+        ) -> rusqlite::Result<Vec<crate::features::usage::models::UsageStat>> {
+            unreachable!()
+        }
+        fn get_summary(
+            &self,
+            _: crate::features::usage::models::UsageFilter,
+        ) -> rusqlite::Result<crate::features::usage::models::UsageSummary> {
+            unreachable!()
+        }
+        fn get_chart_data(
+            &self,
+            _: crate::features::usage::models::UsageFilter,
+            _: &str,
+        ) -> rusqlite::Result<Vec<crate::features::usage::models::UsageChartPoint>> {
+            unreachable!()
+        }
+        fn delete_all(&self) -> rusqlite::Result<()> {
+            self.stats.lock().unwrap().clear();
+            Ok(())
+        }
+        fn create_tool_usage(
+            &self,
+            stat: crate::features::usage::models::ToolUsageStat,
+        ) -> rusqlite::Result<()> {
+            std::thread::sleep(self.write_delay);
+            self.stats.lock().unwrap().push(stat);
+            Ok(())
+        }
+        fn get_tool_usage(
+            &self,
+            filter: crate::features::usage::models::UsageFilter,
+        ) -> rusqlite::Result<Vec<crate::features::usage::models::ToolUsageStat>> {
+            Ok(self
+                .stats
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|s| {
+                    filter
+                        .workspace_id
+                        .as_ref()
+                        .is_none_or(|w| &s.workspace_id == w)
+                        && filter.start_date.is_none_or(|t| s.timestamp >= t)
+                        && filter.end_date.is_none_or(|t| s.timestamp <= t)
+                })
+                .cloned()
+                .collect())
+        }
+        fn get_chat_breakdown(
+            &self,
+            _: crate::features::usage::models::UsageFilter,
+        ) -> rusqlite::Result<Vec<crate::features::usage::models::ChatUsageBreakdown>> {
+            unreachable!()
+        }
+        fn delete_usage_where(
+            &self,
+            _: &crate::features::usage::models::UsageDeleteFilter,
+            _: usize,
+        ) -> rusqlite::Result<crate::features::usage::models::UsagePurgeResult> {
+            unreachable!()
+        }
+        fn anonymize_usage_where(
+            &self,
+            _: &crate::features::usage::models::UsageDeleteFilter,
+            _: usize,
+        ) -> rusqlite::Result<crate::features::usage::models::UsagePurgeResult> {
+            unreachable!()
+        }
+    }
+
+    #[test]
+    fn tool_usage_summary_aggregates_hundreds_of_calls() {
+        use crate::features::usage::UsageService;
+        use std::sync::Arc;
+
+        let service = UsageService::new(Arc::new(MemoryToolUsageRepository::default()));
+        let record = |workspace: &str, tool: &str, connection: &str, status: &str, ms, bytes| {
+            service
+                .record_tool_usage(
+                    workspace.to_string(),
+                    "c1".to_string(),
+                    "m1".to_string(),
+                    tool.to_string(),
+                    Some(connection.to_string()),
+                    status.to_string(),
+                    ms,
+                    bytes,
+                )
+                .unwrap();
+        };
+        for i in 1..=200 {
+            let status = if i % 10 == 0 { "error" } else { "success" };
+            record("w1", "read_file", "fs", status, i, 10);
+        }
+        for i in 1..=100 {
+            let status = if i % 20 == 0 { "timeout" } else { "success" };
+            record("w1", "search", "web", status, 1000 + i, 100);
+        }
+        // Another workspace's calls stay out of a filtered summary
+        for _ in 0..50 {
+            record("w2", "search", "web", "error", 60_000, 1);
+        }
+
+        let summary = service
+            .get_tool_usage_summary(Some("w1".to_string()), None, None)
+            .unwrap();
+        let rows = |breakdowns: &[crate::features::usage::models::ToolUsageBreakdown]| {
+            breakdowns
+                .iter()
+                .map(|b| {
+                    (
+                        b.tool_name.clone(),
+                        b.connection_id.clone().unwrap(),
+                        b.call_count,
+                        b.error_count,
+                        b.p50_latency_ms,
+                        b.p95_latency_ms,
+                        b.total_result_bytes,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+        let read_file = (200, 20, 100, 190, 2000);
+        let search = (100, 5, 1050, 1095, 10_000);
+        let with = |tool: Option<&str>, connection: &str, (calls, errors, p50, p95, bytes)| {
+            (
+                tool.map(str::to_string),
+                connection.to_string(),
+                calls,
+                errors,
+                p50,
+                p95,
+                bytes,
+            )
+        };
+        assert_eq!(
+            rows(&summary.tools),
+            [
+                with(Some("read_file"), "fs", read_file),
+                with(Some("search"), "web", search)
+            ]
+        );
+        assert_eq!(
+            rows(&summary.connections),
+            [with(None, "fs", read_file), with(None, "web", search)]
+        );
+        assert!((summary.tools[0].error_rate - 0.1).abs() < f64::EPSILON);
+        assert!((summary.tools[1].error_rate - 0.05).abs() < f64::EPSILON);
+
+        let everything = service.get_tool_usage_summary(None, None, None).unwrap();
+        assert_eq!(everything.tools[1].call_count, 150);
+        let none = service
+            .get_tool_usage_summary(None, Some(i64::MAX - 1), None)
+            .unwrap();
+        assert!(none.tools.is_empty() && none.connections.is_empty());
+    }
+
+    #[test]
+    fn tool_usage_is_written_off_the_agent_loop() {
+        use crate::features::usage::repository::UsageRepository;
+        use crate::features::usage::UsageService;
+        use std::sync::Arc;
+        use std::time::{Duration, Instant};
+
+        let repository = Arc::new(MemoryToolUsageRepository {
+            write_delay: Duration::from_millis(300),
+            ..MemoryToolUsageRepository::default()
+        });
+        let service = Arc::new(UsageService::new(repository.clone()));
+        let stored = || {
+            repository
+                .get_tool_usage(crate::features::usage::models::UsageFilter {
+                    start_date: None,
+                    end_date: None,
+                    workspace_id: None,
+                })
+                .unwrap()
+                .len()
+        };
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let started = Instant::now();
+            let writes: Vec<_> = (0..5)
+                .map(|i| {
+                    service.record_tool_usage_detached(
+                        "w1".to_string(),
+                        "c1".to_string(),
+                        "m1".to_string(),
+                        format!("tool_{i}"),
+                        None,
+                        "success".to_string(),
+                        12,
+                        64,
+                    )
+                })
+                .collect();
+            // Five slow writes, and the calls returned before any of them ended
+            assert!(started.elapsed() < Duration::from_millis(150));
+            assert_eq!(stored(), 0);
+            for write in writes {
+                write.await.unwrap();
+            }
+            assert_eq!(stored(), 5);
+        });
+    }
 }
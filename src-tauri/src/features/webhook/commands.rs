@@ -0,0 +1,52 @@
+use super::models::Webhook;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::State;
+
+/// A secret is generated when `secret` is left out.
+#[tauri::command]
+pub fn create_webhook(
+    workspace_id: String,
+    url: String,
+    secret: Option<String>,
+    event_kinds: Vec<String>,
+    include_content: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Webhook, AppError> {
+    state.webhook_service.create(
+        workspace_id,
+        &url,
+        secret,
+        event_kinds,
+        include_content.unwrap_or(false),
+    )
+}
+
+#[tauri::command]
+pub fn get_webhooks(
+    workspace_id: String,
+    state: State<'_, AppState>,
+) -> Result<Vec<Webhook>, AppError> {
+    state.webhook_service.get_by_workspace_id(&workspace_id)
+}
+
+/// Fields left out are kept.
+#[tauri::command]
+pub fn update_webhook(
+    id: String,
+    url: Option<String>,
+    secret: Option<String>,
+    enabled: Option<bool>,
+    event_kinds: Option<Vec<String>>,
+    include_content: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<Webhook, AppError> {
+    state
+        .webhook_service
+        .update(&id, url, secret, enabled, event_kinds, include_content)
+}
+
+#[tauri::command]
+pub fn delete_webhook(id: String, state: State<'_, AppState>) -> Result<(), AppError> {
+    state.webhook_service.delete(&id)
+}
@@ -0,0 +1,472 @@
+//! Delivery of workspace activity to webhooks.
+//!
+//! Each delivery is a JSON POST signed with the webhook's secret: the
+//! `X-Lunex-Signature` header holds `sha256=<hex HMAC-SHA256 of the body>`.
+//! Payloads carry ids and a short summary; the message or task output is
+//! only added for webhooks with `include_content`. Network errors, timeouts
+//! and 5xx/429 answers are retried with exponential backoff, or after the
+//! answer's `Retry-After` when it names a wait up to `MAX_RETRY_AFTER`. After
+//! `MAX_CONSECUTIVE_FAILURES` failed deliveries in a row the webhook is
+//! disabled until the user enables it again.
+
+use super::models::{Webhook, WebhookDelivery, WebhookEvent};
+use super::repository::WebhookRepository;
+use crate::services::llm::rate_limit;
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const SIGNATURE_HEADER: &str = "X-Lunex-Signature";
+pub const EVENT_HEADER: &str = "X-Lunex-Event";
+pub const DELIVERY_HEADER: &str = "X-Lunex-Delivery";
+/// Attempts per delivery, the first one included.
+pub const MAX_ATTEMPTS: u32 = 3;
+/// Failed deliveries in a row after which a webhook is disabled.
+pub const MAX_CONSECUTIVE_FAILURES: i64 = 5;
+/// Wait before the first retry; it doubles for each further one.
+const FIRST_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Longest `Retry-After` honored; longer ones fall back to the backoff.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(30);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct WebhookDispatcher {
+    repository: Arc<dyn WebhookRepository>,
+    client: reqwest::Client,
+    first_retry_delay: Duration,
+}
+
+impl WebhookDispatcher {
+    pub fn new(repository: Arc<dyn WebhookRepository>) -> Self {
+        Self::with_retry_delay(repository, FIRST_RETRY_DELAY)
+    }
+
+    pub fn with_retry_delay(
+        repository: Arc<dyn WebhookRepository>,
+        first_retry_delay: Duration,
+    ) -> Self {
+        Self {
+            repository,
+            client: reqwest::Client::builder()
+                .timeout(REQUEST_TIMEOUT)
+                .build()
+                .unwrap_or_default(),
+            first_retry_delay,
+        }
+    }
+
+    /// Deliver `event` in the background. Failures are only logged and kept
+    /// in the webhook's delivery log; webhooks never block or fail a turn.
+    pub fn dispatch(self: &Arc<Self>, event: WebhookEvent) {
+        let dispatcher = self.clone();
+        tokio::spawn(async move {
+            dispatcher.deliver_event(&event).await;
+        });
+    }
+
+    /// Deliver `event` to each enabled webhook of its workspace that
+    /// subscribes to it, and log the outcomes.
+    pub async fn deliver_event(&self, event: &WebhookEvent) -> Vec<WebhookDelivery> {
+        let webhooks = match self.repository.get_by_workspace_id(&event.workspace_id) {
+            Ok(webhooks) => webhooks,
+            Err(e) => {
+                tracing::error!(error = ?e, "Failed to load webhooks");
+                return Vec::new();
+            }
+        };
+        let deliveries = futures::future::join_all(
+            webhooks
+                .iter()
+                .filter(|webhook| webhook.subscribes_to(event.kind))
+                .map(|webhook| self.deliver(webhook, event)),
+        )
+        .await;
+
+        let delivered_at = chrono::Utc::now().timestamp_millis();
+        for delivery in &deliveries {
+            match self
+                .repository
+                .record_delivery(delivery, delivered_at, MAX_CONSECUTIVE_FAILURES)
+            {
+                Ok(true) => tracing::warn!(
+                    webhook_id = %delivery.webhook_id,
+                    "Disabled webhook after {MAX_CONSECUTIVE_FAILURES} failed deliveries"
+                ),
+                Ok(false) => {}
+                Err(e) => tracing::error!(error = ?e, "Failed to record webhook delivery"),
+            }
+        }
+        deliveries
+    }
+
+    /// POST the event to one webhook, retrying failures that may pass.
+    pub async fn deliver(&self, webhook: &Webhook, event: &WebhookEvent) -> WebhookDelivery {
+        let delivery_id = uuid::Uuid::new_v4().to_string();
+        let body = payload(webhook, event, &delivery_id).to_string();
+        let signature = sign(&webhook.secret, body.as_bytes());
+
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let response = self
+                .client
+                .post(&webhook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, &signature)
+                .header(EVENT_HEADER, event.kind.as_str())
+                .header(DELIVERY_HEADER, &delivery_id)
+                .body(body.clone())
+                .send()
+                .await;
+            let (status, error, retryable, retry_after) = match response {
+                Ok(response) if response.status().is_success() => {
+                    return WebhookDelivery {
+                        webhook_id: webhook.id.clone(),
+                        attempts,
+                        status: Some(response.status().as_u16()),
+                        error: None,
+                    };
+                }
+                Ok(response) => {
+                    let status = response.status();
+                    let retryable = status.is_server_error()
+                        || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status == reqwest::StatusCode::REQUEST_TIMEOUT;
+                    let retry_after = response
+                        .headers()
+                        .get(reqwest::header::RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| {
+                            rate_limit::parse_retry_after(
+                                value.trim(),
+                                chrono::Utc::now().timestamp_millis(),
+                            )
+                        })
+                        .map(Duration::from_millis)
+                        .filter(|wait| *wait <= MAX_RETRY_AFTER);
+                    (
+                        Some(status.as_u16()),
+                        format!("HTTP {status}"),
+                        retryable,
+                        retry_after,
+                    )
+                }
+                Err(e) => (None, e.to_string(), true, None),
+            };
+
+            if !retryable || attempts >= MAX_ATTEMPTS {
+                tracing::warn!(
+                    webhook_id = %webhook.id,
+                    attempts,
+                    error = %error,
+                    "Webhook delivery failed"
+                );
+                return WebhookDelivery {
+                    webhook_id: webhook.id.clone(),
+                    attempts,
+                    status,
+                    error: Some(error),
+                };
+            }
+            let backoff = self.first_retry_delay * 2u32.pow(attempts - 1);
+            tokio::time::sleep(retry_after.unwrap_or(backoff)).await;
+        }
+    }
+}
+
+/// The JSON body sent for `event`.
+pub fn payload(webhook: &Webhook, event: &WebhookEvent, delivery_id: &str) -> serde_json::Value {
+    let mut payload = json!({
+        "id": delivery_id,
+        "event": event.kind.as_str(),
+        "webhook_id": webhook.id,
+        "workspace_id": event.workspace_id,
+        "chat_id": event.chat_id,
+        "summary": event.summary,
+        "occurred_at": event.occurred_at,
+    });
+    if webhook.include_content {
+        if let Some(content) = &event.content {
+            payload["content"] = json!(content);
+        }
+    }
+    payload
+}
+
+/// `sha256=<hex>` HMAC-SHA256 signature of `body`.
+pub fn sign(secret: &str, body: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes keys of any length");
+    mac.update(body);
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// Requests a mock webhook receiver got, as (lowercase headers, body).
+    pub type ReceivedRequests = Arc<Mutex<Vec<(HashMap<String, String>, String)>>>;
+
+    /// A local HTTP server answering with `statuses` in turn, then 200.
+    pub async fn mock_webhook_receiver(statuses: Vec<u16>) -> (String, ReceivedRequests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = ReceivedRequests::default();
+        let log = received.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            while let Ok((stream, _)) = listener.accept().await {
+                let mut reader = BufReader::new(stream);
+                let mut headers = HashMap::new();
+                let mut line = String::new();
+                reader.read_line(&mut line).await.unwrap();
+                loop {
+                    line.clear();
+                    reader.read_line(&mut line).await.unwrap();
+                    let Some((name, value)) = line.trim_end().split_once(": ") else {
+                        break;
+                    };
+                    headers.insert(name.to_ascii_lowercase(), value.to_string());
+                }
+                let length = headers
+                    .get("content-length")
+                    .map_or(0, |l| l.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).await.unwrap();
+                log.lock()
+                    .unwrap()
+                    .push((headers, String::from_utf8(body).unwrap()));
+
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {status} Mock\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+                );
+                reader
+                    .into_inner()
+                    .write_all(response.as_bytes())
+                    .await
+                    .unwrap();
+            }
+        });
+        (url, received)
+    }
+
+    struct MemoryWebhookRepository(std::sync::Mutex<rusqlite::Connection>);
+
+    impl crate::features::webhook::WebhookRepository for MemoryWebhookRepository {
+        fn create(
+            &self,
+            webhook: &crate::features::webhook::Webhook,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::webhook::insert_webhook(&self.0.lock().unwrap(), webhook)
+        }
+
+        fn get_by_workspace_id(
+            &self,
+            workspace_id: &str,
+        ) -> Result<Vec<crate::features::webhook::Webhook>, crate::error::AppError> {
+            crate::features::webhook::webhooks_of_workspace(&self.0.lock().unwrap(), workspace_id)
+        }
+
+        fn get_by_id(
+            &self,
+            id: &str,
+        ) -> Result<Option<crate::features::webhook::Webhook>, crate::error::AppError> {
+            crate::features::webhook::webhook_by_id(&self.0.lock().unwrap(), id)
+        }
+
+        fn update(
+            &self,
+            webhook: &crate::features::webhook::Webhook,
+        ) -> Result<(), crate::error::AppError> {
+            crate::features::webhook::update_webhook(&self.0.lock().unwrap(), webhook)
+        }
+
+        fn delete(&self, id: &str) -> Result<(), crate::error::AppError> {
+            self.0
+                .lock()
+                .unwrap()
+                .execute("DELETE FROM webhooks WHERE id = ?1", [id])?;
+            Ok(())
+        }
+
+        fn record_delivery(
+            &self,
+            delivery: &crate::features::webhook::WebhookDelivery,
+            delivered_at: i64,
+            max_failures: i64,
+        ) -> Result<bool, crate::error::AppError> {
+            crate::features::webhook::record_delivery(
+                &self.0.lock().unwrap(),
+                delivery,
+                delivered_at,
+                max_failures,
+            )
+        }
+    }
+
+    fn webhook_repository(
+        url: &str,
+        include_content: bool,
+    ) -> std::sync::Arc<MemoryWebhookRepository> {
+        use crate::features::webhook::{Webhook, WebhookRepository};
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        crate::db::migrations::run_migrations(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO workspaces (id, name, created_at) VALUES ('w1', 'Work', 0)",
+            [],
+        )
+        .unwrap();
+        let repository = std::sync::Arc::new(MemoryWebhookRepository(std::sync::Mutex::new(conn)));
+        repository
+            .create(&Webhook {
+                id: "hook".to_string(),
+                workspace_id: "w1".to_string(),
+                url: url.to_string(),
+                secret: "whsec_test".to_string(),
+                enabled: true,
+                event_kinds: vec![
+                    "turn_completed".to_string(),
+                    "agent_task_failed".to_string(),
+                ],
+                include_content,
+                last_status: None,
+                last_error: None,
+                last_delivery_at: None,
+                failure_count: 0,
+                created_at: 0,
+                updated_at: 0,
+            })
+            .unwrap();
+        repository
+    }
+
+    #[test]
+    fn webhook_deliveries_are_signed_and_leave_content_out() {
+        use crate::features::activity::ActivityKind;
+        use crate::features::webhook::{sign, WebhookDispatcher, WebhookEvent, WebhookRepository};
+        use hmac::{Hmac, Mac};
+
+        // RFC 4231 test case 2
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (url, received) = mock_webhook_receiver(vec![]).await;
+            let event = WebhookEvent::new(ActivityKind::TurnCompleted, "w1", "c1", "Trip plan")
+                .with_content("The full answer");
+
+            for include_content in [false, true] {
+                let repository = webhook_repository(&url, include_content);
+                let webhook = repository.get_by_id("hook").unwrap().unwrap();
+                let dispatcher = WebhookDispatcher::new(repository);
+                let delivery = dispatcher.deliver(&webhook, &event).await;
+                assert!(delivery.succeeded());
+                assert_eq!((delivery.attempts, delivery.status), (1, Some(200)));
+
+                let (headers, body) = received.lock().unwrap().pop().unwrap();
+                let signature = headers["x-lunex-signature"]
+                    .strip_prefix("sha256=")
+                    .unwrap();
+                let mut mac = Hmac::<sha2::Sha256>::new_from_slice(b"whsec_test").unwrap();
+                mac.update(body.as_bytes());
+                mac.verify_slice(&hex::decode(signature).unwrap()).unwrap();
+                assert_eq!(headers["x-lunex-event"], "turn_completed");
+                assert_eq!(headers["content-type"], "application/json");
+
+                let payload: serde_json::Value = serde_json::from_str(&body).unwrap();
+                assert_eq!(payload["event"], "turn_completed");
+                assert_eq!(payload["chat_id"], "c1");
+                assert_eq!(payload["summary"], "Trip plan");
+                assert_eq!(payload["id"], headers["x-lunex-delivery"].as_str());
+                assert_eq!(
+                    payload.get("content").and_then(|c| c.as_str()),
+                    include_content.then_some("The full answer")
+                );
+            }
+        });
+    }
+
+    #[test]
+    fn webhook_deliveries_retry_and_disable_after_repeated_failures() {
+        use crate::features::activity::ActivityKind;
+        use crate::features::webhook::{
+            WebhookDispatcher, WebhookEvent, WebhookRepository, MAX_ATTEMPTS,
+            MAX_CONSECUTIVE_FAILURES,
+        };
+        use std::time::Duration;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let event =
+                WebhookEvent::new(ActivityKind::AgentTaskFailed, "w1", "c1", "@coder: boom");
+
+            // Server errors are retried until one gets through
+            let (url, received) = mock_webhook_receiver(vec![500, 503]).await;
+            let repository = webhook_repository(&url, false);
+            let dispatcher =
+                WebhookDispatcher::with_retry_delay(repository.clone(), Duration::from_millis(1));
+            let deliveries = dispatcher.deliver_event(&event).await;
+            assert_eq!(deliveries.len(), 1);
+            assert!(deliveries[0].succeeded());
+            assert_eq!(deliveries[0].attempts, 3);
+            // Every attempt is the same delivery
+            let ids: std::collections::HashSet<String> = received
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(headers, _)| headers["x-lunex-delivery"].clone())
+                .collect();
+            assert_eq!(ids.len(), 1);
+
+            // A rejected payload is not sent again
+            let (url, received) = mock_webhook_receiver(vec![400]).await;
+            let repository = webhook_repository(&url, false);
+            let dispatcher =
+                WebhookDispatcher::with_retry_delay(repository.clone(), Duration::from_millis(1));
+            let deliveries = dispatcher.deliver_event(&event).await;
+            assert_eq!(
+                (deliveries[0].attempts, deliveries[0].status),
+                (1, Some(400))
+            );
+            assert_eq!(received.lock().unwrap().len(), 1);
+            let webhook = repository.get_by_id("hook").unwrap().unwrap();
+            assert_eq!(webhook.failure_count, 1);
+            assert_eq!(webhook.last_error.as_deref(), Some("HTTP 400 Bad Request"));
+
+            // Events the webhook does not subscribe to are not sent
+            let other = WebhookEvent::new(ActivityKind::ToolDenied, "w1", "c1", "search: denied");
+            assert!(dispatcher.deliver_event(&other).await.is_empty());
+
+            let failures = usize::try_from(MAX_CONSECUTIVE_FAILURES).unwrap();
+            let statuses = vec![500; failures * MAX_ATTEMPTS as usize];
+            let (url, received) = mock_webhook_receiver(statuses).await;
+            let repository = webhook_repository(&url, false);
+            let dispatcher =
+                WebhookDispatcher::with_retry_delay(repository.clone(), Duration::from_millis(1));
+            for _ in 0..failures {
+                let deliveries = dispatcher.deliver_event(&event).await;
+                assert_eq!(deliveries[0].attempts, MAX_ATTEMPTS);
+                assert!(!deliveries[0].succeeded());
+            }
+            let webhook = repository.get_by_id("hook").unwrap().unwrap();
+            assert!(!webhook.enabled);
+            assert_eq!(webhook.failure_count, MAX_CONSECUTIVE_FAILURES);
+            assert_eq!(webhook.last_status, Some(500));
+            assert!(dispatcher.deliver_event(&event).await.is_empty());
+            assert_eq!(
+                received.lock().unwrap().len(),
+                failures * MAX_ATTEMPTS as usize
+            );
+        });
+    }
+}
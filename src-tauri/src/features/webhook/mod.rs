@@ -0,0 +1,10 @@
+pub mod commands;
+pub mod dispatcher;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use dispatcher::*;
+pub use models::*;
+pub use repository::*;
+pub use service::*;
@@ -0,0 +1,81 @@
+use crate::features::activity::ActivityKind;
+use serde::{Deserialize, Serialize};
+
+/// An endpoint notified of a workspace's activity.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: String,
+    pub workspace_id: String,
+    pub url: String,
+    /// Key of the body signature in `X-Lunex-Signature`
+    pub secret: String,
+    pub enabled: bool,
+    /// Activity kinds delivered, e.g. `["agent_task_failed"]`
+    pub event_kinds: Vec<String>,
+    /// Send the message or task output along with the summary
+    pub include_content: bool,
+    /// HTTP status of the last delivery's final attempt, `None` when it got
+    /// no response
+    pub last_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub last_delivery_at: Option<i64>,
+    /// Deliveries failed in a row; the webhook is disabled at
+    /// `MAX_CONSECUTIVE_FAILURES`
+    pub failure_count: i64,
+    pub created_at: i64,
+    pub updated_at: i64,
+}
+
+impl Webhook {
+    pub fn subscribes_to(&self, kind: ActivityKind) -> bool {
+        self.enabled && self.event_kinds.iter().any(|k| k == kind.as_str())
+    }
+}
+
+/// Something that happened in a workspace, as delivered to its webhooks.
+#[derive(Debug, Clone)]
+pub struct WebhookEvent {
+    pub kind: ActivityKind,
+    pub workspace_id: String,
+    pub chat_id: String,
+    /// Short, content-free description, e.g. the chat title or the error
+    pub summary: String,
+    /// Full output, only sent to webhooks with `include_content`
+    pub content: Option<String>,
+    pub occurred_at: i64,
+}
+
+impl WebhookEvent {
+    pub fn new(kind: ActivityKind, workspace_id: &str, chat_id: &str, summary: &str) -> Self {
+        Self {
+            kind,
+            workspace_id: workspace_id.to_string(),
+            chat_id: chat_id.to_string(),
+            summary: summary.to_string(),
+            content: None,
+            occurred_at: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    #[must_use]
+    pub fn with_content(mut self, content: &str) -> Self {
+        self.content = Some(content.to_string());
+        self
+    }
+}
+
+/// How a delivery to one webhook ended.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WebhookDelivery {
+    pub webhook_id: String,
+    pub attempts: u32,
+    /// HTTP status of the final attempt
+    pub status: Option<u16>,
+    pub error: Option<String>,
+}
+
+impl WebhookDelivery {
+    pub const fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
@@ -0,0 +1,197 @@
+use super::models::{Webhook, WebhookDelivery};
+use crate::error::AppError;
+use rusqlite::{params, Connection};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait WebhookRepository: Send + Sync {
+    fn create(&self, webhook: &Webhook) -> Result<(), AppError>;
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<Webhook>, AppError>;
+    fn get_by_id(&self, id: &str) -> Result<Option<Webhook>, AppError>;
+    fn update(&self, webhook: &Webhook) -> Result<(), AppError>;
+    fn delete(&self, id: &str) -> Result<(), AppError>;
+    /// Store the outcome of a delivery; returns whether the webhook was
+    /// disabled for reaching `max_failures` failures in a row.
+    fn record_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        delivered_at: i64,
+        max_failures: i64,
+    ) -> Result<bool, AppError>;
+}
+
+pub struct SqliteWebhookRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteWebhookRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl WebhookRepository for SqliteWebhookRepository {
+    fn create(&self, webhook: &Webhook) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        insert_webhook(&conn, webhook)
+    }
+
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<Webhook>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        webhooks_of_workspace(&conn, workspace_id)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Option<Webhook>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        webhook_by_id(&conn, id)
+    }
+
+    fn update(&self, webhook: &Webhook) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        update_webhook(&conn, webhook)
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        conn.execute("DELETE FROM webhooks WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    fn record_delivery(
+        &self,
+        delivery: &WebhookDelivery,
+        delivered_at: i64,
+        max_failures: i64,
+    ) -> Result<bool, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        record_delivery(&conn, delivery, delivered_at, max_failures)
+    }
+}
+
+const COLUMNS: &str = "id, workspace_id, url, secret, enabled, event_kinds, include_content, last_status, last_error, last_delivery_at, failure_count, created_at, updated_at";
+
+pub fn insert_webhook(conn: &Connection, webhook: &Webhook) -> Result<(), AppError> {
+    conn.execute(
+        &format!(
+            "INSERT INTO webhooks ({COLUMNS}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)"
+        ),
+        params![
+            webhook.id,
+            webhook.workspace_id,
+            webhook.url,
+            webhook.secret,
+            webhook.enabled,
+            serde_json::to_string(&webhook.event_kinds)?,
+            webhook.include_content,
+            webhook.last_status,
+            webhook.last_error,
+            webhook.last_delivery_at,
+            webhook.failure_count,
+            webhook.created_at,
+            webhook.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn webhooks_of_workspace(
+    conn: &Connection,
+    workspace_id: &str,
+) -> Result<Vec<Webhook>, AppError> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {COLUMNS} FROM webhooks WHERE workspace_id = ?1 ORDER BY created_at"
+    ))?;
+    let webhooks = stmt
+        .query_map(params![workspace_id], row_to_webhook)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(webhooks)
+}
+
+pub fn webhook_by_id(conn: &Connection, id: &str) -> Result<Option<Webhook>, AppError> {
+    let result = conn.query_row(
+        &format!("SELECT {COLUMNS} FROM webhooks WHERE id = ?1"),
+        params![id],
+        row_to_webhook,
+    );
+    match result {
+        Ok(webhook) => Ok(Some(webhook)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Save the settings of a webhook; its delivery log is left alone.
+pub fn update_webhook(conn: &Connection, webhook: &Webhook) -> Result<(), AppError> {
+    conn.execute(
+        "UPDATE webhooks SET url = ?1, secret = ?2, enabled = ?3, event_kinds = ?4, include_content = ?5, failure_count = ?6, updated_at = ?7 WHERE id = ?8",
+        params![
+            webhook.url,
+            webhook.secret,
+            webhook.enabled,
+            serde_json::to_string(&webhook.event_kinds)?,
+            webhook.include_content,
+            webhook.failure_count,
+            webhook.updated_at,
+            webhook.id
+        ],
+    )?;
+    Ok(())
+}
+
+/// A success resets the failure count; a failure counts up and disables the
+/// webhook once it reaches `max_failures`, in the same statement so
+/// concurrent deliveries cannot skip past the limit.
+pub fn record_delivery(
+    conn: &Connection,
+    delivery: &WebhookDelivery,
+    delivered_at: i64,
+    max_failures: i64,
+) -> Result<bool, AppError> {
+    let status = delivery.status.map(i64::from);
+    if delivery.succeeded() {
+        conn.execute(
+            "UPDATE webhooks SET last_status = ?1, last_error = NULL, last_delivery_at = ?2, failure_count = 0 WHERE id = ?3",
+            params![status, delivered_at, delivery.webhook_id],
+        )?;
+        return Ok(false);
+    }
+    let disabled = conn.query_row(
+        "UPDATE webhooks SET last_status = ?1, last_error = ?2, last_delivery_at = ?3,
+            failure_count = failure_count + 1,
+            enabled = CASE WHEN failure_count + 1 >= ?4 THEN 0 ELSE enabled END
+         WHERE id = ?5
+         RETURNING enabled = 0 AND failure_count = ?4",
+        params![
+            status,
+            delivery.error,
+            delivered_at,
+            max_failures,
+            delivery.webhook_id
+        ],
+        |row| row.get(0),
+    );
+    match disabled {
+        Ok(disabled) => Ok(disabled),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn row_to_webhook(row: &rusqlite::Row) -> rusqlite::Result<Webhook> {
+    let event_kinds: String = row.get(5)?;
+    Ok(Webhook {
+        id: row.get(0)?,
+        workspace_id: row.get(1)?,
+        url: row.get(2)?,
+        secret: row.get(3)?,
+        enabled: row.get(4)?,
+        event_kinds: serde_json::from_str(&event_kinds).unwrap_or_default(),
+        include_content: row.get(6)?,
+        last_status: row.get(7)?,
+        last_error: row.get(8)?,
+        last_delivery_at: row.get(9)?,
+        failure_count: row.get(10)?,
+        created_at: row.get(11)?,
+        updated_at: row.get(12)?,
+    })
+}
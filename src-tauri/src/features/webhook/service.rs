@@ -0,0 +1,207 @@
+use super::models::Webhook;
+use super::repository::WebhookRepository;
+use crate::error::AppError;
+use crate::features::activity::ActivityKind;
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// Set to allow `http://` and loopback webhook URLs, for trying a receiver
+/// on the local machine.
+pub const ALLOW_LOCAL_WEBHOOKS_ENV: &str = "LUNEX_WEBHOOKS_ALLOW_LOCAL";
+const MAX_URL_CHARS: usize = 2_000;
+
+pub struct WebhookService {
+    repository: Arc<dyn WebhookRepository>,
+    allow_local: bool,
+}
+
+impl WebhookService {
+    pub fn new(repository: Arc<dyn WebhookRepository>) -> Self {
+        Self {
+            repository,
+            allow_local: std::env::var(ALLOW_LOCAL_WEBHOOKS_ENV).is_ok(),
+        }
+    }
+
+    /// A secret is generated when none is given.
+    pub fn create(
+        &self,
+        workspace_id: String,
+        url: &str,
+        secret: Option<String>,
+        event_kinds: Vec<String>,
+        include_content: bool,
+    ) -> Result<Webhook, AppError> {
+        let url = validate_url(url, self.allow_local)?;
+        let event_kinds = validate_event_kinds(event_kinds)?;
+        let now = chrono::Utc::now().timestamp_millis();
+
+        let webhook = Webhook {
+            id: uuid::Uuid::new_v4().to_string(),
+            workspace_id,
+            url,
+            secret: secret
+                .filter(|s| !s.trim().is_empty())
+                .unwrap_or_else(generate_secret),
+            enabled: true,
+            event_kinds,
+            include_content,
+            last_status: None,
+            last_error: None,
+            last_delivery_at: None,
+            failure_count: 0,
+            created_at: now,
+            updated_at: now,
+        };
+
+        self.repository.create(&webhook)?;
+        Ok(webhook)
+    }
+
+    pub fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Vec<Webhook>, AppError> {
+        self.repository.get_by_workspace_id(workspace_id)
+    }
+
+    pub fn get_by_id(&self, id: &str) -> Result<Webhook, AppError> {
+        self.repository
+            .get_by_id(id)?
+            .ok_or_else(|| AppError::NotFound(format!("Webhook not found: {id}")))
+    }
+
+    /// Fields left `None` are kept. Enabling a webhook clears its failure
+    /// count, so one more failure does not disable it again.
+    pub fn update(
+        &self,
+        id: &str,
+        url: Option<String>,
+        secret: Option<String>,
+        enabled: Option<bool>,
+        event_kinds: Option<Vec<String>>,
+        include_content: Option<bool>,
+    ) -> Result<Webhook, AppError> {
+        let mut webhook = self.get_by_id(id)?;
+        if let Some(url) = url {
+            webhook.url = validate_url(&url, self.allow_local)?;
+        }
+        if let Some(secret) = secret.filter(|s| !s.trim().is_empty()) {
+            webhook.secret = secret;
+        }
+        if let Some(enabled) = enabled {
+            if enabled {
+                webhook.failure_count = 0;
+            }
+            webhook.enabled = enabled;
+        }
+        if let Some(kinds) = event_kinds {
+            webhook.event_kinds = validate_event_kinds(kinds)?;
+        }
+        if let Some(include_content) = include_content {
+            webhook.include_content = include_content;
+        }
+        webhook.updated_at = chrono::Utc::now().timestamp_millis();
+
+        self.repository.update(&webhook)?;
+        Ok(webhook)
+    }
+
+    pub fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.repository.delete(id)
+    }
+}
+
+/// The URL as stored. Only `https` is accepted, and no loopback host unless
+/// `allow_local`, which also accepts `http`.
+pub fn validate_url(url: &str, allow_local: bool) -> Result<String, AppError> {
+    let url = url.trim();
+    if url.chars().count() > MAX_URL_CHARS {
+        return Err(AppError::Validation(format!(
+            "Webhook URL is longer than {MAX_URL_CHARS} characters"
+        )));
+    }
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| AppError::Validation(format!("Invalid webhook URL: {e}")))?;
+    match parsed.scheme() {
+        "https" => {}
+        "http" if allow_local => {}
+        scheme => {
+            return Err(AppError::Validation(format!(
+                "Webhook URL must use https, not {scheme}"
+            )))
+        }
+    }
+    let host = parsed
+        .host_str()
+        .ok_or_else(|| AppError::Validation("Webhook URL has no host".to_string()))?;
+    if !allow_local && is_local_host(host) {
+        return Err(AppError::Validation(format!(
+            "Webhook URL points at this machine ({host})"
+        )));
+    }
+    Ok(parsed.to_string())
+}
+
+fn is_local_host(host: &str) -> bool {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return ip.is_loopback() || ip.is_unspecified();
+    }
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    host == "localhost" || host.ends_with(".localhost")
+}
+
+/// Known activity kinds, in the order given, without repeats.
+fn validate_event_kinds(kinds: Vec<String>) -> Result<Vec<String>, AppError> {
+    let mut valid: Vec<String> = Vec::new();
+    for kind in kinds {
+        if ActivityKind::parse(&kind).is_none() {
+            return Err(AppError::Validation(format!(
+                "Unknown webhook event: {kind}"
+            )));
+        }
+        if !valid.contains(&kind) {
+            valid.push(kind);
+        }
+    }
+    if valid.is_empty() {
+        return Err(AppError::Validation(
+            "A webhook needs at least one event".to_string(),
+        ));
+    }
+    Ok(valid)
+}
+
+fn generate_secret() -> String {
+    format!(
+        "whsec_{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn webhook_urls_must_be_https_and_remote() {
+        use crate::features::webhook::validate_url;
+
+        assert_eq!(
+            validate_url(" https://hooks.slack.com/services/T0/B0/x ", false).unwrap(),
+            "https://hooks.slack.com/services/T0/B0/x"
+        );
+        for url in [
+            "http://example.com/hook",
+            "ftp://example.com/hook",
+            "https://localhost/hook",
+            "https://api.localhost:8443/hook",
+            "https://127.0.0.1/hook",
+            "https://[::1]/hook",
+            "https://0.0.0.0/hook",
+            "not a url",
+        ] {
+            assert!(validate_url(url, false).is_err(), "{url} accepted");
+        }
+        // The dev flag allows a receiver on this machine
+        assert!(validate_url("http://localhost:3000/hook", true).is_ok());
+        assert!(validate_url("ftp://localhost/hook", true).is_err());
+    }
+}
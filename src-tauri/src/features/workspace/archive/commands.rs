@@ -0,0 +1,36 @@
+use super::models::{WorkspaceArchiveSummary, WorkspaceImportReport};
+use crate::error::AppError;
+use crate::state::AppState;
+use std::path::PathBuf;
+use tauri::State;
+
+/// Export the workspace, its chats and attachments, the prompts and the MCP
+/// connections its tools use to a zip at `path`. Credentials in the MCP
+/// connections are left out and listed in the result.
+#[tauri::command]
+pub async fn export_workspace_archive(
+    state: State<'_, AppState>,
+    workspace_id: String,
+    path: String,
+) -> Result<WorkspaceArchiveSummary, AppError> {
+    state
+        .workspace_feature
+        .archive_service
+        .export(workspace_id, PathBuf::from(path))
+        .await
+}
+
+/// Import a workspace exported with `export_workspace_archive`, keeping its
+/// ids. The MCP credentials left out of it are listed in the report so they
+/// can be asked for again.
+#[tauri::command]
+pub async fn import_workspace_archive(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<WorkspaceImportReport, AppError> {
+    state
+        .workspace_feature
+        .archive_service
+        .import(PathBuf::from(path))
+        .await
+}
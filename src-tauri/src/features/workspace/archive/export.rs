@@ -0,0 +1,332 @@
+//! Workspace archives, for moving a whole workspace to another machine.
+//!
+//! An archive is a zip holding `manifest.json`, `data.json` with the rows of
+//! the workspace (settings, notes, quick actions, chats, branches, messages
+//! and tool permissions), the prompts and the MCP connections its tools come
+//! from, and the attachment files of its messages under `files/`.
+//!
+//! Unlike a backup, an archive is meant to leave the machine: message fields
+//! are decrypted into it, and credentials in the MCP connections are replaced
+//! by `${NAME}` placeholders and listed in the manifest. LLM connections,
+//! whose API keys are the point of them, and documents attached for
+//! retrieval, which are embedded per machine, are not part of it.
+
+use super::models::{
+    ArchivedFile, Row, StrippedSecrets, WorkspaceArchiveData, WorkspaceArchiveManifest,
+    WorkspaceArchiveSummary,
+};
+use super::rows::{select_rows, text};
+use crate::db::migrations;
+use crate::error::AppError;
+use crate::features::attachment::AttachmentService;
+use crate::features::encryption::FieldCipher;
+use crate::features::mcp_connection::validation::placeholders;
+use crate::features::redaction::secrets::{is_secret_field, mask_text};
+use rusqlite::{params, Connection};
+use serde_json::Value;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+pub const ARCHIVE_FORMAT: &str = "lunex-workspace";
+pub const FORMAT_VERSION: u32 = 1;
+pub const MANIFEST_FILE: &str = "manifest.json";
+pub const DATA_FILE: &str = "data.json";
+pub const FILES_DIR: &str = "files";
+
+/// Message columns that may be encrypted at rest.
+pub const SEALED_MESSAGE_COLUMNS: &[&str] = &["content", "reasoning", "metadata"];
+
+/// Headers that carry credentials whatever their value looks like.
+const CREDENTIAL_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+
+const CHATS_OF_WORKSPACE: &str = "SELECT id FROM chats WHERE workspace_id = ?1";
+
+/// Write the workspace `workspace_id` to a new archive at `dest`, reading
+/// attachments from `files_dir`. The archive only appears under its name
+/// once complete.
+pub fn export_workspace(
+    conn: &Connection,
+    cipher: &FieldCipher,
+    workspace_id: &str,
+    files_dir: &Path,
+    dest: &Path,
+) -> Result<WorkspaceArchiveSummary, AppError> {
+    let (mut data, sources, missing_files) = read_workspace(conn, cipher, workspace_id, files_dir)?;
+    let stripped_secrets = strip_connection_secrets(&mut data.mcp_connections);
+
+    let manifest = WorkspaceArchiveManifest {
+        format: ARCHIVE_FORMAT.to_string(),
+        format_version: FORMAT_VERSION,
+        schema_version: migrations::applied_migrations(conn)?
+            .last()
+            .map_or(0, |m| m.version),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        exported_at: chrono::Utc::now().timestamp_millis(),
+        workspace_id: workspace_id.to_string(),
+        workspace_name: text(&data.workspace, "name")
+            .unwrap_or_default()
+            .to_string(),
+        chat_count: count(data.chats.len()),
+        message_count: count(data.messages.len()),
+        file_count: count(data.files.len()),
+        stripped_secrets,
+    };
+
+    let partial = dest.with_extension("partial");
+    let result = write_archive(&manifest, &data, &sources, &partial)
+        .and_then(|()| fs::rename(&partial, dest).map_err(AppError::from));
+    if result.is_err() {
+        let _ = fs::remove_file(&partial);
+    }
+    result?;
+    Ok(WorkspaceArchiveSummary {
+        path: dest.to_string_lossy().to_string(),
+        manifest,
+        missing_files,
+    })
+}
+
+fn count(len: usize) -> u32 {
+    u32::try_from(len).unwrap_or(u32::MAX)
+}
+
+/// The rows an archive of the workspace holds, with message fields
+/// decrypted, where its attachment files are, and how many referenced
+/// attachments were missing.
+fn read_workspace(
+    conn: &Connection,
+    cipher: &FieldCipher,
+    workspace_id: &str,
+    files_dir: &Path,
+) -> Result<(WorkspaceArchiveData, Vec<PathBuf>, u32), AppError> {
+    let workspace = select_rows(
+        conn,
+        "SELECT * FROM workspaces WHERE id = ?1",
+        params![workspace_id],
+    )?
+    .pop()
+    .ok_or_else(|| AppError::NotFound(format!("Workspace not found: {workspace_id}")))?;
+    let of_workspace = |table: &str| {
+        select_rows(
+            conn,
+            &format!("SELECT * FROM {table} WHERE workspace_id = ?1"),
+            params![workspace_id],
+        )
+    };
+    let of_chats = |table: &str| {
+        select_rows(
+            conn,
+            &format!(
+                "SELECT * FROM {table} WHERE chat_id IN ({CHATS_OF_WORKSPACE}) ORDER BY rowid"
+            ),
+            params![workspace_id],
+        )
+    };
+
+    let workspace_settings = of_workspace("workspace_settings")?;
+    let mut messages = of_chats("messages")?;
+    for message in &mut messages {
+        open_columns(cipher, message)?;
+    }
+    let mcp_connections = select_rows(conn, "SELECT * FROM mcp_server_connections", [])?
+        .into_iter()
+        .filter(|connection| {
+            text(connection, "id").is_some_and(|id| {
+                workspace_settings
+                    .iter()
+                    .any(|settings| tool_connection_ids(settings).contains(id))
+            })
+        })
+        .collect();
+    let (files, sources, missing_files) = referenced_files(conn, &messages)?;
+
+    Ok((
+        WorkspaceArchiveData {
+            workspace,
+            chat_input_settings: of_workspace("chat_input_settings")?,
+            workspace_notes: of_workspace("workspace_notes")?,
+            quick_actions: of_workspace("quick_actions")?,
+            workspace_settings,
+            chats: select_rows(
+                conn,
+                "SELECT * FROM chats WHERE workspace_id = ?1 ORDER BY rowid",
+                params![workspace_id],
+            )?,
+            chat_branches: of_chats("chat_branches")?,
+            messages,
+            chat_tool_permissions: of_chats("chat_tool_permissions")?,
+            prompts: select_rows(conn, "SELECT * FROM prompts ORDER BY rowid", [])?,
+            mcp_connections,
+            files,
+            files_dir: files_dir.to_string_lossy().to_string(),
+        },
+        sources,
+        missing_files,
+    ))
+}
+
+fn open_columns(cipher: &FieldCipher, message: &mut Row) -> Result<(), AppError> {
+    for column in SEALED_MESSAGE_COLUMNS {
+        if let Some(Value::String(value)) = message.get_mut(*column) {
+            *value = cipher.open(std::mem::take(value))?;
+        }
+    }
+    Ok(())
+}
+
+/// Ids of the MCP connections the workspace's tools come from.
+pub fn tool_connection_ids(settings: &Row) -> BTreeSet<String> {
+    text(settings, "mcp_tool_ids")
+        .and_then(|json| serde_json::from_str::<serde_json::Map<String, Value>>(json).ok())
+        .map(|tools| {
+            tools
+                .values()
+                .filter_map(|id| id.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The stored attachments the messages reference with their paths, and how
+/// many of them are gone from disk.
+fn referenced_files(
+    conn: &Connection,
+    messages: &[Row],
+) -> Result<(Vec<ArchivedFile>, Vec<PathBuf>, u32), AppError> {
+    let hashes: BTreeSet<String> = messages
+        .iter()
+        .filter_map(|message| text(message, "metadata"))
+        .flat_map(AttachmentService::extract_file_hashes)
+        .collect();
+    let mut files = Vec::new();
+    let mut sources = Vec::new();
+    let mut missing = 0;
+    for hash in hashes {
+        let stored = select_rows(
+            conn,
+            "SELECT path, size FROM file_hashes WHERE hash = ?1",
+            params![hash],
+        )?;
+        let file = stored.first().and_then(|row| {
+            let path = PathBuf::from(text(row, "path")?);
+            let file = ArchivedFile {
+                name: path.file_name()?.to_string_lossy().to_string(),
+                size: row.get("size").and_then(Value::as_i64).unwrap_or(0),
+                hash: hash.clone(),
+            };
+            path.is_file().then_some((file, path))
+        });
+        let Some((file, path)) = file else {
+            missing += 1;
+            continue;
+        };
+        files.push(file);
+        sources.push(path);
+    }
+    Ok((files, sources, missing))
+}
+
+fn write_archive(
+    manifest: &WorkspaceArchiveManifest,
+    data: &WorkspaceArchiveData,
+    sources: &[PathBuf],
+    partial: &Path,
+) -> Result<(), AppError> {
+    let mut zip = zip::ZipWriter::new(fs::File::create(partial)?);
+    let options = zip::write::SimpleFileOptions::default().large_file(true);
+    // Attachments are mostly compressed already
+    let stored = options.compression_method(zip::CompressionMethod::Stored);
+    zip.start_file(MANIFEST_FILE, options)?;
+    zip.write_all(&serde_json::to_vec_pretty(manifest)?)?;
+    zip.start_file(DATA_FILE, options)?;
+    zip.write_all(&serde_json::to_vec(data)?)?;
+    for (file, source) in data.files.iter().zip(sources) {
+        zip.start_file(format!("{FILES_DIR}/{}", file.name), stored)?;
+        std::io::copy(&mut fs::File::open(source)?, &mut zip)?;
+    }
+    zip.finish()?.sync_all()?;
+    Ok(())
+}
+
+/// Replace the credentials in the headers, env vars and URL of each MCP
+/// connection and drop its auth token, returning what was left out of which.
+pub fn strip_connection_secrets(connections: &mut [Row]) -> Vec<StrippedSecrets> {
+    let mut stripped = Vec::new();
+    for connection in connections.iter_mut() {
+        let mut fields = Vec::new();
+        for column in ["headers", "env_vars"] {
+            let Some(mut values) = text(connection, column)
+                .and_then(|json| serde_json::from_str::<serde_json::Map<String, Value>>(json).ok())
+            else {
+                continue;
+            };
+            let mut changed = false;
+            for (name, value) in &mut values {
+                if let Value::String(value) = value {
+                    if is_credential(name, value) {
+                        *value = format!("${{{}}}", placeholder_name(name));
+                        fields.push(format!("{column}.{name}"));
+                        changed = true;
+                    }
+                }
+            }
+            if changed {
+                connection.insert(
+                    column.to_string(),
+                    Value::String(Value::Object(values).to_string()),
+                );
+            }
+        }
+        if text(connection, "auth_token").is_some_and(|token| !token.is_empty()) {
+            connection.insert("auth_token".to_string(), Value::Null);
+            fields.push("auth_token".to_string());
+        }
+        if let Some(masked) = text(connection, "url").and_then(mask_text) {
+            connection.insert("url".to_string(), Value::String(masked));
+            fields.push("url".to_string());
+        }
+        // Nothing is connected on the machine the archive goes to
+        if connection.contains_key("status") {
+            connection.insert("status".to_string(), Value::from("disconnected"));
+        }
+
+        if !fields.is_empty() {
+            stripped.push(StrippedSecrets {
+                connection_id: text(connection, "id").unwrap_or_default().to_string(),
+                connection_name: text(connection, "name").unwrap_or_default().to_string(),
+                fields,
+            });
+        }
+    }
+    stripped
+}
+
+/// Whether a header or env var holds a credential. Values that only refer to
+/// one through a placeholder are kept.
+fn is_credential(name: &str, value: &str) -> bool {
+    if value.trim().is_empty() {
+        return false;
+    }
+    let looks_secret = mask_text(value).is_some();
+    if !looks_secret && !placeholders(value).is_empty() {
+        return false;
+    }
+    looks_secret
+        || is_secret_field(name)
+        || CREDENTIAL_HEADERS.contains(&name.to_ascii_lowercase().as_str())
+}
+
+/// `NAME` of the placeholder a stripped value is replaced by.
+fn placeholder_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_uppercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,9 @@
+pub mod commands;
+pub mod export;
+pub mod import;
+pub mod models;
+pub mod rows;
+pub mod service;
+
+pub use models::*;
+pub use service::*;
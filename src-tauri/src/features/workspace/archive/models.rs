@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+
+/// A table row by column name, as stored in `data.json`.
+pub type Row = serde_json::Map<String, serde_json::Value>;
+
+/// `manifest.json` of a workspace archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceArchiveManifest {
+    /// Always `lunex-workspace`
+    pub format: String,
+    pub format_version: u32,
+    /// Schema version of the database the archive was made from
+    pub schema_version: u32,
+    pub app_version: String,
+    pub exported_at: i64,
+    pub workspace_id: String,
+    pub workspace_name: String,
+    pub chat_count: u32,
+    pub message_count: u32,
+    pub file_count: u32,
+    /// Secrets left out of the MCP connections
+    pub stripped_secrets: Vec<StrippedSecrets>,
+}
+
+/// The secrets left out of one MCP connection. Header and env var values are
+/// replaced by `${NAME}` placeholders, so the connection checks report them
+/// until they are filled in again.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StrippedSecrets {
+    pub connection_id: String,
+    pub connection_name: String,
+    /// `headers.<name>`, `env_vars.<name>`, `auth_token` or `url`
+    pub fields: Vec<String>,
+}
+
+/// An attachment file in the archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchivedFile {
+    pub hash: String,
+    /// Name under `files/` in the archive and in the attachments directory
+    pub name: String,
+    pub size: i64,
+}
+
+/// `data.json` of a workspace archive: the rows of the workspace, its chats
+/// and what they use. Message fields are in plaintext.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct WorkspaceArchiveData {
+    pub workspace: Row,
+    pub workspace_settings: Vec<Row>,
+    pub chat_input_settings: Vec<Row>,
+    pub workspace_notes: Vec<Row>,
+    pub quick_actions: Vec<Row>,
+    pub chats: Vec<Row>,
+    pub chat_branches: Vec<Row>,
+    pub messages: Vec<Row>,
+    pub chat_tool_permissions: Vec<Row>,
+    pub prompts: Vec<Row>,
+    pub mcp_connections: Vec<Row>,
+    pub files: Vec<ArchivedFile>,
+    /// Attachments directory of the exporting machine, which the paths in
+    /// message metadata start with
+    pub files_dir: String,
+}
+
+/// What `export_workspace_archive` wrote.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceArchiveSummary {
+    pub path: String,
+    pub manifest: WorkspaceArchiveManifest,
+    /// Attachments referenced by messages whose file was gone
+    pub missing_files: u32,
+}
+
+/// What `import_workspace_archive` added.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceImportReport {
+    pub workspace_id: String,
+    pub workspace_name: String,
+    pub chats: u32,
+    pub messages: u32,
+    pub files: u32,
+    /// Prompts and MCP connections with an id already here are kept as they are
+    pub prompts_added: u32,
+    pub mcp_connections_added: u32,
+    /// The workspace's LLM connection does not exist here and was unset
+    pub llm_connection_cleared: bool,
+    /// Secrets to fill in again on the imported MCP connections
+    pub stripped_secrets: Vec<StrippedSecrets>,
+}
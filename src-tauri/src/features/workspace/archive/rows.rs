@@ -0,0 +1,101 @@
+//! Table rows as JSON maps, so an archive carries every column of the
+//! release that made it and an import fills in those this release has.
+
+use super::models::Row;
+use crate::error::AppError;
+use rusqlite::types::{Value as SqlValue, ValueRef};
+use rusqlite::{Connection, Params};
+use serde_json::Value;
+use std::collections::HashSet;
+
+/// Rows `sql` selects, by column name.
+pub fn select_rows<P: Params>(
+    conn: &Connection,
+    sql: &str,
+    params: P,
+) -> Result<Vec<Row>, AppError> {
+    let mut stmt = conn.prepare(sql)?;
+    let names: Vec<String> = stmt
+        .column_names()
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let rows = stmt.query_map(params, |row| {
+        let mut map = Row::new();
+        for (i, name) in names.iter().enumerate() {
+            map.insert(name.clone(), to_json(row.get_ref(i)?));
+        }
+        Ok(map)
+    })?;
+    Ok(rows.collect::<Result<Vec<_>, _>>()?)
+}
+
+fn to_json(value: ValueRef<'_>) -> Value {
+    match value {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::from(i),
+        ValueRef::Real(f) => serde_json::Number::from_f64(f).map_or(Value::Null, Value::Number),
+        ValueRef::Text(text) => Value::String(String::from_utf8_lossy(text).into_owned()),
+        ValueRef::Blob(bytes) => Value::Array(bytes.iter().map(|b| Value::from(*b)).collect()),
+    }
+}
+
+fn to_sql(value: &Value) -> SqlValue {
+    match value {
+        Value::Null => SqlValue::Null,
+        Value::Bool(b) => SqlValue::Integer(i64::from(*b)),
+        Value::Number(n) => n.as_i64().map_or_else(
+            || SqlValue::Real(n.as_f64().unwrap_or(0.0)),
+            SqlValue::Integer,
+        ),
+        Value::String(s) => SqlValue::Text(s.clone()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| item.as_u64().and_then(|b| u8::try_from(b).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .map_or_else(|| SqlValue::Text(value.to_string()), SqlValue::Blob),
+        Value::Object(_) => SqlValue::Text(value.to_string()),
+    }
+}
+
+/// Columns `table` has in this database.
+pub fn table_columns(conn: &Connection, table: &str) -> Result<HashSet<String>, AppError> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({table})"))?;
+    let columns = stmt.query_map([], |row| row.get::<_, String>(1))?;
+    Ok(columns.collect::<Result<HashSet<_>, _>>()?)
+}
+
+/// Insert `row` into `table`, leaving out the columns it does not have.
+/// `table` and `columns` come from the caller, never from the archive.
+pub fn insert_row(
+    conn: &Connection,
+    table: &str,
+    columns: &HashSet<String>,
+    row: &Row,
+) -> Result<(), AppError> {
+    let (names, values): (Vec<&str>, Vec<SqlValue>) = row
+        .iter()
+        .filter(|(name, _)| columns.contains(name.as_str()))
+        .map(|(name, value)| (name.as_str(), to_sql(value)))
+        .unzip();
+    if names.is_empty() {
+        return Err(AppError::Validation(format!(
+            "The archive has an empty row for {table}"
+        )));
+    }
+    let placeholders: Vec<String> = (1..=names.len()).map(|i| format!("?{i}")).collect();
+    conn.execute(
+        &format!(
+            "INSERT INTO {table} ({}) VALUES ({})",
+            names.join(", "),
+            placeholders.join(", ")
+        ),
+        rusqlite::params_from_iter(values),
+    )?;
+    Ok(())
+}
+
+/// The string in column `name` of `row`, if there is one.
+pub fn text<'a>(row: &'a Row, name: &str) -> Option<&'a str> {
+    row.get(name).and_then(Value::as_str)
+}
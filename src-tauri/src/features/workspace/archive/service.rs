@@ -0,0 +1,60 @@
+use super::export::export_workspace;
+use super::import::import_workspace;
+use super::models::{WorkspaceArchiveSummary, WorkspaceImportReport};
+use crate::error::AppError;
+use crate::features::attachment::AttachmentService;
+use crate::features::encryption::FieldCipher;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub struct WorkspaceArchiveService {
+    app: Arc<AppHandle>,
+    cipher: Arc<FieldCipher>,
+    attachment_service: Arc<AttachmentService>,
+}
+
+impl WorkspaceArchiveService {
+    pub const fn new(
+        app: Arc<AppHandle>,
+        cipher: Arc<FieldCipher>,
+        attachment_service: Arc<AttachmentService>,
+    ) -> Self {
+        Self {
+            app,
+            cipher,
+            attachment_service,
+        }
+    }
+
+    /// Write the workspace with its chats, attachments, prompts and MCP
+    /// connections to a zip at `path`.
+    pub async fn export(
+        &self,
+        workspace_id: String,
+        path: PathBuf,
+    ) -> Result<WorkspaceArchiveSummary, AppError> {
+        let app = self.app.clone();
+        let cipher = self.cipher.clone();
+        let files_dir = self.attachment_service.files_dir().to_path_buf();
+        tauri::async_runtime::spawn_blocking(move || {
+            let conn = crate::db::get_connection(&app)?;
+            export_workspace(&conn, &cipher, &workspace_id, &files_dir, &path)
+        })
+        .await
+        .map_err(|e| AppError::Generic(format!("Workspace export failed: {e}")))?
+    }
+
+    /// Add the workspace in the archive at `path`.
+    pub async fn import(&self, path: PathBuf) -> Result<WorkspaceImportReport, AppError> {
+        let app = self.app.clone();
+        let cipher = self.cipher.clone();
+        let files_dir = self.attachment_service.files_dir().to_path_buf();
+        tauri::async_runtime::spawn_blocking(move || {
+            let mut conn = crate::db::get_connection(&app)?;
+            import_workspace(&mut conn, &cipher, &path, &files_dir)
+        })
+        .await
+        .map_err(|e| AppError::Generic(format!("Workspace import failed: {e}")))?
+    }
+}
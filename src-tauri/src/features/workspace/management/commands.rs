@@ -1,5 +1,6 @@
 use super::models::Workspace;
 use crate::error::AppError;
+use crate::features::usage::models::UsageDeleteFilter;
 use crate::state::AppState;
 use tauri::State;
 
@@ -27,6 +28,23 @@ pub fn create_workspace(
         Some(10),    // max_agent_iterations
         Some(false), // internal_tools_enabled
         None,        // selected_skill_ids
+        Some(false), // context_cache_enabled
+        None,        // redaction_config
+        None,        // secret_masking_enabled
+        None,        // paste_dedup_enabled
+        None,        // post_processing_config
+        None,        // confirm_above_estimated_cost
+        None,        // assumed_completion_tokens
+        None,        // max_tool_calls_per_turn
+        None,        // tool_call_caps
+        None,        // auto_continue_interrupted
+        None,        // max_output_bytes
+        None,        // outbox_enabled
+        None,        // agent_tool_permission
+        None,        // generation_params
+        None,        // max_parallel_tools
+        None,        // summarize_after_messages
+        None,        // summary_model
     )?;
 
     Ok(workspace)
@@ -34,11 +52,18 @@ pub fn create_workspace(
 
 #[tauri::command]
 pub fn get_workspaces(state: State<'_, AppState>) -> Result<Vec<Workspace>, AppError> {
-    state
+    let mut workspaces = state
         .workspace_feature
         .service
         .get_all()
-        .map_err(|e| AppError::Generic(e.to_string()))
+        .map_err(|e| AppError::Generic(e.to_string()))?;
+
+    // Lets the sidebar badge workspaces with background activity
+    let unread = state.activity_service.unread_counts()?;
+    for workspace in &mut workspaces {
+        workspace.unread_activity = unread.get(&workspace.id).copied().unwrap_or(0);
+    }
+    Ok(workspaces)
 }
 
 #[tauri::command]
@@ -59,6 +84,14 @@ pub fn delete_workspace(id: String, state: State<'_, AppState>) -> Result<(), Ap
     state
         .workspace_feature
         .service
-        .delete(id)
-        .map_err(|e| AppError::Generic(e.to_string()))
+        .delete(id.clone())
+        .map_err(|e| AppError::Generic(e.to_string()))?;
+    crate::features::usage::commands::purge_deleted_usage(
+        &state,
+        &UsageDeleteFilter {
+            workspace_id: Some(id),
+            ..UsageDeleteFilter::default()
+        },
+    );
+    Ok(())
 }
@@ -5,4 +5,7 @@ pub struct Workspace {
     pub id: String,
     pub name: String,
     pub created_at: i64,
+    /// Unread activity entries, filled in by `get_workspaces`
+    #[serde(default)]
+    pub unread_activity: i64,
 }
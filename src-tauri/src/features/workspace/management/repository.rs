@@ -44,6 +44,7 @@ impl WorkspaceRepository for SqliteWorkspaceRepository {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     created_at: row.get(2)?,
+                    unread_activity: 0,
                 })
             })?
             .collect::<Result<Vec<_>, _>>()?;
@@ -61,6 +62,7 @@ impl WorkspaceRepository for SqliteWorkspaceRepository {
                     id: row.get(0)?,
                     name: row.get(1)?,
                     created_at: row.get(2)?,
+                    unread_activity: 0,
                 })
             },
         );
@@ -84,6 +86,10 @@ impl WorkspaceRepository for SqliteWorkspaceRepository {
     fn delete(&self, id: &str) -> Result<(), AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         conn.execute("DELETE FROM workspaces WHERE id = ?1", params![id])?;
+        conn.execute(
+            "DELETE FROM workspace_notes WHERE workspace_id = ?1",
+            params![id],
+        )?;
         Ok(())
     }
 }
@@ -22,6 +22,7 @@ impl WorkspaceService {
             id,
             name,
             created_at,
+            unread_activity: 0,
         };
 
         self.repository.create(&workspace)?;
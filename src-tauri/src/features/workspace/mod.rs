@@ -1,20 +1,29 @@
+pub mod archive;
 pub mod management;
+pub mod notes;
+pub mod readiness;
 pub mod settings;
 use std::sync::Arc;
 
 pub struct WorkspaceFeature {
     pub service: Arc<management::WorkspaceService>,
     pub settings_service: Arc<settings::WorkspaceSettingsService>,
+    pub notes_service: Arc<notes::WorkspaceNotesService>,
+    pub archive_service: Arc<archive::WorkspaceArchiveService>,
 }
 
 impl WorkspaceFeature {
     pub const fn new(
         service: Arc<management::WorkspaceService>,
         settings_service: Arc<settings::WorkspaceSettingsService>,
+        notes_service: Arc<notes::WorkspaceNotesService>,
+        archive_service: Arc<archive::WorkspaceArchiveService>,
     ) -> Self {
         Self {
             service,
             settings_service,
+            notes_service,
+            archive_service,
         }
     }
 }
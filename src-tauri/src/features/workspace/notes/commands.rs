@@ -0,0 +1,39 @@
+use super::models::WorkspaceNotes;
+use crate::error::AppError;
+use crate::events::{emit_event, WorkspaceNotesUpdatedEvent};
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+#[tauri::command]
+pub fn get_workspace_notes(
+    workspace_id: String,
+    state: State<'_, AppState>,
+) -> Result<WorkspaceNotes, AppError> {
+    state.workspace_feature.notes_service.get(&workspace_id)
+}
+
+/// Save the workspace's notes, up to `MAX_NOTES_BYTES`. While
+/// `include_notes_in_context` is on, its chats get them in the system prompt.
+#[tauri::command]
+pub fn save_workspace_notes(
+    app: AppHandle,
+    workspace_id: String,
+    notes: String,
+    include_notes_in_context: Option<bool>,
+    state: State<'_, AppState>,
+) -> Result<WorkspaceNotes, AppError> {
+    let saved = state.workspace_feature.notes_service.save(
+        workspace_id,
+        notes,
+        include_notes_in_context,
+    )?;
+    emit_event(
+        &app,
+        WorkspaceNotesUpdatedEvent {
+            workspace_id: saved.workspace_id.clone(),
+            include_notes_in_context: saved.include_notes_in_context,
+            updated_at: saved.updated_at,
+        },
+    )?;
+    Ok(saved)
+}
@@ -0,0 +1,8 @@
+pub mod commands;
+pub mod models;
+pub mod repository;
+pub mod service;
+
+pub use models::*;
+pub use repository::*;
+pub use service::*;
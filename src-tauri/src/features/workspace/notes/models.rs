@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+/// The markdown brief of a workspace, shown in its notes panel.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WorkspaceNotes {
+    pub workspace_id: String,
+    pub notes: String,
+    /// Whether chats of the workspace get the notes in their system prompt
+    pub include_notes_in_context: bool,
+    pub updated_at: i64,
+}
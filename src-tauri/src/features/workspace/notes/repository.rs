@@ -0,0 +1,130 @@
+use super::models::WorkspaceNotes;
+use crate::error::AppError;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+pub trait WorkspaceNotesRepository: Send + Sync {
+    fn save(&self, notes: &WorkspaceNotes) -> Result<(), AppError>;
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Option<WorkspaceNotes>, AppError>;
+}
+
+pub struct SqliteWorkspaceNotesRepository {
+    app: Arc<AppHandle>,
+}
+
+impl SqliteWorkspaceNotesRepository {
+    pub const fn new(app: Arc<AppHandle>) -> Self {
+        Self { app }
+    }
+}
+
+impl WorkspaceNotesRepository for SqliteWorkspaceNotesRepository {
+    fn save(&self, notes: &WorkspaceNotes) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        save_notes(&conn, notes)
+    }
+
+    fn get_by_workspace_id(&self, workspace_id: &str) -> Result<Option<WorkspaceNotes>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        notes_of(&conn, workspace_id)
+    }
+}
+
+pub fn save_notes(conn: &Connection, notes: &WorkspaceNotes) -> Result<(), AppError> {
+    conn.execute(
+        "INSERT INTO workspace_notes (workspace_id, notes, include_in_context, updated_at)
+         VALUES (?1, ?2, ?3, ?4)
+         ON CONFLICT(workspace_id) DO UPDATE SET
+            notes = excluded.notes,
+            include_in_context = excluded.include_in_context,
+            updated_at = excluded.updated_at",
+        params![
+            notes.workspace_id,
+            notes.notes,
+            notes.include_notes_in_context,
+            notes.updated_at
+        ],
+    )?;
+    Ok(())
+}
+
+pub fn notes_of(conn: &Connection, workspace_id: &str) -> Result<Option<WorkspaceNotes>, AppError> {
+    Ok(conn
+        .query_row(
+            "SELECT workspace_id, notes, include_in_context, updated_at FROM workspace_notes WHERE workspace_id = ?1",
+            params![workspace_id],
+            |row| {
+                Ok(WorkspaceNotes {
+                    workspace_id: row.get(0)?,
+                    notes: row.get(1)?,
+                    include_notes_in_context: row.get(2)?,
+                    updated_at: row.get(3)?,
+                })
+            },
+        )
+        .optional()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::migrated_db;
+
+    #[test]
+    fn workspace_notes_are_saved_with_their_toggle_and_size_cap() {
+        use crate::features::workspace::notes::{
+            notes_of, save_notes, WorkspaceNotes, WorkspaceNotesRepository, WorkspaceNotesService,
+            MAX_NOTES_BYTES,
+        };
+        use std::sync::{Arc, Mutex};
+
+        struct MemoryWorkspaceNotesRepository(Mutex<rusqlite::Connection>);
+
+        impl WorkspaceNotesRepository for MemoryWorkspaceNotesRepository {
+            fn save(&self, notes: &WorkspaceNotes) -> Result<(), crate::error::AppError> {
+                save_notes(&self.0.lock().unwrap(), notes)
+            }
+
+            fn get_by_workspace_id(
+                &self,
+                workspace_id: &str,
+            ) -> Result<Option<WorkspaceNotes>, crate::error::AppError> {
+                notes_of(&self.0.lock().unwrap(), workspace_id)
+            }
+        }
+
+        let service = WorkspaceNotesService::new(Arc::new(MemoryWorkspaceNotesRepository(
+            Mutex::new(migrated_db()),
+        )));
+        let empty = service.get("w1").unwrap();
+        assert!(empty.notes.is_empty());
+        assert!(!empty.include_notes_in_context);
+        assert_eq!(service.context_notes("w1").unwrap(), None);
+
+        service
+            .save("w1".to_string(), "Brief".to_string(), Some(true))
+            .unwrap();
+        assert_eq!(
+            service.context_notes("w1").unwrap().as_deref(),
+            Some("Brief")
+        );
+
+        // Saving without the toggle keeps it
+        let saved = service
+            .save("w1".to_string(), "Brief v2".to_string(), None)
+            .unwrap();
+        assert!(saved.include_notes_in_context);
+        assert_eq!(service.get("w1").unwrap(), saved);
+
+        service
+            .save("w1".to_string(), "Brief v2".to_string(), Some(false))
+            .unwrap();
+        assert_eq!(service.context_notes("w1").unwrap(), None);
+
+        let too_long = "x".repeat(MAX_NOTES_BYTES + 1);
+        assert!(service
+            .save("w1".to_string(), too_long, Some(true))
+            .is_err());
+        assert_eq!(service.get("w1").unwrap().notes, "Brief v2");
+    }
+}
@@ -0,0 +1,90 @@
+use super::models::WorkspaceNotes;
+use super::repository::WorkspaceNotesRepository;
+use crate::error::AppError;
+use crate::features::chat::cost_gate::{estimate_text_tokens, CHARS_PER_TOKEN};
+use crate::lib::text;
+use std::sync::Arc;
+
+/// Largest notes a workspace may keep, in bytes.
+pub const MAX_NOTES_BYTES: usize = 64 * 1024;
+/// Most estimated tokens of notes a system prompt gets; longer notes are cut.
+pub const NOTES_CONTEXT_TOKEN_CAP: u32 = 2_000;
+/// Ends notes cut down for the system prompt.
+pub const NOTES_TRUNCATION_MARKER: &str = "\n[… notes truncated]";
+
+pub struct WorkspaceNotesService {
+    repository: Arc<dyn WorkspaceNotesRepository>,
+}
+
+impl WorkspaceNotesService {
+    pub fn new(repository: Arc<dyn WorkspaceNotesRepository>) -> Self {
+        Self { repository }
+    }
+
+    /// The workspace's notes; empty and left out of context when never saved.
+    pub fn get(&self, workspace_id: &str) -> Result<WorkspaceNotes, AppError> {
+        Ok(self
+            .repository
+            .get_by_workspace_id(workspace_id)?
+            .unwrap_or_else(|| WorkspaceNotes {
+                workspace_id: workspace_id.to_string(),
+                notes: String::new(),
+                include_notes_in_context: false,
+                updated_at: 0,
+            }))
+    }
+
+    /// Save the notes; without `include_notes_in_context`, the toggle keeps
+    /// its value.
+    pub fn save(
+        &self,
+        workspace_id: String,
+        notes: String,
+        include_notes_in_context: Option<bool>,
+    ) -> Result<WorkspaceNotes, AppError> {
+        if notes.len() > MAX_NOTES_BYTES {
+            return Err(AppError::Validation(format!(
+                "Workspace notes are limited to {} KB",
+                MAX_NOTES_BYTES / 1024
+            )));
+        }
+        let include_notes_in_context = match include_notes_in_context {
+            Some(include) => include,
+            None => self.get(&workspace_id)?.include_notes_in_context,
+        };
+        let notes = WorkspaceNotes {
+            workspace_id,
+            notes,
+            include_notes_in_context,
+            updated_at: chrono::Utc::now().timestamp_millis(),
+        };
+        self.repository.save(&notes)?;
+        Ok(notes)
+    }
+
+    /// The notes a system prompt of the workspace gets, if it is to get any.
+    pub fn context_notes(&self, workspace_id: &str) -> Result<Option<String>, AppError> {
+        Ok(self
+            .repository
+            .get_by_workspace_id(workspace_id)?
+            .filter(|notes| notes.include_notes_in_context)
+            .and_then(|notes| context_notes(&notes.notes, NOTES_CONTEXT_TOKEN_CAP)))
+    }
+}
+
+/// `notes` cut to at most `max_tokens` estimated tokens, marker included;
+/// `None` when they are blank.
+pub fn context_notes(notes: &str, max_tokens: u32) -> Option<String> {
+    let notes = notes.trim();
+    if notes.is_empty() {
+        return None;
+    }
+    let mut max_graphemes = max_tokens as usize * CHARS_PER_TOKEN;
+    let mut cut = text::truncate(notes, max_graphemes, NOTES_TRUNCATION_MARKER);
+    // A grapheme may hold several characters, which the estimate counts
+    while estimate_text_tokens(&cut) > max_tokens && max_graphemes > 0 {
+        max_graphemes = max_graphemes * 9 / 10;
+        cut = text::truncate(notes, max_graphemes, NOTES_TRUNCATION_MARKER);
+    }
+    Some(cut)
+}
@@ -0,0 +1,619 @@
+//! The readiness checks themselves, on data the probe has already read.
+//!
+//! Nothing here touches the database, the network or the file system beyond
+//! reading metadata, so each check can be tried against fixture data.
+
+use super::models::{ReadinessCheck, ReadinessCheckKind as Kind};
+use crate::features::encryption::cipher::FieldCipher;
+use crate::features::llm_connection::model_filter::{ModelFilter, ModelMatch};
+use crate::features::llm_connection::models::LLMConnection;
+use crate::features::mcp_connection::models::MCPServerConnection;
+use crate::features::redaction::RedactionService;
+use crate::features::tool::service::{FILE_SYSTEM_TOOLS, SCRATCHPAD_TOOLS, WEB_TOOLS};
+use crate::features::workspace::settings::WorkspaceSettings;
+use crate::models::llm_types::LLMModel;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::Path;
+
+pub fn settings(settings: Option<&WorkspaceSettings>) -> ReadinessCheck {
+    match settings {
+        Some(_) => ReadinessCheck::pass(Kind::Settings, "Workspace settings exist"),
+        None => ReadinessCheck::fail(
+            Kind::Settings,
+            "The workspace has no settings yet",
+            "Open the workspace settings once; they are created with defaults",
+        ),
+    }
+}
+
+/// What a check that reads the settings reports when there are none.
+pub fn needs_settings(kind: Kind) -> ReadinessCheck {
+    ReadinessCheck::fail(
+        kind,
+        "Cannot be checked without workspace settings",
+        "Open the workspace settings once; they are created with defaults",
+    )
+}
+
+pub fn llm_connection(
+    settings: &WorkspaceSettings,
+    connection: Option<&LLMConnection>,
+) -> ReadinessCheck {
+    let Some(connection_id) = settings.llm_connection_id.as_deref() else {
+        return ReadinessCheck::fail(
+            Kind::LlmConnection,
+            "No LLM connection is chosen for the workspace",
+            "Choose an LLM connection in the workspace settings",
+        );
+    };
+    match connection {
+        None => ReadinessCheck::fail(
+            Kind::LlmConnection,
+            format!("LLM connection {connection_id} no longer exists"),
+            "Choose another LLM connection in the workspace settings",
+        ),
+        Some(connection) if !connection.enabled => ReadinessCheck::fail(
+            Kind::LlmConnection,
+            format!("LLM connection '{}' is disabled", connection.name),
+            "Enable the connection under LLM connections, or choose another one",
+        ),
+        Some(connection) => ReadinessCheck::pass(
+            Kind::LlmConnection,
+            format!("Using LLM connection '{}'", connection.name),
+        ),
+    }
+}
+
+pub fn api_key(connection: Option<&LLMConnection>) -> ReadinessCheck {
+    let Some(connection) = connection else {
+        return ReadinessCheck::fail(
+            Kind::ApiKey,
+            "There is no LLM connection to check",
+            "Choose an LLM connection in the workspace settings",
+        );
+    };
+    if FieldCipher::is_encrypted(&connection.api_key) {
+        return ReadinessCheck::fail(
+            Kind::ApiKey,
+            format!(
+                "The API key of '{}' is encrypted and could not be read",
+                connection.name
+            ),
+            "Unlock encryption so stored keys can be read",
+        );
+    }
+    if FieldCipher::is_secret(&connection.api_key) {
+        return ReadinessCheck::fail(
+            Kind::ApiKey,
+            format!(
+                "The API key of '{}' could not be decrypted",
+                connection.name
+            ),
+            "Enter the API key again: the key it was encrypted with is missing from the OS keychain",
+        );
+    }
+    if !connection.api_key.trim().is_empty() {
+        return ReadinessCheck::pass(Kind::ApiKey, "API key is set");
+    }
+    if RedactionService::is_self_hosted(connection) {
+        return ReadinessCheck::pass(Kind::ApiKey, "No API key needed for a local server");
+    }
+    ReadinessCheck::fail(
+        Kind::ApiKey,
+        format!("LLM connection '{}' has no API key", connection.name),
+        "Add the provider's API key to the connection under LLM connections",
+    )
+}
+
+pub fn default_model(
+    settings: &WorkspaceSettings,
+    connection: Option<&LLMConnection>,
+) -> ReadinessCheck {
+    let Some(connection) = connection else {
+        return ReadinessCheck::fail(
+            Kind::DefaultModel,
+            "There is no LLM connection to check",
+            "Choose an LLM connection in the workspace settings",
+        );
+    };
+    let Some(model) = settings
+        .default_model
+        .as_deref()
+        .or(connection.default_model.as_deref())
+        .filter(|model| !model.is_empty())
+    else {
+        return ReadinessCheck::fail(
+            Kind::DefaultModel,
+            "No default model is set",
+            "Choose a default model in the workspace settings or on the connection",
+        );
+    };
+
+    let matched = ModelFilter::for_connection(connection)
+        .map_or(ModelMatch::Unrestricted, |filter| filter.check(model));
+    if !matched.is_permitted() {
+        return ReadinessCheck::fail(
+            Kind::DefaultModel,
+            format!(
+                "Model '{model}' is not permitted by the model lists of '{}'",
+                connection.name
+            ),
+            "Choose a model the connection's allow and block lists permit",
+        );
+    }
+
+    let listed: Option<Vec<LLMModel>> = connection
+        .models_json
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok());
+    match listed {
+        Some(models) if models.iter().any(|m| m.id == model) => {
+            ReadinessCheck::pass(Kind::DefaultModel, format!("Default model is '{model}'"))
+        }
+        _ if matches!(matched, ModelMatch::Allowlisted(_)) => ReadinessCheck::pass(
+            Kind::DefaultModel,
+            format!("Default model '{model}' is in the connection's allowlist"),
+        ),
+        Some(_) => ReadinessCheck::warn(
+            Kind::DefaultModel,
+            format!(
+                "Model '{model}' is not in the model list of '{}'",
+                connection.name
+            ),
+            "Refresh the connection's models, or choose one from the list",
+        ),
+        None => ReadinessCheck::warn(
+            Kind::DefaultModel,
+            format!("The model list of '{}' was never loaded", connection.name),
+            "Refresh the connection's models to confirm the model exists",
+        ),
+    }
+}
+
+/// MCP servers the workspace takes tools from.
+pub fn workspace_connections<'a>(
+    settings: &WorkspaceSettings,
+    connections: &'a [MCPServerConnection],
+) -> Vec<&'a MCPServerConnection> {
+    let selected: HashMap<String, String> = settings
+        .mcp_tool_ids
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    let ids: HashSet<&str> = selected.values().map(String::as_str).collect();
+    connections
+        .iter()
+        .filter(|connection| ids.contains(connection.id.as_str()))
+        .collect()
+}
+
+pub fn mcp_connections(
+    settings: &WorkspaceSettings,
+    connections: &[MCPServerConnection],
+) -> ReadinessCheck {
+    let permissions: HashMap<String, String> = settings
+        .tool_permission_config
+        .as_deref()
+        .and_then(|json| serde_json::from_str(json).ok())
+        .unwrap_or_default();
+    // Builtin tools need no server
+    let mcp_tools: BTreeSet<&str> = permissions
+        .keys()
+        .map(String::as_str)
+        .filter(|name| {
+            !FILE_SYSTEM_TOOLS.contains(name)
+                && !SCRATCHPAD_TOOLS.contains(name)
+                && !WEB_TOOLS.contains(name)
+        })
+        .collect();
+    if mcp_tools.is_empty() {
+        return ReadinessCheck::pass(Kind::McpConnections, "Tool permissions name no MCP tools");
+    }
+
+    let servers = workspace_connections(settings, connections);
+    let names = mcp_tools.into_iter().collect::<Vec<_>>().join(", ");
+    if servers.is_empty() {
+        return ReadinessCheck::fail(
+            Kind::McpConnections,
+            format!("Tool permissions name {names}, but the workspace uses no MCP server"),
+            "Add an MCP server and select its tools in the workspace settings",
+        );
+    }
+    if !servers.iter().any(|server| server.status == "connected") {
+        return ReadinessCheck::warn(
+            Kind::McpConnections,
+            format!("Tool permissions name {names}, but none of the workspace's MCP servers is connected"),
+            "Connect the servers under MCP connections and check their errors",
+        );
+    }
+    ReadinessCheck::pass(Kind::McpConnections, "An MCP server is connected")
+}
+
+/// `referenced` are the agents chats of the workspace use.
+pub fn agents(referenced: &BTreeSet<String>, installed: &HashSet<String>) -> ReadinessCheck {
+    let missing: Vec<&str> = referenced
+        .iter()
+        .filter(|id| !installed.contains(*id))
+        .map(String::as_str)
+        .collect();
+    if missing.is_empty() {
+        return ReadinessCheck::pass(Kind::Agents, "Every agent in use is installed");
+    }
+    ReadinessCheck::fail(
+        Kind::Agents,
+        format!(
+            "Agents used by chats are not installed: {}",
+            missing.join(", ")
+        ),
+        "Install the agents again, or delete the chats that use them",
+    )
+}
+
+/// Looks at permissions only; nothing is written to find out.
+pub fn attachments_directory(dir: &Path) -> ReadinessCheck {
+    let writable =
+        |path: &Path| std::fs::metadata(path).map(|m| m.is_dir() && !m.permissions().readonly());
+    match writable(dir) {
+        Ok(true) => ReadinessCheck::pass(
+            Kind::AttachmentsDirectory,
+            "Attachments directory is writable",
+        ),
+        Ok(false) => ReadinessCheck::fail(
+            Kind::AttachmentsDirectory,
+            format!("Attachments directory {} is not writable", dir.display()),
+            "Give the app write access to its data directory",
+        ),
+        // Created with the first attachment, if its parent allows it
+        Err(_)
+            if dir
+                .parent()
+                .is_some_and(|parent| writable(parent).unwrap_or(false)) =>
+        {
+            ReadinessCheck::pass(
+                Kind::AttachmentsDirectory,
+                "Attachments directory is created with the first attachment",
+            )
+        }
+        Err(e) => ReadinessCheck::fail(
+            Kind::AttachmentsDirectory,
+            format!(
+                "Attachments directory {} cannot be used: {e}",
+                dir.display()
+            ),
+            "Give the app write access to its data directory",
+        ),
+    }
+}
+
+/// `available` tells whether a command (a name, looked up like the app
+/// does, or a path) can be run.
+pub fn stdio_runtimes(
+    settings: &WorkspaceSettings,
+    connections: &[MCPServerConnection],
+    available: impl Fn(&str) -> bool,
+) -> ReadinessCheck {
+    let mut problems = Vec::new();
+    let mut checked = 0;
+    for server in workspace_connections(settings, connections) {
+        if server.r#type != "stdio" {
+            continue;
+        }
+        checked += 1;
+        let command = match shell_words::split(&server.url) {
+            Ok(parts) if !parts.is_empty() => parts[0].clone(),
+            _ => {
+                problems.push(format!("'{}' has no valid command", server.name));
+                continue;
+            }
+        };
+        let runtime = server
+            .runtime_path
+            .as_deref()
+            .filter(|path| !path.is_empty() && *path != "default");
+        if let Some(runtime) = runtime {
+            if !available(runtime) {
+                problems.push(format!("'{}' needs runtime {runtime}", server.name));
+                continue;
+            }
+        }
+        if !available(&command) {
+            problems.push(format!("'{}' needs {command}", server.name));
+        }
+    }
+
+    if !problems.is_empty() {
+        return ReadinessCheck::fail(
+            Kind::StdioRuntimes,
+            format!("Missing runtimes: {}", problems.join("; ")),
+            "Install the runtime under Add-ons, or put the command on PATH",
+        );
+    }
+    if checked == 0 {
+        return ReadinessCheck::pass(Kind::StdioRuntimes, "No stdio MCP servers in use");
+    }
+    ReadinessCheck::pass(
+        Kind::StdioRuntimes,
+        "Every stdio MCP server's command was found",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::{boot_workspace_settings, connection};
+
+    fn mcp_server(
+        id: &str,
+        r#type: &str,
+        url: &str,
+        status: &str,
+    ) -> crate::features::mcp_connection::models::MCPServerConnection {
+        crate::features::mcp_connection::models::MCPServerConnection {
+            id: id.to_string(),
+            name: id.to_string(),
+            url: url.to_string(),
+            r#type: r#type.to_string(),
+            headers: "{}".to_string(),
+            auth_token: None,
+            env_vars: None,
+            runtime_path: None,
+            auto_connect: false,
+            status: status.to_string(),
+            tools_json: None,
+            error_message: None,
+            created_at: 0,
+            updated_at: 0,
+        }
+    }
+
+    /// A fixture workspace, checked the way the readiness probe checks it.
+    struct ReadinessFixture {
+        settings: Option<crate::features::workspace::settings::WorkspaceSettings>,
+        connection: Option<crate::features::llm_connection::models::LLMConnection>,
+        servers: Vec<crate::features::mcp_connection::models::MCPServerConnection>,
+        agents_in_use: Vec<&'static str>,
+        installed_agents: Vec<&'static str>,
+        attachments_dir: std::path::PathBuf,
+        commands: Vec<&'static str>,
+    }
+
+    impl ReadinessFixture {
+        fn check(&self) -> crate::features::workspace::readiness::WorkspaceReadiness {
+            use crate::features::workspace::readiness::checks;
+            use crate::features::workspace::readiness::{
+                ReadinessCheckKind as Kind, WorkspaceReadiness,
+            };
+
+            let mut all = vec![checks::settings(self.settings.as_ref())];
+            match &self.settings {
+                Some(settings) => all.extend([
+                    checks::llm_connection(settings, self.connection.as_ref()),
+                    checks::api_key(self.connection.as_ref()),
+                    checks::default_model(settings, self.connection.as_ref()),
+                    checks::mcp_connections(settings, &self.servers),
+                ]),
+                None => all.extend(
+                    [
+                        Kind::LlmConnection,
+                        Kind::ApiKey,
+                        Kind::DefaultModel,
+                        Kind::McpConnections,
+                    ]
+                    .map(checks::needs_settings),
+                ),
+            }
+            all.push(checks::agents(
+                &self
+                    .agents_in_use
+                    .iter()
+                    .map(|id| (*id).to_string())
+                    .collect(),
+                &self
+                    .installed_agents
+                    .iter()
+                    .map(|id| (*id).to_string())
+                    .collect(),
+            ));
+            all.push(checks::attachments_directory(&self.attachments_dir));
+            all.push(match &self.settings {
+                Some(settings) => checks::stdio_runtimes(settings, &self.servers, |command| {
+                    self.commands.contains(&command)
+                }),
+                None => checks::needs_settings(Kind::StdioRuntimes),
+            });
+            WorkspaceReadiness::new("ws".to_string(), all)
+        }
+    }
+
+    fn readiness_statuses(
+        readiness: &crate::features::workspace::readiness::WorkspaceReadiness,
+    ) -> Vec<(
+        crate::features::workspace::readiness::ReadinessCheckKind,
+        crate::features::workspace::readiness::CheckStatus,
+    )> {
+        readiness
+            .checks
+            .iter()
+            .map(|check| (check.kind, check.status))
+            .collect()
+    }
+
+    fn ready_fixture(dir: &std::path::Path) -> ReadinessFixture {
+        let mut settings = boot_workspace_settings(Some("cloud"), Some("gpt-4o"));
+        settings.mcp_tool_ids = Some(r#"{"search": "web", "read_page": "web"}"#.to_string());
+        settings.tool_permission_config =
+            Some(r#"{"search": "require", "read_file": "auto"}"#.to_string());
+        let mut connection = connection(
+            "cloud",
+            "openai",
+            "gpt-4o",
+            serde_json::json!([{"id": "gpt-4o", "name": "GPT-4o"}]),
+        );
+        connection.api_key = "sk-test".to_string();
+        connection.base_url = "https://api.openai.com/v1".to_string();
+        ReadinessFixture {
+            settings: Some(settings),
+            connection: Some(connection),
+            servers: vec![
+                mcp_server("web", "stdio", "npx -y web-tools", "connected"),
+                mcp_server("unused", "stdio", "missing-server", "disconnected"),
+            ],
+            agents_in_use: vec!["researcher"],
+            installed_agents: vec!["researcher", "writer"],
+            attachments_dir: dir.join("files"),
+            commands: vec!["npx"],
+        }
+    }
+
+    #[test]
+    fn readiness_ready_workspace_passes_every_check() {
+        use crate::features::workspace::readiness::CheckStatus;
+
+        let dir = tempfile::tempdir().unwrap();
+        let readiness = ready_fixture(dir.path()).check();
+        assert!(readiness.ready);
+        assert_eq!(readiness.status, CheckStatus::Pass);
+        assert_eq!(readiness.checks.len(), 9);
+        assert!(readiness
+            .checks
+            .iter()
+            .all(|check| check.status == CheckStatus::Pass && check.remediation.is_none()));
+    }
+
+    #[test]
+    fn readiness_fresh_workspace_without_settings() {
+        use crate::features::workspace::readiness::{CheckStatus, ReadinessCheckKind as Kind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut fixture = ready_fixture(dir.path());
+        fixture.settings = None;
+        let readiness = fixture.check();
+        assert!(!readiness.ready);
+        assert_eq!(readiness.status, CheckStatus::Fail);
+        assert_eq!(
+            readiness_statuses(&readiness),
+            [
+                (Kind::Settings, CheckStatus::Fail),
+                (Kind::LlmConnection, CheckStatus::Fail),
+                (Kind::ApiKey, CheckStatus::Fail),
+                (Kind::DefaultModel, CheckStatus::Fail),
+                (Kind::McpConnections, CheckStatus::Fail),
+                (Kind::Agents, CheckStatus::Pass),
+                (Kind::AttachmentsDirectory, CheckStatus::Pass),
+                (Kind::StdioRuntimes, CheckStatus::Fail),
+            ]
+        );
+        assert!(readiness
+            .checks
+            .iter()
+            .filter(|check| check.status == CheckStatus::Fail)
+            .all(|check| check.remediation.is_some()));
+    }
+
+    #[test]
+    fn readiness_connection_without_key_or_listed_model() {
+        use crate::features::workspace::readiness::{CheckStatus, ReadinessCheckKind as Kind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut fixture = ready_fixture(dir.path());
+        let connection = fixture.connection.as_mut().unwrap();
+        connection.api_key = String::new();
+        connection.models_json = Some(r#"[{"id": "gpt-4o-mini", "name": "mini"}]"#.to_string());
+        let readiness = fixture.check();
+        assert!(!readiness.ready);
+        let statuses = readiness_statuses(&readiness);
+        assert!(statuses.contains(&(Kind::LlmConnection, CheckStatus::Pass)));
+        assert!(statuses.contains(&(Kind::ApiKey, CheckStatus::Fail)));
+        assert!(statuses.contains(&(Kind::DefaultModel, CheckStatus::Warn)));
+
+        // A key still sealed means the encryption store is locked
+        let connection = fixture.connection.as_mut().unwrap();
+        connection.api_key = "enc:v1:sealed".to_string();
+        let api_key = crate::features::workspace::readiness::checks::api_key(Some(connection));
+        assert_eq!(api_key.status, CheckStatus::Fail);
+        assert!(api_key.remediation.unwrap().contains("encryption"));
+
+        // The model is blocklisted; the connection is disabled
+        let connection = fixture.connection.as_mut().unwrap();
+        connection.api_key = "sk-test".to_string();
+        connection.model_blocklist = Some(r#"["gpt-4o"]"#.to_string());
+        connection.enabled = false;
+        let statuses = readiness_statuses(&fixture.check());
+        assert!(statuses.contains(&(Kind::LlmConnection, CheckStatus::Fail)));
+        assert!(statuses.contains(&(Kind::ApiKey, CheckStatus::Pass)));
+        assert!(statuses.contains(&(Kind::DefaultModel, CheckStatus::Fail)));
+    }
+
+    #[test]
+    fn readiness_local_model_with_disconnected_tools_and_missing_agent() {
+        use crate::features::workspace::readiness::{CheckStatus, ReadinessCheckKind as Kind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let mut fixture = ready_fixture(dir.path());
+        // A local server needs no key; an allowlisted model need not be listed
+        let mut local = connection("cloud", "ollama", "qwen3", serde_json::json!([]));
+        local.model_allowlist = Some(r#"["qwen*"]"#.to_string());
+        fixture.connection = Some(local);
+        fixture.settings.as_mut().unwrap().default_model = None;
+        fixture.servers[0].status = "error".to_string();
+        fixture.agents_in_use = vec!["researcher", "translator"];
+        let readiness = fixture.check();
+        assert!(!readiness.ready);
+        let statuses = readiness_statuses(&readiness);
+        assert!(statuses.contains(&(Kind::ApiKey, CheckStatus::Pass)));
+        assert!(statuses.contains(&(Kind::DefaultModel, CheckStatus::Pass)));
+        assert!(statuses.contains(&(Kind::McpConnections, CheckStatus::Warn)));
+        assert!(statuses.contains(&(Kind::Agents, CheckStatus::Fail)));
+        let agents = readiness
+            .checks
+            .iter()
+            .find(|check| check.kind == Kind::Agents)
+            .unwrap();
+        assert!(agents.message.contains("translator"));
+        assert!(!agents.message.contains("researcher"));
+
+        // The tools' server was removed from the workspace
+        fixture.settings.as_mut().unwrap().mcp_tool_ids = None;
+        fixture.agents_in_use = vec!["researcher"];
+        let statuses = readiness_statuses(&fixture.check());
+        assert!(statuses.contains(&(Kind::McpConnections, CheckStatus::Fail)));
+        // Only builtin tools in the permission config need no server
+        fixture.settings.as_mut().unwrap().tool_permission_config =
+            Some(r#"{"read_file": "require"}"#.to_string());
+        let readiness = fixture.check();
+        assert!(readiness.ready);
+        assert_eq!(readiness.status, CheckStatus::Pass);
+    }
+
+    #[test]
+    fn readiness_missing_runtime_and_read_only_attachments() {
+        use crate::features::workspace::readiness::{CheckStatus, ReadinessCheckKind as Kind};
+
+        let dir = tempfile::tempdir().unwrap();
+        let files = dir.path().join("files");
+        std::fs::create_dir(&files).unwrap();
+        let mut fixture = ready_fixture(dir.path());
+        fixture.commands = vec!["uvx"];
+        fixture.servers[0].runtime_path = Some("/opt/node-22/bin/node".to_string());
+        let mut read_only = std::fs::metadata(&files).unwrap().permissions();
+        read_only.set_readonly(true);
+        std::fs::set_permissions(&files, read_only.clone()).unwrap();
+
+        let readiness = fixture.check();
+        assert!(!readiness.ready);
+        let statuses = readiness_statuses(&readiness);
+        assert!(statuses.contains(&(Kind::AttachmentsDirectory, CheckStatus::Fail)));
+        assert!(statuses.contains(&(Kind::StdioRuntimes, CheckStatus::Fail)));
+        let runtimes = readiness
+            .checks
+            .iter()
+            .find(|check| check.kind == Kind::StdioRuntimes)
+            .unwrap();
+        assert!(runtimes.message.contains("/opt/node-22/bin/node"));
+        // Servers the workspace does not use are not checked
+        assert!(!runtimes.message.contains("missing-server"));
+
+        #[allow(clippy::permissions_set_readonly_false)]
+        read_only.set_readonly(false);
+        std::fs::set_permissions(&files, read_only).unwrap();
+    }
+}
@@ -0,0 +1,16 @@
+use super::models::WorkspaceReadiness;
+use super::probe::ReadinessProbe;
+use crate::error::AppError;
+use crate::state::AppState;
+use tauri::{AppHandle, State};
+
+/// Check whether a workspace is set up to chat, with a hint for everything
+/// that is not. Reads only; `ready` is false when any check failed.
+#[tauri::command]
+pub async fn check_workspace_readiness(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    workspace_id: String,
+) -> Result<WorkspaceReadiness, AppError> {
+    Ok(ReadinessProbe::new(app, &state).run(workspace_id).await)
+}
@@ -0,0 +1,6 @@
+pub mod checks;
+pub mod commands;
+pub mod models;
+pub mod probe;
+
+pub use models::*;
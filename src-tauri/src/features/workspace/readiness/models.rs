@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+
+/// What a readiness check looks at.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReadinessCheckKind {
+    /// The workspace has a settings row
+    Settings,
+    /// An LLM connection is chosen, exists and is enabled
+    LlmConnection,
+    /// The connection has a usable API key, or needs none
+    ApiKey,
+    /// A default model is set and the connection offers and permits it
+    DefaultModel,
+    /// Tools named in the permission config have a connected MCP server
+    McpConnections,
+    /// Agents the workspace's chats use are installed
+    Agents,
+    AttachmentsDirectory,
+    /// Commands of the workspace's stdio servers can be found
+    StdioRuntimes,
+}
+
+/// Outcome of a check; `Fail` blocks sending messages, `Warn` does not.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CheckStatus {
+    Pass,
+    Warn,
+    Fail,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReadinessCheck {
+    pub kind: ReadinessCheckKind,
+    pub status: CheckStatus,
+    pub message: String,
+    /// What the user can do about it, for anything but `Pass`
+    pub remediation: Option<String>,
+}
+
+impl ReadinessCheck {
+    pub fn pass(kind: ReadinessCheckKind, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Pass,
+            message: message.into(),
+            remediation: None,
+        }
+    }
+
+    pub fn warn(
+        kind: ReadinessCheckKind,
+        message: impl Into<String>,
+        remediation: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Warn,
+            message: message.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+
+    pub fn fail(
+        kind: ReadinessCheckKind,
+        message: impl Into<String>,
+        remediation: impl Into<String>,
+    ) -> Self {
+        Self {
+            kind,
+            status: CheckStatus::Fail,
+            message: message.into(),
+            remediation: Some(remediation.into()),
+        }
+    }
+}
+
+/// Result of `check_workspace_readiness`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct WorkspaceReadiness {
+    pub workspace_id: String,
+    /// The worst status of any check
+    pub status: CheckStatus,
+    /// No check failed; the composer can be used
+    pub ready: bool,
+    pub checks: Vec<ReadinessCheck>,
+}
+
+impl WorkspaceReadiness {
+    pub fn new(workspace_id: String, checks: Vec<ReadinessCheck>) -> Self {
+        let status = checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Pass);
+        Self {
+            workspace_id,
+            status,
+            ready: status != CheckStatus::Fail,
+            checks,
+        }
+    }
+}
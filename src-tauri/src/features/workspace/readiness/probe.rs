@@ -0,0 +1,253 @@
+//! Runs the readiness checks of a workspace against the live app.
+//!
+//! Every check reads what it needs on a blocking thread under its own
+//! timeout, so a slow disk or a stuck lookup turns that one check into a
+//! warning instead of holding up the others. Nothing is written: settings
+//! are not created when missing and no probe files are left behind.
+
+use super::checks;
+use super::models::{ReadinessCheck, ReadinessCheckKind as Kind, WorkspaceReadiness};
+use crate::error::AppError;
+use crate::features::addon::models::AddonIndex;
+use crate::features::agent::manager::AgentManager;
+use crate::features::chat::ChatService;
+use crate::features::llm_connection::LLMConnectionService;
+use crate::features::mcp_connection::MCPConnectionService;
+use crate::features::runtime::node::service::NodeRuntime;
+use crate::features::runtime::python::service::PythonRuntime;
+use crate::features::workspace::settings::{WorkspaceSettings, WorkspaceSettingsService};
+use crate::state::AppState;
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+
+/// How long one check may take before it is reported as a warning.
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct ReadinessProbe {
+    app: AppHandle,
+    settings_service: Arc<WorkspaceSettingsService>,
+    llm_connection_service: Arc<LLMConnectionService>,
+    mcp_connection_service: Arc<MCPConnectionService>,
+    chat_service: Arc<ChatService>,
+    agent_manager: Arc<AgentManager>,
+    attachments_dir: PathBuf,
+}
+
+impl ReadinessProbe {
+    pub fn new(app: AppHandle, state: &AppState) -> Self {
+        Self {
+            app,
+            settings_service: state.workspace_feature.settings_service.clone(),
+            llm_connection_service: state.llm_connection_service.clone(),
+            mcp_connection_service: state.mcp_connection_service.clone(),
+            chat_service: state.chat_service.clone(),
+            agent_manager: state.agent_manager.clone(),
+            attachments_dir: state.attachment_service.files_dir().to_path_buf(),
+        }
+    }
+
+    pub async fn run(self, workspace_id: String) -> WorkspaceReadiness {
+        let probe = Arc::new(self);
+
+        let settings = {
+            let probe = probe.clone();
+            let workspace_id = workspace_id.clone();
+            within(Kind::Settings, move || {
+                probe.settings_service.get_stored(&workspace_id)
+            })
+            .await
+        };
+        let (settings_check, settings) = match settings {
+            Ok(settings) => (checks::settings(settings.as_ref()), settings),
+            Err(check) => (check, None),
+        };
+        let settings = settings.map(Arc::new);
+
+        let (connection, mcp, runtimes, agents, attachments) = tokio::join!(
+            probe.clone().connection_checks(settings.clone()),
+            probe.clone().mcp_check(settings.clone()),
+            probe.clone().stdio_runtime_check(settings.clone()),
+            probe.clone().agent_check(workspace_id.clone()),
+            probe.clone().attachments_check(),
+        );
+
+        let mut all = vec![settings_check];
+        all.extend(connection);
+        all.extend([mcp, agents, attachments, runtimes]);
+        WorkspaceReadiness::new(workspace_id, all)
+    }
+
+    /// Connection, API key and default model, which share one lookup.
+    async fn connection_checks(
+        self: Arc<Self>,
+        settings: Option<Arc<WorkspaceSettings>>,
+    ) -> Vec<ReadinessCheck> {
+        let Some(settings) = settings else {
+            return [Kind::LlmConnection, Kind::ApiKey, Kind::DefaultModel]
+                .into_iter()
+                .map(checks::needs_settings)
+                .collect();
+        };
+        let lookup = {
+            let settings = settings.clone();
+            within(Kind::LlmConnection, move || {
+                settings
+                    .llm_connection_id
+                    .as_deref()
+                    .map_or(Ok(None), |id| self.llm_connection_service.get_by_id(id))
+            })
+            .await
+        };
+        match lookup {
+            Ok(connection) => vec![
+                checks::llm_connection(&settings, connection.as_ref()),
+                checks::api_key(connection.as_ref()),
+                checks::default_model(&settings, connection.as_ref()),
+            ],
+            Err(mut check) => {
+                // Stored keys are decrypted on read; a locked store fails here
+                check.remediation = Some(
+                    "If encryption is on, unlock it so the connection can be read".to_string(),
+                );
+                vec![
+                    check,
+                    checks::api_key(None),
+                    checks::default_model(&settings, None),
+                ]
+            }
+        }
+    }
+
+    async fn mcp_check(
+        self: Arc<Self>,
+        settings: Option<Arc<WorkspaceSettings>>,
+    ) -> ReadinessCheck {
+        let Some(settings) = settings else {
+            return checks::needs_settings(Kind::McpConnections);
+        };
+        within(Kind::McpConnections, move || {
+            let connections = self.mcp_connection_service.get_all()?;
+            Ok(checks::mcp_connections(&settings, &connections))
+        })
+        .await
+        .unwrap_or_else(|check| check)
+    }
+
+    async fn stdio_runtime_check(
+        self: Arc<Self>,
+        settings: Option<Arc<WorkspaceSettings>>,
+    ) -> ReadinessCheck {
+        let Some(settings) = settings else {
+            return checks::needs_settings(Kind::StdioRuntimes);
+        };
+        within(Kind::StdioRuntimes, move || {
+            let connections = self.mcp_connection_service.get_all()?;
+            Ok(checks::stdio_runtimes(&settings, &connections, |command| {
+                command_available(&self.app, command)
+            }))
+        })
+        .await
+        .unwrap_or_else(|check| check)
+    }
+
+    async fn agent_check(self: Arc<Self>, workspace_id: String) -> ReadinessCheck {
+        within(Kind::Agents, move || {
+            let referenced: BTreeSet<String> = self
+                .chat_service
+                .get_by_workspace_id(&workspace_id)?
+                .into_iter()
+                .filter_map(|chat| chat.agent_id)
+                .collect();
+            if referenced.is_empty() {
+                return Ok(checks::agents(&referenced, &HashSet::new()));
+            }
+            let installed: HashSet<String> = self
+                .agent_manager
+                .list_installed()
+                .map_err(|e| AppError::Generic(e.to_string()))?
+                .into_iter()
+                .map(|agent| agent.manifest.id)
+                .collect();
+            Ok(checks::agents(&referenced, &installed))
+        })
+        .await
+        .unwrap_or_else(|check| check)
+    }
+
+    async fn attachments_check(self: Arc<Self>) -> ReadinessCheck {
+        within(Kind::AttachmentsDirectory, move || {
+            Ok(checks::attachments_directory(&self.attachments_dir))
+        })
+        .await
+        .unwrap_or_else(|check| check)
+    }
+}
+
+/// Run `read` on a blocking thread for at most `CHECK_TIMEOUT`. A timeout is
+/// a warning, since the check may pass another time; an error fails `kind`.
+async fn within<T: Send + 'static>(
+    kind: Kind,
+    read: impl FnOnce() -> Result<T, AppError> + Send + 'static,
+) -> Result<T, ReadinessCheck> {
+    let Ok(joined) =
+        tokio::time::timeout(CHECK_TIMEOUT, tauri::async_runtime::spawn_blocking(read)).await
+    else {
+        return Err(ReadinessCheck::warn(
+            kind,
+            format!("Did not finish within {} seconds", CHECK_TIMEOUT.as_secs()),
+            "Try again in a moment",
+        ));
+    };
+    joined
+        .map_err(|e| e.to_string())
+        .and_then(|result| result.map_err(|e| e.to_string()))
+        .map_err(|e| {
+            ReadinessCheck::fail(
+                kind,
+                format!("Could not be checked: {e}"),
+                "Try again; if it keeps failing, the app logs have details",
+            )
+        })
+}
+
+/// Whether `command` can be started the way stdio servers start it: a path
+/// that exists, a bundled runtime for `python`, `uv` and Node commands, or a
+/// program on `PATH`.
+fn command_available(app: &AppHandle, command: &str) -> bool {
+    let path = Path::new(command);
+    if path.is_absolute() || command.contains(['/', '\\']) {
+        return path.exists();
+    }
+    let bundled = match command {
+        "python" | "python3" | "uv" => {
+            PythonRuntime::list_installed(app).is_ok_and(|installed| !installed.is_empty())
+        }
+        "node" | "npm" | "npx" => AddonIndex::default()
+            .addons
+            .nodejs
+            .versions
+            .iter()
+            .any(|version| NodeRuntime::detect(app, version).is_ok()),
+        _ => false,
+    };
+    bundled || on_path(command)
+}
+
+fn on_path(command: &str) -> bool {
+    let Some(paths) = std::env::var_os("PATH") else {
+        return false;
+    };
+    let extensions: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    std::env::split_paths(&paths).any(|dir| {
+        extensions
+            .iter()
+            .any(|ext| dir.join(format!("{command}{ext}")).is_file())
+    })
+}
@@ -1,5 +1,7 @@
 use super::models::WorkspaceSettings;
 use crate::error::AppError;
+use crate::features::chat::generation::GenerationParams;
+use crate::features::llm_connection::model_filter;
 use crate::state::AppState;
 use tauri::State;
 
@@ -15,8 +17,35 @@ pub fn save_workspace_settings(
     max_agent_iterations: Option<i64>,
     internal_tools_enabled: Option<bool>,
     selected_skill_ids: Option<String>,
+    context_cache_enabled: Option<bool>,
+    redaction_config: Option<String>,
+    secret_masking_enabled: Option<bool>,
+    paste_dedup_enabled: Option<bool>,
+    post_processing_config: Option<String>,
+    confirm_above_estimated_cost: Option<f64>,
+    assumed_completion_tokens: Option<i64>,
+    max_tool_calls_per_turn: Option<i64>,
+    tool_call_caps: Option<String>,
+    auto_continue_interrupted: Option<bool>,
+    max_output_bytes: Option<i64>,
+    outbox_enabled: Option<bool>,
+    agent_tool_permission: Option<String>,
+    generation_params: Option<GenerationParams>,
+    max_parallel_tools: Option<i64>,
+    summarize_after_messages: Option<i64>,
+    summary_model: Option<String>,
     state: State<'_, AppState>,
 ) -> Result<(), AppError> {
+    // The default model must be one the connection's model lists permit
+    if let (Some(connection_id), Some(model)) = (
+        llm_connection_id.as_deref(),
+        default_model.as_deref().filter(|m| !m.is_empty()),
+    ) {
+        if let Some(connection) = state.llm_connection_service.get_by_id(connection_id)? {
+            model_filter::ensure_model_permitted(&connection, model)?;
+        }
+    }
+
     state
         .workspace_feature
         .settings_service
@@ -31,6 +60,23 @@ pub fn save_workspace_settings(
             max_agent_iterations,
             internal_tools_enabled,
             selected_skill_ids,
+            context_cache_enabled,
+            redaction_config,
+            secret_masking_enabled,
+            paste_dedup_enabled,
+            post_processing_config,
+            confirm_above_estimated_cost,
+            assumed_completion_tokens,
+            max_tool_calls_per_turn,
+            tool_call_caps,
+            auto_continue_interrupted,
+            max_output_bytes,
+            outbox_enabled,
+            agent_tool_permission,
+            generation_params,
+            max_parallel_tools,
+            summarize_after_messages,
+            summary_model,
         )
         .map_err(|e| AppError::Generic(e.to_string()))
 }
@@ -13,6 +13,27 @@ pub struct WorkspaceSettings {
     pub max_agent_iterations: Option<i64>,
     pub internal_tools_enabled: Option<i64>, // 1 for true, 0 for false, default 0
     pub selected_skill_ids: Option<String>,  // JSON array of skill IDs
+    pub context_cache_enabled: Option<i64>,  // 1 for true, 0 for false, default 0
+    pub redaction_config: Option<String>,    // JSON RedactionConfig, NULL when never set
+    pub secret_masking_enabled: Option<i64>, // 1 for true, 0 for false, NULL for default (true)
+    pub paste_dedup_enabled: Option<i64>,    // 1 for true, 0 for false, default 0
+    pub post_processing_config: Option<String>, // JSON PostProcessingConfig, NULL when never set
+    pub confirm_above_estimated_cost: Option<f64>, // Dollars; NULL turns the cost confirmation off
+    pub assumed_completion_tokens: Option<i64>, // Completion size the cost estimate assumes, NULL for default
+    pub max_tool_calls_per_turn: Option<i64>, // Tool calls a turn may run across its iterations, NULL for no cap
+    pub tool_call_caps: Option<String>, // JSON object: { "tool_name": max_calls_per_turn, ... }
+    pub auto_continue_interrupted: Option<i64>, // 1 for true, 0 for false, default 0
+    pub max_output_bytes: Option<i64>,  // Cap on the text of one streamed answer, NULL for default
+    pub outbox_enabled: Option<i64>,    // 1 for true, 0 for false, default 0
+    pub agent_tool_permission: Option<String>, // "auto" | "require" for agent tools their agent does not list, NULL for require
+    pub temperature: Option<f64>,              // NULL for the default (0.7)
+    pub top_p: Option<f64>,                    // NULL leaves it to the provider
+    pub frequency_penalty: Option<f64>,        // NULL leaves it to the provider
+    pub presence_penalty: Option<f64>,         // NULL leaves it to the provider
+    pub max_tokens: Option<i64>,               // Completion cap, NULL leaves it to the provider
+    pub max_parallel_tools: Option<i64>, // Tool calls of one batch run at once, NULL for default
+    pub summarize_after_messages: Option<i64>, // Messages past the chat summary before it is refreshed, NULL turns summaries off
+    pub summary_model: Option<String>, // Model writing chat summaries, NULL for the turn's model
     pub created_at: i64,
     pub updated_at: i64,
 }
@@ -1,5 +1,7 @@
 use super::models::WorkspaceSettings;
 use crate::error::AppError;
+use crate::features::tool::models::ToolOverride;
+use crate::features::tool::overrides;
 use rusqlite::params;
 use std::sync::Arc;
 use tauri::AppHandle;
@@ -10,6 +12,17 @@ pub trait WorkspaceSettingsRepository: Send + Sync {
         &self,
         workspace_id: &str,
     ) -> Result<Option<WorkspaceSettings>, AppError>;
+    /// `(workspace_id, mcp_tool_ids)` of the workspaces with MCP tools and an
+    /// LLM connection, most recently updated first.
+    fn mcp_tool_ids_with_llm_connection(&self) -> Result<Vec<(String, String)>, AppError>;
+    /// How the workspace shows its MCP tools, where it changes anything.
+    fn tool_overrides(&self, workspace_id: &str) -> Result<Vec<ToolOverride>, AppError>;
+    /// Store an override, or drop it when it changes nothing.
+    fn save_tool_override(
+        &self,
+        workspace_id: &str,
+        tool_override: &ToolOverride,
+    ) -> Result<(), AppError>;
 }
 
 pub struct SqliteWorkspaceSettingsRepository {
@@ -37,13 +50,13 @@ impl WorkspaceSettingsRepository for SqliteWorkspaceSettingsRepository {
 
         if exists {
             conn.execute(
-                "UPDATE workspace_settings SET llm_connection_id = ?1, system_message = ?2, mcp_tool_ids = ?3, stream_enabled = ?4, default_model = ?5, tool_permission_config = ?6, max_agent_iterations = ?7, internal_tools_enabled = ?8, selected_skill_ids = ?9, updated_at = ?10 WHERE workspace_id = ?11",
-                params![settings.llm_connection_id, settings.system_message, settings.mcp_tool_ids, settings.stream_enabled, settings.default_model, settings.tool_permission_config, settings.max_agent_iterations, settings.internal_tools_enabled, settings.selected_skill_ids, settings.updated_at, settings.workspace_id],
+                "UPDATE workspace_settings SET llm_connection_id = ?1, system_message = ?2, mcp_tool_ids = ?3, stream_enabled = ?4, default_model = ?5, tool_permission_config = ?6, max_agent_iterations = ?7, internal_tools_enabled = ?8, selected_skill_ids = ?9, context_cache_enabled = ?10, redaction_config = ?11, secret_masking_enabled = ?12, paste_dedup_enabled = ?13, post_processing_config = ?14, confirm_above_estimated_cost = ?15, assumed_completion_tokens = ?16, max_tool_calls_per_turn = ?17, tool_call_caps = ?18, auto_continue_interrupted = ?19, max_output_bytes = ?20, outbox_enabled = ?21, agent_tool_permission = ?22, temperature = ?23, top_p = ?24, frequency_penalty = ?25, presence_penalty = ?26, max_tokens = ?27, max_parallel_tools = ?28, summarize_after_messages = ?29, summary_model = ?30, updated_at = ?31 WHERE workspace_id = ?32",
+                params![settings.llm_connection_id, settings.system_message, settings.mcp_tool_ids, settings.stream_enabled, settings.default_model, settings.tool_permission_config, settings.max_agent_iterations, settings.internal_tools_enabled, settings.selected_skill_ids, settings.context_cache_enabled, settings.redaction_config, settings.secret_masking_enabled, settings.paste_dedup_enabled, settings.post_processing_config, settings.confirm_above_estimated_cost, settings.assumed_completion_tokens, settings.max_tool_calls_per_turn, settings.tool_call_caps, settings.auto_continue_interrupted, settings.max_output_bytes, settings.outbox_enabled, settings.agent_tool_permission, settings.temperature, settings.top_p, settings.frequency_penalty, settings.presence_penalty, settings.max_tokens, settings.max_parallel_tools, settings.summarize_after_messages, settings.summary_model, settings.updated_at, settings.workspace_id],
             )?;
         } else {
             conn.execute(
-                "INSERT INTO workspace_settings (workspace_id, llm_connection_id, system_message, mcp_tool_ids, stream_enabled, default_model, tool_permission_config, max_agent_iterations, internal_tools_enabled, selected_skill_ids, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
-                params![settings.workspace_id, settings.llm_connection_id, settings.system_message, settings.mcp_tool_ids, settings.stream_enabled, settings.default_model, settings.tool_permission_config, settings.max_agent_iterations, settings.internal_tools_enabled, settings.selected_skill_ids, settings.created_at, settings.updated_at],
+                "INSERT INTO workspace_settings (workspace_id, llm_connection_id, system_message, mcp_tool_ids, stream_enabled, default_model, tool_permission_config, max_agent_iterations, internal_tools_enabled, selected_skill_ids, context_cache_enabled, redaction_config, secret_masking_enabled, paste_dedup_enabled, post_processing_config, confirm_above_estimated_cost, assumed_completion_tokens, max_tool_calls_per_turn, tool_call_caps, auto_continue_interrupted, max_output_bytes, outbox_enabled, agent_tool_permission, temperature, top_p, frequency_penalty, presence_penalty, max_tokens, max_parallel_tools, summarize_after_messages, summary_model, created_at, updated_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33)",
+                params![settings.workspace_id, settings.llm_connection_id, settings.system_message, settings.mcp_tool_ids, settings.stream_enabled, settings.default_model, settings.tool_permission_config, settings.max_agent_iterations, settings.internal_tools_enabled, settings.selected_skill_ids, settings.context_cache_enabled, settings.redaction_config, settings.secret_masking_enabled, settings.paste_dedup_enabled, settings.post_processing_config, settings.confirm_above_estimated_cost, settings.assumed_completion_tokens, settings.max_tool_calls_per_turn, settings.tool_call_caps, settings.auto_continue_interrupted, settings.max_output_bytes, settings.outbox_enabled, settings.agent_tool_permission, settings.temperature, settings.top_p, settings.frequency_penalty, settings.presence_penalty, settings.max_tokens, settings.max_parallel_tools, settings.summarize_after_messages, settings.summary_model, settings.created_at, settings.updated_at],
             )?;
         }
 
@@ -56,7 +69,7 @@ impl WorkspaceSettingsRepository for SqliteWorkspaceSettingsRepository {
     ) -> Result<Option<WorkspaceSettings>, AppError> {
         let conn = crate::db::get_connection(&self.app)?;
         let result = conn.query_row(
-            "SELECT workspace_id, llm_connection_id, system_message, mcp_tool_ids, stream_enabled, default_model, tool_permission_config, created_at, updated_at, max_agent_iterations, internal_tools_enabled, selected_skill_ids FROM workspace_settings WHERE workspace_id = ?1",
+            "SELECT workspace_id, llm_connection_id, system_message, mcp_tool_ids, stream_enabled, default_model, tool_permission_config, created_at, updated_at, max_agent_iterations, internal_tools_enabled, selected_skill_ids, context_cache_enabled, redaction_config, secret_masking_enabled, paste_dedup_enabled, post_processing_config, confirm_above_estimated_cost, assumed_completion_tokens, max_tool_calls_per_turn, tool_call_caps, auto_continue_interrupted, max_output_bytes, outbox_enabled, agent_tool_permission, temperature, top_p, frequency_penalty, presence_penalty, max_tokens, max_parallel_tools, summarize_after_messages, summary_model FROM workspace_settings WHERE workspace_id = ?1",
             params![workspace_id],
             |row| {
                 Ok(WorkspaceSettings {
@@ -72,6 +85,27 @@ impl WorkspaceSettingsRepository for SqliteWorkspaceSettingsRepository {
                     max_agent_iterations: row.get(9)?,
                     internal_tools_enabled: row.get(10)?,
                     selected_skill_ids: row.get(11)?,
+                    context_cache_enabled: row.get(12)?,
+                    redaction_config: row.get(13)?,
+                    secret_masking_enabled: row.get(14)?,
+                    paste_dedup_enabled: row.get(15)?,
+                    post_processing_config: row.get(16)?,
+                    confirm_above_estimated_cost: row.get(17)?,
+                    assumed_completion_tokens: row.get(18)?,
+                    max_tool_calls_per_turn: row.get(19)?,
+                    tool_call_caps: row.get(20)?,
+                    auto_continue_interrupted: row.get(21)?,
+                    max_output_bytes: row.get(22)?,
+                    outbox_enabled: row.get(23)?,
+                    agent_tool_permission: row.get(24)?,
+                    temperature: row.get(25)?,
+                    top_p: row.get(26)?,
+                    frequency_penalty: row.get(27)?,
+                    presence_penalty: row.get(28)?,
+                    max_tokens: row.get(29)?,
+                    max_parallel_tools: row.get(30)?,
+                    summarize_after_messages: row.get(31)?,
+                    summary_model: row.get(32)?,
                 })
             },
         );
@@ -82,4 +116,68 @@ impl WorkspaceSettingsRepository for SqliteWorkspaceSettingsRepository {
             Err(e) => Err(e.into()),
         }
     }
+
+    fn mcp_tool_ids_with_llm_connection(&self) -> Result<Vec<(String, String)>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let mut stmt = conn.prepare(
+            "SELECT workspace_id, mcp_tool_ids FROM workspace_settings
+             WHERE mcp_tool_ids IS NOT NULL AND llm_connection_id IS NOT NULL
+             ORDER BY updated_at DESC",
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn tool_overrides(&self, workspace_id: &str) -> Result<Vec<ToolOverride>, AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        let mut stmt = conn.prepare(
+            "SELECT tool_name, disabled, alias, description FROM workspace_tool_overrides
+             WHERE workspace_id = ?1 ORDER BY tool_name",
+        )?;
+        let rows = stmt.query_map(params![workspace_id], |row| {
+            Ok(ToolOverride {
+                tool_name: row.get(0)?,
+                disabled: row.get(1)?,
+                alias: row.get(2)?,
+                description: row.get(3)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    fn save_tool_override(
+        &self,
+        workspace_id: &str,
+        tool_override: &ToolOverride,
+    ) -> Result<(), AppError> {
+        let conn = crate::db::get_connection(&self.app)?;
+        if overrides::is_noop(tool_override) {
+            conn.execute(
+                "DELETE FROM workspace_tool_overrides WHERE workspace_id = ?1 AND tool_name = ?2",
+                params![workspace_id, tool_override.tool_name],
+            )?;
+            return Ok(());
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_millis() as i64;
+        conn.execute(
+            "INSERT INTO workspace_tool_overrides (workspace_id, tool_name, disabled, alias, description, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(workspace_id, tool_name) DO UPDATE SET
+                disabled = excluded.disabled, alias = excluded.alias,
+                description = excluded.description, updated_at = excluded.updated_at",
+            params![
+                workspace_id,
+                tool_override.tool_name,
+                tool_override.disabled,
+                tool_override.alias,
+                tool_override.description,
+                now
+            ],
+        )?;
+        Ok(())
+    }
 }
@@ -1,6 +1,12 @@
 use super::models::WorkspaceSettings;
 use super::repository::WorkspaceSettingsRepository;
 use crate::error::AppError;
+use crate::features::chat::generation::GenerationParams;
+use crate::features::chat::{tool_caps, tool_permissions};
+use crate::features::post_processing::PostProcessingService;
+use crate::features::redaction::RedactionService;
+use crate::features::tool::models::ToolOverride;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 pub struct WorkspaceSettingsService {
@@ -24,6 +30,23 @@ impl WorkspaceSettingsService {
         max_agent_iterations: Option<i64>,
         internal_tools_enabled: Option<bool>,
         selected_skill_ids: Option<String>,
+        context_cache_enabled: Option<bool>,
+        redaction_config: Option<String>,
+        secret_masking_enabled: Option<bool>,
+        paste_dedup_enabled: Option<bool>,
+        post_processing_config: Option<String>,
+        confirm_above_estimated_cost: Option<f64>,
+        assumed_completion_tokens: Option<i64>,
+        max_tool_calls_per_turn: Option<i64>,
+        tool_call_caps: Option<String>,
+        auto_continue_interrupted: Option<bool>,
+        max_output_bytes: Option<i64>,
+        outbox_enabled: Option<bool>,
+        agent_tool_permission: Option<String>,
+        generation_params: Option<GenerationParams>,
+        max_parallel_tools: Option<i64>,
+        summarize_after_messages: Option<i64>,
+        summary_model: Option<String>,
     ) -> Result<(), AppError> {
         let now = std::time::SystemTime::now()
             .duration_since(std::time::UNIX_EPOCH)
@@ -32,6 +55,148 @@ impl WorkspaceSettingsService {
 
         let stream_enabled_i64: Option<i64> = stream_enabled.map(i64::from);
         let internal_tools_enabled_i64 = internal_tools_enabled.map(i64::from);
+        // Keep the stored opt-ins when the caller doesn't specify them
+        let stored = if context_cache_enabled.is_none()
+            || redaction_config.is_none()
+            || secret_masking_enabled.is_none()
+            || paste_dedup_enabled.is_none()
+            || post_processing_config.is_none()
+            || confirm_above_estimated_cost.is_none()
+            || assumed_completion_tokens.is_none()
+            || max_tool_calls_per_turn.is_none()
+            || tool_call_caps.is_none()
+            || auto_continue_interrupted.is_none()
+            || max_output_bytes.is_none()
+            || outbox_enabled.is_none()
+            || agent_tool_permission.is_none()
+            || generation_params.is_none()
+            || max_parallel_tools.is_none()
+            || summarize_after_messages.is_none()
+            || summary_model.is_none()
+        {
+            self.repository.get_by_workspace_id(&workspace_id)?
+        } else {
+            None
+        };
+        let context_cache_enabled_i64 = context_cache_enabled.map_or_else(
+            || stored.as_ref().and_then(|s| s.context_cache_enabled),
+            |enabled| Some(i64::from(enabled)),
+        );
+        let secret_masking_enabled_i64 = secret_masking_enabled.map_or_else(
+            || stored.as_ref().and_then(|s| s.secret_masking_enabled),
+            |enabled| Some(i64::from(enabled)),
+        );
+        let paste_dedup_enabled_i64 = paste_dedup_enabled.map_or_else(
+            || stored.as_ref().and_then(|s| s.paste_dedup_enabled),
+            |enabled| Some(i64::from(enabled)),
+        );
+        let auto_continue_interrupted_i64 = auto_continue_interrupted.map_or_else(
+            || stored.as_ref().and_then(|s| s.auto_continue_interrupted),
+            |enabled| Some(i64::from(enabled)),
+        );
+        let outbox_enabled_i64 = outbox_enabled.map_or_else(
+            || stored.as_ref().and_then(|s| s.outbox_enabled),
+            |enabled| Some(i64::from(enabled)),
+        );
+        let post_processing_config = match post_processing_config {
+            Some(config) => {
+                PostProcessingService::parse_config(&config)?;
+                Some(config)
+            }
+            None => stored
+                .as_ref()
+                .and_then(|s| s.post_processing_config.clone()),
+        };
+        // Zero or less turns the confirmation off, or resets the assumed size
+        let confirm_above_estimated_cost = confirm_above_estimated_cost.map_or_else(
+            || stored.as_ref().and_then(|s| s.confirm_above_estimated_cost),
+            |threshold| (threshold > 0.0).then_some(threshold),
+        );
+        let assumed_completion_tokens = assumed_completion_tokens.map_or_else(
+            || stored.as_ref().and_then(|s| s.assumed_completion_tokens),
+            |tokens| (tokens > 0).then_some(tokens),
+        );
+        // Zero lifts the per-turn cap, an empty map the per-tool ones
+        let max_tool_calls_per_turn = match max_tool_calls_per_turn {
+            Some(max) if max < 0 => {
+                return Err(AppError::Validation(format!(
+                    "Max tool calls per turn cannot be negative, got {max}"
+                )));
+            }
+            Some(max) => (max > 0).then_some(max),
+            None => stored.as_ref().and_then(|s| s.max_tool_calls_per_turn),
+        };
+        let tool_call_caps = match tool_call_caps {
+            Some(caps) => {
+                let parsed = tool_caps::parse_tool_call_caps(&caps)?;
+                (!parsed.is_empty()).then_some(caps)
+            }
+            None => stored.as_ref().and_then(|s| s.tool_call_caps.clone()),
+        };
+        // Zero goes back to the default caps
+        let max_output_bytes = match max_output_bytes {
+            Some(bytes) if bytes < 0 => {
+                return Err(AppError::Validation(format!(
+                    "Max output bytes cannot be negative, got {bytes}"
+                )));
+            }
+            Some(bytes) => (bytes > 0).then_some(bytes),
+            None => stored.as_ref().and_then(|s| s.max_output_bytes),
+        };
+        // Zero goes back to the default
+        let max_parallel_tools = match max_parallel_tools {
+            Some(max) if max < 0 => {
+                return Err(AppError::Validation(format!(
+                    "Max parallel tools cannot be negative, got {max}"
+                )));
+            }
+            Some(max) => (max > 0).then_some(max),
+            None => stored.as_ref().and_then(|s| s.max_parallel_tools),
+        };
+        // Zero turns summaries off
+        let summarize_after_messages = match summarize_after_messages {
+            Some(count) if count < 0 => {
+                return Err(AppError::Validation(format!(
+                    "Summarize after messages cannot be negative, got {count}"
+                )));
+            }
+            Some(count) => (count > 0).then_some(count),
+            None => stored.as_ref().and_then(|s| s.summarize_after_messages),
+        };
+        // Empty goes back to the turn's model
+        let summary_model = match summary_model {
+            Some(model) => Some(model.trim().to_string()).filter(|m| !m.is_empty()),
+            None => stored.as_ref().and_then(|s| s.summary_model.clone()),
+        };
+        // Empty goes back to requiring permission
+        let agent_tool_permission = match agent_tool_permission {
+            Some(permission) if permission.is_empty() => None,
+            Some(permission) => {
+                tool_permissions::validate_permission(&permission)?;
+                Some(permission)
+            }
+            None => stored
+                .as_ref()
+                .and_then(|s| s.agent_tool_permission.clone()),
+        };
+        // Replaced as a whole; an empty set goes back to the defaults
+        let generation_params = match generation_params {
+            Some(params) => {
+                params.validate()?;
+                params
+            }
+            None => stored
+                .as_ref()
+                .map(GenerationParams::of_workspace)
+                .unwrap_or_default(),
+        };
+        let redaction_config = match redaction_config {
+            Some(config) => {
+                RedactionService::parse_config(&config)?;
+                Some(config)
+            }
+            None => stored.and_then(|s| s.redaction_config),
+        };
 
         let settings = WorkspaceSettings {
             workspace_id,
@@ -44,6 +209,27 @@ impl WorkspaceSettingsService {
             max_agent_iterations,
             internal_tools_enabled: internal_tools_enabled_i64,
             selected_skill_ids,
+            context_cache_enabled: context_cache_enabled_i64,
+            redaction_config,
+            secret_masking_enabled: secret_masking_enabled_i64,
+            paste_dedup_enabled: paste_dedup_enabled_i64,
+            post_processing_config,
+            confirm_above_estimated_cost,
+            assumed_completion_tokens,
+            max_tool_calls_per_turn,
+            tool_call_caps,
+            auto_continue_interrupted: auto_continue_interrupted_i64,
+            max_output_bytes,
+            outbox_enabled: outbox_enabled_i64,
+            agent_tool_permission,
+            temperature: generation_params.temperature,
+            top_p: generation_params.top_p,
+            frequency_penalty: generation_params.frequency_penalty,
+            presence_penalty: generation_params.presence_penalty,
+            max_tokens: generation_params.max_tokens.map(i64::from),
+            max_parallel_tools,
+            summarize_after_messages,
+            summary_model,
             created_at: now,
             updated_at: now,
         };
@@ -51,6 +237,88 @@ impl WorkspaceSettingsService {
         self.repository.save(&settings)
     }
 
+    /// Replace the tool permission config of a workspace, leaving the other
+    /// settings as they are.
+    pub fn set_tool_permission_config(
+        &self,
+        workspace_id: &str,
+        tool_permission_config: String,
+    ) -> Result<(), AppError> {
+        let mut settings = self.get_by_workspace_id(workspace_id)?.ok_or_else(|| {
+            AppError::NotFound(format!("Workspace settings not found: {workspace_id}"))
+        })?;
+        settings.tool_permission_config = Some(tool_permission_config);
+        settings.updated_at = chrono::Utc::now().timestamp_millis();
+        self.repository.save(&settings)
+    }
+
+    /// Set the permission of some tools in the workspace's tool permission
+    /// config, keeping the other tools' as they are.
+    pub fn merge_tool_permissions(
+        &self,
+        workspace_id: &str,
+        permissions: BTreeMap<String, String>,
+    ) -> Result<(), AppError> {
+        for (tool, permission) in &permissions {
+            tool_permissions::validate_tool_entry(tool, permission)?;
+        }
+        let settings = self.get_by_workspace_id(workspace_id)?.ok_or_else(|| {
+            AppError::NotFound(format!("Workspace settings not found: {workspace_id}"))
+        })?;
+        let mut config =
+            tool_permissions::parse_permission_config(settings.tool_permission_config.as_deref());
+        config.extend(permissions);
+        self.set_tool_permission_config(workspace_id, serde_json::to_string(&config)?)
+    }
+
+    /// Whether tool-call arguments are masked for display and storage.
+    /// On unless the workspace turned it off.
+    pub fn secret_masking_enabled(&self, workspace_id: &str) -> bool {
+        self.repository
+            .get_by_workspace_id(workspace_id)
+            .ok()
+            .flatten()
+            .and_then(|s| s.secret_masking_enabled)
+            .is_none_or(|v| v == 1)
+    }
+
+    /// Workspaces with an LLM connection whose tools come from the MCP
+    /// connection `connection_id`, most recently updated first.
+    pub fn workspaces_using_mcp_connection(
+        &self,
+        connection_id: &str,
+    ) -> Result<Vec<String>, AppError> {
+        Ok(self
+            .repository
+            .mcp_tool_ids_with_llm_connection()?
+            .into_iter()
+            .filter(|(_, ids_json)| {
+                serde_json::from_str::<std::collections::HashMap<String, String>>(ids_json)
+                    .is_ok_and(|tools| tools.values().any(|id| id == connection_id))
+            })
+            .map(|(workspace_id, _)| workspace_id)
+            .collect())
+    }
+
+    /// How the workspace shows its MCP tools, where it changes anything.
+    pub fn get_tool_overrides(&self, workspace_id: &str) -> Result<Vec<ToolOverride>, AppError> {
+        self.repository.tool_overrides(workspace_id)
+    }
+
+    pub fn save_tool_override(
+        &self,
+        workspace_id: &str,
+        tool_override: &ToolOverride,
+    ) -> Result<(), AppError> {
+        self.repository
+            .save_tool_override(workspace_id, tool_override)
+    }
+
+    /// The stored settings, without creating defaults when there are none.
+    pub fn get_stored(&self, workspace_id: &str) -> Result<Option<WorkspaceSettings>, AppError> {
+        self.repository.get_by_workspace_id(workspace_id)
+    }
+
     pub fn get_by_workspace_id(
         &self,
         workspace_id: &str,
@@ -70,6 +338,23 @@ impl WorkspaceSettingsService {
                 Some(10),
                 Some(false),
                 None,
+                Some(false),
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
+                None,
             )?;
             return self.repository.get_by_workspace_id(workspace_id);
         }
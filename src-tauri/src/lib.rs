@@ -8,11 +8,14 @@ mod models;
 
 mod services;
 mod state;
+#[cfg(test)]
+mod test_support;
 
 // Sentry helper macros and functions
 #[macro_use]
 mod lib {
     pub mod sentry_helpers;
+    pub mod text;
 }
 
 use std::sync::Arc;
@@ -43,6 +46,8 @@ fn init_sentry() -> sentry::ClientInitGuard {
         sample_rate: 1.0,
         traces_sample_rate: if cfg!(debug_assertions) { 1.0 } else { 0.1 },
         attach_stacktrace: true,
+        before_send: Some(std::sync::Arc::new(lib::sentry_helpers::scrub_event)),
+        before_breadcrumb: Some(std::sync::Arc::new(lib::sentry_helpers::scrub_breadcrumb)),
         ..Default::default()
     });
 
@@ -80,6 +85,16 @@ pub fn run() {
                     log::LevelFilter::Info
                 })
                 .rotation_strategy(tauri_plugin_log::RotationStrategy::KeepAll)
+                // The default format, with API keys and other secrets masked
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "{}[{}][{}] {}",
+                        chrono::Utc::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                        record.target(),
+                        record.level(),
+                        features::redaction::secrets::scrub(&message.to_string())
+                    ));
+                })
                 .build(),
         )
         .plugin(tauri_plugin_clipboard_manager::init())
@@ -88,6 +103,16 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_updater::Builder::new().build())
         .plugin(tauri_plugin_process::init())
+        // Stored files referenced from message content as lunex-file://<hash>
+        .register_uri_scheme_protocol(
+            features::attachment::file_uri::FILE_URI_SCHEME,
+            |ctx, request| {
+                features::attachment::commands::serve_lunex_file(
+                    ctx.app_handle(),
+                    &request.uri().to_string(),
+                )
+            },
+        )
         .setup(|app| {
             // Maximize the main window on startup and ensure it's at the top
             if let Some(window) = app.get_webview_window("main") {
@@ -114,20 +139,34 @@ pub fn run() {
                 }
             }
 
-            // Initialize AppState
+            // Initialize AppState, unless another instance holds the data directory
             let app_handle = Arc::new(app.handle().clone());
-            let app_state = state::AppState::new(app_handle).map_err(|e| {
-                // Report initialization error to Sentry
-                sentry::capture_error(&e);
-                sentry::add_breadcrumb(sentry::Breadcrumb {
-                    message: Some("Failed to initialize app state".to_string()),
-                    level: sentry::Level::Error,
-                    ..Default::default()
-                });
-                anyhow::anyhow!("Failed to initialize app state: {e}")
-            })?;
-
-            app.manage(app_state);
+            match state::AppState::new(app_handle) {
+                Ok(app_state) => {
+                    app.manage(app_state);
+                    // Connect flagged MCP servers without holding up the window
+                    features::tool::mcp_autostart::start(app.handle().clone());
+                    // Opened through a lunex:// link (Windows and Linux pass it as an argument)
+                    if let Some(uri) =
+                        features::chat::permalink::launch_uri(std::env::args().skip(1))
+                    {
+                        features::chat::permalink::open_from_os(app.handle(), &uri);
+                    }
+                }
+                Err(error::AppError::InstanceLocked(pid)) => {
+                    resolve_instance_conflict(app.handle().clone(), pid);
+                }
+                Err(e) => {
+                    // Report initialization error to Sentry
+                    sentry::capture_error(&e);
+                    sentry::add_breadcrumb(sentry::Breadcrumb {
+                        message: Some("Failed to initialize app state".to_string()),
+                        level: sentry::Level::Error,
+                        ..Default::default()
+                    });
+                    return Err(anyhow::anyhow!("Failed to initialize app state: {e}").into());
+                }
+            }
 
             // Initialize MCPClientState
             let mcp_client_state = state::MCPClientState::new();
@@ -153,55 +192,124 @@ pub fn run() {
         })
         .invoke_handler(tauri::generate_handler![
             greet,
+            events::catalog::get_event_schema,
             // Workspace commands
             features::workspace::management::commands::create_workspace,
             features::workspace::management::commands::get_workspaces,
             features::workspace::management::commands::update_workspace,
             features::workspace::management::commands::delete_workspace,
+            features::workspace::readiness::commands::check_workspace_readiness,
             // Chat commands
             features::chat::commands::create_chat,
             features::chat::commands::get_chats,
+            features::chat::commands::mark_chat_read,
             features::chat::commands::update_chat,
             features::chat::commands::delete_chat,
             features::chat::commands::get_or_create_specialist_session,
             features::chat::commands::delete_all_chats_by_workspace,
             features::chat::commands::send_message,
+            features::chat::commands::send_message_compare,
             features::chat::commands::generate_chat_title,
+            features::chat::commands::summarize_chat,
             features::chat::commands::edit_and_resend_message,
             features::chat::commands::respond_tool_permission,
+            features::chat::commands::list_remembered_tool_permissions,
+            features::chat::commands::revoke_remembered_tool_permission,
+            features::chat::commands::respond_cost_confirmation,
+            features::chat::commands::run_prompt_oneshot,
+            features::chat::commands::notify_workspace_active,
+            features::chat::commands::get_chat_scratchpad,
+            features::chat::commands::set_chat_scratchpad,
+            features::chat::commands::get_chat_instructions,
+            features::chat::commands::set_chat_instructions,
+            features::chat::commands::get_chat_boot_data,
+            features::chat::commands::get_turn_timeline,
+            features::chat::commands::get_active_chat_status,
+            features::chat::commands::get_message_permalink,
+            features::chat::commands::open_permalink,
+            features::chat::commands::take_pending_navigation,
+            features::chat::commands::export_chat,
+            features::chat::commands::export_workspace,
+            features::chat::commands::list_branches,
+            features::chat::commands::create_branch,
+            features::chat::commands::switch_branch,
+            features::chat::commands::regenerate_message,
+            features::chat::commands::select_message_variant,
+            features::activity::commands::get_activity,
+            features::activity::commands::mark_activity_read,
+            features::semantic_index::commands::semantic_search_messages,
+            features::semantic_index::commands::backfill_semantic_index,
+            features::knowledge::commands::index_document,
+            features::knowledge::commands::list_knowledge_documents,
+            features::knowledge::commands::search_knowledge,
+            features::knowledge::commands::delete_document,
+            features::search::commands::search_messages,
+            features::search::commands::search_chats,
+            features::tool_catalog::commands::get_tool_catalog_history,
             // Message commands
             features::message::commands::create_message,
             features::message::commands::get_messages,
             features::message::commands::update_message,
             features::message::commands::delete_messages_after,
             features::message::commands::cancel_message,
+            features::message::commands::diff_messages,
             // Chat Input Settings commands
             features::chat::input_settings::commands::get_chat_input_settings,
             features::chat::input_settings::commands::save_chat_input_settings,
             // Workspace Settings commands
             features::workspace::settings::commands::save_workspace_settings,
             features::workspace::settings::commands::get_workspace_settings,
+            features::workspace::notes::commands::get_workspace_notes,
+            features::workspace::notes::commands::save_workspace_notes,
+            features::workspace::archive::commands::export_workspace_archive,
+            features::workspace::archive::commands::import_workspace_archive,
             // LLM Connection commands
             features::llm_connection::commands::create_llm_connection,
             features::llm_connection::commands::get_llm_connections,
             features::llm_connection::commands::update_llm_connection,
             features::llm_connection::commands::delete_llm_connection,
             features::llm_connection::commands::test_llm_connection,
+            features::llm_connection::commands::get_effective_models,
+            features::llm_connection::commands::get_connection_rate_status,
+            features::llm_connection::commands::embed_texts,
+            features::llm_connection::commands::get_ollama_model_info,
+            features::llm_connection::commands::pull_ollama_model,
             // MCP Server Connection commands
             features::mcp_connection::commands::create_mcp_server_connection,
             features::mcp_connection::commands::get_mcp_server_connections,
             features::mcp_connection::commands::update_mcp_server_connection,
             features::mcp_connection::commands::delete_mcp_server_connection,
             features::mcp_connection::commands::update_mcp_server_status,
+            features::mcp_connection::commands::set_mcp_server_auto_connect,
+            features::mcp_connection::commands::validate_mcp_connection_config,
             // App Settings commands
             features::app_settings::commands::save_app_setting,
             features::app_settings::commands::get_app_setting,
             features::app_settings::commands::get_all_app_settings,
+            features::app_settings::commands::get_time_settings,
+            features::app_settings::commands::save_time_settings,
+            features::app_settings::commands::get_builtin_tool_settings,
+            features::app_settings::commands::save_builtin_tool_settings,
             // Prompt commands
             features::prompt::commands::create_prompt,
             features::prompt::commands::get_prompts,
             features::prompt::commands::update_prompt,
             features::prompt::commands::delete_prompt,
+            features::prompt::commands::export_prompts,
+            features::prompt::commands::import_prompts,
+            features::prompt::commands::create_prompt_from_messages,
+            features::quick_action::commands::create_quick_action,
+            features::quick_action::commands::get_quick_actions,
+            features::quick_action::commands::update_quick_action,
+            features::quick_action::commands::delete_quick_action,
+            features::quick_action::commands::run_quick_action,
+            features::webhook::commands::create_webhook,
+            features::webhook::commands::get_webhooks,
+            features::webhook::commands::update_webhook,
+            features::webhook::commands::delete_webhook,
+            features::outbox::commands::get_outbox,
+            features::outbox::commands::retry_outbox,
+            features::outbox::commands::discard_outbox_item,
             // Hub commands
             features::hub::commands::fetch_hub_prompts,
             features::hub::commands::fetch_prompt_template,
@@ -211,6 +319,9 @@ pub fn run() {
             features::hub::commands::refresh_hub_index,
             features::hub::commands::fetch_hub_agents,
             features::hub::commands::install_agent_from_hub,
+            features::hub::commands::fetch_hub_packs,
+            features::hub::commands::install_pack_from_hub,
+            features::hub::commands::uninstall_pack_from_hub,
             // MCP Tools commands
             features::tool::commands::test_mcp_connection_and_fetch_tools,
             features::tool::commands::connect_mcp_server_and_fetch_tools,
@@ -218,6 +329,16 @@ pub fn run() {
             features::tool::commands::call_mcp_tool,
             features::tool::commands::disconnect_mcp_client,
             features::tool::commands::get_active_tools_for_workspace,
+            features::tool::commands::get_workspace_tools,
+            features::tool::commands::set_workspace_tool_override,
+            features::tool::commands::list_mcp_resources,
+            features::tool::commands::list_mcp_resource_templates,
+            features::tool::commands::read_mcp_resource,
+            features::tool::commands::attach_mcp_resource,
+            features::tool::commands::list_mcp_prompts,
+            features::tool::commands::get_mcp_prompt,
+            features::tool::commands::apply_mcp_prompt_to_chat,
+            features::tool::commands::respond_mcp_sampling,
             // Python commands
             features::runtime::python::commands::get_python_runtimes_status,
             features::runtime::python::commands::install_python_runtime,
@@ -232,17 +353,52 @@ pub fn run() {
             features::runtime::node::commands::install_node_runtime,
             features::runtime::node::commands::uninstall_node_runtime,
             features::runtime::node::commands::install_node_packages,
+            // Attachment commands
+            features::attachment::commands::approve_file_access,
+            features::attachment::commands::verify_attachments,
+            features::attachment::commands::cancel_verify_attachments,
+            features::attachment::commands::resolve_lunex_file,
+            features::attachment::commands::embed_lunex_files,
+            features::attachment::commands::export_lunex_files,
             // Usage commands
             features::usage::commands::get_usage_summary,
             features::usage::commands::get_usage_chart,
             features::usage::commands::get_usage_logs,
             features::usage::commands::clear_usage,
+            features::usage::commands::get_usage_by_chat,
+            features::usage::commands::delete_usage_where,
+            features::usage::commands::anonymize_usage_where,
+            features::usage::commands::get_tool_usage_summary,
+            // LLM response cache commands
+            features::llm_cache::commands::get_llm_cache_stats,
+            features::llm_cache::commands::clear_llm_cache,
+            // Encryption and system commands
+            features::encryption::commands::enable_encryption,
+            features::encryption::commands::disable_encryption,
+            features::system::commands::get_system_status,
+            features::system::commands::panic_stop_all,
+            features::system::commands::resume_activity,
+            features::maintenance::commands::run_db_maintenance,
+            features::maintenance::commands::get_db_info,
+            features::backup::commands::backup_now,
+            features::backup::commands::list_backups,
+            features::backup::commands::restore_backup,
+            // Chat share commands
+            features::share::commands::start_chat_share,
+            features::share::commands::stop_chat_share,
+            // Chat import commands
+            features::chat_import::commands::import_external_chats,
+            features::chat_import::commands::import_chat,
+            features::chat_import::commands::cancel_chat_import,
             // Agent commands
             features::agent::commands::install_agent,
             features::agent::commands::get_installed_agents,
             features::agent::commands::delete_agent,
             features::agent::commands::get_agent_info,
             features::agent::commands::update_agent,
+            features::agent::commands::set_agent_tool_permissions,
+            features::agent::commands::export_agent,
+            features::agent::commands::import_agent,
             // Skill commands
             features::skill::commands::get_all_skills,
             features::skill::commands::sync_skills,
@@ -255,8 +411,71 @@ pub fn run() {
             features::notes::commands::update_note,
             features::notes::commands::delete_note,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|app, event| {
+            // macOS delivers lunex:// links as an event, also at launch
+            #[cfg(target_os = "macos")]
+            if let tauri::RunEvent::Opened { urls } = &event {
+                for url in urls {
+                    features::chat::permalink::open_from_os(app, url.as_str());
+                }
+            }
+            // Release the data directory for the next instance
+            if matches!(event, tauri::RunEvent::Exit) {
+                if let Some(app_state) = app.try_state::<state::AppState>() {
+                    app_state.instance_lock.release();
+                }
+            }
+        });
+}
+
+/// Another instance holds the data directory: ask whether to quit or take
+/// over. Taking over only succeeds once that instance's heartbeat is stale;
+/// until the lock is ours the main window stays hidden.
+fn resolve_instance_conflict(app: tauri::AppHandle, pid: u32) {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
+
+    if let Some(window) = app.get_webview_window("main") {
+        if let Err(e) = window.hide() {
+            log::error!("Failed to hide window: {e}");
+        }
+    }
+
+    app.dialog()
+        .message(format!(
+            "Another Lunex instance (process {pid}) is using the same data. \
+             Running both can corrupt chats and settings.\n\n\
+             Take over only works once that instance has quit or stopped responding."
+        ))
+        .title("Lunex is already running")
+        .kind(MessageDialogKind::Error)
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Take over".to_string(),
+            "Quit".to_string(),
+        ))
+        .show(move |take_over| {
+            if !take_over {
+                app.exit(0);
+                return;
+            }
+            match state::AppState::new(Arc::new(app.clone())) {
+                Ok(app_state) => {
+                    app.manage(app_state);
+                    features::tool::mcp_autostart::start(app.clone());
+                    if let Some(window) = app.get_webview_window("main") {
+                        if let Err(e) = window.show() {
+                            log::error!("Failed to show window: {e}");
+                        }
+                    }
+                }
+                Err(error::AppError::InstanceLocked(pid)) => resolve_instance_conflict(app, pid),
+                Err(e) => {
+                    log::error!("Failed to initialize app state: {e}");
+                    app.exit(1);
+                }
+            }
+        });
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
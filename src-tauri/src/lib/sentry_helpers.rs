@@ -222,3 +222,52 @@ pub fn track_db_operation<T, E>(
         }
     }
 }
+
+/// Mask API keys and other secrets in an event before it is sent
+pub fn scrub_event(
+    mut event: sentry::protocol::Event<'static>,
+) -> Option<sentry::protocol::Event<'static>> {
+    use crate::features::redaction::secrets::scrub;
+
+    scrub_opt(&mut event.message);
+    if let Some(entry) = event.logentry.as_mut() {
+        entry.message = scrub(&entry.message).into_owned();
+    }
+    for exception in &mut event.exception.values {
+        scrub_opt(&mut exception.value);
+    }
+    event.breadcrumbs.values = std::mem::take(&mut event.breadcrumbs.values)
+        .into_iter()
+        .filter_map(scrub_breadcrumb)
+        .collect();
+    for value in event.extra.values_mut() {
+        scrub_json(value);
+    }
+    Some(event)
+}
+
+/// Mask API keys and other secrets in a breadcrumb before it is recorded
+pub fn scrub_breadcrumb(mut breadcrumb: sentry::Breadcrumb) -> Option<sentry::Breadcrumb> {
+    scrub_opt(&mut breadcrumb.message);
+    for value in breadcrumb.data.values_mut() {
+        scrub_json(value);
+    }
+    Some(breadcrumb)
+}
+
+fn scrub_opt(text: &mut Option<String>) {
+    if let Some(text) = text.as_mut() {
+        *text = crate::features::redaction::secrets::scrub(text).into_owned();
+    }
+}
+
+fn scrub_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(text) => {
+            *text = crate::features::redaction::secrets::scrub(text).into_owned();
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(scrub_json),
+        serde_json::Value::Object(map) => map.values_mut().for_each(scrub_json),
+        _ => {}
+    }
+}
@@ -0,0 +1,119 @@
+//! Cutting text down for titles and previews.
+//!
+//! Lengths are counted in grapheme clusters, what a reader sees as one
+//! character, so a cut never splits a multibyte character, an accented
+//! letter or an emoji sequence such as a family or a flag.
+
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Quote pairs a model may wrap a short answer in.
+const QUOTE_PAIRS: &[(&str, &str)] = &[
+    ("\"", "\""),
+    ("'", "'"),
+    ("“", "”"),
+    ("‘", "’"),
+    ("„", "“"),
+    ("«", "»"),
+    ("「", "」"),
+    ("『", "』"),
+];
+
+pub fn grapheme_count(text: &str) -> usize {
+    text.graphemes(true).count()
+}
+
+/// `text` when it has at most `max` graphemes; otherwise its start followed
+/// by `marker`, together at most `max` graphemes.
+pub fn truncate(text: &str, max: usize, marker: &str) -> String {
+    let Some((end, _)) = text.grapheme_indices(true).nth(max) else {
+        return text.to_string();
+    };
+    let keep = max.saturating_sub(grapheme_count(marker));
+    let end = text
+        .grapheme_indices(true)
+        .nth(keep)
+        .map_or(end, |(index, _)| index);
+    format!("{}{marker}", &text[..end])
+}
+
+/// `text` without the quotes around it, if it is quoted as a whole. Nested
+/// pairs such as `"«Title»"` are all removed; a lone quote is kept.
+pub fn strip_surrounding_quotes(text: &str) -> &str {
+    let mut text = text.trim();
+    while let Some(inner) = QUOTE_PAIRS.iter().find_map(|(open, close)| {
+        text.strip_prefix(open)
+            .and_then(|rest| rest.strip_suffix(close))
+            .map(str::trim)
+            .filter(|inner| !inner.is_empty())
+    }) {
+        text = inner;
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn text_truncate_never_splits_grapheme_clusters() {
+        use crate::features::chat::models::{Chat, LAST_MESSAGE_MAX_GRAPHEMES};
+        use crate::lib::text::{grapheme_count, truncate};
+
+        let family = "👨\u{200d}👩\u{200d}👧\u{200d}👦";
+        let preview = Chat::last_message_preview(&family.repeat(150));
+        assert_eq!(grapheme_count(&preview), LAST_MESSAGE_MAX_GRAPHEMES);
+        assert_eq!(preview, format!("{}...", family.repeat(97)));
+
+        // Flags, skin tones and combining accents stay whole
+        for cluster in ["🇻🇳", "👍🏽", "e\u{301}", "🏳\u{fe0f}\u{200d}🌈"] {
+            for max in 1..6 {
+                let cut = truncate(&cluster.repeat(8), max, "…");
+                assert_eq!(grapheme_count(&cut), max);
+                assert_eq!(cut.trim_end_matches('…'), cluster.repeat(max - 1));
+            }
+        }
+
+        let cjk = "東京と大阪の旅行計画について";
+        assert_eq!(truncate(cjk, 5, "…"), "東京と大…");
+        assert_eq!(truncate(cjk, 14, "…"), cjk);
+        assert_eq!(truncate("abcdef", 4, "..."), "a...");
+        assert_eq!(truncate("short", 100, "..."), "short");
+        assert_eq!(Chat::last_message_preview(""), "");
+
+        // Right-to-left text is cut at its logical start, marks included
+        let hebrew = "\u{200f}שלום עולם, מה שלומך היום?";
+        let cut = truncate(hebrew, 10, "…");
+        assert_eq!(grapheme_count(&cut), 10);
+        assert!(hebrew.starts_with(cut.trim_end_matches('…')));
+        let arabic = "مرحبا بالعالم ".repeat(20);
+        assert_eq!(grapheme_count(&truncate(&arabic, 50, "…")), 50);
+    }
+
+    #[test]
+    fn text_surrounding_quotes_are_stripped_safely() {
+        use crate::lib::text::strip_surrounding_quotes;
+
+        for (title, expected) in [
+            ("\"Trip to Hanoi\"", "Trip to Hanoi"),
+            ("  “Kế hoạch du lịch”\n", "Kế hoạch du lịch"),
+            ("«Résumé du projet»", "Résumé du projet"),
+            ("『東京旅行の計画』", "東京旅行の計画"),
+            ("「予算」", "予算"),
+            ("„Reiseplan“", "Reiseplan"),
+            ("\"«Nested»\"", "Nested"),
+            ("\"שלום עולם\"", "שלום עולם"),
+            (
+                "\"👨\u{200d}👩\u{200d}👧 Family trip\"",
+                "👨\u{200d}👩\u{200d}👧 Family trip",
+            ),
+            // Not quoted as a whole, or nothing inside
+            ("\"Quoted\" word", "\"Quoted\" word"),
+            ("“Mismatched\"", "“Mismatched\""),
+            ("\"", "\""),
+            ("\"\"", "\"\""),
+            ("“”", "“”"),
+            ("", ""),
+        ] {
+            assert_eq!(strip_surrounding_quotes(title), expected, "{title}");
+        }
+    }
+}
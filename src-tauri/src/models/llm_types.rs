@@ -1,3 +1,6 @@
+use crate::features::llm_connection::config::ConnectionConfig;
+use crate::features::llm_connection::model_filter::ModelFilter;
+use crate::services::llm::output_limit::OutputLimits;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -86,6 +89,10 @@ pub struct ImageUrl {
 pub struct FileUrl {
     pub url: String,
     pub mime_type: String,
+    /// SHA-256 of the attachment bytes, used for provider-side upload caching.
+    /// Never sent to providers.
+    #[serde(skip)]
+    pub content_hash: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -126,7 +133,7 @@ pub struct ToolChoiceFunction {
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LLMChatRequest {
     pub model: String,
     pub messages: Vec<ChatMessage>,
@@ -134,6 +141,12 @@ pub struct LLMChatRequest {
     pub temperature: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub frequency_penalty: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub presence_penalty: Option<f64>,
     pub stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tools: Option<Vec<ChatCompletionTool>>,
@@ -147,6 +160,25 @@ pub struct LLMChatRequest {
     pub response_modalities: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_config: Option<ImageConfig>,
+    /// Workspace whose stable prefix may be cached server-side (Gemini context caching).
+    /// None when the workspace has not opted in. Never sent to providers.
+    #[serde(skip)]
+    pub context_cache_scope: Option<String>,
+    /// Allow and block lists of the connection, checked before sending.
+    /// Never sent to providers.
+    #[serde(skip)]
+    pub model_filter: Option<ModelFilter>,
+    /// Provider options of the connection. Never sent to providers.
+    #[serde(skip)]
+    pub connection_config: Option<ConnectionConfig>,
+    /// Sampling seed for reproducible answers; providers without one never
+    /// get it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Caps on the streamed answer, the defaults when None. Never sent to
+    /// providers.
+    #[serde(skip)]
+    pub output_limits: Option<OutputLimits>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -165,6 +197,12 @@ pub struct TokenUsage {
     pub completion_tokens: Option<u32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub total_tokens: Option<u32>,
+    /// Prompt tokens served from a provider-side context cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cached_tokens: Option<u32>,
+    /// Prompt tokens written to a provider-side prompt cache
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_write_tokens: Option<u32>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -180,6 +218,16 @@ pub struct LLMChatResponse {
     pub reasoning: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub images: Option<Vec<InlineData>>,
+    /// Request parameters the provider dropped because the model does not
+    /// support them, e.g. "temperature ignored: not supported by o3-mini"
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub parameter_notes: Vec<String>,
+    /// The model snapshot that answered, when the provider names it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// The backend configuration that answered (OpenAI's `system_fingerprint`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -190,6 +238,8 @@ pub struct SSEChunk {
     pub model: Option<String>,
     pub choices: Option<Vec<SSEChoice>>,
     pub usage: Option<TokenUsage>,
+    #[serde(default)]
+    pub system_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -0,0 +1,296 @@
+//! Repair for tool-call arguments that were cut off mid-stream.
+//!
+//! The repairer only appends what is missing (a closing quote, closing
+//! brackets) and drops a dangling comma. It never invents values: a cut after
+//! a key, after a colon or inside a `true`/`false`/`null` literal fails, so
+//! the call is refused instead of running with guessed arguments.
+
+use crate::models::llm_types::{LLMChatResponse, ToolCall};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Arguments longer than this are not repaired.
+const MAX_REPAIR_BYTES: usize = 256 * 1024;
+/// Deeper nesting than this is not repaired.
+const MAX_REPAIR_DEPTH: usize = 64;
+
+/// Close a truncated JSON object or array so it parses. Returns `None` when
+/// the text is not a truncated object/array or cannot be closed safely.
+pub fn repair_json(input: &str) -> Option<String> {
+    let input = input.trim();
+    if input.len() > MAX_REPAIR_BYTES || !input.starts_with(['{', '[']) {
+        return None;
+    }
+
+    let mut stack = Vec::new();
+    let mut in_string = false;
+    // Byte offset of an escape sequence that is not finished yet
+    let mut pending_escape: Option<usize> = None;
+    let mut unicode_digits = 0;
+
+    for (i, c) in input.char_indices() {
+        if in_string {
+            if unicode_digits > 0 {
+                if !c.is_ascii_hexdigit() {
+                    return None;
+                }
+                unicode_digits -= 1;
+                if unicode_digits == 0 {
+                    pending_escape = None;
+                }
+            } else if pending_escape.is_some() {
+                if c == 'u' {
+                    unicode_digits = 4;
+                } else {
+                    pending_escape = None;
+                }
+            } else if c == '\\' {
+                pending_escape = Some(i);
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => stack.push('}'),
+            '[' => stack.push(']'),
+            '}' | ']' => {
+                let expected = stack.pop();
+                if expected != Some(c) {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+        if stack.len() > MAX_REPAIR_DEPTH {
+            return None;
+        }
+    }
+
+    if stack.is_empty() {
+        // Complete already; if it does not parse, it is not a truncation
+        return None;
+    }
+
+    let mut repaired = input.to_string();
+    if in_string {
+        // Drop a half-written escape such as `\` or `\u00`
+        if let Some(start) = pending_escape {
+            repaired.truncate(start);
+        }
+        repaired.push('"');
+    }
+
+    let trimmed_len = repaired.trim_end().len();
+    repaired.truncate(trimmed_len);
+    if repaired.ends_with(',') {
+        repaired.pop();
+    }
+    repaired.extend(stack.iter().rev());
+
+    serde_json::from_str::<Value>(&repaired)
+        .ok()
+        .map(|_| repaired)
+}
+
+/// Whether a finish reason means the model hit its output token limit.
+pub fn is_truncated_finish(finish_reason: Option<&str>) -> bool {
+    matches!(finish_reason, Some("length" | "max_tokens" | "MAX_TOKENS"))
+}
+
+/// Outcome of checking one tool call's arguments.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ArgumentsCheck {
+    Valid,
+    Repaired(String),
+    /// Not executable: repair failed, or the response hit the token limit
+    Unusable,
+}
+
+/// Check raw tool-call arguments. When the response was cut off by the
+/// token limit, repaired arguments are not trusted either.
+pub fn check_arguments(raw: &str, truncated: bool) -> ArgumentsCheck {
+    let raw = raw.trim();
+    if raw.is_empty() || serde_json::from_str::<Value>(raw).is_ok() {
+        return ArgumentsCheck::Valid;
+    }
+    if truncated {
+        return ArgumentsCheck::Unusable;
+    }
+    repair_json(raw).map_or(ArgumentsCheck::Unusable, ArgumentsCheck::Repaired)
+}
+
+/// How many tool calls of a response needed repair, stored in message
+/// metadata as `argumentRepair`.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct RepairCounts {
+    pub repaired: u32,
+    pub failed: u32,
+}
+
+impl RepairCounts {
+    pub const fn is_empty(self) -> bool {
+        self.repaired == 0 && self.failed == 0
+    }
+}
+
+/// Repair the tool-call arguments of a response in place. Unusable calls keep
+/// their raw text, which `has_valid_arguments` rejects.
+pub fn repair_tool_calls(response: &mut LLMChatResponse) -> RepairCounts {
+    let truncated = is_truncated_finish(response.finish_reason.as_deref());
+    let mut counts = RepairCounts::default();
+    for tool_call in response.tool_calls.iter_mut().flatten() {
+        match check_arguments(&tool_call.function.arguments, truncated) {
+            ArgumentsCheck::Valid => {}
+            ArgumentsCheck::Repaired(arguments) => {
+                tracing::warn!(tool = %tool_call.function.name, "Repaired truncated tool arguments");
+                tool_call.function.arguments = arguments;
+                counts.repaired += 1;
+            }
+            ArgumentsCheck::Unusable => {
+                tracing::warn!(tool = %tool_call.function.name, "Tool arguments are truncated");
+                counts.failed += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Whether a tool call's arguments are empty or parse as JSON.
+pub fn has_valid_arguments(tool_call: &ToolCall) -> bool {
+    let raw = tool_call.function.arguments.trim();
+    raw.is_empty() || serde_json::from_str::<Value>(raw).is_ok()
+}
+
+/// Arguments as JSON for display, repairing a truncated tail when possible.
+pub fn arguments_value(raw: &str) -> Value {
+    serde_json::from_str(raw)
+        .ok()
+        .or_else(|| repair_json(raw).and_then(|r| serde_json::from_str(&r).ok()))
+        .unwrap_or_else(|| serde_json::json!({}))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::tool_call;
+
+    #[test]
+    fn json_repair_closes_truncated_arguments() {
+        use crate::services::llm::json_repair::{check_arguments, ArgumentsCheck};
+
+        for (truncated, expected) in [
+            // Cut inside a string
+            (
+                r#"{"path": "/tmp/notes"#,
+                serde_json::json!({"path": "/tmp/notes"}),
+            ),
+            (r#"{"text": "line\"#, serde_json::json!({"text": "line"})),
+            (r#"{"text": "caf\u00"#, serde_json::json!({"text": "caf"})),
+            // Cut inside a nested object or array
+            (
+                r#"{"query": {"filter": {"tag": "a", "limit": 5"#,
+                serde_json::json!({"query": {"filter": {"tag": "a", "limit": 5}}}),
+            ),
+            (r#"{"ids": [1, 2, 3"#, serde_json::json!({"ids": [1, 2, 3]})),
+            // Cut after a comma
+            (
+                r#"{"a": 1, "b": [true, "x"],"#,
+                serde_json::json!({"a": 1, "b": [true, "x"]}),
+            ),
+            (r#"{"ids": [1, 2,  "#, serde_json::json!({"ids": [1, 2]})),
+        ] {
+            let ArgumentsCheck::Repaired(repaired) = check_arguments(truncated, false) else {
+                panic!("{truncated} was not repaired");
+            };
+            let value: serde_json::Value = serde_json::from_str(&repaired).unwrap();
+            assert_eq!(value, expected, "{truncated}");
+        }
+    }
+
+    #[test]
+    fn json_repair_refuses_what_it_cannot_close() {
+        use crate::services::llm::json_repair::{check_arguments, ArgumentsCheck};
+
+        for truncated in [
+            // A key without a value, or a value cut mid-literal
+            r#"{"a": 1, "b""#,
+            r#"{"a": 1, "b":"#,
+            r#"{"flag": tru"#,
+            r#"{"n": 1."#,
+            // Not JSON to begin with
+            "path=/tmp",
+            r#"{"a": 1}}"#,
+        ] {
+            assert_eq!(
+                check_arguments(truncated, false),
+                ArgumentsCheck::Unusable,
+                "{truncated}"
+            );
+        }
+
+        // At the token limit even a repairable cut is not executed
+        assert_eq!(
+            check_arguments(r#"{"path": "/tmp/notes"#, true),
+            ArgumentsCheck::Unusable
+        );
+        assert_eq!(
+            check_arguments(r#"{"path": "/tmp"}"#, true),
+            ArgumentsCheck::Valid
+        );
+        assert_eq!(check_arguments("  ", false), ArgumentsCheck::Valid);
+    }
+
+    #[test]
+    fn json_repair_skips_unusable_calls_in_a_response() {
+        use crate::models::llm_types::LLMChatResponse;
+        use crate::services::llm::json_repair::{has_valid_arguments, repair_tool_calls};
+
+        let mut cut = tool_call("2", "write_file");
+        cut.function.arguments = r#"{"path": "a.txt", "content": "hel"#.to_string();
+        let mut dangling = tool_call("3", "write_file");
+        dangling.function.arguments = r#"{"path": "b.txt", "content""#.to_string();
+        let mut response = LLMChatResponse {
+            content: String::new(),
+            finish_reason: Some("tool_calls".to_string()),
+            tool_calls: Some(vec![tool_call("1", "read_file"), cut, dangling]),
+            usage: None,
+            reasoning: None,
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        };
+
+        let counts = repair_tool_calls(&mut response);
+        assert_eq!((counts.repaired, counts.failed), (1, 1));
+        let calls = response.tool_calls.unwrap();
+        assert_eq!(
+            calls[1].function.arguments,
+            r#"{"path": "a.txt", "content": "hel"}"#
+        );
+        assert_eq!(
+            calls.iter().map(has_valid_arguments).collect::<Vec<_>>(),
+            [true, true, false]
+        );
+
+        // Cut off by the token limit: nothing is repaired
+        let mut cut = tool_call("4", "write_file");
+        cut.function.arguments = r#"{"path": "a.txt""#.to_string();
+        let mut response = LLMChatResponse {
+            content: String::new(),
+            finish_reason: Some("length".to_string()),
+            tool_calls: Some(vec![cut]),
+            usage: None,
+            reasoning: None,
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        };
+        let counts = repair_tool_calls(&mut response);
+        assert_eq!((counts.repaired, counts.failed), (0, 1));
+        assert!(!has_valid_arguments(&response.tool_calls.unwrap()[0]));
+    }
+}
@@ -1,16 +1,44 @@
+pub mod json_repair;
+pub mod output_limit;
 pub mod providers;
+pub mod rate_limit;
+pub mod reproducibility;
+pub mod stream_recovery;
+pub mod stream_stats;
 
 use crate::error::AppError;
+use crate::features::llm_cache::{LLMCacheService, LLM_CACHE_ENABLED};
 use crate::models::llm_types::{LLMChatRequest, LLMChatResponse, LLMModel};
+use providers::ollama::{self, OllamaModelInfo, OllamaPullProgress};
 use providers::{
-    AnthropicProvider, GoogleProvider, LLMProvider, OpenAICompatProvider, OpenAIProvider,
+    account_key, AnthropicProvider, AzureOpenAIProvider, BedrockProvider, GoogleProvider,
+    LLMProvider, OllamaProvider, OpenAICompatProvider, OpenAIProvider,
 };
+use rate_limit::RateLimitStatus;
 use reqwest::Client;
-use std::sync::Arc;
-use tauri::AppHandle;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tauri::{AppHandle, Manager};
+
+/// Minimum time between two warm-up requests for the same connection and model.
+pub const WARMUP_MIN_INTERVAL_MS: i64 = 5 * 60 * 1000;
+
+/// Providers that serve models from the user's own machine or network and
+/// unload them when idle. Hosted APIs keep their models loaded.
+const WARMUP_PROVIDERS: &[&str] = &["ollama", "vllm", "lmstudio", "localai"];
+
+/// How long Ollama keeps a warmed model loaded before unloading it again.
+const OLLAMA_KEEP_ALIVE: &str = "15m";
 
 pub struct LLMService {
     client: Arc<Client>,
+    // Last warm-up time (ms) per "connection_id|model"
+    warmups: Mutex<HashMap<String, i64>>,
+    // Bumped whenever a workspace becomes active; older keep-warm loops stop
+    keep_warm_generation: AtomicU64,
+    // Last rate limit per provider account, cleared by a successful chat
+    rate_limits: Mutex<HashMap<String, RateLimitStatus>>,
 }
 
 impl LLMService {
@@ -22,6 +50,9 @@ impl LLMService {
 
         Self {
             client: Arc::new(client),
+            warmups: Mutex::new(HashMap::new()),
+            keep_warm_generation: AtomicU64::new(0),
+            rate_limits: Mutex::new(HashMap::new()),
         }
     }
 
@@ -30,9 +61,12 @@ impl LLMService {
             "openai" => Box::new(OpenAIProvider::new(self.client.clone())),
             "google" | "gemini" => Box::new(GoogleProvider::new(self.client.clone())),
             "anthropic" | "claude" => Box::new(AnthropicProvider::new(self.client.clone())),
+            "azure" | "azure-openai" => Box::new(AzureOpenAIProvider::new(self.client.clone())),
+            "bedrock" | "aws-bedrock" => Box::new(BedrockProvider::new(self.client.clone())),
+            "ollama" => Box::new(OllamaProvider::new(self.client.clone())),
             // Explicitly map known OpenAI-compatible providers for clarity
-            "deepseek" | "groq" | "ollama" | "perplexity" | "mistral" | "openrouter"
-            | "lmstudio" | "localai" => Box::new(OpenAICompatProvider::new(self.client.clone())),
+            "deepseek" | "groq" | "perplexity" | "mistral" | "openrouter" | "lmstudio"
+            | "localai" => Box::new(OpenAICompatProvider::new(self.client.clone())),
             // Default fallback to OpenAI compatible for any custom/unknown provider
             _ => Box::new(OpenAICompatProvider::new(self.client.clone())),
         }
@@ -50,6 +84,70 @@ impl LLMService {
         provider_impl.fetch_models(base_url, api_key).await
     }
 
+    /// Embed `inputs` with an embeddings model of the provider.
+    pub async fn embed(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        provider: &str,
+        model: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        self.get_provider(provider)
+            .embed(base_url, api_key, model, inputs)
+            .await
+    }
+
+    /// Check an Azure OpenAI deployment answers, describing it as a model.
+    pub async fn check_azure_deployment(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        deployment: &str,
+        api_version: Option<&str>,
+    ) -> Result<LLMModel, AppError> {
+        AzureOpenAIProvider::new(self.client.clone())
+            .check_deployment(base_url, api_key, deployment, api_version)
+            .await
+    }
+
+    /// Text models of an AWS region, listed with the keys of a credential
+    /// profile, or of the environment when none is named.
+    pub async fn fetch_bedrock_models(
+        &self,
+        region: &str,
+        profile: Option<&str>,
+    ) -> Result<Vec<LLMModel>, AppError> {
+        BedrockProvider::new(self.client.clone())
+            .list_models(region, profile)
+            .await
+    }
+
+    /// Capabilities and details of a model on an Ollama server.
+    pub async fn ollama_model_info(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+    ) -> Result<OllamaModelInfo, AppError> {
+        OllamaProvider::new(self.client.clone())
+            .show(base_url, api_key, model)
+            .await
+    }
+
+    /// Download a model to an Ollama server, reporting its progress.
+    pub async fn pull_ollama_model(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        on_progress: impl Fn(&OllamaPullProgress) + Send,
+    ) -> Result<(), AppError> {
+        OllamaProvider::new(self.client.clone())
+            .pull(base_url, api_key, model, on_progress)
+            .await
+    }
+
     pub async fn chat(
         &self,
         base_url: &str,
@@ -61,18 +159,230 @@ impl LLMService {
         cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
         provider: &str,
     ) -> Result<LLMChatResponse, AppError> {
+        if let Some(filter) = &request.model_filter {
+            filter.ensure_permitted(&request.model)?;
+        }
         let provider_impl = self.get_provider(provider);
-        provider_impl
+        // A stream that breaks off before anything arrived is sent once more
+        let retry = request
+            .stream
+            .then(|| stream_recovery::retry_request(provider, request.clone()));
+        let retry_rx = cancellation_rx
+            .as_ref()
+            .map(tokio::sync::broadcast::Receiver::resubscribe);
+        let mut result = provider_impl
             .chat(
                 base_url,
                 api_key,
                 request,
-                chat_id,
-                message_id,
-                app,
+                chat_id.clone(),
+                message_id.clone(),
+                app.clone(),
                 cancellation_rx,
             )
-            .await
+            .await;
+        if let Some(request) = retry {
+            if let Err(AppError::StreamInterrupted(error)) = &result {
+                tracing::warn!(%error, provider, "Stream broke off before any content, retrying");
+                result = provider_impl
+                    .chat(
+                        base_url, api_key, request, chat_id, message_id, app, retry_rx,
+                    )
+                    .await;
+            }
+        }
+        self.record_rate_limit(base_url, api_key, result.as_ref().err());
+        result
+    }
+
+    /// Remember the rate limit of a failed chat, or forget the account's
+    /// last one after a chat that was not refused.
+    fn record_rate_limit(&self, base_url: &str, api_key: Option<&str>, error: Option<&AppError>) {
+        let Ok(mut rate_limits) = self.rate_limits.lock() else {
+            return;
+        };
+        let key = account_key(base_url, api_key.unwrap_or_default());
+        let now_ms = chrono::Utc::now().timestamp_millis();
+        match error.and_then(|e| RateLimitStatus::from_error(e, now_ms)) {
+            Some(status) => {
+                rate_limits.insert(key, status);
+            }
+            None if error.is_none() => {
+                rate_limits.remove(&key);
+            }
+            None => {}
+        }
+    }
+
+    /// The last rate limit the account of `base_url` and `api_key` ran into,
+    /// if no chat has succeeded since.
+    pub fn rate_limit_status(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+    ) -> Option<RateLimitStatus> {
+        let key = account_key(base_url, api_key.unwrap_or_default());
+        let status = self.rate_limits.lock().ok()?.get(&key).cloned()?;
+        Some(status.at(chrono::Utc::now().timestamp_millis()))
+    }
+
+    /// Like `chat`, but serves repeated deterministic utility calls (title
+    /// generation) from the local response cache once the `llmCacheEnabled`
+    /// setting is on. Returns the response and whether it came from the cache.
+    ///
+    /// Streaming requests and temperatures above 0.3 bypass the cache entirely.
+    /// The chat path must keep calling `chat` directly.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn chat_cached(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        request: LLMChatRequest,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        provider: &str,
+    ) -> Result<(LLMChatResponse, bool), AppError> {
+        let cache = app
+            .try_state::<crate::state::AppState>()
+            .filter(|state| {
+                state
+                    .app_settings_service
+                    .get_by_key(LLM_CACHE_ENABLED)
+                    .ok()
+                    .flatten()
+                    .is_some_and(|value| value == "true")
+            })
+            .map(|state| state.llm_cache_service.clone())
+            .filter(|_| LLMCacheService::is_cacheable(&request));
+        let cache_key = match &cache {
+            Some(_) => Some(LLMCacheService::cache_key(provider, base_url, &request)?),
+            None => None,
+        };
+
+        if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+            match cache.get(key) {
+                Ok(Some(mut response)) => {
+                    // Nothing was sent, so nothing was spent
+                    response.usage = None;
+                    return Ok((response, true));
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = %e, "Failed to read LLM response cache"),
+            }
+        }
+
+        let model = request.model.clone();
+        let response = self
+            .chat(
+                base_url, api_key, request, chat_id, message_id, app, None, provider,
+            )
+            .await?;
+
+        if let (Some(cache), Some(key)) = (&cache, &cache_key) {
+            if let Err(e) = cache.put(key, provider, &model, &response) {
+                tracing::warn!(error = %e, "Failed to write LLM response cache");
+            }
+        }
+
+        Ok((response, false))
+    }
+
+    /// Whether a provider serves models that can be warmed up ahead of use.
+    /// Only the local servers in `WARMUP_PROVIDERS` qualify; an unknown
+    /// provider is not sent warm-up requests.
+    pub fn supports_warmup(provider: &str) -> bool {
+        WARMUP_PROVIDERS
+            .iter()
+            .any(|p| provider.eq_ignore_ascii_case(p))
+    }
+
+    /// Claim the warm-up slot for a connection and model. Returns false when the
+    /// pair was warmed less than `WARMUP_MIN_INTERVAL_MS` before `now_ms`.
+    pub fn try_claim_warmup(&self, connection_id: &str, model: &str, now_ms: i64) -> bool {
+        let Ok(mut warmups) = self.warmups.lock() else {
+            return false;
+        };
+        let key = format!("{connection_id}|{model}");
+        if warmups
+            .get(&key)
+            .is_some_and(|last| now_ms - last < WARMUP_MIN_INTERVAL_MS)
+        {
+            return false;
+        }
+        warmups.insert(key, now_ms);
+        true
+    }
+
+    /// Start a new keep-warm period, ending any previous one. Returns its generation.
+    pub fn begin_keep_warm(&self) -> u64 {
+        self.keep_warm_generation.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    /// Whether the keep-warm period with this generation is still the current one.
+    pub fn is_keep_warm_current(&self, generation: u64) -> bool {
+        self.keep_warm_generation.load(Ordering::SeqCst) == generation
+    }
+
+    /// Load a model into memory without producing any chat output. Ollama gets an
+    /// empty generate call with an explicit `keep_alive`; other servers get a
+    /// one-token completion. Goes straight to HTTP so no chat events are emitted.
+    pub async fn warm_up(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        provider: &str,
+        model: &str,
+    ) -> Result<(), AppError> {
+        let base_url = base_url.trim_end_matches('/');
+        let request = if provider.eq_ignore_ascii_case("ollama") {
+            let root = ollama::native_root(base_url);
+            self.client
+                .post(format!("{root}/api/generate"))
+                .json(&serde_json::json!({ "model": model, "keep_alive": OLLAMA_KEEP_ALIVE }))
+        } else {
+            self.client
+                .post(format!("{base_url}/chat/completions"))
+                .json(&serde_json::json!({
+                    "model": model,
+                    "messages": [{ "role": "user", "content": "hi" }],
+                    "max_tokens": 1,
+                    "stream": false,
+                }))
+        };
+        let request = match api_key {
+            Some(key) if !key.is_empty() => {
+                request.header("Authorization", format!("Bearer {key}"))
+            }
+            _ => request,
+        };
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(AppError::Llm(format!(
+                "Warm-up request failed ({})",
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Warm up a model in the background. A failure is only logged: it comes
+    /// back as the usage status "error", never as an error for the user.
+    pub async fn warm_up_status(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        provider: &str,
+        model: &str,
+    ) -> &'static str {
+        match self.warm_up(base_url, api_key, provider, model).await {
+            Ok(()) => "success",
+            Err(e) => {
+                tracing::warn!(model = %model, error = %e, "Model warm-up failed");
+                "error"
+            }
+        }
     }
 }
 
@@ -81,3 +391,75 @@ impl Default for LLMService {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_json_endpoint;
+
+    #[test]
+    fn warmups_go_only_to_local_servers_and_are_rate_limited() {
+        use crate::services::llm::{LLMService, WARMUP_MIN_INTERVAL_MS};
+
+        for provider in ["ollama", "Ollama", "vllm", "lmstudio", "localai"] {
+            assert!(LLMService::supports_warmup(provider), "{provider}");
+        }
+        // Hosted APIs, and providers nobody listed, are never pinged
+        for provider in [
+            "openai",
+            "anthropic",
+            "google",
+            "azure",
+            "bedrock",
+            "groq",
+            "acme",
+        ] {
+            assert!(!LLMService::supports_warmup(provider), "{provider}");
+        }
+
+        let llm = LLMService::new();
+        assert!(llm.try_claim_warmup("conn", "llama3", 1_000));
+        assert!(!llm.try_claim_warmup("conn", "llama3", 1_000 + WARMUP_MIN_INTERVAL_MS - 1));
+        // The limit is per connection and model
+        assert!(llm.try_claim_warmup("conn", "qwen", 2_000));
+        assert!(llm.try_claim_warmup("other", "llama3", 2_000));
+        // A refused claim does not push the window back
+        assert!(llm.try_claim_warmup("conn", "llama3", 1_000 + WARMUP_MIN_INTERVAL_MS));
+    }
+
+    #[test]
+    fn warmup_failures_are_only_logged() {
+        use crate::services::llm::LLMService;
+
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let llm = LLMService::new();
+            let (url, received) = mock_json_endpoint(vec![
+                (503, serde_json::json!({ "error": "model is loading" })),
+                (200, serde_json::json!({ "choices": [] })),
+            ])
+            .await;
+
+            let status = llm.warm_up_status(&url, None, "vllm", "llama3").await;
+            assert_eq!(status, "error");
+            let status = llm.warm_up_status(&url, None, "vllm", "llama3").await;
+            assert_eq!(status, "success");
+            let status = llm.warm_up_status(&url, None, "ollama", "llama3").await;
+            assert_eq!(status, "success");
+            assert_eq!(
+                *received.lock().unwrap(),
+                [
+                    "POST /chat/completions",
+                    "POST /chat/completions",
+                    "POST /api/generate"
+                ]
+            );
+
+            // A server that is not running is a failed warm-up too
+            let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", closed.local_addr().unwrap());
+            drop(closed);
+            let status = llm.warm_up_status(&url, None, "ollama", "llama3").await;
+            assert_eq!(status, "error");
+        });
+    }
+}
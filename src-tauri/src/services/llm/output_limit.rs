@@ -0,0 +1,381 @@
+//! Caps on how much of a streamed answer is kept.
+//!
+//! A misbehaving server can stream without end, a looping model repeating
+//! one token forever. Every streaming provider reads through an
+//! [`OutputLimiter`]: once the content, the reasoning or the arguments of
+//! one tool call reach their cap, the stream is read no further and dropped,
+//! which closes the connection so the server stops generating. The answer
+//! keeps what arrived up to the cap and finishes as `length_limit`.
+
+use crate::error::AppError;
+use crate::models::llm_types::ToolCall;
+
+/// Finish reason of an answer cut off at an output cap.
+pub const LENGTH_LIMIT: &str = "length_limit";
+
+/// Metadata key of a message cut off at an output cap.
+pub const OUTPUT_LIMIT_METADATA_KEY: &str = "outputLimitReached";
+
+/// Content cap when the workspace sets none.
+pub const DEFAULT_CONTENT_BYTES: usize = 2 * 1024 * 1024;
+
+/// Reasoning cap when the workspace sets none.
+pub const DEFAULT_REASONING_BYTES: usize = 1024 * 1024;
+
+/// Cap on the arguments of one tool call when the workspace sets none.
+pub const DEFAULT_TOOL_ARGUMENTS_BYTES: usize = 256 * 1024;
+
+/// How many times the content cap one unparsed line of the stream may
+/// take, leaving room for JSON escaping and inline images.
+const LINE_CAP_FACTOR: usize = 4;
+
+/// What part of an answer reached its cap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputKind {
+    Content,
+    Reasoning,
+    ToolArguments,
+}
+
+impl OutputKind {
+    pub const fn as_str(self) -> &'static str {
+        match self {
+            Self::Content => "content",
+            Self::Reasoning => "reasoning",
+            Self::ToolArguments => "tool_arguments",
+        }
+    }
+}
+
+/// Byte caps of one streamed answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OutputLimits {
+    pub content_bytes: usize,
+    pub reasoning_bytes: usize,
+    pub tool_arguments_bytes: usize,
+}
+
+impl Default for OutputLimits {
+    fn default() -> Self {
+        Self {
+            content_bytes: DEFAULT_CONTENT_BYTES,
+            reasoning_bytes: DEFAULT_REASONING_BYTES,
+            tool_arguments_bytes: DEFAULT_TOOL_ARGUMENTS_BYTES,
+        }
+    }
+}
+
+impl OutputLimits {
+    /// The caps of a workspace that sets `max_output_bytes` of content or
+    /// not. Reasoning and tool arguments keep the default proportions: half
+    /// and an eighth of the content cap.
+    pub fn for_workspace(max_output_bytes: Option<i64>) -> Self {
+        match max_output_bytes
+            .filter(|bytes| *bytes > 0)
+            .and_then(|bytes| usize::try_from(bytes).ok())
+        {
+            Some(content_bytes) => Self {
+                content_bytes,
+                reasoning_bytes: (content_bytes / 2).max(1),
+                tool_arguments_bytes: (content_bytes / 8).max(1),
+            },
+            None => Self::default(),
+        }
+    }
+
+    pub const fn limit(&self, kind: OutputKind) -> usize {
+        match kind {
+            OutputKind::Content => self.content_bytes,
+            OutputKind::Reasoning => self.reasoning_bytes,
+            OutputKind::ToolArguments => self.tool_arguments_bytes,
+        }
+    }
+}
+
+/// Keeps one streamed answer within its caps.
+#[derive(Debug)]
+pub struct OutputLimiter {
+    limits: OutputLimits,
+    reached: Option<OutputKind>,
+}
+
+impl OutputLimiter {
+    /// A limiter with the request's caps, or the default ones.
+    pub fn new(limits: Option<OutputLimits>) -> Self {
+        Self {
+            limits: limits.unwrap_or_default(),
+            reached: None,
+        }
+    }
+
+    /// The part of `text` that may be appended to `kept` bytes of `kind`,
+    /// cut at a char boundary. Nothing once any cap was reached.
+    pub fn admit<'t>(&mut self, kind: OutputKind, kept: usize, text: &'t str) -> &'t str {
+        if self.reached.is_some() {
+            return "";
+        }
+        let room = self.limits.limit(kind).saturating_sub(kept);
+        if text.len() <= room {
+            return text;
+        }
+        self.reached = Some(kind);
+        let mut end = room;
+        while !text.is_char_boundary(end) {
+            end -= 1;
+        }
+        &text[..end]
+    }
+
+    /// Whether to stop reading: a cap was reached, or the unparsed `buffer`
+    /// holds a single line far longer than the content cap.
+    pub fn should_stop(&mut self, buffer: &str) -> bool {
+        let line_cap = self.limits.content_bytes.saturating_mul(LINE_CAP_FACTOR);
+        if self.reached.is_none() && buffer.len() > line_cap {
+            self.reached = Some(OutputKind::Content);
+        }
+        self.reached.is_some()
+    }
+
+    /// The part of the answer that reached its cap, if any did.
+    pub const fn reached(&self) -> Option<OutputKind> {
+        self.reached
+    }
+
+    pub const fn limit(&self, kind: OutputKind) -> usize {
+        self.limits.limit(kind)
+    }
+
+    /// Ends the read of an answer over its cap, and tells whether it was.
+    /// The answer keeps what fits but not its tool calls; dropping `stream`
+    /// closes the connection so the server stops generating. `notify` gets
+    /// the cap that was reached and its size.
+    pub fn end_at_cap<S>(
+        &self,
+        stream: S,
+        tool_calls: &mut Vec<ToolCall>,
+        notify: impl FnOnce(OutputKind, usize) -> Result<(), AppError>,
+    ) -> Result<bool, AppError> {
+        let Some(kind) = self.reached else {
+            return Ok(false);
+        };
+        drop(stream);
+        tool_calls.clear();
+        notify(kind, self.limit(kind))?;
+        Ok(true)
+    }
+}
+
+/// What a message's metadata keeps of an answer cut off at an output cap.
+/// None when the answer finished within its caps.
+pub fn limit_metadata(finish_reason: Option<&str>) -> Option<serde_json::Value> {
+    (finish_reason == Some(LENGTH_LIMIT)).then(|| serde_json::json!(true))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::guarded_message_service;
+
+    /// Set once the transport it travels with is dropped, which closes the
+    /// connection.
+    struct ClosedOnDrop(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+    impl Drop for ClosedOnDrop {
+        fn drop(&mut self) {
+            self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+        }
+    }
+
+    /// What the read loop of the providers made of an endless stream.
+    struct EndlessRead {
+        content: String,
+        reached: Option<crate::services::llm::output_limit::OutputKind>,
+        elapsed: std::time::Duration,
+        // The transport was dropped, closing the connection
+        closed: bool,
+        // Bytes the server got to send
+        sent: usize,
+    }
+
+    /// Read a server that sends `chunk` forever, as a model stuck repeating
+    /// itself, the way the providers read their streams.
+    fn read_endless_stream(
+        chunk: &'static str,
+        limits: Option<crate::services::llm::output_limit::OutputLimits>,
+    ) -> EndlessRead {
+        use crate::services::llm::output_limit::{OutputKind, OutputLimiter};
+        use crate::services::llm::stream_recovery::GuardedStream;
+        use futures::StreamExt;
+        use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let closed = Arc::new(AtomicBool::new(false));
+        let sent = Arc::new(AtomicUsize::new(0));
+        let guard = ClosedOnDrop(closed.clone());
+        let counter = sent.clone();
+        let transport = futures::stream::repeat(chunk).map(move |chunk| {
+            let _ = &guard;
+            counter.fetch_add(chunk.len(), Ordering::SeqCst);
+            Ok::<_, std::io::Error>(chunk.as_bytes().to_vec())
+        });
+
+        let started = std::time::Instant::now();
+        let (content, reached) = tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let mut stream = GuardedStream::new(transport);
+            let mut limiter = OutputLimiter::new(limits);
+            let mut content = String::new();
+            let mut buffer = String::new();
+            while let Some(chunk) = stream.next().await {
+                buffer.push_str(&String::from_utf8_lossy(&chunk));
+                while let Some(end) = buffer.find("\n\n") {
+                    let event = buffer[..end].to_string();
+                    buffer = buffer[end + 2..].to_string();
+                    if let Some(text) = event.strip_prefix("data: ") {
+                        let text = limiter.admit(OutputKind::Content, content.len(), text);
+                        content.push_str(text);
+                    }
+                }
+                if limiter.should_stop(&buffer) {
+                    break;
+                }
+            }
+            drop(stream);
+            (content, limiter.reached())
+        });
+        EndlessRead {
+            content,
+            reached,
+            elapsed: started.elapsed(),
+            closed: closed.load(Ordering::SeqCst),
+            sent: sent.load(Ordering::SeqCst),
+        }
+    }
+
+    #[test]
+    fn endless_streams_stop_at_the_output_caps() {
+        use crate::services::llm::output_limit::{OutputKind, OutputLimits, DEFAULT_CONTENT_BYTES};
+        use std::time::Duration;
+
+        // A looping model: the answer stops at the default content cap
+        let read = read_endless_stream("data: loop loop loop loop\n\n", None);
+        assert_eq!(read.content.len(), DEFAULT_CONTENT_BYTES);
+        assert!(read.content.starts_with("loop loop"));
+        assert_eq!(read.reached, Some(OutputKind::Content));
+        assert!(read.closed, "the connection is dropped");
+        assert!(
+            read.elapsed < Duration::from_secs(10),
+            "took {:?}",
+            read.elapsed
+        );
+        // Read no further than the cap plus the event framing
+        assert!(
+            read.sent < DEFAULT_CONTENT_BYTES * 2,
+            "read {} bytes",
+            read.sent
+        );
+
+        // One line without end never becomes an event; the unparsed buffer
+        // stops at a few times the content cap
+        let limits = OutputLimits::for_workspace(Some(1024));
+        let read = read_endless_stream("data: xxxxxxxxxxxxxxxx", Some(limits));
+        assert!(read.content.is_empty());
+        assert_eq!(read.reached, Some(OutputKind::Content));
+        assert!(read.closed);
+        assert!(read.sent <= 4 * 1024 + 32, "read {} bytes", read.sent);
+    }
+
+    #[test]
+    fn capped_answers_are_cut_at_char_boundaries_and_kept_as_such() {
+        use crate::services::llm::output_limit::{
+            limit_metadata, OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT,
+            OUTPUT_LIMIT_METADATA_KEY,
+        };
+
+        assert_eq!(OutputLimits::for_workspace(None), OutputLimits::default());
+        assert_eq!(
+            OutputLimits::for_workspace(Some(0)),
+            OutputLimits::default()
+        );
+        let limits = OutputLimits::for_workspace(Some(8));
+        assert_eq!(
+            limits,
+            OutputLimits {
+                content_bytes: 8,
+                reasoning_bytes: 4,
+                tool_arguments_bytes: 1,
+            }
+        );
+
+        let mut limiter = OutputLimiter::new(Some(limits));
+        assert_eq!(limiter.admit(OutputKind::Reasoning, 0, "abc"), "abc");
+        assert_eq!(limiter.admit(OutputKind::Content, 0, "héllo"), "héllo");
+        assert_eq!(limiter.reached(), None);
+        // Two bytes left: "ö" takes two more after "w", so it stays out
+        assert_eq!(limiter.admit(OutputKind::Content, 6, "wörld"), "w");
+        assert_eq!(limiter.reached(), Some(OutputKind::Content));
+        assert_eq!(limiter.limit(OutputKind::Content), 8);
+        // Nothing more of any kind once a cap was hit
+        assert_eq!(limiter.admit(OutputKind::Reasoning, 3, "d"), "");
+        assert!(limiter.should_stop(""));
+
+        // The truncated answer is stored with its flag
+        let service = guarded_message_service();
+        service
+            .update("a1".to_string(), "héllow".to_string(), None, None)
+            .unwrap();
+        let mut metadata = serde_json::json!({});
+        if let Some(limit) = limit_metadata(Some(LENGTH_LIMIT)) {
+            metadata[OUTPUT_LIMIT_METADATA_KEY] = limit;
+        }
+        service
+            .update_metadata("a1".to_string(), Some(metadata.to_string()))
+            .unwrap();
+        let stored = service.get_by_id("a1").unwrap().unwrap();
+        assert_eq!(stored.content, "héllow");
+        let stored: serde_json::Value =
+            serde_json::from_str(stored.metadata.as_deref().unwrap()).unwrap();
+        assert_eq!(stored["outputLimitReached"], serde_json::json!(true));
+        assert_eq!(limit_metadata(Some("stop")), None);
+    }
+
+    #[test]
+    fn reads_within_their_caps_keep_their_tool_calls() {
+        use crate::services::llm::output_limit::{OutputLimiter, OutputLimits};
+        use crate::test_support::tool_call;
+
+        let limiter = OutputLimiter::new(Some(OutputLimits::for_workspace(Some(8))));
+        let mut tool_calls = vec![tool_call("c1", "read_file")];
+        let capped = limiter
+            .end_at_cap((), &mut tool_calls, |_, _| panic!("no cap was reached"))
+            .unwrap();
+        assert!(!capped);
+        assert_eq!(tool_calls.len(), 1);
+    }
+
+    #[test]
+    fn reads_over_a_cap_close_the_stream_drop_tool_calls_and_report_the_cap() {
+        use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits};
+        use crate::test_support::tool_call;
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::sync::Arc;
+
+        let mut limiter = OutputLimiter::new(Some(OutputLimits::for_workspace(Some(8))));
+        limiter.admit(OutputKind::ToolArguments, 0, "{}");
+        let closed = Arc::new(AtomicBool::new(false));
+        let mut tool_calls = vec![tool_call("c1", "read_file")];
+        let mut reported = None;
+        let capped = limiter
+            .end_at_cap(
+                ClosedOnDrop(closed.clone()),
+                &mut tool_calls,
+                |kind, limit| {
+                    reported = Some((kind, limit));
+                    Ok(())
+                },
+            )
+            .unwrap();
+        assert!(capped);
+        assert!(closed.load(Ordering::SeqCst));
+        assert!(tool_calls.is_empty());
+        assert_eq!(reported, Some((OutputKind::ToolArguments, 1)));
+    }
+}
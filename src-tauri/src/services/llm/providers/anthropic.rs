@@ -3,18 +3,37 @@ use crate::error::AppError;
 use crate::events::{
     MessageEmitter, TokenUsage as EventTokenUsage, ToolCall as EventToolCall, ToolEmitter,
 };
+use crate::features::llm_connection::config::{ThinkingBudgets, MIN_THINKING_BUDGET};
 use crate::models::llm_types::{
     AssistantContent, ChatMessage, ContentPart, LLMChatRequest, LLMChatResponse, LLMModel,
     TokenUsage, ToolCall, ToolCallFunction, ToolChoice, UserContent,
 };
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit;
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
 use async_trait::async_trait;
-use futures::StreamExt;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
+use std::collections::HashSet;
+use std::future::Future;
 use std::sync::Arc;
 use tauri::AppHandle;
 
+/// `anthropic-version` header sent with every request.
+pub const ANTHROPIC_VERSION: &str = "2023-06-01";
+/// Models requested per `/v1/models` page (the API maximum).
+const MODELS_PAGE_LIMIT: u32 = 1000;
+/// Pages read before giving up on a cursor that never ends.
+const MODELS_MAX_PAGES: usize = 10;
+/// `max_tokens` of a request that sets none.
+const DEFAULT_MAX_TOKENS: u32 = 4096;
+/// Answer tokens allowed beyond the thinking budget when no maximum is set.
+const THINKING_HEADROOM_TOKENS: u32 = 4096;
+/// Answer tokens kept free under a requested maximum.
+const MIN_ANSWER_TOKENS: u32 = 1024;
+
 pub struct AnthropicProvider {
     client: Arc<Client>,
 }
@@ -64,13 +83,17 @@ struct AnthropicMessage {
 }
 
 #[derive(Serialize, Debug)]
-struct AnthropicRequest {
+pub struct AnthropicRequest {
     model: String,
     messages: Vec<AnthropicMessage>,
     max_tokens: u32,
     stream: bool,
     #[serde(skip_serializing_if = "Option::is_none")]
-    system: Option<String>,
+    system: Option<AnthropicSystem>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f64>,
     #[serde(skip_serializing_if = "Option::is_none")]
     tools: Option<Vec<AnthropicTool>>,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -91,6 +114,35 @@ struct AnthropicTool {
     name: String,
     description: Option<String>,
     input_schema: Value,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cache_control: Option<AnthropicCacheControl>,
+}
+
+/// Marks the end of a prompt prefix for Anthropic's prompt cache.
+#[derive(Serialize, Debug, Clone, Copy)]
+struct AnthropicCacheControl {
+    #[serde(rename = "type")]
+    r#type: &'static str, // "ephemeral"
+}
+
+const EPHEMERAL_CACHE: AnthropicCacheControl = AnthropicCacheControl {
+    r#type: "ephemeral",
+};
+
+/// The system prompt: plain text, or a block carrying a cache breakpoint.
+#[derive(Serialize, Debug)]
+#[serde(untagged)]
+enum AnthropicSystem {
+    Text(String),
+    Blocks(Vec<AnthropicSystemBlock>),
+}
+
+#[derive(Serialize, Debug)]
+struct AnthropicSystemBlock {
+    #[serde(rename = "type")]
+    r#type: &'static str, // "text"
+    text: String,
+    cache_control: AnthropicCacheControl,
 }
 
 #[derive(Serialize, Debug)]
@@ -102,10 +154,38 @@ enum AnthropicToolChoice {
     Tool { name: String },
 }
 
-#[derive(Deserialize, Debug)]
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
 struct AnthropicUsage {
     input_tokens: u32,
     output_tokens: u32,
+    cache_read_input_tokens: u32,
+    cache_creation_input_tokens: u32,
+}
+
+impl AnthropicUsage {
+    /// Anthropic leaves cached tokens out of `input_tokens`; the prompt
+    /// tokens of the usage count them.
+    fn token_usage(&self) -> TokenUsage {
+        let prompt_tokens =
+            self.input_tokens + self.cache_read_input_tokens + self.cache_creation_input_tokens;
+        TokenUsage {
+            prompt_tokens: Some(prompt_tokens),
+            completion_tokens: Some(self.output_tokens),
+            total_tokens: Some(prompt_tokens + self.output_tokens),
+            cached_tokens: Some(self.cache_read_input_tokens).filter(|&n| n > 0),
+            cache_write_tokens: Some(self.cache_creation_input_tokens).filter(|&n| n > 0),
+        }
+    }
+
+    fn event_usage(&self) -> EventTokenUsage {
+        let usage = self.token_usage();
+        EventTokenUsage {
+            prompt_tokens: usage.prompt_tokens,
+            completion_tokens: usage.completion_tokens,
+            total_tokens: usage.total_tokens,
+        }
+    }
 }
 
 #[derive(Deserialize, Debug)]
@@ -120,6 +200,71 @@ impl AnthropicProvider {
         Self { client }
     }
 
+    /// Read `/v1/models` page by page with `fetch_page(after_id)` until the
+    /// API reports no more, keeping the first entry of each model id.
+    pub async fn collect_model_pages<F, Fut>(mut fetch_page: F) -> Result<Vec<LLMModel>, AppError>
+    where
+        F: FnMut(Option<String>) -> Fut,
+        Fut: Future<Output = Result<Value, AppError>>,
+    {
+        let mut models = Vec::new();
+        let mut seen = HashSet::new();
+        let mut after_id = None;
+        for page_number in 1..=MODELS_MAX_PAGES {
+            let page = fetch_page(after_id.take()).await?;
+            for model in Self::parse_models_page(&page) {
+                if seen.insert(model.id.clone()) {
+                    models.push(model);
+                }
+            }
+
+            let has_more = page.get("has_more").and_then(Value::as_bool) == Some(true);
+            let last_id = page.get("last_id").and_then(Value::as_str);
+            match last_id {
+                Some(last_id) if has_more => after_id = Some(last_id.to_string()),
+                _ => break,
+            }
+            if page_number == MODELS_MAX_PAGES {
+                tracing::warn!(
+                    pages = MODELS_MAX_PAGES,
+                    "Stopped reading Anthropic models after the page limit"
+                );
+            }
+        }
+        Ok(models)
+    }
+
+    /// Models of one `/v1/models` page.
+    pub fn parse_models_page(page: &Value) -> Vec<LLMModel> {
+        let Some(data) = page.get("data").and_then(Value::as_array) else {
+            return Vec::new();
+        };
+        data.iter()
+            .filter_map(|item| {
+                let id = item.get("id").and_then(Value::as_str)?;
+                let (supports_tools, supports_thinking, supports_image_generation) =
+                    Self::check_model_capabilities(id);
+                Some(LLMModel {
+                    id: id.to_string(),
+                    name: item
+                        .get("display_name")
+                        .and_then(Value::as_str)
+                        .unwrap_or(id)
+                        .to_string(),
+                    created: item
+                        .get("created_at")
+                        .and_then(Value::as_str)
+                        .and_then(|at| chrono::DateTime::parse_from_rfc3339(at).ok())
+                        .and_then(|at| u64::try_from(at.timestamp()).ok()),
+                    owned_by: Some("anthropic".to_string()),
+                    supports_tools,
+                    supports_thinking,
+                    supports_image_generation,
+                })
+            })
+            .collect()
+    }
+
     fn check_model_capabilities(model_id: &str) -> (bool, bool, bool) {
         let clean_id = model_id.split('/').next_back().unwrap_or(model_id);
         let model_lower = clean_id.to_lowercase();
@@ -132,6 +277,302 @@ impl AnthropicProvider {
         (supports_tools, supports_thinking, false)
     }
 
+    /// The Messages API request of a chat request.
+    pub fn build_request(request: LLMChatRequest) -> AnthropicRequest {
+        // Use helper to check capabilities for dynamic request construction
+        let (_, supports_thinking, _) = Self::check_model_capabilities(&request.model);
+        // Cache the system prompt and tools, which repeat on every turn
+        let caching = request.context_cache_scope.is_some()
+            || request
+                .connection_config
+                .as_ref()
+                .is_some_and(|config| config.prompt_caching);
+
+        // Convert messages
+        let mut messages = Vec::new();
+        let mut system_prompt = None;
+
+        for msg in request.messages {
+            match msg {
+                ChatMessage::System { content } => {
+                    if let Some(existing) = system_prompt {
+                        system_prompt = Some(format!("{existing}\n\n{content}"));
+                    } else {
+                        system_prompt = Some(content);
+                    }
+                }
+                ChatMessage::User { content } => {
+                    match content {
+                        UserContent::Text(text) => {
+                            messages.push(AnthropicMessage {
+                                role: "user".to_string(),
+                                content: AnthropicMessageContent::Text(text),
+                            });
+                        }
+                        UserContent::Parts(parts) => {
+                            let mut blocks = Vec::new();
+                            for part in parts {
+                                match part {
+                                    ContentPart::Text { text } => {
+                                        blocks.push(AnthropicContentBlock::Text { text });
+                                    }
+                                    ContentPart::FileUrl { file_url } => {
+                                        // Anthropic only supports images
+                                        // For non-image files, add a text placeholder
+                                        if file_url.mime_type.starts_with("image/") {
+                                            if let Some(comma_pos) = file_url.url.find(',') {
+                                                let data = &file_url.url[comma_pos + 1..];
+                                                let media_type = &file_url.mime_type;
+
+                                                blocks.push(AnthropicContentBlock::Image {
+                                                    source: AnthropicImageSource {
+                                                        r#type: "base64".to_string(),
+                                                        media_type: media_type.to_string(),
+                                                        data: data.to_string(),
+                                                    },
+                                                });
+                                            }
+                                        } else {
+                                            // Non-image files not supported by Anthropic
+                                            // Add a text placeholder
+                                            blocks.push(AnthropicContentBlock::Text {
+                                                text: format!(
+                                                    "[File attachment: {} - Not supported by this model]",
+                                                    file_url.mime_type
+                                                ),
+                                            });
+                                        }
+                                    }
+                                    ContentPart::ImageUrl { image_url } => {
+                                        // Parse data URL: data:image/jpeg;base64,...
+                                        if let Some(comma_pos) = image_url.url.find(',') {
+                                            let meta = &image_url.url[..comma_pos];
+                                            let data = &image_url.url[comma_pos + 1..];
+
+                                            // Extract mime type
+                                            let media_type = if meta.contains("image/png") {
+                                                "image/png"
+                                            } else if meta.contains("image/jpeg") {
+                                                "image/jpeg"
+                                            } else if meta.contains("image/webp") {
+                                                "image/webp"
+                                            } else if meta.contains("image/gif") {
+                                                "image/gif"
+                                            } else {
+                                                "image/jpeg" // Fallback
+                                            };
+
+                                            blocks.push(AnthropicContentBlock::Image {
+                                                source: AnthropicImageSource {
+                                                    r#type: "base64".to_string(),
+                                                    media_type: media_type.to_string(),
+                                                    data: data.to_string(),
+                                                },
+                                            });
+                                        }
+                                    }
+                                    ContentPart::InlineData { inline_data } => {
+                                        // Handle inline data (e.g., from Google's image generation)
+                                        if inline_data.mime_type.starts_with("image/") {
+                                            blocks.push(AnthropicContentBlock::Image {
+                                                source: AnthropicImageSource {
+                                                    r#type: "base64".to_string(),
+                                                    media_type: inline_data.mime_type,
+                                                    data: inline_data.data,
+                                                },
+                                            });
+                                        } else {
+                                            // Non-image inline data not supported
+                                            blocks.push(AnthropicContentBlock::Text {
+                                                text: format!(
+                                                    "[Inline data: {} - Not supported by this model]",
+                                                    inline_data.mime_type
+                                                ),
+                                            });
+                                        }
+                                    }
+                                }
+                            }
+                            messages.push(AnthropicMessage {
+                                role: "user".to_string(),
+                                content: AnthropicMessageContent::Blocks(blocks),
+                            });
+                        }
+                    }
+                }
+                ChatMessage::Assistant {
+                    content,
+                    tool_calls,
+                } => {
+                    let mut blocks = Vec::new();
+                    // Handle AssistantContent
+                    match content {
+                        AssistantContent::Text(text) => {
+                            if !text.is_empty() {
+                                blocks.push(AnthropicContentBlock::Text { text });
+                            }
+                        }
+                        AssistantContent::Parts(parts) => {
+                            for part in parts {
+                                if let ContentPart::Text { text } = part {
+                                    blocks.push(AnthropicContentBlock::Text { text });
+                                }
+                            }
+                        }
+                    }
+                    if let Some(tcs) = tool_calls {
+                        for tc in tcs {
+                            // Assuming tc.function.arguments is raw JSON string, we need Value.
+                            let input_val: Value = serde_json::from_str(&tc.function.arguments)
+                                .unwrap_or(serde_json::json!({}));
+                            blocks.push(AnthropicContentBlock::ToolUse {
+                                id: tc.id,
+                                name: tc.function.name,
+                                input: input_val,
+                            });
+                        }
+                    }
+
+                    messages.push(AnthropicMessage {
+                        role: "assistant".to_string(),
+                        content: AnthropicMessageContent::Blocks(blocks),
+                    });
+                }
+                ChatMessage::Tool {
+                    content,
+                    tool_call_id,
+                } => {
+                    let block = AnthropicContentBlock::ToolResult {
+                        tool_use_id: tool_call_id,
+                        content,
+                    };
+                    // Results of one assistant turn go together in the next user turn
+                    match messages.last_mut() {
+                        Some(AnthropicMessage {
+                            role,
+                            content: AnthropicMessageContent::Blocks(blocks),
+                        }) if role == "user"
+                            && blocks
+                                .iter()
+                                .all(|b| matches!(b, AnthropicContentBlock::ToolResult { .. })) =>
+                        {
+                            blocks.push(block);
+                        }
+                        _ => messages.push(AnthropicMessage {
+                            role: "user".to_string(), // Tool results are role "user"
+                            content: AnthropicMessageContent::Blocks(vec![block]),
+                        }),
+                    }
+                }
+            }
+        }
+
+        // Handle Tools
+        let tools = request.tools.map(|req_tools| {
+            let mut tools: Vec<AnthropicTool> = req_tools
+                .into_iter()
+                .map(|t| AnthropicTool {
+                    name: t.function.name,
+                    description: t.function.description,
+                    input_schema: t
+                        .function
+                        .parameters
+                        .unwrap_or(serde_json::json!({"type": "object", "properties": {}})),
+                    cache_control: None,
+                })
+                .collect();
+            // A breakpoint on the last tool caches all of them
+            if let Some(last) = tools.last_mut().filter(|_| caching) {
+                last.cache_control = Some(EPHEMERAL_CACHE);
+            }
+            tools
+        });
+
+        // Handle Tool Choice
+        let tool_choice = if let Some(tc) = request.tool_choice {
+            match tc {
+                ToolChoice::String(s) if s == "auto" => Some(AnthropicToolChoice::Auto),
+                ToolChoice::String(s) if s == "any" => Some(AnthropicToolChoice::Any),
+                ToolChoice::Object { function, .. } => Some(AnthropicToolChoice::Tool {
+                    name: function.name,
+                }),
+                _ => None,
+            }
+        } else {
+            None
+        };
+
+        let (thinking, max_tokens) = Self::thinking_settings(
+            supports_thinking,
+            request.reasoning_effort.as_deref(),
+            &request
+                .connection_config
+                .as_ref()
+                .map(|config| config.thinking_budgets)
+                .unwrap_or_default(),
+            request.max_tokens,
+        );
+        // Thinking takes no sampling parameters, and the API has no penalties
+        let (temperature, top_p) = if thinking.is_some() {
+            (None, None)
+        } else {
+            (request.temperature, request.top_p)
+        };
+
+        AnthropicRequest {
+            model: request.model,
+            messages,
+            max_tokens,
+            stream: request.stream,
+            system: system_prompt.map(|text| {
+                if caching {
+                    AnthropicSystem::Blocks(vec![AnthropicSystemBlock {
+                        r#type: "text",
+                        text,
+                        cache_control: EPHEMERAL_CACHE,
+                    }])
+                } else {
+                    AnthropicSystem::Text(text)
+                }
+            }),
+            temperature,
+            top_p,
+            tools,
+            tool_choice,
+            thinking,
+        }
+    }
+
+    /// Thinking config and `max_tokens` of a request. The reasoning effort
+    /// picks a budget from `budgets`, or turns thinking off for `"none"`.
+    /// With thinking on, `max_tokens` defaults to the budget plus room for
+    /// the answer; a requested maximum is kept and the budget shrunk to fit
+    /// under it, dropping thinking when too little is left.
+    fn thinking_settings(
+        supports_thinking: bool,
+        effort: Option<&str>,
+        budgets: &ThinkingBudgets,
+        max_tokens: Option<u32>,
+    ) -> (Option<AnthropicThinkingConfig>, u32) {
+        let budget = budgets.for_effort(effort).filter(|_| supports_thinking);
+        let Some(budget) = budget else {
+            return (None, max_tokens.unwrap_or(DEFAULT_MAX_TOKENS));
+        };
+        let (budget, max_tokens) = max_tokens.map_or_else(
+            || (budget, budget + THINKING_HEADROOM_TOKENS),
+            |max| (budget.min(max.saturating_sub(MIN_ANSWER_TOKENS)), max),
+        );
+        if budget < MIN_THINKING_BUDGET {
+            return (None, max_tokens);
+        }
+        let thinking = AnthropicThinkingConfig {
+            r#type: "enabled".to_string(),
+            budget_tokens: budget,
+        };
+        (Some(thinking), max_tokens)
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_streaming(
         &self,
         req_builder: reqwest::RequestBuilder,
@@ -140,32 +581,24 @@ impl AnthropicProvider {
         message_id: String,
         app: AppHandle,
         mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        output_limits: Option<OutputLimits>,
     ) -> Result<LLMChatResponse, AppError> {
         let response = req_builder.json(&request_body).send().await?;
         let message_emitter = MessageEmitter::new(app.clone());
         let tool_emitter = ToolEmitter::new(app.clone());
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("LLM API error ({status}): {error_text}");
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-            return Err(AppError::Llm(error_msg));
+            let error = rate_limit::read_api_error("LLM API error", response).await;
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
-        let mut stream = response.bytes_stream();
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats = super::start_stream_stats(&app, &chat_id, &message_id);
         let mut full_content = String::new();
         let mut full_thinking = String::new();
         let mut buffer = String::new();
-        let mut input_tokens = 0;
-        let mut output_tokens = 0;
+        let mut usage = AnthropicUsage::default();
         let mut finish_reason = None;
 
         // Track current block state
@@ -175,20 +608,17 @@ impl AnthropicProvider {
         let mut _current_block_index: Option<u32> = None;
 
         let mut tool_calls = Vec::new();
+        let mut limiter = OutputLimiter::new(output_limits);
 
-        while let Some(item) = tokio::select! {
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
             next = stream.next() => next,
-             () = async {
-                if let Some(ref mut rx) = cancellation_rx {
-                    let _ = rx.recv().await;
-                }
-                futures::future::pending::<()>().await;
-            }, if cancellation_rx.is_some() => {
-                let _ = message_emitter.emit_message_error(chat_id, message_id, "Cancelled".into());
-                return Err(AppError::Cancelled);
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
             }
         } {
-            let chunk = item.map_err(|e| AppError::Generic(format!("Stream error: {e}")))?;
             let text = String::from_utf8_lossy(&chunk);
             buffer.push_str(&text);
 
@@ -241,7 +671,13 @@ impl AnthropicProvider {
                             if let Some(delta_type) = delta.get("type").and_then(|s| s.as_str()) {
                                 if delta_type == "text_delta" {
                                     if let Some(text) = delta.get("text").and_then(|s| s.as_str()) {
+                                        let text = limiter.admit(
+                                            OutputKind::Content,
+                                            full_content.len(),
+                                            text,
+                                        );
                                         full_content.push_str(text);
+                                        stats.record_chars(text);
                                         message_emitter.emit_message_chunk(
                                             chat_id.clone(),
                                             message_id.clone(),
@@ -252,13 +688,24 @@ impl AnthropicProvider {
                                     if let Some(partial_json) =
                                         delta.get("partial_json").and_then(|s| s.as_str())
                                     {
+                                        let partial_json = limiter.admit(
+                                            OutputKind::ToolArguments,
+                                            current_tool_input.len(),
+                                            partial_json,
+                                        );
                                         current_tool_input.push_str(partial_json);
                                     }
                                 } else if delta_type == "thinking_delta" {
                                     if let Some(thinking) =
                                         delta.get("thinking").and_then(|s| s.as_str())
                                     {
+                                        let thinking = limiter.admit(
+                                            OutputKind::Reasoning,
+                                            full_thinking.len(),
+                                            thinking,
+                                        );
                                         full_thinking.push_str(thinking);
+                                        stats.record_chars(thinking);
                                         message_emitter.emit_thinking_chunk(
                                             chat_id.clone(),
                                             message_id.clone(),
@@ -276,8 +723,7 @@ impl AnthropicProvider {
                         let arguments = if current_tool_input.is_empty() {
                             serde_json::json!({})
                         } else {
-                            serde_json::from_str(&current_tool_input)
-                                .unwrap_or(serde_json::json!({}))
+                            json_repair::arguments_value(&current_tool_input)
                         };
 
                         let tool_call = ToolCall {
@@ -308,23 +754,23 @@ impl AnthropicProvider {
                     }
                 } else if event_type == "message_start" {
                     if let Ok(val) = serde_json::from_str::<Value>(event_data) {
-                        if let Some(usage) = val.get("message").and_then(|m| m.get("usage")) {
-                            if let Some(it) = usage
-                                .get("input_tokens")
-                                .and_then(serde_json::Value::as_u64)
-                            {
-                                input_tokens = it as u32;
-                            }
+                        // Input and cache tokens are known from the start
+                        if let Some(start) = val
+                            .pointer("/message/usage")
+                            .and_then(|u| serde_json::from_value(u.clone()).ok())
+                        {
+                            usage = start;
                         }
                     }
                 } else if event_type == "message_delta" {
                     if let Ok(val) = serde_json::from_str::<Value>(event_data) {
-                        if let Some(usage) = val.get("usage") {
-                            if let Some(ot) = usage
+                        if let Some(delta_usage) = val.get("usage") {
+                            if let Some(ot) = delta_usage
                                 .get("output_tokens")
                                 .and_then(serde_json::Value::as_u64)
                             {
-                                output_tokens = ot as u32;
+                                usage.output_tokens = ot as u32;
+                                stats.record_output_tokens(ot);
                             }
                         }
                         if let Some(stop) = val.get("stop_reason").and_then(|s| s.as_str()) {
@@ -333,18 +779,56 @@ impl AnthropicProvider {
                     }
                 }
             }
+
+            if limiter.should_stop(&buffer) {
+                break;
+            }
         }
 
-        message_emitter.emit_message_complete(
-            chat_id,
-            message_id,
-            full_content.clone(),
-            Some(EventTokenUsage {
-                prompt_tokens: Some(input_tokens),
-                completion_tokens: Some(output_tokens),
-                total_tokens: Some(input_tokens + output_tokens),
-            }),
-        )?;
+        // A stream cut off inside a tool_use block still returns the call; the
+        // caller repairs its arguments or refuses to run it
+        if !current_tool_id.is_empty() {
+            tool_calls.push(ToolCall {
+                id: current_tool_id,
+                r#type: "function".to_string(),
+                function: ToolCallFunction {
+                    name: current_tool_name,
+                    arguments: current_tool_input,
+                },
+            });
+        }
+
+        stats.stop();
+
+        // A stream that broke off keeps what arrived, but not its unfinished tool calls
+        if stream.finish(!full_content.is_empty() || !full_thinking.is_empty())? {
+            tool_calls.clear();
+            finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        }
+
+        let capped = limiter.end_at_cap(stream, &mut tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+        if capped {
+            finish_reason = Some(LENGTH_LIMIT.to_string());
+        }
+
+        if cancelled {
+            message_emitter.emit_message_cancelled(chat_id, message_id, full_content.clone())?;
+            finish_reason = Some("cancelled".to_string());
+        } else {
+            message_emitter.emit_message_complete(
+                chat_id,
+                message_id,
+                full_content.clone(),
+                Some(usage.event_usage()),
+            )?;
+        }
 
         Ok(LLMChatResponse {
             content: full_content,
@@ -354,17 +838,16 @@ impl AnthropicProvider {
             } else {
                 Some(tool_calls)
             },
-            usage: Some(TokenUsage {
-                prompt_tokens: Some(input_tokens),
-                completion_tokens: Some(output_tokens),
-                total_tokens: Some(input_tokens + output_tokens),
-            }),
+            usage: Some(usage.token_usage()),
             reasoning: if full_thinking.is_empty() {
                 None
             } else {
                 Some(full_thinking)
             },
             images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
         })
     }
 
@@ -383,19 +866,10 @@ impl AnthropicProvider {
             .map_err(|e| AppError::Generic(format!("HTTP request failed: {e}")))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("LLM API error ({status}): {error_text}");
+            let error = rate_limit::read_api_error("LLM API error", response).await;
             let message_emitter = MessageEmitter::new(app.clone());
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-            return Err(AppError::Generic(error_msg));
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
         let json_response: AnthropicResponse = response
@@ -451,13 +925,7 @@ impl AnthropicProvider {
             chat_id.clone(),
             message_id.clone(),
             content_str.clone(),
-            Some(EventTokenUsage {
-                prompt_tokens: Some(json_response.usage.input_tokens),
-                completion_tokens: Some(json_response.usage.output_tokens),
-                total_tokens: Some(
-                    json_response.usage.input_tokens + json_response.usage.output_tokens,
-                ),
-            }),
+            Some(json_response.usage.event_usage()),
         )?;
 
         Ok(LLMChatResponse {
@@ -468,19 +936,16 @@ impl AnthropicProvider {
             } else {
                 Some(tool_calls)
             },
-            usage: Some(TokenUsage {
-                prompt_tokens: Some(json_response.usage.input_tokens),
-                completion_tokens: Some(json_response.usage.output_tokens),
-                total_tokens: Some(
-                    json_response.usage.input_tokens + json_response.usage.output_tokens,
-                ),
-            }),
+            usage: Some(json_response.usage.token_usage()),
             reasoning: if thinking_str.is_empty() {
                 None
             } else {
                 Some(thinking_str)
             },
             images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
         })
     }
 }
@@ -493,53 +958,31 @@ impl LLMProvider for AnthropicProvider {
         api_key: Option<&str>,
     ) -> Result<Vec<LLMModel>, AppError> {
         let url = format!("{}/v1/models", base_url.trim_end_matches('/'));
-        let mut req_builder = self.client.get(&url);
-
-        if let Some(key) = api_key {
-            req_builder = req_builder.header("x-api-key", key);
-        }
-        req_builder = req_builder.header("anthropic-version", "2023-06-01");
-        req_builder = req_builder.header("Content-Type", "application/json");
-
-        let response = req_builder.send().await?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::Llm(format!(
-                "LLM API error ({status}): {error_text}"
-            )));
-        }
+        Self::collect_model_pages(|after_id| {
+            let mut req_builder = self
+                .client
+                .get(&url)
+                .query(&[("limit", MODELS_PAGE_LIMIT.to_string())]);
+            if let Some(after_id) = after_id {
+                req_builder = req_builder.query(&[("after_id", after_id)]);
+            }
+            if let Some(key) = api_key {
+                req_builder = req_builder.header("x-api-key", key);
+            }
+            req_builder = req_builder.header("anthropic-version", ANTHROPIC_VERSION);
+            req_builder = req_builder.header("Content-Type", "application/json");
 
-        let json: serde_json::Value = response.json().await?;
+            async move {
+                let response = req_builder.send().await?;
 
-        let mut models = Vec::new();
-        if let Some(data) = json.get("data").and_then(|d| d.as_array()) {
-            for item in data {
-                if let Some(id) = item.get("id").and_then(|s| s.as_str()) {
-                    let (supports_tools, supports_thinking, supports_image_generation) =
-                        Self::check_model_capabilities(id);
-                    models.push(LLMModel {
-                        id: id.to_string(),
-                        name: item
-                            .get("display_name")
-                            .and_then(|s| s.as_str())
-                            .unwrap_or(id)
-                            .to_string(),
-                        created: item.get("created_at").and_then(|v| v.as_str()).map(|_| 0),
-                        owned_by: Some("anthropic".to_string()),
-                        supports_tools,
-                        supports_thinking,
-                        supports_image_generation,
-                    });
+                if !response.status().is_success() {
+                    return Err(rate_limit::read_api_error("LLM API error", response).await);
                 }
-            }
-        }
 
-        Ok(models)
+                Ok(response.json::<Value>().await?)
+            }
+        })
+        .await
     }
 
     async fn chat(
@@ -558,250 +1001,271 @@ impl LLMProvider for AnthropicProvider {
         if let Some(key) = api_key {
             req_builder = req_builder.header("x-api-key", key);
         }
-        req_builder = req_builder.header("anthropic-version", "2023-06-01");
+        req_builder = req_builder.header("anthropic-version", ANTHROPIC_VERSION);
         req_builder = req_builder.header("Content-Type", "application/json");
 
-        // Use helper to check capabilities for dynamic request construction
-        let (_, supports_thinking, _) = Self::check_model_capabilities(&request.model);
+        let stream = request.stream;
+        let output_limits = request.output_limits;
+        let anthropic_request = Self::build_request(request);
 
-        // Convert messages
-        let mut messages = Vec::new();
-        let mut system_prompt = None;
+        if stream {
+            self.handle_streaming(
+                req_builder,
+                anthropic_request,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                output_limits,
+            )
+            .await
+        } else {
+            self.handle_non_streaming(req_builder, anthropic_request, chat_id, message_id, app)
+                .await
+        }
+    }
+}
 
-        for msg in request.messages {
-            match msg {
-                ChatMessage::System { content } => {
-                    if let Some(existing) = system_prompt {
-                        system_prompt = Some(format!("{existing}\n\n{content}"));
-                    } else {
-                        system_prompt = Some(content);
-                    }
-                }
-                ChatMessage::User { content } => {
-                    match content {
-                        UserContent::Text(text) => {
-                            messages.push(AnthropicMessage {
-                                role: "user".to_string(),
-                                content: AnthropicMessageContent::Text(text),
-                            });
-                        }
-                        UserContent::Parts(parts) => {
-                            let mut blocks = Vec::new();
-                            for part in parts {
-                                match part {
-                                    ContentPart::Text { text } => {
-                                        blocks.push(AnthropicContentBlock::Text { text });
-                                    }
-                                    ContentPart::FileUrl { file_url } => {
-                                        // Anthropic only supports images
-                                        // For non-image files, add a text placeholder
-                                        if file_url.mime_type.starts_with("image/") {
-                                            if let Some(comma_pos) = file_url.url.find(',') {
-                                                let data = &file_url.url[comma_pos + 1..];
-                                                let media_type = &file_url.mime_type;
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn anthropic_models_are_read_across_pages() {
+        use crate::services::llm::providers::AnthropicProvider;
 
-                                                blocks.push(AnthropicContentBlock::Image {
-                                                    source: AnthropicImageSource {
-                                                        r#type: "base64".to_string(),
-                                                        media_type: media_type.to_string(),
-                                                        data: data.to_string(),
-                                                    },
-                                                });
-                                            }
-                                        } else {
-                                            // Non-image files not supported by Anthropic
-                                            // Add a text placeholder
-                                            blocks.push(AnthropicContentBlock::Text {
-                                                text: format!(
-                                                    "[File attachment: {} - Not supported by this model]",
-                                                    file_url.mime_type
-                                                ),
-                                            });
-                                        }
-                                    }
-                                    ContentPart::ImageUrl { image_url } => {
-                                        // Parse data URL: data:image/jpeg;base64,...
-                                        if let Some(comma_pos) = image_url.url.find(',') {
-                                            let meta = &image_url.url[..comma_pos];
-                                            let data = &image_url.url[comma_pos + 1..];
+        let pages = [
+            serde_json::json!({
+                "data": [
+                    {"id": "claude-3-7-sonnet-20250219", "display_name": "Claude 3.7 Sonnet", "created_at": "2025-02-19T00:00:00Z"},
+                    {"id": "claude-3-5-haiku-20241022", "display_name": "Claude 3.5 Haiku", "created_at": "2024-10-22T00:00:00Z"}
+                ],
+                "has_more": true,
+                "first_id": "claude-3-7-sonnet-20250219",
+                "last_id": "claude-3-5-haiku-20241022"
+            }),
+            serde_json::json!({
+                "data": [
+                    {"id": "claude-3-5-haiku-20241022", "display_name": "Claude 3.5 Haiku", "created_at": "2024-10-22T00:00:00Z"},
+                    {"id": "claude-3-opus-20240229", "display_name": "Claude 3 Opus", "created_at": "2024-02-29T00:00:00Z"}
+                ],
+                "has_more": false,
+                "first_id": "claude-3-5-haiku-20241022",
+                "last_id": "claude-3-opus-20240229"
+            }),
+        ];
 
-                                            // Extract mime type
-                                            let media_type = if meta.contains("image/png") {
-                                                "image/png"
-                                            } else if meta.contains("image/jpeg") {
-                                                "image/jpeg"
-                                            } else if meta.contains("image/webp") {
-                                                "image/webp"
-                                            } else if meta.contains("image/gif") {
-                                                "image/gif"
-                                            } else {
-                                                "image/jpeg" // Fallback
-                                            };
+        let mut cursors = Vec::new();
+        let models = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(AnthropicProvider::collect_model_pages(|after_id| {
+                let page = pages[cursors.len()].clone();
+                cursors.push(after_id);
+                async move { Ok(page) }
+            }))
+            .unwrap();
 
-                                            blocks.push(AnthropicContentBlock::Image {
-                                                source: AnthropicImageSource {
-                                                    r#type: "base64".to_string(),
-                                                    media_type: media_type.to_string(),
-                                                    data: data.to_string(),
-                                                },
-                                            });
-                                        }
-                                    }
-                                    ContentPart::InlineData { inline_data } => {
-                                        // Handle inline data (e.g., from Google's image generation)
-                                        if inline_data.mime_type.starts_with("image/") {
-                                            blocks.push(AnthropicContentBlock::Image {
-                                                source: AnthropicImageSource {
-                                                    r#type: "base64".to_string(),
-                                                    media_type: inline_data.mime_type,
-                                                    data: inline_data.data,
-                                                },
-                                            });
-                                        } else {
-                                            // Non-image inline data not supported
-                                            blocks.push(AnthropicContentBlock::Text {
-                                                text: format!(
-                                                    "[Inline data: {} - Not supported by this model]",
-                                                    inline_data.mime_type
-                                                ),
-                                            });
-                                        }
-                                    }
-                                }
-                            }
-                            messages.push(AnthropicMessage {
-                                role: "user".to_string(),
-                                content: AnthropicMessageContent::Blocks(blocks),
-                            });
-                        }
-                    }
-                }
-                ChatMessage::Assistant {
-                    content,
-                    tool_calls,
-                } => {
-                    let mut blocks = Vec::new();
-                    // Handle AssistantContent
-                    match content {
-                        AssistantContent::Text(text) => {
-                            if !text.is_empty() {
-                                blocks.push(AnthropicContentBlock::Text { text });
-                            }
-                        }
-                        AssistantContent::Parts(parts) => {
-                            for part in parts {
-                                if let ContentPart::Text { text } = part {
-                                    blocks.push(AnthropicContentBlock::Text { text });
-                                }
-                            }
-                        }
-                    }
-                    if let Some(tcs) = tool_calls {
-                        for tc in tcs {
-                            // Assuming tc.function.arguments is raw JSON string, we need Value.
-                            let input_val: Value = serde_json::from_str(&tc.function.arguments)
-                                .unwrap_or(serde_json::json!({}));
-                            blocks.push(AnthropicContentBlock::ToolUse {
-                                id: tc.id,
-                                name: tc.function.name,
-                                input: input_val,
-                            });
-                        }
-                    }
+        assert_eq!(
+            cursors,
+            [None, Some("claude-3-5-haiku-20241022".to_string())]
+        );
+        let ids: Vec<_> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "claude-3-7-sonnet-20250219",
+                "claude-3-5-haiku-20241022",
+                "claude-3-opus-20240229"
+            ]
+        );
+        assert_eq!(models[0].name, "Claude 3.7 Sonnet");
+        assert_eq!(models[0].created, Some(1_739_923_200));
+        assert_eq!(models[2].created, Some(1_709_164_800));
+    }
 
-                    messages.push(AnthropicMessage {
-                        role: "assistant".to_string(),
-                        content: AnthropicMessageContent::Blocks(blocks),
-                    });
-                }
-                ChatMessage::Tool {
-                    content,
-                    tool_call_id,
-                } => {
-                    messages.push(AnthropicMessage {
-                        role: "user".to_string(), // Tool results are role "user"
-                        content: AnthropicMessageContent::Blocks(vec![
-                            AnthropicContentBlock::ToolResult {
-                                tool_use_id: tool_call_id,
-                                content,
-                            },
-                        ]),
-                    });
-                }
-            }
-        }
+    #[test]
+    fn anthropic_model_with_bad_created_at_has_no_timestamp() {
+        use crate::services::llm::providers::AnthropicProvider;
 
-        // Handle Tools
-        let tools = request.tools.map(|req_tools| {
-            req_tools
-                .into_iter()
-                .map(|t| AnthropicTool {
-                    name: t.function.name,
-                    description: t.function.description,
-                    input_schema: t
-                        .function
-                        .parameters
-                        .unwrap_or(serde_json::json!({"type": "object", "properties": {}})),
-                })
-                .collect()
-        });
+        let models = AnthropicProvider::parse_models_page(&serde_json::json!({
+            "data": [
+                {"id": "claude-next", "created_at": "last tuesday"},
+                {"id": "claude-undated"},
+                {"display_name": "No id"}
+            ],
+            "has_more": false
+        }));
 
-        // Handle Tool Choice
-        let tool_choice = if let Some(tc) = request.tool_choice {
-            match tc {
-                ToolChoice::String(s) if s == "auto" => Some(AnthropicToolChoice::Auto),
-                ToolChoice::String(s) if s == "any" => Some(AnthropicToolChoice::Any),
-                ToolChoice::Object { function, .. } => Some(AnthropicToolChoice::Tool {
-                    name: function.name,
-                }),
-                _ => None,
-            }
-        } else {
-            None
+        assert_eq!(models.len(), 2);
+        assert_eq!(models[0].name, "claude-next");
+        assert_eq!(models[0].created, None);
+        assert_eq!(models[1].created, None);
+    }
+
+    fn anthropic_request(
+        model: &str,
+        effort: Option<&str>,
+        max_tokens: Option<u32>,
+        config: Option<crate::features::llm_connection::config::ConnectionConfig>,
+    ) -> serde_json::Value {
+        use crate::models::llm_types::{ChatMessage, LLMChatRequest, UserContent};
+        use crate::services::llm::providers::AnthropicProvider;
+
+        let request = LLMChatRequest {
+            model: model.to_string(),
+            messages: vec![ChatMessage::User {
+                content: UserContent::Text("Hi".to_string()),
+            }],
+            temperature: None,
+            max_tokens,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: effort.map(str::to_string),
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: config,
+            seed: None,
+            output_limits: None,
         };
+        serde_json::to_value(AnthropicProvider::build_request(request)).unwrap()
+    }
 
-        // Handle Thinking
-        let thinking = if supports_thinking {
-            // Hardcode 1024 tokens for now or fetch from generic request config if available in future
-            Some(AnthropicThinkingConfig {
-                r#type: "enabled".to_string(),
-                budget_tokens: 2048,
-            })
-        } else {
-            None
+    #[test]
+    fn anthropic_thinking_budget_follows_reasoning_effort() {
+        const MODEL: &str = "claude-3-7-sonnet-20250219";
+
+        for (effort, budget) in [
+            (Some("low"), 1024),
+            (Some("medium"), 4096),
+            (Some("high"), 16384),
+            (None, 4096),
+        ] {
+            let body = anthropic_request(MODEL, effort, None, None);
+            assert_eq!(
+                body["thinking"],
+                serde_json::json!({"type": "enabled", "budget_tokens": budget}),
+                "{effort:?}"
+            );
+            // Room for the answer on top of the budget
+            assert_eq!(body["max_tokens"], budget + 4096, "{effort:?}");
+        }
+
+        let body = anthropic_request(MODEL, Some("none"), None, None);
+        assert!(body.get("thinking").is_none());
+        assert_eq!(body["max_tokens"], 4096);
+
+        // Models without extended thinking never get the field
+        let body = anthropic_request("claude-3-haiku-20240307", Some("high"), None, None);
+        assert!(body.get("thinking").is_none());
+    }
+
+    #[test]
+    fn anthropic_thinking_respects_max_tokens_and_connection_budgets() {
+        use crate::features::llm_connection::config::{parse_config, ConnectionConfig};
+
+        const MODEL: &str = "claude-3-7-sonnet-20250219";
+
+        // A requested maximum is kept; the budget shrinks under it
+        let body = anthropic_request(MODEL, Some("high"), Some(10_000), None);
+        assert_eq!(body["max_tokens"], 10_000);
+        assert_eq!(body["thinking"]["budget_tokens"], 10_000 - 1024);
+        // Too small for any thinking
+        let body = anthropic_request(MODEL, Some("low"), Some(1500), None);
+        assert_eq!(body["max_tokens"], 1500);
+        assert!(body.get("thinking").is_none());
+
+        let config = parse_config(Some(r#"{"thinking_budgets": {"high": 32000}}"#)).unwrap();
+        assert_eq!(config.thinking_budgets.low, 1024);
+        let body = anthropic_request(MODEL, Some("high"), None, Some(config));
+        assert_eq!(body["thinking"]["budget_tokens"], 32000);
+        assert_eq!(body["max_tokens"], 32000 + 4096);
+
+        assert_eq!(
+            parse_config(Some("  ")).unwrap(),
+            ConnectionConfig::default()
+        );
+        assert!(parse_config(Some(r#"{"thinking_budgets": {"low": 100}}"#)).is_err());
+        assert!(parse_config(Some(r#"{"thinking_budget": {}}"#)).is_err());
+    }
+
+    #[test]
+    fn anthropic_prompt_caching_marks_system_and_tools_and_prices_cache_tokens() {
+        use crate::features::llm_connection::config::ConnectionConfig;
+        use crate::features::usage::service::prompt_cache_cost_delta;
+        use crate::models::llm_types::{
+            ChatCompletionTool, ChatCompletionToolFunction, ChatMessage, LLMChatRequest,
+            UserContent,
         };
+        use crate::services::llm::providers::AnthropicProvider;
 
-        // Adjust max_tokens if thinking is enabled to ensure we have room
-        let max_tokens = if supports_thinking && request.max_tokens.unwrap_or(0) < 4096 {
-            8192 // Bump up for thinking models
-        } else {
-            request.max_tokens.unwrap_or(4096)
+        let tool = |name: &str| ChatCompletionTool {
+            r#type: "function".to_string(),
+            function: ChatCompletionToolFunction {
+                name: name.to_string(),
+                description: None,
+                parameters: None,
+            },
+        };
+        let request = |config: Option<ConnectionConfig>| LLMChatRequest {
+            model: "claude-3-5-sonnet-20241022".to_string(),
+            messages: vec![
+                ChatMessage::System {
+                    content: "You are terse.".to_string(),
+                },
+                ChatMessage::User {
+                    content: UserContent::Text("Hi".to_string()),
+                },
+            ],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: Some(vec![tool("read_file"), tool("write_file")]),
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: config,
+            seed: None,
+            output_limits: None,
         };
 
-        let anthropic_request = AnthropicRequest {
-            model: request.model,
-            messages,
-            max_tokens,
-            stream: request.stream,
-            system: system_prompt,
-            tools,
-            tool_choice,
-            thinking,
+        let body = serde_json::to_value(AnthropicProvider::build_request(request(None))).unwrap();
+        assert_eq!(body["system"], "You are terse.");
+        assert!(body["tools"][1].get("cache_control").is_none());
+
+        let config = ConnectionConfig {
+            prompt_caching: true,
+            ..ConnectionConfig::default()
         };
+        let body =
+            serde_json::to_value(AnthropicProvider::build_request(request(Some(config)))).unwrap();
+        assert_eq!(
+            body["system"],
+            serde_json::json!([{
+                "type": "text",
+                "text": "You are terse.",
+                "cache_control": {"type": "ephemeral"}
+            }])
+        );
+        assert!(body["tools"][0].get("cache_control").is_none());
+        assert_eq!(body["tools"][1]["cache_control"]["type"], "ephemeral");
 
-        if request.stream {
-            self.handle_streaming(
-                req_builder,
-                anthropic_request,
-                chat_id,
-                message_id,
-                app,
-                cancellation_rx,
-            )
-            .await
-        } else {
-            self.handle_non_streaming(req_builder, anthropic_request, chat_id, message_id, app)
-                .await
-        }
+        // 3.0 per 1M input tokens: writes cost 25% more, reads 90% less
+        let delta = prompt_cache_cost_delta("anthropic", "claude-3-5-sonnet", 0, 1_000_000);
+        assert!((delta - 0.75).abs() < 1e-9);
+        let delta = prompt_cache_cost_delta("anthropic", "claude-3-5-sonnet", 1_000_000, 0);
+        assert!((delta + 2.7).abs() < 1e-9);
+        assert!(prompt_cache_cost_delta("openai", "gpt-4o", 1_000_000, 0).abs() < f64::EPSILON);
     }
 }
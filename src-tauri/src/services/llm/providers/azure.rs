@@ -0,0 +1,313 @@
+//! Azure OpenAI.
+//!
+//! Azure serves each model as a deployment of a resource: chats go to
+//! `{endpoint}/openai/deployments/{deployment}/chat/completions` with an
+//! `api-version` query parameter, in the OpenAI chat format. A connection
+//! names its deployment, which is its one model, so the model of a request
+//! is the deployment it routes to. Resource keys go in an `api-key` header;
+//! an Azure AD (Entra ID) access token goes as a bearer token instead.
+
+use super::{LLMProvider, OpenAICompatProvider};
+use crate::error::AppError;
+use crate::models::llm_types::{LLMChatRequest, LLMChatResponse, LLMModel};
+use crate::services::llm::rate_limit;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+use std::sync::Arc;
+use tauri::AppHandle;
+
+/// api-version of connections that set none.
+pub const DEFAULT_API_VERSION: &str = "2024-10-21";
+
+const AZURE_API_ERROR: &str = "Azure OpenAI API error";
+
+/// First api-version that takes `max_completion_tokens`.
+const MAX_COMPLETION_TOKENS_SINCE: &str = "2024-09-01";
+
+pub fn is_azure(provider: &str) -> bool {
+    matches!(provider.to_lowercase().as_str(), "azure" | "azure-openai")
+}
+
+/// `YYYY-MM-DD`, or the same with a `-preview` suffix.
+fn is_api_version(version: &str) -> bool {
+    if !version.is_ascii() || version.len() < 10 {
+        return false;
+    }
+    let (date, suffix) = version.split_at(10);
+    let date_shape = date.bytes().enumerate().all(|(i, b)| match i {
+        4 | 7 => b == b'-',
+        _ => b.is_ascii_digit(),
+    });
+    date_shape && (suffix.is_empty() || suffix == "-preview")
+}
+
+/// Check the Azure settings of a connection; other providers need none.
+pub fn validate_deployment(
+    provider: &str,
+    deployment_name: Option<&str>,
+    api_version: Option<&str>,
+) -> Result<(), AppError> {
+    if !is_azure(provider) {
+        return Ok(());
+    }
+    let deployment = deployment_name
+        .map(str::trim)
+        .filter(|d| !d.is_empty())
+        .ok_or_else(|| {
+            AppError::Validation("Azure OpenAI connections need a deployment name".to_string())
+        })?;
+    if !deployment
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err(AppError::Validation(format!(
+            "Invalid deployment name: {deployment}"
+        )));
+    }
+    if let Some(version) = api_version.map(str::trim).filter(|v| !v.is_empty()) {
+        if !is_api_version(version) {
+            return Err(AppError::Validation(format!(
+                "Invalid api-version: {version} (expected e.g. {DEFAULT_API_VERSION} or 2025-01-01-preview)"
+            )));
+        }
+    }
+    Ok(())
+}
+
+/// The resource endpoint, also when the connection was entered with the
+/// `/openai` path.
+fn endpoint(base_url: &str) -> &str {
+    let base_url = base_url.trim_end_matches('/');
+    base_url.strip_suffix("/openai").unwrap_or(base_url)
+}
+
+/// Chat completions URL of a deployment.
+pub fn chat_url(base_url: &str, deployment: &str, api_version: Option<&str>) -> String {
+    format!(
+        "{}/openai/deployments/{deployment}/chat/completions?api-version={}",
+        endpoint(base_url),
+        api_version.unwrap_or(DEFAULT_API_VERSION)
+    )
+}
+
+/// Whether a credential is an Azure AD access token rather than a resource
+/// key. Tokens are JWTs: three dot-separated parts, the first a base64 JSON
+/// header; keys are plain hex.
+pub fn is_ad_token(credential: &str) -> bool {
+    credential.starts_with("eyJ") && credential.split('.').count() == 3
+}
+
+fn authorized(
+    req_builder: reqwest::RequestBuilder,
+    api_key: Option<&str>,
+) -> reqwest::RequestBuilder {
+    match api_key.map(str::trim).filter(|k| !k.is_empty()) {
+        Some(token) if is_ad_token(token) => {
+            req_builder.header("Authorization", format!("Bearer {token}"))
+        }
+        Some(key) => req_builder.header("api-key", key),
+        None => req_builder,
+    }
+}
+
+/// A connection's model: its deployment, with the capabilities of the model
+/// deployed, which Azure names in its answers.
+pub fn deployment_model(deployment: &str, deployed_model: Option<&str>) -> LLMModel {
+    let model = deployed_model.unwrap_or(deployment).to_lowercase();
+    let reasoning = model.starts_with("gpt-5")
+        || (model.starts_with('o') && model[1..].starts_with(|c: char| c.is_ascii_digit()));
+    LLMModel {
+        id: deployment.to_string(),
+        name: deployment.to_string(),
+        created: None,
+        owned_by: deployed_model.map(str::to_string),
+        supports_tools: reasoning || model.starts_with("gpt"),
+        supports_thinking: reasoning,
+        supports_image_generation: false,
+    }
+}
+
+pub struct AzureOpenAIProvider {
+    client: Arc<Client>,
+    // Azure speaks the OpenAI chat format; only addressing and auth differ
+    compat: OpenAICompatProvider,
+}
+
+impl AzureOpenAIProvider {
+    pub fn new(client: Arc<Client>) -> Self {
+        Self {
+            compat: OpenAICompatProvider::new(client.clone()),
+            client,
+        }
+    }
+
+    /// Check a deployment answers, with a one-token chat, and describe it as
+    /// the connection's model.
+    pub async fn check_deployment(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        deployment: &str,
+        api_version: Option<&str>,
+    ) -> Result<LLMModel, AppError> {
+        let version = api_version.unwrap_or(DEFAULT_API_VERSION);
+        let limit_key = if version >= MAX_COMPLETION_TOKENS_SINCE {
+            "max_completion_tokens"
+        } else {
+            "max_tokens"
+        };
+        let response = authorized(
+            self.client
+                .post(chat_url(base_url, deployment, Some(version))),
+            api_key,
+        )
+        .json(&json!({
+            "messages": [{ "role": "user", "content": "hi" }],
+            limit_key: 1,
+            "stream": false,
+        }))
+        .send()
+        .await?;
+        if !response.status().is_success() {
+            return Err(rate_limit::read_api_error(AZURE_API_ERROR, response).await);
+        }
+
+        let answer: Value = response.json().await?;
+        Ok(deployment_model(
+            deployment,
+            answer.get("model").and_then(Value::as_str),
+        ))
+    }
+}
+
+#[async_trait]
+impl LLMProvider for AzureOpenAIProvider {
+    async fn fetch_models(
+        &self,
+        _base_url: &str,
+        _api_key: Option<&str>,
+    ) -> Result<Vec<LLMModel>, AppError> {
+        // Deployments are listed only by Azure's management API
+        Err(AppError::Validation(
+            "Azure OpenAI connections name their deployment instead of listing models".to_string(),
+        ))
+    }
+
+    async fn chat(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        request: LLMChatRequest,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+    ) -> Result<LLMChatResponse, AppError> {
+        let api_version = request
+            .connection_config
+            .as_ref()
+            .and_then(|config| config.api_version.clone());
+        let url = chat_url(base_url, &request.model, api_version.as_deref());
+        let req_builder =
+            authorized(self.client.post(&url), api_key).header("Content-Type", "application/json");
+        self.compat
+            .send_chat(
+                req_builder,
+                request,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+            )
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::webhook::dispatcher::tests::mock_webhook_receiver;
+    use crate::test_support::connection;
+
+    #[test]
+    fn azure_connections_route_to_their_deployment_and_version() {
+        use crate::features::llm_connection::config::ConnectionConfig;
+        use crate::services::llm::providers::azure::{
+            chat_url, deployment_model, is_ad_token, validate_deployment,
+        };
+
+        assert_eq!(
+            chat_url("https://acme.openai.azure.com/openai/", "gpt4o-prod", None),
+            "https://acme.openai.azure.com/openai/deployments/gpt4o-prod/chat/completions?api-version=2024-10-21"
+        );
+        assert!(chat_url(
+            "https://acme.openai.azure.com",
+            "d",
+            Some("2025-01-01-preview")
+        )
+        .ends_with("?api-version=2025-01-01-preview"));
+
+        assert!(validate_deployment("azure", Some("gpt4o-prod"), Some("2024-10-21")).is_ok());
+        assert!(validate_deployment("azure", Some("gpt4o-prod"), None).is_ok());
+        assert!(validate_deployment("azure", None, None).is_err());
+        assert!(validate_deployment("azure", Some("  "), None).is_err());
+        assert!(validate_deployment("azure", Some("a/b"), None).is_err());
+        assert!(validate_deployment("azure", Some("d"), Some("2024-10")).is_err());
+        assert!(validate_deployment("azure", Some("d"), Some("2024-10-21-beta")).is_err());
+        // Other providers need no deployment
+        assert!(validate_deployment("openai", None, None).is_ok());
+
+        assert!(is_ad_token("eyJhbGciOiJSUzI1NiJ9.eyJhdWQiOiJ4In0.c2ln"));
+        assert!(!is_ad_token("0123456789abcdef0123456789abcdef"));
+
+        let model = deployment_model("reasoner", Some("o3-mini-2025-01-31"));
+        assert_eq!(model.id, "reasoner");
+        assert!(model.supports_thinking && model.supports_tools);
+        let model = deployment_model("chat", Some("gpt-4o-2024-08-06"));
+        assert!(!model.supports_thinking && model.supports_tools);
+
+        // The api-version reaches providers with the connection's config
+        let mut connection = connection("az", "azure", "gpt4o-prod", serde_json::json!([]));
+        connection.api_version = Some("2025-01-01-preview".to_string());
+        assert_eq!(
+            ConnectionConfig::for_connection(&connection)
+                .api_version
+                .as_deref(),
+            Some("2025-01-01-preview")
+        );
+    }
+
+    #[test]
+    fn azure_sends_keys_as_api_key_and_ad_tokens_as_bearer() {
+        use crate::services::llm::providers::AzureOpenAIProvider;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let (url, received) = mock_webhook_receiver(vec![401, 401]).await;
+            let endpoint = url.trim_end_matches("/hook");
+            let provider = AzureOpenAIProvider::new(std::sync::Arc::new(reqwest::Client::new()));
+
+            let key = "0123456789abcdef0123456789abcdef";
+            let token = "eyJhbGciOiJSUzI1NiJ9.eyJhdWQiOiJ4In0.c2ln";
+            assert!(provider
+                .check_deployment(endpoint, Some(key), "chat", None)
+                .await
+                .is_err());
+            assert!(provider
+                .check_deployment(endpoint, Some(token), "chat", Some("2024-06-01"))
+                .await
+                .is_err());
+
+            let received = received.lock().unwrap();
+            assert_eq!(received[0].0.get("api-key").map(String::as_str), Some(key));
+            assert!(!received[0].0.contains_key("authorization"));
+            assert_eq!(
+                received[1].0.get("authorization"),
+                Some(&format!("Bearer {token}"))
+            );
+            assert!(!received[1].0.contains_key("api-key"));
+            // Versions before max_completion_tokens get max_tokens
+            assert!(received[0].1.contains("max_completion_tokens"));
+            assert!(received[1].1.contains("\"max_tokens\""));
+        });
+    }
+}
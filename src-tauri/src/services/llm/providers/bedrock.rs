@@ -0,0 +1,1353 @@
+//! AWS Bedrock.
+//!
+//! Chats go through the Converse API, which serves Claude, Llama, Titan and
+//! the other text models of Bedrock in one format; streamed answers arrive in
+//! AWS's binary event stream framing. Requests are signed with Signature
+//! Version 4, with the keys of the connection's profile in the shared AWS
+//! files, or of the environment when it names none. A connection's base URL
+//! is the runtime endpoint of its region.
+
+use super::LLMProvider;
+use crate::error::AppError;
+use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage, ToolEmitter};
+use crate::features::llm_connection::config::ConnectionConfig;
+use crate::models::llm_types::{
+    AssistantContent, ChatMessage, ContentPart, LLMChatRequest, LLMChatResponse, LLMModel,
+    TokenUsage, ToolCall, ToolCallFunction, ToolChoice, UserContent,
+};
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit;
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
+use async_trait::async_trait;
+use hmac::{Hmac, Mac};
+use reqwest::{Client, Method};
+use serde_json::{json, Value};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tauri::AppHandle;
+
+const BEDROCK_API_ERROR: &str = "Bedrock API error";
+
+/// Answer tokens on top of a thinking budget when the request sets no maximum.
+const THINKING_HEADROOM_TOKENS: u32 = 4096;
+
+/// Largest event stream message accepted; AWS caps them at 16 MiB.
+const MAX_EVENT_MESSAGE_BYTES: usize = 17 * 1024 * 1024;
+
+/// Runtime endpoint of a region, the base URL of its connections.
+pub fn runtime_url(region: &str) -> String {
+    format!("https://bedrock-runtime.{region}.amazonaws.com")
+}
+
+/// Region of a runtime endpoint.
+pub fn region_of(base_url: &str) -> Option<&str> {
+    let host = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, rest)| rest)
+        .split(['/', ':'])
+        .next()?;
+    host.strip_prefix("bedrock-runtime.")?
+        .strip_suffix(".amazonaws.com")
+        .filter(|region| is_region(region))
+}
+
+/// An AWS region name, e.g. `us-east-1` or `ap-southeast-2`.
+pub fn is_region(region: &str) -> bool {
+    region.matches('-').count() >= 2
+        && region
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+pub fn is_bedrock(provider: &str) -> bool {
+    matches!(provider.to_lowercase().as_str(), "bedrock" | "aws-bedrock")
+}
+
+/// Check the AWS settings of a connection; other providers need none.
+pub fn validate_settings(provider: &str, region: Option<&str>) -> Result<(), AppError> {
+    if !is_bedrock(provider) {
+        return Ok(());
+    }
+    match region.map(str::trim).filter(|r| !r.is_empty()) {
+        Some(region) if is_region(region) => Ok(()),
+        Some(region) => Err(AppError::Validation(format!(
+            "Invalid AWS region: {region}"
+        ))),
+        None => Err(AppError::Validation(
+            "Bedrock connections need an AWS region".to_string(),
+        )),
+    }
+}
+
+/// Access keys signing Bedrock requests. No `Debug`, so they stay out of logs.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AwsCredentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+impl AwsCredentials {
+    /// Keys of a profile in the text of a shared credentials or config file.
+    /// The config file names its sections `[profile name]`.
+    pub fn from_profile_text(text: &str, profile: &str) -> Option<Self> {
+        let mut in_profile = false;
+        let mut values = HashMap::new();
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let section = section.trim();
+                let name = section.strip_prefix("profile ").map_or(section, str::trim);
+                in_profile = name == profile;
+                continue;
+            }
+            if in_profile {
+                if let Some((key, value)) = line.split_once('=') {
+                    values.insert(key.trim().to_lowercase(), value.trim().to_string());
+                }
+            }
+        }
+        Some(Self {
+            access_key_id: values.remove("aws_access_key_id")?,
+            secret_access_key: values.remove("aws_secret_access_key")?,
+            session_token: values.remove("aws_session_token"),
+        })
+    }
+
+    /// Keys of a profile, or of the environment when none is named and it
+    /// has some. Profiles come from the shared credentials file, then the
+    /// config file; the default profile is `AWS_PROFILE` or "default".
+    pub fn resolve(profile: Option<&str>) -> Result<Self, AppError> {
+        let profile = profile.map(str::trim).filter(|p| !p.is_empty());
+        if profile.is_none() {
+            if let (Ok(access_key_id), Ok(secret_access_key)) = (
+                std::env::var("AWS_ACCESS_KEY_ID"),
+                std::env::var("AWS_SECRET_ACCESS_KEY"),
+            ) {
+                return Ok(Self {
+                    access_key_id,
+                    secret_access_key,
+                    session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+                });
+            }
+        }
+
+        let profile = profile.map_or_else(
+            || std::env::var("AWS_PROFILE").unwrap_or_else(|_| "default".to_string()),
+            str::to_string,
+        );
+        let home = std::env::var_os("HOME")
+            .or_else(|| std::env::var_os("USERPROFILE"))
+            .map(PathBuf::from);
+        for (variable, file) in [
+            ("AWS_SHARED_CREDENTIALS_FILE", "credentials"),
+            ("AWS_CONFIG_FILE", "config"),
+        ] {
+            let path = std::env::var_os(variable)
+                .map(PathBuf::from)
+                .or_else(|| home.as_ref().map(|home| home.join(".aws").join(file)));
+            let Some(text) = path.and_then(|path| std::fs::read_to_string(path).ok()) else {
+                continue;
+            };
+            if let Some(credentials) = Self::from_profile_text(&text, &profile) {
+                return Ok(credentials);
+            }
+        }
+        Err(AppError::Validation(format!(
+            "No AWS access keys found for profile {profile}. Profiles signing in with SSO or assuming a role need their keys exported first"
+        )))
+    }
+}
+
+/// Percent-encoding of SigV4: everything but unreserved characters.
+fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                encoded.push(char::from(byte));
+            }
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC takes keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// What of a request goes into its signature.
+pub struct SigningRequest<'a> {
+    pub method: &'a str,
+    /// With the port, when not the scheme's default
+    pub host: &'a str,
+    /// As sent, already percent-encoded
+    pub path: &'a str,
+    pub query: &'a [(&'a str, &'a str)],
+    /// Signed beside `host`, `x-amz-date` and the session token
+    pub headers: &'a [(&'a str, &'a str)],
+    pub body: &'a [u8],
+}
+
+/// The Signature Version 4 `Authorization` header of a request made at
+/// `amz_date` (`YYYYMMDDTHHMMSSZ`).
+pub fn sigv4_authorization(
+    credentials: &AwsCredentials,
+    region: &str,
+    service: &str,
+    request: &SigningRequest<'_>,
+    amz_date: &str,
+) -> String {
+    let date = &amz_date[..8];
+
+    let mut headers: Vec<(String, String)> = request
+        .headers
+        .iter()
+        .map(|(name, value)| (name.to_lowercase(), value.trim().to_string()))
+        .collect();
+    headers.push(("host".to_string(), request.host.to_string()));
+    headers.push(("x-amz-date".to_string(), amz_date.to_string()));
+    if let Some(token) = &credentials.session_token {
+        headers.push(("x-amz-security-token".to_string(), token.clone()));
+    }
+    headers.sort();
+    let canonical_headers: String = headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+    let signed_headers = headers
+        .iter()
+        .map(|(name, _)| name.as_str())
+        .collect::<Vec<_>>()
+        .join(";");
+
+    let mut query: Vec<(String, String)> = request
+        .query
+        .iter()
+        .map(|(key, value)| (uri_encode(key, true), uri_encode(value, true)))
+        .collect();
+    query.sort();
+    let canonical_query = query
+        .iter()
+        .map(|(key, value)| format!("{key}={value}"))
+        .collect::<Vec<_>>()
+        .join("&");
+
+    // Services other than S3 encode the already encoded path once more
+    let canonical_request = format!(
+        "{}\n{}\n{canonical_query}\n{canonical_headers}\n{signed_headers}\n{}",
+        request.method,
+        uri_encode(request.path, false),
+        hex::encode(Sha256::digest(request.body))
+    );
+    let scope = format!("{date}/{region}/{service}/aws4_request");
+    let string_to_sign = format!(
+        "AWS4-HMAC-SHA256\n{amz_date}\n{scope}\n{}",
+        hex::encode(Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let key = [region, service, "aws4_request"].iter().fold(
+        hmac_sha256(
+            format!("AWS4{}", credentials.secret_access_key).as_bytes(),
+            date,
+        ),
+        |key, part| hmac_sha256(&key, part),
+    );
+    let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+    format!(
+        "AWS4-HMAC-SHA256 Credential={}/{scope}, SignedHeaders={signed_headers}, Signature={signature}",
+        credentials.access_key_id
+    )
+}
+
+/// One message of an event stream, with its string headers.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct EventStreamMessage {
+    pub headers: HashMap<String, String>,
+    pub payload: Vec<u8>,
+}
+
+impl EventStreamMessage {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).map(String::as_str)
+    }
+}
+
+/// Splits a byte stream into event stream messages. Each is a prelude of its
+/// total and headers lengths with a CRC, the headers, the payload and a
+/// message CRC; TLS already guards the bytes, so the CRCs go unchecked.
+#[derive(Default)]
+pub struct EventStreamDecoder {
+    buffer: Vec<u8>,
+}
+
+impl EventStreamDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
+    }
+
+    /// The next whole message, None until one arrived.
+    pub fn next_message(&mut self) -> Result<Option<EventStreamMessage>, AppError> {
+        let malformed = || AppError::Llm(format!("{BEDROCK_API_ERROR}: malformed event stream"));
+        if self.buffer.len() < 12 {
+            return Ok(None);
+        }
+        let word = |at: usize| {
+            u32::from_be_bytes([
+                self.buffer[at],
+                self.buffer[at + 1],
+                self.buffer[at + 2],
+                self.buffer[at + 3],
+            ]) as usize
+        };
+        let (total, headers_len) = (word(0), word(4));
+        if total < 16 + headers_len || total > MAX_EVENT_MESSAGE_BYTES {
+            return Err(malformed());
+        }
+        if self.buffer.len() < total {
+            return Ok(None);
+        }
+
+        let message: Vec<u8> = self.buffer.drain(..total).collect();
+        let headers = parse_event_headers(&message[12..12 + headers_len]).ok_or_else(malformed)?;
+        Ok(Some(EventStreamMessage {
+            headers,
+            payload: message[12 + headers_len..total - 4].to_vec(),
+        }))
+    }
+}
+
+/// Headers of an event stream message: name length and name, a type byte,
+/// and a value whose size the type gives. Only strings are kept.
+fn parse_event_headers(mut bytes: &[u8]) -> Option<HashMap<String, String>> {
+    let mut headers = HashMap::new();
+    while let Some((&name_len, rest)) = bytes.split_first() {
+        let name_len = usize::from(name_len);
+        let name = rest.get(..name_len)?;
+        let (&kind, rest) = rest.get(name_len..)?.split_first()?;
+        let value_len = match kind {
+            0 | 1 => 0,
+            2 => 1,
+            3 => 2,
+            4 => 4,
+            5 | 8 => 8,
+            9 => 16,
+            6 | 7 => {
+                let len = rest.get(..2)?;
+                2 + usize::from(u16::from_be_bytes([len[0], len[1]]))
+            }
+            _ => return None,
+        };
+        let value = rest.get(..value_len)?;
+        if kind == 7 {
+            headers.insert(
+                String::from_utf8_lossy(name).into_owned(),
+                String::from_utf8_lossy(&value[2..]).into_owned(),
+            );
+        }
+        bytes = &rest[value_len..];
+    }
+    Some(headers)
+}
+
+/// Claude models on Bedrock that think.
+fn supports_thinking(model_id: &str) -> bool {
+    let model = model_id.to_lowercase();
+    ["claude-3-7", "claude-sonnet-4", "claude-opus-4"]
+        .iter()
+        .any(|family| model.contains(family))
+}
+
+/// Models Converse offers tool use for.
+fn supports_tools(model_id: &str) -> bool {
+    let model = model_id.to_lowercase();
+    (model.contains("anthropic.claude")
+        && !model.contains("claude-v2")
+        && !model.contains("claude-instant"))
+        || [
+            "llama3-1",
+            "llama3-2-90b",
+            "llama3-3",
+            "llama4",
+            "amazon.nova",
+            "mistral-large",
+            "command-r",
+        ]
+        .iter()
+        .any(|family| model.contains(family))
+}
+
+fn image_format(mime_type: &str) -> Option<&'static str> {
+    match mime_type {
+        "image/png" => Some("png"),
+        "image/jpeg" | "image/jpg" => Some("jpeg"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        _ => None,
+    }
+}
+
+fn document_format(mime_type: &str) -> Option<&'static str> {
+    match mime_type {
+        "application/pdf" => Some("pdf"),
+        "text/csv" => Some("csv"),
+        "text/plain" => Some("txt"),
+        "text/markdown" => Some("md"),
+        "text/html" => Some("html"),
+        "application/msword" => Some("doc"),
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => Some("docx"),
+        "application/vnd.ms-excel" => Some("xls"),
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet" => Some("xlsx"),
+        _ => None,
+    }
+}
+
+/// A content block of base64 `data`: an image, a document, or a placeholder
+/// for what Converse does not take.
+fn media_block(mime_type: &str, data: &str, index: usize) -> Value {
+    if let Some(format) = image_format(mime_type) {
+        return json!({ "image": { "format": format, "source": { "bytes": data } } });
+    }
+    if let Some(format) = document_format(mime_type) {
+        return json!({ "document": {
+            "format": format,
+            "name": format!("attachment-{index}"),
+            "source": { "bytes": data },
+        } });
+    }
+    json!({ "text": format!("[File attachment: {mime_type} - Not supported by this model]") })
+}
+
+fn content_blocks(parts: &[ContentPart]) -> Vec<Value> {
+    parts
+        .iter()
+        .enumerate()
+        .filter_map(|(index, part)| match part {
+            // Converse rejects blank text blocks
+            ContentPart::Text { text } => {
+                (!text.trim().is_empty()).then(|| json!({ "text": text }))
+            }
+            ContentPart::ImageUrl { image_url } => Some(
+                match image_url
+                    .url
+                    .strip_prefix("data:")
+                    .and_then(|url| url.split_once(','))
+                {
+                    Some((meta, data)) => {
+                        media_block(meta.split(';').next().unwrap_or_default(), data, index)
+                    }
+                    None => json!({ "text": format!("[Image: {}]", image_url.url) }),
+                },
+            ),
+            ContentPart::FileUrl { file_url } => Some(match file_url.url.split_once(',') {
+                Some((_, data)) => media_block(&file_url.mime_type, data, index),
+                None => json!({ "text": format!(
+                        "[File attachment: {} - Not supported by this model]",
+                        file_url.mime_type
+                    ) }),
+            }),
+            ContentPart::InlineData { inline_data } => Some(media_block(
+                &inline_data.mime_type,
+                &inline_data.data,
+                index,
+            )),
+        })
+        .collect()
+}
+
+/// Append blocks to the conversation; Converse wants the roles to alternate,
+/// so blocks of the same role as the last message join it.
+fn push_turn(turns: &mut Vec<(&'static str, Vec<Value>)>, role: &'static str, blocks: Vec<Value>) {
+    if blocks.is_empty() {
+        return;
+    }
+    match turns.last_mut() {
+        Some((last_role, content)) if *last_role == role => content.extend(blocks),
+        _ => turns.push((role, blocks)),
+    }
+}
+
+/// The Converse request body of a chat request.
+pub fn converse_body(request: &LLMChatRequest) -> Value {
+    let mut system = Vec::new();
+    let mut turns = Vec::new();
+    for message in &request.messages {
+        match message {
+            ChatMessage::System { content } => system.push(json!({ "text": content })),
+            ChatMessage::User { content } => {
+                let blocks = match content {
+                    UserContent::Text(text) => {
+                        content_blocks(&[ContentPart::Text { text: text.clone() }])
+                    }
+                    UserContent::Parts(parts) => content_blocks(parts),
+                };
+                push_turn(&mut turns, "user", blocks);
+            }
+            ChatMessage::Assistant {
+                content,
+                tool_calls,
+            } => {
+                let mut blocks = match content {
+                    AssistantContent::Text(text) => {
+                        content_blocks(&[ContentPart::Text { text: text.clone() }])
+                    }
+                    AssistantContent::Parts(parts) => content_blocks(parts),
+                };
+                blocks.extend(tool_calls.iter().flatten().map(|call| {
+                    json!({ "toolUse": {
+                        "toolUseId": call.id,
+                        "name": call.function.name,
+                        "input": json_repair::arguments_value(&call.function.arguments),
+                    } })
+                }));
+                push_turn(&mut turns, "assistant", blocks);
+            }
+            ChatMessage::Tool {
+                content,
+                tool_call_id,
+            } => {
+                let text = if content.trim().is_empty() {
+                    "(no output)"
+                } else {
+                    content.as_str()
+                };
+                push_turn(
+                    &mut turns,
+                    "user",
+                    vec![json!({ "toolResult": {
+                        "toolUseId": tool_call_id,
+                        "content": [{ "text": text }],
+                    } })],
+                );
+            }
+        }
+    }
+
+    let messages: Vec<Value> = turns
+        .into_iter()
+        .map(|(role, content)| json!({ "role": role, "content": content }))
+        .collect();
+    let mut body = json!({ "messages": messages });
+    if !system.is_empty() {
+        body["system"] = json!(system);
+    }
+
+    let budgets = request
+        .connection_config
+        .as_ref()
+        .map(|config| config.thinking_budgets)
+        .unwrap_or_default();
+    let thinking = request
+        .reasoning_effort
+        .as_deref()
+        .filter(|_| supports_thinking(&request.model))
+        .and_then(|effort| budgets.for_effort(Some(effort)));
+    let mut inference = serde_json::Map::new();
+    if let Some(budget) = thinking {
+        let max_tokens = request
+            .max_tokens
+            .unwrap_or(budget + THINKING_HEADROOM_TOKENS)
+            .max(budget + 1);
+        inference.insert("maxTokens".to_string(), json!(max_tokens));
+        // Thinking takes no sampling parameters
+        body["additionalModelRequestFields"] =
+            json!({ "thinking": { "type": "enabled", "budget_tokens": budget } });
+    } else {
+        if let Some(max_tokens) = request.max_tokens {
+            inference.insert("maxTokens".to_string(), json!(max_tokens));
+        }
+        if let Some(temperature) = request.temperature {
+            inference.insert("temperature".to_string(), json!(temperature));
+        }
+        // Converse has no penalties
+        if let Some(top_p) = request.top_p {
+            inference.insert("topP".to_string(), json!(top_p));
+        }
+    }
+    if !inference.is_empty() {
+        body["inferenceConfig"] = Value::Object(inference);
+    }
+
+    // Converse has no tool choice of "none"; such requests get no tools
+    let tools_off =
+        matches!(&request.tool_choice, Some(ToolChoice::String(choice)) if choice == "none");
+    let tools = request
+        .tools
+        .as_ref()
+        .filter(|tools| !tools.is_empty() && !tools_off);
+    if let Some(tools) = tools {
+        let specs: Vec<Value> = tools
+            .iter()
+            .map(|tool| {
+                let schema = tool
+                    .function
+                    .parameters
+                    .clone()
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+                json!({ "toolSpec": {
+                    "name": tool.function.name,
+                    "description": tool.function.description.as_ref()
+                        .unwrap_or(&tool.function.name),
+                    "inputSchema": { "json": schema },
+                } })
+            })
+            .collect();
+        body["toolConfig"] = json!({ "tools": specs });
+    }
+    body
+}
+
+fn usage_of(usage: &Value) -> TokenUsage {
+    let count = |key: &str| {
+        usage
+            .get(key)
+            .and_then(Value::as_u64)
+            .and_then(|count| u32::try_from(count).ok())
+    };
+    TokenUsage {
+        prompt_tokens: count("inputTokens"),
+        completion_tokens: count("outputTokens"),
+        total_tokens: count("totalTokens"),
+        cached_tokens: count("cacheReadInputTokens"),
+        cache_write_tokens: count("cacheWriteInputTokens"),
+    }
+}
+
+fn event_tool_calls(tool_calls: &[ToolCall]) -> Vec<crate::events::ToolCall> {
+    tool_calls
+        .iter()
+        .map(|tc| crate::events::ToolCall {
+            id: tc.id.clone(),
+            name: tc.function.name.clone(),
+            arguments: json_repair::arguments_value(&tc.function.arguments),
+        })
+        .collect()
+}
+
+/// The model id to invoke: models served only through cross-region
+/// inference profiles take their geography as a prefix.
+fn invocable_model_id(summary: &Value, region: &str) -> Option<String> {
+    let model_id = summary.get("modelId")?.as_str()?;
+    let inference_types: Vec<&str> = summary
+        .get("inferenceTypesSupported")
+        .and_then(Value::as_array)
+        .map(|types| types.iter().filter_map(Value::as_str).collect())
+        .unwrap_or_default();
+    if inference_types.contains(&"ON_DEMAND") {
+        return Some(model_id.to_string());
+    }
+    if !inference_types.contains(&"INFERENCE_PROFILE") {
+        return None;
+    }
+    let geography = match region.split('-').next()? {
+        "us" => "us",
+        "eu" => "eu",
+        "ap" => "apac",
+        _ => return None,
+    };
+    Some(format!("{geography}.{model_id}"))
+}
+
+pub struct BedrockProvider {
+    client: Arc<Client>,
+}
+
+impl BedrockProvider {
+    pub const fn new(client: Arc<Client>) -> Self {
+        Self { client }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn signed_request(
+        &self,
+        method: Method,
+        url: &str,
+        query: &[(&str, &str)],
+        body: Option<&Value>,
+        credentials: &AwsCredentials,
+        region: &str,
+        service: &str,
+    ) -> Result<reqwest::RequestBuilder, AppError> {
+        let parsed = reqwest::Url::parse(url)
+            .map_err(|e| AppError::Validation(format!("Invalid Bedrock URL {url}: {e}")))?;
+        let host_name = parsed.host_str().unwrap_or_default();
+        let host = parsed.port().map_or_else(
+            || host_name.to_string(),
+            |port| format!("{host_name}:{port}"),
+        );
+        let body = body
+            .map(serde_json::to_vec)
+            .transpose()?
+            .unwrap_or_default();
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+        let content_type = "application/json";
+        let authorization = sigv4_authorization(
+            credentials,
+            region,
+            service,
+            &SigningRequest {
+                method: method.as_str(),
+                host: &host,
+                path: parsed.path(),
+                query,
+                headers: &[("content-type", content_type)],
+                body: &body,
+            },
+            &amz_date,
+        );
+
+        let mut req_builder = self
+            .client
+            .request(method, parsed)
+            .query(query)
+            .header("content-type", content_type)
+            .header("x-amz-date", &amz_date)
+            .header("authorization", authorization);
+        if let Some(token) = &credentials.session_token {
+            req_builder = req_builder.header("x-amz-security-token", token);
+        }
+        Ok(req_builder.body(body))
+    }
+
+    /// Text models of a region that can be invoked.
+    pub async fn list_models(
+        &self,
+        region: &str,
+        profile: Option<&str>,
+    ) -> Result<Vec<LLMModel>, AppError> {
+        let credentials = AwsCredentials::resolve(profile)?;
+        let url = format!("https://bedrock.{region}.amazonaws.com/foundation-models");
+        let response = self
+            .signed_request(
+                Method::GET,
+                &url,
+                &[("byOutputModality", "TEXT")],
+                None,
+                &credentials,
+                region,
+                "bedrock",
+            )?
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(rate_limit::read_api_error(BEDROCK_API_ERROR, response).await);
+        }
+
+        let json: Value = response.json().await?;
+        Ok(json
+            .get("modelSummaries")
+            .and_then(Value::as_array)
+            .map(|summaries| {
+                summaries
+                    .iter()
+                    .filter_map(|summary| {
+                        let id = invocable_model_id(summary, region)?;
+                        Some(LLMModel {
+                            name: summary
+                                .get("modelName")
+                                .and_then(Value::as_str)
+                                .unwrap_or(id.as_str())
+                                .to_string(),
+                            owned_by: summary
+                                .get("providerName")
+                                .and_then(Value::as_str)
+                                .map(str::to_string),
+                            created: None,
+                            supports_tools: supports_tools(&id),
+                            supports_thinking: supports_thinking(&id),
+                            supports_image_generation: false,
+                            id,
+                        })
+                    })
+                    .collect()
+            })
+            .unwrap_or_default())
+    }
+
+    async fn handle_streaming(
+        response: reqwest::Response,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        output_limits: Option<OutputLimits>,
+    ) -> Result<LLMChatResponse, AppError> {
+        let message_emitter = MessageEmitter::new(app.clone());
+        let tool_emitter = ToolEmitter::new(app.clone());
+
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats = super::start_stream_stats(&app, &chat_id, &message_id);
+        let mut decoder = EventStreamDecoder::default();
+        let mut full_content = String::new();
+        let mut full_reasoning = String::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        // Content block index to its tool call
+        let mut tool_blocks: HashMap<u64, usize> = HashMap::new();
+        let mut finish_reason: Option<String> = None;
+        let mut final_usage: Option<TokenUsage> = None;
+        let mut limiter = OutputLimiter::new(output_limits);
+
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
+            next_item = stream.next() => next_item,
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
+            }
+        } {
+            decoder.push(&chunk);
+            while let Some(message) = decoder.next_message()? {
+                let payload: Value = serde_json::from_slice(&message.payload).unwrap_or_default();
+                if message.header(":message-type") == Some("exception") {
+                    let error = AppError::Llm(format!(
+                        "{BEDROCK_API_ERROR} ({}): {}",
+                        message.header(":exception-type").unwrap_or("exception"),
+                        payload
+                            .get("message")
+                            .and_then(Value::as_str)
+                            .unwrap_or("unknown error")
+                    ));
+                    message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+                    return Err(error);
+                }
+
+                let block = payload
+                    .get("contentBlockIndex")
+                    .and_then(Value::as_u64)
+                    .unwrap_or(0);
+                match message.header(":event-type").unwrap_or_default() {
+                    "contentBlockStart" => {
+                        if let Some(tool_use) = payload.pointer("/start/toolUse") {
+                            tool_blocks.insert(block, tool_calls.len());
+                            tool_calls.push(ToolCall {
+                                id: tool_use
+                                    .get("toolUseId")
+                                    .and_then(Value::as_str)
+                                    .unwrap_or_default()
+                                    .to_string(),
+                                r#type: "function".to_string(),
+                                function: ToolCallFunction {
+                                    name: tool_use
+                                        .get("name")
+                                        .and_then(Value::as_str)
+                                        .unwrap_or_default()
+                                        .to_string(),
+                                    arguments: String::new(),
+                                },
+                            });
+                        }
+                    }
+                    "contentBlockDelta" => {
+                        let delta = &payload["delta"];
+                        if let Some(text) = delta.get("text").and_then(Value::as_str) {
+                            let text = limiter.admit(OutputKind::Content, full_content.len(), text);
+                            full_content.push_str(text);
+                            stats.record_chars(text);
+                            message_emitter.emit_message_chunk(
+                                chat_id.clone(),
+                                message_id.clone(),
+                                text.to_string(),
+                            )?;
+                        }
+                        if let Some(text) = delta
+                            .pointer("/reasoningContent/text")
+                            .and_then(Value::as_str)
+                        {
+                            let text =
+                                limiter.admit(OutputKind::Reasoning, full_reasoning.len(), text);
+                            full_reasoning.push_str(text);
+                            stats.record_chars(text);
+                            message_emitter.emit_thinking_chunk(
+                                chat_id.clone(),
+                                message_id.clone(),
+                                text.to_string(),
+                            )?;
+                        }
+                        if let Some(input) = delta.pointer("/toolUse/input").and_then(Value::as_str)
+                        {
+                            if let Some(&index) = tool_blocks.get(&block) {
+                                let arguments = &mut tool_calls[index].function.arguments;
+                                let input = limiter.admit(
+                                    OutputKind::ToolArguments,
+                                    arguments.len(),
+                                    input,
+                                );
+                                arguments.push_str(input);
+                            }
+                        }
+                    }
+                    "messageStop" => {
+                        finish_reason = payload
+                            .get("stopReason")
+                            .and_then(Value::as_str)
+                            .map(str::to_string);
+                    }
+                    "metadata" => {
+                        if let Some(usage) = payload.get("usage") {
+                            let usage = usage_of(usage);
+                            if let Some(output_tokens) = usage.completion_tokens {
+                                stats.record_output_tokens(u64::from(output_tokens));
+                            }
+                            final_usage = Some(usage);
+                        }
+                    }
+                    _ => {}
+                }
+            }
+
+            if limiter.should_stop("") {
+                break;
+            }
+        }
+
+        stats.stop();
+
+        // A stream that broke off keeps what arrived, but not its unfinished tool calls
+        if stream.finish(!full_content.is_empty() || !full_reasoning.is_empty())? {
+            tool_calls.clear();
+            finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        }
+
+        let capped = limiter.end_at_cap(stream, &mut tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+        if capped {
+            finish_reason = Some(LENGTH_LIMIT.to_string());
+        }
+
+        if !tool_calls.is_empty() && !cancelled {
+            tool_emitter.emit_tool_calls_detected(
+                chat_id.clone(),
+                message_id.clone(),
+                event_tool_calls(&tool_calls),
+            )?;
+        }
+
+        if cancelled {
+            message_emitter.emit_message_cancelled(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+            )?;
+            finish_reason = Some("cancelled".to_string());
+        } else {
+            message_emitter.emit_message_complete(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+                final_usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            )?;
+        }
+
+        Ok(LLMChatResponse {
+            content: full_content,
+            finish_reason,
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            },
+            usage: final_usage,
+            reasoning: if full_reasoning.is_empty() {
+                None
+            } else {
+                Some(full_reasoning)
+            },
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        })
+    }
+
+    async fn handle_non_streaming(
+        response: reqwest::Response,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+    ) -> Result<LLMChatResponse, AppError> {
+        let json: Value = response
+            .json()
+            .await
+            .map_err(|e| AppError::Generic(format!("Failed to parse response: {e}")))?;
+
+        let mut content = String::new();
+        let mut reasoning = String::new();
+        let mut tool_calls = Vec::new();
+        let blocks = json
+            .pointer("/output/message/content")
+            .and_then(Value::as_array)
+            .map_or(&[][..], Vec::as_slice);
+        for block in blocks {
+            if let Some(text) = block.get("text").and_then(Value::as_str) {
+                content.push_str(text);
+            }
+            if let Some(text) = block
+                .pointer("/reasoningContent/reasoningText/text")
+                .and_then(Value::as_str)
+            {
+                reasoning.push_str(text);
+            }
+            if let Some(tool_use) = block.get("toolUse") {
+                tool_calls.push(ToolCall {
+                    id: tool_use
+                        .get("toolUseId")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: tool_use
+                            .get("name")
+                            .and_then(Value::as_str)
+                            .unwrap_or_default()
+                            .to_string(),
+                        arguments: tool_use
+                            .get("input")
+                            .map_or_else(|| "{}".to_string(), Value::to_string),
+                    },
+                });
+            }
+        }
+        let usage = json.get("usage").map(usage_of);
+
+        if !tool_calls.is_empty() {
+            ToolEmitter::new(app.clone()).emit_tool_calls_detected(
+                chat_id.clone(),
+                message_id.clone(),
+                event_tool_calls(&tool_calls),
+            )?;
+        }
+        MessageEmitter::new(app).emit_message_complete(
+            chat_id,
+            message_id,
+            content.clone(),
+            usage.as_ref().map(|u| EventTokenUsage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+            }),
+        )?;
+
+        Ok(LLMChatResponse {
+            content,
+            finish_reason: json
+                .get("stopReason")
+                .and_then(Value::as_str)
+                .map(str::to_string),
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            },
+            usage,
+            reasoning: (!reasoning.is_empty()).then_some(reasoning),
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        })
+    }
+}
+
+/// Region of a connection: its own setting, else its endpoint's.
+fn connection_region<'a>(
+    base_url: &'a str,
+    config: Option<&'a ConnectionConfig>,
+) -> Result<&'a str, AppError> {
+    config
+        .and_then(|config| config.aws_region.as_deref())
+        .or_else(|| region_of(base_url))
+        .ok_or_else(|| {
+            AppError::Validation(format!("No AWS region for Bedrock endpoint {base_url}"))
+        })
+}
+
+#[async_trait]
+impl LLMProvider for BedrockProvider {
+    async fn fetch_models(
+        &self,
+        base_url: &str,
+        _api_key: Option<&str>,
+    ) -> Result<Vec<LLMModel>, AppError> {
+        self.list_models(connection_region(base_url, None)?, None)
+            .await
+    }
+
+    async fn chat(
+        &self,
+        base_url: &str,
+        _api_key: Option<&str>,
+        request: LLMChatRequest,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+    ) -> Result<LLMChatResponse, AppError> {
+        let config = request.connection_config.as_ref();
+        let region = connection_region(base_url, config)?;
+        let credentials =
+            AwsCredentials::resolve(config.and_then(|config| config.aws_profile.as_deref()))?;
+        let action = if request.stream {
+            "converse-stream"
+        } else {
+            "converse"
+        };
+        let url = format!(
+            "{}/model/{}/{action}",
+            runtime_url(region),
+            uri_encode(&request.model, true)
+        );
+        let response = self
+            .signed_request(
+                Method::POST,
+                &url,
+                &[],
+                Some(&converse_body(&request)),
+                &credentials,
+                region,
+                "bedrock",
+            )?
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = rate_limit::read_api_error(BEDROCK_API_ERROR, response).await;
+            MessageEmitter::new(app.clone()).emit_api_error(
+                chat_id.clone(),
+                message_id.clone(),
+                &error,
+            )?;
+            return Err(error);
+        }
+
+        if request.stream {
+            Self::handle_streaming(
+                response,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                request.output_limits,
+            )
+            .await
+        } else {
+            Self::handle_non_streaming(response, chat_id, message_id, app).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn bedrock_signs_with_sigv4_and_reads_profiles() {
+        use crate::services::llm::providers::bedrock::{
+            region_of, runtime_url, sigv4_authorization, AwsCredentials, SigningRequest,
+        };
+
+        // The IAM ListUsers example of the AWS Signature Version 4 docs
+        let credentials = AwsCredentials {
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+        let authorization = sigv4_authorization(
+            &credentials,
+            "us-east-1",
+            "iam",
+            &SigningRequest {
+                method: "GET",
+                host: "iam.amazonaws.com",
+                path: "/",
+                query: &[("Action", "ListUsers"), ("Version", "2010-05-08")],
+                headers: &[(
+                    "Content-Type",
+                    "application/x-www-form-urlencoded; charset=utf-8",
+                )],
+                body: b"",
+            },
+            "20150830T123600Z",
+        );
+        assert_eq!(
+            authorization,
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+
+        let config = "[default]\naws_access_key_id = AKIADEFAULT\naws_secret_access_key = default-secret\n\n\
+                      [profile work]\n# a comment\naws_access_key_id=AKIAWORK\n\
+                      aws_secret_access_key=work-secret\naws_session_token=work-token\n";
+        let work = AwsCredentials::from_profile_text(config, "work").unwrap();
+        assert_eq!(work.access_key_id, "AKIAWORK");
+        assert_eq!(work.session_token.as_deref(), Some("work-token"));
+        let default = AwsCredentials::from_profile_text(config, "default").unwrap();
+        assert_eq!(default.secret_access_key, "default-secret");
+        assert!(default.session_token.is_none());
+        assert!(AwsCredentials::from_profile_text(config, "missing").is_none());
+
+        assert_eq!(
+            region_of(&runtime_url("eu-central-1")),
+            Some("eu-central-1")
+        );
+        assert_eq!(region_of("https://api.openai.com/v1"), None);
+    }
+
+    #[test]
+    fn bedrock_decodes_event_streams_and_builds_converse_bodies() {
+        use crate::models::llm_types::{
+            AssistantContent, ChatCompletionTool, ChatCompletionToolFunction, ChatMessage,
+            LLMChatRequest, ToolCall, ToolCallFunction, UserContent,
+        };
+        use crate::services::llm::providers::bedrock::{converse_body, EventStreamDecoder};
+
+        fn frame(headers: &[(&str, &str)], payload: &[u8]) -> Vec<u8> {
+            let mut encoded = Vec::new();
+            for (name, value) in headers {
+                encoded.push(u8::try_from(name.len()).unwrap());
+                encoded.extend_from_slice(name.as_bytes());
+                encoded.push(7);
+                encoded.extend_from_slice(&u16::try_from(value.len()).unwrap().to_be_bytes());
+                encoded.extend_from_slice(value.as_bytes());
+            }
+            // A byte header, skipped
+            encoded.extend_from_slice(&[4, b'f', b'l', b'a', b'g', 2, 1]);
+            let total = 12 + encoded.len() + payload.len() + 4;
+            let mut message = Vec::new();
+            message.extend_from_slice(&u32::try_from(total).unwrap().to_be_bytes());
+            message.extend_from_slice(&u32::try_from(encoded.len()).unwrap().to_be_bytes());
+            message.extend_from_slice(&[0; 4]);
+            message.extend_from_slice(&encoded);
+            message.extend_from_slice(payload);
+            message.extend_from_slice(&[0; 4]);
+            message
+        }
+
+        let mut bytes = frame(
+            &[
+                (":event-type", "contentBlockDelta"),
+                (":message-type", "event"),
+            ],
+            br#"{"contentBlockIndex":0,"delta":{"text":"Hi"}}"#,
+        );
+        bytes.extend(frame(
+            &[(":event-type", "messageStop")],
+            br#"{"stopReason":"end_turn"}"#,
+        ));
+        let mut decoder = EventStreamDecoder::default();
+        // Split mid-prelude: nothing is whole yet
+        decoder.push(&bytes[..7]);
+        assert!(decoder.next_message().unwrap().is_none());
+        decoder.push(&bytes[7..]);
+        let first = decoder.next_message().unwrap().unwrap();
+        assert_eq!(first.headers[":event-type"], "contentBlockDelta");
+        assert!(!first.headers.contains_key("flag"));
+        assert_eq!(
+            first.payload,
+            br#"{"contentBlockIndex":0,"delta":{"text":"Hi"}}"#.to_vec()
+        );
+        let second = decoder.next_message().unwrap().unwrap();
+        assert_eq!(second.headers[":event-type"], "messageStop");
+        assert!(decoder.next_message().unwrap().is_none());
+
+        let request = LLMChatRequest {
+            model: "anthropic.claude-3-7-sonnet-20250219-v1:0".to_string(),
+            messages: vec![
+                ChatMessage::System {
+                    content: "Be brief".to_string(),
+                },
+                ChatMessage::User {
+                    content: UserContent::Text("Weather in Hanoi?".to_string()),
+                },
+                ChatMessage::Assistant {
+                    content: AssistantContent::Text(String::new()),
+                    tool_calls: Some(vec![ToolCall {
+                        id: "tool_1".to_string(),
+                        r#type: "function".to_string(),
+                        function: ToolCallFunction {
+                            name: "weather".to_string(),
+                            arguments: r#"{"city":"Hanoi"}"#.to_string(),
+                        },
+                    }]),
+                },
+                ChatMessage::Tool {
+                    content: "31C".to_string(),
+                    tool_call_id: "tool_1".to_string(),
+                },
+                ChatMessage::User {
+                    content: UserContent::Text("Thanks".to_string()),
+                },
+            ],
+            temperature: Some(0.2),
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: Some(vec![ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: ChatCompletionToolFunction {
+                    name: "weather".to_string(),
+                    description: None,
+                    parameters: None,
+                },
+            }]),
+            tool_choice: None,
+            reasoning_effort: Some("low".to_string()),
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed: None,
+            output_limits: None,
+        };
+        let body = converse_body(&request);
+        assert_eq!(body["system"], serde_json::json!([{ "text": "Be brief" }]));
+        let messages = body["messages"].as_array().unwrap();
+        // The tool result and the next question share one user turn
+        assert_eq!(messages.len(), 3);
+        assert_eq!(
+            messages[1]["content"][0]["toolUse"]["input"]["city"],
+            "Hanoi"
+        );
+        assert_eq!(
+            messages[2]["content"][0]["toolResult"]["toolUseId"],
+            "tool_1"
+        );
+        assert_eq!(messages[2]["content"][1]["text"], "Thanks");
+        assert_eq!(
+            body["additionalModelRequestFields"]["thinking"]["budget_tokens"],
+            1024
+        );
+        assert_eq!(body["inferenceConfig"]["maxTokens"], 1024 + 4096);
+        assert!(body["inferenceConfig"].get("temperature").is_none());
+        assert_eq!(
+            body["toolConfig"]["tools"][0]["toolSpec"]["name"],
+            "weather"
+        );
+    }
+}
@@ -1,17 +1,169 @@
 use super::LLMProvider;
 use crate::error::AppError;
-use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage};
+use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage, ToolEmitter};
+use crate::features::attachment::{AttachmentService, GOOGLE_FILE_TTL_MS};
+use crate::features::context_cache::ContextCacheService;
+use crate::features::llm_connection::config::REASONING_EFFORT_NONE;
 use crate::models::llm_types::{
     AssistantContent, ChatMessage, ContentPart, InlineData, LLMChatRequest, LLMChatResponse,
     LLMModel, TokenUsage, ToolCall, ToolCallFunction, UserContent,
 };
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit::{self, RateLimitInfo};
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
 use async_trait::async_trait;
 use base64::Engine as _;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::json;
+use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::AppHandle;
+use tauri::{AppHandle, Manager};
+
+/// Lifetime of a context cache on Google's side.
+const CONTEXT_CACHE_TTL_SECS: i64 = 3600;
+/// Prefixes smaller than this (~4k tokens, Gemini's minimum for explicit caching)
+/// are sent in full.
+const CONTEXT_CACHE_MIN_PREFIX_CHARS: usize = 16_384;
+/// Thought signature Gemini 3 accepts on replayed function calls it did
+/// not sign itself.
+const SKIP_THOUGHT_SIGNATURE: &str = "skip_thought_signature_validator";
+/// Inputs `batchEmbedContents` takes in one request
+const EMBED_BATCH_LIMIT: usize = 100;
+
+/// Tool calls of a streamed Gemini answer as they form. A `functionCall`
+/// part usually arrives whole; with streamed arguments, the first part names
+/// the call and sets `willContinue`, and later ones carry `partialArgs`, each
+/// a value at a JSON path, strings possibly split over several parts.
+#[derive(Debug, Default)]
+pub struct FunctionCallStream {
+    calls: Vec<ToolCall>,
+    args: Vec<serde_json::Value>,
+    // Call still receiving partial arguments
+    open: Option<usize>,
+    // Path of a string value still being continued
+    open_string: Option<String>,
+}
+
+impl FunctionCallStream {
+    /// Take one `functionCall` part.
+    pub fn push(&mut self, function_call: &serde_json::Value) {
+        let name = function_call
+            .get("name")
+            .and_then(serde_json::Value::as_str)
+            .filter(|name| !name.is_empty());
+        let index = match (self.open, name) {
+            (Some(index), None) => index,
+            _ => {
+                self.open_string = None;
+                self.calls.push(ToolCall {
+                    id: function_call
+                        .get("id")
+                        .and_then(serde_json::Value::as_str)
+                        .filter(|id| !id.is_empty())
+                        .map_or_else(|| format!("call_{}", uuid::Uuid::new_v4()), str::to_string),
+                    r#type: "function".to_string(),
+                    function: ToolCallFunction {
+                        name: name.unwrap_or_default().to_string(),
+                        arguments: String::new(),
+                    },
+                });
+                self.args.push(json!({}));
+                self.calls.len() - 1
+            }
+        };
+
+        if let Some(args) = function_call.get("args").filter(|args| args.is_object()) {
+            self.args[index] = args.clone();
+        }
+        for partial in function_call
+            .get("partialArgs")
+            .and_then(serde_json::Value::as_array)
+            .into_iter()
+            .flatten()
+        {
+            self.apply_partial(index, partial);
+        }
+        self.open = function_call
+            .get("willContinue")
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or(false)
+            .then_some(index);
+        self.calls[index].function.arguments = self.args[index].to_string();
+    }
+
+    fn apply_partial(&mut self, index: usize, partial: &serde_json::Value) {
+        let Some(path) = partial.get("jsonPath").and_then(serde_json::Value::as_str) else {
+            return;
+        };
+        let continues = partial
+            .get("willContinue")
+            .and_then(serde_json::Value::as_bool)
+            .unwrap_or(false);
+        if let Some(text) = partial.get("stringValue").and_then(|v| v.as_str()) {
+            let target = Self::slot(&mut self.args[index], path);
+            match target {
+                serde_json::Value::String(kept) if self.open_string.as_deref() == Some(path) => {
+                    kept.push_str(text);
+                }
+                _ => *target = json!(text),
+            }
+            self.open_string = continues.then(|| path.to_string());
+            return;
+        }
+        let value = partial
+            .get("numberValue")
+            .or_else(|| partial.get("boolValue"))
+            .cloned()
+            .unwrap_or_default();
+        self.open_string = None;
+        *Self::slot(&mut self.args[index], path) = value;
+    }
+
+    /// The value at a `$.a.b[0]` path, created as needed.
+    fn slot<'v>(root: &'v mut serde_json::Value, path: &str) -> &'v mut serde_json::Value {
+        let mut slot = root;
+        for segment in path
+            .trim_start_matches('$')
+            .split('.')
+            .filter(|s| !s.is_empty())
+        {
+            let (key, indices) = segment.split_once('[').unwrap_or((segment, ""));
+            if !key.is_empty() {
+                if !slot.is_object() {
+                    *slot = json!({});
+                }
+                slot = slot
+                    .as_object_mut()
+                    .expect("just made an object")
+                    .entry(key)
+                    .or_insert(serde_json::Value::Null);
+            }
+            for index in indices
+                .split('[')
+                .filter_map(|i| i.trim_end_matches(']').parse::<usize>().ok())
+            {
+                if !slot.is_array() {
+                    *slot = json!([]);
+                }
+                let items = slot.as_array_mut().expect("just made an array");
+                if items.len() <= index {
+                    items.resize(index + 1, serde_json::Value::Null);
+                }
+                slot = &mut items[index];
+            }
+        }
+        slot
+    }
+
+    pub fn calls(&self) -> &[ToolCall] {
+        &self.calls
+    }
+
+    pub fn into_calls(self) -> Vec<ToolCall> {
+        self.calls
+    }
+}
 
 #[derive(Clone)]
 pub struct GoogleProvider {
@@ -211,6 +363,60 @@ impl GoogleProvider {
         Ok((file_uri, file_name))
     }
 
+    /// Upload a file to Google File API, reusing an earlier upload of the same
+    /// content (by hash) while Google still retains it. Returns the file URI.
+    async fn upload_file_cached(
+        &self,
+        app: &AppHandle,
+        api_key: &str,
+        base_url: &str,
+        data: &str,
+        mime_type: &str,
+        content_hash: Option<&str>,
+    ) -> Result<String, AppError> {
+        let state = app.try_state::<crate::state::AppState>();
+        let attachment_service = state.as_ref().map(|s| s.attachment_service.clone());
+        let provider_key = super::account_key(base_url, api_key);
+
+        if let (Some(service), Some(hash)) = (&attachment_service, content_hash) {
+            match service.get_remote_upload(hash, &provider_key) {
+                Ok(Some(upload)) => {
+                    tracing::info!(uri = %upload.file_uri, "Reusing cached Google file upload");
+                    return Ok(upload.file_uri);
+                }
+                Ok(None) => {}
+                Err(e) => tracing::warn!(error = %e, "Failed to read upload cache"),
+            }
+        }
+
+        let (file_uri, file_name) =
+            Self::upload_file_to_google(&self.client, api_key, base_url, data, mime_type).await?;
+
+        // If it's a video, wait for processing
+        if Self::is_video_mime_type(mime_type) {
+            if let Err(e) =
+                Self::wait_for_file_active(&self.client, api_key, base_url, &file_name).await
+            {
+                tracing::warn!(error = %e, "Failed to wait for video processing");
+                // Continue anyway, might work
+            }
+        }
+
+        if let (Some(service), Some(hash)) = (&attachment_service, content_hash) {
+            if let Err(e) = service.save_remote_upload(
+                hash,
+                &provider_key,
+                &file_uri,
+                &file_name,
+                GOOGLE_FILE_TTL_MS,
+            ) {
+                tracing::warn!(error = %e, "Failed to cache Google file upload");
+            }
+        }
+
+        Ok(file_uri)
+    }
+
     /// Wait for a video file to be processed (state becomes ACTIVE)
     async fn wait_for_file_active(
         client: &Client,
@@ -297,6 +503,46 @@ impl GoogleProvider {
         (supports_tools, supports_thinking, supports_image_generation)
     }
 
+    /// Body of a `batchEmbedContents` request embedding `inputs` with `model`.
+    pub fn embed_payload(model: &str, inputs: &[String]) -> serde_json::Value {
+        let model = format!("models/{}", model.strip_prefix("models/").unwrap_or(model));
+        json!({
+            "requests": inputs
+                .iter()
+                .map(|input| json!({
+                    "model": model,
+                    "content": { "parts": [{ "text": input }] },
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// The vectors of a `batchEmbedContents` response, in request order.
+    pub fn parse_embeddings(
+        response: &serde_json::Value,
+        expected: usize,
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        let embeddings = response
+            .get("embeddings")
+            .and_then(|e| e.as_array())
+            .ok_or_else(|| AppError::Llm("Google returned no embeddings".to_string()))?;
+        if embeddings.len() != expected {
+            return Err(AppError::Llm(format!(
+                "Google returned {} embeddings for {expected} inputs",
+                embeddings.len()
+            )));
+        }
+        embeddings
+            .iter()
+            .map(|embedding| {
+                serde_json::from_value::<Vec<f32>>(
+                    embedding.get("values").cloned().unwrap_or_default(),
+                )
+                .map_err(|e| AppError::Llm(format!("Malformed Google embedding: {e}")))
+            })
+            .collect()
+    }
+
     fn get_fallback_models() -> Vec<LLMModel> {
         vec![
             LLMModel {
@@ -347,42 +593,261 @@ impl GoogleProvider {
         ]
     }
 
+    /// A `functionCall` part replaying a call the model made. Gemini 3
+    /// checks the thought signature of replayed calls; calls made elsewhere
+    /// or not recorded with one carry the documented bypass value.
+    pub fn function_call_part(call: &ToolCall, model: &str) -> serde_json::Value {
+        let mut part = json!({
+            "functionCall": {
+                "name": call.function.name,
+                "args": json_repair::arguments_value(&call.function.arguments),
+            }
+        });
+        if model.starts_with("gemini-3") {
+            part["thoughtSignature"] = json!(SKIP_THOUGHT_SIGNATURE);
+        }
+        part
+    }
+
+    /// A `functionResponse` part carrying a tool's result. Gemini takes an
+    /// object: a result that is one is sent as is, any other under `result`.
+    pub fn function_response_part(name: &str, content: &str) -> serde_json::Value {
+        let response = serde_json::from_str::<serde_json::Value>(content)
+            .ok()
+            .filter(serde_json::Value::is_object)
+            .unwrap_or_else(|| json!({ "result": content }));
+        json!({
+            "functionResponse": {
+                "name": name,
+                "response": response,
+            }
+        })
+    }
+
+    fn is_function_responses(content: &serde_json::Value) -> bool {
+        content["role"] == "user"
+            && content["parts"].as_array().is_some_and(|parts| {
+                !parts.is_empty() && parts.iter().all(|p| p.get("functionResponse").is_some())
+            })
+    }
+
+    fn parse_usage_metadata(usage: &serde_json::Value) -> TokenUsage {
+        let count = |field: &str| {
+            usage
+                .get(field)
+                .and_then(serde_json::Value::as_u64)
+                .map(|v| v as u32)
+        };
+
+        TokenUsage {
+            prompt_tokens: count("promptTokenCount"),
+            completion_tokens: count("candidatesTokenCount"),
+            total_tokens: count("totalTokenCount"),
+            cached_tokens: count("cachedContentTokenCount"),
+            cache_write_tokens: None,
+        }
+    }
+
+    /// The part of a request that stays stable across turns and can be served
+    /// from a context cache: system instruction and tool declarations.
+    fn cacheable_prefix(body: &serde_json::Value) -> serde_json::Value {
+        let mut prefix = serde_json::Map::new();
+        for key in ["systemInstruction", "tools"] {
+            if let Some(value) = body.get(key) {
+                prefix.insert(key.to_string(), value.clone());
+            }
+        }
+        serde_json::Value::Object(prefix)
+    }
+
+    /// Replace the cacheable prefix with a reference to the cached content.
+    fn with_cached_content(
+        body: &serde_json::Value,
+        cache_name: Option<&str>,
+    ) -> serde_json::Value {
+        let mut body = body.clone();
+        if let (Some(name), Some(obj)) = (cache_name, body.as_object_mut()) {
+            obj.remove("systemInstruction");
+            obj.remove("tools");
+            obj.insert("cachedContent".to_string(), json!(name));
+        }
+        body
+    }
+
+    fn is_missing_cache_error(error_text: &str) -> bool {
+        let lower = error_text.to_lowercase();
+        (lower.contains("cachedcontent") || lower.contains("cached content"))
+            && (lower.contains("not found")
+                || lower.contains("expired")
+                || lower.contains("permission denied"))
+    }
+
+    /// Return the name of a cached content covering this request's stable prefix,
+    /// creating (or, with `force_recreate`, replacing) it as needed. Caches are
+    /// kept per prefix and model and left to expire. Returns None when the
+    /// workspace has not opted in (`scope` is None), the prefix is too small to
+    /// be worth caching or caching fails, in which case the request is sent in
+    /// full.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn resolve_context_cache(
+        &self,
+        service: &ContextCacheService,
+        scope: Option<&str>,
+        base_url: &str,
+        api_key: &str,
+        model: &str,
+        body: &serde_json::Value,
+        force_recreate: bool,
+    ) -> Option<String> {
+        let scope = scope?;
+        let prefix = Self::cacheable_prefix(body);
+        let prefix_str = prefix.to_string();
+        if prefix_str.len() < CONTEXT_CACHE_MIN_PREFIX_CHARS {
+            return None;
+        }
+
+        let provider_key = super::account_key(base_url, api_key);
+        let prefix_hash = AttachmentService::hash_bytes(format!("{model}|{prefix_str}").as_bytes());
+
+        match service.get(scope, &provider_key, &prefix_hash) {
+            Ok(Some(cached)) if !force_recreate => return Some(cached.cache_name),
+            // Google no longer has it
+            Ok(Some(_)) => {
+                if let Err(e) = service.invalidate(scope, &provider_key, &prefix_hash) {
+                    tracing::warn!(error = %e, "Failed to invalidate context cache");
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, "Failed to read context cache"),
+        }
+
+        match self
+            .create_cached_content(base_url, api_key, model, &prefix)
+            .await
+        {
+            Ok(cache_name) => {
+                if let Err(e) = service.save(
+                    scope,
+                    &provider_key,
+                    &prefix_hash,
+                    &cache_name,
+                    model,
+                    CONTEXT_CACHE_TTL_SECS * 1000,
+                ) {
+                    tracing::warn!(error = %e, "Failed to store context cache");
+                }
+                Some(cache_name)
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, "Failed to create context cache");
+                None
+            }
+        }
+    }
+
+    async fn create_cached_content(
+        &self,
+        base_url: &str,
+        api_key: &str,
+        model: &str,
+        prefix: &serde_json::Value,
+    ) -> Result<String, AppError> {
+        let url = format!("{}/cachedContents", base_url.trim_end_matches('/'));
+
+        let mut payload = prefix.clone();
+        payload["model"] = json!(format!("models/{model}"));
+        payload["ttl"] = json!(format!("{CONTEXT_CACHE_TTL_SECS}s"));
+
+        let response = self
+            .client
+            .post(&url)
+            .query(&[("key", api_key)])
+            .json(&payload)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(
+                rate_limit::read_api_error("Failed to create cached content", response).await,
+            );
+        }
+
+        let json: serde_json::Value = response.json().await?;
+        let name = json
+            .get("name")
+            .and_then(|n| n.as_str())
+            .ok_or_else(|| AppError::Llm("No name in cached content response".to_string()))?;
+
+        tracing::info!(name = %name, "Created context cache");
+        Ok(name.to_string())
+    }
+
+    /// POST `body` to `url`; `None` when the turn is cancelled before the
+    /// answer starts.
+    async fn send_cancellable(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+        cancellation_rx: &mut Option<tokio::sync::broadcast::Receiver<()>>,
+    ) -> Result<Option<reqwest::Response>, AppError> {
+        tokio::select! {
+            response = self.client.post(url).json(body).send() => Ok(Some(response?)),
+            () = super::wait_for_cancellation(cancellation_rx) => Ok(None),
+        }
+    }
+
+    /// The empty answer of a turn cancelled before the model answered.
+    fn cancelled_before_answer(
+        app: &AppHandle,
+        chat_id: String,
+        message_id: String,
+    ) -> Result<LLMChatResponse, AppError> {
+        MessageEmitter::new(app.clone()).emit_message_cancelled(
+            chat_id,
+            message_id,
+            String::new(),
+        )?;
+        Ok(LLMChatResponse {
+            content: String::new(),
+            finish_reason: Some("cancelled".to_string()),
+            tool_calls: None,
+            usage: None,
+            reasoning: None,
+            images: None,
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
+        })
+    }
+
     async fn handle_streaming(
         &self,
-        req_builder: reqwest::RequestBuilder,
+        response: reqwest::Response,
         chat_id: String,
         message_id: String,
         app: AppHandle,
         mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        output_limits: Option<OutputLimits>,
     ) -> Result<LLMChatResponse, AppError> {
-        let response = req_builder.send().await?;
         let message_emitter = MessageEmitter::new(app.clone());
+        let tool_emitter = ToolEmitter::new(app.clone());
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("Google API error ({status}): {error_text}");
-
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-
-            return Err(AppError::Llm(error_msg));
+            let error = rate_limit::read_api_error("Google API error", response).await;
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
-        let mut stream = response.bytes_stream();
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats = super::start_stream_stats(&app, &chat_id, &message_id);
 
         let mut full_content = String::new();
         let mut full_reasoning = String::new();
         let mut buffer = String::new();
         let mut final_usage: Option<TokenUsage> = None;
-        let mut final_tool_calls: Vec<ToolCall> = Vec::new();
+        let mut function_calls = FunctionCallStream::default();
         let mut final_images: Vec<InlineData> = Vec::new();
+        let mut limiter = OutputLimiter::new(output_limits);
 
         // Need to parse a JSON array stream essentially.
         // But Google sends valid JSON array chunks? No, usually it sends partial JSON or a stream of JSON objects.
@@ -393,23 +858,15 @@ impl GoogleProvider {
         // A common pattern for Google REST API streaming is that it returns a JSON array: `[{...}, {...}]`
         // We need to robustly parse this.
 
-        while let Some(item) = tokio::select! {
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
             next_item = stream.next() => next_item,
-            () = async {
-                if let Some(ref mut rx) = cancellation_rx {
-                    let _ = rx.recv().await;
-                }
-                futures::future::pending::<()>().await;
-            }, if cancellation_rx.is_some() => {
-                message_emitter.emit_message_error(
-                    chat_id.clone(),
-                    message_id.clone(),
-                    "Message cancelled by user".to_string(),
-                )?;
-                return Err(AppError::Cancelled);
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
             }
         } {
-            let chunk = item.map_err(|e| AppError::Generic(format!("Stream error: {e}")))?;
             let text = String::from_utf8_lossy(&chunk);
             buffer.push_str(&text);
 
@@ -463,7 +920,13 @@ impl GoogleProvider {
                                             {
                                                 if is_thought {
                                                     // This is thinking content
+                                                    let text = limiter.admit(
+                                                        OutputKind::Reasoning,
+                                                        full_reasoning.len(),
+                                                        text,
+                                                    );
                                                     full_reasoning.push_str(text);
+                                                    stats.record_chars(text);
                                                     message_emitter.emit_thinking_chunk(
                                                         chat_id.clone(),
                                                         message_id.clone(),
@@ -471,7 +934,13 @@ impl GoogleProvider {
                                                     )?;
                                                 } else {
                                                     // This is regular content
+                                                    let text = limiter.admit(
+                                                        OutputKind::Content,
+                                                        full_content.len(),
+                                                        text,
+                                                    );
                                                     full_content.push_str(text);
+                                                    stats.record_chars(text);
                                                     message_emitter.emit_message_chunk(
                                                         chat_id.clone(),
                                                         message_id.clone(),
@@ -502,60 +971,53 @@ impl GoogleProvider {
                             }
                         }
 
-                        // Parse tool calls if any
-                        if let Some(candidates) =
-                            json_val.get("candidates").and_then(|c| c.as_array())
-                        {
-                            for candidate in candidates {
-                                if let Some(content) = candidate.get("content") {
-                                    if let Some(parts) =
-                                        content.get("parts").and_then(|p| p.as_array())
-                                    {
-                                        for part in parts {
-                                            if let Some(function_call) = part.get("functionCall") {
-                                                let name = function_call
-                                                    .get("name")
-                                                    .and_then(|n| n.as_str())
-                                                    .unwrap_or_default();
-                                                let default_args = json!({});
-                                                let args = function_call
-                                                    .get("args")
-                                                    .unwrap_or(&default_args);
-                                                let arguments = args.to_string();
-
-                                                let id = format!("call_{}", uuid::Uuid::new_v4());
-
-                                                final_tool_calls.push(ToolCall {
-                                                    id,
-                                                    function: ToolCallFunction {
-                                                        name: name.to_string(),
-                                                        arguments,
-                                                    },
-                                                    r#type: "function".to_string(),
-                                                });
-                                            }
-                                        }
-                                    }
-                                }
+                        // Tool calls form over the parts; the UI gets them
+                        // from the first part on and again as they grow
+                        let mut tool_calls_changed = false;
+                        let function_call_parts = json_val
+                            .get("candidates")
+                            .and_then(|c| c.as_array())
+                            .into_iter()
+                            .flatten()
+                            .filter_map(|candidate| {
+                                candidate
+                                    .pointer("/content/parts")
+                                    .and_then(|p| p.as_array())
+                            })
+                            .flatten()
+                            .filter_map(|part| part.get("functionCall"));
+                        for function_call in function_call_parts {
+                            function_calls.push(function_call);
+                            tool_calls_changed = true;
+                        }
+                        if tool_calls_changed {
+                            let within_cap = function_calls.calls().iter().all(|call| {
+                                let arguments = &call.function.arguments;
+                                limiter.admit(OutputKind::ToolArguments, 0, arguments).len()
+                                    == arguments.len()
+                            });
+                            if within_cap {
+                                tool_emitter.emit_tool_calls_detected(
+                                    chat_id.clone(),
+                                    message_id.clone(),
+                                    function_calls
+                                        .calls()
+                                        .iter()
+                                        .map(|tc| crate::events::ToolCall {
+                                            id: tc.id.clone(),
+                                            name: tc.function.name.clone(),
+                                            arguments: json_repair::arguments_value(
+                                                &tc.function.arguments,
+                                            ),
+                                        })
+                                        .collect(),
+                                )?;
                             }
                         }
 
                         // Parse usage
                         if let Some(usage) = json_val.get("usageMetadata") {
-                            final_usage = Some(TokenUsage {
-                                prompt_tokens: usage
-                                    .get("promptTokenCount")
-                                    .and_then(serde_json::Value::as_u64)
-                                    .map(|v| v as u32),
-                                completion_tokens: usage
-                                    .get("candidatesTokenCount")
-                                    .and_then(serde_json::Value::as_u64)
-                                    .map(|v| v as u32),
-                                total_tokens: usage
-                                    .get("totalTokenCount")
-                                    .and_then(serde_json::Value::as_u64)
-                                    .map(|v| v as u32),
-                            });
+                            final_usage = Some(Self::parse_usage_metadata(usage));
                         }
                     }
 
@@ -566,23 +1028,58 @@ impl GoogleProvider {
                     break;
                 }
             }
+
+            if limiter.should_stop(&buffer) {
+                break;
+            }
         }
 
-        // Emit complete
-        message_emitter.emit_message_complete(
-            chat_id.clone(),
-            message_id.clone(),
-            full_content.clone(),
-            final_usage.as_ref().map(|u| EventTokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
+        stats.stop();
+        let mut final_tool_calls = function_calls.into_calls();
+
+        // A stream that broke off keeps what arrived, but not its unfinished tool calls
+        let interrupted = stream.finish(
+            !full_content.is_empty() || !full_reasoning.is_empty() || !final_images.is_empty(),
         )?;
+        if interrupted {
+            final_tool_calls.clear();
+        }
+
+        let limit_reached = limiter.end_at_cap(stream, &mut final_tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+
+        if cancelled {
+            message_emitter.emit_message_cancelled(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+            )?;
+        } else {
+            // Emit complete
+            message_emitter.emit_message_complete(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+                final_usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            )?;
+        }
 
         Ok(LLMChatResponse {
             content: full_content,
-            finish_reason: None,
+            finish_reason: cancelled
+                .then(|| "cancelled".to_string())
+                .or_else(|| limit_reached.then(|| LENGTH_LIMIT.to_string()))
+                .or_else(|| interrupted.then(|| STREAM_INTERRUPTED.to_string())),
 
             tool_calls: if final_tool_calls.is_empty() {
                 None
@@ -600,38 +1097,25 @@ impl GoogleProvider {
             } else {
                 Some(final_images)
             },
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
         })
     }
 
     async fn handle_non_streaming(
         &self,
-        req_builder: reqwest::RequestBuilder,
+        response: reqwest::Response,
         chat_id: String,
         message_id: String,
         app: AppHandle,
     ) -> Result<LLMChatResponse, AppError> {
-        let response = req_builder
-            .send()
-            .await
-            .map_err(|e| AppError::Generic(format!("HTTP request failed: {e}")))?;
-
         let message_emitter = MessageEmitter::new(app.clone());
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("Google API error ({status}): {error_text}");
-
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-
-            return Err(AppError::Generic(error_msg));
+            let error = rate_limit::read_api_error("Google API error", response).await;
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
         let json: serde_json::Value = response
@@ -704,20 +1188,7 @@ impl GoogleProvider {
         }
 
         // Parse usage
-        let usage = json.get("usageMetadata").map(|u| TokenUsage {
-            prompt_tokens: u
-                .get("promptTokenCount")
-                .and_then(serde_json::Value::as_u64)
-                .map(|v| v as u32),
-            completion_tokens: u
-                .get("candidatesTokenCount")
-                .and_then(serde_json::Value::as_u64)
-                .map(|v| v as u32),
-            total_tokens: u
-                .get("totalTokenCount")
-                .and_then(serde_json::Value::as_u64)
-                .map(|v| v as u32),
-        });
+        let usage = json.get("usageMetadata").map(Self::parse_usage_metadata);
 
         message_emitter.emit_message_complete(
             chat_id.clone(),
@@ -750,6 +1221,9 @@ impl GoogleProvider {
             } else {
                 Some(images)
             },
+            parameter_notes: Vec::new(),
+            model: None,
+            system_fingerprint: None,
         })
     }
 }
@@ -830,6 +1304,39 @@ impl LLMProvider for GoogleProvider {
         Ok(Self::get_fallback_models())
     }
 
+    async fn embed(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        let model = model.strip_prefix("models/").unwrap_or(model);
+        let url = format!(
+            "{}/models/{}:batchEmbedContents?key={}",
+            base_url.trim_end_matches('/'),
+            model,
+            api_key.unwrap_or("")
+        );
+        let mut vectors = Vec::with_capacity(inputs.len());
+        for batch in inputs.chunks(EMBED_BATCH_LIMIT) {
+            let response = self
+                .client
+                .post(&url)
+                .json(&Self::embed_payload(model, batch))
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(
+                    rate_limit::read_api_error("Google embeddings API error", response).await,
+                );
+            }
+            let json: serde_json::Value = response.json().await?;
+            vectors.extend(Self::parse_embeddings(&json, batch.len())?);
+        }
+        Ok(vectors)
+    }
+
     async fn chat(
         &self,
         base_url: &str,
@@ -838,7 +1345,7 @@ impl LLMProvider for GoogleProvider {
         chat_id: String,
         message_id: String,
         app: AppHandle,
-        cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
     ) -> Result<LLMChatResponse, AppError> {
         // Auto-detect and configure for image generation models
         let is_image_generation_model = Self::is_image_generation_model(&request.model);
@@ -858,8 +1365,10 @@ impl LLMProvider for GoogleProvider {
         }
 
         // Map request to Google format
-        let mut contents = Vec::new();
+        let mut contents: Vec<serde_json::Value> = Vec::new();
         let mut system_instruction = None;
+        // Names of the calls made so far, which their results must repeat
+        let mut tool_names: HashMap<String, String> = HashMap::new();
 
         for msg in request.messages {
             match msg {
@@ -902,32 +1411,18 @@ impl LLMProvider for GoogleProvider {
                                                 }));
                                             } else {
                                                 // Non-image files must be uploaded via File API
-                                                match Self::upload_file_to_google(
-                                                    &self.client,
-                                                    api_key.unwrap_or(""),
-                                                    base_url,
-                                                    data,
-                                                    mime_type,
-                                                )
-                                                .await
+                                                match self
+                                                    .upload_file_cached(
+                                                        &app,
+                                                        api_key.unwrap_or(""),
+                                                        base_url,
+                                                        data,
+                                                        mime_type,
+                                                        file_url.content_hash.as_deref(),
+                                                    )
+                                                    .await
                                                 {
-                                                    Ok((file_uri, file_name)) => {
-                                                        // If it's a video, wait for processing
-                                                        if Self::is_video_mime_type(mime_type) {
-                                                            if let Err(e) =
-                                                                Self::wait_for_file_active(
-                                                                    &self.client,
-                                                                    api_key.unwrap_or(""),
-                                                                    base_url,
-                                                                    &file_name,
-                                                                )
-                                                                .await
-                                                            {
-                                                                tracing::warn!(error = %e, "Failed to wait for video processing");
-                                                                // Continue anyway, might work
-                                                            }
-                                                        }
-
+                                                    Ok(file_uri) => {
                                                         // Add file_data part
                                                         google_parts.push(json!({
                                                             "file_data": {
@@ -1070,16 +1565,16 @@ impl LLMProvider for GoogleProvider {
                         }
                     }
                 }
-                ChatMessage::Assistant { content, .. } => {
+                ChatMessage::Assistant {
+                    content,
+                    tool_calls,
+                } => {
+                    let mut google_parts = Vec::new();
                     match content {
                         AssistantContent::Text(text) => {
-                            contents.push(json!({
-                                "role": "model",
-                                "parts": [{ "text": text }]
-                            }));
+                            google_parts.push(json!({ "text": text }));
                         }
                         AssistantContent::Parts(parts) => {
-                            let mut google_parts = Vec::new();
                             for part in parts {
                                 match part {
                                     ContentPart::Text { text } => {
@@ -1096,14 +1591,49 @@ impl LLMProvider for GoogleProvider {
                                     _ => {} // Skip other types for assistant messages
                                 }
                             }
-                            contents.push(json!({
-                                "role": "model",
-                                "parts": google_parts
-                            }));
                         }
                     }
+                    if let Some(tool_calls) = tool_calls.filter(|calls| !calls.is_empty()) {
+                        // Gemini rejects the empty text a tool-calling turn often has
+                        google_parts.retain(|part| {
+                            part.get("text")
+                                .and_then(|t| t.as_str())
+                                .is_none_or(|text| !text.is_empty())
+                        });
+                        for call in &tool_calls {
+                            tool_names.insert(call.id.clone(), call.function.name.clone());
+                            google_parts.push(Self::function_call_part(call, &request.model));
+                        }
+                    }
+                    contents.push(json!({
+                        "role": "model",
+                        "parts": google_parts
+                    }));
+                }
+                ChatMessage::Tool {
+                    content,
+                    tool_call_id,
+                } => {
+                    let name = tool_names
+                        .get(&tool_call_id)
+                        .map_or(tool_call_id.as_str(), String::as_str);
+                    let part = Self::function_response_part(name, &content);
+                    // The results of one turn's calls go back together, in one content
+                    match contents
+                        .last_mut()
+                        .filter(|last| Self::is_function_responses(last))
+                    {
+                        Some(last) => {
+                            if let Some(parts) = last["parts"].as_array_mut() {
+                                parts.push(part);
+                            }
+                        }
+                        None => contents.push(json!({
+                            "role": "user",
+                            "parts": [part]
+                        })),
+                    }
                 }
-                _ => {} // Tool messages not yet fully supported in this simple implementation
             }
         }
 
@@ -1125,6 +1655,15 @@ impl LLMProvider for GoogleProvider {
             "temperature": request.temperature,
             "maxOutputTokens": request.max_tokens,
         });
+        for (key, value) in [
+            ("topP", request.top_p),
+            ("frequencyPenalty", request.frequency_penalty),
+            ("presencePenalty", request.presence_penalty),
+        ] {
+            if let Some(value) = value {
+                gen_config[key] = json!(value);
+            }
+        }
 
         // Add response modalities if specified (for image generation)
         if let Some(modalities) = request.response_modalities.as_ref() {
@@ -1160,7 +1699,7 @@ impl LLMProvider for GoogleProvider {
         let (_, supports_thinking, _) = Self::check_model_capabilities(&model);
 
         if let Some(effort) = request.reasoning_effort.as_ref() {
-            if !effort.is_empty() && supports_thinking {
+            if !effort.is_empty() && effort != REASONING_EFFORT_NONE && supports_thinking {
                 if let Some(gen_config) = body
                     .get_mut("generationConfig")
                     .and_then(|v| v.as_object_mut())
@@ -1246,14 +1785,249 @@ impl LLMProvider for GoogleProvider {
             }
         }
 
-        let req_builder = self.client.post(&url).json(&body);
+        // Serve the stable prefix from a server-side cache when the workspace opted in
+        let context_cache_scope = request.context_cache_scope.as_deref();
+        let api_key_str = api_key.unwrap_or("");
+        let context_caches = app
+            .try_state::<crate::state::AppState>()
+            .map(|state| state.context_cache_service.clone());
+        let cache_name = match &context_caches {
+            Some(service) => {
+                self.resolve_context_cache(
+                    service,
+                    context_cache_scope,
+                    base_url,
+                    api_key_str,
+                    &model,
+                    &body,
+                    false,
+                )
+                .await
+            }
+            None => None,
+        };
+
+        let Some(mut response) = self
+            .send_cancellable(
+                &url,
+                &Self::with_cached_content(&body, cache_name.as_deref()),
+                &mut cancellation_rx,
+            )
+            .await?
+        else {
+            return Self::cancelled_before_answer(&app, chat_id, message_id);
+        };
+
+        if let (Some(service), Some(_)) = (&context_caches, &cache_name) {
+            if !response.status().is_success() {
+                let status = response.status();
+                let error_text = response
+                    .text()
+                    .await
+                    .unwrap_or_else(|_| "Unknown error".to_string());
+
+                if !Self::is_missing_cache_error(&error_text) {
+                    // Google states its rate limits in the body alone
+                    let info = RateLimitInfo::from_google_body(&error_text);
+                    let error =
+                        rate_limit::api_error("Google API error", status, &info, &error_text);
+                    MessageEmitter::new(app.clone()).emit_api_error(
+                        chat_id.clone(),
+                        message_id.clone(),
+                        &error,
+                    )?;
+                    return Err(error);
+                }
+
+                // The cache was evicted or expired early: recreate it once and retry
+                tracing::info!("Cached content not found, recreating context cache");
+                let cache_name = self
+                    .resolve_context_cache(
+                        service,
+                        context_cache_scope,
+                        base_url,
+                        api_key_str,
+                        &model,
+                        &body,
+                        true,
+                    )
+                    .await;
+                let Some(retried) = self
+                    .send_cancellable(
+                        &url,
+                        &Self::with_cached_content(&body, cache_name.as_deref()),
+                        &mut cancellation_rx,
+                    )
+                    .await?
+                else {
+                    return Self::cancelled_before_answer(&app, chat_id, message_id);
+                };
+                response = retried;
+            }
+        }
 
         if request.stream {
-            self.handle_streaming(req_builder, chat_id, message_id, app, cancellation_rx)
-                .await
+            self.handle_streaming(
+                response,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                request.output_limits,
+            )
+            .await
         } else {
-            self.handle_non_streaming(req_builder, chat_id, message_id, app)
+            self.handle_non_streaming(response, chat_id, message_id, app)
                 .await
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::error::AppError;
+
+    #[test]
+    fn gemini_request_is_cancelled_before_the_model_answers() {
+        use crate::services::llm::providers::GoogleProvider;
+        use std::sync::Arc;
+        use std::time::Duration;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            // A server that takes the request and never answers
+            let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!(
+                "http://{}/models/gemini-2.0-flash:streamGenerateContent",
+                listener.local_addr().unwrap()
+            );
+            tokio::spawn(async move {
+                let (_socket, _) = listener.accept().await.unwrap();
+                std::future::pending::<()>().await;
+            });
+
+            let provider = GoogleProvider::new(Arc::new(reqwest::Client::new()));
+            let (cancel, rx) = tokio::sync::broadcast::channel(1);
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                cancel.send(()).unwrap();
+            });
+            let sent = tokio::time::timeout(
+                Duration::from_secs(5),
+                provider.send_cancellable(&url, &serde_json::json!({}), &mut Some(rx)),
+            )
+            .await
+            .expect("the cancel ends the wait for an answer");
+            assert!(matches!(sent, Ok(None)));
+        });
+    }
+
+    #[test]
+    fn gemini_tool_calls_form_from_whole_and_partial_function_calls() {
+        use crate::services::llm::providers::google::FunctionCallStream;
+        use serde_json::json;
+
+        let mut calls = FunctionCallStream::default();
+        calls.push(&json!({ "name": "search", "args": { "query": "rust" } }));
+        calls.push(&json!({ "name": "write_file", "id": "fc_1", "willContinue": true }));
+        assert_eq!(calls.calls().len(), 2);
+        assert_eq!(calls.calls()[1].function.arguments, "{}");
+
+        calls.push(&json!({ "partialArgs": [
+            { "jsonPath": "$.path", "stringValue": "notes.md" },
+            { "jsonPath": "$.content", "stringValue": "Hello, ", "willContinue": true },
+        ], "willContinue": true }));
+        calls.push(&json!({ "partialArgs": [
+            { "jsonPath": "$.content", "stringValue": "world" },
+            { "jsonPath": "$.options.lines[1]", "numberValue": 3 },
+            { "jsonPath": "$.options.append", "boolValue": true },
+        ] }));
+        // The call is closed: a part without a name starts a new one
+        calls.push(&json!({ "args": {} }));
+
+        let calls = calls.into_calls();
+        assert_eq!(calls.len(), 3);
+        assert_eq!(calls[0].function.name, "search");
+        assert!(calls[0].id.starts_with("call_"));
+        assert_eq!(calls[1].id, "fc_1");
+        let args: serde_json::Value = serde_json::from_str(&calls[1].function.arguments).unwrap();
+        assert_eq!(
+            args,
+            json!({
+                "path": "notes.md",
+                "content": "Hello, world",
+                "options": { "lines": [null, 3], "append": true },
+            })
+        );
+    }
+
+    #[test]
+    fn gemini_replays_tool_calls_and_their_results_as_function_parts() {
+        use crate::models::llm_types::{ToolCall, ToolCallFunction};
+        use crate::services::llm::providers::GoogleProvider;
+        use serde_json::json;
+
+        let call = ToolCall {
+            id: "call_1".to_string(),
+            r#type: "function".to_string(),
+            function: ToolCallFunction {
+                name: "read_file".to_string(),
+                arguments: r#"{"path":"a.txt"}"#.to_string(),
+            },
+        };
+        assert_eq!(
+            GoogleProvider::function_call_part(&call, "gemini-2.5-flash"),
+            json!({ "functionCall": { "name": "read_file", "args": { "path": "a.txt" } } })
+        );
+        assert_eq!(
+            GoogleProvider::function_call_part(&call, "gemini-3-pro-preview")["thoughtSignature"],
+            "skip_thought_signature_validator"
+        );
+
+        // Objects go as they are, anything else under `result`
+        assert_eq!(
+            GoogleProvider::function_response_part("read_file", r#"{"lines":3}"#),
+            json!({ "functionResponse": { "name": "read_file", "response": { "lines": 3 } } })
+        );
+        assert_eq!(
+            GoogleProvider::function_response_part("read_file", "hello")["functionResponse"]
+                ["response"],
+            json!({ "result": "hello" })
+        );
+        assert_eq!(
+            GoogleProvider::function_response_part("count", "[1,2]")["functionResponse"]
+                ["response"],
+            json!({ "result": "[1,2]" })
+        );
+    }
+
+    #[test]
+    fn google_embeddings_are_batched_and_read_in_order() {
+        use crate::services::llm::providers::GoogleProvider;
+
+        let inputs = vec!["first".to_string(), "second".to_string()];
+        assert_eq!(
+            GoogleProvider::embed_payload("models/text-embedding-004", &inputs),
+            serde_json::json!({"requests": [
+                {"model": "models/text-embedding-004", "content": {"parts": [{"text": "first"}]}},
+                {"model": "models/text-embedding-004", "content": {"parts": [{"text": "second"}]}},
+            ]})
+        );
+
+        let response = serde_json::json!({"embeddings": [
+            {"values": [0.5, -1.0]},
+            {"values": [0.25, 2.0]},
+        ]});
+        assert_eq!(
+            GoogleProvider::parse_embeddings(&response, 2).unwrap(),
+            vec![vec![0.5, -1.0], vec![0.25, 2.0]]
+        );
+        assert!(matches!(
+            GoogleProvider::parse_embeddings(&response, 3),
+            Err(AppError::Llm(_))
+        ));
+        assert!(matches!(
+            GoogleProvider::parse_embeddings(&serde_json::json!({"error": {}}), 1),
+            Err(AppError::Llm(_))
+        ));
+    }
+}
@@ -1,18 +1,63 @@
 pub mod anthropic;
+pub mod azure;
+pub mod bedrock;
 pub mod google;
+pub mod ollama;
 pub mod openai;
 pub mod openai_compat;
 
 use crate::error::AppError;
+use crate::events::MessageEmitter;
 use crate::models::llm_types::{LLMChatRequest, LLMChatResponse, LLMModel};
+use crate::services::llm::stream_stats::StreamStats;
 use async_trait::async_trait;
+use sha2::{Digest, Sha256};
 use tauri::AppHandle;
 
 pub use anthropic::AnthropicProvider;
+pub use azure::AzureOpenAIProvider;
+pub use bedrock::BedrockProvider;
 pub use google::GoogleProvider;
+pub use ollama::OllamaProvider;
 pub use openai::OpenAIProvider;
 pub use openai_compat::OpenAICompatProvider;
 
+/// Stable key identifying a provider account (base URL + API key), used to scope
+/// server-side resources such as file uploads and context caches. The API key
+/// itself is never stored.
+pub fn account_key(base_url: &str, api_key: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(format!("{base_url}|{api_key}").as_bytes());
+    hex::encode(hasher.finalize())[..16].to_string()
+}
+
+/// Resolves once a cancellation signal is received. Never resolves when there is
+/// no receiver or the sender has gone away, so it can sit in a `select!` arm.
+pub async fn wait_for_cancellation(rx: &mut Option<tokio::sync::broadcast::Receiver<()>>) {
+    if let Some(rx) = rx {
+        match rx.recv().await {
+            // A lagged receiver still means a signal was sent
+            Ok(()) | Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => return,
+            Err(tokio::sync::broadcast::error::RecvError::Closed) => {}
+        }
+    }
+    futures::future::pending::<()>().await;
+}
+
+/// Start reporting the generation speed of a streamed answer as
+/// `MessageStreamStatsEvent`s.
+pub fn start_stream_stats(app: &AppHandle, chat_id: &str, message_id: &str) -> StreamStats {
+    let emitter = MessageEmitter::new(app.clone());
+    let (chat_id, message_id) = (chat_id.to_string(), message_id.to_string());
+    StreamStats::start(move |stats| {
+        if let Err(e) =
+            emitter.emit_message_stream_stats(chat_id.clone(), message_id.clone(), stats)
+        {
+            tracing::debug!(error = %e, "Failed to emit stream stats");
+        }
+    })
+}
+
 #[async_trait]
 pub trait LLMProvider: Send + Sync {
     async fn fetch_models(
@@ -31,4 +76,73 @@ pub trait LLMProvider: Send + Sync {
         app: AppHandle,
         cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
     ) -> Result<LLMChatResponse, AppError>;
+
+    /// One embedding per input, in order. Providers without an embeddings
+    /// endpoint keep the default.
+    async fn embed(
+        &self,
+        _base_url: &str,
+        _api_key: Option<&str>,
+        _model: &str,
+        _inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        Err(AppError::Llm(
+            "This provider does not offer embeddings".to_string(),
+        ))
+    }
+}
+
+#[derive(serde::Deserialize)]
+struct Embedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+#[derive(serde::Deserialize)]
+struct EmbeddingsResponse {
+    data: Vec<Embedding>,
+}
+
+/// Call an OpenAI-style `/embeddings` endpoint, which OpenAI and most
+/// compatible servers (Ollama, LM Studio, LocalAI and others) share.
+pub async fn openai_embeddings(
+    client: &reqwest::Client,
+    base_url: &str,
+    api_key: Option<&str>,
+    model: &str,
+    inputs: &[String],
+) -> Result<Vec<Vec<f32>>, AppError> {
+    let url = format!("{}/embeddings", base_url.trim_end_matches('/'));
+    let mut req_builder = client
+        .post(&url)
+        .json(&serde_json::json!({ "model": model, "input": inputs }));
+    if let Some(key) = api_key.filter(|key| !key.is_empty()) {
+        req_builder = req_builder.header("Authorization", format!("Bearer {key}"));
+    }
+
+    let response = req_builder.send().await?;
+    if !response.status().is_success() {
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        return Err(AppError::Llm(format!(
+            "Embeddings API error ({status}): {error_text}"
+        )));
+    }
+
+    let mut data = response.json::<EmbeddingsResponse>().await?.data;
+    if data.len() != inputs.len() {
+        return Err(AppError::Llm(format!(
+            "Embeddings API returned {} embeddings for {} inputs",
+            data.len(),
+            inputs.len()
+        )));
+    }
+    data.sort_by_key(|embedding| embedding.index);
+    Ok(data
+        .into_iter()
+        .map(|embedding| embedding.embedding)
+        .collect())
 }
@@ -0,0 +1,936 @@
+//! Ollama's native API, for local models.
+//!
+//! Connections point at the OpenAI-compatible `/v1` API; the native API sits
+//! above it. Chats go to `/api/chat`, which streams one JSON object per line,
+//! models are listed with `/api/tags` and their capabilities read with
+//! `/api/show`, and `/api/pull` downloads a model, reporting its progress.
+
+use super::LLMProvider;
+use crate::error::AppError;
+use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage, ToolEmitter};
+use crate::features::llm_connection::config::REASONING_EFFORT_NONE;
+use crate::models::llm_types::{
+    AssistantContent, ChatMessage, ContentPart, LLMChatRequest, LLMChatResponse, LLMModel,
+    TokenUsage, ToolCall, ToolCallFunction, ToolChoice, UserContent,
+};
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit;
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::AppHandle;
+
+const OLLAMA_API_ERROR: &str = "Ollama API error";
+
+/// How long a model download may take; large models take far longer than
+/// the client's default timeout.
+const PULL_TIMEOUT: Duration = Duration::from_secs(6 * 60 * 60);
+
+/// Root of the native API of a connection, which points at `/v1` or at the
+/// server itself.
+pub fn native_root(base_url: &str) -> &str {
+    let base_url = base_url.trim_end_matches('/');
+    base_url.strip_suffix("/v1").unwrap_or(base_url)
+}
+
+/// What `/api/show` tells about a model.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OllamaModelInfo {
+    pub model: String,
+    /// e.g. "completion", "tools", "thinking", "vision", "embedding"
+    pub capabilities: Vec<String>,
+    pub family: Option<String>,
+    pub parameter_size: Option<String>,
+    pub quantization_level: Option<String>,
+    pub context_length: Option<u64>,
+}
+
+impl OllamaModelInfo {
+    pub fn from_show(model: &str, show: &Value) -> Self {
+        let detail = |key: &str| {
+            show.get("details")
+                .and_then(|details| details.get(key))
+                .and_then(Value::as_str)
+                .filter(|value| !value.is_empty())
+                .map(str::to_string)
+        };
+        // Keys of model_info carry the architecture, e.g. "llama.context_length"
+        let context_length = show
+            .get("model_info")
+            .and_then(Value::as_object)
+            .and_then(|info| {
+                info.iter()
+                    .find(|(key, _)| key.ends_with(".context_length"))
+                    .and_then(|(_, value)| value.as_u64())
+            });
+
+        Self {
+            model: model.to_string(),
+            capabilities: show
+                .get("capabilities")
+                .and_then(Value::as_array)
+                .map(|capabilities| {
+                    capabilities
+                        .iter()
+                        .filter_map(Value::as_str)
+                        .map(str::to_string)
+                        .collect()
+                })
+                .unwrap_or_default(),
+            family: detail("family"),
+            parameter_size: detail("parameter_size"),
+            quantization_level: detail("quantization_level"),
+            context_length,
+        }
+    }
+
+    pub fn has(&self, capability: &str) -> bool {
+        self.capabilities.iter().any(|c| c == capability)
+    }
+
+    /// Embedding-only models cannot chat. Servers too old to report
+    /// capabilities list none, and every model counts as a chat model.
+    pub fn can_chat(&self) -> bool {
+        self.capabilities.is_empty() || self.has("completion")
+    }
+}
+
+/// One line of `/api/pull`'s progress.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct OllamaPullProgress {
+    #[serde(default)]
+    pub status: String,
+    pub digest: Option<String>,
+    pub total: Option<u64>,
+    pub completed: Option<u64>,
+    pub error: Option<String>,
+}
+
+impl OllamaPullProgress {
+    /// The last line of a pull that went through.
+    pub fn is_success(&self) -> bool {
+        self.status == "success"
+    }
+}
+
+/// One line of a `/api/chat` stream, or its whole answer when not streaming.
+#[derive(Debug, Default, Deserialize)]
+struct ChatChunk {
+    model: Option<String>,
+    message: Option<ChunkMessage>,
+    #[serde(default)]
+    done: bool,
+    done_reason: Option<String>,
+    prompt_eval_count: Option<u32>,
+    eval_count: Option<u32>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChunkMessage {
+    #[serde(default)]
+    content: String,
+    thinking: Option<String>,
+    #[serde(default)]
+    tool_calls: Vec<Value>,
+}
+
+impl ChatChunk {
+    fn usage(&self) -> Option<TokenUsage> {
+        if !self.done {
+            return None;
+        }
+        Some(TokenUsage {
+            prompt_tokens: self.prompt_eval_count,
+            completion_tokens: self.eval_count,
+            total_tokens: Some(self.prompt_eval_count.unwrap_or(0) + self.eval_count.unwrap_or(0)),
+            cached_tokens: None,
+            cache_write_tokens: None,
+        })
+    }
+}
+
+/// Tool calls of a native chat message. The API sends each call whole, with
+/// its arguments as an object and, in older versions, without an id.
+pub fn parse_tool_calls(calls: &[Value]) -> Vec<ToolCall> {
+    calls
+        .iter()
+        .filter_map(|call| {
+            let function = call.get("function")?;
+            let name = function.get("name")?.as_str()?.to_string();
+            let arguments = match function.get("arguments") {
+                Some(Value::String(arguments)) => arguments.clone(),
+                Some(arguments) if !arguments.is_null() => arguments.to_string(),
+                _ => "{}".to_string(),
+            };
+            let id = call
+                .get("id")
+                .and_then(Value::as_str)
+                .filter(|id| !id.is_empty())
+                .map_or_else(
+                    || format!("call_{}", uuid::Uuid::new_v4().simple()),
+                    str::to_string,
+                );
+            Some(ToolCall {
+                id,
+                r#type: "function".to_string(),
+                function: ToolCallFunction { name, arguments },
+            })
+        })
+        .collect()
+}
+
+/// Text of the parts, and the base64 of their images, which the API takes
+/// beside the text.
+fn text_and_images(parts: &[ContentPart]) -> (String, Vec<String>) {
+    let mut texts = Vec::new();
+    let mut images = Vec::new();
+    for part in parts {
+        match part {
+            ContentPart::Text { text } => texts.push(text.clone()),
+            ContentPart::ImageUrl { image_url } => match image_url.url.split_once(',') {
+                Some((_, data)) => images.push(data.to_string()),
+                None => texts.push(format!("[Image: {}]", image_url.url)),
+            },
+            ContentPart::FileUrl { file_url } => {
+                match file_url.url.split_once(',') {
+                    Some((_, data)) if file_url.mime_type.starts_with("image/") => {
+                        images.push(data.to_string());
+                    }
+                    // Only images reach the model; other files get a placeholder
+                    _ => texts.push(format!(
+                        "[File attachment: {} - Not supported by this model]",
+                        file_url.mime_type
+                    )),
+                }
+            }
+            ContentPart::InlineData { inline_data } => {
+                if inline_data.mime_type.starts_with("image/") {
+                    images.push(inline_data.data.clone());
+                } else {
+                    texts.push(format!(
+                        "[File attachment: {} - Not supported by this model]",
+                        inline_data.mime_type
+                    ));
+                }
+            }
+        }
+    }
+    (texts.join("\n"), images)
+}
+
+fn native_messages(messages: &[ChatMessage]) -> Vec<Value> {
+    // Tool results name their tool; the API knows no call ids
+    let mut tool_names: HashMap<&str, &str> = HashMap::new();
+    let mut native = Vec::with_capacity(messages.len());
+    for message in messages {
+        native.push(match message {
+            ChatMessage::System { content } => json!({ "role": "system", "content": content }),
+            ChatMessage::User { content } => {
+                let (text, images) = match content {
+                    UserContent::Text(text) => (text.clone(), Vec::new()),
+                    UserContent::Parts(parts) => text_and_images(parts),
+                };
+                let mut message = json!({ "role": "user", "content": text });
+                if !images.is_empty() {
+                    message["images"] = json!(images);
+                }
+                message
+            }
+            ChatMessage::Assistant {
+                content,
+                tool_calls,
+            } => {
+                let text = match content {
+                    AssistantContent::Text(text) => text.clone(),
+                    AssistantContent::Parts(parts) => text_and_images(parts).0,
+                };
+                let mut message = json!({ "role": "assistant", "content": text });
+                if let Some(calls) = tool_calls.as_ref().filter(|calls| !calls.is_empty()) {
+                    for call in calls {
+                        tool_names.insert(&call.id, &call.function.name);
+                    }
+                    message["tool_calls"] = calls
+                        .iter()
+                        .map(|call| {
+                            json!({ "function": {
+                                "name": call.function.name,
+                                "arguments": json_repair::arguments_value(&call.function.arguments),
+                            } })
+                        })
+                        .collect();
+                }
+                message
+            }
+            ChatMessage::Tool {
+                content,
+                tool_call_id,
+            } => {
+                let mut message = json!({ "role": "tool", "content": content });
+                if let Some(name) = tool_names.get(tool_call_id.as_str()) {
+                    message["tool_name"] = json!(name);
+                }
+                message
+            }
+        });
+    }
+    native
+}
+
+/// The `/api/chat` body of a request.
+pub fn chat_body(request: &LLMChatRequest) -> Value {
+    let mut body = json!({
+        "model": request.model,
+        "messages": native_messages(&request.messages),
+        "stream": request.stream,
+    });
+
+    let mut options = serde_json::Map::new();
+    if let Some(temperature) = request.temperature {
+        options.insert("temperature".to_string(), json!(temperature));
+    }
+    if let Some(max_tokens) = request.max_tokens {
+        options.insert("num_predict".to_string(), json!(max_tokens));
+    }
+    for (key, value) in [
+        ("top_p", request.top_p),
+        ("frequency_penalty", request.frequency_penalty),
+        ("presence_penalty", request.presence_penalty),
+    ] {
+        if let Some(value) = value {
+            options.insert(key.to_string(), json!(value));
+        }
+    }
+    if let Some(seed) = request.seed {
+        options.insert("seed".to_string(), json!(seed));
+    }
+    if !options.is_empty() {
+        body["options"] = Value::Object(options);
+    }
+
+    // The API has no tool choice; "none" means sending no tools
+    let tools_off =
+        matches!(&request.tool_choice, Some(ToolChoice::String(choice)) if choice == "none");
+    if let Some(tools) = request.tools.as_ref().filter(|tools| !tools.is_empty()) {
+        if !tools_off {
+            body["tools"] = json!(tools);
+        }
+    }
+
+    // Any effort turns thinking on; the API has no levels
+    if let Some(effort) = request.reasoning_effort.as_deref() {
+        body["think"] = json!(effort != REASONING_EFFORT_NONE);
+    }
+    body
+}
+
+fn event_tool_calls(tool_calls: &[ToolCall]) -> Vec<crate::events::ToolCall> {
+    tool_calls
+        .iter()
+        .map(|tc| crate::events::ToolCall {
+            id: tc.id.clone(),
+            name: tc.function.name.clone(),
+            arguments: json_repair::arguments_value(&tc.function.arguments),
+        })
+        .collect()
+}
+
+pub struct OllamaProvider {
+    client: Arc<Client>,
+}
+
+impl OllamaProvider {
+    pub const fn new(client: Arc<Client>) -> Self {
+        Self { client }
+    }
+
+    fn authorized(
+        req_builder: reqwest::RequestBuilder,
+        api_key: Option<&str>,
+    ) -> reqwest::RequestBuilder {
+        // Servers behind a proxy may want a key; a plain local server ignores it
+        match api_key {
+            Some(key) if !key.is_empty() => {
+                req_builder.header("Authorization", format!("Bearer {key}"))
+            }
+            _ => req_builder,
+        }
+    }
+
+    /// Capabilities and details of a model.
+    pub async fn show(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+    ) -> Result<OllamaModelInfo, AppError> {
+        let url = format!("{}/api/show", native_root(base_url));
+        let response = Self::authorized(self.client.post(&url), api_key)
+            .json(&json!({ "model": model }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(rate_limit::read_api_error(OLLAMA_API_ERROR, response).await);
+        }
+        let show: Value = response.json().await?;
+        Ok(OllamaModelInfo::from_show(model, &show))
+    }
+
+    /// Download a model to the server, reporting each line of progress.
+    /// Pulling a model the server already has only checks it is current.
+    pub async fn pull(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        on_progress: impl Fn(&OllamaPullProgress) + Send,
+    ) -> Result<(), AppError> {
+        let url = format!("{}/api/pull", native_root(base_url));
+        let response = Self::authorized(self.client.post(&url), api_key)
+            .timeout(PULL_TIMEOUT)
+            .json(&json!({ "model": model, "stream": true }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(rate_limit::read_api_error(OLLAMA_API_ERROR, response).await);
+        }
+
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let mut buffer = String::new();
+        while let Some(chunk) = stream.next().await {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+            while let Some(end_idx) = buffer.find('\n') {
+                let line = buffer[..end_idx].trim().to_string();
+                buffer = buffer[end_idx + 1..].to_string();
+                if line.is_empty() {
+                    continue;
+                }
+                let progress: OllamaPullProgress = match serde_json::from_str(&line) {
+                    Ok(progress) => progress,
+                    Err(e) => {
+                        tracing::error!(error = %e, line = %line, "Failed to parse pull progress");
+                        continue;
+                    }
+                };
+                if let Some(error) = &progress.error {
+                    return Err(AppError::Llm(format!("{OLLAMA_API_ERROR}: {error}")));
+                }
+                on_progress(&progress);
+                if progress.is_success() {
+                    return Ok(());
+                }
+            }
+        }
+
+        let reason = stream
+            .interruption()
+            .unwrap_or("the server closed the connection");
+        Err(AppError::Llm(format!(
+            "{OLLAMA_API_ERROR}: the download of {model} stopped before it completed ({reason})"
+        )))
+    }
+
+    async fn handle_streaming(
+        response: reqwest::Response,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        output_limits: Option<OutputLimits>,
+    ) -> Result<LLMChatResponse, AppError> {
+        let message_emitter = MessageEmitter::new(app.clone());
+        let tool_emitter = ToolEmitter::new(app.clone());
+
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats = super::start_stream_stats(&app, &chat_id, &message_id);
+        let mut full_content = String::new();
+        let mut full_reasoning = String::new();
+        let mut tool_calls: Vec<ToolCall> = Vec::new();
+        let mut finish_reason: Option<String> = None;
+        let mut buffer = String::new();
+        let mut final_usage: Option<TokenUsage> = None;
+        let mut served_model: Option<String> = None;
+        let mut limiter = OutputLimiter::new(output_limits);
+
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
+            next_item = stream.next() => next_item,
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
+            }
+        } {
+            buffer.push_str(&String::from_utf8_lossy(&chunk));
+
+            // One JSON object per line
+            while let Some(end_idx) = buffer.find('\n') {
+                let line = buffer[..end_idx].trim().to_string();
+                buffer = buffer[end_idx + 1..].to_string();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let chat_chunk = match serde_json::from_str::<ChatChunk>(&line) {
+                    Ok(chat_chunk) => chat_chunk,
+                    Err(e) => {
+                        tracing::error!(error = %e, line = %line, "Failed to parse Ollama chunk");
+                        continue;
+                    }
+                };
+                if let Some(error) = &chat_chunk.error {
+                    let error = AppError::Llm(format!("{OLLAMA_API_ERROR}: {error}"));
+                    message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+                    return Err(error);
+                }
+                if chat_chunk.model.is_some() {
+                    served_model.clone_from(&chat_chunk.model);
+                }
+                if chat_chunk.done {
+                    final_usage = chat_chunk.usage();
+                    finish_reason.clone_from(&chat_chunk.done_reason);
+                }
+
+                let Some(message) = chat_chunk.message else {
+                    continue;
+                };
+                if !message.content.is_empty() {
+                    let content =
+                        limiter.admit(OutputKind::Content, full_content.len(), &message.content);
+                    full_content.push_str(content);
+                    stats.record_chars(content);
+                    message_emitter.emit_message_chunk(
+                        chat_id.clone(),
+                        message_id.clone(),
+                        content.to_string(),
+                    )?;
+                }
+                if let Some(thinking) = message.thinking.filter(|t| !t.is_empty()) {
+                    let thinking =
+                        limiter.admit(OutputKind::Reasoning, full_reasoning.len(), &thinking);
+                    full_reasoning.push_str(thinking);
+                    stats.record_chars(thinking);
+                    message_emitter.emit_thinking_chunk(
+                        chat_id.clone(),
+                        message_id.clone(),
+                        thinking.to_string(),
+                    )?;
+                }
+                if !message.tool_calls.is_empty() {
+                    // Calls arrive whole; one cut short by the cap is dropped
+                    let calls: Vec<ToolCall> = parse_tool_calls(&message.tool_calls)
+                        .into_iter()
+                        .filter(|call| {
+                            let arguments = &call.function.arguments;
+                            limiter.admit(OutputKind::ToolArguments, 0, arguments).len()
+                                == arguments.len()
+                        })
+                        .collect();
+                    if !calls.is_empty() {
+                        tool_emitter.emit_tool_calls_detected(
+                            chat_id.clone(),
+                            message_id.clone(),
+                            event_tool_calls(&calls),
+                        )?;
+                        tool_calls.extend(calls);
+                    }
+                }
+            }
+
+            if limiter.should_stop(&buffer) {
+                break;
+            }
+        }
+
+        stats.stop();
+
+        // A stream that broke off keeps what arrived, but not its tool calls
+        if stream.finish(!full_content.is_empty() || !full_reasoning.is_empty())? {
+            tool_calls.clear();
+            finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        }
+
+        let capped = limiter.end_at_cap(stream, &mut tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+        if capped {
+            finish_reason = Some(LENGTH_LIMIT.to_string());
+        }
+
+        if cancelled {
+            message_emitter.emit_message_cancelled(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+            )?;
+            finish_reason = Some("cancelled".to_string());
+        } else {
+            message_emitter.emit_message_complete(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+                final_usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            )?;
+        }
+
+        Ok(LLMChatResponse {
+            content: full_content,
+            finish_reason,
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            },
+            usage: final_usage,
+            reasoning: if full_reasoning.is_empty() {
+                None
+            } else {
+                Some(full_reasoning)
+            },
+            images: None,
+            parameter_notes: Vec::new(),
+            model: served_model,
+            system_fingerprint: None,
+        })
+    }
+
+    async fn handle_non_streaming(
+        response: reqwest::Response,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+    ) -> Result<LLMChatResponse, AppError> {
+        let message_emitter = MessageEmitter::new(app.clone());
+        let tool_emitter = ToolEmitter::new(app.clone());
+
+        let chat_chunk: ChatChunk = response
+            .json()
+            .await
+            .map_err(|e| AppError::Generic(format!("Failed to parse response: {e}")))?;
+        if let Some(error) = &chat_chunk.error {
+            let error = AppError::Llm(format!("{OLLAMA_API_ERROR}: {error}"));
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
+        }
+
+        let usage = chat_chunk.usage();
+        let message = chat_chunk.message.unwrap_or_default();
+        let tool_calls = parse_tool_calls(&message.tool_calls);
+        if !tool_calls.is_empty() {
+            tool_emitter.emit_tool_calls_detected(
+                chat_id.clone(),
+                message_id.clone(),
+                event_tool_calls(&tool_calls),
+            )?;
+        }
+
+        message_emitter.emit_message_complete(
+            chat_id.clone(),
+            message_id.clone(),
+            message.content.clone(),
+            usage.as_ref().map(|u| EventTokenUsage {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: u.completion_tokens,
+                total_tokens: u.total_tokens,
+            }),
+        )?;
+
+        Ok(LLMChatResponse {
+            content: message.content,
+            finish_reason: chat_chunk.done_reason,
+            tool_calls: if tool_calls.is_empty() {
+                None
+            } else {
+                Some(tool_calls)
+            },
+            usage,
+            reasoning: message.thinking.filter(|t| !t.is_empty()),
+            images: None,
+            parameter_notes: Vec::new(),
+            model: chat_chunk.model,
+            system_fingerprint: None,
+        })
+    }
+}
+
+#[async_trait]
+impl LLMProvider for OllamaProvider {
+    async fn embed(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        let base_url = format!("{}/v1", native_root(base_url));
+        super::openai_embeddings(&self.client, &base_url, api_key, model, inputs).await
+    }
+
+    async fn fetch_models(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+    ) -> Result<Vec<LLMModel>, AppError> {
+        let url = format!("{}/api/tags", native_root(base_url));
+        let response = Self::authorized(self.client.get(&url), api_key)
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(rate_limit::read_api_error(OLLAMA_API_ERROR, response).await);
+        }
+
+        let json: Value = response.json().await?;
+        let names: Vec<String> = json
+            .get("models")
+            .and_then(Value::as_array)
+            .map(|models| {
+                models
+                    .iter()
+                    .filter_map(|model| model.get("name").or_else(|| model.get("model")))
+                    .filter_map(Value::as_str)
+                    .map(str::to_string)
+                    .collect()
+            })
+            .ok_or_else(|| {
+                AppError::Llm(format!(
+                    "Unexpected response format. Expected an object with 'models'. Got: {json}"
+                ))
+            })?;
+
+        // Capabilities come from the model itself rather than its name
+        let infos =
+            futures::future::join_all(names.iter().map(|name| self.show(base_url, api_key, name)))
+                .await;
+
+        Ok(names
+            .into_iter()
+            .zip(infos)
+            .filter_map(|(name, info)| {
+                let info = info
+                    .inspect_err(|e| tracing::warn!(model = %name, error = %e, "Failed to read model capabilities"))
+                    .unwrap_or_default();
+                info.can_chat().then(|| LLMModel {
+                    id: name.clone(),
+                    name,
+                    created: None,
+                    owned_by: None,
+                    supports_tools: info.has("tools"),
+                    supports_thinking: info.has("thinking"),
+                    supports_image_generation: false,
+                })
+            })
+            .collect())
+    }
+
+    async fn chat(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        request: LLMChatRequest,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+    ) -> Result<LLMChatResponse, AppError> {
+        let url = format!("{}/api/chat", native_root(base_url));
+        let response = Self::authorized(self.client.post(&url), api_key)
+            .json(&chat_body(&request))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let error = rate_limit::read_api_error(OLLAMA_API_ERROR, response).await;
+            MessageEmitter::new(app.clone()).emit_api_error(
+                chat_id.clone(),
+                message_id.clone(),
+                &error,
+            )?;
+            return Err(error);
+        }
+
+        if request.stream {
+            Self::handle_streaming(
+                response,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                request.output_limits,
+            )
+            .await
+        } else {
+            Self::handle_non_streaming(response, chat_id, message_id, app).await
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn ollama_chat_body_speaks_the_native_api() {
+        use crate::models::llm_types::{
+            AssistantContent, ChatCompletionTool, ChatCompletionToolFunction, ChatMessage,
+            ContentPart, FileUrl, ImageUrl, LLMChatRequest, ToolCall, ToolCallFunction,
+            UserContent,
+        };
+        use crate::services::llm::providers::ollama::{chat_body, native_root};
+
+        assert_eq!(
+            native_root("http://localhost:11434/v1/"),
+            "http://localhost:11434"
+        );
+        assert_eq!(
+            native_root("http://localhost:11434"),
+            "http://localhost:11434"
+        );
+
+        let request = LLMChatRequest {
+            model: "qwen3:8b".to_string(),
+            messages: vec![
+                ChatMessage::System {
+                    content: "Be brief".to_string(),
+                },
+                ChatMessage::User {
+                    content: UserContent::Parts(vec![
+                        ContentPart::Text {
+                            text: "What is this?".to_string(),
+                        },
+                        ContentPart::ImageUrl {
+                            image_url: ImageUrl {
+                                url: "data:image/png;base64,iVBORw0".to_string(),
+                            },
+                        },
+                        ContentPart::FileUrl {
+                            file_url: FileUrl {
+                                url: "data:application/pdf;base64,JVBER".to_string(),
+                                mime_type: "application/pdf".to_string(),
+                                content_hash: None,
+                            },
+                        },
+                    ]),
+                },
+                ChatMessage::Assistant {
+                    content: AssistantContent::Text(String::new()),
+                    tool_calls: Some(vec![ToolCall {
+                        id: "call_1".to_string(),
+                        r#type: "function".to_string(),
+                        function: ToolCallFunction {
+                            name: "read_file".to_string(),
+                            arguments: r#"{"path":"a.txt"}"#.to_string(),
+                        },
+                    }]),
+                },
+                ChatMessage::Tool {
+                    content: "hello".to_string(),
+                    tool_call_id: "call_1".to_string(),
+                },
+            ],
+            temperature: Some(0.2),
+            max_tokens: Some(256),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: Some(vec![ChatCompletionTool {
+                r#type: "function".to_string(),
+                function: ChatCompletionToolFunction {
+                    name: "read_file".to_string(),
+                    description: None,
+                    parameters: None,
+                },
+            }]),
+            tool_choice: None,
+            reasoning_effort: Some("high".to_string()),
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed: Some(7),
+            output_limits: None,
+        };
+
+        let body = chat_body(&request);
+        let messages = body["messages"].as_array().unwrap();
+        assert_eq!(messages[1]["images"], serde_json::json!(["iVBORw0"]));
+        let text = messages[1]["content"].as_str().unwrap();
+        assert!(text.starts_with("What is this?"));
+        assert!(text.contains("application/pdf"));
+        // Arguments go as an object, and the result names its tool
+        assert_eq!(
+            messages[2]["tool_calls"][0]["function"]["arguments"]["path"],
+            "a.txt"
+        );
+        assert_eq!(messages[3]["tool_name"], "read_file");
+        assert_eq!(body["options"]["num_predict"], 256);
+        assert_eq!(body["options"]["seed"], 7);
+        assert_eq!(body["think"], true);
+        assert_eq!(body["tools"][0]["function"]["name"], "read_file");
+
+        let mut request = request;
+        request.reasoning_effort = Some("none".to_string());
+        request.temperature = None;
+        request.max_tokens = None;
+        request.seed = None;
+        let body = chat_body(&request);
+        assert_eq!(body["think"], false);
+        assert!(body.get("options").is_none());
+    }
+
+    #[test]
+    fn ollama_tool_calls_and_capabilities_come_from_the_native_shapes() {
+        use crate::services::llm::providers::ollama::{parse_tool_calls, OllamaModelInfo};
+
+        // Older servers send no ids; each call still gets a distinct one
+        let calls = parse_tool_calls(&[
+            serde_json::json!({ "function": { "name": "get_weather", "arguments": { "city": "Hanoi" } } }),
+            serde_json::json!({ "function": { "name": "get_time", "arguments": {} } }),
+            serde_json::json!({ "function": { "arguments": {} } }),
+        ]);
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].function.name, "get_weather");
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&calls[0].function.arguments).unwrap(),
+            serde_json::json!({ "city": "Hanoi" })
+        );
+        assert!(calls[0].id.starts_with("call_"));
+        assert_ne!(calls[0].id, calls[1].id);
+
+        let info = OllamaModelInfo::from_show(
+            "qwen3:8b",
+            &serde_json::json!({
+                "capabilities": ["completion", "tools", "thinking"],
+                "details": { "family": "qwen3", "parameter_size": "8.2B", "quantization_level": "Q4_K_M" },
+                "model_info": { "general.architecture": "qwen3", "qwen3.context_length": 40960 },
+            }),
+        );
+        assert!(info.has("tools") && info.has("thinking") && !info.has("vision"));
+        assert!(info.can_chat());
+        assert_eq!(info.context_length, Some(40960));
+        assert_eq!(info.parameter_size.as_deref(), Some("8.2B"));
+
+        let embedder = OllamaModelInfo::from_show(
+            "nomic-embed-text",
+            &serde_json::json!({ "capabilities": ["embedding"] }),
+        );
+        assert!(!embedder.can_chat());
+        // Servers too old to report capabilities keep every model
+        assert!(OllamaModelInfo::from_show("llama3", &serde_json::json!({})).can_chat());
+    }
+}
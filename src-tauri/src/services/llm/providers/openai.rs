@@ -1,17 +1,50 @@
 use super::LLMProvider;
 use crate::error::AppError;
 use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage, ToolEmitter};
+use crate::features::llm_connection::config::REASONING_EFFORT_NONE;
+use crate::features::tool::strict_schema;
 use crate::models::llm_types::{
-    AssistantContent, ChatMessage, ContentPart, LLMChatRequest, LLMChatResponse, LLMModel,
-    TokenUsage, ToolCall, ToolCallFunction, UserContent,
+    AssistantContent, ChatCompletionTool, ChatMessage, ContentPart, LLMChatRequest,
+    LLMChatResponse, LLMModel, TokenUsage, ToolCall, ToolCallFunction, UserContent,
 };
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit::{self, RateLimitInfo};
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
 use async_trait::async_trait;
-use futures::StreamExt;
 use reqwest::Client;
 use serde_json::json;
-use std::sync::Arc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, LazyLock, Mutex};
 use tauri::AppHandle;
 
+/// Sampling parameters that reasoning models (o-series, gpt-5) reject.
+const REASONING_UNSUPPORTED_PARAMS: &[&str] = &[
+    "temperature",
+    "top_p",
+    "presence_penalty",
+    "frequency_penalty",
+];
+
+/// How errors of the Responses API are labelled.
+const RESPONSES_API_LABEL: &str = "OpenAI Responses API error";
+
+/// Request parameters that must never be stripped when retrying a rejected request.
+const REQUIRED_PARAMS: &[&str] = &["model", "input", "stream"];
+
+/// Parameters the API rejected at runtime, per model (lowercased id). Learned
+/// from 400 responses so later requests leave them out up front.
+static LEARNED_UNSUPPORTED_PARAMS: LazyLock<Mutex<HashMap<String, HashSet<String>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A Responses API request after `send_responses_request`.
+pub enum ResponsesSend {
+    /// The response for the stream handler, whatever its status
+    Sent(reqwest::Response),
+    /// A 400 that dropping a parameter could not fix, with its body
+    Rejected(reqwest::StatusCode, String),
+}
+
 pub struct OpenAIProvider {
     client: Arc<Client>,
 }
@@ -47,20 +80,249 @@ impl OpenAIProvider {
             || model_lower.starts_with("gpt-3.5")
             || model_lower.starts_with("gpt-5")
             || model_lower.starts_with("o1")
-            || model_lower.starts_with("o3");
+            || model_lower.starts_with("o3")
+            || model_lower.starts_with("o4");
 
         // Thinking/Reasoning Support:
         // - O1 series: specialized reasoning models with chain-of-thought
-        // - O3 series: next-gen reasoning models
+        // - O3/O4 series: next-gen reasoning models
         // - GPT-5 series: improved reasoning capabilities
         let supports_thinking = model_lower.starts_with("o1")
             || model_lower.starts_with("o3")
+            || model_lower.starts_with("o4")
             || model_lower.starts_with("gpt-5");
 
         (supports_tools, supports_thinking, supports_image_generation)
     }
 
+    fn model_key(model_id: &str) -> String {
+        model_id
+            .split('/')
+            .next_back()
+            .unwrap_or(model_id)
+            .to_lowercase()
+    }
+
+    /// Translate the app's reasoning effort setting into a value the Responses
+    /// API accepts. Returns None for settings with no equivalent.
+    fn map_reasoning_effort(effort: &str) -> Option<&'static str> {
+        match effort.to_lowercase().as_str() {
+            "minimal" | "low" => Some("low"),
+            "medium" => Some("medium"),
+            "high" | "max" | "xhigh" => Some("high"),
+            _ => None,
+        }
+    }
+
+    /// User-facing note for a parameter that was left out of the request.
+    fn ignored_param_note(param: &str, model: &str) -> String {
+        let name = match param {
+            "max_output_tokens" => "max_tokens",
+            "reasoning" => "reasoning_effort",
+            other => other,
+        };
+        format!("{name} ignored: not supported by {model}")
+    }
+
+    /// Remove parameters the model is known not to accept, from the built-in
+    /// rules for reasoning models and from restrictions learned at runtime.
+    /// Returns a note for every parameter removed.
+    pub fn filter_unsupported_params(
+        model: &str,
+        request_body: &mut serde_json::Value,
+    ) -> Vec<String> {
+        let (_, is_reasoning_model, _) = Self::check_model_capabilities(model);
+        let learned = LEARNED_UNSUPPORTED_PARAMS
+            .lock()
+            .ok()
+            .and_then(|learned| learned.get(&Self::model_key(model)).cloned())
+            .unwrap_or_default();
+
+        let Some(body) = request_body.as_object_mut() else {
+            return Vec::new();
+        };
+
+        let builtin = if is_reasoning_model {
+            REASONING_UNSUPPORTED_PARAMS
+        } else {
+            &[]
+        };
+        let mut notes = Vec::new();
+        for param in builtin.iter().map(|p| (*p).to_string()).chain(learned) {
+            if body.remove(&param).is_some() {
+                notes.push(Self::ignored_param_note(&param, model));
+            }
+        }
+        notes
+    }
+
+    /// The request parameter a 400 response rejects as unsupported, if that is
+    /// why the request failed. Nested parameters map to their top-level key.
+    fn unsupported_param_from_error(error_text: &str) -> Option<String> {
+        let json: serde_json::Value = serde_json::from_str(error_text).ok()?;
+        let error = json.get("error")?;
+        let code = error
+            .get("code")
+            .and_then(|c| c.as_str())
+            .unwrap_or_default();
+        let message = error
+            .get("message")
+            .and_then(|m| m.as_str())
+            .unwrap_or_default();
+        if !matches!(code, "unsupported_parameter" | "unsupported_value")
+            && !message.starts_with("Unsupported parameter")
+        {
+            return None;
+        }
+
+        let param = error.get("param").and_then(|p| p.as_str())?;
+        let param = param.split('.').next().unwrap_or(param);
+        (!REQUIRED_PARAMS.contains(&param)).then(|| param.to_string())
+    }
+
+    fn learn_unsupported_param(model: &str, param: &str) {
+        if let Ok(mut learned) = LEARNED_UNSUPPORTED_PARAMS.lock() {
+            learned
+                .entry(Self::model_key(model))
+                .or_default()
+                .insert(param.to_string());
+        }
+    }
+
+    fn build_request(&self, url: &str, api_key: Option<&str>) -> reqwest::RequestBuilder {
+        let mut req_builder = self.client.post(url);
+
+        if let Some(key) = api_key {
+            req_builder = req_builder.header("Authorization", format!("Bearer {key}"));
+        }
+
+        req_builder.header("Content-Type", "application/json")
+    }
+
+    /// Send a Responses API body. A 400 naming an unsupported parameter
+    /// drops it from `request_body`, remembers it for the model, notes it in
+    /// `parameter_notes` and retries once.
+    pub async fn send_responses_request(
+        &self,
+        url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        request_body: &mut serde_json::Value,
+        parameter_notes: &mut Vec<String>,
+    ) -> Result<ResponsesSend, AppError> {
+        let response = self
+            .build_request(url, api_key)
+            .json(request_body)
+            .send()
+            .await?;
+        if response.status() != reqwest::StatusCode::BAD_REQUEST {
+            return Ok(ResponsesSend::Sent(response));
+        }
+
+        let status = response.status();
+        let error_text = response
+            .text()
+            .await
+            .unwrap_or_else(|_| "Unknown error".to_string());
+        let rejected = Self::unsupported_param_from_error(&error_text).filter(|param| {
+            request_body
+                .as_object_mut()
+                .is_some_and(|body| body.remove(param).is_some())
+        });
+        let Some(param) = rejected else {
+            return Ok(ResponsesSend::Rejected(status, error_text));
+        };
+
+        tracing::warn!(model = %model, param = %param, "Retrying without unsupported parameter");
+        Self::learn_unsupported_param(model, &param);
+        parameter_notes.push(Self::ignored_param_note(&param, model));
+
+        let response = self
+            .build_request(url, api_key)
+            .json(request_body)
+            .send()
+            .await?;
+        Ok(ResponsesSend::Sent(response))
+    }
+
+    /// Surface an API error on the message and hand it back.
+    fn report_api_error(
+        app: &AppHandle,
+        chat_id: &str,
+        message_id: &str,
+        error: AppError,
+    ) -> Result<AppError, AppError> {
+        let message_emitter = MessageEmitter::new(app.clone());
+        message_emitter.emit_api_error(chat_id.to_string(), message_id.to_string(), &error)?;
+
+        Ok(error)
+    }
+
     /// Transform messages to the new generalized 'input' format for Responses API
+    /// Tools in the Responses API format. With `strict_tools`, a tool whose
+    /// schema has a strict variant is sent with it and `strict: true`; the
+    /// rest are sent as they are. Also returns the original schemas of the
+    /// strict tools, by name.
+    pub fn responses_tools(
+        tools: Vec<ChatCompletionTool>,
+        strict_tools: bool,
+    ) -> (Vec<serde_json::Value>, HashMap<String, serde_json::Value>) {
+        let mut strict_schemas = HashMap::new();
+        let mut fallbacks = Vec::new();
+        let tools = tools
+            .into_iter()
+            .map(|tool| {
+                let function = tool.function;
+                let parameters = function
+                    .parameters
+                    .unwrap_or_else(|| json!({ "type": "object", "properties": {} }));
+                let strict = if strict_tools {
+                    strict_schema::to_strict(&parameters)
+                        .map_err(|reason| fallbacks.push(format!("{}: {reason}", function.name)))
+                        .ok()
+                } else {
+                    None
+                };
+                let mut entry = json!({
+                    "type": "function",
+                    "name": function.name,
+                    "strict": strict.is_some(),
+                });
+                if let Some(description) = function.description {
+                    entry["description"] = json!(description);
+                }
+                if let Some(strict) = strict {
+                    strict_schemas.insert(function.name, parameters);
+                    entry["parameters"] = strict;
+                } else {
+                    entry["parameters"] = parameters;
+                }
+                entry
+            })
+            .collect();
+        if !fallbacks.is_empty() {
+            tracing::info!(tools = ?fallbacks, "Tools sent without strict mode");
+        }
+        (tools, strict_schemas)
+    }
+
+    /// Drop the `null`s strict mode made the model send for optional
+    /// arguments, so tools see the arguments as left out.
+    fn drop_strict_nulls(
+        response: &mut LLMChatResponse,
+        strict_schemas: &HashMap<String, serde_json::Value>,
+    ) {
+        for call in response.tool_calls.iter_mut().flatten() {
+            let Some(schema) = strict_schemas.get(&call.function.name) else {
+                continue;
+            };
+            if let Ok(mut arguments) = serde_json::from_str(&call.function.arguments) {
+                strict_schema::drop_null_optionals(&mut arguments, schema);
+                call.function.arguments = arguments.to_string();
+            }
+        }
+    }
+
     fn transform_messages_to_input(messages: Vec<ChatMessage>) -> Vec<serde_json::Value> {
         messages
             .into_iter()
@@ -162,41 +424,90 @@ impl OpenAIProvider {
             .collect()
     }
 
+    /// The Responses API body for `request`, without its tools; the messages
+    /// are moved out of the request into it. Parameters left out for the
+    /// model are noted in `parameter_notes`.
+    pub fn responses_body(
+        request: &mut LLMChatRequest,
+        parameter_notes: &mut Vec<String>,
+    ) -> serde_json::Value {
+        let model = request.model.clone();
+
+        // Transform to new input format
+        let input_messages =
+            Self::transform_messages_to_input(std::mem::take(&mut request.messages));
+
+        // Construct body for Responses API
+        let mut request_body = json!({
+            "model": model,
+            "input": input_messages,
+            "stream": true, // Always stream for event-driven handling
+        });
+        let body = request_body.as_object_mut().unwrap();
+
+        if let Some(temp) = request.temperature {
+            body.insert("temperature".to_string(), json!(temp));
+        }
+        // Reasoning models lose these with the temperature, others that
+        // reject them through the unsupported-parameter retry in `chat`
+        for (key, value) in [
+            ("top_p", request.top_p),
+            ("frequency_penalty", request.frequency_penalty),
+            ("presence_penalty", request.presence_penalty),
+        ] {
+            if let Some(value) = value {
+                body.insert(key.to_string(), json!(value));
+            }
+        }
+
+        if let Some(max_tokens) = request.max_tokens {
+            body.insert("max_output_tokens".to_string(), json!(max_tokens));
+        }
+
+        // Models that reject it lose it through the unsupported-parameter
+        // retry in `chat`, which leaves a note the answer records
+        if let Some(seed) = request.seed {
+            body.insert("seed".to_string(), json!(seed));
+        }
+
+        if let Some(effort) = request.reasoning_effort.as_deref() {
+            let (_, supports_thinking, _) = Self::check_model_capabilities(&model);
+            match Self::map_reasoning_effort(effort) {
+                // Thinking turned off: leave reasoning at the model default
+                None if effort == REASONING_EFFORT_NONE => {}
+                Some(effort) if supports_thinking => {
+                    body.insert("reasoning".to_string(), json!({ "effort": effort }));
+                }
+                _ => parameter_notes.push(Self::ignored_param_note("reasoning", &model)),
+            }
+        }
+        request_body
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn handle_responses_api(
         &self,
-        req_builder: reqwest::RequestBuilder,
-        request_body: serde_json::Value,
+        response: reqwest::Response,
         chat_id: String,
         message_id: String,
         app: AppHandle,
         mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
         is_streaming_requested: bool,
+        parameter_notes: Vec<String>,
+        output_limits: Option<OutputLimits>,
     ) -> Result<LLMChatResponse, AppError> {
-        let response = req_builder.json(&request_body).send().await?;
-
         // Handle error responses before creating emitters
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("OpenAI Responses API error ({status}): {error_text}");
-
-            let message_emitter = MessageEmitter::new(app.clone());
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-
-            return Err(AppError::Llm(error_msg));
+            let error = rate_limit::read_api_error(RESPONSES_API_LABEL, response).await;
+            return Err(Self::report_api_error(&app, &chat_id, &message_id, error)?);
         }
 
         let message_emitter = MessageEmitter::new(app.clone());
         let tool_emitter = ToolEmitter::new(app.clone());
 
-        let mut stream = response.bytes_stream();
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats =
+            is_streaming_requested.then(|| super::start_stream_stats(&app, &chat_id, &message_id));
         let mut buffer = String::new();
 
         // State for aggregation
@@ -204,25 +515,19 @@ impl OpenAIProvider {
         let mut tool_calls: Vec<ToolCall> = Vec::new();
         let mut finish_reason: Option<String> = None;
         let mut final_usage: Option<TokenUsage> = None;
+        let mut served_model: Option<String> = None;
         let tool_calls_emitted = false;
+        let mut limiter = OutputLimiter::new(output_limits);
 
-        while let Some(item) = tokio::select! {
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
             next_item = stream.next() => next_item,
-            () = async {
-                if let Some(ref mut rx) = cancellation_rx {
-                    let _ = rx.recv().await;
-                }
-                futures::future::pending::<()>().await;
-            }, if cancellation_rx.is_some() => {
-                message_emitter.emit_message_error(
-                    chat_id.clone(),
-                    message_id.clone(),
-                    "Message cancelled by user".to_string(),
-                )?;
-                return Err(AppError::Cancelled);
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
             }
         } {
-            let chunk = item.map_err(|e| AppError::Generic(format!("Stream error: {e}")))?;
             let text = String::from_utf8_lossy(&chunk);
             buffer.push_str(&text);
 
@@ -279,7 +584,15 @@ impl OpenAIProvider {
                                     .or_else(|| data.get("content").and_then(|s| s.as_str()));
 
                                 if let Some(text) = content_candidate {
+                                    let text = limiter.admit(
+                                        OutputKind::Content,
+                                        full_content.len(),
+                                        text,
+                                    );
                                     full_content.push_str(text);
+                                    if let Some(stats) = &stats {
+                                        stats.record_chars(text);
+                                    }
                                     if is_streaming_requested {
                                         message_emitter.emit_message_chunk(
                                             chat_id.clone(),
@@ -309,7 +622,9 @@ impl OpenAIProvider {
                                     .get("function")
                                     .and_then(|f| f.get("arguments"))
                                     .and_then(|s| s.as_str())
-                                    .unwrap_or_default()
+                                    .unwrap_or_default();
+                                let args = limiter
+                                    .admit(OutputKind::ToolArguments, 0, args)
                                     .to_string();
 
                                 // Only add if we have at least a name or ID
@@ -340,6 +655,8 @@ impl OpenAIProvider {
                                             .get("total_tokens")
                                             .and_then(serde_json::Value::as_u64)
                                             .map(|v| v as u32),
+                                        cached_tokens: None,
+                                        cache_write_tokens: None,
                                     });
                                 }
                             }
@@ -348,6 +665,14 @@ impl OpenAIProvider {
                                 {
                                     finish_reason = Some(fr.to_string());
                                 }
+                                // The snapshot the model alias resolved to
+                                if let Some(model) = data
+                                    .get("response")
+                                    .and_then(|r| r.get("model"))
+                                    .and_then(|m| m.as_str())
+                                {
+                                    served_model = Some(model.to_string());
+                                }
                                 if let Some(u) = data.get("usage") {
                                     final_usage = u.as_object().map(|obj| TokenUsage {
                                         prompt_tokens: obj
@@ -362,6 +687,8 @@ impl OpenAIProvider {
                                             .get("total_tokens")
                                             .and_then(serde_json::Value::as_u64)
                                             .map(|v| v as u32),
+                                        cached_tokens: None,
+                                        cache_write_tokens: None,
                                     });
                                 }
                             }
@@ -379,7 +706,15 @@ impl OpenAIProvider {
                                             .and_then(|d| d.get("content"))
                                             .and_then(|s| s.as_str())
                                         {
+                                            let content = limiter.admit(
+                                                OutputKind::Content,
+                                                full_content.len(),
+                                                content,
+                                            );
                                             full_content.push_str(content);
+                                            if let Some(stats) = &stats {
+                                                stats.record_chars(content);
+                                            }
                                             if is_streaming_requested {
                                                 message_emitter.emit_message_chunk(
                                                     chat_id.clone(),
@@ -406,17 +741,42 @@ impl OpenAIProvider {
                     }
                 }
             }
+
+            if limiter.should_stop(&buffer) {
+                break;
+            }
+        }
+
+        if let Some(stats) = &stats {
+            stats.stop();
+        }
+
+        // A stream that broke off keeps what arrived, but not its unfinished tool calls
+        if stream.finish(!full_content.is_empty())? {
+            tool_calls.clear();
+            finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        }
+
+        let capped = limiter.end_at_cap(stream, &mut tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+        if capped {
+            finish_reason = Some(LENGTH_LIMIT.to_string());
         }
 
         // Final tool emission
-        if !tool_calls.is_empty() && !tool_calls_emitted {
+        if !tool_calls.is_empty() && !tool_calls_emitted && !cancelled {
             let event_tool_calls: Vec<crate::events::ToolCall> = tool_calls
                 .iter()
                 .map(|tc| crate::events::ToolCall {
                     id: tc.id.clone(),
                     name: tc.function.name.clone(),
-                    arguments: serde_json::from_str(&tc.function.arguments)
-                        .unwrap_or_else(|_| serde_json::json!({})),
+                    arguments: json_repair::arguments_value(&tc.function.arguments),
                 })
                 .collect();
 
@@ -427,16 +787,25 @@ impl OpenAIProvider {
             )?;
         }
 
-        message_emitter.emit_message_complete(
-            chat_id.clone(),
-            message_id.clone(),
-            full_content.clone(),
-            final_usage.as_ref().map(|u| EventTokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-        )?;
+        if cancelled {
+            message_emitter.emit_message_cancelled(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+            )?;
+            finish_reason = Some("cancelled".to_string());
+        } else {
+            message_emitter.emit_message_complete(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+                final_usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            )?;
+        }
 
         Ok(LLMChatResponse {
             content: full_content,
@@ -449,12 +818,25 @@ impl OpenAIProvider {
             usage: final_usage,
             reasoning: None,
             images: None,
+            parameter_notes,
+            model: served_model,
+            system_fingerprint: None,
         })
     }
 }
 
 #[async_trait]
 impl LLMProvider for OpenAIProvider {
+    async fn embed(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        super::openai_embeddings(&self.client, base_url, api_key, model, inputs).await
+    }
+
     async fn fetch_models(
         &self,
         base_url: &str,
@@ -473,14 +855,7 @@ impl LLMProvider for OpenAIProvider {
         let response = req_builder.send().await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::Llm(format!(
-                "OpenAI API error ({status}): {error_text}"
-            )));
+            return Err(rate_limit::read_api_error("OpenAI API error", response).await);
         }
 
         let json: serde_json::Value = response.json().await?;
@@ -526,56 +901,175 @@ impl LLMProvider for OpenAIProvider {
         &self,
         base_url: &str,
         api_key: Option<&str>,
-        request: LLMChatRequest,
+        mut request: LLMChatRequest,
         chat_id: String,
         message_id: String,
         app: AppHandle,
         cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
     ) -> Result<LLMChatResponse, AppError> {
         let url = format!("{}/responses", base_url.trim_end_matches('/'));
+        let model = request.model.clone();
+        let mut parameter_notes = Vec::new();
+        let mut request_body = Self::responses_body(&mut request, &mut parameter_notes);
+        let body = request_body.as_object_mut().unwrap();
 
-        let mut req_builder = self.client.post(&url);
+        // Add tools if present
+        let config = request.connection_config.unwrap_or_default();
+        let strict_schemas = request.tools.map_or_else(HashMap::new, |tools| {
+            let (tools, strict_schemas) = Self::responses_tools(tools, config.strict_tools);
+            body.insert("tools".to_string(), json!(tools));
+            if let Some(parallel) = config.parallel_tool_calls {
+                body.insert("parallel_tool_calls".to_string(), json!(parallel));
+            }
+            strict_schemas
+        });
 
-        if let Some(key) = api_key {
-            req_builder = req_builder.header("Authorization", format!("Bearer {key}"));
-        }
+        parameter_notes.extend(Self::filter_unsupported_params(&model, &mut request_body));
 
-        req_builder = req_builder.header("Content-Type", "application/json");
+        let response = match self
+            .send_responses_request(
+                &url,
+                api_key,
+                &model,
+                &mut request_body,
+                &mut parameter_notes,
+            )
+            .await?
+        {
+            ResponsesSend::Sent(response) => response,
+            ResponsesSend::Rejected(status, error_text) => {
+                let error = rate_limit::api_error(
+                    RESPONSES_API_LABEL,
+                    status,
+                    &RateLimitInfo::default(),
+                    &error_text,
+                );
+                return Err(Self::report_api_error(&app, &chat_id, &message_id, error)?);
+            }
+        };
 
-        // Transform to new input format
-        let input_messages = Self::transform_messages_to_input(request.messages);
+        let mut llm_response = self
+            .handle_responses_api(
+                response,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                request.stream,
+                parameter_notes,
+                request.output_limits,
+            )
+            .await?;
+        Self::drop_strict_nulls(&mut llm_response, &strict_schemas);
+        Ok(llm_response)
+    }
+}
 
-        // Construct body for Responses API
-        let mut request_body = json!({
-            "model": request.model,
-            "input": input_messages,
-            "stream": true, // Always stream for event-driven handling
-        });
+#[cfg(test)]
+mod tests {
+    use crate::test_support::mock_json_endpoint;
 
-        if let Some(temp) = request.temperature {
-            request_body
-                .as_object_mut()
-                .unwrap()
-                .insert("temperature".to_string(), json!(temp));
-        }
+    #[test]
+    fn reasoning_models_lose_sampling_parameters_up_front() {
+        use crate::services::llm::providers::OpenAIProvider;
 
-        // Add tools if present
-        if let Some(tools) = request.tools {
-            request_body
-                .as_object_mut()
-                .unwrap()
-                .insert("tools".to_string(), json!(tools));
+        let sampled = || {
+            serde_json::json!({
+                "model": "o3-mini", "input": [], "stream": true,
+                "temperature": 0.7, "top_p": 0.9, "presence_penalty": 0.5,
+                "max_output_tokens": 256,
+            })
+        };
+        let mut body = sampled();
+        let notes = OpenAIProvider::filter_unsupported_params("o3-mini", &mut body);
+
+        for param in ["temperature", "top_p", "presence_penalty"] {
+            assert!(body.get(param).is_none(), "{param}");
         }
+        assert_eq!(body["max_output_tokens"], 256);
+        assert_eq!(
+            notes,
+            [
+                "temperature ignored: not supported by o3-mini",
+                "top_p ignored: not supported by o3-mini",
+                "presence_penalty ignored: not supported by o3-mini",
+            ]
+        );
+
+        // Other models keep their settings
+        let mut body = sampled();
+        assert!(OpenAIProvider::filter_unsupported_params("gpt-4o", &mut body).is_empty());
+        assert_eq!(body["temperature"], 0.7);
+        assert_eq!(body["top_p"], 0.9);
+    }
 
-        self.handle_responses_api(
-            req_builder,
-            request_body,
-            chat_id,
-            message_id,
-            app,
-            cancellation_rx,
-            request.stream,
-        )
-        .await
+    #[test]
+    fn rejected_parameters_are_dropped_retried_once_and_learned() {
+        use crate::services::llm::providers::openai::ResponsesSend;
+        use crate::services::llm::providers::OpenAIProvider;
+
+        // A model name of its own, so no other test has taught it anything
+        const MODEL: &str = "gpt-4o-retry-probe";
+        let unsupported = |param: &str| {
+            serde_json::json!({ "error": {
+                "message": format!("Unsupported parameter: '{param}' is not supported with this model."),
+                "type": "invalid_request_error",
+                "param": param,
+                "code": "unsupported_parameter"
+            }})
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let provider = OpenAIProvider::new(std::sync::Arc::new(reqwest::Client::new()));
+            let (url, received) = mock_json_endpoint(vec![
+                (400, unsupported("temperature")),
+                (200, serde_json::json!({})),
+                // The retry is only tried once
+                (400, unsupported("top_p")),
+                (400, unsupported("seed")),
+                // A 400 for another reason is not retried
+                (400, serde_json::json!({ "error": { "message": "Invalid 'input'" } })),
+            ])
+            .await;
+            let url = format!("{url}/responses");
+
+            let mut body = serde_json::json!({
+                "model": MODEL, "input": [], "stream": true,
+                "temperature": 0.7, "top_p": 0.9,
+            });
+            let mut notes = Vec::new();
+            let sent = provider
+                .send_responses_request(&url, None, MODEL, &mut body, &mut notes)
+                .await
+                .unwrap();
+            assert!(matches!(sent, ResponsesSend::Sent(r) if r.status() == 200));
+            assert!(body.get("temperature").is_none());
+            assert_eq!(notes, [format!("temperature ignored: not supported by {MODEL}")]);
+
+            // Learned: the next request leaves the temperature out up front
+            let mut next = serde_json::json!({ "model": MODEL, "temperature": 0.2 });
+            assert_eq!(
+                OpenAIProvider::filter_unsupported_params(MODEL, &mut next),
+                [format!("temperature ignored: not supported by {MODEL}")]
+            );
+            assert!(next.get("temperature").is_none());
+
+            let mut notes = Vec::new();
+            let sent = provider
+                .send_responses_request(&url, None, MODEL, &mut body, &mut notes)
+                .await
+                .unwrap();
+            assert!(matches!(sent, ResponsesSend::Sent(r) if r.status() == 400));
+            assert_eq!(notes.len(), 1);
+
+            let sent = provider
+                .send_responses_request(&url, None, MODEL, &mut body, &mut Vec::new())
+                .await
+                .unwrap();
+            assert!(
+                matches!(sent, ResponsesSend::Rejected(status, text) if status == 400 && text.contains("Invalid 'input'"))
+            );
+            assert_eq!(received.lock().unwrap().len(), 5);
+        });
     }
 }
@@ -1,11 +1,15 @@
 use super::LLMProvider;
 use crate::error::AppError;
 use crate::events::{MessageEmitter, TokenUsage as EventTokenUsage, ToolEmitter};
+use crate::features::llm_connection::config::REASONING_EFFORT_NONE;
 use crate::models::llm_types::{
     LLMChatRequest, LLMChatResponse, LLMModel, SSEChunk, TokenUsage, ToolCall, ToolCallFunction,
 };
+use crate::services::llm::json_repair;
+use crate::services::llm::output_limit::{OutputKind, OutputLimiter, OutputLimits, LENGTH_LIMIT};
+use crate::services::llm::rate_limit;
+use crate::services::llm::stream_recovery::{GuardedStream, STREAM_INTERRUPTED};
 use async_trait::async_trait;
-use futures::StreamExt;
 use reqwest::Client;
 use std::sync::Arc;
 use tauri::AppHandle;
@@ -40,6 +44,7 @@ impl OpenAICompatProvider {
         (supports_tools, supports_thinking, false)
     }
 
+    #[allow(clippy::too_many_arguments)]
     async fn handle_streaming(
         &self,
         req_builder: reqwest::RequestBuilder,
@@ -48,6 +53,7 @@ impl OpenAICompatProvider {
         message_id: String,
         app: AppHandle,
         mut cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+        output_limits: Option<OutputLimits>,
     ) -> Result<LLMChatResponse, AppError> {
         let response = req_builder.json(&request_body).send().await?;
 
@@ -55,23 +61,13 @@ impl OpenAICompatProvider {
         let tool_emitter = ToolEmitter::new(app.clone());
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("LLM API error ({status}): {error_text}");
-
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-
-            return Err(AppError::Llm(error_msg));
+            let error = rate_limit::read_api_error("LLM API error", response).await;
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
-        let mut stream = response.bytes_stream();
+        let mut stream = GuardedStream::new(response.bytes_stream());
+        let stats = super::start_stream_stats(&app, &chat_id, &message_id);
         let mut full_content = String::new();
         let mut full_reasoning = String::new();
         let mut tool_calls: Vec<ToolCall> = Vec::new();
@@ -79,27 +75,21 @@ impl OpenAICompatProvider {
         let mut buffer = String::new();
         let mut tool_calls_emitted = false; // Track if we've already emitted tool calls
         let mut final_usage: Option<TokenUsage> = None;
+        let mut served_model: Option<String> = None;
+        let mut system_fingerprint: Option<String> = None;
+        let mut limiter = OutputLimiter::new(output_limits);
 
-        while let Some(item) = tokio::select! {
+        let mut cancelled = false;
+        while let Some(chunk) = tokio::select! {
             // Listen for stream chunks
             next_item = stream.next() => next_item,
             // Listen for cancellation signal
-            () = async {
-                if let Some(ref mut rx) = cancellation_rx {
-                    let _ = rx.recv().await;
-                }
-                futures::future::pending::<()>().await;
-            }, if cancellation_rx.is_some() => {
-                // Cancellation received
-                message_emitter.emit_message_error(
-                    chat_id.clone(),
-                    message_id.clone(),
-                    "Message cancelled by user".to_string(),
-                )?;
-                return Err(AppError::Cancelled);
+            () = super::wait_for_cancellation(&mut cancellation_rx) => {
+                // Stop reading; whatever arrived so far is returned below
+                cancelled = true;
+                None
             }
         } {
-            let chunk = item.map_err(|e| AppError::Generic(format!("Stream error: {e}")))?;
             let text = String::from_utf8_lossy(&chunk);
             buffer.push_str(&text);
 
@@ -121,28 +111,47 @@ impl OpenAICompatProvider {
                             if let Some(usage) = sse_chunk.usage {
                                 final_usage = Some(usage);
                             }
+                            // What answered, for reproducing the answer later
+                            if sse_chunk.model.is_some() {
+                                served_model = sse_chunk.model;
+                            }
+                            if sse_chunk.system_fingerprint.is_some() {
+                                system_fingerprint = sse_chunk.system_fingerprint;
+                            }
                             if let Some(choices) = sse_chunk.choices {
                                 for choice in choices {
                                     if let Some(delta) = choice.delta {
                                         // Handle content delta
                                         if let Some(ref content) = delta.content {
+                                            let content = limiter.admit(
+                                                OutputKind::Content,
+                                                full_content.len(),
+                                                content,
+                                            );
                                             full_content.push_str(content);
+                                            stats.record_chars(content);
 
                                             // Emit chunk event
                                             message_emitter.emit_message_chunk(
                                                 chat_id.clone(),
                                                 message_id.clone(),
-                                                content.clone(),
+                                                content.to_string(),
                                             )?;
                                         }
 
                                         // Handle reasoning/thinking content
                                         if let Some(reasoning) = delta.get_reasoning() {
-                                            full_reasoning.push_str(&reasoning);
+                                            let reasoning = limiter.admit(
+                                                OutputKind::Reasoning,
+                                                full_reasoning.len(),
+                                                &reasoning,
+                                            );
+                                            full_reasoning.push_str(reasoning);
+                                            stats.record_chars(reasoning);
                                             message_emitter.emit_thinking_chunk(
                                                 chat_id.clone(),
                                                 message_id.clone(),
-                                                reasoning,
+                                                reasoning.to_string(),
                                             )?;
                                         }
 
@@ -180,10 +189,14 @@ impl OpenAICompatProvider {
                                                     .as_ref()
                                                     .and_then(|f| f.arguments.as_ref())
                                                 {
-                                                    tool_calls[index]
-                                                        .function
-                                                        .arguments
-                                                        .push_str(args);
+                                                    let arguments =
+                                                        &mut tool_calls[index].function.arguments;
+                                                    let args = limiter.admit(
+                                                        OutputKind::ToolArguments,
+                                                        arguments.len(),
+                                                        args,
+                                                    );
+                                                    arguments.push_str(args);
                                                 }
                                             }
 
@@ -234,17 +247,40 @@ impl OpenAICompatProvider {
                     }
                 }
             }
+
+            if limiter.should_stop(&buffer) {
+                break;
+            }
+        }
+
+        stats.stop();
+
+        // A stream that broke off keeps what arrived, but not its unfinished tool calls
+        if stream.finish(!full_content.is_empty() || !full_reasoning.is_empty())? {
+            tool_calls.clear();
+            finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        }
+
+        let capped = limiter.end_at_cap(stream, &mut tool_calls, |kind, limit| {
+            message_emitter.emit_output_limit_reached(
+                chat_id.clone(),
+                message_id.clone(),
+                kind.as_str(),
+                limit,
+            )
+        })?;
+        if capped {
+            finish_reason = Some(LENGTH_LIMIT.to_string());
         }
 
         // Emit tool calls detected event if we haven't already and have tool calls
-        if !tool_calls_emitted && !tool_calls.is_empty() {
+        if !tool_calls_emitted && !tool_calls.is_empty() && !cancelled {
             let tool_calls: Vec<crate::events::ToolCall> = tool_calls
                 .iter()
                 .map(|tc| crate::events::ToolCall {
                     id: tc.id.clone(),
                     name: tc.function.name.clone(),
-                    arguments: serde_json::from_str(&tc.function.arguments)
-                        .unwrap_or_else(|_| serde_json::json!({})),
+                    arguments: json_repair::arguments_value(&tc.function.arguments),
                 })
                 .collect();
 
@@ -255,18 +291,27 @@ impl OpenAICompatProvider {
             )?;
         }
 
-        // Emit complete event
-        // SSE doesn't provide usage in chunks, would need final chunk
-        message_emitter.emit_message_complete(
-            chat_id.clone(),
-            message_id.clone(),
-            full_content.clone(),
-            final_usage.as_ref().map(|u| EventTokenUsage {
-                prompt_tokens: u.prompt_tokens,
-                completion_tokens: u.completion_tokens,
-                total_tokens: u.total_tokens,
-            }),
-        )?;
+        if cancelled {
+            message_emitter.emit_message_cancelled(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+            )?;
+            finish_reason = Some("cancelled".to_string());
+        } else {
+            // Emit complete event
+            // SSE doesn't provide usage in chunks, would need final chunk
+            message_emitter.emit_message_complete(
+                chat_id.clone(),
+                message_id.clone(),
+                full_content.clone(),
+                final_usage.as_ref().map(|u| EventTokenUsage {
+                    prompt_tokens: u.prompt_tokens,
+                    completion_tokens: u.completion_tokens,
+                    total_tokens: u.total_tokens,
+                }),
+            )?;
+        }
 
         Ok(LLMChatResponse {
             content: full_content,
@@ -283,6 +328,9 @@ impl OpenAICompatProvider {
                 Some(full_reasoning)
             },
             images: None,
+            parameter_notes: Vec::new(),
+            model: served_model,
+            system_fingerprint,
         })
     }
 
@@ -301,21 +349,10 @@ impl OpenAICompatProvider {
             .map_err(|e| AppError::Generic(format!("HTTP request failed: {e}")))?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            let error_msg = format!("LLM API error ({status}): {error_text}");
-
+            let error = rate_limit::read_api_error("LLM API error", response).await;
             let message_emitter = MessageEmitter::new(app.clone());
-            message_emitter.emit_message_error(
-                chat_id.clone(),
-                message_id.clone(),
-                error_msg.clone(),
-            )?;
-
-            return Err(AppError::Generic(error_msg));
+            message_emitter.emit_api_error(chat_id.clone(), message_id.clone(), &error)?;
+            return Err(error);
         }
 
         let json_response: serde_json::Value = response
@@ -422,6 +459,8 @@ impl OpenAICompatProvider {
                 .get("total_tokens")
                 .and_then(serde_json::Value::as_u64)
                 .map(|v| v as u32),
+            cached_tokens: None,
+            cache_write_tokens: None,
         });
 
         // Emit complete event
@@ -443,12 +482,59 @@ impl OpenAICompatProvider {
             usage,
             reasoning: None,
             images: None,
+            parameter_notes: Vec::new(),
+            model: response_text(&json_response, "model"),
+            system_fingerprint: response_text(&json_response, "system_fingerprint"),
         })
     }
+
+    /// Send a chat in the OpenAI format to an addressed and authorized
+    /// request, for servers that differ only in those.
+    pub(super) async fn send_chat(
+        &self,
+        req_builder: reqwest::RequestBuilder,
+        mut request: LLMChatRequest,
+        chat_id: String,
+        message_id: String,
+        app: AppHandle,
+        cancellation_rx: Option<tokio::sync::broadcast::Receiver<()>>,
+    ) -> Result<LLMChatResponse, AppError> {
+        // Thinking turned off: send no reasoning effort at all
+        if request.reasoning_effort.as_deref() == Some(REASONING_EFFORT_NONE) {
+            request.reasoning_effort = None;
+        }
+        let request_body = serde_json::to_value(&request)?;
+
+        if request.stream {
+            self.handle_streaming(
+                req_builder,
+                request_body,
+                chat_id,
+                message_id,
+                app,
+                cancellation_rx,
+                request.output_limits,
+            )
+            .await
+        } else {
+            self.handle_non_streaming(req_builder, request_body, chat_id, message_id, app)
+                .await
+        }
+    }
 }
 
 #[async_trait]
 impl LLMProvider for OpenAICompatProvider {
+    async fn embed(
+        &self,
+        base_url: &str,
+        api_key: Option<&str>,
+        model: &str,
+        inputs: &[String],
+    ) -> Result<Vec<Vec<f32>>, AppError> {
+        super::openai_embeddings(&self.client, base_url, api_key, model, inputs).await
+    }
+
     async fn fetch_models(
         &self,
         base_url: &str,
@@ -488,14 +574,7 @@ impl LLMProvider for OpenAICompatProvider {
         let response = req_builder.send().await?;
 
         if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response
-                .text()
-                .await
-                .unwrap_or_else(|_| "Unknown error".to_string());
-            return Err(AppError::Llm(format!(
-                "LLM API error ({status}): {error_text}"
-            )));
+            return Err(rate_limit::read_api_error("LLM API error", response).await);
         }
 
         let json: serde_json::Value = response.json().await?;
@@ -596,21 +675,22 @@ impl LLMProvider for OpenAICompatProvider {
 
         req_builder = req_builder.header("Content-Type", "application/json");
 
-        let request_body = serde_json::to_value(&request)?;
-
-        if request.stream {
-            self.handle_streaming(
-                req_builder,
-                request_body,
-                chat_id,
-                message_id,
-                app,
-                cancellation_rx,
-            )
-            .await
-        } else {
-            self.handle_non_streaming(req_builder, request_body, chat_id, message_id, app)
-                .await
-        }
+        self.send_chat(
+            req_builder,
+            request,
+            chat_id,
+            message_id,
+            app,
+            cancellation_rx,
+        )
+        .await
     }
 }
+
+/// A top-level string field of a chat completion.
+fn response_text(response: &serde_json::Value, field: &str) -> Option<String> {
+    response
+        .get(field)
+        .and_then(|value| value.as_str())
+        .map(str::to_string)
+}
@@ -0,0 +1,482 @@
+//! Rate-limit hints of a failed provider response: how long to wait before
+//! the next request and which limit ran out.
+//!
+//! Providers state them differently. OpenAI sends `x-ratelimit-*` headers
+//! with durations such as `6m0s`, Anthropic `retry-after` and
+//! `anthropic-ratelimit-*` headers with RFC 3339 times, and Google a
+//! `RetryInfo` detail in the error body.
+
+use crate::error::AppError;
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// Which limit a rate-limited request ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RateLimitKind {
+    Requests,
+    Tokens,
+}
+
+/// What a provider said about its limits on a failed response.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RateLimitInfo {
+    pub retry_after_ms: Option<u64>,
+    pub limit_kind: Option<RateLimitKind>,
+    pub remaining_requests: Option<u64>,
+    pub remaining_tokens: Option<u64>,
+    pub requests_reset_ms: Option<u64>,
+    pub tokens_reset_ms: Option<u64>,
+}
+
+impl RateLimitInfo {
+    /// Hints of the response headers, with `now_ms` to turn reset times into
+    /// waits.
+    pub fn from_headers(headers: &HeaderMap, now_ms: i64) -> Self {
+        let header = |name: &str| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::trim)
+                .filter(|value| !value.is_empty())
+        };
+        let count = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| header(name).and_then(|value| value.parse::<u64>().ok()))
+        };
+        let reset = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| header(name).and_then(|value| parse_reset(value, now_ms)))
+        };
+
+        let retry_after_ms = header("retry-after-ms")
+            .and_then(|value| value.parse::<f64>().ok())
+            .filter(|ms| ms.is_finite() && *ms >= 0.0)
+            .map(millis)
+            .or_else(|| header("retry-after").and_then(|value| parse_retry_after(value, now_ms)));
+
+        Self {
+            retry_after_ms,
+            limit_kind: None,
+            remaining_requests: count(&[
+                "x-ratelimit-remaining-requests",
+                "anthropic-ratelimit-requests-remaining",
+            ]),
+            remaining_tokens: count(&[
+                "x-ratelimit-remaining-tokens",
+                "anthropic-ratelimit-tokens-remaining",
+                "anthropic-ratelimit-input-tokens-remaining",
+                "anthropic-ratelimit-output-tokens-remaining",
+            ]),
+            requests_reset_ms: reset(&[
+                "x-ratelimit-reset-requests",
+                "anthropic-ratelimit-requests-reset",
+                "x-ratelimit-reset",
+            ]),
+            tokens_reset_ms: reset(&[
+                "x-ratelimit-reset-tokens",
+                "anthropic-ratelimit-tokens-reset",
+                "anthropic-ratelimit-input-tokens-reset",
+                "anthropic-ratelimit-output-tokens-reset",
+            ]),
+        }
+        .resolved()
+    }
+
+    /// Hints of a Google error body: the `retryDelay` of its `RetryInfo`
+    /// detail, and from the failed quota's metric, which limit it was.
+    pub fn from_google_body(body: &str) -> Self {
+        let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+            return Self::default();
+        };
+        let details = json
+            .pointer("/error/details")
+            .and_then(|details| details.as_array())
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let of_type = |suffix: &'static str| {
+            details.iter().filter(move |detail| {
+                detail
+                    .get("@type")
+                    .and_then(|t| t.as_str())
+                    .is_some_and(|t| t.ends_with(suffix))
+            })
+        };
+
+        let retry_after_ms = of_type("google.rpc.RetryInfo")
+            .find_map(|detail| detail.get("retryDelay").and_then(|d| d.as_str()))
+            .and_then(parse_duration);
+        let limit_kind = of_type("google.rpc.QuotaFailure")
+            .filter_map(|detail| detail.get("violations").and_then(|v| v.as_array()))
+            .flatten()
+            .find_map(|violation| {
+                let metric = violation
+                    .get("quotaMetric")
+                    .or_else(|| violation.get("quotaId"))
+                    .and_then(|m| m.as_str())?
+                    .to_lowercase();
+                if metric.contains("token") {
+                    Some(RateLimitKind::Tokens)
+                } else if metric.contains("request") {
+                    Some(RateLimitKind::Requests)
+                } else {
+                    None
+                }
+            });
+
+        Self {
+            retry_after_ms,
+            limit_kind,
+            ..Self::default()
+        }
+    }
+
+    /// Fill in the limit that ran out, from the remaining counts, and the
+    /// wait, from that limit's reset, where the provider left them out.
+    fn resolved(mut self) -> Self {
+        if self.limit_kind.is_none() {
+            self.limit_kind = if self.remaining_tokens == Some(0) {
+                Some(RateLimitKind::Tokens)
+            } else if self.remaining_requests == Some(0) {
+                Some(RateLimitKind::Requests)
+            } else {
+                None
+            };
+        }
+        if self.retry_after_ms.is_none() {
+            self.retry_after_ms = match self.limit_kind {
+                Some(RateLimitKind::Tokens) => self.tokens_reset_ms,
+                Some(RateLimitKind::Requests) => self.requests_reset_ms,
+                None => self.requests_reset_ms.max(self.tokens_reset_ms),
+            };
+        }
+        self
+    }
+
+    fn merge(self, other: Self) -> Self {
+        Self {
+            retry_after_ms: self.retry_after_ms.or(other.retry_after_ms),
+            limit_kind: self.limit_kind.or(other.limit_kind),
+            remaining_requests: self.remaining_requests.or(other.remaining_requests),
+            remaining_tokens: self.remaining_tokens.or(other.remaining_tokens),
+            requests_reset_ms: self.requests_reset_ms.or(other.requests_reset_ms),
+            tokens_reset_ms: self.tokens_reset_ms.or(other.tokens_reset_ms),
+        }
+    }
+}
+
+/// The error of a failed provider response: `RateLimited` with its hints
+/// for a 429, `Llm` otherwise. `label` names the API in the message.
+pub fn api_error(label: &str, status: StatusCode, info: &RateLimitInfo, body: &str) -> AppError {
+    let message = format!("{label} ({status}): {body}");
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        AppError::RateLimited {
+            message,
+            retry_after_ms: info.retry_after_ms,
+            limit_kind: info.limit_kind,
+        }
+    } else {
+        AppError::Llm(message)
+    }
+}
+
+/// Read a failed provider response into its error, with the rate-limit
+/// hints of both its headers and its body.
+pub async fn read_api_error(label: &str, response: reqwest::Response) -> AppError {
+    let status = response.status();
+    let headers =
+        RateLimitInfo::from_headers(response.headers(), chrono::Utc::now().timestamp_millis());
+    let body = response
+        .text()
+        .await
+        .unwrap_or_else(|_| "Unknown error".to_string());
+    let info = headers.merge(RateLimitInfo::from_google_body(&body));
+    api_error(label, status, &info, &body)
+}
+
+/// A `Retry-After` value: seconds, or an HTTP date.
+pub fn parse_retry_after(value: &str, now_ms: i64) -> Option<u64> {
+    if let Ok(seconds) = value.parse::<f64>() {
+        return (seconds.is_finite() && seconds >= 0.0).then(|| millis(seconds * 1000.0));
+    }
+    chrono::DateTime::parse_from_rfc2822(value)
+        .ok()
+        .map(|at| until(at.timestamp_millis(), now_ms))
+}
+
+/// A reset header: a duration such as `1m30s`, an RFC 3339 time, or epoch
+/// seconds.
+fn parse_reset(value: &str, now_ms: i64) -> Option<u64> {
+    if let Ok(at) = chrono::DateTime::parse_from_rfc3339(value) {
+        return Some(until(at.timestamp_millis(), now_ms));
+    }
+    if let Ok(number) = value.parse::<f64>() {
+        // Large plain numbers are epoch seconds, small ones seconds to wait
+        if !number.is_finite() || number < 0.0 {
+            return None;
+        }
+        #[allow(clippy::cast_possible_truncation)]
+        return Some(if number > 1_000_000_000.0 {
+            until((number * 1000.0) as i64, now_ms)
+        } else {
+            millis(number * 1000.0)
+        });
+    }
+    parse_duration(value)
+}
+
+/// A Go-style duration such as `6m0s`, `1.5s` or `20ms`, in milliseconds.
+pub fn parse_duration(value: &str) -> Option<u64> {
+    let mut rest = value.trim();
+    if rest.is_empty() {
+        return None;
+    }
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let number_len = rest
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(rest.len());
+        let number: f64 = rest[..number_len].parse().ok()?;
+        rest = &rest[number_len..];
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit() || c == '.')
+            .unwrap_or(rest.len());
+        let factor = match &rest[..unit_len] {
+            "h" => 3_600_000.0,
+            "m" => 60_000.0,
+            "s" => 1000.0,
+            "ms" => 1.0,
+            "us" | "µs" => 0.001,
+            "ns" => 0.000_001,
+            _ => return None,
+        };
+        rest = &rest[unit_len..];
+        total += number * factor;
+    }
+    Some(millis(total))
+}
+
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn millis(ms: f64) -> u64 {
+    ms.ceil() as u64
+}
+
+fn until(at_ms: i64, now_ms: i64) -> u64 {
+    u64::try_from(at_ms.saturating_sub(now_ms)).unwrap_or(0)
+}
+
+/// The last rate limit an account ran into, until a request succeeds again.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RateLimitStatus {
+    pub limited_at: i64,
+    pub retry_after_ms: Option<u64>,
+    pub limit_kind: Option<RateLimitKind>,
+    /// Whether the wait is still running. With no stated wait,
+    /// `UNKNOWN_WAIT_MS` is assumed.
+    pub active: bool,
+    pub message: String,
+}
+
+/// Wait assumed when a provider refuses a request without saying how long.
+pub const UNKNOWN_WAIT_MS: u64 = 60_000;
+
+impl RateLimitStatus {
+    pub fn from_error(error: &AppError, now_ms: i64) -> Option<Self> {
+        let AppError::RateLimited {
+            message,
+            retry_after_ms,
+            limit_kind,
+        } = error
+        else {
+            return None;
+        };
+        Some(Self {
+            limited_at: now_ms,
+            retry_after_ms: *retry_after_ms,
+            limit_kind: *limit_kind,
+            active: true,
+            message: message.clone(),
+        })
+    }
+
+    /// This status as of `now_ms`.
+    #[must_use]
+    pub fn at(mut self, now_ms: i64) -> Self {
+        let wait =
+            i64::try_from(self.retry_after_ms.unwrap_or(UNKNOWN_WAIT_MS)).unwrap_or(i64::MAX);
+        self.active = now_ms < self.limited_at.saturating_add(wait);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    fn rate_limit_headers(pairs: &[(&'static str, &str)]) -> reqwest::header::HeaderMap {
+        let mut headers = reqwest::header::HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    /// 2023-11-14T22:13:20Z
+    const RATE_LIMIT_NOW_MS: i64 = 1_700_000_000_000;
+
+    #[test]
+    fn rate_limit_hints_of_openai_429() {
+        use crate::error::AppError;
+        use crate::services::llm::rate_limit::{api_error, RateLimitInfo, RateLimitKind};
+
+        let headers = rate_limit_headers(&[
+            ("x-ratelimit-limit-requests", "500"),
+            ("x-ratelimit-remaining-requests", "499"),
+            ("x-ratelimit-remaining-tokens", "0"),
+            ("x-ratelimit-reset-requests", "120ms"),
+            ("x-ratelimit-reset-tokens", "6m0s"),
+        ]);
+        let info = RateLimitInfo::from_headers(&headers, RATE_LIMIT_NOW_MS);
+        assert_eq!(info.limit_kind, Some(RateLimitKind::Tokens));
+        assert_eq!(info.retry_after_ms, Some(360_000));
+        assert_eq!(info.requests_reset_ms, Some(120));
+
+        let body = r#"{"error":{"message":"Rate limit reached for gpt-4o","type":"tokens","code":"rate_limit_exceeded"}}"#;
+        let error = api_error(
+            "OpenAI Responses API error",
+            reqwest::StatusCode::TOO_MANY_REQUESTS,
+            &info,
+            body,
+        );
+        match error {
+            AppError::RateLimited {
+                message,
+                retry_after_ms,
+                limit_kind,
+            } => {
+                assert!(message.contains("429"));
+                assert_eq!(retry_after_ms, Some(360_000));
+                assert_eq!(limit_kind, Some(RateLimitKind::Tokens));
+            }
+            other => panic!("expected a rate limit error, got {other}"),
+        }
+
+        // Other failures stay plain provider errors
+        assert!(matches!(
+            api_error(
+                "OpenAI Responses API error",
+                reqwest::StatusCode::BAD_REQUEST,
+                &info,
+                body
+            ),
+            AppError::Llm(_)
+        ));
+    }
+
+    #[test]
+    fn rate_limit_hints_of_anthropic_429() {
+        use crate::services::llm::rate_limit::{RateLimitInfo, RateLimitKind};
+
+        let headers = rate_limit_headers(&[
+            ("retry-after", "17"),
+            ("anthropic-ratelimit-requests-remaining", "0"),
+            ("anthropic-ratelimit-requests-reset", "2023-11-14T22:13:50Z"),
+            ("anthropic-ratelimit-tokens-remaining", "12000"),
+            ("anthropic-ratelimit-tokens-reset", "2023-11-14T22:13:21Z"),
+        ]);
+        let info = RateLimitInfo::from_headers(&headers, RATE_LIMIT_NOW_MS);
+        // The stated wait wins over the reset time
+        assert_eq!(info.retry_after_ms, Some(17_000));
+        assert_eq!(info.limit_kind, Some(RateLimitKind::Requests));
+        assert_eq!(info.requests_reset_ms, Some(30_000));
+        assert_eq!(info.tokens_reset_ms, Some(1_000));
+
+        // Without retry-after, the reset of the exhausted limit is the wait
+        let headers = rate_limit_headers(&[
+            ("anthropic-ratelimit-requests-remaining", "0"),
+            ("anthropic-ratelimit-requests-reset", "2023-11-14T22:13:50Z"),
+        ]);
+        let info = RateLimitInfo::from_headers(&headers, RATE_LIMIT_NOW_MS);
+        assert_eq!(info.retry_after_ms, Some(30_000));
+    }
+
+    #[test]
+    fn rate_limit_hints_of_google_429_body() {
+        use crate::services::llm::rate_limit::{RateLimitInfo, RateLimitKind};
+
+        let body = r#"{
+          "error": {
+            "code": 429,
+            "message": "You exceeded your current quota.",
+            "status": "RESOURCE_EXHAUSTED",
+            "details": [
+              {
+                "@type": "type.googleapis.com/google.rpc.QuotaFailure",
+                "violations": [
+                  {
+                    "quotaMetric": "generativelanguage.googleapis.com/generate_content_free_tier_input_token_count",
+                    "quotaId": "GenerateContentInputTokensPerModelPerMinute-FreeTier"
+                  }
+                ]
+              },
+              {
+                "@type": "type.googleapis.com/google.rpc.RetryInfo",
+                "retryDelay": "37s"
+              }
+            ]
+          }
+        }"#;
+        let info = RateLimitInfo::from_google_body(body);
+        assert_eq!(info.retry_after_ms, Some(37_000));
+        assert_eq!(info.limit_kind, Some(RateLimitKind::Tokens));
+
+        assert_eq!(
+            RateLimitInfo::from_google_body("upstream unavailable"),
+            RateLimitInfo::default()
+        );
+    }
+
+    #[test]
+    fn rate_limit_durations_and_status() {
+        use crate::error::AppError;
+        use crate::services::llm::rate_limit::{
+            parse_duration, parse_retry_after, RateLimitKind, RateLimitStatus,
+        };
+
+        assert_eq!(parse_duration("6m0s"), Some(360_000));
+        assert_eq!(parse_duration("1.5s"), Some(1_500));
+        assert_eq!(parse_duration("20ms"), Some(20));
+        assert_eq!(parse_duration("1h2m3s"), Some(3_723_000));
+        assert_eq!(parse_duration("soon"), None);
+        assert_eq!(parse_retry_after("2", RATE_LIMIT_NOW_MS), Some(2_000));
+        assert_eq!(
+            parse_retry_after("Tue, 14 Nov 2023 22:14:20 GMT", RATE_LIMIT_NOW_MS),
+            Some(60_000)
+        );
+
+        let error = AppError::RateLimited {
+            message: "slow down".to_string(),
+            retry_after_ms: Some(5_000),
+            limit_kind: Some(RateLimitKind::Requests),
+        };
+        let status = RateLimitStatus::from_error(&error, RATE_LIMIT_NOW_MS).unwrap();
+        assert!(status.clone().at(RATE_LIMIT_NOW_MS + 4_999).active);
+        assert!(!status.at(RATE_LIMIT_NOW_MS + 5_000).active);
+        assert!(RateLimitStatus::from_error(&AppError::Llm("x".into()), 0).is_none());
+
+        // Plain errors keep the event payload as it was
+        let event = crate::events::MessageErrorEvent {
+            chat_id: "c".to_string(),
+            message_id: "m".to_string(),
+            error: "boom".to_string(),
+            retry_after_ms: None,
+            limit_kind: None,
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({ "chat_id": "c", "message_id": "m", "error": "boom" })
+        );
+    }
+}
@@ -0,0 +1,259 @@
+//! What an answer keeps so it can be reproduced, or at least explained,
+//! later: the seed it was asked for and whether the provider could honour
+//! it, the model snapshot and system fingerprint the provider reported, and
+//! the sampling parameters that were sent.
+//!
+//! A seed travels in the metadata of the user message that asks for it, so
+//! it stays with the turn through the agent loop and can be found again.
+
+use crate::models::llm_types::{LLMChatRequest, LLMChatResponse};
+use serde_json::{json, Value};
+
+/// Metadata key of a user message that asks for a seed.
+pub const SEED_METADATA_KEY: &str = "seed";
+
+/// Metadata key of an answer's reproducibility record.
+pub const REPRODUCIBILITY_METADATA_KEY: &str = "reproducibility";
+
+/// Whether the provider's API takes a `seed`. Anthropic and Gemini have
+/// none, so the field is left out of their requests.
+pub fn accepts_seed(provider: &str) -> bool {
+    !matches!(
+        provider.to_lowercase().as_str(),
+        "anthropic" | "claude" | "google" | "gemini"
+    )
+}
+
+/// Store `seed` in the outgoing message metadata.
+pub fn with_seed(metadata: Option<String>, seed: Option<i64>) -> Option<String> {
+    let Some(seed) = seed else {
+        return metadata;
+    };
+    let mut meta = metadata
+        .and_then(|m| serde_json::from_str::<Value>(&m).ok())
+        .filter(Value::is_object)
+        .unwrap_or_else(|| json!({}));
+    meta[SEED_METADATA_KEY] = json!(seed);
+    Some(meta.to_string())
+}
+
+/// The seed a user message asks for.
+pub fn requested_seed(metadata: Option<&str>) -> Option<i64> {
+    let meta: Value = serde_json::from_str(metadata?).ok()?;
+    meta.get(SEED_METADATA_KEY)?.as_i64()
+}
+
+/// The seed an answer was generated with, from its reproducibility record.
+pub fn answer_seed(metadata: Option<&str>) -> Option<i64> {
+    let meta: Value = serde_json::from_str(metadata?).ok()?;
+    meta.get(REPRODUCIBILITY_METADATA_KEY)?
+        .get("seed")?
+        .as_i64()
+}
+
+/// The parameters of a request that shape its answer, taken before the
+/// request is sent.
+#[derive(Debug, Clone)]
+pub struct Reproducibility {
+    provider: String,
+    model: String,
+    seed: Option<i64>,
+    temperature: Option<f64>,
+    top_p: Option<f64>,
+    frequency_penalty: Option<f64>,
+    presence_penalty: Option<f64>,
+    max_tokens: Option<u32>,
+    reasoning_effort: Option<String>,
+}
+
+impl Reproducibility {
+    pub fn of_request(provider: &str, request: &LLMChatRequest) -> Self {
+        Self {
+            provider: provider.to_string(),
+            model: request.model.clone(),
+            seed: request.seed,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            frequency_penalty: request.frequency_penalty,
+            presence_penalty: request.presence_penalty,
+            max_tokens: request.max_tokens,
+            reasoning_effort: request.reasoning_effort.clone(),
+        }
+    }
+
+    /// Whether the seed reached the model: the provider takes one and did
+    /// not drop it as unsupported.
+    pub fn seed_honored(&self, response: &LLMChatResponse) -> bool {
+        self.seed.is_some()
+            && accepts_seed(&self.provider)
+            && !response
+                .parameter_notes
+                .iter()
+                .any(|note| note.starts_with("seed "))
+    }
+
+    /// The record for the answer's metadata. None when no seed was asked
+    /// for and the provider reported no fingerprint, as there is nothing to
+    /// reproduce by.
+    pub fn metadata(&self, response: &LLMChatResponse) -> Option<Value> {
+        if self.seed.is_none() && response.system_fingerprint.is_none() {
+            return None;
+        }
+        let mut record = json!({
+            "model": response.model.as_deref().unwrap_or(&self.model),
+            "sampling": {
+                "temperature": self.temperature,
+                "maxTokens": self.max_tokens,
+                "reasoningEffort": self.reasoning_effort,
+            },
+        });
+        // Parameters left to the provider's defaults are left out
+        for (key, value) in [
+            ("topP", self.top_p),
+            ("frequencyPenalty", self.frequency_penalty),
+            ("presencePenalty", self.presence_penalty),
+        ] {
+            if let Some(value) = value {
+                record["sampling"][key] = json!(value);
+            }
+        }
+        if let Some(seed) = self.seed {
+            record["seed"] = json!(seed);
+            record["seedHonored"] = json!(self.seed_honored(response));
+        }
+        if let Some(fingerprint) = &response.system_fingerprint {
+            record["systemFingerprint"] = json!(fingerprint);
+        }
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use crate::models::llm_types::{ChatMessage, LLMChatRequest, UserContent};
+    use crate::test_support::{chat_message, scripted_response};
+
+    /// A one-message request with `seed`.
+    pub fn seeded_request(seed: Option<i64>) -> LLMChatRequest {
+        LLMChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage::User {
+                content: UserContent::Text("Hi".to_string()),
+            }],
+            temperature: Some(0.7),
+            max_tokens: Some(256),
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed,
+            output_limits: None,
+        }
+    }
+
+    #[test]
+    fn seeds_reach_only_providers_that_take_them() {
+        use crate::services::llm::providers::{AnthropicProvider, OpenAIProvider};
+        use crate::services::llm::reproducibility::{accepts_seed, Reproducibility};
+
+        // OpenAI-compatible servers get the request as it is serialized
+        let compat = serde_json::to_value(seeded_request(Some(42))).unwrap();
+        assert_eq!(compat["seed"], 42);
+        let compat = serde_json::to_value(seeded_request(None)).unwrap();
+        assert!(compat.get("seed").is_none());
+
+        let mut notes = Vec::new();
+        let body = OpenAIProvider::responses_body(&mut seeded_request(Some(42)), &mut notes);
+        assert_eq!(body["seed"], 42);
+        let body = OpenAIProvider::responses_body(&mut seeded_request(None), &mut notes);
+        assert!(body.get("seed").is_none());
+        assert!(notes.is_empty());
+
+        // Anthropic and Gemini have no seed; it is left out and not honoured
+        let body = serde_json::to_value(AnthropicProvider::build_request(seeded_request(Some(42))))
+            .unwrap();
+        assert!(body.get("seed").is_none());
+        for provider in ["anthropic", "Claude", "google", "gemini"] {
+            assert!(!accepts_seed(provider), "{provider}");
+        }
+        assert!(accepts_seed("openai") && accepts_seed("ollama") && accepts_seed("custom"));
+
+        // The record: seed, whether it was honoured, snapshot and sampling
+        let mut response = scripted_response("Hello");
+        response.model = Some("gpt-4o-2024-08-06".to_string());
+        response.system_fingerprint = Some("fp_abc".to_string());
+        let record = Reproducibility::of_request("openai", &seeded_request(Some(42)))
+            .metadata(&response)
+            .unwrap();
+        assert_eq!(
+            record,
+            serde_json::json!({
+                "seed": 42,
+                "seedHonored": true,
+                "model": "gpt-4o-2024-08-06",
+                "systemFingerprint": "fp_abc",
+                "sampling": { "temperature": 0.7, "maxTokens": 256, "reasoningEffort": null },
+            })
+        );
+        let record = Reproducibility::of_request("gemini", &seeded_request(Some(42)))
+            .metadata(&scripted_response("Hello"))
+            .unwrap();
+        assert_eq!(record["seedHonored"], false);
+        assert_eq!(record["model"], "gpt-4o");
+
+        // A model that rejected the seed leaves a note, so it was not honoured
+        let mut rejected = scripted_response("Hello");
+        rejected
+            .parameter_notes
+            .push("seed ignored: not supported by o3".to_string());
+        let reproducibility = Reproducibility::of_request("openai", &seeded_request(Some(7)));
+        assert!(!reproducibility.seed_honored(&rejected));
+
+        // Nothing to reproduce by: no record
+        assert!(Reproducibility::of_request("openai", &seeded_request(None))
+            .metadata(&scripted_response("Hello"))
+            .is_none());
+    }
+
+    #[test]
+    fn resends_reuse_the_seed_of_the_replaced_answer() {
+        use crate::features::chat::ChatService;
+        use crate::services::llm::reproducibility::{requested_seed, with_seed};
+
+        let metadata = with_seed(Some(r#"{"reply_to_message_id":"m0"}"#.to_string()), Some(9));
+        assert_eq!(requested_seed(metadata.as_deref()), Some(9));
+        assert!(metadata.unwrap().contains("reply_to_message_id"));
+        assert_eq!(with_seed(None, None), None);
+
+        let mut user = chat_message("u1", "user", "Question");
+        user.metadata = with_seed(None, Some(5));
+        let mut tool_step = chat_message("a1", "assistant", "");
+        tool_step.metadata = Some(r#"{"thinking":{}}"#.to_string());
+        let mut answer = chat_message("a2", "assistant", "Answer");
+        answer.metadata = Some(r#"{"reproducibility":{"seed":42,"seedHonored":true}}"#.to_string());
+        let mut later = chat_message("u2", "user", "Next");
+        later.metadata = with_seed(None, Some(77));
+        let history = vec![user, tool_step, answer, later];
+
+        // The answer's recorded seed comes first
+        assert_eq!(ChatService::seed_to_reuse(&history, "u1"), Some(42));
+        // Without an answer, the seed the message asked for
+        assert_eq!(ChatService::seed_to_reuse(&history, "u2"), Some(77));
+        assert_eq!(ChatService::seed_to_reuse(&history[..2], "u1"), Some(5));
+        // Never a seed from past the next user message
+        let mut unseeded = history.clone();
+        unseeded[0].metadata = None;
+        unseeded[2].metadata = None;
+        assert_eq!(ChatService::seed_to_reuse(&unseeded, "u1"), None);
+        assert_eq!(ChatService::seed_to_reuse(&history, "missing"), None);
+    }
+}
@@ -0,0 +1,331 @@
+//! What happens when a provider stream breaks off before it ends.
+//!
+//! No provider can resume a generation, so every provider takes the same
+//! decision: when nothing arrived yet the request is sent again, and when
+//! part of the answer arrived it is kept and marked `stream_interrupted`,
+//! for the chat turn to ask the model to go on or to leave it as it is.
+
+use crate::error::AppError;
+use crate::models::llm_types::{
+    AssistantContent, ChatMessage, LLMChatRequest, LLMChatResponse, TokenUsage, UserContent,
+};
+use futures::{Stream, StreamExt};
+use std::fmt::Display;
+
+/// Finish reason of an answer whose stream broke off after part of it arrived.
+pub const STREAM_INTERRUPTED: &str = "stream_interrupted";
+
+/// Metadata key of a message whose stream broke off.
+pub const STREAM_INTERRUPTED_METADATA_KEY: &str = "streamInterrupted";
+
+/// How often one answer is continued after its stream broke off.
+pub const MAX_STREAM_CONTINUATIONS: u32 = 2;
+
+/// What the model is told when asked to go on with a broken-off answer.
+const CONTINUE_PROMPT: &str = "Your previous reply was cut off. Continue it exactly where it \
+stopped, without repeating anything or commenting on the interruption.";
+
+/// How a broken-off stream is recovered.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamRecovery {
+    /// Nothing arrived: send the request again.
+    Retry,
+    /// Part of the answer arrived: keep it and ask the model to continue.
+    SalvageAndContinue,
+    /// Part of the answer arrived: keep it as it is.
+    Salvage,
+}
+
+impl StreamRecovery {
+    /// The recovery of a stream that broke off after `received` anything,
+    /// in a workspace that does or does not `auto_continue`.
+    pub const fn decide(received: bool, auto_continue: bool) -> Self {
+        match (received, auto_continue) {
+            (false, _) => Self::Retry,
+            (true, true) => Self::SalvageAndContinue,
+            (true, false) => Self::Salvage,
+        }
+    }
+}
+
+/// A provider byte stream that ends, instead of failing, when its transport
+/// breaks, and remembers why.
+pub struct GuardedStream<S> {
+    inner: S,
+    interruption: Option<String>,
+}
+
+impl<S, T, E> GuardedStream<S>
+where
+    S: Stream<Item = Result<T, E>> + Unpin,
+    E: Display,
+{
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            interruption: None,
+        }
+    }
+
+    /// The next chunk, or None once the stream ended or broke off.
+    pub async fn next(&mut self) -> Option<T> {
+        if self.interruption.is_some() {
+            return None;
+        }
+        match self.inner.next().await? {
+            Ok(chunk) => Some(chunk),
+            Err(e) => {
+                self.interruption = Some(format!("Stream error: {e}"));
+                None
+            }
+        }
+    }
+
+    /// Why the stream broke off, if it did.
+    pub fn interruption(&self) -> Option<&str> {
+        self.interruption.as_deref()
+    }
+
+    /// Close a read loop that `received` part of an answer or not. True when
+    /// the stream broke off and the partial answer is to be salvaged, an
+    /// `StreamInterrupted` error when it broke off before anything arrived.
+    pub fn finish(&self, received: bool) -> Result<bool, AppError> {
+        match (self.interruption(), received) {
+            (None, _) => Ok(false),
+            (Some(_), true) => Ok(true),
+            (Some(error), false) => Err(AppError::StreamInterrupted(error.to_string())),
+        }
+    }
+}
+
+/// The request to send again after a stream broke off before anything
+/// arrived. Google answers the same request without streaming, which avoids
+/// the long-lived connection that broke; the others stream again.
+pub fn retry_request(provider: &str, mut request: LLMChatRequest) -> LLMChatRequest {
+    if matches!(provider.to_lowercase().as_str(), "google" | "gemini") {
+        request.stream = false;
+    }
+    request
+}
+
+/// The request that asks the model to go on with `partial`, the part of its
+/// answer to `request` that arrived before the stream broke off.
+pub fn continuation_request(mut request: LLMChatRequest, partial: &str) -> LLMChatRequest {
+    request.messages.push(ChatMessage::Assistant {
+        content: AssistantContent::Text(partial.to_string()),
+        tool_calls: None,
+    });
+    request.messages.push(ChatMessage::User {
+        content: UserContent::Text(CONTINUE_PROMPT.to_string()),
+    });
+    request
+}
+
+/// A broken-off answer with its continuation appended. The tool calls and
+/// finish reason are the continuation's, the usage is that of both.
+pub fn append_continuation(
+    mut partial: LLMChatResponse,
+    continuation: LLMChatResponse,
+) -> LLMChatResponse {
+    partial.content.push_str(&continuation.content);
+    partial.reasoning = match (partial.reasoning, continuation.reasoning) {
+        (Some(mut first), Some(second)) => {
+            first.push_str(&second);
+            Some(first)
+        }
+        (first, second) => first.or(second),
+    };
+    partial.usage = match (partial.usage, continuation.usage) {
+        (Some(first), Some(second)) => Some(TokenUsage {
+            prompt_tokens: add(first.prompt_tokens, second.prompt_tokens),
+            completion_tokens: add(first.completion_tokens, second.completion_tokens),
+            total_tokens: add(first.total_tokens, second.total_tokens),
+            cached_tokens: add(first.cached_tokens, second.cached_tokens),
+            cache_write_tokens: add(first.cache_write_tokens, second.cache_write_tokens),
+        }),
+        (first, second) => first.or(second),
+    };
+    partial.images = match (partial.images, continuation.images) {
+        (Some(mut first), Some(second)) => {
+            first.extend(second);
+            Some(first)
+        }
+        (first, second) => first.or(second),
+    };
+    partial.tool_calls = continuation.tool_calls;
+    partial.finish_reason = continuation.finish_reason;
+    partial.model = partial.model.or(continuation.model);
+    partial.system_fingerprint = partial
+        .system_fingerprint
+        .or(continuation.system_fingerprint);
+    partial
+}
+
+fn add(first: Option<u32>, second: Option<u32>) -> Option<u32> {
+    match (first, second) {
+        (Some(a), Some(b)) => Some(a.saturating_add(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// What a message's metadata keeps of a stream that broke off: how often
+/// the model was asked to continue, and whether the answer is still cut
+/// short. None when the stream never broke off.
+pub fn interruption_metadata(
+    response: &LLMChatResponse,
+    continuations: u32,
+) -> Option<serde_json::Value> {
+    let cut_short = response.finish_reason.as_deref() == Some(STREAM_INTERRUPTED);
+    (cut_short || continuations > 0).then(|| {
+        serde_json::json!({
+            "cutShort": cut_short,
+            "continuations": continuations,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_support::scripted_response;
+
+    #[test]
+    fn cut_streams_are_retried_or_salvaged_at_every_cut_point() {
+        use crate::error::AppError;
+        use crate::services::llm::stream_recovery::{GuardedStream, StreamRecovery};
+
+        const CHUNKS: [&str; 3] = ["data: Hel\n\n", "data: lo wo\n\n", "data: rld\n\n"];
+
+        // A transport that breaks off after `cut` chunks, then would go on
+        // sending, or ends normally without a cut
+        let transport = |cut: Option<usize>| {
+            let mut items: Vec<Result<Vec<u8>, std::io::Error>> =
+                CHUNKS.iter().map(|c| Ok(c.as_bytes().to_vec())).collect();
+            if let Some(cut) = cut {
+                items.insert(
+                    cut,
+                    Err(std::io::Error::new(
+                        std::io::ErrorKind::ConnectionReset,
+                        "connection reset",
+                    )),
+                );
+            }
+            futures::stream::iter(items)
+        };
+        let read = |cut: Option<usize>| {
+            tokio::runtime::Runtime::new().unwrap().block_on(async {
+                let mut stream = GuardedStream::new(transport(cut));
+                let mut content = String::new();
+                while let Some(chunk) = stream.next().await {
+                    let text = String::from_utf8(chunk).unwrap();
+                    content.push_str(text.trim_end().trim_start_matches("data: "));
+                }
+                let outcome = stream.finish(!content.is_empty());
+                (content, outcome)
+            })
+        };
+
+        let (content, outcome) = read(None);
+        assert_eq!(content, "Hello world");
+        assert!(matches!(outcome, Ok(false)));
+
+        // Nothing arrived: the request is sent again
+        let (content, outcome) = read(Some(0));
+        assert!(content.is_empty());
+        assert!(
+            matches!(&outcome, Err(AppError::StreamInterrupted(e)) if e == "Stream error: connection reset")
+        );
+        assert_eq!(StreamRecovery::decide(false, true), StreamRecovery::Retry);
+        assert_eq!(StreamRecovery::decide(false, false), StreamRecovery::Retry);
+
+        // Part arrived: it is kept, and nothing sent after the break is read
+        for (cut, expected) in [(1, "Hel"), (2, "Hello wo"), (3, "Hello world")] {
+            let (content, outcome) = read(Some(cut));
+            assert_eq!(content, expected, "cut after {cut} chunks");
+            assert!(matches!(outcome, Ok(true)), "cut after {cut} chunks");
+        }
+        assert_eq!(
+            StreamRecovery::decide(true, true),
+            StreamRecovery::SalvageAndContinue
+        );
+        assert_eq!(StreamRecovery::decide(true, false), StreamRecovery::Salvage);
+    }
+
+    #[test]
+    fn interrupted_answers_are_continued_into_one_answer() {
+        use crate::models::llm_types::{
+            AssistantContent, ChatMessage, LLMChatRequest, TokenUsage, UserContent,
+        };
+        use crate::services::llm::stream_recovery::{
+            append_continuation, continuation_request, interruption_metadata, retry_request,
+            STREAM_INTERRUPTED,
+        };
+
+        let request = LLMChatRequest {
+            model: "gpt-4o".to_string(),
+            messages: vec![ChatMessage::User {
+                content: UserContent::Text("Write a poem".to_string()),
+            }],
+            temperature: None,
+            max_tokens: None,
+            top_p: None,
+            frequency_penalty: None,
+            presence_penalty: None,
+            stream: true,
+            tools: None,
+            tool_choice: None,
+            reasoning_effort: None,
+            stream_options: None,
+            response_modalities: None,
+            image_config: None,
+            context_cache_scope: None,
+            model_filter: None,
+            connection_config: None,
+            seed: None,
+            output_limits: None,
+        };
+
+        // Google falls back to a request without streaming, the others stream again
+        assert!(!retry_request("google", request.clone()).stream);
+        assert!(retry_request("anthropic", request.clone()).stream);
+        assert!(retry_request("openai", request.clone()).stream);
+
+        // The continuation carries the part that arrived as the model's own
+        let continuation = continuation_request(request, "Roses are");
+        assert_eq!(continuation.messages.len(), 3);
+        assert!(matches!(
+            &continuation.messages[1],
+            ChatMessage::Assistant { content: AssistantContent::Text(text), tool_calls: None }
+                if text == "Roses are"
+        ));
+        assert!(matches!(continuation.messages[2], ChatMessage::User { .. }));
+
+        let usage = |prompt, completion| TokenUsage {
+            prompt_tokens: Some(prompt),
+            completion_tokens: Some(completion),
+            total_tokens: Some(prompt + completion),
+            cached_tokens: None,
+            cache_write_tokens: None,
+        };
+        let mut partial = scripted_response("Roses are");
+        partial.finish_reason = Some(STREAM_INTERRUPTED.to_string());
+        partial.usage = Some(usage(10, 3));
+        assert_eq!(
+            interruption_metadata(&partial, 0),
+            Some(serde_json::json!({ "cutShort": true, "continuations": 0 }))
+        );
+
+        let mut rest = scripted_response(" red");
+        rest.usage = Some(usage(14, 2));
+        let answer = append_continuation(partial, rest);
+        assert_eq!(answer.content, "Roses are red");
+        assert_eq!(answer.finish_reason.as_deref(), Some("stop"));
+        let total = answer.usage.as_ref().unwrap();
+        assert_eq!(total.prompt_tokens, Some(24));
+        assert_eq!(total.completion_tokens, Some(5));
+        assert_eq!(
+            interruption_metadata(&answer, 1),
+            Some(serde_json::json!({ "cutShort": false, "continuations": 1 }))
+        );
+        assert_eq!(interruption_metadata(&scripted_response("Fine"), 0), None);
+    }
+}
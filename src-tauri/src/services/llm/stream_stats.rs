@@ -0,0 +1,182 @@
+//! Live generation speed of a streamed answer.
+//!
+//! Providers count what arrives inline, on plain atomics, so the read loop
+//! takes no lock. A side task of the [`StreamStats`] reads the counters
+//! about once a second and reports how far the answer got and how fast it
+//! went since the last report, apart from the chunk events: throttling one
+//! does not distort the other. The authoritative numbers still come with
+//! the completed answer's usage.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+
+/// How often a streamed answer reports its speed.
+pub const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Characters per token of the estimate when the provider reports no
+/// token counts while streaming.
+const CHARS_PER_TOKEN: u64 = 4;
+
+/// How far a streamed answer got, as of one report.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StreamStatsSnapshot {
+    pub est_tokens_so_far: u64,
+    /// Over the time since the previous report
+    pub tokens_per_sec: f64,
+    pub elapsed_ms: u64,
+}
+
+#[derive(Default)]
+struct Counters {
+    chars: AtomicU64,
+    /// Output tokens the provider reported so far, 0 while it reported none
+    tokens: AtomicU64,
+}
+
+impl Counters {
+    fn tokens_so_far(&self) -> u64 {
+        match self.tokens.load(Ordering::Relaxed) {
+            0 => self.chars.load(Ordering::Relaxed).div_ceil(CHARS_PER_TOKEN),
+            tokens => tokens,
+        }
+    }
+}
+
+/// Counters of one streamed answer and the task reporting them, which stops
+/// on `stop` or when the stats are dropped.
+pub struct StreamStats {
+    counters: Arc<Counters>,
+    task: JoinHandle<()>,
+}
+
+impl StreamStats {
+    pub fn start(report: impl Fn(StreamStatsSnapshot) + Send + 'static) -> Self {
+        Self::with_interval(STATS_INTERVAL, report)
+    }
+
+    pub fn with_interval(
+        interval: Duration,
+        report: impl Fn(StreamStatsSnapshot) + Send + 'static,
+    ) -> Self {
+        let counters = Arc::new(Counters::default());
+        let task = tokio::spawn({
+            let counters = counters.clone();
+            async move {
+                let started = Instant::now();
+                let mut ticker = tokio::time::interval_at((started + interval).into(), interval);
+                ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+                let (mut last_tokens, mut last_elapsed) = (0, Duration::ZERO);
+                loop {
+                    ticker.tick().await;
+                    let elapsed = started.elapsed();
+                    let tokens = counters.tokens_so_far();
+                    let window = (elapsed - last_elapsed).as_secs_f64();
+                    #[allow(clippy::cast_precision_loss)]
+                    let tokens_per_sec = if window > 0.0 {
+                        tokens.saturating_sub(last_tokens) as f64 / window
+                    } else {
+                        0.0
+                    };
+                    report(StreamStatsSnapshot {
+                        est_tokens_so_far: tokens,
+                        tokens_per_sec,
+                        elapsed_ms: u64::try_from(elapsed.as_millis()).unwrap_or(u64::MAX),
+                    });
+                    (last_tokens, last_elapsed) = (tokens, elapsed);
+                }
+            }
+        });
+        Self { counters, task }
+    }
+
+    /// Count text of the answer, content or reasoning, as it arrives.
+    pub fn record_chars(&self, text: &str) {
+        self.counters
+            .chars
+            .fetch_add(text.chars().count() as u64, Ordering::Relaxed);
+    }
+
+    /// Take the provider's running count of output tokens over the estimate.
+    pub fn record_output_tokens(&self, tokens: u64) {
+        self.counters.tokens.fetch_max(tokens, Ordering::Relaxed);
+    }
+
+    /// Stop reporting: the answer completed or was cancelled.
+    pub fn stop(&self) {
+        self.task.abort();
+    }
+}
+
+impl Drop for StreamStats {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn stream_stats_report_a_plausible_rate_and_stop_on_completion() {
+        use crate::services::llm::stream_stats::StreamStats;
+        use std::time::Duration;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let reports = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+            let stats = StreamStats::with_interval(Duration::from_millis(50), {
+                let reports = reports.clone();
+                move |snapshot| reports.lock().unwrap().push(snapshot)
+            });
+            // A mock stream of 10 tokens every 10ms, at most 1000 tokens/s
+            for _ in 0..30 {
+                stats.record_chars(&"abcd".repeat(10));
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            stats.stop();
+
+            let reported = reports.lock().unwrap().clone();
+            assert!(reported.len() >= 3, "{} reports", reported.len());
+            for pair in reported.windows(2) {
+                assert!(pair[1].elapsed_ms > pair[0].elapsed_ms);
+                assert!(pair[1].est_tokens_so_far >= pair[0].est_tokens_so_far);
+            }
+            for snapshot in &reported {
+                assert!(snapshot.est_tokens_so_far <= 300);
+                assert!(snapshot.tokens_per_sec > 0.0 && snapshot.tokens_per_sec < 2500.0);
+            }
+
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            assert_eq!(reports.lock().unwrap().len(), reported.len());
+        });
+    }
+
+    #[test]
+    fn stream_stats_prefer_reported_output_tokens_over_the_estimate() {
+        use crate::services::llm::stream_stats::StreamStats;
+        use std::time::Duration;
+
+        tokio::runtime::Runtime::new().unwrap().block_on(async {
+            let last = std::sync::Arc::new(std::sync::Mutex::new(None));
+            let report = |last: &std::sync::Arc<std::sync::Mutex<_>>| {
+                let last = last.clone();
+                move |snapshot| *last.lock().unwrap() = Some(snapshot)
+            };
+
+            let stats = StreamStats::with_interval(Duration::from_millis(10), report(&last));
+            stats.record_chars("Xin chào");
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            assert_eq!(last.lock().unwrap().unwrap().est_tokens_so_far, 2);
+
+            stats.record_output_tokens(7);
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            assert_eq!(last.lock().unwrap().unwrap().est_tokens_so_far, 7);
+
+            // Dropped on a cancel or an error, it stops too
+            drop(stats);
+            *last.lock().unwrap() = None;
+            tokio::time::sleep(Duration::from_millis(40)).await;
+            assert!(last.lock().unwrap().is_none());
+        });
+    }
+}
@@ -1,29 +1,74 @@
+use crate::events::EventDispatcher;
+use crate::features::activity::{ActivityRepository, ActivityService, SqliteActivityRepository};
 use crate::features::app_settings::{
     repository::{AppSettingsRepository, SqliteAppSettingsRepository},
     service::AppSettingsService,
 };
+use crate::features::attachment::{
+    AttachmentRepository, AttachmentService, SqliteAttachmentRepository,
+    VERIFY_ATTACHMENTS_ON_STARTUP,
+};
+use crate::features::backup::BackupService;
 use crate::features::chat::input_settings::{
     ChatInputSettingsRepository, ChatInputSettingsService, SqliteChatInputSettingsRepository,
 };
 use crate::features::chat::{ChatRepository, ChatService, SqliteChatRepository};
+use crate::features::chat_import::ChatImportService;
+use crate::features::context_cache::{
+    ContextCacheRepository, ContextCacheService, SqliteContextCacheRepository,
+};
+use crate::features::encryption::{
+    EncryptionRepository, EncryptionService, EncryptionState, FieldCipher, KeychainKeyStore,
+    SecretsService, SqliteEncryptionRepository,
+};
+use crate::features::hub::{
+    packs::HubPackService,
+    repository::{HubPackRepository, SqliteHubPackRepository},
+};
+use crate::features::knowledge::{
+    KnowledgeRepository, KnowledgeService, SqliteKnowledgeRepository,
+};
+use crate::features::llm_cache::{LLMCacheRepository, LLMCacheService, SqliteLLMCacheRepository};
 use crate::features::llm_connection::{
     LLMConnectionRepository, LLMConnectionService, SqliteLLMConnectionRepository,
 };
+use crate::features::maintenance::MaintenanceService;
 use crate::features::mcp_connection::{
     MCPConnectionRepository, MCPConnectionService, SqliteMCPConnectionRepository,
 };
 use crate::features::message::{MessageRepository, MessageService, SqliteMessageRepository};
 use crate::features::prompt::{PromptRepository, PromptService, SqlitePromptRepository};
+use crate::features::quick_action::{
+    QuickActionRepository, QuickActionService, SqliteQuickActionRepository,
+};
+use crate::features::redaction::RedactionService;
+use crate::features::search::{SearchRepository, SearchService, SqliteSearchRepository};
+use crate::features::semantic_index::{
+    SemanticIndexRepository, SemanticIndexService, SqliteSemanticIndexRepository,
+};
+use crate::features::share::ShareService;
 
 use crate::features::notes::{
     repository::{NoteRepository, SqliteNoteRepository},
     service::NoteService,
 };
+use crate::features::outbox::{OutboxRepository, OutboxService, SqliteOutboxRepository};
 use crate::features::skill::SkillService;
-use crate::features::tool::{mcp_refresh::MCPToolRefreshService, service::ToolService};
+use crate::features::system::ActivityHalt;
+use crate::features::tool::{
+    mcp_health::MCPHealthMonitor, mcp_refresh::MCPToolRefreshService, service::ToolService,
+};
+use crate::features::tool_catalog::{
+    SqliteToolCatalogRepository, ToolCatalogRepository, ToolCatalogService,
+};
 use crate::features::usage::{SqliteUsageRepository, UsageRepository, UsageService};
+use crate::features::webhook::{
+    SqliteWebhookRepository, WebhookDispatcher, WebhookRepository, WebhookService,
+};
 use crate::features::workspace::{
+    archive::WorkspaceArchiveService,
     management::{SqliteWorkspaceRepository, WorkspaceRepository, WorkspaceService},
+    notes::{SqliteWorkspaceNotesRepository, WorkspaceNotesRepository, WorkspaceNotesService},
     settings::{
         SqliteWorkspaceSettingsRepository, WorkspaceSettingsRepository, WorkspaceSettingsService,
     },
@@ -34,13 +79,15 @@ use crate::services::LLMService;
 use rusqlite::Connection;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
-use tauri::{AppHandle, Manager};
+use tauri::{AppHandle, Emitter, Manager};
 use tokio::sync::oneshot;
 
 #[derive(Debug)]
 pub struct PermissionDecision {
     pub approved: bool,
     pub allowed_tool_ids: Vec<String>,
+    /// Keep the answer for the tools asked about, so they are not asked again
+    pub remember: Option<crate::features::chat::tool_permissions::RememberScope>,
 }
 
 pub struct AppState {
@@ -51,29 +98,74 @@ pub struct AppState {
     // Services (injected dependencies)
     pub workspace_feature: Arc<WorkspaceFeature>,
     pub chat_service: Arc<ChatService>,
+    pub chat_import_service: Arc<ChatImportService>,
     pub message_service: Arc<MessageService>,
     pub chat_input_settings_service: Arc<ChatInputSettingsService>,
     pub llm_connection_service: Arc<LLMConnectionService>,
+    pub llm_service: Arc<LLMService>,
     pub mcp_connection_service: Arc<MCPConnectionService>,
     pub usage_service: Arc<UsageService>,
     #[allow(dead_code)]
     pub tool_service: Arc<ToolService>,
     pub app_settings_service: Arc<AppSettingsService>,
     pub prompt_service: Arc<PromptService>,
+    pub quick_action_service: Arc<QuickActionService>,
     pub note_service: Arc<NoteService>,
+    pub attachment_service: Arc<AttachmentService>,
+    pub context_cache_service: Arc<ContextCacheService>,
+    pub llm_cache_service: Arc<LLMCacheService>,
+    pub activity_service: Arc<ActivityService>,
+    pub webhook_service: Arc<WebhookService>,
+    pub outbox_service: Arc<OutboxService>,
+    pub tool_catalog_service: Arc<ToolCatalogService>,
+    pub semantic_index_service: Arc<SemanticIndexService>,
+    pub knowledge_service: Arc<KnowledgeService>,
+    pub search_service: Arc<SearchService>,
+    pub encryption_service: Arc<EncryptionService>,
+    pub share_service: Arc<ShareService>,
+    pub maintenance_service: Arc<MaintenanceService>,
+    pub backup_service: Arc<BackupService>,
+    pub event_dispatcher: Arc<EventDispatcher>,
+    pub hub_pack_service: Arc<HubPackService>,
+    /// Set by the kill switch until activity is resumed
+    pub activity_halt: Arc<ActivityHalt>,
 
     // Tool permission state: message_id -> oneshot sender for approval response
     pub pending_tool_permissions: Arc<Mutex<HashMap<String, oneshot::Sender<PermissionDecision>>>>,
 
+    // Link the OS opened the app with, until the frontend takes it
+    pub pending_navigation: Arc<Mutex<Option<crate::events::NavigateToMessageEvent>>>,
+
     // Agent Manager
     pub agent_manager: Arc<crate::features::agent::manager::AgentManager>,
 
     // Skill Service
     pub skill_service: Arc<SkillService>,
+
+    // Single-instance lock on the data directory, released on drop or exit
+    pub instance_lock: super::InstanceLock,
 }
 
 impl AppState {
+    /// Fails with `AppError::InstanceLocked` when another live instance uses
+    /// the data directory; nothing is opened or started in that case.
     pub fn new(app: Arc<AppHandle>) -> Result<Self, crate::error::AppError> {
+        // Claim the data directory before touching the database or starting
+        // background jobs
+        let data_dir = (*app)
+            .path()
+            .app_data_dir()
+            .map_err(crate::error::AppError::Tauri)?;
+        let instance_lock = super::InstanceLock::acquire(&data_dir)?;
+        instance_lock.start_heartbeat();
+
+        // Swap in a backup staged by `restore_backup` before the database opens
+        match crate::features::backup::archive::apply_pending_restore(&data_dir) {
+            Ok(true) => tracing::info!("Restored the staged backup"),
+            Ok(false) => {}
+            Err(e) => tracing::error!(error = %e, "Failed to restore the staged backup"),
+        }
+
         // Initialize database if needed
         let db_state = Arc::new(Mutex::new(None));
         {
@@ -85,14 +177,38 @@ impl AppState {
             }
         }
 
+        // Load the encryption key before any repository reads
+        let cipher = Arc::new(FieldCipher::default());
+        let encryption_repo: Arc<dyn EncryptionRepository> =
+            Arc::new(SqliteEncryptionRepository::new(app.clone()));
+        let encryption_service = Arc::new(EncryptionService::new(
+            encryption_repo.clone(),
+            Arc::new(KeychainKeyStore::database()),
+            cipher.clone(),
+        ));
+        let encryption_state = encryption_service.load()?;
+        // API keys are sealed with a key of their own, inside the database key
+        let secrets_cipher = Arc::new(FieldCipher::secrets());
+        let secrets_service = SecretsService::new(
+            encryption_repo,
+            Arc::new(KeychainKeyStore::api_keys()),
+            secrets_cipher.clone(),
+            cipher.clone(),
+        );
+        if let Err(e) = secrets_service.load() {
+            tracing::error!(error = %e, "Failed to encrypt stored API keys");
+        }
+
         // Create repositories
         let workspace_repo: Arc<dyn WorkspaceRepository> =
             Arc::new(SqliteWorkspaceRepository::new(app.clone()));
         let chat_repo: Arc<dyn ChatRepository> = Arc::new(SqliteChatRepository::new(app.clone()));
         let message_repo: Arc<dyn MessageRepository> =
-            Arc::new(SqliteMessageRepository::new(app.clone()));
+            Arc::new(SqliteMessageRepository::new(app.clone(), cipher.clone()));
         let workspace_settings_repo: Arc<dyn WorkspaceSettingsRepository> =
             Arc::new(SqliteWorkspaceSettingsRepository::new(app.clone()));
+        let workspace_notes_repo: Arc<dyn WorkspaceNotesRepository> =
+            Arc::new(SqliteWorkspaceNotesRepository::new(app.clone()));
 
         let app_settings_repo: Arc<dyn AppSettingsRepository> =
             Arc::new(SqliteAppSettingsRepository::new(app.clone()));
@@ -102,6 +218,18 @@ impl AppState {
             Arc::new(SqliteUsageRepository::new(app.clone()));
         let chat_input_settings_repo: Arc<dyn ChatInputSettingsRepository> =
             Arc::new(SqliteChatInputSettingsRepository::new(app.clone()));
+        let attachment_repo: Arc<dyn AttachmentRepository> =
+            Arc::new(SqliteAttachmentRepository::new(app.clone(), cipher.clone()));
+        let context_cache_repo: Arc<dyn ContextCacheRepository> =
+            Arc::new(SqliteContextCacheRepository::new(app.clone()));
+        let llm_cache_repo: Arc<dyn LLMCacheRepository> =
+            Arc::new(SqliteLLMCacheRepository::new(app.clone()));
+        let activity_repo: Arc<dyn ActivityRepository> =
+            Arc::new(SqliteActivityRepository::new(app.clone()));
+        let webhook_repo: Arc<dyn WebhookRepository> =
+            Arc::new(SqliteWebhookRepository::new(app.clone()));
+        let outbox_repo: Arc<dyn OutboxRepository> =
+            Arc::new(SqliteOutboxRepository::new(app.clone()));
 
         // Initialize Agent Manager first as it's needed by ChatService
         let agent_manager = Arc::new(crate::features::agent::manager::AgentManager::new(
@@ -116,79 +244,254 @@ impl AppState {
         let workspace_service = Arc::new(WorkspaceService::new(workspace_repo));
         workspace_service.ensure_default_workspace()?;
 
-        let message_service = Arc::new(MessageService::new(message_repo));
+        let files_dir = (*app)
+            .path()
+            .app_data_dir()
+            .map_err(crate::error::AppError::Tauri)?
+            .join("files");
+        let attachment_service = Arc::new(AttachmentService::new(attachment_repo, files_dir));
+        // Drop attachments no message references anymore
+        if let Err(e) = attachment_service.collect_garbage() {
+            tracing::warn!(error = %e, "Failed to collect unreferenced attachments");
+        }
+
+        let message_service = Arc::new(MessageService::new(
+            message_repo,
+            attachment_service.clone(),
+        ));
         let workspace_settings_service =
             Arc::new(WorkspaceSettingsService::new(workspace_settings_repo));
+        let workspace_notes_service = Arc::new(WorkspaceNotesService::new(workspace_notes_repo));
+        let workspace_archive_service = Arc::new(WorkspaceArchiveService::new(
+            app.clone(),
+            cipher.clone(),
+            attachment_service.clone(),
+        ));
 
         // Feature: Workspace
         let workspace_feature = Arc::new(WorkspaceFeature::new(
             workspace_service,
             workspace_settings_service.clone(),
+            workspace_notes_service.clone(),
+            workspace_archive_service,
         ));
 
-        let llm_connection_repo: Arc<dyn LLMConnectionRepository> =
-            Arc::new(SqliteLLMConnectionRepository::new(app.clone()));
+        let llm_connection_repo: Arc<dyn LLMConnectionRepository> = Arc::new(
+            SqliteLLMConnectionRepository::new(app.clone(), cipher.clone(), secrets_cipher.clone()),
+        );
         let llm_connection_service = Arc::new(LLMConnectionService::new(llm_connection_repo));
 
         let llm_service = Arc::new(LLMService::new());
         let usage_service = Arc::new(UsageService::new(usage_repo));
-        let mcp_connection_repo: Arc<dyn MCPConnectionRepository> =
-            Arc::new(SqliteMCPConnectionRepository::new(app.clone()));
-        let mcp_connection_service =
-            Arc::new(MCPConnectionService::new(mcp_connection_repo.clone()));
+        let mcp_connection_repo: Arc<dyn MCPConnectionRepository> = Arc::new(
+            SqliteMCPConnectionRepository::new(app.clone(), cipher, secrets_cipher),
+        );
+        let tool_catalog_repo: Arc<dyn ToolCatalogRepository> =
+            Arc::new(SqliteToolCatalogRepository::new(app.clone()));
+        let tool_catalog_service = Arc::new(ToolCatalogService::new(tool_catalog_repo));
+        let mcp_connection_service = Arc::new(MCPConnectionService::new(
+            mcp_connection_repo.clone(),
+            tool_catalog_service.clone(),
+        ));
+        let app_settings_service = Arc::new(AppSettingsService::new(app_settings_repo));
         let tool_service = Arc::new(ToolService::new(
             (*app).clone(),
             mcp_connection_service.clone(),
             workspace_settings_service.clone(),
+            app_settings_service.clone(),
         ));
 
         let skill_service = Arc::new(SkillService::new((*app).clone()));
+        let activity_service = Arc::new(ActivityService::new(activity_repo));
+        let webhook_service = Arc::new(WebhookService::new(webhook_repo.clone()));
+        let webhook_dispatcher = Arc::new(WebhookDispatcher::new(webhook_repo));
+        let outbox_service = Arc::new(OutboxService::new(outbox_repo));
+        let activity_halt = Arc::new(ActivityHalt::new());
 
+        let chat_input_settings_service =
+            Arc::new(ChatInputSettingsService::new(chat_input_settings_repo));
+        // Documents attached to workspaces, embedded like the semantic index
+        let knowledge_repo: Arc<dyn KnowledgeRepository> =
+            Arc::new(SqliteKnowledgeRepository::new(app.clone()));
+        let knowledge_service = Arc::new(KnowledgeService::new(
+            knowledge_repo,
+            app_settings_service.clone(),
+            llm_connection_service.clone(),
+            llm_service.clone(),
+        ));
         let chat_service = Arc::new(ChatService::new(
             chat_repo,
-            llm_service,
+            llm_service.clone(),
             message_service.clone(),
             workspace_settings_service,
+            chat_input_settings_service.clone(),
+            workspace_notes_service,
             llm_connection_service.clone(),
             tool_service.clone(),
             usage_service.clone(),
             agent_manager.clone(),
             skill_service.clone(),
+            attachment_service.clone(),
+            Arc::new(RedactionService::new()),
+            activity_service.clone(),
+            webhook_dispatcher,
+            app_settings_service.clone(),
+            tool_catalog_service.clone(),
+            outbox_service.clone(),
+            activity_halt.clone(),
+            knowledge_service.clone(),
+        ));
+        let chat_import_service = Arc::new(ChatImportService::new(
+            chat_service.clone(),
+            message_service.clone(),
+            attachment_service.clone(),
         ));
 
-        let app_settings_service = Arc::new(AppSettingsService::new(app_settings_repo));
+        // Optional attachment integrity pass: "report" only logs, "fix" also repairs
+        if let Some(mode @ ("report" | "fix")) = app_settings_service
+            .get_by_key(VERIFY_ATTACHMENTS_ON_STARTUP)
+            .ok()
+            .flatten()
+            .as_deref()
+        {
+            let fix = mode == "fix";
+            let service = attachment_service.clone();
+            std::thread::spawn(move || {
+                if let Err(e) = service.verify_attachments(fix) {
+                    tracing::warn!(error = %e, "Startup attachment check failed");
+                }
+            });
+        }
         let prompt_service = Arc::new(PromptService::new(prompt_repo));
-        let chat_input_settings_service =
-            Arc::new(ChatInputSettingsService::new(chat_input_settings_repo));
+        let quick_action_repo: Arc<dyn QuickActionRepository> =
+            Arc::new(SqliteQuickActionRepository::new(app.clone()));
+        let quick_action_service = Arc::new(QuickActionService::new(quick_action_repo));
 
         let note_repo: Arc<dyn NoteRepository> = Arc::new(SqliteNoteRepository::new(app.clone()));
         let note_service = Arc::new(NoteService::new(note_repo));
+        let context_cache_service = Arc::new(ContextCacheService::new(context_cache_repo));
+        let llm_cache_service = Arc::new(LLMCacheService::new(llm_cache_repo));
+        let hub_pack_repo: Arc<dyn HubPackRepository> =
+            Arc::new(SqliteHubPackRepository::new(app.clone()));
+        let hub_pack_service = Arc::new(HubPackService::new(hub_pack_repo));
+
+        // Every event goes out through one ordered queue; running chat shares
+        // see events as they go out
+        let share_service = Arc::new(ShareService::new());
+        let event_dispatcher = {
+            let app = app.clone();
+            let share_service = share_service.clone();
+            Arc::new(EventDispatcher::spawn(move |name, payload| {
+                share_service.observe(name, &payload);
+                if let Err(e) = app.emit(name, payload) {
+                    tracing::warn!(error = %e, "Failed to emit {name} event");
+                }
+            }))
+        };
+
+        // Finish an encryption change interrupted by the last quit
+        if matches!(
+            encryption_state,
+            EncryptionState::Enabling { .. } | EncryptionState::Disabling { .. }
+        ) {
+            let service = encryption_service.clone();
+            let app = app.clone();
+            std::thread::spawn(move || {
+                let result = service.resume(&|progress| {
+                    if let Err(e) = crate::events::emit_event(&app, progress) {
+                        tracing::warn!(error = %e, "Failed to emit encryption progress");
+                    }
+                });
+                if let Err(e) = result {
+                    tracing::error!(error = %e, "Failed to resume encryption change");
+                }
+            });
+        }
 
         // Create and start MCP tool refresh service
         let mcp_tool_refresh_service = Arc::new(MCPToolRefreshService::new(
             (*app).clone(),
-            mcp_connection_repo,
+            mcp_connection_repo.clone(),
+            tool_catalog_service.clone(),
         ));
 
         // Start background refresh job (runs every 5 minutes)
         mcp_tool_refresh_service.start_background_refresh();
 
+        // Ping connected MCP servers and reconnect those that went down
+        Arc::new(MCPHealthMonitor::new((*app).clone(), mcp_connection_repo))
+            .start_background_checks();
+
+        // Automatic database maintenance, while no turns run
+        let maintenance_service = Arc::new(MaintenanceService::new(
+            crate::db::connection::get_db_path(&app)?,
+            app_settings_service.clone(),
+            chat_service.clone(),
+        ));
+        maintenance_service.clone().start_schedule();
+
+        // Scheduled backups of the database and attachments
+        let backup_service = Arc::new(BackupService::new(data_dir, app_settings_service.clone()));
+        backup_service.clone().start_schedule();
+
+        // Resend messages queued while their connection was unreachable
+        chat_service.clone().start_outbox_watcher((*app).clone());
+
+        // Opt-in semantic search; the background task idles while it is off
+        let semantic_index_repo: Arc<dyn SemanticIndexRepository> =
+            Arc::new(SqliteSemanticIndexRepository::new(app.clone()));
+        let semantic_index_service = Arc::new(SemanticIndexService::new(
+            semantic_index_repo,
+            app_settings_service.clone(),
+            llm_connection_service.clone(),
+            llm_service.clone(),
+            encryption_service.clone(),
+            activity_halt.clone(),
+        ));
+        semantic_index_service.clone().start_background();
+
+        let search_repo: Arc<dyn SearchRepository> =
+            Arc::new(SqliteSearchRepository::new(app.clone()));
+        let search_service = Arc::new(SearchService::new(search_repo, encryption_service.clone()));
+
         Ok(Self {
             db_state,
             workspace_feature,
             chat_service,
+            chat_import_service,
             message_service,
             chat_input_settings_service,
             llm_connection_service,
+            llm_service,
             mcp_connection_service,
             usage_service,
             tool_service,
             app_settings_service,
             prompt_service,
+            quick_action_service,
             note_service,
+            attachment_service,
+            context_cache_service,
+            llm_cache_service,
+            activity_service,
+            webhook_service,
+            outbox_service,
+            tool_catalog_service,
+            semantic_index_service,
+            knowledge_service,
+            search_service,
+            encryption_service,
+            share_service,
+            maintenance_service,
+            backup_service,
+            event_dispatcher,
+            hub_pack_service,
+            activity_halt,
             pending_tool_permissions: Arc::new(Mutex::new(HashMap::new())),
+            pending_navigation: Arc::new(Mutex::new(None)),
             agent_manager,
             skill_service,
+            instance_lock,
         })
     }
 }
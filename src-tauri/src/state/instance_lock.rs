@@ -0,0 +1,263 @@
+//! Single-instance lock on the app data directory.
+//!
+//! Two instances sharing a data directory (say a dev and a release build)
+//! would both write the database and run background jobs. The lock file
+//! holds the owner's PID and a heartbeat refreshed every few seconds; a lock
+//! whose heartbeat stopped was left by a crashed instance and is taken over.
+
+use crate::error::AppError;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const LOCK_FILE_NAME: &str = "instance.lock";
+/// How often the owner refreshes its heartbeat.
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// A heartbeat older than this (in milliseconds) means the owner is gone.
+pub const STALE_AFTER_MS: i64 = 30_000;
+
+/// Contents of the lock file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LockOwner {
+    pub pid: u32,
+    /// Identifies one run of the owner; PIDs get reused
+    pub token: String,
+    /// Unix milliseconds of the last heartbeat
+    pub heartbeat: i64,
+}
+
+impl LockOwner {
+    pub const fn is_stale(&self, now: i64) -> bool {
+        now - self.heartbeat > STALE_AFTER_MS
+    }
+}
+
+/// Held for the lifetime of the app; dropping it removes the lock file.
+pub struct InstanceLock {
+    path: PathBuf,
+    token: String,
+    released: Arc<AtomicBool>,
+}
+
+impl InstanceLock {
+    /// Take the lock on `dir`. Fails with `AppError::InstanceLocked` while
+    /// another live instance holds it.
+    pub fn acquire(dir: &Path) -> Result<Self, AppError> {
+        Self::acquire_at(dir, chrono::Utc::now().timestamp_millis())
+    }
+
+    pub fn acquire_at(dir: &Path, now: i64) -> Result<Self, AppError> {
+        fs::create_dir_all(dir)?;
+        let path = dir.join(LOCK_FILE_NAME);
+
+        match read_owner(&path) {
+            Some(owner) if !owner.is_stale(now) => {
+                return Err(AppError::InstanceLocked(owner.pid));
+            }
+            Some(owner) => {
+                tracing::warn!(
+                    pid = owner.pid,
+                    "Taking over the lock of an instance that stopped responding"
+                );
+                take_over(&path, Some(&owner.token))?;
+            }
+            // Unreadable leftovers; complete files are always written atomically
+            None if path.exists() => take_over(&path, None)?,
+            None => {}
+        }
+
+        let owner = LockOwner {
+            pid: std::process::id(),
+            token: uuid::Uuid::new_v4().to_string(),
+            heartbeat: now,
+        };
+        // Write the full contents first, then link them into place: the link
+        // fails if another instance got there in between
+        let staged = staging_path(&path, &owner.token);
+        fs::write(&staged, serde_json::to_vec(&owner)?)?;
+        let linked = fs::hard_link(&staged, &path);
+        let _ = fs::remove_file(&staged);
+        match linked {
+            Ok(()) => Ok(Self {
+                path,
+                token: owner.token,
+                released: Arc::new(AtomicBool::new(false)),
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Err(
+                AppError::InstanceLocked(read_owner(&path).map_or(0, |o| o.pid)),
+            ),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Refresh the heartbeat every `HEARTBEAT_INTERVAL` until released.
+    pub fn start_heartbeat(&self) {
+        let path = self.path.clone();
+        let token = self.token.clone();
+        let released = self.released.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(HEARTBEAT_INTERVAL);
+            if released.load(Ordering::SeqCst) {
+                break;
+            }
+            match write_heartbeat(&path, &token, chrono::Utc::now().timestamp_millis()) {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::error!("Instance lock was taken over by another instance");
+                    break;
+                }
+                Err(e) => tracing::warn!(error = %e, "Failed to refresh the instance lock"),
+            }
+        });
+    }
+
+    /// Remove the lock file if it is still ours. Safe to call more than once.
+    pub fn release(&self) {
+        if self.released.swap(true, Ordering::SeqCst) {
+            return;
+        }
+        if read_owner(&self.path).is_some_and(|o| o.token == self.token) {
+            if let Err(e) = remove_if_present(&self.path) {
+                tracing::warn!(error = %e, "Failed to remove the instance lock");
+            }
+        }
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        self.release();
+    }
+}
+
+fn read_owner(path: &Path) -> Option<LockOwner> {
+    serde_json::from_slice(&fs::read(path).ok()?).ok()
+}
+
+fn staging_path(path: &Path, token: &str) -> PathBuf {
+    path.with_file_name(format!("{LOCK_FILE_NAME}.{token}"))
+}
+
+/// Remove the lock file left by the owner with token `seen` (`None` for an
+/// unreadable file). Another instance may have taken it over since it was
+/// read, so the file is first moved aside, which only one instance can do,
+/// and put back if it turns out to be a live owner's.
+fn take_over(path: &Path, seen: Option<&str>) -> Result<(), AppError> {
+    let claimed = path.with_file_name(format!("{LOCK_FILE_NAME}.{}.stale", uuid::Uuid::new_v4()));
+    match fs::rename(path, &claimed) {
+        Ok(()) => {}
+        // Someone else moved it; linking ours in decides who owns the lock
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e.into()),
+    }
+    let owner = read_owner(&claimed);
+    if owner.as_ref().map(|o| o.token.as_str()) == seen {
+        return remove_if_present(&claimed);
+    }
+    let restored = fs::hard_link(&claimed, path);
+    let _ = fs::remove_file(&claimed);
+    if let Err(e) = restored {
+        tracing::error!(error = %e, "Failed to restore the lock of a live instance");
+    }
+    Err(AppError::InstanceLocked(owner.map_or(0, |o| o.pid)))
+}
+
+fn remove_if_present(path: &Path) -> Result<(), AppError> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+/// Rewrite the heartbeat through a rename so readers never see a partial file.
+fn write_heartbeat(path: &Path, token: &str, now: i64) -> Result<bool, AppError> {
+    let Some(mut owner) = read_owner(path).filter(|o| o.token == token) else {
+        return Ok(false);
+    };
+    owner.heartbeat = now;
+    let staged = staging_path(path, token);
+    fs::write(&staged, serde_json::to_vec(&owner)?)?;
+    fs::rename(&staged, path)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn instance_lock_takes_over_a_stale_lock() {
+        use crate::error::AppError;
+        use crate::state::instance_lock::{InstanceLock, LOCK_FILE_NAME, STALE_AFTER_MS};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        let first = InstanceLock::acquire_at(dir.path(), 0).unwrap();
+        assert!(matches!(
+            InstanceLock::acquire_at(dir.path(), 1_000),
+            Err(AppError::InstanceLocked(pid)) if pid == std::process::id()
+        ));
+
+        // The first owner stopped its heartbeat long enough ago
+        let second = InstanceLock::acquire_at(dir.path(), STALE_AFTER_MS + 1).unwrap();
+        assert!(path.exists());
+
+        // The old owner no longer owns the file and must not remove it
+        drop(first);
+        assert!(path.exists());
+        drop(second);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn two_takeovers_of_one_stale_lock_leave_one_owner() {
+        use crate::error::AppError;
+        use crate::state::instance_lock::{
+            read_owner, take_over, InstanceLock, LOCK_FILE_NAME, STALE_AFTER_MS,
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+        let crashed = InstanceLock::acquire_at(dir.path(), 0).unwrap();
+        let stale = read_owner(&path).unwrap();
+        std::mem::forget(crashed);
+
+        // Both instances read the stale owner; the first one takes over
+        let first = InstanceLock::acquire_at(dir.path(), STALE_AFTER_MS + 1).unwrap();
+        let owner = read_owner(&path).unwrap();
+        assert_ne!(owner.token, stale.token);
+
+        // The second one finds the fresh lock instead and leaves it in place
+        assert!(matches!(
+            take_over(&path, Some(&stale.token)),
+            Err(AppError::InstanceLocked(pid)) if pid == std::process::id()
+        ));
+        assert_eq!(read_owner(&path), Some(owner));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn instance_lock_is_released_on_drop() {
+        use crate::state::instance_lock::{InstanceLock, LOCK_FILE_NAME};
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOCK_FILE_NAME);
+
+        let lock = InstanceLock::acquire_at(dir.path(), 0).unwrap();
+        assert!(path.exists());
+        lock.release();
+        lock.release();
+        assert!(!path.exists());
+        drop(lock);
+
+        // Re-acquiring right away works, and leaves no staging files behind
+        let again = InstanceLock::acquire_at(dir.path(), 1).unwrap();
+        drop(again);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
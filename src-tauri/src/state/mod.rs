@@ -1,6 +1,8 @@
 pub mod app_state;
+pub mod instance_lock;
 pub mod mcp_client_state;
 
 pub use app_state::AppState;
 pub use app_state::PermissionDecision;
+pub use instance_lock::InstanceLock;
 pub use mcp_client_state::MCPClientState;
@@ -0,0 +1,602 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use crate::db::migrations::run_migrations;
+use crate::error::AppError;
+use crate::features::attachment::models::{FileHash, RemoteUpload};
+use crate::features::attachment::{AttachmentRepository, AttachmentService};
+use crate::features::chat_import::reader::read_export;
+use crate::features::chat_import::ImportedConversation;
+use crate::features::llm_connection::models::LLMConnection;
+use crate::features::message::{Message, MessageRepository, MessageService};
+use crate::features::workspace::settings::WorkspaceSettings;
+use crate::models::llm_types::{ChatMessage, LLMChatResponse, ToolCall, ToolCallFunction};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+/// Messages kept in memory, for service tests.
+#[derive(Default)]
+pub struct MemoryMessageRepository(pub Mutex<HashMap<String, Message>>);
+
+impl MessageRepository for MemoryMessageRepository {
+    fn create(&self, message: &Message) -> Result<(), AppError> {
+        self.0
+            .lock()
+            .unwrap()
+            .insert(message.id.clone(), message.clone());
+        Ok(())
+    }
+
+    fn get_by_chat_id(&self, chat_id: &str) -> Result<Vec<Message>, AppError> {
+        let mut messages: Vec<_> = self
+            .0
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|m| m.chat_id == chat_id)
+            .cloned()
+            .collect();
+        messages.sort_by_key(|m| m.timestamp);
+        Ok(messages)
+    }
+
+    fn get_by_id(&self, id: &str) -> Result<Option<Message>, AppError> {
+        Ok(self.0.lock().unwrap().get(id).cloned())
+    }
+
+    fn update(
+        &self,
+        id: &str,
+        content: &str,
+        reasoning: Option<&str>,
+        timestamp: Option<i64>,
+    ) -> Result<(), AppError> {
+        if let Some(message) = self.0.lock().unwrap().get_mut(id) {
+            message.content = content.to_string();
+            message.reasoning = reasoning.map(str::to_string);
+            if let Some(timestamp) = timestamp {
+                message.timestamp = timestamp;
+            }
+        }
+        Ok(())
+    }
+
+    fn delete(&self, id: &str) -> Result<(), AppError> {
+        self.0.lock().unwrap().remove(id);
+        Ok(())
+    }
+
+    fn delete_many(&self, ids: &[String]) -> Result<(), AppError> {
+        let mut messages = self.0.lock().unwrap();
+        for id in ids {
+            messages.remove(id);
+        }
+        Ok(())
+    }
+
+    fn update_metadata(&self, id: &str, metadata: Option<&str>) -> Result<(), AppError> {
+        if let Some(message) = self.0.lock().unwrap().get_mut(id) {
+            message.metadata = metadata.map(str::to_string);
+        }
+        Ok(())
+    }
+}
+
+/// A local HTTP server answering with `replies` in turn, then 200 and
+/// `{}`. Logs each request as "METHOD /path?query".
+pub async fn mock_json_endpoint(replies: Vec<(u16, Value)>) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let received = Arc::new(Mutex::new(Vec::new()));
+    let log = received.clone();
+    tokio::spawn(async move {
+        let mut replies = replies.into_iter();
+        while let Ok((stream, _)) = listener.accept().await {
+            let mut reader = BufReader::new(stream);
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).await.unwrap();
+            let mut length = 0;
+            let mut line = String::new();
+            loop {
+                line.clear();
+                reader.read_line(&mut line).await.unwrap();
+                let Some((name, value)) = line.trim_end().split_once(": ") else {
+                    break;
+                };
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).await.unwrap();
+            let mut parts = request_line.split_whitespace();
+            log.lock().unwrap().push(format!(
+                "{} {}",
+                parts.next().unwrap(),
+                parts.next().unwrap()
+            ));
+
+            let (status, body) = replies.next().unwrap_or_else(|| (200, json!({})));
+            let body = body.to_string();
+            let response = format!(
+                "HTTP/1.1 {status} Mock\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{body}",
+                body.len()
+            );
+            reader
+                .into_inner()
+                .write_all(response.as_bytes())
+                .await
+                .unwrap();
+        }
+    });
+    (url, received)
+}
+
+/// How the mock provider answers a turn.
+pub enum MockReply {
+    Answer(&'static str, u64),
+    Fail(&'static str),
+    /// Cannot connect, as when the model's server is offline
+    Unreachable,
+    /// Streams `partial` and waits for the cancel
+    UntilCancelled(&'static str),
+    /// Calls the named tools, with ids `c1`, `c2`, ...
+    ToolCalls(&'static [&'static str]),
+}
+
+pub async fn mock_provider(
+    reply: MockReply,
+    mut cancellation: broadcast::Receiver<()>,
+) -> Result<LLMChatResponse, AppError> {
+    let response = |content: &str, finish_reason: &str| LLMChatResponse {
+        content: content.to_string(),
+        finish_reason: Some(finish_reason.to_string()),
+        tool_calls: None,
+        usage: None,
+        reasoning: None,
+        images: None,
+        parameter_notes: Vec::new(),
+        model: None,
+        system_fingerprint: None,
+    };
+    let wait = |ms| sleep(Duration::from_millis(ms));
+    match reply {
+        MockReply::Answer(content, ms) => tokio::select! {
+            () = wait(ms) => Ok(response(content, "stop")),
+            _ = cancellation.recv() => Ok(response("", "cancelled")),
+        },
+        MockReply::Fail(error) => Err(AppError::Generic(error.to_string())),
+        MockReply::Unreachable => {
+            // A port nothing listens on any more
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            drop(listener);
+            let error = reqwest::Client::new().post(&url).send().await.unwrap_err();
+            Err(AppError::Http(error))
+        }
+        MockReply::UntilCancelled(partial) => tokio::select! {
+            () = wait(10_000) => Ok(response(partial, "stop")),
+            _ = cancellation.recv() => Ok(response(partial, "cancelled")),
+        },
+        MockReply::ToolCalls(names) => Ok(LLMChatResponse {
+            tool_calls: Some(
+                (1..)
+                    .zip(names)
+                    .map(|(i, name)| tool_call(&format!("c{i}"), name))
+                    .collect(),
+            ),
+            ..response("", "tool_calls")
+        }),
+    }
+}
+
+/// Attachments kept in memory; message metadata comes from `messages`,
+/// as the database joins it in.
+#[derive(Default)]
+pub struct MemoryAttachmentRepository {
+    pub files: Mutex<HashMap<String, FileHash>>,
+    pub uploads: Mutex<HashMap<(String, String), RemoteUpload>>,
+    pub messages: Arc<MemoryMessageRepository>,
+    /// Batches of messages the integrity check asked for
+    pub batches: AtomicUsize,
+    /// Check to cancel while it reads its first batch
+    pub cancel_after: Mutex<Option<Weak<AttachmentService>>>,
+    /// Entry a concurrent store inserts right after the next lookup
+    pub racing: Mutex<Option<FileHash>>,
+}
+
+impl AttachmentRepository for MemoryAttachmentRepository {
+    fn get_by_hash(&self, hash: &str) -> Result<Option<FileHash>, AppError> {
+        let found = self.files.lock().unwrap().get(hash).cloned();
+        if let Some(racing) = self.racing.lock().unwrap().take() {
+            self.files
+                .lock()
+                .unwrap()
+                .insert(racing.hash.clone(), racing);
+        }
+        Ok(found)
+    }
+    fn get_by_path(&self, path: &str) -> Result<Option<FileHash>, AppError> {
+        Ok(self
+            .files
+            .lock()
+            .unwrap()
+            .values()
+            .find(|f| f.path == path)
+            .cloned())
+    }
+    fn get_all(&self) -> Result<Vec<FileHash>, AppError> {
+        Ok(self.files.lock().unwrap().values().cloned().collect())
+    }
+    fn create(&self, file: &FileHash) -> Result<FileHash, AppError> {
+        Ok(self
+            .files
+            .lock()
+            .unwrap()
+            .entry(file.hash.clone())
+            .or_insert_with(|| file.clone())
+            .clone())
+    }
+    fn update_path(&self, hash: &str, path: &str) -> Result<(), AppError> {
+        if let Some(file) = self.files.lock().unwrap().get_mut(hash) {
+            file.path = path.to_string();
+        }
+        Ok(())
+    }
+    fn adjust_ref_count(&self, hash: &str, delta: i64) -> Result<(), AppError> {
+        if let Some(file) = self.files.lock().unwrap().get_mut(hash) {
+            file.ref_count = (file.ref_count + delta).max(0);
+        }
+        Ok(())
+    }
+    fn set_ref_count(&self, hash: &str, ref_count: i64) -> Result<(), AppError> {
+        if let Some(file) = self.files.lock().unwrap().get_mut(hash) {
+            file.ref_count = ref_count;
+        }
+        Ok(())
+    }
+    fn delete(&self, hash: &str) -> Result<(), AppError> {
+        self.files.lock().unwrap().remove(hash);
+        Ok(())
+    }
+    fn get_message_metadata(&self) -> Result<Vec<String>, AppError> {
+        Ok(self
+            .messages
+            .0
+            .lock()
+            .unwrap()
+            .values()
+            .filter_map(|m| m.metadata.clone())
+            .collect())
+    }
+    fn get_message_files_batch(
+        &self,
+        after_rowid: i64,
+        limit: u32,
+    ) -> Result<Vec<(i64, String, String)>, AppError> {
+        self.batches.fetch_add(1, Ordering::SeqCst);
+        let cancel = self.cancel_after.lock().unwrap().take();
+        if let Some(attachments) = cancel.and_then(|a| a.upgrade()) {
+            attachments.cancel_verify();
+        }
+        // Rows in insertion order, as far as timestamps tell it
+        let mut messages: Vec<_> = self.messages.0.lock().unwrap().values().cloned().collect();
+        messages.sort_by(|a, b| (a.timestamp, &a.id).cmp(&(b.timestamp, &b.id)));
+        Ok(messages
+            .into_iter()
+            .zip(1..)
+            .filter(|(_, rowid)| *rowid > after_rowid)
+            .filter_map(|(m, rowid)| {
+                let metadata = m
+                    .metadata
+                    .filter(|meta| meta.contains("\"files\"") || meta.contains("\"images\""))?;
+                Some((rowid, m.id, metadata))
+            })
+            .take(usize::try_from(limit).unwrap())
+            .collect())
+    }
+    fn update_message_metadata(&self, message_id: &str, metadata: &str) -> Result<(), AppError> {
+        if let Some(message) = self.messages.0.lock().unwrap().get_mut(message_id) {
+            message.metadata = Some(metadata.to_string());
+        }
+        Ok(())
+    }
+    fn get_remote_upload(
+        &self,
+        hash: &str,
+        provider_key: &str,
+    ) -> Result<Option<RemoteUpload>, AppError> {
+        Ok(self
+            .uploads
+            .lock()
+            .unwrap()
+            .get(&(hash.to_string(), provider_key.to_string()))
+            .cloned())
+    }
+    fn save_remote_upload(&self, upload: &RemoteUpload) -> Result<(), AppError> {
+        self.uploads.lock().unwrap().insert(
+            (upload.hash.clone(), upload.provider_key.clone()),
+            upload.clone(),
+        );
+        Ok(())
+    }
+    fn delete_remote_upload(&self, hash: &str, provider_key: &str) -> Result<(), AppError> {
+        self.uploads
+            .lock()
+            .unwrap()
+            .remove(&(hash.to_string(), provider_key.to_string()));
+        Ok(())
+    }
+}
+
+/// An attachment store the test never reaches.
+pub struct NoAttachments;
+
+impl AttachmentRepository for NoAttachments {
+    fn get_by_hash(&self, _: &str) -> Result<Option<FileHash>, AppError> {
+        unreachable!()
+    }
+    fn get_by_path(&self, _: &str) -> Result<Option<FileHash>, AppError> {
+        unreachable!()
+    }
+    fn get_all(&self) -> Result<Vec<FileHash>, AppError> {
+        unreachable!()
+    }
+    fn create(&self, _: &FileHash) -> Result<FileHash, AppError> {
+        unreachable!()
+    }
+    fn update_path(&self, _: &str, _: &str) -> Result<(), AppError> {
+        unreachable!()
+    }
+    fn adjust_ref_count(&self, _: &str, _: i64) -> Result<(), AppError> {
+        Ok(())
+    }
+    fn set_ref_count(&self, _: &str, _: i64) -> Result<(), AppError> {
+        unreachable!()
+    }
+    fn delete(&self, _: &str) -> Result<(), AppError> {
+        unreachable!()
+    }
+    fn get_message_metadata(&self) -> Result<Vec<String>, AppError> {
+        unreachable!()
+    }
+    fn get_message_files_batch(
+        &self,
+        _: i64,
+        _: u32,
+    ) -> Result<Vec<(i64, String, String)>, AppError> {
+        unreachable!()
+    }
+    fn update_message_metadata(&self, _: &str, _: &str) -> Result<(), AppError> {
+        unreachable!()
+    }
+    fn get_remote_upload(&self, _: &str, _: &str) -> Result<Option<RemoteUpload>, AppError> {
+        unreachable!()
+    }
+    fn save_remote_upload(&self, _: &RemoteUpload) -> Result<(), AppError> {
+        unreachable!()
+    }
+    fn delete_remote_upload(&self, _: &str, _: &str) -> Result<(), AppError> {
+        unreachable!()
+    }
+}
+
+/// A tool call to `name` with empty arguments.
+pub fn tool_call(id: &str, name: &str) -> ToolCall {
+    ToolCall {
+        id: id.to_string(),
+        r#type: "function".to_string(),
+        function: ToolCallFunction {
+            name: name.to_string(),
+            arguments: "{}".to_string(),
+        },
+    }
+}
+
+/// The tool call ids of `results`, which must all be tool results.
+pub fn tool_result_ids(results: &[ChatMessage]) -> Vec<&str> {
+    results
+        .iter()
+        .map(|m| match m {
+            ChatMessage::Tool { tool_call_id, .. } => tool_call_id.as_str(),
+            other => panic!("expected a tool result, got {other:?}"),
+        })
+        .collect()
+}
+
+/// A workspace permission config gating `delete_file` and `run_command`.
+pub const PERMISSION_CONFIG: &str =
+    r#"{"delete_file": "require", "run_command": "require", "read_file": "auto"}"#;
+
+/// An in-memory database with every migration applied.
+pub fn migrated_db() -> Connection {
+    let conn = Connection::open_in_memory().unwrap();
+    run_migrations(&conn).unwrap();
+    conn
+}
+
+/// An enabled LLM connection offering `models`.
+pub fn connection(id: &str, provider: &str, default_model: &str, models: Value) -> LLMConnection {
+    LLMConnection {
+        id: id.to_string(),
+        name: id.to_string(),
+        base_url: String::new(),
+        provider: provider.to_string(),
+        api_key: String::new(),
+        models_json: Some(models.to_string()),
+        default_model: Some(default_model.to_string()),
+        enabled: true,
+        keep_warm: false,
+        system_prompt_suffix: None,
+        skip_redaction: false,
+        model_allowlist: None,
+        model_blocklist: None,
+        config: None,
+        deployment_name: None,
+        api_version: None,
+        aws_region: None,
+        aws_profile: None,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+/// A local and a cloud connection, for model pinning.
+pub fn pin_connections() -> Vec<LLMConnection> {
+    vec![
+        connection(
+            "local",
+            "ollama",
+            "llama3",
+            json!([
+                {"id": "llama3", "name": "llama3", "supportsTools": true},
+                {"id": "qwen3", "name": "qwen3", "supportsTools": true, "supportsThinking": true}
+            ]),
+        ),
+        connection(
+            "cloud",
+            "openai",
+            "gpt-4o-mini",
+            json!([
+                {"id": "gpt-4o", "name": "GPT-4o", "supportsTools": true},
+                {"id": "gpt-4o-mini", "name": "GPT-4o mini", "supportsTools": true}
+            ]),
+        ),
+    ]
+}
+
+/// A message of chat `chat` with nothing but `content`.
+pub fn chat_message(id: &str, role: &str, content: &str) -> Message {
+    Message {
+        id: id.to_string(),
+        chat_id: "chat".to_string(),
+        role: role.to_string(),
+        content: content.to_string(),
+        reasoning: None,
+        timestamp: 0,
+        assistant_message_id: None,
+        tool_call_id: None,
+        metadata: None,
+        reply_to_message_id: None,
+        branch_id: None,
+    }
+}
+
+/// Workspace settings with only the connection and model set.
+pub fn boot_workspace_settings(
+    llm_connection_id: Option<&str>,
+    default_model: Option<&str>,
+) -> WorkspaceSettings {
+    WorkspaceSettings {
+        workspace_id: "ws".to_string(),
+        llm_connection_id: llm_connection_id.map(str::to_string),
+        system_message: None,
+        mcp_tool_ids: None,
+        stream_enabled: None,
+        default_model: default_model.map(str::to_string),
+        tool_permission_config: None,
+        max_agent_iterations: None,
+        internal_tools_enabled: None,
+        selected_skill_ids: None,
+        context_cache_enabled: None,
+        redaction_config: None,
+        secret_masking_enabled: None,
+        paste_dedup_enabled: None,
+        post_processing_config: None,
+        confirm_above_estimated_cost: None,
+        assumed_completion_tokens: None,
+        max_tool_calls_per_turn: None,
+        tool_call_caps: None,
+        auto_continue_interrupted: None,
+        max_output_bytes: None,
+        outbox_enabled: None,
+        agent_tool_permission: None,
+        temperature: None,
+        top_p: None,
+        frequency_penalty: None,
+        presence_penalty: None,
+        max_tokens: None,
+        max_parallel_tools: None,
+        summarize_after_messages: None,
+        summary_model: None,
+        created_at: 0,
+        updated_at: 0,
+    }
+}
+
+/// A finished answer saying `content`.
+pub fn scripted_response(content: &str) -> LLMChatResponse {
+    LLMChatResponse {
+        content: content.to_string(),
+        finish_reason: Some("stop".to_string()),
+        tool_calls: None,
+        usage: None,
+        reasoning: None,
+        images: None,
+        parameter_notes: Vec::new(),
+        model: None,
+        system_fingerprint: None,
+    }
+}
+
+/// A message service over `MemoryMessageRepository` holding one
+/// assistant answer with app-managed metadata.
+pub fn guarded_message_service() -> MessageService {
+    let attachments = Arc::new(AttachmentService::new(
+        Arc::new(NoAttachments),
+        PathBuf::new(),
+    ));
+    let service = MessageService::new(Arc::new(MemoryMessageRepository::default()), attachments);
+    service
+        .create(
+            "a1".to_string(),
+            "c1".to_string(),
+            "assistant".to_string(),
+            "Answer".to_string(),
+            Some(100),
+            None,
+            None,
+            Some(
+                json!({
+                    "tokenUsage": { "input_tokens": 10 },
+                    "turn_timeline": [],
+                    "model": "gpt-4o"
+                })
+                .to_string(),
+            ),
+        )
+        .unwrap();
+    service
+}
+
+/// The conversations `read_export` reads from a file called `name`.
+pub fn read_fixture(name: &str, contents: &str) -> Result<Vec<Value>, AppError> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    std::fs::write(&path, contents).unwrap();
+    let mut conversations = Vec::new();
+    read_export(&path, &AtomicBool::new(false), |value, progress| {
+        assert_eq!(progress.conversations, conversations.len() + 1);
+        assert_eq!(progress.total_bytes, contents.len() as u64);
+        conversations.push(value);
+        Ok(())
+    })?;
+    Ok(conversations)
+}
+
+/// `(role, content)` of each message of an imported conversation.
+pub fn contents(conversation: &ImportedConversation) -> Vec<(&str, &str)> {
+    conversation
+        .messages
+        .iter()
+        .map(|m| (m.role.as_str(), m.content.as_str()))
+        .collect()
+}
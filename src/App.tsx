@@ -10,6 +10,7 @@ import { useNotificationListener } from '@/hooks/useNotificationListener';
 import { useKeyboardShortcuts } from '@/hooks/useKeyboardShortcuts';
 import { useMenuEvents } from '@/hooks/useMenuEvents';
 import { useChatStreaming } from '@/features/chat/hooks/useChatStreaming';
+import { useWorkspaceActivity } from '@/features/workspace/hooks/useWorkspaceActivity';
 import { loadAppSettings } from '@/features/ui/state/uiSlice';
 import i18n from '@/i18n/config';
 import { useAutoUpdate } from '@/features/updater/hooks/useAutoUpdate';
@@ -32,6 +33,9 @@ function AppContent() {
   // Listen for chat streaming events from Rust core
   useChatStreaming();
 
+  // Keep local models warm for the active workspace
+  useWorkspaceActivity();
+
   // Load all app settings from database on mount
   useEffect(() => {
     dispatch(loadAppSettings());
@@ -24,6 +24,11 @@ export interface Prompt {
   content: string;
   created_at: number;
   updated_at: number;
+  folder?: string | null;
+  source?: string | null;
+  preferred_provider?: string | null;
+  preferred_model?: string | null;
+  required_capabilities?: ('vision' | 'tools' | 'thinking')[];
 }
 
 export type {
@@ -7,23 +7,71 @@
 export const TauriCommands = {
   // Basic commands
   GREET: 'greet',
+  GET_EVENT_SCHEMA: 'get_event_schema',
 
   // Workspace commands
   CREATE_WORKSPACE: 'create_workspace',
   GET_WORKSPACES: 'get_workspaces',
   UPDATE_WORKSPACE: 'update_workspace',
   DELETE_WORKSPACE: 'delete_workspace',
+  CHECK_WORKSPACE_READINESS: 'check_workspace_readiness',
 
   // Chat commands
   CREATE_CHAT: 'create_chat',
   GET_CHATS: 'get_chats',
+  MARK_CHAT_READ: 'mark_chat_read',
   UPDATE_CHAT: 'update_chat',
   DELETE_CHAT: 'delete_chat',
   DELETE_ALL_CHATS_BY_WORKSPACE: 'delete_all_chats_by_workspace',
   SEND_MESSAGE: 'send_message',
+  SEND_MESSAGE_COMPARE: 'send_message_compare',
   EDIT_AND_RESEND_MESSAGE: 'edit_and_resend_message',
   RESPOND_TOOL_PERMISSION: 'respond_tool_permission',
+  LIST_REMEMBERED_TOOL_PERMISSIONS: 'list_remembered_tool_permissions',
+  REVOKE_REMEMBERED_TOOL_PERMISSION: 'revoke_remembered_tool_permission',
+  RESPOND_COST_CONFIRMATION: 'respond_cost_confirmation',
   GENERATE_CHAT_TITLE: 'generate_chat_title',
+  SUMMARIZE_CHAT: 'summarize_chat',
+  RUN_PROMPT_ONESHOT: 'run_prompt_oneshot',
+  NOTIFY_WORKSPACE_ACTIVE: 'notify_workspace_active',
+  GET_CHAT_SCRATCHPAD: 'get_chat_scratchpad',
+  SET_CHAT_SCRATCHPAD: 'set_chat_scratchpad',
+  GET_CHAT_INSTRUCTIONS: 'get_chat_instructions',
+  SET_CHAT_INSTRUCTIONS: 'set_chat_instructions',
+  GET_CHAT_BOOT_DATA: 'get_chat_boot_data',
+  GET_TURN_TIMELINE: 'get_turn_timeline',
+  GET_ACTIVE_CHAT_STATUS: 'get_active_chat_status',
+  GET_MESSAGE_PERMALINK: 'get_message_permalink',
+  OPEN_PERMALINK: 'open_permalink',
+  TAKE_PENDING_NAVIGATION: 'take_pending_navigation',
+  EXPORT_CHAT: 'export_chat',
+  EXPORT_WORKSPACE: 'export_workspace',
+  LIST_BRANCHES: 'list_branches',
+  CREATE_BRANCH: 'create_branch',
+  SWITCH_BRANCH: 'switch_branch',
+  REGENERATE_MESSAGE: 'regenerate_message',
+  SELECT_MESSAGE_VARIANT: 'select_message_variant',
+
+  // Activity commands
+  GET_ACTIVITY: 'get_activity',
+  MARK_ACTIVITY_READ: 'mark_activity_read',
+
+  // Semantic index commands
+  SEMANTIC_SEARCH_MESSAGES: 'semantic_search_messages',
+  BACKFILL_SEMANTIC_INDEX: 'backfill_semantic_index',
+
+  // Knowledge commands
+  INDEX_DOCUMENT: 'index_document',
+  LIST_KNOWLEDGE_DOCUMENTS: 'list_knowledge_documents',
+  SEARCH_KNOWLEDGE: 'search_knowledge',
+  DELETE_DOCUMENT: 'delete_document',
+
+  // Search commands
+  SEARCH_MESSAGES: 'search_messages',
+  SEARCH_CHATS: 'search_chats',
+
+  // Tool catalog commands
+  GET_TOOL_CATALOG_HISTORY: 'get_tool_catalog_history',
 
   // Chat Input Settings commands
   GET_CHAT_INPUT_SETTINGS: 'get_chat_input_settings',
@@ -36,10 +84,15 @@ export const TauriCommands = {
   DELETE_MESSAGE: 'delete_message',
   DELETE_MESSAGES_AFTER: 'delete_messages_after',
   CANCEL_MESSAGE: 'cancel_message',
+  DIFF_MESSAGES: 'diff_messages',
 
   // Workspace Settings commands
   SAVE_WORKSPACE_SETTINGS: 'save_workspace_settings',
   GET_WORKSPACE_SETTINGS: 'get_workspace_settings',
+  GET_WORKSPACE_NOTES: 'get_workspace_notes',
+  SAVE_WORKSPACE_NOTES: 'save_workspace_notes',
+  EXPORT_WORKSPACE_ARCHIVE: 'export_workspace_archive',
+  IMPORT_WORKSPACE_ARCHIVE: 'import_workspace_archive',
 
   // LLM Connection commands
   CREATE_LLM_CONNECTION: 'create_llm_connection',
@@ -47,6 +100,11 @@ export const TauriCommands = {
   UPDATE_LLM_CONNECTION: 'update_llm_connection',
   DELETE_LLM_CONNECTION: 'delete_llm_connection',
   TEST_LLM_CONNECTION: 'test_llm_connection',
+  GET_EFFECTIVE_MODELS: 'get_effective_models',
+  GET_CONNECTION_RATE_STATUS: 'get_connection_rate_status',
+  EMBED_TEXTS: 'embed_texts',
+  GET_OLLAMA_MODEL_INFO: 'get_ollama_model_info',
+  PULL_OLLAMA_MODEL: 'pull_ollama_model',
 
   // MCP Server Connection commands
   CREATE_MCP_SERVER_CONNECTION: 'create_mcp_server_connection',
@@ -54,17 +112,44 @@ export const TauriCommands = {
   UPDATE_MCP_SERVER_CONNECTION: 'update_mcp_server_connection',
   DELETE_MCP_SERVER_CONNECTION: 'delete_mcp_server_connection',
   UPDATE_MCP_SERVER_STATUS: 'update_mcp_server_status',
+  SET_MCP_SERVER_AUTO_CONNECT: 'set_mcp_server_auto_connect',
+  VALIDATE_MCP_CONNECTION_CONFIG: 'validate_mcp_connection_config',
 
   // App Settings commands
   SAVE_APP_SETTING: 'save_app_setting',
   GET_APP_SETTING: 'get_app_setting',
   GET_ALL_APP_SETTINGS: 'get_all_app_settings',
+  GET_TIME_SETTINGS: 'get_time_settings',
+  SAVE_TIME_SETTINGS: 'save_time_settings',
+  GET_BUILTIN_TOOL_SETTINGS: 'get_builtin_tool_settings',
+  SAVE_BUILTIN_TOOL_SETTINGS: 'save_builtin_tool_settings',
 
   // Prompt commands
   CREATE_PROMPT: 'create_prompt',
   GET_PROMPTS: 'get_prompts',
   UPDATE_PROMPT: 'update_prompt',
   DELETE_PROMPT: 'delete_prompt',
+  EXPORT_PROMPTS: 'export_prompts',
+  IMPORT_PROMPTS: 'import_prompts',
+  CREATE_PROMPT_FROM_MESSAGES: 'create_prompt_from_messages',
+
+  // Quick action commands
+  CREATE_QUICK_ACTION: 'create_quick_action',
+  GET_QUICK_ACTIONS: 'get_quick_actions',
+  UPDATE_QUICK_ACTION: 'update_quick_action',
+  DELETE_QUICK_ACTION: 'delete_quick_action',
+  RUN_QUICK_ACTION: 'run_quick_action',
+
+  // Webhook commands
+  CREATE_WEBHOOK: 'create_webhook',
+  GET_WEBHOOKS: 'get_webhooks',
+  UPDATE_WEBHOOK: 'update_webhook',
+  DELETE_WEBHOOK: 'delete_webhook',
+
+  // Outbox commands
+  GET_OUTBOX: 'get_outbox',
+  RETRY_OUTBOX: 'retry_outbox',
+  DISCARD_OUTBOX_ITEM: 'discard_outbox_item',
 
   // Hub commands
   FETCH_HUB_PROMPTS: 'fetch_hub_prompts',
@@ -75,6 +160,9 @@ export const TauriCommands = {
   REFRESH_HUB_INDEX: 'refresh_hub_index',
   FETCH_HUB_AGENTS: 'fetch_hub_agents',
   INSTALL_AGENT_FROM_HUB: 'install_agent_from_hub',
+  FETCH_HUB_PACKS: 'fetch_hub_packs',
+  INSTALL_PACK_FROM_HUB: 'install_pack_from_hub',
+  UNINSTALL_PACK_FROM_HUB: 'uninstall_pack_from_hub',
 
   // MCP Tools commands
   TEST_MCP_CONNECTION_AND_FETCH_TOOLS: 'test_mcp_connection_and_fetch_tools',
@@ -83,6 +171,16 @@ export const TauriCommands = {
   CALL_MCP_TOOL: 'call_mcp_tool',
   DISCONNECT_MCP_CLIENT: 'disconnect_mcp_client',
   GET_ACTIVE_TOOLS_FOR_WORKSPACE: 'get_active_tools_for_workspace',
+  GET_WORKSPACE_TOOLS: 'get_workspace_tools',
+  SET_WORKSPACE_TOOL_OVERRIDE: 'set_workspace_tool_override',
+  LIST_MCP_RESOURCES: 'list_mcp_resources',
+  LIST_MCP_RESOURCE_TEMPLATES: 'list_mcp_resource_templates',
+  READ_MCP_RESOURCE: 'read_mcp_resource',
+  ATTACH_MCP_RESOURCE: 'attach_mcp_resource',
+  LIST_MCP_PROMPTS: 'list_mcp_prompts',
+  GET_MCP_PROMPT: 'get_mcp_prompt',
+  APPLY_MCP_PROMPT_TO_CHAT: 'apply_mcp_prompt_to_chat',
+  RESPOND_MCP_SAMPLING: 'respond_mcp_sampling',
 
   // Python commands
   GET_PYTHON_RUNTIMES_STATUS: 'get_python_runtimes_status',
@@ -105,6 +203,9 @@ export const TauriCommands = {
   DELETE_AGENT: 'delete_agent',
   GET_AGENT_INFO: 'get_agent_info',
   UPDATE_AGENT: 'update_agent',
+  SET_AGENT_TOOL_PERMISSIONS: 'set_agent_tool_permissions',
+  EXPORT_AGENT: 'export_agent',
+  IMPORT_AGENT: 'import_agent',
   GET_OR_CREATE_SPECIALIST_SESSION: 'get_or_create_specialist_session',
 
   // Skill commands
@@ -118,6 +219,35 @@ export const TauriCommands = {
   GET_NOTES: 'get_notes',
   UPDATE_NOTE: 'update_note',
   DELETE_NOTE: 'delete_note',
+  // Attachment commands
+  APPROVE_FILE_ACCESS: 'approve_file_access',
+  VERIFY_ATTACHMENTS: 'verify_attachments',
+  CANCEL_VERIFY_ATTACHMENTS: 'cancel_verify_attachments',
+  RESOLVE_LUNEX_FILE: 'resolve_lunex_file',
+  EMBED_LUNEX_FILES: 'embed_lunex_files',
+  EXPORT_LUNEX_FILES: 'export_lunex_files',
+  // LLM response cache commands
+  GET_LLM_CACHE_STATS: 'get_llm_cache_stats',
+  CLEAR_LLM_CACHE: 'clear_llm_cache',
+  // Encryption and system commands
+  ENABLE_ENCRYPTION: 'enable_encryption',
+  DISABLE_ENCRYPTION: 'disable_encryption',
+  GET_SYSTEM_STATUS: 'get_system_status',
+  RUN_DB_MAINTENANCE: 'run_db_maintenance',
+  GET_DB_INFO: 'get_db_info',
+  PANIC_STOP_ALL: 'panic_stop_all',
+  RESUME_ACTIVITY: 'resume_activity',
+  // Backup commands
+  BACKUP_NOW: 'backup_now',
+  LIST_BACKUPS: 'list_backups',
+  RESTORE_BACKUP: 'restore_backup',
+  // Chat share commands
+  START_CHAT_SHARE: 'start_chat_share',
+  STOP_CHAT_SHARE: 'stop_chat_share',
+  // Chat import commands
+  IMPORT_EXTERNAL_CHATS: 'import_external_chats',
+  IMPORT_CHAT: 'import_chat',
+  CANCEL_CHAT_IMPORT: 'cancel_chat_import',
 } as const;
 
 export type TauriCommand = (typeof TauriCommands)[keyof typeof TauriCommands];
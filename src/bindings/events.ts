@@ -13,6 +13,10 @@ export const TauriEvents = {
   MESSAGE_ERROR: 'message-error',
   MESSAGE_CANCELLED: 'message-cancelled',
   MESSAGE_METADATA_UPDATED: 'message-metadata-updated',
+  OUTPUT_LIMIT_REACHED: 'output-limit-reached',
+  MESSAGE_STREAM_STATS: 'message-stream-stats',
+  MESSAGE_VARIANTS_UPDATED: 'message-variants-updated',
+  CONTEXT_TRIMMED: 'context-trimmed',
 
   // Tool call events
   TOOL_CALL_REQUEST: 'tool-call-request',
@@ -24,9 +28,15 @@ export const TauriEvents = {
   TOOL_EXECUTION_COMPLETED: 'tool-execution-completed',
   TOOL_EXECUTION_ERROR: 'tool-execution-error',
   TOOL_PERMISSION_REQUEST: 'tool-permission-request',
+  COST_CONFIRMATION_REQUEST: 'cost-confirmation-request',
+  SCRATCHPAD_UPDATED: 'scratchpad-updated',
+
+  // Workspace events
+  WORKSPACE_NOTES_UPDATED: 'workspace-notes-updated',
 
   // Agent events
   AGENT_LOOP_ITERATION: 'agent-loop-iteration',
+  AGENT_LOOP_WARNING: 'agent-loop-warning',
 
   // Menu events
   MENU_NEW_CHAT: 'menu-new-chat',
@@ -45,6 +55,38 @@ export const TauriEvents = {
 
   // Chat events
   CHAT_UPDATED: 'chat-updated',
+  NAVIGATE_TO_MESSAGE: 'navigate-to-message',
+  ACTIVE_CHAT_STATUS_CHANGED: 'active-chat-status-changed',
+  OUTBOX_STATUS: 'outbox-status',
+
+  // Activity events
+  ACTIVITY_ADDED: 'activity-added',
+
+  // Model events
+  MODEL_CAPABILITY_WARNING: 'model-capability-warning',
+
+  // Quick action events
+  QUICK_ACTION: 'quick-action',
+
+  // Encryption events
+  ENCRYPTION_PROGRESS: 'encryption-progress',
+
+  // Chat import events
+  CHAT_IMPORT_PROGRESS: 'chat-import-progress',
+
+  // Semantic index events
+  SEMANTIC_INDEX_PROGRESS: 'semantic-index-progress',
+
+  // Kill switch events
+  GLOBAL_HALT: 'global-halt',
+
+  // Local model events
+  OLLAMA_PULL_PROGRESS: 'ollama-pull-progress',
+
+  // MCP events
+  MCP_SAMPLING_REQUEST: 'mcp-sampling-request',
+  MCP_SERVER_STATUS: 'mcp-server-status',
+  MCP_AUTO_CONNECT_PROGRESS: 'mcp-auto-connect-progress',
 } as const;
 
 export type TauriEvent = (typeof TauriEvents)[keyof typeof TauriEvents];
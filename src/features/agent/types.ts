@@ -11,19 +11,25 @@ export interface AgentManifest {
 }
 
 export interface AgentSource {
-  type: 'git' | 'local';
+  type: 'git' | 'local' | 'imported';
   url?: string;
   revision?: string;
   sub_path?: string;
   path?: string;
+  content_hash?: string;
 }
 
 export interface InstallInfo {
   source: AgentSource;
   installed_at: number;
   updated_at: number;
+  file_hashes?: Record<string, string>;
 }
 
+export type AgentUpdateResult =
+  | { status: 'updated'; agent_id: string; local_copy?: string | null }
+  | { status: 'local_changes'; changed_files: string[] };
+
 export interface InstalledAgent {
   manifest: AgentManifest;
   version_ref: string;
@@ -40,7 +40,7 @@ import {
 import { TauriCommands } from '@/bindings/commands';
 import { invokeCommand } from '@/lib/tauri';
 import { useGetInstalledAgentsQuery } from '../state/api';
-import type { InstalledAgent } from '../types';
+import type { AgentUpdateResult, InstalledAgent } from '../types';
 import { ConfirmDialog } from '@/ui/molecules/ConfirmDialog';
 
 export function AgentSettings() {
@@ -68,6 +68,9 @@ export function AgentSettings() {
   const [agentTools, setAgentTools] = useState<
     Array<{ name: string; description?: string }>
   >([]);
+  const [agentToolPermissions, setAgentToolPermissions] = useState<
+    Record<string, string>
+  >({});
   const [agentInstructions, setAgentInstructions] = useState<string>('');
   const [loadingAgentInfo, setLoadingAgentInfo] = useState(false);
 
@@ -134,17 +137,43 @@ export function AgentSettings() {
     }
   };
 
-  const handleUpdateAgent = async () => {
+  const handleUpdateAgent = async (keepLocalChanges?: boolean) => {
     if (!selectedAgent) return;
 
     setUpdating(true);
     toast.info(`Updating agent ${selectedAgent.manifest.name}...`);
 
     try {
-      await invoke(TauriCommands.UPDATE_AGENT, {
-        agentId: selectedAgent.manifest.id,
-      });
-
+      const result = await invoke<AgentUpdateResult | undefined>(
+        TauriCommands.UPDATE_AGENT,
+        {
+          agentId: selectedAgent.manifest.id,
+          ...(keepLocalChanges !== undefined && { keepLocalChanges }),
+        }
+      );
+
+      if (result?.status === 'local_changes') {
+        // Nothing was overwritten; let the user choose what happens to the edits
+        toast.warning(
+          `${selectedAgent.manifest.name} has local changes to ${result.changed_files.length} file(s) that the update would overwrite.`,
+          {
+            duration: 10000,
+            action: {
+              label: 'Back up and update',
+              onClick: () => handleUpdateAgent(true),
+            },
+            cancel: {
+              label: 'Discard and update',
+              onClick: () => handleUpdateAgent(false),
+            },
+          }
+        );
+        return;
+      }
+
+      if (result?.status === 'updated' && result.local_copy) {
+        toast.info(`Local changes saved to ${result.local_copy}`);
+      }
       toast.success('Agent updated successfully!');
       // Refresh list and keep dialog open but refresh details maybe?
       // Actually fetchAgents will update the list state.
@@ -188,13 +217,16 @@ export function AgentSettings() {
       const info = await invokeCommand<{
         tools: Array<{ name: string; description?: string }>;
         instructions: string;
+        tool_permissions?: Record<string, string>;
       }>(TauriCommands.GET_AGENT_INFO, { agentId });
 
       setAgentTools(info.tools || []);
+      setAgentToolPermissions(info.tool_permissions || {});
       setAgentInstructions(info.instructions || '');
     } catch (error) {
       logger.error('Failed to fetch agent info in AgentSettings:', error);
       setAgentTools([]);
+      setAgentToolPermissions({});
       setAgentInstructions('');
     } finally {
       setLoadingAgentInfo(false);
@@ -586,8 +618,15 @@ export function AgentSettings() {
                             key={index}
                             className="p-2 rounded bg-background border"
                           >
-                            <div className="font-medium text-xs mb-0.5">
-                              {tool.name}
+                            <div className="flex items-center justify-between gap-2 mb-0.5">
+                              <span className="font-medium text-xs">
+                                {tool.name}
+                              </span>
+                              <span className="text-[10px] text-muted-foreground">
+                                {agentToolPermissions[tool.name] === 'auto'
+                                  ? 'Runs automatically'
+                                  : 'Asks before running'}
+                              </span>
                             </div>
                             {tool.description && (
                               <p className="text-xs text-muted-foreground leading-snug">
@@ -652,7 +691,7 @@ export function AgentSettings() {
               <Button
                 type="button"
                 variant="outline"
-                onClick={handleUpdateAgent}
+                onClick={() => handleUpdateAgent()}
                 disabled={updating}
                 className="gap-2"
               >
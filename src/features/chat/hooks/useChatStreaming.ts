@@ -1,5 +1,11 @@
 import { useEffect, useRef } from 'react';
-import { listenToEvent, TauriEvents } from '@/lib/tauri';
+import { toast } from 'sonner';
+import {
+  invokeCommand,
+  listenToEvent,
+  TauriCommands,
+  TauriEvents,
+} from '@/lib/tauri';
 import { useAppDispatch } from '@/app/hooks';
 import {
   setStreamingMessageId,
@@ -19,6 +25,11 @@ import { useTranslation } from 'react-i18next';
 import { messagesApi } from '@/features/chat/state/messagesApi';
 import { extractCodeBlocks } from '@/features/chat/lib/code-block-extractor';
 import { logger } from '@/lib/logger';
+import {
+  showInfo,
+  showSuccess,
+  showWarning,
+} from '@/features/notifications/state/notificationSlice';
 
 // Event types
 interface MessageStartedEvent {
@@ -39,6 +50,24 @@ interface ThinkingChunkEvent {
   chunk: string;
 }
 
+interface GlobalHaltEvent {
+  halted: boolean;
+  chats_stopped: number;
+  tool_executions_stopped: number;
+  permission_requests_cancelled: number;
+  cost_confirmations_cancelled: number;
+  sampling_requests_cancelled: number;
+  outbox_chats_paused: number;
+}
+
+interface MessageStreamStatsEvent {
+  chat_id: string;
+  message_id: string;
+  est_tokens_so_far: number;
+  tokens_per_sec: number;
+  elapsed_ms: number;
+}
+
 interface MessageCompleteEvent {
   chat_id: string;
   message_id: string;
@@ -48,12 +77,22 @@ interface MessageCompleteEvent {
     completion_tokens?: number;
     total_tokens?: number;
   };
+  // Cancelled mid-stream; content is what was stored before the cancel
+  partial?: boolean;
 }
 
 interface MessageErrorEvent {
   chat_id: string;
   message_id: string;
   error: string;
+  retry_after_ms?: number;
+  limit_kind?: 'requests' | 'tokens';
+}
+
+interface MessageCancelledEvent {
+  chat_id: string;
+  message_id: string;
+  content: string;
 }
 
 interface ToolCallsDetectedEvent {
@@ -115,6 +154,24 @@ interface ToolPermissionRequestEvent {
   }>;
 }
 
+interface CostConfirmationRequestEvent {
+  chat_id: string;
+  message_id: string;
+  threshold: number;
+  estimate: {
+    model: string;
+    prompt_tokens: number;
+    tool_schema_tokens: number;
+    assumed_completion_tokens: number;
+    input_cost: number;
+    output_cost: number;
+    estimated_cost: number;
+    projected_loop_cost: number | null;
+    projected_calls: number | null;
+  };
+  timeout_secs: number;
+}
+
 interface MessageMetadataUpdatedEvent {
   chat_id: string;
   message_id: string;
@@ -125,6 +182,38 @@ interface ChatUpdatedEvent {
   title: string;
 }
 
+interface ModelCapabilityWarningEvent {
+  chat_id: string;
+  prompt_id: string;
+  model: string;
+  missing_capabilities: string[];
+}
+
+interface OutputLimitReachedEvent {
+  chat_id: string;
+  message_id: string;
+  kind: 'content' | 'reasoning' | 'tool_arguments';
+  limit_bytes: number;
+}
+
+interface ContextTrimmedEvent {
+  chat_id: string;
+  message_id: string;
+  dropped_messages: number;
+  estimated_tokens: number;
+  context_window: number;
+}
+
+interface OutboxStatusEvent {
+  chat_id: string;
+  item_id: string;
+  user_message_id: string;
+  status: 'queued' | 'sending' | 'sent' | 'failed' | 'discarded';
+  attempts: number;
+  error?: string;
+  assistant_message_id?: string;
+}
+
 export function useChatStreaming() {
   const dispatch = useAppDispatch();
   const { t } = useTranslation('chat');
@@ -200,6 +289,28 @@ export function useChatStreaming() {
       }
     );
 
+    // Live generation speed; the complete event replaces it with the real usage
+    const unlistenStreamStats = listenToEvent<MessageStreamStatsEvent>(
+      TauriEvents.MESSAGE_STREAM_STATS,
+      (payload) => {
+        dispatch(
+          messagesApi.util.updateQueryData(
+            'getMessages',
+            payload.chat_id,
+            (draft: Message[]) => {
+              const message = draft.find((m) => m.id === payload.message_id);
+              if (message) {
+                message.tokenUsage = {
+                  ...message.tokenUsage,
+                  tokensPerSecond: payload.tokens_per_sec,
+                };
+              }
+            }
+          )
+        );
+      }
+    );
+
     // Listen to message complete events
     const unlistenComplete = listenToEvent<MessageCompleteEvent>(
       TauriEvents.MESSAGE_COMPLETE,
@@ -270,8 +381,9 @@ export function useChatStreaming() {
         }
 
         // Only clear streaming if this message doesn't have tool calls
-        // If it has tool calls, the loop will continue and the next MESSAGE_STARTED will handle it
-        if (!hasToolCallsRef.current[payload.message_id]) {
+        // If it has tool calls, the loop will continue and the next MESSAGE_STARTED will handle it.
+        // A cancelled message's tool calls never run.
+        if (payload.partial || !hasToolCallsRef.current[payload.message_id]) {
           dispatch(
             setStreamingByChatId({
               chatId: payload.chat_id,
@@ -319,6 +431,34 @@ export function useChatStreaming() {
       }
     );
 
+    // Listen to message cancelled events (partial content is kept)
+    const unlistenCancelled = listenToEvent<MessageCancelledEvent>(
+      TauriEvents.MESSAGE_CANCELLED,
+      (payload) => {
+        dispatch(
+          messagesApi.util.updateQueryData(
+            'getMessages',
+            payload.chat_id,
+            (draft: Message[]) => {
+              const message = draft.find((m) => m.id === payload.message_id);
+              if (message) {
+                message.content = payload.content;
+              }
+            }
+          )
+        );
+
+        dispatch(
+          setStreamingByChatId({
+            chatId: payload.chat_id,
+            messageId: null,
+          })
+        );
+        dispatch(clearStreamingMessageId());
+        dispatch(clearStreamingStartTime(payload.chat_id));
+      }
+    );
+
     // Listen to message metadata updated
     const unlistenMetadataUpdated = listenToEvent<MessageMetadataUpdatedEvent>(
       TauriEvents.MESSAGE_METADATA_UPDATED,
@@ -463,12 +603,148 @@ export function useChatStreaming() {
       }
     );
 
+    const unlistenCostConfirmationRequest =
+      listenToEvent<CostConfirmationRequestEvent>(
+        TauriEvents.COST_CONFIRMATION_REQUEST,
+        (payload) => {
+          const { estimate } = payload;
+          const respond = (approved: boolean) =>
+            invokeCommand(TauriCommands.RESPOND_COST_CONFIRMATION, {
+              messageId: payload.message_id,
+              approved,
+            }).catch((error) =>
+              logger.error('Failed to respond to cost confirmation:', error)
+            );
+          const loop =
+            estimate.projected_loop_cost != null
+              ? ` Up to $${estimate.projected_loop_cost.toFixed(2)} if the agent runs all ${estimate.projected_calls} steps.`
+              : '';
+          toast.warning(
+            `This message is estimated to cost $${estimate.estimated_cost.toFixed(2)} on ${estimate.model} (about ${estimate.prompt_tokens.toLocaleString()} prompt tokens).${loop}`,
+            {
+              duration: payload.timeout_secs * 1000,
+              action: { label: 'Send', onClick: () => respond(true) },
+              cancel: { label: "Don't send", onClick: () => respond(false) },
+            }
+          );
+        }
+      );
+
+    const unlistenModelCapabilityWarning =
+      listenToEvent<ModelCapabilityWarningEvent>(
+        TauriEvents.MODEL_CAPABILITY_WARNING,
+        (payload) => {
+          dispatch(
+            showWarning(
+              t('modelCapabilityWarning'),
+              t('modelCapabilityWarningDescription', {
+                model: payload.model,
+                capabilities: payload.missing_capabilities.join(', '),
+              })
+            )
+          );
+        }
+      );
+
+    const unlistenOutputLimitReached = listenToEvent<OutputLimitReachedEvent>(
+      TauriEvents.OUTPUT_LIMIT_REACHED,
+      (payload) => {
+        dispatch(
+          showWarning(
+            t('outputLimitReached'),
+            t('outputLimitReachedDescription', {
+              kind: t(`outputLimitKind.${payload.kind}`),
+              size: `${Math.round(payload.limit_bytes / 1024).toLocaleString()} KB`,
+            })
+          )
+        );
+      }
+    );
+
+    // The oldest turns were left out so the request fits the model's context
+    const unlistenContextTrimmed = listenToEvent<ContextTrimmedEvent>(
+      TauriEvents.CONTEXT_TRIMMED,
+      (payload) => {
+        dispatch(
+          showInfo(
+            t('contextTrimmed'),
+            t('contextTrimmedDescription', {
+              count: payload.dropped_messages,
+              window: payload.context_window.toLocaleString(),
+            })
+          )
+        );
+      }
+    );
+
+    // The kill switch stopped everything, or activity resumed
+    const unlistenGlobalHalt = listenToEvent<GlobalHaltEvent>(
+      TauriEvents.GLOBAL_HALT,
+      (payload) => {
+        if (payload.halted) {
+          dispatch(
+            showWarning(
+              t('activityHalted'),
+              t('activityHaltedDescription', {
+                chats: payload.chats_stopped,
+                tools: payload.tool_executions_stopped,
+                queued: payload.outbox_chats_paused,
+              })
+            )
+          );
+        } else {
+          dispatch(showInfo(t('activityResumed')));
+        }
+      }
+    );
+
+    // Messages kept while the model was unreachable, and their resends
+    const unlistenOutboxStatus = listenToEvent<OutboxStatusEvent>(
+      TauriEvents.OUTBOX_STATUS,
+      (payload) => {
+        dispatch(
+          messagesApi.util.invalidateTags([
+            { type: 'Message', id: `LIST_${payload.chat_id}` },
+          ])
+        );
+
+        if (payload.status === 'queued') {
+          dispatch(
+            setStreamingByChatId({
+              chatId: payload.chat_id,
+              messageId: null,
+            })
+          );
+          dispatch(clearStreamingMessageId());
+          dispatch(clearStreamingStartTime(payload.chat_id));
+          // Only the first failure is announced; retries stay quiet
+          if (payload.attempts <= 1) {
+            dispatch(
+              showInfo(t('outboxQueued'), t('outboxQueuedDescription'))
+            );
+          }
+        } else if (payload.status === 'sent') {
+          dispatch(showSuccess(t('outboxSent')));
+        } else if (payload.status === 'failed') {
+          dispatch(
+            showWarning(
+              t('outboxFailed'),
+              t('outboxFailedDescription', { error: payload.error ?? '' })
+            )
+          );
+        }
+      }
+    );
+
     return () => {
       unlistenStarted.then((fn) => fn());
       unlistenChunk.then((fn) => fn());
       unlistenThinkingChunk.then((fn) => fn());
+      unlistenStreamStats.then((fn) => fn());
+      unlistenGlobalHalt.then((fn) => fn());
       unlistenComplete.then((fn) => fn());
       unlistenError.then((fn) => fn());
+      unlistenCancelled.then((fn) => fn());
       unlistenToolCalls.then((fn) => fn());
       unlistenToolExecutionStarted.then((fn) => fn());
       unlistenToolExecutionProgress.then((fn) => fn());
@@ -476,8 +752,13 @@ export function useChatStreaming() {
       unlistenToolExecutionError.then((fn) => fn());
       unlistenAgentLoopIteration.then((fn) => fn());
       unlistenToolPermissionRequest.then((fn) => fn());
+      unlistenCostConfirmationRequest.then((fn) => fn());
       unlistenMetadataUpdated.then((fn) => fn());
       unlistenChatUpdated.then((fn) => fn());
+      unlistenModelCapabilityWarning.then((fn) => fn());
+      unlistenOutputLimitReached.then((fn) => fn());
+      unlistenContextTrimmed.then((fn) => fn());
+      unlistenOutboxStatus.then((fn) => fn());
     };
   }, [dispatch, t]);
 }
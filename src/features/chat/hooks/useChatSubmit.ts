@@ -16,12 +16,14 @@ interface UseChatSubmitProps {
   attachedFiles: File[];
   attachedFlow: FlowData | null;
   selectedAgentIds: string[];
-  setInsertedPrompt: (prompt: { name: string; content: string } | null) => void;
+  setInsertedPrompt: (
+    prompt: { id?: string; name: string; content: string } | null
+  ) => void;
   setSelectedAgentIds: (ids: string[]) => void;
   setFlow: (flow: FlowData | null) => void;
   handleFileUpload: (files: File[]) => void;
   input: string;
-  insertedPrompt: { name: string; content: string } | null;
+  insertedPrompt: { id?: string; name: string; content: string } | null;
 }
 
 export function useChatSubmit({
@@ -102,14 +104,18 @@ export function useChatSubmit({
       }
 
       // If we have a flow attachment, we need to add it to metadata
-      let metadata: string | undefined;
+      const meta: Record<string, unknown> = {};
       if (attachedFlow) {
-        metadata = JSON.stringify({
-          type: 'flow_attachment',
-          flow: attachedFlow,
-          timestamp: Date.now(),
-        });
+        meta.type = 'flow_attachment';
+        meta.flow = attachedFlow;
+        meta.timestamp = Date.now();
       }
+      // Lets the backend honour the prompt's preferred model for this turn
+      if (insertedPrompt?.id) {
+        meta.prompt_id = insertedPrompt.id;
+      }
+      const metadata =
+        Object.keys(meta).length > 0 ? JSON.stringify(meta) : undefined;
 
       return {
         input: combinedInput,
@@ -1,6 +1,7 @@
 import { useState, useCallback } from 'react';
 import { logger } from '@/lib/logger';
 import { copyMarkdownToClipboard } from '@/lib/clipboard';
+import { embedLunexFiles } from '@/lib/lunexFile';
 
 export interface UseMessageListStateProps {
   externalMarkdownEnabled?: Record<string, boolean>;
@@ -39,7 +40,7 @@ export function useMessageListState({
   const handleCopy = useCallback(
     async (content: string, messageId: string) => {
       try {
-        await copyMarkdownToClipboard(content);
+        await copyMarkdownToClipboard(await embedLunexFiles(content));
         if (onCopiedIdChange) {
           onCopiedIdChange(messageId);
         } else {
@@ -20,13 +20,16 @@ export function useToolPermission() {
       messageId: string,
       toolId: string,
       toolName: string,
-      approved: boolean
+      approved: boolean,
+      // Keep the answer so the tool is not asked about again
+      remember?: 'workspace' | 'chat'
     ) => {
       try {
         await invokeCommand(TauriCommands.RESPOND_TOOL_PERMISSION, {
           messageId,
           approved,
           allowedToolIds: approved ? [toolId] : [],
+          remember: remember ?? null,
         });
 
         if (!approved && selectedChatId) {
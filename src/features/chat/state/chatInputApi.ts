@@ -1,14 +1,22 @@
 import { baseApi } from '@/app/api/baseApi';
 import { TauriCommands } from '@/bindings/commands';
+import {
+  fromDbGenerationParams,
+  toGenerationParamsPayload,
+  type DbGenerationParams,
+  type GenerationParams,
+} from '@/models/generation';
 
 export interface ChatInputSettings {
   workspaceId: string;
   selectedModel?: string;
   streamEnabled: boolean;
+  /** Overrides of the workspace's generation parameters */
+  generationParams: GenerationParams;
   updatedAt: number;
 }
 
-interface ChatInputSettingsBackend {
+interface ChatInputSettingsBackend extends DbGenerationParams {
   workspace_id: string;
   selected_model: string | null;
   stream_enabled: number;
@@ -35,6 +43,7 @@ export const chatInputApi = baseApi.injectEndpoints({
             workspaceId: data.workspace_id,
             selectedModel: data.selected_model || undefined,
             streamEnabled: data.stream_enabled === 1,
+            generationParams: fromDbGenerationParams(data),
             updatedAt: data.updated_at,
           },
         };
@@ -45,10 +54,16 @@ export const chatInputApi = baseApi.injectEndpoints({
     }),
     saveChatInputSettings: builder.mutation<
       void,
-      { workspaceId: string; selectedModel?: string; streamEnabled: boolean }
+      {
+        workspaceId: string;
+        selectedModel?: string;
+        streamEnabled: boolean;
+        // Left out to keep the stored overrides
+        generationParams?: GenerationParams;
+      }
     >({
       queryFn: async (
-        { workspaceId, selectedModel, streamEnabled },
+        { workspaceId, selectedModel, streamEnabled, generationParams },
         _api,
         _extraOptions,
         baseQuery
@@ -59,6 +74,9 @@ export const chatInputApi = baseApi.injectEndpoints({
             workspaceId,
             selectedModel: selectedModel || null,
             streamEnabled,
+            generationParams: generationParams
+              ? toGenerationParamsPayload(generationParams)
+              : null,
           },
         });
 
@@ -13,6 +13,9 @@ interface Chat {
   updated_at: number;
   agent_id: string | null;
   parent_id: string | null;
+  last_read_at: number | null;
+  unread_count: number;
+  custom_instructions?: string | null;
 }
 
 interface ChatsState {
@@ -116,8 +116,12 @@ export function createEditAndResendMessageThunk(actions: {
           newFiles: files, // Map files to newFiles expected by backend
           metadata,
           selectedModel: context.selectedModel,
-          reasoningEffort:
-            isThinkingEnabled && supportsThinking ? reasoningEffort : undefined,
+          // 'none' turns thinking off on models that think by default
+          reasoningEffort: supportsThinking
+            ? isThinkingEnabled
+              ? reasoningEffort
+              : 'none'
+            : undefined,
           llmConnectionId: context.llmConnection.id,
         }
       );
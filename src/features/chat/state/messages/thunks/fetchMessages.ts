@@ -14,6 +14,7 @@ interface DbMessage {
   tool_call_id: string | null;
   reasoning: string | null;
   metadata: string | null;
+  reply_to_message_id?: string | null;
 }
 
 export const fetchMessages = createAsyncThunk<
@@ -38,6 +39,7 @@ export const fetchMessages = createAsyncThunk<
         codeBlocks: codeBlocks.length > 0 ? codeBlocks : undefined,
         reasoning: m.reasoning ?? undefined,
         metadata: m.metadata ?? undefined,
+        replyToMessageId: m.reply_to_message_id ?? undefined,
       };
     }),
   };
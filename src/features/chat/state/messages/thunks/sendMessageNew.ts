@@ -27,11 +27,13 @@ export function createSendMessageThunkNew(_actions: {
         content,
         files,
         metadata,
+        replyToMessageId,
       }: {
         chatId: string;
         content: string;
         files?: string[];
         metadata?: string;
+        replyToMessageId?: string;
       },
       { getState, dispatch }
     ) => {
@@ -67,9 +69,14 @@ export function createSendMessageThunkNew(_actions: {
           files,
           metadata,
           selectedModel: context.selectedModel,
-          reasoningEffort:
-            isThinkingEnabled && supportsThinking ? reasoningEffort : undefined,
+          // 'none' turns thinking off on models that think by default
+          reasoningEffort: supportsThinking
+            ? isThinkingEnabled
+              ? reasoningEffort
+              : 'none'
+            : undefined,
           llmConnectionId: context.llmConnection.id,
+          replyToMessageId,
         }
       );
 
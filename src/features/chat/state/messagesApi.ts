@@ -13,6 +13,7 @@ interface DbMessage {
   tool_call_id: string | null;
   reasoning: string | null;
   metadata: string | null;
+  reply_to_message_id?: string | null;
 }
 
 export const messagesApi = baseApi.injectEndpoints({
@@ -51,6 +52,8 @@ export const messagesApi = baseApi.injectEndpoints({
             codeBlocks: codeBlocks.length > 0 ? codeBlocks : undefined,
             reasoning: m.reasoning ?? undefined,
             metadata: m.metadata ?? undefined,
+            replyToMessageId: m.reply_to_message_id ?? undefined,
+        replyToMessageId: m.reply_to_message_id ?? undefined,
             tokenUsage,
             toolCallId: m.tool_call_id ?? undefined,
           };
@@ -97,12 +100,14 @@ export const messagesApi = baseApi.injectEndpoints({
       ],
     }),
     updateMessage: builder.mutation<
-      void,
+      DbMessage,
       {
         id: string;
-        content: string;
+        content?: string;
         reasoning?: string | null;
-        timestamp?: number | null;
+        // Merged into the stored metadata unless replaceMetadata is set
+        metadata?: Record<string, unknown>;
+        replaceMetadata?: boolean;
       }
     >({
       query: (body) => ({
@@ -54,5 +54,6 @@ export interface Message {
   tokenUsage?: TokenUsage; // Token usage information for assistant messages
   codeBlocks?: CodeBlock[]; // Extracted code blocks (python, mermaid, etc.)
   metadata?: string; // JSON metadata string (e.g. for agent cards)
+  replyToMessageId?: string; // Earlier message of the chat this user message replies to
   // For tool_call messages, content is JSON string with: { name, arguments, result?, error?, status: "calling" | "completed" | "error" }
 }
@@ -342,8 +342,6 @@ export function ChatArea() {
             await invokeCommand(TauriCommands.UPDATE_MESSAGE, {
               id: currentEditingId,
               content: userInput,
-              reasoning: message.reasoning || null,
-              timestamp: null,
             });
             dispatch(
               messagesApi.util.invalidateTags([
@@ -114,6 +114,7 @@ export function ChatInput({
 
   // State for inserted prompt (to show prompt panel)
   const [insertedPrompt, setInsertedPrompt] = useState<{
+    id?: string;
     name: string;
     content: string;
   } | null>(null);
@@ -156,11 +157,16 @@ export function ChatInput({
   const effectiveIsStreaming = isStreaming && !isAgentStreaming;
 
   // Insert prompt content into input, replacing the slash command
-  const insertPromptContent = (content: string, promptName?: string) => {
+  const insertPromptContent = (
+    content: string,
+    promptName?: string,
+    promptId?: string
+  ) => {
     // Instead of inserting into textarea, set the inserted prompt state
     // This will show the prompt panel above textarea
     if (promptName) {
       setInsertedPrompt({
+        id: promptId,
         name: promptName,
         content: content,
       });
@@ -194,7 +200,7 @@ export function ChatInput({
       slashCommand.close();
       // Use setTimeout to ensure close() state is set before input changes
       setTimeout(() => {
-        insertPromptContent(prompt.content, prompt.name);
+        insertPromptContent(prompt.content, prompt.name, prompt.id);
       }, 0);
     }
   };
@@ -237,7 +243,11 @@ export function ChatInput({
       selectedPrompt.content,
       promptVariables
     );
-    insertPromptContent(renderedContent, selectedPrompt.name);
+    insertPromptContent(
+      renderedContent,
+      selectedPrompt.name,
+      selectedPrompt.id
+    );
     setVariableDialogOpen(false);
     setSelectedPrompt(null);
     setPromptVariables({});
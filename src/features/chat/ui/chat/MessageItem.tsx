@@ -210,6 +210,7 @@ export const MessageItem = memo(
                     <MessageAttachments
                       files={parsedMetadata.files}
                       images={parsedMetadata.images}
+                      missingFiles={parsedMetadata.missingFiles}
                     />
                   )}
 
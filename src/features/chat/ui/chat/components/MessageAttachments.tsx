@@ -1,4 +1,6 @@
 import { memo } from 'react';
+import { useTranslation } from 'react-i18next';
+import { FileX } from 'lucide-react';
 import { MessageImage } from '../MessageImage';
 import { MessageFile } from '../MessageFile';
 import { useAppDispatch } from '@/app/hooks';
@@ -7,13 +9,17 @@ import { setImagePreviewOpen } from '@/features/ui/state/uiSlice';
 interface MessageAttachmentsProps {
   files?: Array<string | { path: string; mimeType: string }>;
   images?: Array<string | { path: string; mimeType: string }>;
+  // Paths flagged by the attachment integrity check
+  missingFiles?: string[];
 }
 
 export const MessageAttachments = memo(function MessageAttachments({
   files,
   images,
+  missingFiles,
 }: MessageAttachmentsProps) {
   const dispatch = useAppDispatch();
+  const { t } = useTranslation('chat');
 
   // Consolidate file list (support both new 'files' and old 'images' format)
   const fileList =
@@ -48,6 +54,20 @@ export const MessageAttachments = memo(function MessageAttachments({
           return null;
         }
 
+        if (missingFiles?.includes(filePath)) {
+          return (
+            <div
+              key={index}
+              className="flex max-w-[400px] items-center gap-2 rounded-lg border border-dashed border-border/70 px-3 py-2 text-sm text-muted-foreground"
+            >
+              <FileX className="h-4 w-4 shrink-0" />
+              <span className="truncate">
+                {t('attachmentMissing')}: {filePath.split(/[\\/]/).pop()}
+              </span>
+            </div>
+          );
+        }
+
         // Determine if it's an image
         const isImage =
           mimeType?.startsWith('image/') ||
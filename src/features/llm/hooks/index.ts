@@ -10,3 +10,6 @@ export type {
   UseLLMConnectionFormOptions,
   UseLLMConnectionFormResult,
 } from './useLLMConnectionForm';
+
+export { useOllamaPull } from './useOllamaPull';
+export type { OllamaPullState, UseOllamaPullResult } from './useOllamaPull';
@@ -1,6 +1,10 @@
 import { useState } from 'react';
 import type { LLMConnection } from '../types';
-import { DEFAULT_URLS } from '../lib/constants';
+import {
+  DEFAULT_AWS_REGION,
+  DEFAULT_URLS,
+  bedrockRuntimeUrl,
+} from '../lib/constants';
 
 export interface UseLLMConnectionFormOptions {
   connection: LLMConnection | null;
@@ -15,6 +19,16 @@ export interface UseLLMConnectionFormResult {
   setBaseUrl: (baseUrl: string) => void;
   apiKey: string;
   setApiKey: (apiKey: string) => void;
+  systemPromptSuffix: string;
+  setSystemPromptSuffix: (suffix: string) => void;
+  deploymentName: string;
+  setDeploymentName: (deploymentName: string) => void;
+  apiVersion: string;
+  setApiVersion: (apiVersion: string) => void;
+  awsRegion: string;
+  setAwsRegion: (awsRegion: string) => void;
+  awsProfile: string;
+  setAwsProfile: (awsProfile: string) => void;
   handleProviderChange: (newProvider: LLMConnection['provider']) => void;
   isValid: boolean;
 }
@@ -35,6 +49,27 @@ export function useLLMConnectionForm({
     connection?.baseUrl || DEFAULT_URLS[connection?.provider || 'openai'] || ''
   );
   const [apiKey, setApiKey] = useState(connection?.apiKey || '');
+  const [systemPromptSuffix, setSystemPromptSuffix] = useState(
+    connection?.systemPromptSuffix || ''
+  );
+  const [deploymentName, setDeploymentName] = useState(
+    connection?.deploymentName || ''
+  );
+  const [apiVersion, setApiVersion] = useState(connection?.apiVersion || '');
+  const [awsRegion, setAwsRegionState] = useState(
+    connection?.awsRegion || DEFAULT_AWS_REGION
+  );
+  const [awsProfile, setAwsProfile] = useState(connection?.awsProfile || '');
+
+  /**
+   * Bedrock connections reach the runtime endpoint of their region
+   */
+  const setAwsRegion = (region: string) => {
+    setAwsRegionState(region);
+    if (provider === 'bedrock') {
+      setBaseUrl(bedrockRuntimeUrl(region.trim()));
+    }
+  };
 
   /**
    * Handle provider change with automatic baseUrl update
@@ -44,13 +79,20 @@ export function useLLMConnectionForm({
     setProvider(newProvider);
 
     // Auto-update baseUrl if it's still the default for current provider
-    if (!baseUrl || baseUrl === DEFAULT_URLS[provider]) {
+    if (newProvider === 'bedrock') {
+      setBaseUrl(bedrockRuntimeUrl(awsRegion.trim()));
+    } else if (!baseUrl || baseUrl === DEFAULT_URLS[provider]) {
       setBaseUrl(DEFAULT_URLS[newProvider]);
     }
   };
 
-  // Form validation
-  const isValid = name.trim().length > 0 && baseUrl.trim().length > 0;
+  // Form validation; Azure connections route to a deployment, Bedrock
+  // connections to a region
+  const isValid =
+    name.trim().length > 0 &&
+    baseUrl.trim().length > 0 &&
+    (provider !== 'azure' || deploymentName.trim().length > 0) &&
+    (provider !== 'bedrock' || awsRegion.trim().length > 0);
 
   return {
     name,
@@ -61,6 +103,16 @@ export function useLLMConnectionForm({
     setBaseUrl,
     apiKey,
     setApiKey,
+    systemPromptSuffix,
+    setSystemPromptSuffix,
+    deploymentName,
+    setDeploymentName,
+    apiVersion,
+    setApiVersion,
+    awsRegion,
+    setAwsRegion,
+    awsProfile,
+    setAwsProfile,
     handleProviderChange,
     isValid,
   };
@@ -0,0 +1,90 @@
+import { useState, useEffect, useCallback } from 'react';
+import {
+  invokeCommand,
+  listenToEvent,
+  TauriCommands,
+  TauriEvents,
+} from '@/lib/tauri';
+
+interface OllamaPullProgressEvent {
+  connection_id: string;
+  model: string;
+  status: string;
+  digest: string | null;
+  total: number | null;
+  completed: number | null;
+  done: boolean;
+  error: string | null;
+}
+
+export interface OllamaPullState {
+  model: string;
+  status: string;
+  /** 0-100 for the layer being downloaded, null between layers */
+  percent: number | null;
+  done: boolean;
+  error: string | null;
+}
+
+export interface UseOllamaPullResult {
+  pull: OllamaPullState | null;
+  isPulling: boolean;
+  pullModel: (model: string) => Promise<boolean>;
+}
+
+/**
+ * Downloads models to an Ollama connection's server and follows their
+ * progress events
+ */
+export function useOllamaPull(connectionId?: string): UseOllamaPullResult {
+  const [pull, setPull] = useState<OllamaPullState | null>(null);
+
+  useEffect(() => {
+    if (!connectionId) return;
+    const unlisten = listenToEvent<OllamaPullProgressEvent>(
+      TauriEvents.OLLAMA_PULL_PROGRESS,
+      (event) => {
+        if (event.connection_id !== connectionId) return;
+        setPull({
+          model: event.model,
+          status: event.status,
+          percent:
+            event.total && event.completed != null
+              ? Math.round((event.completed / event.total) * 100)
+              : null,
+          done: event.done,
+          error: event.error,
+        });
+      }
+    );
+    return () => {
+      void unlisten.then((fn) => fn());
+    };
+  }, [connectionId]);
+
+  const pullModel = useCallback(
+    async (model: string) => {
+      if (!connectionId || !model.trim()) return false;
+      setPull({
+        model: model.trim(),
+        status: '',
+        percent: null,
+        done: false,
+        error: null,
+      });
+      try {
+        await invokeCommand(TauriCommands.PULL_OLLAMA_MODEL, {
+          connectionId,
+          model: model.trim(),
+        });
+        return true;
+      } catch {
+        // The final progress event carries the error
+        return false;
+      }
+    },
+    [connectionId]
+  );
+
+  return { pull, isPulling: pull !== null && !pull.done, pullModel };
+}
@@ -9,6 +9,10 @@ export interface UseTestConnectionOptions {
   apiKey: string;
   provider: string;
   connectionId?: string;
+  deploymentName?: string;
+  apiVersion?: string;
+  awsRegion?: string;
+  awsProfile?: string;
 }
 
 export interface UseTestConnectionResult {
@@ -28,6 +32,10 @@ export function useTestConnection({
   apiKey,
   provider,
   connectionId,
+  deploymentName,
+  apiVersion,
+  awsRegion,
+  awsProfile,
 }: UseTestConnectionOptions): UseTestConnectionResult {
   const { t } = useTranslation('settings');
   const [isTesting, setIsTesting] = useState(false);
@@ -57,6 +65,10 @@ export function useTestConnection({
           baseUrl: baseUrl.trim(),
           provider,
           apiKey: apiKey.trim() || null,
+          deploymentName: deploymentName?.trim() || null,
+          apiVersion: apiVersion?.trim() || null,
+          awsRegion: awsRegion?.trim() || null,
+          awsProfile: awsProfile?.trim() || null,
         }
       );
 
@@ -98,7 +110,17 @@ export function useTestConnection({
     } finally {
       setIsTesting(false);
     }
-  }, [baseUrl, apiKey, provider, connectionId, t]);
+  }, [
+    baseUrl,
+    apiKey,
+    provider,
+    connectionId,
+    deploymentName,
+    apiVersion,
+    awsRegion,
+    awsProfile,
+    t,
+  ]);
 
   // Auto-test when connection parameters change
   useEffect(() => {
@@ -116,7 +138,15 @@ export function useTestConnection({
 
     return () => clearTimeout(timer);
     // eslint-disable-next-line react-hooks/exhaustive-deps
-  }, [baseUrl, apiKey, provider]); // Only depend on primitive values, testConnection is stable via useCallback
+  }, [
+    baseUrl,
+    apiKey,
+    provider,
+    deploymentName,
+    apiVersion,
+    awsRegion,
+    awsProfile,
+  ]); // Only depend on primitive values, testConnection is stable via useCallback
 
   return {
     isTesting,
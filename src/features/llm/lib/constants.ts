@@ -16,6 +16,8 @@ export const DEFAULT_URLS: Record<string, string> = {
   google: 'https://generativelanguage.googleapis.com/v1beta',
   anthropic: 'https://api.anthropic.com',
   deepseek: 'https://api.deepseek.com',
+  azure: 'https://YOUR-RESOURCE.openai.azure.com',
+  bedrock: 'https://bedrock-runtime.us-east-1.amazonaws.com',
 } as const;
 
 /**
@@ -34,6 +36,8 @@ export const VALID_PROVIDERS = [
   'google',
   'anthropic',
   'deepseek',
+  'azure',
+  'bedrock',
 ] as const;
 
 /**
@@ -55,8 +59,27 @@ export const PROVIDER_OPTIONS: {
   { value: 'deepinfra', label: 'DeepInfra' },
   { value: 'anthropic', label: 'Anthropic Claude' },
   { value: 'deepseek', label: 'DeepSeek' },
+  { value: 'azure', label: 'Azure OpenAI' },
+  { value: 'bedrock', label: 'AWS Bedrock' },
 ] as const;
 
+/**
+ * api-version of Azure OpenAI connections that set none
+ */
+export const DEFAULT_AZURE_API_VERSION = '2024-10-21';
+
+/**
+ * Region of new AWS Bedrock connections
+ */
+export const DEFAULT_AWS_REGION = 'us-east-1';
+
+/**
+ * Runtime endpoint of an AWS region, the base URL of Bedrock connections
+ */
+export function bedrockRuntimeUrl(region: string): string {
+  return `https://bedrock-runtime.${region}.amazonaws.com`;
+}
+
 /**
  * Popular OpenAI model patterns for filtering
  */
@@ -60,6 +60,8 @@ export function getProviderDisplayName(
     deepinfra: 'DeepInfra',
     anthropic: 'Anthropic Claude',
     deepseek: 'DeepSeek',
+    azure: 'Azure OpenAI',
+    bedrock: 'AWS Bedrock',
   };
   return displayNames[provider] || provider;
 }
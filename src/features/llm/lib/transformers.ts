@@ -14,6 +14,15 @@ export interface DbLLMConnection {
   models_json: string | null;
   default_model: string | null;
   enabled: boolean;
+  system_prompt_suffix?: string | null;
+  model_allowlist?: string | null;
+  model_blocklist?: string | null;
+  /** JSON object of provider options, e.g. `thinking_budgets` */
+  config?: string | null;
+  deployment_name?: string | null;
+  api_version?: string | null;
+  aws_region?: string | null;
+  aws_profile?: string | null;
   created_at: number;
   updated_at: number;
 }
@@ -46,5 +55,10 @@ export function dbToFrontendLLMConnection(
     apiKey: dbConn.api_key,
     models,
     enabled: dbConn.enabled,
+    systemPromptSuffix: dbConn.system_prompt_suffix ?? undefined,
+    deploymentName: dbConn.deployment_name ?? undefined,
+    apiVersion: dbConn.api_version ?? undefined,
+    awsRegion: dbConn.aws_region ?? undefined,
+    awsProfile: dbConn.aws_profile ?? undefined,
   };
 }
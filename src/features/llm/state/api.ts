@@ -44,6 +44,11 @@ export const llmConnectionsApi = baseApi.injectEndpoints({
             apiKey: connection.apiKey,
             modelsJson,
             defaultModel: null,
+            systemPromptSuffix: connection.systemPromptSuffix ?? null,
+            deploymentName: connection.deploymentName ?? null,
+            apiVersion: connection.apiVersion ?? null,
+            awsRegion: connection.awsRegion ?? null,
+            awsProfile: connection.awsProfile ?? null,
           },
         };
       },
@@ -69,6 +74,11 @@ export const llmConnectionsApi = baseApi.injectEndpoints({
             modelsJson: modelsJson ?? null,
             defaultModel: null,
             enabled: connection.enabled ?? null,
+            systemPromptSuffix: connection.systemPromptSuffix ?? null,
+            deploymentName: connection.deploymentName ?? null,
+            apiVersion: connection.apiVersion ?? null,
+            awsRegion: connection.awsRegion ?? null,
+            awsProfile: connection.awsProfile ?? null,
           },
         };
       },
@@ -24,8 +24,19 @@ export interface LLMConnection {
     | 'deepinfra'
     | 'google'
     | 'anthropic'
-    | 'deepseek';
+    | 'deepseek'
+    | 'azure'
+    | 'bedrock';
   apiKey: string;
   models?: LLMModel[];
   enabled: boolean;
+  // Standing instructions appended to the system prompt for this connection
+  systemPromptSuffix?: string;
+  // Azure OpenAI: the deployment chats route to, and the api-version
+  deploymentName?: string;
+  apiVersion?: string;
+  // AWS Bedrock: region of the runtime endpoint, and the credential profile
+  // of the shared AWS files signing requests
+  awsRegion?: string;
+  awsProfile?: string;
 }
@@ -1,9 +1,10 @@
 import { useTranslation } from 'react-i18next';
-import { useEffect } from 'react';
-import { Trash2, RefreshCw, CheckCircle2 } from 'lucide-react';
+import { useEffect, useState } from 'react';
+import { Trash2, RefreshCw, CheckCircle2, Download } from 'lucide-react';
 import { Button } from '@/ui/atoms/button/button';
 import { Input } from '@/ui/atoms/input';
 import { Label } from '@/ui/atoms/label';
+import { Textarea } from '@/ui/atoms/textarea';
 import {
   Select,
   SelectContent,
@@ -13,9 +14,15 @@ import {
 } from '@/ui/atoms/select';
 import { ProviderIcon } from '@/ui/atoms/provider-icon';
 import type { LLMConnection } from '../types';
-import { DEFAULT_URLS, PROVIDER_OPTIONS } from '../lib/constants';
+import {
+  DEFAULT_AWS_REGION,
+  DEFAULT_AZURE_API_VERSION,
+  DEFAULT_URLS,
+  PROVIDER_OPTIONS,
+} from '../lib/constants';
 import { useTestConnection } from '../hooks/useTestConnection';
 import { useLLMConnectionForm } from '../hooks/useLLMConnectionForm';
+import { useOllamaPull } from '../hooks/useOllamaPull';
 import { ScrollArea } from '@/ui/atoms/scroll-area';
 import { cn } from '@/lib/utils';
 
@@ -53,6 +60,16 @@ export function LLMConnectionForm({
     setBaseUrl,
     apiKey,
     setApiKey,
+    systemPromptSuffix,
+    setSystemPromptSuffix,
+    deploymentName,
+    setDeploymentName,
+    apiVersion,
+    setApiVersion,
+    awsRegion,
+    setAwsRegion,
+    awsProfile,
+    setAwsProfile,
     handleProviderChange,
     isValid,
   } = useLLMConnectionForm({ connection });
@@ -64,8 +81,26 @@ export function LLMConnectionForm({
       apiKey,
       provider,
       connectionId: connection?.id,
+      deploymentName: provider === 'azure' ? deploymentName : undefined,
+      apiVersion: provider === 'azure' ? apiVersion : undefined,
+      awsRegion: provider === 'bedrock' ? awsRegion : undefined,
+      awsProfile: provider === 'bedrock' ? awsProfile : undefined,
     });
 
+  // Downloading models needs a saved Ollama connection
+  const canPull = provider === 'ollama' && !!connection?.id;
+  const { pull, isPulling, pullModel } = useOllamaPull(
+    canPull ? connection?.id : undefined
+  );
+  const [modelToPull, setModelToPull] = useState('');
+
+  const handlePull = async () => {
+    if (await pullModel(modelToPull)) {
+      setModelToPull('');
+      void testConnection();
+    }
+  };
+
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     if (isValid) {
@@ -87,12 +122,19 @@ export function LLMConnectionForm({
         apiKey,
         models: modelsToSave,
         enabled: connection?.enabled ?? true,
+        // Empty string clears a previously saved suffix
+        systemPromptSuffix: systemPromptSuffix.trim(),
+        // Empty strings clear them when the provider changed away from Azure
+        deploymentName: provider === 'azure' ? deploymentName.trim() : '',
+        apiVersion: provider === 'azure' ? apiVersion.trim() : '',
+        awsRegion: provider === 'bedrock' ? awsRegion.trim() : '',
+        awsProfile: provider === 'bedrock' ? awsProfile.trim() : '',
       });
       onClose();
     }
   };
 
-  const saveDisabled = !name.trim() || !baseUrl.trim() || isTesting;
+  const saveDisabled = !isValid || isTesting;
 
   useEffect(() => {
     if (!onFooterRender) return;
@@ -176,23 +218,87 @@ export function LLMConnectionForm({
             onChange={(e) => setBaseUrl(e.target.value)}
             placeholder={DEFAULT_URLS[provider]}
             className="w-full"
+            readOnly={provider === 'bedrock'}
             required
           />
         </div>
+        {provider === 'azure' && (
+          <>
+            <div className="space-y-2 w-full">
+              <Label htmlFor="deploymentName">{t('deploymentName')}</Label>
+              <Input
+                id="deploymentName"
+                value={deploymentName}
+                onChange={(e) => setDeploymentName(e.target.value)}
+                placeholder={t('deploymentNamePlaceholder')}
+                className="w-full"
+                required
+              />
+            </div>
+            <div className="space-y-2 w-full">
+              <Label htmlFor="apiVersion">{t('apiVersion')}</Label>
+              <Input
+                id="apiVersion"
+                value={apiVersion}
+                onChange={(e) => setApiVersion(e.target.value)}
+                placeholder={DEFAULT_AZURE_API_VERSION}
+                className="w-full"
+              />
+            </div>
+          </>
+        )}
+        {provider === 'bedrock' ? (
+          <>
+            <div className="space-y-2 w-full">
+              <Label htmlFor="awsRegion">{t('awsRegion')}</Label>
+              <Input
+                id="awsRegion"
+                value={awsRegion}
+                onChange={(e) => setAwsRegion(e.target.value)}
+                placeholder={DEFAULT_AWS_REGION}
+                className="w-full"
+                required
+              />
+            </div>
+            <div className="space-y-2 w-full">
+              <Label htmlFor="awsProfile">{t('awsProfile')}</Label>
+              <Input
+                id="awsProfile"
+                value={awsProfile}
+                onChange={(e) => setAwsProfile(e.target.value)}
+                placeholder={t('awsProfilePlaceholder')}
+                className="w-full"
+              />
+            </div>
+          </>
+        ) : (
+          <div className="space-y-2 w-full">
+            <Label htmlFor="apiKey">{t('apiKey')}</Label>
+            <Input
+              id="apiKey"
+              type="password"
+              value={apiKey}
+              onChange={(e) => setApiKey(e.target.value)}
+              placeholder={
+                provider === 'vllm' || provider === 'ollama'
+                  ? t('optional') + ' ' + t('enterApiKey')
+                  : t('enterApiKey')
+              }
+              className="w-full"
+              data-tour="llm-api-key-input"
+            />
+          </div>
+        )}
         <div className="space-y-2 w-full">
-          <Label htmlFor="apiKey">{t('apiKey')}</Label>
-          <Input
-            id="apiKey"
-            type="password"
-            value={apiKey}
-            onChange={(e) => setApiKey(e.target.value)}
-            placeholder={
-              provider === 'vllm' || provider === 'ollama'
-                ? t('optional') + ' ' + t('enterApiKey')
-                : t('enterApiKey')
-            }
+          <Label htmlFor="systemPromptSuffix">{t('systemPromptSuffix')}</Label>
+          <Textarea
+            id="systemPromptSuffix"
+            value={systemPromptSuffix}
+            onChange={(e) => setSystemPromptSuffix(e.target.value)}
+            placeholder={t('systemPromptSuffixPlaceholder')}
+            maxLength={4000}
+            rows={3}
             className="w-full"
-            data-tour="llm-api-key-input"
           />
         </div>
 
@@ -245,6 +351,51 @@ export function LLMConnectionForm({
             </div>
           )}
         </div>
+
+        {canPull && (
+          <div className="space-y-2 w-full">
+            <Label htmlFor="pullModel">{t('pullModel')}</Label>
+            <div className="flex gap-2">
+              <Input
+                id="pullModel"
+                value={modelToPull}
+                onChange={(e) => setModelToPull(e.target.value)}
+                placeholder={t('pullModelPlaceholder')}
+                className="w-full"
+                disabled={isPulling}
+              />
+              <Button
+                type="button"
+                variant="outline"
+                onClick={() => void handlePull()}
+                disabled={isPulling || !modelToPull.trim()}
+              >
+                {isPulling ? (
+                  <RefreshCw className="size-4 animate-spin" />
+                ) : (
+                  <Download className="size-4" />
+                )}
+              </Button>
+            </div>
+            {pull && (
+              <p
+                className={cn(
+                  'text-xs',
+                  pull.error ? 'text-destructive' : 'text-muted-foreground'
+                )}
+              >
+                {pull.error
+                  ? t('pullModelFailed', {
+                      model: pull.model,
+                      error: pull.error,
+                    })
+                  : pull.done
+                    ? t('pullModelDone', { model: pull.model })
+                    : `${pull.status}${pull.percent != null ? ` ${pull.percent}%` : ''}`}
+              </p>
+            )}
+          </div>
+        )}
       </div>
     </form>
   );
@@ -26,6 +26,10 @@ export interface MCPServerConnection {
   url: string;
   type: 'sse' | 'stdio' | 'http-streamable';
   headers?: string;
+  /** Sent as a bearer token to remote servers */
+  auth_token?: string;
+  /** Connected in the background when the app starts */
+  auto_connect?: boolean;
   env_vars?: string;
   runtime_path?: string;
   status?: 'disconnected' | 'connecting' | 'connected';
@@ -33,6 +37,84 @@ export interface MCPServerConnection {
   errorMessage?: string;
 }
 
+/** How a workspace shows one of its MCP tools to the model */
+export interface ToolOverride {
+  tool_name: string;
+  disabled: boolean;
+  alias?: string;
+  description?: string;
+}
+
+/** An MCP tool a workspace selected, with how it shows it */
+export interface WorkspaceToolInfo {
+  name: string;
+  connection_id: string;
+  server_name: string;
+  description?: string;
+  override: ToolOverride;
+}
+
+/** Directories the built-in file tools may reach; empty allows none */
+export interface BuiltinToolSettings {
+  allowed_dirs: string[];
+}
+
+export interface MCPResource {
+  uri: string;
+  name: string;
+  title?: string;
+  description?: string;
+  mime_type?: string;
+  size?: number;
+}
+
+export interface MCPResourceTemplate {
+  uri_template: string;
+  name: string;
+  title?: string;
+  description?: string;
+  mime_type?: string;
+}
+
+export interface MCPResourceContent {
+  uri: string;
+  mime_type?: string;
+  text?: string;
+  /** Base64 */
+  blob?: string;
+}
+
+/** Entry of the `mcpResources` list in a user message's metadata */
+export interface AttachedMCPResource {
+  connectionId: string;
+  uri: string;
+  name: string;
+  mimeType?: string;
+  text: string;
+  truncated: boolean;
+}
+
+export interface MCPPromptArgument {
+  name: string;
+  title?: string;
+  description?: string;
+  required: boolean;
+}
+
+export interface MCPPrompt {
+  name: string;
+  title?: string;
+  description?: string;
+  arguments: MCPPromptArgument[];
+}
+
+export interface MCPPromptResult {
+  description?: string;
+  messages: { role: 'user' | 'assistant'; text: string }[];
+  /** The messages as one text */
+  text: string;
+}
+
 export interface HubMCPServer {
   id: string;
   name: string;
@@ -80,6 +80,14 @@ export function UsageOverview({ summary, loading }: UsageOverviewProps) {
               <span className="text-[10px]">OUT</span>
               {summary.total_output_tokens.toLocaleString()}
             </span>
+            {(summary.total_cached_tokens ?? 0) +
+              (summary.total_cache_write_tokens ?? 0) >
+              0 && (
+              <span className="flex items-center gap-1 text-chart-violet font-medium">
+                <span className="text-[10px]">CACHED</span>
+                {(summary.total_cached_tokens ?? 0).toLocaleString()}
+              </span>
+            )}
           </p>
         </CardContent>
       </Card>
@@ -0,0 +1,30 @@
+import { useEffect } from 'react';
+import { useAppSelector } from '@/app/hooks';
+import { invokeCommand, TauriCommands } from '@/lib/tauri';
+import { logger } from '@/lib/logger';
+
+/**
+ * Tell the backend which workspace is active whenever it changes or the window
+ * regains focus, so local models on keep-warm connections stay loaded.
+ */
+export function useWorkspaceActivity() {
+  const selectedWorkspaceId = useAppSelector(
+    (state) => state.workspaces.selectedWorkspaceId
+  );
+
+  useEffect(() => {
+    if (!selectedWorkspaceId) return;
+
+    const notify = () => {
+      invokeCommand(TauriCommands.NOTIFY_WORKSPACE_ACTIVE, {
+        workspaceId: selectedWorkspaceId,
+      }).catch((error) =>
+        logger.error('Failed to notify active workspace:', error)
+      );
+    };
+
+    notify();
+    window.addEventListener('focus', notify);
+    return () => window.removeEventListener('focus', notify);
+  }, [selectedWorkspaceId]);
+}
@@ -1,9 +1,14 @@
 import { baseApi } from '@/app/api/baseApi';
 import { TauriCommands } from '@/bindings/commands';
 import { logger } from '@/lib/logger';
+import {
+  fromDbGenerationParams,
+  toGenerationParamsPayload,
+  type DbGenerationParams,
+} from '@/models/generation';
 import type { WorkspaceSettings } from '../types';
 
-interface DbWorkspaceSettings {
+interface DbWorkspaceSettings extends DbGenerationParams {
   workspace_id: string;
   llm_connection_id: string | null;
   system_message: string | null;
@@ -92,6 +97,7 @@ export const workspaceSettingsApi = baseApi.injectEndpoints({
           selectedSkillIds: dbSettings.selected_skill_ids
             ? JSON.parse(dbSettings.selected_skill_ids)
             : undefined,
+          generationParams: fromDbGenerationParams(dbSettings),
         };
 
         return { data: settings };
@@ -137,6 +143,9 @@ export const workspaceSettingsApi = baseApi.injectEndpoints({
             selectedSkillIds: settings.selectedSkillIds
               ? JSON.stringify(settings.selectedSkillIds)
               : null,
+            generationParams: settings.generationParams
+              ? toGenerationParamsPayload(settings.generationParams)
+              : null,
           },
         });
 
@@ -6,6 +6,7 @@ interface DbWorkspace {
   id: string;
   name: string;
   created_at: number;
+  unread_activity?: number;
 }
 
 export const workspacesApi = baseApi.injectEndpoints({
@@ -18,6 +19,7 @@ export const workspacesApi = baseApi.injectEndpoints({
         return response.map((w) => ({
           id: w.id,
           name: w.name,
+          unreadActivity: w.unread_activity ?? 0,
         }));
       },
       providesTags: (result) =>
@@ -8,6 +8,7 @@ interface DbWorkspace {
   id: string;
   name: string;
   created_at: number;
+  unread_activity?: number;
 }
 
 interface WorkspacesState {
@@ -38,6 +39,7 @@ export const fetchWorkspaces = createAsyncThunk(
       workspaces: dbWorkspaces.map((w) => ({
         id: w.id,
         name: w.name,
+        unreadActivity: w.unread_activity ?? 0,
       })),
       lastWorkspaceId,
     };
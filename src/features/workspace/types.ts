@@ -1,6 +1,9 @@
+import type { GenerationParams } from '@/models/generation';
+
 export interface Workspace {
   id: string;
   name: string;
+  unreadActivity?: number; // Unread background activity entries
 }
 
 export interface WorkspaceSettings {
@@ -15,4 +18,5 @@ export interface WorkspaceSettings {
   maxAgentIterations?: number;
   internalToolsEnabled?: boolean;
   selectedSkillIds?: string[];
+  generationParams?: GenerationParams;
 }
@@ -0,0 +1,32 @@
+import { invokeCommand, TauriCommands } from '@/lib/tauri';
+
+/**
+ * Stored files referenced from message content as `lunex-file://<hash>`.
+ * Resolved through the backend, which returns null once a file was removed.
+ */
+const LUNEX_FILE_URI =
+  /^(?:lunex-file:\/\/(?:localhost\/)?|https?:\/\/lunex-file\.localhost\/)[0-9a-f]{64}\/?$/;
+
+const resolved = new Map<string, Promise<string | null>>();
+
+export function isLunexFileUri(src: string | undefined): src is string {
+  return !!src && LUNEX_FILE_URI.test(src.trim());
+}
+
+/** A data URL for a `lunex-file://` URI, or null when the file is gone. */
+export function resolveLunexFile(uri: string): Promise<string | null> {
+  let result = resolved.get(uri);
+  if (!result) {
+    result = invokeCommand<string | null>(TauriCommands.RESOLVE_LUNEX_FILE, {
+      uri,
+    }).catch(() => null);
+    resolved.set(uri, result);
+  }
+  return result;
+}
+
+/** Content with its `lunex-file://` images embedded, for copies and exports. */
+export async function embedLunexFiles(content: string): Promise<string> {
+  if (!content.includes('lunex-file')) return content;
+  return invokeCommand<string>(TauriCommands.EMBED_LUNEX_FILES, { content });
+}
@@ -0,0 +1,42 @@
+/** Sampling parameters of a chat turn; unset ones are left to the provider. */
+export interface GenerationParams {
+  temperature?: number;
+  topP?: number;
+  frequencyPenalty?: number;
+  presencePenalty?: number;
+  maxTokens?: number;
+}
+
+/** The generation parameter columns of workspace and chat input settings. */
+export interface DbGenerationParams {
+  temperature?: number | null;
+  top_p?: number | null;
+  frequency_penalty?: number | null;
+  presence_penalty?: number | null;
+  max_tokens?: number | null;
+}
+
+export function fromDbGenerationParams(
+  db: DbGenerationParams
+): GenerationParams {
+  return {
+    temperature: db.temperature ?? undefined,
+    topP: db.top_p ?? undefined,
+    frequencyPenalty: db.frequency_penalty ?? undefined,
+    presencePenalty: db.presence_penalty ?? undefined,
+    maxTokens: db.max_tokens ?? undefined,
+  };
+}
+
+/** The `generationParams` argument of the save commands. */
+export function toGenerationParamsPayload(
+  params: GenerationParams
+): DbGenerationParams {
+  return {
+    temperature: params.temperature ?? null,
+    top_p: params.topP ?? null,
+    frequency_penalty: params.frequencyPenalty ?? null,
+    presence_penalty: params.presencePenalty ?? null,
+    max_tokens: params.maxTokens ?? null,
+  };
+}
@@ -27,6 +27,10 @@ export interface UsageFilter {
 export interface UsageSummary {
   total_input_tokens: number;
   total_output_tokens: number;
+  /** Prompt tokens read from a provider-side prompt cache */
+  total_cached_tokens?: number;
+  /** Prompt tokens written to a provider-side prompt cache */
+  total_cache_write_tokens?: number;
   total_cost: number;
   total_requests: number;
   average_latency: number;
@@ -39,3 +43,25 @@ export interface UsageChartPoint {
   input_tokens: number;
   output_tokens: number;
 }
+
+/** Rows to delete or anonymize; at least one condition must be set. */
+export interface UsageDeleteFilter {
+  older_than?: number;
+  workspace_id?: string;
+  chat_id?: string;
+}
+
+export interface UsagePurgeResult {
+  usage_rows: number;
+  tool_usage_rows: number;
+}
+
+/** Usage of one chat; `chat_id` is null for deleted or anonymized chats. */
+export interface ChatUsageBreakdown {
+  chat_id: string | null;
+  chat_title: string;
+  requests: number;
+  input_tokens: number;
+  output_tokens: number;
+  cost: number;
+}
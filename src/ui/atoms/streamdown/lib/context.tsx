@@ -15,7 +15,7 @@ import {
 import { harden } from 'rehype-harden';
 import rehypeKatex from 'rehype-katex';
 import rehypeRaw from 'rehype-raw';
-import rehypeSanitize from 'rehype-sanitize';
+import rehypeSanitize, { defaultSchema } from 'rehype-sanitize';
 import remarkCjkFriendly from 'remark-cjk-friendly';
 import remarkCjkFriendlyGfmStrikethrough from 'remark-cjk-friendly-gfm-strikethrough';
 import remarkGfm from 'remark-gfm';
@@ -87,7 +87,17 @@ export type StreamdownProps = Options & {
 
 export const defaultRehypePlugins: Record<string, Pluggable> = {
   raw: rehypeRaw,
-  sanitize: [rehypeSanitize, {}],
+  sanitize: [
+    rehypeSanitize,
+    {
+      ...defaultSchema,
+      // Stored images are referenced as lunex-file://<hash>
+      protocols: {
+        ...defaultSchema.protocols,
+        src: [...(defaultSchema.protocols?.src ?? []), 'lunex-file'],
+      },
+    },
+  ],
   katex: [rehypeKatex, { errorColor: 'var(--color-muted-foreground)' }],
   harden: [
     harden,
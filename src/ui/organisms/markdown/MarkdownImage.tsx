@@ -1,5 +1,8 @@
+import { useEffect, useState } from 'react';
+import { useTranslation } from 'react-i18next';
 import { useAppDispatch } from '@/app/hooks';
 import { setImagePreviewOpen } from '@/features/ui/state/uiSlice';
+import { isLunexFileUri, resolveLunexFile } from '@/lib/lunexFile';
 import { cn } from '@/lib/utils';
 
 import type { DetailedHTMLProps, ImgHTMLAttributes } from 'react';
@@ -16,24 +19,50 @@ export function MarkdownImage({
   ...props
 }: MarkdownImageProps) {
   const dispatch = useAppDispatch();
+  const { t } = useTranslation('chat');
+  // Stored files are referenced as lunex-file://<hash>; undefined while loading
+  const [fileUrl, setFileUrl] = useState<string | null | undefined>();
+  const isStoredFile = isLunexFileUri(src);
+
+  useEffect(() => {
+    if (!isStoredFile || !src) return;
+    let active = true;
+    resolveLunexFile(src.trim()).then((url) => {
+      if (active) setFileUrl(url);
+    });
+    return () => {
+      active = false;
+    };
+  }, [isStoredFile, src]);
+
+  const imageUrl = isStoredFile ? fileUrl : src;
 
   const handleClick = () => {
-    if (src) {
+    if (imageUrl) {
       dispatch(
         setImagePreviewOpen({
           open: true,
-          url: src,
+          url: imageUrl,
         })
       );
     }
   };
 
-  if (!src) return null;
+  if (isStoredFile && fileUrl === null) {
+    return (
+      <span className="inline-block my-2 px-2 py-1 rounded border border-dashed border-border text-xs text-muted-foreground">
+        {alt ? `${alt}: ` : ''}
+        {t('attachmentMissing')}
+      </span>
+    );
+  }
+
+  if (!imageUrl) return null;
 
   return (
     <span className="inline-block my-2">
       <img
-        src={src}
+        src={imageUrl}
         alt={alt || ''}
         className={cn(
           'max-w-full rounded-lg cursor-zoom-in hover:opacity-90 transition-opacity shadow-sm border border-border/40',